@@ -0,0 +1,208 @@
+//! Audit records for Admin API writes.
+//!
+//! v2 design: Handlers stay unaware of auditing. The auth middleware
+//! snapshots the entities a write can touch, runs the handler, and on a
+//! 2xx compares the snapshot with the cache to emit one record per entity
+//! that was created, updated or deleted — a bulk import yields one record
+//! per entity it changed.
+
+use ando_core::config::AuditLogConfig;
+use ando_observability::audit_file_writer::{AuditFileConfig, AuditFileWriter};
+use ando_observability::audit_log::{AdminAction, AdminAuditEntry, AdminDiff};
+use ando_store::cache::ConfigCache;
+use ando_store::document::ConfigDocument;
+use axum::http::Method;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tracing::error;
+
+/// Where audit records go: `compliance.audit_log.file_path`, or stdout.
+pub enum AuditSink {
+    File(AuditFileWriter),
+    Stdout,
+}
+
+impl AuditSink {
+    /// `None` when `compliance.audit_log` is disabled.
+    pub fn from_config(config: &AuditLogConfig) -> std::io::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let sink = match config.file_path.as_deref().filter(|p| !p.is_empty()) {
+            Some(path) => Self::File(AuditFileWriter::new(AuditFileConfig {
+                file_path: PathBuf::from(path),
+                ..AuditFileConfig::default()
+            })?),
+            None => Self::Stdout,
+        };
+        Ok(Some(sink))
+    }
+
+    pub fn write(&self, entry: &AdminAuditEntry) {
+        let line = entry.to_json_line();
+        match self {
+            Self::File(writer) => {
+                if let Err(e) = writer.write_line(&line) {
+                    error!(error = %e, "admin: failed to write audit record");
+                }
+            }
+            Self::Stdout => println!("{line}"),
+        }
+    }
+
+    /// Write out buffered records, before the process exits.
+    pub fn flush(&self) {
+        if let Self::File(writer) = self
+            && let Err(e) = writer.flush()
+        {
+            error!(error = %e, "admin: failed to flush audit log");
+        }
+    }
+}
+
+/// Entities an Admin API request may write.
+pub(crate) enum Target {
+    One { kind: String, id: String },
+    All,
+}
+
+impl Target {
+    /// `None` for requests that never change config.
+    pub(crate) fn of(method: &Method, path: &str) -> Option<Self> {
+        let rest = path.strip_prefix("/apisix/admin/")?;
+        if method == Method::POST && matches!(rest, "import" | "import/openapi") {
+            return Some(Self::All);
+        }
+        if !matches!(*method, Method::PUT | Method::DELETE) {
+            return None;
+        }
+        let (kind, id) = rest.split_once('/')?;
+        if id.is_empty() || id.contains('/') {
+            return None;
+        }
+        Some(Self::One {
+            kind: kind.to_string(),
+            id: id.to_string(),
+        })
+    }
+}
+
+pub(crate) type Snapshot = BTreeMap<(String, String), Value>;
+
+pub(crate) fn snapshot(cache: &ConfigCache, target: &Target) -> Snapshot {
+    let mut out = Snapshot::new();
+    match target {
+        Target::One { kind, id } => {
+            let value = match kind.as_str() {
+                "routes" => cache
+                    .routes
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                "services" => cache
+                    .services
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                "upstreams" => cache
+                    .upstreams
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                "consumers" => cache
+                    .consumers
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                "global_rules" => cache
+                    .global_rules
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                "ssls" => cache
+                    .ssl_certs
+                    .get(id)
+                    .map(|e| serde_json::to_value(e.value())),
+                _ => None,
+            };
+            if let Some(Ok(mut value)) = value {
+                redact(kind, &mut value);
+                out.insert((kind.clone(), id.clone()), value);
+            }
+        }
+        Target::All => {
+            let Ok(Value::Object(doc)) = serde_json::to_value(ConfigDocument::export(cache)) else {
+                return out;
+            };
+            for (kind, entities) in doc {
+                for value in entities.as_array().into_iter().flatten() {
+                    let id = value.get("id").or_else(|| value.get("username"));
+                    if let Some(id) = id.and_then(Value::as_str) {
+                        let mut value = value.clone();
+                        redact(&kind, &mut value);
+                        out.insert((kind.clone(), id.to_string()), value);
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Private keys of SSL objects stay out of audit records, even encrypted.
+fn redact(kind: &str, value: &mut Value) {
+    if kind == "ssls"
+        && let Some(key) = value.get_mut("key")
+    {
+        *key = Value::String("[redacted]".into());
+    }
+}
+
+/// One entry per entity that differs between `before` and `after`. A
+/// single-entity write is recorded even if it stored identical content;
+/// a DELETE of a missing id records nothing.
+pub(crate) fn entries(
+    target: &Target,
+    before: &Snapshot,
+    after: &Snapshot,
+) -> Vec<AdminAuditEntry> {
+    let mut keys: Vec<_> = before.keys().chain(after.keys()).collect();
+    keys.sort();
+    keys.dedup();
+    keys.into_iter()
+        .filter_map(|key| {
+            let (old, new) = (before.get(key), after.get(key));
+            let action = match (old, new) {
+                (None, Some(_)) => AdminAction::Create,
+                (Some(_), None) => AdminAction::Delete,
+                (Some(o), Some(n)) if o != n || matches!(target, Target::One { .. }) => {
+                    AdminAction::Update
+                }
+                _ => return None,
+            };
+            let mut entry = AdminAuditEntry::new(action, &key.0, &key.1);
+            entry.diff = AdminDiff::between(old, new);
+            Some(entry)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_of_admin_requests() {
+        assert!(matches!(
+            Target::of(&Method::PUT, "/apisix/admin/routes/r1"),
+            Some(Target::One { kind, id }) if kind == "routes" && id == "r1"
+        ));
+        assert!(matches!(
+            Target::of(&Method::POST, "/apisix/admin/import"),
+            Some(Target::All)
+        ));
+        assert!(matches!(
+            Target::of(&Method::POST, "/apisix/admin/import/openapi"),
+            Some(Target::All)
+        ));
+        assert!(Target::of(&Method::GET, "/apisix/admin/routes/r1").is_none());
+        assert!(Target::of(&Method::POST, "/apisix/admin/validate").is_none());
+        assert!(Target::of(&Method::PUT, "/apisix/admin/routes").is_none());
+    }
+}
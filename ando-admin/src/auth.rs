@@ -0,0 +1,246 @@
+//! Admin API keys.
+//!
+//! v2 design: Keys are held as SHA-256 digests in an `ArcSwap`, so the
+//! `X-API-KEY` check is one hash and one map lookup, and a reload of the
+//! gateway config file swaps the whole set without restarting the admin
+//! server. The plain keys are never kept in memory past the reload and
+//! never logged; audit records name a key by its id.
+
+use ando_core::config::{AdminConfig, AdminRole, GatewayConfig};
+use arc_swap::ArcSwap;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+/// The caller behind a valid API key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub key_id: String,
+    pub role: AdminRole,
+}
+
+/// The set of accepted Admin API keys. Cheap to clone; clones share the
+/// same set.
+#[derive(Clone)]
+pub struct AdminAuth {
+    keys: Arc<ArcSwap<HashMap<[u8; 32], Principal>>>,
+}
+
+impl Default for AdminAuth {
+    /// No keys: the Admin API is open.
+    fn default() -> Self {
+        Self {
+            keys: Arc::new(ArcSwap::from_pointee(HashMap::new())),
+        }
+    }
+}
+
+impl AdminAuth {
+    pub fn from_config(config: &AdminConfig) -> Self {
+        let auth = Self::default();
+        auth.keys.store(Arc::new(key_set(config)));
+        auth
+    }
+
+    /// Replace the accepted keys with those in `config`. A reload that
+    /// finds no keys while some are in use keeps them, unless
+    /// `allow_unauthenticated` is set: a bad edit must not open the API.
+    pub fn reload(&self, config: &AdminConfig) {
+        let keys = key_set(config);
+        if keys.is_empty() && self.is_enabled() && !config.allow_unauthenticated {
+            error!(
+                "admin: reloaded config has no API keys, keeping the previous ones \
+                 (set admin.allow_unauthenticated to open the Admin API)"
+            );
+            return;
+        }
+        info!(keys = keys.len(), "admin: API keys reloaded");
+        if keys.is_empty() {
+            warn!("admin: no API keys configured, Admin API is unauthenticated");
+        }
+        self.keys.store(Arc::new(keys));
+    }
+
+    /// Whether requests must carry a key.
+    pub fn is_enabled(&self) -> bool {
+        !self.keys.load().is_empty()
+    }
+
+    pub fn authenticate(&self, key: &str) -> Option<Principal> {
+        self.keys.load().get(&digest(key)).cloned()
+    }
+}
+
+fn digest(key: &str) -> [u8; 32] {
+    Sha256::digest(key.as_bytes()).into()
+}
+
+fn key_set(config: &AdminConfig) -> HashMap<[u8; 32], Principal> {
+    let legacy = config
+        .api_key
+        .iter()
+        .map(|key| (None, key, AdminRole::Admin));
+    let keys = config
+        .api_keys
+        .iter()
+        .map(|k| (k.id.as_deref(), &k.key, k.role));
+    legacy
+        .chain(keys)
+        .filter(|(_, key, _)| !key.is_empty())
+        .map(|(id, key, role)| {
+            let digest = digest(key);
+            let key_id = id.map_or_else(
+                || digest[..4].iter().map(|b| format!("{b:02x}")).collect(),
+                str::to_string,
+            );
+            (digest, Principal { key_id, role })
+        })
+        .collect()
+}
+
+/// Re-reads `admin.api_keys` from the gateway config file when it changes.
+pub struct KeyReloader {
+    path: PathBuf,
+    auth: AdminAuth,
+    seen: Option<(SystemTime, u64)>,
+}
+
+impl KeyReloader {
+    pub fn new(path: impl Into<PathBuf>, auth: AdminAuth) -> Self {
+        let path = path.into();
+        let seen = file_stamp(&path);
+        Self { path, auth, seen }
+    }
+
+    /// Reload if the file changed since it was last read. Returns whether
+    /// new keys were applied. An unreadable file keeps the current keys.
+    pub fn poll(&mut self) -> anyhow::Result<bool> {
+        let stamp = file_stamp(&self.path);
+        if stamp == self.seen {
+            return Ok(false);
+        }
+        self.seen = stamp;
+        let config = GatewayConfig::load(&self.path)?;
+        self.auth.reload(&config.admin);
+        Ok(true)
+    }
+
+    /// Poll the file every `interval` on a dedicated thread.
+    pub fn spawn(mut self, interval: Duration) -> std::thread::JoinHandle<()> {
+        std::thread::Builder::new()
+            .name("ando-admin-keys".to_string())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(interval);
+                    if let Err(e) = self.poll() {
+                        error!(
+                            path = %self.path.display(),
+                            error = %format!("{e:#}"),
+                            "admin: invalid config file, keeping previous API keys"
+                        );
+                    }
+                }
+            })
+            .expect("Failed to spawn admin key reloader thread")
+    }
+}
+
+fn file_stamp(path: &std::path::Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::config::AdminApiKey;
+
+    fn config(keys: &[(&str, &str, AdminRole)]) -> AdminConfig {
+        AdminConfig {
+            api_keys: keys
+                .iter()
+                .map(|(id, key, role)| AdminApiKey {
+                    id: (!id.is_empty()).then(|| id.to_string()),
+                    key: key.to_string(),
+                    role: *role,
+                })
+                .collect(),
+            ..AdminConfig::default()
+        }
+    }
+
+    #[test]
+    fn authenticate_returns_key_id_and_role() {
+        let auth = AdminAuth::from_config(&config(&[
+            ("ops", "admin-secret", AdminRole::Admin),
+            ("", "viewer-secret", AdminRole::Viewer),
+        ]));
+        assert!(auth.is_enabled());
+        assert_eq!(
+            auth.authenticate("admin-secret"),
+            Some(Principal {
+                key_id: "ops".into(),
+                role: AdminRole::Admin
+            })
+        );
+        let viewer = auth.authenticate("viewer-secret").unwrap();
+        assert_eq!(viewer.role, AdminRole::Viewer);
+        // Unnamed keys get a digest prefix, never the key itself.
+        assert_eq!(viewer.key_id.len(), 8);
+        assert!(!viewer.key_id.contains("viewer"));
+        assert!(auth.authenticate("nope").is_none());
+    }
+
+    #[test]
+    fn legacy_api_key_is_an_admin_key() {
+        let auth = AdminAuth::from_config(&AdminConfig {
+            api_key: Some("legacy".into()),
+            ..AdminConfig::default()
+        });
+        assert_eq!(auth.authenticate("legacy").unwrap().role, AdminRole::Admin);
+        assert!(!AdminAuth::default().is_enabled());
+    }
+
+    #[test]
+    fn reloader_swaps_keys_when_file_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ando.yaml");
+        std::fs::write(
+            &path,
+            "admin:\n  api_keys:\n    - {key: old, role: admin}\n",
+        )
+        .unwrap();
+        let auth = AdminAuth::from_config(&GatewayConfig::load(&path).unwrap().admin);
+        let mut reloader = KeyReloader::new(&path, auth.clone());
+        assert!(!reloader.poll().unwrap());
+
+        std::fs::write(
+            &path,
+            "admin:\n  api_keys:\n    - {key: new, role: viewer}\n",
+        )
+        .unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert!(reloader.poll().unwrap());
+        assert!(auth.authenticate("old").is_none());
+        assert_eq!(auth.authenticate("new").unwrap().role, AdminRole::Viewer);
+    }
+
+    #[test]
+    fn reload_without_keys_keeps_the_previous_ones() {
+        let auth = AdminAuth::from_config(&config(&[("ops", "secret", AdminRole::Admin)]));
+        auth.reload(&config(&[]));
+        assert!(auth.is_enabled());
+        assert_eq!(auth.authenticate("secret").unwrap().key_id, "ops");
+
+        auth.reload(&AdminConfig {
+            allow_unauthenticated: true,
+            ..config(&[])
+        });
+        assert!(!auth.is_enabled());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::handlers::routes::rebuild_router;
+use crate::handlers::{ssls, validate};
+use crate::persist;
+use crate::server::AdminState;
+use ando_core::router::Router;
+use ando_store::document::{ApplyMode, ConfigDocument};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    mode: ApplyMode,
+}
+
+/// GET /apisix/admin/export
+///
+/// SSL private keys are exported as the SSL endpoints show them: encrypted
+/// or `[redacted]`. Importing the document back keeps the stored keys.
+pub async fn export_config(State(state): State<Arc<AdminState>>) -> Json<ConfigDocument> {
+    let mut doc = ConfigDocument::export(&state.cache);
+    doc.ssls.iter_mut().for_each(ssls::redact_key);
+    Json(doc)
+}
+
+/// POST /apisix/admin/import?mode=merge|replace
+///
+/// The resulting config is validated as a whole — every object, every
+/// reference and the router build — before anything is written, so an
+/// import either lands completely or not at all.
+pub async fn import_config(
+    State(state): State<Arc<AdminState>>,
+    Query(params): Query<ImportParams>,
+    Json(body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    let mut doc: ConfigDocument = match serde_json::from_value(body) {
+        Ok(d) => d,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            );
+        }
+    };
+
+    for ssl in &mut doc.ssls {
+        ssls::restore_key(&state, ssl);
+    }
+    let resulting = doc.resulting(&state.cache, params.mode);
+    let mut errors = resulting.errors(&|route| validate::route_plugins(&state, route));
+    errors.extend(
+        doc.ssls
+            .iter()
+            .filter(|ssl| ssl.key == ssls::REDACTED_KEY)
+            .map(|ssl| format!("ssl `{}`: key is redacted and none is stored", ssl.id)),
+    );
+    if errors.is_empty()
+        && let Err(e) = Router::build(resulting.router_routes(), 0)
+    {
+        errors.push(e.to_string());
+    }
+    if !errors.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid import", "errors": errors})),
+        );
+    }
+
+    if let Err(e) = persist::store_replace(&state, &resulting).await {
+        return e;
+    }
+    let summary = doc.apply(&state.cache, params.mode);
+    rebuild_router(&state);
+    persist::save_state(&state);
+
+    (
+        StatusCode::OK,
+        Json(json!({"mode": params.mode, "summary": summary})),
+    )
+}
@@ -1,7 +1,11 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::validate::{self, WriteParams};
 use crate::persist;
 use crate::server::AdminState;
 use ando_core::consumer::Consumer;
-use axum::extract::{Path, State};
+use ando_store::changes::Entity;
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::Json;
 use serde_json::{Value, json};
@@ -10,6 +14,7 @@ use std::sync::Arc;
 pub async fn put_consumer(
     State(state): State<Arc<AdminState>>,
     Path(username): Path<String>,
+    Query(params): Query<WriteParams>,
     Json(mut body): Json<Value>,
 ) -> (StatusCode, Json<Value>) {
     body["username"] = json!(username);
@@ -24,11 +29,24 @@ pub async fn put_consumer(
         }
     };
 
+    let report = validate::consumer(&state, &consumer);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+
+    if let Err(e) = persist::store_put(&state, Kind::Consumer, &consumer.username, &consumer).await
+    {
+        return e;
+    }
+    state.cache.put_consumer(consumer.clone());
     state
         .cache
-        .consumers
-        .insert(consumer.username.clone(), consumer.clone());
-    state.cache.rebuild_consumer_key_index();
+        .changes
+        .record(Entity::Consumer, &consumer.username);
+    state.cache.notifier.notify();
     persist::save_state(&state);
 
     (
@@ -54,18 +72,26 @@ pub async fn delete_consumer(
     State(state): State<Arc<AdminState>>,
     Path(username): Path<String>,
 ) -> (StatusCode, Json<Value>) {
-    state.cache.consumers.remove(&username);
-    state.cache.rebuild_consumer_key_index();
+    if let Err(e) = persist::store_delete(&state, Kind::Consumer, &username).await {
+        return e;
+    }
+    state.cache.remove_consumer(&username);
+    state.cache.changes.record(Entity::Consumer, &username);
+    state.cache.notifier.notify();
     persist::save_state(&state);
     (StatusCode::OK, Json(json!({"deleted": true})))
 }
 
-pub async fn list_consumers(State(state): State<Arc<AdminState>>) -> Json<Value> {
-    let consumers: Vec<Consumer> = state
-        .cache
-        .consumers
-        .iter()
-        .map(|c| c.value().clone())
-        .collect();
-    Json(json!({"list": consumers, "total": consumers.len()}))
+/// GET /apisix/admin/consumers[?page=&page_size=&plugin=]
+pub async fn list_consumers(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond(&state.cache.consumers, |c| {
+        query
+            .plugin
+            .as_deref()
+            .is_none_or(|plugin| c.plugins.contains_key(plugin))
+    }))
 }
@@ -0,0 +1,97 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::validate::{self, WriteParams};
+use crate::persist;
+use crate::server::AdminState;
+use ando_core::global_rule::GlobalRule;
+use ando_store::changes::Entity;
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+/// PUT /apisix/admin/global_rules/:id[?dry_run=true]
+pub async fn put_global_rule(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
+    Json(mut body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    body["id"] = json!(id);
+
+    let rule: GlobalRule = match serde_json::from_value(body) {
+        Ok(g) => g,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            );
+        }
+    };
+
+    let report = validate::global_rule(&state, &rule);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    for warning in &report.warnings {
+        tracing::warn!(global_rule = %rule.id, "{}", warning.message);
+    }
+
+    if let Err(e) = persist::store_put(&state, Kind::GlobalRule, &rule.id, &rule).await {
+        return e;
+    }
+    state
+        .cache
+        .global_rules
+        .insert(rule.id.clone(), rule.clone());
+    state.cache.changes.record(Entity::GlobalRule, &rule.id);
+    state.cache.notifier.notify();
+    persist::save_state(&state);
+
+    (
+        StatusCode::OK,
+        Json(json!({"id": rule.id, "status": "created"})),
+    )
+}
+
+/// GET /apisix/admin/global_rules/:id
+pub async fn get_global_rule(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Value>) {
+    match state.cache.global_rules.get(&id) {
+        Some(g) => (StatusCode::OK, Json(json!(g.value().clone()))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Global rule not found"})),
+        ),
+    }
+}
+
+/// DELETE /apisix/admin/global_rules/:id
+pub async fn delete_global_rule(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Value>) {
+    if let Err(e) = persist::store_delete(&state, Kind::GlobalRule, &id).await {
+        return e;
+    }
+    state.cache.global_rules.remove(&id);
+    state.cache.changes.record(Entity::GlobalRule, &id);
+    state.cache.notifier.notify();
+    persist::save_state(&state);
+    (StatusCode::OK, Json(json!({"deleted": true})))
+}
+
+/// GET /apisix/admin/global_rules[?page=&page_size=]
+pub async fn list_global_rules(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond(&state.cache.global_rules, |_| true))
+}
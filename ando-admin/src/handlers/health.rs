@@ -1,9 +1,10 @@
 use crate::server::AdminState;
+use ando_store::integrity;
 use axum::extract::State;
 use axum::response::Json;
 use serde_json::{Value, json};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::UNIX_EPOCH;
 
 pub async fn health_check(State(state): State<Arc<AdminState>>) -> Json<Value> {
     // Collect persistence metadata when a state file is configured.
@@ -42,3 +43,27 @@ pub async fn health_check(State(state): State<Arc<AdminState>>) -> Json<Value> {
         "persistence": persistence,
     }))
 }
+
+/// GET /apisix/admin/health/config
+///
+/// Broken references in the live config: ids of upstreams, services and
+/// plugin configs that do not exist, and plugins the registry lacks.
+pub async fn config_health(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    let mut broken = integrity::broken_refs(&state.cache);
+    broken.extend(integrity::unknown_plugins(&state.cache, &|name| {
+        state.plugin_registry.get(name).is_some()
+    }));
+    let list: Vec<Value> = broken
+        .iter()
+        .map(|r| {
+            let mut entry = json!(r);
+            entry["message"] = json!(r.to_string());
+            entry
+        })
+        .collect();
+    Json(json!({
+        "status": if list.is_empty() { "ok" } else { "degraded" },
+        "broken_references": list,
+        "total": list.len(),
+    }))
+}
@@ -0,0 +1,108 @@
+use axum::http::StatusCode;
+use axum::response::Json;
+use dashmap::DashMap;
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+/// Largest accepted `page_size`; bigger values are clamped.
+const MAX_PAGE_SIZE: usize = 500;
+
+/// Query of the list endpoints.
+///
+/// `?page=&page_size=` select one page of the id-ordered result; without
+/// either the whole result is returned, as before pagination existed.
+/// Every `?label.<key>=<value>` must match.
+#[derive(Debug, Default)]
+pub(crate) struct ListQuery {
+    page: usize,
+    page_size: Option<usize>,
+    pub uri_prefix: Option<String>,
+    pub host: Option<String>,
+    pub plugin: Option<String>,
+    labels: Vec<(String, String)>,
+}
+
+impl ListQuery {
+    pub(crate) fn parse(pairs: Vec<(String, String)>) -> Result<Self, (StatusCode, Json<Value>)> {
+        let mut query = Self {
+            page: 1,
+            ..Self::default()
+        };
+        for (key, value) in pairs {
+            match key.as_str() {
+                "page" => query.page = number(&key, &value)?.max(1),
+                "page_size" => {
+                    query.page_size = Some(number(&key, &value)?.clamp(1, MAX_PAGE_SIZE));
+                }
+                "uri_prefix" => query.uri_prefix = Some(value),
+                "host" => query.host = Some(value),
+                "plugin" => query.plugin = Some(value),
+                _ => {
+                    if let Some(label) = key.strip_prefix("label.") {
+                        query.labels.push((label.to_string(), value));
+                    }
+                }
+            }
+        }
+        if query.page > 1 && query.page_size.is_none() {
+            query.page_size = Some(MAX_PAGE_SIZE);
+        }
+        Ok(query)
+    }
+
+    pub(crate) fn labels_match(&self, labels: &HashMap<String, String>) -> bool {
+        self.labels
+            .iter()
+            .all(|(k, v)| labels.get(k).is_some_and(|l| l == v))
+    }
+
+    /// Serve one page of the entries of `map` that pass `filter`. Only
+    /// the entries on the page are cloned.
+    pub(crate) fn respond<T: Serialize>(
+        &self,
+        map: &DashMap<String, T>,
+        filter: impl Fn(&T) -> bool,
+    ) -> Json<Value> {
+        self.respond_with(map, filter, |e| json!(e))
+    }
+
+    /// Like [`ListQuery::respond`], with each entity shown as `view` returns it.
+    pub(crate) fn respond_with<T>(
+        &self,
+        map: &DashMap<String, T>,
+        filter: impl Fn(&T) -> bool,
+        view: impl Fn(&T) -> Value,
+    ) -> Json<Value> {
+        let mut ids: Vec<String> = map
+            .iter()
+            .filter(|e| filter(e.value()))
+            .map(|e| e.key().clone())
+            .collect();
+        ids.sort_unstable();
+
+        let total = ids.len();
+        let page_size = self.page_size.unwrap_or(total);
+        let list: Vec<Value> = ids
+            .iter()
+            .skip((self.page - 1).saturating_mul(page_size))
+            .take(page_size)
+            .filter_map(|id| map.get(id).map(|e| view(e.value())))
+            .collect();
+        Json(json!({
+            "list": list,
+            "total": total,
+            "page": self.page,
+            "page_size": page_size,
+        }))
+    }
+}
+
+fn number(key: &str, value: &str) -> Result<usize, (StatusCode, Json<Value>)> {
+    value.parse().map_err(|_| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": format!("`{key}` must be a positive integer")})),
+        )
+    })
+}
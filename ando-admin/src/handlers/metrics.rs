@@ -0,0 +1,115 @@
+use crate::handlers::ssls;
+use crate::server::AdminState;
+use axum::extract::State;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+/// GET /metrics
+///
+/// Prometheus text exposition of the data plane's collector. Worker
+/// series lag by up to one flush interval; the worker and config gauges
+/// of `/apisix/admin/status`, the plugin stats and certificate expiry
+/// are refreshed on every scrape.
+pub async fn prometheus_metrics(State(state): State<Arc<AdminState>>) -> Response {
+    match state.metrics.as_ref().filter(|m| m.is_enabled()) {
+        Some(metrics) => {
+            if let Some(workers) = &state.workers {
+                metrics.report_workers(&workers.report());
+            }
+            let plugin_stats = state.plugin_registry.stats();
+            metrics.report_plugin_stats(
+                plugin_stats
+                    .iter()
+                    .map(|(plugin, s)| (*plugin, s.route.as_str(), s.stat, s.value)),
+            );
+            metrics.report_config(
+                state.router_swap.load().version(),
+                state.cache.notifier.last_notified(),
+                state.cache.sync.last_error().is_some(),
+            );
+            let expiries = ssls::expiries(&state);
+            metrics.report_ssl(
+                expiries.iter().map(|e| (e.id.as_str(), e.not_after)),
+                expiries.iter().filter(|e| e.expiring).count(),
+            );
+            (
+                [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+                metrics.render(),
+            )
+                .into_response()
+        }
+        None => (
+            StatusCode::NOT_FOUND,
+            "metrics are disabled (observability.prometheus.enabled)\n",
+        )
+            .into_response(),
+    }
+}
+
+/// GET /apisix/admin/workers/stats
+///
+/// Accepted connections, open connections and handled requests per worker
+/// thread, and their totals. Counted whether or not Prometheus metrics are
+/// enabled.
+pub async fn worker_stats(State(state): State<Arc<AdminState>>) -> Response {
+    match state.workers.as_ref() {
+        Some(workers) => Json(workers.report()).into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "no data plane workers in this process"})),
+        )
+            .into_response(),
+    }
+}
+
+/// GET /apisix/admin/status
+///
+/// Uptime, whether the data plane is draining for shutdown, the options
+/// of its listening sockets, the config the workers run (router version,
+/// object counts, when it last changed and the last failure to sync it),
+/// the certificates expired or expiring within `ssl.expiry_warning_days`
+/// and the per-worker counters of `/apisix/admin/workers/stats`.
+pub async fn status(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    let cache = &state.cache;
+    let last_reload_unix_ms = cache
+        .notifier
+        .last_notified()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    let mut body = json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_seconds": state.started.elapsed().as_secs(),
+        "draining": state.shutdown.as_ref().is_some_and(|s| s.is_draining()),
+        "config": {
+            "router_version": state.router_swap.load().version(),
+            "routes": cache.routes.len(),
+            "services": cache.services.len(),
+            "upstreams": cache.upstreams.len(),
+            "consumers": cache.consumers.len(),
+            "last_reload_unix_ms": last_reload_unix_ms,
+            "last_sync_error": cache.sync.last_error(),
+        },
+    });
+    let expiring: Vec<Value> = ssls::expiries(&state)
+        .into_iter()
+        .filter(|e| e.expiring)
+        .map(|e| json!({"id": e.id, "snis": e.snis, "not_after_unix": e.not_after}))
+        .collect();
+    body["ssl"] = json!({
+        "certificates": cache.ssl_certs.len(),
+        "expiry_warning_days": state.ssl.expiry_warning_days,
+        "expiring": expiring,
+    });
+    if let Some(listener) = &state.listener {
+        body["listener"] = json!(listener);
+    }
+    if let Some(workers) = &state.workers {
+        let report = workers.report();
+        body["workers"] = json!(report.workers);
+        body["total"] = json!(report.total);
+    }
+    Json(body)
+}
@@ -1,7 +1,14 @@
+pub mod bulk;
 pub mod consumers;
 pub mod dashboard;
+pub mod global_rules;
 pub mod health;
+pub mod list;
+pub mod metrics;
+pub mod openapi;
 pub mod plugins;
 pub mod routes;
 pub mod services;
+pub mod ssls;
 pub mod upstreams;
+pub mod validate;
@@ -0,0 +1,452 @@
+//! Routes generated from an OpenAPI 3 spec.
+//!
+//! v2 design: Each path + method of the spec becomes one route, written
+//! like a merge-mode bulk import: the whole set is validated before any of
+//! it is stored. Route ids come from the `operationId`, or from a hash of
+//! the method and path when there is none, so importing an edited spec
+//! again updates its routes instead of adding copies. Parts of a spec with
+//! no route equivalent are skipped with a warning rather than failing the
+//! import.
+
+use crate::handlers::routes::rebuild_router;
+use crate::handlers::validate;
+use crate::persist;
+use crate::server::AdminState;
+use ando_core::route::Route;
+use ando_core::router::Router;
+use ando_core::upstream::Upstream;
+use ando_store::document::{ApplyMode, ConfigDocument};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+/// Operation keys of an OpenAPI path item.
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+
+/// Most `$ref` hops followed to resolve one object.
+const MAX_REF_DEPTH: usize = 8;
+
+/// Body of `POST /apisix/admin/import/openapi`, as JSON or YAML.
+#[derive(Debug, Deserialize)]
+pub struct OpenApiImport {
+    /// The OpenAPI 3.0/3.1 document, inline or as JSON/YAML text.
+    spec: Value,
+    /// Named upstream of every generated route.
+    #[serde(default)]
+    upstream_id: Option<String>,
+    /// Inline upstream of every generated route, instead of `upstream_id`.
+    #[serde(default)]
+    upstream: Option<Upstream>,
+    /// Prepended to every generated URI.
+    #[serde(default)]
+    route_prefix: String,
+    /// Plugins attached to every generated route.
+    #[serde(default)]
+    plugins: HashMap<String, Value>,
+    /// Leave the base path of the spec's server out of the generated URIs.
+    #[serde(default)]
+    strip_base_path: bool,
+    /// Attach a `request-validation` config derived from each operation's
+    /// query and header parameters and JSON request body.
+    #[serde(default)]
+    request_validation: bool,
+}
+
+/// Routes generated from a spec, and what of it was left out.
+struct Generated {
+    routes: Vec<Route>,
+    warnings: Vec<String>,
+}
+
+/// POST /apisix/admin/import/openapi
+///
+/// Answers the ids of the routes created, updated and left unchanged, and
+/// a warning per construct that was skipped.
+pub async fn import_openapi(
+    State(state): State<Arc<AdminState>>,
+    body: Bytes,
+) -> (StatusCode, Json<Value>) {
+    let generated = parse_document(&body)
+        .and_then(|v| serde_json::from_value::<OpenApiImport>(v).map_err(|e| e.to_string()))
+        .and_then(|import| generate(&import));
+    let Generated { routes, warnings } = match generated {
+        Ok(g) => g,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e}))),
+    };
+    if routes.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "spec has no importable operations", "warnings": warnings})),
+        );
+    }
+
+    let doc = ConfigDocument {
+        routes,
+        ..Default::default()
+    };
+    let resulting = doc.resulting(&state.cache, ApplyMode::Merge);
+    let mut errors = resulting.errors(&|route| validate::route_plugins(&state, route));
+    if errors.is_empty()
+        && let Err(e) = Router::build(resulting.router_routes(), 0)
+    {
+        errors.push(e.to_string());
+    }
+    if !errors.is_empty() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({"error": "invalid import", "errors": errors, "warnings": warnings})),
+        );
+    }
+
+    let (mut created, mut updated, mut unchanged) = (Vec::new(), Vec::new(), Vec::new());
+    for route in &doc.routes {
+        let existing = state
+            .cache
+            .routes
+            .get(&route.id)
+            .map(|r| serde_json::to_value(r.value()).ok());
+        match existing {
+            None => created.push(&route.id),
+            Some(old) if old == serde_json::to_value(route).ok() => unchanged.push(&route.id),
+            Some(_) => updated.push(&route.id),
+        }
+    }
+    let summary = json!({
+        "created": created,
+        "updated": updated,
+        "unchanged": unchanged,
+        "warnings": warnings,
+    });
+
+    if let Err(e) = persist::store_replace(&state, &resulting).await {
+        return e;
+    }
+    doc.apply(&state.cache, ApplyMode::Merge);
+    rebuild_router(&state);
+    persist::save_state(&state);
+
+    (StatusCode::OK, Json(summary))
+}
+
+/// JSON or YAML text. JSON is tried first for its exact error messages.
+fn parse_document(text: &[u8]) -> Result<Value, String> {
+    serde_json::from_slice(text)
+        .or_else(|_| serde_yaml::from_slice(text).map_err(|e| e.to_string()))
+}
+
+fn generate(import: &OpenApiImport) -> Result<Generated, String> {
+    let spec = match &import.spec {
+        Value::String(text) => parse_document(text.as_bytes())?,
+        other => other.clone(),
+    };
+    if !spec
+        .get("openapi")
+        .and_then(Value::as_str)
+        .is_some_and(|v| v.starts_with("3."))
+    {
+        return Err("`spec` is not an OpenAPI 3 document".into());
+    }
+    match (&import.upstream_id, &import.upstream) {
+        (Some(_), Some(_)) => return Err("`upstream_id` and `upstream` are exclusive".into()),
+        (None, None) => return Err("requires `upstream_id` or `upstream`".into()),
+        _ => {}
+    }
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or("`spec` has no `paths`")?;
+
+    let mut warnings = Vec::new();
+    let base = if import.strip_base_path {
+        String::new()
+    } else {
+        base_path(&spec, &mut warnings)
+    };
+    let prefix = match import.route_prefix.trim_end_matches('/') {
+        "" => String::new(),
+        p if p.starts_with('/') => p.to_string(),
+        p => format!("/{p}"),
+    };
+    let upstream = import
+        .upstream
+        .as_ref()
+        .map(serde_json::to_value)
+        .transpose()
+        .map_err(|e| e.to_string())?;
+
+    let mut routes = Vec::new();
+    let mut ids = HashSet::new();
+    for (path, item) in paths {
+        if !path.starts_with('/') || !whole_segment_params(path) {
+            warnings.push(format!(
+                "path `{path}`: only whole-segment `{{param}}` templates are supported, skipped"
+            ));
+            continue;
+        }
+        if item.get("$ref").is_some() {
+            warnings.push(format!(
+                "path `{path}`: `$ref` path items are not supported, skipped"
+            ));
+            continue;
+        }
+        if item
+            .get("parameters")
+            .and_then(Value::as_array)
+            .is_some_and(|p| !p.is_empty())
+        {
+            warnings.push(format!(
+                "path `{path}`: path-level parameters are not supported and not validated"
+            ));
+        }
+        for method in METHODS {
+            let Some(op) = item.get(method) else {
+                continue;
+            };
+            let at = format!("{} {path}", method.to_uppercase());
+            let operation_id = op.get("operationId").and_then(Value::as_str);
+            let id = operation_id
+                .map(sanitize_id)
+                .filter(|id| !id.is_empty())
+                .unwrap_or_else(|| hashed_id(method, path));
+            if !ids.insert(id.clone()) {
+                warnings.push(format!("{at}: duplicate route id `{id}`, skipped"));
+                continue;
+            }
+
+            let mut plugins = import.plugins.clone();
+            if import.request_validation
+                && let Some(config) = validation_config(&spec, op, &at, &mut warnings)
+            {
+                plugins
+                    .entry("request-validation".to_string())
+                    .or_insert(config);
+            }
+            let summary = op.get("summary").and_then(Value::as_str);
+            let route = json!({
+                "id": id,
+                "uri": format!("{prefix}{base}{path}"),
+                "methods": [method.to_uppercase()],
+                "upstream_id": import.upstream_id,
+                "upstream": upstream,
+                "plugins": plugins,
+                "name": operation_id.or(summary),
+                "desc": summary.or_else(|| op.get("description").and_then(Value::as_str)),
+            });
+            routes.push(serde_json::from_value(route).map_err(|e| format!("{at}: {e}"))?);
+        }
+    }
+    Ok(Generated { routes, warnings })
+}
+
+/// Path of the first server's URL, without a trailing slash.
+fn base_path(spec: &Value, warnings: &mut Vec<String>) -> String {
+    let urls: Vec<&str> = spec
+        .get("servers")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s.get("url").and_then(Value::as_str))
+        .collect();
+    let Some(first) = urls.first() else {
+        return String::new();
+    };
+    if first.contains('{') {
+        warnings.push(format!(
+            "server `{first}`: server variables are not supported, its base path is not applied"
+        ));
+        return String::new();
+    }
+    let base = url_path(first);
+    if urls[1..].iter().any(|url| url_path(url) != base) {
+        warnings.push(format!(
+            "servers have different base paths, `{base}` of the first is used"
+        ));
+    }
+    base
+}
+
+fn url_path(url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => url,
+    };
+    let path = path.trim_end_matches('/');
+    if path.is_empty() || path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{path}")
+    }
+}
+
+/// Whether every `{param}` of `path` spans a whole segment, the only form
+/// the router matches.
+fn whole_segment_params(path: &str) -> bool {
+    path.split('/').all(|segment| {
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) => !name.is_empty() && !name.contains(['{', '}']),
+            None => !segment.contains(['{', '}']),
+        }
+    })
+}
+
+fn sanitize_id(operation_id: &str) -> String {
+    operation_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Id of an operation without `operationId`.
+fn hashed_id(method: &str, path: &str) -> String {
+    let digest = Sha256::digest(format!("{method} {path}").as_bytes());
+    let hex: String = digest[..6].iter().map(|b| format!("{b:02x}")).collect();
+    format!("{method}-{hex}")
+}
+
+/// Follow `#/...` references within the spec.
+fn resolve<'a>(spec: &'a Value, mut value: &'a Value) -> &'a Value {
+    for _ in 0..MAX_REF_DEPTH {
+        let Some(target) = value
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|r| r.strip_prefix('#'))
+            .and_then(|pointer| spec.pointer(pointer))
+        else {
+            break;
+        };
+        value = target;
+    }
+    value
+}
+
+/// An object schema built up one property at a time.
+#[derive(Default)]
+struct ObjectSchema {
+    properties: Map<String, Value>,
+    required: Vec<String>,
+}
+
+impl ObjectSchema {
+    fn add(&mut self, name: String, schema: Value, required: bool) {
+        if required {
+            self.required.push(name.clone());
+        }
+        self.properties.insert(name, schema);
+    }
+
+    fn build(self) -> Option<Value> {
+        if self.properties.is_empty() {
+            return None;
+        }
+        let mut schema = json!({"type": "object", "properties": self.properties});
+        if !self.required.is_empty() {
+            schema["required"] = json!(self.required);
+        }
+        Some(schema)
+    }
+}
+
+/// `request-validation` config for one operation, `None` if it declares
+/// nothing to check.
+fn validation_config(
+    spec: &Value,
+    op: &Value,
+    at: &str,
+    warnings: &mut Vec<String>,
+) -> Option<Value> {
+    let mut query = ObjectSchema::default();
+    let mut header = ObjectSchema::default();
+    for param in op
+        .get("parameters")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let param = resolve(spec, param);
+        let Some(name) = param.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let (target, name) = match param.get("in").and_then(Value::as_str) {
+            Some("query") => (&mut query, name.to_string()),
+            Some("header") => (&mut header, name.to_ascii_lowercase()),
+            Some("cookie") => {
+                warnings.push(format!("{at}: cookie parameter `{name}` is not validated"));
+                continue;
+            }
+            // Path parameters are captured by the route itself.
+            _ => continue,
+        };
+        let schema = string_schema(param.get("schema").map(|s| resolve(spec, s)));
+        let required = param.get("required").and_then(Value::as_bool) == Some(true);
+        target.add(name, schema, required);
+    }
+
+    let mut config = Map::new();
+    if let Some(schema) = query.build() {
+        config.insert("querystring_schema".into(), with_components(spec, schema));
+    }
+    if let Some(schema) = header.build() {
+        config.insert("header_schema".into(), with_components(spec, schema));
+    }
+    if let Some(body) = op.get("requestBody").map(|b| resolve(spec, b)) {
+        let json_schema = body
+            .get("content")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .find(|(media_type, _)| is_json(media_type))
+            .and_then(|(_, media)| media.get("schema"));
+        match json_schema {
+            Some(schema) => {
+                config.insert("body_schema".into(), with_components(spec, schema.clone()));
+            }
+            None => warnings.push(format!("{at}: non-JSON request body is not validated")),
+        }
+    }
+    (!config.is_empty()).then_some(Value::Object(config))
+}
+
+/// Query and header values are strings: a parameter of another type is
+/// checked through the pattern of its string form.
+fn string_schema(schema: Option<&Value>) -> Value {
+    let Some(schema) = schema else {
+        return json!({});
+    };
+    match schema.get("type").and_then(Value::as_str) {
+        Some("string") => schema.clone(),
+        Some("integer") => json!({"type": "string", "pattern": "^-?[0-9]+$"}),
+        Some("number") => json!({"type": "string", "pattern": "^-?[0-9]+(\\.[0-9]+)?$"}),
+        Some("boolean") => json!({"enum": ["true", "false"]}),
+        _ => json!({}),
+    }
+}
+
+/// `schema` with the spec's `components` alongside, so `#/components/...`
+/// references still resolve from the schema as root.
+fn with_components(spec: &Value, schema: Value) -> Value {
+    match spec.get("components") {
+        Some(components) if schema.to_string().contains("\"$ref\"") => {
+            json!({"allOf": [schema], "components": components})
+        }
+        _ => schema,
+    }
+}
+
+fn is_json(media_type: &str) -> bool {
+    let essence = media_type.split(';').next().unwrap_or("").trim();
+    essence.eq_ignore_ascii_case("application/json") || essence.ends_with("+json")
+}
@@ -1,5 +1,6 @@
 use crate::server::AdminState;
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::response::Json;
 use serde_json::{Value, json};
 use std::sync::Arc;
@@ -9,14 +10,37 @@ const CE_PLUGINS: &[(&str, &str, bool)] = &[
     ("key-auth", "Access", true),
     ("jwt-auth", "Access", true),
     ("basic-auth", "Access", true),
+    ("hmac-auth", "Access", true),
+    ("mtls-auth", "Access", true),
+    ("consumer-restriction", "Access", true),
+    ("openid-connect", "Access", true),
+    ("real-ip", "Rewrite", true),
+    ("fault-injection", "Access", true),
+    ("client-control", "Rewrite", true),
     ("ip-restriction", "Access", true),
+    ("ua-restriction", "Access", true),
+    ("referer-restriction", "Access", true),
+    ("time-restriction", "Access", true),
+    ("request-validation", "Access", true),
     ("rate-limiting", "Access", true),
+    ("limit-req", "Access", true),
+    ("limit-conn", "Access", true),
+    ("api-breaker", "Access", true),
+    ("mock", "Access", true),
+    ("proxy-cache", "Access", true),
+    ("traffic-split", "Access", true),
+    ("body-routing", "Access", true),
+    ("request-transformer", "Access", true),
+    ("proxy-mirror", "BeforeProxy", true),
+    ("debug-echo", "BeforeProxy", true),
     ("cors", "HeaderFilter", true),
+    ("response-headers-policy", "HeaderFilter", true),
+    ("response-transformer", "HeaderFilter", true),
+    ("error-page", "HeaderFilter", true),
 ];
 
 /// Enterprise Edition plugins — visible in the API but not available in CE.
 const EE_PLUGINS: &[(&str, &str, bool)] = &[
-    ("hmac-auth", "Access", false),
     ("oauth2", "Access", false),
     ("rate-limiting-advanced", "Access", false),
     ("traffic-mirror", "Upstream", false),
@@ -29,7 +53,7 @@ pub async fn list_plugins(State(state): State<Arc<AdminState>>) -> Json<Value> {
     let registered = state.plugin_registry.list();
     let is_enterprise = state.edition == "enterprise";
 
-    let ce: Vec<Value> = CE_PLUGINS
+    let mut ce: Vec<Value> = CE_PLUGINS
         .iter()
         .map(|(name, phase, _)| {
             json!({
@@ -41,6 +65,17 @@ pub async fn list_plugins(State(state): State<Arc<AdminState>>) -> Json<Value> {
         })
         .collect();
 
+    // Plugins loaded from `plugins.external_dirs`
+    ce.extend(state.plugin_registry.external().iter().filter_map(|name| {
+        let plugin = state.plugin_registry.get(name)?;
+        Some(json!({
+            "name":      name,
+            "phase":     plugin.phases().first().map(|p| format!("{p:?}")),
+            "available": true,
+            "edition":   "external"
+        }))
+    }));
+
     let ee: Vec<Value> = EE_PLUGINS
         .iter()
         .map(|(name, phase, _)| {
@@ -71,3 +106,52 @@ pub async fn list_plugins(State(state): State<Arc<AdminState>>) -> Json<Value> {
         "edition": state.edition
     }))
 }
+
+/// GET /apisix/admin/plugins
+///
+/// Every plugin in the live registry with its default priority and
+/// phases, in the order a route runs them unless it sets
+/// `_meta.priority`.
+pub async fn registered_plugins(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    let mut plugins: Vec<_> = state
+        .plugin_registry
+        .list()
+        .into_iter()
+        .filter_map(|name| state.plugin_registry.get(name))
+        .collect();
+    plugins.sort_by(|a, b| {
+        b.priority()
+            .cmp(&a.priority())
+            .then_with(|| a.name().cmp(b.name()))
+    });
+    let plugins: Vec<Value> = plugins
+        .iter()
+        .map(|plugin| {
+            json!({
+                "name":     plugin.name(),
+                "priority": plugin.priority(),
+                "phases":   plugin.phases().iter().map(|p| p.as_str()).collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    Json(json!({ "total": plugins.len(), "plugins": plugins }))
+}
+
+/// GET /apisix/admin/plugins/stats
+///
+/// Calls and estimated p50/p99 wall time per plugin since start, from
+/// `ando_plugin_duration_seconds`. Worker-side only: async access hooks
+/// are not timed.
+pub async fn plugin_stats(
+    State(state): State<Arc<AdminState>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    match state.metrics.as_ref().filter(|m| m.is_enabled()) {
+        Some(metrics) => Ok(Json(json!({ "plugins": metrics.plugin_latencies() }))),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            Json(json!({
+                "error": "metrics are disabled (observability.prometheus.enabled)"
+            })),
+        )),
+    }
+}
@@ -1,17 +1,22 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::validate::{self, WriteParams};
 use crate::persist;
 use crate::server::AdminState;
 use ando_core::route::Route;
 use ando_core::router::Router;
-use axum::extract::{Path, State};
+use ando_store::changes::Entity;
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::Json;
 use serde_json::{Value, json};
 use std::sync::Arc;
 
-/// PUT /apisix/admin/routes/:id
+/// PUT /apisix/admin/routes/:id[?dry_run=true]
 pub async fn put_route(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
     Json(mut body): Json<Value>,
 ) -> (StatusCode, Json<Value>) {
     // Ensure the ID is set
@@ -27,7 +32,22 @@ pub async fn put_route(
         }
     };
 
+    let report = validate::route(&state, &route, params.force);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    for warning in &report.warnings {
+        tracing::warn!(route = %route.id, "{}", warning.message);
+    }
+
+    if let Err(e) = persist::store_put(&state, Kind::Route, &route.id, &route).await {
+        return e;
+    }
     state.cache.routes.insert(route.id.clone(), route.clone());
+    state.cache.changes.record(Entity::Route, &route.id);
 
     // Rebuild router
     rebuild_router(&state);
@@ -60,25 +80,81 @@ pub async fn delete_route(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
 ) -> (StatusCode, Json<Value>) {
+    if let Err(e) = persist::store_delete(&state, Kind::Route, &id).await {
+        return e;
+    }
     state.cache.routes.remove(&id);
+    state.cache.changes.record(Entity::Route, &id);
     rebuild_router(&state);
     persist::save_state(&state);
     (StatusCode::OK, Json(json!({"deleted": true})))
 }
 
-/// GET /apisix/admin/routes
-pub async fn list_routes(State(state): State<Arc<AdminState>>) -> Json<Value> {
-    let routes: Vec<Route> = state.cache.all_routes();
-    Json(json!({"list": routes, "total": routes.len()}))
+/// GET /apisix/admin/routes[?page=&page_size=&uri_prefix=&host=&plugin=&label.<key>=]
+///
+/// `plugin` matches routes that get the plugin from their own config, their
+/// service or their plugin config. Labels and hosts include the ones
+/// inherited from the service.
+pub async fn list_routes(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond(&state.cache.routes, |route| {
+        // Filter on the labels and hosts the route inherits from its service.
+        let inherited;
+        let route = match route
+            .service_id
+            .as_deref()
+            .and_then(|id| state.cache.services.get(id))
+        {
+            Some(service) => {
+                let mut route = route.clone();
+                service.apply_defaults(&mut route);
+                inherited = route;
+                &inherited
+            }
+            None => route,
+        };
+        query.labels_match(&route.labels)
+            && query
+                .uri_prefix
+                .as_deref()
+                .is_none_or(|prefix| route.paths().any(|p| p.starts_with(prefix)))
+            && query
+                .host
+                .as_deref()
+                .is_none_or(|host| route.hosts.iter().any(|h| h.eq_ignore_ascii_case(host)))
+            && query
+                .plugin
+                .as_deref()
+                .is_none_or(|plugin| uses_plugin(&state, route, plugin))
+    }))
+}
+
+fn uses_plugin(state: &AdminState, route: &Route, plugin: &str) -> bool {
+    let cache = &state.cache;
+    route.plugins.contains_key(plugin)
+        || route
+            .service_id
+            .as_ref()
+            .and_then(|id| cache.services.get(id))
+            .is_some_and(|s| s.plugins.contains_key(plugin))
+        || route
+            .plugin_config_id
+            .as_ref()
+            .and_then(|id| cache.plugin_configs.get(id))
+            .is_some_and(|pc| pc.plugins.contains_key(plugin))
 }
 
 /// Rebuild the router from cache and swap it in.
-fn rebuild_router(state: &AdminState) {
+pub(crate) fn rebuild_router(state: &AdminState) {
     let routes = state.cache.all_routes();
     let current_ver = state.router_swap.load().version();
     match Router::build(routes, current_ver + 1) {
         Ok(new_router) => {
             state.router_swap.store(Arc::new(new_router));
+            state.cache.notifier.notify();
             state.config_changed.notify_waiters();
         }
         Err(e) => {
@@ -1,16 +1,23 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::routes::rebuild_router;
+use crate::handlers::validate::{self, WriteParams};
 use crate::persist;
 use crate::server::AdminState;
 use ando_core::service::Service;
-use axum::extract::{Path, State};
+use ando_store::changes::Entity;
+use ando_store::integrity::RefTarget;
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::Json;
 use serde_json::{Value, json};
 use std::sync::Arc;
 
-/// PUT /apisix/admin/services/:id
+/// PUT /apisix/admin/services/:id[?dry_run=true]
 pub async fn put_service(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
     Json(mut body): Json<Value>,
 ) -> (StatusCode, Json<Value>) {
     body["id"] = json!(id);
@@ -25,10 +32,27 @@ pub async fn put_service(
         }
     };
 
+    let report = validate::service(&state, &service, params.force);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    for warning in &report.warnings {
+        tracing::warn!(service = %service.id, "{}", warning.message);
+    }
+
+    if let Err(e) = persist::store_put(&state, Kind::Service, &service.id, &service).await {
+        return e;
+    }
     state
         .cache
         .services
         .insert(service.id.clone(), service.clone());
+    state.cache.changes.record(Entity::Service, &service.id);
+    // Its routes inherit its hosts and timeout; see `Service::apply_defaults`.
+    rebuild_router(&state);
     persist::save_state(&state);
 
     (
@@ -51,23 +75,36 @@ pub async fn get_service(
     }
 }
 
-/// DELETE /apisix/admin/services/:id
+/// DELETE /apisix/admin/services/:id[?force=true]
+///
+/// Refused while routes still use the service, unless forced.
 pub async fn delete_service(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
 ) -> (StatusCode, Json<Value>) {
+    let report = validate::deletion(&state, RefTarget::Service, &id, params.force);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    if let Err(e) = persist::store_delete(&state, Kind::Service, &id).await {
+        return e;
+    }
     state.cache.services.remove(&id);
+    state.cache.changes.record(Entity::Service, &id);
+    rebuild_router(&state);
     persist::save_state(&state);
     (StatusCode::OK, Json(json!({"deleted": true})))
 }
 
-/// GET /apisix/admin/services
-pub async fn list_services(State(state): State<Arc<AdminState>>) -> Json<Value> {
-    let services: Vec<Service> = state
-        .cache
-        .services
-        .iter()
-        .map(|s| s.value().clone())
-        .collect();
-    Json(json!({"list": services, "total": services.len()}))
+/// GET /apisix/admin/services[?page=&page_size=&label.<key>=]
+pub async fn list_services(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond(&state.cache.services, |s| query.labels_match(&s.labels)))
 }
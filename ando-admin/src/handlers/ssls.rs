@@ -0,0 +1,174 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::validate::{self, WriteParams};
+use crate::persist;
+use crate::server::AdminState;
+use ando_core::ssl::{CertificateInfo, SslCertificate};
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What GET returns in place of a key stored as plain PEM.
+pub const REDACTED_KEY: &str = "[redacted]";
+
+/// PUT /apisix/admin/ssls/:id[?dry_run=true]
+///
+/// The certificate must parse, match its key and cover every SNI in
+/// `snis`. With an SSL master key configured the key is stored encrypted;
+/// a key that already is (as returned by GET) is kept as it is, and so is
+/// the stored key when the body carries `[redacted]`.
+pub async fn put_ssl(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
+    Json(mut body): Json<Value>,
+) -> (StatusCode, Json<Value>) {
+    body["id"] = json!(id);
+
+    let mut ssl: SslCertificate = match serde_json::from_value(body) {
+        Ok(s) => s,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": e.to_string()})),
+            );
+        }
+    };
+
+    restore_key(&state, &mut ssl);
+
+    let report = validate::ssl(&state, &ssl);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    for warning in &report.warnings {
+        tracing::warn!(ssl = %ssl.id, "{}", warning.message);
+    }
+
+    if !ssl.key_is_encrypted() {
+        match state.ssl.master_key() {
+            Some(master) => ssl.key = master.encrypt(&ssl.key),
+            None => tracing::warn!(
+                ssl = %ssl.id,
+                "SSL private key stored unencrypted; set ssl.master_key or ANDO_SSL_MASTER_KEY"
+            ),
+        }
+    }
+    if let Err(e) = persist::store_put(&state, Kind::Ssl, &ssl.id, &ssl).await {
+        return e;
+    }
+    // The TLS resolver reads the map on every handshake; no rebuild needed.
+    state.cache.ssl_certs.insert(ssl.id.clone(), ssl.clone());
+    persist::save_state(&state);
+
+    (
+        StatusCode::OK,
+        Json(json!({"id": ssl.id, "status": "created"})),
+    )
+}
+
+/// GET /apisix/admin/ssls/:id
+pub async fn get_ssl(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Value>) {
+    match state.cache.ssl_certs.get(&id) {
+        Some(s) => (StatusCode::OK, Json(public_view(s.value()))),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "SSL not found"})),
+        ),
+    }
+}
+
+/// DELETE /apisix/admin/ssls/:id
+pub async fn delete_ssl(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Value>) {
+    if let Err(e) = persist::store_delete(&state, Kind::Ssl, &id).await {
+        return e;
+    }
+    state.cache.ssl_certs.remove(&id);
+    persist::save_state(&state);
+    (StatusCode::OK, Json(json!({"deleted": true})))
+}
+
+/// GET /apisix/admin/ssls[?page=&page_size=]
+pub async fn list_ssls(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond_with(&state.cache.ssl_certs, |_| true, public_view))
+}
+
+/// An SSL object as the Admin API returns it: the private key only in its
+/// encrypted form, a plain one replaced with [`REDACTED_KEY`].
+fn public_view(ssl: &SslCertificate) -> Value {
+    let mut ssl = ssl.clone();
+    redact_key(&mut ssl);
+    json!(ssl)
+}
+
+/// Replace a plain private key with [`REDACTED_KEY`].
+pub(crate) fn redact_key(ssl: &mut SslCertificate) {
+    if !ssl.key_is_encrypted() {
+        ssl.key = REDACTED_KEY.to_string();
+    }
+}
+
+/// Undo [`redact_key`] for a written-back object: a [`REDACTED_KEY`]
+/// stands for the key stored under the same id, if any.
+pub(crate) fn restore_key(state: &AdminState, ssl: &mut SslCertificate) {
+    if ssl.key == REDACTED_KEY
+        && let Some(stored) = state.cache.ssl_certs.get(&ssl.id)
+    {
+        ssl.key.clone_from(&stored.key);
+    }
+}
+
+/// Expiry of one enabled SSL object's certificate.
+pub(crate) struct Expiry {
+    pub id: String,
+    pub snis: Vec<String>,
+    /// Unix time of `notAfter`.
+    pub not_after: i64,
+    /// Expired, or expires within `ssl.expiry_warning_days`.
+    pub expiring: bool,
+}
+
+/// Expiry of every enabled SSL object whose certificate parses, by id.
+pub(crate) fn expiries(state: &AdminState) -> Vec<Expiry> {
+    let now = unix_now();
+    let days = state.ssl.expiry_warning_days;
+    let mut out: Vec<Expiry> = state
+        .cache
+        .ssl_certs
+        .iter()
+        .filter(|e| e.status == 1)
+        .filter_map(|e| {
+            let info = CertificateInfo::parse(&e.cert).ok()?;
+            Some(Expiry {
+                id: e.id.clone(),
+                snis: e.snis.clone(),
+                not_after: info.not_after,
+                expiring: info.expires_within(now, days),
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.id.cmp(&b.id));
+    out
+}
+
+pub(crate) fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64)
+}
@@ -1,7 +1,12 @@
+use crate::handlers::list::ListQuery;
+use crate::handlers::validate::{self, WriteParams};
 use crate::persist;
 use crate::server::AdminState;
 use ando_core::upstream::Upstream;
-use axum::extract::{Path, State};
+use ando_store::changes::Entity;
+use ando_store::integrity::RefTarget;
+use ando_store::store::Kind;
+use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::Json;
 use serde_json::{Value, json};
@@ -10,6 +15,7 @@ use std::sync::Arc;
 pub async fn put_upstream(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
     Json(mut body): Json<Value>,
 ) -> (StatusCode, Json<Value>) {
     body["id"] = json!(id);
@@ -24,8 +30,21 @@ pub async fn put_upstream(
         }
     };
 
+    let report = validate::upstream(&upstream);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+
     let uid = upstream.id.clone().unwrap_or(id.clone());
+    if let Err(e) = persist::store_put(&state, Kind::Upstream, &uid, &upstream).await {
+        return e;
+    }
     state.cache.upstreams.insert(uid.clone(), upstream);
+    state.cache.changes.record(Entity::Upstream, &uid);
+    state.cache.notifier.notify();
     persist::save_state(&state);
 
     (
@@ -47,21 +66,89 @@ pub async fn get_upstream(
     }
 }
 
+/// DELETE /apisix/admin/upstreams/:id[?force=true]
+///
+/// Refused while routes or services still use the upstream, unless forced.
 pub async fn delete_upstream(
     State(state): State<Arc<AdminState>>,
     Path(id): Path<String>,
+    Query(params): Query<WriteParams>,
 ) -> (StatusCode, Json<Value>) {
+    let report = validate::deletion(&state, RefTarget::Upstream, &id, params.force);
+    if params.dry_run {
+        return report.dry_run();
+    }
+    if !report.is_valid() {
+        return report.rejection();
+    }
+    if let Err(e) = persist::store_delete(&state, Kind::Upstream, &id).await {
+        return e;
+    }
     state.cache.upstreams.remove(&id);
+    state.cache.changes.record(Entity::Upstream, &id);
+    state.cache.notifier.notify();
     persist::save_state(&state);
     (StatusCode::OK, Json(json!({"deleted": true})))
 }
 
-pub async fn list_upstreams(State(state): State<Arc<AdminState>>) -> Json<Value> {
-    let upstreams: Vec<Upstream> = state
+/// GET /apisix/admin/upstreams[?page=&page_size=&label.<key>=]
+pub async fn list_upstreams(
+    State(state): State<Arc<AdminState>>,
+    Query(pairs): Query<Vec<(String, String)>>,
+) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
+    let query = ListQuery::parse(pairs)?;
+    Ok(query.respond(&state.cache.upstreams, |u| query.labels_match(&u.labels)))
+}
+
+/// Health of every node of one upstream. Nodes the active checker has not
+/// probed yet are reported healthy, matching how the proxy treats them.
+fn upstream_health(state: &AdminState, id: &str, upstream: &Upstream) -> Value {
+    let checked = state.cache.health.upstream(id).unwrap_or_default();
+    let tripped = state.cache.breakers.open_nodes();
+    let mut nodes: Vec<Value> = upstream
+        .nodes
+        .keys()
+        .map(|addr| {
+            let health = checked.get(addr).cloned().unwrap_or_default();
+            let mut node = json!(health);
+            node["node"] = json!(addr);
+            node["breaker_open"] = json!(tripped.contains(addr));
+            node
+        })
+        .collect();
+    nodes.sort_by(|a, b| a["node"].as_str().cmp(&b["node"].as_str()));
+    let ejected = nodes.iter().filter(|n| n["healthy"] == false).count();
+    json!({
+        "id": id,
+        "checks_enabled": upstream.checks.as_ref().is_some_and(|c| c.active.is_some()),
+        "nodes": nodes,
+        "ejected": ejected,
+    })
+}
+
+pub async fn get_upstream_health(
+    State(state): State<Arc<AdminState>>,
+    Path(id): Path<String>,
+) -> (StatusCode, Json<Value>) {
+    match state.cache.upstreams.get(&id) {
+        Some(u) => (
+            StatusCode::OK,
+            Json(upstream_health(&state, &id, u.value())),
+        ),
+        None => (
+            StatusCode::NOT_FOUND,
+            Json(json!({"error": "Upstream not found"})),
+        ),
+    }
+}
+
+pub async fn list_upstream_health(State(state): State<Arc<AdminState>>) -> Json<Value> {
+    let mut list: Vec<Value> = state
         .cache
         .upstreams
         .iter()
-        .map(|u| u.value().clone())
+        .map(|u| upstream_health(&state, u.key(), u.value()))
         .collect();
-    Json(json!({"list": upstreams, "total": upstreams.len()}))
+    list.sort_by(|a, b| a["id"].as_str().cmp(&b["id"].as_str()));
+    Json(json!({"list": list, "total": list.len()}))
 }
@@ -0,0 +1,384 @@
+use crate::server::AdminState;
+use ando_core::consumer::Consumer;
+use ando_core::global_rule::GlobalRule;
+use ando_core::plugin_config::PluginConfig;
+use ando_core::route::Route;
+use ando_core::router::validate_conditions;
+use ando_core::service::Service;
+use ando_core::ssl::{CertificateInfo, SslCertificate};
+use ando_core::upstream::Upstream;
+use ando_store::integrity::{self, BrokenRef, RefTarget};
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::Json;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// One validation finding.
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    /// Field the finding is about, e.g. `plugins.cors` or `uri`. Empty for
+    /// errors about the document as a whole.
+    pub field: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<String>,
+    pub message: String,
+}
+
+/// Result of validating one entity. Warnings never block a write.
+#[derive(Debug, Default, Serialize)]
+pub struct Report {
+    pub errors: Vec<Issue>,
+    pub warnings: Vec<Issue>,
+}
+
+impl Report {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    fn error(&mut self, field: &str, message: impl Into<String>) {
+        self.errors.push(Issue {
+            field: field.to_string(),
+            plugin: None,
+            message: message.into(),
+        });
+    }
+
+    fn body(&self) -> Value {
+        json!({
+            "valid": self.is_valid(),
+            "errors": self.errors,
+            "warnings": self.warnings,
+        })
+    }
+
+    /// 400 for a write that failed validation. `error` carries the first
+    /// message; `errors` lists all of them.
+    pub(crate) fn rejection(&self) -> (StatusCode, Json<Value>) {
+        let first = self.errors.first().map(|i| i.message.as_str());
+        let mut body = self.body();
+        body["error"] = json!(first.unwrap_or("invalid config"));
+        (StatusCode::BAD_REQUEST, Json(body))
+    }
+
+    /// Response for a `?dry_run=true` write: nothing is stored either way.
+    pub(crate) fn dry_run(&self) -> (StatusCode, Json<Value>) {
+        let status = if self.is_valid() {
+            StatusCode::OK
+        } else {
+            StatusCode::BAD_REQUEST
+        };
+        let mut body = self.body();
+        body["dry_run"] = json!(true);
+        (status, Json(body))
+    }
+
+    /// Dangling references are errors, or warnings on a forced write.
+    fn references(&mut self, refs: Vec<BrokenRef>, force: bool) {
+        let issues = if force {
+            &mut self.warnings
+        } else {
+            &mut self.errors
+        };
+        issues.extend(refs.into_iter().map(|r| Issue {
+            field: r.target.field().to_string(),
+            plugin: None,
+            message: format!("unknown {} `{}`", r.target.as_str(), r.missing),
+        }));
+    }
+}
+
+/// `?dry_run=true` on admin writes: validate and report without storing.
+/// `?force=true` stores an object whose references are dangling, or
+/// deletes one that is still referenced.
+#[derive(Debug, Default, Deserialize)]
+pub struct WriteParams {
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Configure every plugin block against the live registry. Returns whether
+/// any of them is a terminal plugin.
+fn check_plugins(
+    state: &AdminState,
+    plugins: &HashMap<String, Value>,
+    report: &mut Report,
+) -> bool {
+    let mut names: Vec<&String> = plugins.keys().collect();
+    names.sort();
+    let mut terminal = false;
+    for name in names {
+        let field = format!("plugins.{name}");
+        // Unknown plugins (e.g. EE-only) are skipped by the data plane too.
+        let Some(plugin) = state.plugin_registry.get(name) else {
+            report.warnings.push(Issue {
+                field,
+                plugin: Some(name.clone()),
+                message: format!("unknown plugin `{name}` is ignored"),
+            });
+            continue;
+        };
+        // A route's opt-out of a service or global plugin configures nothing.
+        if ando_plugin::plugin::is_disabled(&plugins[name]) {
+            continue;
+        }
+        let meta = ando_plugin::plugin::priority_override(&plugins[name])
+            .map_err(|e| ("priority", e))
+            .and_then(|_| {
+                ando_plugin::filter::PluginFilter::from_config(&plugins[name])
+                    .map_err(|e| ("filter", e))
+            });
+        if let Err((key, e)) = meta {
+            report.errors.push(Issue {
+                field: format!("{field}._meta.{key}"),
+                plugin: Some(name.clone()),
+                message: format!("invalid config for plugin `{name}`: {e}"),
+            });
+            continue;
+        }
+        match plugin.configure(&plugins[name]) {
+            Ok(_) => terminal |= plugin.is_terminal(),
+            Err(e) => report.errors.push(Issue {
+                field,
+                plugin: Some(name.clone()),
+                message: format!("invalid config for plugin `{name}`: {e}"),
+            }),
+        }
+    }
+    terminal
+}
+
+/// Routes without `upstream`, `upstream_id` or `service_id` are accepted
+/// only with a terminal plugin such as `mock`, on the route or its plugin
+/// config, which answers every request itself. The data plane answers 503
+/// for a request that reaches a route with neither.
+pub(crate) fn route(state: &AdminState, route: &Route, force: bool) -> Report {
+    let mut report = Report::default();
+    if route.paths().next().is_none() {
+        report.error("uri", "route requires `uri` or `uris`");
+    }
+    if let Err(e) = validate_conditions(route) {
+        report.error("vars", e);
+    }
+    if let Some(ref listeners) = state.listeners {
+        for name in route.listeners.iter().filter(|n| !listeners.contains(n)) {
+            report.error("listeners", format!("unknown listener `{name}`"));
+        }
+    }
+    let mut terminal = check_plugins(state, &route.plugins, &mut report);
+    report.references(integrity::route_refs(&state.cache, route), force);
+    if let Some(ref ups) = route.upstream {
+        check_discovery(ups, "upstream.", &mut report);
+    }
+
+    if let Some(ref id) = route.plugin_config_id
+        && let Some(config) = state.cache.plugin_configs.get(id)
+    {
+        // Its own errors were reported when the plugin config was written.
+        terminal |= check_plugins(state, &config.plugins, &mut Report::default());
+    }
+    let has_upstream =
+        route.upstream.is_some() || route.upstream_id.is_some() || route.service_id.is_some();
+    if !has_upstream && !terminal {
+        report.error(
+            "upstream",
+            "route requires `upstream`, `upstream_id` or `service_id`, or a terminal plugin",
+        );
+    }
+    report
+}
+
+/// Plugin errors of `route` as one message, for checks that take a
+/// `Result` (bulk import).
+pub(crate) fn route_plugins(state: &AdminState, route: &Route) -> Result<(), String> {
+    let mut report = Report::default();
+    check_plugins(state, &route.plugins, &mut report);
+    if report.is_valid() {
+        return Ok(());
+    }
+    let messages: Vec<_> = report.errors.into_iter().map(|i| i.message).collect();
+    Err(messages.join("; "))
+}
+
+pub(crate) fn service(state: &AdminState, service: &Service, force: bool) -> Report {
+    let mut report = Report::default();
+    check_plugins(state, &service.plugins, &mut report);
+    report.references(integrity::service_refs(&state.cache, service), force);
+    if let Some(ref ups) = service.upstream {
+        check_discovery(ups, "upstream.", &mut report);
+    }
+    report
+}
+
+pub(crate) fn upstream(upstream: &Upstream) -> Report {
+    let mut report = Report::default();
+    check_discovery(upstream, "", &mut report);
+    report
+}
+
+/// A discovered upstream must name its service in the registry's format.
+fn check_discovery(upstream: &Upstream, prefix: &str, report: &mut Report) {
+    if let Err(e) = upstream.check_discovery() {
+        report.error(&format!("{prefix}service_name"), e);
+    }
+}
+
+/// Objects still referencing `target` `id`, which deleting it would leave
+/// dangling. Errors, or warnings on a forced delete.
+pub(crate) fn deletion(state: &AdminState, target: RefTarget, id: &str, force: bool) -> Report {
+    let mut report = Report::default();
+    let issues = if force {
+        &mut report.warnings
+    } else {
+        &mut report.errors
+    };
+    issues.extend(
+        integrity::refs_to(&state.cache, target, id)
+            .into_iter()
+            .map(|r| Issue {
+                field: String::new(),
+                plugin: None,
+                message: format!(
+                    "{} `{id}` is still used by {} `{}`",
+                    target.as_str(),
+                    r.kind,
+                    r.id
+                ),
+            }),
+    );
+    report
+}
+
+pub(crate) fn plugin_config(state: &AdminState, pc: &PluginConfig) -> Report {
+    let mut report = Report::default();
+    check_plugins(state, &pc.plugins, &mut report);
+    report
+}
+
+pub(crate) fn global_rule(state: &AdminState, rule: &GlobalRule) -> Report {
+    let mut report = Report::default();
+    check_plugins(state, &rule.plugins, &mut report);
+    report
+}
+
+/// The certificate must parse, belong to the private key (decrypted with
+/// the SSL master key if stored encrypted) and cover every SNI in
+/// `snis`. An expired or expiring certificate is a warning.
+pub(crate) fn ssl(state: &AdminState, ssl: &SslCertificate) -> Report {
+    let mut report = Report::default();
+    let info = match CertificateInfo::parse(&ssl.cert) {
+        Ok(info) => info,
+        Err(e) => {
+            report.error("cert", e);
+            return report;
+        }
+    };
+    match ssl.key_pem(state.ssl.master_key().as_ref()) {
+        Ok(key) => {
+            if let Err(e) = info.check_key(&key) {
+                report.error("key", e);
+            }
+        }
+        Err(e) => report.error("key", e),
+    }
+    for sni in &ssl.snis {
+        if !info.covers(sni) {
+            report.error("snis", format!("certificate does not cover SNI `{sni}`"));
+        }
+    }
+    let now = crate::handlers::ssls::unix_now();
+    if info.expires_within(now, state.ssl.expiry_warning_days) {
+        let message = if info.not_after <= now {
+            "certificate has expired".to_string()
+        } else {
+            format!(
+                "certificate expires in {} days",
+                (info.not_after - now) / 86_400
+            )
+        };
+        report.warnings.push(Issue {
+            field: "cert".to_string(),
+            plugin: None,
+            message,
+        });
+    }
+    report
+}
+
+/// Consumer plugin blocks hold credentials, not plugin configs, so only
+/// the plugin names are checked.
+pub(crate) fn consumer(state: &AdminState, consumer: &Consumer) -> Report {
+    let mut report = Report::default();
+    for name in consumer.plugins.keys() {
+        if state.plugin_registry.get(name).is_none() {
+            report.warnings.push(Issue {
+                field: format!("plugins.{name}"),
+                plugin: Some(name.clone()),
+                message: format!("unknown plugin `{name}` is ignored"),
+            });
+        }
+    }
+    report
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ValidateRequest {
+    /// `route`, `service`, `upstream`, `consumer`, `plugin_config`,
+    /// `global_rule` or `ssl`.
+    #[serde(rename = "type")]
+    kind: String,
+    value: Value,
+}
+
+/// POST /apisix/admin/validate
+///
+/// Runs the same checks as the matching PUT — including `configure()` of
+/// every plugin block — and always answers 200 with the findings.
+pub async fn validate(
+    State(state): State<Arc<AdminState>>,
+    Json(req): Json<ValidateRequest>,
+) -> (StatusCode, Json<Value>) {
+    let id_field = if req.kind == "consumer" {
+        "username"
+    } else {
+        "id"
+    };
+    let mut value = req.value;
+    // The id comes from the URL on a PUT; don't require it here.
+    if let Some(obj) = value.as_object_mut() {
+        obj.entry(id_field).or_insert_with(|| json!("validate"));
+    }
+
+    let report = match req.kind.as_str() {
+        "route" => parse::<Route>(value).map(|r| route(&state, &r, false)),
+        "service" => parse::<Service>(value).map(|s| service(&state, &s, false)),
+        "upstream" => parse::<Upstream>(value).map(|u| upstream(&u)),
+        "consumer" => parse::<Consumer>(value).map(|c| consumer(&state, &c)),
+        "plugin_config" => parse::<PluginConfig>(value).map(|p| plugin_config(&state, &p)),
+        "global_rule" => parse::<GlobalRule>(value).map(|g| global_rule(&state, &g)),
+        "ssl" => parse::<SslCertificate>(value).map(|s| ssl(&state, &s)),
+        other => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({"error": format!("unknown type `{other}`")})),
+            );
+        }
+    }
+    .unwrap_or_else(|report| report);
+
+    (StatusCode::OK, Json(report.body()))
+}
+
+fn parse<T: DeserializeOwned>(value: Value) -> Result<T, Report> {
+    serde_json::from_value(value).map_err(|e| {
+        let mut report = Report::default();
+        report.error("", e.to_string());
+        report
+    })
+}
@@ -1,3 +1,6 @@
+pub mod audit;
+pub mod auth;
 pub mod handlers;
+pub mod middleware;
 pub mod persist;
 pub mod server;
@@ -1 +1,94 @@
-// Admin API key middleware (placeholder — optional for benchmarks)
+// Admin API key middleware
+
+use crate::audit::{self, Target};
+use crate::handlers::validate::WriteParams;
+use crate::server::AdminState;
+use ando_core::config::AdminRole;
+use axum::extract::{Query, Request, State};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use serde_json::json;
+use std::sync::Arc;
+
+/// Reject writes while the config is owned by a standalone config file;
+/// they would be overwritten on its next reload.
+pub async fn read_only_guard(
+    State(state): State<Arc<AdminState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if state.read_only && !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return (
+            StatusCode::METHOD_NOT_ALLOWED,
+            Json(json!({
+                "error": "admin API is read-only: config is loaded from a standalone config file"
+            })),
+        )
+            .into_response();
+    }
+    next.run(req).await
+}
+
+fn is_read(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+fn deny(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({"error": message}))).into_response()
+}
+
+/// Check the `X-API-KEY` header and audit every write it lets through.
+///
+/// `admin` keys may do anything, `viewer` keys only read (plus
+/// `POST /validate`, which stores nothing). The dashboard's static files,
+/// the health check and CORS preflights need no key. Without any
+/// configured key the Admin API is open and records carry no key id.
+pub async fn authenticate(
+    State(state): State<Arc<AdminState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    if !path.starts_with("/apisix/admin/")
+        || path == "/apisix/admin/health"
+        || *req.method() == Method::OPTIONS
+    {
+        return next.run(req).await;
+    }
+
+    let mut key_id = None;
+    if state.auth.is_enabled() {
+        let key = req.headers().get("x-api-key").and_then(|v| v.to_str().ok());
+        let Some(principal) = key.and_then(|k| state.auth.authenticate(k)) else {
+            return deny(StatusCode::UNAUTHORIZED, "missing or invalid X-API-KEY");
+        };
+        if principal.role == AdminRole::Viewer
+            && !is_read(req.method())
+            && path != "/apisix/admin/validate"
+        {
+            return deny(StatusCode::FORBIDDEN, "API key role `viewer` is read-only");
+        }
+        key_id = Some(principal.key_id);
+    }
+
+    let dry_run = Query::<WriteParams>::try_from_uri(req.uri()).is_ok_and(|q| q.dry_run);
+    let target = Target::of(req.method(), path);
+    let (Some(sink), Some(target), false) = (state.audit.as_ref(), target, dry_run) else {
+        return next.run(req).await;
+    };
+
+    let (method, uri) = (req.method().to_string(), path.to_string());
+    let before = audit::snapshot(&state.cache, &target);
+    let resp = next.run(req).await;
+    if resp.status().is_success() {
+        let after = audit::snapshot(&state.cache, &target);
+        for mut entry in audit::entries(&target, &before, &after) {
+            entry.key_id = key_id.clone();
+            entry.method = method.clone();
+            entry.uri = uri.clone();
+            sink.write(&entry);
+        }
+    }
+    resp
+}
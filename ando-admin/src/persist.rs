@@ -1,22 +1,34 @@
 //! File-based persistence for standalone mode.
 //!
-//! On every write (PUT/DELETE route, upstream, consumer) the current in-memory
-//! state is serialized to a JSON file.  On startup the file is loaded back into
-//! the ConfigCache so data survives restarts.
+//! On every write (PUT/DELETE route, upstream, consumer, bulk import) the
+//! current in-memory state is serialized to a JSON file.  On startup the file
+//! is loaded back into the ConfigCache so data survives restarts.
 //!
 //! The file is written atomically: first to a `.tmp` sibling, then renamed
 //! over the final path, so a crash mid-write never corrupts the stored state.
 //!
 //! The implementation is a no-op when `AdminState::state_file` is `None`
 //! (e.g. in unit tests that build an `AdminState` without specifying a path).
+//!
+//! With a config store (etcd or SQL) there is no state file: writes go
+//! through to the store first, and are only applied locally once it
+//! accepted them.
 
 use crate::server::AdminState;
 use ando_core::consumer::Consumer;
+use ando_core::global_rule::GlobalRule;
+use ando_core::plugin_config::PluginConfig;
 use ando_core::route::Route;
 use ando_core::service::Service;
+use ando_core::ssl::SslCertificate;
 use ando_core::upstream::Upstream;
 use ando_store::cache::ConfigCache;
+use ando_store::document::ConfigDocument;
+use ando_store::store::{self, Kind};
+use axum::http::StatusCode;
+use axum::response::Json;
 use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::path::Path;
 
@@ -31,6 +43,54 @@ pub struct PersistedState {
     pub upstreams: HashMap<String, Upstream>,
     #[serde(default)]
     pub consumers: HashMap<String, Consumer>,
+    #[serde(default)]
+    pub plugin_configs: HashMap<String, PluginConfig>,
+    #[serde(default)]
+    pub ssls: HashMap<String, SslCertificate>,
+    #[serde(default)]
+    pub global_rules: HashMap<String, GlobalRule>,
+}
+
+/// Answer for a write the config store refused.
+pub type StoreError = (StatusCode, Json<Value>);
+
+fn unavailable(e: anyhow::Error) -> StoreError {
+    tracing::error!(error = %format!("{e:#}"), "Config store write failed");
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(json!({"error": format!("config store unavailable: {e}")})),
+    )
+}
+
+/// Write `object` through to `state.store`. No-op without a store.
+pub async fn store_put<T: Serialize>(
+    state: &AdminState,
+    kind: Kind,
+    id: &str,
+    object: &T,
+) -> Result<(), StoreError> {
+    match state.store {
+        Some(ref s) => store::put_object(s.as_ref(), kind, id, object)
+            .await
+            .map_err(unavailable),
+        None => Ok(()),
+    }
+}
+
+/// Delete an object from `state.store`. No-op without a store.
+pub async fn store_delete(state: &AdminState, kind: Kind, id: &str) -> Result<(), StoreError> {
+    match state.store {
+        Some(ref s) => s.delete(kind, id).await.map_err(unavailable),
+        None => Ok(()),
+    }
+}
+
+/// Make `state.store` hold exactly `doc`. No-op without a store.
+pub async fn store_replace(state: &AdminState, doc: &ConfigDocument) -> Result<(), StoreError> {
+    match state.store {
+        Some(ref s) => store::replace(s.as_ref(), doc).await.map_err(unavailable),
+        None => Ok(()),
+    }
 }
 
 /// Save the current `ConfigCache` contents to `state.state_file`.
@@ -69,6 +129,24 @@ pub fn save_state(state: &AdminState) {
             .iter()
             .map(|e| (e.key().clone(), e.value().clone()))
             .collect(),
+        plugin_configs: state
+            .cache
+            .plugin_configs
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect(),
+        ssls: state
+            .cache
+            .ssl_certs
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect(),
+        global_rules: state
+            .cache
+            .global_rules
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect(),
     };
 
     // Serialize
@@ -147,7 +225,16 @@ pub fn load_state(path: &Path, cache: &ConfigCache) {
     for (k, v) in persisted.consumers {
         cache.consumers.insert(k, v);
     }
-    cache.rebuild_consumer_key_index();
+    for (k, v) in persisted.plugin_configs {
+        cache.plugin_configs.insert(k, v);
+    }
+    for (k, v) in persisted.ssls {
+        cache.ssl_certs.insert(k, v);
+    }
+    for (k, v) in persisted.global_rules {
+        cache.global_rules.insert(k, v);
+    }
+    cache.reindex_consumers();
 
     tracing::info!(
         routes = routes_count,
@@ -216,6 +303,9 @@ mod tests {
                 .map(|e| (e.key().clone(), e.value().clone()))
                 .collect(),
             consumers: Default::default(),
+            plugin_configs: Default::default(),
+            ssls: Default::default(),
+            global_rules: Default::default(),
         };
         let json = serde_json::to_string_pretty(&persisted).unwrap();
         std::fs::write(&path, &json).unwrap();
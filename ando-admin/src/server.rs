@@ -1,20 +1,30 @@
+use crate::audit::AuditSink;
+use crate::auth::AdminAuth;
 use crate::handlers;
-use ando_core::config::AdminConfig;
+use ando_core::config::{AdminConfig, ListenerConfig, SslConfig};
 use ando_core::router::Router;
+use ando_core::shutdown::Shutdown;
+use ando_observability::metrics::MetricsCollector;
+use ando_observability::worker_stats::WorkerStats;
 use ando_plugin::registry::PluginRegistry;
 use ando_store::cache::ConfigCache;
+use ando_store::store::ConfigStore;
 use arc_swap::ArcSwap;
 use axum::{
     Router as AxumRouter,
-    routing::{delete, get, put},
+    routing::{delete, get, post, put},
 };
 use http::Method;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Notify;
 use tower_http::cors::{Any, CorsLayer};
 use tracing::info;
 
+/// How often a bind of an address in use is retried.
+const ADMIN_BIND_RETRY: Duration = Duration::from_millis(100);
+
 /// Shared state for the admin API.
 pub struct AdminState {
     pub cache: ConfigCache,
@@ -27,6 +37,34 @@ pub struct AdminState {
     pub state_file: Option<PathBuf>,
     /// "community" or "enterprise" — controls plugin visibility in the dashboard.
     pub edition: &'static str,
+    /// Reject PUT/DELETE: set when a standalone config file owns the config.
+    pub read_only: bool,
+    /// Accepted `X-API-KEY` values; empty leaves the Admin API open.
+    pub auth: AdminAuth,
+    /// Destination of config change audit records. `None` when
+    /// `compliance.audit_log` is disabled.
+    pub audit: Option<AuditSink>,
+    /// Data plane metrics served on `/metrics`.
+    pub metrics: Option<Arc<MetricsCollector>>,
+    /// Per-worker accept/connection/request counters of the data plane.
+    /// `None` when the Admin API runs without workers.
+    pub workers: Option<Arc<WorkerStats>>,
+    /// When the gateway process started, for the reported uptime.
+    pub started: Instant,
+    /// Graceful shutdown of the data plane, reported as `draining`.
+    /// `None` when the Admin API runs without workers.
+    pub shutdown: Option<Arc<Shutdown>>,
+    /// The `proxy.listener` options the data plane's sockets use.
+    /// `None` when the Admin API runs without workers.
+    pub listener: Option<ListenerConfig>,
+    /// Names of the data plane's listeners, which route `listeners` must
+    /// be among. `None` accepts any name.
+    pub listeners: Option<Vec<String>>,
+    /// Master key for SSL private keys and the expiry warning threshold.
+    pub ssl: SslConfig,
+    /// etcd or SQL config store that writes go through to. `None` keeps
+    /// config in memory (and `state_file`).
+    pub store: Option<Arc<dyn ConfigStore>>,
 }
 
 /// Start the admin API server on a dedicated tokio runtime.
@@ -36,9 +74,29 @@ pub struct AdminState {
 /// are applied to the shared ConfigCache + ArcSwap<Router> and worker
 /// cores pick them up via atomic loads on the next accept iteration.
 pub async fn start_admin(config: AdminConfig, state: Arc<AdminState>) -> anyhow::Result<()> {
+    start_admin_when_free(config, state, Duration::ZERO).await
+}
+
+/// `start_admin`, retrying the bind for up to `wait` while the address is
+/// in use: after a binary upgrade the previous process serves the Admin
+/// API until it has drained.
+pub async fn start_admin_when_free(
+    config: AdminConfig,
+    state: Arc<AdminState>,
+    wait: Duration,
+) -> anyhow::Result<()> {
     let app = build_admin_router(state);
 
-    let listener = tokio::net::TcpListener::bind(&config.addr).await?;
+    let deadline = Instant::now() + wait;
+    let listener = loop {
+        match tokio::net::TcpListener::bind(&config.addr).await {
+            Ok(listener) => break listener,
+            Err(e) if e.kind() == std::io::ErrorKind::AddrInUse && Instant::now() < deadline => {
+                tokio::time::sleep(ADMIN_BIND_RETRY).await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    };
     info!(addr = %config.addr, "Admin API listening");
 
     axum::serve(listener, app).await?;
@@ -85,6 +143,14 @@ pub fn build_admin_router(state: Arc<AdminState>) -> AxumRouter {
             "/apisix/admin/upstreams",
             get(handlers::upstreams::list_upstreams),
         )
+        .route(
+            "/apisix/admin/upstreams/health",
+            get(handlers::upstreams::list_upstream_health),
+        )
+        .route(
+            "/apisix/admin/upstreams/{id}/health",
+            get(handlers::upstreams::get_upstream_health),
+        )
         .route(
             "/apisix/admin/consumers/{username}",
             put(handlers::consumers::put_consumer),
@@ -117,15 +183,77 @@ pub fn build_admin_router(state: Arc<AdminState>) -> AxumRouter {
             "/apisix/admin/services",
             get(handlers::services::list_services),
         )
+        .route(
+            "/apisix/admin/global_rules/{id}",
+            put(handlers::global_rules::put_global_rule),
+        )
+        .route(
+            "/apisix/admin/global_rules/{id}",
+            get(handlers::global_rules::get_global_rule),
+        )
+        .route(
+            "/apisix/admin/global_rules/{id}",
+            delete(handlers::global_rules::delete_global_rule),
+        )
+        .route(
+            "/apisix/admin/global_rules",
+            get(handlers::global_rules::list_global_rules),
+        )
+        .route("/apisix/admin/ssls/{id}", put(handlers::ssls::put_ssl))
+        .route("/apisix/admin/ssls/{id}", get(handlers::ssls::get_ssl))
+        .route(
+            "/apisix/admin/ssls/{id}",
+            delete(handlers::ssls::delete_ssl),
+        )
+        .route("/apisix/admin/ssls", get(handlers::ssls::list_ssls))
+        .route("/apisix/admin/export", get(handlers::bulk::export_config))
+        .route("/apisix/admin/import", post(handlers::bulk::import_config))
+        .route(
+            "/apisix/admin/import/openapi",
+            post(handlers::openapi::import_openapi),
+        )
+        .route("/apisix/admin/validate", post(handlers::validate::validate))
         .route("/apisix/admin/health", get(handlers::health::health_check))
+        .route(
+            "/apisix/admin/health/config",
+            get(handlers::health::config_health),
+        )
+        .route("/metrics", get(handlers::metrics::prometheus_metrics))
+        .route(
+            "/apisix/admin/plugins",
+            get(handlers::plugins::registered_plugins),
+        )
         .route(
             "/apisix/admin/plugins/list",
             get(handlers::plugins::list_plugins),
         )
+        .route(
+            "/apisix/admin/plugins/stats",
+            get(handlers::plugins::plugin_stats),
+        )
+        .route(
+            "/apisix/admin/workers/stats",
+            get(handlers::metrics::worker_stats),
+        )
+        .route("/apisix/admin/status", get(handlers::metrics::status))
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::clone(&state),
+            crate::middleware::read_only_guard,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::clone(&state),
+            crate::middleware::authenticate,
+        ))
         .layer(
             CorsLayer::new()
                 .allow_origin(Any)
-                .allow_methods([Method::GET, Method::PUT, Method::DELETE, Method::OPTIONS])
+                .allow_methods([
+                    Method::GET,
+                    Method::POST,
+                    Method::PUT,
+                    Method::DELETE,
+                    Method::OPTIONS,
+                ])
                 .allow_headers(Any),
         )
         .with_state(state)
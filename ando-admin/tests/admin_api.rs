@@ -3,10 +3,15 @@
 //! Uses `tower::ServiceExt::oneshot` to call handlers without binding a real
 //! TCP port — every test gets a fresh in-memory state.
 
+use ando_admin::audit::AuditSink;
+use ando_admin::auth::AdminAuth;
 use ando_admin::server::{AdminState, build_admin_router};
+use ando_core::config::{AdminApiKey, AdminConfig, AdminRole, AuditLogConfig, SslConfig};
 use ando_core::router::Router;
 use ando_plugin::registry::PluginRegistry;
 use ando_store::cache::ConfigCache;
+use ando_store::sql::SqlStore;
+use ando_store::store::{ConfigStore, Kind};
 use arc_swap::ArcSwap;
 use axum::body::{Body, to_bytes};
 use axum::http::{Method, Request, StatusCode};
@@ -17,15 +22,30 @@ use tower::ServiceExt; // .oneshot()
 // ── Helper ────────────────────────────────────────────────────
 
 fn make_state() -> Arc<AdminState> {
+    make_state_with_registry(PluginRegistry::new())
+}
+
+fn make_state_with_registry(plugin_registry: PluginRegistry) -> Arc<AdminState> {
     let cache = ConfigCache::new();
     let initial_router = Router::build(vec![], 1).unwrap();
     Arc::new(AdminState {
         cache,
         router_swap: Arc::new(ArcSwap::new(Arc::new(initial_router))),
-        plugin_registry: Arc::new(PluginRegistry::new()),
+        plugin_registry: Arc::new(plugin_registry),
         config_changed: Arc::new(Notify::new()),
         state_file: None, // tests run in-memory, no disk I/O
         edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: None,
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
     })
 }
 
@@ -87,11 +107,173 @@ async fn put_route_creates_and_returns_200() {
     assert_eq!(j["id"], "r1");
 }
 
+#[tokio::test]
+async fn put_route_accepts_upstream_less_route_with_mock_plugin() {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let app = build_admin_router(make_state_with_registry(registry));
+    let body = serde_json::json!({
+        "uri": "/maintenance",
+        "plugins": { "mock": { "status": 503, "body": { "error": "maintenance" } } }
+    });
+    let resp = app
+        .oneshot(json_put("/apisix/admin/routes/r1", body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn put_route_rejects_upstream_less_route_without_terminal_plugin() {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let state = make_state_with_registry(registry);
+    for body in [
+        serde_json::json!({ "uri": "/a" }),
+        serde_json::json!({ "uri": "/a", "plugins": { "cors": {} } }),
+    ] {
+        let (status, j) = send(&state, json_put("/apisix/admin/routes/r1", body)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{j}");
+        assert_eq!(j["errors"][0]["field"], "upstream");
+    }
+    assert!(state.cache.routes.is_empty());
+
+    // The terminal plugin may come from the route's plugin config.
+    let config = serde_json::json!({ "id": "pc1", "plugins": { "mock": { "status": 200 } } });
+    state
+        .cache
+        .plugin_configs
+        .insert("pc1".into(), serde_json::from_value(config).unwrap());
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "uri": "/a", "plugin_config_id": "pc1" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+}
+
+#[tokio::test]
+async fn put_route_rejects_invalid_plugin_config() {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let app = build_admin_router(make_state_with_registry(registry));
+    let body = serde_json::json!({
+        "uri": "/maintenance",
+        "plugins": { "mock": { "status": 42 } }
+    });
+    let resp = app
+        .oneshot(json_put("/apisix/admin/routes/r1", body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let j = body_json(resp).await;
+    assert!(j["error"].as_str().unwrap().contains("`mock`"));
+}
+
+#[tokio::test]
+async fn put_route_rejects_invalid_json_schema() {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let app = build_admin_router(make_state_with_registry(registry));
+    let body = serde_json::json!({
+        "uri": "/users",
+        "plugins": { "request-validation": { "body_schema": { "type": "no-such-type" } } },
+        "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+    });
+    let resp = app
+        .oneshot(json_put("/apisix/admin/routes/r1", body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let j = body_json(resp).await;
+    assert!(j["error"].as_str().unwrap().contains("body_schema"));
+}
+
+#[tokio::test]
+async fn put_route_rejects_invalid_match_conditions() {
+    let app = build_admin_router(make_state());
+    let body = serde_json::json!({
+        "uri": "/test",
+        "remote_addrs": ["10.0.0.0/8"],
+        "vars": [["http_x_env", "??", "staging"]]
+    });
+    let resp = app
+        .oneshot(json_put("/apisix/admin/routes/r1", body))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let j = body_json(resp).await;
+    assert!(j["error"].as_str().unwrap().contains("??"));
+}
+
+#[tokio::test]
+async fn put_route_rejects_unknown_listeners() {
+    let mut state = Arc::into_inner(make_state()).unwrap();
+    state.listeners = Some(vec!["public".into(), "internal".into()]);
+    let app = build_admin_router(Arc::new(state));
+    let route = |listeners: serde_json::Value| {
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({
+                "uri": "/test",
+                "listeners": listeners,
+                "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+            }),
+        )
+    };
+
+    let resp = app
+        .clone()
+        .oneshot(route(serde_json::json!(["public", "extranet"])))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let j = body_json(resp).await;
+    assert_eq!(j["error"], "unknown listener `extranet`");
+    assert_eq!(j["errors"][0]["field"], "listeners");
+
+    let resp = app
+        .oneshot(route(serde_json::json!(["internal"])))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn put_route_requires_uri_or_uris() {
+    let app = build_admin_router(make_state());
+    let resp = app
+        .clone()
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "status": 1 }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = app
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "uris": ["/a", "/b/*"], "upstream": { "nodes": { "127.0.0.1:8080": 1 } } }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
 #[tokio::test]
 async fn get_route_returns_route_after_put() {
     let state = make_state();
     let app1 = build_admin_router(Arc::clone(&state));
-    let body = serde_json::json!({ "uri": "/hello", "status": 1 });
+    let body = serde_json::json!({
+        "uri": "/hello",
+        "status": 1,
+        "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+    });
     app1.oneshot(json_put("/apisix/admin/routes/r-hello", body))
         .await
         .unwrap();
@@ -179,7 +361,7 @@ async fn list_routes_returns_all_inserted() {
         let app = build_admin_router(Arc::clone(&state));
         app.oneshot(json_put(
             &format!("/apisix/admin/routes/{id}"),
-            serde_json::json!({ "uri": format!("/{id}"), "status": 1 }),
+            serde_json::json!({ "uri": format!("/{id}"), "status": 1, "upstream": { "nodes": { "127.0.0.1:8080": 1 } } }),
         ))
         .await
         .unwrap();
@@ -206,6 +388,27 @@ async fn put_upstream_creates_and_returns_200() {
     assert_eq!(resp.status(), StatusCode::OK);
 }
 
+#[tokio::test]
+async fn put_upstream_validates_discovery_service_name() {
+    let state = make_state();
+    let upstream = serde_json::json!({
+        "discovery_type": "kubernetes",
+        "service_name": "checkout"
+    });
+    let (status, j) = send(&state, json_put("/apisix/admin/upstreams/u1", upstream)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"][0]["field"], "service_name");
+    assert!(state.cache.upstreams.is_empty());
+
+    let upstream = serde_json::json!({
+        "discovery_type": "kubernetes",
+        "service_name": "shop/checkout",
+        "port_name": "http"
+    });
+    let (status, _) = send(&state, json_put("/apisix/admin/upstreams/u1", upstream)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
 #[tokio::test]
 async fn get_upstream_returns_404_when_missing() {
     let app = build_admin_router(make_state());
@@ -263,6 +466,193 @@ async fn list_upstreams_total_reflects_inserts() {
     assert_eq!(j["total"], 2);
 }
 
+#[tokio::test]
+async fn upstream_health_reports_ejected_nodes() {
+    let state = make_state();
+    let app = build_admin_router(Arc::clone(&state));
+    app.oneshot(json_put(
+        "/apisix/admin/upstreams/u1",
+        serde_json::json!({
+            "nodes": { "10.0.0.1:80": 1, "10.0.0.2:80": 1 },
+            "checks": { "active": { "http_path": "/healthz" } }
+        }),
+    ))
+    .await
+    .unwrap();
+    state
+        .cache
+        .health
+        .record("u1", "10.0.0.2:80", Err("refused".into()), 2, 1, 42);
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(get_req("/apisix/admin/upstreams/u1/health"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["checks_enabled"], true);
+    assert_eq!(j["ejected"], 1);
+    assert_eq!(j["nodes"][0]["node"], "10.0.0.1:80");
+    assert_eq!(j["nodes"][0]["healthy"], true);
+    assert_eq!(j["nodes"][1]["healthy"], false);
+    assert_eq!(j["nodes"][1]["last_error"], "refused");
+    assert_eq!(j["nodes"][1]["breaker_open"], false);
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(get_req("/apisix/admin/upstreams/health"))
+        .await
+        .unwrap();
+    let j = body_json(resp).await;
+    assert_eq!(j["total"], 1);
+    assert_eq!(j["list"][0]["id"], "u1");
+}
+
+#[tokio::test]
+async fn upstream_health_returns_404_when_missing() {
+    let app = build_admin_router(make_state());
+    let resp = app
+        .oneshot(get_req("/apisix/admin/upstreams/no-such/health"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+// ── Referential integrity ─────────────────────────────────────
+
+async fn send(state: &Arc<AdminState>, req: Request<Body>) -> (StatusCode, serde_json::Value) {
+    let resp = build_admin_router(Arc::clone(state))
+        .oneshot(req)
+        .await
+        .unwrap();
+    let status = resp.status();
+    (status, body_json(resp).await)
+}
+
+#[tokio::test]
+async fn put_route_rejects_dangling_references_unless_forced() {
+    let state = make_state();
+    let route = serde_json::json!({ "uri": "/a", "upstream_id": "ups-9" });
+
+    let (status, j) = send(&state, json_put("/apisix/admin/routes/r1", route.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["error"], "unknown upstream `ups-9`");
+    assert_eq!(j["errors"][0]["field"], "upstream_id");
+    assert!(state.cache.routes.is_empty());
+
+    let (status, _) = send(
+        &state,
+        json_put("/apisix/admin/routes/r1?force=true", route),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, j) = send(&state, get_req("/apisix/admin/health/config")).await;
+    assert_eq!(j["status"], "degraded");
+    assert_eq!(
+        j["broken_references"][0]["message"],
+        "route `r1`: unknown upstream `ups-9`"
+    );
+}
+
+#[tokio::test]
+async fn delete_upstream_still_referenced_is_rejected_and_reported() {
+    let state = make_state();
+    let upstream = serde_json::json!({ "nodes": { "127.0.0.1:8080": 1 } });
+    send(&state, json_put("/apisix/admin/upstreams/u1", upstream)).await;
+    send(
+        &state,
+        json_put(
+            "/apisix/admin/services/s1",
+            serde_json::json!({ "upstream_id": "u1" }),
+        ),
+    )
+    .await;
+    let (status, _) = send(
+        &state,
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "uri": "/a", "upstream_id": "u1" }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, j) = send(&state, get_req("/apisix/admin/health/config")).await;
+    assert_eq!(j["status"], "ok");
+    assert_eq!(j["total"], 0);
+
+    let (status, j) = send(&state, delete_req("/apisix/admin/upstreams/u1")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let messages: Vec<&str> = j["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "upstream `u1` is still used by route `r1`",
+            "upstream `u1` is still used by service `s1`",
+        ]
+    );
+    assert!(state.cache.upstreams.contains_key("u1"));
+
+    let (status, _) = send(&state, delete_req("/apisix/admin/upstreams/u1?force=true")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, j) = send(&state, get_req("/apisix/admin/health/config")).await;
+    assert_eq!(j["status"], "degraded");
+    assert_eq!(j["total"], 2);
+    assert_eq!(j["broken_references"][0]["kind"], "route");
+    assert_eq!(j["broken_references"][0]["target"], "upstream");
+    assert_eq!(j["broken_references"][0]["missing"], "u1");
+    assert_eq!(
+        j["broken_references"][1]["message"],
+        "service `s1`: unknown upstream `u1`"
+    );
+}
+
+#[tokio::test]
+async fn delete_service_still_referenced_is_rejected() {
+    let state = make_state();
+    send(
+        &state,
+        json_put("/apisix/admin/services/s1", serde_json::json!({})),
+    )
+    .await;
+    send(
+        &state,
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "uri": "/a", "service_id": "s1" }),
+        ),
+    )
+    .await;
+
+    let (status, j) = send(&state, delete_req("/apisix/admin/services/s1")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["error"], "service `s1` is still used by route `r1`");
+
+    send(&state, delete_req("/apisix/admin/routes/r1")).await;
+    let (status, _) = send(&state, delete_req("/apisix/admin/services/s1")).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn config_health_reports_unknown_plugins() {
+    let state = make_state();
+    insert_route(
+        &state,
+        serde_json::json!({"id": "r1", "uri": "/a", "plugins": {"ee-only-plugin": {}}}),
+    );
+    let (status, j) = send(&state, get_req("/apisix/admin/health/config")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        j["broken_references"][0]["message"],
+        "route `r1`: unknown plugin `ee-only-plugin`"
+    );
+}
+
 // ── Consumers ─────────────────────────────────────────────────
 
 #[tokio::test]
@@ -359,3 +749,1760 @@ async fn plugins_list_returns_ok() {
         .unwrap();
     assert_eq!(resp.status(), StatusCode::OK);
 }
+
+#[tokio::test]
+async fn registered_plugins_lists_priorities_and_phases_in_run_order() {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let total = registry.list().len();
+    let app = build_admin_router(make_state_with_registry(registry));
+    let resp = app.oneshot(get_req("/apisix/admin/plugins")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["total"], total);
+    let plugins = j["plugins"].as_array().unwrap();
+    let priorities: Vec<i64> = plugins
+        .iter()
+        .map(|p| p["priority"].as_i64().unwrap())
+        .collect();
+    assert!(priorities.windows(2).all(|w| w[0] >= w[1]));
+    let cors = plugins.iter().find(|p| p["name"] == "cors").unwrap();
+    assert_eq!(cors["priority"], 2000);
+    assert_eq!(cors["phases"], serde_json::json!(["access"]));
+}
+
+// ── Read-only (standalone config file) ────────────────────────
+
+#[tokio::test]
+async fn read_only_state_rejects_writes_but_serves_reads() {
+    let cache = ConfigCache::new();
+    let state = Arc::new(AdminState {
+        cache,
+        router_swap: Arc::new(ArcSwap::new(Arc::new(Router::build(vec![], 1).unwrap()))),
+        plugin_registry: Arc::new(PluginRegistry::new()),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: true,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: None,
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({"uri": "/ro"}),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(state.cache.routes.is_empty());
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(delete_req("/apisix/admin/routes/r1"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    let app = build_admin_router(state);
+    let resp = app.oneshot(get_req("/apisix/admin/routes")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ── Bulk import / export ──────────────────────────────────────
+
+fn json_post(uri: &str, body: serde_json::Value) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap()
+}
+
+async fn import(
+    state: &Arc<AdminState>,
+    mode: &str,
+    body: serde_json::Value,
+) -> (StatusCode, serde_json::Value) {
+    let app = build_admin_router(Arc::clone(state));
+    let resp = app
+        .oneshot(json_post(
+            &format!("/apisix/admin/import?mode={mode}"),
+            body,
+        ))
+        .await
+        .unwrap();
+    let status = resp.status();
+    (status, body_json(resp).await)
+}
+
+fn seed_document() -> serde_json::Value {
+    serde_json::json!({
+        "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+        "routes": [
+            { "id": "r1", "uri": "/one", "upstream_id": "u1" },
+            { "id": "r2", "uri": "/two", "upstream_id": "u1" }
+        ]
+    })
+}
+
+#[tokio::test]
+async fn import_rejects_dangling_references_and_writes_nothing() {
+    let state = make_state();
+    let (status, j) = import(
+        &state,
+        "merge",
+        serde_json::json!({
+            "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+            "routes": [
+                { "id": "r1", "uri": "/one", "upstream_id": "u1" },
+                { "id": "r2", "uri": "/two", "upstream_id": "u404" },
+                { "id": "r3", "uri": "/three", "service_id": "s404" }
+            ]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(
+        j["errors"],
+        serde_json::json!([
+            "route `r2`: unknown upstream `u404`",
+            "route `r3`: unknown service `s404`"
+        ])
+    );
+    assert!(state.cache.routes.is_empty());
+    assert!(state.cache.upstreams.is_empty());
+    assert_eq!(state.router_swap.load().version(), 1);
+}
+
+#[tokio::test]
+async fn import_merge_keeps_and_replace_deletes_missing_objects() {
+    let state = make_state();
+    let (status, j) = import(&state, "replace", seed_document()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["summary"]["routes"]["created"], 2);
+    assert_eq!(j["summary"]["upstreams"]["created"], 1);
+
+    let update = serde_json::json!({
+        "routes": [
+            { "id": "r1", "uri": "/one-v2", "upstream_id": "u1" },
+            { "id": "r3", "uri": "/three", "upstream_id": "u1" }
+        ]
+    });
+    // Merge: r2 and u1 stay; u1 resolves from the existing config.
+    let (status, j) = import(&state, "merge", update.clone()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        j["summary"]["routes"],
+        serde_json::json!({"created": 1, "updated": 1, "deleted": 0})
+    );
+    assert_eq!(state.cache.routes.len(), 3);
+    assert!(state.router_swap.load().get_route("r3").is_some());
+
+    // Replace: the update alone would leave r1/r3 pointing at a deleted u1.
+    let (status, _) = import(&state, "replace", update).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(state.cache.routes.len(), 3);
+
+    let (status, j) = import(
+        &state,
+        "replace",
+        serde_json::json!({
+            "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+            "routes": [{ "id": "r1", "uri": "/one-v2", "upstream_id": "u1" }]
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        j["summary"]["routes"],
+        serde_json::json!({"created": 0, "updated": 0, "deleted": 2})
+    );
+    assert_eq!(state.cache.routes.len(), 1);
+    assert!(state.router_swap.load().get_route("r2").is_none());
+}
+
+#[tokio::test]
+async fn export_round_trips_through_import() {
+    let state = make_state();
+    import(&state, "replace", seed_document()).await;
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app.oneshot(get_req("/apisix/admin/export")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let exported = body_json(resp).await;
+    assert_eq!(exported["routes"][0]["id"], "r1");
+    assert_eq!(exported["routes"][1]["id"], "r2");
+
+    let fresh = make_state();
+    let (status, j) = import(&fresh, "replace", exported).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["summary"]["routes"]["created"], 2);
+    assert_eq!(fresh.cache.routes.len(), 2);
+}
+
+// ── OpenAPI import ────────────────────────────────────────────
+
+const PETSTORE: &str = r#"
+openapi: 3.0.0
+info:
+  title: Swagger Petstore
+  version: 1.0.0
+servers:
+  - url: http://petstore.swagger.io/v1
+paths:
+  /pets:
+    get:
+      summary: List all pets
+      operationId: listPets
+      parameters:
+        - name: limit
+          in: query
+          required: false
+          schema:
+            type: integer
+            format: int32
+      responses:
+        200:
+          description: A paged array of pets
+    post:
+      summary: Create a pet
+      operationId: createPets
+      requestBody:
+        required: true
+        content:
+          application/json:
+            schema:
+              $ref: '#/components/schemas/Pet'
+      responses:
+        '201':
+          description: Null response
+  /pets/{petId}:
+    get:
+      summary: Info for a specific pet
+      operationId: showPetById
+      parameters:
+        - $ref: '#/components/parameters/PetId'
+      responses:
+        '200':
+          description: Expected response to a valid request
+components:
+  parameters:
+    PetId:
+      name: petId
+      in: path
+      required: true
+      schema:
+        type: string
+  schemas:
+    Pet:
+      type: object
+      required: [id, name]
+      properties:
+        id:
+          type: integer
+          format: int64
+        name:
+          type: string
+        tag:
+          type: string
+"#;
+
+async fn import_openapi(
+    state: &Arc<AdminState>,
+    content_type: &str,
+    body: String,
+) -> (StatusCode, serde_json::Value) {
+    let app = build_admin_router(Arc::clone(state));
+    let resp = app
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri("/apisix/admin/import/openapi")
+                .header("content-type", content_type)
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = resp.status();
+    (status, body_json(resp).await)
+}
+
+/// A YAML import body wrapping the petstore spec.
+fn petstore_import(options: &str) -> String {
+    let spec: String = PETSTORE.lines().map(|l| format!("  {l}\n")).collect();
+    format!("upstream:\n  nodes:\n    \"127.0.0.1:8080\": 1\n{options}spec:\n{spec}")
+}
+
+#[tokio::test]
+async fn openapi_import_generates_a_route_per_operation() {
+    let state = full_state();
+    let body = petstore_import("route_prefix: /petstore\nrequest_validation: true\n");
+    let (status, j) = import_openapi(&state, "application/yaml", body.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+    let mut created: Vec<&str> = j["created"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| v.as_str().unwrap())
+        .collect();
+    created.sort();
+    assert_eq!(created, ["createPets", "listPets", "showPetById"]);
+    assert_eq!(j["warnings"], serde_json::json!([]));
+
+    let show = state.cache.routes.get("showPetById").unwrap().clone();
+    assert_eq!(show.uri, "/petstore/v1/pets/{petId}");
+    assert_eq!(show.methods, ["GET"]);
+    assert_eq!(show.name.as_deref(), Some("showPetById"));
+    assert_eq!(show.desc.as_deref(), Some("Info for a specific pet"));
+    assert!(show.upstream.is_some());
+    // Path parameters are matched by the route, nothing is left to check.
+    assert!(!show.plugins.contains_key("request-validation"));
+
+    let list = state.cache.routes.get("listPets").unwrap().clone();
+    let query = &list.plugins["request-validation"]["querystring_schema"];
+    assert_eq!(query["properties"]["limit"]["pattern"], "^-?[0-9]+$");
+    assert!(query.get("required").is_none());
+
+    let create = state.cache.routes.get("createPets").unwrap().clone();
+    assert_eq!(create.methods, ["POST"]);
+    let body_schema = &create.plugins["request-validation"]["body_schema"];
+    assert_eq!(body_schema["allOf"][0]["$ref"], "#/components/schemas/Pet");
+    assert_eq!(
+        body_schema["components"]["schemas"]["Pet"]["required"],
+        serde_json::json!(["id", "name"])
+    );
+
+    let router = state.router_swap.load();
+    assert!(router.get_route("listPets").is_some());
+    assert!(router.get_route("createPets").is_some());
+
+    // Importing the same spec again changes nothing...
+    let (status, j) = import_openapi(&state, "application/yaml", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["created"], serde_json::json!([]));
+    assert_eq!(j["updated"], serde_json::json!([]));
+    assert_eq!(j["unchanged"].as_array().unwrap().len(), 3);
+
+    // ...and an edited import updates the same routes in place.
+    let body = petstore_import("route_prefix: /petstore\nstrip_base_path: true\n");
+    let (status, j) = import_openapi(&state, "application/yaml", body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["updated"].as_array().unwrap().len(), 3);
+    assert_eq!(state.cache.routes.len(), 3);
+    assert_eq!(
+        state.cache.routes.get("showPetById").unwrap().uri,
+        "/petstore/pets/{petId}"
+    );
+}
+
+#[tokio::test]
+async fn openapi_import_warns_about_unsupported_constructs() {
+    let state = make_state();
+    state.cache.upstreams.insert(
+        "u1".into(),
+        serde_json::from_value(serde_json::json!({"id": "u1", "nodes": {"127.0.0.1:8080": 1}}))
+            .unwrap(),
+    );
+    let spec = serde_json::json!({
+        "openapi": "3.1.0",
+        "info": {"title": "t", "version": "1"},
+        "servers": [{"url": "https://{region}.example.com/api"}],
+        "paths": {
+            "/items/{id}": {
+                "parameters": [{"name": "id", "in": "path", "required": true}],
+                "delete": {},
+                "patch": {"operationId": "patch item"}
+            },
+            "/files/{name}.json": {"get": {}}
+        }
+    });
+    let body = serde_json::json!({
+        "upstream_id": "u1",
+        "plugins": {"limit-count": {"count": 10, "time_window": 60}},
+        "spec": spec.to_string(),
+    });
+    let (status, j) = import_openapi(&state, "application/json", body.to_string()).await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+    assert_eq!(
+        j["warnings"],
+        serde_json::json!([
+            "server `https://{region}.example.com/api`: server variables are not supported, its base path is not applied",
+            "path `/files/{name}.json`: only whole-segment `{param}` templates are supported, skipped",
+            "path `/items/{id}`: path-level parameters are not supported and not validated"
+        ])
+    );
+
+    let patch = state.cache.routes.get("patch-item").unwrap().clone();
+    assert_eq!(patch.uri, "/items/{id}");
+    assert_eq!(patch.upstream_id.as_deref(), Some("u1"));
+    assert!(patch.plugins.contains_key("limit-count"));
+
+    // Without an operationId the id hashes the method and path, so it is
+    // stable across imports.
+    let deleted: Vec<String> = state
+        .cache
+        .routes
+        .iter()
+        .map(|r| r.key().clone())
+        .filter(|id| id.starts_with("delete-"))
+        .collect();
+    assert_eq!(deleted.len(), 1);
+    let (_, j) = import_openapi(&state, "application/json", body.to_string()).await;
+    assert_eq!(j["unchanged"].as_array().unwrap().len(), 2);
+    assert!(state.cache.routes.contains_key(&deleted[0]));
+}
+
+#[tokio::test]
+async fn openapi_import_rejects_bad_input_and_writes_nothing() {
+    let state = make_state();
+    let cases = [
+        (
+            serde_json::json!({"spec": {"swagger": "2.0", "paths": {}}, "upstream_id": "u1"}),
+            "`spec` is not an OpenAPI 3 document",
+        ),
+        (
+            serde_json::json!({"spec": {"openapi": "3.0.3", "paths": {"/a": {"get": {}}}}}),
+            "requires `upstream_id` or `upstream`",
+        ),
+    ];
+    for (body, error) in cases {
+        let (status, j) = import_openapi(&state, "application/json", body.to_string()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(j["error"], error);
+    }
+
+    // Routes referencing a missing upstream fail as a whole.
+    let body = serde_json::json!({
+        "spec": {"openapi": "3.0.3", "paths": {"/a": {"get": {}}, "/b": {"get": {}}}},
+        "upstream_id": "u404",
+    });
+    let (status, j) = import_openapi(&state, "application/json", body.to_string()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"].as_array().unwrap().len(), 2);
+    assert!(state.cache.routes.is_empty());
+    assert_eq!(state.router_swap.load().version(), 1);
+}
+
+// ── Validation / dry run ──────────────────────────────────────
+
+fn full_state() -> Arc<AdminState> {
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    make_state_with_registry(registry)
+}
+
+#[tokio::test]
+async fn validate_reports_plugin_config_errors_by_plugin() {
+    let app = build_admin_router(full_state());
+    let resp = app
+        .oneshot(json_post(
+            "/apisix/admin/validate",
+            serde_json::json!({
+                "type": "route",
+                "value": {
+                    "uri": "/api/*",
+                    "plugins": {
+                        "cors": { "allow_origins": "not-an-array" },
+                        "ee-only-plugin": {}
+                    },
+                    "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+                }
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["valid"], false);
+    assert_eq!(j["errors"][0]["plugin"], "cors");
+    assert_eq!(j["errors"][0]["field"], "plugins.cors");
+    assert!(
+        j["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("invalid type")
+    );
+    assert_eq!(j["warnings"][0]["plugin"], "ee-only-plugin");
+}
+
+#[tokio::test]
+async fn validate_rejects_meta_priority_outside_i32() {
+    let state = full_state();
+    for (priority, valid) in [
+        (serde_json::json!(3000), true),
+        (serde_json::json!(-3000), true),
+        (serde_json::json!(1u64 << 31), false),
+        (serde_json::json!("high"), false),
+    ] {
+        let app = build_admin_router(Arc::clone(&state));
+        let resp = app
+            .oneshot(json_post(
+                "/apisix/admin/validate",
+                serde_json::json!({
+                    "type": "route",
+                    "value": {
+                        "uri": "/api/*",
+                        "plugins": { "cors": { "_meta": { "priority": priority } } },
+                        "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+                    }
+                }),
+            ))
+            .await
+            .unwrap();
+        let j = body_json(resp).await;
+        assert_eq!(j["valid"], valid, "{priority}: {j}");
+        if !valid {
+            assert_eq!(j["errors"][0]["field"], "plugins.cors._meta.priority");
+        }
+    }
+}
+
+#[tokio::test]
+async fn validate_rejects_invalid_meta_filter() {
+    let state = full_state();
+    for (filter, valid) in [
+        (serde_json::json!([["consumer", "==", ""]]), true),
+        (serde_json::json!([["consumer", "??", ""]]), false),
+        (serde_json::json!([["no_such_var", "==", "1"]]), false),
+        (serde_json::json!("consumer == ''"), false),
+    ] {
+        let app = build_admin_router(Arc::clone(&state));
+        let resp = app
+            .oneshot(json_post(
+                "/apisix/admin/validate",
+                serde_json::json!({
+                    "type": "route",
+                    "value": {
+                        "uri": "/api/*",
+                        "plugins": { "cors": { "_meta": { "filter": filter } } },
+                        "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+                    }
+                }),
+            ))
+            .await
+            .unwrap();
+        let j = body_json(resp).await;
+        assert_eq!(j["valid"], valid, "{filter}: {j}");
+        if !valid {
+            assert_eq!(j["errors"][0]["field"], "plugins.cors._meta.filter");
+        }
+    }
+}
+
+#[tokio::test]
+async fn validate_accepts_valid_entities_and_reports_schema_errors() {
+    let state = full_state();
+    for (kind, value, valid) in [
+        (
+            "service",
+            serde_json::json!({ "plugins": { "cors": {} } }),
+            true,
+        ),
+        (
+            "plugin_config",
+            serde_json::json!({ "plugins": { "mock": { "status": 42 } } }),
+            false,
+        ),
+        (
+            "consumer",
+            serde_json::json!({ "plugins": { "key-auth": { "key": "k" } } }),
+            true,
+        ),
+        (
+            "upstream",
+            serde_json::json!({ "nodes": "not-a-map" }),
+            false,
+        ),
+    ] {
+        let app = build_admin_router(Arc::clone(&state));
+        let resp = app
+            .oneshot(json_post(
+                "/apisix/admin/validate",
+                serde_json::json!({ "type": kind, "value": value }),
+            ))
+            .await
+            .unwrap();
+        let j = body_json(resp).await;
+        assert_eq!(j["valid"], valid, "{kind}: {j}");
+    }
+    assert!(state.cache.services.is_empty());
+}
+
+#[tokio::test]
+async fn put_route_dry_run_validates_without_storing() {
+    let state = full_state();
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1?dry_run=true",
+            serde_json::json!({
+                "uri": "/dry",
+                "upstream": { "nodes": { "127.0.0.1:8080": 1 } }
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["dry_run"], true);
+    assert_eq!(j["valid"], true);
+
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1?dry_run=true",
+            serde_json::json!({
+                "uri": "/dry",
+                "plugins": { "cors": { "allow_origins": "not-an-array" } }
+            }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert!(state.cache.routes.is_empty());
+    assert_eq!(state.router_swap.load().version(), 1);
+}
+
+#[tokio::test]
+async fn put_service_rejects_invalid_plugin_config() {
+    let state = full_state();
+    let app = build_admin_router(Arc::clone(&state));
+    let resp = app
+        .oneshot(json_put(
+            "/apisix/admin/services/s1",
+            serde_json::json!({ "plugins": { "cors": { "allow_origins": "not-an-array" } } }),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let j = body_json(resp).await;
+    assert!(j["error"].as_str().unwrap().contains("`cors`"));
+    assert!(state.cache.services.is_empty());
+}
+
+// ── Global rules ─────────────────────────────────────────────
+
+#[tokio::test]
+async fn global_rule_crud_records_changes() {
+    let state = full_state();
+    let before = state.cache.changes.version();
+    let (status, _) = send(
+        &state,
+        json_put(
+            "/apisix/admin/global_rules/1",
+            serde_json::json!({ "plugins": { "response-headers-policy": {} } }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(state.cache.changes.version() > before);
+
+    let (status, j) = send(&state, get_req("/apisix/admin/global_rules/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["id"], "1");
+    assert!(j["plugins"]["response-headers-policy"].is_object());
+    let (_, j) = send(&state, get_req("/apisix/admin/global_rules")).await;
+    assert_eq!(j["total"], 1);
+
+    let (status, _) = send(&state, delete_req("/apisix/admin/global_rules/1")).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send(&state, get_req("/apisix/admin/global_rules/1")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn global_rule_rejects_invalid_plugin_config() {
+    let state = full_state();
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/global_rules/1",
+            serde_json::json!({ "plugins": { "response-headers-policy": { "remove": ["bad name"] } } }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(
+        j["error"]
+            .as_str()
+            .unwrap()
+            .contains("response-headers-policy")
+    );
+    assert!(state.cache.global_rules.is_empty());
+}
+
+// ── SSL ──────────────────────────────────────────────────────
+
+const API_CERT: &str = include_str!("../../ando-core/tests/fixtures/ssl/api.crt");
+const API_KEY: &str = include_str!("../../ando-core/tests/fixtures/ssl/api.key");
+const OTHER_KEY: &str = include_str!("../../ando-core/tests/fixtures/ssl/other.key");
+
+fn ssl_state(ssl: SslConfig) -> Arc<AdminState> {
+    let mut state = Arc::into_inner(make_state()).unwrap();
+    state.ssl = ssl;
+    Arc::new(state)
+}
+
+fn api_ssl(key: &str, snis: &[&str]) -> serde_json::Value {
+    serde_json::json!({ "cert": API_CERT, "key": key, "snis": snis })
+}
+
+#[tokio::test]
+async fn ssl_crud_stores_the_key_encrypted() {
+    let state = ssl_state(SslConfig {
+        master_key: Some("s3cret".into()),
+        ..SslConfig::default()
+    });
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(API_KEY, &["api.example.com", "v1.api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+
+    // The TLS resolver serves from this map.
+    let stored = state.cache.ssl_certs.get("api").unwrap().clone();
+    assert!(stored.key.starts_with("enc:"));
+    let master = ando_core::ssl::MasterKey::new("s3cret");
+    assert_eq!(stored.key_pem(Some(&master)).unwrap(), API_KEY);
+
+    let (status, j) = send(&state, get_req("/apisix/admin/ssls/api")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["snis"][1], "v1.api.example.com");
+    assert!(!j["key"].as_str().unwrap().contains("PRIVATE KEY"));
+    let (_, j) = send(&state, get_req("/apisix/admin/ssls")).await;
+    assert_eq!(j["total"], 1);
+
+    // Writing back what GET returned keeps the encrypted key.
+    let (status, _) = send(
+        &state,
+        json_put("/apisix/admin/ssls/api", api_ssl(&stored.key, &[])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state.cache.ssl_certs.get("api").unwrap().key, stored.key);
+
+    let (status, _) = send(&state, delete_req("/apisix/admin/ssls/api")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(state.cache.ssl_certs.is_empty());
+    let (status, _) = send(&state, get_req("/apisix/admin/ssls/api")).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn ssl_plain_keys_are_redacted_from_get_and_list() {
+    let state = ssl_state(SslConfig::default());
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(API_KEY, &["api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+    assert_eq!(state.cache.ssl_certs.get("api").unwrap().key, API_KEY);
+
+    let (_, j) = send(&state, get_req("/apisix/admin/ssls/api")).await;
+    assert_eq!(j["key"], "[redacted]");
+    let (_, j) = send(&state, get_req("/apisix/admin/ssls")).await;
+    assert!(!j.to_string().contains("PRIVATE KEY"), "{j}");
+
+    // Writing back what GET returned keeps the stored key.
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl("[redacted]", &["api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+    assert_eq!(state.cache.ssl_certs.get("api").unwrap().key, API_KEY);
+}
+
+#[tokio::test]
+async fn export_redacts_ssl_keys_and_import_keeps_them() {
+    let state = ssl_state(SslConfig::default());
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(API_KEY, &["api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+
+    let (status, exported) = send(&state, get_req("/apisix/admin/export")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!exported.to_string().contains("PRIVATE KEY"), "{exported}");
+    assert_eq!(exported["ssls"][0]["key"], "[redacted]");
+
+    let (status, j) = import(&state, "replace", exported.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{j}");
+    assert_eq!(state.cache.ssl_certs.get("api").unwrap().key, API_KEY);
+
+    // Nothing to restore the key from.
+    let (status, j) = import(&ssl_state(SslConfig::default()), "merge", exported).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(j["errors"].to_string().contains("redacted"), "{j}");
+}
+
+#[tokio::test]
+async fn ssl_put_rejects_mismatched_key_and_uncovered_snis() {
+    let state = ssl_state(SslConfig::default());
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(OTHER_KEY, &["api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"][0]["field"], "key");
+    assert_eq!(j["error"], "private key does not match the certificate");
+
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(API_KEY, &["api.example.com", "example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"][0]["field"], "snis");
+    assert_eq!(j["error"], "certificate does not cover SNI `example.com`");
+
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            serde_json::json!({ "cert": "not a certificate", "key": API_KEY }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"][0]["field"], "cert");
+
+    // An encrypted key needs the master key to be checked at all.
+    let encrypted = ando_core::ssl::MasterKey::new("s3cret").encrypt(API_KEY);
+    let (status, j) = send(
+        &state,
+        json_put("/apisix/admin/ssls/api", api_ssl(&encrypted, &[])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(j["errors"][0]["field"], "key");
+    assert!(state.cache.ssl_certs.is_empty());
+}
+
+#[tokio::test]
+async fn ssl_expiry_is_flagged_on_status_and_metrics() {
+    let metrics = Arc::new(ando_observability::metrics::MetricsCollector::new(true).unwrap());
+    let mut state = Arc::into_inner(make_state()).unwrap();
+    state.metrics = Some(Arc::clone(&metrics));
+    let state = Arc::new(state);
+    let (status, _) = send(
+        &state,
+        json_put(
+            "/apisix/admin/ssls/api",
+            api_ssl(API_KEY, &["api.example.com"]),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // The fixture is valid for a century: not within the default 30 days.
+    let (_, j) = send(&state, get_req("/apisix/admin/status")).await;
+    assert_eq!(j["ssl"]["certificates"], 1);
+    assert_eq!(j["ssl"]["expiry_warning_days"], 30);
+    assert_eq!(j["ssl"]["expiring"], serde_json::json!([]));
+
+    // A threshold past its expiry flags it.
+    let mut state = Arc::into_inner(state).unwrap();
+    state.ssl.expiry_warning_days = 200 * 365;
+    let state = Arc::new(state);
+    let (_, j) = send(&state, get_req("/apisix/admin/status")).await;
+    assert_eq!(j["ssl"]["expiring"][0]["id"], "api");
+    assert_eq!(j["ssl"]["expiring"][0]["snis"][0], "api.example.com");
+    assert!(j["ssl"]["expiring"][0]["not_after_unix"].as_i64().unwrap() > 0);
+
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(get_req("/metrics"))
+        .await
+        .unwrap();
+    let text =
+        String::from_utf8(to_bytes(resp.into_body(), 1 << 20).await.unwrap().to_vec()).unwrap();
+    assert!(text.contains("ando_ssl_certificates_expiring 1"), "{text}");
+    assert!(text.contains("ando_ssl_certificate_expiry_timestamp_seconds{ssl=\"api\"}"));
+
+    // Writing it now warns.
+    let (status, j) = send(
+        &state,
+        json_put("/apisix/admin/ssls/api?dry_run=true", api_ssl(API_KEY, &[])),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(j["warnings"][0]["field"], "cert");
+    assert!(
+        j["warnings"][0]["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("certificate expires in")
+    );
+}
+
+// ── Authentication & audit ───────────────────────────────────
+
+const ADMIN_KEY: &str = "admin-secret-key";
+const VIEWER_KEY: &str = "viewer-secret-key";
+
+/// State with an `ops` admin key, an unnamed viewer key and audit records
+/// written to `audit_path`.
+fn secured_state(audit_path: &std::path::Path) -> Arc<AdminState> {
+    let auth = AdminAuth::from_config(&AdminConfig {
+        api_keys: vec![
+            AdminApiKey {
+                id: Some("ops".into()),
+                key: ADMIN_KEY.into(),
+                role: AdminRole::Admin,
+            },
+            AdminApiKey {
+                id: None,
+                key: VIEWER_KEY.into(),
+                role: AdminRole::Viewer,
+            },
+        ],
+        ..AdminConfig::default()
+    });
+    let audit = AuditSink::from_config(&AuditLogConfig {
+        enabled: true,
+        include_request_body_hash: false,
+        format: "json".into(),
+        file_path: Some(audit_path.display().to_string()),
+    })
+    .unwrap();
+    Arc::new(AdminState {
+        cache: ConfigCache::new(),
+        router_swap: Arc::new(ArcSwap::new(Arc::new(Router::build(vec![], 1).unwrap()))),
+        plugin_registry: Arc::new(PluginRegistry::new()),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth,
+        audit,
+        metrics: None,
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    })
+}
+
+fn with_key(mut req: Request<Body>, key: &str) -> Request<Body> {
+    req.headers_mut().insert("x-api-key", key.parse().unwrap());
+    req
+}
+
+fn audit_records(path: &std::path::Path) -> Vec<serde_json::Value> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn request_without_valid_key_is_unauthorized() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = secured_state(&dir.path().join("audit.log"));
+    for req in [
+        get_req("/apisix/admin/routes"),
+        with_key(get_req("/apisix/admin/routes"), "wrong"),
+        json_put("/apisix/admin/routes/r1", serde_json::json!({"uri": "/a"})),
+    ] {
+        let resp = build_admin_router(Arc::clone(&state))
+            .oneshot(req)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+    // Probes need no key.
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(get_req("/apisix/admin/health"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(state.cache.routes.is_empty());
+}
+
+#[tokio::test]
+async fn viewer_key_can_read_but_not_write() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit_path = dir.path().join("audit.log");
+    let state = secured_state(&audit_path);
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(with_key(get_req("/apisix/admin/routes"), VIEWER_KEY))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    for req in [
+        json_put("/apisix/admin/routes/r1", serde_json::json!({"uri": "/a"})),
+        delete_req("/apisix/admin/routes/r1"),
+    ] {
+        let resp = build_admin_router(Arc::clone(&state))
+            .oneshot(with_key(req, VIEWER_KEY))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(state.cache.routes.is_empty());
+    assert!(audit_records(&audit_path).is_empty());
+}
+
+#[tokio::test]
+async fn admin_key_writes_are_audited_with_key_id_and_diff() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit_path = dir.path().join("audit.log");
+    let state = secured_state(&audit_path);
+    let upstream = serde_json::json!({ "nodes": { "127.0.0.1:8080": 1 } });
+
+    for req in [
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({"uri": "/a", "upstream": upstream}),
+        ),
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({"uri": "/b", "upstream": upstream, "desc": "moved"}),
+        ),
+        // Dry runs store nothing and are not audited.
+        json_put(
+            "/apisix/admin/routes/r1?dry_run=true",
+            serde_json::json!({"uri": "/c", "upstream": upstream}),
+        ),
+        delete_req("/apisix/admin/routes/r1"),
+    ] {
+        let resp = build_admin_router(Arc::clone(&state))
+            .oneshot(with_key(req, ADMIN_KEY))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let records = audit_records(&audit_path);
+    let actions: Vec<_> = records.iter().map(|r| r["action"].clone()).collect();
+    assert_eq!(actions, ["create", "update", "delete"]);
+    for r in &records {
+        assert_eq!(r["key_id"], "ops");
+        assert_eq!(r["entity_type"], "routes");
+        assert_eq!(r["entity_id"], "r1");
+        assert_eq!(r["uri"], "/apisix/admin/routes/r1");
+    }
+    assert!(
+        records[0]["diff"]["added"]
+            .as_array()
+            .unwrap()
+            .contains(&"uri".into())
+    );
+    assert_eq!(records[1]["diff"]["added"], serde_json::json!(["desc"]));
+    assert_eq!(records[1]["diff"]["changed"], serde_json::json!(["uri"]));
+    assert_eq!(records[2]["method"], "DELETE");
+    assert!(
+        records[2]["diff"]["removed"]
+            .as_array()
+            .unwrap()
+            .contains(&"uri".into())
+    );
+
+    let raw = std::fs::read_to_string(&audit_path).unwrap();
+    assert!(
+        !raw.contains(ADMIN_KEY),
+        "audit log must not contain the key"
+    );
+}
+
+#[tokio::test]
+async fn import_is_audited_per_entity() {
+    let dir = tempfile::tempdir().unwrap();
+    let audit_path = dir.path().join("audit.log");
+    let state = secured_state(&audit_path);
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("/apisix/admin/import")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            serde_json::json!({
+                "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+                "routes": [{ "id": "r1", "uri": "/a", "upstream_id": "u1" }]
+            })
+            .to_string(),
+        ))
+        .unwrap();
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(with_key(req, ADMIN_KEY))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let records = audit_records(&audit_path);
+    let entities: Vec<_> = records
+        .iter()
+        .map(|r| {
+            let field = |k: &str| r[k].as_str().unwrap().to_string();
+            format!(
+                "{} {}/{}",
+                field("action"),
+                field("entity_type"),
+                field("entity_id")
+            )
+        })
+        .collect();
+    assert_eq!(entities, ["create routes/r1", "create upstreams/u1"]);
+}
+
+#[tokio::test]
+async fn reloaded_keys_apply_without_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = secured_state(&dir.path().join("audit.log"));
+    state.auth.reload(&AdminConfig {
+        api_keys: vec![AdminApiKey {
+            id: Some("rotated".into()),
+            key: "rotated-key".into(),
+            role: AdminRole::Viewer,
+        }],
+        ..AdminConfig::default()
+    });
+
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(with_key(get_req("/apisix/admin/routes"), ADMIN_KEY))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(with_key(get_req("/apisix/admin/routes"), "rotated-key"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+// ── List pagination & filters ────────────────────────────────
+
+async fn list(state: &Arc<AdminState>, uri: &str) -> serde_json::Value {
+    let resp = build_admin_router(Arc::clone(state))
+        .oneshot(get_req(uri))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK, "{uri}");
+    body_json(resp).await
+}
+
+fn ids(j: &serde_json::Value, field: &str) -> Vec<String> {
+    j["list"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e[field].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn insert_route(state: &AdminState, route: serde_json::Value) {
+    let route: ando_core::route::Route = serde_json::from_value(route).unwrap();
+    state.cache.routes.insert(route.id.clone(), route);
+}
+
+#[tokio::test]
+async fn list_routes_paginates_in_id_order() {
+    let state = make_state();
+    for i in [3, 1, 5, 2, 4] {
+        insert_route(
+            &state,
+            serde_json::json!({"id": format!("r{i}"), "uri": "/x"}),
+        );
+    }
+
+    let j = list(&state, "/apisix/admin/routes").await;
+    assert_eq!(ids(&j, "id"), ["r1", "r2", "r3", "r4", "r5"]);
+    assert_eq!(j["total"], 5);
+
+    let j = list(&state, "/apisix/admin/routes?page=2&page_size=2").await;
+    assert_eq!(ids(&j, "id"), ["r3", "r4"]);
+    assert_eq!(j["total"], 5);
+    assert_eq!(j["page"], 2);
+    assert_eq!(j["page_size"], 2);
+
+    let j = list(&state, "/apisix/admin/routes?page=4&page_size=2").await;
+    assert!(ids(&j, "id").is_empty());
+
+    let resp = build_admin_router(Arc::clone(&state))
+        .oneshot(get_req("/apisix/admin/routes?page=abc"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn list_routes_filters_by_label_uri_prefix_and_host() {
+    let state = make_state();
+    insert_route(
+        &state,
+        serde_json::json!({"id": "a", "uri": "/api/users", "hosts": ["api.example.com"],
+            "labels": {"env": "prod", "team": "core"}}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "b", "uris": ["/web", "/api/orders"],
+            "labels": {"env": "prod", "team": "shop"}}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "c", "uri": "/api/admin", "labels": {"env": "dev"}}),
+    );
+
+    let j = list(&state, "/apisix/admin/routes?label.env=prod").await;
+    assert_eq!(ids(&j, "id"), ["a", "b"]);
+    assert_eq!(j["total"], 2);
+    let j = list(
+        &state,
+        "/apisix/admin/routes?label.env=prod&label.team=shop",
+    )
+    .await;
+    assert_eq!(ids(&j, "id"), ["b"]);
+    let j = list(&state, "/apisix/admin/routes?label.missing=x").await;
+    assert_eq!(j["total"], 0);
+
+    let j = list(&state, "/apisix/admin/routes?uri_prefix=/api/").await;
+    assert_eq!(ids(&j, "id"), ["a", "b", "c"]);
+    let j = list(&state, "/apisix/admin/routes?uri_prefix=/api/o").await;
+    assert_eq!(ids(&j, "id"), ["b"]);
+    let j = list(&state, "/apisix/admin/routes?host=API.example.com").await;
+    assert_eq!(ids(&j, "id"), ["a"]);
+}
+
+#[tokio::test]
+async fn list_routes_filters_by_plugin_from_route_service_or_plugin_config() {
+    let state = make_state();
+    let service: ando_core::service::Service =
+        serde_json::from_value(serde_json::json!({"id": "s1", "plugins": {"key-auth": {}}}))
+            .unwrap();
+    state.cache.services.insert("s1".into(), service);
+    let pc: ando_core::plugin_config::PluginConfig =
+        serde_json::from_value(serde_json::json!({"id": "pc1", "plugins": {"key-auth": {}}}))
+            .unwrap();
+    state.cache.plugin_configs.insert("pc1".into(), pc);
+
+    insert_route(
+        &state,
+        serde_json::json!({"id": "own", "uri": "/a", "plugins": {"key-auth": {}}}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "via-service", "uri": "/b", "service_id": "s1"}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "via-pc", "uri": "/c", "plugin_config_id": "pc1"}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "other", "uri": "/d", "plugins": {"cors": {}}}),
+    );
+
+    let j = list(&state, "/apisix/admin/routes?plugin=key-auth").await;
+    assert_eq!(ids(&j, "id"), ["own", "via-pc", "via-service"]);
+    let j = list(&state, "/apisix/admin/routes?plugin=cors").await;
+    assert_eq!(ids(&j, "id"), ["other"]);
+}
+
+#[tokio::test]
+async fn list_routes_filters_on_labels_and_hosts_inherited_from_the_service() {
+    let state = make_state();
+    let service: ando_core::service::Service = serde_json::from_value(serde_json::json!({
+        "id": "s1", "hosts": ["shop.example.com"], "labels": {"team": "shop"}
+    }))
+    .unwrap();
+    state.cache.services.insert("s1".into(), service);
+    insert_route(
+        &state,
+        serde_json::json!({"id": "inherits", "uri": "/a", "service_id": "s1"}),
+    );
+    insert_route(
+        &state,
+        serde_json::json!({"id": "overrides", "uri": "/b", "service_id": "s1",
+            "hosts": ["own.example.com"], "labels": {"team": "core"}}),
+    );
+
+    let j = list(&state, "/apisix/admin/routes?label.team=shop").await;
+    assert_eq!(ids(&j, "id"), ["inherits"]);
+    let j = list(&state, "/apisix/admin/routes?host=shop.example.com").await;
+    assert_eq!(ids(&j, "id"), ["inherits"]);
+    let j = list(&state, "/apisix/admin/routes?host=own.example.com").await;
+    assert_eq!(ids(&j, "id"), ["overrides"]);
+}
+
+#[tokio::test]
+async fn service_edit_reaches_the_router_without_touching_its_routes() {
+    let state = make_state();
+    let put = |uri: &str, body: serde_json::Value| {
+        build_admin_router(Arc::clone(&state)).oneshot(json_put(uri, body))
+    };
+    let resp = put(
+        "/apisix/admin/services/s1",
+        serde_json::json!({"hosts": ["a.example.com"], "timeout": {"read": 30}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = put(
+        "/apisix/admin/routes/r1",
+        serde_json::json!({"uri": "/r1", "service_id": "s1",
+            "upstream": {"nodes": {"127.0.0.1:8080": 1}, "type": "roundrobin"}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = put(
+        "/apisix/admin/routes/r2",
+        serde_json::json!({"uri": "/r2", "service_id": "s1", "hosts": ["own.example.com"],
+            "upstream": {"nodes": {"127.0.0.1:8080": 1}, "type": "roundrobin"}}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let router = state.router_swap.load_full();
+    let r1 = router.get_route("r1").unwrap();
+    assert_eq!(r1.hosts, ["a.example.com"]);
+    assert_eq!(r1.timeout.unwrap().read, Some(30.0));
+    assert_eq!(router.get_route("r2").unwrap().hosts, ["own.example.com"]);
+
+    let resp = put(
+        "/apisix/admin/services/s1",
+        serde_json::json!({"hosts": ["b.example.com"]}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let edited = state.router_swap.load_full();
+    assert!(edited.version() > router.version());
+    let r1 = edited.get_route("r1").unwrap();
+    assert_eq!(r1.hosts, ["b.example.com"]);
+    assert!(r1.timeout.is_none());
+    assert_eq!(edited.get_route("r2").unwrap().hosts, ["own.example.com"]);
+    // The stored route keeps only its own values.
+    assert!(state.cache.routes.get("r1").unwrap().hosts.is_empty());
+}
+
+#[tokio::test]
+async fn list_consumers_filters_by_plugin() {
+    let state = make_state();
+    for (name, plugin) in [
+        ("alice", "key-auth"),
+        ("bob", "jwt-auth"),
+        ("carol", "key-auth"),
+    ] {
+        let consumer: ando_core::consumer::Consumer = serde_json::from_value(
+            serde_json::json!({"username": name, "plugins": {plugin: {"key": name}}}),
+        )
+        .unwrap();
+        state.cache.consumers.insert(name.into(), consumer);
+    }
+
+    let j = list(&state, "/apisix/admin/consumers?plugin=key-auth").await;
+    assert_eq!(ids(&j, "username"), ["alice", "carol"]);
+    let j = list(&state, "/apisix/admin/consumers?page=2&page_size=2").await;
+    assert_eq!(ids(&j, "username"), ["carol"]);
+    assert_eq!(j["total"], 3);
+}
+
+// ── Prometheus metrics ─────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn metrics_endpoint_serves_collector_without_api_key() {
+    use ando_observability::metrics::MetricsCollector;
+
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    metrics.record_request("r1", "GET", 200, 0.01);
+    let secured = secured_state(&tempfile::tempdir().unwrap().path().join("audit.log"));
+    let state = Arc::new(AdminState {
+        cache: secured.cache.clone(),
+        router_swap: Arc::clone(&secured.router_swap),
+        plugin_registry: Arc::clone(&secured.plugin_registry),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: secured.auth.clone(),
+        audit: None,
+        metrics: Some(metrics),
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+
+    let resp = build_admin_router(state)
+        .oneshot(get_req("/metrics"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(
+        resp.headers()["content-type"]
+            .to_str()
+            .unwrap()
+            .starts_with("text/plain")
+    );
+    let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    assert!(text.contains(r#"ando_http_requests_total{method="GET",route="r1",status="200"} 1"#));
+
+    let resp = build_admin_router(make_state())
+        .oneshot(get_req("/metrics"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn metrics_report_limit_conn_requests_in_flight() {
+    use ando_observability::metrics::MetricsCollector;
+    use ando_plugin::plugin::PluginContext;
+
+    let base = full_state();
+    let limit_conn = base.plugin_registry.get("limit-conn").unwrap();
+    let inst = limit_conn
+        .configure(&serde_json::json!({ "conn": 2, "key": "route" }))
+        .unwrap();
+    let mut ctx = PluginContext::new(
+        "r1".into(),
+        "10.0.0.1".into(),
+        "GET".into(),
+        "/".into(),
+        Default::default(),
+    );
+    assert!(inst.access_async(&mut ctx).is_none());
+
+    let state = Arc::new(AdminState {
+        cache: base.cache.clone(),
+        router_swap: Arc::clone(&base.router_swap),
+        plugin_registry: Arc::clone(&base.plugin_registry),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: Some(Arc::new(MetricsCollector::new(true).unwrap())),
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+    let scrape = || async {
+        let resp = build_admin_router(Arc::clone(&state))
+            .oneshot(get_req("/metrics"))
+            .await
+            .unwrap();
+        let body = to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+    let series = r#"ando_plugin_stats{plugin="limit-conn",route="r1",stat="in_flight"}"#;
+    assert!(scrape().await.contains(&format!("{series} 1")));
+    drop(ctx);
+    assert!(!scrape().await.contains(series));
+}
+
+#[tokio::test]
+async fn plugin_stats_summarize_plugin_durations() {
+    use ando_observability::metrics::MetricsCollector;
+
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    for _ in 0..10 {
+        metrics.record_plugin_duration("key-auth", "access", 0.0002);
+    }
+    let base = make_state();
+    let state = Arc::new(AdminState {
+        cache: base.cache.clone(),
+        router_swap: Arc::clone(&base.router_swap),
+        plugin_registry: Arc::clone(&base.plugin_registry),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: Some(metrics),
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+
+    let resp = build_admin_router(state)
+        .oneshot(get_req("/apisix/admin/plugins/stats"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["plugins"][0]["plugin"], "key-auth");
+    assert_eq!(j["plugins"][0]["calls"], 10);
+    let p50 = j["plugins"][0]["p50_seconds"].as_f64().unwrap();
+    assert!(p50 > 0.0001 && p50 <= 0.00025, "{p50}");
+
+    let resp = build_admin_router(make_state())
+        .oneshot(get_req("/apisix/admin/plugins/stats"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn worker_stats_report_each_worker_and_the_total() {
+    use ando_observability::worker_stats::WorkerStats;
+
+    let workers = Arc::new(WorkerStats::new(2));
+    workers.worker(0).accepted();
+    workers.worker(0).request();
+    workers.worker(1).accepted();
+    workers.worker(1).connection_opened();
+    let base = make_state();
+    let state = Arc::new(AdminState {
+        cache: base.cache.clone(),
+        router_swap: Arc::clone(&base.router_swap),
+        plugin_registry: Arc::clone(&base.plugin_registry),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: None,
+        workers: Some(workers),
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+
+    let resp = build_admin_router(state)
+        .oneshot(get_req("/apisix/admin/workers/stats"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let j = body_json(resp).await;
+    assert_eq!(j["workers"].as_array().unwrap().len(), 2);
+    assert_eq!(j["workers"][1]["connections"], 1);
+    assert_eq!(j["total"]["accepts"], 2);
+    assert_eq!(j["total"]["requests"], 1);
+
+    let resp = build_admin_router(make_state())
+        .oneshot(get_req("/apisix/admin/workers/stats"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn status_reports_config_sync_and_workers() {
+    use ando_core::shutdown::Shutdown;
+    use ando_observability::worker_stats::WorkerStats;
+
+    let workers = Arc::new(WorkerStats::new(1));
+    let shutdown = Arc::new(Shutdown::new(std::time::Duration::from_secs(30)));
+    workers.worker(0).request();
+    workers.worker(0).response(404);
+    let base = make_state();
+    let state = Arc::new(AdminState {
+        cache: base.cache.clone(),
+        router_swap: Arc::clone(&base.router_swap),
+        plugin_registry: Arc::clone(&base.plugin_registry),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: None,
+        workers: Some(workers),
+        started: std::time::Instant::now(),
+        shutdown: Some(Arc::clone(&shutdown)),
+        listener: Some(ando_core::config::ListenerConfig::default()),
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: None,
+    });
+    let app = build_admin_router(Arc::clone(&state));
+
+    let j = body_json(
+        app.clone()
+            .oneshot(get_req("/apisix/admin/status"))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(j["draining"], false);
+    assert_eq!(j["listener"]["backlog"], 1024);
+    assert_eq!(j["listener"]["nodelay"], true);
+    assert_eq!(j["config"]["routes"], 0);
+    let version = j["config"]["router_version"].as_u64().unwrap();
+    assert!(j["config"]["last_reload_unix_ms"].is_null());
+    assert!(j["config"]["last_sync_error"].is_null());
+    assert_eq!(j["total"]["requests"], 1);
+    assert_eq!(j["workers"][0]["responses_4xx"], 1);
+
+    state.cache.sync.failed("etcd watch lost");
+    let resp = app
+        .clone()
+        .oneshot(json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({"uri": "/a", "upstream": {"nodes": {"127.0.0.1:80": 1}}}),
+        ))
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let j = body_json(
+        app.clone()
+            .oneshot(get_req("/apisix/admin/status"))
+            .await
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(j["config"]["routes"], 1);
+    assert!(j["config"]["router_version"].as_u64().unwrap() > version);
+    assert!(j["config"]["last_reload_unix_ms"].as_u64().unwrap() > 0);
+    assert_eq!(j["config"]["last_sync_error"]["message"], "etcd watch lost");
+
+    shutdown.begin();
+    let j = body_json(app.oneshot(get_req("/apisix/admin/status")).await.unwrap()).await;
+    assert_eq!(j["draining"], true);
+}
+
+// ── Config store write-through ────────────────────────────────
+
+fn make_state_with_store(store: Arc<dyn ConfigStore>) -> Arc<AdminState> {
+    Arc::new(AdminState {
+        cache: ConfigCache::new(),
+        router_swap: Arc::new(ArcSwap::new(Arc::new(Router::build(vec![], 1).unwrap()))),
+        plugin_registry: Arc::new(PluginRegistry::new()),
+        config_changed: Arc::new(Notify::new()),
+        state_file: None,
+        edition: "community",
+        read_only: false,
+        auth: AdminAuth::default(),
+        audit: None,
+        metrics: None,
+        workers: None,
+        started: std::time::Instant::now(),
+        shutdown: None,
+        listener: None,
+        listeners: None,
+        ssl: SslConfig::default(),
+        store: Some(store),
+    })
+}
+
+/// Refuses every write.
+struct DownStore;
+
+#[async_trait::async_trait]
+impl ConfigStore for DownStore {
+    async fn list(&self, _: Kind) -> anyhow::Result<Vec<serde_json::Value>> {
+        anyhow::bail!("connection refused")
+    }
+    async fn get(&self, _: Kind, _: &str) -> anyhow::Result<Option<serde_json::Value>> {
+        anyhow::bail!("connection refused")
+    }
+    async fn put(&self, _: Kind, _: &str, _: &serde_json::Value) -> anyhow::Result<()> {
+        anyhow::bail!("connection refused")
+    }
+    async fn delete(&self, _: Kind, _: &str) -> anyhow::Result<()> {
+        anyhow::bail!("connection refused")
+    }
+    async fn watch(
+        &self,
+        _: ConfigCache,
+        _: Arc<ArcSwap<Router>>,
+        _: crossbeam_channel::Sender<()>,
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn writes_go_through_to_the_config_store() {
+    let store = Arc::new(SqlStore::connect("sqlite::memory:", 1).await.unwrap());
+    let state = make_state_with_store(store.clone());
+
+    let upstream = serde_json::json!({ "nodes": { "127.0.0.1:8080": 1 } });
+    let (status, _) = send(&state, json_put("/apisix/admin/upstreams/u1", upstream)).await;
+    assert_eq!(status, StatusCode::OK);
+    let route = serde_json::json!({ "uri": "/a", "upstream_id": "u1" });
+    let (status, _) = send(&state, json_put("/apisix/admin/routes/r1", route)).await;
+    assert_eq!(status, StatusCode::OK);
+    let stored = store.get(Kind::Route, "r1").await.unwrap().unwrap();
+    assert_eq!(stored["upstream_id"], "u1");
+    assert!(store.get(Kind::Upstream, "u1").await.unwrap().is_some());
+
+    let (status, _) = send(&state, delete_req("/apisix/admin/routes/r1")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(store.get(Kind::Route, "r1").await.unwrap().is_none());
+
+    let doc = serde_json::json!({ "routes": [{ "id": "r2", "uri": "/b" }] });
+    let (status, _) = send(&state, json_post("/apisix/admin/import?mode=replace", doc)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(store.list(Kind::Route).await.unwrap().len(), 1);
+    assert!(store.list(Kind::Upstream).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn write_refused_by_the_config_store_is_not_applied() {
+    let state = make_state_with_store(Arc::new(DownStore));
+    let (status, j) = send(
+        &state,
+        json_put(
+            "/apisix/admin/routes/r1",
+            serde_json::json!({ "uri": "/a", "upstream": { "nodes": { "127.0.0.1:8080": 1 } } }),
+        ),
+    )
+    .await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(j["error"], "config store unavailable: connection refused");
+    assert!(state.cache.routes.is_empty());
+    assert!(state.router_swap.load().get_route("r1").is_none());
+}
@@ -0,0 +1,81 @@
+//! Host matching with one SaaS-style route listing 10k customer domains,
+//! plus wildcard routes: the compiled host index versus a linear scan of
+//! every route's `hosts`.
+//!
+//! Run with `cargo bench -p ando-core --bench host_match`.
+
+use ando_core::route::Route;
+use ando_core::router::{MatchContext, Router};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const HOSTS: usize = 10_000;
+const WILDCARDS: usize = 100;
+const LOOKUPS: usize = 100_000;
+
+fn routes() -> Vec<Route> {
+    let mut routes: Vec<Route> = vec![
+        serde_json::from_value(serde_json::json!({
+            "id": "saas",
+            "uri": "/api/*",
+            "hosts": (0..HOSTS).map(|i| format!("customer{i}.example.com")).collect::<Vec<_>>(),
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .expect("valid route"),
+    ];
+    routes.extend((0..WILDCARDS).map(|i| {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("w{i}"),
+            "uri": "/api/*",
+            "hosts": [format!("*.tenant{i}.example.org")],
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .expect("valid route")
+    }));
+    routes
+}
+
+/// The per-request scan the host index replaces.
+fn linear_match<'r>(routes: &'r [Route], host: &str) -> Option<&'r Route> {
+    routes.iter().find(|r| {
+        r.hosts.iter().any(|h| match h.strip_prefix('*') {
+            Some(suffix) => host.ends_with(suffix),
+            None => h == host,
+        })
+    })
+}
+
+fn time(mut f: impl FnMut(usize)) -> Duration {
+    let started = Instant::now();
+    for i in 0..LOOKUPS {
+        f(i);
+    }
+    started.elapsed() / LOOKUPS as u32
+}
+
+fn main() {
+    let table = routes();
+    let router = Router::build(table.clone(), 1).unwrap();
+    let ctx = MatchContext::default();
+    let hosts: Vec<String> = (0..1_000)
+        .map(|i| match i % 3 {
+            0 => format!("customer{}.example.com", i * 7 % HOSTS),
+            1 => format!("app.tenant{}.example.org", i % WILDCARDS),
+            _ => format!("unknown{i}.example.net"),
+        })
+        .collect();
+
+    let indexed = time(|i| {
+        black_box(router.match_route("GET", "/api/x", Some(&hosts[i % hosts.len()]), &ctx));
+    });
+    println!("host index, per request:   {indexed:>10.2?}");
+
+    let linear = time(|i| {
+        black_box(linear_match(&table, &hosts[i % hosts.len()]));
+    });
+    println!("linear scan, per request:  {linear:>10.2?}");
+    println!(
+        "speedup: {:.1}x",
+        linear.as_secs_f64() / indexed.as_secs_f64()
+    );
+}
@@ -0,0 +1,63 @@
+//! Router rebuild cost with 10k routes: one full build, and a burst of
+//! single-key changes rebuilt per change versus once for the burst (the
+//! config watcher's debounce).
+//!
+//! Run with `cargo bench -p ando-core --bench router_build`.
+
+use ando_core::route::Route;
+use ando_core::router::Router;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ROUTES: usize = 10_000;
+const BURST: usize = 50;
+const RUNS: usize = 5;
+
+fn routes(n: usize) -> Vec<Route> {
+    (0..n)
+        .map(|i| {
+            serde_json::from_value(serde_json::json!({
+                "id": format!("r{i}"),
+                "uri": format!("/svc{}/v{}/items/:id/*", i % 100, i),
+                "hosts": [format!("tenant{}.example.com", i % 10)],
+                "methods": ["GET", "POST"],
+                "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            }))
+            .expect("valid route")
+        })
+        .collect()
+}
+
+/// Median wall time of `RUNS` calls to `f`.
+fn median(mut f: impl FnMut()) -> Duration {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let started = Instant::now();
+            f();
+            started.elapsed()
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+fn main() {
+    let table = routes(ROUTES);
+
+    // The initial sync, and a debounced burst: one build either way.
+    let once = median(|| {
+        black_box(Router::build(table.clone(), 1).unwrap());
+    });
+    println!("build {ROUTES} routes once:              {once:>10.2?}");
+
+    let per_change = median(|| {
+        for version in 0..BURST {
+            black_box(Router::build(table.clone(), version as u64).unwrap());
+        }
+    });
+    println!("burst of {BURST} changes, rebuild each:   {per_change:>10.2?}");
+    println!(
+        "debounced burst speedup: {:.1}x",
+        per_change.as_secs_f64() / once.as_secs_f64()
+    );
+}
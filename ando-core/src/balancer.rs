@@ -0,0 +1,488 @@
+use crate::upstream::Upstream;
+use std::collections::HashMap;
+
+/// Per-upstream load balancer, selected by `Upstream::lb_type`.
+///
+/// Unknown types fall back to weighted round-robin.
+#[derive(Debug, Clone)]
+pub enum Balancer {
+    RoundRobin(RoundRobin),
+    Chash(ConsistentHash),
+}
+
+impl Balancer {
+    pub fn new(upstream: &Upstream) -> Self {
+        Self::for_nodes(&upstream.lb_type, &upstream.nodes)
+    }
+
+    /// A balancer of type `lb_type` over `nodes` (address → weight), for
+    /// when they differ from the upstream's own, e.g. once resolved.
+    pub fn for_nodes(lb_type: &str, nodes: &HashMap<String, u32>) -> Self {
+        match lb_type {
+            "chash" => Balancer::Chash(ConsistentHash::new(nodes)),
+            _ => Balancer::RoundRobin(RoundRobin::new(nodes)),
+        }
+    }
+
+    /// Pick a node. `hash_key` is only consulted by hashing balancers.
+    #[inline]
+    pub fn pick(&mut self, hash_key: Option<&str>) -> Option<&str> {
+        match self {
+            Balancer::RoundRobin(rr) => rr.pick(),
+            Balancer::Chash(ch) => ch.pick(hash_key.unwrap_or("")),
+        }
+    }
+
+    /// Pick a node among those for which `usable` returns true.
+    /// Returns `None` if no node is usable.
+    #[inline]
+    pub fn pick_where(
+        &mut self,
+        hash_key: Option<&str>,
+        usable: impl Fn(&str) -> bool,
+    ) -> Option<&str> {
+        match self {
+            Balancer::RoundRobin(rr) => rr.pick_where(usable),
+            Balancer::Chash(ch) => ch.pick_where(hash_key.unwrap_or(""), usable),
+        }
+    }
+
+    /// Whether this balancer needs a per-request hash key.
+    #[inline]
+    pub fn needs_hash_key(&self) -> bool {
+        matches!(self, Balancer::Chash(_))
+    }
+}
+
+/// Request attribute a `chash` upstream hashes on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HashOn {
+    /// A request variable: `remote_addr`, `uri`, or `host`.
+    Var(String),
+    /// A request header (lowercase name).
+    Header(String),
+    /// A cookie by name.
+    Cookie(String),
+}
+
+impl HashOn {
+    /// Parse APISIX-style `hash_on` + `key`, also accepting the combined
+    /// `source.name` form in `hash_on` (e.g. `header.x-user-id`).
+    pub fn parse(hash_on: &str, key: Option<&str>) -> Self {
+        let (source, name) = match hash_on.split_once('.') {
+            Some((source, name)) => (source, Some(name)),
+            None => (hash_on, key),
+        };
+        match (source, name) {
+            ("header", Some(n)) if !n.is_empty() => HashOn::Header(n.to_ascii_lowercase()),
+            ("cookie", Some(n)) if !n.is_empty() => HashOn::Cookie(n.to_string()),
+            ("vars", Some(n)) if !n.is_empty() => HashOn::Var(n.to_string()),
+            _ => HashOn::Var("remote_addr".into()),
+        }
+    }
+}
+
+/// Smooth weighted round-robin balancer (nginx algorithm).
+///
+/// v2 design: Each worker core owns its balancers (no atomics, no locks).
+/// State is rebuilt lazily after a router version bump, so weight changes
+/// take effect on the next config swap.
+///
+/// The smooth variant interleaves picks instead of bursting: weights
+/// `{a: 3, b: 1}` yield `a a b a` rather than `a a a b`.
+#[derive(Debug, Clone)]
+pub struct RoundRobin {
+    nodes: Vec<WeightedNode>,
+    total_weight: i64,
+}
+
+#[derive(Debug, Clone)]
+struct WeightedNode {
+    addr: String,
+    weight: i64,
+    current: i64,
+}
+
+impl RoundRobin {
+    /// Build a balancer from an upstream node map (address → weight).
+    ///
+    /// Nodes with weight 0 receive no traffic. Nodes are sorted by address
+    /// so that every worker produces the same sequence for the same config.
+    pub fn new(nodes: &HashMap<String, u32>) -> Self {
+        let mut list: Vec<WeightedNode> = nodes
+            .iter()
+            .filter(|(_, w)| **w > 0)
+            .map(|(addr, w)| WeightedNode {
+                addr: addr.clone(),
+                weight: i64::from(*w),
+                current: 0,
+            })
+            .collect();
+        list.sort_by(|a, b| a.addr.cmp(&b.addr));
+        let total_weight = list.iter().map(|n| n.weight).sum();
+        Self {
+            nodes: list,
+            total_weight,
+        }
+    }
+
+    /// Pick the next node. Returns `None` when no node has a positive weight.
+    #[inline]
+    pub fn pick(&mut self) -> Option<&str> {
+        match self.nodes.len() {
+            0 => return None,
+            1 => return Some(self.nodes[0].addr.as_str()),
+            _ => {}
+        }
+
+        let mut best = 0;
+        for i in 0..self.nodes.len() {
+            let node = &mut self.nodes[i];
+            node.current += node.weight;
+            if node.current > self.nodes[best].current {
+                best = i;
+            }
+        }
+
+        self.nodes[best].current -= self.total_weight;
+        Some(self.nodes[best].addr.as_str())
+    }
+
+    /// Pick the next node, skipping nodes for which `usable` returns false.
+    /// Skipped nodes keep their state, as nginx does for peers marked down.
+    pub fn pick_where(&mut self, usable: impl Fn(&str) -> bool) -> Option<&str> {
+        let mut best: Option<usize> = None;
+        let mut total = 0;
+        for i in 0..self.nodes.len() {
+            if !usable(&self.nodes[i].addr) {
+                continue;
+            }
+            let node = &mut self.nodes[i];
+            node.current += node.weight;
+            total += node.weight;
+            if best.is_none_or(|b| self.nodes[i].current > self.nodes[b].current) {
+                best = Some(i);
+            }
+        }
+
+        let best = best?;
+        self.nodes[best].current -= total;
+        Some(self.nodes[best].addr.as_str())
+    }
+
+    /// Number of nodes eligible for selection.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+/// Virtual nodes placed on the ring per unit of weight.
+const VNODES_PER_WEIGHT: u32 = 40;
+
+/// Consistent-hash balancer (ketama-style ring with weighted virtual nodes).
+///
+/// Adding or removing a node only remaps the keys that land on that node's
+/// ring segments — roughly `1 / nodes` of the keyspace.
+#[derive(Debug, Clone)]
+pub struct ConsistentHash {
+    addrs: Vec<String>,
+    /// (point, index into `addrs`), sorted by point.
+    ring: Vec<(u64, usize)>,
+}
+
+impl ConsistentHash {
+    pub fn new(nodes: &HashMap<String, u32>) -> Self {
+        let mut addrs: Vec<String> = nodes
+            .iter()
+            .filter(|(_, w)| **w > 0)
+            .map(|(a, _)| a.clone())
+            .collect();
+        addrs.sort();
+
+        let mut ring = Vec::new();
+        for (idx, addr) in addrs.iter().enumerate() {
+            let vnodes = nodes[addr].saturating_mul(VNODES_PER_WEIGHT);
+            for v in 0..vnodes {
+                let point = hash_str(&format!("{addr}#{v}"));
+                ring.push((point, idx));
+            }
+        }
+        ring.sort_unstable();
+
+        Self { addrs, ring }
+    }
+
+    /// Pick the node owning `key` — the first ring point at or after its hash.
+    #[inline]
+    pub fn pick(&self, key: &str) -> Option<&str> {
+        match self.addrs.len() {
+            0 => return None,
+            1 => return Some(self.addrs[0].as_str()),
+            _ => {}
+        }
+        let h = hash_str(key);
+        let pos = self.ring.partition_point(|(p, _)| *p < h);
+        let (_, idx) = self.ring[pos % self.ring.len()];
+        Some(self.addrs[idx].as_str())
+    }
+
+    /// Like `pick`, but walks the ring past nodes for which `usable`
+    /// returns false, so only keys owned by those nodes are remapped.
+    pub fn pick_where(&self, key: &str, usable: impl Fn(&str) -> bool) -> Option<&str> {
+        if self.ring.is_empty() {
+            return None;
+        }
+        let h = hash_str(key);
+        let start = self.ring.partition_point(|(p, _)| *p < h);
+        (0..self.ring.len())
+            .map(|i| self.ring[(start + i) % self.ring.len()].1)
+            .map(|idx| self.addrs[idx].as_str())
+            .find(|addr| usable(addr))
+    }
+
+    pub fn len(&self) -> usize {
+        self.addrs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addrs.is_empty()
+    }
+}
+
+/// FNV-1a with a splitmix64 finalizer — stable across processes and builds,
+/// unlike `std`'s `DefaultHasher`.
+#[inline]
+fn hash_str(s: &str) -> u64 {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in s.as_bytes() {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    h ^= h >> 30;
+    h = h.wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h ^= h >> 27;
+    h = h.wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nodes(list: &[(&str, u32)]) -> HashMap<String, u32> {
+        list.iter().map(|(a, w)| (a.to_string(), *w)).collect()
+    }
+
+    fn pick_n(rr: &mut RoundRobin, n: usize) -> Vec<String> {
+        (0..n).map(|_| rr.pick().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn empty_balancer_returns_none() {
+        let mut rr = RoundRobin::new(&HashMap::new());
+        assert!(rr.is_empty());
+        assert!(rr.pick().is_none());
+    }
+
+    #[test]
+    fn single_node_always_selected() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 5)]));
+        assert_eq!(rr.len(), 1);
+        for _ in 0..10 {
+            assert_eq!(rr.pick(), Some("a:80"));
+        }
+    }
+
+    #[test]
+    fn equal_weights_alternate() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 1), ("b:80", 1)]));
+        assert_eq!(pick_n(&mut rr, 4), vec!["a:80", "b:80", "a:80", "b:80"]);
+    }
+
+    #[test]
+    fn weights_are_respected_over_a_cycle() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 3), ("b:80", 1)]));
+        let picks = pick_n(&mut rr, 400);
+        let a = picks.iter().filter(|p| *p == "a:80").count();
+        let b = picks.iter().filter(|p| *p == "b:80").count();
+        assert_eq!(a, 300);
+        assert_eq!(b, 100);
+    }
+
+    #[test]
+    fn selection_is_smooth_not_bursty() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 3), ("b:80", 1)]));
+        assert_eq!(pick_n(&mut rr, 4), vec!["a:80", "a:80", "b:80", "a:80"]);
+    }
+
+    #[test]
+    fn zero_weight_nodes_are_skipped() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 0), ("b:80", 2)]));
+        assert_eq!(rr.len(), 1);
+        for _ in 0..5 {
+            assert_eq!(rr.pick(), Some("b:80"));
+        }
+    }
+
+    #[test]
+    fn all_zero_weights_returns_none() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 0), ("b:80", 0)]));
+        assert!(rr.pick().is_none());
+    }
+
+    #[test]
+    fn three_nodes_distribution_matches_weights() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 5), ("b:80", 3), ("c:80", 2)]));
+        let picks = pick_n(&mut rr, 1000);
+        assert_eq!(picks.iter().filter(|p| *p == "a:80").count(), 500);
+        assert_eq!(picks.iter().filter(|p| *p == "b:80").count(), 300);
+        assert_eq!(picks.iter().filter(|p| *p == "c:80").count(), 200);
+    }
+
+    // ── HashOn ───────────────────────────────────────────────────
+
+    #[test]
+    fn hash_on_defaults_to_remote_addr() {
+        assert_eq!(
+            HashOn::parse("vars", None),
+            HashOn::Var("remote_addr".into())
+        );
+        assert_eq!(HashOn::parse("", None), HashOn::Var("remote_addr".into()));
+    }
+
+    #[test]
+    fn hash_on_combined_form() {
+        assert_eq!(
+            HashOn::parse("vars.remote_addr", None),
+            HashOn::Var("remote_addr".into())
+        );
+        assert_eq!(
+            HashOn::parse("header.X-User-Id", None),
+            HashOn::Header("x-user-id".into())
+        );
+        assert_eq!(
+            HashOn::parse("cookie.session", None),
+            HashOn::Cookie("session".into())
+        );
+    }
+
+    #[test]
+    fn hash_on_apisix_form_with_key() {
+        assert_eq!(
+            HashOn::parse("header", Some("x-tenant")),
+            HashOn::Header("x-tenant".into())
+        );
+        assert_eq!(
+            HashOn::parse("cookie", Some("sid")),
+            HashOn::Cookie("sid".into())
+        );
+        // header without a name is meaningless — fall back to client IP
+        assert_eq!(
+            HashOn::parse("header", None),
+            HashOn::Var("remote_addr".into())
+        );
+    }
+
+    // ── ConsistentHash ───────────────────────────────────────────
+
+    #[test]
+    fn chash_same_key_is_sticky() {
+        let ch = ConsistentHash::new(&nodes(&[("a:80", 1), ("b:80", 1), ("c:80", 1)]));
+        let first = ch.pick("user-42").unwrap().to_string();
+        for _ in 0..100 {
+            assert_eq!(ch.pick("user-42"), Some(first.as_str()));
+        }
+    }
+
+    #[test]
+    fn chash_distributes_across_nodes() {
+        let ch = ConsistentHash::new(&nodes(&[("a:80", 1), ("b:80", 1), ("c:80", 1)]));
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for i in 0..3000 {
+            let node = ch.pick(&format!("10.0.{}.{}", i / 256, i % 256)).unwrap();
+            *counts.entry(node.to_string()).or_default() += 1;
+        }
+        assert_eq!(counts.len(), 3);
+        for (node, n) in &counts {
+            assert!(
+                (600..=1400).contains(n),
+                "node {node} got {n} of 3000 keys — distribution too skewed"
+            );
+        }
+    }
+
+    #[test]
+    fn chash_removing_node_only_remaps_its_keys() {
+        let before = ConsistentHash::new(&nodes(&[("a:80", 1), ("b:80", 1), ("c:80", 1)]));
+        let after = ConsistentHash::new(&nodes(&[("a:80", 1), ("b:80", 1)]));
+        for i in 0..2000 {
+            let key = format!("key-{i}");
+            let old = before.pick(&key).unwrap();
+            if old != "c:80" {
+                assert_eq!(after.pick(&key), Some(old), "key {key} moved unnecessarily");
+            }
+        }
+    }
+
+    #[test]
+    fn chash_single_and_empty() {
+        assert!(ConsistentHash::new(&HashMap::new()).pick("k").is_none());
+        let ch = ConsistentHash::new(&nodes(&[("a:80", 1)]));
+        assert_eq!(ch.pick("anything"), Some("a:80"));
+    }
+
+    #[test]
+    fn chash_skips_zero_weight_nodes() {
+        let ch = ConsistentHash::new(&nodes(&[("a:80", 0), ("b:80", 1), ("c:80", 1)]));
+        assert_eq!(ch.len(), 2);
+        for i in 0..200 {
+            assert_ne!(ch.pick(&format!("k{i}")), Some("a:80"));
+        }
+    }
+
+    #[test]
+    fn balancer_selects_type_from_upstream() {
+        let mut ups: Upstream = serde_json::from_value(serde_json::json!({
+            "type": "chash",
+            "nodes": { "a:80": 1, "b:80": 1 }
+        }))
+        .unwrap();
+        assert!(Balancer::new(&ups).needs_hash_key());
+        ups.lb_type = "roundrobin".into();
+        assert!(!Balancer::new(&ups).needs_hash_key());
+        ups.lb_type = "ewma".into();
+        assert!(matches!(Balancer::new(&ups), Balancer::RoundRobin(_)));
+    }
+
+    #[test]
+    fn rr_pick_where_skips_unusable_nodes() {
+        let mut rr = RoundRobin::new(&nodes(&[("a:80", 1), ("b:80", 1), ("c:80", 2)]));
+        for _ in 0..20 {
+            assert_ne!(rr.pick_where(|a| a != "c:80"), Some("c:80"));
+        }
+        let picks: Vec<_> = (0..4)
+            .map(|_| rr.pick_where(|a| a != "c:80").unwrap().to_string())
+            .collect();
+        assert_eq!(picks.iter().filter(|a| *a == "a:80").count(), 2);
+        assert!(rr.pick_where(|_| false).is_none());
+    }
+
+    #[test]
+    fn chash_pick_where_only_remaps_unusable_keys() {
+        let ch = ConsistentHash::new(&nodes(&[("a:80", 1), ("b:80", 1), ("c:80", 1)]));
+        for i in 0..500 {
+            let key = format!("k{i}");
+            let owner = ch.pick(&key).unwrap();
+            let alt = ch.pick_where(&key, |a| a != "b:80").unwrap();
+            assert_ne!(alt, "b:80");
+            if owner != "b:80" {
+                assert_eq!(alt, owner);
+            }
+        }
+        assert!(ch.pick_where("k", |_| false).is_none());
+    }
+}
@@ -17,6 +17,27 @@ pub struct Consumer {
     pub labels: HashMap<String, String>,
 }
 
+/// Consumer plugin config field that identifies a credential, per auth
+/// plugin. mtls-auth identifies certificates by either of two fields.
+pub const CREDENTIAL_ID_FIELDS: &[(&str, &str)] = &[
+    ("key-auth", "key"),
+    ("basic-auth", "username"),
+    ("hmac-auth", "access_key"),
+    ("mtls-auth", "fingerprint"),
+    ("mtls-auth", "san"),
+];
+
+impl Consumer {
+    /// The consumer's credential ids as `(plugin, id)` pairs, one per
+    /// configured field listed in [`CREDENTIAL_ID_FIELDS`].
+    pub fn credential_ids(&self) -> impl Iterator<Item = (&'static str, &str)> + '_ {
+        CREDENTIAL_ID_FIELDS.iter().filter_map(|(plugin, field)| {
+            let id = self.plugins.get(*plugin)?.get(*field)?.as_str()?;
+            Some((*plugin, id))
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -43,6 +64,27 @@ mod tests {
         assert_eq!(key_auth["key"], "secret-key");
     }
 
+    #[test]
+    fn credential_ids_cover_every_auth_plugin() {
+        let json = r#"{"username":"alice","plugins":{
+            "key-auth":{"key":"k"},
+            "basic-auth":{"username":"al","password":"pw"},
+            "hmac-auth":{"access_key":"ak","secret_key":"sk"},
+            "mtls-auth":{"san":"partner.example"},
+            "limit-count":{"count":1}}}"#;
+        let c: Consumer = serde_json::from_str(json).unwrap();
+        let ids: Vec<_> = c.credential_ids().collect();
+        assert_eq!(
+            ids,
+            [
+                ("key-auth", "k"),
+                ("basic-auth", "al"),
+                ("hmac-auth", "ak"),
+                ("mtls-auth", "partner.example")
+            ]
+        );
+    }
+
     #[test]
     fn test_consumer_serde_roundtrip() {
         let mut c = Consumer {
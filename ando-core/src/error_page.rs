@@ -0,0 +1,322 @@
+use crate::config::ErrorPageSet;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+/// Compiled error pages: bodies of the responses the gateway generates
+/// itself (no route matched, upstream failure, a limiter's rejection),
+/// by status code.
+///
+/// v2 design: templates are parsed once, when the config is loaded, into
+/// text and variable segments; rendering only concatenates. Without a
+/// page for a status the data plane keeps its pre-built static response,
+/// so unconfigured errors still cost no allocation.
+///
+/// Variables: `{{status}}`, `{{route_id}}` (empty when no route matched)
+/// and `{{request_id}}` (the request's `X-Request-Id`). Values are escaped
+/// for the page's content type: JSON string escapes for `*json`, entities
+/// for HTML and XML.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages {
+    pages: HashMap<u16, Vec<ErrorPage>>,
+}
+
+/// One body template and its content type.
+#[derive(Debug, Clone)]
+pub struct ErrorPage {
+    content_type: String,
+    escape: Escape,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Escape {
+    Json,
+    Markup,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Text(String),
+    Status,
+    RouteId,
+    RequestId,
+}
+
+/// Values of the template variables for one response.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorVars<'a> {
+    pub status: u16,
+    pub route_id: &'a str,
+    pub request_id: &'a str,
+}
+
+impl ErrorPages {
+    /// Compile `errors`-style config: status codes (as strings, 400-599)
+    /// to one page or a list to choose from by `Accept`.
+    pub fn compile(config: &HashMap<String, ErrorPageSet>) -> Result<Self, String> {
+        let mut pages = HashMap::with_capacity(config.len());
+        for (status, set) in config {
+            let code: u16 = status
+                .trim()
+                .parse()
+                .ok()
+                .filter(|code| (400..=599).contains(code))
+                .ok_or_else(|| format!("error page `{status}`: expected a status 400-599"))?;
+            let compiled = set
+                .pages()
+                .iter()
+                .map(|page| ErrorPage::compile(&page.content_type, &page.body_template))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("error page {code}: {e}"))?;
+            if compiled.is_empty() {
+                return Err(format!("error page {code}: no page given"));
+            }
+            pages.insert(code, compiled);
+        }
+        Ok(Self { pages })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    /// The page for `status` that best fits `accept`: the first page whose
+    /// content type the most preferred media range matches, else the
+    /// first page configured.
+    pub fn select(&self, status: u16, accept: Option<&str>) -> Option<&ErrorPage> {
+        let pages = self.pages.get(&status)?;
+        if pages.len() > 1
+            && let Some(accept) = accept
+        {
+            for range in media_ranges(accept) {
+                if let Some(page) = pages.iter().find(|p| media_matches(range, &p.content_type)) {
+                    return Some(page);
+                }
+            }
+        }
+        pages.first()
+    }
+}
+
+impl ErrorPage {
+    /// Parse `template`. Unknown variables and an unclosed `{{` are errors.
+    pub fn compile(content_type: &str, template: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+        while let Some(open) = rest.find("{{") {
+            if open > 0 {
+                segments.push(Segment::Text(rest[..open].to_string()));
+            }
+            let after = &rest[open + 2..];
+            let close = after
+                .find("}}")
+                .ok_or_else(|| "unclosed `{{` in body_template".to_string())?;
+            segments.push(match after[..close].trim() {
+                "status" => Segment::Status,
+                "route_id" => Segment::RouteId,
+                "request_id" => Segment::RequestId,
+                name => return Err(format!("unknown variable `{{{{{name}}}}}`")),
+            });
+            rest = &after[close + 2..];
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Text(rest.to_string()));
+        }
+        let essence = essence(content_type);
+        let escape = if essence.ends_with("json") {
+            Escape::Json
+        } else if essence.ends_with("html") || essence.ends_with("xml") {
+            Escape::Markup
+        } else {
+            Escape::None
+        };
+        Ok(Self {
+            content_type: content_type.to_string(),
+            escape,
+            segments,
+        })
+    }
+
+    pub fn content_type(&self) -> &str {
+        &self.content_type
+    }
+
+    /// The body for `vars`.
+    pub fn render(&self, vars: &ErrorVars<'_>) -> Vec<u8> {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                Segment::Text(text) => out.push_str(text),
+                Segment::Status => {
+                    let _ = write!(out, "{}", vars.status);
+                }
+                Segment::RouteId => self.push_escaped(&mut out, vars.route_id),
+                Segment::RequestId => self.push_escaped(&mut out, vars.request_id),
+            }
+        }
+        out.into_bytes()
+    }
+
+    fn push_escaped(&self, out: &mut String, value: &str) {
+        for c in value.chars() {
+            match (self.escape, c) {
+                (Escape::Json, '"') => out.push_str("\\\""),
+                (Escape::Json, '\\') => out.push_str("\\\\"),
+                (Escape::Json, c) if c.is_control() => {
+                    let _ = write!(out, "\\u{:04x}", c as u32);
+                }
+                (Escape::Markup, '<') => out.push_str("&lt;"),
+                (Escape::Markup, '>') => out.push_str("&gt;"),
+                (Escape::Markup, '&') => out.push_str("&amp;"),
+                (Escape::Markup, '"') => out.push_str("&quot;"),
+                (Escape::Markup, '\'') => out.push_str("&#39;"),
+                (_, c) => out.push(c),
+            }
+        }
+    }
+}
+
+/// `type/subtype` of a media type, without parameters, lower case.
+fn essence(media_type: &str) -> String {
+    media_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase()
+}
+
+/// Media ranges of an `Accept` value, most preferred first; `q=0` ones
+/// are dropped.
+fn media_ranges(accept: &str) -> Vec<&str> {
+    let mut ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let range = parts.next()?.trim();
+            let q = parts
+                .filter_map(|p| p.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse().ok())
+                .unwrap_or(1.0);
+            (!range.is_empty() && q > 0.0).then_some((range, q))
+        })
+        .collect();
+    // Stable: ranges of equal weight keep the client's order.
+    ranges.sort_by(|a, b| b.1.total_cmp(&a.1));
+    ranges.into_iter().map(|(range, _)| range).collect()
+}
+
+/// Whether the media `range` (`*/*`, `text/*`, `text/html`) covers
+/// `content_type`.
+fn media_matches(range: &str, content_type: &str) -> bool {
+    let range = range.to_ascii_lowercase();
+    let essence = essence(content_type);
+    range == "*/*"
+        || range == essence
+        || range
+            .strip_suffix("/*")
+            .is_some_and(|kind| essence.split('/').next() == Some(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ErrorPageConfig;
+
+    fn page(content_type: &str, body_template: &str) -> ErrorPageConfig {
+        ErrorPageConfig {
+            content_type: content_type.to_string(),
+            body_template: body_template.to_string(),
+        }
+    }
+
+    fn pages(entries: &[(&str, ErrorPageSet)]) -> Result<ErrorPages, String> {
+        ErrorPages::compile(
+            &entries
+                .iter()
+                .map(|(status, set)| (status.to_string(), set.clone()))
+                .collect(),
+        )
+    }
+
+    fn render(pages: &ErrorPages, status: u16, accept: Option<&str>) -> Option<(String, String)> {
+        let page = pages.select(status, accept)?;
+        let vars = ErrorVars {
+            status,
+            route_id: "r1",
+            request_id: "abc",
+        };
+        Some((
+            page.content_type().to_string(),
+            String::from_utf8(page.render(&vars)).unwrap(),
+        ))
+    }
+
+    #[test]
+    fn renders_variables() {
+        let pages = pages(&[(
+            "502",
+            ErrorPageSet::One(page(
+                "application/json",
+                r#"{"code":{{status}},"route":"{{ route_id }}","id":"{{request_id}}"}"#,
+            )),
+        )])
+        .unwrap();
+        assert_eq!(
+            render(&pages, 502, None).unwrap(),
+            (
+                "application/json".to_string(),
+                r#"{"code":502,"route":"r1","id":"abc"}"#.to_string()
+            )
+        );
+        assert!(render(&pages, 404, None).is_none());
+    }
+
+    #[test]
+    fn escapes_values_for_the_content_type() {
+        let vars = ErrorVars {
+            status: 404,
+            route_id: "",
+            request_id: "\"<x>&",
+        };
+        let json = ErrorPage::compile("application/problem+json", "{{request_id}}").unwrap();
+        assert_eq!(json.render(&vars), br#"\"<x>&"#);
+        let html = ErrorPage::compile("text/html; charset=utf-8", "{{request_id}}").unwrap();
+        assert_eq!(html.render(&vars), b"&quot;&lt;x&gt;&amp;");
+        let text = ErrorPage::compile("text/plain", "{{request_id}}").unwrap();
+        assert_eq!(text.render(&vars), b"\"<x>&");
+    }
+
+    #[test]
+    fn rejects_bad_templates_and_statuses() {
+        let one = |template: &str| ErrorPageSet::One(page("text/plain", template));
+        assert!(pages(&[("404", one("{{host}}"))]).is_err());
+        assert!(pages(&[("404", one("{{status"))]).is_err());
+        assert!(pages(&[("200", one("ok"))]).is_err());
+        assert!(pages(&[("abc", one("ok"))]).is_err());
+        assert!(pages(&[("404", ErrorPageSet::Many(vec![]))]).is_err());
+    }
+
+    #[test]
+    fn picks_a_page_by_accept() {
+        let pages = pages(&[(
+            "404",
+            ErrorPageSet::Many(vec![
+                page("application/json", r#"{"status":{{status}}}"#),
+                page("text/html", "<h1>{{status}}</h1>"),
+            ]),
+        )])
+        .unwrap();
+        let content_type = |accept| render(&pages, 404, accept).unwrap().0;
+        assert_eq!(content_type(None), "application/json");
+        assert_eq!(content_type(Some("text/html,*/*;q=0.8")), "text/html");
+        assert_eq!(content_type(Some("text/*")), "text/html");
+        assert_eq!(
+            content_type(Some("text/html;q=0.5, application/json")),
+            "application/json"
+        );
+        assert_eq!(content_type(Some("image/png")), "application/json");
+        assert_eq!(content_type(Some("text/html;q=0")), "application/json");
+    }
+}
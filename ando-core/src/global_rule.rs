@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Plugins applied to every route — APISIX-compatible.
+///
+/// Their configs are merged under those of each route and its service, so
+/// a route configuring the same plugin overrides the global one, and
+/// `{"_meta": {"disable": true}}` turns it off for that route.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalRule {
+    pub id: String,
+
+    /// Plugin configurations.
+    #[serde(default)]
+    pub plugins: HashMap<String, serde_json::Value>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn global_rule_deserializes() {
+        let json = r#"{"id":"1","plugins":{"response-headers-policy":{}}}"#;
+        let rule: GlobalRule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.id, "1");
+        assert!(rule.plugins.contains_key("response-headers-policy"));
+
+        let empty: GlobalRule = serde_json::from_str(r#"{"id":"2"}"#).unwrap();
+        assert!(empty.plugins.is_empty());
+    }
+}
@@ -0,0 +1,174 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// Route `hosts` compiled into one index shared by all routes.
+///
+/// v2 design: Built once with the router. Routes that restrict hosts get a
+/// dense slot number; exact hosts map to the slots that list them, and
+/// `*.suffix` wildcards live in a trie keyed by reversed labels. A request
+/// host is looked up once, before any path candidate is checked, giving
+/// the set of slots it may match. Hosts compare case-insensitively.
+#[derive(Default)]
+pub struct HostIndex {
+    exact: HashMap<String, Vec<u32>>,
+    wildcards: LabelTrie,
+}
+
+/// Reversed-label trie: `*.example.com` is stored under `com` → `example`.
+#[derive(Default)]
+struct LabelTrie {
+    children: HashMap<String, LabelTrie>,
+    /// Slots of wildcards ending at this node.
+    slots: Vec<u32>,
+}
+
+/// The route slots a request host matches.
+pub struct HostMatches<'a> {
+    /// Sorted slot lists: the exact entry, then one per matching wildcard.
+    sets: Vec<&'a [u32]>,
+}
+
+impl HostIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register `pattern` (`api.example.com` or `*.example.com`) for
+    /// `slot`. Slots must be inserted in ascending order.
+    pub fn insert(&mut self, pattern: &str, slot: u32) {
+        let pattern = pattern.to_ascii_lowercase();
+        let slots = match pattern.strip_prefix("*.") {
+            Some(suffix) => {
+                let mut node = &mut self.wildcards;
+                for label in suffix.rsplit('.') {
+                    node = node.children.entry(label.to_string()).or_default();
+                }
+                &mut node.slots
+            }
+            None => self.exact.entry(pattern).or_default(),
+        };
+        // A route listing the same host twice registers it once.
+        if slots.last() != Some(&slot) {
+            slots.push(slot);
+        }
+    }
+
+    /// The slots whose hosts match `host`.
+    ///
+    /// `*.example.com` matches `a.example.com` and `a.b.example.com`, but
+    /// neither the apex `example.com` nor `badexample.com`. `host` may be
+    /// a raw `Host` header: a `:port` and a trailing dot are ignored.
+    pub fn lookup(&self, host: &str) -> HostMatches<'_> {
+        let host = bare_host(host);
+        let host = if host.bytes().any(|b| b.is_ascii_uppercase()) {
+            Cow::Owned(host.to_ascii_lowercase())
+        } else {
+            Cow::Borrowed(host)
+        };
+        let mut sets = Vec::new();
+        if let Some(slots) = self.exact.get(host.as_ref()) {
+            sets.push(slots.as_slice());
+        }
+        let mut node = &self.wildcards;
+        let mut labels = host.rsplit('.').peekable();
+        while let Some(label) = labels.next() {
+            let Some(child) = node.children.get(label) else {
+                break;
+            };
+            node = child;
+            // A wildcard needs at least one label in front of its suffix.
+            if labels.peek().is_some_and(|l| !l.is_empty()) && !node.slots.is_empty() {
+                sets.push(&node.slots);
+            }
+        }
+        HostMatches { sets }
+    }
+}
+
+/// `host` without a `:port` suffix or trailing dot. A bracketed IPv6
+/// literal keeps its brackets and the colons inside them.
+fn bare_host(host: &str) -> &str {
+    let host = match host.strip_prefix('[') {
+        Some(rest) => rest.find(']').map_or(host, |end| &host[..end + 2]),
+        None => host
+            .rsplit_once(':')
+            .filter(|(_, port)| port.bytes().all(|b| b.is_ascii_digit()))
+            .map_or(host, |(name, _)| name),
+    };
+    host.strip_suffix('.').unwrap_or(host)
+}
+
+impl HostMatches<'_> {
+    /// Whether the host matched a pattern registered for `slot`.
+    #[inline]
+    pub fn contains(&self, slot: u32) -> bool {
+        self.sets.iter().any(|s| s.binary_search(&slot).is_ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(patterns: &[(&str, u32)]) -> HostIndex {
+        let mut index = HostIndex::new();
+        for (pattern, slot) in patterns {
+            index.insert(pattern, *slot);
+        }
+        index
+    }
+
+    #[test]
+    fn exact_hosts_match_case_insensitively() {
+        let index = index(&[("api.example.com", 0), ("API.example.com", 1)]);
+        let m = index.lookup("Api.Example.Com");
+        assert!(m.contains(0) && m.contains(1));
+        assert!(!index.lookup("example.com").contains(0));
+    }
+
+    #[test]
+    fn wildcard_needs_a_label_in_front_of_its_suffix() {
+        let index = index(&[("*.example.com", 0)]);
+        assert!(index.lookup("a.example.com").contains(0));
+        assert!(index.lookup("a.b.example.com").contains(0));
+        for host in [
+            "example.com",
+            "badexample.com",
+            ".example.com",
+            "example.com.evil",
+        ] {
+            assert!(!index.lookup(host).contains(0), "{host}");
+        }
+    }
+
+    #[test]
+    fn nested_wildcards_all_match() {
+        let index = index(&[("*.com", 0), ("*.example.com", 1), ("a.example.com", 2)]);
+        let m = index.lookup("a.example.com");
+        assert!(m.contains(0) && m.contains(1) && m.contains(2));
+        let m = index.lookup("example.com");
+        assert!(m.contains(0) && !m.contains(1));
+    }
+
+    #[test]
+    fn port_and_trailing_dot_are_ignored() {
+        let index = index(&[("api.example.com", 0), ("*.example.org", 1), ("[::1]", 2)]);
+        for host in [
+            "api.example.com:8443",
+            "api.example.com.",
+            "API.example.com.:80",
+        ] {
+            assert!(index.lookup(host).contains(0), "{host}");
+        }
+        assert!(index.lookup("a.example.org:9080").contains(1));
+        assert!(index.lookup("[::1]:9080").contains(2));
+        assert!(index.lookup("[::1]").contains(2));
+        assert!(!index.lookup("api.example.com:x").contains(0));
+    }
+
+    #[test]
+    fn repeated_host_is_registered_once() {
+        let index = index(&[("a.com", 3), ("a.com", 3), ("a.com", 7)]);
+        assert_eq!(index.exact["a.com"], [3, 7]);
+    }
+}
@@ -1,9 +1,16 @@
+pub mod balancer;
 pub mod config;
+mod config_file;
 pub mod consumer;
 pub mod error;
+pub mod error_page;
+pub mod global_rule;
+pub mod host_index;
 pub mod plugin_config;
 pub mod route;
 pub mod router;
 pub mod service;
+pub mod shutdown;
 pub mod ssl;
 pub mod upstream;
+pub mod vars;
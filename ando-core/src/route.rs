@@ -7,8 +7,13 @@ pub struct Route {
     pub id: String,
 
     /// URI path pattern (e.g. "/api/v1/*" or "/exact/path").
+    #[serde(default)]
     pub uri: String,
 
+    /// Additional URI patterns; the route matches any of `uri` and `uris`.
+    #[serde(default)]
+    pub uris: Vec<String>,
+
     /// HTTP methods (empty = all methods).
     #[serde(default)]
     pub methods: Vec<String>,
@@ -17,6 +22,31 @@ pub struct Route {
     #[serde(default)]
     pub hosts: Vec<String>,
 
+    /// Client IPs or CIDRs allowed to match this route (empty = any).
+    #[serde(default)]
+    pub remote_addrs: Vec<String>,
+
+    /// Names of the `proxy.listeners` this route is served on (empty =
+    /// all of them).
+    #[serde(default)]
+    pub listeners: Vec<String>,
+
+    /// Extra match conditions, e.g. `[["http_x_env", "==", "staging"]]`.
+    /// All must hold. See `vars::VarExpr` for the supported syntax.
+    #[serde(default)]
+    pub vars: Vec<Vec<serde_json::Value>>,
+
+    /// Query-argument conditions in the `vars` syntax, naming the argument
+    /// instead of a variable: `[["engine", "==", "beta"]]`. Values are
+    /// percent-decoded before comparison. All must hold.
+    #[serde(default)]
+    pub query: Vec<Vec<serde_json::Value>>,
+
+    /// Cookie conditions, like `query` but naming a cookie:
+    /// `[["canary", "==", "1"]]`. All must hold.
+    #[serde(default)]
+    pub cookie: Vec<Vec<serde_json::Value>>,
+
     /// Inline upstream definition.
     pub upstream: Option<crate::upstream::Upstream>,
 
@@ -51,6 +81,38 @@ pub struct Route {
     #[serde(default)]
     pub strip_prefix: bool,
 
+    /// Let WebSocket handshakes through: the `Upgrade` headers are
+    /// forwarded and, once the upstream answers 101, the connection is
+    /// relayed both ways until either side closes.
+    #[serde(default)]
+    pub enable_websocket: bool,
+
+    /// Treat every response as a long-lived stream, as is done for
+    /// `text/event-stream` ones: bytes are relayed as they arrive, under
+    /// `proxy.streaming_idle_timeout_ms` instead of the read timeout, and
+    /// body-filter plugins leave them alone.
+    #[serde(default)]
+    pub streaming: bool,
+
+    /// Export OpenTelemetry spans for this route. Set to `false` on
+    /// high-rate routes that shouldn't be traced; their requests still
+    /// forward the client's `traceparent` unchanged.
+    #[serde(default = "default_tracing")]
+    pub tracing: bool,
+
+    /// Upstream timeouts for this route, over those of its upstream.
+    pub timeout: Option<crate::upstream::Timeout>,
+
+    /// Largest request body in bytes, over `proxy.max_body_size`. Bigger
+    /// requests get a 413.
+    #[serde(default)]
+    pub max_body_size: Option<u64>,
+
+    /// Decode gzip, deflate and br request bodies before plugins and the
+    /// upstream see them.
+    #[serde(default)]
+    pub request_decompression: Option<RequestDecompression>,
+
     /// Human-readable name.
     pub name: Option<String>,
 
@@ -62,16 +124,44 @@ pub struct Route {
     pub labels: HashMap<String, String>,
 }
 
+/// Route `request_decompression` settings.
+///
+/// A buffered request body with `Content-Encoding: gzip`, `deflate` or
+/// `br` is decoded, and forwarded without the header and with its decoded
+/// length. Corrupt bodies get a 400, bodies decoding past
+/// `max_decompressed_size` a 413.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestDecompression {
+    /// Largest decoded body in bytes.
+    #[serde(default = "default_max_decompressed_size")]
+    pub max_decompressed_size: u64,
+}
+
+fn default_max_decompressed_size() -> u64 {
+    10 * 1024 * 1024
+}
+
 fn default_status() -> u8 {
     1
 }
 
+fn default_tracing() -> bool {
+    true
+}
+
 impl Route {
     /// Returns true if this route has any plugins (from route-level config).
     pub fn has_plugins(&self) -> bool {
         !self.plugins.is_empty() || self.plugin_config_id.is_some() || self.service_id.is_some()
     }
 
+    /// All URI patterns of this route: `uri` (if set) followed by `uris`.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.uri.as_str())
+            .filter(|u| !u.is_empty())
+            .chain(self.uris.iter().map(String::as_str))
+    }
+
     /// Check if a given HTTP method is allowed.
     pub fn matches_method(&self, method: &str) -> bool {
         self.methods.is_empty() || self.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
@@ -86,8 +176,14 @@ mod tests {
         Route {
             id: "test".into(),
             uri: uri.into(),
+            uris: vec![],
             methods: methods.into_iter().map(|s| s.to_string()).collect(),
             hosts: vec![],
+            listeners: vec![],
+            remote_addrs: vec![],
+            vars: vec![],
+            query: vec![],
+            cookie: vec![],
             upstream: None,
             upstream_id: None,
             service_id: None,
@@ -96,6 +192,12 @@ mod tests {
             priority: 0,
             status: 1,
             strip_prefix: false,
+            enable_websocket: false,
+            streaming: false,
+            tracing: true,
+            timeout: None,
+            max_body_size: None,
+            request_decompression: None,
             name: None,
             desc: None,
             labels: Default::default(),
@@ -128,6 +230,16 @@ mod tests {
         assert!(route.matches_method("GET"));
     }
 
+    #[test]
+    fn test_paths_combines_uri_and_uris() {
+        let mut route = make_route("/a", vec![]);
+        route.uris = vec!["/b".into(), "/c/*".into()];
+        assert_eq!(route.paths().collect::<Vec<_>>(), ["/a", "/b", "/c/*"]);
+
+        route.uri.clear();
+        assert_eq!(route.paths().collect::<Vec<_>>(), ["/b", "/c/*"]);
+    }
+
     #[test]
     fn test_has_plugins_empty() {
         let route = make_route("/api", vec![]);
@@ -181,6 +293,33 @@ mod tests {
         assert_eq!(decoded.plugins.len(), 1);
     }
 
+    #[test]
+    fn test_enable_websocket_defaults_off() {
+        let route: Route = serde_json::from_str(r#"{"id":"r1","uri":"/ws"}"#).unwrap();
+        assert!(!route.enable_websocket);
+        let route: Route =
+            serde_json::from_str(r#"{"id":"r1","uri":"/ws","enable_websocket":true}"#).unwrap();
+        assert!(route.enable_websocket);
+    }
+
+    #[test]
+    fn test_streaming_defaults_off() {
+        let route: Route = serde_json::from_str(r#"{"id":"r1","uri":"/sse"}"#).unwrap();
+        assert!(!route.streaming);
+        let route: Route =
+            serde_json::from_str(r#"{"id":"r1","uri":"/sse","streaming":true}"#).unwrap();
+        assert!(route.streaming);
+    }
+
+    #[test]
+    fn test_tracing_defaults_on() {
+        let route: Route = serde_json::from_str(r#"{"id":"r1","uri":"/a"}"#).unwrap();
+        assert!(route.tracing);
+        let route: Route =
+            serde_json::from_str(r#"{"id":"r1","uri":"/a","tracing":false}"#).unwrap();
+        assert!(!route.tracing);
+    }
+
     #[test]
     fn test_status_zero_disabled() {
         let json = r#"{"id":"r1","uri":"/test","status":0}"#;
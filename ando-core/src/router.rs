@@ -1,5 +1,9 @@
+use crate::host_index::{HostIndex, HostMatches};
 use crate::route::Route;
+use crate::vars::{VarExpr, cookie_value, percent_decode, query_arg};
+use ipnet::IpNet;
 use std::collections::HashMap;
+use std::net::IpAddr;
 use tracing::info;
 
 /// Thread-safe radix-trie router.
@@ -8,64 +12,172 @@ use tracing::info;
 /// a new Router is built and swapped in atomically via `arc_swap::ArcSwap`.
 /// This eliminates all locking from the hot path — each worker core reads
 /// the current Arc<Router> via a single atomic load.
+///
+/// Several routes may share a path (e.g. differing only by `listeners`,
+/// `remote_addrs`, `vars`, `query` or `cookie`). Each trie leaf holds all of them, ordered
+/// by priority with conditioned routes first on a tie, and the first one
+/// whose host and conditions match wins. Route `hosts` are
+/// compiled into one [`HostIndex`] and the request host is looked up once.
 pub struct Router {
     /// matchit trie for each HTTP method.
-    method_trees: HashMap<String, matchit::Router<String>>,
+    method_trees: HashMap<String, matchit::Router<Vec<Candidate>>>,
     /// Catch-all tree (for routes with no method filter).
-    any_tree: matchit::Router<String>,
+    any_tree: matchit::Router<Vec<Candidate>>,
     /// All routes keyed by ID.
     routes: HashMap<String, Route>,
+    /// Pre-compiled `remote_addrs` / `vars` / `query` / `cookie` for routes
+    /// that declare them.
+    conditions: HashMap<String, RouteConditions>,
+    /// `hosts` of all routes, keyed by the routes' host slots.
+    hosts: HostIndex,
     /// Monotonic version — bumped on every rebuild.
     version: u64,
 }
 
+/// Request data beyond method, path and host, used by route conditions.
+/// The query string and `Cookie` headers are only parsed for routes whose
+/// conditions read them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MatchContext<'a> {
+    /// Name of the listener the connection arrived on, checked against
+    /// route `listeners`.
+    pub listener: &'a str,
+    pub client_ip: &'a str,
+    pub headers: &'a [(&'a str, &'a str)],
+    /// Raw query string (without the leading `?`).
+    pub query: Option<&'a str>,
+}
+
+/// A matched route with the pattern that matched and its path parameters.
+#[derive(Debug)]
+pub struct RouteMatch<'r, 'p> {
+    pub route: &'r Route,
+    /// The `uri` / `uris` entry that matched.
+    pub uri: &'r str,
+    /// Captured parameters, e.g. `("id", "42")` or `("*", "a/b/c")`.
+    pub params: Vec<(&'r str, &'p str)>,
+}
+
+impl RouteMatch<'_, '_> {
+    /// Look up a captured parameter by name.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(k, _)| *k == name)
+            .map(|(_, v)| *v)
+    }
+}
+
+/// Compiled extra match conditions of a route.
+struct RouteConditions {
+    remote_addrs: Vec<IpNet>,
+    vars: Vec<VarExpr>,
+    /// Conditions on query arguments, each naming its argument.
+    query: Vec<VarExpr>,
+    /// Conditions on cookies, each naming its cookie.
+    cookie: Vec<VarExpr>,
+}
+
+/// A route registered under a trie path.
+struct Candidate {
+    id: String,
+    /// The route pattern (`uri` or one of `uris`) this entry came from.
+    uri: String,
+    priority: i32,
+    /// Registered from a `/*` pattern (ranks after exact entries).
+    wildcard: bool,
+    /// Trailing-slash alias of a wildcard route (ranks after real entries).
+    alias: bool,
+    /// The route's slot in the host index; `None` when it takes any host.
+    host_slot: Option<u32>,
+    /// The route has match conditions (ranks before routes without on a
+    /// priority tie).
+    conditioned: bool,
+}
+
 impl Router {
     /// Build a new frozen router from a set of routes.
     pub fn build(routes: Vec<Route>, version: u64) -> anyhow::Result<Self> {
-        let mut method_trees: HashMap<String, matchit::Router<String>> = HashMap::new();
-        let mut any_tree = matchit::Router::new();
+        let mut method_paths: HashMap<String, HashMap<String, Vec<Candidate>>> = HashMap::new();
+        let mut any_paths: HashMap<String, Vec<Candidate>> = HashMap::new();
         let mut route_map = HashMap::with_capacity(routes.len());
+        let mut conditions = HashMap::new();
+        let mut hosts = HostIndex::new();
+        let mut next_slot = 0;
 
         for route in routes {
             if route.status == 0 {
                 continue; // skip disabled routes
             }
 
-            let path = normalize_path(&route.uri);
-
-            // For wildcard routes (e.g. /api/v1/*) matchit's {*rest} catch-all
-            // does NOT match an empty capture, so /api/v1/ would 404. We also
-            // register the trailing-slash base path as an exact entry so that
-            // both /api/v1/ and /api/v1/anything are handled by the same route.
-            let base_slash = if route.uri.ends_with("/*") && route.uri.len() > 2 {
-                // "/api/v1/*"  →  "/api/v1/"
-                Some(route.uri[..route.uri.len() - 1].to_string())
-            } else if route.uri == "/*" {
-                // "/*" → "/"  (already covered by a plain "/" exact match would
-                // conflict, so skip — "/" is handled by the catch-all directly)
-                None
-            } else {
-                None
-            };
-
-            if route.methods.is_empty() {
-                // Match any method
-                if let Err(e) = any_tree.insert(&path, route.id.clone()) {
-                    tracing::warn!(route_id = %route.id, path = %path, "Failed to insert catch-all route: {e}");
+            let conditioned = !route.listeners.is_empty()
+                || !route.remote_addrs.is_empty()
+                || !route.vars.is_empty()
+                || !route.query.is_empty()
+                || !route.cookie.is_empty();
+            if conditioned {
+                match RouteConditions::compile(&route) {
+                    Ok(c) => {
+                        conditions.insert(route.id.clone(), c);
+                    }
+                    Err(e) => {
+                        tracing::warn!(route_id = %route.id, "Skipping route with invalid match conditions: {e}");
+                        continue;
+                    }
                 }
-                if let Some(ref bp) = base_slash {
-                    // Ignore conflict errors — a more-specific exact route may already own this path
-                    let _ = any_tree.insert(bp, route.id.clone());
+            }
+
+            let host_slot = (!route.hosts.is_empty()).then(|| {
+                let slot = next_slot;
+                next_slot += 1;
+                for host in &route.hosts {
+                    hosts.insert(host, slot);
                 }
-            } else {
-                for method in &route.methods {
-                    let method_upper = method.to_uppercase();
-                    let tree = method_trees.entry(method_upper).or_default();
-                    if let Err(e) = tree.insert(&path, route.id.clone()) {
-                        tracing::warn!(route_id = %route.id, method = %method, path = %path, "Failed to insert route: {e}");
-                    }
+                slot
+            });
+
+            for uri in route.paths() {
+                let path = normalize_path(uri);
+                let wildcard = uri.ends_with('*');
+
+                // For wildcard routes (e.g. /api/v1/*) matchit's {*rest} catch-all
+                // does NOT match an empty capture, so /api/v1/ would 404. We also
+                // register the trailing-slash base path so that both /api/v1/ and
+                // /api/v1/anything are handled by the same route.
+                let base_slash = if uri.ends_with("/*") && uri.len() > 2 {
+                    // "/api/v1/*"  →  "/api/v1/"
+                    Some(uri[..uri.len() - 1].to_string())
+                } else {
+                    // "/*" → "/" is handled by the catch-all directly
+                    None
+                };
+
+                let register = |paths: &mut HashMap<String, Vec<Candidate>>| {
+                    let mut push = |path: &String, alias: bool| {
+                        let list = paths.entry(path.clone()).or_default();
+                        // `uri` and `uris` may overlap; keep the first entry.
+                        if !list.iter().any(|c| c.id == route.id) {
+                            list.push(Candidate {
+                                id: route.id.clone(),
+                                uri: uri.to_string(),
+                                priority: route.priority,
+                                wildcard,
+                                alias,
+                                host_slot,
+                                conditioned,
+                            });
+                        }
+                    };
+                    push(&path, false);
                     if let Some(ref bp) = base_slash {
-                        let _ = tree.insert(bp, route.id.clone());
+                        push(bp, true);
+                    }
+                };
+                if route.methods.is_empty() {
+                    register(&mut any_paths);
+                } else {
+                    for method in &route.methods {
+                        register(method_paths.entry(method.to_uppercase()).or_default());
                     }
                 }
             }
@@ -73,12 +185,20 @@ impl Router {
             route_map.insert(route.id.clone(), route);
         }
 
+        let mut method_trees = HashMap::with_capacity(method_paths.len());
+        for (method, paths) in method_paths {
+            method_trees.insert(method, build_tree(paths));
+        }
+        let any_tree = build_tree(any_paths);
+
         info!(routes = route_map.len(), version, "Router built");
 
         Ok(Self {
             method_trees,
             any_tree,
             routes: route_map,
+            conditions,
+            hosts,
             version,
         })
     }
@@ -89,34 +209,118 @@ impl Router {
     /// the route_id String and allocates a Vec for params on every match,
     /// this returns `&Route` directly. The caller can access `route.id`
     /// and other fields without any allocation.
+    ///
+    /// Candidates whose host, `remote_addrs` or `vars` do not match are
+    /// skipped in favour of the next one on the same path. Use
+    /// [`match_route_params`](Self::match_route_params) when the captured
+    /// path parameters are needed.
     #[inline]
-    pub fn match_route(&self, method: &str, path: &str, host: Option<&str>) -> Option<&Route> {
-        // Try method-specific tree first
-        if let Some(tree) = self.method_trees.get(method)
-            && let Ok(matched) = tree.at(path)
-        {
-            let route_id = matched.value.as_str();
-            if let Some(route) = self.routes.get(route_id)
-                && check_host(route, host)
-            {
-                return Some(route);
-            }
-        }
+    pub fn match_route<'a>(
+        &self,
+        method: &'a str,
+        path: &'a str,
+        host: Option<&'a str>,
+        ctx: &MatchContext<'a>,
+    ) -> Option<&Route> {
+        self.find(method, path, host, ctx)
+            .map(|(route, _, _)| route)
+    }
 
-        // Try catch-all (any method) tree
-        if let Ok(matched) = self.any_tree.at(path) {
-            let route_id = matched.value.as_str();
-            if let Some(route) = self.routes.get(route_id) {
-                if !route.matches_method(method) {
-                    return None;
-                }
-                if check_host(route, host) {
-                    return Some(route);
-                }
+    /// Like [`match_route`](Self::match_route), but also returns the
+    /// matched pattern and captured path parameters. A `/*` wildcard's
+    /// remainder is reported under the name `*`.
+    pub fn match_route_params<'r, 'p>(
+        &'r self,
+        method: &'p str,
+        path: &'p str,
+        host: Option<&'p str>,
+        ctx: &MatchContext<'p>,
+    ) -> Option<RouteMatch<'r, 'p>> {
+        let (route, candidate, params) = self.find(method, path, host, ctx)?;
+        let params = params
+            .iter()
+            .map(|(k, v)| (if k == "rest" { "*" } else { k }, v))
+            .collect();
+        Some(RouteMatch {
+            route,
+            uri: &candidate.uri,
+            params,
+        })
+    }
+
+    fn find<'r, 'p>(
+        &'r self,
+        method: &'p str,
+        path: &'p str,
+        host: Option<&'p str>,
+        ctx: &MatchContext<'p>,
+    ) -> Option<(&'r Route, &'r Candidate, matchit::Params<'r, 'p>)> {
+        let specific = self
+            .method_trees
+            .get(method)
+            .and_then(|tree| tree.at(path).ok());
+        let any = self.any_tree.at(path).ok();
+        let (specific_list, any_list) = (
+            specific.as_ref().map_or(&[][..], |m| m.value.as_slice()),
+            any.as_ref().map_or(&[][..], |m| m.value.as_slice()),
+        );
+
+        // Both lists are sorted by priority; walk them merged so a
+        // higher-priority any-method route beats a method-specific one.
+        // On a tie an exact path beats a wildcard, then a conditioned route
+        // beats an unconditioned one, then the method-specific route wins.
+        let rank = |c: &Candidate| (-(c.priority as i64), c.wildcard, !c.conditioned);
+        // Looked up on the first candidate that restricts hosts.
+        let mut host_matches: Option<HostMatches<'_>> = None;
+        let (mut i, mut j) = (0, 0);
+        loop {
+            let take_specific = match (specific_list.get(i), any_list.get(j)) {
+                (Some(a), Some(b)) => rank(a) <= rank(b),
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return None,
+            };
+            let (candidate, matched) = if take_specific {
+                i += 1;
+                (&specific_list[i - 1], specific.as_ref())
+            } else {
+                j += 1;
+                (&any_list[j - 1], any.as_ref())
+            };
+            let host_ok = match (candidate.host_slot, host) {
+                (None, _) => true,
+                (Some(_), None) => false,
+                (Some(slot), Some(h)) => host_matches
+                    .get_or_insert_with(|| self.hosts.lookup(h))
+                    .contains(slot),
+            };
+            if host_ok
+                && let Some(route) = self.routes.get(&candidate.id)
+                && self.accepts(route, method, path, host, ctx)
+                && let Some(m) = matched
+            {
+                return Some((route, candidate, m.params.clone()));
             }
         }
+    }
 
-        None
+    /// `listeners` / `remote_addrs` / `vars` / `query` / `cookie` checks
+    /// for a path candidate.
+    #[inline]
+    fn accepts<'a>(
+        &self,
+        route: &Route,
+        method: &'a str,
+        path: &'a str,
+        host: Option<&'a str>,
+        ctx: &MatchContext<'a>,
+    ) -> bool {
+        (route.listeners.is_empty() || route.listeners.iter().any(|l| l == ctx.listener))
+            && (self.conditions.is_empty()
+                || self
+                    .conditions
+                    .get(&route.id)
+                    .is_none_or(|c| c.matches(method, path, host, ctx)))
     }
 
     /// Get a route by ID.
@@ -147,17 +351,129 @@ impl Router {
     }
 }
 
-/// Check host filtering. Returns true if the route allows the given host
-/// (or has no host restriction).
-#[inline]
-fn check_host(route: &Route, host: Option<&str>) -> bool {
-    if route.hosts.is_empty() {
-        return true;
+/// Check that a route's `remote_addrs`, `vars`, `query` and `cookie`
+/// conditions compile, so invalid ones can be rejected at write time
+/// instead of silently skipped.
+pub fn validate_conditions(route: &Route) -> Result<(), String> {
+    RouteConditions::compile(route).map(|_| ())
+}
+
+impl RouteConditions {
+    fn compile(route: &Route) -> Result<Self, String> {
+        let remote_addrs = route
+            .remote_addrs
+            .iter()
+            .map(|a| {
+                a.parse::<IpNet>()
+                    .or_else(|_| a.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("invalid remote_addr `{a}`"))
+            })
+            .collect::<Result<_, _>>()?;
+        let vars = VarExpr::compile_all(&route.vars)?;
+        let query = VarExpr::compile_all(&route.query).map_err(|e| format!("query: {e}"))?;
+        let cookie = VarExpr::compile_all(&route.cookie).map_err(|e| format!("cookie: {e}"))?;
+        Ok(Self {
+            remote_addrs,
+            vars,
+            query,
+            cookie,
+        })
+    }
+
+    fn matches<'a>(
+        &self,
+        method: &'a str,
+        path: &'a str,
+        host: Option<&'a str>,
+        ctx: &MatchContext<'a>,
+    ) -> bool {
+        if !self.remote_addrs.is_empty() {
+            let Ok(ip) = ctx.client_ip.parse::<IpAddr>() else {
+                return false;
+            };
+            if !self.remote_addrs.iter().any(|net| net.contains(&ip)) {
+                return false;
+            }
+        }
+        self.vars
+            .iter()
+            .all(|v| v.matches(|name| lookup_var(name, method, path, host, ctx)))
+            && self.query.iter().all(|q| {
+                let value = ctx.query.and_then(|query| query_arg(query, q.var()));
+                q.matches_value(value.map(percent_decode).as_deref())
+            })
+            && self
+                .cookie
+                .iter()
+                .all(|c| c.matches_value(request_cookie(ctx, c.var())))
+    }
+}
+
+/// A cookie from the request's `Cookie` headers.
+fn request_cookie<'a>(ctx: &MatchContext<'a>, name: &str) -> Option<&'a str> {
+    ctx.headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("cookie"))
+        .find_map(|(_, v)| cookie_value(v, name))
+}
+
+/// Resolve an APISIX variable name against the request.
+fn lookup_var<'a>(
+    name: &str,
+    method: &'a str,
+    path: &'a str,
+    host: Option<&'a str>,
+    ctx: &MatchContext<'a>,
+) -> Option<&'a str> {
+    match name {
+        "remote_addr" => Some(ctx.client_ip),
+        "uri" => Some(path.split_once('?').map_or(path, |(p, _)| p)),
+        "host" => host,
+        "request_method" => Some(method),
+        _ => {
+            if let Some(header) = name.strip_prefix("http_") {
+                ctx.headers
+                    .iter()
+                    .find(|(k, _)| header_name_eq(k, header))
+                    .map(|(_, v)| *v)
+            } else if let Some(arg) = name.strip_prefix("arg_") {
+                query_arg(ctx.query?, arg)
+            } else if let Some(cookie) = name.strip_prefix("cookie_") {
+                request_cookie(ctx, cookie)
+            } else {
+                None
+            }
+        }
     }
-    match host {
-        Some(h) => route.hosts.iter().any(|rh| rh == h),
-        None => false,
+}
+
+/// Compare a header name with its `http_` variable form, where `_` stands
+/// for `-` (`x_api_key` matches `X-Api-Key`).
+#[inline]
+fn header_name_eq(header: &str, var: &str) -> bool {
+    header.len() == var.len()
+        && header
+            .bytes()
+            .zip(var.bytes())
+            .all(|(h, v)| h.eq_ignore_ascii_case(&v) || (h == b'-' && v == b'_'))
+}
+
+/// Insert grouped candidates into a matchit trie, best candidate first.
+fn build_tree(paths: HashMap<String, Vec<Candidate>>) -> matchit::Router<Vec<Candidate>> {
+    let mut tree = matchit::Router::new();
+    for (path, mut candidates) in paths {
+        candidates.sort_by(|a, b| {
+            b.priority
+                .cmp(&a.priority)
+                .then(a.alias.cmp(&b.alias))
+                .then(b.conditioned.cmp(&a.conditioned))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        if let Err(e) = tree.insert(&path, candidates) {
+            tracing::warn!(path = %path, "Failed to insert route: {e}");
+        }
     }
+    tree
 }
 
 /// Normalize path for matchit compatibility.
@@ -180,8 +496,14 @@ mod tests {
         Route {
             id: id.to_string(),
             uri: uri.to_string(),
+            uris: vec![],
             methods: methods.into_iter().map(|s| s.to_string()).collect(),
             hosts: vec![],
+            listeners: vec![],
+            remote_addrs: vec![],
+            vars: vec![],
+            query: vec![],
+            cookie: vec![],
             upstream: None,
             upstream_id: None,
             service_id: None,
@@ -190,6 +512,12 @@ mod tests {
             priority: 0,
             status: 1,
             strip_prefix: false,
+            enable_websocket: false,
+            streaming: false,
+            tracing: true,
+            timeout: None,
+            max_body_size: None,
+            request_decompression: None,
             name: None,
             desc: None,
             labels: Default::default(),
@@ -205,16 +533,26 @@ mod tests {
         ];
         let router = Router::build(routes, 1).unwrap();
 
-        let route = router.match_route("GET", "/api/v1/users", None).unwrap();
+        let route = router
+            .match_route("GET", "/api/v1/users", None, &MatchContext::default())
+            .unwrap();
         assert_eq!(route.id, "r1");
 
-        let route = router.match_route("POST", "/api/v1/users", None).unwrap();
+        let route = router
+            .match_route("POST", "/api/v1/users", None, &MatchContext::default())
+            .unwrap();
         assert_eq!(route.id, "r2");
 
-        let route = router.match_route("GET", "/health", None).unwrap();
+        let route = router
+            .match_route("GET", "/health", None, &MatchContext::default())
+            .unwrap();
         assert_eq!(route.id, "r3");
 
-        assert!(router.match_route("GET", "/not/found", None).is_none());
+        assert!(
+            router
+                .match_route("GET", "/not/found", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -222,7 +560,9 @@ mod tests {
         let routes = vec![make_route("r1", "/api/*", vec!["GET"])];
         let router = Router::build(routes, 1).unwrap();
 
-        let route = router.match_route("GET", "/api/v1/anything", None).unwrap();
+        let route = router
+            .match_route("GET", "/api/v1/anything", None, &MatchContext::default())
+            .unwrap();
         assert_eq!(route.id, "r1");
     }
 
@@ -233,14 +573,18 @@ mod tests {
         let router = Router::build(routes, 1).unwrap();
         // exact base with trailing slash
         eprintln!("matching /api/v1/");
-        let r = router.match_route("GET", "/api/v1/", None);
+        let r = router.match_route("GET", "/api/v1/", None, &MatchContext::default());
         eprintln!("result: {:?}", r.map(|x| &x.id));
         assert!(r.is_some(), "/api/v1/ should match /api/v1/*");
         // path with content
-        assert!(router.match_route("GET", "/api/v1/users", None).is_some());
         assert!(
             router
-                .match_route("GET", "/api/v1/users/123", None)
+                .match_route("GET", "/api/v1/users", None, &MatchContext::default())
+                .is_some()
+        );
+        assert!(
+            router
+                .match_route("GET", "/api/v1/users/123", None, &MatchContext::default())
                 .is_some()
         );
     }
@@ -250,7 +594,11 @@ mod tests {
         let mut route = make_route("r1", "/test", vec!["GET"]);
         route.status = 0;
         let router = Router::build(vec![route], 1).unwrap();
-        assert!(router.match_route("GET", "/test", None).is_none());
+        assert!(
+            router
+                .match_route("GET", "/test", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -270,7 +618,11 @@ mod tests {
         let router = Router::build(vec![], 1).unwrap();
         assert!(router.is_empty());
         assert_eq!(router.len(), 0);
-        assert!(router.match_route("GET", "/anything", None).is_none());
+        assert!(
+            router
+                .match_route("GET", "/anything", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -289,7 +641,12 @@ mod tests {
         let router = Router::build(vec![route], 1).unwrap();
         assert!(
             router
-                .match_route("GET", "/api", Some("api.example.com"))
+                .match_route(
+                    "GET",
+                    "/api",
+                    Some("api.example.com"),
+                    &MatchContext::default()
+                )
                 .is_some()
         );
     }
@@ -301,10 +658,19 @@ mod tests {
         let router = Router::build(vec![route], 1).unwrap();
         assert!(
             router
-                .match_route("GET", "/api", Some("other.example.com"))
+                .match_route(
+                    "GET",
+                    "/api",
+                    Some("other.example.com"),
+                    &MatchContext::default()
+                )
+                .is_none()
+        );
+        assert!(
+            router
+                .match_route("GET", "/api", None, &MatchContext::default())
                 .is_none()
         );
-        assert!(router.match_route("GET", "/api", None).is_none());
     }
 
     #[test]
@@ -313,19 +679,40 @@ mod tests {
         let router = Router::build(vec![route], 1).unwrap();
         assert!(
             router
-                .match_route("GET", "/api", Some("any.host.com"))
+                .match_route(
+                    "GET",
+                    "/api",
+                    Some("any.host.com"),
+                    &MatchContext::default()
+                )
+                .is_some()
+        );
+        assert!(
+            router
+                .match_route("GET", "/api", None, &MatchContext::default())
                 .is_some()
         );
-        assert!(router.match_route("GET", "/api", None).is_some());
     }
 
     #[test]
     fn test_method_not_allowed() {
         let routes = vec![make_route("r1", "/api", vec!["GET"])];
         let router = Router::build(routes, 1).unwrap();
-        assert!(router.match_route("GET", "/api", None).is_some());
-        assert!(router.match_route("POST", "/api", None).is_none());
-        assert!(router.match_route("DELETE", "/api", None).is_none());
+        assert!(
+            router
+                .match_route("GET", "/api", None, &MatchContext::default())
+                .is_some()
+        );
+        assert!(
+            router
+                .match_route("POST", "/api", None, &MatchContext::default())
+                .is_none()
+        );
+        assert!(
+            router
+                .match_route("DELETE", "/api", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -349,17 +736,24 @@ mod tests {
         ];
         let router = Router::build(routes, 1).unwrap();
         assert_eq!(
-            router.match_route("GET", "/api/v1/users", None).unwrap().id,
+            router
+                .match_route("GET", "/api/v1/users", None, &MatchContext::default())
+                .unwrap()
+                .id,
             "r1"
         );
         assert_eq!(
             router
-                .match_route("POST", "/api/v1/tokens", None)
+                .match_route("POST", "/api/v1/tokens", None, &MatchContext::default())
                 .unwrap()
                 .id,
             "r2"
         );
-        assert!(router.match_route("GET", "/api/v1/tokens", None).is_none());
+        assert!(
+            router
+                .match_route("GET", "/api/v1/tokens", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -371,18 +765,31 @@ mod tests {
         ];
         let router = Router::build(routes, 1).unwrap();
         assert_eq!(
-            router.match_route("GET", "/resource", None).unwrap().id,
+            router
+                .match_route("GET", "/resource", None, &MatchContext::default())
+                .unwrap()
+                .id,
             "r_get"
         );
         assert_eq!(
-            router.match_route("POST", "/resource", None).unwrap().id,
+            router
+                .match_route("POST", "/resource", None, &MatchContext::default())
+                .unwrap()
+                .id,
             "r_post"
         );
         assert_eq!(
-            router.match_route("DELETE", "/resource", None).unwrap().id,
+            router
+                .match_route("DELETE", "/resource", None, &MatchContext::default())
+                .unwrap()
+                .id,
             "r_del"
         );
-        assert!(router.match_route("PUT", "/resource", None).is_none());
+        assert!(
+            router
+                .match_route("PUT", "/resource", None, &MatchContext::default())
+                .is_none()
+        );
     }
 
     #[test]
@@ -393,6 +800,479 @@ mod tests {
         assert_eq!(normalize_path("/a/b/c"), "/a/b/c");
     }
 
+    // ── remote_addrs / vars conditions ────────────────────────────
+
+    fn ctx<'a>(ip: &'a str, headers: &'a [(&'a str, &'a str)]) -> MatchContext<'a> {
+        MatchContext {
+            listener: "http",
+            client_ip: ip,
+            headers,
+            query: None,
+        }
+    }
+
+    #[test]
+    fn test_listeners_scope_routes() {
+        let mut public = make_route("public", "/api", vec![]);
+        public.listeners = vec!["public".into()];
+        let mut internal = make_route("internal", "/api", vec![]);
+        internal.listeners = vec!["internal".into()];
+        let mut metrics = make_route("metrics", "/metrics", vec![]);
+        metrics.listeners = vec!["internal".into()];
+        let open = make_route("open", "/health", vec![]);
+        let router = Router::build(vec![public, internal, metrics, open], 1).unwrap();
+        let on = |listener: &'static str, path: &str| {
+            let ctx = MatchContext {
+                listener,
+                ..MatchContext::default()
+            };
+            router
+                .match_route("GET", path, None, &ctx)
+                .map(|r| r.id.clone())
+        };
+
+        assert_eq!(on("public", "/api").as_deref(), Some("public"));
+        assert_eq!(on("internal", "/api").as_deref(), Some("internal"));
+        assert_eq!(on("internal", "/metrics").as_deref(), Some("metrics"));
+        assert!(on("public", "/metrics").is_none());
+        // Routes without `listeners` are served on every listener.
+        assert_eq!(on("public", "/health").as_deref(), Some("open"));
+        assert_eq!(on("internal", "/health").as_deref(), Some("open"));
+    }
+
+    #[test]
+    fn test_remote_addrs_cidr_and_single_ip() {
+        let mut internal = make_route("internal", "/admin", vec![]);
+        internal.remote_addrs = vec!["10.0.0.0/8".into(), "192.168.1.5".into()];
+        let router = Router::build(vec![internal], 1).unwrap();
+
+        assert!(
+            router
+                .match_route("GET", "/admin", None, &ctx("10.20.30.40", &[]))
+                .is_some()
+        );
+        assert!(
+            router
+                .match_route("GET", "/admin", None, &ctx("192.168.1.5", &[]))
+                .is_some()
+        );
+        assert!(
+            router
+                .match_route("GET", "/admin", None, &ctx("192.168.1.6", &[]))
+                .is_none()
+        );
+        assert!(
+            router
+                .match_route("GET", "/admin", None, &ctx("not-an-ip", &[]))
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_same_uri_routes_differ_by_remote_addrs_fall_through() {
+        let mut internal = make_route("internal", "/api", vec!["GET"]);
+        internal.remote_addrs = vec!["10.0.0.0/8".into()];
+        internal.priority = 10;
+        let public = make_route("public", "/api", vec!["GET"]);
+        let router = Router::build(vec![public, internal], 1).unwrap();
+
+        let m = |ip| {
+            router
+                .match_route("GET", "/api", None, &ctx(ip, &[]))
+                .unwrap()
+                .id
+                .clone()
+        };
+        assert_eq!(m("10.1.1.1"), "internal");
+        assert_eq!(m("8.8.8.8"), "public");
+    }
+
+    #[test]
+    fn test_vars_header_equality_and_regex() {
+        let mut staging = make_route("staging", "/svc", vec![]);
+        staging.vars = vec![vec!["http_x_env".into(), "==".into(), "staging".into()]];
+        staging.priority = 2;
+        let mut versioned = make_route("v2", "/svc", vec![]);
+        versioned.vars = vec![vec![
+            "http_x_api_version".into(),
+            "~~".into(),
+            "^2\\.".into(),
+        ]];
+        versioned.priority = 1;
+        let fallback = make_route("default", "/svc", vec![]);
+        let router = Router::build(vec![fallback, versioned, staging], 1).unwrap();
+
+        let m = |headers: &[(&str, &str)]| {
+            router
+                .match_route("GET", "/svc", None, &ctx("1.1.1.1", headers))
+                .unwrap()
+                .id
+                .clone()
+        };
+        assert_eq!(m(&[("X-Env", "staging")]), "staging");
+        assert_eq!(m(&[("x-api-version", "2.1")]), "v2");
+        assert_eq!(m(&[("x-api-version", "1.9")]), "default");
+        assert_eq!(
+            m(&[("X-Env", "staging"), ("x-api-version", "2.0")]),
+            "staging"
+        );
+        assert_eq!(m(&[]), "default");
+    }
+
+    #[test]
+    fn test_vars_query_arg_and_cookie() {
+        let mut beta = make_route("beta", "/app", vec![]);
+        beta.vars = vec![
+            vec!["arg_channel".into(), "==".into(), "beta".into()],
+            vec![
+                "cookie_tier".into(),
+                "in".into(),
+                serde_json::json!(["gold", "silver"]),
+            ],
+        ];
+        let router = Router::build(vec![beta], 1).unwrap();
+        let headers = [("cookie", "a=1; tier=gold")];
+        let hit = MatchContext {
+            listener: "http",
+            client_ip: "1.1.1.1",
+            headers: &headers,
+            query: Some("x=1&channel=beta"),
+        };
+        assert!(router.match_route("GET", "/app", None, &hit).is_some());
+        let miss = MatchContext {
+            query: Some("channel=stable"),
+            ..hit
+        };
+        assert!(router.match_route("GET", "/app", None, &miss).is_none());
+    }
+
+    fn search_router() -> Router {
+        let mut beta = make_route("beta", "/search", vec![]);
+        beta.query = vec![vec!["engine".into(), "==".into(), "beta".into()]];
+        let mut canary = make_route("canary", "/search", vec![]);
+        canary.cookie = vec![vec!["canary".into(), "==".into(), "1".into()]];
+        canary.priority = -1;
+        // Sorts ahead of both by id; loses the tie to conditioned routes.
+        let default = make_route("a-default", "/search", vec![]);
+        Router::build(vec![default, canary, beta], 1).unwrap()
+    }
+
+    fn search<'a>(router: &'a Router, query: Option<&str>, headers: &[(&str, &str)]) -> &'a str {
+        let ctx = MatchContext {
+            listener: "http",
+            client_ip: "1.1.1.1",
+            headers,
+            query,
+        };
+        &router.match_route("GET", "/search", None, &ctx).unwrap().id
+    }
+
+    #[test]
+    fn test_query_condition_routes_beta_elsewhere() {
+        let router = search_router();
+        assert_eq!(search(&router, Some("q=rust&engine=beta"), &[]), "beta");
+        assert_eq!(search(&router, Some("engine=stable"), &[]), "a-default");
+        assert_eq!(search(&router, None, &[]), "a-default");
+    }
+
+    #[test]
+    fn test_query_condition_values_are_percent_decoded() {
+        let mut route = make_route("spaced", "/q", vec![]);
+        route.query = vec![vec!["tag".into(), "==".into(), "café au lait".into()]];
+        let router = Router::build(vec![route], 1).unwrap();
+        let hit = |query| {
+            let ctx = MatchContext {
+                query: Some(query),
+                ..MatchContext::default()
+            };
+            router.match_route("GET", "/q", None, &ctx).is_some()
+        };
+        assert!(hit("tag=caf%C3%A9+au%20lait"));
+        assert!(!hit("tag=caf%C3%A9"));
+    }
+
+    #[test]
+    fn test_cookie_condition_and_missing_cookie_falls_through() {
+        let router = search_router();
+        // Conditions only break priority ties: the lower-priority canary
+        // route stays behind the unconditioned default.
+        assert_eq!(
+            search(&router, None, &[("Cookie", "sid=x; canary=1")]),
+            "a-default"
+        );
+        let mut canary = make_route("canary", "/search", vec![]);
+        canary.cookie = vec![vec!["canary".into(), "==".into(), "1".into()]];
+        let router =
+            Router::build(vec![make_route("a-default", "/search", vec![]), canary], 1).unwrap();
+        assert_eq!(search(&router, None, &[("cookie", "canary=1")]), "canary");
+        assert_eq!(search(&router, None, &[("cookie", "sid=x")]), "a-default");
+        assert_eq!(search(&router, None, &[]), "a-default");
+    }
+
+    #[test]
+    fn test_invalid_query_and_cookie_conditions_are_rejected() {
+        let mut route = make_route("bad", "/b", vec![]);
+        route.query = vec![vec!["engine".into(), "??".into(), "1".into()]];
+        assert!(
+            validate_conditions(&route)
+                .unwrap_err()
+                .starts_with("query: ")
+        );
+        route.query.clear();
+        route.cookie = vec![vec![]];
+        assert!(
+            validate_conditions(&route)
+                .unwrap_err()
+                .starts_with("cookie: ")
+        );
+    }
+
+    #[test]
+    fn test_method_route_falls_through_to_any_method_route() {
+        let mut restricted = make_route("restricted", "/x", vec!["GET"]);
+        restricted.remote_addrs = vec!["10.0.0.1".into()];
+        let open = make_route("open", "/x", vec![]);
+        let router = Router::build(vec![restricted, open], 1).unwrap();
+        assert_eq!(
+            router
+                .match_route("GET", "/x", None, &ctx("10.0.0.1", &[]))
+                .unwrap()
+                .id,
+            "restricted"
+        );
+        assert_eq!(
+            router
+                .match_route("GET", "/x", None, &ctx("10.0.0.2", &[]))
+                .unwrap()
+                .id,
+            "open"
+        );
+    }
+
+    #[test]
+    fn test_invalid_conditions_skip_route() {
+        let mut bad_ip = make_route("bad_ip", "/a", vec![]);
+        bad_ip.remote_addrs = vec!["nope".into()];
+        let mut bad_var = make_route("bad_var", "/b", vec![]);
+        bad_var.vars = vec![vec!["arg_x".into(), "??".into(), "1".into()]];
+        let router = Router::build(vec![bad_ip, bad_var], 1).unwrap();
+        assert!(router.is_empty());
+    }
+
+    #[test]
+    fn test_same_path_priority_order() {
+        let mut low = make_route("low", "/p", vec![]);
+        low.priority = 1;
+        let mut high = make_route("high", "/p", vec![]);
+        high.priority = 5;
+        let router = Router::build(vec![low, high], 1).unwrap();
+        assert_eq!(
+            router
+                .match_route("GET", "/p", None, &MatchContext::default())
+                .unwrap()
+                .id,
+            "high"
+        );
+    }
+
+    #[test]
+    fn test_any_method_route_with_higher_priority_wins() {
+        let specific = make_route("specific", "/p", vec!["GET"]);
+        let mut any = make_route("any", "/p", vec![]);
+        any.priority = 10;
+        let router = Router::build(vec![specific, any], 1).unwrap();
+        let m = |method| {
+            router
+                .match_route(method, "/p", None, &MatchContext::default())
+                .map(|r| r.id.clone())
+        };
+        assert_eq!(m("GET").as_deref(), Some("any"));
+        assert_eq!(m("POST").as_deref(), Some("any"));
+    }
+
+    #[test]
+    fn test_equal_priority_prefers_method_specific_route() {
+        let any = make_route("any", "/p", vec![]);
+        let specific = make_route("specific", "/p", vec!["GET"]);
+        let router = Router::build(vec![any, specific], 1).unwrap();
+        assert_eq!(
+            router
+                .match_route("GET", "/p", None, &MatchContext::default())
+                .unwrap()
+                .id,
+            "specific"
+        );
+    }
+
+    #[test]
+    fn test_host_route_does_not_shadow_hostless_route() {
+        let mut tenant = make_route("tenant", "/p", vec![]);
+        tenant.hosts = vec!["tenant.example.com".into()];
+        tenant.priority = 10;
+        let fallback = make_route("fallback", "/p", vec![]);
+        let router = Router::build(vec![tenant, fallback], 1).unwrap();
+        let m = |host| {
+            router
+                .match_route("GET", "/p", host, &MatchContext::default())
+                .map(|r| r.id.clone())
+        };
+        assert_eq!(m(Some("tenant.example.com")).as_deref(), Some("tenant"));
+        assert_eq!(m(Some("other.example.com")).as_deref(), Some("fallback"));
+        assert_eq!(m(None).as_deref(), Some("fallback"));
+    }
+
+    #[test]
+    fn test_wildcard_host_matches_subdomains_only() {
+        let mut route = make_route("wild", "/p", vec![]);
+        route.hosts = vec!["*.example.com".into()];
+        let router = Router::build(vec![route], 1).unwrap();
+        let m = |host| {
+            router
+                .match_route("GET", "/p", Some(host), &MatchContext::default())
+                .is_some()
+        };
+        assert!(m("a.example.com"));
+        assert!(m("a.b.example.com"));
+        assert!(m("A.Example.COM"));
+        assert!(!m("example.com"));
+        assert!(!m("badexample.com"));
+        assert!(!m("a.example.com.evil"));
+        assert!(m("a.example.com:9443"));
+        assert!(!m("example.com:9443"));
+    }
+
+    #[test]
+    fn test_host_with_port_matches() {
+        let mut route = make_route("r1", "/api", vec!["GET"]);
+        route.hosts = vec!["api.example.com".to_string()];
+        let router = Router::build(vec![route], 1).unwrap();
+        let m = |host| {
+            router
+                .match_route("GET", "/api", Some(host), &MatchContext::default())
+                .is_some()
+        };
+        assert!(m("api.example.com:8080"));
+        assert!(m("api.example.com."));
+        assert!(m("API.example.com.:443"));
+        assert!(!m("other.example.com:8080"));
+    }
+
+    #[test]
+    fn test_exact_host_ranks_by_priority_against_wildcard() {
+        let mut exact = make_route("exact", "/p", vec![]);
+        exact.hosts = vec!["api.example.com".into()];
+        let mut wild = make_route("wild", "/p", vec![]);
+        wild.hosts = vec!["*.example.com".into()];
+        wild.priority = 10;
+        let router = Router::build(vec![exact, wild], 1).unwrap();
+        let m = |host| {
+            router
+                .match_route("GET", "/p", Some(host), &MatchContext::default())
+                .map(|r| r.id.clone())
+        };
+        assert_eq!(m("api.example.com").as_deref(), Some("wild"));
+        assert_eq!(m("www.example.com").as_deref(), Some("wild"));
+        assert_eq!(m("example.com"), None);
+    }
+
+    #[test]
+    fn test_route_with_thousands_of_hosts() {
+        let mut saas = make_route("saas", "/p", vec![]);
+        saas.hosts = (0..5_000)
+            .map(|i| format!("customer{i}.example.net"))
+            .collect();
+        let mut other = make_route("other", "/p", vec![]);
+        other.hosts = vec!["customer1.example.org".into()];
+        let router = Router::build(vec![saas, other], 1).unwrap();
+        let m = |host| {
+            router
+                .match_route("GET", "/p", Some(host), &MatchContext::default())
+                .map(|r| r.id.clone())
+        };
+        assert_eq!(m("customer4999.example.net").as_deref(), Some("saas"));
+        assert_eq!(m("customer1.example.org").as_deref(), Some("other"));
+        assert_eq!(m("customer5000.example.net"), None);
+    }
+
+    #[test]
+    fn test_uris_registers_every_pattern() {
+        let mut route = make_route("multi", "/a", vec!["GET"]);
+        route.uris = vec!["/b".into(), "/c/*".into(), "/a".into()];
+        let router = Router::build(vec![route], 1).unwrap();
+        let ctx = MatchContext::default();
+        for path in ["/a", "/b", "/c/", "/c/x/y"] {
+            assert_eq!(
+                router
+                    .match_route("GET", path, None, &ctx)
+                    .map(|r| r.id.as_str()),
+                Some("multi"),
+                "{path}"
+            );
+        }
+        assert!(router.match_route("GET", "/d", None, &ctx).is_none());
+
+        let m = router
+            .match_route_params("GET", "/c/x/y", None, &ctx)
+            .unwrap();
+        assert_eq!(m.uri, "/c/*");
+    }
+
+    #[test]
+    fn test_uris_only_route() {
+        let mut route = make_route("only", "", vec![]);
+        route.uris = vec!["/x".into()];
+        let router = Router::build(vec![route], 1).unwrap();
+        assert!(
+            router
+                .match_route("GET", "/x", None, &MatchContext::default())
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_nested_wildcard_exposes_remainder() {
+        let router = Router::build(vec![make_route("api", "/api/*", vec![])], 1).unwrap();
+        let ctx = MatchContext::default();
+        let m = router
+            .match_route_params("GET", "/api/a/b/c", None, &ctx)
+            .unwrap();
+        assert_eq!(m.route.id, "api");
+        assert_eq!(m.params, vec![("*", "a/b/c")]);
+        assert_eq!(m.param("*"), Some("a/b/c"));
+
+        let m = router
+            .match_route_params("GET", "/api/", None, &ctx)
+            .unwrap();
+        assert_eq!(m.route.id, "api");
+        assert!(m.params.is_empty());
+    }
+
+    #[test]
+    fn test_named_params_are_exposed() {
+        let router = Router::build(vec![make_route("user", "/users/{id}", vec![])], 1).unwrap();
+        let m = router
+            .match_route_params("GET", "/users/42", None, &MatchContext::default())
+            .unwrap();
+        assert_eq!(m.param("id"), Some("42"));
+    }
+
+    #[test]
+    fn test_exact_route_beats_wildcard_at_same_prefix() {
+        let wildcard = make_route("wild", "/api/*", vec!["GET"]);
+        let exact = make_route("exact", "/api/users", vec![]);
+        let root = make_route("root", "/api/", vec![]);
+        let router = Router::build(vec![wildcard, exact, root], 1).unwrap();
+        let m = |path| {
+            router
+                .match_route("GET", path, None, &MatchContext::default())
+                .map(|r| r.id.clone())
+        };
+        assert_eq!(m("/api/users").as_deref(), Some("exact"));
+        assert_eq!(m("/api/").as_deref(), Some("root"));
+        assert_eq!(m("/api/orders").as_deref(), Some("wild"));
+        assert_eq!(m("/api/users/1").as_deref(), Some("wild"));
+    }
+
     // ── Property-based tests ──────────────────────────────────────
 
     proptest::proptest! {
@@ -404,7 +1284,7 @@ mod tests {
         ) {
             let router = Router::build(vec![], 1).unwrap();
             // Should always return None without panicking
-            let _ = router.match_route(&method, &path, None);
+            let _ = router.match_route(&method, &path, None, &MatchContext::default());
         }
 
         /// A single registered route is never incorrectly matched for a
@@ -418,7 +1298,7 @@ mod tests {
 
             let query_path = format!("/other/{suffix}");
             // /other/... must never match /fixed/path
-            let result = router.match_route("GET", &query_path, None);
+            let result = router.match_route("GET", &query_path, None, &MatchContext::default());
             assert!(result.is_none() || result.unwrap().uri == "/fixed/path",
                 "Unexpected match for {query_path}");
         }
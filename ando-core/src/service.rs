@@ -1,8 +1,10 @@
+use crate::route::Route;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// Service definition — APISIX-compatible.
-/// A service is a reusable bundle of upstream + plugins.
+/// A service is a reusable bundle of upstream + plugins, and of defaults
+/// for its routes; see [`Service::apply_defaults`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Service {
     pub id: String,
@@ -19,11 +21,40 @@ pub struct Service {
     #[serde(default)]
     pub plugins: HashMap<String, serde_json::Value>,
 
-    /// Labels.
+    /// Hosts for routes of this service that list none.
+    #[serde(default)]
+    pub hosts: Vec<String>,
+
+    /// Upstream timeouts for routes of this service, under the route's own.
+    pub timeout: Option<crate::upstream::Timeout>,
+
+    /// Labels, inherited by routes that do not set the same key.
     #[serde(default)]
     pub labels: HashMap<String, String>,
 }
 
+impl Service {
+    /// Apply this service's defaults to `route`, one of its routes: the
+    /// hosts when the route lists none, the timeout fields the route
+    /// leaves unset, and the labels it does not set. Done when the router
+    /// is built, so stored routes keep only their own values.
+    pub fn apply_defaults(&self, route: &mut Route) {
+        if route.hosts.is_empty() {
+            route.hosts.clone_from(&self.hosts);
+        }
+        route.timeout = match (route.timeout, self.timeout) {
+            (Some(own), Some(inherited)) => Some(own.or(inherited)),
+            (own, inherited) => own.or(inherited),
+        };
+        for (key, value) in &self.labels {
+            route
+                .labels
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -46,6 +77,8 @@ mod tests {
             desc: Some("Test service".into()),
             upstream_id: Some("ups1".into()),
             upstream: None,
+            hosts: Vec::new(),
+            timeout: None,
             plugins: {
                 let mut m = HashMap::new();
                 m.insert("rate-limiting".into(), serde_json::json!({"count": 100}));
@@ -80,4 +113,36 @@ mod tests {
         let ups = svc.upstream.as_ref().unwrap();
         assert!(ups.nodes.contains_key("10.0.0.1:8080"));
     }
+
+    #[test]
+    fn defaults_apply_where_the_route_sets_none() {
+        let svc: Service = serde_json::from_value(serde_json::json!({
+            "id": "svc1",
+            "hosts": ["api.example.com"],
+            "timeout": {"connect": 1.0, "read": 30.0},
+            "labels": {"team": "shop", "env": "prod"}
+        }))
+        .unwrap();
+
+        let mut bare: Route =
+            serde_json::from_value(serde_json::json!({"id": "r1", "uri": "/a"})).unwrap();
+        svc.apply_defaults(&mut bare);
+        assert_eq!(bare.hosts, ["api.example.com"]);
+        assert_eq!(bare.timeout, svc.timeout);
+        assert_eq!(bare.labels, svc.labels);
+
+        let mut own: Route = serde_json::from_value(serde_json::json!({
+            "id": "r2", "uri": "/b",
+            "hosts": ["b.example.com"],
+            "timeout": {"read": 5.0},
+            "labels": {"env": "dev"}
+        }))
+        .unwrap();
+        svc.apply_defaults(&mut own);
+        assert_eq!(own.hosts, ["b.example.com"]);
+        let timeout = own.timeout.unwrap();
+        assert_eq!((timeout.connect, timeout.read), (Some(1.0), Some(5.0)));
+        assert_eq!(own.labels["env"], "dev");
+        assert_eq!(own.labels["team"], "shop");
+    }
 }
@@ -0,0 +1,60 @@
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Graceful shutdown state shared by the workers and the Admin API.
+///
+/// v2 design: `begin` is called once, by the signal handling thread; it
+/// fixes the drain deadline. Workers poll `is_draining` (one atomic load)
+/// to stop accepting and close keepalive connections after their current
+/// response, then wait at most until `deadline` for requests in flight.
+pub struct Shutdown {
+    timeout: Duration,
+    began: OnceLock<Instant>,
+}
+
+impl Shutdown {
+    /// Requests in flight get `timeout` after `begin` to finish.
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            began: OnceLock::new(),
+        }
+    }
+
+    /// Start draining. Later calls keep the first deadline.
+    pub fn begin(&self) {
+        let _ = self.began.set(Instant::now());
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.began.get().is_some()
+    }
+
+    /// When workers stop waiting for requests in flight; `None` until
+    /// `begin`.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.began.get().map(|began| *began + self.timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn begin_starts_draining_once() {
+        let shutdown = Shutdown::new(Duration::from_secs(5));
+        assert!(!shutdown.is_draining());
+        assert!(shutdown.deadline().is_none());
+
+        let before = Instant::now();
+        shutdown.begin();
+        assert!(shutdown.is_draining());
+        let deadline = shutdown.deadline().unwrap();
+        assert!(deadline >= before + Duration::from_secs(5));
+
+        std::thread::sleep(Duration::from_millis(5));
+        shutdown.begin();
+        assert_eq!(shutdown.deadline(), Some(deadline));
+    }
+}
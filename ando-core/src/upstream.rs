@@ -1,5 +1,7 @@
+use crate::balancer::HashOn;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Upstream target definition — APISIX-compatible.
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,8 +19,35 @@ pub struct Upstream {
     #[serde(default)]
     pub nodes: HashMap<String, u32>,
 
-    /// Health check config.
-    pub health_check: Option<HealthCheck>,
+    /// Take the nodes from a service registry instead of `nodes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discovery_type: Option<DiscoveryType>,
+
+    /// Service to discover: `namespace/name` for `kubernetes`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+
+    /// Named port of the discovered service. Defaults to its only port,
+    /// or to its unnamed one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port_name: Option<String>,
+
+    /// Hash source for `type = "chash"`: `vars`, `header`, `cookie`, or the
+    /// combined form `vars.remote_addr`, `header.<name>`, `cookie.<name>`.
+    #[serde(default = "default_hash_on")]
+    pub hash_on: String,
+
+    /// Hash key name when `hash_on` is not in combined form
+    /// (e.g. `remote_addr` for `vars`, a header or cookie name otherwise).
+    pub key: Option<String>,
+
+    /// Health check config (APISIX `checks`).
+    #[serde(alias = "health_check")]
+    pub checks: Option<HealthCheck>,
+
+    /// Per-phase timeouts to the nodes (APISIX `timeout`). Unset fields
+    /// fall back to the `*_timeout_ms` fields below.
+    pub timeout: Option<Timeout>,
 
     /// Connection timeout override (ms).
     pub connect_timeout_ms: Option<u64>,
@@ -34,10 +63,37 @@ pub struct Upstream {
     /// Upstream host header (used when pass_host = "rewrite").
     pub upstream_host: Option<String>,
 
-    /// Retries on upstream failure.
+    /// Further attempts, each on a node not tried yet, after a failure
+    /// listed in `retry_on`.
     #[serde(default = "default_retries")]
     pub retries: u32,
 
+    /// Seconds from the first attempt after which no retry starts.
+    /// 0 = no limit.
+    #[serde(default)]
+    pub retry_timeout: f64,
+
+    /// Failures that are retried.
+    #[serde(default = "default_retry_on")]
+    pub retry_on: Vec<RetryOn>,
+
+    /// Protocol to the nodes: "http" | "https" | "grpc" | "grpcs".
+    #[serde(default = "default_scheme")]
+    pub scheme: String,
+
+    /// Verify the node's certificate against the system roots (https only).
+    #[serde(default = "default_tls_verify")]
+    pub tls_verify: bool,
+
+    /// Server name sent in the TLS handshake and checked against the
+    /// certificate. Defaults to the host part of the node address.
+    pub sni: Option<String>,
+
+    /// Start every connection to the nodes with a PROXY protocol header
+    /// naming the client, for nodes behind HAProxy-style `accept-proxy`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send_proxy_protocol: Option<ProxyProtocolVersion>,
+
     /// Description.
     pub desc: Option<String>,
 
@@ -46,36 +102,140 @@ pub struct Upstream {
     pub labels: HashMap<String, String>,
 }
 
+/// PROXY protocol version of `send_proxy_protocol`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProxyProtocolVersion {
+    /// Human-readable text header.
+    V1,
+    /// Binary header.
+    V2,
+}
+
+/// Service registry an upstream takes its nodes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscoveryType {
+    /// Ready endpoints of a Kubernetes Service, from its EndpointSlices.
+    Kubernetes,
+}
+
+impl DiscoveryType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiscoveryType::Kubernetes => "kubernetes",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HealthCheck {
     #[serde(default)]
     pub active: Option<ActiveHealthCheck>,
+    /// Shared, so the proxy hands it on with every request without a copy.
+    #[serde(default)]
+    pub passive: Option<Arc<PassiveHealthCheck>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ActiveHealthCheck {
+    /// Probe type: "http" or "tcp" (connect only).
     #[serde(default = "default_hc_type")]
     pub r#type: String,
+    /// Seconds between probes of the same node.
     #[serde(default = "default_hc_interval")]
     pub interval: u64,
+    /// Probe timeout in seconds.
     #[serde(default = "default_hc_timeout")]
     pub timeout: u64,
+    /// Path requested by HTTP probes (default `/`).
     pub http_path: Option<String>,
+    /// Host header sent by HTTP probes (default: the node address).
+    pub host: Option<String>,
     #[serde(default = "default_healthy_successes")]
     pub healthy_successes: u32,
     #[serde(default = "default_unhealthy_failures")]
     pub unhealthy_failures: u32,
 }
 
+/// Passive health check (circuit breaker) driven by proxied traffic.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassiveHealthCheck {
+    #[serde(default)]
+    pub unhealthy: PassiveUnhealthy,
+    /// Seconds a tripped node is skipped before a trial request is let through.
+    #[serde(default = "default_passive_cooldown")]
+    pub cooldown: u64,
+    /// Seconds within which the `unhealthy` failures must fall to trip the
+    /// breaker; a failure streak older than this starts over.
+    #[serde(default = "default_passive_window")]
+    pub window: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PassiveUnhealthy {
+    /// Consecutive failing responses that trip the breaker.
+    #[serde(default = "default_passive_http_failures")]
+    pub http_failures: u32,
+    /// Consecutive connect / read failures that trip the breaker.
+    #[serde(default = "default_passive_tcp_failures")]
+    pub tcp_failures: u32,
+    /// Response statuses counted as failures.
+    #[serde(default = "default_passive_http_statuses")]
+    pub http_statuses: Vec<u16>,
+}
+
+impl Default for PassiveUnhealthy {
+    fn default() -> Self {
+        Self {
+            http_failures: default_passive_http_failures(),
+            tcp_failures: default_passive_tcp_failures(),
+            http_statuses: default_passive_http_statuses(),
+        }
+    }
+}
+
+/// Split a node address into host and port. IPv6 hosts keep their
+/// brackets; an address without a port yields `None`.
+fn split_node(addr: &str) -> (&str, Option<u16>) {
+    match addr.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (addr, None),
+        },
+        _ => (addr, None),
+    }
+}
+
+/// Host part of a node address, without IPv6 brackets.
+pub fn node_host(addr: &str) -> &str {
+    let (host, _) = split_node(addr);
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
 fn default_lb_type() -> String {
     "roundrobin".into()
 }
+fn default_hash_on() -> String {
+    "vars".into()
+}
 fn default_pass_host() -> String {
     "pass".into()
 }
 fn default_retries() -> u32 {
     1
 }
+fn default_retry_on() -> Vec<RetryOn> {
+    vec![RetryOn::ConnectFailure]
+}
+fn default_scheme() -> String {
+    "http".into()
+}
+fn default_tls_verify() -> bool {
+    true
+}
 fn default_hc_type() -> String {
     "http".into()
 }
@@ -91,8 +251,109 @@ fn default_healthy_successes() -> u32 {
 fn default_unhealthy_failures() -> u32 {
     3
 }
+fn default_passive_cooldown() -> u64 {
+    10
+}
+fn default_passive_window() -> u64 {
+    60
+}
+fn default_passive_http_failures() -> u32 {
+    5
+}
+fn default_passive_tcp_failures() -> u32 {
+    2
+}
+fn default_passive_http_statuses() -> Vec<u16> {
+    vec![500, 502, 503, 504]
+}
+
+/// Upstream failure that `retry_on` can list. A response is only retried
+/// before any of it reached the client, and never once a streamed request
+/// body has been sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryOn {
+    /// The connection (or TLS handshake) failed or timed out.
+    ConnectFailure,
+    #[serde(rename = "http_502")]
+    Http502,
+    #[serde(rename = "http_503")]
+    Http503,
+    #[serde(rename = "http_504")]
+    Http504,
+}
+
+impl RetryOn {
+    /// The condition a response status matches, if any.
+    pub fn from_status(status: u16) -> Option<Self> {
+        match status {
+            502 => Some(RetryOn::Http502),
+            503 => Some(RetryOn::Http503),
+            504 => Some(RetryOn::Http504),
+            _ => None,
+        }
+    }
+}
+
+/// Upstream timeouts in seconds — APISIX `timeout: {connect, send, read}`.
+/// Set on a route or an upstream; unset fields take the next level's value.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timeout {
+    /// Establishing the connection, TLS handshake included.
+    pub connect: Option<f64>,
+    /// Each write of the request to the upstream.
+    pub send: Option<f64>,
+    /// Each read of the response from the upstream.
+    pub read: Option<f64>,
+}
+
+impl Timeout {
+    /// Fill the fields unset in `self` from `fallback`.
+    pub fn or(self, fallback: Timeout) -> Timeout {
+        Timeout {
+            connect: self.connect.or(fallback.connect),
+            send: self.send.or(fallback.send),
+            read: self.read.or(fallback.read),
+        }
+    }
+}
 
 impl Upstream {
+    /// Timeouts set on this upstream, by `timeout` or `*_timeout_ms`.
+    pub fn timeouts(&self) -> Timeout {
+        let secs = |ms: Option<u64>| ms.map(|ms| ms as f64 / 1000.0);
+        self.timeout.unwrap_or_default().or(Timeout {
+            connect: secs(self.connect_timeout_ms),
+            send: secs(self.write_timeout_ms),
+            read: secs(self.read_timeout_ms),
+        })
+    }
+
+    /// Registry and service the nodes are discovered from, if any.
+    pub fn discovery(&self) -> Option<(DiscoveryType, &str)> {
+        Some((
+            self.discovery_type?,
+            self.service_name.as_deref().unwrap_or(""),
+        ))
+    }
+
+    /// Check that a discovered upstream names its service properly.
+    pub fn check_discovery(&self) -> Result<(), String> {
+        let Some((kind, service)) = self.discovery() else {
+            return Ok(());
+        };
+        match kind {
+            DiscoveryType::Kubernetes => match service.split_once('/') {
+                Some((ns, name)) if !ns.is_empty() && !name.is_empty() && !name.contains('/') => {
+                    Ok(())
+                }
+                _ => Err(format!(
+                    "service_name must be `namespace/name` for discovery_type kubernetes, got `{service}`"
+                )),
+            },
+        }
+    }
+
     /// Get the first node address (for single-node upstreams).
     pub fn first_node(&self) -> Option<&str> {
         self.nodes.keys().next().map(|s| s.as_str())
@@ -102,6 +363,43 @@ impl Upstream {
     pub fn is_empty(&self) -> bool {
         self.nodes.is_empty()
     }
+
+    /// Passive health check config, if enabled.
+    pub fn passive_check(&self) -> Option<&Arc<PassiveHealthCheck>> {
+        self.checks.as_ref().and_then(|c| c.passive.as_ref())
+    }
+
+    /// Whether nodes are reached over TLS.
+    pub fn is_tls(&self) -> bool {
+        self.scheme.eq_ignore_ascii_case("https") || self.scheme.eq_ignore_ascii_case("grpcs")
+    }
+
+    /// Whether requests are forwarded as gRPC, over HTTP/2.
+    pub fn is_grpc(&self) -> bool {
+        self.scheme.eq_ignore_ascii_case("grpc") || self.scheme.eq_ignore_ascii_case("grpcs")
+    }
+
+    /// Host header to send to `node` under `pass_host`; `None` keeps the
+    /// client's. `node` mode omits the port when it is the scheme default.
+    pub fn host_header(&self, node: &str) -> Option<String> {
+        match self.pass_host.as_str() {
+            "node" => {
+                let (host, port) = split_node(node);
+                let default_port = if self.is_tls() { 443 } else { 80 };
+                Some(match port {
+                    Some(p) if p != default_port => node.to_string(),
+                    _ => host.to_string(),
+                })
+            }
+            "rewrite" => self.upstream_host.clone(),
+            _ => None,
+        }
+    }
+
+    /// The request attribute used by the `chash` balancer.
+    pub fn hash_source(&self) -> HashOn {
+        HashOn::parse(&self.hash_on, self.key.as_deref())
+    }
 }
 
 #[cfg(test)]
@@ -114,13 +412,25 @@ mod tests {
             name: Some("test".into()),
             lb_type: "roundrobin".into(),
             nodes: nodes.into_iter().map(|(k, v)| (k.to_string(), v)).collect(),
-            health_check: None,
+            discovery_type: None,
+            service_name: None,
+            port_name: None,
+            hash_on: "vars".into(),
+            key: None,
+            checks: None,
+            timeout: None,
             connect_timeout_ms: None,
             read_timeout_ms: None,
             write_timeout_ms: None,
             pass_host: "pass".into(),
             upstream_host: None,
             retries: 1,
+            retry_timeout: 0.0,
+            retry_on: default_retry_on(),
+            scheme: "http".into(),
+            tls_verify: true,
+            sni: None,
+            send_proxy_protocol: None,
             desc: None,
             labels: Default::default(),
         }
@@ -145,8 +455,31 @@ mod tests {
         let json = r#"{"nodes":{"127.0.0.1:8080":1}}"#;
         let us: Upstream = serde_json::from_str(json).unwrap();
         assert_eq!(us.lb_type, "roundrobin");
+        assert_eq!(us.hash_on, "vars");
+        assert!(us.key.is_none());
         assert_eq!(us.pass_host, "pass");
         assert_eq!(us.retries, 1);
+        assert_eq!(us.retry_timeout, 0.0);
+        assert_eq!(us.retry_on, [RetryOn::ConnectFailure]);
+    }
+
+    #[test]
+    fn retry_on_parses_apisix_names() {
+        let us: Upstream = serde_json::from_value(serde_json::json!({
+            "nodes": { "a:80": 1 },
+            "retry_on": ["connect_failure", "http_502", "http_504"]
+        }))
+        .unwrap();
+        assert_eq!(
+            us.retry_on,
+            [RetryOn::ConnectFailure, RetryOn::Http502, RetryOn::Http504]
+        );
+        assert_eq!(RetryOn::from_status(503), Some(RetryOn::Http503));
+        assert_eq!(RetryOn::from_status(500), None);
+        assert!(
+            serde_json::from_value::<Upstream>(serde_json::json!({ "retry_on": ["http_500"] }))
+                .is_err()
+        );
     }
 
     #[test]
@@ -172,7 +505,7 @@ mod tests {
     fn test_health_check_defaults() {
         let json = r#"{"nodes":{"127.0.0.1:8080":1},"health_check":{"active":{}}}"#;
         let us: Upstream = serde_json::from_str(json).unwrap();
-        let hc = us.health_check.unwrap();
+        let hc = us.checks.unwrap();
         let active = hc.active.unwrap();
         assert_eq!(active.r#type, "http");
         assert_eq!(active.interval, 5);
@@ -180,4 +513,146 @@ mod tests {
         assert_eq!(active.healthy_successes, 2);
         assert_eq!(active.unhealthy_failures, 3);
     }
+
+    #[test]
+    fn test_checks_block_apisix_style() {
+        let json = r#"{"nodes":{"127.0.0.1:8080":1},
+            "checks":{"active":{"http_path":"/healthz","host":"api.local","interval":2}}}"#;
+        let us: Upstream = serde_json::from_str(json).unwrap();
+        let active = us.checks.unwrap().active.unwrap();
+        assert_eq!(active.http_path.as_deref(), Some("/healthz"));
+        assert_eq!(active.host.as_deref(), Some("api.local"));
+        assert_eq!(active.interval, 2);
+    }
+
+    #[test]
+    fn test_passive_check_defaults() {
+        let json = r#"{"nodes":{"127.0.0.1:8080":1},"checks":{"passive":{}}}"#;
+        let us: Upstream = serde_json::from_str(json).unwrap();
+        let passive = us.passive_check().unwrap();
+        assert_eq!(passive.cooldown, 10);
+        assert_eq!(passive.window, 60);
+        assert_eq!(passive.unhealthy.http_failures, 5);
+        assert_eq!(passive.unhealthy.tcp_failures, 2);
+        assert_eq!(passive.unhealthy.http_statuses, vec![500, 502, 503, 504]);
+    }
+
+    #[test]
+    fn test_tls_defaults_and_overrides() {
+        let us: Upstream = serde_json::from_str(r#"{"nodes":{"a:80":1}}"#).unwrap();
+        assert!(!us.is_tls());
+        assert!(us.tls_verify);
+        assert!(us.sni.is_none());
+
+        let json = r#"{"nodes":{"api.example.com:443":1},"scheme":"https",
+            "tls_verify":false,"sni":"internal.example.com"}"#;
+        let us: Upstream = serde_json::from_str(json).unwrap();
+        assert!(us.is_tls());
+        assert!(!us.tls_verify);
+        assert_eq!(us.sni.as_deref(), Some("internal.example.com"));
+    }
+
+    #[test]
+    fn test_grpc_schemes() {
+        let mut us: Upstream = serde_json::from_str(r#"{"nodes":{"a:50051":1}}"#).unwrap();
+        assert!(!us.is_grpc());
+        us.scheme = "grpc".into();
+        assert!(us.is_grpc() && !us.is_tls());
+        us.scheme = "grpcs".into();
+        assert!(us.is_grpc() && us.is_tls());
+    }
+
+    #[test]
+    fn test_node_host_strips_port_and_brackets() {
+        assert_eq!(node_host("api.example.com:8443"), "api.example.com");
+        assert_eq!(node_host("api.example.com"), "api.example.com");
+        assert_eq!(node_host("[::1]:8080"), "::1");
+        assert_eq!(node_host("::1"), "::1");
+    }
+
+    #[test]
+    fn test_host_header_by_pass_host_mode() {
+        let mut us = make_upstream(vec![("backend.local:8080", 1)]);
+        assert_eq!(us.host_header("backend.local:8080"), None);
+
+        us.pass_host = "node".into();
+        assert_eq!(
+            us.host_header("backend.local:8080").as_deref(),
+            Some("backend.local:8080")
+        );
+        assert_eq!(
+            us.host_header("backend.local:80").as_deref(),
+            Some("backend.local")
+        );
+        assert_eq!(us.host_header("[::1]:9000").as_deref(), Some("[::1]:9000"));
+        us.scheme = "https".into();
+        assert_eq!(
+            us.host_header("backend.local:443").as_deref(),
+            Some("backend.local")
+        );
+        assert_eq!(
+            us.host_header("backend.local:80").as_deref(),
+            Some("backend.local:80")
+        );
+
+        us.pass_host = "rewrite".into();
+        assert_eq!(us.host_header("backend.local:8080"), None);
+        us.upstream_host = Some("api.example.com".into());
+        assert_eq!(
+            us.host_header("backend.local:8080").as_deref(),
+            Some("api.example.com")
+        );
+    }
+
+    #[test]
+    fn timeouts_prefer_timeout_object_over_ms_fields() {
+        let mut us = make_upstream(vec![("a:80", 1)]);
+        assert_eq!(us.timeouts(), Timeout::default());
+
+        us.read_timeout_ms = Some(1500);
+        us.connect_timeout_ms = Some(200);
+        us.timeout = Some(serde_json::from_value(serde_json::json!({ "read": 120 })).unwrap());
+        let t = us.timeouts();
+        assert_eq!(t.read, Some(120.0));
+        assert_eq!(t.connect, Some(0.2));
+        assert_eq!(t.send, None);
+
+        let route = Timeout {
+            read: Some(0.5),
+            ..Timeout::default()
+        };
+        assert_eq!(route.or(t).read, Some(0.5));
+        assert_eq!(route.or(t).connect, Some(0.2));
+    }
+
+    #[test]
+    fn discovery_fields_parse_and_validate() {
+        let ups: Upstream = serde_json::from_value(serde_json::json!({
+            "discovery_type": "kubernetes",
+            "service_name": "shop/checkout",
+            "port_name": "http"
+        }))
+        .unwrap();
+        assert_eq!(
+            ups.discovery(),
+            Some((DiscoveryType::Kubernetes, "shop/checkout"))
+        );
+        assert!(ups.check_discovery().is_ok());
+        assert!(ups.nodes.is_empty());
+
+        for bad in ["checkout", "shop/", "/checkout", "a/b/c"] {
+            let mut ups = ups.clone();
+            ups.service_name = Some(bad.into());
+            assert!(ups.check_discovery().is_err(), "{bad}");
+        }
+        let mut ups = ups;
+        ups.service_name = None;
+        assert!(ups.check_discovery().is_err());
+
+        assert!(
+            serde_json::from_value::<Upstream>(serde_json::json!({ "discovery_type": "consul" }))
+                .is_err()
+        );
+        assert!(make_upstream(vec![("a:80", 1)]).check_discovery().is_ok());
+    }
 }
@@ -0,0 +1,272 @@
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// A compiled APISIX-style `vars` condition, e.g. `["http_x_env", "==", "staging"]`.
+///
+/// Supported variables: `http_<header>`, `arg_<query arg>`, `cookie_<name>`,
+/// `remote_addr`, `uri`, `host`, `request_method`. Header names use `_` in
+/// place of `-` (`http_x_api_version` → `x-api-version`).
+///
+/// Operators: `==`, `~=`, `>`, `>=`, `<`, `<=`, `~~` (regex), `~*`
+/// (case-insensitive regex), `in`, `has`. A leading `"!"` negates the
+/// operator: `["arg_debug", "!", "==", "1"]`. The two-element form
+/// `["arg_v", "1"]` is shorthand for `==`.
+#[derive(Debug, Clone)]
+pub struct VarExpr {
+    var: String,
+    negate: bool,
+    op: Op,
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Eq(String),
+    Ne(String),
+    Gt(f64),
+    Ge(f64),
+    Lt(f64),
+    Le(f64),
+    Regex(Regex),
+    In(Vec<String>),
+    Has(String),
+}
+
+impl VarExpr {
+    /// Compile a single condition tuple.
+    pub fn compile(tuple: &[Value]) -> Result<Self, String> {
+        let var = tuple
+            .first()
+            .and_then(Value::as_str)
+            .ok_or("vars condition must start with a variable name")?
+            .to_string();
+
+        let (negate, rest) = match tuple.get(1).and_then(Value::as_str) {
+            Some("!") => (true, &tuple[2..]),
+            _ => (false, &tuple[1..]),
+        };
+
+        let op = match rest {
+            [v] => Op::Eq(value_string(v)?),
+            [op, v] => {
+                let op = op.as_str().ok_or("vars operator must be a string")?;
+                compile_op(op, v)?
+            }
+            _ => return Err(format!("invalid vars condition for `{var}`")),
+        };
+
+        Ok(Self { var, negate, op })
+    }
+
+    /// Compile a full `vars` list. All conditions must hold (logical AND).
+    pub fn compile_all(vars: &[Vec<Value>]) -> Result<Vec<Self>, String> {
+        vars.iter().map(|t| Self::compile(t)).collect()
+    }
+
+    /// The variable the condition reads.
+    pub fn var(&self) -> &str {
+        &self.var
+    }
+
+    /// Evaluate against a variable lookup. A missing variable only
+    /// satisfies negated conditions and `~=`.
+    pub fn matches<'a>(&self, lookup: impl Fn(&str) -> Option<&'a str>) -> bool {
+        self.matches_value(lookup(&self.var))
+    }
+
+    /// Evaluate against the variable's value, `None` when it is missing.
+    pub fn matches_value(&self, value: Option<&str>) -> bool {
+        let result = match value {
+            None => matches!(self.op, Op::Ne(_)),
+            Some(actual) => match &self.op {
+                Op::Eq(v) => actual == v,
+                Op::Ne(v) => actual != v,
+                Op::Gt(n) => parse_num(actual).is_some_and(|a| a > *n),
+                Op::Ge(n) => parse_num(actual).is_some_and(|a| a >= *n),
+                Op::Lt(n) => parse_num(actual).is_some_and(|a| a < *n),
+                Op::Le(n) => parse_num(actual).is_some_and(|a| a <= *n),
+                Op::Regex(re) => re.is_match(actual),
+                Op::In(list) => list.iter().any(|v| v == actual),
+                Op::Has(v) => actual.contains(v.as_str()),
+            },
+        };
+        result != self.negate
+    }
+}
+
+fn compile_op(op: &str, v: &Value) -> Result<Op, String> {
+    let num = || {
+        v.as_f64()
+            .or_else(|| v.as_str().and_then(parse_num))
+            .ok_or_else(|| format!("operator `{op}` needs a number"))
+    };
+    Ok(match op {
+        "==" => Op::Eq(value_string(v)?),
+        "~=" => Op::Ne(value_string(v)?),
+        ">" => Op::Gt(num()?),
+        ">=" => Op::Ge(num()?),
+        "<" => Op::Lt(num()?),
+        "<=" => Op::Le(num()?),
+        "~~" => Op::Regex(Regex::new(&value_string(v)?).map_err(|e| e.to_string())?),
+        "~*" => {
+            Op::Regex(Regex::new(&format!("(?i){}", value_string(v)?)).map_err(|e| e.to_string())?)
+        }
+        "in" => Op::In(
+            v.as_array()
+                .ok_or("operator `in` needs an array")?
+                .iter()
+                .map(value_string)
+                .collect::<Result<_, _>>()?,
+        ),
+        "has" => Op::Has(value_string(v)?),
+        other => return Err(format!("unsupported vars operator `{other}`")),
+    })
+}
+
+fn value_string(v: &Value) -> Result<String, String> {
+    match v {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(format!("unsupported vars value `{v}`")),
+    }
+}
+
+fn parse_num(s: &str) -> Option<f64> {
+    s.trim().parse().ok()
+}
+
+/// Look up a query argument in a raw query string (no percent-decoding).
+pub fn query_arg<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| {
+        let (k, v) = pair.split_once('=').unwrap_or((pair, ""));
+        (k == name).then_some(v)
+    })
+}
+
+/// Decode `%XX` escapes and `+` (a space) in a query-string component.
+/// Invalid escapes are kept as they are.
+pub fn percent_decode(s: &str) -> Cow<'_, str> {
+    if !s.bytes().any(|b| b == b'%' || b == b'+') {
+        return Cow::Borrowed(s);
+    }
+    let hex = |b: u8| (b as char).to_digit(16).map(|d| d as u8);
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(hi), Some(lo)) => {
+                    out.push(hi << 4 | lo);
+                    i += 2;
+                }
+                _ => out.push(b'%'),
+            },
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    Cow::Owned(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// Look up a cookie value by name in a `Cookie` header.
+pub fn cookie_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split(';').find_map(|pair| {
+        let (k, v) = pair.trim().split_once('=')?;
+        (k == name).then_some(v)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn expr(v: Value) -> VarExpr {
+        VarExpr::compile(v.as_array().unwrap()).unwrap()
+    }
+
+    fn eval(v: Value, actual: Option<&'static str>) -> bool {
+        expr(v).matches(|_| actual)
+    }
+
+    #[test]
+    fn equality_and_inequality() {
+        assert!(eval(
+            json!(["http_x_env", "==", "staging"]),
+            Some("staging")
+        ));
+        assert!(!eval(json!(["http_x_env", "==", "staging"]), Some("prod")));
+        assert!(!eval(json!(["http_x_env", "==", "staging"]), None));
+        assert!(eval(json!(["http_x_env", "~=", "staging"]), Some("prod")));
+        assert!(eval(json!(["http_x_env", "~=", "staging"]), None));
+        assert!(eval(json!(["arg_v", "2"]), Some("2")));
+    }
+
+    #[test]
+    fn numeric_comparisons() {
+        assert!(eval(json!(["arg_n", ">", 10]), Some("11")));
+        assert!(!eval(json!(["arg_n", ">", 10]), Some("10")));
+        assert!(eval(json!(["arg_n", ">=", "10"]), Some("10")));
+        assert!(eval(json!(["arg_n", "<", 1.5]), Some("1")));
+        assert!(!eval(json!(["arg_n", "<=", 1]), Some("abc")));
+    }
+
+    #[test]
+    fn regex_operators() {
+        assert!(eval(
+            json!(["uri", "~~", "^/api/v[0-9]+/"]),
+            Some("/api/v2/x")
+        ));
+        assert!(!eval(
+            json!(["uri", "~~", "^/api/v[0-9]+/"]),
+            Some("/api/x")
+        ));
+        assert!(eval(json!(["http_ua", "~*", "mobile"]), Some("Foo MOBILE")));
+        assert!(!eval(
+            json!(["http_ua", "~~", "mobile"]),
+            Some("Foo MOBILE")
+        ));
+    }
+
+    #[test]
+    fn in_has_and_negation() {
+        assert!(eval(json!(["arg_r", "in", ["eu", "us"]]), Some("us")));
+        assert!(!eval(json!(["arg_r", "in", ["eu", "us"]]), Some("ap")));
+        assert!(eval(
+            json!(["http_accept", "has", "json"]),
+            Some("application/json")
+        ));
+        assert!(eval(json!(["arg_debug", "!", "==", "1"]), Some("0")));
+        assert!(eval(json!(["arg_debug", "!", "==", "1"]), None));
+        assert!(!eval(json!(["arg_debug", "!", "==", "1"]), Some("1")));
+    }
+
+    #[test]
+    fn compile_rejects_invalid_conditions() {
+        assert!(VarExpr::compile(&[]).is_err());
+        assert!(VarExpr::compile(json!(["a", "??", "b"]).as_array().unwrap()).is_err());
+        assert!(VarExpr::compile(json!(["a", "~~", "("]).as_array().unwrap()).is_err());
+        assert!(VarExpr::compile(json!(["a", ">", "x"]).as_array().unwrap()).is_err());
+        assert!(VarExpr::compile(json!(["a", "==", "b", "c"]).as_array().unwrap()).is_err());
+    }
+
+    #[test]
+    fn query_and_cookie_helpers() {
+        assert_eq!(query_arg("a=1&b=2&flag", "b"), Some("2"));
+        assert_eq!(query_arg("a=1&flag", "flag"), Some(""));
+        assert_eq!(query_arg("a=1", "c"), None);
+        assert_eq!(cookie_value("x=1; sid=abc", "sid"), Some("abc"));
+        assert_eq!(cookie_value("x=1", "sid"), None);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        assert!(matches!(percent_decode("beta"), Cow::Borrowed("beta")));
+        assert_eq!(percent_decode("caf%C3%A9+au+lait"), "café au lait");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
@@ -1,4 +1,27 @@
+//! Access log file.
+//!
+//! v2 design: Workers never touch the file. A request's entry is started
+//! when it is handled, completed when the response is sent and queued on
+//! a bounded channel; a full queue drops the entry and counts it. A writer
+//! thread formats queued entries and writes them through the rotating
+//! [`AuditFileWriter`] in batches, once `buffer_bytes` have accumulated or
+//! `flush_interval_ms` has passed.
+
+use crate::audit_file_writer::{AuditFileConfig, AuditFileWriter};
+use crate::pii_scrubber::{anonymize_ip, scrub_header};
+use ando_core::config::AccessLogConfig;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, sync_channel};
+use std::time::{Duration, Instant};
+use tracing::{error, warn};
+
+/// Entries waiting for the writer thread; more are dropped.
+const QUEUE_CAPACITY: usize = 16_384;
 
 /// Structured access log entry.
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,6 +34,379 @@ pub struct AccessLogEntry {
     pub response_status: u16,
     pub latency_ms: f64,
     pub upstream_addr: Option<String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub consumer: Option<String>,
+    /// The request's `X-Request-Id` header.
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// Why the upstream exchange failed, e.g. `connect_timeout`.
+    #[serde(default)]
+    pub upstream_error: Option<String>,
+    /// Request headers read by `$http_*` template variables, lower-case.
+    #[serde(skip)]
+    pub headers: Vec<(String, String)>,
+}
+
+// ── Format ───────────────────────────────────────────────────
+
+/// Line format of the access log file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AccessLogFormat {
+    /// One JSON object per line.
+    Json,
+    /// Text with `$variables` substituted; `-` stands in for missing values.
+    Template(Vec<Segment>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Var {
+    RemoteAddr,
+    RouteId,
+    Status,
+    /// Seconds, millisecond resolution.
+    RequestTime,
+    UpstreamAddr,
+    UpstreamError,
+    Consumer,
+    RequestId,
+    RequestMethod,
+    Uri,
+    /// `GET /path HTTP/1.1`
+    Request,
+    Host,
+    TimeLocal,
+    TimeIso8601,
+    /// `$http_user_agent` reads `user-agent`.
+    Header(String),
+}
+
+impl Var {
+    fn named(name: &str) -> Option<Self> {
+        Some(match name {
+            "remote_addr" => Self::RemoteAddr,
+            "route_id" => Self::RouteId,
+            "status" => Self::Status,
+            "request_time" => Self::RequestTime,
+            "upstream_addr" => Self::UpstreamAddr,
+            "upstream_error" => Self::UpstreamError,
+            "consumer" => Self::Consumer,
+            "request_id" => Self::RequestId,
+            "request_method" => Self::RequestMethod,
+            "uri" | "request_uri" => Self::Uri,
+            "request" => Self::Request,
+            "host" => Self::Host,
+            "time_local" => Self::TimeLocal,
+            "time_iso8601" => Self::TimeIso8601,
+            _ => {
+                let header = name.strip_prefix("http_").filter(|h| !h.is_empty())?;
+                Self::Header(header.replace('_', "-"))
+            }
+        })
+    }
+}
+
+impl AccessLogFormat {
+    /// `json`, or a template. Unknown variables are an error.
+    pub fn parse(format: &str) -> Result<Self, String> {
+        if format.trim() == "json" {
+            return Ok(Self::Json);
+        }
+        let mut segments = Vec::new();
+        let mut text = String::new();
+        let mut rest = format;
+        while let Some(pos) = rest.find('$') {
+            text.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            let len = after
+                .bytes()
+                .take_while(|b| b.is_ascii_alphanumeric() || *b == b'_')
+                .count();
+            if len == 0 {
+                text.push('$');
+                rest = after;
+                continue;
+            }
+            let name = &after[..len];
+            let var =
+                Var::named(name).ok_or_else(|| format!("unknown access log variable `${name}`"))?;
+            if !text.is_empty() {
+                segments.push(Segment::Text(std::mem::take(&mut text)));
+            }
+            segments.push(Segment::Var(var));
+            rest = &after[len..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            segments.push(Segment::Text(text));
+        }
+        Ok(Self::Template(segments))
+    }
+
+    /// Request headers the format reads.
+    pub fn headers(&self) -> Vec<String> {
+        let Self::Template(segments) = self else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = segments
+            .iter()
+            .filter_map(|s| match s {
+                Segment::Var(Var::Header(name)) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Append the line for `entry`, without the newline, to `out`.
+    pub fn render(&self, entry: &AccessLogEntry, out: &mut String) {
+        let segments = match self {
+            Self::Json => {
+                out.push_str(&serde_json::to_string(entry).unwrap_or_default());
+                return;
+            }
+            Self::Template(segments) => segments,
+        };
+        for segment in segments {
+            let var = match segment {
+                Segment::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Segment::Var(var) => var,
+            };
+            let value = match var {
+                Var::RemoteAddr => Some(entry.client_ip.as_str()),
+                Var::RouteId => Some(entry.route_id.as_str()).filter(|r| !r.is_empty()),
+                Var::Status => {
+                    let _ = write!(out, "{}", entry.response_status);
+                    continue;
+                }
+                Var::RequestTime => {
+                    let _ = write!(out, "{:.3}", entry.latency_ms / 1000.0);
+                    continue;
+                }
+                Var::UpstreamAddr => entry.upstream_addr.as_deref(),
+                Var::UpstreamError => entry.upstream_error.as_deref(),
+                Var::Consumer => entry.consumer.as_deref(),
+                Var::RequestId => entry.request_id.as_deref(),
+                Var::RequestMethod => Some(entry.method.as_str()),
+                Var::Uri => Some(entry.uri.as_str()),
+                Var::Request => {
+                    let _ = write!(out, "{} {} HTTP/1.1", entry.method, entry.uri);
+                    continue;
+                }
+                Var::Host => entry.host.as_deref(),
+                Var::TimeLocal => {
+                    match DateTime::parse_from_rfc3339(&entry.timestamp) {
+                        Ok(t) => {
+                            let _ = write!(out, "{}", t.format("%d/%b/%Y:%H:%M:%S %z"));
+                        }
+                        Err(_) => out.push_str(&entry.timestamp),
+                    }
+                    continue;
+                }
+                Var::TimeIso8601 => Some(entry.timestamp.as_str()),
+                Var::Header(name) => entry
+                    .headers
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.as_str()),
+            };
+            out.push_str(value.filter(|v| !v.is_empty()).unwrap_or("-"));
+        }
+    }
+}
+
+// ── Logger ───────────────────────────────────────────────────
+
+enum Message {
+    Entry(Box<AccessLogEntry>),
+    Flush(SyncSender<()>),
+}
+
+/// Hands finished entries to the writer thread.
+pub struct AccessLogger {
+    sender: SyncSender<Message>,
+    /// Request headers captured for `$http_*` variables.
+    headers: Vec<String>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl AccessLogger {
+    /// Open the log file and start the writer thread. `None` when
+    /// `observability.access_log` is disabled.
+    pub fn from_config(config: &AccessLogConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        let format = AccessLogFormat::parse(&config.format).map_err(anyhow::Error::msg)?;
+        let file = AuditFileWriter::new(AuditFileConfig {
+            file_path: PathBuf::from(&config.path),
+            max_file_size_bytes: config.max_file_size_bytes,
+            max_rotated_files: config.max_rotated_files,
+        })?;
+        let (sender, receiver) = sync_channel(QUEUE_CAPACITY);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let writer = LogWriter {
+            headers: format.headers(),
+            file,
+            format,
+            interval: Duration::from_millis(config.flush_interval_ms.max(1)),
+            buffer_bytes: config.buffer_bytes,
+            anonymize_ip: config.anonymize_client_ip,
+            dropped: Arc::clone(&dropped),
+        };
+        let headers = writer.headers.clone();
+        std::thread::Builder::new()
+            .name("ando-access-log".to_string())
+            .spawn(move || writer.run(receiver))?;
+        Ok(Some(Self {
+            sender,
+            headers,
+            dropped,
+        }))
+    }
+
+    /// Start the entry of a request. Status, latency, route, upstream and
+    /// consumer are filled in as the request proceeds.
+    pub fn entry(
+        &self,
+        method: &str,
+        path: &str,
+        host: Option<&str>,
+        headers: &[(&str, &str)],
+        client_ip: &str,
+    ) -> AccessLogEntry {
+        let mut request_id = None;
+        let mut captured = Vec::new();
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("x-request-id") {
+                request_id = Some(value.to_string());
+            }
+            if let Some(wanted) = self.headers.iter().find(|h| name.eq_ignore_ascii_case(h)) {
+                captured.push((wanted.clone(), value.to_string()));
+            }
+        }
+        AccessLogEntry {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            route_id: String::new(),
+            client_ip: client_ip.to_string(),
+            method: method.to_string(),
+            uri: path.to_string(),
+            response_status: 0,
+            latency_ms: 0.0,
+            upstream_addr: None,
+            host: host.map(str::to_string),
+            consumer: None,
+            request_id,
+            upstream_error: None,
+            headers: captured,
+        }
+    }
+
+    /// Queue a finished entry. Never blocks; the entry is dropped when
+    /// the writer has fallen `QUEUE_CAPACITY` entries behind.
+    #[inline]
+    pub fn log(&self, entry: AccessLogEntry) {
+        if self
+            .sender
+            .try_send(Message::Entry(Box::new(entry)))
+            .is_err()
+        {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Write every entry queued so far and wait until it is on disk.
+    pub fn flush(&self) {
+        let (ack, done) = sync_channel(1);
+        if self.sender.send(Message::Flush(ack)).is_ok() {
+            let _ = done.recv();
+        }
+    }
+}
+
+/// The writer thread's state.
+struct LogWriter {
+    file: AuditFileWriter,
+    format: AccessLogFormat,
+    headers: Vec<String>,
+    interval: Duration,
+    buffer_bytes: usize,
+    anonymize_ip: bool,
+    dropped: Arc<AtomicU64>,
+}
+
+impl LogWriter {
+    fn run(self, receiver: Receiver<Message>) {
+        let mut lines: Vec<String> = Vec::new();
+        let mut pending = 0;
+        let mut deadline = Instant::now() + self.interval;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            let mut ack = None;
+            match receiver.recv_timeout(timeout) {
+                Ok(Message::Entry(entry)) => {
+                    let line = self.line(*entry);
+                    pending += line.len() + 1;
+                    lines.push(line);
+                    if pending < self.buffer_bytes {
+                        continue;
+                    }
+                }
+                Ok(Message::Flush(done)) => ack = Some(done),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.write(&mut lines);
+                    return;
+                }
+            }
+            self.write(&mut lines);
+            pending = 0;
+            deadline = Instant::now() + self.interval;
+            if let Some(done) = ack {
+                let _ = done.send(());
+            }
+        }
+    }
+
+    /// Scrub and format one entry. Credential headers are always masked.
+    fn line(&self, mut entry: AccessLogEntry) -> String {
+        if self.anonymize_ip {
+            entry.client_ip = anonymize_ip(&entry.client_ip);
+        }
+        for (name, value) in &mut entry.headers {
+            let (scrubbed, _) = scrub_header(name, value, &[]);
+            *value = scrubbed;
+        }
+        let mut line = String::with_capacity(256);
+        self.format.render(&entry, &mut line);
+        line
+    }
+
+    fn write(&self, lines: &mut Vec<String>) {
+        let dropped = self.dropped.swap(0, Ordering::Relaxed);
+        if dropped > 0 {
+            warn!(dropped, "access log queue full, entries dropped");
+        }
+        if lines.is_empty() {
+            return;
+        }
+        if let Err(e) = self.file.write_lines(lines.iter().map(String::as_str)) {
+            error!(error = %e, "failed to write access log");
+        }
+        lines.clear();
+    }
 }
 
 #[cfg(test)]
@@ -27,9 +423,35 @@ mod tests {
             response_status: 200,
             latency_ms: 12.5,
             upstream_addr: upstream.map(str::to_string),
+            host: Some("api.example.com".into()),
+            consumer: None,
+            request_id: None,
+            upstream_error: None,
+            headers: Vec::new(),
         }
     }
 
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    fn config(format: &str, flush_interval_ms: u64, buffer_bytes: usize) -> AccessLogConfig {
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let dir =
+            std::env::temp_dir().join(format!("ando-access-log-test-{}-{}", std::process::id(), n));
+        let _ = std::fs::remove_dir_all(&dir);
+        AccessLogConfig {
+            enabled: true,
+            path: dir.join("access.log").to_string_lossy().into_owned(),
+            format: format.into(),
+            flush_interval_ms,
+            buffer_bytes,
+            ..AccessLogConfig::default()
+        }
+    }
+
+    fn read(config: &AccessLogConfig) -> String {
+        std::fs::read_to_string(&config.path).unwrap_or_default()
+    }
+
     // ── Serialisation ────────────────────────────────────────────
 
     #[test]
@@ -44,6 +466,7 @@ mod tests {
         assert_eq!(json["response_status"], 200);
         assert_eq!(json["latency_ms"], 12.5);
         assert_eq!(json["upstream_addr"], "10.0.0.1:8080");
+        assert!(json["upstream_error"].is_null());
     }
 
     #[test]
@@ -93,4 +516,106 @@ mod tests {
             assert_eq!(json["response_status"], status);
         }
     }
+
+    // ── Template ─────────────────────────────────────────────────
+
+    #[test]
+    fn template_substitutes_variables() {
+        let format = AccessLogFormat::parse(
+            "$remote_addr [$time_local] \"$request\" $status $request_time $upstream_addr $consumer $http_user_agent 5$",
+        )
+        .unwrap();
+        assert_eq!(format.headers(), vec!["user-agent".to_string()]);
+        let mut entry = sample_entry(Some("10.0.0.1:8080"));
+        entry.headers = vec![("user-agent".into(), "curl/8".into())];
+        let mut line = String::new();
+        format.render(&entry, &mut line);
+        assert_eq!(
+            line,
+            "192.168.1.1 [01/Jan/2024:00:00:00 +0000] \"GET /api/hello HTTP/1.1\" 200 0.013 10.0.0.1:8080 - curl/8 5$"
+        );
+    }
+
+    #[test]
+    fn template_names_the_upstream_error() {
+        let format = AccessLogFormat::parse("$status $upstream_error").unwrap();
+        let mut entry = sample_entry(None);
+        let mut line = String::new();
+        format.render(&entry, &mut line);
+        assert_eq!(line, "200 -");
+        entry.response_status = 504;
+        entry.upstream_error = Some("connect_timeout".into());
+        line.clear();
+        format.render(&entry, &mut line);
+        assert_eq!(line, "504 connect_timeout");
+    }
+
+    #[test]
+    fn unknown_variable_is_rejected() {
+        let err = AccessLogFormat::parse("$status $bogus").unwrap_err();
+        assert!(err.contains("$bogus"), "{err}");
+        assert_eq!(
+            AccessLogFormat::parse(" json ").unwrap(),
+            AccessLogFormat::Json
+        );
+    }
+
+    // ── Logger ───────────────────────────────────────────────────
+
+    #[test]
+    fn json_lines_are_written_on_flush_with_scrubbing() {
+        let mut config = config(
+            "$remote_addr $http_authorization $request_id",
+            60_000,
+            1 << 20,
+        );
+        config.anonymize_client_ip = true;
+        let logger = AccessLogger::from_config(&config).unwrap().unwrap();
+        let mut entry = logger.entry(
+            "GET",
+            "/a",
+            None,
+            &[
+                ("Authorization", "Bearer secret"),
+                ("X-Request-Id", "req-1"),
+            ],
+            "192.168.1.77",
+        );
+        entry.response_status = 200;
+        logger.log(entry);
+        // The flush interval is long and the buffer large: nothing yet.
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(read(&config), "");
+
+        logger.flush();
+        let written = read(&config);
+        assert!(written.starts_with("192.168.1.0 "), "{written}");
+        assert!(!written.contains("secret"), "{written}");
+        assert!(written.ends_with(" req-1\n"), "{written}");
+    }
+
+    #[test]
+    fn full_buffer_is_written_without_flush() {
+        let config = config("json", 60_000, 1);
+        let logger = AccessLogger::from_config(&config).unwrap().unwrap();
+        let mut entry = logger.entry("POST", "/b", Some("h"), &[], "10.0.0.9");
+        entry.route_id = "r1".into();
+        logger.log(entry);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while read(&config).is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        let line: serde_json::Value = serde_json::from_str(read(&config).trim()).unwrap();
+        assert_eq!(line["route_id"], "r1");
+        assert_eq!(line["host"], "h");
+    }
+
+    #[test]
+    fn disabled_config_builds_no_logger() {
+        assert!(
+            AccessLogger::from_config(&AccessLogConfig::default())
+                .unwrap()
+                .is_none()
+        );
+    }
 }
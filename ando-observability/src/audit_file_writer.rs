@@ -7,6 +7,8 @@
 //!
 //! Also supports rotation by file size (`max_file_size_bytes`).
 //!
+//! The file access logger writes through the same type, in batches.
+//!
 //! Thread-safe: uses a `Mutex<BufWriter>` internally so multiple proxy
 //! workers can write concurrently (though in practice writes happen from the
 //! single admin/audit thread).
@@ -67,10 +69,10 @@ impl AuditFileWriter {
     /// Create a new writer, opening (or creating) the audit log file.
     pub fn new(config: AuditFileConfig) -> io::Result<Self> {
         // Ensure parent directory exists
-        if let Some(parent) = config.file_path.parent() {
-            if !parent.as_os_str().is_empty() {
-                fs::create_dir_all(parent)?;
-            }
+        if let Some(parent) = config.file_path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
         }
 
         let file = OpenOptions::new()
@@ -81,7 +83,7 @@ impl AuditFileWriter {
         let current_size = file.metadata()?.len();
         let today = Utc::now().date_naive();
 
-        info!(path = %config.file_path.display(), "Audit log file writer opened");
+        info!(path = %config.file_path.display(), "Log file writer opened");
 
         Ok(Self {
             config,
@@ -95,11 +97,28 @@ impl AuditFileWriter {
 
     /// Write a single JSON audit line. Rotates if needed.
     pub fn write_line(&self, line: &str) -> io::Result<()> {
+        self.write_lines([line])
+    }
+
+    /// Write a batch of lines with one flush at the end, rotating between
+    /// lines as needed.
+    pub fn write_lines<'a>(&self, lines: impl IntoIterator<Item = &'a str>) -> io::Result<()> {
         let mut state = self
             .inner
             .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "audit writer lock poisoned"))?;
+            .map_err(|_| io::Error::other("audit writer lock poisoned"))?;
+
+        for line in lines {
+            self.rotate_if_needed(&mut state)?;
+            let bytes = line.as_bytes();
+            state.writer.write_all(bytes)?;
+            state.writer.write_all(b"\n")?;
+            state.current_size += bytes.len() as u64 + 1;
+        }
+        state.writer.flush()
+    }
 
+    fn rotate_if_needed(&self, state: &mut WriterState) -> io::Result<()> {
         let today = Utc::now().date_naive();
 
         // Check rotation conditions
@@ -107,67 +126,60 @@ impl AuditFileWriter {
         let needs_size_rotate = self.config.max_file_size_bytes > 0
             && state.current_size >= self.config.max_file_size_bytes;
 
-        if needs_date_rotate || needs_size_rotate {
-            // Flush current writer before rotation
-            state.writer.flush()?;
-            drop(std::mem::replace(
-                &mut state.writer,
-                BufWriter::new(File::create("/dev/null")?),
-            ));
-
-            // Generate rotated file name
-            let suffix = if needs_date_rotate {
-                state.current_date.format("%Y-%m-%d").to_string()
-            } else {
-                Utc::now().format("%Y-%m-%d-%H%M%S").to_string()
-            };
-
-            let rotated_path = rotated_file_path(&self.config.file_path, &suffix);
-
-            // Rename current file
-            if self.config.file_path.exists() {
-                if let Err(e) = fs::rename(&self.config.file_path, &rotated_path) {
-                    error!(
-                        error = %e,
-                        from = %self.config.file_path.display(),
-                        to = %rotated_path.display(),
-                        "Failed to rotate audit log"
-                    );
-                } else {
-                    info!(
-                        from = %self.config.file_path.display(),
-                        to = %rotated_path.display(),
-                        "Rotated audit log"
-                    );
-                }
-            }
+        if !needs_date_rotate && !needs_size_rotate {
+            return Ok(());
+        }
 
-            // Prune old rotated files
-            if self.config.max_rotated_files > 0 {
-                if let Err(e) =
-                    prune_rotated_files(&self.config.file_path, self.config.max_rotated_files)
-                {
-                    warn!(error = %e, "Failed to prune old audit log files");
-                }
-            }
+        // Flush current writer before rotation
+        state.writer.flush()?;
+        drop(std::mem::replace(
+            &mut state.writer,
+            BufWriter::new(File::create("/dev/null")?),
+        ));
+
+        // Generate rotated file name
+        let suffix = if needs_date_rotate {
+            state.current_date.format("%Y-%m-%d").to_string()
+        } else {
+            Utc::now().format("%Y-%m-%d-%H%M%S").to_string()
+        };
 
-            // Open new file
-            let new_file = OpenOptions::new()
-                .create(true)
-                .append(true)
-                .open(&self.config.file_path)?;
-            state.writer = BufWriter::new(new_file);
-            state.current_date = today;
-            state.current_size = 0;
+        let rotated_path = rotated_file_path(&self.config.file_path, &suffix);
+
+        // Rename current file
+        if self.config.file_path.exists() {
+            if let Err(e) = fs::rename(&self.config.file_path, &rotated_path) {
+                error!(
+                    error = %e,
+                    from = %self.config.file_path.display(),
+                    to = %rotated_path.display(),
+                    "Failed to rotate log file"
+                );
+            } else {
+                info!(
+                    from = %self.config.file_path.display(),
+                    to = %rotated_path.display(),
+                    "Rotated log file"
+                );
+            }
         }
 
-        // Write the line
-        let bytes = line.as_bytes();
-        state.writer.write_all(bytes)?;
-        state.writer.write_all(b"\n")?;
-        state.writer.flush()?;
-        state.current_size += bytes.len() as u64 + 1;
+        // Prune old rotated files
+        if self.config.max_rotated_files > 0
+            && let Err(e) =
+                prune_rotated_files(&self.config.file_path, self.config.max_rotated_files)
+        {
+            warn!(error = %e, "Failed to prune old rotated log files");
+        }
 
+        // Open new file
+        let new_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.config.file_path)?;
+        state.writer = BufWriter::new(new_file);
+        state.current_date = today;
+        state.current_size = 0;
         Ok(())
     }
 
@@ -176,7 +188,7 @@ impl AuditFileWriter {
         let mut state = self
             .inner
             .lock()
-            .map_err(|_| io::Error::new(io::ErrorKind::Other, "audit writer lock poisoned"))?;
+            .map_err(|_| io::Error::other("audit writer lock poisoned"))?;
         state.writer.flush()
     }
 }
@@ -194,10 +206,7 @@ fn rotated_file_path(base: &Path, suffix: &str) -> PathBuf {
 /// Remove old rotated files, keeping only the newest `keep` files.
 fn prune_rotated_files(base_path: &Path, keep: usize) -> io::Result<()> {
     let parent = base_path.parent().unwrap_or(Path::new("."));
-    let base_name = base_path
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy();
+    let base_name = base_path.file_name().unwrap_or_default().to_string_lossy();
 
     let mut rotated_files: Vec<PathBuf> = Vec::new();
 
@@ -218,7 +227,7 @@ fn prune_rotated_files(base_path: &Path, keep: usize) -> io::Result<()> {
     if rotated_files.len() > keep {
         let to_remove = rotated_files.len() - keep;
         for path in rotated_files.iter().take(to_remove) {
-            debug!(path = %path.display(), "Pruning old rotated audit log");
+            debug!(path = %path.display(), "Pruning old rotated log file");
             fs::remove_file(path)?;
         }
     }
@@ -239,11 +248,8 @@ mod tests {
 
     fn temp_dir() -> PathBuf {
         let n = COUNTER.fetch_add(1, AtomOrd::Relaxed);
-        let dir = std::env::temp_dir().join(format!(
-            "ando-audit-test-{}-{}",
-            std::process::id(),
-            n,
-        ));
+        let dir =
+            std::env::temp_dir().join(format!("ando-audit-test-{}-{}", std::process::id(), n,));
         let _ = fs::remove_dir_all(&dir);
         fs::create_dir_all(&dir).unwrap();
         dir
@@ -309,6 +315,24 @@ mod tests {
         let _ = fs::remove_dir_all(&dir);
     }
 
+    #[test]
+    fn write_lines_writes_a_batch_in_order() {
+        let dir = temp_dir();
+        let path = dir.join("access.log");
+        let config = AuditFileConfig {
+            file_path: path.clone(),
+            max_file_size_bytes: 0,
+            max_rotated_files: 0,
+        };
+        let writer = AuditFileWriter::new(config).unwrap();
+        writer.write_lines(["a", "b"]).unwrap();
+        writer.write_lines(["c"]).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\nb\nc\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn size_based_rotation_creates_rotated_file() {
         let dir = temp_dir();
@@ -334,11 +358,12 @@ mod tests {
         assert!(content.contains("second-line"));
 
         // There should be a rotated file
-        let entries: Vec<_> = fs::read_dir(&dir)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .collect();
-        assert!(entries.len() >= 2, "Expected rotated file, got {:?}", entries);
+        let entries: Vec<_> = fs::read_dir(&dir).unwrap().filter_map(|e| e.ok()).collect();
+        assert!(
+            entries.len() >= 2,
+            "Expected rotated file, got {:?}",
+            entries
+        );
 
         let _ = fs::remove_dir_all(&dir);
     }
@@ -359,11 +384,7 @@ mod tests {
         let remaining: Vec<_> = fs::read_dir(&dir)
             .unwrap()
             .filter_map(|e| e.ok())
-            .filter(|e| {
-                e.file_name()
-                    .to_string_lossy()
-                    .starts_with("audit.log.")
-            })
+            .filter(|e| e.file_name().to_string_lossy().starts_with("audit.log."))
             .collect();
         assert_eq!(remaining.len(), 2);
 
@@ -134,6 +134,111 @@ impl AuditLogEntry {
     }
 }
 
+// ─────────────────────────────────────────────────────────────
+// Admin API changes
+// ─────────────────────────────────────────────────────────────
+
+/// Kind of Admin API change.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AdminAction {
+    Create,
+    Update,
+    Delete,
+}
+
+/// Top-level fields that differ between the old and new version of an
+/// entity. Names only: values may hold credentials.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AdminDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl AdminDiff {
+    /// Compare two JSON objects; `None` stands for "does not exist".
+    pub fn between(old: Option<&serde_json::Value>, new: Option<&serde_json::Value>) -> Self {
+        let empty = serde_json::Map::new();
+        let old = old.and_then(|v| v.as_object()).unwrap_or(&empty);
+        let new = new.and_then(|v| v.as_object()).unwrap_or(&empty);
+        let mut diff = Self::default();
+        for k in new.keys() {
+            match (get(old, k), get(new, k)) {
+                (None, Some(_)) => diff.added.push(k.clone()),
+                (Some(o), Some(n)) if o != n => diff.changed.push(k.clone()),
+                _ => {}
+            }
+        }
+        for k in old.keys() {
+            if get(old, k).is_some() && get(new, k).is_none() {
+                diff.removed.push(k.clone());
+            }
+        }
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Unset optional fields serialise as `null`; treat them as absent.
+fn get<'a>(
+    obj: &'a serde_json::Map<String, serde_json::Value>,
+    key: &str,
+) -> Option<&'a serde_json::Value> {
+    obj.get(key).filter(|v| !v.is_null())
+}
+
+/// A single Admin API config change, written to the same audit trail as
+/// [`AuditLogEntry`] (SOC2 CC8.1 change management).
+///
+/// Identifies the caller by API key *id*, never by the key itself.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminAuditEntry {
+    /// ISO-8601 UTC timestamp (RFC 3339).
+    pub timestamp: String,
+    /// Id of the API key that made the change. `None` when the Admin API
+    /// runs without keys.
+    pub key_id: Option<String>,
+    /// HTTP method and path of the Admin API request.
+    pub method: String,
+    pub uri: String,
+    pub action: AdminAction,
+    /// `routes`, `services`, `upstreams`, `consumers`, …
+    pub entity_type: String,
+    pub entity_id: String,
+    pub diff: AdminDiff,
+}
+
+impl AdminAuditEntry {
+    pub fn new(
+        action: AdminAction,
+        entity_type: impl Into<String>,
+        entity_id: impl Into<String>,
+    ) -> Self {
+        Self {
+            timestamp: Utc::now().to_rfc3339(),
+            key_id: None,
+            method: String::new(),
+            uri: String::new(),
+            action,
+            entity_type: entity_type.into(),
+            entity_id: entity_id.into(),
+            diff: AdminDiff::default(),
+        }
+    }
+
+    /// Serialise to a compact JSON line suitable for log shipping.
+    pub fn to_json_line(&self) -> String {
+        serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string())
+    }
+}
+
 // ─────────────────────────────────────────────────────────────
 // Tests
 // ─────────────────────────────────────────────────────────────
@@ -272,4 +377,33 @@ mod tests {
         assert_eq!(e.deny_plugin.as_deref(), Some("plugin-b"));
         assert_eq!(e.outcome, AuditOutcome::Deny);
     }
+
+    // ── Admin API changes ─────────────────────────────────────────
+
+    #[test]
+    fn admin_diff_lists_field_names_only() {
+        let old =
+            serde_json::json!({"uri": "/a", "desc": "x", "plugins": {"key-auth": {"key": "k1"}}});
+        let new = serde_json::json!({"uri": "/a", "plugins": {"key-auth": {"key": "k2"}}, "hosts": ["h"]});
+        let diff = AdminDiff::between(Some(&old), Some(&new));
+        assert_eq!(diff.added, vec!["hosts"]);
+        assert_eq!(diff.removed, vec!["desc"]);
+        assert_eq!(diff.changed, vec!["plugins"]);
+        assert!(!serde_json::to_string(&diff).unwrap().contains("k2"));
+
+        let created = AdminDiff::between(None, Some(&new));
+        assert_eq!(created.added, vec!["hosts", "plugins", "uri"]);
+        assert!(AdminDiff::between(Some(&old), Some(&old)).is_empty());
+    }
+
+    #[test]
+    fn admin_entry_serialises_lowercase_action() {
+        let mut e = AdminAuditEntry::new(AdminAction::Delete, "routes", "r1");
+        e.key_id = Some("ops".into());
+        let json: serde_json::Value = serde_json::from_str(&e.to_json_line()).unwrap();
+        assert_eq!(json["action"], "delete");
+        assert_eq!(json["entity_type"], "routes");
+        assert_eq!(json["entity_id"], "r1");
+        assert_eq!(json["key_id"], "ops");
+    }
 }
@@ -3,5 +3,8 @@ pub mod audit_file_writer;
 pub mod audit_log;
 pub mod logger;
 pub mod metrics;
+pub mod otel;
 pub mod pii_scrubber;
 pub mod prometheus_exporter;
+pub mod statsd;
+pub mod worker_stats;
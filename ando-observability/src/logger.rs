@@ -1,32 +1,68 @@
+use crate::metrics::MetricsCollector;
 use ando_core::config::VictoriaLogsConfig;
 use chrono::Utc;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use prometheus::IntCounterVec;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_ENCODING, CONTENT_TYPE};
 use serde_json::json;
-use tokio::sync::mpsc;
-use tokio::time::{Duration, interval};
-use tracing::{debug, error};
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::{Duration, MissedTickBehavior, interval, sleep};
+use tracing::{debug, error, warn};
+
+/// Longest wait between push retries.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
+/// Timeout of one push request.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// VictoriaLogs exporter — true no-op when disabled.
 ///
-/// v2 design: When `enabled = false`, no channel or task is created.
-/// The `access_log()` method becomes a branch-predicted no-op.
+/// v2 design: When `enabled = false`, no queue or task is created.
+/// The `access_log()` method becomes a branch-predicted no-op. Otherwise
+/// records go to a bounded queue that a background task drains in
+/// batches of newline-delimited JSON, one push per batch. A full queue
+/// drops its oldest record, and a push that still fails after its
+/// retries drops its batch; both count in `ando_logs_dropped_total`.
 pub struct VictoriaLogsExporter {
-    sender: Option<mpsc::Sender<serde_json::Value>>,
+    queue: Option<Arc<LogQueue>>,
+    task: Option<JoinHandle<()>>,
 }
 
 impl VictoriaLogsExporter {
-    pub fn new(config: VictoriaLogsConfig) -> Self {
+    /// Start the push task on the current tokio runtime. Dropped records
+    /// are counted in `metrics` when it is enabled.
+    pub fn new(config: VictoriaLogsConfig, metrics: &MetricsCollector) -> Self {
         if !config.enabled {
-            return Self { sender: None };
+            return Self::disabled();
         }
 
-        let (tx, rx) = mpsc::channel(10_000);
-        tokio::spawn(Self::flush_loop(config, rx));
-        Self { sender: Some(tx) }
+        let queue = Arc::new(LogQueue {
+            records: Mutex::new(VecDeque::with_capacity(config.max_batch_size)),
+            capacity: config.queue_capacity.max(1),
+            batch_size: config.max_batch_size.max(1),
+            wake: Notify::new(),
+            closed: AtomicBool::new(false),
+            dropped: metrics.logs_dropped.clone(),
+        });
+        let task = tokio::spawn(Self::push_loop(config, Arc::clone(&queue)));
+        Self {
+            queue: Some(queue),
+            task: Some(task),
+        }
     }
 
     /// No-op constructor for disabled logging.
     pub fn disabled() -> Self {
-        Self { sender: None }
+        Self {
+            queue: None,
+            task: None,
+        }
     }
 
     #[inline]
@@ -41,10 +77,10 @@ impl VictoriaLogsExporter {
         client_ip: &str,
         upstream_addr: Option<&str>,
     ) {
-        if self.sender.is_none() {
+        let Some(ref queue) = self.queue else {
             return;
-        }
-        let entry = json!({
+        };
+        queue.push(json!({
             "_msg": format!("{} {} {} {} {:.2}ms", method, uri, status, client_ip, latency_ms),
             "_time": Utc::now().to_rfc3339(),
             "level": "info",
@@ -56,98 +92,305 @@ impl VictoriaLogsExporter {
             "latency_ms": latency_ms,
             "client_ip": client_ip,
             "upstream_addr": upstream_addr,
-        });
-        if let Some(ref sender) = self.sender {
-            let _ = sender.try_send(entry);
+        }));
+    }
+
+    /// Push every queued record, then stop the push task.
+    pub async fn shutdown(mut self) {
+        self.close();
+        if let Some(task) = self.task.take() {
+            let _ = task.await;
+        }
+    }
+
+    fn close(&self) {
+        if let Some(ref queue) = self.queue {
+            queue.closed.store(true, Ordering::Release);
+            queue.wake.notify_one();
         }
     }
 
-    async fn flush_loop(config: VictoriaLogsConfig, mut rx: mpsc::Receiver<serde_json::Value>) {
-        let client = reqwest::Client::new();
-        let mut batch: Vec<serde_json::Value> = Vec::with_capacity(config.batch_size);
-        let mut flush_interval = interval(Duration::from_secs(config.flush_interval_secs));
+    /// Push full batches as they fill, and whatever is queued every
+    /// `max_batch_delay_ms` and on shutdown.
+    async fn push_loop(config: VictoriaLogsConfig, queue: Arc<LogQueue>) {
+        let client = reqwest::Client::builder()
+            .timeout(PUSH_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        let mut tick = interval(Duration::from_millis(config.max_batch_delay_ms.max(1)));
+        tick.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        tick.tick().await;
 
         loop {
-            tokio::select! {
-                Some(entry) = rx.recv() => {
-                    batch.push(entry);
-                    if batch.len() >= config.batch_size {
-                        Self::flush(&client, &config.endpoint, &mut batch).await;
-                    }
-                }
-                _ = flush_interval.tick() => {
-                    if !batch.is_empty() {
-                        Self::flush(&client, &config.endpoint, &mut batch).await;
-                    }
+            let due = tokio::select! {
+                _ = queue.wake.notified() => false,
+                _ = tick.tick() => true,
+            };
+            let closing = queue.closed.load(Ordering::Acquire);
+            loop {
+                let batch = queue.take(due || closing);
+                if batch.is_empty() {
+                    break;
                 }
+                Self::push(&client, &config, &queue, &batch).await;
+            }
+            if closing {
+                return;
             }
         }
     }
 
-    async fn flush(client: &reqwest::Client, endpoint: &str, batch: &mut Vec<serde_json::Value>) {
-        if batch.is_empty() {
-            return;
-        }
-        let mut body = String::new();
-        for entry in batch.iter() {
-            body.push_str(&serde_json::to_string(entry).unwrap_or_default());
-            body.push('\n');
-        }
+    /// Push one batch, retrying 5xx, 429 and connection errors with
+    /// exponential backoff.
+    async fn push(
+        client: &reqwest::Client,
+        config: &VictoriaLogsConfig,
+        queue: &LogQueue,
+        batch: &[serde_json::Value],
+    ) {
         let count = batch.len();
-        match client
-            .post(endpoint)
-            .header("Content-Type", "application/stream+json")
-            .body(body)
-            .send()
-            .await
-        {
-            Ok(resp) if resp.status().is_success() => {
-                debug!(count, "Flushed logs to VictoriaLogs");
+        let body = match encode(batch, config.compress) {
+            Ok(body) => body,
+            Err(e) => {
+                error!(error = %e, count, "VictoriaLogs batch encoding failed, batch dropped");
+                queue.count_dropped("push_failed", count);
+                return;
             }
-            Ok(resp) => {
-                error!(status = %resp.status(), "VictoriaLogs flush failed");
+        };
+        let mut backoff = Duration::from_millis(config.retry_backoff_ms);
+        let mut attempt = 0;
+        loop {
+            let mut request = client
+                .post(&config.endpoint)
+                .header(CONTENT_TYPE, "application/stream+json")
+                .body(body.clone());
+            if config.compress {
+                request = request.header(CONTENT_ENCODING, "gzip");
             }
-            Err(e) => {
-                error!(error = %e, "VictoriaLogs connection error");
+            let retryable = match request.send().await {
+                Ok(resp) if resp.status().is_success() => {
+                    debug!(count, "Flushed logs to VictoriaLogs");
+                    return;
+                }
+                Ok(resp) => {
+                    let status = resp.status();
+                    warn!(%status, attempt, "VictoriaLogs push failed");
+                    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => {
+                    warn!(error = %e, attempt, "VictoriaLogs connection error");
+                    true
+                }
+            };
+            if !retryable || attempt >= config.max_retries {
+                break;
             }
+            attempt += 1;
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
         }
-        batch.clear();
+        error!(count, "VictoriaLogs push failed, batch dropped");
+        queue.count_dropped("push_failed", count);
+    }
+}
+
+impl Drop for VictoriaLogsExporter {
+    /// Let the push task send what is queued and exit.
+    fn drop(&mut self) {
+        self.close();
     }
 }
 
+/// Records waiting for the push task.
+struct LogQueue {
+    records: Mutex<VecDeque<serde_json::Value>>,
+    capacity: usize,
+    batch_size: usize,
+    /// Wakes the push task when a batch fills, and on shutdown.
+    wake: Notify,
+    closed: AtomicBool,
+    dropped: Option<IntCounterVec>,
+}
+
+impl LogQueue {
+    fn push(&self, record: serde_json::Value) {
+        let len = {
+            let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+            if records.len() >= self.capacity {
+                records.pop_front();
+                self.count_dropped("queue_full", 1);
+            }
+            records.push_back(record);
+            records.len()
+        };
+        if len >= self.batch_size {
+            self.wake.notify_one();
+        }
+    }
+
+    /// Take the next batch: a full one, or with `partial` whatever is
+    /// queued up to the batch size.
+    fn take(&self, partial: bool) -> Vec<serde_json::Value> {
+        let mut records = self.records.lock().unwrap_or_else(PoisonError::into_inner);
+        if records.len() < self.batch_size && !partial {
+            return Vec::new();
+        }
+        let n = records.len().min(self.batch_size);
+        records.drain(..n).collect()
+    }
+
+    fn count_dropped(&self, reason: &str, count: usize) {
+        if let Some(ref counter) = self.dropped {
+            counter.with_label_values(&[reason]).inc_by(count as u64);
+        }
+    }
+}
+
+/// Newline-delimited JSON, gzipped when `compress` is set.
+fn encode(batch: &[serde_json::Value], compress: bool) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::with_capacity(batch.len() * 256);
+    for record in batch {
+        serde_json::to_writer(&mut body, record)?;
+        body.push(b'\n');
+    }
+    if !compress {
+        return Ok(body);
+    }
+    let mut gzip = GzEncoder::new(Vec::with_capacity(body.len() / 4), Compression::fast());
+    gzip.write_all(&body)?;
+    gzip.finish()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ando_core::config::VictoriaLogsConfig;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
     fn disabled_config() -> VictoriaLogsConfig {
         VictoriaLogsConfig {
             enabled: false,
-            endpoint: "http://localhost:9428/insert/jsonline".to_string(),
-            batch_size: 100,
-            flush_interval_secs: 5,
+            ..VictoriaLogsConfig::default()
         }
     }
 
     fn enabled_config() -> VictoriaLogsConfig {
         VictoriaLogsConfig {
             enabled: true,
-            endpoint: "http://localhost:9428/insert/jsonline".to_string(),
-            batch_size: 100,
-            flush_interval_secs: 5,
+            max_batch_size: 100,
+            ..VictoriaLogsConfig::default()
+        }
+    }
+
+    fn no_metrics() -> MetricsCollector {
+        MetricsCollector::new(false).unwrap()
+    }
+
+    fn dropped(metrics: &MetricsCollector, reason: &str) -> u64 {
+        metrics
+            .logs_dropped
+            .as_ref()
+            .unwrap()
+            .with_label_values(&[reason])
+            .get()
+    }
+
+    fn log(exporter: &VictoriaLogsExporter, n: usize) {
+        exporter.access_log("r1", "GET", &format!("/{n}"), 200, 0.5, "127.0.0.1", None);
+    }
+
+    /// Push bodies received by `MockServer`, ungzipped.
+    type Bodies = Arc<Mutex<Vec<String>>>;
+
+    /// Minimal HTTP/1.1 server answering each request with the next
+    /// scripted status, then 204.
+    async fn mock_server(statuses: &[u16]) -> (String, Bodies) {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/insert/jsonline", listener.local_addr().unwrap());
+        let statuses = Arc::new(Mutex::new(
+            statuses.iter().copied().collect::<VecDeque<_>>(),
+        ));
+        let bodies = Bodies::default();
+        let seen = Arc::clone(&bodies);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let statuses = Arc::clone(&statuses);
+                let seen = Arc::clone(&seen);
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        let head_end = loop {
+                            if let Some(p) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                                break p + 4;
+                            }
+                            match stream.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                            }
+                        };
+                        let head = String::from_utf8_lossy(&buf[..head_end]).to_lowercase();
+                        let len: usize = head
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .map_or(0, |v| v.trim().parse().unwrap());
+                        while buf.len() < head_end + len {
+                            match stream.read(&mut chunk).await {
+                                Ok(0) | Err(_) => return,
+                                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                            }
+                        }
+                        let body: Vec<u8> = buf.drain(..head_end + len).skip(head_end).collect();
+                        let mut text = String::new();
+                        if head.contains("content-encoding: gzip") {
+                            GzDecoder::new(&body[..]).read_to_string(&mut text).unwrap();
+                        } else {
+                            text = String::from_utf8(body).unwrap();
+                        }
+                        seen.lock().unwrap().push(text);
+                        let status = statuses.lock().unwrap().pop_front().unwrap_or(204);
+                        let resp = format!("HTTP/1.1 {status} X\r\ncontent-length: 0\r\n\r\n");
+                        if stream.write_all(resp.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (endpoint, bodies)
+    }
+
+    async fn wait_for(bodies: &Bodies, n: usize) {
+        for _ in 0..500 {
+            if bodies.lock().unwrap().len() >= n {
+                return;
+            }
+            sleep(Duration::from_millis(10)).await;
         }
     }
 
+    fn batch_sizes(bodies: &Bodies) -> Vec<usize> {
+        bodies
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|b| b.lines().count())
+            .collect()
+    }
+
     #[test]
-    fn disabled_constructor_has_no_sender() {
+    fn disabled_constructor_has_no_queue() {
         let exporter = VictoriaLogsExporter::disabled();
-        assert!(exporter.sender.is_none());
+        assert!(exporter.queue.is_none());
     }
 
     #[test]
-    fn new_with_disabled_config_has_no_sender() {
-        let exporter = VictoriaLogsExporter::new(disabled_config());
-        assert!(exporter.sender.is_none());
+    fn new_with_disabled_config_has_no_queue() {
+        let exporter = VictoriaLogsExporter::new(disabled_config(), &no_metrics());
+        assert!(exporter.queue.is_none());
+        assert!(exporter.task.is_none());
     }
 
     #[test]
@@ -167,15 +410,15 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn new_with_enabled_config_has_sender() {
-        let exporter = VictoriaLogsExporter::new(enabled_config());
-        assert!(exporter.sender.is_some());
+    async fn new_with_enabled_config_has_queue() {
+        let exporter = VictoriaLogsExporter::new(enabled_config(), &no_metrics());
+        assert!(exporter.queue.is_some());
     }
 
     #[tokio::test]
     async fn access_log_on_enabled_does_not_block() {
-        let exporter = VictoriaLogsExporter::new(enabled_config());
-        // Should not block or panic — try_send returns immediately
+        let exporter = VictoriaLogsExporter::new(enabled_config(), &no_metrics());
+        // Should not block or panic — the queue never waits
         exporter.access_log("r1", "GET", "/health", 200, 0.5, "127.0.0.1", None);
         exporter.access_log(
             "r2",
@@ -186,14 +429,14 @@ mod tests {
             "10.0.0.1",
             Some("10.0.0.2:8080"),
         );
-        // Give channel consumer a moment
+        // Give the push task a moment
         tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
     }
 
     #[tokio::test]
     async fn access_log_backpressure_does_not_panic() {
-        let exporter = VictoriaLogsExporter::new(enabled_config());
-        // Flood the channel (capacity 10_000) — should never panic via try_send
+        let exporter = VictoriaLogsExporter::new(enabled_config(), &no_metrics());
+        // Flood the queue (capacity 10_000) — the oldest records go
         for i in 0..10_100u32 {
             exporter.access_log(
                 "r1",
@@ -206,4 +449,130 @@ mod tests {
             );
         }
     }
+
+    // ── Batching ─────────────────────────────────────────────────
+
+    #[test]
+    fn encode_writes_ndjson_and_gzips() {
+        let batch = [json!({"a": 1}), json!({"b": 2})];
+        let plain = encode(&batch, false).unwrap();
+        assert_eq!(plain, b"{\"a\":1}\n{\"b\":2}\n");
+        let mut unzipped = Vec::new();
+        GzDecoder::new(&encode(&batch, true).unwrap()[..])
+            .read_to_end(&mut unzipped)
+            .unwrap();
+        assert_eq!(unzipped, plain);
+    }
+
+    #[tokio::test]
+    async fn full_batches_push_at_once_and_shutdown_pushes_the_rest() {
+        let (endpoint, bodies) = mock_server(&[]).await;
+        let exporter = VictoriaLogsExporter::new(
+            VictoriaLogsConfig {
+                endpoint,
+                max_batch_size: 3,
+                max_batch_delay_ms: 60_000,
+                ..enabled_config()
+            },
+            &no_metrics(),
+        );
+        for n in 0..7 {
+            log(&exporter, n);
+        }
+        wait_for(&bodies, 2).await;
+        assert_eq!(batch_sizes(&bodies), vec![3, 3]);
+
+        exporter.shutdown().await;
+        assert_eq!(batch_sizes(&bodies), vec![3, 3, 1]);
+        let last: serde_json::Value =
+            serde_json::from_str(bodies.lock().unwrap()[2].trim()).unwrap();
+        assert_eq!(last["uri"], "/6");
+    }
+
+    #[tokio::test]
+    async fn partial_batch_pushes_after_batch_delay() {
+        let (endpoint, bodies) = mock_server(&[]).await;
+        let exporter = VictoriaLogsExporter::new(
+            VictoriaLogsConfig {
+                endpoint,
+                max_batch_size: 100,
+                max_batch_delay_ms: 20,
+                ..enabled_config()
+            },
+            &no_metrics(),
+        );
+        log(&exporter, 0);
+        log(&exporter, 1);
+        wait_for(&bodies, 1).await;
+        assert_eq!(batch_sizes(&bodies), vec![2]);
+    }
+
+    // ── Retries and drops ────────────────────────────────────────
+
+    #[tokio::test]
+    async fn failed_push_is_retried_until_accepted() {
+        let (endpoint, bodies) = mock_server(&[500, 503]).await;
+        let metrics = MetricsCollector::new(true).unwrap();
+        let exporter = VictoriaLogsExporter::new(
+            VictoriaLogsConfig {
+                endpoint,
+                retry_backoff_ms: 1,
+                ..enabled_config()
+            },
+            &metrics,
+        );
+        log(&exporter, 0);
+        exporter.shutdown().await;
+
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 3);
+        assert!(bodies.iter().all(|b| *b == bodies[0]));
+        assert_eq!(dropped(&metrics, "push_failed"), 0);
+    }
+
+    #[tokio::test]
+    async fn batch_is_dropped_after_last_retry() {
+        let (endpoint, bodies) = mock_server(&[500, 500, 500]).await;
+        let metrics = MetricsCollector::new(true).unwrap();
+        let exporter = VictoriaLogsExporter::new(
+            VictoriaLogsConfig {
+                endpoint,
+                max_retries: 1,
+                retry_backoff_ms: 1,
+                ..enabled_config()
+            },
+            &metrics,
+        );
+        log(&exporter, 0);
+        log(&exporter, 1);
+        exporter.shutdown().await;
+
+        assert_eq!(bodies.lock().unwrap().len(), 2);
+        assert_eq!(dropped(&metrics, "push_failed"), 2);
+    }
+
+    #[tokio::test]
+    async fn full_queue_drops_oldest_records() {
+        let (endpoint, bodies) = mock_server(&[]).await;
+        let metrics = MetricsCollector::new(true).unwrap();
+        let exporter = VictoriaLogsExporter::new(
+            VictoriaLogsConfig {
+                endpoint,
+                queue_capacity: 2,
+                ..enabled_config()
+            },
+            &metrics,
+        );
+        // The push task cannot run until this test yields.
+        for n in 0..5 {
+            log(&exporter, n);
+        }
+        assert_eq!(dropped(&metrics, "queue_full"), 3);
+
+        exporter.shutdown().await;
+        let bodies = bodies.lock().unwrap();
+        assert_eq!(bodies.len(), 1);
+        assert!(!bodies[0].contains("\"/2\""), "{}", bodies[0]);
+        assert!(bodies[0].contains("\"/3\"") && bodies[0].contains("\"/4\""));
+    }
 }
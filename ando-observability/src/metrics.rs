@@ -1,6 +1,14 @@
+use crate::worker_stats::WorkerStatsReport;
+use prometheus::core::Collector;
+use prometheus::local::{LocalHistogramVec, LocalIntCounterVec};
 use prometheus::{
-    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
 };
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Metrics collector — all counters are gated behind `enabled`.
 ///
@@ -13,6 +21,31 @@ pub struct MetricsCollector {
     pub http_requests_total: Option<IntCounterVec>,
     pub http_request_duration: Option<HistogramVec>,
     pub active_connections: Option<IntGauge>,
+    pub worker_connections: Option<IntGaugeVec>,
+    pub upstream_breaker_transitions: Option<IntCounterVec>,
+    pub upstream_pool_events: Option<IntCounterVec>,
+    pub plugin_config_errors: Option<IntCounterVec>,
+    pub upstream_pool_connections: Option<IntGaugeVec>,
+    pub plugin_responses: Option<IntCounterVec>,
+    pub logs_dropped: Option<IntCounterVec>,
+    pub statsd_dropped: Option<IntCounterVec>,
+    pub plugin_duration: Option<HistogramVec>,
+    pub upstream_retries: Option<IntCounterVec>,
+    pub upstream_errors: Option<IntCounterVec>,
+    pub cache_requests: Option<IntCounterVec>,
+    pub mirror_requests: Option<IntCounterVec>,
+    pub fault_injections: Option<IntCounterVec>,
+    pub config_errors: Option<IntCounterVec>,
+    pub worker_stats: Option<IntGaugeVec>,
+    pub plugin_stats: Option<IntGaugeVec>,
+    pub router_version: Option<IntGauge>,
+    pub config_last_reload: Option<IntGauge>,
+    pub config_sync_failing: Option<IntGauge>,
+    pub ssl_expiry: Option<IntGaugeVec>,
+    pub ssl_expiring: Option<IntGauge>,
+    /// Exporters fed by the workers' flushes, whether or not prometheus
+    /// is enabled.
+    sinks: Vec<Arc<dyn MetricsSink>>,
 }
 
 impl MetricsCollector {
@@ -25,30 +58,243 @@ impl MetricsCollector {
                 http_requests_total: None,
                 http_request_duration: None,
                 active_connections: None,
+                worker_connections: None,
+                upstream_breaker_transitions: None,
+                upstream_pool_events: None,
+                plugin_config_errors: None,
+                upstream_pool_connections: None,
+                plugin_responses: None,
+                logs_dropped: None,
+                statsd_dropped: None,
+                plugin_duration: None,
+                upstream_retries: None,
+                upstream_errors: None,
+                cache_requests: None,
+                mirror_requests: None,
+                fault_injections: None,
+                config_errors: None,
+                worker_stats: None,
+                plugin_stats: None,
+                router_version: None,
+                config_last_reload: None,
+                config_sync_failing: None,
+                ssl_expiry: None,
+                ssl_expiring: None,
+                sinks: Vec::new(),
             });
         }
 
         let registry = Registry::new();
 
         let http_requests_total = IntCounterVec::new(
-            Opts::new("ando_http_requests_total", "Total HTTP requests").namespace("ando"),
+            Opts::new("ando_http_requests_total", "Total HTTP requests"),
             &["route", "method", "status"],
         )?;
 
         let http_request_duration = HistogramVec::new(
-            HistogramOpts::new("ando_http_request_duration_seconds", "Request latency")
-                .namespace("ando")
-                .buckets(vec![
+            HistogramOpts::new("ando_http_request_duration_seconds", "Request latency").buckets(
+                vec![
                     0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
-                ]),
+                ],
+            ),
             &["route"],
         )?;
 
         let active_connections = IntGauge::new("ando_active_connections", "Active connections")?;
 
+        let worker_connections = IntGaugeVec::new(
+            Opts::new(
+                "ando_worker_connections",
+                "Open client connections per worker",
+            ),
+            &["worker"],
+        )?;
+
+        let upstream_breaker_transitions = IntCounterVec::new(
+            Opts::new(
+                "ando_upstream_breaker_transitions_total",
+                "Upstream node circuit breaker state changes",
+            ),
+            &["node", "state"],
+        )?;
+
+        let upstream_pool_events = IntCounterVec::new(
+            Opts::new(
+                "ando_upstream_pool_events_total",
+                "Upstream connection pool hits, misses and evictions",
+            ),
+            &["event"],
+        )?;
+
+        let plugin_config_errors = IntCounterVec::new(
+            Opts::new(
+                "ando_plugin_config_errors_total",
+                "Plugins skipped because their config failed to load",
+            ),
+            &["route", "plugin"],
+        )?;
+
+        let upstream_pool_connections = IntGaugeVec::new(
+            Opts::new(
+                "ando_upstream_pool_connections",
+                "Idle pooled upstream connections",
+            ),
+            &["addr"],
+        )?;
+
+        let plugin_responses = IntCounterVec::new(
+            Opts::new(
+                "ando_plugin_responses_total",
+                "Requests answered by a plugin instead of the upstream",
+            ),
+            &["plugin", "status"],
+        )?;
+
+        let logs_dropped = IntCounterVec::new(
+            Opts::new(
+                "ando_logs_dropped_total",
+                "Log records lost before reaching the log backend",
+            ),
+            &["reason"],
+        )?;
+
+        let statsd_dropped = IntCounterVec::new(
+            Opts::new(
+                "ando_statsd_dropped_total",
+                "Worker metric batches (queue_full) and packets (send_failed) the StatsD exporter lost",
+            ),
+            &["reason"],
+        )?;
+
+        let plugin_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "ando_plugin_duration_seconds",
+                "Wall time of a single plugin call",
+            )
+            .buckets(vec![
+                0.00005, 0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1,
+                0.25, 0.5, 1.0,
+            ]),
+            &["plugin", "phase"],
+        )?;
+
+        let upstream_retries = IntCounterVec::new(
+            Opts::new(
+                "ando_upstream_retries_total",
+                "Upstream attempts retried on another node, and retries refused",
+            ),
+            &["route", "outcome"],
+        )?;
+
+        let upstream_errors = IntCounterVec::new(
+            Opts::new(
+                "ando_upstream_errors_total",
+                "Failed upstream exchanges by kind (connect, connect_timeout, timeout, exchange, invalid_response, truncated)",
+            ),
+            &["route", "kind"],
+        )?;
+
+        let cache_requests = IntCounterVec::new(
+            Opts::new(
+                "ando_cache_requests_total",
+                "proxy-cache lookups by outcome (HIT, MISS, BYPASS)",
+            ),
+            &["route", "status"],
+        )?;
+
+        let mirror_requests = IntCounterVec::new(
+            Opts::new(
+                "ando_mirror_requests_total",
+                "Request copies for proxy-mirror, queued or dropped on a full queue",
+            ),
+            &["route", "outcome"],
+        )?;
+
+        let fault_injections = IntCounterVec::new(
+            Opts::new(
+                "ando_fault_injections_total",
+                "Faults injected by fault-injection (abort, delay)",
+            ),
+            &["route", "fault"],
+        )?;
+
+        let config_errors = IntCounterVec::new(
+            Opts::new(
+                "ando_config_errors_total",
+                "Requests refused because their route has no upstream or references missing config",
+            ),
+            &["kind"],
+        )?;
+
+        let worker_stats = IntGaugeVec::new(
+            Opts::new(
+                "ando_worker_stats",
+                "Per-worker accepts, open connections, requests, 4xx/5xx responses \
+                 and pooled upstream connections, refreshed on scrape",
+            ),
+            &["worker", "stat"],
+        )?;
+
+        let plugin_stats = IntGaugeVec::new(
+            Opts::new(
+                "ando_plugin_stats",
+                "Per-route state plugins share across workers, such as limit-conn \
+                 requests in flight, refreshed on scrape",
+            ),
+            &["plugin", "route", "stat"],
+        )?;
+
+        let router_version = IntGauge::new("ando_router_version", "Version of the router in use")?;
+
+        let config_last_reload = IntGauge::new(
+            "ando_config_last_reload_timestamp_seconds",
+            "Unix time of the last config change applied to the workers",
+        )?;
+
+        let config_sync_failing = IntGauge::new(
+            "ando_config_sync_failing",
+            "1 while loading config from its source fails, else 0",
+        )?;
+
+        let ssl_expiry = IntGaugeVec::new(
+            Opts::new(
+                "ando_ssl_certificate_expiry_timestamp_seconds",
+                "Unix time each SSL object's certificate expires, refreshed on scrape",
+            ),
+            &["ssl"],
+        )?;
+
+        let ssl_expiring = IntGauge::new(
+            "ando_ssl_certificates_expiring",
+            "Enabled SSL certificates expired or expiring within \
+             ssl.expiry_warning_days, refreshed on scrape",
+        )?;
+
         registry.register(Box::new(http_requests_total.clone()))?;
         registry.register(Box::new(http_request_duration.clone()))?;
         registry.register(Box::new(active_connections.clone()))?;
+        registry.register(Box::new(worker_connections.clone()))?;
+        registry.register(Box::new(upstream_breaker_transitions.clone()))?;
+        registry.register(Box::new(upstream_pool_events.clone()))?;
+        registry.register(Box::new(plugin_config_errors.clone()))?;
+        registry.register(Box::new(upstream_pool_connections.clone()))?;
+        registry.register(Box::new(plugin_responses.clone()))?;
+        registry.register(Box::new(logs_dropped.clone()))?;
+        registry.register(Box::new(statsd_dropped.clone()))?;
+        registry.register(Box::new(plugin_duration.clone()))?;
+        registry.register(Box::new(upstream_retries.clone()))?;
+        registry.register(Box::new(upstream_errors.clone()))?;
+        registry.register(Box::new(cache_requests.clone()))?;
+        registry.register(Box::new(mirror_requests.clone()))?;
+        registry.register(Box::new(fault_injections.clone()))?;
+        registry.register(Box::new(config_errors.clone()))?;
+        registry.register(Box::new(worker_stats.clone()))?;
+        registry.register(Box::new(plugin_stats.clone()))?;
+        registry.register(Box::new(router_version.clone()))?;
+        registry.register(Box::new(config_last_reload.clone()))?;
+        registry.register(Box::new(config_sync_failing.clone()))?;
+        registry.register(Box::new(ssl_expiry.clone()))?;
+        registry.register(Box::new(ssl_expiring.clone()))?;
 
         Ok(Self {
             enabled: true,
@@ -56,6 +302,29 @@ impl MetricsCollector {
             http_requests_total: Some(http_requests_total),
             http_request_duration: Some(http_request_duration),
             active_connections: Some(active_connections),
+            worker_connections: Some(worker_connections),
+            upstream_breaker_transitions: Some(upstream_breaker_transitions),
+            upstream_pool_events: Some(upstream_pool_events),
+            plugin_config_errors: Some(plugin_config_errors),
+            upstream_pool_connections: Some(upstream_pool_connections),
+            plugin_responses: Some(plugin_responses),
+            logs_dropped: Some(logs_dropped),
+            statsd_dropped: Some(statsd_dropped),
+            plugin_duration: Some(plugin_duration),
+            upstream_retries: Some(upstream_retries),
+            upstream_errors: Some(upstream_errors),
+            cache_requests: Some(cache_requests),
+            mirror_requests: Some(mirror_requests),
+            fault_injections: Some(fault_injections),
+            config_errors: Some(config_errors),
+            worker_stats: Some(worker_stats),
+            plugin_stats: Some(plugin_stats),
+            router_version: Some(router_version),
+            config_last_reload: Some(config_last_reload),
+            config_sync_failing: Some(config_sync_failing),
+            ssl_expiry: Some(ssl_expiry),
+            ssl_expiring: Some(ssl_expiring),
+            sinks: Vec::new(),
         })
     }
 
@@ -77,6 +346,218 @@ impl MetricsCollector {
         }
     }
 
+    /// Record a circuit breaker transition for an upstream node (no-op when
+    /// disabled). `state` is `"open"` or `"closed"`.
+    pub fn record_breaker_transition(&self, node: &str, state: &str) {
+        if let Some(ref counter) = self.upstream_breaker_transitions {
+            counter.with_label_values(&[node, state]).inc();
+        }
+    }
+
+    /// Add a worker's connection pool counters since its last report
+    /// (no-op when disabled).
+    pub fn record_pool_events(&self, hits: u64, misses: u64, evictions: u64) {
+        if let Some(ref counter) = self.upstream_pool_events {
+            counter.with_label_values(&["hit"]).inc_by(hits);
+            counter.with_label_values(&["miss"]).inc_by(misses);
+            counter.with_label_values(&["eviction"]).inc_by(evictions);
+        }
+    }
+
+    /// Count a plugin dropped from a route's pipeline because `configure()`
+    /// failed (no-op when disabled).
+    pub fn record_plugin_config_error(&self, route: &str, plugin: &str) {
+        if let Some(ref counter) = self.plugin_config_errors {
+            counter.with_label_values(&[route, plugin]).inc();
+        }
+    }
+
+    /// Count a request answered by `plugin` (no-op when disabled).
+    pub fn record_plugin_response(&self, plugin: &str, status: u16) {
+        if let Some(ref counter) = self.plugin_responses {
+            let mut buf = itoa::Buffer::new();
+            counter
+                .with_label_values(&[plugin, buf.format(status)])
+                .inc();
+        }
+    }
+
+    /// Count an upstream retry decision for `route` (no-op when disabled).
+    /// `outcome` is `"retried"`, or `"exhausted"` when the attempts or the
+    /// time budget ran out, or no untried node was left.
+    pub fn record_upstream_retry(&self, route: &str, outcome: &str) {
+        if let Some(ref counter) = self.upstream_retries {
+            counter.with_label_values(&[route, outcome]).inc();
+        }
+    }
+
+    /// Count a request refused over a dangling config reference (no-op
+    /// when disabled). `kind` is e.g. `"missing_upstream"`.
+    pub fn record_config_error(&self, kind: &str) {
+        if let Some(ref counter) = self.config_errors {
+            counter.with_label_values(&[kind]).inc();
+        }
+    }
+
+    /// Record how long one plugin call took (no-op when disabled).
+    pub fn record_plugin_duration(&self, plugin: &str, phase: &str, duration_secs: f64) {
+        if let Some(ref hist) = self.plugin_duration {
+            hist.with_label_values(&[plugin, phase])
+                .observe(duration_secs);
+        }
+    }
+
+    /// Publish the workers' runtime counters (no-op when disabled).
+    pub fn report_workers(&self, report: &WorkerStatsReport) {
+        let Some(ref gauge) = self.worker_stats else {
+            return;
+        };
+        let mut buf = itoa::Buffer::new();
+        for (id, w) in report.workers.iter().enumerate() {
+            let worker = buf.format(id);
+            for (stat, value) in [
+                ("accepts", w.accepts),
+                ("connections", w.connections),
+                ("requests", w.requests),
+                ("responses_4xx", w.responses_4xx),
+                ("responses_5xx", w.responses_5xx),
+                ("pool_connections", w.pool_connections),
+            ] {
+                gauge
+                    .with_label_values(&[worker, stat])
+                    .set(value.min(i64::MAX as u64) as i64);
+            }
+        }
+    }
+
+    /// Replace the plugin stats with `stats`, as `(plugin, route, stat,
+    /// value)`, so routes that are gone drop out (no-op when disabled).
+    pub fn report_plugin_stats<'a>(
+        &self,
+        stats: impl IntoIterator<Item = (&'a str, &'a str, &'a str, u64)>,
+    ) {
+        let Some(ref gauge) = self.plugin_stats else {
+            return;
+        };
+        gauge.reset();
+        for (plugin, route, stat, value) in stats {
+            gauge
+                .with_label_values(&[plugin, route, stat])
+                .set(value.min(i64::MAX as u64) as i64);
+        }
+    }
+
+    /// Publish the router version, when config was last applied and
+    /// whether syncing it currently fails (no-op when disabled).
+    pub fn report_config(&self, version: u64, last_reload: Option<SystemTime>, failing: bool) {
+        if let Some(ref gauge) = self.router_version {
+            gauge.set(version.min(i64::MAX as u64) as i64);
+        }
+        if let Some(ref gauge) = self.config_last_reload
+            && let Some(at) = last_reload.and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        {
+            gauge.set(at.as_secs() as i64);
+        }
+        if let Some(ref gauge) = self.config_sync_failing {
+            gauge.set(failing as i64);
+        }
+    }
+
+    /// Replace the certificate expiry times with `certs`, as `(ssl id,
+    /// notAfter Unix time)`, and publish how many are expiring (no-op
+    /// when disabled).
+    pub fn report_ssl<'a>(&self, certs: impl IntoIterator<Item = (&'a str, i64)>, expiring: usize) {
+        if let Some(ref gauge) = self.ssl_expiry {
+            gauge.reset();
+            for (id, not_after) in certs {
+                gauge.with_label_values(&[id]).set(not_after);
+            }
+        }
+        if let Some(ref gauge) = self.ssl_expiring {
+            gauge.set(expiring as i64);
+        }
+    }
+
+    /// Call count and estimated p50/p99 of every plugin since start, over
+    /// all phases, ordered by plugin name. Quantiles are interpolated
+    /// within `ando_plugin_duration_seconds` buckets. Empty when disabled.
+    pub fn plugin_latencies(&self) -> Vec<PluginLatency> {
+        let Some(ref hist) = self.plugin_duration else {
+            return Vec::new();
+        };
+        // Per plugin: upper bounds and cumulative counts, summed over phases.
+        let mut buckets: BTreeMap<String, (u64, Vec<(f64, u64)>)> = BTreeMap::new();
+        for family in hist.collect() {
+            for metric in family.get_metric() {
+                let Some(plugin) = metric.get_label().iter().find(|l| l.get_name() == "plugin")
+                else {
+                    continue;
+                };
+                let h = metric.get_histogram();
+                let (count, sums) = buckets.entry(plugin.get_value().to_string()).or_default();
+                *count += h.get_sample_count();
+                if sums.is_empty() {
+                    sums.extend(h.get_bucket().iter().map(|b| (b.get_upper_bound(), 0)));
+                }
+                for (sum, b) in sums.iter_mut().zip(h.get_bucket()) {
+                    sum.1 += b.get_cumulative_count();
+                }
+            }
+        }
+        buckets
+            .into_iter()
+            .filter(|(_, (count, _))| *count > 0)
+            .map(|(plugin, (calls, sums))| PluginLatency {
+                plugin,
+                calls,
+                p50_seconds: quantile(&sums, calls, 0.5),
+                p99_seconds: quantile(&sums, calls, 0.99),
+            })
+            .collect()
+    }
+
+    /// Also hand the request series the workers flush to `sink`.
+    pub fn with_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// Whether an exporter is fed besides prometheus.
+    pub fn has_sinks(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+
+    /// Per-thread buffer for the hot-path series. `None` when disabled
+    /// and no sink is attached.
+    pub fn local(&self) -> Option<LocalMetrics> {
+        let series = self.local_series();
+        if series.is_none() && self.sinks.is_empty() {
+            return None;
+        }
+        Some(LocalMetrics {
+            series,
+            batch: MetricsBatch::default(),
+            sinks: self.sinks.clone(),
+        })
+    }
+
+    fn local_series(&self) -> Option<LocalSeries> {
+        Some(LocalSeries {
+            requests: self.http_requests_total.as_ref()?.local(),
+            duration: self.http_request_duration.as_ref()?.local(),
+            plugin_responses: self.plugin_responses.as_ref()?.local(),
+            upstream_errors: self.upstream_errors.as_ref()?.local(),
+            cache_requests: self.cache_requests.as_ref()?.local(),
+            mirror_requests: self.mirror_requests.as_ref()?.local(),
+            fault_injections: self.fault_injections.as_ref()?.local(),
+            pool_connections: self.upstream_pool_connections.clone()?,
+            reported_pool: HashMap::new(),
+            active_connections: self.active_connections.clone()?,
+            worker_connections: self.worker_connections.clone()?,
+            reported_connections: 0,
+        })
+    }
+
     /// Render prometheus text exposition format.
     pub fn render(&self) -> String {
         if let Some(ref registry) = self.registry {
@@ -95,6 +576,297 @@ impl MetricsCollector {
     }
 }
 
+/// Latency summary of one plugin, from [`MetricsCollector::plugin_latencies`].
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginLatency {
+    pub plugin: String,
+    pub calls: u64,
+    pub p50_seconds: f64,
+    pub p99_seconds: f64,
+}
+
+/// Estimate the `q` quantile of `count` samples from cumulative
+/// `(upper_bound, count)` buckets, interpolating linearly inside the bucket
+/// it falls in. Samples past the last bound are reported at that bound.
+fn quantile(buckets: &[(f64, u64)], count: u64, q: f64) -> f64 {
+    let rank = q * count as f64;
+    let (mut lower, mut below) = (0.0, 0);
+    for &(upper, cumulative) in buckets {
+        if cumulative as f64 >= rank && cumulative > below {
+            let share = (rank - below as f64) / (cumulative - below) as f64;
+            return lower + (upper - lower) * share;
+        }
+        (lower, below) = (upper, cumulative);
+    }
+    lower
+}
+
+/// Receives the request series every worker flushes, for exporters that
+/// push to an agent instead of being scraped, such as
+/// [`crate::statsd::StatsdExporter`].
+///
+/// `submit` runs on a worker thread once per flush and must not block.
+pub trait MetricsSink: Send + Sync {
+    fn submit(&self, batch: MetricsBatch);
+}
+
+/// Durations a [`MetricsBatch`] keeps per series; later requests of the
+/// series are only counted.
+pub const MAX_BATCH_SAMPLES: usize = 10_000;
+
+/// Requests one worker served between two flushes, by route.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsBatch {
+    pub routes: HashMap<String, Vec<RequestSeries>>,
+}
+
+/// Requests of a route with the same method and status.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestSeries {
+    pub method: String,
+    pub status: u16,
+    pub count: u64,
+    /// In seconds, for the first [`MAX_BATCH_SAMPLES`] requests.
+    pub durations: Vec<f64>,
+}
+
+impl MetricsBatch {
+    #[inline]
+    pub fn record(&mut self, route: &str, method: &str, status: u16, duration_secs: f64) {
+        if !self.routes.contains_key(route) {
+            self.routes.insert(route.to_string(), Vec::new());
+        }
+        let Some(series) = self.routes.get_mut(route) else {
+            return;
+        };
+        let index = match series
+            .iter()
+            .position(|s| s.status == status && s.method == method)
+        {
+            Some(index) => index,
+            None => {
+                series.push(RequestSeries {
+                    method: method.to_string(),
+                    status,
+                    count: 0,
+                    durations: Vec::new(),
+                });
+                series.len() - 1
+            }
+        };
+        let series = &mut series[index];
+        series.count += 1;
+        if series.durations.len() < MAX_BATCH_SAMPLES {
+            series.durations.push(duration_secs);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// Per-thread buffer in front of a [`MetricsCollector`].
+///
+/// v2 design: Mirrors the worker's thread-local caches. The hot path bumps
+/// plain per-thread counters and histograms; `flush`, called periodically
+/// by the worker, adds them to the shared atomics that `/metrics` renders
+/// and hands the requests to the collector's sinks as one batch.
+/// No cross-core cache-line traffic per request.
+pub struct LocalMetrics {
+    /// Prometheus buffers; `None` when only sinks are fed.
+    series: Option<LocalSeries>,
+    /// Requests since the last flush, for `sinks`.
+    batch: MetricsBatch,
+    sinks: Vec<Arc<dyn MetricsSink>>,
+}
+
+struct LocalSeries {
+    requests: LocalIntCounterVec,
+    duration: LocalHistogramVec,
+    plugin_responses: LocalIntCounterVec,
+    upstream_errors: LocalIntCounterVec,
+    cache_requests: LocalIntCounterVec,
+    mirror_requests: LocalIntCounterVec,
+    fault_injections: LocalIntCounterVec,
+    pool_connections: IntGaugeVec,
+    /// This thread's share of `pool_connections`, per address.
+    reported_pool: HashMap<String, i64>,
+    active_connections: IntGauge,
+    worker_connections: IntGaugeVec,
+    /// This thread's share of `active_connections`.
+    reported_connections: i64,
+}
+
+impl LocalMetrics {
+    #[inline]
+    pub fn record_request(&mut self, route: &str, method: &str, status: u16, duration_secs: f64) {
+        if let Some(ref mut series) = self.series {
+            series.record_request(route, method, status, duration_secs);
+        }
+        if !self.sinks.is_empty() {
+            self.batch.record(route, method, status, duration_secs);
+        }
+    }
+
+    #[inline]
+    pub fn record_plugin_response(&mut self, plugin: &str, status: u16) {
+        if let Some(ref mut series) = self.series {
+            series.record_plugin_response(plugin, status);
+        }
+    }
+
+    /// Count a failed upstream exchange on `route`; `kind` is e.g.
+    /// `"connect"`, `"timeout"` or `"truncated"`.
+    #[inline]
+    pub fn record_upstream_error(&mut self, route: &str, kind: &str) {
+        if let Some(ref mut series) = self.series {
+            series.record_upstream_error(route, kind);
+        }
+    }
+
+    /// Count a proxy-cache lookup on `route`; `status` is `"HIT"`,
+    /// `"MISS"` or `"BYPASS"`.
+    #[inline]
+    pub fn record_cache_status(&mut self, route: &str, status: &str) {
+        if let Some(ref mut series) = self.series {
+            series.record_cache_status(route, status);
+        }
+    }
+
+    /// Count a proxy-mirror copy of a request on `route`; `outcome` is
+    /// `"queued"` or `"dropped"`.
+    #[inline]
+    pub fn record_mirror(&mut self, route: &str, outcome: &str) {
+        if let Some(ref mut series) = self.series {
+            series.record_mirror(route, outcome);
+        }
+    }
+
+    /// Count a fault injected into a request on `route`; `fault` is
+    /// `"abort"` or `"delay"`.
+    pub fn record_fault(&mut self, route: &str, fault: &str) {
+        if let Some(ref mut series) = self.series {
+            series.record_fault(route, fault);
+        }
+    }
+
+    /// Replace this thread's idle connection counts. Applied as deltas so
+    /// the shared gauge sums every worker's pool.
+    pub fn report_pool_connections<'a>(&mut self, idle: impl Iterator<Item = (&'a str, usize)>) {
+        if let Some(ref mut series) = self.series {
+            series.report_pool_connections(idle);
+        }
+    }
+
+    /// Replace this thread's open client connection count: its own
+    /// `worker` series, and its share of the total.
+    pub fn report_open_connections(&mut self, worker: &str, open: usize) {
+        if let Some(ref mut series) = self.series {
+            series.report_open_connections(worker, open);
+        }
+    }
+
+    /// Add everything recorded since the last flush to the shared series,
+    /// and hand the requests to the sinks.
+    pub fn flush(&mut self) {
+        if let Some(ref series) = self.series {
+            series.flush();
+        }
+        if self.batch.is_empty() {
+            return;
+        }
+        let batch = std::mem::take(&mut self.batch);
+        if let Some((last, rest)) = self.sinks.split_last() {
+            for sink in rest {
+                sink.submit(batch.clone());
+            }
+            last.submit(batch);
+        }
+    }
+}
+
+impl LocalSeries {
+    fn record_request(&mut self, route: &str, method: &str, status: u16, duration_secs: f64) {
+        let mut buf = itoa::Buffer::new();
+        self.requests
+            .with_label_values(&[route, method, buf.format(status)])
+            .inc();
+        self.duration
+            .with_label_values(&[route])
+            .observe(duration_secs);
+    }
+
+    fn record_plugin_response(&mut self, plugin: &str, status: u16) {
+        let mut buf = itoa::Buffer::new();
+        self.plugin_responses
+            .with_label_values(&[plugin, buf.format(status)])
+            .inc();
+    }
+
+    fn record_upstream_error(&mut self, route: &str, kind: &str) {
+        self.upstream_errors.with_label_values(&[route, kind]).inc();
+    }
+
+    fn record_cache_status(&mut self, route: &str, status: &str) {
+        self.cache_requests
+            .with_label_values(&[route, status])
+            .inc();
+    }
+
+    fn record_mirror(&mut self, route: &str, outcome: &str) {
+        self.mirror_requests
+            .with_label_values(&[route, outcome])
+            .inc();
+    }
+
+    fn record_fault(&mut self, route: &str, fault: &str) {
+        self.fault_injections
+            .with_label_values(&[route, fault])
+            .inc();
+    }
+
+    fn report_pool_connections<'a>(&mut self, idle: impl Iterator<Item = (&'a str, usize)>) {
+        let mut current: HashMap<String, i64> =
+            idle.map(|(addr, n)| (addr.to_string(), n as i64)).collect();
+        for (addr, before) in &self.reported_pool {
+            let now = current.get(addr).copied().unwrap_or(0);
+            if now != *before {
+                self.pool_connections
+                    .with_label_values(&[addr])
+                    .add(now - before);
+            }
+        }
+        for (addr, now) in &current {
+            if !self.reported_pool.contains_key(addr) && *now != 0 {
+                self.pool_connections.with_label_values(&[addr]).add(*now);
+            }
+        }
+        current.retain(|_, n| *n != 0);
+        self.reported_pool = current;
+    }
+
+    fn report_open_connections(&mut self, worker: &str, open: usize) {
+        let open = open as i64;
+        self.worker_connections
+            .with_label_values(&[worker])
+            .set(open);
+        self.active_connections
+            .add(open - self.reported_connections);
+        self.reported_connections = open;
+    }
+
+    fn flush(&self) {
+        self.requests.flush();
+        self.duration.flush();
+        self.plugin_responses.flush();
+        self.upstream_errors.flush();
+        self.cache_requests.flush();
+        self.mirror_requests.flush();
+        self.fault_injections.flush();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -108,6 +880,51 @@ mod tests {
         assert!(mc.http_requests_total.is_none());
         assert!(mc.http_request_duration.is_none());
         assert!(mc.active_connections.is_none());
+        assert!(mc.worker_connections.is_none());
+        assert!(mc.upstream_breaker_transitions.is_none());
+        assert!(mc.upstream_pool_events.is_none());
+        assert!(mc.plugin_config_errors.is_none());
+        assert!(mc.upstream_pool_connections.is_none());
+        assert!(mc.plugin_responses.is_none());
+        assert!(mc.logs_dropped.is_none());
+        assert!(mc.statsd_dropped.is_none());
+        assert!(mc.plugin_duration.is_none());
+        assert!(mc.upstream_retries.is_none());
+        assert!(mc.upstream_errors.is_none());
+        assert!(mc.cache_requests.is_none());
+        assert!(mc.mirror_requests.is_none());
+        assert!(mc.fault_injections.is_none());
+        assert!(mc.config_errors.is_none());
+        assert!(mc.worker_stats.is_none());
+        assert!(mc.router_version.is_none());
+        assert!(mc.local().is_none());
+        mc.record_plugin_duration("p", "access", 0.1);
+        assert!(mc.plugin_latencies().is_empty());
+    }
+
+    // ── Plugin latency ───────────────────────────────────────────
+
+    #[test]
+    fn plugin_latencies_summarize_all_phases() {
+        let mc = MetricsCollector::new(true).unwrap();
+        for _ in 0..98 {
+            mc.record_plugin_duration("key-auth", "access", 0.0002);
+        }
+        mc.record_plugin_duration("key-auth", "rewrite", 0.3);
+        mc.record_plugin_duration("key-auth", "rewrite", 0.3);
+        mc.record_plugin_duration("cors", "header_filter", 2.0);
+
+        let stats = mc.plugin_latencies();
+        let names: Vec<_> = stats.iter().map(|s| s.plugin.as_str()).collect();
+        assert_eq!(names, ["cors", "key-auth"]);
+
+        let auth = &stats[1];
+        assert_eq!(auth.calls, 100);
+        assert!(auth.p50_seconds > 0.0001 && auth.p50_seconds <= 0.00025);
+        assert!(auth.p99_seconds > 0.25 && auth.p99_seconds <= 0.5);
+        // Past the last bucket: reported at its bound.
+        assert_eq!(stats[0].p99_seconds, 1.0);
+        assert!(mc.render().contains("ando_plugin_duration_seconds_bucket"));
     }
 
     #[test]
@@ -133,6 +950,14 @@ mod tests {
         assert!(mc.http_requests_total.is_some());
         assert!(mc.http_request_duration.is_some());
         assert!(mc.active_connections.is_some());
+        assert!(mc.worker_connections.is_some());
+        assert!(mc.upstream_breaker_transitions.is_some());
+        assert!(mc.upstream_pool_events.is_some());
+        assert!(mc.plugin_config_errors.is_some());
+        assert!(mc.upstream_pool_connections.is_some());
+        assert!(mc.plugin_responses.is_some());
+        assert!(mc.logs_dropped.is_some());
+        assert!(mc.statsd_dropped.is_some());
     }
 
     #[test]
@@ -142,6 +967,7 @@ mod tests {
         let output = mc.render();
         assert!(output.contains("ando_http_requests_total"));
         assert!(output.contains("ando_http_request_duration_seconds"));
+        assert!(!output.contains("ando_ando_"), "{output}");
     }
 
     #[test]
@@ -184,4 +1010,213 @@ mod tests {
             1
         );
     }
+
+    #[test]
+    fn breaker_transitions_counted_per_node_and_state() {
+        let mc = MetricsCollector::new(true).unwrap();
+        mc.record_breaker_transition("10.0.0.1:80", "open");
+        mc.record_breaker_transition("10.0.0.1:80", "closed");
+        mc.record_breaker_transition("10.0.0.1:80", "open");
+
+        let counter = mc.upstream_breaker_transitions.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["10.0.0.1:80", "open"]).get(), 2);
+        assert_eq!(
+            counter.with_label_values(&["10.0.0.1:80", "closed"]).get(),
+            1
+        );
+        MetricsCollector::new(false)
+            .unwrap()
+            .record_breaker_transition("x", "open");
+    }
+
+    #[test]
+    fn pool_events_accumulate_per_event() {
+        let mc = MetricsCollector::new(true).unwrap();
+        mc.record_pool_events(5, 2, 1);
+        mc.record_pool_events(3, 0, 1);
+
+        let counter = mc.upstream_pool_events.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["hit"]).get(), 8);
+        assert_eq!(counter.with_label_values(&["miss"]).get(), 2);
+        assert_eq!(counter.with_label_values(&["eviction"]).get(), 2);
+        MetricsCollector::new(false)
+            .unwrap()
+            .record_pool_events(1, 1, 1);
+    }
+
+    #[test]
+    fn worker_and_config_gauges_are_rendered() {
+        let mc = MetricsCollector::new(true).unwrap();
+        let stats = crate::worker_stats::WorkerStats::new(2);
+        stats.worker(1).request();
+        stats.worker(1).response(503);
+        mc.report_workers(&stats.report());
+        mc.report_config(
+            7,
+            Some(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+            true,
+        );
+
+        let gauge = mc.worker_stats.as_ref().unwrap();
+        assert_eq!(gauge.with_label_values(&["1", "requests"]).get(), 1);
+        assert_eq!(gauge.with_label_values(&["1", "responses_5xx"]).get(), 1);
+        assert_eq!(gauge.with_label_values(&["0", "requests"]).get(), 0);
+        let text = mc.render();
+        assert!(text.contains("ando_router_version 7"));
+        assert!(text.contains("ando_config_last_reload_timestamp_seconds 1700000000"));
+        assert!(text.contains("ando_config_sync_failing 1"));
+    }
+
+    #[test]
+    fn ssl_expiry_replaces_the_previous_report() {
+        let mc = MetricsCollector::new(true).unwrap();
+        mc.report_ssl([("a", 1_700_000_000), ("b", 1_800_000_000)], 1);
+        mc.report_ssl([("b", 1_800_000_000)], 0);
+        let text = mc.render();
+        assert!(!text.contains("ssl=\"a\""));
+        assert!(
+            text.contains("ando_ssl_certificate_expiry_timestamp_seconds{ssl=\"b\"} 1800000000")
+        );
+        assert!(text.contains("ando_ssl_certificates_expiring 0"));
+        MetricsCollector::new(false)
+            .unwrap()
+            .report_ssl([("a", 1)], 1);
+    }
+
+    #[test]
+    fn plugin_stats_replace_the_previous_report() {
+        let mc = MetricsCollector::new(true).unwrap();
+        mc.report_plugin_stats([
+            ("limit-conn", "r1", "in_flight", 3),
+            ("limit-conn", "r2", "rejected", 5),
+        ]);
+        mc.report_plugin_stats([("limit-conn", "r1", "in_flight", 1)]);
+        let text = mc.render();
+        assert!(
+            text.contains(
+                r#"ando_plugin_stats{plugin="limit-conn",route="r1",stat="in_flight"} 1"#
+            )
+        );
+        assert!(!text.contains(r#"route="r2""#), "{text}");
+    }
+
+    // ── Per-thread buffer ────────────────────────────────────────
+
+    #[test]
+    fn local_metrics_reach_shared_series_on_flush() {
+        let mc = MetricsCollector::new(true).unwrap();
+        let mut local = mc.local().unwrap();
+        local.record_request("r1", "GET", 200, 0.01);
+        local.record_request("r1", "GET", 200, 0.02);
+        local.record_plugin_response("key-auth", 401);
+        local.record_cache_status("r1", "HIT");
+        local.record_mirror("r1", "dropped");
+        local.record_fault("r1", "abort");
+        local.record_upstream_error("r1", "connect");
+
+        let counter = mc.http_requests_total.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["r1", "GET", "200"]).get(), 0);
+        local.flush();
+        assert_eq!(counter.with_label_values(&["r1", "GET", "200"]).get(), 2);
+        let hist = mc.http_request_duration.as_ref().unwrap();
+        assert_eq!(hist.with_label_values(&["r1"]).get_sample_count(), 2);
+        let plugins = mc.plugin_responses.as_ref().unwrap();
+        assert_eq!(plugins.with_label_values(&["key-auth", "401"]).get(), 1);
+        let cache = mc.cache_requests.as_ref().unwrap();
+        assert_eq!(cache.with_label_values(&["r1", "HIT"]).get(), 1);
+        let mirror = mc.mirror_requests.as_ref().unwrap();
+        assert_eq!(mirror.with_label_values(&["r1", "dropped"]).get(), 1);
+        let faults = mc.fault_injections.as_ref().unwrap();
+        assert_eq!(faults.with_label_values(&["r1", "abort"]).get(), 1);
+        let errors = mc.upstream_errors.as_ref().unwrap();
+        assert_eq!(errors.with_label_values(&["r1", "connect"]).get(), 1);
+    }
+
+    #[derive(Default)]
+    struct RecordingSink(std::sync::Mutex<Vec<MetricsBatch>>);
+    impl MetricsSink for RecordingSink {
+        fn submit(&self, batch: MetricsBatch) {
+            self.0.lock().unwrap().push(batch);
+        }
+    }
+
+    #[test]
+    fn sinks_get_the_requests_of_each_flush_without_prometheus() {
+        let sink = Arc::new(RecordingSink::default());
+        let mc = MetricsCollector::new(false)
+            .unwrap()
+            .with_sink(sink.clone());
+        assert!(mc.has_sinks());
+        let mut local = mc.local().unwrap();
+        local.record_request("r1", "GET", 200, 0.01);
+        local.record_request("r1", "GET", 200, 0.02);
+        local.record_request("r1", "POST", 201, 0.03);
+        local.record_plugin_response("key-auth", 401);
+        local.flush();
+        // Nothing recorded since: no empty batch.
+        local.flush();
+
+        let batches = sink.0.lock().unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(
+            batches[0].routes["r1"],
+            vec![
+                RequestSeries {
+                    method: "GET".into(),
+                    status: 200,
+                    count: 2,
+                    durations: vec![0.01, 0.02],
+                },
+                RequestSeries {
+                    method: "POST".into(),
+                    status: 201,
+                    count: 1,
+                    durations: vec![0.03],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn batch_keeps_counting_past_its_samples() {
+        let mut batch = MetricsBatch::default();
+        for _ in 0..MAX_BATCH_SAMPLES + 5 {
+            batch.record("r1", "GET", 200, 0.001);
+        }
+        let series = &batch.routes["r1"][0];
+        assert_eq!(series.count, MAX_BATCH_SAMPLES as u64 + 5);
+        assert_eq!(series.durations.len(), MAX_BATCH_SAMPLES);
+    }
+
+    #[test]
+    fn pool_connections_sum_over_threads() {
+        let mc = MetricsCollector::new(true).unwrap();
+        let (mut a, mut b) = (mc.local().unwrap(), mc.local().unwrap());
+        a.report_pool_connections([("10.0.0.1:80", 3)].into_iter());
+        b.report_pool_connections([("10.0.0.1:80", 2), ("10.0.0.2:80", 1)].into_iter());
+        let gauge = mc.upstream_pool_connections.as_ref().unwrap();
+        assert_eq!(gauge.with_label_values(&["10.0.0.1:80"]).get(), 5);
+
+        a.report_pool_connections([("10.0.0.1:80", 1)].into_iter());
+        b.report_pool_connections(std::iter::empty());
+        assert_eq!(gauge.with_label_values(&["10.0.0.1:80"]).get(), 1);
+        assert_eq!(gauge.with_label_values(&["10.0.0.2:80"]).get(), 0);
+    }
+
+    #[test]
+    fn open_connections_reported_per_worker_and_in_total() {
+        let mc = MetricsCollector::new(true).unwrap();
+        let (mut a, mut b) = (mc.local().unwrap(), mc.local().unwrap());
+        a.report_open_connections("0", 4);
+        b.report_open_connections("1", 2);
+        let total = mc.active_connections.as_ref().unwrap();
+        let per_worker = mc.worker_connections.as_ref().unwrap();
+        assert_eq!(total.get(), 6);
+        assert_eq!(per_worker.with_label_values(&["0"]).get(), 4);
+
+        a.report_open_connections("0", 1);
+        assert_eq!(total.get(), 3);
+        assert_eq!(per_worker.with_label_values(&["0"]).get(), 1);
+        assert_eq!(per_worker.with_label_values(&["1"]).get(), 2);
+    }
 }
@@ -0,0 +1,469 @@
+//! OpenTelemetry request spans with W3C trace context propagation.
+//!
+//! v2 design: Workers never block on tracing. Per request a worker parses
+//! `traceparent`, draws a span id from a thread-local generator and makes
+//! the sampling decision; the SDK span is only built — with explicit start
+//! and end times — once the response status is known and the span is to
+//! be exported. It is then queued on the SDK batch processor, whose own
+//! thread does the OTLP/HTTP I/O and drops spans when its queue is full.
+//!
+//! Without the `otel` feature the trace context handling still builds,
+//! but `RequestTracer::from_config` refuses an enabled config.
+
+use ando_core::config::OpenTelemetryConfig;
+use std::cell::Cell;
+use std::time::{Duration, SystemTime};
+
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::trace::{SdkTracer, SdkTracerProvider};
+
+/// A W3C `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: u128,
+    pub parent_id: u64,
+    pub sampled: bool,
+}
+
+impl TraceParent {
+    /// `None` for malformed or all-zero ids. Versions above `00` may carry
+    /// extra fields, which are ignored.
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.trim().split('-');
+        let version = fields.next()?;
+        let trace_id = fields.next()?;
+        let parent_id = fields.next()?;
+        let flags = fields.next()?;
+        if version.len() != 2
+            || !is_lower_hex(version)
+            || version == "ff"
+            || (version == "00" && fields.next().is_some())
+            || trace_id.len() != 32
+            || parent_id.len() != 16
+            || flags.len() != 2
+            || !is_lower_hex(trace_id)
+            || !is_lower_hex(parent_id)
+            || !is_lower_hex(flags)
+        {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let parent_id = u64::from_str_radix(parent_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        if trace_id == 0 || parent_id == 0 {
+            return None;
+        }
+        Some(Self {
+            trace_id,
+            parent_id,
+            sampled: flags & 1 == 1,
+        })
+    }
+}
+
+fn is_lower_hex(s: &str) -> bool {
+    s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+thread_local! {
+    static ID_STATE: Cell<u64> = Cell::new(id_seed());
+}
+
+fn id_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(std::thread::current().id()) | 1
+}
+
+/// Next non-zero id from this thread's xorshift generator. Ids only need
+/// to be unique, not unpredictable.
+fn next_id() -> u64 {
+    ID_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    })
+}
+
+/// The span of one proxied request, from route match to response.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub struct RequestSpan {
+    trace_id: u128,
+    span_id: u64,
+    parent: Option<TraceParent>,
+    tracestate: Option<String>,
+    sampled: bool,
+    start: SystemTime,
+    method: String,
+    path: String,
+    route_id: String,
+    route_uri: String,
+    upstream: Option<String>,
+    consumer: Option<String>,
+}
+
+impl RequestSpan {
+    /// `traceparent` for the upstream request: this span as the parent.
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+
+    pub fn set_upstream(&mut self, addr: &str) {
+        self.upstream = Some(addr.to_string());
+    }
+
+    pub fn set_consumer(&mut self, consumer: Option<&str>) {
+        self.consumer = consumer.map(str::to_string);
+    }
+}
+
+/// Starts request spans and hands finished ones to the exporter.
+pub struct RequestTracer {
+    /// New traces are sampled when the low 64 bits of the trace id are
+    /// below this.
+    threshold: u64,
+    sample_errors: bool,
+    #[cfg(feature = "otel")]
+    provider: SdkTracerProvider,
+    #[cfg(feature = "otel")]
+    tracer: SdkTracer,
+}
+
+impl RequestTracer {
+    /// `None` when `observability.opentelemetry` is disabled.
+    pub fn from_config(config: &OpenTelemetryConfig) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        #[cfg(feature = "otel")]
+        {
+            use opentelemetry_otlp::WithExportConfig;
+            let exporter = opentelemetry_otlp::SpanExporter::builder()
+                .with_http()
+                .with_endpoint(config.endpoint.as_str())
+                .with_timeout(Duration::from_millis(config.export_timeout_ms))
+                .build()?;
+            let provider = SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(
+                    opentelemetry_sdk::Resource::builder()
+                        .with_service_name(config.service_name.clone())
+                        .build(),
+                )
+                .build();
+            Ok(Some(Self::with_provider(provider, config)))
+        }
+        #[cfg(not(feature = "otel"))]
+        anyhow::bail!("opentelemetry is enabled but ando was built without the `otel` feature")
+    }
+
+    /// A tracer exporting through `provider`, e.g. one with an in-memory
+    /// exporter in tests.
+    #[cfg(feature = "otel")]
+    pub fn with_provider(provider: SdkTracerProvider, config: &OpenTelemetryConfig) -> Self {
+        use opentelemetry::trace::TracerProvider;
+        let ratio = config.sample_ratio.clamp(0.0, 1.0);
+        Self {
+            threshold: if ratio >= 1.0 {
+                u64::MAX
+            } else {
+                (ratio * u64::MAX as f64) as u64
+            },
+            sample_errors: config.sample_errors,
+            tracer: provider.tracer("ando"),
+            provider,
+        }
+    }
+
+    /// Begin the span of a request matched to a route. A valid
+    /// `traceparent` makes it a child of the caller's span and decides
+    /// sampling. `None` when the request will not be exported whatever
+    /// its outcome.
+    pub fn start(
+        &self,
+        method: &str,
+        path: &str,
+        route_id: &str,
+        route_uri: &str,
+        headers: &[(&str, &str)],
+    ) -> Option<RequestSpan> {
+        let mut parent = None;
+        let mut tracestate = None;
+        for (name, value) in headers {
+            if name.eq_ignore_ascii_case("traceparent") {
+                parent = TraceParent::parse(value);
+            } else if name.eq_ignore_ascii_case("tracestate") {
+                tracestate = Some(*value);
+            }
+        }
+        let (trace_id, sampled) = match parent {
+            Some(p) => (p.trace_id, p.sampled),
+            None => {
+                let trace_id = (u128::from(next_id()) << 64) | u128::from(next_id());
+                let sampled = self.threshold == u64::MAX || (trace_id as u64) < self.threshold;
+                (trace_id, sampled)
+            }
+        };
+        if !sampled && !self.sample_errors {
+            return None;
+        }
+        Some(RequestSpan {
+            trace_id,
+            span_id: next_id(),
+            tracestate: parent.and(tracestate).map(str::to_string),
+            parent,
+            sampled,
+            start: SystemTime::now(),
+            method: method.to_string(),
+            path: path.split_once('?').map_or(path, |(p, _)| p).to_string(),
+            route_id: route_id.to_string(),
+            route_uri: route_uri.to_string(),
+            upstream: None,
+            consumer: None,
+        })
+    }
+
+    /// End `span` `elapsed` after it started with the response `status`,
+    /// and queue it for export if it was sampled or failed with a 5xx.
+    pub fn finish(&self, span: RequestSpan, status: u16, elapsed: Duration) {
+        let error = status >= 500;
+        if !(span.sampled || error && self.sample_errors) {
+            return;
+        }
+        #[cfg(feature = "otel")]
+        self.export(span, status, elapsed, error);
+        #[cfg(not(feature = "otel"))]
+        let _ = (span, elapsed);
+    }
+
+    #[cfg(feature = "otel")]
+    fn export(&self, span: RequestSpan, status: u16, elapsed: Duration, error: bool) {
+        use opentelemetry::trace::{
+            SamplingDecision, SamplingResult, Span, SpanBuilder, SpanContext, SpanId, SpanKind,
+            Status, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer,
+        };
+        use opentelemetry::{Context, KeyValue};
+
+        let trace_state = span
+            .tracestate
+            .as_deref()
+            .and_then(|s| s.parse::<TraceState>().ok())
+            .unwrap_or_default();
+        let parent_cx = match span.parent {
+            Some(p) => Context::new().with_remote_span_context(SpanContext::new(
+                TraceId::from(p.trace_id),
+                SpanId::from(p.parent_id),
+                if p.sampled {
+                    TraceFlags::SAMPLED
+                } else {
+                    TraceFlags::default()
+                },
+                true,
+                trace_state.clone(),
+            )),
+            None => Context::new(),
+        };
+
+        let mut attributes = vec![
+            KeyValue::new("http.request.method", span.method.clone()),
+            KeyValue::new("url.path", span.path),
+            KeyValue::new("http.route", span.route_uri.clone()),
+            KeyValue::new("http.response.status_code", i64::from(status)),
+            KeyValue::new("ando.route.id", span.route_id),
+        ];
+        if let Some(addr) = span.upstream {
+            attributes.push(KeyValue::new("ando.upstream.address", addr));
+        }
+        if let Some(consumer) = span.consumer {
+            attributes.push(KeyValue::new("ando.consumer", consumer));
+        }
+
+        let builder = SpanBuilder::from_name(format!("{} {}", span.method, span.route_uri))
+            .with_kind(SpanKind::Server)
+            .with_trace_id(TraceId::from(span.trace_id))
+            .with_span_id(SpanId::from(span.span_id))
+            .with_start_time(span.start)
+            .with_attributes(attributes)
+            .with_status(if error {
+                Status::error(format!("HTTP {status}"))
+            } else {
+                Status::Unset
+            })
+            .with_sampling_result(SamplingResult {
+                decision: SamplingDecision::RecordAndSample,
+                attributes: Vec::new(),
+                trace_state,
+            });
+        let mut otel_span = self.tracer.build_with_context(builder, &parent_cx);
+        otel_span.end_with_timestamp(span.start + elapsed);
+    }
+
+    /// Export the spans still queued. Called on shutdown.
+    pub fn shutdown(&self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            tracing::warn!(error = %e, "OpenTelemetry span export did not finish");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn traceparent_parses_valid_header() {
+        let p = TraceParent::parse(PARENT).unwrap();
+        assert_eq!(p.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(p.parent_id, 0x00f067aa0ba902b7);
+        assert!(p.sampled);
+        let p = TraceParent::parse(&PARENT.replace("-01", "-00")).unwrap();
+        assert!(!p.sampled);
+        // A future version may append fields.
+        assert!(TraceParent::parse(&format!("01{}-extra", &PARENT[2..])).is_some());
+    }
+
+    #[test]
+    fn traceparent_rejects_malformed_headers() {
+        for bad in [
+            "",
+            "garbage",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert!(TraceParent::parse(bad).is_none(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn disabled_config_builds_no_tracer() {
+        let tracer = RequestTracer::from_config(&OpenTelemetryConfig::default()).unwrap();
+        assert!(tracer.is_none());
+    }
+
+    #[test]
+    fn ids_are_nonzero_and_distinct() {
+        let (a, b) = (next_id(), next_id());
+        assert!(a != 0 && b != 0 && a != b);
+    }
+
+    #[cfg(feature = "otel")]
+    mod export {
+        use super::*;
+        use opentelemetry::Value;
+        use opentelemetry::trace::{SpanKind, Status};
+        use opentelemetry_sdk::trace::{InMemorySpanExporter, SpanData};
+
+        fn tracer(config: OpenTelemetryConfig) -> (RequestTracer, InMemorySpanExporter) {
+            let exporter = InMemorySpanExporter::default();
+            let provider = SdkTracerProvider::builder()
+                .with_simple_exporter(exporter.clone())
+                .build();
+            (RequestTracer::with_provider(provider, &config), exporter)
+        }
+
+        fn attr(span: &SpanData, key: &str) -> Option<Value> {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        }
+
+        #[test]
+        fn child_span_keeps_trace_and_records_attributes() {
+            let (tracer, exporter) = tracer(OpenTelemetryConfig::default());
+            let headers = [("traceparent", PARENT), ("tracestate", "vendor=abc")];
+            let mut span = tracer
+                .start("GET", "/api/users?page=2", "r1", "/api/*", &headers)
+                .unwrap();
+            span.set_upstream("10.0.0.1:8080");
+            span.set_consumer(Some("alice"));
+            let traceparent = span.traceparent();
+            assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+            assert!(traceparent.ends_with("-01"));
+            assert!(!traceparent.contains("00f067aa0ba902b7"));
+            tracer.finish(span, 200, Duration::from_millis(5));
+
+            let spans = exporter.get_finished_spans().unwrap();
+            assert_eq!(spans.len(), 1);
+            let s = &spans[0];
+            assert_eq!(s.name, "GET /api/*");
+            assert_eq!(s.span_kind, SpanKind::Server);
+            assert_eq!(
+                s.span_context.trace_id().to_string(),
+                "4bf92f3577b34da6a3ce929d0e0e4736"
+            );
+            assert_eq!(s.parent_span_id.to_string(), "00f067aa0ba902b7");
+            assert_eq!(
+                traceparent,
+                format!(
+                    "00-{}-{}-01",
+                    s.span_context.trace_id(),
+                    s.span_context.span_id()
+                )
+            );
+            assert_eq!(s.span_context.trace_state().header(), "vendor=abc");
+            assert_eq!(attr(s, "ando.route.id"), Some("r1".into()));
+            assert_eq!(attr(s, "url.path"), Some("/api/users".into()));
+            assert_eq!(attr(s, "http.response.status_code"), Some(200i64.into()));
+            assert_eq!(
+                attr(s, "ando.upstream.address"),
+                Some("10.0.0.1:8080".into())
+            );
+            assert_eq!(attr(s, "ando.consumer"), Some("alice".into()));
+            assert_eq!(s.status, Status::Unset);
+            assert_eq!(
+                s.end_time.duration_since(s.start_time).unwrap(),
+                Duration::from_millis(5)
+            );
+        }
+
+        #[test]
+        fn unsampled_requests_export_only_errors() {
+            let (tracer, exporter) = tracer(OpenTelemetryConfig {
+                sample_ratio: 0.0,
+                ..OpenTelemetryConfig::default()
+            });
+            let span = tracer.start("GET", "/", "r1", "/", &[]).unwrap();
+            assert!(span.traceparent().ends_with("-00"));
+            tracer.finish(span, 200, Duration::ZERO);
+            assert!(exporter.get_finished_spans().unwrap().is_empty());
+
+            let span = tracer.start("GET", "/", "r1", "/", &[]).unwrap();
+            tracer.finish(span, 502, Duration::ZERO);
+            let spans = exporter.get_finished_spans().unwrap();
+            assert_eq!(spans.len(), 1);
+            assert!(matches!(spans[0].status, Status::Error { .. }));
+        }
+
+        #[test]
+        fn unsampled_parent_is_followed_without_error_sampling() {
+            let (tracer, _) = tracer(OpenTelemetryConfig {
+                sample_errors: false,
+                ..OpenTelemetryConfig::default()
+            });
+            let unsampled = PARENT.replace("-01", "-00");
+            assert!(
+                tracer
+                    .start("GET", "/", "r1", "/", &[("traceparent", &unsampled)])
+                    .is_none()
+            );
+            assert!(tracer.start("GET", "/", "r1", "/", &[]).is_some());
+        }
+    }
+}
@@ -0,0 +1,600 @@
+//! StatsD / DogStatsD export of the request series.
+//!
+//! v2 design: Workers never touch the socket. Each worker's metrics flush
+//! hands its [`MetricsBatch`] to a bounded queue — a full queue drops the
+//! batch and counts it in `ando_statsd_dropped_total` — and the exporter
+//! thread merges the batches per route, method and status. Every
+//! `flush_interval_ms` it sends one counter and one timing line per series:
+//!
+//! ```text
+//! ando.http.requests:3|c|#route:orders,method:GET,status:200,node_id:gw-1,env:prod
+//! ando.http.request.duration:12.5:3.25:40|ms|#route:orders,method:GET,status:200,node_id:gw-1,env:prod
+//! ```
+//!
+//! A timing line carries at most `max_samples` durations per series and
+//! interval, a uniform sample of them, with the sample rate (`|@0.25`) for
+//! the agent to scale by. Lines are packed into datagrams of at most 1432
+//! bytes over UDP and 8192 over a Unix socket.
+//!
+//! Without the `statsd` feature, `StatsdExporter::from_config` refuses an
+//! enabled config.
+#![cfg_attr(not(feature = "statsd"), allow(dead_code))]
+
+use crate::metrics::{MetricsBatch, MetricsCollector, MetricsSink};
+use ando_core::config::StatsdConfig;
+use prometheus::IntCounterVec;
+use std::collections::HashMap;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Largest datagram sent over UDP, to stay under a common path MTU.
+const UDP_PACKET_SIZE: usize = 1432;
+/// Largest datagram sent over a Unix socket.
+const UNIX_PACKET_SIZE: usize = 8192;
+
+/// Pushes the request series to a StatsD / DogStatsD agent; attach it to
+/// the [`MetricsCollector`] with `with_sink`.
+pub struct StatsdExporter {
+    queue: Option<SyncSender<MetricsBatch>>,
+    thread: Option<JoinHandle<()>>,
+    dropped: Option<IntCounterVec>,
+}
+
+impl StatsdExporter {
+    /// Open the agent's socket and start the exporter thread. `None` when
+    /// `observability.statsd` is disabled. Lost batches and packets are
+    /// counted in `metrics` when it is enabled.
+    pub fn from_config(
+        config: &StatsdConfig,
+        metrics: &MetricsCollector,
+    ) -> anyhow::Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+        #[cfg(feature = "statsd")]
+        {
+            let transport = Transport::open(config)?;
+            let node_id = match config.node_id.as_str() {
+                "" => host_name(),
+                id => Some(id.to_string()),
+            };
+            let aggregator = Aggregator::new(config, node_id.as_deref());
+            let interval = Duration::from_millis(config.flush_interval_ms.max(1));
+            let (queue, batches) = std::sync::mpsc::sync_channel(config.queue_capacity.max(1));
+            let dropped = metrics.statsd_dropped.clone();
+            let thread = std::thread::Builder::new()
+                .name("ando-statsd".into())
+                .spawn({
+                    let dropped = dropped.clone();
+                    move || run(batches, aggregator, transport, interval, dropped)
+                })?;
+            Ok(Some(Self {
+                queue: Some(queue),
+                thread: Some(thread),
+                dropped,
+            }))
+        }
+        #[cfg(not(feature = "statsd"))]
+        {
+            let _ = metrics;
+            anyhow::bail!("statsd is enabled but ando was built without the `statsd` feature")
+        }
+    }
+}
+
+impl MetricsSink for StatsdExporter {
+    fn submit(&self, batch: MetricsBatch) {
+        let Some(ref queue) = self.queue else {
+            return;
+        };
+        if queue.try_send(batch).is_err() {
+            count_dropped(&self.dropped, "queue_full", 1);
+        }
+    }
+}
+
+impl Drop for StatsdExporter {
+    /// Send what is merged so far and stop the exporter thread.
+    fn drop(&mut self) {
+        self.queue.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Merge batches as they come and send the series every `interval`, and
+/// once more when the queue closes.
+fn run(
+    batches: Receiver<MetricsBatch>,
+    mut aggregator: Aggregator,
+    mut transport: Transport,
+    interval: Duration,
+    dropped: Option<IntCounterVec>,
+) {
+    let mut next = Instant::now() + interval;
+    loop {
+        let closed = match batches.recv_timeout(next.saturating_duration_since(Instant::now())) {
+            Ok(batch) => {
+                aggregator.merge(batch);
+                if Instant::now() < next {
+                    continue;
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        let size = transport.packet_size();
+        let mut failed = 0;
+        for packet in packets(&aggregator.lines(size), size) {
+            if let Err(e) = transport.send(&packet) {
+                tracing::debug!(error = %e, "StatsD packet not sent");
+                failed += 1;
+            }
+        }
+        if failed > 0 {
+            tracing::warn!(
+                packets = failed,
+                "StatsD agent unreachable, metrics dropped"
+            );
+            count_dropped(&dropped, "send_failed", failed);
+        }
+        if closed {
+            return;
+        }
+        next = (next + interval).max(Instant::now());
+    }
+}
+
+fn count_dropped(dropped: &Option<IntCounterVec>, reason: &str, count: u64) {
+    if let Some(counter) = dropped {
+        counter.with_label_values(&[reason]).inc_by(count);
+    }
+}
+
+/// Series merged from the workers' batches since the last send.
+struct Aggregator {
+    /// `prefix.`, or empty.
+    prefix: String,
+    /// `,node_id:…` and the configured tags, appended to every line.
+    tags: String,
+    max_samples: usize,
+    series: HashMap<(String, String, u16), Timings>,
+    rng: u64,
+}
+
+#[derive(Default)]
+struct Timings {
+    count: u64,
+    /// Durations offered to `samples`.
+    seen: u64,
+    /// In seconds.
+    samples: Vec<f64>,
+}
+
+impl Aggregator {
+    fn new(config: &StatsdConfig, node_id: Option<&str>) -> Self {
+        let mut tags = String::new();
+        if let Some(node_id) = node_id.filter(|id| !id.is_empty()) {
+            tags.push_str(",node_id:");
+            tags.push_str(&tag_value(node_id));
+        }
+        for tag in config.tags.iter().filter(|t| !t.is_empty()) {
+            tags.push(',');
+            tags.push_str(&tag_value(tag));
+        }
+        Self {
+            prefix: match config.prefix.trim_end_matches('.') {
+                "" => String::new(),
+                prefix => format!("{prefix}."),
+            },
+            tags,
+            max_samples: config.max_samples,
+            series: HashMap::new(),
+            rng: rng_seed(),
+        }
+    }
+
+    fn merge(&mut self, batch: MetricsBatch) {
+        for (route, series) in batch.routes {
+            for s in series {
+                let timings = self
+                    .series
+                    .entry((route.clone(), s.method, s.status))
+                    .or_default();
+                timings.count += s.count;
+                // Reservoir sampling over every duration of the interval.
+                for duration in s.durations {
+                    timings.seen += 1;
+                    if timings.samples.len() < self.max_samples {
+                        timings.samples.push(duration);
+                    } else {
+                        let slot = next_random(&mut self.rng) % timings.seen;
+                        if let Some(kept) = timings.samples.get_mut(slot as usize) {
+                            *kept = duration;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Lines for the series merged since the last call, which starts the
+    /// next interval. Timing lines are split to fit `max_line` bytes.
+    fn lines(&mut self, max_line: usize) -> Vec<String> {
+        let mut series: Vec<_> = self.series.drain().collect();
+        series.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        let mut lines = Vec::with_capacity(series.len() * 2);
+        for ((route, method, status), timings) in series {
+            let tags = format!(
+                "|#route:{},method:{},status:{status}{}",
+                tag_value(&route),
+                tag_value(&method),
+                self.tags
+            );
+            lines.push(format!(
+                "{}http.requests:{}|c{tags}",
+                self.prefix, timings.count
+            ));
+            if timings.samples.is_empty() {
+                continue;
+            }
+            let rate = timings.samples.len() as f64 / timings.count as f64;
+            let tail = if rate < 1.0 {
+                format!("|ms|@{rate}{tags}")
+            } else {
+                format!("|ms{tags}")
+            };
+            let head = format!("{}http.request.duration", self.prefix);
+            let mut line = head.clone();
+            for duration in timings.samples {
+                let value = millis(duration);
+                if line.len() > head.len() && line.len() + 1 + value.len() + tail.len() > max_line {
+                    line.push_str(&tail);
+                    lines.push(std::mem::replace(&mut line, head.clone()));
+                }
+                line.push(':');
+                line.push_str(&value);
+            }
+            line.push_str(&tail);
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+/// Pack lines into newline-separated datagrams of at most `max` bytes. A
+/// longer line goes alone.
+fn packets(lines: &[String], max: usize) -> Vec<Vec<u8>> {
+    let mut packets = Vec::new();
+    let mut packet: Vec<u8> = Vec::with_capacity(max);
+    for line in lines {
+        if !packet.is_empty() && packet.len() + 1 + line.len() > max {
+            packets.push(std::mem::replace(&mut packet, Vec::with_capacity(max)));
+        }
+        if !packet.is_empty() {
+            packet.push(b'\n');
+        }
+        packet.extend_from_slice(line.as_bytes());
+    }
+    if !packet.is_empty() {
+        packets.push(packet);
+    }
+    packets
+}
+
+/// Seconds as milliseconds with at most three decimals.
+fn millis(seconds: f64) -> String {
+    let value = format!("{:.3}", seconds * 1000.0);
+    value
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// A tag with the characters of the DogStatsD syntax replaced by `_`.
+fn tag_value(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            ',' | '|' | '#' | '\n' | '\r' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+fn rng_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(Instant::now()) | 1
+}
+
+/// Xorshift: the sample only needs to be uniform, not unpredictable.
+fn next_random(state: &mut u64) -> u64 {
+    let mut x = *state;
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    *state = x;
+    x
+}
+
+/// The machine's host name, for the `node_id` tag.
+fn host_name() -> Option<String> {
+    std::env::var("HOSTNAME")
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// The agent's socket.
+enum Transport {
+    Udp(std::net::UdpSocket),
+    /// Connected on first use, and again after a failed send, so the
+    /// agent may start after the gateway.
+    Unix {
+        path: String,
+        socket: Option<std::os::unix::net::UnixDatagram>,
+    },
+}
+
+impl Transport {
+    fn open(config: &StatsdConfig) -> std::io::Result<Self> {
+        if let Some(ref path) = config.socket_path {
+            return Ok(Self::Unix {
+                path: path.clone(),
+                socket: None,
+            });
+        }
+        use std::net::ToSocketAddrs;
+        let addr = (config.host.as_str(), config.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("no address for StatsD host {}", config.host),
+                )
+            })?;
+        let local = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = std::net::UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        Ok(Self::Udp(socket))
+    }
+
+    fn packet_size(&self) -> usize {
+        match self {
+            Self::Udp(_) => UDP_PACKET_SIZE,
+            Self::Unix { .. } => UNIX_PACKET_SIZE,
+        }
+    }
+
+    fn send(&mut self, packet: &[u8]) -> std::io::Result<()> {
+        match self {
+            Self::Udp(socket) => socket.send(packet).map(drop),
+            Self::Unix { path, socket } => {
+                let connected = match socket.take() {
+                    Some(connected) => connected,
+                    None => {
+                        let fresh = std::os::unix::net::UnixDatagram::unbound()?;
+                        fresh.connect(path.as_str())?;
+                        fresh.set_nonblocking(true)?;
+                        fresh
+                    }
+                };
+                connected.send(packet)?;
+                *socket = Some(connected);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> StatsdConfig {
+        StatsdConfig {
+            enabled: true,
+            prefix: "gw".into(),
+            tags: vec!["env:test".into()],
+            node_id: "n1".into(),
+            flush_interval_ms: 60_000,
+            ..StatsdConfig::default()
+        }
+    }
+
+    fn batch(requests: &[(&str, &str, u16, f64)]) -> MetricsBatch {
+        let mut batch = MetricsBatch::default();
+        for &(route, method, status, secs) in requests {
+            batch.record(route, method, status, secs);
+        }
+        batch
+    }
+
+    #[test]
+    fn merges_batches_into_one_counter_and_timing_per_series() {
+        let mut aggregator = Aggregator::new(&config(), Some("n1"));
+        aggregator.merge(batch(&[
+            ("r1", "GET", 200, 0.01),
+            ("r1", "GET", 200, 0.0205),
+        ]));
+        aggregator.merge(batch(&[("r1", "GET", 200, 0.03), ("r2", "POST", 500, 1.5)]));
+        assert_eq!(
+            aggregator.lines(UDP_PACKET_SIZE),
+            [
+                "gw.http.requests:3|c|#route:r1,method:GET,status:200,node_id:n1,env:test",
+                "gw.http.request.duration:10:20.5:30|ms|#route:r1,method:GET,status:200,node_id:n1,env:test",
+                "gw.http.requests:1|c|#route:r2,method:POST,status:500,node_id:n1,env:test",
+                "gw.http.request.duration:1500|ms|#route:r2,method:POST,status:500,node_id:n1,env:test",
+            ]
+        );
+        // The next interval starts empty.
+        assert!(aggregator.lines(UDP_PACKET_SIZE).is_empty());
+    }
+
+    #[test]
+    fn samples_timings_beyond_max_samples_with_their_rate() {
+        let mut aggregator = Aggregator::new(
+            &StatsdConfig {
+                max_samples: 2,
+                ..config()
+            },
+            None,
+        );
+        aggregator.merge(batch(&[("r1", "GET", 200, 0.001); 8]));
+        let lines = aggregator.lines(UDP_PACKET_SIZE);
+        assert_eq!(
+            lines[0],
+            "gw.http.requests:8|c|#route:r1,method:GET,status:200,env:test"
+        );
+        assert_eq!(
+            lines[1],
+            "gw.http.request.duration:1:1|ms|@0.25|#route:r1,method:GET,status:200,env:test"
+        );
+    }
+
+    #[test]
+    fn long_timing_lines_are_split_and_packed_into_datagrams() {
+        let mut aggregator = Aggregator::new(&config(), None);
+        aggregator.merge(batch(&[("r1", "GET", 200, 0.1234); 50]));
+        let lines = aggregator.lines(200);
+        assert!(lines.len() > 2);
+        for line in &lines[1..] {
+            assert!(line.len() <= 200, "{line}");
+            assert!(line.ends_with("|ms|#route:r1,method:GET,status:200,env:test"));
+        }
+        let values: usize = lines[1..].iter().map(|l| l.matches(":123.4").count()).sum();
+        assert_eq!(values, 50);
+
+        let packets = packets(&lines, 450);
+        assert!(packets.iter().all(|p| p.len() <= 450));
+        let joined: Vec<String> = packets
+            .iter()
+            .flat_map(|p| {
+                String::from_utf8_lossy(p)
+                    .lines()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(joined, lines);
+    }
+
+    #[test]
+    fn tags_cannot_break_the_line_format() {
+        let aggregator = Aggregator::new(
+            &StatsdConfig {
+                prefix: String::new(),
+                tags: vec!["team:a,b".into(), "".into()],
+                ..config()
+            },
+            Some("gw 1"),
+        );
+        assert_eq!(aggregator.prefix, "");
+        assert_eq!(aggregator.tags, ",node_id:gw_1,team:a_b");
+        assert_eq!(tag_value("/api|v1#x"), "/api_v1_x");
+        assert_eq!(millis(0.0123456), "12.346");
+        assert_eq!(millis(2.0), "2000");
+    }
+
+    #[cfg(feature = "statsd")]
+    mod export {
+        use super::*;
+        use std::net::UdpSocket;
+
+        fn agent() -> (UdpSocket, StatsdConfig) {
+            let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+            agent
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let config = StatsdConfig {
+                host: "127.0.0.1".into(),
+                port: agent.local_addr().unwrap().port(),
+                ..config()
+            };
+            (agent, config)
+        }
+
+        fn receive(agent: &UdpSocket) -> Vec<String> {
+            let mut buf = [0u8; UDP_PACKET_SIZE];
+            let n = agent.recv(&mut buf).unwrap();
+            String::from_utf8_lossy(&buf[..n])
+                .lines()
+                .map(str::to_string)
+                .collect()
+        }
+
+        #[test]
+        fn sends_the_series_of_a_flush_window_to_the_agent() {
+            let (agent, config) = agent();
+            let metrics = MetricsCollector::new(false).unwrap();
+            let exporter = StatsdExporter::from_config(&config, &metrics)
+                .unwrap()
+                .unwrap();
+            // Two workers' flushes in the same window.
+            exporter.submit(batch(&[("r1", "GET", 200, 0.004)]));
+            exporter.submit(batch(&[("r1", "GET", 200, 0.006)]));
+            // Closing sends what the window holds.
+            drop(exporter);
+            assert_eq!(
+                receive(&agent),
+                [
+                    "gw.http.requests:2|c|#route:r1,method:GET,status:200,node_id:n1,env:test",
+                    "gw.http.request.duration:4:6|ms|#route:r1,method:GET,status:200,node_id:n1,env:test",
+                ]
+            );
+        }
+
+        #[test]
+        fn flushes_every_interval() {
+            let (agent, config) = agent();
+            let config = StatsdConfig {
+                flush_interval_ms: 50,
+                ..config
+            };
+            let metrics = MetricsCollector::new(false).unwrap();
+            let exporter = StatsdExporter::from_config(&config, &metrics)
+                .unwrap()
+                .unwrap();
+            exporter.submit(batch(&[("r1", "GET", 404, 0.001)]));
+            let lines = receive(&agent);
+            assert_eq!(
+                lines[0],
+                "gw.http.requests:1|c|#route:r1,method:GET,status:404,node_id:n1,env:test"
+            );
+        }
+
+        #[test]
+        fn full_queue_drops_batches_and_counts_them() {
+            let metrics = MetricsCollector::new(true).unwrap();
+            // A queue nobody drains.
+            let (queue, _batches) = std::sync::mpsc::sync_channel(1);
+            let exporter = StatsdExporter {
+                queue: Some(queue),
+                thread: None,
+                dropped: metrics.statsd_dropped.clone(),
+            };
+            for _ in 0..3 {
+                exporter.submit(batch(&[("r1", "GET", 200, 0.001)]));
+            }
+            let dropped = metrics.statsd_dropped.as_ref().unwrap();
+            assert_eq!(dropped.with_label_values(&["queue_full"]).get(), 2);
+        }
+    }
+
+    #[cfg(not(feature = "statsd"))]
+    #[test]
+    fn enabled_config_needs_the_feature() {
+        let metrics = MetricsCollector::new(false).unwrap();
+        assert!(StatsdExporter::from_config(&config(), &metrics).is_err());
+    }
+}
@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Accept, connection, request and response counters of every worker thread.
+///
+/// v2 design: Each worker owns one cache-line aligned set of atomics and is
+/// its only writer, so the hot path is an uncontended relaxed add that
+/// never bounces a line between cores. Readers (the Admin API) sum them on
+/// demand. Always on, independent of `observability.prometheus`.
+pub struct WorkerStats {
+    workers: Vec<Arc<WorkerCounters>>,
+}
+
+/// Counters of one worker.
+#[derive(Debug, Default)]
+#[repr(align(64))]
+pub struct WorkerCounters {
+    accepts: AtomicU64,
+    open_connections: AtomicU64,
+    requests: AtomicU64,
+    responses_4xx: AtomicU64,
+    responses_5xx: AtomicU64,
+    pool_connections: AtomicU64,
+}
+
+/// Point-in-time view of one worker's counters.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WorkerSnapshot {
+    /// Connections accepted since start.
+    pub accepts: u64,
+    /// Client connections open now.
+    pub connections: u64,
+    /// Requests handled since start.
+    pub requests: u64,
+    /// Responses with a 4xx status since start.
+    pub responses_4xx: u64,
+    /// Responses with a 5xx status since start.
+    pub responses_5xx: u64,
+    /// Idle upstream connections pooled, as of the last pool sweep.
+    pub pool_connections: u64,
+}
+
+/// Per-worker snapshots and their sum, as served by the Admin API.
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerStatsReport {
+    pub workers: Vec<WorkerSnapshot>,
+    pub total: WorkerSnapshot,
+}
+
+impl WorkerStats {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers: (0..workers).map(|_| Arc::default()).collect(),
+        }
+    }
+
+    /// The counters of worker `id`. Ids past the configured count get a
+    /// detached set that is not reported.
+    pub fn worker(&self, id: usize) -> Arc<WorkerCounters> {
+        self.workers.get(id).cloned().unwrap_or_default()
+    }
+
+    pub fn report(&self) -> WorkerStatsReport {
+        let workers: Vec<WorkerSnapshot> = self.workers.iter().map(|w| w.snapshot()).collect();
+        let total = workers
+            .iter()
+            .fold(WorkerSnapshot::default(), |sum, w| WorkerSnapshot {
+                accepts: sum.accepts + w.accepts,
+                connections: sum.connections + w.connections,
+                requests: sum.requests + w.requests,
+                responses_4xx: sum.responses_4xx + w.responses_4xx,
+                responses_5xx: sum.responses_5xx + w.responses_5xx,
+                pool_connections: sum.pool_connections + w.pool_connections,
+            });
+        WorkerStatsReport { workers, total }
+    }
+}
+
+impl WorkerCounters {
+    #[inline]
+    pub fn accepted(&self) {
+        self.accepts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn connection_opened(&self) {
+        self.open_connections.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn connection_closed(&self) {
+        // The worker is the only writer, so load-then-store cannot race.
+        let open = self.open_connections.load(Ordering::Relaxed);
+        self.open_connections
+            .store(open.saturating_sub(1), Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn request(&self) {
+        self.requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Count a response sent with `status`.
+    #[inline]
+    pub fn response(&self, status: u16) {
+        match status {
+            400..=499 => self.responses_4xx.fetch_add(1, Ordering::Relaxed),
+            500..=599 => self.responses_5xx.fetch_add(1, Ordering::Relaxed),
+            _ => return,
+        };
+    }
+
+    /// Publish the number of idle upstream connections in the worker's pool.
+    pub fn set_pool_connections(&self, idle: usize) {
+        self.pool_connections.store(idle as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> WorkerSnapshot {
+        WorkerSnapshot {
+            accepts: self.accepts.load(Ordering::Relaxed),
+            connections: self.open_connections.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            responses_4xx: self.responses_4xx.load(Ordering::Relaxed),
+            responses_5xx: self.responses_5xx.load(Ordering::Relaxed),
+            pool_connections: self.pool_connections.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_sums_every_worker() {
+        let stats = WorkerStats::new(2);
+        let (a, b) = (stats.worker(0), stats.worker(1));
+        a.accepted();
+        a.connection_opened();
+        a.request();
+        a.request();
+        a.response(200);
+        a.response(404);
+        a.set_pool_connections(3);
+        b.accepted();
+        b.connection_opened();
+        b.connection_closed();
+        b.request();
+        b.response(502);
+
+        let report = stats.report();
+        assert_eq!(
+            report.workers[0],
+            WorkerSnapshot {
+                accepts: 1,
+                connections: 1,
+                requests: 2,
+                responses_4xx: 1,
+                responses_5xx: 0,
+                pool_connections: 3,
+            }
+        );
+        assert_eq!(report.workers[1].connections, 0);
+        assert_eq!(
+            report.total,
+            WorkerSnapshot {
+                accepts: 2,
+                connections: 1,
+                requests: 3,
+                responses_4xx: 1,
+                responses_5xx: 1,
+                pool_connections: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn unknown_worker_is_not_reported() {
+        let stats = WorkerStats::new(1);
+        stats.worker(5).request();
+        stats.worker(0).connection_closed();
+        assert_eq!(stats.report().total, WorkerSnapshot::default());
+    }
+}
@@ -0,0 +1,52 @@
+//! `add-header`: sets one header on the upstream request.
+//!
+//! ```yaml
+//! plugins:
+//!   add-header: { name: x-team, value: payments }
+//! ```
+
+use ando_plugin_sdk::{Phase, Plugin, PluginContext, PluginInstance, PluginResult, anyhow};
+
+pub struct AddHeaderPlugin;
+
+impl Plugin for AddHeaderPlugin {
+    fn name(&self) -> &str {
+        "add-header"
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::BeforeProxy]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let field = |key: &str| {
+            config[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow::anyhow!("add-header config error: `{key}` is required"))
+        };
+        Ok(Box::new(AddHeader {
+            name: field("name")?,
+            value: field("value")?,
+        }))
+    }
+}
+
+struct AddHeader {
+    name: String,
+    value: String,
+}
+
+impl PluginInstance for AddHeader {
+    fn name(&self) -> &str {
+        "add-header"
+    }
+
+    fn before_proxy(&self, ctx: &mut PluginContext) -> PluginResult {
+        ctx.upstream_headers
+            .insert(self.name.clone(), self.value.clone());
+        PluginResult::Continue
+    }
+}
+
+ando_plugin_sdk::export_plugins!(AddHeaderPlugin);
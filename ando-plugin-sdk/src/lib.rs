@@ -0,0 +1,16 @@
+//! Build Ando plugins outside the gateway tree.
+//!
+//! A plugin library is a `cdylib` that implements [`Plugin`] and ends with
+//! [`export_plugins!`]. List its directory in `plugins.external_dirs` and
+//! the gateway registers its plugins at startup, next to the built-ins.
+//! The library must be built with the same compiler and the same Ando
+//! release as the gateway (same `Cargo.lock`); others are refused. See
+//! `examples/add_header.rs`.
+
+pub use ando_plugin::export_plugins;
+pub use ando_plugin::external::{ABI_TAG, ABI_VERSION};
+pub use ando_plugin::plugin::{
+    Phase, Plugin, PluginContext, PluginInstance, PluginResult, RequestHeaders,
+};
+pub use anyhow;
+pub use serde_json;
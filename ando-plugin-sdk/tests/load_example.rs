@@ -0,0 +1,79 @@
+//! Loads the `add_header` example library the way the gateway loads
+//! `plugins.external_dirs`.
+
+use ando_plugin::external::{load_dirs, load_library};
+use ando_plugin::pipeline::PluginPipeline;
+use ando_plugin::plugin::{Phase, PluginContext, PluginResult};
+use ando_plugin::registry::PluginRegistry;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// `cargo test` builds examples next to the test binaries' directory.
+fn example_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let dir = exe.parent().unwrap().parent().unwrap().join("examples");
+    assert!(
+        example_library(&dir).exists(),
+        "{} missing; run `cargo build -p ando-plugin-sdk --examples` first",
+        example_library(&dir).display()
+    );
+    dir
+}
+
+fn example_library(dir: &std::path::Path) -> PathBuf {
+    dir.join(format!(
+        "{}add_header{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    ))
+}
+
+#[test]
+fn example_plugin_registers_and_runs_like_a_builtin() {
+    let mut registry = PluginRegistry::new();
+    let names = load_library(&mut registry, &example_library(&example_dir())).unwrap();
+    assert_eq!(names, vec!["add-header".to_string()]);
+    assert_eq!(registry.external(), ["add-header".to_string()]);
+
+    let plugin = registry.get("add-header").unwrap();
+    assert_eq!(plugin.phases(), &[Phase::BeforeProxy]);
+    let err = plugin
+        .configure(&serde_json::json!({"name": "x-team"}))
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("`value` is required"), "{err}");
+
+    let instance = plugin
+        .configure(&serde_json::json!({"name": "x-team", "value": "payments"}))
+        .unwrap();
+    let pipeline = PluginPipeline::build(vec![Arc::from(instance)], false);
+    let mut ctx = PluginContext::new(
+        "r1".into(),
+        "127.0.0.1".into(),
+        "GET".into(),
+        "/".into(),
+        HashMap::new(),
+    );
+    assert!(matches!(
+        pipeline.execute_phase(Phase::BeforeProxy, &mut ctx),
+        PluginResult::Continue
+    ));
+    assert_eq!(ctx.upstream_headers["x-team"], "payments");
+    // Values the library allocated are freed by the host.
+    drop(ctx);
+    drop(pipeline);
+}
+
+#[test]
+fn directory_scan_skips_plugins_already_registered() {
+    let dir = example_dir().to_string_lossy().into_owned();
+    let mut registry = PluginRegistry::new();
+    assert_eq!(
+        load_dirs(&mut registry, std::slice::from_ref(&dir)),
+        vec!["add-header"]
+    );
+    // Loading it again keeps the first registration.
+    assert!(load_dirs(&mut registry, &[dir]).is_empty());
+    assert_eq!(registry.len(), 1);
+}
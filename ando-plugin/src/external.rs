@@ -0,0 +1,325 @@
+//! Plugins loaded from dynamic libraries.
+//!
+//! v2 design: A plugin library is a `cdylib` built with `export_plugins!`
+//! (re-exported by `ando-plugin-sdk`). It exports `ando_plugin_abi`, read
+//! before anything else, and `ando_plugin_register`, which hands its
+//! plugins to the host registry as ordinary `Arc<dyn Plugin>` — from there
+//! they are indistinguishable from built-ins.
+//!
+//! Rust trait objects have no stable layout, so a library must be built by
+//! the same compiler against the same `ando-plugin` as the gateway; the ABI
+//! tag encodes both and a mismatched library is refused before any of its
+//! code runs. The library's global allocator forwards to the host's, so
+//! values allocated on one side can be freed on the other. Libraries are
+//! never unloaded.
+
+use crate::plugin::Plugin;
+use crate::registry::PluginRegistry;
+use anyhow::{Context, bail};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ffi::{CStr, c_char};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use tracing::{error, info, warn};
+
+/// Bumped whenever the plugin traits or `PluginRegistryHandle` change in a
+/// way `ando-plugin`'s version would not show.
+pub const ABI_VERSION: u32 = 1;
+
+/// What a library and the gateway must agree on: ABI version,
+/// `ando-plugin` version and compiler. NUL-terminated for the C ABI.
+pub const ABI_TAG: &str = concat!(
+    "1/",
+    env!("CARGO_PKG_VERSION"),
+    "/",
+    env!("ANDO_RUSTC_VERSION"),
+    "\0"
+);
+
+/// Symbol returning the library's `ABI_TAG`.
+const ABI_SYMBOL: &[u8] = b"ando_plugin_abi\0";
+/// Symbol registering the library's plugins.
+const REGISTER_SYMBOL: &[u8] = b"ando_plugin_register\0";
+
+type AbiFn = unsafe extern "C" fn() -> *const c_char;
+type RegisterFn = unsafe extern "C" fn(*mut PluginRegistryHandle);
+
+// ── Host side ────────────────────────────────────────────────
+
+/// Load every plugin library in each of `dirs`, in file name order. A
+/// library that fails to load is logged and skipped. Returns the names of
+/// the plugins registered.
+pub fn load_dirs(registry: &mut PluginRegistry, dirs: &[String]) -> Vec<String> {
+    let mut loaded = Vec::new();
+    for dir in dirs {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!(dir = %dir, error = %e, "Cannot read external plugin directory");
+                continue;
+            }
+        };
+        let mut paths: Vec<_> = entries
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| {
+                p.extension()
+                    .is_some_and(|x| x == std::env::consts::DLL_EXTENSION)
+            })
+            .collect();
+        paths.sort();
+        for path in paths {
+            match load_library(registry, &path) {
+                Ok(names) => loaded.extend(names),
+                Err(e) => {
+                    error!(path = %path.display(), error = %format!("{e:#}"), "External plugin library rejected");
+                }
+            }
+        }
+    }
+    loaded
+}
+
+/// Load one plugin library and register its plugins. Plugins whose name
+/// is already registered are skipped with a warning.
+pub fn load_library(registry: &mut PluginRegistry, path: &Path) -> anyhow::Result<Vec<String>> {
+    // SAFETY: loading runs the library's initializers. Libraries come from
+    // directories the operator configured.
+    let library = unsafe { libloading::Library::new(path) }
+        .with_context(|| format!("cannot load {}", path.display()))?;
+
+    // SAFETY: `ando_plugin_abi` has had the same signature in every ABI
+    // version and returns a static string; nothing else is called before
+    // the tag matches.
+    let tag = unsafe {
+        let abi = library
+            .get::<AbiFn>(ABI_SYMBOL)
+            .context("not an Ando plugin library: no `ando_plugin_abi` symbol")?;
+        CStr::from_ptr(abi()).to_string_lossy().into_owned()
+    };
+    check_abi(&tag)?;
+
+    let mut handle = PluginRegistryHandle {
+        registry,
+        allocator: &HOST_ALLOCATOR,
+        registered: Vec::new(),
+        duplicates: Vec::new(),
+    };
+    // SAFETY: the tag guarantees the library was built by this compiler
+    // against this `ando-plugin`, so the handle's layout matches.
+    unsafe {
+        let register = library
+            .get::<RegisterFn>(REGISTER_SYMBOL)
+            .context("no `ando_plugin_register` symbol")?;
+        register(&mut handle);
+    }
+    // Registered plugins point into the library for the life of the process.
+    std::mem::forget(library);
+
+    let PluginRegistryHandle {
+        registry,
+        registered,
+        duplicates,
+        ..
+    } = handle;
+    for name in &duplicates {
+        warn!(plugin = %name, path = %path.display(), "External plugin skipped, name already registered");
+    }
+    for name in &registered {
+        info!(plugin = %name, path = %path.display(), "Registered external plugin");
+        registry.mark_external(name);
+    }
+    Ok(registered)
+}
+
+fn check_abi(tag: &str) -> anyhow::Result<()> {
+    let ours = ABI_TAG.trim_end_matches('\0');
+    if tag != ours {
+        bail!(
+            "built for plugin ABI `{tag}`, the gateway needs `{ours}`; rebuild it against this release"
+        );
+    }
+    Ok(())
+}
+
+/// The host's allocator, as seen by plugin libraries.
+#[repr(C)]
+pub struct HostAllocator {
+    alloc: unsafe extern "C" fn(usize, usize) -> *mut u8,
+    dealloc: unsafe extern "C" fn(*mut u8, usize, usize),
+    realloc: unsafe extern "C" fn(*mut u8, usize, usize, usize) -> *mut u8,
+}
+
+static HOST_ALLOCATOR: HostAllocator = HostAllocator {
+    alloc: host_alloc,
+    dealloc: host_dealloc,
+    realloc: host_realloc,
+};
+
+unsafe extern "C" fn host_alloc(size: usize, align: usize) -> *mut u8 {
+    // SAFETY: the caller passes a layout its own allocator accepted.
+    unsafe { std::alloc::alloc(Layout::from_size_align_unchecked(size, align)) }
+}
+
+unsafe extern "C" fn host_dealloc(ptr: *mut u8, size: usize, align: usize) {
+    // SAFETY: `ptr` was allocated by `host_alloc` with this layout.
+    unsafe { std::alloc::dealloc(ptr, Layout::from_size_align_unchecked(size, align)) }
+}
+
+unsafe extern "C" fn host_realloc(ptr: *mut u8, size: usize, align: usize, new: usize) -> *mut u8 {
+    // SAFETY: as for `host_dealloc`.
+    unsafe { std::alloc::realloc(ptr, Layout::from_size_align_unchecked(size, align), new) }
+}
+
+// ── Library side ─────────────────────────────────────────────
+
+/// Passed to a library's `ando_plugin_register`.
+pub struct PluginRegistryHandle<'a> {
+    registry: &'a mut PluginRegistry,
+    allocator: &'static HostAllocator,
+    registered: Vec<String>,
+    duplicates: Vec<String>,
+}
+
+impl PluginRegistryHandle<'_> {
+    /// Add a plugin to the gateway's registry, unless a plugin of that
+    /// name is already registered.
+    pub fn register(&mut self, plugin: Arc<dyn Plugin>) {
+        let name = plugin.name().to_string();
+        if self.registry.get(&name).is_some() {
+            self.duplicates.push(name);
+            return;
+        }
+        self.registry.register(plugin);
+        self.registered.push(name);
+    }
+
+    /// The host allocator, for `ForwardingAllocator::attach`.
+    pub fn allocator(&self) -> &'static HostAllocator {
+        self.allocator
+    }
+}
+
+/// Global allocator of a plugin library: the system allocator until the
+/// host attaches its own, which `export_plugins!` does before anything is
+/// allocated.
+pub struct ForwardingAllocator {
+    host: AtomicPtr<HostAllocator>,
+}
+
+impl ForwardingAllocator {
+    pub const fn new() -> Self {
+        Self {
+            host: AtomicPtr::new(std::ptr::null_mut()),
+        }
+    }
+
+    pub fn attach(&self, host: &'static HostAllocator) {
+        self.host
+            .store(std::ptr::from_ref(host).cast_mut(), Ordering::Release);
+    }
+
+    fn host(&self) -> Option<&'static HostAllocator> {
+        // SAFETY: only ever set from a `&'static HostAllocator`.
+        unsafe { self.host.load(Ordering::Acquire).as_ref() }
+    }
+}
+
+impl Default for ForwardingAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: forwards to the host's global allocator, or to `System`.
+unsafe impl GlobalAlloc for ForwardingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        match self.host() {
+            Some(host) => unsafe { (host.alloc)(layout.size(), layout.align()) },
+            None => unsafe { System.alloc(layout) },
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        match self.host() {
+            Some(host) => unsafe { (host.dealloc)(ptr, layout.size(), layout.align()) },
+            None => unsafe { System.dealloc(ptr, layout) },
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        match self.host() {
+            Some(host) => unsafe { (host.realloc)(ptr, layout.size(), layout.align(), new_size) },
+            None => unsafe { System.realloc(ptr, layout, new_size) },
+        }
+    }
+}
+
+/// Export plugins from a `cdylib`:
+///
+/// ```ignore
+/// ando_plugin::export_plugins!(MyPlugin, OtherPlugin::new());
+/// ```
+///
+/// Each argument is a value implementing `Plugin`. Also installs the
+/// library's global allocator, so the library must not declare its own.
+#[macro_export]
+macro_rules! export_plugins {
+    ($($plugin:expr),+ $(,)?) => {
+        #[global_allocator]
+        static ANDO_PLUGIN_ALLOCATOR: $crate::external::ForwardingAllocator =
+            $crate::external::ForwardingAllocator::new();
+
+        #[unsafe(no_mangle)]
+        pub extern "C" fn ando_plugin_abi() -> *const ::std::ffi::c_char {
+            $crate::external::ABI_TAG.as_ptr().cast()
+        }
+
+        /// # Safety
+        ///
+        /// Called once by the gateway, after `ando_plugin_abi` matched.
+        #[unsafe(no_mangle)]
+        pub unsafe extern "C" fn ando_plugin_register(
+            handle: *mut $crate::external::PluginRegistryHandle,
+        ) {
+            // SAFETY: the gateway passes a valid, exclusive handle.
+            let handle = unsafe { &mut *handle };
+            ANDO_PLUGIN_ALLOCATOR.attach(handle.allocator());
+            $(handle.register(::std::sync::Arc::new($plugin));)+
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abi_tag_names_version_and_compiler() {
+        assert!(ABI_TAG.starts_with(&format!("{ABI_VERSION}/{}/", env!("CARGO_PKG_VERSION"))));
+        assert!(ABI_TAG.contains("rustc"));
+        assert!(ABI_TAG.ends_with('\0'));
+        assert!(check_abi(ABI_TAG.trim_end_matches('\0')).is_ok());
+    }
+
+    #[test]
+    fn mismatched_abi_is_rejected() {
+        let err = check_abi("1/0.0.1/rustc 1.0.0").unwrap_err().to_string();
+        assert!(err.contains("rebuild"), "{err}");
+    }
+
+    #[test]
+    fn non_library_file_is_rejected() {
+        let dir = std::env::temp_dir().join(format!("ando-ext-plugins-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("bogus.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::write(&path, b"not a library").unwrap();
+
+        let mut registry = PluginRegistry::new();
+        assert!(load_library(&mut registry, &path).is_err());
+        assert!(load_dirs(&mut registry, &[dir.to_string_lossy().into_owned()]).is_empty());
+        assert!(load_dirs(&mut registry, &["/nonexistent/ando-plugins".into()]).is_empty());
+        assert!(registry.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::plugin::PluginContext;
+use ando_core::vars::{VarExpr, cookie_value, query_arg};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// A plugin's `_meta.filter`: conditions on the request, all of which must
+/// hold for the plugin to run, e.g.
+/// `{"_meta": {"filter": [["consumer", "==", ""], ["http_x_debug", "!", "==", "1"]]}}`.
+///
+/// Conditions use the syntax of a route's `vars` (see [`VarExpr`]) and
+/// are compiled once, when the pipeline is built. The variables are the
+/// router's (`http_<header>`, `arg_<query arg>`, `cookie_<name>`,
+/// `remote_addr`, `uri`, `host`, `request_method`) plus `client_ip`,
+/// `method`, `consumer` (empty before authentication), `route_id` and
+/// `path_<name>` for the route's path parameters. An unknown variable is
+/// a config error.
+#[derive(Debug, Clone)]
+pub struct PluginFilter(Arc<[(RequestVar, VarExpr)]>);
+
+impl PluginFilter {
+    /// Compile the `_meta.filter` of a plugin config, `None` without one.
+    pub fn from_config(config: &Value) -> Result<Option<Self>, String> {
+        let Some(filter) = config.pointer("/_meta/filter") else {
+            return Ok(None);
+        };
+        let conditions = filter
+            .as_array()
+            .ok_or("_meta.filter must be an array of conditions")?;
+        conditions
+            .iter()
+            .map(|condition| {
+                let tuple = condition
+                    .as_array()
+                    .ok_or("_meta.filter conditions must be arrays")?;
+                let expr = VarExpr::compile(tuple).map_err(|e| format!("_meta.filter: {e}"))?;
+                let var = RequestVar::parse(expr.var())
+                    .ok_or_else(|| format!("_meta.filter: unknown variable `{}`", expr.var()))?;
+                Ok((var, expr))
+            })
+            .collect::<Result<Arc<[_]>, String>>()
+            .map(|conditions| Some(Self(conditions)))
+    }
+
+    /// Whether the plugin runs for this request.
+    #[inline]
+    pub fn matches(&self, ctx: &PluginContext) -> bool {
+        self.0
+            .iter()
+            .all(|(var, expr)| expr.matches_value(var.get(ctx)))
+    }
+}
+
+/// A variable a filter condition reads, resolved from its name up front.
+#[derive(Debug, Clone)]
+enum RequestVar {
+    ClientIp,
+    Uri,
+    Host,
+    Method,
+    Consumer,
+    RouteId,
+    /// Lowercase, with `-` in place of `_`.
+    Header(String),
+    Arg(String),
+    Cookie(String),
+    Path(String),
+}
+
+impl RequestVar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "remote_addr" | "client_ip" => Self::ClientIp,
+            "uri" => Self::Uri,
+            "host" => Self::Host,
+            "request_method" | "method" => Self::Method,
+            "consumer" => Self::Consumer,
+            "route_id" => Self::RouteId,
+            _ => {
+                let (prefix, rest) = name.split_once('_')?;
+                if rest.is_empty() {
+                    return None;
+                }
+                match prefix {
+                    "http" => Self::Header(rest.to_ascii_lowercase().replace('_', "-")),
+                    "arg" => Self::Arg(rest.to_string()),
+                    "cookie" => Self::Cookie(rest.to_string()),
+                    "path" => Self::Path(rest.to_string()),
+                    _ => return None,
+                }
+            }
+        })
+    }
+
+    fn get<'a>(&self, ctx: &'a PluginContext) -> Option<&'a str> {
+        match self {
+            Self::ClientIp => Some(&ctx.client_ip),
+            Self::Uri => Some(ctx.uri.split_once('?').map_or(ctx.uri.as_str(), |(p, _)| p)),
+            Self::Host => ctx.get_header("host"),
+            Self::Method => Some(&ctx.method),
+            Self::Consumer => Some(ctx.consumer.as_deref().unwrap_or("")),
+            Self::RouteId => Some(&ctx.route_id),
+            Self::Header(name) => ctx.get_header(name),
+            Self::Arg(name) => query_arg(ctx.uri.split_once('?')?.1, name),
+            Self::Cookie(name) => cookie_value(ctx.get_header("cookie")?, name),
+            Self::Path(name) => ctx.route_param(name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn filter(conditions: Value) -> PluginFilter {
+        PluginFilter::from_config(&json!({ "_meta": { "filter": conditions } }))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn ctx() -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "orders".into(),
+            "10.0.0.1".into(),
+            "POST".into(),
+            "/orders/7?debug=1".into(),
+            HashMap::new(),
+        );
+        ctx.request_headers = vec![
+            ("X-Tenant".to_string(), "acme".to_string()),
+            ("Cookie".to_string(), "sid=abc; theme=dark".to_string()),
+        ]
+        .into();
+        ctx.route_params = vec![("id".into(), "7".into())];
+        ctx
+    }
+
+    #[test]
+    fn reads_request_variables() {
+        let ctx = ctx();
+        for (conditions, expected) in [
+            (json!([["http_x_tenant", "==", "acme"]]), true),
+            (json!([["http_x_tenant", "==", "other"]]), false),
+            (json!([["arg_debug", "1"]]), true),
+            (json!([["cookie_theme", "==", "dark"]]), true),
+            (json!([["client_ip", "==", "10.0.0.1"]]), true),
+            (json!([["remote_addr", "~~", "^10\\."]]), true),
+            (json!([["method", "in", ["GET", "HEAD"]]]), false),
+            (json!([["uri", "==", "/orders/7"]]), true),
+            (json!([["path_id", ">", 5]]), true),
+            (json!([["route_id", "==", "orders"]]), true),
+            (json!([["http_x_missing", "~=", "x"]]), true),
+        ] {
+            assert_eq!(
+                filter(conditions.clone()).matches(&ctx),
+                expected,
+                "{conditions}"
+            );
+        }
+    }
+
+    #[test]
+    fn conditions_must_all_hold() {
+        let mut ctx = ctx();
+        let anonymous_posts = filter(json!([["consumer", "==", ""], ["method", "POST"]]));
+        assert!(anonymous_posts.matches(&ctx));
+        ctx.consumer = Some("alice".into());
+        assert!(!anonymous_posts.matches(&ctx));
+        assert!(filter(json!([])).matches(&ctx));
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        assert!(matches!(PluginFilter::from_config(&json!({})), Ok(None)));
+        for conditions in [
+            json!("consumer == alice"),
+            json!(["consumer", "==", "alice"]),
+            json!([["consumer", "??", "alice"]]),
+            json!([["uri", "~~", "("]]),
+            json!([["nope", "==", "1"]]),
+            json!([["http_", "==", "1"]]),
+        ] {
+            let config = json!({ "_meta": { "filter": conditions } });
+            assert!(PluginFilter::from_config(&config).is_err(), "{conditions}");
+        }
+    }
+}
@@ -1,3 +1,5 @@
+pub mod external;
+pub mod filter;
 pub mod pipeline;
 pub mod plugin;
 pub mod registry;
@@ -1,5 +1,44 @@
-use crate::plugin::{Phase, PluginContext, PluginInstance, PluginResult};
+use crate::filter::PluginFilter;
+use crate::plugin::{AccessFuture, Phase, PluginContext, PluginInstance, PluginResult};
+use ando_core::error_page::ErrorPages;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// `ctx.vars` key holding the names of the plugins that ran, in order.
+pub const EXECUTED_PLUGINS_VAR: &str = "_executed_plugins";
+
+/// Told how long each plugin call of a pipeline took.
+pub trait PluginTimer: Send + Sync {
+    fn observe(&self, route_id: &str, plugin: &str, phase: Phase, elapsed: Duration);
+}
+
+/// A configured plugin with the route's `_meta` settings for it.
+#[derive(Clone)]
+pub struct PipelineEntry {
+    pub instance: Arc<dyn PluginInstance>,
+    /// Orders the plugin within its phases: `_meta.priority`, or the
+    /// plugin's own.
+    pub priority: i32,
+    /// `_meta.filter`: requests it does not match skip the plugin.
+    pub filter: Option<PluginFilter>,
+}
+
+impl PipelineEntry {
+    /// An entry with the plugin's own priority and no filter.
+    pub fn new(instance: Arc<dyn PluginInstance>) -> Self {
+        Self {
+            priority: instance.priority(),
+            instance,
+            filter: None,
+        }
+    }
+
+    /// Whether the plugin runs for this request.
+    #[inline]
+    fn runs(&self, ctx: &PluginContext) -> bool {
+        self.filter.as_ref().is_none_or(|f| f.matches(ctx))
+    }
+}
 
 /// Pre-built plugin pipeline for a route.
 ///
@@ -9,12 +48,13 @@ use std::sync::Arc;
 /// for common plugin combinations).
 pub struct PluginPipeline {
     /// Plugins sorted by phase, then by priority (descending).
-    rewrite: Vec<Arc<dyn PluginInstance>>,
-    access: Vec<Arc<dyn PluginInstance>>,
-    before_proxy: Vec<Arc<dyn PluginInstance>>,
-    header_filter: Vec<Arc<dyn PluginInstance>>,
-    _body_filter: Vec<Arc<dyn PluginInstance>>,
-    log: Vec<Arc<dyn PluginInstance>>,
+    rewrite: Vec<PipelineEntry>,
+    access: Vec<PipelineEntry>,
+    async_access: Vec<PipelineEntry>,
+    before_proxy: Vec<PipelineEntry>,
+    header_filter: Vec<PipelineEntry>,
+    body_filter: Vec<PipelineEntry>,
+    log: Vec<PipelineEntry>,
 
     /// Pre-computed flags for O(1) phase-presence checks.
     has_rewrite: bool,
@@ -26,13 +66,40 @@ pub struct PluginPipeline {
 
     /// Whether any auth plugin is present (for consumer injection).
     has_auth: bool,
+
+    /// Whether executed plugin names are recorded into `ctx.vars`.
+    trace: bool,
+
+    /// Whether any plugin reads the request body.
+    reads_body: bool,
+
+    /// The route's own error pages, from its `error-page` plugin.
+    error_pages: Option<Arc<ErrorPages>>,
+
+    /// Receives the duration of every plugin call. Without one no clock
+    /// is read.
+    timer: Option<Arc<dyn PluginTimer>>,
 }
 
 impl PluginPipeline {
     /// Build a pipeline from a list of plugin instances.
     pub fn build(instances: Vec<Arc<dyn PluginInstance>>, has_auth: bool) -> Self {
+        Self::build_entries(
+            instances.into_iter().map(PipelineEntry::new).collect(),
+            has_auth,
+        )
+    }
+
+    /// Build a pipeline from configured plugins. Equal priorities keep
+    /// their order in `entries`.
+    pub fn build_entries(mut entries: Vec<PipelineEntry>, has_auth: bool) -> Self {
+        // Sort by priority (descending — higher priority first); every
+        // phase vector is filled in this order.
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.priority));
+
         let mut rewrite = Vec::new();
         let mut access = Vec::new();
+        let mut async_access = Vec::new();
         let mut before_proxy = Vec::new();
         let mut header_filter = Vec::new();
         let mut body_filter = Vec::new();
@@ -41,27 +108,35 @@ impl PluginPipeline {
         // For now, add all instances to all phase vectors.
         // In a production system, we'd have phase metadata per instance.
         // The trait methods have default no-op impls, so calling them is cheap.
-        for inst in &instances {
-            rewrite.push(Arc::clone(inst));
-            access.push(Arc::clone(inst));
-            before_proxy.push(Arc::clone(inst));
-            header_filter.push(Arc::clone(inst));
-            body_filter.push(Arc::clone(inst));
-            log.push(Arc::clone(inst));
+        for entry in &entries {
+            let inst = &entry.instance;
+            rewrite.push(entry.clone());
+            access.push(entry.clone());
+            if inst.has_async_access() {
+                async_access.push(entry.clone());
+            }
+            before_proxy.push(entry.clone());
+            // Rewriting the response head costs a copy of its headers,
+            // so header filtering is opt-in as well.
+            if inst.filters_headers() {
+                header_filter.push(entry.clone());
+            }
+            // Body filtering forces response buffering, so only opt-in
+            // instances join this phase.
+            if inst.filters_body() {
+                body_filter.push(entry.clone());
+            }
+            // Likewise the log phase keeps the context alive past the
+            // upstream exchange.
+            if inst.logs() {
+                log.push(entry.clone());
+            }
         }
 
-        // Sort by priority (descending — higher priority first)
-        let sort_fn = |a: &Arc<dyn PluginInstance>, b: &Arc<dyn PluginInstance>| {
-            b.priority().cmp(&a.priority())
-        };
-        rewrite.sort_by(sort_fn);
-        access.sort_by(sort_fn);
-        before_proxy.sort_by(sort_fn);
-        header_filter.sort_by(sort_fn);
-        body_filter.sort_by(sort_fn);
-        log.sort_by(sort_fn);
-
         Self {
+            trace: entries.iter().any(|e| e.instance.traces_execution()),
+            reads_body: entries.iter().any(|e| e.instance.reads_body()),
+            error_pages: entries.iter().find_map(|e| e.instance.error_pages()),
             has_rewrite: !rewrite.is_empty(),
             has_access: !access.is_empty(),
             has_before_proxy: !before_proxy.is_empty(),
@@ -70,14 +145,22 @@ impl PluginPipeline {
             has_log: !log.is_empty(),
             rewrite,
             access,
+            async_access,
             before_proxy,
             header_filter,
-            _body_filter: body_filter,
+            body_filter,
             log,
             has_auth,
+            timer: None,
         }
     }
 
+    /// Time every plugin call and report it to `timer`.
+    pub fn with_timer(mut self, timer: Arc<dyn PluginTimer>) -> Self {
+        self.timer = Some(timer);
+        self
+    }
+
     /// Execute a specific phase. Returns early on short-circuit.
     #[inline]
     pub fn execute_phase(&self, phase: Phase, ctx: &mut PluginContext) -> PluginResult {
@@ -106,38 +189,102 @@ impl PluginPipeline {
                 }
                 &self.header_filter
             }
-            Phase::BodyFilter | Phase::Log => return PluginResult::Continue,
+            Phase::BodyFilter => {
+                if !self.has_body_filter {
+                    return PluginResult::Continue;
+                }
+                &self.body_filter
+            }
+            Phase::Log => return PluginResult::Continue,
         };
 
-        for plugin in plugins {
+        for entry in plugins {
+            if !entry.runs(ctx) {
+                continue;
+            }
+            let plugin = &entry.instance;
+            if self.trace {
+                record_executed(ctx, plugin.name());
+            }
+            let started = self.timer.as_ref().map(|_| Instant::now());
             let result = match phase {
                 Phase::Rewrite => plugin.rewrite(ctx),
                 Phase::Access => plugin.access(ctx),
                 Phase::BeforeProxy => plugin.before_proxy(ctx),
                 Phase::HeaderFilter => plugin.header_filter(ctx),
-                _ => PluginResult::Continue,
+                Phase::BodyFilter => plugin.body_filter(ctx),
+                Phase::Log => PluginResult::Continue,
             };
+            if let (Some(timer), Some(started)) = (&self.timer, started) {
+                timer.observe(&ctx.route_id, plugin.name(), phase, started.elapsed());
+            }
 
             match result {
                 PluginResult::Continue => continue,
-                PluginResult::Response { .. } => return result,
+                PluginResult::Response { .. } => {
+                    ctx.responder = Some(plugin.name().to_string());
+                    ctx.error_page = plugin.uses_error_pages();
+                    return result;
+                }
             }
         }
 
         PluginResult::Continue
     }
 
+    /// Start the async access hooks, in priority order.
+    /// Each future comes with the name of its plugin.
+    pub fn access_futures(&self, ctx: &mut PluginContext) -> Vec<(String, AccessFuture)> {
+        self.async_access
+            .iter()
+            .filter_map(|entry| {
+                if !entry.runs(ctx) {
+                    return None;
+                }
+                let plugin = &entry.instance;
+                Some((plugin.name().to_string(), plugin.access_async(ctx)?))
+            })
+            .collect()
+    }
+
+    /// Check if any plugin has an async access hook.
+    #[inline]
+    pub fn has_async_access(&self) -> bool {
+        !self.async_access.is_empty()
+    }
+
     /// Execute the log phase (all plugins, fire-and-forget).
     #[inline]
     pub fn execute_log(&self, ctx: &PluginContext) {
         if !self.has_log {
             return;
         }
-        for plugin in &self.log {
+        for entry in &self.log {
+            if !entry.runs(ctx) {
+                continue;
+            }
+            let plugin = &entry.instance;
+            let started = self.timer.as_ref().map(|_| Instant::now());
             plugin.log(ctx);
+            if let (Some(timer), Some(started)) = (&self.timer, started) {
+                timer.observe(&ctx.route_id, plugin.name(), Phase::Log, started.elapsed());
+            }
         }
     }
 
+    /// Check if any plugin needs `ctx.request_body`.
+    #[inline]
+    pub fn reads_request_body(&self) -> bool {
+        self.reads_body
+    }
+
+    /// Error pages of the route, replacing the gateway's for the
+    /// statuses they cover.
+    #[inline]
+    pub fn error_pages(&self) -> Option<&Arc<ErrorPages>> {
+        self.error_pages.as_ref()
+    }
+
     /// Check if this pipeline has auth plugins.
     #[inline]
     pub fn has_auth_plugins(&self) -> bool {
@@ -167,6 +314,20 @@ impl PluginPipeline {
     }
 }
 
+/// Append `name` to `ctx.vars[EXECUTED_PLUGINS_VAR]`. Every instance takes
+/// part in every phase, so a plugin is listed once, when it first runs.
+fn record_executed(ctx: &mut PluginContext, name: &str) {
+    let entry = ctx
+        .vars
+        .entry(EXECUTED_PLUGINS_VAR.to_string())
+        .or_insert_with(|| serde_json::Value::Array(Vec::new()));
+    if let serde_json::Value::Array(names) = entry
+        && !names.iter().any(|n| n == name)
+    {
+        names.push(serde_json::Value::String(name.to_string()));
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,6 +416,7 @@ mod tests {
         } else {
             panic!("Expected Response from block plugin");
         }
+        assert_eq!(ctx.responder.as_deref(), Some("block"));
     }
 
     #[test]
@@ -277,6 +439,44 @@ mod tests {
         assert_eq!(pipeline.len(), 1);
     }
 
+    #[derive(Default)]
+    struct RecordingTimer(std::sync::Mutex<Vec<(String, String, Phase)>>);
+    impl PluginTimer for RecordingTimer {
+        fn observe(&self, route_id: &str, plugin: &str, phase: Phase, _elapsed: Duration) {
+            self.0
+                .lock()
+                .unwrap()
+                .push((route_id.into(), plugin.into(), phase));
+        }
+    }
+
+    #[test]
+    fn timer_sees_every_plugin_call_until_short_circuit() {
+        let timer = Arc::new(RecordingTimer::default());
+        let pipeline = PluginPipeline::build(
+            vec![Arc::new(PassPlugin), Arc::new(BlockPlugin { status: 403 })],
+            false,
+        )
+        .with_timer(timer.clone());
+        let mut ctx = make_ctx();
+        pipeline.execute_phase(Phase::Rewrite, &mut ctx);
+        pipeline.execute_phase(Phase::Access, &mut ctx);
+
+        let calls = timer.0.lock().unwrap();
+        let names: Vec<_> = calls
+            .iter()
+            .map(|(r, p, ph)| (r.as_str(), p.as_str(), *ph))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("r1", "block", Phase::Rewrite),
+                ("r1", "pass", Phase::Rewrite),
+                ("r1", "block", Phase::Access),
+            ]
+        );
+    }
+
     #[test]
     fn test_empty_pipeline_no_phases() {
         let pipeline = PluginPipeline::build(vec![], false);
@@ -286,6 +486,66 @@ mod tests {
         assert!(!pipeline.has_phase(Phase::Log));
     }
 
+    #[test]
+    fn test_header_filter_phase_is_opt_in() {
+        struct StripServer;
+        impl PluginInstance for StripServer {
+            fn name(&self) -> &str {
+                "strip-server"
+            }
+            fn filters_headers(&self) -> bool {
+                true
+            }
+            fn header_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+                ctx.upstream_response_headers.retain(|(n, _)| n != "server");
+                PluginResult::Continue
+            }
+        }
+        let pipeline = PluginPipeline::build(vec![Arc::new(PassPlugin)], false);
+        assert!(!pipeline.has_phase(Phase::HeaderFilter));
+
+        let pipeline =
+            PluginPipeline::build(vec![Arc::new(PassPlugin), Arc::new(StripServer)], false);
+        assert!(pipeline.has_phase(Phase::HeaderFilter));
+        let mut ctx = make_ctx();
+        ctx.upstream_response_headers = vec![("server".into(), "nginx".into())];
+        pipeline.execute_phase(Phase::HeaderFilter, &mut ctx);
+        assert!(ctx.upstream_response_headers.is_empty());
+    }
+
+    struct UppercaseBody;
+    impl PluginInstance for UppercaseBody {
+        fn name(&self) -> &str {
+            "uppercase-body"
+        }
+        fn filters_body(&self) -> bool {
+            true
+        }
+        fn body_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+            if let Some(body) = ctx.response_body.as_mut() {
+                body.make_ascii_uppercase();
+            }
+            PluginResult::Continue
+        }
+    }
+
+    #[test]
+    fn test_body_filter_phase_is_opt_in() {
+        let plugin: Arc<dyn PluginInstance> = Arc::new(PassPlugin);
+        let pipeline = PluginPipeline::build(vec![plugin], false);
+        assert!(!pipeline.has_phase(Phase::BodyFilter));
+
+        let plugin: Arc<dyn PluginInstance> = Arc::new(UppercaseBody);
+        let pipeline = PluginPipeline::build(vec![plugin], false);
+        assert!(pipeline.has_phase(Phase::BodyFilter));
+
+        let mut ctx = make_ctx();
+        ctx.response_body = Some(b"hello".to_vec());
+        let result = pipeline.execute_phase(Phase::BodyFilter, &mut ctx);
+        assert!(matches!(result, PluginResult::Continue));
+        assert_eq!(ctx.response_body.as_deref(), Some(&b"HELLO"[..]));
+    }
+
     // ── Priority ordering: higher priority runs first ─────────────
 
     /// A plugin that appends its name to a shared log vec via context vars.
@@ -423,4 +683,209 @@ mod tests {
         pipeline.execute_phase(Phase::Rewrite, &mut ctx);
         assert_eq!(ctx.consumer.as_deref(), Some("rewrite-ran"));
     }
+
+    // ── Async access hooks ───────────────────────────────────────
+
+    #[test]
+    fn only_async_instances_produce_access_futures() {
+        use crate::plugin::{AccessFuture, AsyncAccess};
+        struct AsyncPlugin(i32);
+        impl PluginInstance for AsyncPlugin {
+            fn name(&self) -> &str {
+                "async"
+            }
+            fn priority(&self) -> i32 {
+                self.0
+            }
+            fn has_async_access(&self) -> bool {
+                true
+            }
+            fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+                ctx.vars
+                    .entry("order".into())
+                    .or_insert_with(|| serde_json::json!([]))
+                    .as_array_mut()
+                    .unwrap()
+                    .push(self.0.into());
+                Some(Box::pin(std::future::ready(AsyncAccess {
+                    result: PluginResult::Continue,
+                    response_headers: vec![],
+                    upstream_headers: Vec::new(),
+                })))
+            }
+        }
+
+        let sync_only = PluginPipeline::build(vec![Arc::new(PassPlugin)], false);
+        assert!(!sync_only.has_async_access());
+        assert!(sync_only.access_futures(&mut make_ctx()).is_empty());
+
+        let pipeline = PluginPipeline::build(
+            vec![
+                Arc::new(AsyncPlugin(1)),
+                Arc::new(PassPlugin),
+                Arc::new(AsyncPlugin(5)),
+            ],
+            false,
+        );
+        assert!(pipeline.has_async_access());
+        let mut ctx = make_ctx();
+        assert_eq!(pipeline.access_futures(&mut ctx).len(), 2);
+        assert_eq!(ctx.vars["order"], serde_json::json!([5, 1]));
+    }
+
+    // ── Execution tracing ──
+
+    struct Traced(&'static str, i32, bool);
+    impl PluginInstance for Traced {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn priority(&self) -> i32 {
+            self.1
+        }
+        fn traces_execution(&self) -> bool {
+            self.2
+        }
+    }
+
+    #[test]
+    fn test_executed_plugins_recorded_in_priority_order() {
+        let pipeline = PluginPipeline::build(
+            vec![
+                Arc::new(Traced("low", 1, true)),
+                Arc::new(Traced("high", 100, false)),
+                Arc::new(Traced("mid", 50, false)),
+            ],
+            false,
+        );
+        let mut ctx = make_ctx();
+        pipeline.execute_phase(Phase::Rewrite, &mut ctx);
+        pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert_eq!(
+            ctx.vars[EXECUTED_PLUGINS_VAR],
+            serde_json::json!(["high", "mid", "low"])
+        );
+    }
+
+    #[test]
+    fn test_priority_overrides_replace_plugin_priorities() {
+        let entry = |inst: Traced, priority| PipelineEntry {
+            priority,
+            ..PipelineEntry::new(Arc::new(inst))
+        };
+        let pipeline = PluginPipeline::build_entries(
+            vec![
+                entry(Traced("high", 100, true), 100),
+                entry(Traced("low", 1, false), 200),
+                entry(Traced("mid", 50, false), 50),
+            ],
+            false,
+        );
+        let mut ctx = make_ctx();
+        pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert_eq!(
+            ctx.vars[EXECUTED_PLUGINS_VAR],
+            serde_json::json!(["low", "high", "mid"])
+        );
+    }
+
+    #[test]
+    fn test_filtered_plugin_is_skipped_for_unmatched_requests() {
+        let filter = PluginFilter::from_config(
+            &serde_json::json!({ "_meta": { "filter": [["http_x_debug", "==", "1"]] } }),
+        )
+        .unwrap();
+        let pipeline = PluginPipeline::build_entries(
+            vec![
+                PipelineEntry::new(Arc::new(Traced("always", 100, true))),
+                PipelineEntry {
+                    filter,
+                    ..PipelineEntry::new(Arc::new(BlockPlugin { status: 403 }))
+                },
+            ],
+            false,
+        );
+
+        let mut ctx = make_ctx();
+        let result = pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert!(matches!(result, PluginResult::Continue));
+        assert_eq!(
+            ctx.vars[EXECUTED_PLUGINS_VAR],
+            serde_json::json!(["always"])
+        );
+
+        let mut ctx = make_ctx();
+        ctx.request_headers.insert("X-Debug".into(), "1".into());
+        let result = pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert!(matches!(result, PluginResult::Response { status: 403, .. }));
+        assert_eq!(
+            ctx.vars[EXECUTED_PLUGINS_VAR],
+            serde_json::json!(["always", "block"])
+        );
+    }
+
+    #[test]
+    fn test_short_circuit_stops_recording() {
+        let pipeline = PluginPipeline::build(
+            vec![
+                Arc::new(Traced("after", 1, true)),
+                Arc::new(BlockPlugin { status: 403 }),
+                Arc::new(Traced("before", 100, false)),
+            ],
+            false,
+        );
+        let mut ctx = make_ctx();
+        let result = pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert!(matches!(result, PluginResult::Response { status: 403, .. }));
+        assert_eq!(
+            ctx.vars[EXECUTED_PLUGINS_VAR],
+            serde_json::json!(["before", "block"])
+        );
+    }
+
+    #[test]
+    fn test_no_recording_without_tracing_plugin() {
+        let pipeline = PluginPipeline::build(vec![Arc::new(Traced("a", 1, false))], false);
+        let mut ctx = make_ctx();
+        pipeline.execute_phase(Phase::Access, &mut ctx);
+        assert!(!ctx.vars.contains_key(EXECUTED_PLUGINS_VAR));
+    }
+
+    #[test]
+    fn test_reads_request_body_is_opt_in() {
+        struct BodyReader;
+        impl PluginInstance for BodyReader {
+            fn name(&self) -> &str {
+                "body-reader"
+            }
+            fn reads_body(&self) -> bool {
+                true
+            }
+        }
+
+        let pipeline = PluginPipeline::build(vec![Arc::new(PassPlugin)], false);
+        assert!(!pipeline.reads_request_body());
+        let pipeline =
+            PluginPipeline::build(vec![Arc::new(PassPlugin), Arc::new(BodyReader)], false);
+        assert!(pipeline.reads_request_body());
+    }
+
+    #[test]
+    fn test_log_phase_is_opt_in() {
+        struct StatusLogger;
+        impl PluginInstance for StatusLogger {
+            fn name(&self) -> &str {
+                "status-logger"
+            }
+            fn logs(&self) -> bool {
+                true
+            }
+        }
+
+        let pipeline = PluginPipeline::build(vec![Arc::new(PassPlugin)], false);
+        assert!(!pipeline.has_phase(Phase::Log));
+        let pipeline =
+            PluginPipeline::build(vec![Arc::new(PassPlugin), Arc::new(StatusLogger)], false);
+        assert!(pipeline.has_phase(Phase::Log));
+    }
 }
@@ -1,4 +1,9 @@
-use std::collections::HashMap;
+use ando_core::consumer::Consumer;
+use ando_core::error_page::ErrorPages;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
 
 /// Plugin execution phases — APISIX-compatible.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -11,6 +16,44 @@ pub enum Phase {
     Log,
 }
 
+impl Phase {
+    /// Name of the phase as used in metric labels and logs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Phase::Rewrite => "rewrite",
+            Phase::Access => "access",
+            Phase::BeforeProxy => "before_proxy",
+            Phase::HeaderFilter => "header_filter",
+            Phase::BodyFilter => "body_filter",
+            Phase::Log => "log",
+        }
+    }
+}
+
+/// Whether a plugin config turns the plugin off, APISIX-style:
+/// `{"_meta": {"disable": true}}`. A route uses it to opt out of a plugin
+/// it would otherwise get from its service or a global rule.
+pub fn is_disabled(config: &serde_json::Value) -> bool {
+    config
+        .pointer("/_meta/disable")
+        .and_then(serde_json::Value::as_bool)
+        == Some(true)
+}
+
+/// The priority a plugin config sets for its plugin on this route,
+/// `{"_meta": {"priority": 2000}}`, in place of the plugin's own. Errors
+/// unless the value is an integer within `i32`.
+pub fn priority_override(config: &serde_json::Value) -> Result<Option<i32>, String> {
+    let Some(value) = config.pointer("/_meta/priority") else {
+        return Ok(None);
+    };
+    value
+        .as_i64()
+        .and_then(|p| i32::try_from(p).ok())
+        .map(Some)
+        .ok_or_else(|| format!("_meta.priority must be an integer within i32, got {value}"))
+}
+
 /// Result of plugin execution.
 pub enum PluginResult {
     /// Continue to next plugin / proxy upstream.
@@ -23,6 +66,23 @@ pub enum PluginResult {
     },
 }
 
+/// Outcome of an async access hook.
+pub struct AsyncAccess {
+    pub result: PluginResult,
+    /// Headers to add to the response when the request is proxied.
+    pub response_headers: Vec<(String, String)>,
+    /// Headers to set on the upstream request (lowercase names), for
+    /// values only known once the hook has finished. They replace client
+    /// headers of the same name.
+    pub upstream_headers: Vec<(String, String)>,
+}
+
+/// Future returned by `PluginInstance::access_async`.
+///
+/// It is polled on the worker's monoio runtime, so it must not need a
+/// tokio reactor: run the I/O on a background runtime and await a channel.
+pub type AccessFuture = Pin<Box<dyn Future<Output = AsyncAccess>>>;
+
 /// Context passed through the plugin pipeline for a single request.
 ///
 /// v2 design: Stack-allocated where possible. No Box, no Arc on the hot path.
@@ -30,22 +90,64 @@ pub enum PluginResult {
 pub struct PluginContext {
     /// Route ID.
     pub route_id: String,
+    /// Service the route belongs to, if any.
+    pub service_id: Option<String>,
     /// Client IP.
     pub client_ip: String,
+    /// `"https"` for requests on the TLS listener, `"http"` otherwise.
+    pub scheme: &'static str,
     /// HTTP method.
     pub method: String,
     /// Request URI.
     pub uri: String,
-    /// Request headers (lowercase keys).
-    pub request_headers: HashMap<String, String>,
+    /// Request headers as the client sent them: original casing, every
+    /// value of a repeated header.
+    pub request_headers: RequestHeaders,
+    /// Request body. Only set on routes where a plugin reads it (see
+    /// `PluginInstance::reads_body`).
+    pub request_body: Option<Vec<u8>>,
+    /// Size of the request body in bytes: its `content-length`, or the
+    /// decoded size of a chunked body. Set on every request.
+    pub request_body_size: u64,
+    /// Path parameters captured by the matched route, e.g. `("id", "42")`.
+    /// A `/*` wildcard's remainder is stored under `*`.
+    pub route_params: Vec<(String, String)>,
+    /// Headers to set on the upstream request (lowercase keys). They
+    /// replace client headers of the same name.
+    pub upstream_headers: HashMap<String, String>,
+    /// Node (`host:port`) to proxy to instead of the route's upstream,
+    /// chosen by a plugin such as traffic-split.
+    pub upstream_addr: Option<String>,
+    /// Named upstream to proxy to instead of the route's upstream. The
+    /// proxy balances across its nodes. `upstream_addr` takes precedence.
+    pub upstream_id: Option<String>,
     /// Response status (set by upstream or plugin).
     pub response_status: Option<u16>,
-    /// Response headers to add/modify.
-    pub response_headers: HashMap<String, String>,
+    /// Response headers plugins set or add; see [`ResponseHeaders`].
+    pub response_headers: ResponseHeaders,
+    /// Buffered upstream response body. Only set while the body-filter
+    /// phase runs; plugins rewrite it in place.
+    pub response_body: Option<Vec<u8>>,
+    /// Upstream response headers (lowercase names, framing headers
+    /// removed). Only set while the body-filter phase runs; plugins may
+    /// edit them in place.
+    pub upstream_response_headers: Vec<(String, String)>,
     /// Matched consumer username (set by auth plugins).
     pub consumer: Option<String>,
+    /// Consumer credentials, for auth plugins that verify secrets
+    /// themselves. Only set on routes with auth plugins.
+    pub consumers: Option<Arc<ConsumerIndex>>,
     /// Arbitrary plugin context data.
     pub vars: HashMap<String, serde_json::Value>,
+    /// Plugin that answered the request itself, set by the pipeline when
+    /// a phase short-circuits.
+    pub responder: Option<String>,
+    /// Set with `responder` when that plugin `uses_error_pages`.
+    pub error_page: bool,
+    /// Values held until the request is finished, such as a limit-conn
+    /// permit. They are dropped with the context, however the request
+    /// ended.
+    pub guards: Vec<Box<dyn std::any::Any + Send>>,
 }
 
 impl PluginContext {
@@ -58,29 +160,368 @@ impl PluginContext {
     ) -> Self {
         Self {
             route_id,
+            service_id: None,
             client_ip,
+            scheme: "http",
             method,
             uri,
-            request_headers,
+            request_headers: request_headers.into(),
+            request_body: None,
+            request_body_size: 0,
+            route_params: Vec::new(),
+            upstream_headers: HashMap::new(),
+            upstream_addr: None,
+            upstream_id: None,
             response_status: None,
-            response_headers: HashMap::new(),
+            response_headers: ResponseHeaders::default(),
+            response_body: None,
+            upstream_response_headers: Vec::new(),
             consumer: None,
+            consumers: None,
             vars: HashMap::new(),
+            responder: None,
+            error_page: false,
+            guards: Vec::new(),
         }
     }
 
-    /// Get a request header value.
+    /// Get a request header value. A repeated header yields its first
+    /// value.
     #[inline]
     pub fn get_header(&self, name: &str) -> Option<&str> {
-        self.request_headers.get(name).map(|s| s.as_str())
+        self.request_headers.get(name)
+    }
+
+    /// Every value of a request header, in the order the client sent them.
+    pub fn get_headers_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.request_headers.get_all(name)
+    }
+
+    /// Add a value to a request header, keeping the values it already has.
+    pub fn append_header(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.request_headers.append(name, value);
     }
+
+    /// Get a captured route parameter (`*` for the wildcard remainder).
+    #[inline]
+    pub fn route_param(&self, name: &str) -> Option<&str> {
+        self.route_params
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Request headers of a [`PluginContext`], in the order the client sent
+/// them.
+///
+/// Names keep their original casing and a repeated header (`Accept`,
+/// `X-Forwarded-For`) keeps every value; lookups ignore ASCII case. The
+/// names plugins set, append or remove are recorded so the proxy can
+/// forward those edits upstream.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestHeaders {
+    entries: Vec<(String, String)>,
+    /// Lowercase names edited since the headers were built.
+    edited: Vec<String>,
+}
+
+impl RequestHeaders {
+    /// First value of a header.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of a header, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Replace every value of a header with `value`. Returns the first
+    /// value it had.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        let old = self.remove(&name);
+        self.entries.push((name, value));
+        old
+    }
+
+    /// Add a value to a header, keeping the values it already has.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        let name = name.into();
+        self.note_edit(&name);
+        self.entries.push((name, value.into()));
+    }
+
+    /// Remove every value of a header. Returns the first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.note_edit(name);
+        let mut first = None;
+        self.entries.retain_mut(|(k, v)| {
+            if !k.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if first.is_none() {
+                first = Some(std::mem::take(v));
+            }
+            false
+        });
+        first
+    }
+
+    /// Every header as a `(name, value)` pair; a repeated header yields
+    /// one pair per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// One field per header under its lowercase name, the values of a
+    /// repeated header joined with `", "`.
+    pub fn combined(&self) -> BTreeMap<String, String> {
+        let mut fields: BTreeMap<String, String> = BTreeMap::new();
+        for (name, value) in &self.entries {
+            fields
+                .entry(name.to_ascii_lowercase())
+                .and_modify(|joined| {
+                    joined.push_str(", ");
+                    joined.push_str(value);
+                })
+                .or_insert_with(|| value.clone());
+        }
+        fields
+    }
+
+    /// Number of `(name, value)` pairs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lowercase names of the headers set, appended or removed since the
+    /// headers were built.
+    pub fn edited(&self) -> &[String] {
+        &self.edited
+    }
+
+    fn note_edit(&mut self, name: &str) {
+        if !self.edited.iter().any(|e| e.eq_ignore_ascii_case(name)) {
+            self.edited.push(name.to_ascii_lowercase());
+        }
+    }
+}
+
+impl FromIterator<(String, String)> for RequestHeaders {
+    fn from_iter<I: IntoIterator<Item = (String, String)>>(iter: I) -> Self {
+        Self {
+            entries: iter.into_iter().collect(),
+            edited: Vec::new(),
+        }
+    }
+}
+
+impl From<Vec<(String, String)>> for RequestHeaders {
+    fn from(entries: Vec<(String, String)>) -> Self {
+        Self {
+            entries,
+            edited: Vec::new(),
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for RequestHeaders {
+    fn from(map: HashMap<String, String>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+/// Response headers plugins put on a [`PluginContext`], in order.
+///
+/// A name can carry several values (two `Set-Cookie` headers, say).
+/// [`insert`](Self::insert) sets a header: in the header-filter phase it
+/// replaces the upstream's headers of that name. [`append`](Self::append)
+/// adds a value and keeps the upstream's. Lookups ignore ASCII case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResponseHeaders {
+    entries: Vec<(String, String)>,
+    /// Lowercase names set with `insert`.
+    replaced: Vec<String>,
+}
+
+impl ResponseHeaders {
+    /// First value of a header.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of a header, in order.
+    pub fn get_all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Set a header, replacing the values it had here and, in the
+    /// header-filter phase, the upstream's. Returns the first value it had.
+    pub fn insert(&mut self, name: String, value: String) -> Option<String> {
+        let old = self.remove(&name);
+        if !self.replaces(&name) {
+            self.replaced.push(name.to_ascii_lowercase());
+        }
+        self.entries.push((name, value));
+        old
+    }
+
+    /// Add a value to a header, keeping the values it already has here
+    /// and upstream.
+    pub fn append(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((name.into(), value.into()));
+    }
+
+    /// Remove every value of a header. Returns the first one.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let mut first = None;
+        self.entries.retain_mut(|(k, v)| {
+            if !k.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            if first.is_none() {
+                first = Some(std::mem::take(v));
+            }
+            false
+        });
+        first
+    }
+
+    /// Keep only the values `keep` returns `true` for.
+    pub fn retain(&mut self, mut keep: impl FnMut(&str, &str) -> bool) {
+        self.entries.retain(|(k, v)| keep(k, v));
+    }
+
+    /// Whether the upstream's headers named `name` are replaced, i.e. the
+    /// header was set with `insert`.
+    pub fn replaces(&self, name: &str) -> bool {
+        self.replaced.iter().any(|r| r.eq_ignore_ascii_case(name))
+    }
+
+    /// Every header as a `(name, value)` pair, one pair per value.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Take every `(name, value)` pair out, leaving the headers empty.
+    pub fn drain(&mut self) -> impl Iterator<Item = (String, String)> + '_ {
+        self.replaced.clear();
+        self.entries.drain(..)
+    }
+
+    /// Number of `(name, value)` pairs.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Sets each header, like [`ResponseHeaders::insert`].
+impl Extend<(String, String)> for ResponseHeaders {
+    fn extend<I: IntoIterator<Item = (String, String)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl std::ops::Index<&str> for ResponseHeaders {
+    type Output = String;
+
+    /// First value of a header. Panics if it is absent.
+    fn index(&self, name: &str) -> &String {
+        self.entries
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v)
+            .unwrap_or_else(|| panic!("no response header `{name}`"))
+    }
+}
+
+/// A consumer's credential for one auth plugin.
+#[derive(Debug, Clone)]
+pub struct ConsumerCredential {
+    pub username: String,
+    /// The consumer's config block for the plugin (holds the secret).
+    pub config: serde_json::Value,
+}
+
+/// Consumer credentials indexed by auth plugin and credential id
+/// (e.g. `"hmac-auth"` → access key).
+///
+/// v2 design: Built on the cold path from `ConfigCache.consumers` and
+/// shared with the plugin pipeline behind an `Arc`, so lookups on the hot
+/// path never touch a DashMap.
+#[derive(Debug, Default)]
+pub struct ConsumerIndex {
+    by_plugin: HashMap<String, HashMap<String, ConsumerCredential>>,
+}
+
+impl ConsumerIndex {
+    pub fn build<'a>(consumers: impl IntoIterator<Item = &'a Consumer>) -> Self {
+        let mut by_plugin: HashMap<String, HashMap<String, ConsumerCredential>> = HashMap::new();
+        for consumer in consumers {
+            for (plugin, id) in consumer.credential_ids() {
+                by_plugin.entry(plugin.to_string()).or_default().insert(
+                    id.to_string(),
+                    ConsumerCredential {
+                        username: consumer.username.clone(),
+                        config: consumer.plugins[plugin].clone(),
+                    },
+                );
+            }
+        }
+        Self { by_plugin }
+    }
+
+    /// Look up the credential registered for `plugin` under `id`.
+    pub fn get(&self, plugin: &str, id: &str) -> Option<&ConsumerCredential> {
+        self.by_plugin.get(plugin)?.get(id)
+    }
+}
+
+/// A value a plugin reports for one route, such as its requests in
+/// flight. Published on every metrics scrape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginStat {
+    pub route: String,
+    pub stat: &'static str,
+    pub value: u64,
 }
 
 /// The Plugin trait — implemented by all plugins (Rust native).
 ///
 /// v2 design: Synchronous execution by default. Plugins run on the
 /// monoio worker thread — no async overhead for simple plugins.
-/// Complex plugins that need I/O can use monoio's async internally.
+/// Plugins that need network I/O implement `access_async` instead.
 pub trait Plugin: Send + Sync {
     /// Plugin name (must be unique).
     fn name(&self) -> &str;
@@ -93,8 +534,19 @@ pub trait Plugin: Send + Sync {
     /// Which phases this plugin participates in.
     fn phases(&self) -> &[Phase];
 
+    /// Whether the plugin answers every request itself (e.g. `mock`), so a
+    /// route using it needs no upstream.
+    fn is_terminal(&self) -> bool {
+        false
+    }
+
     /// Create a configured instance from JSON config.
     fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>>;
+
+    /// Current values of the state the plugin shares across workers.
+    fn stats(&self) -> Vec<PluginStat> {
+        Vec::new()
+    }
 }
 
 /// A configured plugin instance bound to a specific route.
@@ -117,23 +569,89 @@ pub trait PluginInstance: Send + Sync {
         PluginResult::Continue
     }
 
+    /// Whether this instance reads `ctx.request_body`. The body is only
+    /// copied into the context for routes where some plugin returns `true`.
+    fn reads_body(&self) -> bool {
+        false
+    }
+
+    /// Whether this instance implements `access_async`.
+    fn has_async_access(&self) -> bool {
+        false
+    }
+
+    /// Async access hook for plugins that need network I/O (e.g. a shared
+    /// counter store). Called after the synchronous phases; the returned
+    /// future is awaited by the connection loop, outside the worker borrow,
+    /// before the upstream request is sent. `None` skips the hook.
+    fn access_async(&self, _ctx: &mut PluginContext) -> Option<AccessFuture> {
+        None
+    }
+
+    /// Whether the pipeline should record the names of the plugins it runs
+    /// into `ctx.vars["_executed_plugins"]`. Only debugging plugins opt in.
+    fn traces_execution(&self) -> bool {
+        false
+    }
+
     /// Execute before proxying upstream.
     fn before_proxy(&self, _ctx: &mut PluginContext) -> PluginResult {
         PluginResult::Continue
     }
 
-    /// Execute header filter phase.
+    /// Whether this instance implements `header_filter`.
+    ///
+    /// Upstream response heads are only rebuilt for routes where at least
+    /// one plugin returns `true`.
+    fn filters_headers(&self) -> bool {
+        false
+    }
+
+    /// Execute header filter phase. The upstream response headers are in
+    /// `ctx.upstream_response_headers`.
     fn header_filter(&self, _ctx: &mut PluginContext) -> PluginResult {
         PluginResult::Continue
     }
 
-    /// Execute body filter phase.
-    fn body_filter(&self, _ctx: &mut PluginContext, _body: &mut Vec<u8>) -> PluginResult {
+    /// Whether this instance implements `body_filter`.
+    ///
+    /// Upstream responses are only buffered for routes where at least one
+    /// plugin returns `true`; everything else keeps streaming.
+    fn filters_body(&self) -> bool {
+        false
+    }
+
+    /// Execute body filter phase. The full upstream body is in
+    /// `ctx.response_body`.
+    fn body_filter(&self, _ctx: &mut PluginContext) -> PluginResult {
         PluginResult::Continue
     }
 
-    /// Execute log phase (fire-and-forget).
+    /// Whether this instance implements `log`.
+    ///
+    /// The request context is only kept past the upstream exchange for
+    /// routes where at least one plugin returns `true`.
+    fn logs(&self) -> bool {
+        false
+    }
+
+    /// Execute log phase (fire-and-forget). Runs after a proxied exchange,
+    /// with `ctx.response_status` set to the status sent to the client.
     fn log(&self, _ctx: &PluginContext) {}
+
+    /// Error pages this instance gives its route (`error-page`). They
+    /// replace the gateway's `errors` for the statuses they cover.
+    fn error_pages(&self) -> Option<Arc<ErrorPages>> {
+        None
+    }
+
+    /// Whether the responses this instance short-circuits with are
+    /// gateway errors, sent with the error page of their status when one
+    /// is configured (a limiter's rejection). Other plugin responses are
+    /// sent as built.
+    fn uses_error_pages(&self) -> bool {
+        false
+    }
 }
 
 #[cfg(test)]
@@ -184,6 +702,83 @@ mod tests {
         assert_eq!(ctx.get_header("x-token"), None);
     }
 
+    #[test]
+    fn repeated_request_headers_keep_every_value_and_casing() {
+        let mut ctx = make_ctx(vec![]);
+        ctx.request_headers = vec![
+            ("X-Forwarded-For".to_string(), "10.0.0.1".to_string()),
+            ("Accept".to_string(), "text/html".to_string()),
+            ("x-forwarded-for".to_string(), "10.0.0.2".to_string()),
+        ]
+        .into();
+        assert_eq!(ctx.get_header("x-forwarded-for"), Some("10.0.0.1"));
+        assert_eq!(
+            ctx.get_headers_all("X-FORWARDED-FOR").collect::<Vec<_>>(),
+            ["10.0.0.1", "10.0.0.2"]
+        );
+        assert!(ctx.request_headers.edited().is_empty());
+
+        ctx.append_header("X-Forwarded-For", "10.0.0.3");
+        assert_eq!(ctx.get_headers_all("x-forwarded-for").count(), 3);
+        assert_eq!(
+            ctx.request_headers.iter().next(),
+            Some(("X-Forwarded-For", "10.0.0.1"))
+        );
+        assert_eq!(ctx.request_headers.edited(), ["x-forwarded-for"]);
+    }
+
+    #[test]
+    fn request_header_insert_and_remove_cover_every_value() {
+        let mut headers: RequestHeaders = vec![
+            ("Accept".to_string(), "text/html".to_string()),
+            ("accept".to_string(), "application/json".to_string()),
+            ("Apikey".to_string(), "k".to_string()),
+        ]
+        .into();
+        assert_eq!(
+            headers.insert("accept".into(), "*/*".into()),
+            Some("text/html".to_string())
+        );
+        assert_eq!(headers.get_all("Accept").collect::<Vec<_>>(), ["*/*"]);
+        assert_eq!(headers.remove("apikey"), Some("k".to_string()));
+        assert!(!headers.contains_key("APIKEY"));
+        assert_eq!(headers.remove("apikey"), None);
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers.edited(), ["accept", "apikey"]);
+
+        headers.append("ACCEPT", "text/plain");
+        assert_eq!(headers.combined()["accept"], "*/*, text/plain");
+    }
+
+    #[test]
+    fn response_headers_set_replaces_and_add_keeps_values() {
+        let mut headers = ResponseHeaders::default();
+        headers.append("Set-Cookie", "session=1");
+        headers.append("set-cookie", "csrf=2");
+        assert_eq!(
+            headers.get_all("set-cookie").collect::<Vec<_>>(),
+            ["session=1", "csrf=2"]
+        );
+        assert!(!headers.replaces("set-cookie"));
+
+        assert_eq!(
+            headers.insert("x-frame-options".into(), "DENY".into()),
+            None
+        );
+        assert_eq!(
+            headers.insert("X-Frame-Options".into(), "SAMEORIGIN".into()),
+            Some("DENY".to_string())
+        );
+        assert_eq!(headers["x-frame-options"], "SAMEORIGIN");
+        assert!(headers.replaces("x-frame-options"));
+        assert_eq!(headers.len(), 3);
+
+        headers.retain(|name, _| !name.eq_ignore_ascii_case("set-cookie"));
+        assert_eq!(headers.drain().count(), 1);
+        assert!(headers.is_empty());
+        assert!(!headers.replaces("x-frame-options"));
+    }
+
     #[test]
     fn test_context_vars_mutable() {
         let mut ctx = make_ctx(vec![]);
@@ -234,10 +829,56 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_consumer_index_lookup_is_scoped_by_plugin() {
+        let consumers: Vec<Consumer> = serde_json::from_value(serde_json::json!([
+            {"username": "alice", "plugins": {"hmac-auth": {"access_key": "ak", "secret_key": "s"}}},
+            {"username": "bob", "plugins": {"key-auth": {"key": "ak"}}},
+            {"username": "carol"}
+        ]))
+        .unwrap();
+        let index = ConsumerIndex::build(&consumers);
+        let hmac = index.get("hmac-auth", "ak").unwrap();
+        assert_eq!(hmac.username, "alice");
+        assert_eq!(hmac.config["secret_key"], "s");
+        assert_eq!(index.get("key-auth", "ak").unwrap().username, "bob");
+        assert!(index.get("hmac-auth", "missing").is_none());
+        assert!(index.get("jwt-auth", "ak").is_none());
+    }
+
     #[test]
     fn test_phase_equality() {
         assert_eq!(Phase::Access, Phase::Access);
         assert_ne!(Phase::Access, Phase::Rewrite);
         assert_ne!(Phase::HeaderFilter, Phase::BodyFilter);
     }
+
+    #[test]
+    fn meta_disable_turns_a_plugin_off() {
+        assert!(is_disabled(
+            &serde_json::json!({"_meta": {"disable": true}})
+        ));
+        assert!(!is_disabled(
+            &serde_json::json!({"_meta": {"disable": false}})
+        ));
+        assert!(!is_disabled(&serde_json::json!({"disable": true})));
+        assert!(!is_disabled(&serde_json::json!({})));
+    }
+
+    #[test]
+    fn meta_priority_must_be_an_i32() {
+        use serde_json::json;
+        assert_eq!(priority_override(&json!({})), Ok(None));
+        assert_eq!(
+            priority_override(&json!({"_meta": {"priority": -5}})),
+            Ok(Some(-5))
+        );
+        assert_eq!(
+            priority_override(&json!({"_meta": {"priority": i32::MAX}})),
+            Ok(Some(i32::MAX))
+        );
+        assert!(priority_override(&json!({"_meta": {"priority": 1u64 << 31}})).is_err());
+        assert!(priority_override(&json!({"_meta": {"priority": 1.5}})).is_err());
+        assert!(priority_override(&json!({"_meta": {"priority": "10"}})).is_err());
+    }
 }
@@ -1,4 +1,4 @@
-use crate::plugin::Plugin;
+use crate::plugin::{Plugin, PluginStat};
 use std::collections::HashMap;
 use std::sync::Arc;
 
@@ -8,12 +8,15 @@ use std::sync::Arc;
 /// Worker cores receive a shared Arc<PluginRegistry>.
 pub struct PluginRegistry {
     plugins: HashMap<String, Arc<dyn Plugin>>,
+    /// Plugins loaded from dynamic libraries; see `external`.
+    external: Vec<String>,
 }
 
 impl PluginRegistry {
     pub fn new() -> Self {
         Self {
             plugins: HashMap::new(),
+            external: Vec::new(),
         }
     }
 
@@ -34,6 +37,23 @@ impl PluginRegistry {
         self.plugins.keys().map(|s| s.as_str()).collect()
     }
 
+    /// Stats of every plugin that reports any, by plugin name.
+    pub fn stats(&self) -> Vec<(&str, PluginStat)> {
+        self.plugins
+            .iter()
+            .flat_map(|(name, p)| p.stats().into_iter().map(move |s| (name.as_str(), s)))
+            .collect()
+    }
+
+    /// Names of the plugins loaded from dynamic libraries.
+    pub fn external(&self) -> &[String] {
+        &self.external
+    }
+
+    pub(crate) fn mark_external(&mut self, name: &str) {
+        self.external.push(name.to_string());
+    }
+
     /// Number of registered plugins.
     pub fn len(&self) -> usize {
         self.plugins.len()
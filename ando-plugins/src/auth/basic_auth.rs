@@ -5,7 +5,9 @@ use base64::engine::general_purpose::STANDARD as BASE64;
 /// Basic-auth plugin — APISIX-compatible.
 ///
 /// Extracts credentials from `Authorization: Basic <base64>` header and
-/// stores them in `ctx.vars` for downstream consumer validation.
+/// stores them in `ctx.vars`. With a consumer index on the context, the
+/// user must match a consumer's `basic-auth` `username` and `password`;
+/// the matched consumer becomes `ctx.consumer`.
 pub struct BasicAuthPlugin;
 
 struct BasicAuthInstance;
@@ -76,6 +78,17 @@ impl PluginInstance for BasicAuthInstance {
             }
         };
 
+        if let Some(index) = &ctx.consumers {
+            let matched = index.get("basic-auth", &username).filter(|cred| {
+                let expected = cred.config.get("password").and_then(|v| v.as_str());
+                expected.is_some_and(|p| same_secret(p.as_bytes(), password.as_bytes()))
+            });
+            let Some(cred) = matched else {
+                return deny_401(br#"{"error":"Invalid user authorization","status":401}"#);
+            };
+            ctx.consumer = Some(cred.username.clone());
+        }
+
         ctx.vars.insert(
             "_basic_auth_user".to_string(),
             serde_json::Value::String(username),
@@ -89,6 +102,11 @@ impl PluginInstance for BasicAuthInstance {
     }
 }
 
+/// Compare secrets without returning early on the first differing byte.
+fn same_secret(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 fn deny_401(body: &'static [u8]) -> PluginResult {
     PluginResult::Response {
         status: 401,
@@ -203,6 +221,46 @@ mod tests {
         assert!(matches!(result, PluginResult::Continue));
     }
 
+    // ── Consumer validation ──────────────────────────────────────
+
+    fn with_consumers(mut ctx: PluginContext) -> PluginContext {
+        use ando_core::consumer::Consumer;
+        use ando_plugin::plugin::ConsumerIndex;
+        let consumers: Vec<Consumer> = serde_json::from_value(serde_json::json!([
+            {"username": "alice", "plugins": {"basic-auth": {"username": "al", "password": "pw"}}},
+        ]))
+        .unwrap();
+        ctx.consumers = Some(std::sync::Arc::new(ConsumerIndex::build(&consumers)));
+        ctx
+    }
+
+    #[test]
+    fn matching_consumer_credentials_set_the_consumer() {
+        let header = basic_header("al", "pw");
+        let mut ctx = with_consumers(make_ctx(vec![("authorization", &header)]));
+        assert!(matches!(
+            instance().access(&mut ctx),
+            PluginResult::Continue
+        ));
+        assert_eq!(ctx.consumer.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn wrong_password_or_unknown_user_returns_401() {
+        for (user, pass) in [("al", "nope"), ("al", "pw2"), ("bob", "pw")] {
+            let header = basic_header(user, pass);
+            let mut ctx = with_consumers(make_ctx(vec![("authorization", &header)]));
+            assert!(
+                matches!(
+                    instance().access(&mut ctx),
+                    PluginResult::Response { status: 401, .. }
+                ),
+                "{user}:{pass}"
+            );
+            assert!(ctx.consumer.is_none());
+        }
+    }
+
     // ── Plugin trait ─────────────────────────────────────────────
 
     #[test]
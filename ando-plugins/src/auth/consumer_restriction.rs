@@ -0,0 +1,260 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+
+/// Consumer-restriction plugin — allow or deny authenticated consumers.
+///
+/// Runs after the auth plugins (lower priority) and checks `ctx.consumer`
+/// against a whitelist or blacklist. When both are set, only the whitelist
+/// applies. Requests without an authenticated consumer get 401.
+pub struct ConsumerRestrictionPlugin;
+
+#[derive(Debug, Deserialize)]
+struct ConsumerRestrictionConfig {
+    #[serde(default)]
+    whitelist: Vec<String>,
+    #[serde(default)]
+    blacklist: Vec<String>,
+    /// What the lists contain — "consumer_name" (default) or "service_id".
+    #[serde(default = "default_allowed_by", alias = "type")]
+    allowed_by: String,
+    /// Status for rejected consumers — default 403.
+    #[serde(default = "default_rejected_code")]
+    rejected_code: u16,
+    #[serde(default = "default_rejected_msg")]
+    rejected_msg: String,
+}
+
+fn default_allowed_by() -> String {
+    "consumer_name".to_string()
+}
+fn default_rejected_code() -> u16 {
+    403
+}
+fn default_rejected_msg() -> String {
+    "The consumer is not allowed".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AllowedBy {
+    ConsumerName,
+    /// Lists hold service ids; the consumer may only use routes of those
+    /// services.
+    ServiceId,
+}
+
+struct ConsumerRestrictionInstance {
+    allowed_by: AllowedBy,
+    whitelist: Vec<String>,
+    blacklist: Vec<String>,
+    rejected_code: u16,
+    /// Pre-rendered rejection body.
+    rejected_body: Vec<u8>,
+}
+
+impl Plugin for ConsumerRestrictionPlugin {
+    fn name(&self) -> &str {
+        "consumer-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2400 // after key-auth (2500) and the other auth plugins
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ConsumerRestrictionConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("consumer-restriction config error: {e}"))?;
+
+        let allowed_by = match cfg.allowed_by.as_str() {
+            "consumer_name" => AllowedBy::ConsumerName,
+            "service_id" => AllowedBy::ServiceId,
+            other => anyhow::bail!("consumer-restriction: unsupported allowed_by `{other}`"),
+        };
+        if cfg.whitelist.is_empty() && cfg.blacklist.is_empty() {
+            anyhow::bail!("consumer-restriction requires 'whitelist' or 'blacklist'");
+        }
+        if !(400..=599).contains(&cfg.rejected_code) {
+            anyhow::bail!("consumer-restriction: rejected_code must be a 4xx or 5xx status");
+        }
+
+        let rejected_body = serde_json::json!({
+            "error": cfg.rejected_msg,
+            "status": cfg.rejected_code,
+        })
+        .to_string()
+        .into_bytes();
+
+        Ok(Box::new(ConsumerRestrictionInstance {
+            allowed_by,
+            whitelist: cfg.whitelist,
+            blacklist: cfg.blacklist,
+            rejected_code: cfg.rejected_code,
+            rejected_body,
+        }))
+    }
+}
+
+impl ConsumerRestrictionInstance {
+    fn permits(&self, subject: Option<&str>) -> bool {
+        if !self.whitelist.is_empty() {
+            return subject.is_some_and(|s| self.whitelist.iter().any(|w| w == s));
+        }
+        !subject.is_some_and(|s| self.blacklist.iter().any(|b| b == s))
+    }
+}
+
+impl PluginInstance for ConsumerRestrictionInstance {
+    fn name(&self) -> &str {
+        "consumer-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2400
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let Some(consumer) = ctx.consumer.as_deref() else {
+            return PluginResult::Response {
+                status: 401,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: Some(br#"{"error":"Missing authentication","status":401}"#.to_vec()),
+            };
+        };
+
+        let subject = match self.allowed_by {
+            AllowedBy::ConsumerName => Some(consumer),
+            AllowedBy::ServiceId => ctx.service_id.as_deref(),
+        };
+        if self.permits(subject) {
+            return PluginResult::Continue;
+        }
+
+        PluginResult::Response {
+            status: self.rejected_code,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(self.rejected_body.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn make_ctx(consumer: Option<&str>) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        );
+        ctx.consumer = consumer.map(str::to_string);
+        ctx
+    }
+
+    fn instance(config: serde_json::Value) -> Box<dyn PluginInstance> {
+        ConsumerRestrictionPlugin.configure(&config).unwrap()
+    }
+
+    fn status(inst: &dyn PluginInstance, ctx: &mut PluginContext) -> u16 {
+        match inst.access(ctx) {
+            PluginResult::Continue => 200,
+            PluginResult::Response { status, .. } => status,
+        }
+    }
+
+    #[test]
+    fn whitelist_only_admits_listed_consumers() {
+        let inst = instance(serde_json::json!({ "whitelist": ["alice", "bob"] }));
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("alice"))), 200);
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("bob"))), 200);
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("mallory"))), 403);
+    }
+
+    #[test]
+    fn blacklist_only_rejects_listed_consumers() {
+        let inst = instance(serde_json::json!({ "blacklist": ["mallory"] }));
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("alice"))), 200);
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("mallory"))), 403);
+    }
+
+    #[test]
+    fn whitelist_wins_when_both_are_set() {
+        let inst = instance(serde_json::json!({
+            "whitelist": ["alice"],
+            "blacklist": ["alice", "bob"],
+        }));
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("alice"))), 200);
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("bob"))), 403);
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("carol"))), 403);
+    }
+
+    #[test]
+    fn anonymous_request_returns_401() {
+        let inst = instance(serde_json::json!({ "blacklist": ["mallory"] }));
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(None)), 401);
+        let inst = instance(serde_json::json!({ "whitelist": ["alice"] }));
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(None)), 401);
+    }
+
+    #[test]
+    fn rejection_uses_configured_code_and_message() {
+        let inst = instance(serde_json::json!({
+            "whitelist": ["alice"],
+            "rejected_code": 404,
+            "rejected_msg": "Not your tenant",
+        }));
+        match inst.access(&mut make_ctx(Some("bob"))) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 404);
+                let body: serde_json::Value = serde_json::from_slice(&body.unwrap()).unwrap();
+                assert_eq!(body["error"], "Not your tenant");
+                assert_eq!(body["status"], 404);
+            }
+            PluginResult::Continue => panic!("expected rejection"),
+        }
+    }
+
+    #[test]
+    fn service_id_mode_checks_the_route_service() {
+        let inst = instance(serde_json::json!({
+            "allowed_by": "service_id",
+            "whitelist": ["svc-a"],
+        }));
+        let mut ctx = make_ctx(Some("alice"));
+        ctx.service_id = Some("svc-a".into());
+        assert_eq!(status(inst.as_ref(), &mut ctx), 200);
+        let mut ctx = make_ctx(Some("alice"));
+        ctx.service_id = Some("svc-b".into());
+        assert_eq!(status(inst.as_ref(), &mut ctx), 403);
+        // Routes without a service are not in any whitelist.
+        assert_eq!(status(inst.as_ref(), &mut make_ctx(Some("alice"))), 403);
+    }
+
+    #[test]
+    fn plugin_name_priority_phases() {
+        assert_eq!(ConsumerRestrictionPlugin.name(), "consumer-restriction");
+        assert!(ConsumerRestrictionPlugin.priority() < 2500);
+        assert_eq!(ConsumerRestrictionPlugin.phases(), &[Phase::Access]);
+    }
+
+    #[test]
+    fn configure_rejects_invalid_settings() {
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "whitelist": [], "blacklist": [] }),
+            serde_json::json!({ "whitelist": ["a"], "allowed_by": "route_id" }),
+            serde_json::json!({ "whitelist": ["a"], "rejected_code": 200 }),
+        ] {
+            assert!(
+                ConsumerRestrictionPlugin.configure(&config).is_err(),
+                "{config}"
+            );
+        }
+    }
+}
@@ -0,0 +1,508 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// HMAC-auth plugin — APISIX-compatible.
+///
+/// Clients sign each request with a secret shared with their consumer:
+///
+/// ```text
+/// Authorization: hmac access_key="ak", algorithm="hmac-sha256",
+///                timestamp="1700000000", headers="host x-date", signature="<base64>"
+/// ```
+///
+/// or with the equivalent `X-HMAC-ACCESS-KEY`, `X-HMAC-SIGNATURE`,
+/// `X-HMAC-TIMESTAMP`, `X-HMAC-ALGORITHM` and `X-HMAC-SIGNED-HEADERS`
+/// (`;`-separated) headers. The signature is computed over
+///
+/// ```text
+/// METHOD \n URI \n TIMESTAMP \n name1:value1 \n ... nameN:valueN
+/// ```
+///
+/// where the `name:value` lines follow the signed header list in order.
+/// Consumers carry `{"access_key": "...", "secret_key": "..."}`.
+pub struct HmacAuthPlugin;
+
+#[derive(Debug, Deserialize)]
+struct HmacAuthConfig {
+    /// Maximum allowed difference between the request timestamp and the
+    /// gateway clock, in seconds. 0 disables the check.
+    #[serde(default = "default_clock_skew")]
+    clock_skew: u64,
+    /// Algorithms clients may use.
+    #[serde(default = "default_algorithms")]
+    allowed_algorithms: Vec<String>,
+    /// Headers every request must include in its signature.
+    #[serde(default)]
+    signed_headers: Vec<String>,
+}
+
+fn default_clock_skew() -> u64 {
+    300
+}
+fn default_algorithms() -> Vec<String> {
+    vec![
+        "hmac-sha1".to_string(),
+        "hmac-sha256".to_string(),
+        "hmac-sha512".to_string(),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Algorithm {
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+impl Algorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "hmac-sha1" => Some(Self::Sha1),
+            "hmac-sha256" => Some(Self::Sha256),
+            "hmac-sha512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// Constant-time check of `signature` over `message`.
+    fn verify(self, secret: &[u8], message: &[u8], signature: &[u8]) -> bool {
+        fn check<M: Mac + hmac::digest::KeyInit>(secret: &[u8], msg: &[u8], sig: &[u8]) -> bool {
+            match <M as hmac::digest::KeyInit>::new_from_slice(secret) {
+                Ok(mut mac) => {
+                    mac.update(msg);
+                    mac.verify_slice(sig).is_ok()
+                }
+                Err(_) => false,
+            }
+        }
+        match self {
+            Self::Sha1 => check::<Hmac<sha1::Sha1>>(secret, message, signature),
+            Self::Sha256 => check::<Hmac<sha2::Sha256>>(secret, message, signature),
+            Self::Sha512 => check::<Hmac<sha2::Sha512>>(secret, message, signature),
+        }
+    }
+}
+
+struct HmacAuthInstance {
+    clock_skew: u64,
+    allowed: Vec<Algorithm>,
+    signed_headers: Vec<String>,
+}
+
+/// Signature parameters extracted from the request.
+#[derive(Debug, Default, PartialEq)]
+struct SignedRequest {
+    access_key: String,
+    algorithm: String,
+    timestamp: String,
+    headers: Vec<String>,
+    signature: String,
+}
+
+impl Plugin for HmacAuthPlugin {
+    fn name(&self) -> &str {
+        "hmac-auth"
+    }
+
+    fn priority(&self) -> i32 {
+        2530
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: HmacAuthConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("hmac-auth config error: {e}"))?;
+        let allowed = cfg
+            .allowed_algorithms
+            .iter()
+            .map(|a| {
+                Algorithm::parse(a).ok_or_else(|| anyhow::anyhow!("unknown HMAC algorithm: {a}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Box::new(HmacAuthInstance {
+            clock_skew: cfg.clock_skew,
+            allowed,
+            signed_headers: cfg
+                .signed_headers
+                .iter()
+                .map(|h| h.to_ascii_lowercase())
+                .collect(),
+        }))
+    }
+}
+
+impl PluginInstance for HmacAuthInstance {
+    fn name(&self) -> &str {
+        "hmac-auth"
+    }
+
+    fn priority(&self) -> i32 {
+        2530
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        match self.authenticate(ctx, now) {
+            Ok((username, access_key)) => {
+                ctx.consumer = Some(username);
+                ctx.vars.insert(
+                    "hmac_access_key".to_string(),
+                    serde_json::Value::String(access_key),
+                );
+                PluginResult::Continue
+            }
+            Err(msg) => deny_401(msg),
+        }
+    }
+}
+
+impl HmacAuthInstance {
+    /// Verify the request signature. Returns (consumer username, access key).
+    fn authenticate(
+        &self,
+        ctx: &PluginContext,
+        now: u64,
+    ) -> Result<(String, String), &'static str> {
+        let req = parse_signature(ctx).ok_or("Missing or malformed HMAC signature")?;
+
+        let algorithm = Algorithm::parse(&req.algorithm)
+            .filter(|a| self.allowed.contains(a))
+            .ok_or("Unsupported HMAC algorithm")?;
+
+        let timestamp: u64 = req
+            .timestamp
+            .parse()
+            .map_err(|_| "Invalid HMAC timestamp")?;
+        if self.clock_skew > 0 && now.abs_diff(timestamp) > self.clock_skew {
+            return Err("HMAC timestamp outside the allowed clock skew");
+        }
+
+        if let Some(missing) = self
+            .signed_headers
+            .iter()
+            .find(|h| !req.headers.contains(h))
+        {
+            tracing::debug!(header = %missing, "hmac-auth: required header not signed");
+            return Err("Required header not signed");
+        }
+
+        let credential = ctx
+            .consumers
+            .as_ref()
+            .and_then(|c| c.get("hmac-auth", &req.access_key))
+            .ok_or("Invalid access key")?;
+        let secret = credential
+            .config
+            .get("secret_key")
+            .and_then(|v| v.as_str())
+            .ok_or("Invalid access key")?;
+
+        let signature = BASE64
+            .decode(req.signature.trim())
+            .map_err(|_| "Invalid signature encoding")?;
+        let message = signing_string(ctx, &req.timestamp, &req.headers)
+            .ok_or("Signed header missing from request")?;
+        if !algorithm.verify(secret.as_bytes(), message.as_bytes(), &signature) {
+            return Err("Invalid signature");
+        }
+
+        Ok((credential.username.clone(), req.access_key))
+    }
+}
+
+/// Extract signature parameters from `Authorization: hmac ...` or the
+/// `X-HMAC-*` header set.
+fn parse_signature(ctx: &PluginContext) -> Option<SignedRequest> {
+    if let Some(auth) = ctx.get_header("authorization")
+        && let Some(params) = auth
+            .strip_prefix("hmac ")
+            .or_else(|| auth.strip_prefix("HMAC "))
+    {
+        let mut req = SignedRequest {
+            algorithm: "hmac-sha256".to_string(),
+            ..Default::default()
+        };
+        for pair in params.split(',') {
+            let (k, v) = pair.trim().split_once('=')?;
+            let v = v.trim().trim_matches('"').to_string();
+            match k.trim() {
+                "access_key" => req.access_key = v,
+                "algorithm" => req.algorithm = v,
+                "timestamp" => req.timestamp = v,
+                "headers" => req.headers = split_headers(&v, ' '),
+                "signature" => req.signature = v,
+                _ => {}
+            }
+        }
+        return (!req.access_key.is_empty()
+            && !req.signature.is_empty()
+            && !req.timestamp.is_empty())
+        .then_some(req);
+    }
+
+    Some(SignedRequest {
+        access_key: ctx.get_header("x-hmac-access-key")?.to_string(),
+        signature: ctx.get_header("x-hmac-signature")?.to_string(),
+        timestamp: ctx.get_header("x-hmac-timestamp")?.to_string(),
+        algorithm: ctx
+            .get_header("x-hmac-algorithm")
+            .unwrap_or("hmac-sha256")
+            .to_string(),
+        headers: ctx
+            .get_header("x-hmac-signed-headers")
+            .map(|h| split_headers(h, ';'))
+            .unwrap_or_default(),
+    })
+}
+
+fn split_headers(list: &str, sep: char) -> Vec<String> {
+    list.split(sep)
+        .map(|h| h.trim().to_ascii_lowercase())
+        .filter(|h| !h.is_empty())
+        .collect()
+}
+
+/// The canonical string clients sign. `None` if a signed header is absent.
+fn signing_string(ctx: &PluginContext, timestamp: &str, headers: &[String]) -> Option<String> {
+    let mut s = format!("{}\n{}\n{}", ctx.method, ctx.uri, timestamp);
+    for name in headers {
+        let value = ctx.get_header(name)?;
+        s.push('\n');
+        s.push_str(name);
+        s.push(':');
+        s.push_str(value);
+    }
+    Some(s)
+}
+
+fn deny_401(msg: &str) -> PluginResult {
+    PluginResult::Response {
+        status: 401,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: Some(
+            serde_json::json!({ "error": msg, "status": 401 })
+                .to_string()
+                .into_bytes(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::consumer::Consumer;
+    use ando_plugin::plugin::ConsumerIndex;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn instance(config: serde_json::Value) -> HmacAuthInstance {
+        let cfg: HmacAuthConfig = serde_json::from_value(config).unwrap();
+        HmacAuthInstance {
+            clock_skew: cfg.clock_skew,
+            allowed: cfg
+                .allowed_algorithms
+                .iter()
+                .map(|a| Algorithm::parse(a).unwrap())
+                .collect(),
+            signed_headers: cfg.signed_headers,
+        }
+    }
+
+    fn make_ctx(headers: Vec<(&str, String)>) -> PluginContext {
+        let map: HashMap<String, String> = headers
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v))
+            .collect();
+        let consumers: Vec<Consumer> = serde_json::from_value(serde_json::json!([
+            {"username": "alice", "plugins": {"hmac-auth": {"access_key": "ak", "secret_key": "s3cret"}}}
+        ]))
+        .unwrap();
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "127.0.0.1".into(),
+            "GET".into(),
+            "/orders?id=7".into(),
+            map,
+        );
+        ctx.consumers = Some(Arc::new(ConsumerIndex::build(&consumers)));
+        ctx
+    }
+
+    fn sign<M: Mac + hmac::digest::KeyInit>(message: &str) -> String {
+        let mut mac = <M as hmac::digest::KeyInit>::new_from_slice(b"s3cret").unwrap();
+        mac.update(message.as_bytes());
+        BASE64.encode(mac.finalize().into_bytes())
+    }
+
+    /// `X-HMAC-*` headers for a request signed at `ts` over `x-date`.
+    fn signed_headers(ts: u64, signature: String) -> Vec<(&'static str, String)> {
+        vec![
+            ("x-date", "today".to_string()),
+            ("x-hmac-access-key", "ak".to_string()),
+            ("x-hmac-timestamp", ts.to_string()),
+            ("x-hmac-signed-headers", "x-date".to_string()),
+            ("x-hmac-signature", signature),
+        ]
+    }
+
+    fn message(ts: u64) -> String {
+        format!("GET\n/orders?id=7\n{ts}\nx-date:today")
+    }
+
+    #[test]
+    fn valid_signature_sets_consumer() {
+        let inst = instance(serde_json::json!({}));
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(NOW));
+        let ctx = make_ctx(signed_headers(NOW, sig));
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Ok(("alice".to_string(), "ak".to_string()))
+        );
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(now));
+        let mut ctx = make_ctx(signed_headers(now, sig));
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.consumer.as_deref(), Some("alice"));
+        assert_eq!(ctx.vars["hmac_access_key"], "ak");
+    }
+
+    #[test]
+    fn authorization_header_form_with_sha1_and_sha512() {
+        let inst = instance(serde_json::json!({}));
+        for (alg, sig) in [
+            ("hmac-sha1", sign::<Hmac<sha1::Sha1>>(&message(NOW))),
+            ("hmac-sha512", sign::<Hmac<sha2::Sha512>>(&message(NOW))),
+        ] {
+            let auth = format!(
+                r#"hmac access_key="ak", algorithm="{alg}", timestamp="{NOW}", headers="x-date", signature="{sig}""#
+            );
+            let ctx = make_ctx(vec![
+                ("x-date", "today".to_string()),
+                ("authorization", auth),
+            ]);
+            assert!(inst.authenticate(&ctx, NOW).is_ok(), "{alg}");
+        }
+    }
+
+    #[test]
+    fn disallowed_algorithm_rejected() {
+        let inst = instance(serde_json::json!({ "allowed_algorithms": ["hmac-sha512"] }));
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(NOW));
+        let ctx = make_ctx(signed_headers(NOW, sig));
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Err("Unsupported HMAC algorithm")
+        );
+    }
+
+    #[test]
+    fn replay_window_enforced_both_directions() {
+        let inst = instance(serde_json::json!({ "clock_skew": 60 }));
+        let ts = NOW - 60;
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(ts));
+        let ctx = make_ctx(signed_headers(ts, sig));
+        assert!(inst.authenticate(&ctx, NOW).is_ok(), "edge of window");
+        assert_eq!(
+            inst.authenticate(&ctx, NOW + 1),
+            Err("HMAC timestamp outside the allowed clock skew")
+        );
+        assert!(inst.authenticate(&ctx, ts - 61).is_err(), "from the future");
+
+        let unbounded = instance(serde_json::json!({ "clock_skew": 0 }));
+        assert!(unbounded.authenticate(&ctx, NOW + 86_400).is_ok());
+    }
+
+    #[test]
+    fn tampered_request_rejected() {
+        let inst = instance(serde_json::json!({}));
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(NOW));
+        let mut headers = signed_headers(NOW, sig);
+        headers[0].1 = "tomorrow".to_string();
+        let ctx = make_ctx(headers);
+        assert_eq!(inst.authenticate(&ctx, NOW), Err("Invalid signature"));
+    }
+
+    #[test]
+    fn missing_and_garbled_signature_headers_rejected() {
+        let inst = instance(serde_json::json!({}));
+
+        let ctx = make_ctx(vec![]);
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Err("Missing or malformed HMAC signature")
+        );
+
+        let mut headers = signed_headers(NOW, "x".to_string());
+        headers.retain(|(k, _)| *k != "x-hmac-timestamp");
+        assert!(inst.authenticate(&make_ctx(headers), NOW).is_err());
+
+        let ctx = make_ctx(signed_headers(NOW, "!!not-base64!!".to_string()));
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Err("Invalid signature encoding")
+        );
+
+        let ctx = make_ctx(vec![("authorization", "hmac garbage".to_string())]);
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Err("Missing or malformed HMAC signature")
+        );
+
+        let mut headers = signed_headers(NOW, "x".to_string());
+        headers[2].1 = "yesterday".to_string();
+        assert_eq!(
+            inst.authenticate(&make_ctx(headers), NOW),
+            Err("Invalid HMAC timestamp")
+        );
+    }
+
+    #[test]
+    fn unknown_access_key_rejected() {
+        let inst = instance(serde_json::json!({}));
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(NOW));
+        let mut headers = signed_headers(NOW, sig);
+        headers[1].1 = "nobody".to_string();
+        assert_eq!(
+            inst.authenticate(&make_ctx(headers), NOW),
+            Err("Invalid access key")
+        );
+    }
+
+    #[test]
+    fn required_signed_header_enforced() {
+        let inst = instance(serde_json::json!({ "signed_headers": ["x-date", "host"] }));
+        let sig = sign::<Hmac<sha2::Sha256>>(&message(NOW));
+        let ctx = make_ctx(signed_headers(NOW, sig));
+        assert_eq!(
+            inst.authenticate(&ctx, NOW),
+            Err("Required header not signed")
+        );
+    }
+
+    #[test]
+    fn configure_rejects_unknown_algorithm() {
+        let plugin = HmacAuthPlugin;
+        assert!(plugin.configure(&serde_json::json!({})).is_ok());
+        assert!(
+            plugin
+                .configure(&serde_json::json!({ "allowed_algorithms": ["md5"] }))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,243 @@
+use arc_swap::ArcSwap;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::jwk::JwkSet;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// How often the refresher wakes up to look for due or stale key sets.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Minimum gap between fetches triggered by unknown `kid`s, so a flood of
+/// forged tokens cannot turn into a flood of JWKS requests.
+const MIN_STALE_REFETCH: Duration = Duration::from_secs(5);
+
+/// Keys published at a JWKS endpoint, indexed by `kid`.
+///
+/// v2 design: The access phase only ever reads the current snapshot; it
+/// never performs I/O. Fetches happen on the `ando-jwks` thread, which
+/// swaps in a new map on success and keeps the old one on failure.
+pub struct JwksSet {
+    uri: String,
+    refresh: Duration,
+    keys: ArcSwap<HashMap<String, DecodingKey>>,
+    /// Set on a `kid` miss so the refresher fetches ahead of schedule.
+    stale: AtomicBool,
+}
+
+impl JwksSet {
+    pub fn new(uri: impl Into<String>, refresh: Duration) -> Self {
+        Self {
+            uri: uri.into(),
+            refresh,
+            keys: ArcSwap::from_pointee(HashMap::new()),
+            stale: AtomicBool::new(false),
+        }
+    }
+
+    pub fn uri(&self) -> &str {
+        &self.uri
+    }
+
+    /// Run `f` with the key for `kid`. A token without `kid` is accepted
+    /// only when the set holds a single key.
+    pub fn with_key<R>(&self, kid: Option<&str>, f: impl FnOnce(&DecodingKey) -> R) -> Option<R> {
+        let keys = self.keys.load();
+        let key = match kid {
+            Some(kid) => keys.get(kid),
+            None if keys.len() == 1 => keys.values().next(),
+            None => None,
+        };
+        match key {
+            Some(key) => Some(f(key)),
+            None => {
+                self.stale.store(true, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    /// Whether a lookup missed since the last successful load.
+    pub fn is_stale(&self) -> bool {
+        self.stale.load(Ordering::Relaxed)
+    }
+
+    /// Replace the key set from a JWKS document. Keys without a `kid` are
+    /// stored under the empty string; unsupported keys are skipped.
+    pub fn load_json(&self, body: &str) -> Result<usize, String> {
+        let set: JwkSet = serde_json::from_str(body).map_err(|e| format!("invalid JWKS: {e}"))?;
+        let mut keys = HashMap::with_capacity(set.keys.len());
+        for jwk in &set.keys {
+            let kid = jwk.common.key_id.clone().unwrap_or_default();
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => {
+                    keys.insert(kid, key);
+                }
+                Err(e) => debug!(uri = %self.uri, kid, error = %e, "skipping JWK"),
+            }
+        }
+        let count = keys.len();
+        self.keys.store(Arc::new(keys));
+        self.stale.store(false, Ordering::Relaxed);
+        Ok(count)
+    }
+}
+
+/// Key sets by URI, shared by every jwt-auth instance in the process.
+fn registry() -> &'static Mutex<HashMap<String, Arc<JwksSet>>> {
+    static SETS: OnceLock<Mutex<HashMap<String, Arc<JwksSet>>>> = OnceLock::new();
+    SETS.get_or_init(Default::default)
+}
+
+/// Get the shared key set for `uri`, registering it with the background
+/// refresher on first use. The first registration spawns the refresher.
+pub fn key_set(uri: &str, refresh: Duration) -> Arc<JwksSet> {
+    let mut sets = registry().lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(set) = sets.get(uri) {
+        return set.clone();
+    }
+    if sets.is_empty() {
+        spawn_refresher();
+    }
+    let set = Arc::new(JwksSet::new(uri, refresh));
+    sets.insert(uri.to_string(), set.clone());
+    set
+}
+
+fn spawn_refresher() {
+    let spawned = std::thread::Builder::new()
+        .name("ando-jwks".to_string())
+        .spawn(|| {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .expect("Failed to build tokio runtime for JWKS refresher");
+            rt.block_on(refresh_loop());
+        });
+    if let Err(e) = spawned {
+        warn!(error = %e, "failed to spawn JWKS refresher");
+    }
+}
+
+async fn refresh_loop() {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .unwrap_or_default();
+    // uri → (last attempt, last success)
+    let mut fetched: HashMap<String, (Instant, Option<Instant>)> = HashMap::new();
+    loop {
+        let sets: Vec<Arc<JwksSet>> = registry()
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        let now = Instant::now();
+        for set in sets {
+            let due = match fetched.get(set.uri()) {
+                None => true,
+                Some((attempt, success)) => {
+                    let scheduled = success.is_none_or(|s| now >= s + set.refresh)
+                        && now >= *attempt + MIN_STALE_REFETCH;
+                    let stale = set.is_stale() && now >= *attempt + MIN_STALE_REFETCH;
+                    scheduled || stale
+                }
+            };
+            if !due {
+                continue;
+            }
+            let entry = fetched.entry(set.uri().to_string()).or_insert((now, None));
+            entry.0 = now;
+            match fetch(&client, set.uri()).await {
+                Ok(body) => match set.load_json(&body) {
+                    Ok(count) => {
+                        debug!(uri = set.uri(), keys = count, "JWKS refreshed");
+                        entry.1 = Some(now);
+                    }
+                    Err(e) => warn!(uri = set.uri(), error = %e, "JWKS refresh failed"),
+                },
+                Err(e) => warn!(uri = set.uri(), error = %e, "JWKS refresh failed"),
+            }
+        }
+        tokio::time::sleep(TICK).await;
+    }
+}
+
+async fn fetch(client: &reqwest::Client, uri: &str) -> Result<String, String> {
+    let resp = client.get(uri).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    resp.text().await.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    const JWKS: &str = r#"{"keys":[
+        {"kty":"oct","kid":"k1","alg":"HS256","k":"c2VjcmV0LW9uZQ"},
+        {"kty":"oct","kid":"k2","alg":"HS256","k":"c2VjcmV0LXR3bw"}
+    ]}"#;
+
+    #[test]
+    fn load_json_indexes_keys_by_kid() {
+        let set = JwksSet::new("http://unused", Duration::from_secs(60));
+        assert_eq!(set.load_json(JWKS), Ok(2));
+        assert!(set.with_key(Some("k1"), |_| ()).is_some());
+        assert!(set.with_key(Some("k2"), |_| ()).is_some());
+        // Ambiguous without a kid.
+        assert!(set.with_key(None, |_| ()).is_none());
+    }
+
+    #[test]
+    fn miss_marks_stale_until_next_load() {
+        let set = JwksSet::new("http://unused", Duration::from_secs(60));
+        set.load_json(JWKS).unwrap();
+        assert!(!set.is_stale());
+        assert!(set.with_key(Some("k3"), |_| ()).is_none());
+        assert!(set.is_stale());
+        set.load_json(JWKS).unwrap();
+        assert!(!set.is_stale());
+    }
+
+    #[test]
+    fn invalid_document_keeps_previous_keys() {
+        let set = JwksSet::new("http://unused", Duration::from_secs(60));
+        set.load_json(JWKS).unwrap();
+        assert!(set.load_json("not json").is_err());
+        assert!(set.with_key(Some("k1"), |_| ()).is_some());
+    }
+
+    #[test]
+    fn key_set_is_fetched_in_background() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}/jwks.json", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let mut buf = [0u8; 1024];
+                let _ = stream.read(&mut buf);
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    JWKS.len(),
+                    JWKS
+                );
+                let _ = stream.write_all(resp.as_bytes());
+            }
+        });
+
+        let set = key_set(&uri, Duration::from_secs(300));
+        assert!(Arc::ptr_eq(&set, &key_set(&uri, Duration::from_secs(300))));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while set.with_key(Some("k1"), |_| ()).is_none() {
+            assert!(Instant::now() < deadline, "JWKS was not fetched");
+            std::thread::sleep(Duration::from_millis(50));
+        }
+    }
+}
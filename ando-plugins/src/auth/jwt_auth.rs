@@ -1,6 +1,10 @@
+use super::jwks::{self, JwksSet};
 use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
-use jsonwebtoken::{Algorithm, DecodingKey, Validation, decode};
+use jsonwebtoken::{Algorithm, DecodingKey, TokenData, Validation, decode, decode_header};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub struct JwtAuthPlugin;
 
@@ -9,15 +13,30 @@ struct JwtAuthConfig {
     /// Secret for HMAC algorithms (HS256 / HS384 / HS512).
     #[serde(default)]
     secret: Option<String>,
-    /// PEM/DER public key for RS/EC algorithms.
+    /// PEM public key for RS / PS / ES / EdDSA algorithms.
     #[serde(default)]
     public_key: Option<String>,
+    /// JWKS endpoint; when set, keys are looked up by the token's `kid`.
+    #[serde(default)]
+    jwks_uri: Option<String>,
+    /// Seconds between JWKS refreshes — default 300.
+    #[serde(default = "default_jwks_refresh")]
+    jwks_refresh_interval: u64,
     /// Algorithm — default "HS256".
     #[serde(default = "default_algorithm")]
     algorithm: String,
     /// Header that carries the token — default "authorization".
     #[serde(default = "default_header")]
     header: String,
+    /// Required `iss` claim.
+    #[serde(default, alias = "iss")]
+    issuer: Option<String>,
+    /// Required `aud` claim (any of the listed values).
+    #[serde(default, alias = "aud")]
+    audience: Option<Vec<String>>,
+    /// Claim name → upstream request header, e.g. `{"sub": "X-User-Id"}`.
+    #[serde(default)]
+    claims_to_headers: HashMap<String, String>,
 }
 
 fn default_algorithm() -> String {
@@ -26,6 +45,9 @@ fn default_algorithm() -> String {
 fn default_header() -> String {
     "authorization".to_string()
 }
+fn default_jwks_refresh() -> u64 {
+    300
+}
 
 /// Minimal JWT claims we extract.
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -33,13 +55,39 @@ struct Claims {
     sub: Option<String>,
     exp: Option<u64>,
     #[serde(flatten)]
-    extra: std::collections::HashMap<String, serde_json::Value>,
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// Where verification keys come from.
+enum KeySource {
+    Static(DecodingKey),
+    /// Keys from a JWKS endpoint, refreshed in the background.
+    Jwks(Arc<JwksSet>),
 }
 
 struct JwtAuthInstance {
-    decoding_key: DecodingKey,
+    key: KeySource,
     validation: Validation,
     header: String,
+    /// (claim, lowercase header name)
+    claims_to_headers: Vec<(String, String)>,
+}
+
+impl JwtAuthInstance {
+    fn verify(&self, token: &str) -> Result<TokenData<Claims>, String> {
+        match &self.key {
+            KeySource::Static(key) => {
+                decode::<Claims>(token, key, &self.validation).map_err(|e| e.to_string())
+            }
+            KeySource::Jwks(set) => {
+                let header = decode_header(token).map_err(|e| e.to_string())?;
+                set.with_key(header.kid.as_deref(), |key| {
+                    decode::<Claims>(token, key, &self.validation).map_err(|e| e.to_string())
+                })
+                .unwrap_or_else(|| Err("unknown signing key".to_string()))
+            }
+        }
+    }
 }
 
 impl Plugin for JwtAuthPlugin {
@@ -64,29 +112,52 @@ impl Plugin for JwtAuthPlugin {
             .parse()
             .map_err(|_| anyhow::anyhow!("unknown JWT algorithm: {}", cfg.algorithm))?;
 
-        let decoding_key = match algorithm {
-            Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => {
+        let key = match (&cfg.jwks_uri, algorithm) {
+            (Some(uri), _) => KeySource::Jwks(jwks::key_set(
+                uri,
+                Duration::from_secs(cfg.jwks_refresh_interval.max(1)),
+            )),
+            (None, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) => {
                 let secret = cfg.secret.as_deref().ok_or_else(|| {
                     anyhow::anyhow!("jwt-auth requires 'secret' for HMAC algorithms")
                 })?;
-                DecodingKey::from_secret(secret.as_bytes())
+                KeySource::Static(DecodingKey::from_secret(secret.as_bytes()))
             }
-            _ => {
-                let key = cfg.public_key.as_deref().ok_or_else(|| {
-                    anyhow::anyhow!("jwt-auth requires 'public_key' for asymmetric algorithms")
+            (None, _) => {
+                let pem = cfg.public_key.as_deref().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "jwt-auth requires 'public_key' or 'jwks_uri' for asymmetric algorithms"
+                    )
                 })?;
-                DecodingKey::from_rsa_pem(key.as_bytes())
-                    .map_err(|e| anyhow::anyhow!("invalid public key: {e}"))?
+                let key = match algorithm {
+                    Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(pem.as_bytes()),
+                    Algorithm::EdDSA => DecodingKey::from_ed_pem(pem.as_bytes()),
+                    _ => DecodingKey::from_rsa_pem(pem.as_bytes()),
+                };
+                KeySource::Static(key.map_err(|e| anyhow::anyhow!("invalid public key: {e}"))?)
             }
         };
 
         let mut validation = Validation::new(algorithm);
         validation.validate_exp = true;
+        if let Some(iss) = &cfg.issuer {
+            validation.set_issuer(&[iss]);
+            validation.required_spec_claims.insert("iss".to_string());
+        }
+        if let Some(aud) = &cfg.audience {
+            validation.set_audience(aud);
+            validation.required_spec_claims.insert("aud".to_string());
+        }
 
         Ok(Box::new(JwtAuthInstance {
-            decoding_key,
+            key,
             validation,
             header: cfg.header,
+            claims_to_headers: cfg
+                .claims_to_headers
+                .into_iter()
+                .map(|(claim, header)| (claim, header.to_ascii_lowercase()))
+                .collect(),
         }))
     }
 }
@@ -101,8 +172,8 @@ impl PluginInstance for JwtAuthInstance {
     }
 
     fn access(&self, ctx: &mut PluginContext) -> PluginResult {
-        let raw_header = match ctx.request_headers.get(&self.header) {
-            Some(h) => h.clone(),
+        let raw_header = match ctx.get_header(&self.header) {
+            Some(h) => h.to_string(),
             None => {
                 return deny_401(br#"{"error":"Missing Authorization header","status":401}"#);
             }
@@ -114,12 +185,12 @@ impl PluginInstance for JwtAuthInstance {
             raw_header.trim()
         };
 
-        let data = match decode::<Claims>(token, &self.decoding_key, &self.validation) {
+        let data = match self.verify(token) {
             Ok(d) => d,
             Err(e) => {
                 let msg = format!(
                     "{{\"error\":\"Invalid token: {}\",\"status\":401}}",
-                    e.to_string().replace('"', "'")
+                    e.replace('"', "'")
                 );
                 return PluginResult::Response {
                     status: 401,
@@ -132,6 +203,25 @@ impl PluginInstance for JwtAuthInstance {
             }
         };
 
+        // A mapped header only ever carries the token's claim: a client
+        // value goes even when the claim is missing.
+        for (_, header) in &self.claims_to_headers {
+            ctx.request_headers.remove(header);
+        }
+        for (claim, header) in &self.claims_to_headers {
+            let value = match claim.as_str() {
+                "sub" => data.claims.sub.clone(),
+                "exp" => data.claims.exp.map(|e| e.to_string()),
+                _ => data.claims.extra.get(claim).map(|v| match v {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                }),
+            };
+            if let Some(value) = value {
+                ctx.upstream_headers.insert(header.clone(), value);
+            }
+        }
+
         if let Some(sub) = data.claims.sub {
             ctx.consumer = Some(sub.clone());
             ctx.vars
@@ -157,10 +247,20 @@ fn deny_401(body: &'static [u8]) -> PluginResult {
 mod tests {
     use super::*;
     use jsonwebtoken::{EncodingKey, Header, encode};
-    use std::collections::HashMap;
 
     const SECRET: &str = "test-secret-key";
 
+    impl JwtAuthInstance {
+        fn new(key: DecodingKey, validation: Validation) -> Self {
+            Self {
+                key: KeySource::Static(key),
+                validation,
+                header: default_header(),
+                claims_to_headers: Vec::new(),
+            }
+        }
+    }
+
     fn make_ctx(auth: Option<&str>) -> PluginContext {
         let mut headers = HashMap::new();
         if let Some(v) = auth {
@@ -176,11 +276,10 @@ mod tests {
     }
 
     fn make_instance() -> JwtAuthInstance {
-        JwtAuthInstance {
-            decoding_key: DecodingKey::from_secret(SECRET.as_bytes()),
-            validation: Validation::new(Algorithm::HS256),
-            header: "authorization".to_string(),
-        }
+        JwtAuthInstance::new(
+            DecodingKey::from_secret(SECRET.as_bytes()),
+            Validation::new(Algorithm::HS256),
+        )
     }
 
     fn make_token(sub: &str, exp_offset_secs: i64) -> String {
@@ -238,11 +337,10 @@ mod tests {
 
     #[test]
     fn wrong_secret_returns_401() {
-        let inst = JwtAuthInstance {
-            decoding_key: DecodingKey::from_secret(b"wrong-secret"),
-            validation: Validation::new(Algorithm::HS256),
-            header: "authorization".to_string(),
-        };
+        let inst = JwtAuthInstance::new(
+            DecodingKey::from_secret(b"wrong-secret"),
+            Validation::new(Algorithm::HS256),
+        );
         let token = make_token("alice", 3600);
         let result = inst.access(&mut make_ctx(Some(&format!("Bearer {token}"))));
         assert!(matches!(result, PluginResult::Response { status: 401, .. }));
@@ -418,11 +516,7 @@ mod tests {
         let mut validation = Validation::new(Algorithm::HS256);
         validation.validate_exp = true;
         validation.validate_nbf = true;
-        let inst = JwtAuthInstance {
-            decoding_key: DecodingKey::from_secret(SECRET.as_bytes()),
-            validation,
-            header: "authorization".to_string(),
-        };
+        let inst = JwtAuthInstance::new(DecodingKey::from_secret(SECRET.as_bytes()), validation);
         let result = inst.access(&mut make_ctx(Some(&format!("Bearer {token}"))));
         assert!(
             matches!(result, PluginResult::Response { status: 401, .. }),
@@ -514,4 +608,210 @@ mod tests {
         );
         assert_eq!(ctx.consumer.as_deref(), Some("alice"));
     }
+
+    // ── Asymmetric keys ──────────────────────────────────────────
+
+    const RSA_PRIVATE: &str = include_str!("../../tests/fixtures/jwt/rsa_private.pem");
+    const RSA_PUBLIC: &str = include_str!("../../tests/fixtures/jwt/rsa_public.pem");
+    const EC_PRIVATE: &str = include_str!("../../tests/fixtures/jwt/ec_private.pem");
+    const EC_PUBLIC: &str = include_str!("../../tests/fixtures/jwt/ec_public.pem");
+    const JWKS_ROTATED: &str = include_str!("../../tests/fixtures/jwt/jwks_rotated.json");
+
+    fn sign(header: Header, claims: serde_json::Value, key: &EncodingKey) -> String {
+        let mut claims = claims;
+        let exp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            + 3600;
+        claims["exp"] = exp.into();
+        encode(&header, &claims, key).unwrap()
+    }
+
+    fn rs256_token(kid: Option<&str>, claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = kid.map(str::to_string);
+        let key = EncodingKey::from_rsa_pem(RSA_PRIVATE.as_bytes()).unwrap();
+        sign(header, claims, &key)
+    }
+
+    fn bearer(token: &str) -> PluginContext {
+        make_ctx(Some(&format!("Bearer {token}")))
+    }
+
+    #[test]
+    fn rs256_public_key_verifies_token() {
+        let config = serde_json::json!({ "algorithm": "RS256", "public_key": RSA_PUBLIC });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+        let mut ctx = bearer(&rs256_token(None, serde_json::json!({ "sub": "alice" })));
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.consumer.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn es256_public_key_verifies_token() {
+        let config = serde_json::json!({ "algorithm": "ES256", "public_key": EC_PUBLIC });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+        let key = EncodingKey::from_ec_pem(EC_PRIVATE.as_bytes()).unwrap();
+        let token = sign(
+            Header::new(Algorithm::ES256),
+            serde_json::json!({ "sub": "bob" }),
+            &key,
+        );
+        let mut ctx = bearer(&token);
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.consumer.as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn hs256_token_rejected_by_rs256_instance() {
+        let config = serde_json::json!({ "algorithm": "RS256", "public_key": RSA_PUBLIC });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+        let result = inst.access(&mut bearer(&make_token("alice", 3600)));
+        assert!(matches!(result, PluginResult::Response { status: 401, .. }));
+    }
+
+    #[test]
+    fn configure_asymmetric_without_key_fails() {
+        let config = serde_json::json!({ "algorithm": "ES256" });
+        assert!(JwtAuthPlugin.configure(&config).is_err());
+        let config = serde_json::json!({ "algorithm": "ES256", "public_key": "garbage" });
+        assert!(JwtAuthPlugin.configure(&config).is_err());
+    }
+
+    // ── iss / aud ────────────────────────────────────────────────
+
+    #[test]
+    fn issuer_and_audience_are_enforced() {
+        let config = serde_json::json!({
+            "algorithm": "RS256",
+            "public_key": RSA_PUBLIC,
+            "iss": "https://idp.example.com",
+            "aud": ["gateway"],
+        });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+
+        let ok = rs256_token(
+            None,
+            serde_json::json!({ "sub": "a", "iss": "https://idp.example.com", "aud": "gateway" }),
+        );
+        assert!(matches!(
+            inst.access(&mut bearer(&ok)),
+            PluginResult::Continue
+        ));
+
+        let wrong_iss = rs256_token(
+            None,
+            serde_json::json!({ "sub": "a", "iss": "https://evil.example.com", "aud": "gateway" }),
+        );
+        assert!(matches!(
+            inst.access(&mut bearer(&wrong_iss)),
+            PluginResult::Response { status: 401, .. }
+        ));
+
+        let wrong_aud = rs256_token(
+            None,
+            serde_json::json!({ "sub": "a", "iss": "https://idp.example.com", "aud": "other" }),
+        );
+        assert!(matches!(
+            inst.access(&mut bearer(&wrong_aud)),
+            PluginResult::Response { status: 401, .. }
+        ));
+
+        let missing = rs256_token(None, serde_json::json!({ "sub": "a" }));
+        assert!(matches!(
+            inst.access(&mut bearer(&missing)),
+            PluginResult::Response { status: 401, .. }
+        ));
+    }
+
+    // ── claims_to_headers ────────────────────────────────────────
+
+    #[test]
+    fn claims_are_forwarded_as_upstream_headers() {
+        let config = serde_json::json!({
+            "secret": SECRET,
+            "claims_to_headers": { "sub": "X-User-Id", "tier": "X-Tier", "missing": "X-Missing" },
+        });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+        let token = sign(
+            Header::new(Algorithm::HS256),
+            serde_json::json!({ "sub": "alice", "tier": "gold" }),
+            &EncodingKey::from_secret(SECRET.as_bytes()),
+        );
+        let mut ctx = bearer(&token);
+        ctx.request_headers
+            .append("X-Missing", "forged-by-the-client");
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(
+            ctx.upstream_headers.get("x-user-id").map(String::as_str),
+            Some("alice")
+        );
+        assert_eq!(
+            ctx.upstream_headers.get("x-tier").map(String::as_str),
+            Some("gold")
+        );
+        assert!(!ctx.upstream_headers.contains_key("x-missing"));
+        assert!(!ctx.request_headers.contains_key("x-missing"));
+    }
+
+    // ── JWKS ─────────────────────────────────────────────────────
+
+    fn jwks_instance(set: Arc<JwksSet>) -> JwtAuthInstance {
+        JwtAuthInstance {
+            key: KeySource::Jwks(set),
+            ..JwtAuthInstance::new(
+                DecodingKey::from_secret(b""),
+                Validation::new(Algorithm::RS256),
+            )
+        }
+    }
+
+    #[test]
+    fn jwks_unknown_kid_rejected_until_refresh() {
+        let set = Arc::new(JwksSet::new("http://unused", Duration::from_secs(300)));
+        set.load_json(&JWKS_ROTATED.replace("\"rotated\"", "\"initial\""))
+            .unwrap();
+        let inst = jwks_instance(set.clone());
+
+        let token = rs256_token(Some("rotated"), serde_json::json!({ "sub": "alice" }));
+        assert!(matches!(
+            inst.access(&mut bearer(&token)),
+            PluginResult::Response { status: 401, .. }
+        ));
+        assert!(set.is_stale(), "kid miss should request a refresh");
+
+        // The refresher picks up the rotated key set.
+        set.load_json(JWKS_ROTATED).unwrap();
+        let mut ctx = bearer(&token);
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.consumer.as_deref(), Some("alice"));
+    }
+
+    #[test]
+    fn jwks_token_without_kid_uses_single_key() {
+        let set = Arc::new(JwksSet::new("http://unused", Duration::from_secs(300)));
+        set.load_json(JWKS_ROTATED).unwrap();
+        let inst = jwks_instance(set);
+        let token = rs256_token(None, serde_json::json!({ "sub": "alice" }));
+        assert!(matches!(
+            inst.access(&mut bearer(&token)),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn configure_with_jwks_uri_needs_no_key() {
+        let config = serde_json::json!({
+            "algorithm": "RS256",
+            "jwks_uri": "http://127.0.0.1:9/unreachable/jwks.json",
+        });
+        let inst = JwtAuthPlugin.configure(&config).unwrap();
+        // Nothing fetched yet: every token is rejected without blocking.
+        let token = rs256_token(Some("rotated"), serde_json::json!({ "sub": "alice" }));
+        assert!(matches!(
+            inst.access(&mut bearer(&token)),
+            PluginResult::Response { status: 401, .. }
+        ));
+    }
 }
@@ -61,37 +61,28 @@ impl PluginInstance for KeyAuthInstance {
         2500
     }
 
-    /// Check for API key in the configured header.
+    /// Check the API key in the configured header against the consumers'
+    /// `key-auth` credentials.
     ///
-    /// The actual key validation is done against the consumer store.
-    /// This plugin sets `ctx.consumer` to the matched consumer username,
-    /// or returns 401 if the key is missing/invalid.
+    /// Sets `ctx.consumer` to the matched consumer username, or returns 401
+    /// if the key is missing or unknown. Without a consumer index on the
+    /// context no key can be verified, so every request is rejected.
     ///
-    /// v2 design: The consumer lookup is done inline (no async) using
-    /// a pre-built HashMap that each worker core holds locally.
+    /// v2 design: The consumer lookup is done inline (no async) against
+    /// the `ConsumerIndex` snapshot each worker core holds locally.
     fn access(&self, ctx: &mut PluginContext) -> PluginResult {
-        let key = match ctx.get_header(&self.header) {
-            Some(k) if !k.is_empty() => k.to_string(),
-            _ => {
-                return PluginResult::Response {
-                    status: 401,
-                    headers: vec![
-                        ("content-type".to_string(), "application/json".to_string()),
-                        (
-                            "www-authenticate".to_string(),
-                            "Key realm=\"Ando\"".to_string(),
-                        ),
-                    ],
-                    body: Some(br#"{"error":"Missing API key","status":401}"#.to_vec()),
-                };
-            }
+        let username = match ctx.get_header(&self.header) {
+            Some(k) if !k.is_empty() => ctx
+                .consumers
+                .as_ref()
+                .and_then(|index| index.get("key-auth", k))
+                .map(|cred| cred.username.clone()),
+            _ => return unauthorized(br#"{"error":"Missing API key","status":401}"#),
         };
-
-        // Store the key in vars for the proxy to validate against consumers.
-        // The proxy layer handles the actual consumer lookup since it has
-        // access to the consumer store.
-        ctx.vars
-            .insert("_key_auth_key".to_string(), serde_json::Value::String(key));
+        let Some(username) = username else {
+            return unauthorized(br#"{"error":"Invalid API key","status":401}"#);
+        };
+        ctx.consumer = Some(username);
 
         if self.hide_credentials {
             ctx.request_headers.remove(&self.header);
@@ -101,17 +92,59 @@ impl PluginInstance for KeyAuthInstance {
     }
 }
 
+fn unauthorized(body: &[u8]) -> PluginResult {
+    PluginResult::Response {
+        status: 401,
+        headers: vec![
+            ("content-type".to_string(), "application/json".to_string()),
+            (
+                "www-authenticate".to_string(),
+                "Key realm=\"Ando\"".to_string(),
+            ),
+        ],
+        body: Some(body.to_vec()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ando_core::consumer::Consumer;
+    use ando_plugin::plugin::ConsumerIndex;
     use std::collections::HashMap;
+    use std::sync::Arc;
+
+    /// Consumers known to every context built by `make_ctx`.
+    const KEYS: &[(&str, &str)] = &[
+        ("alice", "my-secret-key"),
+        ("bob", "Bearer eyJhbGci"),
+        ("carol", "custom-key"),
+        ("dave", "my-key"),
+    ];
+
+    fn index(keys: &[(&str, &str)]) -> Arc<ConsumerIndex> {
+        let consumers: Vec<Consumer> = keys
+            .iter()
+            .map(|(name, key)| {
+                serde_json::from_value(serde_json::json!({
+                    "username": name,
+                    "plugins": { "key-auth": { "key": key } }
+                }))
+                .unwrap()
+            })
+            .collect();
+        Arc::new(ConsumerIndex::build(&consumers))
+    }
 
     fn make_ctx(headers: Vec<(&str, &str)>) -> PluginContext {
         let map: HashMap<String, String> = headers
             .into_iter()
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
-        PluginContext::new("r1".into(), "1.2.3.4".into(), "GET".into(), "/".into(), map)
+        let mut ctx =
+            PluginContext::new("r1".into(), "1.2.3.4".into(), "GET".into(), "/".into(), map);
+        ctx.consumers = Some(index(KEYS));
+        ctx
     }
 
     fn inst(header: &str, hide: bool) -> KeyAuthInstance {
@@ -121,25 +154,28 @@ mod tests {
         }
     }
 
+    fn error_body(result: PluginResult) -> String {
+        match result {
+            PluginResult::Response {
+                status,
+                body,
+                headers,
+            } => {
+                assert_eq!(status, 401);
+                assert!(headers.iter().any(|(k, _)| k == "www-authenticate"));
+                String::from_utf8(body.unwrap()).unwrap()
+            }
+            PluginResult::Continue => panic!("Expected 401 Response"),
+        }
+    }
+
     // ── Missing / empty key ──────────────────────────────────────────────────
 
     #[test]
     fn test_missing_key_returns_401() {
         let mut ctx = make_ctx(vec![]);
-        let result = inst("apikey", false).access(&mut ctx);
-        if let PluginResult::Response {
-            status,
-            body,
-            headers,
-        } = result
-        {
-            assert_eq!(status, 401);
-            let body_text = String::from_utf8(body.unwrap()).unwrap();
-            assert!(body_text.contains("Missing API key"));
-            assert!(headers.iter().any(|(k, _)| k == "www-authenticate"));
-        } else {
-            panic!("Expected 401 Response");
-        }
+        let body = error_body(inst("apikey", false).access(&mut ctx));
+        assert!(body.contains("Missing API key"));
     }
 
     #[test]
@@ -152,21 +188,51 @@ mod tests {
     // ── Valid key ────────────────────────────────────────────────────────────
 
     #[test]
-    fn test_valid_key_stored_in_vars() {
+    fn test_valid_key_sets_consumer() {
         let mut ctx = make_ctx(vec![("apikey", "my-secret-key")]);
         let result = inst("apikey", false).access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
-        assert_eq!(
-            ctx.vars["_key_auth_key"],
-            serde_json::json!("my-secret-key")
-        );
+        assert_eq!(ctx.consumer.as_deref(), Some("alice"));
     }
 
     #[test]
-    fn test_key_value_preserved_correctly() {
+    fn test_key_value_matched_exactly() {
         let mut ctx = make_ctx(vec![("apikey", "Bearer eyJhbGci")]);
         inst("apikey", false).access(&mut ctx);
-        assert_eq!(ctx.vars["_key_auth_key"], "Bearer eyJhbGci");
+        assert_eq!(ctx.consumer.as_deref(), Some("bob"));
+    }
+
+    // ── Unknown key ──────────────────────────────────────────────────────────
+
+    #[test]
+    fn test_unknown_key_returns_401() {
+        let mut ctx = make_ctx(vec![("apikey", "random-string")]);
+        let body = error_body(inst("apikey", false).access(&mut ctx));
+        assert_eq!(body, r#"{"error":"Invalid API key","status":401}"#);
+        assert!(ctx.consumer.is_none());
+    }
+
+    #[test]
+    fn test_no_consumers_configured_rejects_any_key() {
+        let mut ctx = make_ctx(vec![("apikey", "my-secret-key")]);
+        ctx.consumers = Some(index(&[]));
+        let body = error_body(inst("apikey", false).access(&mut ctx));
+        assert!(body.contains("Invalid API key"));
+    }
+
+    #[test]
+    fn test_missing_consumer_index_rejects_any_key() {
+        let mut ctx = make_ctx(vec![("apikey", "my-secret-key")]);
+        ctx.consumers = None;
+        let body = error_body(inst("apikey", false).access(&mut ctx));
+        assert!(body.contains("Invalid API key"));
+    }
+
+    #[test]
+    fn test_unknown_key_keeps_header() {
+        let mut ctx = make_ctx(vec![("apikey", "random-string")]);
+        inst("apikey", true).access(&mut ctx);
+        assert!(ctx.request_headers.contains_key("apikey"));
     }
 
     // ── hide_credentials ─────────────────────────────────────────────────────
@@ -181,14 +247,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_hide_credentials_removes_every_value() {
+        let mut ctx = make_ctx(vec![]);
+        ctx.request_headers = vec![
+            ("ApiKey".to_string(), "my-key".to_string()),
+            ("apikey".to_string(), "other".to_string()),
+        ]
+        .into();
+        inst("apikey", true).access(&mut ctx);
+        assert_eq!(ctx.consumer.as_deref(), Some("dave"));
+        assert_eq!(ctx.get_headers_all("apikey").count(), 0);
+        assert_eq!(ctx.request_headers.edited(), ["apikey"]);
+    }
+
     #[test]
     fn test_no_hide_keeps_header() {
         let mut ctx = make_ctx(vec![("apikey", "my-key")]);
         inst("apikey", false).access(&mut ctx);
-        assert_eq!(
-            ctx.request_headers.get("apikey").map(|s| s.as_str()),
-            Some("my-key")
-        );
+        assert_eq!(ctx.request_headers.get("apikey"), Some("my-key"));
     }
 
     // ── Custom header name ───────────────────────────────────────────────────
@@ -198,13 +275,13 @@ mod tests {
         let mut ctx = make_ctx(vec![("x-api-key", "custom-key")]);
         let result = inst("x-api-key", false).access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
-        assert_eq!(ctx.vars["_key_auth_key"], "custom-key");
+        assert_eq!(ctx.consumer.as_deref(), Some("carol"));
     }
 
     #[test]
     fn test_custom_header_wrong_name_returns_401() {
         // Configured for x-api-key but sends apikey — should reject
-        let mut ctx = make_ctx(vec![("apikey", "some-key")]);
+        let mut ctx = make_ctx(vec![("apikey", "custom-key")]);
         let result = inst("x-api-key", false).access(&mut ctx);
         assert!(matches!(result, PluginResult::Response { status: 401, .. }));
     }
@@ -212,7 +289,7 @@ mod tests {
     #[test]
     fn test_header_name_case_insensitive_config() {
         // configure() lowercases the header; lookup uses lowercase header from ctx
-        let mut ctx = make_ctx(vec![("x-api-key", "k")]);
+        let mut ctx = make_ctx(vec![("x-api-key", "custom-key")]);
         let result = inst("X-Api-Key", false).access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
     }
@@ -243,7 +320,7 @@ mod tests {
         let i = plugin.configure(&serde_json::json!({})).unwrap();
         assert_eq!(i.name(), "key-auth");
         // Default header = apikey
-        let mut ctx = make_ctx(vec![("apikey", "test-key")]);
+        let mut ctx = make_ctx(vec![("apikey", "my-key")]);
         assert!(matches!(i.access(&mut ctx), PluginResult::Continue));
     }
 
@@ -256,7 +333,7 @@ mod tests {
                 "hide_credentials": true
             }))
             .unwrap();
-        let mut ctx = make_ctx(vec![("authorization", "token-abc")]);
+        let mut ctx = make_ctx(vec![("authorization", "my-key")]);
         let result = i.access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
         // header removed because hide_credentials = true
@@ -1,3 +1,8 @@
 pub mod basic_auth;
+pub mod consumer_restriction;
+pub mod hmac_auth;
+pub mod jwks;
 pub mod jwt_auth;
 pub mod key_auth;
+pub mod mtls_auth;
+pub mod openid_connect;
@@ -0,0 +1,230 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+
+/// mTLS-auth plugin — admits requests whose TLS connection presented a
+/// client certificate that verified against the `client_tls` CAs of the
+/// SSL object it was served.
+///
+/// The gateway exposes the certificate as `ctx.vars["ssl_client_*"]`; SSL
+/// objects in `optional` mode let connections without one through, and
+/// this plugin rejects their requests with 401 on the routes it guards.
+/// With `consumer_by` the certificate also names the consumer, like a key
+/// does for key-auth: consumers carry `{"fingerprint": "<sha-256 hex>"}`
+/// (lower case, no colons) or `{"san": "partner.example.com"}`, matched
+/// against the certificate's subjectAltNames in order.
+pub struct MtlsAuthPlugin;
+
+#[derive(Debug, Deserialize)]
+struct MtlsAuthConfig {
+    /// Map the certificate to a consumer; unmapped certificates get 401.
+    #[serde(default)]
+    consumer_by: Option<ConsumerBy>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ConsumerBy {
+    Fingerprint,
+    San,
+}
+
+struct MtlsAuthInstance {
+    consumer_by: Option<ConsumerBy>,
+}
+
+impl Plugin for MtlsAuthPlugin {
+    fn name(&self) -> &str {
+        "mtls-auth"
+    }
+
+    fn priority(&self) -> i32 {
+        2540
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: MtlsAuthConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("mtls-auth config error: {e}"))?;
+        Ok(Box::new(MtlsAuthInstance {
+            consumer_by: cfg.consumer_by,
+        }))
+    }
+}
+
+impl MtlsAuthInstance {
+    /// The consumer registered for the certificate in `ctx.vars`.
+    fn consumer(&self, ctx: &PluginContext, by: ConsumerBy) -> Option<String> {
+        let index = ctx.consumers.as_ref()?;
+        let lookup = |field: &str, id: &str| {
+            index
+                .get("mtls-auth", id)
+                .filter(|cred| cred.config.get(field).and_then(|v| v.as_str()) == Some(id))
+                .map(|cred| cred.username.clone())
+        };
+        match by {
+            ConsumerBy::Fingerprint => {
+                let fingerprint = ctx.vars.get("ssl_client_fingerprint")?.as_str()?;
+                lookup("fingerprint", fingerprint)
+            }
+            ConsumerBy::San => ctx
+                .vars
+                .get("ssl_client_san")?
+                .as_array()?
+                .iter()
+                .filter_map(|name| name.as_str())
+                .find_map(|name| lookup("san", name)),
+        }
+    }
+}
+
+impl PluginInstance for MtlsAuthInstance {
+    fn name(&self) -> &str {
+        "mtls-auth"
+    }
+
+    fn priority(&self) -> i32 {
+        2540
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        match ctx.vars.get("ssl_client_verify").and_then(|v| v.as_str()) {
+            Some("SUCCESS") => {}
+            Some(v) if v.starts_with("FAILED") => {
+                return unauthorized(br#"{"error":"Invalid client certificate","status":401}"#);
+            }
+            _ => return unauthorized(br#"{"error":"Missing client certificate","status":401}"#),
+        }
+        let Some(by) = self.consumer_by else {
+            return PluginResult::Continue;
+        };
+        let Some(username) = self.consumer(ctx, by) else {
+            return unauthorized(br#"{"error":"Unknown client certificate","status":401}"#);
+        };
+        ctx.consumer = Some(username);
+        PluginResult::Continue
+    }
+}
+
+fn unauthorized(body: &[u8]) -> PluginResult {
+    PluginResult::Response {
+        status: 401,
+        headers: vec![("content-type".to_string(), "application/json".to_string())],
+        body: Some(body.to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::consumer::Consumer;
+    use ando_plugin::plugin::ConsumerIndex;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const FINGERPRINT: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+    fn index() -> Arc<ConsumerIndex> {
+        let consumers: Vec<Consumer> = [
+            (
+                "partner-a",
+                serde_json::json!({ "fingerprint": FINGERPRINT }),
+            ),
+            (
+                "partner-b",
+                serde_json::json!({ "san": "b.partners.example" }),
+            ),
+        ]
+        .into_iter()
+        .map(|(name, cred)| {
+            serde_json::from_value(serde_json::json!({
+                "username": name,
+                "plugins": { "mtls-auth": cred }
+            }))
+            .unwrap()
+        })
+        .collect();
+        Arc::new(ConsumerIndex::build(&consumers))
+    }
+
+    fn ctx(verify: Option<&str>, fingerprint: &str, san: &[&str]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        );
+        if let Some(verify) = verify {
+            ctx.vars
+                .insert("ssl_client_verify".into(), verify.to_string().into());
+            ctx.vars
+                .insert("ssl_client_fingerprint".into(), fingerprint.into());
+            ctx.vars.insert("ssl_client_san".into(), san.into());
+        }
+        ctx.consumers = Some(index());
+        ctx
+    }
+
+    fn run(config: serde_json::Value, ctx: &mut PluginContext) -> Result<(), String> {
+        match MtlsAuthPlugin.configure(&config).unwrap().access(ctx) {
+            PluginResult::Continue => Ok(()),
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 401);
+                Err(String::from_utf8(body.unwrap()).unwrap())
+            }
+        }
+    }
+
+    #[test]
+    fn requires_a_verified_certificate() {
+        let config = serde_json::json!({});
+        assert!(run(config.clone(), &mut ctx(Some("SUCCESS"), "x", &[])).is_ok());
+        let err = run(config.clone(), &mut ctx(None, "", &[])).unwrap_err();
+        assert!(err.contains("Missing"), "{err}");
+        let err = run(config.clone(), &mut ctx(Some("NONE"), "", &[])).unwrap_err();
+        assert!(err.contains("Missing"), "{err}");
+        let err = run(config, &mut ctx(Some("FAILED:unknown issuer"), "x", &[])).unwrap_err();
+        assert!(err.contains("Invalid"), "{err}");
+    }
+
+    #[test]
+    fn maps_fingerprint_to_consumer() {
+        let config = serde_json::json!({ "consumer_by": "fingerprint" });
+        let mut known = ctx(Some("SUCCESS"), FINGERPRINT, &["b.partners.example"]);
+        assert!(run(config.clone(), &mut known).is_ok());
+        assert_eq!(known.consumer.as_deref(), Some("partner-a"));
+
+        let mut unknown = ctx(Some("SUCCESS"), "00ff", &["b.partners.example"]);
+        let err = run(config, &mut unknown).unwrap_err();
+        assert!(err.contains("Unknown"), "{err}");
+        assert!(unknown.consumer.is_none());
+    }
+
+    #[test]
+    fn maps_first_registered_san_to_consumer() {
+        let config = serde_json::json!({ "consumer_by": "san" });
+        let mut known = ctx(
+            Some("SUCCESS"),
+            "00ff",
+            &["x.example", "b.partners.example"],
+        );
+        assert!(run(config.clone(), &mut known).is_ok());
+        assert_eq!(known.consumer.as_deref(), Some("partner-b"));
+
+        // A fingerprint credential does not match as a SAN.
+        let mut other = ctx(Some("SUCCESS"), "00ff", &[FINGERPRINT]);
+        assert!(run(config, &mut other).is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_consumer_by() {
+        assert!(
+            MtlsAuthPlugin
+                .configure(&serde_json::json!({ "consumer_by": "subject" }))
+                .is_err()
+        );
+    }
+}
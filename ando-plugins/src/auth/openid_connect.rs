@@ -0,0 +1,1351 @@
+use super::jwks::{self, JwksSet};
+use crate::background;
+use ando_core::vars::{cookie_value, percent_decode, query_arg};
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+};
+use arc_swap::ArcSwapOption;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use jsonwebtoken::{Validation, decode, decode_header};
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::digest::{SHA256, digest};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// How long a login started by a redirect to the provider stays valid.
+const LOGIN_TTL: u64 = 600;
+
+/// How often a bearer token whose key is not loaded yet is retried while
+/// the JWKS refresher fetches it.
+const KEY_WAIT_STEP: Duration = Duration::from_millis(100);
+
+pub struct OpenIdConnectPlugin;
+
+#[derive(Debug, Deserialize)]
+struct OidcConfig {
+    /// Provider discovery document, e.g.
+    /// `https://idp.example.com/.well-known/openid-configuration`.
+    discovery: String,
+    client_id: String,
+    #[serde(default)]
+    client_secret: Option<String>,
+    /// Scopes requested at login — default "openid".
+    #[serde(default = "default_scope")]
+    scope: String,
+    /// Callback URL registered with the provider. Requests to its path
+    /// complete the login. Required unless `bearer_only`.
+    #[serde(default)]
+    redirect_uri: Option<String>,
+    /// Only accept `Authorization: Bearer` access tokens; never redirect.
+    #[serde(default)]
+    bearer_only: bool,
+    /// Check bearer tokens at the provider's introspection endpoint instead
+    /// of verifying them against its JWKS.
+    #[serde(default)]
+    use_introspection: bool,
+    /// What happens to requests without a session: "auth" (redirect to the
+    /// provider, the default), "deny" (401) or "pass".
+    #[serde(default)]
+    unauth_action: UnauthAction,
+    /// Session cookie settings. Required unless `bearer_only`.
+    #[serde(default)]
+    session: Option<SessionConfig>,
+    /// Send the identity claims upstream as base64 JSON in `X-Userinfo`.
+    #[serde(default = "default_true")]
+    set_userinfo_header: bool,
+    /// Send the access token upstream in `X-Access-Token`.
+    #[serde(default = "default_true")]
+    set_access_token_header: bool,
+    /// Seconds between JWKS refreshes — default 300.
+    #[serde(default = "default_jwks_refresh")]
+    jwks_refresh_interval: u64,
+    /// Milliseconds allowed for each call to the provider — default 3000.
+    #[serde(default = "default_timeout")]
+    timeout: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct SessionConfig {
+    /// Key the session cookie is encrypted with (at least 16 characters).
+    secret: String,
+    /// Session cookie name — default "ando_session".
+    #[serde(default = "default_cookie_name")]
+    cookie_name: String,
+    /// Seconds a session lasts, however often its tokens are refreshed —
+    /// default 3600.
+    #[serde(default = "default_lifetime")]
+    lifetime: u64,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum UnauthAction {
+    #[default]
+    Auth,
+    Deny,
+    Pass,
+}
+
+fn default_scope() -> String {
+    "openid".to_string()
+}
+fn default_true() -> bool {
+    true
+}
+fn default_jwks_refresh() -> u64 {
+    300
+}
+fn default_timeout() -> u64 {
+    3000
+}
+fn default_cookie_name() -> String {
+    "ando_session".to_string()
+}
+fn default_lifetime() -> u64 {
+    3600
+}
+
+/// The parts of the discovery document the plugin uses.
+#[derive(Debug, Deserialize)]
+struct Metadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    #[serde(default)]
+    jwks_uri: Option<String>,
+    #[serde(default)]
+    introspection_endpoint: Option<String>,
+}
+
+/// A provider's discovery metadata, shared by every openid-connect
+/// instance in the process that names the same discovery URL.
+struct Provider {
+    discovery: String,
+    metadata: ArcSwapOption<Metadata>,
+    /// Signing keys from `jwks_uri`, registered with the JWKS refresher
+    /// once the metadata is in.
+    keys: OnceLock<Arc<JwksSet>>,
+    jwks_refresh: Duration,
+}
+
+impl Provider {
+    fn keys(&self) -> Option<&Arc<JwksSet>> {
+        self.keys.get()
+    }
+
+    /// The cached metadata, fetching the discovery document on the
+    /// background runtime if it has not been loaded yet.
+    async fn load(self: &Arc<Self>, timeout: Duration) -> Result<Arc<Metadata>, String> {
+        if let Some(metadata) = self.metadata.load_full() {
+            return Ok(metadata);
+        }
+        let provider = self.clone();
+        background::spawn(async move { provider.fetch(timeout).await })
+            .await
+            .unwrap_or_else(|| Err("discovery task failed".to_string()))
+    }
+
+    async fn fetch(&self, timeout: Duration) -> Result<Arc<Metadata>, String> {
+        let resp = http()
+            .get(&self.discovery)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if !resp.status().is_success() {
+            return Err(format!("HTTP {}", resp.status()));
+        }
+        let metadata: Metadata = resp
+            .json()
+            .await
+            .map_err(|e| format!("invalid discovery document: {e}"))?;
+        if let Some(uri) = &metadata.jwks_uri {
+            self.keys
+                .get_or_init(|| jwks::key_set(uri, self.jwks_refresh));
+        }
+        let metadata = Arc::new(metadata);
+        self.metadata.store(Some(metadata.clone()));
+        debug!(discovery = %self.discovery, "OIDC discovery loaded");
+        Ok(metadata)
+    }
+}
+
+/// Get the shared provider for `discovery`. The first registration starts
+/// fetching its metadata so the first request need not wait for it.
+fn provider(discovery: &str, jwks_refresh: Duration, timeout: Duration) -> Arc<Provider> {
+    static PROVIDERS: OnceLock<Mutex<HashMap<String, Arc<Provider>>>> = OnceLock::new();
+    let mut providers = PROVIDERS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    providers
+        .entry(discovery.to_string())
+        .or_insert_with(|| {
+            let provider = Arc::new(Provider {
+                discovery: discovery.to_string(),
+                metadata: ArcSwapOption::empty(),
+                keys: OnceLock::new(),
+                jwks_refresh,
+            });
+            let warm = provider.clone();
+            background::runtime().spawn(async move {
+                if let Err(e) = warm.fetch(timeout).await {
+                    warn!(discovery = %warm.discovery, error = %e, "OIDC discovery failed");
+                }
+            });
+            provider
+        })
+        .clone()
+}
+
+/// HTTP client for provider calls; it must only be used on the background
+/// runtime.
+fn http() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
+/// Tokens from the provider's token endpoint.
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    id_token: Option<String>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// What the session cookie holds.
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    /// ID token claims.
+    claims: Value,
+    access_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
+    /// Unix time the access token expires.
+    expires_at: u64,
+    /// Unix time the session ends.
+    ends_at: u64,
+}
+
+/// What the login-state cookie holds between the redirect to the provider
+/// and its callback.
+#[derive(Debug, Serialize, Deserialize)]
+struct LoginState {
+    state: String,
+    nonce: String,
+    /// Request URI to return to once logged in.
+    target: String,
+}
+
+/// Encrypted cookies of the browser flow.
+struct Cookies {
+    /// AES-256-GCM key derived from `session.secret`.
+    key: LessSafeKey,
+    /// Session cookie name.
+    session: String,
+    /// Login-state cookie name, `<session>_state`.
+    state: String,
+    lifetime: u64,
+}
+
+impl Cookies {
+    fn new(cfg: &SessionConfig) -> Self {
+        let digest = digest(&SHA256, cfg.secret.as_bytes());
+        let key = UnboundKey::new(&AES_256_GCM, digest.as_ref()).expect("SHA-256 is a valid key");
+        Self {
+            key: LessSafeKey::new(key),
+            state: format!("{}_state", cfg.cookie_name),
+            session: cfg.cookie_name.clone(),
+            lifetime: cfg.lifetime,
+        }
+    }
+
+    /// Encrypt `value` for the cookie `name`; the name is authenticated so
+    /// one cookie's value cannot be replayed as another's.
+    fn seal(&self, name: &str, value: &impl Serialize) -> String {
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .expect("system RNG available");
+        let mut data = serde_json::to_vec(value).expect("cookie values serialize");
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(name.as_bytes()),
+                &mut data,
+            )
+            .expect("cookie value within AES-GCM limits");
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&data);
+        URL_SAFE_NO_PAD.encode(sealed)
+    }
+
+    fn open<T: DeserializeOwned>(&self, name: &str, value: &str) -> Option<T> {
+        let mut sealed = URL_SAFE_NO_PAD.decode(value).ok()?;
+        if sealed.len() < NONCE_LEN {
+            return None;
+        }
+        let mut data = sealed.split_off(NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(&sealed).ok()?;
+        let plain = self
+            .key
+            .open_in_place(nonce, Aad::from(name.as_bytes()), &mut data)
+            .ok()?;
+        serde_json::from_slice(plain).ok()
+    }
+
+    /// The request's session, if its cookie decrypts and has not ended.
+    fn session(&self, ctx: &PluginContext) -> Option<Session> {
+        let session: Session = self.open(&self.session, request_cookie(ctx, &self.session)?)?;
+        (session.ends_at > unix_now()).then_some(session)
+    }
+
+    fn set_session(&self, session: &Session, secure: bool) -> String {
+        let max_age = session.ends_at.saturating_sub(unix_now());
+        set_cookie(
+            &self.session,
+            &self.seal(&self.session, session),
+            max_age,
+            secure,
+        )
+    }
+}
+
+struct Oidc {
+    provider: Arc<Provider>,
+    client_id: String,
+    client_secret: Option<String>,
+    scope: String,
+    redirect_uri: String,
+    /// Path of `redirect_uri`.
+    callback_path: String,
+    use_introspection: bool,
+    unauth_action: UnauthAction,
+    /// Set for the browser flow; `None` when `bearer_only`.
+    cookies: Option<Arc<Cookies>>,
+    set_userinfo_header: bool,
+    set_access_token_header: bool,
+    timeout: Duration,
+}
+
+/// Why a bearer JWT was not accepted.
+enum Rejected {
+    /// No key with the token's `kid` is loaded (yet).
+    UnknownKey,
+    Invalid(String),
+}
+
+impl Oidc {
+    /// Upstream headers carrying the caller's identity.
+    fn identity_headers(&self, claims: &Value, access_token: &str) -> Vec<(String, String)> {
+        let mut headers = Vec::new();
+        if self.set_access_token_header {
+            headers.push(("x-access-token".to_string(), access_token.to_string()));
+        }
+        if self.set_userinfo_header {
+            headers.push((
+                "x-userinfo".to_string(),
+                STANDARD.encode(claims.to_string()),
+            ));
+        }
+        headers
+    }
+
+    /// Expose the claims as `_oidc_claims` / `_oidc_sub` in `ctx.vars`.
+    fn expose_claims(&self, ctx: &mut PluginContext, claims: &Value) {
+        if let Some(sub) = claims.get("sub").and_then(Value::as_str) {
+            ctx.vars
+                .insert("_oidc_sub".to_string(), Value::String(sub.to_string()));
+        }
+        ctx.vars.insert("_oidc_claims".to_string(), claims.clone());
+    }
+
+    fn authenticate(&self, ctx: &mut PluginContext, claims: &Value, access_token: &str) {
+        self.expose_claims(ctx, claims);
+        ctx.upstream_headers
+            .extend(self.identity_headers(claims, access_token));
+    }
+
+    /// POST a form to a provider endpoint with the client's credentials.
+    async fn post<T: DeserializeOwned + Send + 'static>(
+        &self,
+        endpoint: &str,
+        mut form: Vec<(&'static str, String)>,
+    ) -> Result<T, String> {
+        form.push(("client_id", self.client_id.clone()));
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret.clone()));
+        }
+        let request = http().post(endpoint).form(&form).timeout(self.timeout);
+        background::spawn(async move {
+            let resp = request.send().await.map_err(|e| e.to_string())?;
+            if !resp.status().is_success() {
+                return Err(format!("HTTP {}", resp.status()));
+            }
+            resp.json::<T>().await.map_err(|e| e.to_string())
+        })
+        .await
+        .unwrap_or_else(|| Err("provider call failed".to_string()))
+    }
+
+    // ── Bearer tokens ──
+
+    fn bearer(self: &Arc<Self>, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let Some(token) = ctx.get_header("authorization").and_then(bearer_token) else {
+            return ready(deny(401, "Missing bearer token"));
+        };
+        let token = token.to_string();
+        let oidc = self.clone();
+        if self.use_introspection {
+            return Some(Box::pin(async move { oidc.introspect(token).await }));
+        }
+        if let (Some(keys), Some(metadata)) =
+            (self.provider.keys(), self.provider.metadata.load_full())
+        {
+            match verify_jwt(keys, &token, &metadata.issuer) {
+                Ok(claims) => {
+                    self.authenticate(ctx, &claims, &token);
+                    return None;
+                }
+                Err(Rejected::Invalid(e)) => {
+                    return ready(deny(401, &format!("Invalid token: {e}")));
+                }
+                Err(Rejected::UnknownKey) => {}
+            }
+        }
+        // Keys not loaded yet, or rotated: wait for the refresher.
+        Some(Box::pin(
+            async move { oidc.verify_when_loaded(token).await },
+        ))
+    }
+
+    async fn verify_when_loaded(&self, token: String) -> AsyncAccess {
+        let deadline = Instant::now() + self.timeout;
+        let metadata = match self.provider.load(self.timeout).await {
+            Ok(metadata) => metadata,
+            Err(e) => return unavailable(&e),
+        };
+        let Some(keys) = self.provider.keys() else {
+            return finished(deny(401, "Provider publishes no JWKS"));
+        };
+        loop {
+            match verify_jwt(keys, &token, &metadata.issuer) {
+                Ok(claims) => {
+                    return AsyncAccess {
+                        result: PluginResult::Continue,
+                        response_headers: Vec::new(),
+                        upstream_headers: self.identity_headers(&claims, &token),
+                    };
+                }
+                Err(Rejected::Invalid(e)) => {
+                    return finished(deny(401, &format!("Invalid token: {e}")));
+                }
+                Err(Rejected::UnknownKey) if Instant::now() < deadline => {
+                    background::sleep(KEY_WAIT_STEP).await;
+                }
+                Err(Rejected::UnknownKey) => {
+                    return finished(deny(401, "Invalid token: unknown signing key"));
+                }
+            }
+        }
+    }
+
+    async fn introspect(&self, token: String) -> AsyncAccess {
+        let metadata = match self.provider.load(self.timeout).await {
+            Ok(metadata) => metadata,
+            Err(e) => return unavailable(&e),
+        };
+        let Some(endpoint) = &metadata.introspection_endpoint else {
+            return unavailable("provider has no introspection endpoint");
+        };
+        let form = vec![
+            ("token", token.clone()),
+            ("token_type_hint", "access_token".to_string()),
+        ];
+        match self.post::<Value>(endpoint, form).await {
+            Ok(claims) if claims.get("active") == Some(&Value::Bool(true)) => AsyncAccess {
+                result: PluginResult::Continue,
+                response_headers: Vec::new(),
+                upstream_headers: self.identity_headers(&claims, &token),
+            },
+            Ok(_) => finished(deny(401, "Token is not active")),
+            Err(e) => unavailable(&e),
+        }
+    }
+
+    // ── Browser flow ──
+
+    fn browser(
+        self: &Arc<Self>,
+        cookies: &Arc<Cookies>,
+        ctx: &mut PluginContext,
+    ) -> Option<AccessFuture> {
+        let (path, query) = ctx.uri.split_once('?').unwrap_or((&ctx.uri, ""));
+        let secure = ctx.scheme == "https";
+        if path == self.callback_path {
+            return self.callback(cookies, ctx, query, secure);
+        }
+        let target = ctx.uri.clone();
+        let oidc = self.clone();
+        let cookies = cookies.clone();
+        match cookies.session(ctx) {
+            Some(session) if session.expires_at > unix_now() => {
+                self.authenticate(ctx, &session.claims, &session.access_token);
+                None
+            }
+            Some(session) if session.refresh_token.is_some() => {
+                self.expose_claims(ctx, &session.claims);
+                Some(Box::pin(async move {
+                    match oidc.refresh(session).await {
+                        Ok(session) => AsyncAccess {
+                            result: PluginResult::Continue,
+                            response_headers: vec![(
+                                "set-cookie".to_string(),
+                                cookies.set_session(&session, secure),
+                            )],
+                            upstream_headers: oidc
+                                .identity_headers(&session.claims, &session.access_token),
+                        },
+                        Err(e) => {
+                            debug!(error = %e, "OIDC token refresh failed");
+                            oidc.unauthenticated(&cookies, target, secure).await
+                        }
+                    }
+                }))
+            }
+            _ if self.unauth_action == UnauthAction::Pass => None,
+            _ => Some(Box::pin(async move {
+                oidc.unauthenticated(&cookies, target, secure).await
+            })),
+        }
+    }
+
+    async fn unauthenticated(
+        &self,
+        cookies: &Cookies,
+        target: String,
+        secure: bool,
+    ) -> AsyncAccess {
+        match self.unauth_action {
+            UnauthAction::Pass => finished(PluginResult::Continue),
+            UnauthAction::Deny => finished(deny(401, "Authentication required")),
+            UnauthAction::Auth => self.login(cookies, target, secure).await,
+        }
+    }
+
+    /// Redirect to the provider's authorization endpoint.
+    async fn login(&self, cookies: &Cookies, target: String, secure: bool) -> AsyncAccess {
+        let metadata = match self.provider.load(self.timeout).await {
+            Ok(metadata) => metadata,
+            Err(e) => return unavailable(&e),
+        };
+        let login = LoginState {
+            state: random_token(),
+            nonce: random_token(),
+            target,
+        };
+        let location = reqwest::Url::parse_with_params(
+            &metadata.authorization_endpoint,
+            [
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_uri),
+                ("scope", &self.scope),
+                ("state", &login.state),
+                ("nonce", &login.nonce),
+            ],
+        );
+        let Ok(location) = location else {
+            return unavailable("invalid authorization_endpoint");
+        };
+        let state = set_cookie(
+            &cookies.state,
+            &cookies.seal(&cookies.state, &login),
+            LOGIN_TTL,
+            secure,
+        );
+        finished(redirect(location.as_str(), vec![state]))
+    }
+
+    /// Complete a login: check the state, exchange the code for tokens and
+    /// issue the session cookie.
+    fn callback(
+        self: &Arc<Self>,
+        cookies: &Arc<Cookies>,
+        ctx: &PluginContext,
+        query: &str,
+        secure: bool,
+    ) -> Option<AccessFuture> {
+        let arg = |name| query_arg(query, name).map(|v| percent_decode(v).into_owned());
+        if let Some(error) = arg("error") {
+            return ready(deny(401, &format!("Login failed: {error}")));
+        }
+        let (Some(code), Some(state)) = (arg("code"), arg("state")) else {
+            return ready(deny(400, "Missing code or state"));
+        };
+        let login = request_cookie(ctx, &cookies.state)
+            .and_then(|value| cookies.open::<LoginState>(&cookies.state, value))
+            .filter(|login| login.state == state);
+        let Some(login) = login else {
+            return ready(deny(401, "Invalid login state"));
+        };
+        let oidc = self.clone();
+        let cookies = cookies.clone();
+        Some(Box::pin(async move {
+            match oidc.exchange(code, &login.nonce, cookies.lifetime).await {
+                Ok(session) => finished(redirect(
+                    &login.target,
+                    vec![
+                        cookies.set_session(&session, secure),
+                        set_cookie(&cookies.state, "", 0, secure),
+                    ],
+                )),
+                Err(e) => {
+                    warn!(error = %e, "OIDC login failed");
+                    finished(deny(401, "Login failed"))
+                }
+            }
+        }))
+    }
+
+    async fn exchange(&self, code: String, nonce: &str, lifetime: u64) -> Result<Session, String> {
+        let metadata = self.provider.load(self.timeout).await?;
+        let form = vec![
+            ("grant_type", "authorization_code".to_string()),
+            ("code", code),
+            ("redirect_uri", self.redirect_uri.clone()),
+        ];
+        let tokens: TokenResponse = self.post(&metadata.token_endpoint, form).await?;
+        let id_token = tokens.id_token.as_deref().ok_or("no ID token")?;
+        let claims = self.id_token_claims(id_token, &metadata.issuer, nonce)?;
+        let now = unix_now();
+        Ok(Session {
+            claims,
+            expires_at: now + tokens.expires_in.unwrap_or(lifetime),
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token,
+            ends_at: now + lifetime,
+        })
+    }
+
+    /// Claims of an ID token from the token endpoint. It came straight from
+    /// the provider over TLS, so (OIDC Core 3.1.3.7) its issuer, audience
+    /// and nonce are checked but not its signature.
+    fn id_token_claims(&self, id_token: &str, issuer: &str, nonce: &str) -> Result<Value, String> {
+        let claims: Value = id_token
+            .split('.')
+            .nth(1)
+            .and_then(|payload| URL_SAFE_NO_PAD.decode(payload).ok())
+            .and_then(|payload| serde_json::from_slice(&payload).ok())
+            .ok_or("malformed ID token")?;
+        if claims.get("iss").and_then(Value::as_str) != Some(issuer) {
+            return Err("ID token issuer mismatch".to_string());
+        }
+        let audience = match claims.get("aud") {
+            Some(Value::String(aud)) => aud == &self.client_id,
+            Some(Value::Array(auds)) => auds.iter().any(|aud| aud == self.client_id.as_str()),
+            _ => false,
+        };
+        if !audience {
+            return Err("ID token audience mismatch".to_string());
+        }
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            return Err("ID token nonce mismatch".to_string());
+        }
+        Ok(claims)
+    }
+
+    /// Renew an expired access token with the session's refresh token.
+    async fn refresh(&self, session: Session) -> Result<Session, String> {
+        let metadata = self.provider.load(self.timeout).await?;
+        let refresh_token = session.refresh_token.clone().unwrap_or_default();
+        let form = vec![
+            ("grant_type", "refresh_token".to_string()),
+            ("refresh_token", refresh_token),
+        ];
+        let tokens: TokenResponse = self.post(&metadata.token_endpoint, form).await?;
+        let lifetime = session.ends_at.saturating_sub(unix_now());
+        Ok(Session {
+            expires_at: unix_now() + tokens.expires_in.unwrap_or(lifetime),
+            access_token: tokens.access_token,
+            refresh_token: tokens.refresh_token.or(session.refresh_token),
+            ..session
+        })
+    }
+}
+
+fn verify_jwt(keys: &JwksSet, token: &str, issuer: &str) -> Result<Value, Rejected> {
+    let header = decode_header(token).map_err(|e| Rejected::Invalid(e.to_string()))?;
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[issuer]);
+    validation.required_spec_claims.insert("iss".to_string());
+    // Access tokens name the resource server, not this client.
+    validation.validate_aud = false;
+    keys.with_key(header.kid.as_deref(), |key| {
+        decode::<Value>(token, key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| Rejected::Invalid(e.to_string()))
+    })
+    .unwrap_or(Err(Rejected::UnknownKey))
+}
+
+struct OidcInstance {
+    oidc: Arc<Oidc>,
+}
+
+impl Plugin for OpenIdConnectPlugin {
+    fn name(&self) -> &str {
+        "openid-connect"
+    }
+
+    fn priority(&self) -> i32 {
+        2599 // APISIX default priority for openid-connect
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        Ok(Box::new(OidcInstance::new(config)?))
+    }
+}
+
+impl OidcInstance {
+    fn new(config: &serde_json::Value) -> anyhow::Result<Self> {
+        let cfg: OidcConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("openid-connect config error: {e}"))?;
+
+        let (cookies, redirect_uri, callback_path) = if cfg.bearer_only {
+            (None, String::new(), String::new())
+        } else {
+            let session = cfg.session.as_ref().ok_or_else(|| {
+                anyhow::anyhow!("openid-connect requires 'session.secret' unless 'bearer_only'")
+            })?;
+            if session.secret.len() < 16 {
+                anyhow::bail!("openid-connect 'session.secret' must be at least 16 characters");
+            }
+            let redirect_uri = cfg.redirect_uri.clone().ok_or_else(|| {
+                anyhow::anyhow!("openid-connect requires 'redirect_uri' unless 'bearer_only'")
+            })?;
+            let url = reqwest::Url::parse(&redirect_uri)
+                .map_err(|e| anyhow::anyhow!("invalid redirect_uri '{redirect_uri}': {e}"))?;
+            let path = url.path().to_string();
+            (Some(Arc::new(Cookies::new(session))), redirect_uri, path)
+        };
+
+        let timeout = Duration::from_millis(cfg.timeout.max(1));
+        Ok(Self {
+            oidc: Arc::new(Oidc {
+                provider: provider(
+                    &cfg.discovery,
+                    Duration::from_secs(cfg.jwks_refresh_interval.max(1)),
+                    timeout,
+                ),
+                client_id: cfg.client_id,
+                client_secret: cfg.client_secret,
+                scope: cfg.scope,
+                redirect_uri,
+                callback_path,
+                use_introspection: cfg.use_introspection,
+                unauth_action: cfg.unauth_action,
+                cookies,
+                set_userinfo_header: cfg.set_userinfo_header,
+                set_access_token_header: cfg.set_access_token_header,
+                timeout,
+            }),
+        })
+    }
+}
+
+impl PluginInstance for OidcInstance {
+    fn name(&self) -> &str {
+        "openid-connect"
+    }
+
+    fn priority(&self) -> i32 {
+        2599
+    }
+
+    fn has_async_access(&self) -> bool {
+        true
+    }
+
+    // Sessions and locally verified tokens are handled without awaiting;
+    // provider calls (discovery, code exchange, refresh, introspection)
+    // run on the background runtime.
+    fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        // The identity headers only ever carry what the plugin verified: a
+        // client's own go even when nothing replaces them.
+        ctx.request_headers.remove("x-userinfo");
+        ctx.request_headers.remove("x-access-token");
+        match &self.oidc.cookies {
+            Some(cookies) => self.oidc.browser(cookies, ctx),
+            None => self.oidc.bearer(ctx),
+        }
+    }
+}
+
+fn ready(result: PluginResult) -> Option<AccessFuture> {
+    Some(Box::pin(std::future::ready(finished(result))))
+}
+
+fn finished(result: PluginResult) -> AsyncAccess {
+    AsyncAccess {
+        result,
+        response_headers: Vec::new(),
+        upstream_headers: Vec::new(),
+    }
+}
+
+fn deny(status: u16, message: &str) -> PluginResult {
+    let mut headers = vec![("content-type".to_string(), "application/json".to_string())];
+    if status == 401 {
+        headers.push(("www-authenticate".to_string(), "Bearer".to_string()));
+    }
+    PluginResult::Response {
+        status,
+        headers,
+        body: Some(
+            json!({"error": message, "status": status})
+                .to_string()
+                .into_bytes(),
+        ),
+    }
+}
+
+fn unavailable(error: &str) -> AsyncAccess {
+    warn!(error = %error, "OIDC provider unavailable");
+    finished(deny(503, "Identity provider unavailable"))
+}
+
+fn redirect(location: &str, cookies: Vec<String>) -> PluginResult {
+    let mut headers = vec![
+        ("location".to_string(), location.to_string()),
+        ("cache-control".to_string(), "no-store".to_string()),
+    ];
+    headers.extend(
+        cookies
+            .into_iter()
+            .map(|cookie| ("set-cookie".to_string(), cookie)),
+    );
+    PluginResult::Response {
+        status: 302,
+        headers,
+        body: None,
+    }
+}
+
+fn set_cookie(name: &str, value: &str, max_age: u64, secure: bool) -> String {
+    let secure = if secure { "; Secure" } else { "" };
+    format!("{name}={value}; Path=/; Max-Age={max_age}; HttpOnly; SameSite=Lax{secure}")
+}
+
+fn request_cookie<'a>(ctx: &'a PluginContext, name: &str) -> Option<&'a str> {
+    ctx.get_headers_all("cookie")
+        .find_map(|header| cookie_value(header, name))
+}
+
+fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("system RNG available");
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header, encode};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    const JWKS: &str = r#"{"keys":[{"kty":"oct","kid":"k1","alg":"HS256","k":"c2VjcmV0LW9uZQ"}]}"#;
+    const SIGNING_SECRET: &[u8] = b"secret-one";
+    const SESSION_SECRET: &str = "0123456789abcdef";
+
+    /// A provider that issues an ID token whose nonce is the authorization
+    /// code, so a test can complete a login with the nonce it was sent.
+    fn mock_provider() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let issuer = base.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                serve(stream, &issuer);
+            }
+        });
+        base
+    }
+
+    fn serve(mut stream: TcpStream, issuer: &str) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            if line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                length = value.trim().parse().unwrap();
+            }
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        let form = String::from_utf8(body).unwrap();
+        let field = |name| query_arg(&form, name).map(|v| percent_decode(v).into_owned());
+
+        let path = request_line.split(' ').nth(1).unwrap_or("/");
+        let (status, body) = match path {
+            "/.well-known/openid-configuration" => (
+                200,
+                json!({
+                    "issuer": issuer,
+                    "authorization_endpoint": format!("{issuer}/authorize"),
+                    "token_endpoint": format!("{issuer}/token"),
+                    "jwks_uri": format!("{issuer}/jwks"),
+                    "introspection_endpoint": format!("{issuer}/introspect"),
+                }),
+            ),
+            "/jwks" => (200, serde_json::from_str(JWKS).unwrap()),
+            "/token" => match field("grant_type").as_deref() {
+                _ if field("client_secret").as_deref() != Some("s3cret") => (401, json!({})),
+                Some("authorization_code") => {
+                    let nonce = field("code").unwrap();
+                    let claims = json!({
+                        "iss": issuer, "aud": "gateway", "sub": "alice",
+                        "email": "alice@example.com", "nonce": nonce,
+                    });
+                    let id_token =
+                        format!("e30.{}.sig", URL_SAFE_NO_PAD.encode(claims.to_string()));
+                    (
+                        200,
+                        json!({"access_token": "at-1", "id_token": id_token,
+                               "refresh_token": "rt-1", "expires_in": 300}),
+                    )
+                }
+                Some("refresh_token") if field("refresh_token").as_deref() == Some("rt-1") => {
+                    (200, json!({"access_token": "at-2", "expires_in": 300}))
+                }
+                _ => (400, json!({"error": "invalid_grant"})),
+            },
+            "/introspect" => match field("token").as_deref() {
+                Some("good") => (200, json!({"active": true, "sub": "bob"})),
+                _ => (200, json!({"active": false})),
+            },
+            _ => (404, json!({})),
+        };
+        let body = body.to_string();
+        let resp = format!(
+            "HTTP/1.1 {status} X\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let _ = stream.write_all(resp.as_bytes());
+    }
+
+    fn browser(base: &str, extra: Value) -> OidcInstance {
+        let mut config = json!({
+            "discovery": format!("{base}/.well-known/openid-configuration"),
+            "client_id": "gateway",
+            "client_secret": "s3cret",
+            "redirect_uri": "http://gw.example.com/callback",
+            "session": {"secret": SESSION_SECRET},
+        });
+        config
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        OidcInstance::new(&config).unwrap()
+    }
+
+    fn bearer(base: &str, mut extra: Value) -> OidcInstance {
+        extra["bearer_only"] = json!(true);
+        browser(base, extra)
+    }
+
+    fn make_ctx(uri: &str, headers: &[(&str, &str)]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "1.1.1.1".into(),
+            "GET".into(),
+            uri.into(),
+            HashMap::new(),
+        );
+        for (name, value) in headers {
+            ctx.append_header(*name, *value);
+        }
+        ctx
+    }
+
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    fn run(inst: &OidcInstance, ctx: &mut PluginContext) -> Option<AsyncAccess> {
+        inst.access_async(ctx).map(block_on)
+    }
+
+    /// Status and headers of a response, or panic.
+    fn response(outcome: Option<AsyncAccess>) -> (u16, Vec<(String, String)>) {
+        match outcome.map(|o| o.result) {
+            Some(PluginResult::Response {
+                status, headers, ..
+            }) => (status, headers),
+            _ => panic!("expected a response"),
+        }
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Vec<&'a str> {
+        headers
+            .iter()
+            .filter(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
+    /// `name=value` of a `Set-Cookie` header.
+    fn cookie_pair(set_cookie: &str) -> String {
+        set_cookie.split(';').next().unwrap().to_string()
+    }
+
+    /// Start a login at `uri`: the redirect's query and the state cookie.
+    fn start_login(inst: &OidcInstance, uri: &str) -> (HashMap<String, String>, String) {
+        let (status, headers) = response(run(inst, &mut make_ctx(uri, &[])));
+        assert_eq!(status, 302);
+        let location = reqwest::Url::parse(header(&headers, "location")[0]).unwrap();
+        let query = location.query_pairs().into_owned().collect();
+        (query, cookie_pair(header(&headers, "set-cookie")[0]))
+    }
+
+    /// Log in and return the session cookie.
+    fn login(inst: &OidcInstance) -> String {
+        let (query, state_cookie) = start_login(inst, "/app?x=1");
+        let callback = format!("/callback?code={}&state={}", query["nonce"], query["state"]);
+        let (status, headers) = response(run(
+            inst,
+            &mut make_ctx(&callback, &[("Cookie", &state_cookie)]),
+        ));
+        assert_eq!(status, 302);
+        assert_eq!(header(&headers, "location"), ["/app?x=1"]);
+        let cookies = header(&headers, "set-cookie");
+        assert!(cookies[1].starts_with("ando_session_state=; Path=/; Max-Age=0"));
+        cookie_pair(cookies[0])
+    }
+
+    fn userinfo(headers: &HashMap<String, String>) -> Value {
+        serde_json::from_slice(&STANDARD.decode(&headers["x-userinfo"]).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn browser_flow_requires_session_secret_and_redirect_uri() {
+        let config = |extra: Value| {
+            let mut config = json!({"discovery": "http://127.0.0.1:9/d", "client_id": "c"});
+            config
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            OpenIdConnectPlugin.configure(&config)
+        };
+        assert!(config(json!({"redirect_uri": "http://gw/cb"})).is_err());
+        assert!(
+            config(json!({"redirect_uri": "http://gw/cb", "session": {"secret": "short"}}))
+                .is_err()
+        );
+        assert!(config(json!({"session": {"secret": SESSION_SECRET}})).is_err());
+        assert!(
+            config(json!({"redirect_uri": "/cb", "session": {"secret": SESSION_SECRET}})).is_err()
+        );
+        assert!(config(json!({"bearer_only": true})).is_ok());
+        assert!(
+            config(json!({"redirect_uri": "http://gw/cb", "session": {"secret": SESSION_SECRET}}))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn unauthenticated_request_redirects_to_provider() {
+        let base = mock_provider();
+        let inst = browser(&base, json!({"scope": "openid email"}));
+        let (status, headers) = response(run(&inst, &mut make_ctx("/app", &[])));
+        assert_eq!(status, 302);
+        let location = reqwest::Url::parse(header(&headers, "location")[0]).unwrap();
+        assert_eq!(
+            location.as_str().split('?').next(),
+            Some(&*format!("{base}/authorize"))
+        );
+        let query: HashMap<_, _> = location.query_pairs().into_owned().collect();
+        assert_eq!(query["response_type"], "code");
+        assert_eq!(query["client_id"], "gateway");
+        assert_eq!(query["redirect_uri"], "http://gw.example.com/callback");
+        assert_eq!(query["scope"], "openid email");
+        assert!(!query["state"].is_empty() && !query["nonce"].is_empty());
+        let state = header(&headers, "set-cookie")[0];
+        assert!(state.starts_with("ando_session_state="));
+        assert!(state.contains("HttpOnly") && !state.contains("Secure"));
+    }
+
+    #[test]
+    fn callback_issues_session_cookie_that_authenticates() {
+        let inst = browser(&mock_provider(), json!({}));
+        let session = login(&inst);
+        assert!(session.starts_with("ando_session="));
+
+        let mut ctx = make_ctx("/app", &[("Cookie", &format!("theme=dark; {session}"))]);
+        assert!(run(&inst, &mut ctx).is_none());
+        assert_eq!(ctx.vars["_oidc_sub"], "alice");
+        assert_eq!(ctx.vars["_oidc_claims"]["email"], "alice@example.com");
+        assert_eq!(ctx.upstream_headers["x-access-token"], "at-1");
+        assert_eq!(userinfo(&ctx.upstream_headers)["sub"], "alice");
+    }
+
+    #[test]
+    fn callback_rejects_forged_or_missing_state() {
+        let inst = browser(&mock_provider(), json!({}));
+        let (query, state_cookie) = start_login(&inst, "/app");
+        let forged = format!("/callback?code={}&state=forged", query["nonce"]);
+        let (status, _) = response(run(
+            &inst,
+            &mut make_ctx(&forged, &[("Cookie", &state_cookie)]),
+        ));
+        assert_eq!(status, 401);
+
+        let callback = format!("/callback?code={}&state={}", query["nonce"], query["state"]);
+        let (status, _) = response(run(&inst, &mut make_ctx(&callback, &[])));
+        assert_eq!(status, 401);
+
+        // The provider's ID token carries the code as its nonce.
+        let wrong_nonce = format!("/callback?code=other&state={}", query["state"]);
+        let (status, _) = response(run(
+            &inst,
+            &mut make_ctx(&wrong_nonce, &[("Cookie", &state_cookie)]),
+        ));
+        assert_eq!(status, 401);
+
+        let (status, _) = response(run(
+            &inst,
+            &mut make_ctx("/callback?error=access_denied", &[]),
+        ));
+        assert_eq!(status, 401);
+    }
+
+    #[test]
+    fn tampered_or_foreign_cookies_are_not_sessions() {
+        let inst = browser(&mock_provider(), json!({}));
+        let session = login(&inst);
+        let mut tampered = session.clone();
+        let last = tampered.pop().unwrap();
+        tampered.push(if last == 'A' { 'B' } else { 'A' });
+        let (status, _) = response(run(&inst, &mut make_ctx("/app", &[("Cookie", &tampered)])));
+        assert_eq!(status, 302);
+
+        // A state cookie's value does not decrypt as a session.
+        let (_, state_cookie) = start_login(&inst, "/app");
+        let value = state_cookie.split_once('=').unwrap().1;
+        let foreign = format!("ando_session={value}");
+        let (status, _) = response(run(&inst, &mut make_ctx("/app", &[("Cookie", &foreign)])));
+        assert_eq!(status, 302);
+
+        // Another secret cannot read it.
+        let other = browser(
+            &mock_provider(),
+            json!({"session": {"secret": "fedcba9876543210"}}),
+        );
+        let (status, _) = response(run(&other, &mut make_ctx("/app", &[("Cookie", &session)])));
+        assert_eq!(status, 302);
+    }
+
+    fn sealed_session(inst: &OidcInstance, refresh_token: Option<&str>) -> String {
+        let cookies = inst.oidc.cookies.as_ref().unwrap();
+        let now = unix_now();
+        let session = Session {
+            claims: json!({"sub": "alice"}),
+            access_token: "at-1".to_string(),
+            refresh_token: refresh_token.map(str::to_string),
+            expires_at: now - 1,
+            ends_at: now + 3600,
+        };
+        format!("ando_session={}", cookies.seal("ando_session", &session))
+    }
+
+    #[test]
+    fn expired_access_token_is_refreshed() {
+        let inst = browser(&mock_provider(), json!({}));
+        let cookie = sealed_session(&inst, Some("rt-1"));
+        let mut ctx = make_ctx("/app", &[("Cookie", &cookie)]);
+        let outcome = run(&inst, &mut ctx).expect("refresh");
+        assert!(matches!(outcome.result, PluginResult::Continue));
+        assert_eq!(ctx.vars["_oidc_sub"], "alice");
+        let upstream: HashMap<_, _> = outcome.upstream_headers.into_iter().collect();
+        assert_eq!(upstream["x-access-token"], "at-2");
+        assert_eq!(userinfo(&upstream)["sub"], "alice");
+
+        // The renewed session keeps the refresh token and authenticates.
+        let renewed = cookie_pair(header(&outcome.response_headers, "set-cookie")[0]);
+        let mut ctx = make_ctx("/app", &[("Cookie", &renewed)]);
+        assert!(run(&inst, &mut ctx).is_none());
+        assert_eq!(ctx.upstream_headers["x-access-token"], "at-2");
+    }
+
+    #[test]
+    fn expired_session_without_valid_refresh_logs_in_again() {
+        let inst = browser(&mock_provider(), json!({}));
+        for refresh in [None, Some("revoked")] {
+            let cookie = sealed_session(&inst, refresh);
+            let (status, headers) =
+                response(run(&inst, &mut make_ctx("/app", &[("Cookie", &cookie)])));
+            assert_eq!(status, 302);
+            assert!(header(&headers, "location")[0].contains("/authorize?"));
+        }
+    }
+
+    #[test]
+    fn unauth_action_deny_and_pass() {
+        let base = mock_provider();
+        let deny = browser(&base, json!({"unauth_action": "deny"}));
+        let (status, _) = response(run(&deny, &mut make_ctx("/app", &[])));
+        assert_eq!(status, 401);
+
+        let pass = browser(&base, json!({"unauth_action": "pass"}));
+        let mut ctx = make_ctx(
+            "/app",
+            &[
+                ("X-Userinfo", "eyJzdWIiOiJyb290In0="),
+                ("X-Access-Token", "forged"),
+            ],
+        );
+        assert!(run(&pass, &mut ctx).is_none());
+        assert!(ctx.upstream_headers.is_empty());
+        assert!(!ctx.request_headers.contains_key("x-userinfo"));
+        assert!(!ctx.request_headers.contains_key("x-access-token"));
+    }
+
+    #[test]
+    fn client_identity_headers_are_dropped_when_not_set() {
+        let inst = browser(
+            &mock_provider(),
+            json!({"set_userinfo_header": false, "set_access_token_header": false}),
+        );
+        let session = login(&inst);
+        let mut ctx = make_ctx(
+            "/app",
+            &[
+                ("Cookie", &session),
+                ("X-Userinfo", "eyJzdWIiOiJyb290In0="),
+                ("X-Access-Token", "forged"),
+            ],
+        );
+        assert!(run(&inst, &mut ctx).is_none());
+        assert_eq!(ctx.vars["_oidc_sub"], "alice");
+        assert!(ctx.upstream_headers.is_empty());
+        assert!(!ctx.request_headers.contains_key("x-userinfo"));
+        assert!(!ctx.request_headers.contains_key("x-access-token"));
+    }
+
+    fn jwt(issuer: &str, key: &[u8], exp_offset: i64) -> String {
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("k1".to_string());
+        let exp = unix_now() as i64 + exp_offset;
+        let claims = json!({"iss": issuer, "sub": "carol", "exp": exp});
+        encode(&header, &claims, &EncodingKey::from_secret(key)).unwrap()
+    }
+
+    #[test]
+    fn bearer_only_verifies_tokens_against_provider_jwks() {
+        let base = mock_provider();
+        let inst = bearer(&base, json!({}));
+        let token = format!("Bearer {}", jwt(&base, SIGNING_SECRET, 300));
+
+        // The first request may wait for the keys; later ones are checked
+        // without awaiting.
+        let mut ctx = make_ctx("/api", &[("Authorization", &token)]);
+        if let Some(outcome) = run(&inst, &mut ctx) {
+            assert!(matches!(outcome.result, PluginResult::Continue));
+            let upstream: HashMap<_, _> = outcome.upstream_headers.into_iter().collect();
+            assert_eq!(userinfo(&upstream)["sub"], "carol");
+        }
+        let mut ctx = make_ctx("/api", &[("Authorization", &token)]);
+        assert!(run(&inst, &mut ctx).is_none());
+        assert_eq!(ctx.vars["_oidc_sub"], "carol");
+
+        for bad in [
+            jwt(&base, b"wrong-secret", 300),
+            jwt(&base, SIGNING_SECRET, -300),
+            jwt("https://other-issuer", SIGNING_SECRET, 300),
+        ] {
+            let auth = format!("Bearer {bad}");
+            let (status, _) = response(run(
+                &inst,
+                &mut make_ctx("/api", &[("Authorization", &auth)]),
+            ));
+            assert_eq!(status, 401);
+        }
+        let (status, headers) = response(run(&inst, &mut make_ctx("/api", &[])));
+        assert_eq!(status, 401);
+        assert_eq!(header(&headers, "www-authenticate"), ["Bearer"]);
+    }
+
+    #[test]
+    fn bearer_only_can_introspect_tokens() {
+        let inst = bearer(&mock_provider(), json!({"use_introspection": true}));
+        let outcome = run(
+            &inst,
+            &mut make_ctx("/api", &[("Authorization", "Bearer good")]),
+        )
+        .expect("introspection");
+        assert!(matches!(outcome.result, PluginResult::Continue));
+        let upstream: HashMap<_, _> = outcome.upstream_headers.into_iter().collect();
+        assert_eq!(upstream["x-access-token"], "good");
+        assert_eq!(userinfo(&upstream)["sub"], "bob");
+
+        let (status, _) = response(run(
+            &inst,
+            &mut make_ctx("/api", &[("Authorization", "Bearer revoked")]),
+        ));
+        assert_eq!(status, 401);
+    }
+
+    #[test]
+    fn unreachable_provider_is_503() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let inst = browser(&base, json!({"timeout": 500}));
+        let (status, _) = response(run(&inst, &mut make_ctx("/app", &[])));
+        assert_eq!(status, 503);
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// Background runtime for plugin I/O and timers.
+///
+/// v2 design: Workers run on monoio, which cannot drive tokio sockets or
+/// timers. Plugins spawn that work here and hand results back over a
+/// oneshot channel, which any executor can await.
+pub fn runtime() -> &'static Runtime {
+    static RT: OnceLock<Runtime> = OnceLock::new();
+    RT.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("ando-plugin-bg")
+            .enable_all()
+            .build()
+            .expect("Failed to build background runtime for plugins")
+    })
+}
+
+/// Sleep for `duration` on the background runtime. The returned future can
+/// be awaited from any executor.
+pub fn sleep(duration: Duration) -> impl Future<Output = ()> + 'static {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    runtime().spawn(async move {
+        tokio::time::sleep(duration).await;
+        let _ = tx.send(());
+    });
+    async move {
+        let _ = rx.await;
+    }
+}
+
+/// Run `fut` on the background runtime. The returned future can be awaited
+/// from any executor; it yields `None` if the task panicked.
+pub fn spawn<T: Send + 'static>(
+    fut: impl Future<Output = T> + Send + 'static,
+) -> impl Future<Output = Option<T>> + 'static {
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    runtime().spawn(async move {
+        let _ = tx.send(fut.await);
+    });
+    async move { rx.await.ok() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn sleep_completes_on_a_foreign_executor() {
+        let start = Instant::now();
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(sleep(Duration::from_millis(20)));
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
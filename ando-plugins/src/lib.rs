@@ -1,4 +1,5 @@
 pub mod auth;
+pub mod background;
 pub mod traffic;
 
 use ando_plugin::registry::PluginRegistry;
@@ -9,8 +10,48 @@ pub fn register_all(registry: &mut PluginRegistry) {
     registry.register(Arc::new(auth::key_auth::KeyAuthPlugin));
     registry.register(Arc::new(auth::basic_auth::BasicAuthPlugin));
     registry.register(Arc::new(auth::jwt_auth::JwtAuthPlugin));
+    registry.register(Arc::new(auth::hmac_auth::HmacAuthPlugin));
+    registry.register(Arc::new(auth::mtls_auth::MtlsAuthPlugin));
+    registry.register(Arc::new(
+        auth::consumer_restriction::ConsumerRestrictionPlugin,
+    ));
+    registry.register(Arc::new(auth::openid_connect::OpenIdConnectPlugin));
+    registry.register(Arc::new(traffic::real_ip::RealIpPlugin));
+    registry.register(Arc::new(
+        traffic::fault_injection::FaultInjectionPlugin::new(),
+    ));
+    registry.register(Arc::new(traffic::client_control::ClientControlPlugin));
     registry.register(Arc::new(traffic::ip_restriction::IpRestrictionPlugin));
+    registry.register(Arc::new(traffic::ua_restriction::UaRestrictionPlugin));
+    registry.register(Arc::new(
+        traffic::time_restriction::TimeRestrictionPlugin::new(),
+    ));
+    registry.register(Arc::new(
+        traffic::referer_restriction::RefererRestrictionPlugin,
+    ));
+    registry.register(Arc::new(
+        traffic::request_validation::RequestValidationPlugin,
+    ));
     registry.register(Arc::new(traffic::rate_limiting::RateLimitingPlugin));
+    registry.register(Arc::new(traffic::limit_req::LimitReqPlugin));
+    registry.register(Arc::new(traffic::limit_conn::LimitConnPlugin::new()));
+    registry.register(Arc::new(traffic::api_breaker::ApiBreakerPlugin::new()));
+    registry.register(Arc::new(traffic::mock::MockPlugin));
+    registry.register(Arc::new(traffic::proxy_cache::ProxyCachePlugin::new()));
+    registry.register(Arc::new(traffic::proxy_mirror::ProxyMirrorPlugin::new()));
+    registry.register(Arc::new(traffic::traffic_split::TrafficSplitPlugin::new()));
+    registry.register(Arc::new(traffic::body_routing::BodyRoutingPlugin));
+    registry.register(Arc::new(
+        traffic::request_transformer::RequestTransformerPlugin,
+    ));
+    registry.register(Arc::new(traffic::debug_echo::DebugEchoPlugin));
     registry.register(Arc::new(traffic::cors::CorsPlugin));
     registry.register(Arc::new(traffic::security_headers::SecurityHeadersPlugin));
+    registry.register(Arc::new(
+        traffic::response_headers_policy::ResponseHeadersPolicyPlugin,
+    ));
+    registry.register(Arc::new(
+        traffic::response_transformer::ResponseTransformerPlugin,
+    ));
+    registry.register(Arc::new(traffic::error_page::ErrorPagePlugin));
 }
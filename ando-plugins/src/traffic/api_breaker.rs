@@ -0,0 +1,440 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Length of the first open window; each consecutive trip doubles it.
+const BASE_BREAK: Duration = Duration::from_secs(2);
+
+/// API breaker plugin — a route-level circuit breaker driven by upstream
+/// status codes.
+///
+/// v2 design: Breaker state lives in a map owned by the plugin factory and
+/// keyed by route id, so every worker (and every rebuilt pipeline) sees the
+/// same state. The log phase feeds it statuses; the access phase rejects
+/// requests while the breaker is open.
+#[derive(Default)]
+pub struct ApiBreakerPlugin {
+    breakers: Arc<DashMap<String, Breaker>>,
+}
+
+impl ApiBreakerPlugin {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiBreakerConfig {
+    /// Status returned while the breaker is open.
+    break_response_code: u16,
+    #[serde(default)]
+    break_response_body: Option<String>,
+    /// Upper bound on the open window, in seconds.
+    #[serde(default = "default_max_breaker_sec")]
+    max_breaker_sec: u64,
+    #[serde(default)]
+    unhealthy: UnhealthyConfig,
+    #[serde(default)]
+    healthy: HealthyConfig,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnhealthyConfig {
+    #[serde(default = "default_unhealthy_statuses")]
+    http_statuses: Vec<u16>,
+    /// Consecutive unhealthy responses that trip the breaker.
+    #[serde(default = "default_threshold")]
+    failures: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct HealthyConfig {
+    #[serde(default = "default_healthy_statuses")]
+    http_statuses: Vec<u16>,
+    /// Healthy responses needed after a trip to close the breaker.
+    #[serde(default = "default_threshold")]
+    successes: u32,
+}
+
+impl Default for UnhealthyConfig {
+    fn default() -> Self {
+        Self {
+            http_statuses: default_unhealthy_statuses(),
+            failures: default_threshold(),
+        }
+    }
+}
+
+impl Default for HealthyConfig {
+    fn default() -> Self {
+        Self {
+            http_statuses: default_healthy_statuses(),
+            successes: default_threshold(),
+        }
+    }
+}
+
+fn default_max_breaker_sec() -> u64 {
+    300
+}
+fn default_unhealthy_statuses() -> Vec<u16> {
+    vec![500]
+}
+fn default_healthy_statuses() -> Vec<u16> {
+    vec![200]
+}
+fn default_threshold() -> u32 {
+    3
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BreakerState {
+    Closed,
+    /// Rejecting requests until the window ends.
+    Open,
+    /// Window over; requests pass and their statuses decide what is next.
+    HalfOpen,
+}
+
+/// Breaker state for one route.
+#[derive(Debug, Default)]
+struct Breaker {
+    /// Consecutive unhealthy responses while closed.
+    failures: u32,
+    /// Healthy responses while half-open.
+    successes: u32,
+    /// Consecutive trips without recovering; sets the window length.
+    trips: u32,
+    open_until: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+struct Policy {
+    unhealthy_statuses: Vec<u16>,
+    failures: u32,
+    healthy_statuses: Vec<u16>,
+    successes: u32,
+    max_break: Duration,
+}
+
+impl Policy {
+    /// Open window after the `trips`-th consecutive trip.
+    fn window(&self, trips: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(trips.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        BASE_BREAK.saturating_mul(factor).min(self.max_break)
+    }
+}
+
+impl Breaker {
+    fn state(&self, now: Instant) -> BreakerState {
+        match self.open_until {
+            Some(until) if now < until => BreakerState::Open,
+            _ if self.trips > 0 => BreakerState::HalfOpen,
+            _ => BreakerState::Closed,
+        }
+    }
+
+    fn record(&mut self, status: u16, policy: &Policy, now: Instant) {
+        let state = self.state(now);
+        if state == BreakerState::Open {
+            // Stragglers from before the trip.
+            return;
+        }
+        if policy.unhealthy_statuses.contains(&status) {
+            self.successes = 0;
+            self.failures += 1;
+            if state == BreakerState::HalfOpen || self.failures >= policy.failures {
+                self.trips += 1;
+                self.failures = 0;
+                self.open_until = Some(now + policy.window(self.trips));
+            }
+        } else if policy.healthy_statuses.contains(&status) {
+            self.failures = 0;
+            if state == BreakerState::HalfOpen {
+                self.successes += 1;
+                if self.successes >= policy.successes {
+                    *self = Breaker::default();
+                }
+            }
+        }
+    }
+}
+
+struct ApiBreakerInstance {
+    breakers: Arc<DashMap<String, Breaker>>,
+    policy: Policy,
+    break_code: u16,
+    break_body: Vec<u8>,
+}
+
+impl Plugin for ApiBreakerPlugin {
+    fn name(&self) -> &str {
+        "api-breaker"
+    }
+
+    fn priority(&self) -> i32 {
+        1005
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access, Phase::Log]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ApiBreakerConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("api-breaker config error: {e}"))?;
+
+        if !(200..=599).contains(&cfg.break_response_code) {
+            anyhow::bail!("api-breaker: break_response_code must be between 200 and 599");
+        }
+        if cfg.max_breaker_sec < 3 {
+            anyhow::bail!("api-breaker: max_breaker_sec must be at least 3");
+        }
+        if cfg.unhealthy.failures == 0 || cfg.healthy.successes == 0 {
+            anyhow::bail!("api-breaker: failures and successes must be at least 1");
+        }
+        let statuses = cfg
+            .unhealthy
+            .http_statuses
+            .iter()
+            .chain(&cfg.healthy.http_statuses);
+        if let Some(s) = statuses.clone().find(|s| !(200..=599).contains(*s)) {
+            anyhow::bail!("api-breaker: invalid http status {s}");
+        }
+
+        Ok(Box::new(ApiBreakerInstance {
+            breakers: Arc::clone(&self.breakers),
+            policy: Policy {
+                unhealthy_statuses: cfg.unhealthy.http_statuses,
+                failures: cfg.unhealthy.failures,
+                healthy_statuses: cfg.healthy.http_statuses,
+                successes: cfg.healthy.successes,
+                max_break: Duration::from_secs(cfg.max_breaker_sec),
+            },
+            break_code: cfg.break_response_code,
+            break_body: cfg.break_response_body.unwrap_or_default().into_bytes(),
+        }))
+    }
+}
+
+impl ApiBreakerInstance {
+    fn is_open(&self, route_id: &str, now: Instant) -> bool {
+        self.breakers
+            .get(route_id)
+            .is_some_and(|b| b.state(now) == BreakerState::Open)
+    }
+
+    fn record(&self, route_id: &str, status: u16, now: Instant) {
+        let tracked = self.policy.unhealthy_statuses.contains(&status)
+            || self.policy.healthy_statuses.contains(&status);
+        if !tracked {
+            return;
+        }
+        if let Some(mut breaker) = self.breakers.get_mut(route_id) {
+            breaker.record(status, &self.policy, now);
+            return;
+        }
+        // Routes that have only seen healthy traffic stay out of the map.
+        if self.policy.unhealthy_statuses.contains(&status) {
+            self.breakers
+                .entry(route_id.to_string())
+                .or_default()
+                .record(status, &self.policy, now);
+        }
+    }
+}
+
+impl PluginInstance for ApiBreakerInstance {
+    fn name(&self) -> &str {
+        "api-breaker"
+    }
+
+    fn priority(&self) -> i32 {
+        1005
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        if !self.is_open(&ctx.route_id, Instant::now()) {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: self.break_code,
+            headers: Vec::new(),
+            body: Some(self.break_body.clone()),
+        }
+    }
+
+    fn logs(&self) -> bool {
+        true
+    }
+
+    fn log(&self, ctx: &PluginContext) {
+        if let Some(status) = ctx.response_status {
+            self.record(&ctx.route_id, status, Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(failures: u32, successes: u32, max_secs: u64) -> Policy {
+        Policy {
+            unhealthy_statuses: vec![500, 502],
+            failures,
+            healthy_statuses: vec![200],
+            successes,
+            max_break: Duration::from_secs(max_secs),
+        }
+    }
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    #[test]
+    fn trips_after_consecutive_failures() {
+        let p = policy(3, 2, 300);
+        let t0 = Instant::now();
+        let mut b = Breaker::default();
+        b.record(500, &p, t0);
+        b.record(502, &p, t0);
+        assert_eq!(b.state(t0), BreakerState::Closed);
+        b.record(500, &p, t0);
+        assert_eq!(b.state(t0), BreakerState::Open);
+        assert_eq!(b.state(t0 + secs(1)), BreakerState::Open);
+        assert_eq!(b.state(t0 + secs(2)), BreakerState::HalfOpen);
+    }
+
+    #[test]
+    fn healthy_response_resets_failure_streak() {
+        let p = policy(3, 2, 300);
+        let t0 = Instant::now();
+        let mut b = Breaker::default();
+        for status in [500, 500, 200, 500, 500] {
+            b.record(status, &p, t0);
+        }
+        assert_eq!(b.state(t0), BreakerState::Closed);
+        // Untracked statuses neither count nor reset.
+        b.record(404, &p, t0);
+        b.record(500, &p, t0);
+        assert_eq!(b.state(t0), BreakerState::Open);
+    }
+
+    #[test]
+    fn open_half_open_closed_transitions() {
+        let p = policy(1, 2, 300);
+        let t0 = Instant::now();
+        let mut b = Breaker::default();
+        b.record(500, &p, t0);
+        assert_eq!(b.state(t0), BreakerState::Open);
+
+        // Statuses of requests that were in flight during the window are
+        // ignored.
+        b.record(200, &p, t0 + secs(1));
+        assert_eq!(b.state(t0 + secs(1)), BreakerState::Open);
+
+        let t1 = t0 + secs(2);
+        assert_eq!(b.state(t1), BreakerState::HalfOpen);
+        b.record(200, &p, t1);
+        assert_eq!(b.state(t1), BreakerState::HalfOpen);
+        b.record(200, &p, t1);
+        assert_eq!(b.state(t1), BreakerState::Closed);
+        assert_eq!(b.trips, 0);
+    }
+
+    #[test]
+    fn failure_while_half_open_reopens_with_doubled_window() {
+        let p = policy(1, 1, 300);
+        let t0 = Instant::now();
+        let mut b = Breaker::default();
+        b.record(500, &p, t0);
+        let t1 = t0 + secs(2);
+        b.record(500, &p, t1);
+        assert_eq!(b.trips, 2);
+        assert_eq!(b.state(t1 + secs(3)), BreakerState::Open);
+        assert_eq!(b.state(t1 + secs(4)), BreakerState::HalfOpen);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let p = policy(1, 1, 10);
+        let windows: Vec<u64> = (1..=6).map(|t| p.window(t).as_secs()).collect();
+        assert_eq!(windows, vec![2, 4, 8, 10, 10, 10]);
+        assert_eq!(p.window(200), secs(10));
+    }
+
+    fn instance(plugin: &ApiBreakerPlugin) -> Box<dyn PluginInstance> {
+        plugin
+            .configure(&serde_json::json!({
+                "break_response_code": 503,
+                "break_response_body": "breaker open",
+                "unhealthy": { "http_statuses": [500], "failures": 2 }
+            }))
+            .unwrap()
+    }
+
+    fn ctx(route_id: &str, status: Option<u16>) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            route_id.into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            Default::default(),
+        );
+        ctx.response_status = status;
+        ctx
+    }
+
+    #[test]
+    fn state_is_shared_across_instances_and_keyed_by_route() {
+        let plugin = ApiBreakerPlugin::new();
+        // Two workers' pipelines for the same route.
+        let a = instance(&plugin);
+        let b = instance(&plugin);
+        assert!(a.logs());
+
+        a.log(&ctx("r1", Some(500)));
+        b.log(&ctx("r1", Some(500)));
+        match a.access(&mut ctx("r1", None)) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 503);
+                assert_eq!(body.unwrap(), b"breaker open");
+            }
+            PluginResult::Continue => panic!("breaker should be open"),
+        }
+        assert!(matches!(
+            b.access(&mut ctx("r2", None)),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn healthy_routes_are_not_tracked() {
+        let plugin = ApiBreakerPlugin::new();
+        let inst = instance(&plugin);
+        for _ in 0..10 {
+            inst.log(&ctx("r1", Some(200)));
+        }
+        assert!(plugin.breakers.is_empty());
+    }
+
+    #[test]
+    fn configure_validates_settings() {
+        let plugin = ApiBreakerPlugin::new();
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "break_response_code": 100 }),
+            serde_json::json!({ "break_response_code": 503, "max_breaker_sec": 2 }),
+            serde_json::json!({ "break_response_code": 503, "unhealthy": { "failures": 0 } }),
+            serde_json::json!({ "break_response_code": 503, "healthy": { "http_statuses": [42] } }),
+        ] {
+            assert!(plugin.configure(&config).is_err(), "{config}");
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+use serde_json::Value;
+
+/// Body routing plugin — sends requests to another upstream by a field of
+/// their JSON body, e.g. `"version": 2` to a new service.
+///
+/// Rules are tried in order; the first that holds sets the upstream, like
+/// traffic-split does. It runs after traffic-split, so a matching rule
+/// takes precedence over traffic-split's pick, and no match leaves that
+/// pick (or the route's upstream) in place. Bodies that are not JSON,
+/// larger than `max_body` or streamed past the buffered part are not
+/// evaluated.
+pub struct BodyRoutingPlugin;
+
+#[derive(Debug, Deserialize)]
+struct BodyRoutingConfig {
+    rules: Vec<RuleConfig>,
+    /// Largest body evaluated, in bytes — default 1 MiB.
+    #[serde(default = "default_max_body")]
+    max_body: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+    /// RFC 6901 JSON Pointer into the body, e.g. `/version`.
+    pointer: String,
+    /// `==`, `!=` or `exists` — default `==`.
+    #[serde(default = "default_op")]
+    op: String,
+    #[serde(default)]
+    value: Option<Value>,
+    upstream_id: Option<String>,
+    /// Node (`host:port`) to proxy to directly.
+    node: Option<String>,
+}
+
+fn default_max_body() -> u64 {
+    1024 * 1024
+}
+fn default_op() -> String {
+    "==".to_string()
+}
+
+struct BodyRoutingInstance {
+    rules: Vec<Rule>,
+    max_body: u64,
+}
+
+struct Rule {
+    pointer: String,
+    op: Op,
+    target: Target,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Op {
+    Eq(Value),
+    Ne(Value),
+    Exists,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    Upstream(String),
+    Node(String),
+}
+
+impl Rule {
+    fn compile(cfg: RuleConfig) -> Result<Self, String> {
+        if !cfg.pointer.is_empty() && !cfg.pointer.starts_with('/') {
+            return Err(format!(
+                "pointer '{}' must be empty or start with '/'",
+                cfg.pointer
+            ));
+        }
+        let op = match (cfg.op.as_str(), cfg.value) {
+            ("exists", _) => Op::Exists,
+            ("==", Some(value)) => Op::Eq(value),
+            ("!=", Some(value)) => Op::Ne(value),
+            ("==" | "!=", None) => return Err(format!("op '{}' needs a value", cfg.op)),
+            (op, _) => return Err(format!("unknown op '{op}'")),
+        };
+        let target = match (cfg.upstream_id, cfg.node) {
+            (Some(id), None) => Target::Upstream(id),
+            (None, Some(node)) => Target::Node(node),
+            _ => return Err("set exactly one of upstream_id or node".to_string()),
+        };
+        Ok(Self {
+            pointer: cfg.pointer,
+            op,
+            target,
+        })
+    }
+
+    /// Whether the rule holds for `body`. A pointer that misses never
+    /// holds, whatever the op.
+    fn holds(&self, body: &Value) -> bool {
+        let Some(found) = body.pointer(&self.pointer) else {
+            return false;
+        };
+        match &self.op {
+            Op::Exists => true,
+            Op::Eq(value) => same(found, value),
+            Op::Ne(value) => !same(found, value),
+        }
+    }
+}
+
+/// JSON equality, with numbers compared by value (`2` equals `2.0`).
+fn same(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x.as_f64() == y.as_f64(),
+        _ => a == b,
+    }
+}
+
+impl Plugin for BodyRoutingPlugin {
+    fn name(&self) -> &str {
+        "body-routing"
+    }
+
+    fn priority(&self) -> i32 {
+        960 // after traffic-split (966), so a match overrides its pick
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: BodyRoutingConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("body-routing config error: {e}"))?;
+        let rules = cfg
+            .rules
+            .into_iter()
+            .map(Rule::compile)
+            .collect::<Result<_, String>>()
+            .map_err(|e| anyhow::anyhow!("body-routing config error: {e}"))?;
+        Ok(Box::new(BodyRoutingInstance {
+            rules,
+            max_body: cfg.max_body,
+        }))
+    }
+}
+
+impl BodyRoutingInstance {
+    /// The request body as JSON, if it is complete, within `max_body` and
+    /// parses.
+    fn body(&self, ctx: &PluginContext) -> Option<Value> {
+        let body = ctx.request_body.as_deref()?;
+        if ctx.request_body_size > self.max_body || body.len() as u64 != ctx.request_body_size {
+            return None;
+        }
+        serde_json::from_slice(body).ok()
+    }
+}
+
+impl PluginInstance for BodyRoutingInstance {
+    fn name(&self) -> &str {
+        "body-routing"
+    }
+
+    fn priority(&self) -> i32 {
+        960
+    }
+
+    fn reads_body(&self) -> bool {
+        true
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let Some(body) = self.body(ctx) else {
+            return PluginResult::Continue;
+        };
+        let Some(rule) = self.rules.iter().find(|rule| rule.holds(&body)) else {
+            return PluginResult::Continue;
+        };
+        // The proxy prefers `upstream_addr`, so clear whichever one
+        // traffic-split may have set.
+        match &rule.target {
+            Target::Upstream(id) => {
+                ctx.upstream_addr = None;
+                ctx.upstream_id = Some(id.clone());
+            }
+            Target::Node(node) => {
+                ctx.upstream_id = None;
+                ctx.upstream_addr = Some(node.clone());
+            }
+        }
+        PluginResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic::traffic_split::TrafficSplitPlugin;
+    use ando_plugin::pipeline::PluginPipeline;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn instance(config: Value) -> Box<dyn PluginInstance> {
+        BodyRoutingPlugin.configure(&config).unwrap()
+    }
+
+    fn make_ctx(body: &[u8]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "127.0.0.1".into(),
+            "POST".into(),
+            "/orders".into(),
+            HashMap::new(),
+        );
+        ctx.request_body = Some(body.to_vec());
+        ctx.request_body_size = body.len() as u64;
+        ctx
+    }
+
+    /// Where the request goes: `id:<upstream>`, a node address, or
+    /// `default`.
+    fn target(ctx: PluginContext) -> String {
+        match (ctx.upstream_addr, ctx.upstream_id) {
+            (Some(addr), _) => addr,
+            (None, Some(id)) => format!("id:{id}"),
+            (None, None) => "default".into(),
+        }
+    }
+
+    fn route(plugin: &dyn PluginInstance, body: &[u8]) -> String {
+        let mut ctx = make_ctx(body);
+        assert!(matches!(plugin.access(&mut ctx), PluginResult::Continue));
+        target(ctx)
+    }
+
+    fn versioned() -> Box<dyn PluginInstance> {
+        instance(json!({"rules": [
+            {"pointer": "/version", "op": "==", "value": 2, "upstream_id": "orders-v2"},
+            {"pointer": "/meta/tenant", "op": "exists", "node": "10.0.0.9:8080"}
+        ]}))
+    }
+
+    #[test]
+    fn first_matching_rule_picks_the_upstream() {
+        let plugin = versioned();
+        assert!(plugin.reads_body());
+        assert_eq!(route(&*plugin, br#"{"version": 2}"#), "id:orders-v2");
+        assert_eq!(route(&*plugin, br#"{"version": 2.0}"#), "id:orders-v2");
+        assert_eq!(
+            route(&*plugin, br#"{"version": 2, "meta": {"tenant": "a"}}"#),
+            "id:orders-v2"
+        );
+        assert_eq!(
+            route(&*plugin, br#"{"version": 1, "meta": {"tenant": null}}"#),
+            "10.0.0.9:8080"
+        );
+    }
+
+    #[test]
+    fn no_match_or_pointer_miss_keeps_the_route_upstream() {
+        let plugin = versioned();
+        assert_eq!(route(&*plugin, br#"{"version": 1}"#), "default");
+        assert_eq!(route(&*plugin, br#"{"version": "2"}"#), "default");
+        assert_eq!(route(&*plugin, br#"{"meta": {}}"#), "default");
+        assert_eq!(route(&*plugin, br#"[2]"#), "default");
+
+        let ne = instance(json!({"rules": [
+            {"pointer": "/version", "op": "!=", "value": 1, "upstream_id": "new"}
+        ]}));
+        assert_eq!(route(&*ne, br#"{"version": 3}"#), "id:new");
+        assert_eq!(route(&*ne, br#"{"version": 1}"#), "default");
+        assert_eq!(route(&*ne, br#"{}"#), "default");
+    }
+
+    #[test]
+    fn unreadable_bodies_fall_through() {
+        let plugin = versioned();
+        assert_eq!(route(&*plugin, br#"{"version": 2"#), "default");
+        assert_eq!(route(&*plugin, b"version=2"), "default");
+        assert_eq!(route(&*plugin, b""), "default");
+
+        // Only part of a streamed body was buffered.
+        let mut ctx = make_ctx(br#"{"version": 2}"#);
+        ctx.request_body_size = 1 << 20;
+        plugin.access(&mut ctx);
+        assert_eq!(target(ctx), "default");
+
+        let small = instance(json!({"max_body": 13, "rules": [
+            {"pointer": "/version", "value": 2, "upstream_id": "orders-v2"}
+        ]}));
+        assert_eq!(route(&*small, br#"{"version":2}"#), "id:orders-v2");
+        assert_eq!(route(&*small, br#"{"version": 2}"#), "default");
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        for rule in [
+            json!({"pointer": "version", "value": 2, "upstream_id": "a"}),
+            json!({"pointer": "/v", "op": "<", "value": 2, "upstream_id": "a"}),
+            json!({"pointer": "/v", "op": "==", "upstream_id": "a"}),
+            json!({"pointer": "/v", "op": "exists"}),
+            json!({"pointer": "/v", "op": "exists", "upstream_id": "a", "node": "b:1"}),
+        ] {
+            assert!(
+                BodyRoutingPlugin
+                    .configure(&json!({"rules": [rule]}))
+                    .is_err(),
+                "{rule} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn body_match_overrides_traffic_split() {
+        let split = TrafficSplitPlugin::with_rng(Arc::new(|| 0.0))
+            .configure(&json!({"rules": [{"weighted_upstreams": [
+                {"upstream": {"nodes": {"10.0.0.1:80": 1}}}
+            ]}]}))
+            .unwrap();
+        let pipeline = PluginPipeline::build(vec![Arc::from(versioned()), Arc::from(split)], false);
+
+        let run = |body: &[u8]| {
+            let mut ctx = make_ctx(body);
+            pipeline.execute_phase(Phase::Access, &mut ctx);
+            target(ctx)
+        };
+        assert_eq!(run(br#"{"version": 2}"#), "id:orders-v2");
+        assert_eq!(run(br#"{"version": 1}"#), "10.0.0.1:80");
+        assert_eq!(run(b"not json"), "10.0.0.1:80");
+    }
+}
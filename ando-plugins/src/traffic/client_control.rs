@@ -0,0 +1,113 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+
+/// Client control plugin — per-route request body size limit (APISIX
+/// `client-control`).
+///
+/// Bodies larger than `max_body_size` bytes are answered with 413. The
+/// size is the declared content-length, or the decoded size of a chunked
+/// body. 0 means no limit.
+pub struct ClientControlPlugin;
+
+#[derive(Debug, Deserialize)]
+struct ClientControlConfig {
+    #[serde(default)]
+    max_body_size: u64,
+}
+
+struct ClientControlInstance {
+    max_body_size: u64,
+}
+
+const BODY_413: &[u8] = br#"{"error":"request body too large","status":413}"#;
+
+impl Plugin for ClientControlPlugin {
+    fn name(&self) -> &str {
+        "client-control"
+    }
+
+    fn priority(&self) -> i32 {
+        22000 // APISIX default
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Rewrite]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ClientControlConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("client-control config error: {e}"))?;
+        Ok(Box::new(ClientControlInstance {
+            max_body_size: cfg.max_body_size,
+        }))
+    }
+}
+
+impl PluginInstance for ClientControlInstance {
+    fn name(&self) -> &str {
+        "client-control"
+    }
+
+    fn priority(&self) -> i32 {
+        22000
+    }
+
+    fn rewrite(&self, ctx: &mut PluginContext) -> PluginResult {
+        if self.max_body_size == 0 || ctx.request_body_size <= self.max_body_size {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: 413,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(BODY_413.to_vec()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn status(config: serde_json::Value, body_size: u64) -> u16 {
+        let inst = ClientControlPlugin.configure(&config).unwrap();
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "POST".into(),
+            "/".into(),
+            HashMap::new(),
+        );
+        ctx.request_body_size = body_size;
+        match inst.rewrite(&mut ctx) {
+            PluginResult::Continue => 200,
+            PluginResult::Response { status, .. } => status,
+        }
+    }
+
+    #[test]
+    fn bodies_over_the_limit_get_413() {
+        let config = serde_json::json!({ "max_body_size": 10 });
+        assert_eq!(status(config.clone(), 0), 200);
+        assert_eq!(status(config.clone(), 10), 200);
+        assert_eq!(status(config, 11), 413);
+    }
+
+    #[test]
+    fn zero_means_no_limit() {
+        assert_eq!(status(serde_json::json!({}), u64::MAX), 200);
+        assert_eq!(
+            status(serde_json::json!({ "max_body_size": 0 }), 1 << 40),
+            200
+        );
+    }
+
+    #[test]
+    fn configure_rejects_a_negative_size() {
+        assert!(
+            ClientControlPlugin
+                .configure(&serde_json::json!({ "max_body_size": -1 }))
+                .is_err()
+        );
+    }
+}
@@ -1,20 +1,41 @@
 use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use regex::Regex;
 use serde::Deserialize;
 
+/// CORS plugin — answers preflight requests and adds the CORS headers to
+/// the responses of allowed origins.
+///
+/// Origins are allowed by `allow_origins` (exact, or `*`) or by a regex of
+/// `allow_origins_by_regex`, matched against the whole origin. Browsers
+/// refuse `*` on credentialed requests, so with `allow_credentials` a `*`
+/// in `allow_origins`, `allow_methods` or `allow_headers` reflects what
+/// the request names instead. Whenever the allowed origin is echoed
+/// rather than `*`, responses carry `Vary: Origin` so caches keep the
+/// answer for one origin from another.
 pub struct CorsPlugin;
 
 #[derive(Debug, Deserialize, Clone)]
 struct CorsConfig {
     #[serde(default = "default_allow_origins")]
     allow_origins: Vec<String>,
+    /// Regexes an origin may match instead, e.g. `https://.*\.example\.com`.
+    #[serde(default)]
+    allow_origins_by_regex: Vec<String>,
     #[serde(default = "default_allow_methods")]
     allow_methods: Vec<String>,
     #[serde(default = "default_allow_headers")]
     allow_headers: Vec<String>,
+    /// Response headers scripts may read (`Access-Control-Expose-Headers`).
+    #[serde(default)]
+    expose_headers: Vec<String>,
     #[serde(default)]
     allow_credentials: bool,
     #[serde(default = "default_max_age")]
     max_age: u32,
+    /// Origins (or `*`) whose pages may read the response's resource
+    /// timing (`Timing-Allow-Origin`).
+    #[serde(default)]
+    timing_allow_origin: Vec<String>,
 }
 
 fn default_allow_origins() -> Vec<String> {
@@ -38,6 +59,7 @@ fn default_max_age() -> u32 {
 
 struct CorsInstance {
     cfg: CorsConfig,
+    origin_regexes: Vec<Regex>,
 }
 
 impl Plugin for CorsPlugin {
@@ -56,41 +78,70 @@ impl Plugin for CorsPlugin {
     fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
         let cfg: CorsConfig = serde_json::from_value(config.clone())
             .map_err(|e| anyhow::anyhow!("cors config error: {e}"))?;
-        Ok(Box::new(CorsInstance { cfg }))
+        Ok(Box::new(CorsInstance::new(cfg)?))
     }
 }
 
 impl CorsInstance {
+    fn new(cfg: CorsConfig) -> anyhow::Result<Self> {
+        let origin_regexes = cfg
+            .allow_origins_by_regex
+            .iter()
+            .map(|p| {
+                Regex::new(&format!("^(?:{p})$"))
+                    .map_err(|e| anyhow::anyhow!("cors: invalid origin regex `{p}`: {e}"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            cfg,
+            origin_regexes,
+        })
+    }
+
     /// Returns the matched origin string, or None if origin is disallowed.
     fn resolve_origin(&self, origin: &str) -> Option<String> {
         if self.cfg.allow_origins.iter().any(|o| o == "*") {
-            return Some("*".to_string());
+            // `*` does not do for credentialed requests.
+            return Some(if self.cfg.allow_credentials {
+                origin.to_string()
+            } else {
+                "*".to_string()
+            });
         }
-        if self.cfg.allow_origins.iter().any(|o| o == origin) {
+        if self.cfg.allow_origins.iter().any(|o| o == origin)
+            || self.origin_regexes.iter().any(|r| r.is_match(origin))
+        {
             return Some(origin.to_string());
         }
         None
     }
 
-    fn cors_headers(&self, origin_value: &str) -> Vec<(String, String)> {
-        let mut h = vec![
-            (
-                "access-control-allow-origin".to_string(),
-                origin_value.to_string(),
-            ),
-            (
-                "access-control-allow-methods".to_string(),
-                self.cfg.allow_methods.join(", "),
-            ),
-            (
-                "access-control-allow-headers".to_string(),
-                self.cfg.allow_headers.join(", "),
-            ),
-            (
-                "access-control-max-age".to_string(),
-                self.cfg.max_age.to_string(),
-            ),
-        ];
+    /// `list` joined, or with credentials allowed and `*` in it, what the
+    /// preflight asked for in `requested` (nothing when it asked for none).
+    fn allowed(&self, list: &[String], requested: Option<&str>) -> Option<String> {
+        if self.cfg.allow_credentials && list.iter().any(|v| v == "*") {
+            return requested.map(str::to_string);
+        }
+        Some(list.join(", "))
+    }
+
+    fn cors_headers(&self, origin_value: &str, ctx: &PluginContext) -> Vec<(String, String)> {
+        let mut h = vec![(
+            "access-control-allow-origin".to_string(),
+            origin_value.to_string(),
+        )];
+        let requested_method = ctx.get_header("access-control-request-method");
+        if let Some(methods) = self.allowed(&self.cfg.allow_methods, requested_method) {
+            h.push(("access-control-allow-methods".to_string(), methods));
+        }
+        let requested_headers = ctx.get_header("access-control-request-headers");
+        if let Some(headers) = self.allowed(&self.cfg.allow_headers, requested_headers) {
+            h.push(("access-control-allow-headers".to_string(), headers));
+        }
+        h.push((
+            "access-control-max-age".to_string(),
+            self.cfg.max_age.to_string(),
+        ));
         if self.cfg.allow_credentials {
             h.push((
                 "access-control-allow-credentials".to_string(),
@@ -99,6 +150,15 @@ impl CorsInstance {
         }
         h
     }
+
+    /// `Timing-Allow-Origin` for a request from `origin`.
+    fn timing_allow_origin(&self, origin: &str) -> Option<&str> {
+        self.cfg
+            .timing_allow_origin
+            .iter()
+            .find(|o| *o == "*" || *o == origin)
+            .map(String::as_str)
+    }
 }
 
 impl PluginInstance for CorsInstance {
@@ -111,8 +171,8 @@ impl PluginInstance for CorsInstance {
     }
 
     fn access(&self, ctx: &mut PluginContext) -> PluginResult {
-        let origin = match ctx.request_headers.get("origin") {
-            Some(o) => o.clone(),
+        let origin = match ctx.get_header("origin") {
+            Some(o) => o.to_string(),
             None => return PluginResult::Continue, // not a CORS request
         };
 
@@ -128,10 +188,14 @@ impl PluginInstance for CorsInstance {
                 };
             }
         };
+        let vary = resolved != "*";
 
         // Preflight
         if ctx.method == "OPTIONS" {
-            let mut headers = self.cors_headers(&resolved);
+            let mut headers = self.cors_headers(&resolved, ctx);
+            if vary {
+                headers.push(("vary".to_string(), "Origin".to_string()));
+            }
             headers.push(("content-length".to_string(), "0".to_string()));
             return PluginResult::Response {
                 status: 204,
@@ -140,10 +204,22 @@ impl PluginInstance for CorsInstance {
             };
         }
 
-        // Add CORS headers to context variables for response phase (simple request)
-        for (k, v) in self.cors_headers(&resolved) {
-            ctx.vars
-                .insert(format!("_cors_{k}"), serde_json::Value::String(v));
+        // Simple request: the CORS headers go on the proxied response.
+        let headers = self.cors_headers(&resolved, ctx);
+        ctx.response_headers.extend(headers);
+        if !self.cfg.expose_headers.is_empty() {
+            ctx.response_headers.insert(
+                "access-control-expose-headers".to_string(),
+                self.cfg.expose_headers.join(", "),
+            );
+        }
+        if let Some(timing) = self.timing_allow_origin(&origin) {
+            ctx.response_headers
+                .insert("timing-allow-origin".to_string(), timing.to_string());
+        }
+        if vary {
+            // Appended: the upstream's own Vary values still apply.
+            ctx.response_headers.append("vary", "Origin");
         }
 
         PluginResult::Continue
@@ -171,7 +247,34 @@ mod tests {
 
     fn instance(config: serde_json::Value) -> CorsInstance {
         let cfg: CorsConfig = serde_json::from_value(config).unwrap();
-        CorsInstance { cfg }
+        CorsInstance::new(cfg).unwrap()
+    }
+
+    fn preflight(
+        inst: &CorsInstance,
+        origin: &str,
+        request_headers: &[(&str, &str)],
+    ) -> Vec<(String, String)> {
+        let mut ctx = make_ctx("OPTIONS", Some(origin));
+        for (name, value) in request_headers {
+            ctx.request_headers
+                .insert(name.to_string(), value.to_string());
+        }
+        match inst.access(&mut ctx) {
+            PluginResult::Response {
+                status: 204,
+                headers,
+                ..
+            } => headers,
+            _ => panic!("Expected preflight Response"),
+        }
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
     }
 
     // ── No origin header → pass through ─────────────────────────
@@ -270,15 +373,18 @@ mod tests {
         }
     }
 
-    // ── Simple GET request continues and stores CORS vars ────────
+    // ── Simple GET request continues and sets CORS headers ───────
 
     #[test]
-    fn simple_get_continues_and_stores_cors_vars() {
+    fn simple_get_continues_and_sets_cors_headers() {
         let inst = instance(serde_json::json!({}));
         let mut ctx = make_ctx("GET", Some("https://example.com"));
         let result = inst.access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
-        assert!(ctx.vars.contains_key("_cors_access-control-allow-origin"));
+        assert!(
+            ctx.response_headers
+                .contains_key("access-control-allow-origin")
+        );
     }
 
     // ── Plugin trait ─────────────────────────────────────────────
@@ -362,10 +468,10 @@ mod tests {
         );
     }
 
-    // ── Simple request stores all CORS vars in context ────────────
+    // ── Simple request sets every CORS header on the response ─────
 
     #[test]
-    fn simple_request_stores_all_cors_headers_in_vars() {
+    fn simple_request_sets_all_cors_headers() {
         let inst = instance(serde_json::json!({
             "allow_methods": ["GET", "POST"],
             "allow_headers": ["Content-Type"],
@@ -376,14 +482,15 @@ mod tests {
         let mut ctx = make_ctx("GET", Some("https://example.com"));
         let result = inst.access(&mut ctx);
         assert!(matches!(result, PluginResult::Continue));
-        assert!(ctx.vars.contains_key("_cors_access-control-allow-origin"));
-        assert!(ctx.vars.contains_key("_cors_access-control-allow-methods"));
-        assert!(ctx.vars.contains_key("_cors_access-control-allow-headers"));
-        assert!(ctx.vars.contains_key("_cors_access-control-max-age"));
-        assert!(
-            ctx.vars
-                .contains_key("_cors_access-control-allow-credentials")
-        );
+        for name in [
+            "access-control-allow-origin",
+            "access-control-allow-methods",
+            "access-control-allow-headers",
+            "access-control-max-age",
+            "access-control-allow-credentials",
+        ] {
+            assert!(ctx.response_headers.contains_key(name), "{name}");
+        }
     }
 
     // ── Wildcard origin: reflected as "*" not the actual origin ────
@@ -393,10 +500,7 @@ mod tests {
         let inst = instance(serde_json::json!({})); // defaults to allow_origins: ["*"]
         let mut ctx = make_ctx("GET", Some("https://specific-origin.com"));
         inst.access(&mut ctx);
-        let origin_val = ctx
-            .vars
-            .get("_cors_access-control-allow-origin")
-            .and_then(|v| v.as_str());
+        let origin_val = ctx.response_headers.get("access-control-allow-origin");
         assert_eq!(
             origin_val,
             Some("*"),
@@ -413,10 +517,7 @@ mod tests {
         }));
         let mut ctx = make_ctx("GET", Some("https://example.com"));
         inst.access(&mut ctx);
-        let origin_val = ctx
-            .vars
-            .get("_cors_access-control-allow-origin")
-            .and_then(|v| v.as_str());
+        let origin_val = ctx.response_headers.get("access-control-allow-origin");
         assert_eq!(
             origin_val,
             Some("https://example.com"),
@@ -446,4 +547,141 @@ mod tests {
             _ => panic!("Expected Response"),
         }
     }
+
+    // ── Credentialed wildcards reflect the request ─────────────────
+
+    #[test]
+    fn credentialed_wildcard_reflects_origin_and_requested_headers() {
+        let inst = instance(serde_json::json!({
+            "allow_credentials": true,
+            "allow_methods": ["*"]
+        }));
+        let headers = preflight(
+            &inst,
+            "https://app.example.com",
+            &[
+                ("access-control-request-method", "PATCH"),
+                ("access-control-request-headers", "x-token, content-type"),
+            ],
+        );
+        assert_eq!(
+            header(&headers, "access-control-allow-origin"),
+            Some("https://app.example.com")
+        );
+        assert_eq!(
+            header(&headers, "access-control-allow-methods"),
+            Some("PATCH")
+        );
+        assert_eq!(
+            header(&headers, "access-control-allow-headers"),
+            Some("x-token, content-type")
+        );
+        assert_eq!(header(&headers, "vary"), Some("Origin"));
+
+        // Nothing requested, nothing allowed: never a literal `*`.
+        let headers = preflight(&inst, "https://app.example.com", &[]);
+        assert_eq!(header(&headers, "access-control-allow-headers"), None);
+        assert_eq!(header(&headers, "access-control-allow-methods"), None);
+    }
+
+    #[test]
+    fn uncredentialed_wildcard_keeps_star() {
+        let inst = instance(serde_json::json!({}));
+        let headers = preflight(
+            &inst,
+            "https://app.example.com",
+            &[("access-control-request-headers", "x-token")],
+        );
+        assert_eq!(header(&headers, "access-control-allow-origin"), Some("*"));
+        assert_eq!(header(&headers, "access-control-allow-headers"), Some("*"));
+        assert_eq!(header(&headers, "vary"), None);
+    }
+
+    // ── Regex origins ──────────────────────────────────────────────
+
+    #[test]
+    fn regex_origins_match_the_whole_origin() {
+        let inst = instance(serde_json::json!({
+            "allow_origins": ["https://example.com"],
+            "allow_origins_by_regex": [r"https://[a-z0-9-]+\.example\.com"]
+        }));
+        for allowed in ["https://example.com", "https://app.example.com"] {
+            let mut ctx = make_ctx("GET", Some(allowed));
+            assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+            assert_eq!(
+                ctx.response_headers.get("access-control-allow-origin"),
+                Some(allowed)
+            );
+        }
+        for denied in [
+            "https://app.example.com.evil.net",
+            "http://app.example.com",
+            "https://a.b.example.com",
+        ] {
+            let result = inst.access(&mut make_ctx("GET", Some(denied)));
+            assert!(
+                matches!(result, PluginResult::Response { status: 403, .. }),
+                "{denied}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_origin_regex_fails_configure() {
+        let config = serde_json::json!({ "allow_origins_by_regex": ["https://(unclosed"] });
+        assert!(CorsPlugin.configure(&config).is_err());
+    }
+
+    // ── Vary: Origin ──────────────────────────────────────────────
+
+    #[test]
+    fn echoed_origin_varies_on_origin_for_simple_and_preflight() {
+        let inst = instance(serde_json::json!({
+            "allow_origins": ["https://example.com"]
+        }));
+        let headers = preflight(&inst, "https://example.com", &[]);
+        assert_eq!(header(&headers, "vary"), Some("Origin"));
+
+        let mut ctx = make_ctx("GET", Some("https://example.com"));
+        inst.access(&mut ctx);
+        assert_eq!(ctx.response_headers.get("vary"), Some("Origin"));
+        // Appended, so the upstream's Vary is kept.
+        assert!(!ctx.response_headers.replaces("vary"));
+
+        let mut ctx = make_ctx("GET", Some("https://example.com"));
+        instance(serde_json::json!({})).access(&mut ctx);
+        assert_eq!(ctx.response_headers.get("vary"), None);
+    }
+
+    // ── Expose headers and timing ─────────────────────────────────
+
+    #[test]
+    fn expose_headers_and_timing_allow_origin_on_simple_responses() {
+        let inst = instance(serde_json::json!({
+            "expose_headers": ["x-request-id", "x-ratelimit-remaining"],
+            "timing_allow_origin": ["https://example.com"]
+        }));
+        let mut ctx = make_ctx("GET", Some("https://example.com"));
+        inst.access(&mut ctx);
+        assert_eq!(
+            ctx.response_headers.get("access-control-expose-headers"),
+            Some("x-request-id, x-ratelimit-remaining")
+        );
+        assert_eq!(
+            ctx.response_headers.get("timing-allow-origin"),
+            Some("https://example.com")
+        );
+
+        let mut other = make_ctx("GET", Some("https://other.com"));
+        inst.access(&mut other);
+        assert_eq!(other.response_headers.get("timing-allow-origin"), None);
+
+        let mut plain = make_ctx("GET", Some("https://example.com"));
+        instance(serde_json::json!({})).access(&mut plain);
+        assert!(
+            !plain
+                .response_headers
+                .contains_key("access-control-expose-headers")
+        );
+    }
 }
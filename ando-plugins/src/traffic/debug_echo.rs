@@ -0,0 +1,282 @@
+use ando_plugin::pipeline::EXECUTED_PLUGINS_VAR;
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+
+/// Environment variable naming the deployment environment. Anything other
+/// than an explicit non-production value counts as production.
+const ENVIRONMENT_VAR: &str = "ANDO_ENVIRONMENT";
+
+/// Debug-echo plugin — answers with what the gateway would send upstream.
+///
+/// Runs last, before proxying, and reflects the method, final URI, path
+/// params, request headers after transformation, the consumer and the
+/// plugins that ran. It stays inert in production unless
+/// `enabled_in_production` is set, and only answers allowlisted clients;
+/// everyone else is proxied as if the plugin were absent.
+pub struct DebugEchoPlugin;
+
+#[derive(Debug, Deserialize)]
+struct DebugEchoConfig {
+    #[serde(default)]
+    enabled_in_production: bool,
+    /// Client IPs or CIDRs allowed to see the echo.
+    #[serde(default = "default_allow_ips")]
+    allow_ips: Vec<String>,
+}
+
+fn default_allow_ips() -> Vec<String> {
+    vec!["127.0.0.1".to_string(), "::1".to_string()]
+}
+
+struct DebugEchoInstance {
+    active: bool,
+    allow: Vec<IpNet>,
+}
+
+fn is_production() -> bool {
+    std::env::var(ENVIRONMENT_VAR).map_or(true, |v| v.eq_ignore_ascii_case("production"))
+}
+
+impl Plugin for DebugEchoPlugin {
+    fn name(&self) -> &str {
+        "debug-echo"
+    }
+
+    fn priority(&self) -> i32 {
+        -1000 // after every other plugin
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::BeforeProxy]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: DebugEchoConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("debug-echo config error: {e}"))?;
+
+        let allow = cfg
+            .allow_ips
+            .iter()
+            .map(|s| {
+                s.parse::<IpNet>()
+                    .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| anyhow::anyhow!("debug-echo: invalid IP or CIDR `{s}`"))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Box::new(DebugEchoInstance {
+            active: cfg.enabled_in_production || !is_production(),
+            allow,
+        }))
+    }
+}
+
+impl DebugEchoInstance {
+    fn allows(&self, client_ip: &str) -> bool {
+        client_ip
+            .parse::<IpAddr>()
+            .is_ok_and(|ip| self.allow.iter().any(|net| net.contains(&ip)))
+    }
+
+    fn echo(ctx: &PluginContext) -> serde_json::Value {
+        let mut headers = ctx.request_headers.combined();
+        for (k, v) in &ctx.upstream_headers {
+            headers.insert(k.clone(), v.clone());
+        }
+        let path_params: BTreeMap<&str, &str> = ctx
+            .route_params
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+
+        serde_json::json!({
+            "route_id": ctx.route_id,
+            "method": ctx.method,
+            "uri": ctx.uri,
+            "path_params": path_params,
+            "headers": headers,
+            "consumer": ctx.consumer,
+            "executed_plugins": ctx
+                .vars
+                .get(EXECUTED_PLUGINS_VAR)
+                .cloned()
+                .unwrap_or_else(|| serde_json::json!([])),
+        })
+    }
+}
+
+impl PluginInstance for DebugEchoInstance {
+    fn name(&self) -> &str {
+        "debug-echo"
+    }
+
+    fn priority(&self) -> i32 {
+        -1000
+    }
+
+    fn traces_execution(&self) -> bool {
+        self.active
+    }
+
+    fn before_proxy(&self, ctx: &mut PluginContext) -> PluginResult {
+        if !self.active || !self.allows(&ctx.client_ip) {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: 200,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(Self::echo(ctx).to_string().into_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_plugin::pipeline::PluginPipeline;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn make_ctx(client_ip: &str) -> PluginContext {
+        let mut headers = HashMap::new();
+        headers.insert("accept".to_string(), "*/*".to_string());
+        headers.insert("x-env".to_string(), "client".to_string());
+        PluginContext::new(
+            "r1".into(),
+            client_ip.into(),
+            "POST".into(),
+            "/users/42?verbose=1".into(),
+            headers,
+        )
+    }
+
+    fn instance(active: bool, allow: &[&str]) -> DebugEchoInstance {
+        DebugEchoInstance {
+            active,
+            allow: allow.iter().map(|s| s.parse().unwrap()).collect(),
+        }
+    }
+
+    fn echoed(result: PluginResult) -> serde_json::Value {
+        match result {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 200);
+                serde_json::from_slice(&body.unwrap()).unwrap()
+            }
+            PluginResult::Continue => panic!("expected an echo"),
+        }
+    }
+
+    #[test]
+    fn echo_reflects_request_after_transformation() {
+        let inst = instance(true, &["10.0.0.0/8"]);
+        let mut ctx = make_ctx("10.1.2.3");
+        ctx.route_params = vec![("id".into(), "42".into())];
+        ctx.upstream_headers
+            .insert("x-env".to_string(), "gateway".to_string());
+        ctx.consumer = Some("alice".into());
+
+        let body = echoed(inst.before_proxy(&mut ctx));
+        assert_eq!(body["route_id"], "r1");
+        assert_eq!(body["method"], "POST");
+        assert_eq!(body["uri"], "/users/42?verbose=1");
+        assert_eq!(body["path_params"], serde_json::json!({ "id": "42" }));
+        assert_eq!(
+            body["headers"],
+            serde_json::json!({ "accept": "*/*", "x-env": "gateway" })
+        );
+        assert_eq!(body["consumer"], "alice");
+        assert_eq!(body["executed_plugins"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn pipeline_records_plugins_that_ran_before_the_echo() {
+        struct Named(&'static str, i32);
+        impl PluginInstance for Named {
+            fn name(&self) -> &str {
+                self.0
+            }
+            fn priority(&self) -> i32 {
+                self.1
+            }
+        }
+
+        let pipeline = PluginPipeline::build(
+            vec![
+                Arc::new(instance(true, &["127.0.0.1/32"])),
+                Arc::new(Named("auth", 2500)),
+                Arc::new(Named("limits", 1000)),
+            ],
+            false,
+        );
+        let mut ctx = make_ctx("127.0.0.1");
+        for phase in [Phase::Rewrite, Phase::Access] {
+            assert!(matches!(
+                pipeline.execute_phase(phase, &mut ctx),
+                PluginResult::Continue
+            ));
+        }
+        let body = echoed(pipeline.execute_phase(Phase::BeforeProxy, &mut ctx));
+        assert_eq!(
+            body["executed_plugins"],
+            serde_json::json!(["auth", "limits", "debug-echo"])
+        );
+    }
+
+    #[test]
+    fn clients_outside_allowlist_are_proxied() {
+        let inst = instance(true, &["127.0.0.1/32"]);
+        assert!(matches!(
+            inst.before_proxy(&mut make_ctx("203.0.113.9")),
+            PluginResult::Continue
+        ));
+        assert!(matches!(
+            inst.before_proxy(&mut make_ctx("not-an-ip")),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn inactive_instance_is_inert_and_does_not_trace() {
+        let inst = instance(false, &["127.0.0.1/32"]);
+        assert!(!inst.traces_execution());
+        assert!(matches!(
+            inst.before_proxy(&mut make_ctx("127.0.0.1")),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn enabled_in_production_overrides_environment() {
+        let inst = DebugEchoPlugin
+            .configure(&serde_json::json!({ "enabled_in_production": true }))
+            .unwrap();
+        assert!(inst.traces_execution());
+        // Default allowlist is loopback only.
+        assert!(matches!(
+            inst.before_proxy(&mut make_ctx("::1")),
+            PluginResult::Response { .. }
+        ));
+        assert!(matches!(
+            inst.before_proxy(&mut make_ctx("10.0.0.1")),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn configure_rejects_invalid_allowlist() {
+        assert!(
+            DebugEchoPlugin
+                .configure(&serde_json::json!({ "allow_ips": ["10.0.0.0/33"] }))
+                .is_err()
+        );
+        assert!(
+            DebugEchoPlugin
+                .configure(&serde_json::json!({ "allow_ips": ["10.0.0.1", "fd00::/8"] }))
+                .is_ok()
+        );
+    }
+}
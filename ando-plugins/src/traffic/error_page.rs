@@ -0,0 +1,102 @@
+use ando_core::config::ErrorPageSet;
+use ando_core::error_page::ErrorPages;
+use ando_plugin::plugin::{Phase, Plugin, PluginInstance};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Error page plugin — per-route bodies for the errors the gateway
+/// generates itself (upstream failure, a limiter's rejection, ...).
+///
+/// `pages` has the shape of the gateway's `errors`: status codes to a
+/// `{content_type, body_template}` page, or a list of them to pick from
+/// by `Accept`. Statuses it does not cover keep the global pages. The
+/// templates are compiled when the route's pipeline is built; the plugin
+/// runs in no phase.
+pub struct ErrorPagePlugin;
+
+#[derive(Debug, Deserialize)]
+struct ErrorPageConfig {
+    pages: HashMap<String, ErrorPageSet>,
+}
+
+struct ErrorPageInstance {
+    pages: Arc<ErrorPages>,
+}
+
+impl Plugin for ErrorPagePlugin {
+    fn name(&self) -> &str {
+        "error-page"
+    }
+
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ErrorPageConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("error-page config error: {e}"))?;
+        let pages = ErrorPages::compile(&cfg.pages)
+            .map_err(|e| anyhow::anyhow!("error-page config error: {e}"))?;
+        Ok(Box::new(ErrorPageInstance {
+            pages: Arc::new(pages),
+        }))
+    }
+}
+
+impl PluginInstance for ErrorPageInstance {
+    fn name(&self) -> &str {
+        "error-page"
+    }
+
+    fn error_pages(&self) -> Option<Arc<ErrorPages>> {
+        Some(Arc::clone(&self.pages))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::error_page::ErrorVars;
+    use ando_plugin::pipeline::PluginPipeline;
+    use serde_json::json;
+
+    #[test]
+    fn pipeline_carries_the_route_pages() {
+        let inst = ErrorPagePlugin
+            .configure(&json!({
+                "pages": {
+                    "502": {"content_type": "text/plain", "body_template": "down ({{status}})"}
+                }
+            }))
+            .unwrap();
+        let pipeline = PluginPipeline::build(vec![Arc::from(inst)], false);
+        let page = pipeline.error_pages().unwrap().select(502, None).unwrap();
+        assert_eq!(page.content_type(), "text/plain");
+        let vars = ErrorVars {
+            status: 502,
+            ..ErrorVars::default()
+        };
+        assert_eq!(page.render(&vars), b"down (502)");
+        assert!(PluginPipeline::build(vec![], false).error_pages().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_pages() {
+        assert!(ErrorPagePlugin.configure(&json!({})).is_err());
+        assert!(
+            ErrorPagePlugin
+                .configure(&json!({"pages": {"404": {"body_template": "{{nope}}"}}}))
+                .is_err()
+        );
+        assert!(
+            ErrorPagePlugin
+                .configure(&json!({"pages": {"404": {"content_type": "text/plain"}}}))
+                .is_err()
+        );
+    }
+}
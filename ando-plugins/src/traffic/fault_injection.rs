@@ -0,0 +1,430 @@
+use super::traffic_split::{Rng, lookup, random};
+use crate::background;
+use ando_core::vars::VarExpr;
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest configurable `delay.duration_ms`.
+const MAX_DELAY_MS: u64 = 60_000;
+
+/// Faults chosen for a request, left in `ctx.vars` for the per-route
+/// metric and for the async hook.
+const FAULTS_VAR: &str = "fault_injection";
+
+/// Fault injection plugin — aborts or delays a share of requests, for
+/// testing how clients cope with a failing backend.
+///
+/// Each fault applies to `percentage` of the requests matching its `vars`.
+/// The faults are picked in the access phase, ahead of auth and limits
+/// (priority 11000, as in APISIX). An abort alone answers there; a delay
+/// is slept in the async access hook, after the synchronous phases, and
+/// is followed by the abort when both were picked. The faults injected are
+/// listed in `ctx.vars["fault_injection"]` for the per-route metric.
+pub struct FaultInjectionPlugin {
+    rng: Rng,
+}
+
+impl FaultInjectionPlugin {
+    pub fn new() -> Self {
+        Self::with_rng(Arc::new(random))
+    }
+
+    /// A plugin drawing its percentages from `rng`, for deterministic
+    /// tests.
+    pub fn with_rng(rng: Rng) -> Self {
+        Self { rng }
+    }
+}
+
+impl Default for FaultInjectionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FaultInjectionConfig {
+    abort: Option<AbortConfig>,
+    delay: Option<DelayConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbortConfig {
+    http_status: u16,
+    #[serde(default)]
+    body: String,
+    #[serde(default = "default_percentage")]
+    percentage: u8,
+    /// APISIX-style `vars`: the fault applies when every condition of any
+    /// one group holds. Empty matches every request.
+    #[serde(default)]
+    vars: Vec<Vec<Vec<Value>>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DelayConfig {
+    duration_ms: u64,
+    #[serde(default = "default_percentage")]
+    percentage: u8,
+    #[serde(default)]
+    vars: Vec<Vec<Vec<Value>>>,
+}
+
+fn default_percentage() -> u8 {
+    100
+}
+
+struct FaultInjectionInstance {
+    abort: Option<(Fault, u16, Vec<u8>)>,
+    delay: Option<(Fault, Duration)>,
+    rng: Rng,
+}
+
+/// When a fault applies.
+struct Fault {
+    percentage: u8,
+    /// Empty when the fault has no `vars`.
+    vars: Vec<Vec<VarExpr>>,
+}
+
+impl Fault {
+    fn compile(percentage: u8, vars: &[Vec<Vec<Value>>]) -> Result<Self, String> {
+        if percentage > 100 {
+            return Err("percentage must be between 0 and 100".into());
+        }
+        let vars = vars
+            .iter()
+            .map(|group| VarExpr::compile_all(group))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { percentage, vars })
+    }
+
+    /// Whether to inject the fault into this request.
+    fn applies(&self, ctx: &PluginContext, rng: &Rng) -> bool {
+        let matched = self.vars.is_empty()
+            || self.vars.iter().any(|all| {
+                all.iter()
+                    .all(|expr| expr.matches(|name| lookup(ctx, name)))
+            });
+        matched
+            && match self.percentage {
+                0 => false,
+                100 => true,
+                p => rng() * 100.0 < f64::from(p),
+            }
+    }
+}
+
+impl Plugin for FaultInjectionPlugin {
+    fn name(&self) -> &str {
+        "fault-injection"
+    }
+
+    fn priority(&self) -> i32 {
+        11000 // APISIX default; ahead of auth and limits
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: FaultInjectionConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("fault-injection config error: {e}"))?;
+        if cfg.abort.is_none() && cfg.delay.is_none() {
+            anyhow::bail!("fault-injection: set abort, delay or both");
+        }
+        let err = |e: String| anyhow::anyhow!("fault-injection config error: {e}");
+
+        let abort = match cfg.abort {
+            Some(abort) => {
+                if !(200..=599).contains(&abort.http_status) {
+                    anyhow::bail!("fault-injection: abort.http_status must be between 200 and 599");
+                }
+                let fault = Fault::compile(abort.percentage, &abort.vars).map_err(err)?;
+                Some((fault, abort.http_status, abort.body.into_bytes()))
+            }
+            None => None,
+        };
+        let delay = match cfg.delay {
+            Some(delay) => {
+                if delay.duration_ms > MAX_DELAY_MS {
+                    anyhow::bail!(
+                        "fault-injection: delay.duration_ms must be at most {MAX_DELAY_MS}"
+                    );
+                }
+                let fault = Fault::compile(delay.percentage, &delay.vars).map_err(err)?;
+                Some((fault, Duration::from_millis(delay.duration_ms)))
+            }
+            None => None,
+        };
+
+        Ok(Box::new(FaultInjectionInstance {
+            abort,
+            delay,
+            rng: Arc::clone(&self.rng),
+        }))
+    }
+}
+
+impl FaultInjectionInstance {
+    fn abort_response(&self) -> PluginResult {
+        match &self.abort {
+            Some((_, status, body)) => PluginResult::Response {
+                status: *status,
+                headers: Vec::new(),
+                body: Some(body.clone()),
+            },
+            None => PluginResult::Continue,
+        }
+    }
+}
+
+/// Whether `fault` was picked for the request in `ctx`.
+fn picked(ctx: &PluginContext, fault: &str) -> bool {
+    ctx.vars
+        .get(FAULTS_VAR)
+        .and_then(Value::as_array)
+        .is_some_and(|faults| faults.iter().any(|f| f == fault))
+}
+
+impl PluginInstance for FaultInjectionInstance {
+    fn name(&self) -> &str {
+        "fault-injection"
+    }
+
+    fn priority(&self) -> i32 {
+        11000
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let delay = self
+            .delay
+            .as_ref()
+            .is_some_and(|(fault, _)| fault.applies(ctx, &self.rng));
+        let abort = self
+            .abort
+            .as_ref()
+            .is_some_and(|(fault, ..)| fault.applies(ctx, &self.rng));
+        let mut faults = Vec::new();
+        if delay {
+            faults.push(Value::from("delay"));
+        }
+        if abort {
+            faults.push(Value::from("abort"));
+        }
+        if !faults.is_empty() {
+            ctx.vars
+                .insert(FAULTS_VAR.to_string(), Value::Array(faults));
+        }
+        if abort && !delay {
+            return self.abort_response();
+        }
+        // A picked delay is slept, then aborted, in `access_async`.
+        PluginResult::Continue
+    }
+
+    fn has_async_access(&self) -> bool {
+        self.delay.is_some()
+    }
+
+    fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let (_, duration) = self.delay.as_ref()?;
+        if !picked(ctx, "delay") {
+            return None;
+        }
+        let duration = *duration;
+        let result = if picked(ctx, "abort") {
+            self.abort_response()
+        } else {
+            PluginResult::Continue
+        };
+        Some(Box::pin(async move {
+            background::sleep(duration).await;
+            AsyncAccess {
+                result,
+                response_headers: Vec::new(),
+                upstream_headers: Vec::new(),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Instant;
+
+    /// An rng stepping evenly through `[0, 1)` in `steps` draws.
+    fn stepping(steps: u64) -> Rng {
+        let n = AtomicU64::new(0);
+        Arc::new(move || (n.fetch_add(1, Ordering::Relaxed) % steps) as f64 / steps as f64)
+    }
+
+    fn instance(config: serde_json::Value) -> Box<dyn PluginInstance> {
+        FaultInjectionPlugin::with_rng(stepping(100))
+            .configure(&config)
+            .unwrap()
+    }
+
+    fn make_ctx(headers: &[(&str, &str)]) -> PluginContext {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            headers,
+        )
+    }
+
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    #[test]
+    fn full_percentage_aborts_every_request() {
+        let inst = instance(json!({
+            "abort": {"http_status": 503, "body": "injected"}
+        }));
+        assert!(!inst.has_async_access());
+        for _ in 0..10 {
+            let mut ctx = make_ctx(&[]);
+            match inst.access(&mut ctx) {
+                PluginResult::Response { status, body, .. } => {
+                    assert_eq!(status, 503);
+                    assert_eq!(body.unwrap(), b"injected");
+                }
+                PluginResult::Continue => panic!("expected an abort"),
+            }
+            assert_eq!(ctx.vars[FAULTS_VAR], json!(["abort"]));
+        }
+    }
+
+    #[test]
+    fn zero_percentage_never_injects() {
+        let inst = instance(json!({
+            "abort": {"http_status": 500, "percentage": 0},
+            "delay": {"duration_ms": 10, "percentage": 0}
+        }));
+        for _ in 0..100 {
+            let mut ctx = make_ctx(&[]);
+            assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+            assert!(inst.access_async(&mut ctx).is_none());
+            assert!(!ctx.vars.contains_key(FAULTS_VAR));
+        }
+    }
+
+    #[test]
+    fn percentage_selects_that_share_of_requests() {
+        let inst = instance(json!({
+            "abort": {"http_status": 500, "percentage": 30}
+        }));
+        let aborted = (0..100)
+            .filter(|_| {
+                matches!(
+                    inst.access(&mut make_ctx(&[])),
+                    PluginResult::Response { .. }
+                )
+            })
+            .count();
+        assert_eq!(aborted, 30);
+    }
+
+    #[test]
+    fn delay_sleeps_for_the_configured_duration() {
+        let inst = instance(json!({"delay": {"duration_ms": 50}}));
+        assert!(inst.has_async_access());
+        let mut ctx = make_ctx(&[]);
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.vars[FAULTS_VAR], json!(["delay"]));
+
+        let fut = inst.access_async(&mut ctx).expect("delay picked");
+        let start = Instant::now();
+        let outcome = block_on(fut);
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "{elapsed:?}");
+        assert!(elapsed < Duration::from_secs(2), "{elapsed:?}");
+        assert!(matches!(outcome.result, PluginResult::Continue));
+    }
+
+    #[test]
+    fn delay_then_abort_when_both_are_picked() {
+        let inst = instance(json!({
+            "abort": {"http_status": 429},
+            "delay": {"duration_ms": 20}
+        }));
+        let mut ctx = make_ctx(&[]);
+        // The abort waits for the delay.
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+        assert_eq!(ctx.vars[FAULTS_VAR], json!(["delay", "abort"]));
+        let start = Instant::now();
+        let outcome = block_on(inst.access_async(&mut ctx).unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert!(matches!(
+            outcome.result,
+            PluginResult::Response { status: 429, .. }
+        ));
+    }
+
+    #[test]
+    fn vars_restrict_faults_to_tagged_traffic() {
+        let inst = instance(json!({
+            "abort": {
+                "http_status": 503,
+                "vars": [[["http_x_chaos", "==", "on"]]]
+            }
+        }));
+        assert!(matches!(
+            inst.access(&mut make_ctx(&[])),
+            PluginResult::Continue
+        ));
+        assert!(matches!(
+            inst.access(&mut make_ctx(&[("x-chaos", "off")])),
+            PluginResult::Continue
+        ));
+        assert!(matches!(
+            inst.access(&mut make_ctx(&[("x-chaos", "on")])),
+            PluginResult::Response { status: 503, .. }
+        ));
+    }
+
+    #[test]
+    fn runs_ahead_of_auth_and_limits() {
+        let plugin = FaultInjectionPlugin::new();
+        assert_eq!(plugin.name(), "fault-injection");
+        assert!(plugin.priority() > crate::auth::key_auth::KeyAuthPlugin.priority());
+        assert!(plugin.priority() > crate::traffic::limit_req::LimitReqPlugin.priority());
+    }
+
+    #[test]
+    fn configure_rejects_invalid_settings() {
+        for config in [
+            json!({}),
+            json!({"abort": {"http_status": 100}}),
+            json!({"abort": {"http_status": 500, "percentage": 101}}),
+            json!({"delay": {"duration_ms": MAX_DELAY_MS + 1}}),
+            json!({"delay": {"percentage": 50}}),
+            json!({"abort": {"http_status": 500, "vars": [[["http_x", "~~~", "a"]]]}}),
+        ] {
+            assert!(
+                FaultInjectionPlugin::new().configure(&config).is_err(),
+                "{config}"
+            );
+        }
+    }
+}
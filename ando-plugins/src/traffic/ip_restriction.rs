@@ -1,13 +1,21 @@
 use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
 use ipnet::IpNet;
 use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::HashSet;
 use std::net::IpAddr;
 use std::str::FromStr;
 
 /// IP restriction plugin — allowlist/denylist based access control.
+///
+/// Lists take IPv4 and IPv6 CIDRs or plain addresses; entries that parse
+/// as neither are skipped. The client is `ctx.client_ip`, so behind
+/// `trusted_proxies` or real-ip it is the forwarded client, not the peer;
+/// IPv4-mapped IPv6 clients match IPv4 entries. A client address that
+/// does not parse is denied.
 pub struct IpRestrictionPlugin;
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 struct IpRestrictionConfig {
     /// If non-empty, only these CIDRs/IPs are allowed.
     #[serde(default)]
@@ -15,11 +23,111 @@ struct IpRestrictionConfig {
     /// If non-empty, these CIDRs/IPs are blocked.
     #[serde(default)]
     denylist: Vec<String>,
+    /// Status of the rejection.
+    #[serde(default = "default_response_code")]
+    response_code: u16,
+    #[serde(default = "default_message")]
+    message: String,
+}
+
+impl Default for IpRestrictionConfig {
+    fn default() -> Self {
+        Self {
+            allowlist: vec![],
+            denylist: vec![],
+            response_code: default_response_code(),
+            message: default_message(),
+        }
+    }
+}
+
+fn default_response_code() -> u16 {
+    403
+}
+
+fn default_message() -> String {
+    "IP not allowed".to_string()
 }
 
 struct IpRestrictionInstance {
-    allowlist: Vec<IpNet>,
-    denylist: Vec<IpNet>,
+    allowlist: CidrSet,
+    denylist: CidrSet,
+    status: u16,
+    /// Pre-rendered rejection body.
+    body: Vec<u8>,
+}
+
+/// A list of CIDRs compiled for lookup: networks are grouped by prefix
+/// length, so testing an address masks it once per length in use and
+/// probes a hash set. Thousands of entries cost no more per request than
+/// a handful, as lists rarely use more than a few lengths.
+#[derive(Debug, Default)]
+struct CidrSet {
+    /// Prefix length and the masked networks of that length, longest first.
+    v4: Vec<(u8, HashSet<u32>)>,
+    v6: Vec<(u8, HashSet<u128>)>,
+}
+
+impl CidrSet {
+    /// Entries that parse as a CIDR or a plain address (a /32 or /128).
+    fn compile(list: &[String]) -> Self {
+        let mut set = Self::default();
+        for entry in list {
+            let net = IpNet::from_str(entry)
+                .or_else(|_| IpAddr::from_str(entry).map(IpNet::from))
+                .map(|net| net.trunc());
+            match net {
+                Ok(IpNet::V4(net)) => {
+                    insert(&mut set.v4, net.prefix_len(), u32::from(net.network()))
+                }
+                Ok(IpNet::V6(net)) => {
+                    insert(&mut set.v6, net.prefix_len(), u128::from(net.network()))
+                }
+                Err(_) => tracing::warn!(entry = %entry, "ip-restriction: skipping invalid entry"),
+            }
+        }
+        set.v4.sort_by_key(|(len, _)| Reverse(*len));
+        set.v6.sort_by_key(|(len, _)| Reverse(*len));
+        set
+    }
+
+    fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty()
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        match ip.to_canonical() {
+            IpAddr::V4(ip) => {
+                let ip = u32::from(ip);
+                self.v4
+                    .iter()
+                    .any(|(len, nets)| nets.contains(&(ip & mask(*len, 32) as u32)))
+            }
+            IpAddr::V6(ip) => {
+                let ip = u128::from(ip);
+                self.v6
+                    .iter()
+                    .any(|(len, nets)| nets.contains(&(ip & mask(*len, 128))))
+            }
+        }
+    }
+}
+
+fn insert<T: std::hash::Hash + Eq>(by_len: &mut Vec<(u8, HashSet<T>)>, len: u8, network: T) {
+    match by_len.iter_mut().find(|(l, _)| *l == len) {
+        Some((_, nets)) => {
+            nets.insert(network);
+        }
+        None => by_len.push((len, HashSet::from([network]))),
+    }
+}
+
+/// Mask of a `len`-bit prefix of a `bits`-bit address.
+fn mask(len: u8, bits: u32) -> u128 {
+    match len {
+        0 => 0,
+        len => (u128::MAX << (128 - u32::from(len))) >> (128 - bits),
+    }
 }
 
 impl Plugin for IpRestrictionPlugin {
@@ -36,36 +144,29 @@ impl Plugin for IpRestrictionPlugin {
     }
 
     fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
-        let cfg: IpRestrictionConfig =
-            serde_json::from_value(config.clone()).unwrap_or_else(|_| IpRestrictionConfig {
-                allowlist: vec![],
-                denylist: vec![],
-            });
-
-        let parse_list = |list: Vec<String>| -> Vec<IpNet> {
-            list.iter()
-                .filter_map(|s| {
-                    // Try to parse as CIDR first, then as plain IP (host /32 or /128)
-                    IpNet::from_str(s).ok().or_else(|| {
-                        IpAddr::from_str(s).ok().map(|ip| match ip {
-                            IpAddr::V4(a) => IpNet::from(ipnet::Ipv4Net::from(a)),
-                            IpAddr::V6(a) => IpNet::from(ipnet::Ipv6Net::from(a)),
-                        })
-                    })
-                })
-                .collect()
-        };
-
-        Ok(Box::new(IpRestrictionInstance {
-            allowlist: parse_list(cfg.allowlist),
-            denylist: parse_list(cfg.denylist),
-        }))
+        let cfg: IpRestrictionConfig = serde_json::from_value(config.clone()).unwrap_or_default();
+        Ok(Box::new(IpRestrictionInstance::new(cfg)))
     }
 }
 
 impl IpRestrictionInstance {
-    fn matches_any(ip: &IpAddr, list: &[IpNet]) -> bool {
-        list.iter().any(|net| net.contains(ip))
+    fn new(cfg: IpRestrictionConfig) -> Self {
+        Self {
+            allowlist: CidrSet::compile(&cfg.allowlist),
+            denylist: CidrSet::compile(&cfg.denylist),
+            status: cfg.response_code,
+            body: serde_json::json!({ "error": cfg.message, "status": cfg.response_code })
+                .to_string()
+                .into_bytes(),
+        }
+    }
+
+    fn deny(&self) -> PluginResult {
+        PluginResult::Response {
+            status: self.status,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(self.body.clone()),
+        }
     }
 }
 
@@ -82,33 +183,29 @@ impl PluginInstance for IpRestrictionInstance {
         let ip = match IpAddr::from_str(&ctx.client_ip) {
             Ok(ip) => ip,
             Err(_) => {
-                // Unparseable IP — block to be safe
-                return deny_403();
+                tracing::warn!(
+                    route = %ctx.route_id,
+                    client_ip = %ctx.client_ip,
+                    "ip-restriction: client IP does not parse, denying"
+                );
+                return self.deny();
             }
         };
 
         // Denylist takes priority
-        if !self.denylist.is_empty() && Self::matches_any(&ip, &self.denylist) {
-            return deny_403();
+        if self.denylist.contains(ip) {
+            return self.deny();
         }
 
         // Allowlist: if set, IP must be in it
-        if !self.allowlist.is_empty() && !Self::matches_any(&ip, &self.allowlist) {
-            return deny_403();
+        if !self.allowlist.is_empty() && !self.allowlist.contains(ip) {
+            return self.deny();
         }
 
         PluginResult::Continue
     }
 }
 
-fn deny_403() -> PluginResult {
-    PluginResult::Response {
-        status: 403,
-        headers: vec![("content-type".to_string(), "application/json".to_string())],
-        body: Some(br#"{"error":"IP not allowed","status":403}"#.to_vec()),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,27 +222,14 @@ mod tests {
     }
 
     fn instance_from(config: serde_json::Value) -> IpRestrictionInstance {
-        let plugin = IpRestrictionPlugin;
-        let inst = plugin.configure(&config).unwrap();
-        // Downcast by re-constructing from config
-        let cfg: IpRestrictionConfig = serde_json::from_value(config).unwrap_or_default();
-        let parse_list = |list: Vec<String>| -> Vec<IpNet> {
-            list.iter()
-                .filter_map(|s| {
-                    IpNet::from_str(s).ok().or_else(|| {
-                        IpAddr::from_str(s).ok().map(|ip| match ip {
-                            IpAddr::V4(a) => IpNet::from(ipnet::Ipv4Net::from(a)),
-                            IpAddr::V6(a) => IpNet::from(ipnet::Ipv6Net::from(a)),
-                        })
-                    })
-                })
-                .collect()
-        };
-        let _ = inst; // drop boxed instance
-        IpRestrictionInstance {
-            allowlist: parse_list(cfg.allowlist),
-            denylist: parse_list(cfg.denylist),
-        }
+        IpRestrictionInstance::new(serde_json::from_value(config).unwrap_or_default())
+    }
+
+    fn denied(inst: &IpRestrictionInstance, client_ip: &str) -> bool {
+        matches!(
+            inst.access(&mut make_ctx(client_ip)),
+            PluginResult::Response { .. }
+        )
     }
 
     // ── No restrictions ──────────────────────────────────────────
@@ -240,6 +324,112 @@ mod tests {
         let _ = inst;
     }
 
+    // ── IPv6 and mixed lists ─────────────────────────────────────
+
+    #[test]
+    fn ipv6_allowlist_and_denylist() {
+        let inst = instance_from(serde_json::json!({
+            "allowlist": ["2001:db8::/32"],
+            "denylist": ["2001:db8:dead::/48", "2001:db8::7"]
+        }));
+        assert!(!denied(&inst, "2001:db8:1::1"));
+        assert!(denied(&inst, "2001:db8:dead:beef::1"));
+        assert!(denied(&inst, "2001:db8::7"));
+        assert!(!denied(&inst, "2001:db8::8"));
+        assert!(denied(&inst, "2001:db9::1"));
+        assert!(denied(&inst, "::1"));
+    }
+
+    #[test]
+    fn mixed_lists_match_each_family() {
+        let inst = instance_from(serde_json::json!({
+            "allowlist": ["10.0.0.0/8", "fd00::/8", "0.0.0.0/0"],
+            "denylist": ["10.9.0.0/16", "fd00:1::/32"]
+        }));
+        assert!(!denied(&inst, "10.1.2.3"));
+        assert!(denied(&inst, "10.9.2.3"));
+        assert!(!denied(&inst, "fd00:2::1"));
+        assert!(denied(&inst, "fd00:1::1"));
+        // 0.0.0.0/0 covers every IPv4 client and no IPv6 one.
+        assert!(!denied(&inst, "8.8.8.8"));
+        assert!(denied(&inst, "2001:db8::1"));
+        // IPv4-mapped IPv6 clients match IPv4 entries.
+        assert!(denied(&inst, "::ffff:10.9.2.3"));
+        assert!(!denied(&inst, "::ffff:10.1.2.3"));
+    }
+
+    #[test]
+    fn non_canonical_cidrs_match_their_network() {
+        let inst = instance_from(serde_json::json!({
+            "denylist": ["192.168.1.77/24", "2001:db8::1/64"]
+        }));
+        assert!(denied(&inst, "192.168.1.1"));
+        assert!(denied(&inst, "2001:db8::ffff"));
+        assert!(!denied(&inst, "192.168.2.1"));
+    }
+
+    #[test]
+    fn large_allowlist_lookup() {
+        // 10,000 entries: /24s of 10.0.0.0/8, /64s under 2001:db8::/32
+        // and single hosts of 172.16.0.0/16.
+        let mut allow = Vec::new();
+        for i in 0..4000u32 {
+            allow.push(format!("10.{}.{}.0/24", i / 256, i % 256));
+            allow.push(format!("2001:db8:{:x}::/64", i));
+        }
+        for i in 0..2000u32 {
+            allow.push(format!("172.16.{}.{}", i / 256, i % 256));
+        }
+        assert_eq!(allow.len(), 10_000);
+        let inst = instance_from(serde_json::json!({ "allowlist": allow }));
+
+        assert!(!denied(&inst, "10.0.0.1"));
+        assert!(!denied(&inst, "10.15.159.254")); // entry 3999
+        assert!(denied(&inst, "10.15.160.1")); // entry 4000
+        assert!(!denied(&inst, "2001:db8:f9f::abcd"));
+        assert!(denied(&inst, "2001:db8:fa0::1"));
+        assert!(!denied(&inst, "172.16.7.207")); // entry 1999
+        assert!(denied(&inst, "172.16.7.208"));
+        assert!(denied(&inst, "192.0.2.1"));
+    }
+
+    // ── Client address and rejection ─────────────────────────────
+
+    #[test]
+    fn unparseable_client_ip_is_denied() {
+        let inst = instance_from(serde_json::json!({ "denylist": ["10.0.0.0/8"] }));
+        for client_ip in ["", "unknown", "10.0.0.1:8080", "999.1.1.1"] {
+            assert!(denied(&inst, client_ip), "{client_ip}");
+        }
+    }
+
+    #[test]
+    fn response_code_and_message_are_configurable() {
+        let inst = instance_from(serde_json::json!({
+            "denylist": ["10.0.0.0/8"],
+            "response_code": 401,
+            "message": "go away"
+        }));
+        match inst.access(&mut make_ctx("10.0.0.1")) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 401);
+                let body: serde_json::Value = serde_json::from_slice(&body.unwrap()).unwrap();
+                assert_eq!(body, serde_json::json!({"error": "go away", "status": 401}));
+            }
+            PluginResult::Continue => panic!("expected a rejection"),
+        }
+        // Defaults keep the original body.
+        match instance_from(serde_json::json!({ "denylist": ["10.0.0.0/8"] }))
+            .access(&mut make_ctx("10.0.0.1"))
+        {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 403);
+                assert_eq!(body.unwrap(), br#"{"error":"IP not allowed","status":403}"#);
+            }
+            PluginResult::Continue => panic!("expected a rejection"),
+        }
+    }
+
     // ── Plugin trait ─────────────────────────────────────────────
 
     #[test]
@@ -0,0 +1,577 @@
+use super::limit_key::LimitKey;
+use crate::background;
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+    PluginStat,
+};
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Concurrent request limiting.
+///
+/// At most `conn` requests per key are proxied at once. Up to `burst`
+/// more wait in a queue for a slot, for at most `default_conn_delay_ms`;
+/// anything beyond that is rejected with `rejected_code`.
+///
+/// v2 design: Slots are semaphores owned by the plugin factory and keyed
+/// by route and key, so the limit holds across workers. A request takes
+/// its slot in the async access hook, once every other plugin has let it
+/// through, and keeps it in `ctx.guards` until the log phase releases it.
+/// Requests that end any other way (upstream errors, rejected hooks,
+/// client disconnects) release it when their context is dropped.
+pub struct LimitConnPlugin {
+    limits: Arc<Limits>,
+}
+
+impl LimitConnPlugin {
+    pub fn new() -> Self {
+        Self {
+            limits: Arc::new(Limits::default()),
+        }
+    }
+}
+
+impl Default for LimitConnPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LimitConnConfig {
+    /// Requests proxied at once per key.
+    conn: u32,
+    /// Requests allowed to wait for a slot — default 0.
+    #[serde(default)]
+    burst: u32,
+    /// Longest wait for a slot in milliseconds — default 1000.
+    #[serde(default = "default_conn_delay")]
+    default_conn_delay_ms: u64,
+    /// Slot key — default "remote_addr".
+    #[serde(default = "default_key", alias = "key_type")]
+    key: String,
+    /// Status for rejected requests — default 503.
+    #[serde(default = "default_rejected_code")]
+    rejected_code: u16,
+}
+
+fn default_conn_delay() -> u64 {
+    1000
+}
+fn default_key() -> String {
+    "remote_addr".to_string()
+}
+fn default_rejected_code() -> u16 {
+    503
+}
+
+/// Route and key of one set of slots.
+type LimitId = (String, String);
+
+/// The slots of one route and key.
+struct Limiter {
+    conn: u32,
+    slots: Arc<Semaphore>,
+    /// Requests waiting for a slot.
+    queued: AtomicUsize,
+}
+
+impl Limiter {
+    fn new(conn: u32) -> Self {
+        Self {
+            conn,
+            slots: Arc::new(Semaphore::new(conn as usize)),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    fn in_flight(&self) -> u64 {
+        (self.conn as usize).saturating_sub(self.slots.available_permits()) as u64
+    }
+
+    fn is_idle(&self) -> bool {
+        self.in_flight() == 0 && self.queued.load(Ordering::Acquire) == 0
+    }
+}
+
+/// State shared by every instance of the plugin.
+#[derive(Default)]
+struct Limits {
+    limiters: DashMap<LimitId, Arc<Limiter>>,
+    /// Rejected requests per route.
+    rejected: DashMap<String, AtomicU64>,
+}
+
+enum Admission {
+    Admitted(ConnPermit),
+    /// No free slot; the request may wait for one.
+    Queued(Waiter),
+    Rejected,
+}
+
+impl Limits {
+    /// Take a slot for `id`, or a place in its queue.
+    fn admit(self: &Arc<Self>, id: LimitId, conn: u32, burst: u32) -> Admission {
+        // The entry lock orders admissions against `forget_if_idle`.
+        let mut entry = self
+            .limiters
+            .entry(id.clone())
+            .or_insert_with(|| Arc::new(Limiter::new(conn)));
+        if entry.conn != conn {
+            // Reconfigured: requests holding the old slots finish on them.
+            *entry = Arc::new(Limiter::new(conn));
+        }
+        let limiter = Arc::clone(&entry);
+        if let Ok(permit) = Arc::clone(&limiter.slots).try_acquire_owned() {
+            drop(entry);
+            return Admission::Admitted(ConnPermit {
+                permit: Some(permit),
+                limits: Arc::clone(self),
+                id,
+                limiter,
+            });
+        }
+        if limiter.queued.load(Ordering::Acquire) >= burst as usize {
+            drop(entry);
+            self.count_rejection(&id.0);
+            return Admission::Rejected;
+        }
+        limiter.queued.fetch_add(1, Ordering::AcqRel);
+        drop(entry);
+        Admission::Queued(Waiter {
+            limits: Arc::clone(self),
+            id,
+            limiter,
+        })
+    }
+
+    fn count_rejection(&self, route: &str) {
+        if let Some(n) = self.rejected.get(route) {
+            n.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.rejected
+            .entry(route.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Drop `limiter` once nothing holds or waits for its slots, so keys
+    /// such as client addresses don't pile up.
+    fn forget_if_idle(&self, id: &LimitId, limiter: &Arc<Limiter>) {
+        self.limiters
+            .remove_if(id, |_, l| Arc::ptr_eq(l, limiter) && l.is_idle());
+    }
+
+    fn stats(&self) -> Vec<PluginStat> {
+        let mut routes: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        for entry in self.limiters.iter() {
+            let (in_flight, queued) = routes.entry(entry.key().0.clone()).or_default();
+            *in_flight += entry.value().in_flight();
+            *queued += entry.value().queued.load(Ordering::Acquire) as u64;
+        }
+        let mut stats = Vec::new();
+        for (route, (in_flight, queued)) in routes {
+            stats.push(PluginStat {
+                route: route.clone(),
+                stat: "in_flight",
+                value: in_flight,
+            });
+            stats.push(PluginStat {
+                route,
+                stat: "queued",
+                value: queued,
+            });
+        }
+        for entry in self.rejected.iter() {
+            stats.push(PluginStat {
+                route: entry.key().clone(),
+                stat: "rejected",
+                value: entry.value().load(Ordering::Relaxed),
+            });
+        }
+        stats
+    }
+}
+
+/// A request's slot. Dropping it frees the slot.
+struct ConnPermit {
+    permit: Option<OwnedSemaphorePermit>,
+    limits: Arc<Limits>,
+    id: LimitId,
+    limiter: Arc<Limiter>,
+}
+
+impl Drop for ConnPermit {
+    fn drop(&mut self) {
+        drop(self.permit.take());
+        self.limits.forget_if_idle(&self.id, &self.limiter);
+    }
+}
+
+/// A request's place in a queue. Dropping it leaves the queue.
+struct Waiter {
+    limits: Arc<Limits>,
+    id: LimitId,
+    limiter: Arc<Limiter>,
+}
+
+impl Waiter {
+    /// Wait up to `max_wait` for a slot.
+    async fn wait(self, max_wait: Duration) -> Option<ConnPermit> {
+        let permit = tokio::select! {
+            biased;
+            permit = Arc::clone(&self.limiter.slots).acquire_owned() => permit.ok(),
+            _ = background::sleep(max_wait) => None,
+        };
+        match permit {
+            Some(permit) => Some(ConnPermit {
+                permit: Some(permit),
+                limits: Arc::clone(&self.limits),
+                id: self.id.clone(),
+                limiter: Arc::clone(&self.limiter),
+            }),
+            None => {
+                self.limits.count_rejection(&self.id.0);
+                None
+            }
+        }
+    }
+}
+
+impl Drop for Waiter {
+    fn drop(&mut self) {
+        self.limiter.queued.fetch_sub(1, Ordering::AcqRel);
+        self.limits.forget_if_idle(&self.id, &self.limiter);
+    }
+}
+
+/// The slot a request holds, kept in `ctx.guards`. Filled when the
+/// request is admitted, which may be after its context moved on.
+#[derive(Clone, Default)]
+struct Held(Arc<Mutex<Option<ConnPermit>>>);
+
+impl Held {
+    fn set(&self, permit: ConnPermit) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(permit);
+    }
+
+    fn release(&self) {
+        let permit = self.0.lock().unwrap_or_else(|e| e.into_inner()).take();
+        drop(permit);
+    }
+}
+
+struct LimitConnInstance {
+    limits: Arc<Limits>,
+    conn: u32,
+    burst: u32,
+    max_wait: Duration,
+    key: LimitKey,
+    rejected_code: u16,
+}
+
+impl LimitConnInstance {
+    fn reject(&self) -> PluginResult {
+        PluginResult::Response {
+            status: self.rejected_code,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(
+                format!(
+                    r#"{{"error":"Too many concurrent requests","status":{}}}"#,
+                    self.rejected_code
+                )
+                .into_bytes(),
+            ),
+        }
+    }
+}
+
+impl Plugin for LimitConnPlugin {
+    fn name(&self) -> &str {
+        "limit-conn"
+    }
+
+    fn priority(&self) -> i32 {
+        1003
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access, Phase::Log]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: LimitConnConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("limit-conn config error: {e}"))?;
+        if cfg.conn == 0 {
+            anyhow::bail!("limit-conn: conn must be at least 1");
+        }
+        if !(200..=599).contains(&cfg.rejected_code) {
+            anyhow::bail!("limit-conn: rejected_code must be an HTTP status");
+        }
+        let key = LimitKey::parse(&cfg.key).map_err(|e| anyhow::anyhow!("limit-conn: {e}"))?;
+
+        Ok(Box::new(LimitConnInstance {
+            limits: Arc::clone(&self.limits),
+            conn: cfg.conn,
+            burst: cfg.burst,
+            max_wait: Duration::from_millis(cfg.default_conn_delay_ms),
+            key,
+            rejected_code: cfg.rejected_code,
+        }))
+    }
+
+    fn stats(&self) -> Vec<PluginStat> {
+        self.limits.stats()
+    }
+}
+
+impl PluginInstance for LimitConnInstance {
+    fn name(&self) -> &str {
+        "limit-conn"
+    }
+
+    fn priority(&self) -> i32 {
+        1003
+    }
+
+    fn uses_error_pages(&self) -> bool {
+        true
+    }
+
+    fn has_async_access(&self) -> bool {
+        true
+    }
+
+    fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let id = (ctx.route_id.clone(), self.key.resolve(ctx).to_string());
+        let result = match self.limits.admit(id, self.conn, self.burst) {
+            Admission::Admitted(permit) => {
+                let held = Held::default();
+                held.set(permit);
+                ctx.guards.push(Box::new(held));
+                return None;
+            }
+            Admission::Rejected => self.reject(),
+            Admission::Queued(waiter) => {
+                let held = Held::default();
+                ctx.guards.push(Box::new(held.clone()));
+                let (max_wait, rejection) = (self.max_wait, self.reject());
+                return Some(Box::pin(async move {
+                    let result = match waiter.wait(max_wait).await {
+                        Some(permit) => {
+                            held.set(permit);
+                            PluginResult::Continue
+                        }
+                        None => rejection,
+                    };
+                    AsyncAccess {
+                        result,
+                        response_headers: Vec::new(),
+                        upstream_headers: Vec::new(),
+                    }
+                }));
+            }
+        };
+        Some(Box::pin(std::future::ready(AsyncAccess {
+            result,
+            response_headers: Vec::new(),
+            upstream_headers: Vec::new(),
+        })))
+    }
+
+    fn logs(&self) -> bool {
+        true
+    }
+
+    fn log(&self, ctx: &PluginContext) {
+        for held in ctx.guards.iter().filter_map(|g| g.downcast_ref::<Held>()) {
+            held.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    fn make_ctx(route: &str, ip: &str) -> PluginContext {
+        PluginContext::new(
+            route.into(),
+            ip.into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        )
+    }
+
+    fn block_on<F: std::future::Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    fn stat(plugin: &LimitConnPlugin, route: &str, name: &str) -> u64 {
+        plugin
+            .stats()
+            .into_iter()
+            .find(|s| s.route == route && s.stat == name)
+            .map_or(0, |s| s.value)
+    }
+
+    /// Run the access hook: `None` when the request was admitted at once.
+    fn admit(inst: &dyn PluginInstance, ctx: &mut PluginContext) -> Option<PluginResult> {
+        inst.access_async(ctx).map(|fut| block_on(fut).result)
+    }
+
+    fn is_rejected(result: Option<PluginResult>, code: u16) -> bool {
+        matches!(result, Some(PluginResult::Response { status, .. }) if status == code)
+    }
+
+    #[test]
+    fn requests_beyond_conn_are_rejected_until_one_finishes() {
+        let plugin = LimitConnPlugin::new();
+        let inst = plugin
+            .configure(&serde_json::json!({ "conn": 2, "key": "route" }))
+            .unwrap();
+        let mut a = make_ctx("r1", "1.1.1.1");
+        let mut b = make_ctx("r1", "2.2.2.2");
+        assert!(admit(inst.as_ref(), &mut a).is_none());
+        assert!(admit(inst.as_ref(), &mut b).is_none());
+        assert_eq!(stat(&plugin, "r1", "in_flight"), 2);
+        assert!(is_rejected(
+            admit(inst.as_ref(), &mut make_ctx("r1", "3.3.3.3")),
+            503
+        ));
+        assert_eq!(stat(&plugin, "r1", "rejected"), 1);
+
+        // The log phase frees the slot.
+        inst.log(&a);
+        assert_eq!(stat(&plugin, "r1", "in_flight"), 1);
+        assert!(admit(inst.as_ref(), &mut make_ctx("r1", "3.3.3.3")).is_none());
+    }
+
+    #[test]
+    fn dropping_the_context_frees_the_slot() {
+        let plugin = LimitConnPlugin::new();
+        let inst = plugin.configure(&serde_json::json!({ "conn": 1 })).unwrap();
+        let mut ctx = make_ctx("r1", "1.1.1.1");
+        assert!(admit(inst.as_ref(), &mut ctx).is_none());
+        assert!(admit(inst.as_ref(), &mut make_ctx("r1", "1.1.1.1")).is_some());
+        // A request that never reaches the log phase.
+        drop(ctx);
+        assert!(admit(inst.as_ref(), &mut make_ctx("r1", "1.1.1.1")).is_none());
+        assert!(plugin.limits.limiters.is_empty());
+    }
+
+    #[test]
+    fn keys_and_routes_have_their_own_slots() {
+        let plugin = LimitConnPlugin::new();
+        let inst = plugin.configure(&serde_json::json!({ "conn": 1 })).unwrap();
+        let mut held = Vec::new();
+        for (route, ip) in [("r1", "a"), ("r1", "b"), ("r2", "a")] {
+            let mut ctx = make_ctx(route, ip);
+            assert!(admit(inst.as_ref(), &mut ctx).is_none(), "{route} {ip}");
+            held.push(ctx);
+        }
+        assert_eq!(stat(&plugin, "r1", "in_flight"), 2);
+        assert_eq!(stat(&plugin, "r2", "in_flight"), 1);
+    }
+
+    #[test]
+    fn queued_request_gets_the_slot_when_it_frees() {
+        let plugin = LimitConnPlugin::new();
+        let inst = plugin
+            .configure(&serde_json::json!({ "conn": 1, "burst": 1, "default_conn_delay_ms": 2000 }))
+            .unwrap();
+        let mut first = make_ctx("r1", "ip");
+        assert!(admit(inst.as_ref(), &mut first).is_none());
+        let mut second = make_ctx("r1", "ip");
+        let queued = inst.access_async(&mut second).unwrap();
+        assert_eq!(stat(&plugin, "r1", "queued"), 1);
+        // The queue is full.
+        assert!(is_rejected(
+            admit(inst.as_ref(), &mut make_ctx("r1", "ip")),
+            503
+        ));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(30));
+            drop(first);
+        });
+        let started = Instant::now();
+        assert!(matches!(block_on(queued).result, PluginResult::Continue));
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        release.join().unwrap();
+        assert_eq!(stat(&plugin, "r1", "in_flight"), 1);
+        assert_eq!(stat(&plugin, "r1", "queued"), 0);
+        drop(second);
+        assert!(plugin.limits.limiters.is_empty());
+    }
+
+    #[test]
+    fn queued_request_is_rejected_after_the_delay() {
+        let plugin = LimitConnPlugin::new();
+        let inst = plugin
+            .configure(&serde_json::json!({
+                "conn": 1, "burst": 4, "default_conn_delay_ms": 30, "rejected_code": 429
+            }))
+            .unwrap();
+        let mut first = make_ctx("r1", "ip");
+        assert!(admit(inst.as_ref(), &mut first).is_none());
+        let started = Instant::now();
+        assert!(is_rejected(
+            admit(inst.as_ref(), &mut make_ctx("r1", "ip")),
+            429
+        ));
+        assert!(started.elapsed() >= Duration::from_millis(30));
+        assert_eq!(stat(&plugin, "r1", "queued"), 0);
+        assert_eq!(stat(&plugin, "r1", "rejected"), 1);
+    }
+
+    #[test]
+    fn reconfigured_conn_takes_effect() {
+        let plugin = LimitConnPlugin::new();
+        let one = plugin.configure(&serde_json::json!({ "conn": 1 })).unwrap();
+        let mut held = make_ctx("r1", "ip");
+        assert!(admit(one.as_ref(), &mut held).is_none());
+        let two = plugin.configure(&serde_json::json!({ "conn": 2 })).unwrap();
+        assert!(admit(two.as_ref(), &mut make_ctx("r1", "ip")).is_none());
+    }
+
+    #[test]
+    fn plugin_name_priority_phases() {
+        let plugin = LimitConnPlugin::new();
+        assert_eq!(plugin.name(), "limit-conn");
+        assert_eq!(plugin.priority(), 1003);
+        assert_eq!(plugin.phases(), &[Phase::Access, Phase::Log]);
+        let inst = plugin.configure(&serde_json::json!({ "conn": 1 })).unwrap();
+        assert!(inst.has_async_access() && inst.logs());
+    }
+
+    #[test]
+    fn configure_validates_settings() {
+        let plugin = LimitConnPlugin::new();
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "conn": 0 }),
+            serde_json::json!({ "conn": 1, "burst": -1 }),
+            serde_json::json!({ "conn": 1, "rejected_code": 99 }),
+            serde_json::json!({ "conn": 1, "key": "cookie.sid" }),
+        ] {
+            assert!(plugin.configure(&config).is_err(), "{config}");
+        }
+        for key in ["route", "consumer_name", "header.x-tenant"] {
+            let config = serde_json::json!({ "conn": 5, "burst": 10, "key": key });
+            assert!(plugin.configure(&config).is_ok(), "{key}");
+        }
+    }
+}
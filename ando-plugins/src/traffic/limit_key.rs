@@ -0,0 +1,46 @@
+use ando_core::vars::query_arg;
+use ando_plugin::plugin::PluginContext;
+
+/// Which request identity a counter is keyed by.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum LimitKey {
+    /// One counter for the whole route.
+    Route,
+    RemoteAddr,
+    Consumer,
+    /// Lowercase header name.
+    Header(String),
+    Query(String),
+}
+
+impl LimitKey {
+    pub(crate) fn parse(key: &str) -> anyhow::Result<Self> {
+        match key {
+            "route" => Ok(Self::Route),
+            "remote_addr" => Ok(Self::RemoteAddr),
+            "consumer_name" => Ok(Self::Consumer),
+            _ => match key.split_once('.') {
+                Some(("header", name)) if !name.is_empty() => {
+                    Ok(Self::Header(name.to_ascii_lowercase()))
+                }
+                Some(("query", name)) if !name.is_empty() => Ok(Self::Query(name.to_string())),
+                _ => anyhow::bail!("unsupported limit key `{key}`"),
+            },
+        }
+    }
+
+    /// The identity for this request, falling back to the client IP.
+    pub(crate) fn resolve<'a>(&self, ctx: &'a PluginContext) -> &'a str {
+        let value = match self {
+            Self::Route => Some(ctx.route_id.as_str()),
+            Self::RemoteAddr => None,
+            Self::Consumer => ctx.consumer.as_deref(),
+            Self::Header(name) => ctx.get_header(name),
+            Self::Query(name) => ctx
+                .uri
+                .split_once('?')
+                .and_then(|(_, q)| query_arg(q, name)),
+        };
+        value.filter(|v| !v.is_empty()).unwrap_or(&ctx.client_ip)
+    }
+}
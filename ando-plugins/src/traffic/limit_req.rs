@@ -0,0 +1,475 @@
+use super::limit_key::LimitKey;
+use crate::background;
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Leaky-bucket request limiting.
+///
+/// Requests drain from each key's bucket at `rate` per second. Up to
+/// `burst` requests above that rate are queued: they are delayed until
+/// their slot comes up (or passed straight through with `nodelay`).
+/// Anything beyond the burst is rejected with `rejected_code`.
+///
+/// Every request is admitted or rejected in the sync access hook; only a
+/// delay is awaited in the async one, so a queued request never blocks the
+/// worker thread. Buckets follow the same per-worker semantics as
+/// `rate-limiting`'s local policy.
+pub struct LimitReqPlugin;
+
+/// Number of bucket shards; keys are spread by hash to cut lock contention.
+const SHARDS: usize = 16;
+
+/// How often a shard drops buckets that have fully drained.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Slack for float rounding when comparing the queue against `burst`.
+const EPSILON: f64 = 1e-9;
+
+/// `ctx.vars` entry handing a delay in seconds from `access` to
+/// `access_async`.
+const DELAY_VAR: &str = "_limit_req_delay";
+
+#[derive(Debug, Deserialize)]
+struct LimitReqConfig {
+    /// Sustained requests per second.
+    rate: f64,
+    /// Requests allowed above `rate` before rejecting.
+    #[serde(default)]
+    burst: f64,
+    /// Status for rejected requests — default 503.
+    #[serde(default = "default_rejected_code")]
+    rejected_code: u16,
+    /// Bucket key — default "remote_addr".
+    #[serde(default = "default_key", alias = "key_type")]
+    key: String,
+    /// Pass burst requests through immediately instead of delaying them.
+    #[serde(default)]
+    nodelay: bool,
+    /// Longest delay in milliseconds; requests that would wait longer are
+    /// rejected — default 1000.
+    #[serde(default = "default_max_delay")]
+    max_delay_ms: u64,
+}
+
+fn default_rejected_code() -> u16 {
+    503
+}
+fn default_key() -> String {
+    "remote_addr".to_string()
+}
+fn default_max_delay() -> u64 {
+    1000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Decision {
+    Pass,
+    /// Within the burst: proxy after this delay.
+    Delay(Duration),
+    Reject,
+}
+
+struct Bucket {
+    /// Requests queued ahead of the next one, as of `last`.
+    excess: f64,
+    last: Instant,
+}
+
+#[derive(Default)]
+struct Shard {
+    /// route → key → bucket.
+    buckets: HashMap<String, HashMap<String, Bucket>>,
+    last_sweep: Option<Instant>,
+}
+
+struct LimitReqInstance {
+    rate: f64,
+    burst: f64,
+    rejected_code: u16,
+    key: LimitKey,
+    nodelay: bool,
+    max_delay: Duration,
+    hasher: RandomState,
+    shards: Box<[Mutex<Shard>]>,
+}
+
+impl LimitReqInstance {
+    fn new(rate: f64, burst: f64, key: LimitKey) -> Self {
+        Self {
+            rate,
+            burst,
+            rejected_code: default_rejected_code(),
+            key,
+            nodelay: false,
+            max_delay: Duration::from_millis(default_max_delay()),
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    /// Admit a request for `(route, key)` arriving at `now`. Rejected
+    /// requests do not count against the bucket.
+    fn take(&self, route: &str, key: &str, now: Instant) -> Decision {
+        let idx = self.hasher.hash_one((route, key)) as usize % self.shards.len();
+        let mut shard = self.shards[idx].lock().unwrap_or_else(|e| e.into_inner());
+
+        if shard
+            .last_sweep
+            .is_none_or(|t| now.saturating_duration_since(t) >= SWEEP_INTERVAL)
+        {
+            let rate = self.rate;
+            shard.buckets.retain(|_, keys| {
+                keys.retain(|_, b| {
+                    b.excess - rate * now.saturating_duration_since(b.last).as_secs_f64() > 0.0
+                });
+                !keys.is_empty()
+            });
+            shard.last_sweep = Some(now);
+        }
+
+        if !shard.buckets.contains_key(route) {
+            shard.buckets.insert(route.to_string(), HashMap::new());
+        }
+        let keys = shard.buckets.get_mut(route).expect("inserted above");
+        let Some(bucket) = keys.get_mut(key) else {
+            // An idle key starts with an empty bucket.
+            keys.insert(
+                key.to_string(),
+                Bucket {
+                    excess: 0.0,
+                    last: now,
+                },
+            );
+            return Decision::Pass;
+        };
+
+        // Drain since the last admitted request, then queue this one.
+        let elapsed = now.saturating_duration_since(bucket.last).as_secs_f64();
+        let excess = (bucket.excess - self.rate * elapsed + 1.0).max(0.0);
+        if excess > self.burst + EPSILON {
+            return Decision::Reject;
+        }
+
+        let delay = Duration::from_secs_f64(excess / self.rate);
+        if !self.nodelay && delay > self.max_delay {
+            return Decision::Reject;
+        }
+        bucket.excess = excess;
+        bucket.last = now;
+        if self.nodelay || excess <= EPSILON {
+            Decision::Pass
+        } else {
+            Decision::Delay(delay)
+        }
+    }
+
+    fn reject(&self) -> PluginResult {
+        PluginResult::Response {
+            status: self.rejected_code,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(
+                format!(
+                    r#"{{"error":"Too many requests","status":{}}}"#,
+                    self.rejected_code
+                )
+                .into_bytes(),
+            ),
+        }
+    }
+
+    #[cfg(test)]
+    fn tracked_keys(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| {
+                s.lock()
+                    .unwrap()
+                    .buckets
+                    .values()
+                    .map(HashMap::len)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
+}
+
+impl Plugin for LimitReqPlugin {
+    fn name(&self) -> &str {
+        "limit-req"
+    }
+
+    fn priority(&self) -> i32 {
+        1001
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: LimitReqConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("limit-req config error: {e}"))?;
+        if !(cfg.rate > 0.0 && cfg.rate.is_finite()) {
+            anyhow::bail!("limit-req: rate must be a positive number");
+        }
+        if !(cfg.burst >= 0.0 && cfg.burst.is_finite()) {
+            anyhow::bail!("limit-req: burst must not be negative");
+        }
+        if !(200..=599).contains(&cfg.rejected_code) {
+            anyhow::bail!("limit-req: rejected_code must be an HTTP status");
+        }
+        let key = LimitKey::parse(&cfg.key).map_err(|e| anyhow::anyhow!("limit-req: {e}"))?;
+
+        Ok(Box::new(LimitReqInstance {
+            rejected_code: cfg.rejected_code,
+            nodelay: cfg.nodelay,
+            max_delay: Duration::from_millis(cfg.max_delay_ms),
+            ..LimitReqInstance::new(cfg.rate, cfg.burst, key)
+        }))
+    }
+}
+
+impl PluginInstance for LimitReqInstance {
+    fn name(&self) -> &str {
+        "limit-req"
+    }
+
+    fn priority(&self) -> i32 {
+        1001
+    }
+
+    fn uses_error_pages(&self) -> bool {
+        true
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        match self.take(&ctx.route_id, self.key.resolve(ctx), Instant::now()) {
+            Decision::Pass => PluginResult::Continue,
+            Decision::Delay(delay) => {
+                ctx.vars
+                    .insert(DELAY_VAR.to_string(), delay.as_secs_f64().into());
+                PluginResult::Continue
+            }
+            Decision::Reject => self.reject(),
+        }
+    }
+
+    fn has_async_access(&self) -> bool {
+        !self.nodelay
+    }
+
+    fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let delay = Duration::from_secs_f64(ctx.vars.remove(DELAY_VAR)?.as_f64()?);
+        Some(Box::pin(async move {
+            background::sleep(delay).await;
+            AsyncAccess {
+                result: PluginResult::Continue,
+                response_headers: Vec::new(),
+                upstream_headers: Vec::new(),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_ctx(ip: &str) -> PluginContext {
+        PluginContext::new(
+            "r1".into(),
+            ip.into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        )
+    }
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    fn nodelay(mut inst: LimitReqInstance) -> LimitReqInstance {
+        inst.nodelay = true;
+        inst
+    }
+
+    // ── Leaky bucket (driven by an explicit clock) ───────────────
+
+    #[test]
+    fn steady_rate_passes_and_faster_is_rejected() {
+        let inst = nodelay(LimitReqInstance::new(10.0, 0.0, LimitKey::RemoteAddr));
+        let t0 = Instant::now();
+        for i in 0..50 {
+            assert_eq!(inst.take("r1", "ip", t0 + ms(100 * i)), Decision::Pass);
+        }
+        // Half a slot after the last request: too early.
+        assert_eq!(inst.take("r1", "ip", t0 + ms(4950)), Decision::Reject);
+        // A rejected request does not use up the next slot.
+        assert_eq!(inst.take("r1", "ip", t0 + ms(5000)), Decision::Pass);
+    }
+
+    #[test]
+    fn burst_is_absorbed_with_increasing_delays() {
+        let inst = LimitReqInstance::new(10.0, 3.0, LimitKey::RemoteAddr);
+        let t0 = Instant::now();
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Pass);
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Delay(ms(100)));
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Delay(ms(200)));
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Delay(ms(300)));
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Reject);
+    }
+
+    #[test]
+    fn nodelay_passes_burst_immediately() {
+        let inst = nodelay(LimitReqInstance::new(10.0, 3.0, LimitKey::RemoteAddr));
+        let t0 = Instant::now();
+        for _ in 0..4 {
+            assert_eq!(inst.take("r1", "ip", t0), Decision::Pass);
+        }
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Reject);
+    }
+
+    #[test]
+    fn bucket_leaks_at_rate() {
+        let inst = nodelay(LimitReqInstance::new(10.0, 2.0, LimitKey::RemoteAddr));
+        let t0 = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(inst.take("r1", "ip", t0), Decision::Pass);
+        }
+        assert_eq!(inst.take("r1", "ip", t0 + ms(50)), Decision::Reject);
+        // One slot drained.
+        assert_eq!(inst.take("r1", "ip", t0 + ms(100)), Decision::Pass);
+        assert_eq!(inst.take("r1", "ip", t0 + ms(100)), Decision::Reject);
+        // Fully drained after burst / rate.
+        for _ in 0..3 {
+            assert_eq!(inst.take("r1", "ip", t0 + ms(500)), Decision::Pass);
+        }
+    }
+
+    #[test]
+    fn delays_beyond_max_delay_are_rejected() {
+        let mut inst = LimitReqInstance::new(1.0, 5.0, LimitKey::RemoteAddr);
+        inst.max_delay = ms(1500);
+        let t0 = Instant::now();
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Pass);
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Delay(ms(1000)));
+        assert_eq!(inst.take("r1", "ip", t0), Decision::Reject);
+    }
+
+    #[test]
+    fn keys_and_routes_have_independent_buckets() {
+        let inst = nodelay(LimitReqInstance::new(1.0, 0.0, LimitKey::RemoteAddr));
+        let t0 = Instant::now();
+        assert_eq!(inst.take("r1", "a", t0), Decision::Pass);
+        assert_eq!(inst.take("r1", "a", t0), Decision::Reject);
+        assert_eq!(inst.take("r1", "b", t0), Decision::Pass);
+        assert_eq!(inst.take("r2", "a", t0), Decision::Pass);
+    }
+
+    #[test]
+    fn drained_buckets_are_swept() {
+        let inst = nodelay(LimitReqInstance::new(10.0, 5.0, LimitKey::RemoteAddr));
+        let t0 = Instant::now();
+        for i in 0..100 {
+            inst.take("r1", &format!("10.0.0.{i}"), t0);
+            inst.take("r1", &format!("10.0.0.{i}"), t0);
+        }
+        assert_eq!(inst.tracked_keys(), 100);
+        let later = t0 + Duration::from_secs(2);
+        for i in 0..1000 {
+            inst.take("r1", &format!("10.1.{}.{}", i / 256, i % 256), later);
+        }
+        assert_eq!(inst.tracked_keys(), 1000);
+    }
+
+    // ── PluginInstance ───────────────────────────────────────────
+
+    fn configure(config: serde_json::Value) -> Box<dyn PluginInstance> {
+        LimitReqPlugin.configure(&config).unwrap()
+    }
+
+    fn run_async(inst: &dyn PluginInstance, ctx: &mut PluginContext) -> Option<PluginResult> {
+        assert!(matches!(inst.access(ctx), PluginResult::Continue));
+        let fut = inst.access_async(ctx)?;
+        let outcome = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut);
+        Some(outcome.result)
+    }
+
+    #[test]
+    fn nodelay_rejects_in_sync_access_with_configured_code() {
+        let inst = configure(serde_json::json!({
+            "rate": 1, "burst": 0, "nodelay": true, "rejected_code": 429
+        }));
+        assert!(!inst.has_async_access());
+        assert!(matches!(
+            inst.access(&mut make_ctx("1.1.1.1")),
+            PluginResult::Continue
+        ));
+        match inst.access(&mut make_ctx("1.1.1.1")) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 429);
+                assert!(String::from_utf8(body.unwrap()).unwrap().contains("429"));
+            }
+            PluginResult::Continue => panic!("expected rejection"),
+        }
+        assert!(matches!(
+            inst.access(&mut make_ctx("2.2.2.2")),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn burst_is_rejected_in_sync_access_and_delayed_in_async_hook() {
+        let inst = configure(serde_json::json!({ "rate": 20, "burst": 1 }));
+        assert!(inst.has_async_access());
+        // First request: no delay.
+        assert!(run_async(inst.as_ref(), &mut make_ctx("1.1.1.1")).is_none());
+
+        let mut delayed = make_ctx("1.1.1.1");
+        assert!(matches!(inst.access(&mut delayed), PluginResult::Continue));
+        assert!(matches!(
+            inst.access(&mut make_ctx("1.1.1.1")),
+            PluginResult::Response { status: 503, .. }
+        ));
+        let start = Instant::now();
+        let fut = inst.access_async(&mut delayed).unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        assert!(matches!(rt.block_on(fut).result, PluginResult::Continue));
+        assert!(start.elapsed() >= ms(30), "burst request should be delayed");
+        assert!(!delayed.vars.contains_key(DELAY_VAR));
+    }
+
+    #[test]
+    fn plugin_name_priority_phases() {
+        assert_eq!(LimitReqPlugin.name(), "limit-req");
+        assert_eq!(LimitReqPlugin.priority(), 1001);
+        assert_eq!(LimitReqPlugin.phases(), &[Phase::Access]);
+    }
+
+    #[test]
+    fn configure_validates_settings() {
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "rate": 0 }),
+            serde_json::json!({ "rate": 1, "burst": -1 }),
+            serde_json::json!({ "rate": 1, "rejected_code": 99 }),
+            serde_json::json!({ "rate": 1, "key": "cookie.sid" }),
+        ] {
+            assert!(LimitReqPlugin.configure(&config).is_err(), "{config}");
+        }
+        let config = serde_json::json!({ "rate": 5, "burst": 10, "key_type": "consumer_name" });
+        assert!(LimitReqPlugin.configure(&config).is_ok());
+    }
+}
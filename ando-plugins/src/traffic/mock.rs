@@ -0,0 +1,292 @@
+use crate::background;
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// Longest configurable `delay_ms`.
+const MAX_DELAY_MS: u64 = 60_000;
+
+/// Mock plugin — answers requests with a canned response.
+///
+/// Used for maintenance pages and API mocking. The route's upstream is never
+/// contacted, so routes with this plugin may omit `upstream` entirely.
+pub struct MockPlugin;
+
+#[derive(Debug, Deserialize)]
+struct MockConfig {
+    #[serde(default = "default_status")]
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    /// A string is sent as-is; any other JSON value is serialized.
+    #[serde(default)]
+    body: serde_json::Value,
+    /// Wait this long before answering, to simulate a slow backend.
+    #[serde(default)]
+    delay_ms: u64,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+struct MockInstance {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    delay: Option<Duration>,
+}
+
+impl Plugin for MockPlugin {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn priority(&self) -> i32 {
+        1 // last in the access phase, so auth and limits still apply
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn is_terminal(&self) -> bool {
+        true
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: MockConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("mock config error: {e}"))?;
+
+        if !(100..=599).contains(&cfg.status) {
+            anyhow::bail!("mock: status must be between 100 and 599");
+        }
+        if cfg.delay_ms > MAX_DELAY_MS {
+            anyhow::bail!("mock: delay_ms must be at most {MAX_DELAY_MS}");
+        }
+
+        let (body, default_type) = match cfg.body {
+            serde_json::Value::Null => (Vec::new(), None),
+            serde_json::Value::String(s) => (s.into_bytes(), Some("text/plain")),
+            value => (value.to_string().into_bytes(), Some("application/json")),
+        };
+
+        // content-length and connection are written by the proxy.
+        let mut headers: Vec<(String, String)> = cfg
+            .headers
+            .into_iter()
+            .map(|(k, v)| (k.to_lowercase(), v))
+            .filter(|(k, _)| k != "content-length" && k != "connection")
+            .collect();
+        headers.sort();
+        if let Some(content_type) = default_type
+            && !headers.iter().any(|(k, _)| k == "content-type")
+        {
+            headers.push(("content-type".to_string(), content_type.to_string()));
+        }
+
+        Ok(Box::new(MockInstance {
+            status: cfg.status,
+            headers,
+            body,
+            delay: (cfg.delay_ms > 0).then(|| Duration::from_millis(cfg.delay_ms)),
+        }))
+    }
+}
+
+impl MockInstance {
+    fn response(&self) -> PluginResult {
+        PluginResult::Response {
+            status: self.status,
+            headers: self.headers.clone(),
+            body: Some(self.body.clone()),
+        }
+    }
+}
+
+impl PluginInstance for MockInstance {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn priority(&self) -> i32 {
+        1
+    }
+
+    fn access(&self, _ctx: &mut PluginContext) -> PluginResult {
+        if self.delay.is_some() {
+            // Answered from `access_async`, where the delay can be awaited.
+            return PluginResult::Continue;
+        }
+        self.response()
+    }
+
+    fn has_async_access(&self) -> bool {
+        self.delay.is_some()
+    }
+
+    fn access_async(&self, _ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let delay = self.delay?;
+        let result = self.response();
+        Some(Box::pin(async move {
+            background::sleep(delay).await;
+            AsyncAccess {
+                result,
+                response_headers: Vec::new(),
+                upstream_headers: Vec::new(),
+            }
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    fn make_ctx() -> PluginContext {
+        PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        )
+    }
+
+    fn configure(config: serde_json::Value) -> Box<dyn PluginInstance> {
+        MockPlugin.configure(&config).unwrap()
+    }
+
+    fn header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn json_body_is_serialized_with_json_content_type() {
+        let inst = configure(serde_json::json!({
+            "status": 201,
+            "body": { "id": 7, "tags": ["a", "b"] }
+        }));
+        match inst.access(&mut make_ctx()) {
+            PluginResult::Response {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 201);
+                assert_eq!(header(&headers, "content-type"), Some("application/json"));
+                let body: serde_json::Value = serde_json::from_slice(&body.unwrap()).unwrap();
+                assert_eq!(body, serde_json::json!({ "id": 7, "tags": ["a", "b"] }));
+            }
+            PluginResult::Continue => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn string_body_is_sent_verbatim() {
+        let inst = configure(serde_json::json!({
+            "status": 503,
+            "headers": { "Content-Type": "text/html", "Retry-After": "120" },
+            "body": "<h1>Down for maintenance</h1>"
+        }));
+        match inst.access(&mut make_ctx()) {
+            PluginResult::Response {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 503);
+                assert_eq!(header(&headers, "content-type"), Some("text/html"));
+                assert_eq!(header(&headers, "retry-after"), Some("120"));
+                assert_eq!(body.unwrap(), b"<h1>Down for maintenance</h1>");
+            }
+            PluginResult::Continue => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn defaults_to_empty_200() {
+        let inst = configure(serde_json::json!({}));
+        match inst.access(&mut make_ctx()) {
+            PluginResult::Response {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 200);
+                assert!(headers.is_empty());
+                assert!(body.unwrap().is_empty());
+            }
+            PluginResult::Continue => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn proxy_owned_headers_are_dropped() {
+        let inst = configure(serde_json::json!({
+            "headers": { "Content-Length": "999", "Connection": "close", "X-Mock": "1" }
+        }));
+        match inst.access(&mut make_ctx()) {
+            PluginResult::Response { headers, .. } => {
+                assert_eq!(headers, vec![("x-mock".to_string(), "1".to_string())]);
+            }
+            PluginResult::Continue => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn delay_answers_from_async_access_after_waiting() {
+        let inst = configure(serde_json::json!({ "delay_ms": 50, "body": "late" }));
+        assert!(inst.has_async_access());
+        let mut ctx = make_ctx();
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+
+        let fut = inst.access_async(&mut ctx).expect("delayed response");
+        let start = Instant::now();
+        let outcome = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        match outcome.result {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 200);
+                assert_eq!(body.unwrap(), b"late");
+            }
+            PluginResult::Continue => panic!("expected a response"),
+        }
+    }
+
+    #[test]
+    fn no_delay_skips_async_access() {
+        let inst = configure(serde_json::json!({ "body": "now" }));
+        assert!(!inst.has_async_access());
+        assert!(inst.access_async(&mut make_ctx()).is_none());
+    }
+
+    #[test]
+    fn plugin_is_terminal() {
+        assert_eq!(MockPlugin.name(), "mock");
+        assert!(MockPlugin.is_terminal());
+        assert_eq!(MockPlugin.phases(), &[Phase::Access]);
+    }
+
+    #[test]
+    fn configure_rejects_invalid_settings() {
+        for config in [
+            serde_json::json!({ "status": 42 }),
+            serde_json::json!({ "status": 600 }),
+            serde_json::json!({ "delay_ms": MAX_DELAY_MS + 1 }),
+            serde_json::json!({ "headers": ["x"] }),
+        ] {
+            assert!(MockPlugin.configure(&config).is_err(), "{config}");
+        }
+    }
+}
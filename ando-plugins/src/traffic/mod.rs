@@ -1,4 +1,27 @@
+pub mod api_breaker;
+pub mod body_routing;
+pub mod client_control;
 pub mod cors;
+pub mod debug_echo;
+pub mod error_page;
+pub mod fault_injection;
 pub mod ip_restriction;
+pub mod limit_conn;
+pub(crate) mod limit_key;
+pub mod limit_req;
+pub mod mock;
+pub mod proxy_cache;
+pub mod proxy_mirror;
 pub mod rate_limiting;
+pub mod real_ip;
+pub mod redis_counter;
+pub mod referer_restriction;
+pub mod request_transformer;
+pub mod request_validation;
+pub mod response_headers_policy;
+pub mod response_transformer;
 pub mod security_headers;
+pub mod time_restriction;
+pub mod traffic_split;
+pub(crate) mod transform;
+pub mod ua_restriction;
@@ -0,0 +1,745 @@
+use ando_core::vars::{cookie_value, query_arg};
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Proxy cache plugin — serves repeated requests from an in-memory copy
+/// of the upstream response.
+///
+/// Lookups happen in the access phase: a fresh entry answers the request
+/// with `X-Cache-Status: HIT`. On a miss the upstream response is captured
+/// in the body-filter phase, so only bodies the proxy buffers for body
+/// filters (with a content-length up to `max_filtered_body_bytes`) are
+/// stored. Entries live in one size-bounded LRU shared by every worker
+/// and route. The outcome is left in `ctx.vars["cache_status"]` for the
+/// per-route hit ratio metric.
+pub struct ProxyCachePlugin {
+    store: Arc<CacheStore>,
+}
+
+/// Default total size of the shared cache.
+pub const DEFAULT_CACHE_CAPACITY: usize = 64 * 1024 * 1024;
+
+impl ProxyCachePlugin {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CACHE_CAPACITY)
+    }
+
+    /// A plugin whose cache holds at most `bytes` of keys, headers and
+    /// bodies.
+    pub fn with_capacity(bytes: usize) -> Self {
+        Self {
+            store: Arc::new(CacheStore::new(bytes)),
+        }
+    }
+}
+
+impl Default for ProxyCachePlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyCacheConfig {
+    #[serde(default = "default_ttl")]
+    ttl_secs: u64,
+    /// Parts of the key: `$`-prefixed variables or literal text.
+    #[serde(default = "default_cache_key")]
+    cache_key: Vec<String>,
+    #[serde(default = "default_cache_method")]
+    cache_method: Vec<String>,
+    #[serde(default = "default_cache_http_status")]
+    cache_http_status: Vec<u16>,
+    /// Larger bodies are passed through and not stored.
+    #[serde(default = "default_max_object_size")]
+    max_object_size: usize,
+    /// Request headers whose value is part of the key.
+    #[serde(default)]
+    vary: Vec<String>,
+    /// Variables that, when set and not `"0"`, skip the lookup.
+    #[serde(default)]
+    cache_bypass: Vec<String>,
+    /// Variables that, when set and not `"0"`, keep the response out of
+    /// the cache.
+    #[serde(default)]
+    no_cache: Vec<String>,
+    /// Store responses marked `no-store`, `no-cache` or `private`, or
+    /// setting a cookie.
+    #[serde(default)]
+    ignore_cache_control: bool,
+}
+
+fn default_ttl() -> u64 {
+    300
+}
+
+fn default_cache_key() -> Vec<String> {
+    vec![
+        "$request_method".to_string(),
+        "$host".to_string(),
+        "$request_uri".to_string(),
+    ]
+}
+
+fn default_cache_method() -> Vec<String> {
+    vec!["GET".to_string(), "HEAD".to_string()]
+}
+
+fn default_cache_http_status() -> Vec<u16> {
+    vec![200, 301, 404]
+}
+
+fn default_max_object_size() -> usize {
+    1024 * 1024
+}
+
+/// A request variable usable in `cache_key`, `cache_bypass` and
+/// `no_cache`.
+#[derive(Debug, Clone, PartialEq)]
+enum Var {
+    Method,
+    Host,
+    /// Path without the query string.
+    Uri,
+    /// Path and query string.
+    RequestUri,
+    RemoteAddr,
+    /// Lowercase header name.
+    Header(String),
+    Arg(String),
+    Cookie(String),
+    Literal(String),
+}
+
+impl Var {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let Some(name) = s.strip_prefix('$') else {
+            return Ok(Self::Literal(s.to_string()));
+        };
+        Ok(match name {
+            "request_method" => Self::Method,
+            "host" => Self::Host,
+            "uri" => Self::Uri,
+            "request_uri" => Self::RequestUri,
+            "remote_addr" => Self::RemoteAddr,
+            _ => {
+                if let Some(h) = name.strip_prefix("http_") {
+                    Self::Header(h.replace('_', "-").to_ascii_lowercase())
+                } else if let Some(a) = name.strip_prefix("arg_") {
+                    Self::Arg(a.to_string())
+                } else if let Some(c) = name.strip_prefix("cookie_") {
+                    Self::Cookie(c.to_string())
+                } else {
+                    anyhow::bail!("proxy-cache config error: unknown variable `{s}`")
+                }
+            }
+        })
+    }
+
+    fn resolve<'a>(&'a self, ctx: &'a PluginContext) -> Option<&'a str> {
+        match self {
+            Self::Method => Some(&ctx.method),
+            Self::Host => ctx.get_header("host"),
+            Self::Uri => Some(ctx.uri.split_once('?').map_or(&ctx.uri, |(p, _)| p)),
+            Self::RequestUri => Some(&ctx.uri),
+            Self::RemoteAddr => Some(&ctx.client_ip),
+            Self::Header(name) => ctx.get_header(name),
+            Self::Arg(name) => ctx
+                .uri
+                .split_once('?')
+                .and_then(|(_, q)| query_arg(q, name)),
+            Self::Cookie(name) => ctx.get_header("cookie").and_then(|c| cookie_value(c, name)),
+            Self::Literal(text) => Some(text),
+        }
+    }
+}
+
+fn parse_vars(list: &[String]) -> anyhow::Result<Vec<Var>> {
+    list.iter().map(|s| Var::parse(s)).collect()
+}
+
+/// Whether any of `vars` is set to something other than `""` or `"0"`.
+fn any_set(vars: &[Var], ctx: &PluginContext) -> bool {
+    vars.iter()
+        .filter_map(|v| v.resolve(ctx))
+        .any(|v| !v.is_empty() && v != "0")
+}
+
+struct ProxyCacheInstance {
+    store: Arc<CacheStore>,
+    ttl: Duration,
+    key: Vec<Var>,
+    methods: Vec<String>,
+    statuses: Vec<u16>,
+    max_object_size: usize,
+    vary: Vec<String>,
+    bypass: Vec<Var>,
+    no_cache: Vec<Var>,
+    ignore_cache_control: bool,
+}
+
+impl Plugin for ProxyCachePlugin {
+    fn name(&self) -> &str {
+        "proxy-cache"
+    }
+
+    fn priority(&self) -> i32 {
+        1085 // APISIX default; after auth and rate limiting
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access, Phase::BodyFilter]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ProxyCacheConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("proxy-cache config error: {e}"))?;
+        if cfg.ttl_secs == 0 {
+            anyhow::bail!("proxy-cache config error: ttl_secs must be at least 1");
+        }
+        if cfg.cache_key.is_empty() {
+            anyhow::bail!("proxy-cache config error: cache_key must not be empty");
+        }
+
+        Ok(Box::new(ProxyCacheInstance {
+            store: Arc::clone(&self.store),
+            ttl: Duration::from_secs(cfg.ttl_secs),
+            key: parse_vars(&cfg.cache_key)?,
+            methods: cfg
+                .cache_method
+                .iter()
+                .map(|m| m.to_ascii_uppercase())
+                .collect(),
+            statuses: cfg.cache_http_status,
+            max_object_size: cfg.max_object_size,
+            vary: cfg.vary.iter().map(|h| h.to_ascii_lowercase()).collect(),
+            bypass: parse_vars(&cfg.cache_bypass)?,
+            no_cache: parse_vars(&cfg.no_cache)?,
+            ignore_cache_control: cfg.ignore_cache_control,
+        }))
+    }
+}
+
+impl ProxyCacheInstance {
+    /// The entry key: route, `cache_key` parts and `vary` header values.
+    fn cache_key(&self, ctx: &PluginContext) -> String {
+        let mut key = ctx.route_id.clone();
+        for var in &self.key {
+            key.push('\u{1f}');
+            key.push_str(var.resolve(ctx).unwrap_or(""));
+        }
+        for name in &self.vary {
+            key.push('\u{1f}');
+            key.push_str(name);
+            key.push('=');
+            key.push_str(ctx.get_header(name).unwrap_or(""));
+        }
+        key
+    }
+
+    /// Whether the upstream allows the response to be stored. A response
+    /// setting a cookie is meant for one client, so like `private` it is
+    /// only stored with `ignore_cache_control`.
+    fn storable(&self, headers: &[(String, String)]) -> bool {
+        let forbids = |v: &str| {
+            v.split(',').map(str::trim).any(|d| {
+                d.eq_ignore_ascii_case("no-store")
+                    || d.eq_ignore_ascii_case("no-cache")
+                    || d.eq_ignore_ascii_case("private")
+                    || d == "*"
+            })
+        };
+        headers.iter().all(|(k, v)| match k.as_str() {
+            "set-cookie" => self.ignore_cache_control,
+            "cache-control" if !self.ignore_cache_control => !forbids(v),
+            "vary" => !forbids(v),
+            _ => true,
+        })
+    }
+}
+
+/// Headers of the upstream connection rather than of the response; not
+/// stored, nor any header the response's `Connection` names.
+const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// The upstream headers replayed with a stored response.
+fn stored_headers(headers: &[(String, String)]) -> Vec<(String, String)> {
+    let listed: Vec<&str> = headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("connection"))
+        .flat_map(|(_, v)| v.split(','))
+        .map(str::trim)
+        .collect();
+    headers
+        .iter()
+        .filter(|(k, _)| {
+            !HOP_BY_HOP
+                .iter()
+                .chain(&listed)
+                .any(|n| k.eq_ignore_ascii_case(n))
+        })
+        .cloned()
+        .collect()
+}
+
+fn set_status(ctx: &mut PluginContext, status: &'static str) {
+    ctx.vars.insert("cache_status".to_string(), status.into());
+    ctx.response_headers
+        .insert("x-cache-status".to_string(), status.to_string());
+}
+
+impl PluginInstance for ProxyCacheInstance {
+    fn name(&self) -> &str {
+        "proxy-cache"
+    }
+
+    fn priority(&self) -> i32 {
+        1085
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        if !self.methods.contains(&ctx.method) {
+            return PluginResult::Continue;
+        }
+        let key = self.cache_key(ctx);
+        if any_set(&self.bypass, ctx) {
+            set_status(ctx, "BYPASS");
+        } else if let Some(hit) = self.store.get(&key, Instant::now()) {
+            set_status(ctx, "HIT");
+            let mut headers = hit.headers.clone();
+            headers.push(("x-cache-status".to_string(), "HIT".to_string()));
+            return PluginResult::Response {
+                status: hit.status,
+                headers,
+                body: Some(hit.body.clone()),
+            };
+        } else {
+            set_status(ctx, "MISS");
+        }
+        if !any_set(&self.no_cache, ctx) {
+            ctx.vars.insert("cache_key".to_string(), key.into());
+        }
+        PluginResult::Continue
+    }
+
+    fn filters_body(&self) -> bool {
+        true
+    }
+
+    fn body_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+        let Some(key) = ctx.vars.remove("cache_key") else {
+            return PluginResult::Continue;
+        };
+        let (Some(key), Some(status), Some(body)) = (
+            key.as_str(),
+            ctx.response_status,
+            ctx.response_body.as_ref(),
+        ) else {
+            return PluginResult::Continue;
+        };
+        if self.statuses.contains(&status)
+            && body.len() <= self.max_object_size
+            && self.storable(&ctx.upstream_response_headers)
+        {
+            self.store.insert(
+                key.to_string(),
+                CachedResponse {
+                    status,
+                    headers: stored_headers(&ctx.upstream_response_headers),
+                    body: body.clone(),
+                    expires: Instant::now() + self.ttl,
+                },
+            );
+        }
+        PluginResult::Continue
+    }
+}
+
+// ── Store ────────────────────────────────────────────────────────────────
+
+/// A stored upstream response.
+#[derive(Debug)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    expires: Instant,
+}
+
+impl CachedResponse {
+    fn size(&self, key: &str) -> usize {
+        key.len()
+            + self.body.len()
+            + self
+                .headers
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+    }
+}
+
+/// Size-bounded LRU of cached responses, shared across workers.
+struct CacheStore {
+    capacity: usize,
+    inner: Mutex<Lru>,
+}
+
+#[derive(Default)]
+struct Lru {
+    entries: HashMap<String, Slot>,
+    /// Last use → key, oldest first.
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    size: usize,
+}
+
+struct Slot {
+    response: Arc<CachedResponse>,
+    used: u64,
+    size: usize,
+}
+
+impl Lru {
+    fn remove(&mut self, key: &str) {
+        if let Some(slot) = self.entries.remove(key) {
+            self.order.remove(&slot.used);
+            self.size -= slot.size;
+        }
+    }
+}
+
+impl CacheStore {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(Lru::default()),
+        }
+    }
+
+    /// A fresh entry for `key`. Expired entries are dropped.
+    fn get(&self, key: &str, now: Instant) -> Option<Arc<CachedResponse>> {
+        let mut lru = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let lru = &mut *lru;
+        let slot = lru.entries.get_mut(key)?;
+        if slot.response.expires <= now {
+            lru.remove(key);
+            return None;
+        }
+        lru.tick += 1;
+        lru.order.remove(&slot.used);
+        slot.used = lru.tick;
+        lru.order.insert(lru.tick, key.to_string());
+        Some(Arc::clone(&slot.response))
+    }
+
+    /// Store `response`, evicting the least recently used entries to make
+    /// room. Responses bigger than the whole cache are not stored.
+    fn insert(&self, key: String, response: CachedResponse) {
+        let size = response.size(&key);
+        if size > self.capacity {
+            return;
+        }
+        let mut lru = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        lru.remove(&key);
+        while lru.size + size > self.capacity {
+            let Some((_, oldest)) = lru.order.pop_first() else {
+                break;
+            };
+            if let Some(slot) = lru.entries.remove(&oldest) {
+                lru.size -= slot.size;
+            }
+        }
+        lru.tick += 1;
+        let used = lru.tick;
+        lru.order.insert(used, key.clone());
+        lru.size += size;
+        lru.entries.insert(
+            key,
+            Slot {
+                response: Arc::new(response),
+                used,
+                size,
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instance(plugin: &ProxyCachePlugin, config: serde_json::Value) -> Box<dyn PluginInstance> {
+        plugin.configure(&config).unwrap()
+    }
+
+    fn make_ctx(uri: &str, headers: &[(&str, &str)]) -> PluginContext {
+        let mut headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        headers
+            .entry("host".to_string())
+            .or_insert_with(|| "api.test".to_string());
+        PluginContext::new(
+            "r1".into(),
+            "127.0.0.1".into(),
+            "GET".into(),
+            uri.into(),
+            headers,
+        )
+    }
+
+    /// Run one request through the plugin. On a miss the upstream answers
+    /// with `status`, `headers` and `body`. Returns the cache status.
+    fn exchange(
+        p: &dyn PluginInstance,
+        ctx: &mut PluginContext,
+        status: u16,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> String {
+        if let PluginResult::Response {
+            status: hit,
+            body: b,
+            ..
+        } = p.access(ctx)
+        {
+            assert_eq!(hit, status);
+            assert_eq!(b.as_deref(), Some(body));
+        } else {
+            ctx.response_status = Some(status);
+            ctx.response_body = Some(body.to_vec());
+            ctx.upstream_response_headers = headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            assert!(matches!(p.body_filter(ctx), PluginResult::Continue));
+        }
+        ctx.vars
+            .get("cache_status")
+            .and_then(|v| v.as_str())
+            .unwrap_or("")
+            .to_string()
+    }
+
+    fn get(p: &dyn PluginInstance, uri: &str, headers: &[(&str, &str)]) -> String {
+        exchange(p, &mut make_ctx(uri, headers), 200, &[], b"catalog")
+    }
+
+    #[test]
+    fn miss_then_hit() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(&plugin, serde_json::json!({}));
+        assert_eq!(get(p.as_ref(), "/items?page=1", &[]), "MISS");
+        let mut ctx = make_ctx("/items?page=1", &[]);
+        match p.access(&mut ctx) {
+            PluginResult::Response {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 200);
+                assert_eq!(body.unwrap(), b"catalog");
+                assert!(headers.contains(&("x-cache-status".to_string(), "HIT".to_string())));
+            }
+            PluginResult::Continue => panic!("expected a hit"),
+        }
+        // Another query string is another entry.
+        assert_eq!(get(p.as_ref(), "/items?page=2", &[]), "MISS");
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let store = CacheStore::new(1024);
+        let now = Instant::now();
+        store.insert(
+            "k".to_string(),
+            CachedResponse {
+                status: 200,
+                headers: Vec::new(),
+                body: b"x".to_vec(),
+                expires: now + Duration::from_secs(1),
+            },
+        );
+        assert!(store.get("k", now).is_some());
+        assert!(store.get("k", now + Duration::from_secs(1)).is_none());
+        // The expired entry is gone for good.
+        assert!(store.get("k", now).is_none());
+        assert_eq!(store.inner.lock().unwrap().size, 0);
+    }
+
+    #[test]
+    fn vary_header_is_part_of_the_key() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(&plugin, serde_json::json!({ "vary": ["Accept-Language"] }));
+        assert_eq!(get(p.as_ref(), "/", &[("accept-language", "en")]), "MISS");
+        assert_eq!(get(p.as_ref(), "/", &[("accept-language", "de")]), "MISS");
+        assert_eq!(get(p.as_ref(), "/", &[("accept-language", "en")]), "HIT");
+        assert_eq!(get(p.as_ref(), "/", &[("accept-language", "de")]), "HIT");
+        assert_eq!(get(p.as_ref(), "/", &[]), "MISS");
+    }
+
+    #[test]
+    fn oversized_bodies_are_not_stored() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(&plugin, serde_json::json!({ "max_object_size": 4 }));
+        let big = |p: &dyn PluginInstance| exchange(p, &mut make_ctx("/", &[]), 200, &[], b"12345");
+        assert_eq!(big(p.as_ref()), "MISS");
+        assert_eq!(big(p.as_ref()), "MISS");
+        let small =
+            |p: &dyn PluginInstance| exchange(p, &mut make_ctx("/s", &[]), 200, &[], b"1234");
+        assert_eq!(small(p.as_ref()), "MISS");
+        assert_eq!(small(p.as_ref()), "HIT");
+    }
+
+    #[test]
+    fn cache_control_is_respected_unless_ignored() {
+        let plugin = ProxyCachePlugin::new();
+        let private = [("cache-control", "max-age=60, private")];
+        let run = |p: &dyn PluginInstance, uri: &str| {
+            exchange(p, &mut make_ctx(uri, &[]), 200, &private, b"me")
+        };
+        let p = instance(&plugin, serde_json::json!({}));
+        assert_eq!(run(p.as_ref(), "/me"), "MISS");
+        assert_eq!(run(p.as_ref(), "/me"), "MISS");
+        let p = instance(&plugin, serde_json::json!({ "ignore_cache_control": true }));
+        assert_eq!(run(p.as_ref(), "/me2"), "MISS");
+        assert_eq!(run(p.as_ref(), "/me2"), "HIT");
+    }
+
+    #[test]
+    fn set_cookie_responses_are_never_hits() {
+        let plugin = ProxyCachePlugin::new();
+        let cookie = [("set-cookie", "session=alice; HttpOnly")];
+        let run = |p: &dyn PluginInstance, uri: &str| {
+            exchange(p, &mut make_ctx(uri, &[]), 200, &cookie, b"alice")
+        };
+        let p = instance(&plugin, serde_json::json!({}));
+        assert_eq!(run(p.as_ref(), "/profile"), "MISS");
+        assert_eq!(run(p.as_ref(), "/profile"), "MISS");
+        assert!(matches!(
+            p.access(&mut make_ctx("/profile", &[])),
+            PluginResult::Continue
+        ));
+        let p = instance(&plugin, serde_json::json!({ "ignore_cache_control": true }));
+        assert_eq!(run(p.as_ref(), "/profile2"), "MISS");
+        assert_eq!(run(p.as_ref(), "/profile2"), "HIT");
+    }
+
+    #[test]
+    fn hop_by_hop_headers_are_not_stored() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(&plugin, serde_json::json!({}));
+        let upstream = [
+            ("connection", "keep-alive, x-upstream-conn"),
+            ("keep-alive", "timeout=5"),
+            ("x-upstream-conn", "1"),
+            ("transfer-encoding", "chunked"),
+            ("content-type", "text/plain"),
+        ];
+        assert_eq!(
+            exchange(p.as_ref(), &mut make_ctx("/", &[]), 200, &upstream, b"x"),
+            "MISS"
+        );
+        let PluginResult::Response { headers, .. } = p.access(&mut make_ctx("/", &[])) else {
+            panic!("expected a hit");
+        };
+        assert_eq!(
+            headers,
+            [
+                ("content-type".to_string(), "text/plain".to_string()),
+                ("x-cache-status".to_string(), "HIT".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn bypass_and_no_cache_conditions() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(
+            &plugin,
+            serde_json::json!({
+                "cache_key": ["$uri"],
+                "cache_bypass": ["$arg_fresh"],
+                "no_cache": ["$http_x_no_cache"]
+            }),
+        );
+        // Not stored: no_cache is set.
+        assert_eq!(get(p.as_ref(), "/c", &[("x-no-cache", "1")]), "MISS");
+        assert_eq!(get(p.as_ref(), "/c", &[("x-no-cache", "0")]), "MISS");
+        assert_eq!(get(p.as_ref(), "/c", &[]), "HIT");
+        // A bypassed request skips the lookup but refreshes the entry.
+        assert_eq!(get(p.as_ref(), "/c?fresh=1", &[]), "BYPASS");
+        assert_eq!(get(p.as_ref(), "/c?fresh=0", &[]), "HIT");
+    }
+
+    #[test]
+    fn other_methods_and_statuses_pass_through() {
+        let plugin = ProxyCachePlugin::new();
+        let p = instance(&plugin, serde_json::json!({}));
+        let mut post = make_ctx("/", &[]);
+        post.method = "POST".into();
+        assert_eq!(exchange(p.as_ref(), &mut post, 200, &[], b"x"), "");
+        assert!(post.response_headers.is_empty());
+        let err = |p: &dyn PluginInstance| exchange(p, &mut make_ctx("/e", &[]), 500, &[], b"x");
+        assert_eq!(err(p.as_ref()), "MISS");
+        assert_eq!(err(p.as_ref()), "MISS");
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let store = CacheStore::new(30);
+        let now = Instant::now();
+        let entry = || CachedResponse {
+            status: 200,
+            headers: Vec::new(),
+            body: vec![0; 9],
+            expires: now + Duration::from_secs(60),
+        };
+        store.insert("a".into(), entry());
+        store.insert("b".into(), entry());
+        store.insert("c".into(), entry());
+        assert!(store.get("a", now).is_some());
+        store.insert("d".into(), entry());
+        assert!(store.get("b", now).is_none());
+        assert!(store.get("a", now).is_some());
+        assert!(store.get("c", now).is_some());
+        assert!(store.get("d", now).is_some());
+        // Bigger than the whole cache: not stored, nothing evicted.
+        store.insert(
+            "e".into(),
+            CachedResponse {
+                body: vec![0; 40],
+                ..entry()
+            },
+        );
+        assert!(store.get("e", now).is_none());
+        assert!(store.get("a", now).is_some());
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        let plugin = ProxyCachePlugin::new();
+        assert!(
+            plugin
+                .configure(&serde_json::json!({ "ttl_secs": 0 }))
+                .is_err()
+        );
+        assert!(
+            plugin
+                .configure(&serde_json::json!({ "cache_key": ["$nope"] }))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::background;
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+use std::cell::Cell;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{Semaphore, mpsc};
+use tracing::debug;
+
+/// Proxy mirror plugin — shadows a sample of requests to another service.
+///
+/// Sampled requests are copied (method, original URI, headers, buffered
+/// body) onto a bounded queue in the before-proxy phase, once the primary
+/// upstream is chosen. A sender on the plugins' background runtime fires
+/// them at the mirror with a short timeout and discards the responses, so
+/// the primary request is never delayed or failed. When the queue is full
+/// the copy is dropped. The outcome (`queued` or `dropped`) is left in
+/// `ctx.vars["mirror"]` for the per-route metric.
+pub struct ProxyMirrorPlugin {
+    sender: Arc<MirrorSender>,
+}
+
+/// Mirrored requests waiting for the sender.
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+/// Mirrored requests in flight at once.
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+
+impl ProxyMirrorPlugin {
+    pub fn new() -> Self {
+        Self::with_limits(DEFAULT_QUEUE_CAPACITY, DEFAULT_MAX_IN_FLIGHT)
+    }
+
+    /// A plugin queueing at most `queue` requests and sending at most
+    /// `in_flight` at a time.
+    pub fn with_limits(queue: usize, in_flight: usize) -> Self {
+        Self {
+            sender: Arc::new(MirrorSender {
+                queue: queue.max(1),
+                in_flight: in_flight.max(1),
+                tx: OnceLock::new(),
+            }),
+        }
+    }
+}
+
+impl Default for ProxyMirrorPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ProxyMirrorConfig {
+    /// Mirror base URL, e.g. `http://10.0.0.9:8080`.
+    host: String,
+    #[serde(default = "default_sample_ratio")]
+    sample_ratio: f64,
+    #[serde(default)]
+    path_concat_mode: PathConcatMode,
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_timeout_ms() -> u64 {
+    1000
+}
+
+/// How the request URI is joined to `host`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PathConcatMode {
+    /// `host` is scheme and authority only; the request URI is the path.
+    #[default]
+    Replace,
+    /// The request URI is appended to the path of `host`.
+    Prefix,
+}
+
+struct ProxyMirrorInstance {
+    sender: Arc<MirrorSender>,
+    /// `host` without a trailing slash.
+    base: String,
+    sample_ratio: f64,
+    timeout: Duration,
+}
+
+impl Plugin for ProxyMirrorPlugin {
+    fn name(&self) -> &str {
+        "proxy-mirror"
+    }
+
+    fn priority(&self) -> i32 {
+        1010 // APISIX default
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::BeforeProxy]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ProxyMirrorConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("proxy-mirror config error: {e}"))?;
+        let url = reqwest::Url::parse(&cfg.host)
+            .map_err(|e| anyhow::anyhow!("proxy-mirror config error: invalid host: {e}"))?;
+        if !matches!(url.scheme(), "http" | "https") || !url.has_host() {
+            anyhow::bail!("proxy-mirror config error: host must be an http(s) URL");
+        }
+        if url.query().is_some() {
+            anyhow::bail!("proxy-mirror config error: host must not have a query string");
+        }
+        if cfg.path_concat_mode == PathConcatMode::Replace && url.path() != "/" {
+            anyhow::bail!(
+                "proxy-mirror config error: host must not have a path unless path_concat_mode is prefix"
+            );
+        }
+        if !(0.0..=1.0).contains(&cfg.sample_ratio) {
+            anyhow::bail!("proxy-mirror config error: sample_ratio must be between 0 and 1");
+        }
+
+        Ok(Box::new(ProxyMirrorInstance {
+            sender: Arc::clone(&self.sender),
+            base: url.as_str().trim_end_matches('/').to_string(),
+            sample_ratio: cfg.sample_ratio,
+            timeout: Duration::from_millis(cfg.timeout_ms.max(1)),
+        }))
+    }
+}
+
+/// Headers not copied to the mirror: hop-by-hop, framing (the body is
+/// re-framed), and `host`, which comes from the mirror URL.
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "connection",
+    "keep-alive",
+    "transfer-encoding",
+    "content-length",
+    "expect",
+    "upgrade",
+    "te",
+    "trailer",
+    "proxy-connection",
+];
+
+impl ProxyMirrorInstance {
+    /// The mirror copy of the request, or `None` when its body was not
+    /// fully buffered (streamed uploads) and cannot be replayed.
+    fn mirrored(&self, ctx: &PluginContext) -> Option<Mirrored> {
+        let body = ctx.request_body.clone().unwrap_or_default();
+        let declared = ctx
+            .get_header("content-length")
+            .and_then(|v| v.trim().parse::<usize>().ok());
+        if declared.is_some_and(|len| len != body.len()) {
+            return None;
+        }
+        let headers = ctx
+            .request_headers
+            .iter()
+            .filter(|(k, _)| !SKIPPED_HEADERS.iter().any(|s| k.eq_ignore_ascii_case(s)))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Some(Mirrored {
+            method: ctx.method.clone(),
+            url: format!("{}{}", self.base, ctx.uri),
+            headers,
+            body,
+            timeout: self.timeout,
+        })
+    }
+}
+
+impl PluginInstance for ProxyMirrorInstance {
+    fn name(&self) -> &str {
+        "proxy-mirror"
+    }
+
+    fn priority(&self) -> i32 {
+        1010
+    }
+
+    fn reads_body(&self) -> bool {
+        true
+    }
+
+    fn before_proxy(&self, ctx: &mut PluginContext) -> PluginResult {
+        if !sampled(self.sample_ratio) {
+            return PluginResult::Continue;
+        }
+        let Some(request) = self.mirrored(ctx) else {
+            debug!(route = %ctx.route_id, "Not mirroring a request with a streamed body");
+            return PluginResult::Continue;
+        };
+        let outcome = if self.sender.enqueue(request) {
+            "queued"
+        } else {
+            "dropped"
+        };
+        ctx.vars.insert("mirror".to_string(), outcome.into());
+        PluginResult::Continue
+    }
+}
+
+// ── Sampling ─────────────────────────────────────────────────────────────
+
+thread_local! {
+    static SAMPLE_STATE: Cell<u64> = Cell::new(sample_seed());
+}
+
+fn sample_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(std::thread::current().id()) | 1
+}
+
+/// Whether to mirror this request, with probability `ratio`.
+fn sampled(ratio: f64) -> bool {
+    if ratio >= 1.0 {
+        return true;
+    }
+    if ratio <= 0.0 {
+        return false;
+    }
+    let x = SAMPLE_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+    // Top 53 bits as a uniform float in [0, 1).
+    ((x >> 11) as f64 / (1u64 << 53) as f64) < ratio
+}
+
+// ── Sender ───────────────────────────────────────────────────────────────
+
+/// One request copy for the mirror.
+#[derive(Debug)]
+struct Mirrored {
+    method: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+    timeout: Duration,
+}
+
+/// Bounded queue drained by a task on the background runtime, started on
+/// first use.
+struct MirrorSender {
+    queue: usize,
+    in_flight: usize,
+    tx: OnceLock<mpsc::Sender<Mirrored>>,
+}
+
+impl MirrorSender {
+    /// Queue `request`. `false` when the queue is full and it was dropped.
+    fn enqueue(&self, request: Mirrored) -> bool {
+        let tx = self.tx.get_or_init(|| {
+            let (tx, rx) = mpsc::channel(self.queue);
+            background::runtime().spawn(send_loop(rx, self.in_flight));
+            tx
+        });
+        tx.try_send(request).is_ok()
+    }
+}
+
+/// Send queued requests, at most `in_flight` at once. While all permits
+/// are taken the queue fills up and further copies are dropped.
+async fn send_loop(mut rx: mpsc::Receiver<Mirrored>, in_flight: usize) {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap_or_default();
+    let permits = Arc::new(Semaphore::new(in_flight));
+    loop {
+        let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+            return;
+        };
+        let Some(request) = rx.recv().await else {
+            return;
+        };
+        let client = client.clone();
+        tokio::spawn(async move {
+            send(&client, request).await;
+            drop(permit);
+        });
+    }
+}
+
+async fn send(client: &reqwest::Client, request: Mirrored) {
+    let Ok(method) = reqwest::Method::from_bytes(request.method.as_bytes()) else {
+        return;
+    };
+    let mut builder = client
+        .request(method, &request.url)
+        .timeout(request.timeout)
+        .body(request.body);
+    for (name, value) in &request.headers {
+        builder = builder.header(name, value);
+    }
+    if let Err(e) = builder.send().await {
+        debug!(url = %request.url, error = %e, "Mirror request failed");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc as std_mpsc;
+
+    fn instance(plugin: &ProxyMirrorPlugin, config: serde_json::Value) -> Box<dyn PluginInstance> {
+        plugin.configure(&config).unwrap()
+    }
+
+    fn make_ctx(uri: &str, headers: &[(&str, &str)], body: &[u8]) -> PluginContext {
+        let headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "127.0.0.1".into(),
+            "POST".into(),
+            uri.into(),
+            headers,
+        );
+        ctx.request_body = Some(body.to_vec());
+        ctx
+    }
+
+    fn mirror(p: &dyn PluginInstance, ctx: &mut PluginContext) -> Option<String> {
+        assert!(matches!(p.before_proxy(ctx), PluginResult::Continue));
+        ctx.vars
+            .get("mirror")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+    }
+
+    /// Mirror target that reports every request it reads, then answers
+    /// after `delay`.
+    fn spawn_mirror(delay: Duration) -> (std::net::SocketAddr, std_mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (tx, rx) = std_mpsc::channel();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { break };
+                let tx = tx.clone();
+                std::thread::spawn(move || {
+                    let mut req = Vec::new();
+                    let mut buf = [0u8; 4096];
+                    while let Ok(n) = stream.read(&mut buf) {
+                        if n == 0 {
+                            break;
+                        }
+                        req.extend_from_slice(&buf[..n]);
+                        if let Some(pos) = req.windows(4).position(|w| w == b"\r\n\r\n") {
+                            let head = String::from_utf8_lossy(&req[..pos]).to_ascii_lowercase();
+                            let len: usize = head
+                                .lines()
+                                .find_map(|l| l.strip_prefix("content-length: "))
+                                .map_or(0, |v| v.parse().unwrap());
+                            if req.len() >= pos + 4 + len {
+                                break;
+                            }
+                        }
+                    }
+                    let _ = tx.send(String::from_utf8_lossy(&req).into_owned());
+                    std::thread::sleep(delay);
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n",
+                    );
+                });
+            }
+        });
+        (addr, rx)
+    }
+
+    #[test]
+    fn mirror_receives_path_headers_and_body() {
+        let (addr, requests) = spawn_mirror(Duration::ZERO);
+        let plugin = ProxyMirrorPlugin::new();
+        let p = instance(
+            &plugin,
+            serde_json::json!({ "host": format!("http://{addr}") }),
+        );
+        let mut ctx = make_ctx(
+            "/orders/7?expand=items",
+            &[
+                ("host", "api.test"),
+                ("x-request-id", "abc"),
+                ("content-type", "application/json"),
+                ("content-length", "8"),
+            ],
+            b"{\"a\":1}\n",
+        );
+        assert_eq!(mirror(p.as_ref(), &mut ctx).as_deref(), Some("queued"));
+
+        let req = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(
+            req.starts_with("POST /orders/7?expand=items HTTP/1.1\r\n"),
+            "{req}"
+        );
+        let lower = req.to_ascii_lowercase();
+        assert!(lower.contains("x-request-id: abc\r\n"), "{req}");
+        assert!(
+            lower.contains("content-type: application/json\r\n"),
+            "{req}"
+        );
+        assert!(lower.contains(&format!("host: {addr}\r\n")), "{req}");
+        assert!(req.ends_with("\r\n\r\n{\"a\":1}\n"), "{req}");
+    }
+
+    #[test]
+    fn prefix_mode_appends_to_host_path() {
+        let (addr, requests) = spawn_mirror(Duration::ZERO);
+        let plugin = ProxyMirrorPlugin::new();
+        let p = instance(
+            &plugin,
+            serde_json::json!({
+                "host": format!("http://{addr}/shadow/"),
+                "path_concat_mode": "prefix"
+            }),
+        );
+        mirror(p.as_ref(), &mut make_ctx("/orders", &[], b""));
+        let req = requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(req.starts_with("POST /shadow/orders HTTP/1.1\r\n"), "{req}");
+    }
+
+    #[test]
+    fn unreachable_mirror_does_not_affect_the_request() {
+        let dead = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let plugin = ProxyMirrorPlugin::new();
+        let p = instance(
+            &plugin,
+            serde_json::json!({ "host": format!("http://{dead}") }),
+        );
+        let started = std::time::Instant::now();
+        for _ in 0..10 {
+            assert_eq!(
+                mirror(p.as_ref(), &mut make_ctx("/", &[], b"")).as_deref(),
+                Some("queued")
+            );
+        }
+        assert!(started.elapsed() < Duration::from_millis(100));
+    }
+
+    #[test]
+    fn full_queue_drops_copies() {
+        let (addr, requests) = spawn_mirror(Duration::from_secs(2));
+        let plugin = ProxyMirrorPlugin::with_limits(1, 1);
+        let p = instance(
+            &plugin,
+            serde_json::json!({ "host": format!("http://{addr}"), "timeout_ms": 5000 }),
+        );
+        let send = |p: &dyn PluginInstance| mirror(p, &mut make_ctx("/", &[], b""));
+        assert_eq!(send(p.as_ref()).as_deref(), Some("queued"));
+        // The first copy holds the only in-flight slot...
+        requests.recv_timeout(Duration::from_secs(5)).unwrap();
+        // ...so the second waits in the queue and the third is dropped.
+        assert_eq!(send(p.as_ref()).as_deref(), Some("queued"));
+        assert_eq!(send(p.as_ref()).as_deref(), Some("dropped"));
+    }
+
+    #[test]
+    fn streamed_bodies_are_not_mirrored() {
+        let plugin = ProxyMirrorPlugin::new();
+        let p = instance(&plugin, serde_json::json!({ "host": "http://127.0.0.1:9" }));
+        let mut ctx = make_ctx("/", &[("content-length", "100")], b"partial");
+        assert_eq!(mirror(p.as_ref(), &mut ctx), None);
+    }
+
+    #[test]
+    fn sampling_ratio_is_honored() {
+        let hits = (0..20_000).filter(|_| sampled(0.3)).count();
+        assert!((5_400..=6_600).contains(&hits), "{hits}");
+        assert!((0..1000).all(|_| sampled(1.0)));
+        assert!(!(0..1000).any(|_| sampled(0.0)));
+
+        let plugin = ProxyMirrorPlugin::new();
+        let p = instance(
+            &plugin,
+            serde_json::json!({ "host": "http://127.0.0.1:9", "sample_ratio": 0.0 }),
+        );
+        assert_eq!(mirror(p.as_ref(), &mut make_ctx("/", &[], b"")), None);
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        let plugin = ProxyMirrorPlugin::new();
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "host": "not a url" }),
+            serde_json::json!({ "host": "ftp://127.0.0.1" }),
+            serde_json::json!({ "host": "http://127.0.0.1/path" }),
+            serde_json::json!({ "host": "http://127.0.0.1", "sample_ratio": 1.5 }),
+            serde_json::json!({ "host": "http://127.0.0.1", "path_concat_mode": "append" }),
+        ] {
+            assert!(plugin.configure(&config).is_err(), "{config}");
+        }
+    }
+}
@@ -1,25 +1,75 @@
-use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use super::limit_key::LimitKey;
+use super::redis_counter::RedisCounter;
+use crate::background;
+use ando_plugin::plugin::{
+    AccessFuture, AsyncAccess, Phase, Plugin, PluginContext, PluginInstance, PluginResult,
+};
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::hash::{BuildHasher, RandomState};
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tracing::warn;
 
-/// Rate-limiting plugin — fixed window counter per client IP.
+/// Rate-limiting plugin — fixed window counter per client key.
 ///
-/// **Important: per-worker semantics.** When running with SO_REUSEPORT
-/// (N worker threads), each worker maintains its own rate counter.
-/// The effective global limit is `count × N`. This is a deliberate
-/// trade-off for zero atomic contention on the hot path. For exact
-/// global rate limiting, use a shared store (Redis / etcd) — available
-/// in Ando Enterprise Edition's `rate-limiting-advanced` plugin.
+/// The key defaults to the client IP; `key` selects another identity
+/// (`consumer_name`, `header.<name>`, `query.<name>`). Requests without
+/// that identity fall back to the client IP.
+///
+/// **Important: per-worker semantics.** With the default `policy: local`
+/// and SO_REUSEPORT (N worker threads), each worker maintains its own
+/// rate counter. The effective global limit is `count × N`. This is a
+/// deliberate trade-off for zero atomic contention on the hot path.
+///
+/// `policy: redis` keeps the counters in Redis instead, so the limit is
+/// exact across workers and gateway instances. The Redis round-trip runs
+/// in the async access hook; `allow_degradation` decides whether Redis
+/// errors let requests through or answer 503.
 pub struct RateLimitingPlugin;
 
+/// Number of counter shards; keys are spread by hash to cut lock contention.
+const SHARDS: usize = 16;
+
 #[derive(Debug, Deserialize)]
 struct RateLimitingConfig {
     /// Maximum requests allowed in the window.
     count: u64,
     /// Window size in seconds.
     time_window: u64,
+    /// Counter key — default "remote_addr".
+    #[serde(default = "default_key", alias = "key_type")]
+    key: String,
+    /// Counter store — "local" (default) or "redis".
+    #[serde(default = "default_policy")]
+    policy: String,
+    #[serde(default)]
+    redis_host: Option<String>,
+    #[serde(default = "default_redis_port")]
+    redis_port: u16,
+    #[serde(default)]
+    redis_password: Option<String>,
+    #[serde(default)]
+    redis_database: u32,
+    /// Redis timeout in milliseconds — default 1000.
+    #[serde(default = "default_redis_timeout")]
+    redis_timeout: u64,
+    /// Let requests through when Redis fails (otherwise 503).
+    #[serde(default)]
+    allow_degradation: bool,
+}
+
+fn default_key() -> String {
+    "remote_addr".to_string()
+}
+fn default_policy() -> String {
+    "local".to_string()
+}
+fn default_redis_port() -> u16 {
+    6379
+}
+fn default_redis_timeout() -> u64 {
+    1000
 }
 
 struct WindowState {
@@ -27,11 +77,175 @@ struct WindowState {
     window_start: Instant,
 }
 
-struct RateLimitingInstance {
+#[derive(Default)]
+struct Shard {
+    /// route → key → window state.
+    counters: HashMap<String, HashMap<String, WindowState>>,
+    last_sweep: Option<Instant>,
+}
+
+/// Limit settings, copied into async access futures.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
     max_count: u64,
     window: Duration,
-    /// IP address → window state.
-    counters: Mutex<HashMap<String, WindowState>>,
+    allow_degradation: bool,
+}
+
+impl Limits {
+    /// `X-RateLimit-*` headers for a request that made `count` hits.
+    fn rate_headers(&self, count: u64, reset: Duration) -> Vec<(String, String)> {
+        vec![
+            ("x-ratelimit-limit".to_string(), self.max_count.to_string()),
+            (
+                "x-ratelimit-remaining".to_string(),
+                self.max_count.saturating_sub(count).to_string(),
+            ),
+            (
+                "x-ratelimit-reset".to_string(),
+                (reset.as_secs_f64().ceil() as u64).to_string(),
+            ),
+        ]
+    }
+
+    /// Turn a hit count into the plugin verdict.
+    fn verdict(&self, count: u64, reset: Duration) -> AsyncAccess {
+        let rate_headers = self.rate_headers(count, reset);
+        if count > self.max_count {
+            let mut headers = vec![
+                ("content-type".to_string(), "application/json".to_string()),
+                ("retry-after".to_string(), self.window.as_secs().to_string()),
+            ];
+            headers.extend(rate_headers);
+            return AsyncAccess {
+                result: PluginResult::Response {
+                    status: 429,
+                    headers,
+                    body: Some(br#"{"error":"Too many requests","status":429}"#.to_vec()),
+                },
+                response_headers: Vec::new(),
+                upstream_headers: Vec::new(),
+            };
+        }
+        AsyncAccess {
+            result: PluginResult::Continue,
+            response_headers: rate_headers,
+            upstream_headers: Vec::new(),
+        }
+    }
+
+    /// Verdict when the Redis store could not be reached.
+    fn degraded(&self) -> AsyncAccess {
+        let result = if self.allow_degradation {
+            PluginResult::Continue
+        } else {
+            PluginResult::Response {
+                status: 503,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: Some(br#"{"error":"Rate limit store unavailable","status":503}"#.to_vec()),
+            }
+        };
+        AsyncAccess {
+            result,
+            response_headers: Vec::new(),
+            upstream_headers: Vec::new(),
+        }
+    }
+}
+
+struct RateLimitingInstance {
+    limits: Limits,
+    key: LimitKey,
+    hasher: RandomState,
+    shards: Box<[Mutex<Shard>]>,
+    /// Set for `policy: redis`; the local shards are then unused.
+    redis: Option<Arc<RedisCounter>>,
+}
+
+impl RateLimitingInstance {
+    fn new(max_count: u64, window: Duration, key: LimitKey) -> Self {
+        Self {
+            limits: Limits {
+                max_count,
+                window,
+                allow_degradation: false,
+            },
+            key,
+            hasher: RandomState::new(),
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            redis: None,
+        }
+    }
+
+    fn with_redis(mut self, redis: RedisCounter, allow_degradation: bool) -> Self {
+        self.redis = Some(Arc::new(redis));
+        self.limits.allow_degradation = allow_degradation;
+        self
+    }
+
+    /// Count a request against `(route, key)`. Returns the count within the
+    /// current window and the time left until it resets.
+    fn hit(&self, route: &str, key: &str, now: Instant) -> (u64, Duration) {
+        let idx = self.hasher.hash_one((route, key)) as usize % self.shards.len();
+        let mut shard = self.shards[idx].lock().unwrap_or_else(|e| e.into_inner());
+
+        // Drop windows that have ended, at most once per window, so unique
+        // keys do not accumulate forever.
+        if shard
+            .last_sweep
+            .is_none_or(|t| now.duration_since(t) >= self.limits.window)
+        {
+            let window = self.limits.window;
+            shard.counters.retain(|_, keys| {
+                keys.retain(|_, s| now.duration_since(s.window_start) < window);
+                !keys.is_empty()
+            });
+            shard.last_sweep = Some(now);
+        }
+
+        if !shard.counters.contains_key(route) {
+            shard.counters.insert(route.to_string(), HashMap::new());
+        }
+        let keys = shard.counters.get_mut(route).expect("inserted above");
+        if !keys.contains_key(key) {
+            keys.insert(
+                key.to_string(),
+                WindowState {
+                    count: 0,
+                    window_start: now,
+                },
+            );
+        }
+        let state = keys.get_mut(key).expect("inserted above");
+
+        // Reset window if expired
+        if now.duration_since(state.window_start) >= self.limits.window {
+            state.count = 0;
+            state.window_start = now;
+        }
+
+        state.count += 1;
+        let reset = self
+            .limits
+            .window
+            .saturating_sub(now.duration_since(state.window_start));
+        (state.count, reset)
+    }
+
+    #[cfg(test)]
+    fn tracked_keys(&self) -> usize {
+        self.shards
+            .iter()
+            .map(|s| {
+                s.lock()
+                    .unwrap()
+                    .counters
+                    .values()
+                    .map(HashMap::len)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
 }
 
 impl Plugin for RateLimitingPlugin {
@@ -50,12 +264,33 @@ impl Plugin for RateLimitingPlugin {
     fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
         let cfg: RateLimitingConfig = serde_json::from_value(config.clone())
             .map_err(|e| anyhow::anyhow!("rate-limiting config error: {e}"))?;
+        if cfg.time_window == 0 {
+            anyhow::bail!("rate-limiting: time_window must be at least 1");
+        }
 
-        Ok(Box::new(RateLimitingInstance {
-            max_count: cfg.count,
-            window: Duration::from_secs(cfg.time_window),
-            counters: Mutex::new(HashMap::new()),
-        }))
+        let instance = RateLimitingInstance::new(
+            cfg.count,
+            Duration::from_secs(cfg.time_window),
+            LimitKey::parse(&cfg.key).map_err(|e| anyhow::anyhow!("rate-limiting: {e}"))?,
+        );
+        let instance = match cfg.policy.as_str() {
+            "local" => instance,
+            "redis" => {
+                let host = cfg.redis_host.ok_or_else(|| {
+                    anyhow::anyhow!("rate-limiting: policy redis needs redis_host")
+                })?;
+                let redis = RedisCounter::new(
+                    host,
+                    cfg.redis_port,
+                    cfg.redis_password,
+                    cfg.redis_database,
+                    Duration::from_millis(cfg.redis_timeout.max(1)),
+                );
+                instance.with_redis(redis, cfg.allow_degradation)
+            }
+            other => anyhow::bail!("rate-limiting: unsupported policy `{other}`"),
+        };
+        Ok(Box::new(instance))
     }
 }
 
@@ -68,42 +303,56 @@ impl PluginInstance for RateLimitingInstance {
         1001
     }
 
-    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
-        let key = ctx.client_ip.clone();
-        let now = Instant::now();
-
-        let mut counters = self.counters.lock().unwrap();
-        let state = counters.entry(key).or_insert_with(|| WindowState {
-            count: 0,
-            window_start: now,
-        });
+    fn uses_error_pages(&self) -> bool {
+        true
+    }
 
-        // Reset window if expired
-        if now.duration_since(state.window_start) >= self.window {
-            state.count = 0;
-            state.window_start = now;
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        if self.redis.is_some() {
+            // Counted in `access_async`.
+            return PluginResult::Continue;
         }
+        let key = self.key.resolve(ctx);
+        let (count, reset) = self.hit(&ctx.route_id, key, Instant::now());
+        let verdict = self.limits.verdict(count, reset);
+        ctx.response_headers.extend(verdict.response_headers);
+        verdict.result
+    }
 
-        state.count += 1;
+    fn has_async_access(&self) -> bool {
+        self.redis.is_some()
+    }
 
-        if state.count > self.max_count {
-            return PluginResult::Response {
-                status: 429,
-                headers: vec![
-                    ("content-type".to_string(), "application/json".to_string()),
-                    ("retry-after".to_string(), self.window.as_secs().to_string()),
-                ],
-                body: Some(br#"{"error":"Too many requests","status":429}"#.to_vec()),
-            };
-        }
+    fn access_async(&self, ctx: &mut PluginContext) -> Option<AccessFuture> {
+        let redis = self.redis.clone()?;
+        let redis_key = format!(
+            "ando:rate-limiting:{}:{}",
+            ctx.route_id,
+            self.key.resolve(ctx)
+        );
+        let limits = self.limits;
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        background::runtime().spawn(async move {
+            let _ = tx.send(redis.incr(&redis_key, limits.window).await);
+        });
 
-        PluginResult::Continue
+        Some(Box::pin(async move {
+            match rx.await {
+                Ok(Ok((count, reset))) => limits.verdict(count, reset),
+                Ok(Err(e)) => {
+                    warn!(error = %e, "rate-limiting: redis unavailable");
+                    limits.degraded()
+                }
+                Err(_) => limits.degraded(),
+            }
+        }))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use ando_plugin::plugin::AsyncAccess;
     use std::collections::HashMap;
 
     fn make_ctx(ip: &str) -> PluginContext {
@@ -117,11 +366,11 @@ mod tests {
     }
 
     fn instance(count: u64, time_window: u64) -> RateLimitingInstance {
-        RateLimitingInstance {
-            max_count: count,
-            window: Duration::from_secs(time_window),
-            counters: Mutex::new(HashMap::new()),
-        }
+        RateLimitingInstance::new(
+            count,
+            Duration::from_secs(time_window),
+            LimitKey::RemoteAddr,
+        )
     }
 
     // ── Within limit ─────────────────────────────────────────────
@@ -170,11 +419,11 @@ mod tests {
     #[test]
     fn expired_window_resets_counter() {
         // Use a 0-second window so it expires immediately
-        let inst = RateLimitingInstance {
-            max_count: 1,
-            window: Duration::from_nanos(1), // expires after 1ns
-            counters: Mutex::new(HashMap::new()),
-        };
+        let inst = RateLimitingInstance::new(
+            1,
+            Duration::from_nanos(1), // expires after 1ns
+            LimitKey::RemoteAddr,
+        );
 
         // First request — within limit
         assert!(matches!(
@@ -251,6 +500,15 @@ mod tests {
         );
     }
 
+    #[test]
+    fn configure_zero_time_window_fails() {
+        let config = serde_json::json!({ "count": 10, "time_window": 0 });
+        assert!(
+            RateLimitingPlugin.configure(&config).is_err(),
+            "A zero 'time_window' must fail"
+        );
+    }
+
     #[test]
     fn configured_instance_enforces_rate_limit() {
         let config = serde_json::json!({ "count": 2, "time_window": 60 });
@@ -298,11 +556,11 @@ mod tests {
     #[test]
     fn rate_limiter_mutex_is_safe_under_concurrent_access() {
         use std::sync::Arc;
-        let instance: Arc<RateLimitingInstance> = Arc::new(RateLimitingInstance {
-            max_count: 1000,
-            window: Duration::from_secs(60),
-            counters: Mutex::new(HashMap::new()),
-        });
+        let instance: Arc<RateLimitingInstance> = Arc::new(RateLimitingInstance::new(
+            1000,
+            Duration::from_secs(60),
+            LimitKey::RemoteAddr,
+        ));
 
         let mut handles = vec![];
         for _ in 0..4 {
@@ -333,4 +591,254 @@ mod tests {
             PluginResult::Continue
         ));
     }
+
+    // ── Keys ─────────────────────────────────────────────────────
+
+    fn keyed(key: &str, count: u64) -> Box<dyn PluginInstance> {
+        let config = serde_json::json!({ "count": count, "time_window": 60, "key": key });
+        RateLimitingPlugin.configure(&config).unwrap()
+    }
+
+    fn ctx_with(ip: &str, uri: &str, headers: &[(&str, &str)]) -> PluginContext {
+        PluginContext::new(
+            "r1".into(),
+            ip.into(),
+            "GET".into(),
+            uri.into(),
+            headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn consumer_key_gives_each_consumer_its_own_budget() {
+        let inst = keyed("consumer_name", 1);
+        let mut alice = make_ctx("1.1.1.1");
+        alice.consumer = Some("alice".into());
+        let mut bob = make_ctx("1.1.1.1");
+        bob.consumer = Some("bob".into());
+
+        assert!(matches!(inst.access(&mut alice), PluginResult::Continue));
+        assert!(matches!(
+            inst.access(&mut alice),
+            PluginResult::Response { status: 429, .. }
+        ));
+        // Same IP, different consumer: independent budget.
+        assert!(matches!(inst.access(&mut bob), PluginResult::Continue));
+    }
+
+    #[test]
+    fn consumer_key_falls_back_to_client_ip() {
+        let inst = keyed("consumer_name", 1);
+        assert!(matches!(
+            inst.access(&mut make_ctx("1.1.1.1")),
+            PluginResult::Continue
+        ));
+        assert!(matches!(
+            inst.access(&mut make_ctx("1.1.1.1")),
+            PluginResult::Response { status: 429, .. }
+        ));
+        assert!(matches!(
+            inst.access(&mut make_ctx("2.2.2.2")),
+            PluginResult::Continue
+        ));
+    }
+
+    #[test]
+    fn header_and_query_keys() {
+        let inst = keyed("header.X-Api-Key", 1);
+        let mut a = ctx_with("1.1.1.1", "/", &[("x-api-key", "a")]);
+        let mut b = ctx_with("1.1.1.1", "/", &[("x-api-key", "b")]);
+        assert!(matches!(inst.access(&mut a), PluginResult::Continue));
+        assert!(matches!(inst.access(&mut b), PluginResult::Continue));
+        assert!(matches!(
+            inst.access(&mut a),
+            PluginResult::Response { status: 429, .. }
+        ));
+
+        let inst = keyed("query.tenant", 1);
+        let mut a = ctx_with("1.1.1.1", "/x?tenant=a&v=1", &[]);
+        let mut b = ctx_with("1.1.1.1", "/x?v=1&tenant=b", &[]);
+        assert!(matches!(inst.access(&mut a), PluginResult::Continue));
+        assert!(matches!(inst.access(&mut b), PluginResult::Continue));
+        assert!(matches!(
+            inst.access(&mut a),
+            PluginResult::Response { status: 429, .. }
+        ));
+    }
+
+    #[test]
+    fn routes_have_independent_counters() {
+        let inst = instance(1, 60);
+        let mut a = make_ctx("1.1.1.1");
+        let mut b = make_ctx("1.1.1.1");
+        b.route_id = "r2".into();
+        assert!(matches!(inst.access(&mut a), PluginResult::Continue));
+        assert!(matches!(inst.access(&mut b), PluginResult::Continue));
+    }
+
+    #[test]
+    fn configure_rejects_unknown_key() {
+        for key in ["cookie.sid", "header.", "consumer"] {
+            let config = serde_json::json!({ "count": 1, "time_window": 60, "key": key });
+            assert!(RateLimitingPlugin.configure(&config).is_err(), "{key}");
+        }
+        let config =
+            serde_json::json!({ "count": 1, "time_window": 60, "key_type": "consumer_name" });
+        assert!(RateLimitingPlugin.configure(&config).is_ok());
+    }
+
+    // ── X-RateLimit-* headers ────────────────────────────────────
+
+    #[test]
+    fn ratelimit_headers_reflect_per_key_remaining() {
+        let inst = keyed("consumer_name", 3);
+        let mut alice = make_ctx("1.1.1.1");
+        alice.consumer = Some("alice".into());
+        inst.access(&mut alice);
+        inst.access(&mut alice);
+        assert_eq!(alice.response_headers["x-ratelimit-limit"], "3");
+        assert_eq!(alice.response_headers["x-ratelimit-remaining"], "1");
+        let reset: u64 = alice.response_headers["x-ratelimit-reset"].parse().unwrap();
+        assert!(reset > 0 && reset <= 60);
+
+        let mut bob = make_ctx("1.1.1.1");
+        bob.consumer = Some("bob".into());
+        inst.access(&mut bob);
+        assert_eq!(bob.response_headers["x-ratelimit-remaining"], "2");
+
+        inst.access(&mut alice);
+        match inst.access(&mut alice) {
+            PluginResult::Response {
+                status, headers, ..
+            } => {
+                assert_eq!(status, 429);
+                assert!(headers.contains(&("x-ratelimit-remaining".into(), "0".into())));
+            }
+            _ => panic!("expected 429"),
+        }
+    }
+
+    // ── Expiry of stale windows ──────────────────────────────────
+
+    #[test]
+    fn stale_windows_are_swept() {
+        let inst = RateLimitingInstance::new(10, Duration::from_millis(20), LimitKey::RemoteAddr);
+        let start = Instant::now();
+        for i in 0..100 {
+            inst.hit("r1", &format!("10.0.0.{i}"), start);
+        }
+        assert_eq!(inst.tracked_keys(), 100);
+
+        // After the window, each shard sweeps on its next hit.
+        let later = start + Duration::from_millis(50);
+        for i in 0..1000 {
+            inst.hit("r1", &format!("10.1.{}.{}", i / 256, i % 256), later);
+        }
+        assert_eq!(inst.tracked_keys(), 1000);
+    }
+
+    // ── policy: redis ────────────────────────────────────────────
+
+    use crate::traffic::redis_counter::mock::MockRedis;
+
+    fn redis_instance(port: u16, count: u64, degrade: bool) -> Box<dyn PluginInstance> {
+        let config = serde_json::json!({
+            "count": count,
+            "time_window": 60,
+            "key": "consumer_name",
+            "policy": "redis",
+            "redis_host": "127.0.0.1",
+            "redis_port": port,
+            "redis_timeout": 500,
+            "allow_degradation": degrade,
+        });
+        RateLimitingPlugin.configure(&config).unwrap()
+    }
+
+    /// Run the sync and async access hooks the way the proxy does.
+    fn run_access(inst: &dyn PluginInstance, ctx: &mut PluginContext) -> AsyncAccess {
+        assert!(matches!(inst.access(ctx), PluginResult::Continue));
+        assert!(inst.has_async_access());
+        let fut = inst.access_async(ctx).expect("async hook");
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
+    fn consumer_ctx(name: &str) -> PluginContext {
+        let mut ctx = make_ctx("1.1.1.1");
+        ctx.consumer = Some(name.into());
+        ctx
+    }
+
+    #[test]
+    fn redis_policy_shares_budget_across_instances() {
+        let redis = MockRedis::start(None);
+        // Two gateway instances pointing at the same Redis.
+        let a = redis_instance(redis.port, 2, false);
+        let b = redis_instance(redis.port, 2, false);
+
+        let first = run_access(a.as_ref(), &mut consumer_ctx("alice"));
+        assert!(matches!(first.result, PluginResult::Continue));
+        assert!(
+            first
+                .response_headers
+                .contains(&("x-ratelimit-remaining".into(), "1".into()))
+        );
+        assert!(matches!(
+            run_access(b.as_ref(), &mut consumer_ctx("alice")).result,
+            PluginResult::Continue
+        ));
+        assert!(matches!(
+            run_access(a.as_ref(), &mut consumer_ctx("alice")).result,
+            PluginResult::Response { status: 429, .. }
+        ));
+        // Another key has its own budget.
+        assert!(matches!(
+            run_access(b.as_ref(), &mut consumer_ctx("bob")).result,
+            PluginResult::Continue
+        ));
+        assert_eq!(
+            redis.counters.lock().unwrap()["ando:rate-limiting:r1:alice"],
+            3
+        );
+    }
+
+    #[test]
+    fn redis_errors_honor_allow_degradation() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let open = redis_instance(port, 1, true);
+        assert!(matches!(
+            run_access(open.as_ref(), &mut consumer_ctx("alice")).result,
+            PluginResult::Continue
+        ));
+        let closed = redis_instance(port, 1, false);
+        assert!(matches!(
+            run_access(closed.as_ref(), &mut consumer_ctx("alice")).result,
+            PluginResult::Response { status: 503, .. }
+        ));
+    }
+
+    #[test]
+    fn local_policy_has_no_async_hook() {
+        let inst = instance(1, 60);
+        assert!(!inst.has_async_access());
+        assert!(inst.access_async(&mut make_ctx("1.1.1.1")).is_none());
+    }
+
+    #[test]
+    fn configure_rejects_bad_policy() {
+        let config = serde_json::json!({ "count": 1, "time_window": 60, "policy": "redis" });
+        assert!(RateLimitingPlugin.configure(&config).is_err());
+        let config = serde_json::json!({ "count": 1, "time_window": 60, "policy": "etcd" });
+        assert!(RateLimitingPlugin.configure(&config).is_err());
+    }
 }
@@ -0,0 +1,247 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use ipnet::IpNet;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+
+/// Real-IP plugin — derives the client address from a header set by a
+/// CDN or load balancer in front of Ando.
+///
+/// Runs first in the rewrite phase so ip-restriction, auth and rate
+/// limiting all see the derived address. The address it replaced is kept
+/// in `ctx.vars["original_client_ip"]`.
+pub struct RealIpPlugin;
+
+#[derive(Debug, Deserialize)]
+struct RealIpConfig {
+    /// `http_<header>` (APISIX style, e.g. `http_x_forwarded_for`) or a
+    /// plain header name such as `CF-Connecting-IP`.
+    source: String,
+    /// Walk a comma-separated list right to left past trusted addresses
+    /// instead of taking its last entry.
+    #[serde(default)]
+    recursive: bool,
+    /// Peers whose header is believed. Empty trusts every peer.
+    #[serde(default)]
+    trusted_addresses: Vec<String>,
+}
+
+struct RealIpInstance {
+    /// Lowercase header name.
+    header: String,
+    recursive: bool,
+    trusted: Vec<IpNet>,
+}
+
+impl Plugin for RealIpPlugin {
+    fn name(&self) -> &str {
+        "real-ip"
+    }
+
+    fn priority(&self) -> i32 {
+        23000 // APISIX default; ahead of every other plugin
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Rewrite]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: RealIpConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("real-ip config error: {e}"))?;
+
+        let header = match cfg.source.strip_prefix("http_") {
+            Some(name) => name.replace('_', "-"),
+            None => cfg.source.clone(),
+        }
+        .to_ascii_lowercase();
+        if header.is_empty() {
+            anyhow::bail!("real-ip config error: empty source");
+        }
+        let trusted = cfg
+            .trusted_addresses
+            .iter()
+            .map(|s| {
+                s.parse::<IpNet>()
+                    .or_else(|_| s.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| anyhow::anyhow!("real-ip config error: invalid address `{s}`"))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Box::new(RealIpInstance {
+            header,
+            recursive: cfg.recursive,
+            trusted,
+        }))
+    }
+}
+
+/// An address as found in a header: bare, or `ip:port` / `[v6]:port`.
+fn parse_addr(s: &str) -> Option<IpAddr> {
+    let s = s.trim();
+    s.parse::<IpAddr>()
+        .ok()
+        .or_else(|| s.parse::<SocketAddr>().ok().map(|a| a.ip()))
+}
+
+impl RealIpInstance {
+    fn is_trusted(&self, ip: IpAddr) -> bool {
+        self.trusted.is_empty() || self.trusted.iter().any(|net| net.contains(&ip))
+    }
+
+    /// The client address named by `value`. `None` when the value is
+    /// malformed, so the current client address stands.
+    fn derive(&self, value: &str) -> Option<IpAddr> {
+        let mut hops = value.rsplit(',');
+        let last = parse_addr(hops.next()?)?;
+        if !self.recursive {
+            return Some(last);
+        }
+        let mut client = last;
+        while self.is_trusted(client) {
+            match hops.next() {
+                Some(hop) => client = parse_addr(hop)?,
+                None => break,
+            }
+        }
+        Some(client)
+    }
+}
+
+impl PluginInstance for RealIpInstance {
+    fn name(&self) -> &str {
+        "real-ip"
+    }
+
+    fn priority(&self) -> i32 {
+        23000
+    }
+
+    fn rewrite(&self, ctx: &mut PluginContext) -> PluginResult {
+        let Ok(peer) = ctx.client_ip.parse::<IpAddr>() else {
+            return PluginResult::Continue;
+        };
+        if !self.is_trusted(peer) {
+            return PluginResult::Continue;
+        }
+        let Some(client) = ctx.get_header(&self.header).and_then(|v| self.derive(v)) else {
+            return PluginResult::Continue;
+        };
+        let client = client.to_string();
+        if client != ctx.client_ip {
+            let original = std::mem::replace(&mut ctx.client_ip, client);
+            ctx.vars
+                .insert("original_client_ip".to_string(), original.into());
+            ctx.upstream_headers
+                .insert("x-real-ip".to_string(), ctx.client_ip.clone());
+        }
+        PluginResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn instance(config: serde_json::Value) -> Box<dyn PluginInstance> {
+        RealIpPlugin.configure(&config).unwrap()
+    }
+
+    fn rewrite(plugin: &dyn PluginInstance, peer: &str, headers: &[(&str, &str)]) -> PluginContext {
+        let headers: HashMap<String, String> = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let mut ctx =
+            PluginContext::new("r1".into(), peer.into(), "GET".into(), "/".into(), headers);
+        assert!(matches!(plugin.rewrite(&mut ctx), PluginResult::Continue));
+        ctx
+    }
+
+    #[test]
+    fn recursive_resolution_skips_trusted_hops() {
+        let p = instance(serde_json::json!({
+            "source": "http_x_forwarded_for",
+            "recursive": true,
+            "trusted_addresses": ["10.0.0.0/8", "172.16.0.1"]
+        }));
+        let ctx = rewrite(
+            p.as_ref(),
+            "10.0.0.1",
+            &[(
+                "x-forwarded-for",
+                "6.6.6.6, 198.51.100.7, 172.16.0.1, 10.2.3.4",
+            )],
+        );
+        assert_eq!(ctx.client_ip, "198.51.100.7");
+        assert_eq!(ctx.vars["original_client_ip"], "10.0.0.1");
+        assert_eq!(ctx.upstream_headers["x-real-ip"], "198.51.100.7");
+    }
+
+    #[test]
+    fn non_recursive_takes_the_last_hop() {
+        let p = instance(serde_json::json!({
+            "source": "http_x_forwarded_for",
+            "trusted_addresses": ["10.0.0.0/8"]
+        }));
+        let ctx = rewrite(
+            p.as_ref(),
+            "10.0.0.1",
+            &[("x-forwarded-for", "198.51.100.7, 10.2.3.4")],
+        );
+        assert_eq!(ctx.client_ip, "10.2.3.4");
+    }
+
+    #[test]
+    fn custom_header_and_port_suffix() {
+        let p = instance(serde_json::json!({ "source": "CF-Connecting-IP" }));
+        let ctx = rewrite(
+            p.as_ref(),
+            "173.245.48.1",
+            &[("cf-connecting-ip", "[2001:db8::1]:443")],
+        );
+        assert_eq!(ctx.client_ip, "2001:db8::1");
+    }
+
+    #[test]
+    fn untrusted_peer_is_ignored() {
+        let p = instance(serde_json::json!({
+            "source": "http_x_real_ip",
+            "trusted_addresses": ["10.0.0.0/8"]
+        }));
+        let ctx = rewrite(p.as_ref(), "203.0.113.9", &[("x-real-ip", "1.2.3.4")]);
+        assert_eq!(ctx.client_ip, "203.0.113.9");
+        assert!(!ctx.vars.contains_key("original_client_ip"));
+        assert!(ctx.upstream_headers.is_empty());
+    }
+
+    #[test]
+    fn malformed_values_leave_the_client_ip_alone() {
+        let p = instance(serde_json::json!({
+            "source": "http_x_forwarded_for",
+            "recursive": true,
+            "trusted_addresses": ["10.0.0.0/8"]
+        }));
+        for value in ["unknown", "", "1.2.3.4, garbage, 10.0.0.2", "999.1.1.1"] {
+            let ctx = rewrite(p.as_ref(), "10.0.0.1", &[("x-forwarded-for", value)]);
+            assert_eq!(ctx.client_ip, "10.0.0.1", "{value:?}");
+            assert!(!ctx.vars.contains_key("original_client_ip"));
+        }
+        // No header at all.
+        let ctx = rewrite(p.as_ref(), "10.0.0.1", &[]);
+        assert_eq!(ctx.client_ip, "10.0.0.1");
+    }
+
+    #[test]
+    fn rejects_bad_config() {
+        assert!(RealIpPlugin.configure(&serde_json::json!({})).is_err());
+        assert!(
+            RealIpPlugin
+                .configure(&serde_json::json!({
+                    "source": "http_x_real_ip",
+                    "trusted_addresses": ["not-a-cidr"]
+                }))
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use std::io;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+/// Increment the window counter and start its expiry on the first hit.
+/// Returns `{count, ttl_ms}`.
+const INCR_SCRIPT: &str = "local c = redis.call('INCR', KEYS[1]) \
+if c == 1 then redis.call('PEXPIRE', KEYS[1], ARGV[1]) end \
+return {c, redis.call('PTTL', KEYS[1])}";
+
+/// Idle connections kept per counter.
+const MAX_IDLE: usize = 8;
+
+/// Fixed-window counters stored in Redis, shared by every gateway
+/// instance that points at the same server. Calls must run on
+/// `background::runtime()`.
+pub struct RedisCounter {
+    host: String,
+    port: u16,
+    password: Option<String>,
+    database: u32,
+    timeout: Duration,
+    idle: Mutex<Vec<Connection>>,
+}
+
+impl RedisCounter {
+    pub fn new(
+        host: String,
+        port: u16,
+        password: Option<String>,
+        database: u32,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            host,
+            port,
+            password,
+            database,
+            timeout,
+            idle: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Count a hit on `key` for a window of `window` length. Returns the
+    /// count so far and the time until the window resets.
+    pub async fn incr(&self, key: &str, window: Duration) -> io::Result<(u64, Duration)> {
+        tokio::time::timeout(self.timeout, self.incr_inner(key, window))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "redis timeout"))?
+    }
+
+    async fn incr_inner(&self, key: &str, window: Duration) -> io::Result<(u64, Duration)> {
+        let pooled = self.idle.lock().unwrap_or_else(|e| e.into_inner()).pop();
+        let mut conn = match pooled {
+            Some(conn) => conn,
+            None => self.connect().await?,
+        };
+        let window_ms = window.as_millis().max(1).to_string();
+        let reply = conn
+            .command(&["EVAL", INCR_SCRIPT, "1", key, &window_ms])
+            .await?;
+        let (count, ttl_ms) = match reply {
+            Reply::Array(items) => match items.as_slice() {
+                [Reply::Int(c), Reply::Int(t)] => (*c, *t),
+                _ => return Err(protocol_error("unexpected EVAL reply")),
+            },
+            Reply::Error(e) => return Err(io::Error::other(e)),
+            _ => return Err(protocol_error("unexpected EVAL reply")),
+        };
+
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        if idle.len() < MAX_IDLE {
+            idle.push(conn);
+        }
+        Ok((
+            count.max(0) as u64,
+            Duration::from_millis(ttl_ms.max(0) as u64),
+        ))
+    }
+
+    async fn connect(&self) -> io::Result<Connection> {
+        let stream = TcpStream::connect((self.host.as_str(), self.port)).await?;
+        stream.set_nodelay(true)?;
+        let mut conn = Connection {
+            stream: BufReader::new(stream),
+        };
+        if let Some(password) = &self.password {
+            conn.expect_ok(&["AUTH", password]).await?;
+        }
+        if self.database != 0 {
+            conn.expect_ok(&["SELECT", &self.database.to_string()])
+                .await?;
+        }
+        Ok(conn)
+    }
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+// ── Minimal RESP client ──────────────────────────────────────
+
+#[derive(Debug, PartialEq)]
+enum Reply {
+    Simple(String),
+    Error(String),
+    Int(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Vec<Reply>),
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+}
+
+impl Connection {
+    async fn command(&mut self, args: &[&str]) -> io::Result<Reply> {
+        let mut buf = Vec::with_capacity(64);
+        buf.extend_from_slice(format!("*{}\r\n", args.len()).as_bytes());
+        for arg in args {
+            buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+            buf.extend_from_slice(arg.as_bytes());
+            buf.extend_from_slice(b"\r\n");
+        }
+        self.stream.get_mut().write_all(&buf).await?;
+        read_reply(&mut self.stream).await
+    }
+
+    async fn expect_ok(&mut self, args: &[&str]) -> io::Result<()> {
+        match self.command(args).await? {
+            Reply::Simple(_) => Ok(()),
+            Reply::Error(e) => Err(io::Error::new(io::ErrorKind::PermissionDenied, e)),
+            _ => Err(protocol_error("unexpected reply")),
+        }
+    }
+}
+
+async fn read_line(r: &mut BufReader<TcpStream>) -> io::Result<String> {
+    let mut line = String::new();
+    if r.read_line(&mut line).await? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+fn read_reply(
+    r: &mut BufReader<TcpStream>,
+) -> std::pin::Pin<Box<dyn Future<Output = io::Result<Reply>> + Send + '_>> {
+    Box::pin(async move {
+        let line = read_line(r).await?;
+        let (kind, rest) = line
+            .split_at_checked(1)
+            .ok_or_else(|| protocol_error("empty reply"))?;
+        let int = || {
+            rest.parse::<i64>()
+                .map_err(|_| protocol_error("invalid integer"))
+        };
+        Ok(match kind {
+            "+" => Reply::Simple(rest.to_string()),
+            "-" => Reply::Error(rest.to_string()),
+            ":" => Reply::Int(int()?),
+            "$" => match int()? {
+                n if n < 0 => Reply::Bulk(None),
+                n => {
+                    let mut data = vec![0u8; n as usize + 2];
+                    r.read_exact(&mut data).await?;
+                    data.truncate(n as usize);
+                    Reply::Bulk(Some(data))
+                }
+            },
+            "*" => match int()? {
+                n if n < 0 => Reply::Array(Vec::new()),
+                n => {
+                    let mut items = Vec::with_capacity(n as usize);
+                    for _ in 0..n {
+                        items.push(read_reply(r).await?);
+                    }
+                    Reply::Array(items)
+                }
+            },
+            _ => return Err(protocol_error("unknown reply type")),
+        })
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    //! In-process stand-in for Redis that understands AUTH, SELECT and the
+    //! counter script.
+
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+
+    pub struct MockRedis {
+        pub port: u16,
+        pub counters: Arc<Mutex<HashMap<String, i64>>>,
+    }
+
+    impl MockRedis {
+        pub fn start(password: Option<&str>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let port = listener.local_addr().unwrap().port();
+            let counters = Arc::new(Mutex::new(HashMap::new()));
+            let shared = counters.clone();
+            let password = password.map(str::to_string);
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else { break };
+                    let counters = shared.clone();
+                    let password = password.clone();
+                    std::thread::spawn(move || serve(stream, counters, password));
+                }
+            });
+            Self { port, counters }
+        }
+    }
+
+    fn read_command(r: &mut BufReader<TcpStream>) -> Option<Vec<String>> {
+        let mut line = String::new();
+        r.read_line(&mut line).ok().filter(|n| *n > 0)?;
+        let n: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(n);
+        for _ in 0..n {
+            line.clear();
+            r.read_line(&mut line).ok()?;
+            let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+            let mut data = vec![0u8; len + 2];
+            r.read_exact(&mut data).ok()?;
+            data.truncate(len);
+            args.push(String::from_utf8(data).ok()?);
+        }
+        Some(args)
+    }
+
+    fn serve(
+        stream: TcpStream,
+        counters: Arc<Mutex<HashMap<String, i64>>>,
+        password: Option<String>,
+    ) {
+        let mut writer = stream.try_clone().unwrap();
+        let mut reader = BufReader::new(stream);
+        let mut authed = password.is_none();
+        while let Some(args) = read_command(&mut reader) {
+            let reply = match args[0].as_str() {
+                "AUTH" if Some(&args[1]) == password.as_ref() => {
+                    authed = true;
+                    "+OK\r\n".to_string()
+                }
+                "AUTH" => "-WRONGPASS invalid password\r\n".to_string(),
+                _ if !authed => "-NOAUTH Authentication required.\r\n".to_string(),
+                "SELECT" => "+OK\r\n".to_string(),
+                "EVAL" => {
+                    let mut counters = counters.lock().unwrap();
+                    let count = counters.entry(args[3].clone()).or_insert(0);
+                    *count += 1;
+                    format!("*2\r\n:{}\r\n:{}\r\n", count, args[4])
+                }
+                other => format!("-ERR unknown command '{other}'\r\n"),
+            };
+            if writer.write_all(reply.as_bytes()).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockRedis;
+    use super::*;
+    use crate::background::runtime;
+
+    fn counter(port: u16, password: Option<&str>) -> RedisCounter {
+        RedisCounter::new(
+            "127.0.0.1".into(),
+            port,
+            password.map(str::to_string),
+            0,
+            Duration::from_secs(2),
+        )
+    }
+
+    #[test]
+    fn incr_counts_per_key_and_reuses_connections() {
+        let redis = MockRedis::start(None);
+        let c = counter(redis.port, None);
+        runtime().block_on(async {
+            assert_eq!(
+                c.incr("a", Duration::from_secs(60)).await.unwrap(),
+                (1, Duration::from_secs(60))
+            );
+            assert_eq!(c.incr("a", Duration::from_secs(60)).await.unwrap().0, 2);
+            assert_eq!(c.incr("b", Duration::from_secs(60)).await.unwrap().0, 1);
+        });
+        assert_eq!(c.idle.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn incr_authenticates_with_password() {
+        let redis = MockRedis::start(Some("s3cret"));
+        runtime().block_on(async {
+            let ok = counter(redis.port, Some("s3cret"));
+            assert_eq!(ok.incr("k", Duration::from_secs(1)).await.unwrap().0, 1);
+            let wrong = counter(redis.port, Some("nope"));
+            assert!(wrong.incr("k", Duration::from_secs(1)).await.is_err());
+            let missing = counter(redis.port, None);
+            assert!(missing.incr("k", Duration::from_secs(1)).await.is_err());
+        });
+    }
+
+    #[test]
+    fn incr_fails_when_redis_is_unreachable() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let c = counter(port, None);
+        assert!(
+            runtime()
+                .block_on(c.incr("k", Duration::from_secs(1)))
+                .is_err()
+        );
+    }
+}
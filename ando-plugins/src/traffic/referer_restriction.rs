@@ -0,0 +1,245 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+
+/// Referer restriction plugin — allowlist/denylist of `Referer` hosts.
+///
+/// Entries are exact hosts (`example.com`) or wildcards (`*.example.com`,
+/// which matches subdomains but not the apex). The denylist takes priority
+/// over the allowlist.
+pub struct RefererRestrictionPlugin;
+
+#[derive(Debug, Deserialize)]
+struct RefererRestrictionConfig {
+    #[serde(default)]
+    allowlist: Vec<String>,
+    #[serde(default)]
+    denylist: Vec<String>,
+    /// Let requests without a usable `Referer` through.
+    #[serde(default)]
+    bypass_missing: bool,
+    #[serde(default = "default_message")]
+    message: String,
+}
+
+fn default_message() -> String {
+    "Your referer host is not allowed".to_string()
+}
+
+#[derive(Debug)]
+enum HostPattern {
+    Exact(String),
+    /// `*.example.com`, stored as `.example.com`.
+    Suffix(String),
+}
+
+impl HostPattern {
+    fn parse(s: &str) -> anyhow::Result<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        let pattern = match s.strip_prefix('*') {
+            Some(rest) if rest.starts_with('.') && rest.len() > 1 => {
+                HostPattern::Suffix(rest.to_string())
+            }
+            Some(_) => anyhow::bail!("referer-restriction: invalid wildcard `{s}`"),
+            None if s.is_empty() || s.contains(['/', '*', ':']) => {
+                anyhow::bail!("referer-restriction: invalid host `{s}`")
+            }
+            None => HostPattern::Exact(s),
+        };
+        Ok(pattern)
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            HostPattern::Exact(h) => h == host,
+            HostPattern::Suffix(suffix) => host.ends_with(suffix.as_str()),
+        }
+    }
+}
+
+struct RefererRestrictionInstance {
+    allowlist: Vec<HostPattern>,
+    denylist: Vec<HostPattern>,
+    bypass_missing: bool,
+    /// Pre-rendered 403 body.
+    body: Vec<u8>,
+}
+
+/// Host of an absolute URL, lowercased and without port or userinfo.
+fn referer_host(referer: &str) -> Option<String> {
+    let (_, rest) = referer.split_once("://")?;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit_once('@').map_or(authority, |(_, h)| h);
+    let host = if let Some(v6) = host.strip_prefix('[') {
+        v6.split_once(']')?.0
+    } else {
+        host.split(':').next()?
+    };
+    (!host.is_empty()).then(|| host.to_ascii_lowercase())
+}
+
+impl Plugin for RefererRestrictionPlugin {
+    fn name(&self) -> &str {
+        "referer-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2990
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: RefererRestrictionConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("referer-restriction config error: {e}"))?;
+
+        let parse = |list: &[String]| {
+            list.iter()
+                .map(|s| HostPattern::parse(s))
+                .collect::<anyhow::Result<Vec<_>>>()
+        };
+        Ok(Box::new(RefererRestrictionInstance {
+            allowlist: parse(&cfg.allowlist)?,
+            denylist: parse(&cfg.denylist)?,
+            bypass_missing: cfg.bypass_missing,
+            body: serde_json::json!({ "error": cfg.message, "status": 403 })
+                .to_string()
+                .into_bytes(),
+        }))
+    }
+}
+
+impl RefererRestrictionInstance {
+    fn permits(&self, referer: Option<&str>) -> bool {
+        let Some(host) = referer.and_then(referer_host) else {
+            return self.bypass_missing;
+        };
+        // Denylist takes priority
+        if self.denylist.iter().any(|p| p.matches(&host)) {
+            return false;
+        }
+        self.allowlist.is_empty() || self.allowlist.iter().any(|p| p.matches(&host))
+    }
+}
+
+impl PluginInstance for RefererRestrictionInstance {
+    fn name(&self) -> &str {
+        "referer-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2990
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        if self.permits(ctx.get_header("referer")) {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: 403,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(self.body.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn make_ctx(referer: Option<&str>) -> PluginContext {
+        let mut headers = HashMap::new();
+        if let Some(referer) = referer {
+            headers.insert("referer".to_string(), referer.to_string());
+        }
+        PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            headers,
+        )
+    }
+
+    fn status(config: serde_json::Value, referer: Option<&str>) -> u16 {
+        let inst = RefererRestrictionPlugin.configure(&config).unwrap();
+        match inst.access(&mut make_ctx(referer)) {
+            PluginResult::Continue => 200,
+            PluginResult::Response { status, .. } => status,
+        }
+    }
+
+    #[test]
+    fn allowlist_matches_exact_and_wildcard_hosts() {
+        let config = serde_json::json!({ "allowlist": ["example.com", "*.example.org"] });
+        assert_eq!(
+            status(config.clone(), Some("https://example.com/page")),
+            200
+        );
+        assert_eq!(
+            status(config.clone(), Some("https://EXAMPLE.com:8443/")),
+            200
+        );
+        assert_eq!(
+            status(config.clone(), Some("https://cdn.example.org/x")),
+            200
+        );
+        // A wildcard does not cover the apex, an exact host not its subdomains.
+        assert_eq!(status(config.clone(), Some("https://example.org/")), 403);
+        assert_eq!(
+            status(config.clone(), Some("https://www.example.com/")),
+            403
+        );
+        assert_eq!(status(config, Some("https://evil-example.com/")), 403);
+    }
+
+    #[test]
+    fn denylist_takes_priority_over_allowlist() {
+        let config = serde_json::json!({
+            "allowlist": ["*.example.com"],
+            "denylist": ["ads.example.com"]
+        });
+        assert_eq!(
+            status(config.clone(), Some("https://www.example.com/")),
+            200
+        );
+        assert_eq!(status(config, Some("https://ads.example.com/")), 403);
+    }
+
+    #[test]
+    fn missing_header_depends_on_bypass_missing() {
+        let strict = serde_json::json!({ "allowlist": ["example.com"] });
+        assert_eq!(status(strict.clone(), None), 403);
+        // Unparsable referers count as missing.
+        assert_eq!(status(strict, Some("not a url")), 403);
+        let lenient = serde_json::json!({ "allowlist": ["example.com"], "bypass_missing": true });
+        assert_eq!(status(lenient.clone(), None), 200);
+        assert_eq!(status(lenient.clone(), Some("not a url")), 200);
+        assert_eq!(status(lenient, Some("https://other.com/")), 403);
+    }
+
+    #[test]
+    fn referer_host_strips_userinfo_port_and_brackets() {
+        assert_eq!(
+            referer_host("https://user:pw@Example.com:8080/a?b").as_deref(),
+            Some("example.com")
+        );
+        assert_eq!(referer_host("http://[::1]:80/").as_deref(), Some("::1"));
+        assert_eq!(referer_host("https:///path"), None);
+        assert_eq!(referer_host("example.com"), None);
+    }
+
+    #[test]
+    fn configure_rejects_invalid_patterns() {
+        for entry in ["*example.com", "*.", "https://example.com", "a*.com", ""] {
+            assert!(
+                RefererRestrictionPlugin
+                    .configure(&serde_json::json!({ "allowlist": [entry] }))
+                    .is_err(),
+                "{entry}"
+            );
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use super::transform::{Transform, TransformConfig, is_plain_json};
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+
+/// Request transformer plugin — edits the request before it is proxied:
+/// removes, renames, replaces and adds headers and, for JSON bodies,
+/// top-level fields. Values are templates over the request, e.g.
+/// `{"add": {"headers": {"x-consumer": "$consumer"}}}`; see
+/// [`super::transform`] for the variables.
+///
+/// It runs in the access phase after the auth plugins, so `$consumer`
+/// is known. Bodies that are not JSON objects, content-encoded or
+/// streamed past the buffered part are forwarded as they are.
+pub struct RequestTransformerPlugin;
+
+struct RequestTransformerInstance {
+    transform: Transform,
+}
+
+impl Plugin for RequestTransformerPlugin {
+    fn name(&self) -> &str {
+        "request-transformer"
+    }
+
+    fn priority(&self) -> i32 {
+        801
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: TransformConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("request-transformer config error: {e}"))?;
+        let transform = Transform::compile(cfg)
+            .map_err(|e| anyhow::anyhow!("request-transformer config error: {e}"))?;
+        Ok(Box::new(RequestTransformerInstance { transform }))
+    }
+}
+
+impl PluginInstance for RequestTransformerInstance {
+    fn name(&self) -> &str {
+        "request-transformer"
+    }
+
+    fn priority(&self) -> i32 {
+        801
+    }
+
+    fn reads_body(&self) -> bool {
+        self.transform.has_json_ops()
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        // The body is checked against the headers as the client sent them.
+        if self.transform.has_json_ops()
+            && is_plain_json(ctx.request_headers.iter())
+            && let Some(body) = ctx.request_body.as_deref()
+            && body.len() as u64 == ctx.request_body_size
+            && let Some(edited) = self.transform.apply_json(ctx, body)
+        {
+            ctx.request_body = Some(edited);
+        }
+        self.transform
+            .header_edits(ctx)
+            .apply(&mut ctx.request_headers);
+        PluginResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+
+    fn ctx(content_type: &str, body: &[u8]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "orders".into(),
+            "10.0.0.1".into(),
+            "POST".into(),
+            "/orders/7".into(),
+            HashMap::new(),
+        );
+        ctx.request_headers = vec![
+            ("Content-Type".to_string(), content_type.to_string()),
+            ("X-Internal".to_string(), "1".to_string()),
+        ]
+        .into();
+        ctx.route_params = vec![("id".into(), "7".into())];
+        ctx.consumer = Some("alice".into());
+        ctx.request_body = Some(body.to_vec());
+        ctx.request_body_size = body.len() as u64;
+        ctx
+    }
+
+    fn run(config: Value, ctx: &mut PluginContext) {
+        let instance = RequestTransformerPlugin.configure(&config).unwrap();
+        assert!(matches!(instance.access(ctx), PluginResult::Continue));
+    }
+
+    #[test]
+    fn adds_templated_headers_and_records_the_edits() {
+        let mut ctx = ctx("application/json", b"{}");
+        run(
+            json!({
+                "remove": {"headers": ["x-internal"]},
+                "add": {"headers": {
+                    "x-consumer": "$consumer",
+                    "x-route": "$route_id",
+                    "x-original-path": "$uri"
+                }}
+            }),
+            &mut ctx,
+        );
+        assert_eq!(ctx.get_header("x-consumer"), Some("alice"));
+        assert_eq!(ctx.get_header("x-route"), Some("orders"));
+        assert_eq!(ctx.get_header("x-original-path"), Some("/orders/7"));
+        assert_eq!(ctx.get_header("x-internal"), None);
+        // The proxy forwards edited headers upstream.
+        assert!(ctx.request_headers.edited().contains(&"x-internal".into()));
+        assert!(ctx.request_headers.edited().contains(&"x-consumer".into()));
+    }
+
+    #[test]
+    fn edits_json_body_fields() {
+        let mut ctx = ctx("application/json", br#"{"item":"book","debug":true}"#);
+        run(
+            json!({
+                "remove": {"json": ["debug"]},
+                "add": {"json": {"order_id": "$path_id", "by": "$consumer"}}
+            }),
+            &mut ctx,
+        );
+        let body: Value = serde_json::from_slice(ctx.request_body.as_deref().unwrap()).unwrap();
+        assert_eq!(
+            body,
+            json!({"item": "book", "order_id": "7", "by": "alice"})
+        );
+    }
+
+    #[test]
+    fn passes_non_json_bodies_through() {
+        let config = json!({"add": {"json": {"by": "$consumer"}}});
+        for (content_type, body) in [
+            ("text/plain", &b"{\"a\":1}"[..]),
+            ("application/json", b"not json"),
+            ("application/json", b"[1]"),
+        ] {
+            let mut ctx = ctx(content_type, body);
+            run(config.clone(), &mut ctx);
+            assert_eq!(ctx.request_body.as_deref(), Some(body));
+        }
+
+        // Part of a streamed body.
+        let mut streamed = ctx("application/json", b"{}");
+        streamed.request_body_size = 10_000_000;
+        run(config, &mut streamed);
+        assert_eq!(streamed.request_body.as_deref(), Some(&b"{}"[..]));
+    }
+
+    #[test]
+    fn reads_body_only_for_json_ops() {
+        let headers = RequestTransformerPlugin
+            .configure(&json!({"add": {"headers": {"x-a": "1"}}}))
+            .unwrap();
+        assert!(!headers.reads_body());
+        let body = RequestTransformerPlugin
+            .configure(&json!({"remove": {"json": ["a"]}}))
+            .unwrap();
+        assert!(body.reads_body());
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        assert!(
+            RequestTransformerPlugin
+                .configure(&json!({"add": {"headers": ["x-a: 1"]}}))
+                .is_err()
+        );
+        assert!(
+            RequestTransformerPlugin
+                .configure(&json!({"add": {"headers": {"x-a": "${consumer"}}}))
+                .is_err()
+        );
+    }
+}
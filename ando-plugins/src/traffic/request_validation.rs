@@ -0,0 +1,385 @@
+use ando_core::vars::percent_decode;
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use jsonschema::Validator;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Most violations listed in a 400 response.
+const MAX_VIOLATIONS: usize = 10;
+
+/// Longest single violation message, in bytes.
+const MAX_MESSAGE_LEN: usize = 200;
+
+/// Request-validation plugin — checks headers, query parameters and the
+/// JSON body against JSON Schemas before the request is proxied.
+///
+/// Schemas are compiled once in `configure()`, so an invalid schema fails
+/// the route's config and each request pays only for validation.
+pub struct RequestValidationPlugin;
+
+#[derive(Debug, Deserialize)]
+struct RequestValidationConfig {
+    /// Applied to an object of lowercase header names to values.
+    #[serde(default)]
+    header_schema: Option<Value>,
+    /// Applied to an object of query parameter names to values. Repeated
+    /// parameters become arrays; all values are strings.
+    #[serde(default)]
+    querystring_schema: Option<Value>,
+    /// Applied to the request body, which must be JSON.
+    #[serde(default)]
+    body_schema: Option<Value>,
+    #[serde(default = "default_rejected_code")]
+    rejected_code: u16,
+}
+
+fn default_rejected_code() -> u16 {
+    400
+}
+
+struct RequestValidationInstance {
+    header: Option<Validator>,
+    querystring: Option<Validator>,
+    body: Option<Validator>,
+    rejected_code: u16,
+}
+
+fn compile(name: &str, schema: Option<Value>) -> anyhow::Result<Option<Validator>> {
+    schema
+        .map(|schema| {
+            jsonschema::validator_for(&schema)
+                .map_err(|e| anyhow::anyhow!("request-validation: invalid {name}: {e}"))
+        })
+        .transpose()
+}
+
+impl Plugin for RequestValidationPlugin {
+    fn name(&self) -> &str {
+        "request-validation"
+    }
+
+    fn priority(&self) -> i32 {
+        2800 // before auth, like APISIX
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: RequestValidationConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("request-validation config error: {e}"))?;
+
+        if cfg.header_schema.is_none()
+            && cfg.querystring_schema.is_none()
+            && cfg.body_schema.is_none()
+        {
+            anyhow::bail!(
+                "request-validation requires 'header_schema', 'querystring_schema' or 'body_schema'"
+            );
+        }
+        if !(400..=599).contains(&cfg.rejected_code) {
+            anyhow::bail!("request-validation: rejected_code must be a 4xx or 5xx status");
+        }
+
+        Ok(Box::new(RequestValidationInstance {
+            header: compile("header_schema", cfg.header_schema)?,
+            querystring: compile("querystring_schema", cfg.querystring_schema)?,
+            body: compile("body_schema", cfg.body_schema)?,
+            rejected_code: cfg.rejected_code,
+        }))
+    }
+}
+
+impl RequestValidationInstance {
+    fn check(&self, ctx: &PluginContext, violations: &mut Vec<String>) {
+        if let Some(validator) = &self.header {
+            let headers: Map<String, Value> = ctx
+                .request_headers
+                .combined()
+                .into_iter()
+                .map(|(k, v)| (k, Value::String(v)))
+                .collect();
+            collect(validator, "header", &Value::Object(headers), violations);
+        }
+
+        if let Some(validator) = &self.querystring {
+            let query = ctx.uri.split_once('?').map_or("", |(_, q)| q);
+            collect(validator, "query", &parse_query(query), violations);
+        }
+
+        if let Some(validator) = &self.body {
+            let body = ctx.request_body.as_deref().unwrap_or_default();
+            if body.is_empty() {
+                violations.push("body: expected a JSON body".to_string());
+            } else {
+                match serde_json::from_slice::<Value>(body) {
+                    Ok(value) => collect(validator, "body", &value, violations),
+                    Err(e) => violations.push(truncate(format!("body: invalid JSON: {e}"))),
+                }
+            }
+        }
+    }
+}
+
+/// Append `validator`'s errors for `instance`, up to `MAX_VIOLATIONS` total.
+fn collect(validator: &Validator, part: &str, instance: &Value, violations: &mut Vec<String>) {
+    let room = MAX_VIOLATIONS.saturating_sub(violations.len());
+    for error in validator.iter_errors(instance).take(room) {
+        let path = error.instance_path().to_string();
+        let message = if path.is_empty() {
+            format!("{part}: {error}")
+        } else {
+            format!("{part} {path}: {error}")
+        };
+        violations.push(truncate(message));
+    }
+}
+
+fn truncate(mut message: String) -> String {
+    if message.len() > MAX_MESSAGE_LEN {
+        let mut end = MAX_MESSAGE_LEN;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+        message.push('…');
+    }
+    message
+}
+
+/// Decode a query string into an object. Repeated names become arrays.
+fn parse_query(query: &str) -> Value {
+    let mut params = Map::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let value = Value::String(percent_decode(value).into_owned());
+        match params.get_mut(percent_decode(name).as_ref()) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None => {
+                params.insert(percent_decode(name).into_owned(), value);
+            }
+        }
+    }
+    Value::Object(params)
+}
+
+impl PluginInstance for RequestValidationInstance {
+    fn name(&self) -> &str {
+        "request-validation"
+    }
+
+    fn priority(&self) -> i32 {
+        2800
+    }
+
+    fn reads_body(&self) -> bool {
+        self.body.is_some()
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let mut violations = Vec::new();
+        self.check(ctx, &mut violations);
+        if violations.is_empty() {
+            return PluginResult::Continue;
+        }
+
+        let body = serde_json::json!({
+            "error": "request validation failed",
+            "status": self.rejected_code,
+            "violations": violations,
+        });
+        PluginResult::Response {
+            status: self.rejected_code,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(body.to_string().into_bytes()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn make_ctx(uri: &str, headers: &[(&str, &str)], body: Option<&str>) -> PluginContext {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<_, _>>();
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "POST".into(),
+            uri.into(),
+            headers,
+        );
+        ctx.request_body = body.map(|b| b.as_bytes().to_vec());
+        ctx
+    }
+
+    fn configure(config: Value) -> Box<dyn PluginInstance> {
+        RequestValidationPlugin.configure(&config).unwrap()
+    }
+
+    /// The violations of a rejected request; panics if it passed.
+    fn violations(inst: &dyn PluginInstance, ctx: &mut PluginContext) -> Vec<String> {
+        match inst.access(ctx) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 400);
+                let body: Value = serde_json::from_slice(&body.unwrap()).unwrap();
+                assert_eq!(body["error"], "request validation failed");
+                serde_json::from_value(body["violations"].clone()).unwrap()
+            }
+            PluginResult::Continue => panic!("expected a rejection"),
+        }
+    }
+
+    fn user_schema() -> Value {
+        serde_json::json!({
+            "body_schema": {
+                "type": "object",
+                "required": ["name", "age"],
+                "properties": {
+                    "name": { "type": "string" },
+                    "age": { "type": "integer", "minimum": 0 }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn valid_body_passes() {
+        let inst = configure(user_schema());
+        assert!(inst.reads_body());
+        let mut ctx = make_ctx("/users", &[], Some(r#"{"name":"alice","age":30}"#));
+        assert!(matches!(inst.access(&mut ctx), PluginResult::Continue));
+    }
+
+    #[test]
+    fn missing_required_field_is_reported() {
+        let inst = configure(user_schema());
+        let mut ctx = make_ctx("/users", &[], Some(r#"{"name":"alice"}"#));
+        let v = violations(inst.as_ref(), &mut ctx);
+        assert_eq!(v.len(), 1);
+        assert!(v[0].starts_with("body: "), "{v:?}");
+        assert!(v[0].contains("age"), "{v:?}");
+    }
+
+    #[test]
+    fn type_mismatches_are_reported_with_their_path() {
+        let inst = configure(user_schema());
+        let mut ctx = make_ctx("/users", &[], Some(r#"{"name":7,"age":"old"}"#));
+        let v = violations(inst.as_ref(), &mut ctx);
+        assert_eq!(v.len(), 2, "{v:?}");
+        assert!(v.iter().any(|m| m.starts_with("body /name:")), "{v:?}");
+        assert!(v.iter().any(|m| m.starts_with("body /age:")), "{v:?}");
+    }
+
+    #[test]
+    fn non_json_body_is_rejected() {
+        let inst = configure(user_schema());
+        let mut ctx = make_ctx("/users", &[], Some("name=alice"));
+        let v = violations(inst.as_ref(), &mut ctx);
+        assert!(v[0].starts_with("body: invalid JSON"), "{v:?}");
+
+        let mut ctx = make_ctx("/users", &[], None);
+        let v = violations(inst.as_ref(), &mut ctx);
+        assert_eq!(v, vec!["body: expected a JSON body"]);
+    }
+
+    #[test]
+    fn querystring_schema_checks_decoded_params() {
+        let inst = configure(serde_json::json!({
+            "querystring_schema": {
+                "type": "object",
+                "required": ["q"],
+                "properties": {
+                    "q": { "type": "string", "minLength": 3 },
+                    "page": { "type": "string", "pattern": "^[0-9]+$" }
+                }
+            }
+        }));
+        assert!(!inst.reads_body());
+        let mut ok = make_ctx("/search?q=rust%20lang&page=2", &[], None);
+        assert!(matches!(inst.access(&mut ok), PluginResult::Continue));
+
+        let mut missing = make_ctx("/search?page=2", &[], None);
+        let v = violations(inst.as_ref(), &mut missing);
+        assert!(
+            v[0].starts_with("query: ") && v[0].contains("\"q\""),
+            "{v:?}"
+        );
+
+        let mut bad = make_ctx("/search?q=ok!&page=two", &[], None);
+        let v = violations(inst.as_ref(), &mut bad);
+        assert!(v[0].starts_with("query /page:"), "{v:?}");
+    }
+
+    #[test]
+    fn header_schema_checks_lowercase_headers() {
+        let inst = configure(serde_json::json!({
+            "header_schema": {
+                "type": "object",
+                "required": ["x-tenant"],
+                "properties": { "x-tenant": { "enum": ["a", "b"] } }
+            }
+        }));
+        let mut ok = make_ctx("/", &[("x-tenant", "a")], None);
+        assert!(matches!(inst.access(&mut ok), PluginResult::Continue));
+        let mut bad = make_ctx("/", &[("x-tenant", "z")], None);
+        let v = violations(inst.as_ref(), &mut bad);
+        assert!(v[0].starts_with("header /x-tenant:"), "{v:?}");
+    }
+
+    #[test]
+    fn violations_are_capped() {
+        let properties: Map<String, Value> = (0..30)
+            .map(|i| (format!("f{i}"), serde_json::json!({ "type": "integer" })))
+            .collect();
+        let inst = configure(serde_json::json!({
+            "body_schema": { "type": "object", "properties": properties }
+        }));
+        let body: Map<String, Value> = (0..30)
+            .map(|i| (format!("f{i}"), Value::String("x".repeat(500))))
+            .collect();
+        let body = Value::Object(body).to_string();
+        let mut ctx = make_ctx("/", &[], Some(&body));
+        let v = violations(inst.as_ref(), &mut ctx);
+        assert_eq!(v.len(), MAX_VIOLATIONS);
+        assert!(
+            v.iter()
+                .all(|m| m.len() <= MAX_MESSAGE_LEN + '…'.len_utf8())
+        );
+    }
+
+    #[test]
+    fn parse_query_handles_repeats_and_encoding() {
+        assert_eq!(
+            parse_query("a=1&b=x+y&a=2&c&d=%E2%9C%93&e=%zz"),
+            serde_json::json!({
+                "a": ["1", "2"],
+                "b": "x y",
+                "c": "",
+                "d": "✓",
+                "e": "%zz"
+            })
+        );
+    }
+
+    #[test]
+    fn configure_rejects_invalid_schemas() {
+        for config in [
+            serde_json::json!({}),
+            serde_json::json!({ "body_schema": { "type": "no-such-type" } }),
+            serde_json::json!({ "querystring_schema": { "minLength": -1 } }),
+            serde_json::json!({ "header_schema": { "type": "object" }, "rejected_code": 200 }),
+        ] {
+            assert!(
+                RequestValidationPlugin.configure(&config).is_err(),
+                "{config}"
+            );
+        }
+    }
+}
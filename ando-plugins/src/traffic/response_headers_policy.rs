@@ -0,0 +1,288 @@
+//! Response Headers Policy plugin.
+//!
+//! Strips headers that fingerprint the upstream stack (`Server`,
+//! `X-Powered-By`, ...), sets a fixed list of headers on every response and
+//! adds another next to the upstream's own (e.g. a second `Set-Cookie`).
+//! It is meant to run as a global plugin next to `security-headers`; a
+//! route that must keep its upstream headers opts out with
+//! `_meta.disable`.
+//!
+//! # Example plugin config
+//!
+//! ```yaml
+//! plugins:
+//!   global:
+//!     response-headers-policy:
+//!       remove: ["server", "x-powered-by"]
+//!       set:
+//!         x-served-by: "ando"
+//!       add:
+//!         - "set-cookie: region=eu; Path=/"
+//! ```
+
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// ─────────────────────────────────────────────────────────────
+// Config
+// ─────────────────────────────────────────────────────────────
+
+/// Configuration for the response-headers-policy plugin.
+#[derive(Debug, Deserialize, Clone)]
+struct ResponseHeadersPolicyConfig {
+    /// Upstream response headers to drop, matched case-insensitively.
+    /// Default: the common server-fingerprinting headers.
+    #[serde(default = "default_remove")]
+    remove: Vec<String>,
+
+    /// Headers to set on every response, replacing any upstream value.
+    #[serde(default)]
+    set: HashMap<String, String>,
+
+    /// `"name: value"` headers to add to every response, keeping the
+    /// upstream's values of the same name. A name may repeat.
+    #[serde(default)]
+    add: Vec<String>,
+}
+
+fn default_remove() -> Vec<String> {
+    [
+        "server",
+        "x-powered-by",
+        "x-aspnet-version",
+        "x-aspnetmvc-version",
+    ]
+    .into_iter()
+    .map(String::from)
+    .collect()
+}
+
+// ─────────────────────────────────────────────────────────────
+// Plugin factory
+// ─────────────────────────────────────────────────────────────
+
+/// Response Headers Policy plugin factory.
+pub struct ResponseHeadersPolicyPlugin;
+
+impl Plugin for ResponseHeadersPolicyPlugin {
+    fn name(&self) -> &str {
+        "response-headers-policy"
+    }
+
+    /// Priority 899: runs after `security-headers` so that its `set` list
+    /// wins when both name the same header.
+    fn priority(&self) -> i32 {
+        899
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::HeaderFilter]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: ResponseHeadersPolicyConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("response-headers-policy config error: {e}"))?;
+
+        let mut add = Vec::with_capacity(cfg.add.len());
+        for entry in &cfg.add {
+            let Some((name, value)) = entry.split_once(':') else {
+                anyhow::bail!("response-headers-policy: add entry '{entry}' is not 'name: value'");
+            };
+            add.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+        }
+
+        for name in cfg
+            .remove
+            .iter()
+            .chain(cfg.set.keys())
+            .chain(add.iter().map(|(name, _)| name))
+        {
+            if !is_header_name(name) {
+                anyhow::bail!("response-headers-policy: invalid header name '{name}'");
+            }
+        }
+        if let Some((name, _)) = cfg
+            .set
+            .iter()
+            .chain(add.iter().map(|(k, v)| (k, v)))
+            .find(|(_, v)| v.bytes().any(|b| b == b'\r' || b == b'\n'))
+        {
+            anyhow::bail!("response-headers-policy: value of '{name}' contains a line break");
+        }
+
+        Ok(Box::new(ResponseHeadersPolicyInstance {
+            remove: cfg.remove.iter().map(|n| n.to_ascii_lowercase()).collect(),
+            set: cfg
+                .set
+                .into_iter()
+                .map(|(k, v)| (k.to_ascii_lowercase(), v))
+                .collect(),
+            add,
+        }))
+    }
+}
+
+/// An RFC 9110 token.
+pub(crate) fn is_header_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+// ─────────────────────────────────────────────────────────────
+// Instance
+// ─────────────────────────────────────────────────────────────
+
+struct ResponseHeadersPolicyInstance {
+    /// Lower-cased names to drop.
+    remove: Vec<String>,
+    /// Lower-cased names and values to set.
+    set: Vec<(String, String)>,
+    /// Lower-cased names and values to add.
+    add: Vec<(String, String)>,
+}
+
+impl PluginInstance for ResponseHeadersPolicyInstance {
+    fn name(&self) -> &str {
+        "response-headers-policy"
+    }
+    fn priority(&self) -> i32 {
+        899
+    }
+
+    fn filters_headers(&self) -> bool {
+        true
+    }
+
+    fn header_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+        let dropped = |name: &str| self.remove.iter().any(|r| r.eq_ignore_ascii_case(name));
+        ctx.upstream_response_headers
+            .retain(|(name, _)| !dropped(name));
+        ctx.response_headers.retain(|name, _| !dropped(name));
+        for (k, v) in &self.set {
+            ctx.response_headers.insert(k.clone(), v.clone());
+        }
+        for (k, v) in &self.add {
+            ctx.response_headers.append(k.clone(), v.clone());
+        }
+        PluginResult::Continue
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Tests
+// ─────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx_with_upstream(headers: &[(&str, &str)]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "route-1".into(),
+            "127.0.0.1".into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        );
+        ctx.upstream_response_headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        ctx
+    }
+
+    #[test]
+    fn default_config_strips_fingerprinting_headers() {
+        let instance = ResponseHeadersPolicyPlugin
+            .configure(&serde_json::json!({}))
+            .unwrap();
+        let mut ctx = ctx_with_upstream(&[
+            ("Server", "nginx/1.25"),
+            ("X-Powered-By", "PHP/8.2"),
+            ("Content-Type", "text/plain"),
+        ]);
+        assert!(matches!(
+            instance.header_filter(&mut ctx),
+            PluginResult::Continue
+        ));
+        assert_eq!(
+            ctx.upstream_response_headers,
+            vec![("Content-Type".to_string(), "text/plain".to_string())]
+        );
+    }
+
+    #[test]
+    fn set_headers_replace_upstream_values() {
+        let instance = ResponseHeadersPolicyPlugin
+            .configure(&serde_json::json!({
+                "remove": [],
+                "set": { "X-Served-By": "ando" }
+            }))
+            .unwrap();
+        let mut ctx = ctx_with_upstream(&[("Server", "nginx")]);
+        instance.header_filter(&mut ctx);
+        assert_eq!(ctx.upstream_response_headers.len(), 1);
+        assert_eq!(ctx.response_headers.get("x-served-by"), Some("ando"));
+    }
+
+    #[test]
+    fn added_headers_keep_upstream_values() {
+        let instance = ResponseHeadersPolicyPlugin
+            .configure(&serde_json::json!({
+                "remove": [],
+                "add": ["Set-Cookie: region=eu; Path=/", "set-cookie: csrf=x"]
+            }))
+            .unwrap();
+        let mut ctx = ctx_with_upstream(&[("set-cookie", "session=1")]);
+        instance.header_filter(&mut ctx);
+        assert_eq!(ctx.upstream_response_headers.len(), 1);
+        assert_eq!(
+            ctx.response_headers
+                .get_all("set-cookie")
+                .collect::<Vec<_>>(),
+            ["region=eu; Path=/", "csrf=x"]
+        );
+        assert!(!ctx.response_headers.replaces("set-cookie"));
+    }
+
+    #[test]
+    fn removal_also_covers_headers_set_by_earlier_plugins() {
+        let instance = ResponseHeadersPolicyPlugin
+            .configure(&serde_json::json!({ "remove": ["X-Debug"] }))
+            .unwrap();
+        let mut ctx = ctx_with_upstream(&[]);
+        ctx.response_headers.insert("x-debug".into(), "1".into());
+        instance.header_filter(&mut ctx);
+        assert!(ctx.response_headers.is_empty());
+    }
+
+    #[test]
+    fn meta_is_tolerated() {
+        assert!(
+            ResponseHeadersPolicyPlugin
+                .configure(&serde_json::json!({ "_meta": { "disable": false } }))
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn invalid_names_and_values_are_rejected() {
+        for config in [
+            serde_json::json!({ "remove": ["bad header"] }),
+            serde_json::json!({ "set": { "": "x" } }),
+            serde_json::json!({ "set": { "x-ok": "a\r\nInjected: 1" } }),
+            serde_json::json!({ "remove": "server" }),
+            serde_json::json!({ "add": ["no-colon"] }),
+            serde_json::json!({ "add": ["bad name: 1"] }),
+            serde_json::json!({ "add": ["x-ok: a\nInjected: 1"] }),
+        ] {
+            assert!(
+                ResponseHeadersPolicyPlugin.configure(&config).is_err(),
+                "{config}"
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::transform::{Transform, TransformConfig, is_plain_json};
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+
+/// Response transformer plugin — edits the upstream response: removes,
+/// renames, replaces and adds headers and, for JSON bodies, top-level
+/// fields. Values are templates over the request (`$header_<name>` is a
+/// request header); see [`super::transform`] for the variables.
+///
+/// Responses are only buffered when a `json` operation is configured.
+/// Bodies that are not JSON objects or are content-encoded are passed on
+/// as they are.
+pub struct ResponseTransformerPlugin;
+
+struct ResponseTransformerInstance {
+    transform: Transform,
+}
+
+impl Plugin for ResponseTransformerPlugin {
+    fn name(&self) -> &str {
+        "response-transformer"
+    }
+
+    fn priority(&self) -> i32 {
+        800
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::HeaderFilter, Phase::BodyFilter]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: TransformConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("response-transformer config error: {e}"))?;
+        let transform = Transform::compile(cfg)
+            .map_err(|e| anyhow::anyhow!("response-transformer config error: {e}"))?;
+        Ok(Box::new(ResponseTransformerInstance { transform }))
+    }
+}
+
+impl PluginInstance for ResponseTransformerInstance {
+    fn name(&self) -> &str {
+        "response-transformer"
+    }
+
+    fn priority(&self) -> i32 {
+        800
+    }
+
+    fn filters_headers(&self) -> bool {
+        true
+    }
+
+    fn header_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+        let mut headers = std::mem::take(&mut ctx.upstream_response_headers);
+        self.transform.header_edits(ctx).apply(&mut headers);
+        ctx.upstream_response_headers = headers;
+        PluginResult::Continue
+    }
+
+    fn filters_body(&self) -> bool {
+        self.transform.has_json_ops()
+    }
+
+    fn body_filter(&self, ctx: &mut PluginContext) -> PluginResult {
+        let headers = ctx
+            .upstream_response_headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()));
+        if is_plain_json(headers)
+            && let Some(body) = ctx.response_body.as_deref()
+            && let Some(edited) = self.transform.apply_json(ctx, body)
+        {
+            // The proxy re-frames the body with its new length.
+            ctx.response_body = Some(edited);
+        }
+        PluginResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use std::collections::HashMap;
+
+    fn ctx(content_type: &str, body: &[u8]) -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "users".into(),
+            "10.0.0.1".into(),
+            "GET".into(),
+            "/users/9".into(),
+            HashMap::new(),
+        );
+        ctx.request_headers = vec![("x-request-id".to_string(), "req-1".to_string())].into();
+        ctx.upstream_response_headers = vec![
+            ("content-type".to_string(), content_type.to_string()),
+            ("server".to_string(), "app/1.0".to_string()),
+        ];
+        ctx.response_body = Some(body.to_vec());
+        ctx
+    }
+
+    fn run(config: Value, ctx: &mut PluginContext) {
+        let instance = ResponseTransformerPlugin.configure(&config).unwrap();
+        assert!(matches!(
+            instance.header_filter(ctx),
+            PluginResult::Continue
+        ));
+        if instance.filters_body() {
+            assert!(matches!(instance.body_filter(ctx), PluginResult::Continue));
+        }
+    }
+
+    fn header<'a>(ctx: &'a PluginContext, name: &str) -> Option<&'a str> {
+        ctx.upstream_response_headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn edits_upstream_headers_with_templates() {
+        let mut ctx = ctx("application/json", b"{}");
+        run(
+            json!({
+                "remove": {"headers": ["server"]},
+                "rename": {"headers": {"content-type": "x-upstream-type"}},
+                "add": {"headers": {"x-request-id": "$header_x_request_id", "x-route": "$route_id"}}
+            }),
+            &mut ctx,
+        );
+        assert_eq!(header(&ctx, "server"), None);
+        assert_eq!(header(&ctx, "content-type"), None);
+        assert_eq!(header(&ctx, "x-upstream-type"), Some("application/json"));
+        assert_eq!(header(&ctx, "x-request-id"), Some("req-1"));
+        assert_eq!(header(&ctx, "x-route"), Some("users"));
+    }
+
+    #[test]
+    fn edits_json_body_fields() {
+        let mut ctx = ctx(
+            "application/json; charset=utf-8",
+            br#"{"id":9,"password_hash":"x","name":"n"}"#,
+        );
+        run(
+            json!({
+                "remove": {"json": ["password_hash"]},
+                "replace": {"json": {"name": "redacted"}},
+                "add": {"json": {"route": "$route_id"}}
+            }),
+            &mut ctx,
+        );
+        let body: Value = serde_json::from_slice(ctx.response_body.as_deref().unwrap()).unwrap();
+        assert_eq!(body, json!({"id": 9, "name": "redacted", "route": "users"}));
+    }
+
+    #[test]
+    fn passes_non_json_bodies_through() {
+        let config = json!({"remove": {"json": ["id"]}});
+        for (content_type, body) in [
+            ("text/html", &b"{\"id\":1}"[..]),
+            ("application/json", b"<html></html>"),
+        ] {
+            let mut ctx = ctx(content_type, body);
+            run(config.clone(), &mut ctx);
+            assert_eq!(ctx.response_body.as_deref(), Some(body));
+        }
+
+        let mut gzipped = ctx("application/json", b"\x1f\x8b");
+        gzipped
+            .upstream_response_headers
+            .push(("content-encoding".into(), "gzip".into()));
+        run(config, &mut gzipped);
+        assert_eq!(gzipped.response_body.as_deref(), Some(&b"\x1f\x8b"[..]));
+    }
+
+    #[test]
+    fn buffers_bodies_only_for_json_ops() {
+        let headers = ResponseTransformerPlugin
+            .configure(&json!({"remove": {"headers": ["server"]}}))
+            .unwrap();
+        assert!(headers.filters_headers());
+        assert!(!headers.filters_body());
+        let body = ResponseTransformerPlugin
+            .configure(&json!({"add": {"json": {"a": 1}}}))
+            .unwrap();
+        assert!(body.filters_body());
+    }
+}
@@ -146,6 +146,10 @@ impl PluginInstance for SecurityHeadersInstance {
         3000
     }
 
+    fn filters_headers(&self) -> bool {
+        true
+    }
+
     fn header_filter(&self, ctx: &mut PluginContext) -> PluginResult {
         for (k, v) in &self.headers {
             ctx.response_headers.insert(k.clone(), v.clone());
@@ -0,0 +1,471 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Time restriction plugin — lets requests through only in (or only
+/// outside) weekly time windows, e.g. business hours or a maintenance
+/// window answering 503.
+///
+/// Each rule is a window on `days` from `start` to `end` in its own IANA
+/// `timezone`, with an `action`. Rules are checked in order and the first
+/// window containing the current time decides: `allow` lets the request
+/// through, `deny` rejects it. Outside every window requests are rejected
+/// when any rule is an `allow` rule and let through otherwise. No rules,
+/// no restriction.
+///
+/// Windows are wall-clock times: one whose `end` is not after its `start`
+/// crosses midnight and belongs to the day it starts on, and across DST
+/// changes it covers the local times that exist that day (an hour shorter
+/// or longer than usual).
+pub struct TimeRestrictionPlugin {
+    clock: Arc<dyn Clock>,
+}
+
+/// The current time, for the plugin to check windows against.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The system's clock.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+impl TimeRestrictionPlugin {
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Check windows against `clock` instead of the system's.
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self { clock }
+    }
+
+    fn instance(&self, config: &serde_json::Value) -> anyhow::Result<TimeRestrictionInstance> {
+        let cfg: TimeRestrictionConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("time-restriction config error: {e}"))?;
+        let rules = cfg
+            .rules
+            .iter()
+            .map(Window::compile)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let outside = if rules.iter().any(|rule| rule.action == Action::Allow) {
+            Action::Deny
+        } else {
+            Action::Allow
+        };
+
+        Ok(TimeRestrictionInstance {
+            rules,
+            outside,
+            clock: Arc::clone(&self.clock),
+            rejected_code: cfg.rejected_code,
+            body: serde_json::json!({ "error": cfg.message, "status": cfg.rejected_code })
+                .to_string()
+                .into_bytes(),
+        })
+    }
+}
+
+impl Default for TimeRestrictionPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeRestrictionConfig {
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+    /// Status for rejected requests — default 403.
+    #[serde(default = "default_rejected_code")]
+    rejected_code: u16,
+    #[serde(default = "default_message")]
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+    /// `mon` .. `sun`; empty means every day.
+    #[serde(default)]
+    days: Vec<String>,
+    /// `HH:MM` or `HH:MM:SS`; `end` may be `24:00`.
+    start: String,
+    end: String,
+    /// IANA name — default "UTC".
+    #[serde(default = "default_timezone")]
+    timezone: String,
+    #[serde(default)]
+    action: Action,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Action {
+    #[default]
+    Allow,
+    Deny,
+}
+
+fn default_rejected_code() -> u16 {
+    403
+}
+
+fn default_message() -> String {
+    "Not allowed at this time".to_string()
+}
+
+fn default_timezone() -> String {
+    "UTC".to_string()
+}
+
+struct Window {
+    /// Bit `n` set: the window starts on the day `n` days from Monday.
+    days: u8,
+    start: NaiveTime,
+    end: NaiveTime,
+    timezone: Tz,
+    action: Action,
+}
+
+struct TimeRestrictionInstance {
+    rules: Vec<Window>,
+    /// What happens outside every window.
+    outside: Action,
+    clock: Arc<dyn Clock>,
+    rejected_code: u16,
+    /// Pre-rendered rejection body.
+    body: Vec<u8>,
+}
+
+fn parse_days(days: &[String]) -> anyhow::Result<u8> {
+    if days.is_empty() {
+        return Ok(0x7f);
+    }
+    days.iter().try_fold(0u8, |mask, day| {
+        let weekday: Weekday = day
+            .parse()
+            .map_err(|_| anyhow::anyhow!("time-restriction: invalid day `{day}`"))?;
+        Ok(mask | 1 << weekday.num_days_from_monday())
+    })
+}
+
+fn parse_time(time: &str) -> anyhow::Result<NaiveTime> {
+    let time = time.trim();
+    if time == "24:00" {
+        return Ok(NaiveTime::MIN);
+    }
+    NaiveTime::parse_from_str(time, "%H:%M:%S")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M"))
+        .map_err(|_| anyhow::anyhow!("time-restriction: invalid time `{time}`, expected HH:MM"))
+}
+
+impl Window {
+    fn compile(rule: &RuleConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            days: parse_days(&rule.days)?,
+            start: parse_time(&rule.start)?,
+            end: parse_time(&rule.end)?,
+            timezone: rule.timezone.parse().map_err(|_| {
+                anyhow::anyhow!("time-restriction: unknown timezone `{}`", rule.timezone)
+            })?,
+            action: rule.action,
+        })
+    }
+
+    fn on(&self, day: Weekday) -> bool {
+        self.days & 1 << day.num_days_from_monday() != 0
+    }
+
+    fn contains(&self, now: DateTime<Utc>) -> bool {
+        let local: NaiveDateTime = now.with_timezone(&self.timezone).naive_local();
+        let (day, time) = (local.weekday(), local.time());
+        if self.start < self.end {
+            self.on(day) && self.start <= time && time < self.end
+        } else {
+            // Crosses midnight (a whole day when start == end): the early
+            // hours belong to the previous day's window.
+            (self.on(day) && self.start <= time) || (self.on(day.pred()) && time < self.end)
+        }
+    }
+}
+
+impl Plugin for TimeRestrictionPlugin {
+    fn name(&self) -> &str {
+        "time-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2980
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        Ok(Box::new(self.instance(config)?))
+    }
+}
+
+impl TimeRestrictionInstance {
+    fn permits(&self, now: DateTime<Utc>) -> bool {
+        let action = self
+            .rules
+            .iter()
+            .find(|rule| rule.contains(now))
+            .map_or(self.outside, |rule| rule.action);
+        action == Action::Allow
+    }
+}
+
+impl PluginInstance for TimeRestrictionInstance {
+    fn name(&self) -> &str {
+        "time-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2980
+    }
+
+    fn access(&self, _ctx: &mut PluginContext) -> PluginResult {
+        if self.permits(self.clock.now()) {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: self.rejected_code,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(self.body.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, TimeZone, Timelike};
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    struct FixedClock(Mutex<DateTime<Utc>>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn instance(config: serde_json::Value) -> TimeRestrictionInstance {
+        TimeRestrictionPlugin::new().instance(&config).unwrap()
+    }
+
+    /// Every minute from `from` (inclusive) to `to` (exclusive).
+    fn minutes(from: DateTime<Utc>, to: DateTime<Utc>) -> impl Iterator<Item = DateTime<Utc>> {
+        std::iter::successors(Some(from), |t| Some(*t + Duration::minutes(1)))
+            .take_while(move |t| *t < to)
+    }
+
+    fn ctx() -> PluginContext {
+        PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn rejects_with_the_configured_status_per_the_clock() {
+        let clock = Arc::new(FixedClock(Mutex::new(utc(2026, 6, 5, 23, 30))));
+        let inst = TimeRestrictionPlugin::with_clock(clock.clone())
+            .configure(&serde_json::json!({
+                "rules": [{"days": ["fri"], "start": "23:00", "end": "01:00", "action": "deny"}],
+                "rejected_code": 503,
+                "message": "Down for maintenance"
+            }))
+            .unwrap();
+        match inst.access(&mut ctx()) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 503);
+                let body: serde_json::Value = serde_json::from_slice(&body.unwrap()).unwrap();
+                assert_eq!(body["error"], "Down for maintenance");
+            }
+            PluginResult::Continue => panic!("Expected the maintenance window to reject"),
+        }
+        *clock.0.lock().unwrap() = utc(2026, 6, 6, 1, 0);
+        assert!(matches!(inst.access(&mut ctx()), PluginResult::Continue));
+    }
+
+    #[test]
+    fn empty_config_never_restricts() {
+        let clock = Arc::new(FixedClock(Mutex::new(utc(2026, 1, 1, 3, 0))));
+        let inst = TimeRestrictionPlugin::with_clock(clock)
+            .configure(&serde_json::json!({}))
+            .unwrap();
+        assert!(matches!(inst.access(&mut ctx()), PluginResult::Continue));
+    }
+
+    #[test]
+    fn window_crossing_midnight_belongs_to_its_start_day() {
+        let inst = instance(serde_json::json!({
+            "rules": [{"days": ["fri"], "start": "22:00", "end": "06:00", "action": "deny"}]
+        }));
+        // A whole week, minute by minute.
+        for t in minutes(utc(2026, 6, 1, 0, 0), utc(2026, 6, 8, 0, 0)) {
+            let (day, hour) = (t.weekday(), t.hour());
+            let inside = (day == Weekday::Fri && hour >= 22) || (day == Weekday::Sat && hour < 6);
+            assert_eq!(inst.permits(t), !inside, "{t}");
+        }
+    }
+
+    #[test]
+    fn equal_start_and_end_cover_a_whole_day_and_24_00_ends_it() {
+        let whole = instance(serde_json::json!({
+            "rules": [{"days": ["wed"], "start": "12:00", "end": "12:00"}]
+        }));
+        let until_midnight = instance(serde_json::json!({
+            "rules": [{"days": ["wed"], "start": "12:00", "end": "24:00"}]
+        }));
+        // 2026-06-03 is a Wednesday.
+        for t in minutes(utc(2026, 6, 3, 0, 0), utc(2026, 6, 5, 0, 0)) {
+            let since_wed_noon = t - utc(2026, 6, 3, 12, 0);
+            let whole_day = (Duration::zero()..Duration::hours(24)).contains(&since_wed_noon);
+            assert_eq!(whole.permits(t), whole_day, "{t}");
+            let afternoon = (Duration::zero()..Duration::hours(12)).contains(&since_wed_noon);
+            assert_eq!(until_midnight.permits(t), afternoon, "{t}");
+        }
+    }
+
+    #[test]
+    fn business_hours_follow_dst_in_the_configured_timezone() {
+        let inst = instance(serde_json::json!({
+            "rules": [{
+                "days": ["mon", "tue", "wed", "thu", "fri"],
+                "start": "09:00", "end": "17:00",
+                "timezone": "America/New_York"
+            }]
+        }));
+        // EST (UTC-5) before 2026-03-08, EDT (UTC-4) after.
+        assert!(!inst.permits(utc(2026, 3, 6, 13, 59)));
+        assert!(inst.permits(utc(2026, 3, 6, 14, 0)));
+        assert!(!inst.permits(utc(2026, 3, 9, 12, 59)));
+        assert!(inst.permits(utc(2026, 3, 9, 13, 0)));
+        assert!(!inst.permits(utc(2026, 3, 9, 21, 0)));
+
+        // Across both transitions the window opens and closes exactly at
+        // local 09:00 and 17:00 and stays open eight hours each weekday.
+        let tz: Tz = "America/New_York".parse().unwrap();
+        for (from, to) in [
+            (utc(2026, 3, 4, 0, 0), utc(2026, 3, 12, 0, 0)),
+            (utc(2026, 10, 28, 0, 0), utc(2026, 11, 5, 0, 0)),
+        ] {
+            let mut open = HashMap::new();
+            let mut prev = inst.permits(from);
+            for t in minutes(from, to) {
+                let now = inst.permits(t);
+                if now {
+                    *open.entry(t.with_timezone(&tz).date_naive()).or_insert(0) += 1;
+                }
+                if now != prev {
+                    let local = t.with_timezone(&tz).time();
+                    let edge = if now { "09:00" } else { "17:00" };
+                    assert_eq!(local, parse_time(edge).unwrap(), "{t}");
+                }
+                prev = now;
+            }
+            assert_eq!(open.len(), 6);
+            assert!(open.values().all(|&minutes| minutes == 8 * 60), "{open:?}");
+        }
+    }
+
+    #[test]
+    fn overnight_window_spans_the_local_hours_of_a_dst_night() {
+        let inst = instance(serde_json::json!({
+            "rules": [{
+                "days": ["sat"], "start": "23:00", "end": "04:00",
+                "timezone": "Europe/Berlin", "action": "deny"
+            }]
+        }));
+        let denied = |from, to| minutes(from, to).filter(|t| !inst.permits(*t)).count();
+        // A regular night is five hours.
+        assert_eq!(
+            denied(utc(2026, 3, 21, 12, 0), utc(2026, 3, 22, 12, 0)),
+            5 * 60
+        );
+        // Clocks skip 02:00-03:00 on 2026-03-29 ...
+        assert_eq!(
+            denied(utc(2026, 3, 28, 12, 0), utc(2026, 3, 29, 12, 0)),
+            4 * 60
+        );
+        // ... and repeat 02:00-03:00 on 2026-10-25.
+        assert_eq!(
+            denied(utc(2026, 10, 24, 12, 0), utc(2026, 10, 25, 12, 0)),
+            6 * 60
+        );
+        // 22:00 UTC is 23:00 CET, 21:00 UTC is 23:00 CEST.
+        assert!(!inst.permits(utc(2026, 3, 21, 22, 0)));
+        assert!(inst.permits(utc(2026, 3, 21, 21, 59)));
+        assert!(!inst.permits(utc(2026, 10, 24, 21, 0)));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let maintenance = serde_json::json!(
+            {"days": ["tue"], "start": "10:00", "end": "11:00", "action": "deny"}
+        );
+        let business = serde_json::json!(
+            {"days": ["mon", "tue"], "start": "09:00", "end": "17:00", "action": "allow"}
+        );
+        // 2026-06-02 is a Tuesday.
+        let during = utc(2026, 6, 2, 10, 30);
+        let deny_first = instance(serde_json::json!({"rules": [maintenance, business]}));
+        assert!(!deny_first.permits(during));
+        assert!(deny_first.permits(utc(2026, 6, 2, 11, 0)));
+        assert!(!deny_first.permits(utc(2026, 6, 2, 17, 0)));
+        let allow_first = instance(serde_json::json!({"rules": [business, maintenance]}));
+        assert!(allow_first.permits(during));
+
+        // Only deny rules: open outside them.
+        let only_deny = instance(serde_json::json!({"rules": [maintenance]}));
+        assert!(only_deny.permits(utc(2026, 6, 2, 12, 0)));
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        let configure = |rule: serde_json::Value| {
+            TimeRestrictionPlugin::new().configure(&serde_json::json!({ "rules": [rule] }))
+        };
+        assert!(configure(serde_json::json!({"start": "09:00", "end": "17:00"})).is_ok());
+        assert!(
+            configure(serde_json::json!({"days": ["Monday"], "start": "9:00", "end": "17:00"}))
+                .is_ok()
+        );
+        assert!(
+            configure(serde_json::json!({"days": ["someday"], "start": "09:00", "end": "17:00"}))
+                .is_err()
+        );
+        assert!(configure(serde_json::json!({"start": "25:00", "end": "17:00"})).is_err());
+        assert!(configure(serde_json::json!({"start": "09:00"})).is_err());
+        assert!(
+            configure(
+                serde_json::json!({"start": "09:00", "end": "17:00", "timezone": "Mars/Olympus"})
+            )
+            .is_err()
+        );
+        assert!(
+            configure(serde_json::json!({"start": "09:00", "end": "17:00", "action": "maybe"}))
+                .is_err()
+        );
+    }
+}
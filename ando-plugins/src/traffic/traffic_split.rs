@@ -0,0 +1,458 @@
+use ando_core::vars::{VarExpr, cookie_value, query_arg};
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use serde::Deserialize;
+use serde_json::Value;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Traffic split plugin — sends a weighted share of a route's traffic to
+/// other upstreams (canary releases, blue/green).
+///
+/// Rules are tried in order; the first whose `match` holds picks one of
+/// its `weighted_upstreams` by weight. An entry is an inline `upstream`
+/// (a node is then picked by weight and proxied to directly), an
+/// `upstream_id` (balanced by the proxy like any named upstream), or
+/// neither, meaning the route's own upstream. No matching rule leaves the
+/// route's upstream in place.
+pub struct TrafficSplitPlugin {
+    rng: Rng,
+}
+
+/// Source of uniform floats in `[0, 1)` for the weighted picks.
+pub type Rng = Arc<dyn Fn() -> f64 + Send + Sync>;
+
+impl TrafficSplitPlugin {
+    pub fn new() -> Self {
+        Self::with_rng(Arc::new(random))
+    }
+
+    /// A plugin drawing its weighted picks from `rng`, for deterministic
+    /// tests.
+    pub fn with_rng(rng: Rng) -> Self {
+        Self { rng }
+    }
+}
+
+impl Default for TrafficSplitPlugin {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TrafficSplitConfig {
+    #[serde(default)]
+    rules: Vec<RuleConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuleConfig {
+    /// Alternatives; the rule applies when any of them holds. Empty
+    /// matches every request.
+    #[serde(default, rename = "match")]
+    matches: Vec<MatchConfig>,
+    weighted_upstreams: Vec<WeightedUpstreamConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MatchConfig {
+    /// APISIX-style `vars` conditions, all of which must hold.
+    #[serde(default)]
+    vars: Vec<Vec<Value>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeightedUpstreamConfig {
+    upstream: Option<InlineUpstream>,
+    upstream_id: Option<String>,
+    #[serde(default = "default_weight")]
+    weight: u32,
+}
+
+/// The part of an inline upstream this plugin uses. Other upstream fields
+/// are accepted and ignored.
+#[derive(Debug, Deserialize)]
+struct InlineUpstream {
+    /// Nodes: address → weight.
+    #[serde(default)]
+    nodes: HashMap<String, u32>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+struct TrafficSplitInstance {
+    rules: Vec<Rule>,
+    rng: Rng,
+}
+
+struct Rule {
+    /// Empty when the rule has no `match`.
+    matches: Vec<Vec<VarExpr>>,
+    targets: Weighted<Target>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Target {
+    /// The route's own upstream.
+    Default,
+    Upstream(String),
+    Nodes(Weighted<String>),
+}
+
+/// Items with positive weights, in a stable order.
+#[derive(Debug, Clone, PartialEq)]
+struct Weighted<T> {
+    items: Vec<(T, u32)>,
+    total: u64,
+}
+
+impl<T> Weighted<T> {
+    fn new(items: Vec<(T, u32)>) -> Self {
+        let items: Vec<(T, u32)> = items.into_iter().filter(|(_, w)| *w > 0).collect();
+        let total = items.iter().map(|(_, w)| u64::from(*w)).sum();
+        Self { items, total }
+    }
+
+    /// The item at `r` (in `[0, 1)`) of the way through the total weight.
+    fn pick(&self, r: f64) -> Option<&T> {
+        let point = (r.clamp(0.0, 1.0) * self.total as f64) as u64;
+        let mut acc = 0;
+        for (item, weight) in &self.items {
+            acc += u64::from(*weight);
+            if point < acc {
+                return Some(item);
+            }
+        }
+        self.items.last().map(|(item, _)| item)
+    }
+}
+
+impl Plugin for TrafficSplitPlugin {
+    fn name(&self) -> &str {
+        "traffic-split"
+    }
+
+    fn priority(&self) -> i32 {
+        966 // APISIX default
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: TrafficSplitConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("traffic-split config error: {e}"))?;
+        let rules = cfg
+            .rules
+            .into_iter()
+            .map(Rule::compile)
+            .collect::<Result<_, String>>()
+            .map_err(|e| anyhow::anyhow!("traffic-split config error: {e}"))?;
+        Ok(Box::new(TrafficSplitInstance {
+            rules,
+            rng: Arc::clone(&self.rng),
+        }))
+    }
+}
+
+impl Rule {
+    fn compile(cfg: RuleConfig) -> Result<Self, String> {
+        let matches = cfg
+            .matches
+            .iter()
+            .map(|m| VarExpr::compile_all(&m.vars))
+            .collect::<Result<_, _>>()?;
+        let targets = cfg
+            .weighted_upstreams
+            .into_iter()
+            .map(|w| {
+                let target = match (w.upstream, w.upstream_id) {
+                    (Some(_), Some(_)) => {
+                        return Err("set either upstream or upstream_id, not both".to_string());
+                    }
+                    (Some(ups), None) => {
+                        let mut nodes: Vec<(String, u32)> = ups.nodes.into_iter().collect();
+                        nodes.sort();
+                        let nodes = Weighted::new(nodes);
+                        if nodes.items.is_empty() {
+                            return Err("inline upstream needs a node with positive weight".into());
+                        }
+                        Target::Nodes(nodes)
+                    }
+                    (None, Some(id)) => Target::Upstream(id),
+                    (None, None) => Target::Default,
+                };
+                Ok((target, w.weight))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let targets = Weighted::new(targets);
+        if targets.items.is_empty() {
+            return Err("weighted_upstreams needs an entry with positive weight".into());
+        }
+        Ok(Self { matches, targets })
+    }
+
+    fn applies(&self, ctx: &PluginContext) -> bool {
+        self.matches.is_empty()
+            || self.matches.iter().any(|all| {
+                all.iter()
+                    .all(|expr| expr.matches(|name| lookup(ctx, name)))
+            })
+    }
+}
+
+/// Request variables for `match`, named as in the router's `vars`.
+pub(crate) fn lookup<'a>(ctx: &'a PluginContext, name: &str) -> Option<&'a str> {
+    match name {
+        "remote_addr" => Some(&ctx.client_ip),
+        "uri" => Some(ctx.uri.split_once('?').map_or(ctx.uri.as_str(), |(p, _)| p)),
+        "host" => ctx.get_header("host"),
+        "request_method" => Some(&ctx.method),
+        _ => {
+            if let Some(header) = name.strip_prefix("http_") {
+                ctx.get_header(&header.to_ascii_lowercase().replace('_', "-"))
+            } else if let Some(arg) = name.strip_prefix("arg_") {
+                query_arg(ctx.uri.split_once('?')?.1, arg)
+            } else if let Some(cookie) = name.strip_prefix("cookie_") {
+                cookie_value(ctx.get_header("cookie")?, cookie)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+impl PluginInstance for TrafficSplitInstance {
+    fn name(&self) -> &str {
+        "traffic-split"
+    }
+
+    fn priority(&self) -> i32 {
+        966
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        let Some(rule) = self.rules.iter().find(|rule| rule.applies(ctx)) else {
+            return PluginResult::Continue;
+        };
+        match rule.targets.pick((self.rng)()) {
+            Some(Target::Upstream(id)) => ctx.upstream_id = Some(id.clone()),
+            Some(Target::Nodes(nodes)) => {
+                ctx.upstream_addr = nodes.pick((self.rng)()).cloned();
+            }
+            Some(Target::Default) | None => {}
+        }
+        PluginResult::Continue
+    }
+}
+
+// ── Randomness ───────────────────────────────────────────────────────────
+
+thread_local! {
+    static RNG_STATE: Cell<u64> = Cell::new(rng_seed());
+}
+
+fn rng_seed() -> u64 {
+    use std::hash::BuildHasher;
+    std::collections::hash_map::RandomState::new().hash_one(std::thread::current().id()) | 1
+}
+
+/// A uniform float in `[0, 1)` from a per-thread xorshift generator.
+pub(crate) fn random() -> f64 {
+    let x = RNG_STATE.with(|state| {
+        let mut x = state.get();
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+    // Top 53 bits as a uniform float in [0, 1).
+    (x >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// An rng stepping evenly through `[0, 1)` in `steps` draws.
+    fn stepping(steps: u64) -> Rng {
+        let n = AtomicU64::new(0);
+        Arc::new(move || (n.fetch_add(1, Ordering::Relaxed) % steps) as f64 / steps as f64)
+    }
+
+    fn instance(rng: Rng, config: serde_json::Value) -> Box<dyn PluginInstance> {
+        TrafficSplitPlugin::with_rng(rng)
+            .configure(&config)
+            .unwrap()
+    }
+
+    fn make_ctx(uri: &str, headers: &[(&str, &str)]) -> PluginContext {
+        let headers = headers
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        PluginContext::new(
+            "r1".into(),
+            "127.0.0.1".into(),
+            "GET".into(),
+            uri.into(),
+            headers,
+        )
+    }
+
+    /// Where the request goes: `id:<upstream>`, a node address, or
+    /// `default`.
+    fn target(plugin: &dyn PluginInstance, uri: &str, headers: &[(&str, &str)]) -> String {
+        let mut ctx = make_ctx(uri, headers);
+        assert!(matches!(plugin.access(&mut ctx), PluginResult::Continue));
+        match (ctx.upstream_addr, ctx.upstream_id) {
+            (Some(addr), _) => addr,
+            (None, Some(id)) => format!("id:{id}"),
+            (None, None) => "default".into(),
+        }
+    }
+
+    #[test]
+    fn splits_traffic_by_weight() {
+        let plugin = instance(
+            stepping(100),
+            json!({"rules": [{"weighted_upstreams": [
+                {"upstream_id": "canary", "weight": 5},
+                {"weight": 95}
+            ]}]}),
+        );
+        let mut counts: HashMap<String, u32> = HashMap::new();
+        for _ in 0..1000 {
+            *counts.entry(target(plugin.as_ref(), "/", &[])).or_default() += 1;
+        }
+        assert_eq!(counts["id:canary"], 50);
+        assert_eq!(counts["default"], 950);
+    }
+
+    #[test]
+    fn random_picks_follow_weights() {
+        let plugin = TrafficSplitPlugin::new()
+            .configure(&json!({"rules": [{"weighted_upstreams": [
+                {"upstream_id": "b", "weight": 1},
+                {"upstream_id": "a", "weight": 3}
+            ]}]}))
+            .unwrap();
+        let b = (0..4000)
+            .filter(|_| target(plugin.as_ref(), "/", &[]) == "id:b")
+            .count();
+        assert!((800..1200).contains(&b), "got {b} of 4000 to b");
+    }
+
+    #[test]
+    fn inline_upstream_picks_a_node_by_weight() {
+        let plugin = instance(
+            stepping(4),
+            json!({"rules": [{"weighted_upstreams": [{"upstream": {
+                "type": "roundrobin",
+                "nodes": {"10.0.0.1:80": 1, "10.0.0.2:80": 1}
+            }, "weight": 1}]}]}),
+        );
+        // Each request draws twice: once for the entry, once for the node.
+        let picks: Vec<String> = (0..4).map(|_| target(plugin.as_ref(), "/", &[])).collect();
+        assert_eq!(
+            picks,
+            ["10.0.0.1:80", "10.0.0.2:80", "10.0.0.1:80", "10.0.0.2:80"]
+        );
+    }
+
+    #[test]
+    fn match_gates_the_rule() {
+        let plugin = instance(
+            stepping(2),
+            json!({"rules": [
+                {
+                    "match": [
+                        {"vars": [["http_x_canary", "==", "always"]]},
+                        {"vars": [["arg_beta", "==", "1"], ["cookie_team", "~~", "^qa"]]}
+                    ],
+                    "weighted_upstreams": [{"upstream_id": "canary"}]
+                }
+            ]}),
+        );
+        assert_eq!(
+            target(plugin.as_ref(), "/", &[("x-canary", "always")]),
+            "id:canary"
+        );
+        assert_eq!(
+            target(plugin.as_ref(), "/?beta=1", &[("cookie", "team=qa-1")]),
+            "id:canary"
+        );
+        // One condition of the second alternative alone is not enough.
+        assert_eq!(target(plugin.as_ref(), "/?beta=1", &[]), "default");
+        assert_eq!(
+            target(plugin.as_ref(), "/", &[("x-canary", "never")]),
+            "default"
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins_and_unmatched_falls_through() {
+        let plugin = instance(
+            stepping(2),
+            json!({"rules": [
+                {
+                    "match": [{"vars": [["http_x_env", "==", "staging"]]}],
+                    "weighted_upstreams": [{"upstream_id": "staging"}]
+                },
+                {
+                    "match": [{"vars": [["http_x_env", "~~", "^stag|^prod"]]}],
+                    "weighted_upstreams": [{"upstream_id": "other"}]
+                }
+            ]}),
+        );
+        assert_eq!(
+            target(plugin.as_ref(), "/", &[("x-env", "staging")]),
+            "id:staging"
+        );
+        assert_eq!(
+            target(plugin.as_ref(), "/", &[("x-env", "prod")]),
+            "id:other"
+        );
+        assert_eq!(target(plugin.as_ref(), "/", &[]), "default");
+    }
+
+    #[test]
+    fn zero_weight_entries_are_never_picked() {
+        let plugin = instance(
+            stepping(10),
+            json!({"rules": [{"weighted_upstreams": [
+                {"upstream_id": "off", "weight": 0},
+                {"weight": 1}
+            ]}]}),
+        );
+        assert!((0..10).all(|_| target(plugin.as_ref(), "/", &[]) == "default"));
+    }
+
+    #[test]
+    fn rejects_invalid_config() {
+        let plugin = TrafficSplitPlugin::new();
+        for config in [
+            json!({"rules": [{}]}),
+            json!({"rules": [{"weighted_upstreams": []}]}),
+            json!({"rules": [{"weighted_upstreams": [{"weight": 0}]}]}),
+            json!({"rules": [{"weighted_upstreams": [{"upstream": {"nodes": {}}}]}]}),
+            json!({"rules": [{"weighted_upstreams": [
+                {"upstream": {"nodes": {"a:1": 1}}, "upstream_id": "x"}
+            ]}]}),
+            json!({"rules": [{
+                "match": [{"vars": [["http_x", "~~", "("]]}],
+                "weighted_upstreams": [{}]
+            }]}),
+        ] {
+            let err = plugin.configure(&config).err().expect("should be rejected");
+            assert!(err.to_string().starts_with("traffic-split config error"));
+        }
+    }
+}
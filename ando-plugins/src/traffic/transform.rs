@@ -0,0 +1,568 @@
+//! Shared by `request-transformer` and `response-transformer`: templated
+//! values and the header and JSON body operations both plugins apply.
+//!
+//! A template is text with variables in it, `$name` or `${name}`:
+//!
+//! | variable          | value                                          |
+//! |-------------------|------------------------------------------------|
+//! | `$consumer`       | consumer an auth plugin matched                |
+//! | `$route_id`       | matched route                                  |
+//! | `$service_id`     | service of the matched route                   |
+//! | `$client_ip`      | client address                                 |
+//! | `$method`         | request method                                 |
+//! | `$uri`            | request URI as the client sent it              |
+//! | `$path_<name>`    | path parameter `<name>` of the route's URI     |
+//! | `$header_<name>`  | request header; `_` also matches `-`           |
+//! | `$<name>`         | `ctx.vars[<name>]`                             |
+//!
+//! A variable with no value renders as the empty string; `$$` is a
+//! literal `$`.
+
+use super::response_headers_policy::is_header_name;
+use ando_plugin::plugin::{PluginContext, RequestHeaders};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+// ─────────────────────────────────────────────────────────────
+// Templates
+// ─────────────────────────────────────────────────────────────
+
+/// A value with `$variables`, parsed once at configure time.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Var {
+    Consumer,
+    RouteId,
+    ServiceId,
+    ClientIp,
+    Method,
+    Uri,
+    Path(String),
+    Header(String),
+    Ctx(String),
+}
+
+impl Var {
+    fn parse(name: &str) -> Self {
+        match name {
+            "consumer" => Self::Consumer,
+            "route_id" => Self::RouteId,
+            "service_id" => Self::ServiceId,
+            "client_ip" => Self::ClientIp,
+            "method" => Self::Method,
+            "uri" => Self::Uri,
+            _ => {
+                if let Some(param) = name.strip_prefix("path_") {
+                    Self::Path(param.to_string())
+                } else if let Some(header) = name.strip_prefix("header_") {
+                    Self::Header(header.to_ascii_lowercase())
+                } else {
+                    Self::Ctx(name.to_string())
+                }
+            }
+        }
+    }
+
+    fn render(&self, ctx: &PluginContext, out: &mut String) {
+        match self {
+            Self::Consumer => out.push_str(ctx.consumer.as_deref().unwrap_or("")),
+            Self::RouteId => out.push_str(&ctx.route_id),
+            Self::ServiceId => out.push_str(ctx.service_id.as_deref().unwrap_or("")),
+            Self::ClientIp => out.push_str(&ctx.client_ip),
+            Self::Method => out.push_str(&ctx.method),
+            Self::Uri => out.push_str(&ctx.uri),
+            Self::Path(name) => out.push_str(ctx.route_param(name).unwrap_or("")),
+            Self::Header(name) => {
+                let value = ctx
+                    .get_header(name)
+                    .or_else(|| ctx.get_header(&name.replace('_', "-")));
+                out.push_str(value.unwrap_or(""));
+            }
+            Self::Ctx(name) => match ctx.vars.get(name) {
+                None | Some(Value::Null) => {}
+                Some(Value::String(s)) => out.push_str(s),
+                Some(other) => out.push_str(&other.to_string()),
+            },
+        }
+    }
+}
+
+impl Template {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+        while let Some(at) = rest.find('$') {
+            text.push_str(&rest[..at]);
+            rest = &rest[at + 1..];
+            let (name, after) = if let Some(braced) = rest.strip_prefix('{') {
+                let end = braced
+                    .find('}')
+                    .ok_or_else(|| format!("unclosed '${{' in '{source}'"))?;
+                (&braced[..end], &braced[end + 1..])
+            } else if let Some(after) = rest.strip_prefix('$') {
+                text.push('$');
+                rest = after;
+                continue;
+            } else {
+                let end = rest
+                    .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                    .unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            };
+            if name.is_empty() {
+                // A lone `$` stays as it is.
+                text.push('$');
+                continue;
+            }
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(Part::Var(Var::parse(name)));
+            rest = after;
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Self { parts })
+    }
+
+    pub(crate) fn render(&self, ctx: &PluginContext) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Var(var) => var.render(ctx, &mut out),
+            }
+        }
+        out
+    }
+}
+
+// ─────────────────────────────────────────────────────────────
+// Config
+// ─────────────────────────────────────────────────────────────
+
+/// The `remove`, `rename`, `replace` and `add` sections of a transformer
+/// config. Each has `headers` and `json` (top-level body fields).
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct TransformConfig {
+    #[serde(default)]
+    remove: Names,
+    /// Old name to new name.
+    #[serde(default)]
+    rename: Renames,
+    /// Set only where the header or field already exists.
+    #[serde(default)]
+    replace: Values,
+    /// Set only where the header or field does not exist yet.
+    #[serde(default)]
+    add: Values,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Names {
+    #[serde(default)]
+    headers: Vec<String>,
+    #[serde(default)]
+    json: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Renames {
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    #[serde(default)]
+    json: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Values {
+    /// Header templates.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Field values; strings are templates, anything else is set as is.
+    #[serde(default)]
+    json: Map<String, Value>,
+}
+
+// ─────────────────────────────────────────────────────────────
+// Compiled operations
+// ─────────────────────────────────────────────────────────────
+
+/// A JSON field value: rendered into a string, or set as configured.
+#[derive(Debug)]
+enum FieldValue {
+    Template(Template),
+    Literal(Value),
+}
+
+impl FieldValue {
+    fn render(&self, ctx: &PluginContext) -> Value {
+        match self {
+            Self::Template(t) => Value::String(t.render(ctx)),
+            Self::Literal(v) => v.clone(),
+        }
+    }
+}
+
+/// Header and JSON operations, applied in the order remove, rename,
+/// replace, add.
+#[derive(Debug, Default)]
+pub(crate) struct Transform {
+    remove_headers: Vec<String>,
+    rename_headers: Vec<(String, String)>,
+    replace_headers: Vec<(String, Template)>,
+    add_headers: Vec<(String, Template)>,
+    remove_json: Vec<String>,
+    rename_json: Vec<(String, String)>,
+    replace_json: Vec<(String, FieldValue)>,
+    add_json: Vec<(String, FieldValue)>,
+}
+
+impl Transform {
+    pub(crate) fn compile(cfg: TransformConfig) -> Result<Self, String> {
+        let lower = |name: String| -> Result<String, String> {
+            if is_header_name(&name) {
+                Ok(name.to_ascii_lowercase())
+            } else {
+                Err(format!("invalid header name '{name}'"))
+            }
+        };
+        let templates = |headers: BTreeMap<String, String>| {
+            headers
+                .into_iter()
+                .map(|(name, value)| {
+                    if value.bytes().any(|b| b == b'\r' || b == b'\n') {
+                        return Err(format!("value of '{name}' contains a line break"));
+                    }
+                    Ok((lower(name)?, Template::parse(&value)?))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        let fields = |json: Map<String, Value>| {
+            json.into_iter()
+                .map(|(field, value)| {
+                    let value = match value {
+                        Value::String(s) => FieldValue::Template(Template::parse(&s)?),
+                        other => FieldValue::Literal(other),
+                    };
+                    Ok((field, value))
+                })
+                .collect::<Result<Vec<_>, String>>()
+        };
+        Ok(Self {
+            remove_headers: cfg
+                .remove
+                .headers
+                .into_iter()
+                .map(lower)
+                .collect::<Result<_, _>>()?,
+            rename_headers: cfg
+                .rename
+                .headers
+                .into_iter()
+                .map(|(from, to)| Ok((lower(from)?, lower(to)?)))
+                .collect::<Result<_, String>>()?,
+            replace_headers: templates(cfg.replace.headers)?,
+            add_headers: templates(cfg.add.headers)?,
+            remove_json: cfg.remove.json,
+            rename_json: cfg.rename.json.into_iter().collect(),
+            replace_json: fields(cfg.replace.json)?,
+            add_json: fields(cfg.add.json)?,
+        })
+    }
+
+    /// Whether any operation touches the body.
+    pub(crate) fn has_json_ops(&self) -> bool {
+        !self.remove_json.is_empty()
+            || !self.rename_json.is_empty()
+            || !self.replace_json.is_empty()
+            || !self.add_json.is_empty()
+    }
+
+    /// The header operations with their values rendered for this request,
+    /// to apply to whichever headers the plugin edits.
+    pub(crate) fn header_edits(&self, ctx: &PluginContext) -> HeaderEdits<'_> {
+        HeaderEdits {
+            transform: self,
+            replace: render_all(&self.replace_headers, ctx),
+            add: render_all(&self.add_headers, ctx),
+        }
+    }
+
+    /// Apply the JSON operations to `body`. `None` when the body is not a
+    /// JSON object; it is then passed on untouched.
+    pub(crate) fn apply_json(&self, ctx: &PluginContext, body: &[u8]) -> Option<Vec<u8>> {
+        let Ok(Value::Object(mut object)) = serde_json::from_slice::<Value>(body) else {
+            return None;
+        };
+        for field in &self.remove_json {
+            object.remove(field);
+        }
+        for (from, to) in &self.rename_json {
+            if let Some(value) = object.remove(from) {
+                object.insert(to.clone(), value);
+            }
+        }
+        for (field, value) in &self.replace_json {
+            if let Some(slot) = object.get_mut(field) {
+                *slot = value.render(ctx);
+            }
+        }
+        for (field, value) in &self.add_json {
+            if !object.contains_key(field) {
+                object.insert(field.clone(), value.render(ctx));
+            }
+        }
+        serde_json::to_vec(&object).ok()
+    }
+}
+
+fn render_all<'a>(list: &'a [(String, Template)], ctx: &PluginContext) -> Vec<(&'a str, String)> {
+    list.iter()
+        .map(|(name, t)| (name.as_str(), t.render(ctx)))
+        .collect()
+}
+
+/// Header operations of a [`Transform`] with rendered values.
+pub(crate) struct HeaderEdits<'a> {
+    transform: &'a Transform,
+    replace: Vec<(&'a str, String)>,
+    add: Vec<(&'a str, String)>,
+}
+
+impl HeaderEdits<'_> {
+    pub(crate) fn apply(self, headers: &mut impl HeaderList) {
+        for name in &self.transform.remove_headers {
+            headers.remove_all(name);
+        }
+        for (from, to) in &self.transform.rename_headers {
+            for value in headers.remove_all(from) {
+                headers.push(to, value);
+            }
+        }
+        for (name, value) in self.replace {
+            if headers.has(name) {
+                headers.remove_all(name);
+                headers.push(name, value);
+            }
+        }
+        for (name, value) in self.add {
+            if !headers.has(name) {
+                headers.push(name, value);
+            }
+        }
+    }
+}
+
+/// Headers a transformer edits: the request's, or the upstream
+/// response's.
+pub(crate) trait HeaderList {
+    fn has(&self, name: &str) -> bool;
+    /// Remove every value of `name`, returning them in order.
+    fn remove_all(&mut self, name: &str) -> Vec<String>;
+    fn push(&mut self, name: &str, value: String);
+}
+
+impl HeaderList for RequestHeaders {
+    fn has(&self, name: &str) -> bool {
+        self.contains_key(name)
+    }
+
+    fn remove_all(&mut self, name: &str) -> Vec<String> {
+        let values: Vec<String> = self.get_all(name).map(String::from).collect();
+        if !values.is_empty() {
+            self.remove(name);
+        }
+        values
+    }
+
+    fn push(&mut self, name: &str, value: String) {
+        self.append(name, value);
+    }
+}
+
+impl HeaderList for Vec<(String, String)> {
+    fn has(&self, name: &str) -> bool {
+        self.iter().any(|(k, _)| k.eq_ignore_ascii_case(name))
+    }
+
+    fn remove_all(&mut self, name: &str) -> Vec<String> {
+        let mut values = Vec::new();
+        self.retain_mut(|(k, v)| {
+            if !k.eq_ignore_ascii_case(name) {
+                return true;
+            }
+            values.push(std::mem::take(v));
+            false
+        });
+        values
+    }
+
+    fn push(&mut self, name: &str, value: String) {
+        self.push((name.to_string(), value));
+    }
+}
+
+/// Whether a body with these headers is JSON the transformers can edit:
+/// an `application/json` or `+json` media type, not content-encoded.
+pub(crate) fn is_plain_json<'a>(mut headers: impl Iterator<Item = (&'a str, &'a str)>) -> bool {
+    let mut json = false;
+    let mut encoded = false;
+    for (name, value) in headers.by_ref() {
+        if name.eq_ignore_ascii_case("content-type") {
+            let media = value.split(';').next().unwrap_or("").trim();
+            json = media.eq_ignore_ascii_case("application/json")
+                || media.to_ascii_lowercase().ends_with("+json");
+        } else if name.eq_ignore_ascii_case("content-encoding") {
+            encoded = !value.trim().eq_ignore_ascii_case("identity");
+        }
+    }
+    json && !encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn ctx() -> PluginContext {
+        let mut ctx = PluginContext::new(
+            "r1".into(),
+            "10.0.0.7".into(),
+            "POST".into(),
+            "/users/42?full=1".into(),
+            HashMap::new(),
+        );
+        ctx.request_headers = vec![
+            ("X-Request-Id".to_string(), "abc".to_string()),
+            ("x-tenant".to_string(), "acme".to_string()),
+        ]
+        .into();
+        ctx.route_params = vec![("id".into(), "42".into())];
+        ctx.service_id = Some("svc".into());
+        ctx.consumer = Some("alice".into());
+        ctx.vars.insert("region".into(), json!("eu"));
+        ctx.vars.insert("attempt".into(), json!(2));
+        ctx
+    }
+
+    fn render(source: &str) -> String {
+        Template::parse(source).unwrap().render(&ctx())
+    }
+
+    #[test]
+    fn renders_each_variable_source() {
+        assert_eq!(render("$consumer"), "alice");
+        assert_eq!(render("$route_id/$service_id"), "r1/svc");
+        assert_eq!(render("$client_ip"), "10.0.0.7");
+        assert_eq!(render("$method $uri"), "POST /users/42?full=1");
+        assert_eq!(render("user-$path_id"), "user-42");
+        assert_eq!(render("$header_x_request_id"), "abc");
+        assert_eq!(render("${header_X-Tenant}"), "acme");
+        assert_eq!(render("$region#$attempt"), "eu#2");
+    }
+
+    #[test]
+    fn missing_variables_render_empty_and_dollars_stay() {
+        assert_eq!(render("[$path_nope][$header_nope][$nope]"), "[][][]");
+        assert_eq!(render("cost: $$5, $ and $"), "cost: $5, $ and $");
+        assert_eq!(render("${consumer}s"), "alices");
+        assert!(Template::parse("${consumer").is_err());
+    }
+
+    fn transform(cfg: Value) -> Transform {
+        Transform::compile(serde_json::from_value(cfg).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn header_ops_apply_in_order() {
+        let t = transform(json!({
+            "remove": {"headers": ["X-Drop"]},
+            "rename": {"headers": {"x-old": "x-new"}},
+            "replace": {"headers": {"x-keep": "$route_id", "x-absent": "no"}},
+            "add": {"headers": {"x-consumer": "$consumer", "x-keep": "ignored"}}
+        }));
+        let mut headers: Vec<(String, String)> = [
+            ("x-drop", "1"),
+            ("X-Old", "a"),
+            ("x-old", "b"),
+            ("x-keep", "v"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        t.header_edits(&ctx()).apply(&mut headers);
+        let pairs: Vec<(&str, &str)> = headers
+            .iter()
+            .map(|(k, v)| (k.as_str(), v.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("x-new", "a"),
+                ("x-new", "b"),
+                ("x-keep", "r1"),
+                ("x-consumer", "alice")
+            ]
+        );
+    }
+
+    #[test]
+    fn json_ops_edit_top_level_fields() {
+        let t = transform(json!({
+            "remove": {"json": ["password"]},
+            "rename": {"json": {"mail": "email"}},
+            "replace": {"json": {"role": "user", "absent": 1}},
+            "add": {"json": {"by": "$consumer", "tags": ["a"], "role": "ignored"}}
+        }));
+        let body = br#"{"name":"n","password":"p","mail":"m","role":"admin"}"#;
+        let out: Value = serde_json::from_slice(&t.apply_json(&ctx(), body).unwrap()).unwrap();
+        assert_eq!(
+            out,
+            json!({"name": "n", "email": "m", "role": "user", "by": "alice", "tags": ["a"]})
+        );
+    }
+
+    #[test]
+    fn non_object_bodies_are_left_alone() {
+        let t = transform(json!({"add": {"json": {"a": 1}}}));
+        assert!(t.apply_json(&ctx(), b"[1,2]").is_none());
+        assert!(t.apply_json(&ctx(), b"not json").is_none());
+    }
+
+    #[test]
+    fn rejects_bad_header_names_and_values() {
+        let bad = |cfg: Value| Transform::compile(serde_json::from_value(cfg).unwrap()).is_err();
+        assert!(bad(json!({"add": {"headers": {"bad name": "v"}}})));
+        assert!(bad(json!({"add": {"headers": {"x-a": "v\r\nx-b: w"}}})));
+        assert!(bad(json!({"rename": {"headers": {"x-a": ""}}})));
+    }
+
+    #[test]
+    fn recognises_plain_json_bodies() {
+        let is = |headers: &[(&str, &str)]| is_plain_json(headers.iter().copied());
+        assert!(is(&[("Content-Type", "application/json; charset=utf-8")]));
+        assert!(is(&[("content-type", "application/problem+json")]));
+        assert!(!is(&[("content-type", "text/plain")]));
+        assert!(!is(&[]));
+        assert!(!is(&[
+            ("content-type", "application/json"),
+            ("content-encoding", "gzip")
+        ]));
+    }
+}
@@ -0,0 +1,197 @@
+use ando_plugin::plugin::{Phase, Plugin, PluginContext, PluginInstance, PluginResult};
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+
+/// UA restriction plugin — allowlist/denylist of `User-Agent` patterns.
+///
+/// Patterns are case-insensitive regexes compiled at configure time. As in
+/// ip-restriction, the denylist takes priority over the allowlist.
+pub struct UaRestrictionPlugin;
+
+#[derive(Debug, Deserialize)]
+struct UaRestrictionConfig {
+    /// If non-empty, only matching user agents are allowed.
+    #[serde(default)]
+    allowlist: Vec<String>,
+    /// If non-empty, matching user agents are blocked.
+    #[serde(default)]
+    denylist: Vec<String>,
+    /// Let requests without a `User-Agent` through.
+    #[serde(default)]
+    bypass_missing: bool,
+    #[serde(default = "default_message")]
+    message: String,
+}
+
+fn default_message() -> String {
+    "Not allowed".to_string()
+}
+
+struct UaRestrictionInstance {
+    allowlist: Vec<Regex>,
+    denylist: Vec<Regex>,
+    bypass_missing: bool,
+    /// Pre-rendered 403 body.
+    body: Vec<u8>,
+}
+
+fn compile(list: &[String]) -> anyhow::Result<Vec<Regex>> {
+    list.iter()
+        .map(|p| {
+            RegexBuilder::new(p)
+                .case_insensitive(true)
+                .build()
+                .map_err(|e| anyhow::anyhow!("ua-restriction: invalid pattern `{p}`: {e}"))
+        })
+        .collect()
+}
+
+impl Plugin for UaRestrictionPlugin {
+    fn name(&self) -> &str {
+        "ua-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2999
+    }
+
+    fn phases(&self) -> &[Phase] {
+        &[Phase::Access]
+    }
+
+    fn configure(&self, config: &serde_json::Value) -> anyhow::Result<Box<dyn PluginInstance>> {
+        let cfg: UaRestrictionConfig = serde_json::from_value(config.clone())
+            .map_err(|e| anyhow::anyhow!("ua-restriction config error: {e}"))?;
+
+        Ok(Box::new(UaRestrictionInstance {
+            allowlist: compile(&cfg.allowlist)?,
+            denylist: compile(&cfg.denylist)?,
+            bypass_missing: cfg.bypass_missing,
+            body: serde_json::json!({ "error": cfg.message, "status": 403 })
+                .to_string()
+                .into_bytes(),
+        }))
+    }
+}
+
+impl UaRestrictionInstance {
+    fn permits(&self, ua: Option<&str>) -> bool {
+        let Some(ua) = ua.filter(|ua| !ua.is_empty()) else {
+            return self.bypass_missing;
+        };
+        // Denylist takes priority
+        if self.denylist.iter().any(|re| re.is_match(ua)) {
+            return false;
+        }
+        self.allowlist.is_empty() || self.allowlist.iter().any(|re| re.is_match(ua))
+    }
+}
+
+impl PluginInstance for UaRestrictionInstance {
+    fn name(&self) -> &str {
+        "ua-restriction"
+    }
+
+    fn priority(&self) -> i32 {
+        2999
+    }
+
+    fn access(&self, ctx: &mut PluginContext) -> PluginResult {
+        if self.permits(ctx.get_header("user-agent")) {
+            return PluginResult::Continue;
+        }
+        PluginResult::Response {
+            status: 403,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: Some(self.body.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn make_ctx(ua: Option<&str>) -> PluginContext {
+        let mut headers = HashMap::new();
+        if let Some(ua) = ua {
+            headers.insert("user-agent".to_string(), ua.to_string());
+        }
+        PluginContext::new(
+            "r1".into(),
+            "1.2.3.4".into(),
+            "GET".into(),
+            "/".into(),
+            headers,
+        )
+    }
+
+    fn status(config: serde_json::Value, ua: Option<&str>) -> u16 {
+        let inst = UaRestrictionPlugin.configure(&config).unwrap();
+        match inst.access(&mut make_ctx(ua)) {
+            PluginResult::Continue => 200,
+            PluginResult::Response { status, .. } => status,
+        }
+    }
+
+    #[test]
+    fn denylist_matches_case_insensitively() {
+        let config = serde_json::json!({ "denylist": ["scrapy", "^curl/"] });
+        assert_eq!(
+            status(config.clone(), Some("Scrapy/2.11 (+https://scrapy.org)")),
+            403
+        );
+        assert_eq!(status(config.clone(), Some("CURL/8.4.0")), 403);
+        assert_eq!(status(config, Some("Mozilla/5.0")), 200);
+    }
+
+    #[test]
+    fn allowlist_blocks_everything_else() {
+        let config = serde_json::json!({ "allowlist": ["mozilla"] });
+        assert_eq!(status(config.clone(), Some("Mozilla/5.0 (X11)")), 200);
+        assert_eq!(status(config, Some("python-requests/2.31")), 403);
+    }
+
+    #[test]
+    fn denylist_takes_priority_over_allowlist() {
+        let config = serde_json::json!({
+            "allowlist": ["mozilla"],
+            "denylist": ["headless"]
+        });
+        assert_eq!(status(config.clone(), Some("Mozilla/5.0 Chrome")), 200);
+        assert_eq!(status(config, Some("Mozilla/5.0 HeadlessChrome")), 403);
+    }
+
+    #[test]
+    fn missing_header_depends_on_bypass_missing() {
+        let strict = serde_json::json!({ "denylist": ["bot"] });
+        assert_eq!(status(strict.clone(), None), 403);
+        assert_eq!(status(strict, Some("")), 403);
+        let lenient = serde_json::json!({ "allowlist": ["mozilla"], "bypass_missing": true });
+        assert_eq!(status(lenient, None), 200);
+    }
+
+    #[test]
+    fn rejection_uses_configured_message() {
+        let inst = UaRestrictionPlugin
+            .configure(&serde_json::json!({ "denylist": ["bot"], "message": "No bots" }))
+            .unwrap();
+        match inst.access(&mut make_ctx(Some("Googlebot"))) {
+            PluginResult::Response { status, body, .. } => {
+                assert_eq!(status, 403);
+                assert_eq!(body.unwrap(), br#"{"error":"No bots","status":403}"#);
+            }
+            PluginResult::Continue => panic!("expected 403"),
+        }
+    }
+
+    #[test]
+    fn configure_rejects_invalid_regex() {
+        let err = UaRestrictionPlugin
+            .configure(&serde_json::json!({ "denylist": ["(unclosed"] }))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("(unclosed"));
+    }
+}
@@ -0,0 +1,241 @@
+//! Incremental decoder for `Transfer-Encoding: chunked` message bodies.
+//!
+//! v2 design: The decoder is a byte-level state machine fed straight from
+//! the connection's read buffers, so chunk boundaries may fall anywhere
+//! across TCP segments. It can either just track framing (to forward the
+//! raw bytes and know where the message ends) or also collect the decoded
+//! payload. Trailer fields are consumed and never decoded into the payload.
+
+/// Malformed chunked framing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidChunk;
+
+impl std::fmt::Display for InvalidChunk {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid chunked encoding")
+    }
+}
+
+impl std::error::Error for InvalidChunk {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Reading the hex chunk size; `digits` counts digits seen so far.
+    Size {
+        size: usize,
+        digits: u8,
+    },
+    /// Skipping a chunk extension up to the CR.
+    Extension {
+        size: usize,
+    },
+    /// Expecting the LF that ends the size line.
+    SizeLf {
+        size: usize,
+    },
+    Data {
+        remaining: usize,
+    },
+    DataCr,
+    DataLf,
+    /// Reading trailer fields after the last chunk; `line_len` is the
+    /// length of the current line so far.
+    Trailer {
+        line_len: usize,
+    },
+    TrailerLf {
+        line_len: usize,
+    },
+    Done,
+}
+
+/// Streaming chunked-body decoder.
+#[derive(Debug, Clone)]
+pub struct ChunkedDecoder {
+    state: State,
+}
+
+impl Default for ChunkedDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        Self {
+            state: State::Size { size: 0, digits: 0 },
+        }
+    }
+
+    /// Whether the terminal chunk and trailer section have been consumed.
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    /// Consume framing from `input`, appending chunk data to `out` when
+    /// given. Returns how many bytes belong to this message; anything
+    /// after the end of the message is left unconsumed.
+    pub fn feed(
+        &mut self,
+        input: &[u8],
+        mut out: Option<&mut Vec<u8>>,
+    ) -> Result<usize, InvalidChunk> {
+        let mut i = 0;
+        while i < input.len() {
+            let b = input[i];
+            self.state = match self.state {
+                State::Size { size, digits } => match hex_value(b) {
+                    Some(v) => {
+                        // 15 hex digits keep the size well inside usize.
+                        if digits >= 15 {
+                            return Err(InvalidChunk);
+                        }
+                        State::Size {
+                            size: size << 4 | v,
+                            digits: digits + 1,
+                        }
+                    }
+                    None if digits == 0 => return Err(InvalidChunk),
+                    None => match b {
+                        b'\r' => State::SizeLf { size },
+                        b';' | b' ' | b'\t' => State::Extension { size },
+                        _ => return Err(InvalidChunk),
+                    },
+                },
+                State::Extension { size } => match b {
+                    b'\r' => State::SizeLf { size },
+                    b'\n' => return Err(InvalidChunk),
+                    _ => State::Extension { size },
+                },
+                State::SizeLf { size } => match b {
+                    b'\n' if size == 0 => State::Trailer { line_len: 0 },
+                    b'\n' => State::Data { remaining: size },
+                    _ => return Err(InvalidChunk),
+                },
+                State::Data { remaining } => {
+                    let take = remaining.min(input.len() - i);
+                    if let Some(out) = out.as_deref_mut() {
+                        out.extend_from_slice(&input[i..i + take]);
+                    }
+                    i += take;
+                    self.state = match remaining - take {
+                        0 => State::DataCr,
+                        remaining => State::Data { remaining },
+                    };
+                    continue;
+                }
+                State::DataCr if b == b'\r' => State::DataLf,
+                State::DataLf if b == b'\n' => State::Size { size: 0, digits: 0 },
+                State::DataCr | State::DataLf => return Err(InvalidChunk),
+                State::Trailer { line_len } => match b {
+                    b'\r' => State::TrailerLf { line_len },
+                    b'\n' => return Err(InvalidChunk),
+                    _ => State::Trailer {
+                        line_len: line_len + 1,
+                    },
+                },
+                State::TrailerLf { line_len } => match b {
+                    b'\n' if line_len == 0 => State::Done,
+                    b'\n' => State::Trailer { line_len: 0 },
+                    _ => return Err(InvalidChunk),
+                },
+                State::Done => return Ok(i),
+            };
+            i += 1;
+        }
+        Ok(i)
+    }
+}
+
+fn hex_value(b: u8) -> Option<usize> {
+    (b as char).to_digit(16).map(|v| v as usize)
+}
+
+/// Whether a `transfer-encoding` value ends in `chunked`, i.e. the body
+/// is chunk-framed.
+pub fn is_chunked(transfer_encoding: &str) -> bool {
+    transfer_encoding
+        .rsplit(',')
+        .next()
+        .is_some_and(|coding| coding.trim().eq_ignore_ascii_case("chunked"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BODY: &[u8] = b"5\r\nhello\r\n7;name=value\r\n, world\r\n0\r\nx-checksum: abc\r\n\r\n";
+
+    fn decode(input: &[u8]) -> Result<(Vec<u8>, usize, bool), InvalidChunk> {
+        let mut decoder = ChunkedDecoder::new();
+        let mut out = Vec::new();
+        let used = decoder.feed(input, Some(&mut out))?;
+        Ok((out, used, decoder.is_done()))
+    }
+
+    #[test]
+    fn decodes_chunks_extensions_and_trailers() {
+        let (out, used, done) = decode(BODY).unwrap();
+        assert_eq!(out, b"hello, world");
+        assert_eq!(used, BODY.len());
+        assert!(done);
+    }
+
+    #[test]
+    fn decodes_across_every_split_point() {
+        for split in 0..=BODY.len() {
+            let mut decoder = ChunkedDecoder::new();
+            let mut out = Vec::new();
+            let a = decoder.feed(&BODY[..split], Some(&mut out)).unwrap();
+            assert_eq!(a, split);
+            let b = decoder.feed(&BODY[split..], Some(&mut out)).unwrap();
+            assert_eq!(a + b, BODY.len());
+            assert!(decoder.is_done(), "split at {split}");
+            assert_eq!(out, b"hello, world", "split at {split}");
+        }
+    }
+
+    #[test]
+    fn stops_at_end_of_message() {
+        let mut input = b"3\r\nabc\r\n0\r\n\r\n".to_vec();
+        let end = input.len();
+        input.extend_from_slice(b"GET / HTTP/1.1\r\n");
+        let (out, used, done) = decode(&input).unwrap();
+        assert_eq!(out, b"abc");
+        assert_eq!(used, end);
+        assert!(done);
+    }
+
+    #[test]
+    fn framing_only_without_output() {
+        let mut decoder = ChunkedDecoder::new();
+        assert_eq!(decoder.feed(b"A\r\n0123456789\r\n", None), Ok(15));
+        assert!(!decoder.is_done());
+        assert_eq!(decoder.feed(b"0\r\n\r\n", None), Ok(5));
+        assert!(decoder.is_done());
+    }
+
+    #[test]
+    fn rejects_malformed_framing() {
+        for input in [
+            &b"\r\n"[..],
+            b"zz\r\n",
+            b"3\nabc\r\n",
+            b"3\r\nabcd\r\n",
+            b"3\r\nabc\n",
+            b"ffffffffffffffff\r\n",
+            b"0\r\nbad\ntrailer",
+        ] {
+            assert_eq!(decode(input), Err(InvalidChunk), "{input:?}");
+        }
+    }
+
+    #[test]
+    fn is_chunked_checks_final_coding() {
+        assert!(is_chunked("chunked"));
+        assert!(is_chunked("gzip, Chunked"));
+        assert!(!is_chunked("chunked, gzip"));
+        assert!(!is_chunked("identity"));
+    }
+}
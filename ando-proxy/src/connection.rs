@@ -1,11 +1,49 @@
+use crate::chunked::{ChunkedDecoder, is_chunked};
+use crate::hop_by_hop::{BodyFraming, HopByHop, content_length, lists_close, request_framing};
 use crate::proxy::{
-    ConnPool, ProxyWorker, RESP_502, RequestResult, build_response, build_upstream_request,
+    ConnPool, Exchange, MAX_HEADERS, ProxyWorker, RESP_502, RESP_504, Rejection, RequestResult,
+    ResponsePlugins, UpstreamStream, UpstreamTarget, UpstreamTimeouts, build_response,
+    build_upgrade_request, build_upstream_request, build_upstream_request_head,
+    split_absolute_form,
 };
-use monoio::io::{AsyncReadRent, AsyncWriteRentExt};
+use crate::proxy_protocol;
+use crate::tls::{ClientCert, TlsServer, UpstreamTls};
+use ando_core::config::{HTTP_LISTENER, HTTPS_LISTENER};
+use ando_core::upstream::{PassiveHealthCheck, RetryOn};
+use ando_store::health::UpstreamFailure;
+use monoio::buf::IoBufMut;
+use monoio::io::{AsyncReadRent, AsyncWriteRent, AsyncWriteRentExt, Split, Splitable};
 use monoio::net::TcpStream;
 use std::cell::RefCell;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::mem::MaybeUninit;
 use std::net::SocketAddr;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const RESP_400: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+const RESP_408: &[u8] =
+    b"HTTP/1.1 408 Request Timeout\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+const RESP_413: &[u8] =
+    b"HTTP/1.1 413 Payload Too Large\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+const RESP_431: &[u8] = b"HTTP/1.1 431 Request Header Fields Too Large\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
+const RESP_100_CONTINUE: &[u8] = b"HTTP/1.1 100 Continue\r\n\r\n";
+
+/// Content-length bodies up to this size are read in full before routing,
+/// so plugins see the whole body. Larger ones are streamed to the upstream
+/// after the head and plugins only see the bytes that arrived with it.
+const MAX_BUFFERED_REQUEST_BODY: usize = 1024 * 1024;
+
+/// Largest decoded chunked request body buffered before forwarding.
+const MAX_CHUNKED_REQUEST_BODY: usize = 8 * 1024 * 1024;
+
+/// After refusing a request whose body is still arriving, read and drop
+/// up to this much of it, for up to `LINGER_TIMEOUT`, before closing.
+const LINGER_BYTES: usize = 1024 * 1024;
+const LINGER_TIMEOUT: Duration = Duration::from_secs(1);
 
 /// Resolve an `addr` string (e.g. `"localhost:3001"`) to a list of `SocketAddr`s.
 ///
@@ -15,13 +53,14 @@ use std::rc::Rc;
 /// kernel AIO interface does not support `getaddrinfo`.  The blocking call is
 /// acceptable here because it only runs when the connection pool is empty
 /// (startup, first request, or after upstream failure) — it is NOT on the
-/// steady-state hot path.
+/// steady-state hot path.  Once the DNS refresher has resolved a hostname
+/// node, balancers hand out its addresses and this takes the fast path.
 ///
 /// Returns candidates sorted IPv4-first, because on macOS `localhost` resolves
 /// to both `::1` (IPv6) and `127.0.0.1` (IPv4), and `.next()` often returns
 /// `::1` first.  Most upstream servers listen on IPv4-only, so we try IPv4
 /// first to avoid spurious "Connection refused" on the IPv6 address.
-fn resolve_addrs(addr: &str) -> Vec<SocketAddr> {
+pub(crate) fn resolve_addrs(addr: &str) -> Vec<SocketAddr> {
     // Fast path: already an IP:port literal
     if let Ok(sa) = addr.parse::<SocketAddr>() {
         return vec![sa];
@@ -39,9 +78,228 @@ fn resolve_addrs(addr: &str) -> Vec<SocketAddr> {
     v4
 }
 
+/// Open a new connection to `addr`. It starts with `preamble` (a PROXY
+/// protocol header), then a TLS handshake for `https` upstreams.
+async fn new_upstream_conn(
+    addr: &str,
+    tls: Option<&UpstreamTls>,
+    preamble: Option<Vec<u8>>,
+) -> Option<UpstreamStream> {
+    let mut tcp = connect_tcp(addr).await?;
+    if let Some(preamble) = preamble
+        && let (Err(e), _) = tcp.write_all(preamble).await
+    {
+        tracing::warn!(addr = %addr, error = %e, "Upstream PROXY protocol header failed");
+        return None;
+    }
+    let Some(tls) = tls else {
+        return Some(UpstreamStream::Plain(tcp));
+    };
+    match UpstreamStream::handshake(tcp, tls).await {
+        Ok(stream) => Some(stream),
+        Err(e) => {
+            tracing::warn!(addr = %addr, sni = %tls.sni, error = %e, "Upstream TLS handshake failed");
+            None
+        }
+    }
+}
+
+/// A new connection to `target` for a request from `client`, within
+/// `limit`. Fails with `TimedOut` when the deadline passes first.
+pub(crate) async fn connect_upstream(
+    target: &UpstreamTarget,
+    client: &ClientAddrs,
+    limit: Duration,
+) -> std::io::Result<UpstreamStream> {
+    let preamble = target
+        .proxy_protocol
+        .map(|version| proxy_protocol::encode(version, client.peer, client.local));
+    let tls = target.tls.as_ref();
+    match within(limit, async {
+        Ok(new_upstream_conn(&target.addr, tls, preamble).await)
+    })
+    .await?
+    {
+        Some(stream) => Ok(stream),
+        None => Err(ErrorKind::ConnectionRefused.into()),
+    }
+}
+
+/// Run upstream I/O with a deadline, failing with `TimedOut` when it passes.
+async fn within<T>(
+    limit: Duration,
+    io: impl Future<Output = std::io::Result<T>>,
+) -> std::io::Result<T> {
+    monoio::time::timeout(limit, io)
+        .await
+        .unwrap_or_else(|_| Err(ErrorKind::TimedOut.into()))
+}
+
+/// Read from the upstream with a deadline. On expiry the buffer was lost
+/// with the cancelled read, so a fresh one of the same size comes back.
+pub(crate) async fn read_upstream(
+    upstream: &mut UpstreamStream,
+    buf: Vec<u8>,
+    limit: Duration,
+) -> (std::io::Result<usize>, Vec<u8>) {
+    let len = buf.len();
+    match monoio::time::timeout(limit, upstream.read(buf)).await {
+        Ok(done) => done,
+        Err(_) => (Err(ErrorKind::TimedOut.into()), vec![0u8; len]),
+    }
+}
+
+/// Write all of `data` to the upstream with a deadline.
+async fn write_upstream(
+    upstream: &mut UpstreamStream,
+    data: Vec<u8>,
+    limit: Duration,
+) -> std::io::Result<()> {
+    within(limit, async { upstream.write_all(data).await.0.map(drop) }).await
+}
+
+/// Why an upstream attempt produced no response.
+#[derive(Debug)]
+pub(crate) enum Failure {
+    /// No connection could be opened; nothing reached the node.
+    Connect(std::io::Error),
+    /// The connection failed while sending or awaiting the response.
+    Exchange(std::io::Error),
+}
+
+impl Failure {
+    pub(crate) fn error(&self) -> &std::io::Error {
+        match self {
+            Failure::Connect(e) | Failure::Exchange(e) => e,
+        }
+    }
+
+    /// The `kind` this failure is counted and logged under; see
+    /// [`Exchange::upstream_failed`].
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            Failure::Connect(e) if e.kind() == ErrorKind::TimedOut => "connect_timeout",
+            Failure::Connect(_) => "connect",
+            Failure::Exchange(e) => exchange_error_kind(e),
+        }
+    }
+}
+
+/// The `kind` of an I/O error once a request was sent upstream.
+pub(crate) fn exchange_error_kind(e: &std::io::Error) -> &'static str {
+    if e.kind() == ErrorKind::TimedOut {
+        "timeout"
+    } else {
+        "exchange"
+    }
+}
+
+/// Note on `exchange`, when it is still to be recorded, that the upstream
+/// failed with `kind`.
+pub(crate) fn upstream_failed(exchange: &mut Option<Exchange>, kind: &'static str) {
+    if let Some(exchange) = exchange {
+        exchange.upstream_failed(kind);
+    }
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Failure::Connect(e) => write!(f, "connect: {e}"),
+            Failure::Exchange(e) => write!(f, "exchange: {e}"),
+        }
+    }
+}
+
+/// Send `request` from `client` to `target` on a pooled connection, or a
+/// new one. A pooled connection that fails the write is assumed stale and
+/// replaced once.
+pub(crate) async fn send_request(
+    conn_pool: &Rc<RefCell<ConnPool>>,
+    pool_key: &str,
+    target: &UpstreamTarget,
+    client: &ClientAddrs,
+    request: &[u8],
+    timeouts: UpstreamTimeouts,
+) -> Result<UpstreamStream, Failure> {
+    let pooled = conn_pool.borrow_mut().take(pool_key);
+    let reused = pooled.is_some();
+    let mut upstream = match pooled {
+        Some(upstream) => upstream,
+        None => connect_upstream(target, client, timeouts.connect)
+            .await
+            .map_err(Failure::Connect)?,
+    };
+    match write_upstream(&mut upstream, request.to_vec(), timeouts.send).await {
+        Ok(()) => Ok(upstream),
+        Err(e) if !reused || e.kind() == ErrorKind::TimedOut => Err(Failure::Exchange(e)),
+        Err(_) => {
+            let mut fresh = connect_upstream(target, client, timeouts.connect)
+                .await
+                .map_err(Failure::Connect)?;
+            write_upstream(&mut fresh, request.to_vec(), timeouts.send)
+                .await
+                .map_err(Failure::Exchange)?;
+            Ok(fresh)
+        }
+    }
+}
+
+/// Status code of a response whose head is complete in `buf`.
+fn response_status(buf: &[u8]) -> Option<u16> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut resp = httparse::Response::new(&mut headers);
+    match resp.parse(buf) {
+        Ok(httparse::Status::Complete(_)) => resp.code,
+        _ => None,
+    }
+}
+
+/// Passive health outcome of a response with `status`.
+pub(crate) fn status_failure(
+    passive: &Option<Arc<PassiveHealthCheck>>,
+    status: u16,
+) -> Option<UpstreamFailure> {
+    passive
+        .as_ref()?
+        .unhealthy
+        .http_statuses
+        .contains(&status)
+        .then_some(UpstreamFailure::Http)
+}
+
+/// The status and canned response for a failed upstream exchange.
+pub(crate) fn upstream_error(e: &std::io::Error) -> (u16, &'static [u8]) {
+    if e.kind() == ErrorKind::TimedOut {
+        (504, RESP_504)
+    } else {
+        (502, RESP_502)
+    }
+}
+
+/// The response for a gateway error `status` of the exchange: its error
+/// page, else `fallback`. Call before the exchange is recorded.
+pub(crate) fn error_response(
+    exchange: &Option<Exchange>,
+    status: u16,
+    fallback: &'static [u8],
+) -> Vec<u8> {
+    exchange
+        .as_ref()
+        .and_then(|exchange| exchange.error_response(status))
+        .unwrap_or_else(|| fallback.to_vec())
+}
+
+/// Whether a `content-type` value names Server-Sent Events.
+pub(crate) fn is_event_stream(content_type: &[u8]) -> bool {
+    content_type
+        .get(..17)
+        .is_some_and(|t| t.eq_ignore_ascii_case(b"text/event-stream"))
+}
+
 /// Open a new TCP connection to `addr`, trying all resolved addresses
 /// (IPv4-first) and returning the first that succeeds.
-async fn new_upstream_conn(addr: &str) -> Option<TcpStream> {
+async fn connect_tcp(addr: &str) -> Option<TcpStream> {
     let candidates = resolve_addrs(addr);
     if candidates.is_empty() {
         tracing::warn!(addr = %addr, "Upstream address resolve failed");
@@ -63,6 +321,337 @@ async fn new_upstream_conn(addr: &str) -> Option<TcpStream> {
     None
 }
 
+/// Report an upstream exchange outcome for passive health checking.
+/// No-op unless the upstream has `checks.passive` configured.
+#[inline]
+pub(crate) fn report_upstream(
+    proxy: &Rc<RefCell<ProxyWorker>>,
+    addr: &str,
+    passive: &Option<Arc<PassiveHealthCheck>>,
+    failure: Option<UpstreamFailure>,
+) {
+    if let Some(cfg) = passive {
+        proxy.borrow().record_upstream_outcome(addr, cfg, failure);
+    }
+}
+
+/// Upstream response headers to keep when re-framing a filtered body.
+/// Framing and connection headers are rewritten by `build_response`.
+fn forwarded_headers(headers: &[httparse::Header<'_>]) -> Vec<(String, String)> {
+    let hop = HopByHop::of_response(headers);
+    headers
+        .iter()
+        .take_while(|h| !h.name.is_empty())
+        .filter(|h| !hop.drops(h.name) && !h.name.eq_ignore_ascii_case("content-length"))
+        .map(|h| {
+            (
+                h.name.to_ascii_lowercase(),
+                String::from_utf8_lossy(h.value).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// Write the head of a relayed upstream response: its status line and
+/// end-to-end headers, a single `content-length` when it has one, the
+/// plugins' `extra` headers and this connection's own `connection`.
+/// `filtered` replaces the end-to-end headers once header-filter plugins
+/// rewrote them; the upstream's framing headers are kept either way.
+fn relayed_head(
+    buf: &mut Vec<u8>,
+    resp: &httparse::Response<'_, '_>,
+    hop: &HopByHop<'_>,
+    filtered: Option<&[(String, String)]>,
+    content_length: Option<usize>,
+    extra: &[(String, String)],
+    keep_alive: bool,
+) {
+    let mut itoa_buf = itoa::Buffer::new();
+    buf.extend_from_slice(b"HTTP/1.1 ");
+    buf.extend_from_slice(itoa_buf.format(resp.code.unwrap_or(502)).as_bytes());
+    buf.push(b' ');
+    buf.extend_from_slice(resp.reason.unwrap_or("").as_bytes());
+    buf.extend_from_slice(b"\r\n");
+    for h in resp.headers.iter().take_while(|h| !h.name.is_empty()) {
+        if hop.drops_from_response(h.name)
+            || h.name.eq_ignore_ascii_case("content-length")
+            || (filtered.is_some() && !hop.drops(h.name))
+        {
+            continue;
+        }
+        buf.extend_from_slice(h.name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(h.value);
+        buf.extend_from_slice(b"\r\n");
+    }
+    for (name, value) in filtered.into_iter().flatten() {
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    if let Some(length) = content_length {
+        buf.extend_from_slice(b"content-length: ");
+        buf.extend_from_slice(itoa_buf.format(length).as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    for (name, value) in extra {
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    buf.extend_from_slice(if keep_alive {
+        b"connection: keep-alive\r\n\r\n"
+    } else {
+        b"connection: close\r\n\r\n"
+    });
+}
+
+/// Read from `stream` with a deadline, failing with `TimedOut` when it
+/// passes. The buffer only comes back from a successful read; callers
+/// give up on the connection otherwise.
+pub(crate) async fn read_within<R: AsyncReadRent, T: IoBufMut>(
+    stream: &mut R,
+    buf: T,
+    limit: Duration,
+) -> std::io::Result<(usize, T)> {
+    match monoio::time::timeout(limit, stream.read(buf)).await {
+        Ok((Ok(n), buf)) => Ok((n, buf)),
+        Ok((Err(e), _)) => Err(e),
+        Err(_) => Err(ErrorKind::TimedOut.into()),
+    }
+}
+
+/// Read a `content-length` body of `len` bytes, starting with the bytes
+/// that arrived alongside the headers. Each read waits at most `limit`.
+async fn read_body<R: AsyncReadRent>(
+    stream: &mut R,
+    first: &[u8],
+    len: usize,
+    limit: Duration,
+) -> std::io::Result<Vec<u8>> {
+    let mut body = Vec::with_capacity(len);
+    body.extend_from_slice(&first[..first.len().min(len)]);
+    while body.len() < len {
+        let chunk = vec![0u8; (len - body.len()).min(65536)];
+        match read_within(stream, chunk, limit).await? {
+            (0, _) => return Err(ErrorKind::UnexpectedEof.into()),
+            (n, chunk) => body.extend_from_slice(&chunk[..n]),
+        }
+    }
+    Ok(body)
+}
+
+/// Read and decode a chunked request body, starting with the bytes that
+/// arrived alongside the headers. On failure returns the response to send
+/// before closing the connection.
+async fn read_chunked_request<S: AsyncReadRent>(
+    client: &mut S,
+    first: &[u8],
+    max: usize,
+    limit: Duration,
+) -> Result<Vec<u8>, &'static [u8]> {
+    let mut decoder = ChunkedDecoder::new();
+    let mut body = Vec::new();
+    decoder.feed(first, Some(&mut body)).map_err(|_| RESP_400)?;
+    let mut buf = vec![0u8; 8192];
+    while !decoder.is_done() {
+        if body.len() > max {
+            return Err(RESP_413);
+        }
+        match read_within(client, buf, limit).await {
+            Err(e) if e.kind() == ErrorKind::TimedOut => return Err(RESP_408),
+            Ok((0, _)) | Err(_) => return Err(RESP_400),
+            Ok((n, returned)) => {
+                buf = returned;
+                decoder
+                    .feed(&buf[..n], Some(&mut body))
+                    .map_err(|_| RESP_400)?;
+            }
+        }
+    }
+    if body.len() > max {
+        return Err(RESP_413);
+    }
+    Ok(body)
+}
+
+/// Send `resp` and close the connection. What the client is still sending
+/// is read and dropped for a while first: closing with unread data resets
+/// the connection, and the client may then never see the response.
+async fn reject<S: AsyncReadRent + AsyncWriteRent>(
+    client: &mut S,
+    resp: &[u8],
+) -> anyhow::Result<()> {
+    let (res, _) = client.write_all(resp.to_vec()).await;
+    res?;
+    let _ = client.shutdown().await;
+    let deadline = Instant::now() + LINGER_TIMEOUT;
+    let mut drained = 0;
+    let mut buf = vec![0u8; 16384];
+    while drained < LINGER_BYTES {
+        let left = deadline.saturating_duration_since(Instant::now());
+        match monoio::time::timeout(left, client.read(buf)).await {
+            Ok((Ok(n), returned)) if n > 0 => {
+                drained += n;
+                buf = returned;
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
+
+/// Why a streamed request body could not be relayed.
+enum RelayError {
+    /// The client sent nothing for a whole read timeout.
+    ClientTimeout,
+    /// Either side closed or failed.
+    Broken,
+}
+
+/// Copy `remaining` request body bytes from the client to the upstream.
+/// Each client read waits at most `read_limit`, each upstream write at
+/// most `write_limit`.
+async fn relay_request_body<S: AsyncReadRent>(
+    client: &mut S,
+    upstream: &mut UpstreamStream,
+    mut remaining: usize,
+    read_limit: Duration,
+    write_limit: Duration,
+) -> Result<(), RelayError> {
+    let mut buf = vec![0u8; remaining.min(65536)];
+    while remaining > 0 {
+        let want = remaining.min(buf.capacity());
+        let n = match read_within(client, buf.slice_mut(..want), read_limit).await {
+            Ok((0, _)) => return Err(RelayError::Broken),
+            Ok((n, slice)) => {
+                buf = slice.into_inner();
+                n
+            }
+            Err(e) if e.kind() == ErrorKind::TimedOut => return Err(RelayError::ClientTimeout),
+            Err(_) => return Err(RelayError::Broken),
+        };
+        remaining -= n;
+        let Ok(Ok(returned)) = monoio::time::timeout(write_limit, async {
+            let (res, returned) = upstream.write_all(buf).await;
+            res.map(|_| returned)
+        })
+        .await
+        else {
+            return Err(RelayError::Broken);
+        };
+        buf = returned;
+    }
+    Ok(())
+}
+
+/// Counts a client connection as open on its worker while alive.
+struct OpenConnection(Rc<RefCell<ProxyWorker>>);
+
+impl OpenConnection {
+    fn new(proxy: &Rc<RefCell<ProxyWorker>>) -> Self {
+        proxy.borrow_mut().connection_opened();
+        Self(Rc::clone(proxy))
+    }
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.0.borrow_mut().connection_closed();
+    }
+}
+
+/// Counts a request in `ProxyWorker::in_flight` for as long as it lives,
+/// so a draining worker waits for it.
+pub(crate) struct InFlight(Rc<RefCell<ProxyWorker>>);
+
+impl InFlight {
+    pub(crate) fn new(proxy: &Rc<RefCell<ProxyWorker>>) -> Self {
+        proxy.borrow_mut().request_started();
+        Self(Rc::clone(proxy))
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.borrow_mut().request_finished();
+    }
+}
+
+/// Relay bytes both ways between an upgraded client connection and its
+/// upstream until either side closes.
+async fn tunnel<S>(client: S, upstream: UpstreamStream)
+where
+    S: AsyncReadRent + AsyncWriteRent + Split,
+{
+    let (mut client_read, mut client_write) = client.into_split();
+    let (mut upstream_read, mut upstream_write) = upstream.into_split();
+    monoio::select! {
+        _ = monoio::io::copy(&mut client_read, &mut upstream_write) => {}
+        _ = monoio::io::copy(&mut upstream_read, &mut client_write) => {}
+    }
+}
+
+/// Record a finished exchange in the worker's status counts and the
+/// request metrics, end its span and write its access log entry.
+#[inline]
+pub(crate) fn record_exchange(
+    proxy: &Rc<RefCell<ProxyWorker>>,
+    exchange: &mut Option<Exchange>,
+    method: &str,
+    status: u16,
+) {
+    if let Some(exchange) = exchange.take() {
+        proxy.borrow_mut().record_exchange(exchange, method, status);
+    }
+}
+
+/// Status code of a pre-built `HTTP/1.1 NNN ...` response.
+pub(crate) fn static_status(resp: &[u8]) -> u16 {
+    resp.get(9..12)
+        .and_then(|code| std::str::from_utf8(code).ok())
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Finish a proxied exchange: run the log phase, if the route has log
+/// plugins, and record the exchange.
+pub(crate) fn finish_exchange(
+    proxy: &Rc<RefCell<ProxyWorker>>,
+    exchange: &mut Option<Exchange>,
+    method: &str,
+    plugins: &mut Option<Box<ResponsePlugins>>,
+    status: u16,
+) {
+    if let Some(plugins) = plugins {
+        plugins.log(status);
+    }
+    record_exchange(proxy, exchange, method, status);
+}
+
+/// Addresses of a client connection: the client's, and the one it
+/// connected to. Behind a PROXY protocol load balancer, the ones its
+/// header names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientAddrs {
+    pub peer: SocketAddr,
+    pub local: SocketAddr,
+}
+
+impl ClientAddrs {
+    /// Addresses of `stream`, accepted from `peer`.
+    pub fn of(stream: &TcpStream, peer: SocketAddr) -> Self {
+        Self {
+            peer,
+            local: stream
+                .local_addr()
+                .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 0))),
+        }
+    }
+}
+
 /// Handle a single client connection (HTTP/1.1 with keepalive).
 ///
 /// Shares ProxyWorker and ConnPool with all other connections
@@ -75,42 +664,160 @@ async fn new_upstream_conn(addr: &str) -> Option<TcpStream> {
 ///   - Connection pool with stale-retry
 ///   - Upstream response streaming for large bodies
 pub async fn handle_connection(
-    mut client: TcpStream,
+    client: TcpStream,
+    peer_addr: SocketAddr,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) -> anyhow::Result<()> {
+    let addrs = ClientAddrs::of(&client, peer_addr);
+    handle_connection_on(Rc::from(HTTP_LISTENER), client, addrs, proxy, conn_pool).await
+}
+
+/// `handle_connection` for a connection accepted on the listener named
+/// `listener`: routes scoped to other listeners are not matched.
+pub async fn handle_connection_on(
+    listener: Rc<str>,
+    client: TcpStream,
+    addrs: ClientAddrs,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) -> anyhow::Result<()> {
+    let _open = OpenConnection::new(&proxy);
+    serve_connection(client, addrs, "http", listener, None, proxy, conn_pool).await
+}
+
+/// Handle a client connection on the HTTPS listener: complete the TLS
+/// handshake, then serve HTTP/1.1 over the decrypted stream exactly like
+/// `handle_connection`.
+pub async fn handle_tls_connection(
+    client: TcpStream,
     peer_addr: SocketAddr,
+    tls: Arc<TlsServer>,
     proxy: Rc<RefCell<ProxyWorker>>,
     conn_pool: Rc<RefCell<ConnPool>>,
 ) -> anyhow::Result<()> {
-    let client_ip = peer_addr.ip().to_string();
+    let listener = Rc::from(HTTPS_LISTENER);
+    let addrs = ClientAddrs::of(&client, peer_addr);
+    handle_tls_connection_on(listener, client, addrs, tls, proxy, conn_pool).await
+}
+
+/// `handle_tls_connection` for a connection accepted on the listener
+/// named `listener`.
+pub async fn handle_tls_connection_on(
+    listener: Rc<str>,
+    client: TcpStream,
+    addrs: ClientAddrs,
+    tls: Arc<TlsServer>,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) -> anyhow::Result<()> {
+    let _open = OpenConnection::new(&proxy);
+    // The handshake counts against the header timeout.
+    let header_timeout = proxy.borrow().client_timeouts().header;
+    let (acceptor, handshake) = tls.acceptor();
+    let Ok(client) = monoio::time::timeout(header_timeout, acceptor.accept(client)).await else {
+        return Ok(());
+    };
+    let client = client?;
+    let client_cert = handshake.and_then(|h| h.client_cert()).map(Rc::new);
+    if client.alpn_protocol() == Some(b"h2") {
+        return crate::h2::serve_h2(client, addrs, listener, client_cert, proxy, conn_pool).await;
+    }
+    serve_connection(
+        client,
+        addrs,
+        "https",
+        listener,
+        client_cert,
+        proxy,
+        conn_pool,
+    )
+    .await
+}
+
+async fn serve_connection<S>(
+    mut client: S,
+    addrs: ClientAddrs,
+    scheme: &'static str,
+    listener: Rc<str>,
+    client_cert: Option<Rc<ClientCert>>,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) -> anyhow::Result<()>
+where
+    S: AsyncReadRent + AsyncWriteRent + Split,
+{
+    let client_ip = addrs.peer.ip().to_string();
 
     // ── All buffers allocated ONCE, reused across keepalive requests ──
-    let mut read_buf = vec![0u8; 8192];
+    let limits = proxy.borrow().request_limits();
+    let client_timeouts = proxy.borrow().client_timeouts();
+    let streaming_idle_timeout = proxy.borrow().streaming_idle_timeout();
+    let mut read_buf = vec![0u8; limits.max_header_size];
     let mut upstream_req_buf = Vec::with_capacity(2048);
     let mut resp_buf = Vec::with_capacity(4096);
     let mut upstream_buf = vec![0u8; 65536];
 
-    loop {
+    // Bytes of an incomplete request head kept from the previous read.
+    let mut head_len = 0;
+    // Requests started on this connection.
+    let mut served = 0;
+    // When the head of the request being read must be complete.
+    let mut head_deadline = Instant::now() + client_timeouts.header;
+    // Until its first request is read, a connection counts as in flight:
+    // a drain that begins right after the accept must not drop it.
+    let mut first_request = Some(InFlight::new(&proxy));
+
+    'requests: loop {
         // ── Read request ──
-        let (res, returned_buf) = client.read(read_buf).await;
-        read_buf = returned_buf;
-        let n = match res {
-            Ok(0) => return Ok(()),
-            Ok(n) => n,
-            Err(e) => return Err(e.into()),
+        // Between requests the connection idles; once a request has begun,
+        // its head has until `head_deadline`.
+        let idle = head_len == 0 && served > 0;
+        let limit = if idle {
+            client_timeouts.idle
+        } else {
+            client_timeouts
+                .read
+                .min(head_deadline.saturating_duration_since(Instant::now()))
         };
+        let (read, returned_buf) =
+            match read_within(&mut client, read_buf.slice_mut(head_len..), limit).await {
+                Ok(done) => done,
+                Err(e) if e.kind() == ErrorKind::TimedOut => {
+                    if idle {
+                        return Ok(());
+                    }
+                    return reject(&mut client, RESP_408).await;
+                }
+                Err(e) => return Err(e.into()),
+            };
+        read_buf = returned_buf.into_inner();
+        if read == 0 {
+            return Ok(());
+        }
+        if idle {
+            head_deadline = Instant::now() + client_timeouts.header;
+        }
+        let n = head_len + read;
+        head_len = 0;
 
         // ── Parse HTTP request ──
-        let mut headers_raw = [httparse::EMPTY_HEADER; 64];
-        let mut req = httparse::Request::new(&mut headers_raw);
+        let mut headers_raw = [const { MaybeUninit::<httparse::Header>::uninit() }; MAX_HEADERS];
+        let mut req = httparse::Request::new(&mut []);
 
-        match req.parse(&read_buf[..n]) {
+        match req.parse_with_uninit_headers(&read_buf[..n], &mut headers_raw[..limits.max_headers])
+        {
             Ok(httparse::Status::Complete(body_offset)) => {
                 let method = req.method.unwrap_or("GET");
-                let path = req.path.unwrap_or("/");
+                // An absolute-form target names the host itself, which
+                // takes precedence over the Host header (RFC 9112 3.2.2).
+                let (target_host, path) = split_absolute_form(req.path.unwrap_or("/"));
 
                 // Zero-copy header extraction (references into read_buf)
                 let mut headers: Vec<(&str, &str)> = Vec::with_capacity(16);
                 let mut host: Option<&str> = None;
                 let mut keep_alive = true;
+                let mut expect_continue = false;
 
                 for h in req.headers.iter() {
                     if h.name.is_empty() {
@@ -121,159 +828,685 @@ pub async fn handle_connection(
                     if h.name.eq_ignore_ascii_case("host") {
                         host = Some(val);
                     } else if h.name.eq_ignore_ascii_case("connection") {
-                        keep_alive = !val.eq_ignore_ascii_case("close");
+                        keep_alive = !lists_close(val);
+                    } else if h.name.eq_ignore_ascii_case("expect") {
+                        expect_continue = val.eq_ignore_ascii_case("100-continue");
+                    }
+                }
+                if target_host.is_some() {
+                    host = target_host;
+                }
+                // A body the upstream could delimit differently is refused
+                // rather than forwarded; see `crate::hop_by_hop`.
+                let framing = match request_framing(&headers) {
+                    Ok(framing) => framing,
+                    Err(e) => {
+                        tracing::debug!(error = %e, "Ambiguous request framing");
+                        let (res, _) = client.write_all(RESP_400.to_vec()).await;
+                        res?;
+                        return Ok(());
+                    }
+                };
+                let chunked_request = framing == BodyFraming::Chunked;
+
+                served += 1;
+                let _in_flight = InFlight::new(&proxy);
+                drop(first_request.take());
+                if client_timeouts
+                    .max_requests
+                    .is_some_and(|max| served >= max)
+                    || proxy.borrow().draining()
+                {
+                    keep_alive = false;
+                }
+
+                // ── Request body ──
+                // Chunked uploads are decoded up front and forwarded with a
+                // content-length; content-length bodies are read in full up
+                // to MAX_BUFFERED_REQUEST_BODY and streamed beyond that.
+                let first = &read_buf[body_offset..n];
+                let declared = match framing {
+                    BodyFraming::Length(len) => len,
+                    BodyFraming::None | BodyFraming::Chunked => 0,
+                };
+                // A declared body over the global limit is refused before
+                // any of it is read (and before 100-continue is sent).
+                if let Some(max) = limits.max_body_size
+                    && declared as u64 > max
+                {
+                    return reject(&mut client, RESP_413).await;
+                }
+                if expect_continue && (chunked_request || declared > first.len()) {
+                    let (res, _) = client.write_all(RESP_100_CONTINUE.to_vec()).await;
+                    res?;
+                }
+                let owned_body;
+                let mut stream_remaining = 0;
+                let body: &[u8] = if chunked_request {
+                    let max = limits
+                        .max_body_size
+                        .map_or(MAX_CHUNKED_REQUEST_BODY, |max| {
+                            MAX_CHUNKED_REQUEST_BODY.min(max.try_into().unwrap_or(usize::MAX))
+                        });
+                    match read_chunked_request(&mut client, first, max, client_timeouts.read).await
+                    {
+                        Ok(decoded) => {
+                            owned_body = decoded;
+                            &owned_body
+                        }
+                        Err(resp) => return reject(&mut client, resp).await,
                     }
+                } else if declared <= first.len() {
+                    &first[..declared]
+                } else if declared <= MAX_BUFFERED_REQUEST_BODY {
+                    match read_body(&mut client, first, declared, client_timeouts.read).await {
+                        Ok(full) => {
+                            owned_body = full;
+                            &owned_body
+                        }
+                        Err(e) if e.kind() == ErrorKind::TimedOut => {
+                            return reject(&mut client, RESP_408).await;
+                        }
+                        Err(_) => return Ok(()),
+                    }
+                } else {
+                    stream_remaining = declared - first.len();
+                    first
+                };
+                // Until a streamed body has been relayed, the rest of it is
+                // still in the socket and the connection cannot be reused.
+                let client_keep_alive = keep_alive;
+                if stream_remaining > 0 {
+                    keep_alive = false;
                 }
 
                 // ── Process request (brief RefCell borrow, NO await) ──
-                let result = {
+                let started = Instant::now();
+                let (result, mut exchange) = {
                     let mut pw = proxy.borrow_mut();
-                    pw.handle_request(method, path, host, &headers, &client_ip)
+                    let result = pw.handle_request_with_scheme(
+                        scheme,
+                        &listener,
+                        client_cert.as_deref(),
+                        method,
+                        path,
+                        host,
+                        &headers,
+                        &client_ip,
+                        body,
+                    );
+                    (result, Some(pw.exchange(started)))
                 };
                 // Borrow dropped here — safe to do async I/O
 
                 match result {
+                    // gRPC upstreams are only spoken to over HTTP/2, from
+                    // HTTP/2 clients; see `crate::grpc`.
+                    RequestResult::Proxy { grpc: true, .. } => {
+                        let resp = error_response(&exchange, 502, RESP_502);
+                        record_exchange(&proxy, &mut exchange, method, 502);
+                        let (res, _) = client.write_all(resp).await;
+                        res?;
+                    }
+
                     RequestResult::Proxy {
                         ref upstream_addr,
                         ref upstream_path,
+                        upgrade,
+                        streaming,
+                        ref passive,
+                        ref tls,
+                        grpc: _,
+                        ref upstream_host,
+                        proxy_protocol,
+                        timeouts,
+                        mut retry,
+                        mut upstream_headers,
+                        ref removed_headers,
+                        mut response_headers,
+                        pending_access,
+                        mut response_plugins,
+                        request_body,
                     } => {
-                        // Build upstream request while header refs are valid
-                        let body_data = &read_buf[body_offset..n];
-                        build_upstream_request(
-                            &mut upstream_req_buf,
-                            method,
-                            upstream_path,
-                            &headers,
-                            body_data,
-                        );
-
-                        // Get or open upstream connection
-                        let maybe_conn = conn_pool.borrow_mut().take(upstream_addr);
-                        let mut upstream = match maybe_conn {
-                            Some(s) => s,
-                            None => match new_upstream_conn(upstream_addr).await {
-                                Some(s) => s,
-                                None => {
-                                    let (res, _) = client.write_all(RESP_502.to_vec()).await;
+                        // A body decoded for `request_decompression` or
+                        // rewritten by a plugin is sent instead, without
+                        // its content-encoding.
+                        // Headers a plugin removed are not forwarded.
+                        let kept_headers: Vec<(&str, &str)>;
+                        let headers = if request_body.is_some() || !removed_headers.is_empty() {
+                            kept_headers = headers
+                                .iter()
+                                .filter(|(name, _)| {
+                                    let decoded = request_body.is_some()
+                                        && name.eq_ignore_ascii_case("content-encoding");
+                                    let removed = removed_headers
+                                        .iter()
+                                        .any(|r| name.eq_ignore_ascii_case(r));
+                                    !decoded && !removed
+                                })
+                                .copied()
+                                .collect();
+                            kept_headers.as_slice()
+                        } else {
+                            headers.as_slice()
+                        };
+                        let body = match request_body {
+                            Some(ref decoded) => decoded.as_slice(),
+                            None => body,
+                        };
+
+                        // Async access hooks (e.g. shared rate-limit store)
+                        // run here, outside the worker borrow.
+                        if let Some(pending) = pending_access {
+                            match pending.run().await {
+                                Ok(admitted) => {
+                                    admitted.apply(&mut upstream_headers, &mut response_headers)
+                                }
+                                Err(Rejection {
+                                    plugin,
+                                    status,
+                                    headers,
+                                    body,
+                                }) => {
+                                    proxy
+                                        .borrow_mut()
+                                        .record_plugin_response(Some(&plugin), status);
+                                    record_exchange(&proxy, &mut exchange, method, status);
+                                    build_response(&mut resp_buf, status, &headers, &body);
+                                    let (res, _) = client.write_all(resp_buf.clone()).await;
                                     res?;
                                     if !keep_alive {
                                         return Ok(());
                                     }
                                     continue;
                                 }
-                            },
+                            }
+                        }
+
+                        // Each attempt goes to a node not tried before;
+                        // see `ProxyWorker::retry_node`.
+                        let mut target = UpstreamTarget {
+                            addr: upstream_addr.clone(),
+                            tls: tls.clone(),
+                            host: upstream_host.clone(),
+                            proxy_protocol,
                         };
+                        let (mut upstream, pool_key, resp_n) = loop {
+                            // Build upstream request while header refs are valid
+                            if upgrade {
+                                build_upgrade_request(
+                                    &mut upstream_req_buf,
+                                    method,
+                                    upstream_path,
+                                    headers,
+                                    &upstream_headers,
+                                    target.host.as_deref(),
+                                );
+                            } else if stream_remaining == 0 {
+                                build_upstream_request(
+                                    &mut upstream_req_buf,
+                                    method,
+                                    upstream_path,
+                                    headers,
+                                    &upstream_headers,
+                                    target.host.as_deref(),
+                                    body,
+                                );
+                            } else {
+                                build_upstream_request_head(
+                                    &mut upstream_req_buf,
+                                    method,
+                                    upstream_path,
+                                    headers,
+                                    &upstream_headers,
+                                    target.host.as_deref(),
+                                    declared,
+                                );
+                                upstream_req_buf.extend_from_slice(body);
+                            }
 
-                        // Send request to upstream
-                        let req_data = upstream_req_buf.clone();
-                        let (res, _) = upstream.write_all(req_data).await;
-                        if res.is_err() {
-                            // Pooled conn was stale — retry with a fresh connection
-                            match new_upstream_conn(upstream_addr).await {
-                                Some(mut new_upstream) => {
-                                    let req_data = upstream_req_buf.clone();
-                                    let (res, _) = new_upstream.write_all(req_data).await;
-                                    if res.is_err() {
-                                        tracing::warn!(addr = %upstream_addr, "Upstream write failed after reconnect");
-                                        let (res, _) = client.write_all(RESP_502.to_vec()).await;
-                                        res?;
-                                        if !keep_alive {
-                                            return Ok(());
+                            // Get or open upstream connection.
+                            let pool_key = target.pool_key(&addrs);
+                            let sent = send_request(
+                                &conn_pool,
+                                &pool_key,
+                                &target,
+                                &addrs,
+                                &upstream_req_buf,
+                                timeouts,
+                            )
+                            .await;
+                            let attempt = match sent {
+                                Err(failure) => Err(failure),
+                                Ok(mut upstream) => {
+                                    if stream_remaining > 0 {
+                                        match relay_request_body(
+                                            &mut client,
+                                            &mut upstream,
+                                            stream_remaining,
+                                            client_timeouts.read,
+                                            timeouts.send,
+                                        )
+                                        .await
+                                        {
+                                            Ok(()) => {}
+                                            Err(RelayError::ClientTimeout) => {
+                                                return reject(&mut client, RESP_408).await;
+                                            }
+                                            Err(RelayError::Broken) => {
+                                                tracing::warn!(addr = %target.addr, "Request body relay failed");
+                                                return Ok(());
+                                            }
                                         }
-                                        continue;
+                                        keep_alive = client_keep_alive;
                                     }
-                                    upstream = new_upstream;
-                                }
-                                None => {
-                                    let (res, _) = client.write_all(RESP_502.to_vec()).await;
-                                    res?;
-                                    if !keep_alive {
-                                        return Ok(());
+                                    // Read upstream response — reuse buffer across keepalive
+                                    let (res, returned_ubuf) =
+                                        read_upstream(&mut upstream, upstream_buf, timeouts.read)
+                                            .await;
+                                    upstream_buf = returned_ubuf;
+                                    match res {
+                                        Ok(0) => {
+                                            Err(Failure::Exchange(ErrorKind::UnexpectedEof.into()))
+                                        }
+                                        Ok(n) => Ok((upstream, n)),
+                                        Err(e) => Err(Failure::Exchange(e)),
                                     }
-                                    continue;
                                 }
-                            }
-                        }
+                            };
 
-                        // Read upstream response — reuse buffer across keepalive
-                        let (res, returned_ubuf) = upstream.read(upstream_buf).await;
-                        upstream_buf = returned_ubuf;
-                        let resp_n = match res {
-                            Ok(0) => {
-                                tracing::warn!(addr = %upstream_addr, "Upstream closed connection without response");
-                                let (res, _) = client.write_all(RESP_502.to_vec()).await;
-                                res?;
-                                if !keep_alive {
-                                    return Ok(());
+                            // A response may be retried while none of it
+                            // has reached the client, unless the request
+                            // body was streamed and cannot be sent again.
+                            let cause = match attempt {
+                                Err(ref failure) => {
+                                    tracing::warn!(addr = %target.addr, error = %failure, "Upstream request failed");
+                                    report_upstream(
+                                        &proxy,
+                                        &target.addr,
+                                        passive,
+                                        Some(UpstreamFailure::Tcp),
+                                    );
+                                    matches!(failure, Failure::Connect(_))
+                                        .then_some(RetryOn::ConnectFailure)
+                                }
+                                Ok((_, n)) if retry.is_some() && stream_remaining == 0 => {
+                                    response_status(&upstream_buf[..n])
+                                        .and_then(RetryOn::from_status)
                                 }
+                                Ok(_) => None,
+                            };
+                            if let Some(cause) = cause
+                                && let Some(ref mut retry) = retry
+                                && let Some(next) =
+                                    proxy.borrow_mut().retry_node(retry, &target.addr, cause)
+                            {
+                                if let Ok((_, n)) = attempt
+                                    && let Some(status) = response_status(&upstream_buf[..n])
+                                {
+                                    report_upstream(
+                                        &proxy,
+                                        &target.addr,
+                                        passive,
+                                        status_failure(passive, status),
+                                    );
+                                }
+                                tracing::info!(from = %target.addr, to = %next.addr, ?cause, "Retrying upstream request");
+                                target = next;
                                 continue;
                             }
-                            Ok(n) => n,
-                            Err(e) => {
-                                tracing::warn!(addr = %upstream_addr, error = %e, "Upstream read error");
-                                let (res, _) = client.write_all(RESP_502.to_vec()).await;
-                                res?;
-                                if !keep_alive {
-                                    return Ok(());
+                            match attempt {
+                                Ok((upstream, n)) => break (upstream, pool_key, n),
+                                Err(failure) => {
+                                    let (status, resp) = upstream_error(failure.error());
+                                    let resp = error_response(&exchange, status, resp);
+                                    upstream_failed(&mut exchange, failure.kind());
+                                    finish_exchange(
+                                        &proxy,
+                                        &mut exchange,
+                                        method,
+                                        &mut response_plugins,
+                                        status,
+                                    );
+                                    let (res, _) = client.write_all(resp).await;
+                                    res?;
+                                    if !keep_alive {
+                                        return Ok(());
+                                    }
+                                    continue 'requests;
                                 }
-                                continue;
                             }
                         };
+                        let upstream_addr = &target.addr;
 
-                        // Parse upstream response headers for content-length
+                        // Parse upstream response headers for body framing
                         let mut resp_headers = [httparse::EMPTY_HEADER; 64];
                         let mut resp = httparse::Response::new(&mut resp_headers);
-                        let mut content_length: Option<usize> = None;
+                        let mut chunked = false;
                         let mut upstream_keepalive = true;
+                        let mut streaming = streaming;
+
+                        // A drain that began while the upstream worked
+                        // still closes the connection after this response.
+                        if proxy.borrow().draining() {
+                            keep_alive = false;
+                        }
 
                         if let Ok(httparse::Status::Complete(hdr_len)) =
                             resp.parse(&upstream_buf[..resp_n])
                         {
+                            let failure = status_failure(passive, resp.code.unwrap_or(0));
+                            report_upstream(&proxy, upstream_addr, passive, failure);
                             for h in resp.headers.iter() {
                                 if h.name.is_empty() {
                                     break;
                                 }
-                                if h.name.eq_ignore_ascii_case("content-length") {
-                                    content_length = std::str::from_utf8(h.value)
-                                        .ok()
-                                        .and_then(|s| s.parse().ok());
-                                }
                                 if h.name.eq_ignore_ascii_case("connection") {
                                     let v = std::str::from_utf8(h.value).unwrap_or("");
-                                    upstream_keepalive = !v.eq_ignore_ascii_case("close");
+                                    upstream_keepalive = !lists_close(v);
+                                }
+                                if h.name.eq_ignore_ascii_case("transfer-encoding") {
+                                    chunked = std::str::from_utf8(h.value).is_ok_and(is_chunked);
+                                }
+                                if h.name.eq_ignore_ascii_case("content-type") {
+                                    streaming |= is_event_stream(h.value);
                                 }
                             }
+                            let hop = HopByHop::of_response(resp.headers);
+                            let lengths = resp
+                                .headers
+                                .iter()
+                                .map(|h| (h.name, std::str::from_utf8(h.value).unwrap_or("")));
+                            let mut content_length = match content_length(lengths) {
+                                Ok(length) => length,
+                                Err(e) => {
+                                    // Where this body ends is anyone's guess.
+                                    tracing::warn!(addr = %upstream_addr, error = %e, "Upstream response framing refused");
+                                    let resp = error_response(&exchange, 502, RESP_502);
+                                    upstream_failed(&mut exchange, "invalid_response");
+                                    finish_exchange(
+                                        &proxy,
+                                        &mut exchange,
+                                        method,
+                                        &mut response_plugins,
+                                        502,
+                                    );
+                                    let (res, _) = client.write_all(resp).await;
+                                    res?;
+                                    return Ok(());
+                                }
+                            };
 
-                            // Forward first chunk to client
-                            let first_chunk = upstream_buf[..resp_n].to_vec();
-                            let (res, _) = client.write_all(first_chunk).await;
-                            res?;
+                            let status = resp.code.unwrap_or(0);
+                            if upgrade && status == 101 {
+                                // Handshake accepted — hand both sockets to
+                                // the tunnel. Frames the upstream sent with
+                                // the 101 go out with it.
+                                let data = upstream_buf[..resp_n].to_vec();
+                                let (res, _) = client.write_all(data).await;
+                                res?;
+                                finish_exchange(
+                                    &proxy,
+                                    &mut exchange,
+                                    method,
+                                    &mut response_plugins,
+                                    status,
+                                );
+                                tunnel(client, upstream).await;
+                                return Ok(());
+                            }
+                            let has_body =
+                                method != "HEAD" && !matches!(status, 100..=199 | 204 | 304);
+                            // Transfer-Encoding overrides Content-Length.
+                            chunked &= has_body;
+                            if chunked {
+                                content_length = None;
+                            }
+                            // Streams go out as they arrive, past body
+                            // filters, and may idle up to their own deadline.
+                            let body_timeout = if streaming {
+                                streaming_idle_timeout
+                            } else {
+                                timeouts.read
+                            };
+                            let filter = response_plugins.as_mut().filter(|f| {
+                                f.filters_body()
+                                    && !streaming
+                                    && has_body
+                                    && content_length.is_some_and(|cl| cl <= f.max_body)
+                            });
+                            let filtered = filter.is_some();
+
+                            if let Some(filter) = filter {
+                                // Buffer the whole body, run body-filter
+                                // plugins, then reply with fresh framing.
+                                let cl = content_length.unwrap_or(0);
+                                let mut headers = forwarded_headers(resp.headers);
+                                if filter.filters_headers() {
+                                    headers = filter.filter_headers(status, headers);
+                                }
+                                let first = &upstream_buf[hdr_len..resp_n];
+                                // The read deadline bounds the whole buffered body.
+                                let body = monoio::time::timeout(
+                                    timeouts.read,
+                                    read_body(&mut upstream, first, cl, timeouts.read),
+                                )
+                                .await
+                                .ok()
+                                .and_then(Result::ok);
+                                match body {
+                                    Some(body) => {
+                                        let (status, headers, body) =
+                                            filter.apply(status, headers, &response_headers, body);
+                                        build_response(&mut resp_buf, status, &headers, &body);
+                                        let (res, _) = client.write_all(resp_buf.clone()).await;
+                                        res?;
+                                        finish_exchange(
+                                            &proxy,
+                                            &mut exchange,
+                                            method,
+                                            &mut response_plugins,
+                                            status,
+                                        );
+                                    }
+                                    None => {
+                                        tracing::warn!(addr = %upstream_addr, "Upstream body truncated");
+                                        upstream_keepalive = false;
+                                        let resp = error_response(&exchange, 502, RESP_502);
+                                        upstream_failed(&mut exchange, "truncated");
+                                        finish_exchange(
+                                            &proxy,
+                                            &mut exchange,
+                                            method,
+                                            &mut response_plugins,
+                                            502,
+                                        );
+                                        let (res, _) = client.write_all(resp).await;
+                                        res?;
+                                    }
+                                }
+                            } else {
+                                // Forward the first read with a head of our
+                                // own: end-to-end headers only, plus the
+                                // plugins' ones.
+                                let close_delimited =
+                                    has_body && !chunked && content_length.is_none();
+                                let filtered = response_plugins
+                                    .as_mut()
+                                    .filter(|p| p.filters_headers())
+                                    .map(|p| {
+                                        p.filter_headers(status, forwarded_headers(resp.headers))
+                                    });
+                                let mut first_chunk = Vec::with_capacity(resp_n + 256);
+                                relayed_head(
+                                    &mut first_chunk,
+                                    &resp,
+                                    &hop,
+                                    filtered.as_deref(),
+                                    content_length,
+                                    &response_headers,
+                                    keep_alive && !close_delimited,
+                                );
+                                first_chunk.extend_from_slice(&upstream_buf[hdr_len..resp_n]);
+                                let (res, _) = client.write_all(first_chunk).await;
+                                res?;
+                                // Recorded once the body has been relayed,
+                                // so a body cut short is counted.
+                            }
 
                             // Stream remaining body if needed
-                            if let Some(cl) = content_length {
+                            if !filtered
+                                && has_body
+                                && let Some(cl) = content_length
+                            {
                                 let body_in_first = resp_n - hdr_len;
                                 let mut remaining = cl.saturating_sub(body_in_first);
 
                                 while remaining > 0 {
                                     let chunk_size = remaining.min(65536);
                                     let mut chunk_buf = vec![0u8; chunk_size];
-                                    let (res, returned_chunk) = upstream.read(chunk_buf).await;
+                                    let (res, returned_chunk) =
+                                        read_upstream(&mut upstream, chunk_buf, body_timeout).await;
                                     chunk_buf = returned_chunk;
                                     let cn = match res {
-                                        Ok(0) => break,
+                                        Ok(0) | Err(_) => {
+                                            // The client is owed more bytes
+                                            // than will come; close both sides.
+                                            tracing::warn!(addr = %upstream_addr, "Upstream body truncated");
+                                            upstream_failed(&mut exchange, "truncated");
+                                            finish_exchange(
+                                                &proxy,
+                                                &mut exchange,
+                                                method,
+                                                &mut response_plugins,
+                                                status,
+                                            );
+                                            return Ok(());
+                                        }
                                         Ok(n) => n,
-                                        Err(_) => break,
                                     };
                                     remaining -= cn;
                                     let data = chunk_buf[..cn].to_vec();
                                     let (res, _) = client.write_all(data).await;
                                     if res.is_err() {
+                                        finish_exchange(
+                                            &proxy,
+                                            &mut exchange,
+                                            method,
+                                            &mut response_plugins,
+                                            status,
+                                        );
+                                        return Ok(());
+                                    }
+                                }
+                            }
+
+                            // Relay chunked bodies verbatim (trailers
+                            // included) until the terminal chunk.
+                            if chunked {
+                                let mut decoder = ChunkedDecoder::new();
+                                let mut well_formed =
+                                    decoder.feed(&upstream_buf[hdr_len..resp_n], None).is_ok();
+                                while well_formed && !decoder.is_done() {
+                                    let (res, returned_ubuf) =
+                                        read_upstream(&mut upstream, upstream_buf, body_timeout)
+                                            .await;
+                                    upstream_buf = returned_ubuf;
+                                    let cn = match res {
+                                        Ok(0) | Err(_) => break,
+                                        Ok(n) => n,
+                                    };
+                                    let Ok(used) = decoder.feed(&upstream_buf[..cn], None) else {
+                                        well_formed = false;
+                                        break;
+                                    };
+                                    let data = upstream_buf[..used].to_vec();
+                                    let (res, _) = client.write_all(data).await;
+                                    if res.is_err() {
+                                        finish_exchange(
+                                            &proxy,
+                                            &mut exchange,
+                                            method,
+                                            &mut response_plugins,
+                                            status,
+                                        );
                                         return Ok(());
                                     }
                                 }
+                                if !decoder.is_done() {
+                                    // The client cannot tell where the
+                                    // body ends, so close both sides.
+                                    tracing::warn!(addr = %upstream_addr, "Upstream chunked body truncated or malformed");
+                                    upstream_failed(&mut exchange, "truncated");
+                                    finish_exchange(
+                                        &proxy,
+                                        &mut exchange,
+                                        method,
+                                        &mut response_plugins,
+                                        status,
+                                    );
+                                    return Ok(());
+                                }
+                            }
+
+                            // Without framing the body runs until the
+                            // upstream closes; the client sees the same.
+                            if has_body && !filtered && !chunked && content_length.is_none() {
+                                loop {
+                                    let (res, returned_ubuf) =
+                                        read_upstream(&mut upstream, upstream_buf, body_timeout)
+                                            .await;
+                                    upstream_buf = returned_ubuf;
+                                    let cn = match res {
+                                        Ok(0) => break,
+                                        Ok(n) => n,
+                                        Err(e) => {
+                                            tracing::warn!(addr = %upstream_addr, error = %e, "Upstream stream ended early");
+                                            upstream_failed(&mut exchange, "truncated");
+                                            break;
+                                        }
+                                    };
+                                    let data = upstream_buf[..cn].to_vec();
+                                    let (res, _) = client.write_all(data).await;
+                                    if res.is_err() {
+                                        break;
+                                    }
+                                }
+                                finish_exchange(
+                                    &proxy,
+                                    &mut exchange,
+                                    method,
+                                    &mut response_plugins,
+                                    status,
+                                );
+                                return Ok(());
+                            }
+                            if !filtered {
+                                finish_exchange(
+                                    &proxy,
+                                    &mut exchange,
+                                    method,
+                                    &mut response_plugins,
+                                    status,
+                                );
                             }
                         } else {
-                            // Couldn't parse response headers — forward raw
+                            // Couldn't parse response headers — forward raw,
+                            // recorded under the status line if it has one.
+                            let status = match response_status(&upstream_buf[..resp_n]) {
+                                Some(status) => status,
+                                None => {
+                                    upstream_failed(&mut exchange, "invalid_response");
+                                    502
+                                }
+                            };
+                            finish_exchange(
+                                &proxy,
+                                &mut exchange,
+                                method,
+                                &mut response_plugins,
+                                status,
+                            );
                             let data = upstream_buf[..resp_n].to_vec();
                             let (res, _) = client.write_all(data).await;
                             res?;
@@ -282,11 +1515,12 @@ pub async fn handle_connection(
 
                         // Return upstream connection to pool if keepalive
                         if upstream_keepalive {
-                            conn_pool.borrow_mut().put(upstream_addr.clone(), upstream);
+                            conn_pool.borrow_mut().put(pool_key, upstream);
                         }
                     }
 
                     RequestResult::Static(resp_bytes) => {
+                        record_exchange(&proxy, &mut exchange, method, static_status(resp_bytes));
                         let (res, _) = client.write_all(resp_bytes.to_vec()).await;
                         res?;
                     }
@@ -296,6 +1530,7 @@ pub async fn handle_connection(
                         ref headers,
                         ref body,
                     } => {
+                        record_exchange(&proxy, &mut exchange, method, status);
                         build_response(&mut resp_buf, status, headers, body);
                         let data = resp_buf.clone();
                         let (res, _) = client.write_all(data).await;
@@ -308,17 +1543,17 @@ pub async fn handle_connection(
                 }
             }
             Ok(httparse::Status::Partial) => {
-                let resp =
-                    b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
-                let (res, _) = client.write_all(resp.to_vec()).await;
-                res?;
-                return Ok(());
+                // Head split across segments — keep it and read more.
+                if n < limits.max_header_size {
+                    head_len = n;
+                    continue;
+                }
+                return reject(&mut client, RESP_431).await;
             }
+            Err(httparse::Error::TooManyHeaders) => return reject(&mut client, RESP_431).await,
             Err(e) => {
                 tracing::debug!(error = %e, "HTTP parse error");
-                let resp =
-                    b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\nconnection: close\r\n\r\n";
-                let (res, _) = client.write_all(resp.to_vec()).await;
+                let (res, _) = client.write_all(RESP_400.to_vec()).await;
                 res?;
                 return Ok(());
             }
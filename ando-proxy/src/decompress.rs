@@ -0,0 +1,166 @@
+//! Request body decompression for routes with `request_decompression`.
+//!
+//! v2 design: Bodies are decoded in one pass through a reader capped one
+//! byte past the route's `max_decompressed_size`, so a small body that
+//! expands without bound (a zip bomb) costs at most the limit in memory
+//! before it is refused. Only fully buffered bodies are decoded; a
+//! compressed body still streaming from the client is refused too, as it
+//! could not be checked against the limit before being forwarded.
+
+use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+
+/// A `Content-Encoding` the gateway can decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coding {
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+/// Why a request body could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The body is not valid for its coding (400).
+    Corrupt,
+    /// The decoded body exceeds the limit (413).
+    TooLarge,
+}
+
+impl Coding {
+    /// The coding named by a `Content-Encoding` value. `None` for
+    /// `identity`, unknown codings and lists of several codings, which are
+    /// forwarded untouched.
+    pub fn from_header(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("gzip") || value.eq_ignore_ascii_case("x-gzip") {
+            Some(Self::Gzip)
+        } else if value.eq_ignore_ascii_case("deflate") {
+            Some(Self::Deflate)
+        } else if value.eq_ignore_ascii_case("br") {
+            Some(Self::Brotli)
+        } else {
+            None
+        }
+    }
+
+    /// The coding of a request, from its `Content-Encoding` header.
+    pub fn of_request(headers: &[(&str, &str)]) -> Option<Self> {
+        headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-encoding"))
+            .and_then(|(_, value)| Self::from_header(value))
+    }
+}
+
+/// Decode `body`, refusing output over `limit` bytes.
+pub fn decompress(coding: Coding, body: &[u8], limit: u64) -> Result<Vec<u8>, DecodeError> {
+    match coding {
+        Coding::Gzip => read_capped(GzDecoder::new(body), limit),
+        // `deflate` is zlib-wrapped (RFC 9110 8.4.1.2), but some clients
+        // send a raw deflate stream.
+        Coding::Deflate => read_capped(ZlibDecoder::new(body), limit).or_else(|e| match e {
+            DecodeError::Corrupt => read_capped(DeflateDecoder::new(body), limit),
+            DecodeError::TooLarge => Err(e),
+        }),
+        Coding::Brotli => read_capped(brotli_decompressor::Decompressor::new(body, 4096), limit),
+    }
+}
+
+fn read_capped(reader: impl Read, limit: u64) -> Result<Vec<u8>, DecodeError> {
+    let mut out = Vec::new();
+    reader
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut out)
+        .map_err(|_| DecodeError::Corrupt)?;
+    if out.len() as u64 > limit {
+        return Err(DecodeError::TooLarge);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::Compression;
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut enc = GzEncoder::new(Vec::new(), Compression::default());
+        enc.write_all(data).unwrap();
+        enc.finish().unwrap()
+    }
+
+    #[test]
+    fn gzip_round_trip() {
+        let body = br#"{"user":"alice","items":[1,2,3]}"#;
+        assert_eq!(
+            decompress(Coding::Gzip, &gzip(body), 1024).unwrap(),
+            body.to_vec()
+        );
+    }
+
+    #[test]
+    fn deflate_accepts_zlib_and_raw_streams() {
+        let body = b"payload payload payload";
+        let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+        zlib.write_all(body).unwrap();
+        let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+        raw.write_all(body).unwrap();
+        for encoded in [zlib.finish().unwrap(), raw.finish().unwrap()] {
+            assert_eq!(
+                decompress(Coding::Deflate, &encoded, 1024).unwrap(),
+                body.to_vec()
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_body_is_rejected() {
+        assert_eq!(
+            decompress(Coding::Gzip, b"not gzip at all", 1024),
+            Err(DecodeError::Corrupt)
+        );
+        let mut truncated = gzip(b"some text to compress");
+        truncated.truncate(truncated.len() / 2);
+        assert_eq!(
+            decompress(Coding::Gzip, &truncated, 1024),
+            Err(DecodeError::Corrupt)
+        );
+        assert_eq!(
+            decompress(Coding::Brotli, b"\xff\xff\xff\xff", 1024),
+            Err(DecodeError::Corrupt)
+        );
+    }
+
+    #[test]
+    fn expansion_past_the_limit_is_refused() {
+        // 10 MiB of zeros compresses to about 10 KiB.
+        let bomb = gzip(&vec![0u8; 10 << 20]);
+        assert!(bomb.len() < 64 << 10);
+        assert_eq!(
+            decompress(Coding::Gzip, &bomb, 1 << 20),
+            Err(DecodeError::TooLarge)
+        );
+        let exact = gzip(&[7u8; 100]);
+        assert_eq!(decompress(Coding::Gzip, &exact, 100).unwrap().len(), 100);
+        assert_eq!(
+            decompress(Coding::Gzip, &exact, 99),
+            Err(DecodeError::TooLarge)
+        );
+    }
+
+    #[test]
+    fn only_single_known_codings_are_decoded() {
+        assert_eq!(Coding::from_header("GZIP"), Some(Coding::Gzip));
+        assert_eq!(Coding::from_header(" br "), Some(Coding::Brotli));
+        assert_eq!(Coding::from_header("identity"), None);
+        assert_eq!(Coding::from_header("gzip, br"), None);
+        assert_eq!(
+            Coding::of_request(&[("Content-Encoding", "deflate")]),
+            Some(Coding::Deflate)
+        );
+        assert_eq!(Coding::of_request(&[]), None);
+    }
+}
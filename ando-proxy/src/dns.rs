@@ -0,0 +1,318 @@
+use ando_core::upstream::{Upstream, node_host};
+use ando_store::cache::ConfigCache;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// How often the refresher wakes up to look for due lookups.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Shortest TTL honored, so a zero-TTL record does not turn the refresher
+/// into a busy loop.
+const MIN_TTL: Duration = Duration::from_secs(1);
+
+/// Addresses a node resolved to, and how long they may be cached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolution {
+    pub addrs: Vec<SocketAddr>,
+    /// `None` when the resolver does not know; the refresh interval applies.
+    pub ttl: Option<Duration>,
+}
+
+/// Resolves a `host:port` node to socket addresses.
+pub trait Resolve: Send + 'static {
+    fn resolve(&self, node: &str) -> std::io::Result<Resolution>;
+}
+
+/// The system resolver (`getaddrinfo`), which honors `/etc/hosts` but
+/// does not report TTLs.
+///
+/// Only IPv4 addresses are kept when there are any: on macOS `localhost`
+/// resolves to both `::1` and `127.0.0.1`, and most upstream servers listen
+/// on IPv4 only, so balancing over both would fail half the requests.
+pub struct SystemResolver;
+
+impl Resolve for SystemResolver {
+    fn resolve(&self, node: &str) -> std::io::Result<Resolution> {
+        let all: Vec<SocketAddr> = node.to_socket_addrs()?.collect();
+        let addrs = if all.iter().any(SocketAddr::is_ipv4) {
+            all.into_iter().filter(SocketAddr::is_ipv4).collect()
+        } else {
+            all
+        };
+        Ok(Resolution { addrs, ttl: None })
+    }
+}
+
+/// Spawn the DNS refresher on its own thread.
+///
+/// v2 design: Lookups block, so they run on a dedicated thread rather than
+/// on worker cores. Answers go to the shared `DnsTable`; workers rebuild
+/// the balancers of affected upstreams on their next accept.
+pub fn spawn_dns_refresher(cache: ConfigCache, refresh: Duration) -> std::thread::JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("ando-dns".to_string())
+        .spawn(move || {
+            let mut refresher = DnsRefresher::new(cache, SystemResolver, refresh);
+            loop {
+                refresher.run_once(Instant::now());
+                std::thread::sleep(TICK);
+            }
+        })
+        .expect("Failed to spawn DNS refresher thread")
+}
+
+/// Re-resolves every upstream node given by hostname — in named upstreams
+/// and in those inline in routes and services — once its last answer has
+/// expired: after its TTL, or after `refresh` when there is none.
+///
+/// A failed lookup keeps the previous answer, so a DNS outage does not
+/// take working upstreams down with it.
+pub struct DnsRefresher<R> {
+    cache: ConfigCache,
+    resolver: R,
+    refresh: Duration,
+    /// Node → when it is next due.
+    next_due: HashMap<String, Instant>,
+}
+
+impl<R: Resolve> DnsRefresher<R> {
+    pub fn new(cache: ConfigCache, resolver: R, refresh: Duration) -> Self {
+        Self {
+            cache,
+            resolver,
+            refresh: refresh.max(MIN_TTL),
+            next_due: HashMap::new(),
+        }
+    }
+
+    /// Resolve the nodes that are due and record the answers.
+    pub fn run_once(&mut self, now: Instant) {
+        let nodes = hostname_nodes(&self.cache);
+        self.next_due.retain(|node, _| nodes.contains(node));
+        self.cache.dns.retain(|node| nodes.contains(node));
+
+        for node in nodes {
+            if self.next_due.get(&node).is_some_and(|due| *due > now) {
+                continue;
+            }
+            let valid_for = match self.resolver.resolve(&node) {
+                Ok(answer) if !answer.addrs.is_empty() => {
+                    let ttl = answer.ttl.map(|t| t.max(MIN_TTL));
+                    if self.cache.dns.update(&node, answer.addrs) {
+                        info!(node = %node, addrs = ?self.cache.dns.get(&node).unwrap_or_default(), "Upstream node resolved");
+                    }
+                    ttl.unwrap_or(self.refresh)
+                }
+                Ok(_) => {
+                    warn!(node = %node, "Upstream node resolved to no addresses, keeping the last answer");
+                    self.refresh
+                }
+                Err(e) => {
+                    warn!(node = %node, error = %e, "Upstream node resolve failed, keeping the last answer");
+                    self.refresh
+                }
+            };
+            self.next_due.insert(node, now + valid_for);
+        }
+    }
+}
+
+/// Whether `node` names its host rather than giving an IP address.
+pub(crate) fn is_hostname(node: &str) -> bool {
+    node_host(node).parse::<IpAddr>().is_err()
+}
+
+/// Every configured node given by hostname.
+fn hostname_nodes(cache: &ConfigCache) -> HashSet<String> {
+    let mut nodes = HashSet::new();
+    let mut add = |ups: &Upstream| {
+        nodes.extend(ups.nodes.keys().filter(|n| is_hostname(n)).cloned());
+    };
+    for entry in cache.upstreams.iter() {
+        add(entry.value());
+    }
+    for entry in cache.routes.iter() {
+        if let Some(ups) = &entry.value().upstream {
+            add(ups);
+        }
+    }
+    for entry in cache.services.iter() {
+        if let Some(ups) = &entry.value().upstream {
+            add(ups);
+        }
+    }
+    nodes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Answers from a table the test rewrites between runs, counting lookups.
+    #[derive(Clone, Default)]
+    struct StubResolver {
+        answers: Arc<Mutex<HashMap<String, Resolution>>>,
+        lookups: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl StubResolver {
+        fn answer(&self, node: &str, addrs: &[&str], ttl: Option<u64>) {
+            self.answers.lock().unwrap().insert(
+                node.to_string(),
+                Resolution {
+                    addrs: addrs.iter().map(|a| a.parse().unwrap()).collect(),
+                    ttl: ttl.map(Duration::from_secs),
+                },
+            );
+        }
+
+        fn lookups(&self) -> usize {
+            self.lookups.lock().unwrap().len()
+        }
+    }
+
+    impl Resolve for StubResolver {
+        fn resolve(&self, node: &str) -> std::io::Result<Resolution> {
+            self.lookups.lock().unwrap().push(node.to_string());
+            self.answers
+                .lock()
+                .unwrap()
+                .get(node)
+                .cloned()
+                .ok_or_else(|| std::io::ErrorKind::NotFound.into())
+        }
+    }
+
+    fn cache_with_nodes(nodes: &[&str]) -> ConfigCache {
+        let cache = ConfigCache::new();
+        let nodes: serde_json::Map<String, serde_json::Value> =
+            nodes.iter().map(|n| (n.to_string(), 1.into())).collect();
+        let ups: Upstream =
+            serde_json::from_value(serde_json::json!({ "id": "u1", "nodes": nodes })).unwrap();
+        cache.upstreams.insert("u1".into(), ups);
+        cache
+    }
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn only_hostname_nodes_are_resolved() {
+        let cache = cache_with_nodes(&["api.internal:80", "10.0.0.9:80", "[::1]:80"]);
+        let stub = StubResolver::default();
+        stub.answer("api.internal:80", &["10.0.0.1:80"], None);
+        let mut refresher = DnsRefresher::new(cache.clone(), stub.clone(), Duration::from_secs(30));
+        refresher.run_once(Instant::now());
+        assert_eq!(stub.lookups(), 1);
+        assert_eq!(
+            cache.dns.get("api.internal:80").unwrap(),
+            addrs(&["10.0.0.1:80"])
+        );
+    }
+
+    #[test]
+    fn changing_answers_are_picked_up_when_the_ttl_expires() {
+        let cache = cache_with_nodes(&["api.internal:80"]);
+        let stub = StubResolver::default();
+        stub.answer("api.internal:80", &["10.0.0.1:80", "10.0.0.2:80"], Some(5));
+        let mut refresher = DnsRefresher::new(cache.clone(), stub.clone(), Duration::from_secs(30));
+        let start = Instant::now();
+        refresher.run_once(start);
+        assert_eq!(cache.dns.version(), 1);
+
+        stub.answer("api.internal:80", &["10.0.0.3:80"], Some(5));
+        refresher.run_once(start + Duration::from_secs(4));
+        assert_eq!(stub.lookups(), 1, "answer still within its TTL");
+        assert_eq!(cache.dns.get("api.internal:80").unwrap().len(), 2);
+
+        refresher.run_once(start + Duration::from_secs(5));
+        assert_eq!(stub.lookups(), 2);
+        assert_eq!(
+            cache.dns.get("api.internal:80").unwrap(),
+            addrs(&["10.0.0.3:80"])
+        );
+        assert_eq!(cache.dns.version(), 2);
+    }
+
+    #[test]
+    fn refresh_interval_applies_without_a_ttl() {
+        let cache = cache_with_nodes(&["api.internal:80"]);
+        let stub = StubResolver::default();
+        stub.answer("api.internal:80", &["10.0.0.1:80"], None);
+        let mut refresher = DnsRefresher::new(cache.clone(), stub.clone(), Duration::from_secs(30));
+        let start = Instant::now();
+        refresher.run_once(start);
+        refresher.run_once(start + Duration::from_secs(29));
+        assert_eq!(stub.lookups(), 1);
+        refresher.run_once(start + Duration::from_secs(30));
+        assert_eq!(stub.lookups(), 2);
+        assert_eq!(cache.dns.version(), 1, "same answer, no change");
+    }
+
+    #[test]
+    fn failed_lookup_keeps_the_last_answer() {
+        let cache = cache_with_nodes(&["api.internal:80"]);
+        let stub = StubResolver::default();
+        stub.answer("api.internal:80", &["10.0.0.1:80"], Some(1));
+        let mut refresher = DnsRefresher::new(cache.clone(), stub.clone(), Duration::from_secs(30));
+        let start = Instant::now();
+        refresher.run_once(start);
+
+        stub.answers.lock().unwrap().clear();
+        refresher.run_once(start + Duration::from_secs(1));
+        assert_eq!(stub.lookups(), 2);
+        assert_eq!(
+            cache.dns.get("api.internal:80").unwrap(),
+            addrs(&["10.0.0.1:80"])
+        );
+
+        stub.answer("api.internal:80", &[], None);
+        refresher.run_once(start + Duration::from_secs(31));
+        assert_eq!(
+            cache.dns.get("api.internal:80").unwrap(),
+            addrs(&["10.0.0.1:80"])
+        );
+    }
+
+    #[test]
+    fn removed_nodes_are_forgotten() {
+        let cache = cache_with_nodes(&["api.internal:80"]);
+        let stub = StubResolver::default();
+        stub.answer("api.internal:80", &["10.0.0.1:80"], None);
+        let mut refresher = DnsRefresher::new(cache.clone(), stub, Duration::from_secs(30));
+        refresher.run_once(Instant::now());
+        cache.upstreams.clear();
+        refresher.run_once(Instant::now());
+        assert!(cache.dns.get("api.internal:80").is_none());
+        assert_eq!(cache.dns.version(), 2);
+    }
+
+    #[test]
+    fn inline_upstreams_are_resolved() {
+        let cache = ConfigCache::new();
+        let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/",
+            "upstream": { "nodes": { "inline.internal:8080": 1 } }
+        }))
+        .unwrap();
+        cache.routes.insert("r1".into(), route);
+        let stub = StubResolver::default();
+        stub.answer("inline.internal:8080", &["10.0.0.7:8080"], None);
+        let mut refresher = DnsRefresher::new(cache.clone(), stub, Duration::from_secs(30));
+        refresher.run_once(Instant::now());
+        assert!(cache.dns.get("inline.internal:8080").is_some());
+    }
+
+    #[test]
+    fn system_resolver_prefers_ipv4() {
+        let answer = SystemResolver.resolve("localhost:80").unwrap();
+        assert!(!answer.addrs.is_empty());
+        assert!(answer.addrs.iter().all(SocketAddr::is_ipv4));
+        assert_eq!(answer.ttl, None);
+    }
+}
@@ -0,0 +1,258 @@
+//! Client address resolution and `X-Forwarded-*` headers.
+//!
+//! v2 design: The immediate peer is the client unless it falls inside the
+//! configured `trusted_proxies` CIDRs. Only then is the incoming
+//! `X-Forwarded-For` chain honored: it is walked right to left past
+//! trusted hops and the first untrusted address is the client. Headers
+//! from untrusted peers are replaced, never passed through, so a client
+//! cannot spoof its address to the upstream or to `ip-restriction` and
+//! rate limiting.
+
+use std::net::IpAddr;
+
+use ipnet::IpNet;
+
+/// Peers whose `X-Forwarded-*` headers are believed.
+#[derive(Debug, Clone, Default)]
+pub struct TrustedProxies {
+    nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// Parse CIDRs or plain addresses. Invalid entries are logged and
+    /// skipped.
+    pub fn new(entries: &[String]) -> Self {
+        let nets = entries
+            .iter()
+            .filter_map(|s| {
+                let net = s
+                    .parse::<IpNet>()
+                    .or_else(|_| s.parse::<IpAddr>().map(IpNet::from));
+                if net.is_err() {
+                    tracing::warn!(entry = %s, "Ignoring invalid trusted_proxies entry");
+                }
+                net.ok()
+            })
+            .collect();
+        Self { nets }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nets.is_empty()
+    }
+
+    /// Whether `ip` is a trusted proxy. Unparseable addresses never are.
+    pub fn contains(&self, ip: &str) -> bool {
+        !self.nets.is_empty()
+            && ip
+                .parse::<IpAddr>()
+                .is_ok_and(|ip| self.nets.iter().any(|net| net.contains(&ip)))
+    }
+
+    /// The client address of a request from `peer`: the peer itself, or
+    /// for a trusted peer the rightmost `X-Forwarded-For` hop that is not
+    /// a trusted proxy. A malformed hop ends the walk at the last trusted
+    /// one.
+    pub fn client_ip<'a>(&self, peer: &'a str, headers: &[(&'a str, &'a str)]) -> &'a str {
+        if !self.contains(peer) {
+            return peer;
+        }
+        let hops = headers
+            .iter()
+            .rev()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("x-forwarded-for"))
+            .flat_map(|(_, value)| value.rsplit(','));
+        let mut client = peer;
+        for hop in hops {
+            let hop = hop.trim();
+            if hop.parse::<IpAddr>().is_err() {
+                break;
+            }
+            client = hop;
+            if !self.contains(hop) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+/// Set `X-Forwarded-For`, `X-Real-IP`, `X-Forwarded-Proto` and
+/// `X-Forwarded-Host` on the upstream request by adding them to its
+/// header `overrides`, which replace the client's own. A header a plugin
+/// already set is left alone.
+///
+/// `X-Forwarded-For` gains `peer` after the incoming chain when the peer
+/// is trusted and is just `peer` otherwise. The incoming proto and host
+/// are kept from trusted peers; otherwise they come from the listener
+/// `scheme` and the original `Host`.
+pub fn add_forwarded_headers(
+    overrides: &mut Vec<(String, String)>,
+    trusted: &TrustedProxies,
+    peer: &str,
+    client_ip: &str,
+    scheme: &str,
+    headers: &[(&str, &str)],
+) {
+    let peer_trusted = trusted.contains(peer);
+    let incoming = |name: &'static str| {
+        headers
+            .iter()
+            .filter(move |(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim())
+            .filter(|v| !v.is_empty())
+    };
+
+    let mut xff = String::new();
+    if peer_trusted {
+        for value in incoming("x-forwarded-for") {
+            xff.push_str(value);
+            xff.push_str(", ");
+        }
+    }
+    xff.push_str(peer);
+    let proto = peer_trusted
+        .then(|| incoming("x-forwarded-proto").next())
+        .flatten()
+        .unwrap_or(scheme);
+    let host = peer_trusted
+        .then(|| incoming("x-forwarded-host").next())
+        .flatten()
+        .or_else(|| incoming("host").next());
+
+    let mut set = |name: &str, value: String| {
+        if !overrides.iter().any(|(k, _)| k.eq_ignore_ascii_case(name)) {
+            overrides.push((name.to_string(), value));
+        }
+    };
+    set("x-forwarded-for", xff);
+    set("x-real-ip", client_ip.to_string());
+    set("x-forwarded-proto", proto.to_string());
+    if let Some(host) = host {
+        set("x-forwarded-host", host.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted(entries: &[&str]) -> TrustedProxies {
+        TrustedProxies::new(&entries.iter().map(|s| s.to_string()).collect::<Vec<_>>())
+    }
+
+    fn forwarded(
+        trusted: &TrustedProxies,
+        peer: &str,
+        headers: &[(&str, &str)],
+    ) -> Vec<(String, String)> {
+        let client = trusted.client_ip(peer, headers);
+        let mut overrides = Vec::new();
+        add_forwarded_headers(&mut overrides, trusted, peer, client, "http", headers);
+        overrides
+    }
+
+    fn header<'a>(overrides: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        overrides
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[test]
+    fn parses_cidrs_and_plain_addresses() {
+        let t = trusted(&["10.0.0.0/8", "192.168.1.1", "::1", "not-an-ip"]);
+        assert!(t.contains("10.1.2.3"));
+        assert!(t.contains("192.168.1.1"));
+        assert!(!t.contains("192.168.1.2"));
+        assert!(t.contains("::1"));
+        assert!(!t.contains("garbage"));
+        assert!(TrustedProxies::default().is_empty());
+    }
+
+    #[test]
+    fn untrusted_peer_is_the_client_whatever_it_claims() {
+        let t = trusted(&["10.0.0.0/8"]);
+        let headers = [("X-Forwarded-For", "1.2.3.4")];
+        assert_eq!(t.client_ip("203.0.113.9", &headers), "203.0.113.9");
+        assert_eq!(
+            TrustedProxies::default().client_ip("203.0.113.9", &headers),
+            "203.0.113.9"
+        );
+    }
+
+    #[test]
+    fn trusted_peer_yields_rightmost_untrusted_hop() {
+        let t = trusted(&["10.0.0.0/8"]);
+        let headers = [
+            ("x-forwarded-for", "6.6.6.6, 198.51.100.7"),
+            ("x-forwarded-for", "10.0.0.2"),
+        ];
+        assert_eq!(t.client_ip("10.0.0.1", &headers), "198.51.100.7");
+        // Every hop trusted: the leftmost one is as far as we can see.
+        let headers = [("x-forwarded-for", "10.9.9.9, 10.0.0.2")];
+        assert_eq!(t.client_ip("10.0.0.1", &headers), "10.9.9.9");
+        // No header: the proxy itself.
+        assert_eq!(t.client_ip("10.0.0.1", &[]), "10.0.0.1");
+    }
+
+    #[test]
+    fn malformed_hop_stops_at_the_last_trusted_one() {
+        let t = trusted(&["10.0.0.0/8"]);
+        let headers = [("x-forwarded-for", "1.2.3.4, unknown, 10.0.0.2")];
+        assert_eq!(t.client_ip("10.0.0.1", &headers), "10.0.0.2");
+    }
+
+    #[test]
+    fn spoofed_headers_from_untrusted_peer_are_replaced() {
+        let t = trusted(&["10.0.0.0/8"]);
+        let headers = [
+            ("host", "api.example.com"),
+            ("x-forwarded-for", "1.2.3.4"),
+            ("x-real-ip", "1.2.3.4"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "evil.example.com"),
+        ];
+        let out = forwarded(&t, "203.0.113.9", &headers);
+        assert_eq!(header(&out, "x-forwarded-for"), Some("203.0.113.9"));
+        assert_eq!(header(&out, "x-real-ip"), Some("203.0.113.9"));
+        assert_eq!(header(&out, "x-forwarded-proto"), Some("http"));
+        assert_eq!(header(&out, "x-forwarded-host"), Some("api.example.com"));
+    }
+
+    #[test]
+    fn trusted_peer_chain_is_appended_to() {
+        let t = trusted(&["10.0.0.0/8"]);
+        let headers = [
+            ("host", "internal:9080"),
+            ("x-forwarded-for", "198.51.100.7"),
+            ("x-forwarded-proto", "https"),
+            ("x-forwarded-host", "api.example.com"),
+        ];
+        let out = forwarded(&t, "10.0.0.1", &headers);
+        assert_eq!(
+            header(&out, "x-forwarded-for"),
+            Some("198.51.100.7, 10.0.0.1")
+        );
+        assert_eq!(header(&out, "x-real-ip"), Some("198.51.100.7"));
+        assert_eq!(header(&out, "x-forwarded-proto"), Some("https"));
+        assert_eq!(header(&out, "x-forwarded-host"), Some("api.example.com"));
+    }
+
+    #[test]
+    fn plugin_set_headers_win() {
+        let mut overrides = vec![("X-Real-IP".to_string(), "plugin".to_string())];
+        add_forwarded_headers(
+            &mut overrides,
+            &TrustedProxies::default(),
+            "127.0.0.1",
+            "127.0.0.1",
+            "https",
+            &[],
+        );
+        assert_eq!(header(&overrides, "X-Real-IP"), Some("plugin"));
+        assert_eq!(header(&overrides, "x-real-ip"), None);
+        assert_eq!(header(&overrides, "x-forwarded-proto"), Some("https"));
+        assert_eq!(header(&overrides, "x-forwarded-host"), None);
+    }
+}
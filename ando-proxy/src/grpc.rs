@@ -0,0 +1,435 @@
+//! gRPC upstreams.
+//!
+//! v2 design: A route whose upstream scheme is `grpc` or `grpcs` is
+//! forwarded over HTTP/2 rather than the pooled HTTP/1.1 connections,
+//! with one multiplexed connection per node and worker kept in
+//! `ConnPool`. Such requests come from h2 clients (see `crate::h2`).
+//! `application/grpc` request bodies are streamed, never buffered, so
+//! streaming calls work and body plugins never see gRPC frames.
+//!
+//! Response data and trailers are relayed as they arrive. The status
+//! recorded for metrics and the access log is the HTTP equivalent of
+//! `grpc-status`, since gRPC answers 200 even for failed calls. An
+//! upstream that cannot be reached yields a trailers-only response with
+//! `grpc-status: 14` (UNAVAILABLE) instead of an HTTP error body that a
+//! gRPC client cannot parse.
+
+use crate::connection::{ClientAddrs, connect_upstream, report_upstream, upstream_failed};
+use crate::h2::{CONNECTION_HEADERS, send_data};
+use crate::proxy::{ConnPool, Exchange, ProxyWorker, Retry, UpstreamTarget, UpstreamTimeouts};
+use ando_core::upstream::{PassiveHealthCheck, RetryOn};
+use ando_store::health::UpstreamFailure;
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{HeaderMap, Request, Response};
+use monoio_http::h2::client::{self, SendRequest};
+use monoio_http::h2::server::SendResponse;
+use monoio_http::h2::{RecvStream, SendStream};
+use std::cell::RefCell;
+use std::io::{Error, ErrorKind};
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// `grpc-status` for a failed call: DEADLINE_EXCEEDED.
+const DEADLINE_EXCEEDED: u8 = 4;
+/// `grpc-status` for a failed call: UNAVAILABLE.
+const UNAVAILABLE: u8 = 14;
+
+/// Whether `content_type` marks a gRPC request (`application/grpc`,
+/// optionally `+proto` or another codec suffix).
+pub fn is_grpc_content_type(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    essence
+        .get(..16)
+        .is_some_and(|p| p.eq_ignore_ascii_case("application/grpc"))
+        && matches!(essence.as_bytes().get(16), None | Some(b'+'))
+}
+
+/// HTTP status equivalent to a `grpc-status` code, as recorded for
+/// metrics and the access log (the mapping used by grpc-gateway).
+pub fn grpc_http_status(code: u8) -> u16 {
+    match code {
+        0 => 200,
+        1 => 499,
+        3 | 9 | 11 => 400,
+        4 => 504,
+        5 => 404,
+        6 | 10 => 409,
+        7 => 403,
+        8 => 429,
+        12 => 501,
+        14 => 503,
+        16 => 401,
+        _ => 500,
+    }
+}
+
+/// `grpc-status` in a header or trailer block.
+fn grpc_status(headers: &HeaderMap) -> Option<u8> {
+    headers
+        .get("grpc-status")?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Body of a request to a gRPC upstream.
+pub(crate) enum RequestBody {
+    /// Read in full before routing (not an `application/grpc` request).
+    Buffered(Vec<u8>),
+    /// Still arriving from the client.
+    Stream(RecvStream),
+}
+
+/// One request to forward.
+pub(crate) struct GrpcRequest<'a> {
+    pub method: &'a str,
+    /// Path and query to send upstream.
+    pub path: &'a str,
+    /// Client headers, with `host` standing for `:authority`.
+    pub headers: &'a [(&'a str, &'a str)],
+    /// Headers set by plugins, replacing client headers of the same name.
+    pub overrides: &'a [(String, String)],
+    pub body: RequestBody,
+}
+
+/// Forward `req` from `client` to a gRPC upstream, starting at `target`,
+/// and relay the response on `respond`. Nodes that refuse the connection
+/// are retried like HTTP/1.1 requests. Returns the status to record;
+/// upstream failures are noted on `exchange`.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn forward(
+    req: GrpcRequest<'_>,
+    mut target: UpstreamTarget,
+    client: &ClientAddrs,
+    mut retry: Option<Box<Retry>>,
+    passive: &Option<Arc<PassiveHealthCheck>>,
+    timeouts: UpstreamTimeouts,
+    proxy: &Rc<RefCell<ProxyWorker>>,
+    exchange: &mut Option<Exchange>,
+    conn_pool: &Rc<RefCell<ConnPool>>,
+    respond: &mut SendResponse<Bytes>,
+) -> u16 {
+    let mut sender = loop {
+        match connect(conn_pool, &target, client, timeouts).await {
+            Ok(sender) => break sender,
+            Err(e) => {
+                tracing::warn!(addr = %target.addr, error = %e, "gRPC upstream connect failed");
+                report_upstream(proxy, &target.addr, passive, Some(UpstreamFailure::Tcp));
+                if let Some(ref mut retry) = retry
+                    && let Some(next) =
+                        proxy
+                            .borrow_mut()
+                            .retry_node(retry, &target.addr, RetryOn::ConnectFailure)
+                {
+                    target = next;
+                    continue;
+                }
+                let code = if e.kind() == ErrorKind::TimedOut {
+                    upstream_failed(exchange, "connect_timeout");
+                    DEADLINE_EXCEEDED
+                } else {
+                    upstream_failed(exchange, "connect");
+                    UNAVAILABLE
+                };
+                return send_error(respond, code, "upstream unavailable");
+            }
+        }
+    };
+
+    let end_of_stream = match req.body {
+        RequestBody::Buffered(ref body) => body.is_empty(),
+        RequestBody::Stream(ref recv) => recv.is_end_stream(),
+    };
+    let request = upstream_request(&req, &target);
+    let (response, send) = match sender.send_request(request, end_of_stream) {
+        Ok(sent) => sent,
+        Err(e) => {
+            tracing::warn!(addr = %target.addr, error = %e, "gRPC upstream request failed");
+            report_upstream(proxy, &target.addr, passive, Some(UpstreamFailure::Tcp));
+            upstream_failed(exchange, "exchange");
+            return send_error(respond, UNAVAILABLE, "upstream unavailable");
+        }
+    };
+    if !end_of_stream {
+        match req.body {
+            RequestBody::Buffered(body) => {
+                let mut send = send;
+                monoio::spawn(async move { send_data(&mut send, Bytes::from(body), true).await });
+            }
+            RequestBody::Stream(recv) => {
+                monoio::spawn(pump(recv, send));
+            }
+        }
+    }
+
+    let response = match monoio::time::timeout(timeouts.read, response).await {
+        Ok(Ok(response)) => response,
+        Ok(Err(e)) => {
+            tracing::warn!(addr = %target.addr, error = %e, "gRPC upstream response failed");
+            report_upstream(proxy, &target.addr, passive, Some(UpstreamFailure::Tcp));
+            upstream_failed(exchange, "exchange");
+            return send_error(respond, UNAVAILABLE, "upstream unavailable");
+        }
+        Err(_) => {
+            tracing::warn!(addr = %target.addr, "gRPC upstream response timed out");
+            report_upstream(proxy, &target.addr, passive, Some(UpstreamFailure::Tcp));
+            upstream_failed(exchange, "timeout");
+            return send_error(respond, DEADLINE_EXCEEDED, "upstream timed out");
+        }
+    };
+    report_upstream(proxy, &target.addr, passive, None);
+    relay(response, respond, exchange).await
+}
+
+/// The shared connection to `target`, opening one when none is usable.
+async fn connect(
+    conn_pool: &Rc<RefCell<ConnPool>>,
+    target: &UpstreamTarget,
+    client: &ClientAddrs,
+    timeouts: UpstreamTimeouts,
+) -> std::io::Result<SendRequest<Bytes>> {
+    let key = match target.tls {
+        Some(ref tls) => tls.pool_key(&target.addr),
+        None => format!("h2c://{}", target.addr),
+    };
+    let key = target.client_key(key, client);
+    let pooled = conn_pool.borrow().h2_sender(&key);
+    if let Some(sender) = pooled {
+        if let Ok(sender) = sender.ready().await {
+            return Ok(sender);
+        }
+        conn_pool.borrow_mut().remove_h2_sender(&key);
+    }
+
+    let stream = connect_upstream(target, client, timeouts.connect).await?;
+    let (sender, connection) =
+        match monoio::time::timeout(timeouts.connect, client::handshake(stream)).await {
+            Ok(Ok(handshake)) => handshake,
+            Ok(Err(e)) => return Err(Error::other(e)),
+            Err(_) => return Err(ErrorKind::TimedOut.into()),
+        };
+    let addr = target.addr.clone();
+    monoio::spawn(async move {
+        if let Err(e) = connection.await {
+            tracing::debug!(addr = %addr, error = %e, "gRPC upstream connection closed");
+        }
+    });
+    let sender = sender.ready().await.map_err(Error::other)?;
+    conn_pool.borrow_mut().put_h2_sender(key, sender.clone());
+    Ok(sender)
+}
+
+/// The upstream request head: client headers minus connection-specific
+/// ones, plugin overrides on top, and the authority from `pass_host`,
+/// a plugin or the client, in that order of precedence.
+fn upstream_request(req: &GrpcRequest<'_>, target: &UpstreamTarget) -> Request<()> {
+    let override_host = req
+        .overrides
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+        .map(|(_, value)| value.as_str());
+    let client_host = req
+        .headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("host"))
+        .map(|(_, value)| *value);
+    let authority = override_host
+        .or(target.host.as_deref())
+        .or(client_host)
+        .unwrap_or(&target.addr);
+    let scheme = if target.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+
+    let mut request = Request::builder()
+        .method(req.method)
+        .uri(format!("{scheme}://{authority}{}", req.path))
+        .body(())
+        .unwrap_or_default();
+    let headers = request.headers_mut();
+    let client = req.headers.iter().copied().filter(|(name, _)| {
+        !name.eq_ignore_ascii_case("host")
+            && !req
+                .overrides
+                .iter()
+                .any(|(o, _)| name.eq_ignore_ascii_case(o))
+    });
+    let overrides = req
+        .overrides
+        .iter()
+        .filter(|(name, _)| !name.eq_ignore_ascii_case("host"))
+        .map(|(name, value)| (name.as_str(), value.as_str()));
+    for (name, value) in client.chain(overrides) {
+        if CONNECTION_HEADERS
+            .iter()
+            .any(|n| name.eq_ignore_ascii_case(n))
+        {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.to_ascii_lowercase().as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    request
+}
+
+/// Copy the client's request body and trailers to the upstream stream.
+async fn pump(mut recv: RecvStream, mut send: SendStream<Bytes>) {
+    while let Some(chunk) = recv.data().await {
+        let Ok(chunk) = chunk else {
+            send.send_reset(monoio_http::h2::Reason::CANCEL);
+            return;
+        };
+        let _ = recv.flow_control().release_capacity(chunk.len());
+        if !send_data(&mut send, chunk, false).await {
+            return;
+        }
+    }
+    match recv.trailers().await {
+        Ok(Some(trailers)) => {
+            let _ = send.send_trailers(trailers);
+        }
+        Ok(None) => {
+            let _ = send.send_data(Bytes::new(), true);
+        }
+        Err(_) => send.send_reset(monoio_http::h2::Reason::CANCEL),
+    }
+}
+
+/// Relay the upstream response: head, data as it arrives, then the
+/// trailers. Returns the status to record.
+async fn relay(
+    response: Response<RecvStream>,
+    respond: &mut SendResponse<Bytes>,
+    exchange: &mut Option<Exchange>,
+) -> u16 {
+    let (mut parts, mut recv) = response.into_parts();
+    for name in CONNECTION_HEADERS {
+        parts.headers.remove(*name);
+    }
+    let http_status = parts.status.as_u16();
+    // Trailers-only response: grpc-status is in the head.
+    let head_status = grpc_status(&parts.headers);
+    let end_of_stream = recv.is_end_stream();
+    let Ok(mut send) = respond.send_response(Response::from_parts(parts, ()), end_of_stream) else {
+        return 499;
+    };
+    if end_of_stream {
+        return head_status.map_or(http_status, grpc_http_status);
+    }
+
+    while let Some(chunk) = recv.data().await {
+        let Ok(chunk) = chunk else {
+            upstream_failed(exchange, "truncated");
+            send.send_reset(monoio_http::h2::Reason::INTERNAL_ERROR);
+            return 502;
+        };
+        let _ = recv.flow_control().release_capacity(chunk.len());
+        if !send_data(&mut send, chunk, false).await {
+            // The client went away; dropping `recv` cancels upstream.
+            return 499;
+        }
+    }
+    match recv.trailers().await {
+        Ok(Some(trailers)) => {
+            let status = grpc_status(&trailers).or(head_status);
+            let _ = send.send_trailers(trailers);
+            status.map_or(http_status, grpc_http_status)
+        }
+        Ok(None) => {
+            let _ = send.send_data(Bytes::new(), true);
+            head_status.map_or(http_status, grpc_http_status)
+        }
+        Err(_) => {
+            upstream_failed(exchange, "truncated");
+            send.send_reset(monoio_http::h2::Reason::INTERNAL_ERROR);
+            502
+        }
+    }
+}
+
+/// Answer with a trailers-only gRPC error. Returns the status to record.
+fn send_error(respond: &mut SendResponse<Bytes>, code: u8, message: &str) -> u16 {
+    let mut resp = Response::new(());
+    let headers = resp.headers_mut();
+    headers.insert(
+        http::header::CONTENT_TYPE,
+        HeaderValue::from_static("application/grpc"),
+    );
+    headers.insert("grpc-status", HeaderValue::from(u16::from(code)));
+    if let Ok(message) = HeaderValue::from_str(message) {
+        headers.insert("grpc-message", message);
+    }
+    let _ = respond.send_response(resp, true);
+    grpc_http_status(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grpc_content_types() {
+        assert!(is_grpc_content_type("application/grpc"));
+        assert!(is_grpc_content_type("application/grpc+proto"));
+        assert!(is_grpc_content_type("Application/GRPC; charset=utf-8"));
+        assert!(!is_grpc_content_type("application/grpc-web"));
+        assert!(!is_grpc_content_type("application/json"));
+        assert!(!is_grpc_content_type(""));
+    }
+
+    #[test]
+    fn grpc_status_maps_to_http_status() {
+        assert_eq!(grpc_http_status(0), 200);
+        assert_eq!(grpc_http_status(UNAVAILABLE), 503);
+        assert_eq!(grpc_http_status(DEADLINE_EXCEEDED), 504);
+        assert_eq!(grpc_http_status(16), 401);
+        assert_eq!(grpc_http_status(2), 500);
+    }
+
+    #[test]
+    fn upstream_request_prefers_pass_host_and_drops_connection_headers() {
+        let headers = [
+            ("host", "client.example.com"),
+            ("te", "trailers"),
+            ("connection", "keep-alive"),
+            ("content-type", "application/grpc"),
+            ("x-user", "client"),
+        ];
+        let overrides = [("x-user".to_string(), "plugin".to_string())];
+        let req = GrpcRequest {
+            method: "POST",
+            path: "/pkg.Svc/Call",
+            headers: &headers,
+            overrides: &overrides,
+            body: RequestBody::Buffered(Vec::new()),
+        };
+        let mut target = UpstreamTarget {
+            addr: "10.0.0.1:50051".into(),
+            tls: None,
+            host: None,
+            proxy_protocol: None,
+        };
+        let request = upstream_request(&req, &target);
+        assert_eq!(
+            request.uri().to_string(),
+            "http://client.example.com/pkg.Svc/Call"
+        );
+        assert_eq!(request.headers()["te"], "trailers");
+        assert_eq!(request.headers()["x-user"], "plugin");
+        assert!(request.headers().get("connection").is_none());
+        assert!(request.headers().get("host").is_none());
+
+        target.host = Some("backend.internal".into());
+        let request = upstream_request(&req, &target);
+        assert_eq!(request.uri().authority().unwrap(), "backend.internal");
+    }
+}
@@ -0,0 +1,657 @@
+//! HTTP/2 for downstream clients.
+//!
+//! v2 design: h2 is offered by ALPN on the TLS listener when
+//! `proxy.enable_http2` is set. Every stream is routed exactly like an
+//! HTTP/1.1 request: `:authority` stands in for the Host header, and a
+//! `host` header is synthesized from it so plugins and the upstream see
+//! one. Upstreams are still spoken to over pooled HTTP/1.1 connections.
+//! Request bodies are buffered before routing, as plugins expect, except
+//! for gRPC; upstream response bodies are relayed as they arrive, within
+//! h2 flow control. Routes to `grpc`/`grpcs` upstreams go through
+//! `crate::grpc` instead.
+
+use crate::chunked::{ChunkedDecoder, is_chunked};
+use crate::connection::{
+    ClientAddrs, Failure, InFlight, error_response, exchange_error_kind, finish_exchange,
+    is_event_stream, read_within, record_exchange, report_upstream, send_request, static_status,
+    status_failure, upstream_error, upstream_failed,
+};
+use crate::grpc::{self, GrpcRequest, RequestBody, is_grpc_content_type};
+use crate::hop_by_hop::{HopByHop, lists_close};
+use crate::proxy::{
+    ConnPool, Exchange, ProxyWorker, RESP_413, RESP_502, Rejection, RequestResult, ResponsePlugins,
+    UpstreamStream, UpstreamTarget, build_upstream_request,
+};
+use crate::tls::ClientCert;
+use ando_core::upstream::RetryOn;
+use ando_store::health::UpstreamFailure;
+use bytes::Bytes;
+use http::header::{HeaderName, HeaderValue};
+use http::{Request, Response, StatusCode};
+use monoio::buf::IoBufMut;
+use monoio::io::{AsyncReadRent, AsyncWriteRent};
+use monoio_http::h2::server::{self, SendResponse};
+use monoio_http::h2::{RecvStream, SendStream};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Largest request body buffered from one stream.
+const MAX_REQUEST_BODY: usize = 8 * 1024 * 1024;
+
+/// Headers that only mean something to one HTTP/1.1 connection; h2
+/// forbids them.
+pub(crate) const CONNECTION_HEADERS: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Serve an h2 connection: accept streams and handle each on its own task
+/// until the client goes away.
+pub(crate) async fn serve_h2<S>(
+    io: S,
+    addrs: ClientAddrs,
+    listener: Rc<str>,
+    client_cert: Option<Rc<ClientCert>>,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) -> anyhow::Result<()>
+where
+    S: AsyncReadRent + AsyncWriteRent + Unpin + 'static,
+{
+    let limits = proxy.borrow().request_limits();
+    let mut builder = server::Builder::new();
+    builder.max_header_list_size(limits.max_header_size.try_into().unwrap_or(u32::MAX));
+    let mut conn = builder.handshake::<S, Bytes>(io).await?;
+    let client_ip: Rc<str> = addrs.peer.ip().to_string().into();
+    while let Some(stream) = conn.accept().await {
+        let (req, respond) = stream?;
+        monoio::spawn(serve_stream(
+            req,
+            respond,
+            addrs,
+            Rc::clone(&client_ip),
+            Rc::clone(&listener),
+            client_cert.clone(),
+            Rc::clone(&proxy),
+            Rc::clone(&conn_pool),
+        ));
+    }
+    Ok(())
+}
+
+/// Route and answer one request stream.
+#[allow(clippy::too_many_arguments)]
+async fn serve_stream(
+    req: Request<RecvStream>,
+    mut respond: SendResponse<Bytes>,
+    addrs: ClientAddrs,
+    client_ip: Rc<str>,
+    listener: Rc<str>,
+    client_cert: Option<Rc<ClientCert>>,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+) {
+    let _in_flight = InFlight::new(&proxy);
+    let (parts, mut recv) = req.into_parts();
+
+    // ── Request body ──
+    // gRPC bodies are streamed to the upstream after routing, so plugins
+    // see an empty body for them.
+    let limits = proxy.borrow().request_limits();
+    let max_body = limits.max_body_size.map_or(MAX_REQUEST_BODY, |max| {
+        MAX_REQUEST_BODY.min(max.try_into().unwrap_or(usize::MAX))
+    });
+    let grpc_request = parts
+        .headers
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_grpc_content_type);
+    let mut body = Vec::new();
+    if !grpc_request {
+        match read_body(&mut recv, &mut respond, max_body).await {
+            Some(read) => body = read,
+            None => return,
+        }
+    }
+
+    // ── Request head ──
+    let mut owned: Vec<(String, String)> = parts
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    if !owned.iter().any(|(name, _)| name == "host")
+        && let Some(authority) = parts.uri.authority()
+    {
+        owned.push(("host".to_string(), authority.to_string()));
+    }
+    let mut headers: Vec<(&str, &str)> = owned
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.as_str()))
+        .collect();
+    let host = headers
+        .iter()
+        .find(|(name, _)| *name == "host")
+        .map(|(_, value)| *value);
+    let method = parts.method.as_str();
+    let path = parts.uri.path_and_query().map_or("/", |p| p.as_str());
+
+    // ── Process request (brief RefCell borrow, NO await) ──
+    let started = Instant::now();
+    let (result, mut exchange) = {
+        let mut pw = proxy.borrow_mut();
+        let result = pw.handle_request_with_scheme(
+            "https",
+            &listener,
+            client_cert.as_deref(),
+            method,
+            path,
+            host,
+            &headers,
+            &client_ip,
+            &body,
+        );
+        (result, Some(pw.exchange(started)))
+    };
+
+    match result {
+        RequestResult::Static(resp) => {
+            record_exchange(&proxy, &mut exchange, method, static_status(resp));
+            send_static(&mut respond, resp).await;
+        }
+        RequestResult::PluginResponse {
+            status,
+            headers,
+            body,
+        } => {
+            record_exchange(&proxy, &mut exchange, method, status);
+            send_full(&mut respond, status, &headers, body).await;
+        }
+        RequestResult::Proxy {
+            upstream_addr,
+            upstream_path,
+            passive,
+            tls,
+            upstream_host,
+            proxy_protocol,
+            grpc,
+            streaming,
+            timeouts,
+            mut retry,
+            mut upstream_headers,
+            removed_headers,
+            mut response_headers,
+            pending_access,
+            mut response_plugins,
+            request_body,
+            ..
+        } => {
+            // A body decoded for `request_decompression` is sent instead,
+            // without its content-encoding.
+            if let Some(decoded) = request_body {
+                body = decoded;
+                headers.retain(|(name, _)| !name.eq_ignore_ascii_case("content-encoding"));
+            }
+            // Headers a plugin removed are not forwarded.
+            headers
+                .retain(|(name, _)| !removed_headers.iter().any(|r| name.eq_ignore_ascii_case(r)));
+            if let Some(pending) = pending_access {
+                match pending.run().await {
+                    Ok(admitted) => admitted.apply(&mut upstream_headers, &mut response_headers),
+                    Err(Rejection {
+                        plugin,
+                        status,
+                        headers,
+                        body,
+                    }) => {
+                        proxy
+                            .borrow_mut()
+                            .record_plugin_response(Some(&plugin), status);
+                        record_exchange(&proxy, &mut exchange, method, status);
+                        send_full(&mut respond, status, &headers, body).await;
+                        return;
+                    }
+                }
+            }
+
+            // Nodes that refuse the connection are retried like HTTP/1.1
+            // requests; see `ProxyWorker::retry_node`.
+            let mut target = UpstreamTarget {
+                addr: upstream_addr,
+                tls,
+                host: upstream_host,
+                proxy_protocol,
+            };
+            if grpc {
+                let req = GrpcRequest {
+                    method,
+                    path: &upstream_path,
+                    headers: &headers,
+                    overrides: &upstream_headers,
+                    body: if grpc_request {
+                        RequestBody::Stream(recv)
+                    } else {
+                        RequestBody::Buffered(body)
+                    },
+                };
+                let status = grpc::forward(
+                    req,
+                    target,
+                    &addrs,
+                    retry,
+                    &passive,
+                    timeouts,
+                    &proxy,
+                    &mut exchange,
+                    &conn_pool,
+                    &mut respond,
+                )
+                .await;
+                finish_exchange(&proxy, &mut exchange, method, &mut response_plugins, status);
+                return;
+            }
+            if grpc_request {
+                match read_body(&mut recv, &mut respond, max_body).await {
+                    Some(read) => body = read,
+                    None => return,
+                }
+            }
+            let mut request = Vec::with_capacity(2048);
+            let (upstream, pool_key) = loop {
+                build_upstream_request(
+                    &mut request,
+                    method,
+                    &upstream_path,
+                    &headers,
+                    &upstream_headers,
+                    target.host.as_deref(),
+                    &body,
+                );
+                let pool_key = target.pool_key(&addrs);
+                match send_request(&conn_pool, &pool_key, &target, &addrs, &request, timeouts).await
+                {
+                    Ok(upstream) => break (upstream, pool_key),
+                    Err(failure) => {
+                        tracing::warn!(addr = %target.addr, error = %failure, "Upstream request failed");
+                        report_upstream(&proxy, &target.addr, &passive, Some(UpstreamFailure::Tcp));
+                        if matches!(failure, Failure::Connect(_))
+                            && let Some(ref mut retry) = retry
+                            && let Some(next) = proxy.borrow_mut().retry_node(
+                                retry,
+                                &target.addr,
+                                RetryOn::ConnectFailure,
+                            )
+                        {
+                            target = next;
+                            continue;
+                        }
+                        let (status, resp) = upstream_error(failure.error());
+                        let resp = error_response(&exchange, status, resp);
+                        upstream_failed(&mut exchange, failure.kind());
+                        finish_exchange(
+                            &proxy,
+                            &mut exchange,
+                            method,
+                            &mut response_plugins,
+                            status,
+                        );
+                        send_static(&mut respond, &resp).await;
+                        return;
+                    }
+                }
+            };
+
+            let relay = Relay {
+                proxy: &proxy,
+                exchange: &mut exchange,
+                method,
+                response_plugins: &mut response_plugins,
+                response_headers: &response_headers,
+                read_timeout: timeouts.read,
+                streaming,
+                streaming_idle_timeout: proxy.borrow().streaming_idle_timeout(),
+            };
+            let reusable = relay.run(upstream, &target, &passive, &mut respond).await;
+            if let Some(upstream) = reusable {
+                conn_pool.borrow_mut().put(pool_key, upstream);
+            }
+        }
+    }
+}
+
+/// Read a request body of at most `max` bytes. Answers 413 past that;
+/// `None` when the stream is over.
+async fn read_body(
+    recv: &mut RecvStream,
+    respond: &mut SendResponse<Bytes>,
+    max: usize,
+) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    while let Some(chunk) = recv.data().await {
+        let chunk = chunk.ok()?;
+        let _ = recv.flow_control().release_capacity(chunk.len());
+        if body.len() + chunk.len() > max {
+            send_static(respond, RESP_413).await;
+            return None;
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Some(body)
+}
+
+/// What relaying one upstream response needs from the stream's state.
+struct Relay<'a> {
+    proxy: &'a Rc<RefCell<ProxyWorker>>,
+    exchange: &'a mut Option<Exchange>,
+    method: &'a str,
+    response_plugins: &'a mut Option<Box<ResponsePlugins>>,
+    response_headers: &'a [(String, String)],
+    read_timeout: std::time::Duration,
+    /// The route asks for streaming; SSE responses stream regardless.
+    streaming: bool,
+    streaming_idle_timeout: std::time::Duration,
+}
+
+impl Relay<'_> {
+    /// Read the upstream response and send it on `respond`. Returns the
+    /// upstream connection when it can be reused.
+    async fn run(
+        self,
+        mut upstream: UpstreamStream,
+        target: &UpstreamTarget,
+        passive: &Option<Arc<ando_core::upstream::PassiveHealthCheck>>,
+        respond: &mut SendResponse<Bytes>,
+    ) -> Option<UpstreamStream> {
+        let Relay {
+            proxy,
+            exchange,
+            method,
+            response_plugins,
+            response_headers,
+            read_timeout,
+            mut streaming,
+            streaming_idle_timeout,
+        } = self;
+
+        // ── Response head, possibly over several reads ──
+        let mut buf: Vec<u8> = Vec::with_capacity(65536);
+        let (status, mut headers, hdr_len, content_length, chunked, keepalive) = loop {
+            let (len, cap) = (buf.len(), buf.capacity());
+            let read = read_within(&mut upstream, buf.slice_mut(len..cap), read_timeout).await;
+            match read {
+                Ok((n, returned)) if n > 0 => buf = returned.into_inner(),
+                Ok(_) => {
+                    upstream_failed(exchange, "exchange");
+                    return fail(proxy, exchange, method, response_plugins, respond, RESP_502)
+                        .await;
+                }
+                Err(e) => {
+                    tracing::warn!(addr = %target.addr, error = %e, "Upstream request failed");
+                    report_upstream(proxy, &target.addr, passive, Some(UpstreamFailure::Tcp));
+                    let (_, resp) = upstream_error(&e);
+                    upstream_failed(exchange, exchange_error_kind(&e));
+                    return fail(proxy, exchange, method, response_plugins, respond, resp).await;
+                }
+            }
+            let mut raw = [httparse::EMPTY_HEADER; 64];
+            let mut resp = httparse::Response::new(&mut raw);
+            match resp.parse(&buf) {
+                Ok(httparse::Status::Complete(hdr_len)) => {
+                    let status = resp.code.unwrap_or(502);
+                    let mut content_length = None;
+                    let mut chunked = false;
+                    let mut keepalive = true;
+                    for h in resp.headers.iter() {
+                        let value = std::str::from_utf8(h.value).unwrap_or("");
+                        if h.name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse::<usize>().ok();
+                        } else if h.name.eq_ignore_ascii_case("transfer-encoding") {
+                            chunked = is_chunked(value);
+                        } else if h.name.eq_ignore_ascii_case("connection") {
+                            keepalive = !lists_close(value);
+                        } else if h.name.eq_ignore_ascii_case("content-type") {
+                            streaming |= is_event_stream(h.value);
+                        }
+                    }
+                    let headers = response_header_list(resp.headers);
+                    break (status, headers, hdr_len, content_length, chunked, keepalive);
+                }
+                Ok(httparse::Status::Partial) if buf.len() < buf.capacity() => continue,
+                _ => {
+                    upstream_failed(exchange, "invalid_response");
+                    return fail(proxy, exchange, method, response_plugins, respond, RESP_502)
+                        .await;
+                }
+            }
+        };
+        report_upstream(
+            proxy,
+            &target.addr,
+            passive,
+            status_failure(passive, status),
+        );
+
+        if let Some(plugins) = response_plugins.as_mut().filter(|p| p.filters_headers()) {
+            headers = plugins.filter_headers(status, headers);
+        }
+
+        let has_body = method != "HEAD" && !matches!(status, 100..=199 | 204 | 304);
+        let content_length = if chunked { None } else { content_length };
+        let first = buf[hdr_len..].to_vec();
+        let body_timeout = if streaming {
+            streaming_idle_timeout
+        } else {
+            read_timeout
+        };
+
+        // ── Body-filter plugins need the whole body ──
+        if let Some(filter) = response_plugins.as_mut().filter(|f| {
+            f.filters_body()
+                && !streaming
+                && has_body
+                && content_length.is_some_and(|cl| cl <= f.max_body)
+        }) {
+            headers.retain(|(name, _)| name != "content-length");
+            let cl = content_length.unwrap_or(0);
+            let mut body = first;
+            while body.len() < cl {
+                let want = (cl - body.len()).min(65536);
+                let len = body.len();
+                body.reserve(want);
+                match read_within(&mut upstream, body.slice_mut(len..len + want), read_timeout)
+                    .await
+                {
+                    Ok((n, returned)) if n > 0 => body = returned.into_inner(),
+                    _ => {
+                        tracing::warn!(addr = %target.addr, "Upstream body truncated");
+                        upstream_failed(exchange, "truncated");
+                        return fail(proxy, exchange, method, response_plugins, respond, RESP_502)
+                            .await;
+                    }
+                }
+            }
+            body.truncate(cl);
+            let (status, headers, body) = filter.apply(status, headers, response_headers, body);
+            send_full(respond, status, &headers, body).await;
+            finish_exchange(proxy, exchange, method, response_plugins, status);
+            return keepalive.then_some(upstream);
+        }
+
+        // ── Relay as it arrives ──
+        headers.extend(response_headers.iter().cloned());
+        let Ok(mut stream) = respond.send_response(response(status, &headers), !has_body) else {
+            return None;
+        };
+        if !has_body {
+            finish_exchange(proxy, exchange, method, response_plugins, status);
+            return keepalive.then_some(upstream);
+        }
+
+        // Recorded once the body has been relayed, so a body cut short is
+        // counted.
+        let mut decoder = chunked.then(ChunkedDecoder::new);
+        let mut remaining = content_length;
+        let mut data = first;
+        let reusable = loop {
+            // Turn what was read into body bytes; `done` once the
+            // message is complete.
+            let (out, done) = match (&mut decoder, &mut remaining) {
+                (Some(decoder), _) => {
+                    let mut out = Vec::new();
+                    let Ok(used) = decoder.feed(&data, Some(&mut out)) else {
+                        tracing::warn!(addr = %target.addr, "Upstream chunked body malformed");
+                        upstream_failed(exchange, "truncated");
+                        stream.send_reset(monoio_http::h2::Reason::INTERNAL_ERROR);
+                        break None;
+                    };
+                    debug_assert!(used <= data.len());
+                    (out, decoder.is_done())
+                }
+                (None, Some(left)) => {
+                    data.truncate(*left);
+                    *left -= data.len();
+                    (std::mem::take(&mut data), *left == 0)
+                }
+                (None, None) => (std::mem::take(&mut data), false),
+            };
+            if !send_data(&mut stream, Bytes::from(out), done).await {
+                break None;
+            }
+            if done {
+                break keepalive.then_some(upstream);
+            }
+            match read_within(&mut upstream, vec![0u8; 65536], body_timeout).await {
+                Ok((n, mut chunk)) if n > 0 => {
+                    chunk.truncate(n);
+                    data = chunk;
+                }
+                // Without framing the body ends when the upstream closes.
+                Ok(_) if decoder.is_none() && remaining.is_none() => {
+                    send_data(&mut stream, Bytes::new(), true).await;
+                    break None;
+                }
+                _ => {
+                    tracing::warn!(addr = %target.addr, "Upstream body truncated");
+                    upstream_failed(exchange, "truncated");
+                    stream.send_reset(monoio_http::h2::Reason::INTERNAL_ERROR);
+                    break None;
+                }
+            }
+        };
+        finish_exchange(proxy, exchange, method, response_plugins, status);
+        reusable
+    }
+}
+
+/// Answer with a canned response after the upstream exchange failed.
+async fn fail(
+    proxy: &Rc<RefCell<ProxyWorker>>,
+    exchange: &mut Option<Exchange>,
+    method: &str,
+    response_plugins: &mut Option<Box<ResponsePlugins>>,
+    respond: &mut SendResponse<Bytes>,
+    resp: &'static [u8],
+) -> Option<UpstreamStream> {
+    let status = static_status(resp);
+    let resp = error_response(exchange, status, resp);
+    finish_exchange(proxy, exchange, method, response_plugins, status);
+    send_static(respond, &resp).await;
+    None
+}
+
+/// Upstream response headers that may travel over h2.
+fn response_header_list(headers: &[httparse::Header<'_>]) -> Vec<(String, String)> {
+    let hop = HopByHop::of_response(headers);
+    headers
+        .iter()
+        .take_while(|h| !h.name.is_empty())
+        .filter(|h| !hop.drops(h.name))
+        .map(|h| {
+            (
+                h.name.to_ascii_lowercase(),
+                String::from_utf8_lossy(h.value).into_owned(),
+            )
+        })
+        .collect()
+}
+
+/// An h2 response head. Headers h2 cannot carry are dropped.
+fn response(status: u16, headers: &[(String, String)]) -> Response<()> {
+    let mut resp = Response::new(());
+    *resp.status_mut() = StatusCode::from_u16(status).unwrap_or(StatusCode::BAD_GATEWAY);
+    for (name, value) in headers {
+        if CONNECTION_HEADERS
+            .iter()
+            .any(|n| name.eq_ignore_ascii_case(n))
+        {
+            continue;
+        }
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            resp.headers_mut().append(name, value);
+        }
+    }
+    resp
+}
+
+/// Send a complete response with a content-length.
+async fn send_full(
+    respond: &mut SendResponse<Bytes>,
+    status: u16,
+    headers: &[(String, String)],
+    body: Vec<u8>,
+) {
+    let mut resp = response(status, headers);
+    resp.headers_mut().remove(http::header::CONTENT_LENGTH);
+    resp.headers_mut()
+        .insert(http::header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    if let Ok(mut stream) = respond.send_response(resp, body.is_empty())
+        && !body.is_empty()
+    {
+        send_data(&mut stream, Bytes::from(body), true).await;
+    }
+}
+
+/// Send a pre-built HTTP/1.1 response.
+async fn send_static(respond: &mut SendResponse<Bytes>, resp: &[u8]) {
+    let mut raw = [httparse::EMPTY_HEADER; 16];
+    let mut parsed = httparse::Response::new(&mut raw);
+    let Ok(httparse::Status::Complete(hdr_len)) = parsed.parse(resp) else {
+        respond.send_reset(monoio_http::h2::Reason::INTERNAL_ERROR);
+        return;
+    };
+    let status = parsed.code.unwrap_or(502);
+    let headers = response_header_list(parsed.headers);
+    send_full(respond, status, &headers, resp[hdr_len..].to_vec()).await;
+}
+
+/// Send `data` as flow control allows. False if the client reset the
+/// stream or went away.
+pub(crate) async fn send_data(stream: &mut SendStream<Bytes>, mut data: Bytes, end: bool) -> bool {
+    loop {
+        if data.is_empty() {
+            return !end || stream.send_data(Bytes::new(), true).is_ok();
+        }
+        stream.reserve_capacity(data.len());
+        let capacity = match std::future::poll_fn(|cx| stream.poll_capacity(cx)).await {
+            Some(Ok(capacity)) if capacity > 0 => capacity,
+            Some(Ok(_)) => continue,
+            _ => return false,
+        };
+        let chunk = data.split_to(capacity.min(data.len()));
+        let last = end && data.is_empty();
+        if stream.send_data(chunk, last).is_err() {
+            return false;
+        }
+        if last {
+            return true;
+        }
+    }
+}
@@ -0,0 +1,282 @@
+use crate::connection::resolve_addrs;
+use ando_core::upstream::ActiveHealthCheck;
+use ando_store::cache::ConfigCache;
+use monoio::io::{AsyncReadRent, AsyncWriteRentExt};
+use monoio::net::TcpStream;
+use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// How often the checker wakes up to look for due probes.
+const TICK: Duration = Duration::from_secs(1);
+
+/// Spawn the active health checker on its own thread.
+///
+/// v2 design: Probes run on a dedicated monoio runtime so they never
+/// compete with request handling on worker cores. Results go to the shared
+/// `HealthTable`; workers pick up ejections on their next accept.
+pub fn spawn_health_checker(cache: ConfigCache) -> std::thread::JoinHandle<()> {
+    std::thread::Builder::new()
+        .name("ando-health".to_string())
+        .spawn(move || {
+            let mut rt = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+                .enable_all()
+                .build()
+                .expect("Failed to build monoio runtime for health checker");
+
+            rt.block_on(async move {
+                let mut checker = HealthChecker::new(cache);
+                loop {
+                    checker.run_once().await;
+                    monoio::time::sleep(TICK).await;
+                }
+            });
+        })
+        .expect("Failed to spawn health checker thread")
+}
+
+/// Probes every node of every upstream in `ConfigCache.upstreams` that has
+/// a `checks.active` block, each at its configured interval.
+pub struct HealthChecker {
+    cache: ConfigCache,
+    /// (upstream id, node address) → when the node is next due.
+    next_due: HashMap<(String, String), Instant>,
+}
+
+impl HealthChecker {
+    pub fn new(cache: ConfigCache) -> Self {
+        Self {
+            cache,
+            next_due: HashMap::new(),
+        }
+    }
+
+    /// Run all probes that are due, concurrently, and record the results.
+    pub async fn run_once(&mut self) {
+        let targets = self.targets();
+        self.next_due
+            .retain(|(ups, addr), _| targets.iter().any(|(u, a, _)| u == ups && a == addr));
+        self.cache.health.retain(|ups, addr| {
+            targets
+                .iter()
+                .any(|(u, a, _)| u.as_str() == ups && a.as_str() == addr)
+        });
+
+        let now = Instant::now();
+        let mut probes = Vec::new();
+        for (ups, addr, check) in targets {
+            let key = (ups, addr);
+            if self.next_due.get(&key).is_some_and(|due| *due > now) {
+                continue;
+            }
+            self.next_due.insert(
+                key.clone(),
+                now + Duration::from_secs(check.interval.max(1)),
+            );
+            probes.push(monoio::spawn(async move {
+                let result = probe(&key.1, &check).await;
+                (key, check, result)
+            }));
+        }
+
+        let now_unix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        for handle in probes {
+            let ((ups, addr), check, result) = handle.await;
+            let err = result.as_ref().err().cloned();
+            let flipped = self.cache.health.record(
+                &ups,
+                &addr,
+                result,
+                check.healthy_successes,
+                check.unhealthy_failures,
+                now_unix,
+            );
+            if flipped {
+                match err {
+                    Some(e) => {
+                        warn!(upstream = %ups, node = %addr, error = %e, "Upstream node marked unhealthy")
+                    }
+                    None => info!(upstream = %ups, node = %addr, "Upstream node marked healthy"),
+                }
+            }
+        }
+    }
+
+    /// (upstream id, node address, check config) for every checked node.
+    fn targets(&self) -> Vec<(String, String, ActiveHealthCheck)> {
+        let mut targets = Vec::new();
+        for entry in self.cache.upstreams.iter() {
+            let Some(active) = entry
+                .value()
+                .checks
+                .as_ref()
+                .and_then(|c| c.active.as_ref())
+            else {
+                continue;
+            };
+            for addr in entry.value().nodes.keys() {
+                targets.push((entry.key().clone(), addr.clone(), active.clone()));
+            }
+        }
+        targets
+    }
+}
+
+/// Probe a single node. HTTP probes pass on a 2xx or 3xx status;
+/// TCP probes pass once the connection is established.
+pub async fn probe(addr: &str, check: &ActiveHealthCheck) -> Result<(), String> {
+    let timeout = Duration::from_secs(check.timeout.max(1));
+    match monoio::time::timeout(timeout, probe_inner(addr, check)).await {
+        Ok(result) => result,
+        Err(_) => Err("timeout".to_string()),
+    }
+}
+
+async fn probe_inner(addr: &str, check: &ActiveHealthCheck) -> Result<(), String> {
+    let Some(sa) = resolve_addrs(addr).into_iter().next() else {
+        return Err("address resolve failed".to_string());
+    };
+    let mut stream = TcpStream::connect(sa).await.map_err(|e| e.to_string())?;
+    if check.r#type == "tcp" {
+        return Ok(());
+    }
+
+    let req = format!(
+        "GET {} HTTP/1.1\r\nhost: {}\r\nuser-agent: ando-health-check\r\nconnection: close\r\n\r\n",
+        check.http_path.as_deref().unwrap_or("/"),
+        check.host.as_deref().unwrap_or(addr),
+    );
+    let (res, _) = stream.write_all(req.into_bytes()).await;
+    res.map_err(|e| e.to_string())?;
+
+    let (res, buf) = stream.read(vec![0u8; 1024]).await;
+    let n = res.map_err(|e| e.to_string())?;
+    match parse_status(&buf[..n]) {
+        Some(status) if (200..400).contains(&status) => Ok(()),
+        Some(status) => Err(format!("unhealthy status {status}")),
+        None => Err("invalid HTTP response".to_string()),
+    }
+}
+
+/// Status code from an HTTP/1.x status line.
+fn parse_status(buf: &[u8]) -> Option<u16> {
+    let line = buf.split(|b| *b == b'\n').next()?;
+    let line = std::str::from_utf8(line).ok()?;
+    let mut parts = line.split_ascii_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::upstream::Upstream;
+
+    fn make_rt() -> monoio::Runtime<monoio::time::TimeDriver<monoio::LegacyDriver>> {
+        monoio::RuntimeBuilder::<monoio::LegacyDriver>::new()
+            .enable_timer()
+            .build()
+            .expect("monoio runtime build failed")
+    }
+
+    /// Serve `status` to every connection on an ephemeral port.
+    fn serve_status(status: u16) -> String {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        monoio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let (_, _) = stream.read(vec![0u8; 1024]).await;
+                let resp = format!("HTTP/1.1 {status} X\r\ncontent-length: 0\r\n\r\n");
+                let (_, _) = stream.write_all(resp.into_bytes()).await;
+            }
+        });
+        addr
+    }
+
+    fn checked_upstream(nodes: &[&str]) -> Upstream {
+        let nodes: HashMap<&str, u32> = nodes.iter().map(|n| (*n, 1)).collect();
+        serde_json::from_value(serde_json::json!({
+            "nodes": nodes,
+            "checks": { "active": {
+                "interval": 1, "timeout": 1,
+                "healthy_successes": 1, "unhealthy_failures": 1
+            }}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn parse_status_reads_status_line() {
+        assert_eq!(parse_status(b"HTTP/1.1 204 No Content\r\n"), Some(204));
+        assert_eq!(parse_status(b"HTTP/1.0 503"), Some(503));
+        assert_eq!(parse_status(b"garbage"), None);
+        assert_eq!(parse_status(b""), None);
+    }
+
+    #[test]
+    fn probe_passes_on_2xx_and_fails_on_5xx() {
+        make_rt().block_on(async {
+            let check = checked_upstream(&[]).checks.unwrap().active.unwrap();
+            let ok = serve_status(200);
+            let bad = serve_status(503);
+            assert!(probe(&ok, &check).await.is_ok());
+            let err = probe(&bad, &check).await.unwrap_err();
+            assert!(err.contains("503"), "got {err}");
+        });
+    }
+
+    #[test]
+    fn probe_fails_when_connection_refused() {
+        make_rt().block_on(async {
+            let addr = {
+                let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                l.local_addr().unwrap().to_string()
+            };
+            let check = checked_upstream(&[]).checks.unwrap().active.unwrap();
+            assert!(probe(&addr, &check).await.is_err());
+        });
+    }
+
+    #[test]
+    fn run_once_ejects_failing_node_only() {
+        make_rt().block_on(async {
+            let ok = serve_status(200);
+            let bad = serve_status(500);
+            let cache = ConfigCache::new();
+            cache
+                .upstreams
+                .insert("u1".into(), checked_upstream(&[&ok, &bad]));
+
+            let mut checker = HealthChecker::new(cache.clone());
+            checker.run_once().await;
+
+            assert!(cache.health.is_healthy("u1", &ok));
+            assert!(!cache.health.is_healthy("u1", &bad));
+            assert_eq!(cache.health.version(), 1);
+        });
+    }
+
+    #[test]
+    fn run_once_forgets_removed_upstreams() {
+        make_rt().block_on(async {
+            let bad = serve_status(500);
+            let cache = ConfigCache::new();
+            cache
+                .upstreams
+                .insert("u1".into(), checked_upstream(&[&bad]));
+
+            let mut checker = HealthChecker::new(cache.clone());
+            checker.run_once().await;
+            assert!(!cache.health.is_healthy("u1", &bad));
+
+            cache.upstreams.remove("u1");
+            checker.run_once().await;
+            assert!(cache.health.all().is_empty());
+        });
+    }
+}
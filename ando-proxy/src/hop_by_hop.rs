@@ -0,0 +1,259 @@
+//! Hop-by-hop headers and request framing.
+//!
+//! v2 design: Headers that describe a single connection (RFC 9110 7.6.1)
+//! end at the proxy in both directions: the fixed set below plus any
+//! header a message names in its own `Connection` header. Framing headers
+//! are checked before a request is forwarded, so the upstream can never
+//! read a body boundary differently from the gateway (RFC 9112 6.3):
+//! `Content-Length` together with `Transfer-Encoding`, a transfer coding
+//! other than chunked, or disagreeing lengths are refused with 400, and
+//! repeated lengths that agree collapse into one.
+
+use crate::chunked::is_chunked;
+
+/// Headers that never cross the proxy.
+pub const HOP_BY_HOP: &[&str] = &[
+    "connection",
+    "keep-alive",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Response framing headers that stay on relayed responses: chunked
+/// bodies, trailers included, reach the client byte for byte.
+const RESPONSE_FRAMING: &[&str] = &["transfer-encoding", "trailer"];
+
+/// The hop-by-hop headers of one message.
+#[derive(Debug, Default)]
+pub struct HopByHop<'a> {
+    /// Names listed in the message's `Connection` headers.
+    listed: Vec<&'a str>,
+}
+
+impl<'a> HopByHop<'a> {
+    /// Collect the names listed in the `Connection` headers of `headers`.
+    pub fn new(headers: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let listed = headers
+            .into_iter()
+            .filter(|(name, _)| name.eq_ignore_ascii_case("connection"))
+            .flat_map(|(_, value)| value.split(','))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .collect();
+        Self { listed }
+    }
+
+    /// Like [`HopByHop::new`], for a parsed upstream response.
+    pub fn of_response(headers: &'a [httparse::Header<'a>]) -> Self {
+        Self::new(
+            headers
+                .iter()
+                .take_while(|h| !h.name.is_empty())
+                .map(|h| (h.name, std::str::from_utf8(h.value).unwrap_or(""))),
+        )
+    }
+
+    /// Whether the request header `name` must not be forwarded.
+    pub fn drops(&self, name: &str) -> bool {
+        HOP_BY_HOP.iter().any(|n| name.eq_ignore_ascii_case(n))
+            || self.listed.iter().any(|n| name.eq_ignore_ascii_case(n))
+    }
+
+    /// Whether the response header `name` must not reach the client.
+    /// Framing headers stay; see `RESPONSE_FRAMING`.
+    pub fn drops_from_response(&self, name: &str) -> bool {
+        self.drops(name)
+            && !RESPONSE_FRAMING
+                .iter()
+                .any(|n| name.eq_ignore_ascii_case(n))
+    }
+}
+
+/// Whether a `Connection` value lists `close`.
+pub fn lists_close(connection: &str) -> bool {
+    connection
+        .split(',')
+        .any(|token| token.trim().eq_ignore_ascii_case("close"))
+}
+
+/// How a request head frames its body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BodyFraming {
+    None,
+    Length(usize),
+    Chunked,
+}
+
+/// Why a message's framing was refused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FramingError {
+    /// Both `Content-Length` and `Transfer-Encoding` are present.
+    LengthAndEncoding,
+    /// A transfer coding the gateway cannot find the end of.
+    UnknownEncoding,
+    /// `Content-Length` values that disagree.
+    ConflictingLengths,
+    /// A `Content-Length` that is not a number.
+    InvalidLength,
+}
+
+impl std::fmt::Display for FramingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::LengthAndEncoding => "both content-length and transfer-encoding",
+            Self::UnknownEncoding => "transfer-encoding does not end in chunked",
+            Self::ConflictingLengths => "conflicting content-length values",
+            Self::InvalidLength => "invalid content-length",
+        })
+    }
+}
+
+/// The body framing of a request, or why it is ambiguous.
+pub fn request_framing(headers: &[(&str, &str)]) -> Result<BodyFraming, FramingError> {
+    let mut encodings = headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("transfer-encoding"))
+        .map(|(_, value)| *value)
+        .peekable();
+    if encodings.peek().is_none() {
+        return Ok(
+            content_length(headers.iter().copied())?.map_or(BodyFraming::None, BodyFraming::Length)
+        );
+    }
+    if content_length(headers.iter().copied())?.is_some() {
+        return Err(FramingError::LengthAndEncoding);
+    }
+    // Repeated headers form one list; only its last coding counts.
+    let last = encodings.last().unwrap_or_default();
+    if is_chunked(last) {
+        Ok(BodyFraming::Chunked)
+    } else {
+        Err(FramingError::UnknownEncoding)
+    }
+}
+
+/// The `Content-Length` of a message. Repeated headers and
+/// comma-separated lists are accepted when every value agrees.
+pub fn content_length<'a>(
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Result<Option<usize>, FramingError> {
+    let mut length = None;
+    for (name, value) in headers {
+        if !name.eq_ignore_ascii_case("content-length") {
+            continue;
+        }
+        for item in value.split(',') {
+            let item = item.trim();
+            if item.is_empty() || !item.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(FramingError::InvalidLength);
+            }
+            let n = item.parse().map_err(|_| FramingError::InvalidLength)?;
+            if length.is_some_and(|l| l != n) {
+                return Err(FramingError::ConflictingLengths);
+            }
+            length = Some(n);
+        }
+    }
+    Ok(length)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_fixed_and_connection_listed_headers() {
+        let headers = [
+            ("Connection", "keep-alive, X-Secret"),
+            ("connection", "x-other"),
+        ];
+        let hop = HopByHop::new(headers);
+        for name in ["Proxy-Connection", "TE", "Trailer", "x-secret", "X-Other"] {
+            assert!(hop.drops(name), "{name}");
+        }
+        assert!(!hop.drops("x-forwarded-for"));
+    }
+
+    #[test]
+    fn response_keeps_its_framing() {
+        let hop = HopByHop::new([("connection", "close, x-upstream-only")]);
+        assert!(!hop.drops_from_response("transfer-encoding"));
+        assert!(!hop.drops_from_response("trailer"));
+        assert!(hop.drops_from_response("keep-alive"));
+        assert!(hop.drops_from_response("x-upstream-only"));
+    }
+
+    #[test]
+    fn close_is_found_among_other_tokens() {
+        assert!(lists_close("Close"));
+        assert!(lists_close("x-secret, close"));
+        assert!(!lists_close("keep-alive, x-close"));
+    }
+
+    #[test]
+    fn length_and_encoding_together_is_refused() {
+        let headers = [("content-length", "5"), ("transfer-encoding", "chunked")];
+        assert_eq!(
+            request_framing(&headers),
+            Err(FramingError::LengthAndEncoding)
+        );
+    }
+
+    #[test]
+    fn encoding_must_end_in_chunked() {
+        assert_eq!(
+            request_framing(&[("transfer-encoding", "chunked, gzip")]),
+            Err(FramingError::UnknownEncoding)
+        );
+        // Smuggling payloads often hide the coding behind odd spelling.
+        assert_eq!(
+            request_framing(&[("transfer-encoding", "xchunked")]),
+            Err(FramingError::UnknownEncoding)
+        );
+        assert_eq!(
+            request_framing(&[
+                ("transfer-encoding", "gzip"),
+                ("Transfer-Encoding", "chunked")
+            ]),
+            Ok(BodyFraming::Chunked)
+        );
+    }
+
+    #[test]
+    fn agreeing_lengths_collapse_into_one() {
+        let headers = [("content-length", "5"), ("Content-Length", "5, 5")];
+        assert_eq!(request_framing(&headers), Ok(BodyFraming::Length(5)));
+    }
+
+    #[test]
+    fn disagreeing_lengths_are_refused() {
+        let headers = [("content-length", "5"), ("content-length", "6")];
+        assert_eq!(
+            request_framing(&headers),
+            Err(FramingError::ConflictingLengths)
+        );
+        assert_eq!(
+            request_framing(&[("content-length", "5, 6")]),
+            Err(FramingError::ConflictingLengths)
+        );
+    }
+
+    #[test]
+    fn malformed_lengths_are_refused() {
+        for value in ["", "+5", "-1", "0x10", "5 5", "99999999999999999999999"] {
+            assert_eq!(
+                request_framing(&[("content-length", value)]),
+                Err(FramingError::InvalidLength),
+                "{value:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn no_framing_headers_means_no_body() {
+        assert_eq!(request_framing(&[("host", "a")]), Ok(BodyFraming::None));
+    }
+}
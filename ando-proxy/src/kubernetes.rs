@@ -0,0 +1,474 @@
+//! Kubernetes service discovery.
+//!
+//! Upstreams with `discovery_type: kubernetes` and `service_name:
+//! namespace/name` take their nodes from the ready endpoints of that
+//! Service. A watcher keeps a copy of every EndpointSlice labelled with
+//! `kubernetes.io/service-name` and publishes each service's endpoints to
+//! the `DiscoveryRegistry`; workers rebuild only the balancers of the
+//! services that changed, without a router rebuild.
+//!
+//! v2 design: The watch runs on its own thread with a current-thread tokio
+//! runtime (`kube` needs tokio), so it never touches worker cores. Without
+//! the `kubernetes` feature the slice bookkeeping still builds, but
+//! `spawn_kubernetes_discovery` refuses to start.
+//!
+//! # RBAC
+//!
+//! The service account Ando runs as must be able to read EndpointSlices:
+//!
+//! ```yaml
+//! apiVersion: rbac.authorization.k8s.io/v1
+//! kind: ClusterRole            # a Role when `discovery.kubernetes.namespace` is set
+//! metadata:
+//!   name: ando-discovery
+//! rules:
+//!   - apiGroups: ["discovery.k8s.io"]
+//!     resources: ["endpointslices"]
+//!     verbs: ["get", "list", "watch"]
+//! ---
+//! apiVersion: rbac.authorization.k8s.io/v1
+//! kind: ClusterRoleBinding     # a RoleBinding in that namespace for a Role
+//! metadata:
+//!   name: ando-discovery
+//! roleRef:
+//!   apiGroup: rbac.authorization.k8s.io
+//!   kind: ClusterRole
+//!   name: ando-discovery
+//! subjects:
+//!   - kind: ServiceAccount
+//!     name: ando
+//!     namespace: ando
+//! ```
+
+use ando_core::config::KubernetesDiscoveryConfig;
+use ando_core::upstream::DiscoveryType;
+use ando_store::discovery::{DiscoveredNode, DiscoveryRegistry};
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use tracing::info;
+
+/// Label linking an EndpointSlice to its Service.
+pub const SERVICE_NAME_LABEL: &str = "kubernetes.io/service-name";
+
+/// An EndpointSlice, reduced to what balancing needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slice {
+    pub namespace: String,
+    /// Name of the EndpointSlice itself.
+    pub name: String,
+    /// Name of the Service it belongs to.
+    pub service: String,
+    pub ports: Vec<SlicePort>,
+    pub endpoints: Vec<SliceEndpoint>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlicePort {
+    /// Empty for an unnamed port.
+    pub name: String,
+    pub port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SliceEndpoint {
+    pub addresses: Vec<String>,
+    /// `conditions.ready`; Kubernetes reads an unset condition as ready.
+    pub ready: bool,
+}
+
+impl Slice {
+    fn key(&self) -> (String, String) {
+        (self.namespace.clone(), self.name.clone())
+    }
+
+    /// `namespace/name` of the Service, as in `service_name`.
+    fn service_name(&self) -> String {
+        format!("{}/{}", self.namespace, self.service)
+    }
+}
+
+/// A watch event, as the `kube` watcher reports it.
+#[derive(Debug, Clone)]
+pub enum SliceEvent {
+    /// A slice was added or modified.
+    Apply(Slice),
+    /// A slice was deleted.
+    Delete(Slice),
+    /// The watch restarted; a full listing follows as `InitApply` events.
+    Init,
+    InitApply(Slice),
+    /// The listing is complete and replaces everything known before it.
+    InitDone,
+}
+
+/// EndpointSlices seen so far, turned into the registry's node sets.
+pub struct EndpointSliceTracker {
+    registry: DiscoveryRegistry,
+    /// (namespace, slice name) → slice.
+    slices: HashMap<(String, String), Slice>,
+    /// Slices listed since the last `Init`, until `InitDone`.
+    relist: Option<HashMap<(String, String), Slice>>,
+}
+
+impl EndpointSliceTracker {
+    pub fn new(registry: DiscoveryRegistry) -> Self {
+        Self {
+            registry,
+            slices: HashMap::new(),
+            relist: None,
+        }
+    }
+
+    /// Apply one watch event and republish the services it touched.
+    pub fn apply(&mut self, event: SliceEvent) {
+        let touched: HashSet<String> = match event {
+            SliceEvent::Apply(slice) => {
+                let service = slice.service_name();
+                let old = self.slices.insert(slice.key(), slice);
+                // A slice never moves between services, but be safe.
+                old.map(|s| s.service_name())
+                    .into_iter()
+                    .chain([service])
+                    .collect()
+            }
+            SliceEvent::Delete(slice) => {
+                self.slices.remove(&slice.key());
+                HashSet::from([slice.service_name()])
+            }
+            SliceEvent::Init => {
+                self.relist = Some(HashMap::new());
+                HashSet::new()
+            }
+            SliceEvent::InitApply(slice) => {
+                self.relist
+                    .get_or_insert_with(HashMap::new)
+                    .insert(slice.key(), slice);
+                HashSet::new()
+            }
+            SliceEvent::InitDone => {
+                let listed = self.relist.take().unwrap_or_default();
+                let old = std::mem::replace(&mut self.slices, listed);
+                old.values()
+                    .chain(self.slices.values())
+                    .map(Slice::service_name)
+                    .collect()
+            }
+        };
+        for service in touched {
+            self.publish(&service);
+        }
+    }
+
+    /// Publish the ready endpoints of `service`, or forget it once its
+    /// last slice is gone.
+    fn publish(&self, service: &str) {
+        let slices: Vec<&Slice> = self
+            .slices
+            .values()
+            .filter(|s| s.service_name() == service)
+            .collect();
+        if slices.is_empty() {
+            if self.registry.remove(DiscoveryType::Kubernetes, service) {
+                info!(service = %service, "Kubernetes service has no EndpointSlices left");
+            }
+            return;
+        }
+        let mut nodes = Vec::new();
+        for slice in slices {
+            for endpoint in slice.endpoints.iter().filter(|e| e.ready) {
+                for ip in endpoint
+                    .addresses
+                    .iter()
+                    .filter_map(|a| a.parse::<IpAddr>().ok())
+                {
+                    nodes.extend(slice.ports.iter().map(|p| DiscoveredNode {
+                        port_name: p.name.clone(),
+                        addr: SocketAddr::new(ip, p.port).to_string(),
+                    }));
+                }
+            }
+        }
+        let ready = nodes.len();
+        if self
+            .registry
+            .publish(DiscoveryType::Kubernetes, service, nodes)
+        {
+            info!(service = %service, endpoints = ready, "Kubernetes service endpoints changed");
+        }
+    }
+}
+
+/// Start watching EndpointSlices on a dedicated thread.
+#[cfg(feature = "kubernetes")]
+pub fn spawn_kubernetes_discovery(
+    registry: DiscoveryRegistry,
+    config: &KubernetesDiscoveryConfig,
+) -> anyhow::Result<std::thread::JoinHandle<()>> {
+    let namespace = config.namespace.clone();
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let handle = std::thread::Builder::new()
+        .name("ando-k8s".to_string())
+        .spawn(move || rt.block_on(watch(registry, namespace)))?;
+    Ok(handle)
+}
+
+/// Start watching EndpointSlices on a dedicated thread.
+#[cfg(not(feature = "kubernetes"))]
+pub fn spawn_kubernetes_discovery(
+    _registry: DiscoveryRegistry,
+    _config: &KubernetesDiscoveryConfig,
+) -> anyhow::Result<std::thread::JoinHandle<()>> {
+    anyhow::bail!("discovery.kubernetes is set but ando was built without the `kubernetes` feature")
+}
+
+/// Feed the EndpointSlice watch into a tracker. The watcher relists after
+/// a disconnect, backing off between attempts; the last known endpoints
+/// stay in use meanwhile.
+#[cfg(feature = "kubernetes")]
+async fn watch(registry: DiscoveryRegistry, namespace: Option<String>) {
+    use futures::StreamExt;
+    use k8s_openapi::api::discovery::v1::EndpointSlice;
+    use kube::runtime::{WatchStreamExt, watcher};
+
+    let client = match kube::Client::try_default().await {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(error = %e, "Kubernetes client setup failed, discovery disabled");
+            return;
+        }
+    };
+    let api: kube::Api<EndpointSlice> = match namespace {
+        Some(ref ns) => kube::Api::namespaced(client, ns),
+        None => kube::Api::all(client),
+    };
+    info!(
+        namespace = namespace.as_deref().unwrap_or("*"),
+        "Watching Kubernetes EndpointSlices"
+    );
+    let config = watcher::Config::default().labels(SERVICE_NAME_LABEL);
+    let mut events = watcher(api, config).default_backoff().boxed();
+    let mut tracker = EndpointSliceTracker::new(registry);
+    while let Some(event) = events.next().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                tracing::warn!(error = %e, "Kubernetes EndpointSlice watch failed, retrying");
+                continue;
+            }
+        };
+        let event = match event {
+            watcher::Event::Apply(s) => Slice::from_k8s(&s).map(SliceEvent::Apply),
+            watcher::Event::Delete(s) => Slice::from_k8s(&s).map(SliceEvent::Delete),
+            watcher::Event::Init => Some(SliceEvent::Init),
+            watcher::Event::InitApply(s) => Slice::from_k8s(&s).map(SliceEvent::InitApply),
+            watcher::Event::InitDone => Some(SliceEvent::InitDone),
+        };
+        if let Some(event) = event {
+            tracker.apply(event);
+        }
+    }
+}
+
+#[cfg(feature = "kubernetes")]
+impl Slice {
+    /// `None` for slices without a service label or namespace, and for
+    /// FQDN slices, which carry no IP addresses.
+    fn from_k8s(slice: &k8s_openapi::api::discovery::v1::EndpointSlice) -> Option<Self> {
+        if slice.address_type == "FQDN" {
+            return None;
+        }
+        let meta = &slice.metadata;
+        Some(Self {
+            namespace: meta.namespace.clone()?,
+            name: meta.name.clone()?,
+            service: meta.labels.as_ref()?.get(SERVICE_NAME_LABEL)?.clone(),
+            ports: slice
+                .ports
+                .iter()
+                .flatten()
+                .filter_map(|p| {
+                    Some(SlicePort {
+                        name: p.name.clone().unwrap_or_default(),
+                        port: u16::try_from(p.port?).ok()?,
+                    })
+                })
+                .collect(),
+            endpoints: slice
+                .endpoints
+                .iter()
+                .flatten()
+                .map(|e| SliceEndpoint {
+                    addresses: e.addresses.clone(),
+                    ready: e.conditions.as_ref().and_then(|c| c.ready).unwrap_or(true),
+                })
+                .collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slice(name: &str, ports: &[(&str, u16)], endpoints: &[(&str, bool)]) -> Slice {
+        Slice {
+            namespace: "shop".into(),
+            name: name.into(),
+            service: "checkout".into(),
+            ports: ports
+                .iter()
+                .map(|(name, port)| SlicePort {
+                    name: name.to_string(),
+                    port: *port,
+                })
+                .collect(),
+            endpoints: endpoints
+                .iter()
+                .map(|(addr, ready)| SliceEndpoint {
+                    addresses: vec![addr.to_string()],
+                    ready: *ready,
+                })
+                .collect(),
+        }
+    }
+
+    fn addrs(registry: &DiscoveryRegistry) -> Option<Vec<String>> {
+        registry
+            .get(DiscoveryType::Kubernetes, "shop/checkout")
+            .map(|nodes| nodes.into_iter().map(|n| n.addr).collect())
+    }
+
+    /// Replay a faked watch stream.
+    fn replay(events: Vec<SliceEvent>) -> (EndpointSliceTracker, DiscoveryRegistry) {
+        let registry = DiscoveryRegistry::new();
+        let mut tracker = EndpointSliceTracker::new(registry.clone());
+        for event in events {
+            tracker.apply(event);
+        }
+        (tracker, registry)
+    }
+
+    #[test]
+    fn initial_listing_publishes_ready_endpoints() {
+        let (_, registry) = replay(vec![
+            SliceEvent::Init,
+            SliceEvent::InitApply(slice(
+                "checkout-a",
+                &[("http", 8080)],
+                &[("10.1.0.1", true), ("10.1.0.2", false)],
+            )),
+            SliceEvent::InitApply(slice("checkout-b", &[("http", 8080)], &[("fd00::3", true)])),
+        ]);
+        assert_eq!(addrs(&registry), None, "nothing published before InitDone");
+
+        let (_, registry) = replay(vec![
+            SliceEvent::Init,
+            SliceEvent::InitApply(slice(
+                "checkout-a",
+                &[("http", 8080)],
+                &[("10.1.0.1", true), ("10.1.0.2", false)],
+            )),
+            SliceEvent::InitApply(slice("checkout-b", &[("http", 8080)], &[("fd00::3", true)])),
+            SliceEvent::InitDone,
+        ]);
+        assert_eq!(
+            addrs(&registry).unwrap(),
+            ["10.1.0.1:8080", "[fd00::3]:8080"]
+        );
+    }
+
+    #[test]
+    fn deleted_endpoints_are_withdrawn() {
+        let (mut tracker, registry) = replay(vec![
+            SliceEvent::Apply(slice(
+                "checkout-a",
+                &[("", 80)],
+                &[("10.1.0.1", true), ("10.1.0.2", true)],
+            )),
+            SliceEvent::Apply(slice("checkout-b", &[("", 80)], &[("10.1.0.3", true)])),
+        ]);
+        assert_eq!(addrs(&registry).unwrap().len(), 3);
+
+        tracker.apply(SliceEvent::Apply(slice(
+            "checkout-a",
+            &[("", 80)],
+            &[("10.1.0.1", true)],
+        )));
+        assert_eq!(addrs(&registry).unwrap(), ["10.1.0.1:80", "10.1.0.3:80"]);
+
+        tracker.apply(SliceEvent::Delete(slice("checkout-b", &[], &[])));
+        assert_eq!(addrs(&registry).unwrap(), ["10.1.0.1:80"]);
+
+        // The last pod goes unready: the service stays known, with no node.
+        let version = registry.version();
+        tracker.apply(SliceEvent::Apply(slice(
+            "checkout-a",
+            &[("", 80)],
+            &[("10.1.0.1", false)],
+        )));
+        assert_eq!(addrs(&registry), Some(vec![]));
+        assert_eq!(registry.version(), version + 1);
+
+        tracker.apply(SliceEvent::Delete(slice("checkout-a", &[], &[])));
+        assert_eq!(addrs(&registry), None);
+    }
+
+    #[test]
+    fn relist_drops_slices_deleted_while_disconnected() {
+        let (mut tracker, registry) = replay(vec![
+            SliceEvent::Apply(slice("checkout-a", &[("", 80)], &[("10.1.0.1", true)])),
+            SliceEvent::Apply(slice("checkout-b", &[("", 80)], &[("10.1.0.2", true)])),
+        ]);
+        tracker.apply(SliceEvent::Init);
+        tracker.apply(SliceEvent::InitApply(slice(
+            "checkout-b",
+            &[("", 80)],
+            &[("10.1.0.2", true)],
+        )));
+        assert_eq!(
+            addrs(&registry).unwrap().len(),
+            2,
+            "old set kept until InitDone"
+        );
+        tracker.apply(SliceEvent::InitDone);
+        assert_eq!(addrs(&registry).unwrap(), ["10.1.0.2:80"]);
+    }
+
+    #[test]
+    fn every_port_is_published_with_its_name() {
+        let (_, registry) = replay(vec![SliceEvent::Apply(slice(
+            "checkout-a",
+            &[("http", 8080), ("metrics", 9090)],
+            &[("10.1.0.1", true)],
+        ))]);
+        let nodes = registry
+            .get(DiscoveryType::Kubernetes, "shop/checkout")
+            .unwrap();
+        assert_eq!(
+            nodes,
+            [
+                DiscoveredNode {
+                    port_name: "http".into(),
+                    addr: "10.1.0.1:8080".into()
+                },
+                DiscoveredNode {
+                    port_name: "metrics".into(),
+                    addr: "10.1.0.1:9090".into()
+                },
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "kubernetes"))]
+    #[test]
+    fn spawning_needs_the_feature() {
+        let err = spawn_kubernetes_discovery(
+            DiscoveryRegistry::new(),
+            &KubernetesDiscoveryConfig::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`kubernetes` feature"));
+    }
+}
@@ -1,3 +1,15 @@
+pub mod chunked;
 pub mod connection;
+pub mod decompress;
+pub mod dns;
+pub mod forwarded;
+pub mod grpc;
+pub mod h2;
+pub mod health_check;
+pub mod hop_by_hop;
+pub mod kubernetes;
+pub mod listener;
 pub mod proxy;
+pub mod proxy_protocol;
+pub mod tls;
 pub mod worker;
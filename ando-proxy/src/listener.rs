@@ -0,0 +1,737 @@
+//! Listening sockets of the worker threads.
+//!
+//! Every worker accepts on all of `proxy.effective_listeners()`: the named
+//! `proxy.listeners`, or `http_addr` and `https_addr`.
+//!
+//! v2 design: With `proxy.reuse_port` every worker gets its own socket bound
+//! to the proxy address with SO_REUSEPORT. The kernel hashes incoming
+//! connections across them, so workers never contend on one accept queue
+//! and a connection is handled on the core that accepted it. Only Linux
+//! balances reuseport sockets this way (macOS hands every connection to
+//! the last bound socket), so elsewhere, or with `reuse_port: false`, one
+//! socket is bound and every worker accepts from a handle to it.
+//!
+//! Sockets are bound on the spawning thread, before any worker starts, so
+//! a bad address fails startup once instead of once per worker. The
+//! `proxy.listener` options (backlog, buffer sizes, TCP Fast Open) are
+//! applied to each socket; accepted connections inherit the buffer sizes.
+//! An option the platform rejects is logged and left at the OS default
+//! rather than failing startup.
+//!
+//! For a binary upgrade the running process hands its sockets to the new
+//! one instead: their descriptors stay open across `exec` and are listed
+//! in `LISTEN_FDS_ENV` (see `ListenerFds`), and the new process adopts
+//! them with `WorkerListeners::inherit` rather than binding. Connections
+//! queued on the sockets are never refused while both processes run.
+
+use ando_core::config::{ListenerConfig, ListenerProtocol, ProxyConfig, ProxyListener};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::str::FromStr;
+use tracing::{info, warn};
+
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+#[cfg(not(unix))]
+type RawFd = i32;
+
+/// Environment variable listing the listening sockets a binary upgrade
+/// hands to the new process; see `ListenerFds`.
+pub const LISTEN_FDS_ENV: &str = "ANDO_LISTEN_FDS";
+
+/// How the workers share the proxy address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListenerMode {
+    /// One SO_REUSEPORT socket per worker.
+    PerWorker,
+    /// One socket, accepted from by every worker.
+    Shared,
+}
+
+impl ListenerMode {
+    /// The mode for `proxy.reuse_port()` on this platform.
+    pub fn from_config(reuse_port: bool) -> Self {
+        if !reuse_port {
+            Self::Shared
+        } else if cfg!(target_os = "linux") {
+            Self::PerWorker
+        } else {
+            warn!(
+                "proxy.reuse_port: SO_REUSEPORT does not balance connections on this platform, \
+                 workers share one listener"
+            );
+            Self::Shared
+        }
+    }
+}
+
+/// Bind `count` listeners on `addr`, one per worker. With port 0, every
+/// listener ends up on the port the kernel picked for the first.
+pub fn bind_listeners(
+    addr: &str,
+    count: usize,
+    mode: ListenerMode,
+    options: &ListenerConfig,
+) -> io::Result<Vec<TcpListener>> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty address"))?;
+    let first = bind(addr, mode == ListenerMode::PerWorker, options)?;
+    let addr = first.local_addr()?;
+    let mut listeners = Vec::with_capacity(count.max(1));
+    for _ in 1..count {
+        listeners.push(match mode {
+            ListenerMode::PerWorker => bind(addr, true, options)?,
+            ListenerMode::Shared => first.try_clone()?,
+        });
+    }
+    listeners.insert(0, first);
+    Ok(listeners)
+}
+
+/// The sockets of one of `proxy.effective_listeners()`; worker `n`
+/// accepts on the `n`th.
+pub struct NamedListener {
+    pub spec: ProxyListener,
+    pub sockets: Vec<TcpListener>,
+}
+
+/// The listeners of the workers, in config order.
+pub struct WorkerListeners {
+    /// Without the HTTPS listener of `proxy.https_addr` when that fails
+    /// to bind.
+    pub listeners: Vec<NamedListener>,
+    /// The `proxy.listener` options in effect: `reuseport` resolved and
+    /// anything this platform cannot do turned off.
+    pub options: ListenerConfig,
+}
+
+impl WorkerListeners {
+    /// Bind `count` sockets for each of `proxy.effective_listeners()`.
+    pub fn bind(proxy: &ProxyConfig, count: usize) -> io::Result<Self> {
+        let (mode, options) = effective_options(proxy);
+        let mut listeners = Vec::new();
+        for spec in checked_listeners(proxy)? {
+            if let Some(sockets) = bind_named(proxy, &spec, count, mode, &options)? {
+                listeners.push(NamedListener { spec, sockets });
+            }
+        }
+        let listeners = Self { listeners, options };
+        listeners.log_options();
+        Ok(listeners)
+    }
+
+    /// Adopt the sockets a previous process handed over in `fds` instead
+    /// of binding. A listener that was not handed over, or whose address
+    /// the config has changed since, is bound afresh; extra sockets are
+    /// bound (or shared) up to `count`, surplus ones closed, as are those
+    /// of listeners no longer configured. Inherited sockets keep their
+    /// backlog; the other options are applied anew.
+    ///
+    /// `fds` must name listening sockets this process owns: the ones
+    /// inherited from the process that started it for the upgrade.
+    #[cfg(unix)]
+    pub fn inherit(fds: &ListenerFds, proxy: &ProxyConfig, count: usize) -> io::Result<Self> {
+        let (mode, options) = effective_options(proxy);
+        // SAFETY: see above; each descriptor is adopted exactly once.
+        let mut adopted: Vec<(&str, Vec<TcpListener>)> = fds
+            .listeners
+            .iter()
+            .map(|(name, fds)| {
+                let sockets = fds
+                    .iter()
+                    .map(|&fd| unsafe { TcpListener::from_raw_fd(fd) })
+                    .collect();
+                (name.as_str(), sockets)
+            })
+            .collect();
+        let mut listeners = Vec::new();
+        for spec in checked_listeners(proxy)? {
+            let inherited = adopted
+                .iter()
+                .position(|(name, _)| *name == spec.name)
+                .map(|i| adopted.swap_remove(i).1)
+                .unwrap_or_default();
+            let sockets = if inherited.is_empty() {
+                bind_named(proxy, &spec, count, mode, &options)?
+            } else {
+                Some(reuse(inherited, &spec.addr, count, mode, &options)?)
+            };
+            if let Some(sockets) = sockets {
+                listeners.push(NamedListener { spec, sockets });
+            }
+        }
+        // Dropping the sockets left over closes them.
+        for (name, _) in adopted.iter().filter(|(_, sockets)| !sockets.is_empty()) {
+            info!(listener = %name, "Upgrade: listener no longer configured, closing its sockets");
+        }
+        let listeners = Self { listeners, options };
+        listeners.log_options();
+        Ok(listeners)
+    }
+
+    /// Sockets per listener: the number of workers to run.
+    pub fn workers(&self) -> usize {
+        self.listeners.first().map_or(0, |l| l.sockets.len())
+    }
+
+    /// Log the options the sockets ended up with. Buffer sizes are read
+    /// back, as the kernel rounds (Linux doubles) what was asked for.
+    fn log_options(&self) {
+        let Some(first) = self.listeners.first().and_then(|l| l.sockets.first()) else {
+            return;
+        };
+        let socket = SockRef::from(first);
+        info!(
+            backlog = self.options.backlog,
+            nodelay = self.options.nodelay,
+            reuseport = self.options.reuseport.unwrap_or(false),
+            fastopen_queue = self.options.fastopen_queue,
+            recv_buffer_size = socket.recv_buffer_size().unwrap_or(0),
+            send_buffer_size = socket.send_buffer_size().unwrap_or(0),
+            "Listener options"
+        );
+    }
+
+    /// The descriptors of these sockets, to hand them to a new process
+    /// after the workers have taken ownership of them.
+    #[cfg(unix)]
+    pub fn fds(&self) -> ListenerFds {
+        ListenerFds {
+            listeners: self
+                .listeners
+                .iter()
+                .map(|l| {
+                    let fds = l.sockets.iter().map(AsRawFd::as_raw_fd).collect();
+                    (l.spec.name.clone(), fds)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// `proxy.effective_listeners()`, once their names are checked: unique,
+/// and made of letters, digits, `-` and `_` so they can be listed in
+/// `LISTEN_FDS_ENV`.
+fn checked_listeners(proxy: &ProxyConfig) -> io::Result<Vec<ProxyListener>> {
+    let listeners = proxy.effective_listeners();
+    for (i, listener) in listeners.iter().enumerate() {
+        let name = &listener.name;
+        let valid = !name.is_empty()
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+        let problem = if !valid {
+            "names may only hold letters, digits, `-` and `_`"
+        } else if listeners[..i].iter().any(|other| other.name == *name) {
+            "duplicate name"
+        } else {
+            continue;
+        };
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("proxy.listeners: `{name}`: {problem}"),
+        ));
+    }
+    Ok(listeners)
+}
+
+/// Bind `count` sockets for `spec`. The HTTPS listener of
+/// `proxy.https_addr` is left out (`None`) when it fails to bind; any
+/// other failure is an error.
+fn bind_named(
+    proxy: &ProxyConfig,
+    spec: &ProxyListener,
+    count: usize,
+    mode: ListenerMode,
+    options: &ListenerConfig,
+) -> io::Result<Option<Vec<TcpListener>>> {
+    match bind_listeners(&spec.addr, count, mode, options) {
+        Ok(sockets) => Ok(Some(sockets)),
+        Err(e) if proxy.listeners.is_empty() && spec.protocol == ListenerProtocol::Https => {
+            warn!(addr = %spec.addr, error = %e, "TLS listener disabled: bind failed");
+            Ok(None)
+        }
+        Err(e) => Err(io::Error::new(
+            e.kind(),
+            format!("listener `{}` on {}: {e}", spec.name, spec.addr),
+        )),
+    }
+}
+
+/// Make `count` listeners on `addr` out of inherited ones.
+#[cfg(unix)]
+fn reuse(
+    mut listeners: Vec<TcpListener>,
+    addr: &str,
+    count: usize,
+    mode: ListenerMode,
+    options: &ListenerConfig,
+) -> io::Result<Vec<TcpListener>> {
+    let wanted = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty address"))?;
+    let local = match listeners.first() {
+        Some(first) => first.local_addr()?,
+        None => return bind_listeners(addr, count, mode, options),
+    };
+    // Port 0 asks for any port: the inherited one will do.
+    let same = local == wanted || (wanted.port() == 0 && local.ip() == wanted.ip());
+    if !same {
+        warn!(%addr, inherited = %local, "Upgrade: listen address changed, binding it anew");
+        return bind_listeners(addr, count, mode, options);
+    }
+    info!(%addr, sockets = listeners.len(), "Upgrade: took over listening sockets");
+    for listener in &listeners {
+        listener.set_nonblocking(true)?;
+        tune(&SockRef::from(listener), options);
+    }
+    let count = count.max(1);
+    if listeners.len() > count {
+        warn!(%addr, closed = listeners.len() - count, "Upgrade: fewer workers, closing extra sockets");
+        listeners.truncate(count);
+    }
+    while listeners.len() < count {
+        let extra = match mode {
+            ListenerMode::PerWorker => bind(local, true, options)?,
+            ListenerMode::Shared => listeners[0].try_clone()?,
+        };
+        listeners.push(extra);
+    }
+    Ok(listeners)
+}
+
+/// Descriptors of the listening sockets handed to an upgraded process,
+/// by listener name, written to `LISTEN_FDS_ENV` as
+/// `http=3,4;https=5,6`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ListenerFds {
+    pub listeners: Vec<(String, Vec<RawFd>)>,
+}
+
+impl ListenerFds {
+    /// Every descriptor, in listener order.
+    pub fn all(&self) -> impl Iterator<Item = RawFd> + '_ {
+        self.listeners.iter().flat_map(|(_, fds)| fds).copied()
+    }
+}
+
+impl fmt::Display for ListenerFds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list: Vec<String> = self
+            .listeners
+            .iter()
+            .map(|(name, fds)| {
+                let fds: Vec<String> = fds.iter().map(ToString::to_string).collect();
+                format!("{name}={}", fds.join(","))
+            })
+            .collect();
+        f.write_str(&list.join(";"))
+    }
+}
+
+impl FromStr for ListenerFds {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> io::Result<Self> {
+        let invalid = || {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{LISTEN_FDS_ENV}: expected `http=3,4;https=5`, got `{s}`"),
+            )
+        };
+        let mut fds = Self::default();
+        for part in s.split(';').filter(|part| !part.is_empty()) {
+            let (name, list) = part.split_once('=').ok_or_else(invalid)?;
+            let name = name.trim();
+            if name.is_empty() || fds.listeners.iter().any(|(n, _)| n == name) {
+                return Err(invalid());
+            }
+            let list = list
+                .split(',')
+                .filter(|fd| !fd.is_empty())
+                .map(|fd| fd.trim().parse().map_err(|_| invalid()))
+                .collect::<io::Result<Vec<RawFd>>>()?;
+            fds.listeners.push((name.to_string(), list));
+        }
+        Ok(fds)
+    }
+}
+
+/// The listener mode and options `proxy` asks for, as far as this
+/// platform supports them.
+fn effective_options(proxy: &ProxyConfig) -> (ListenerMode, ListenerConfig) {
+    let mode = ListenerMode::from_config(proxy.reuse_port());
+    let mut options = proxy.listener.clone();
+    options.reuseport = Some(mode == ListenerMode::PerWorker);
+    if options.fastopen_queue > 0 && !cfg!(target_os = "linux") {
+        warn!("proxy.listener.fastopen_queue: TCP Fast Open is not supported on this platform");
+        options.fastopen_queue = 0;
+    }
+    (mode, options)
+}
+
+/// Apply the buffer sizes and TCP Fast Open queue of `options`; what the
+/// kernel rejects is left at its default.
+fn tune(socket: &SockRef<'_>, options: &ListenerConfig) {
+    if let Some(size) = options.recv_buffer_size
+        && let Err(e) = socket.set_recv_buffer_size(size)
+    {
+        warn!(size, error = %e, "proxy.listener.recv_buffer_size: not applied");
+    }
+    if let Some(size) = options.send_buffer_size
+        && let Err(e) = socket.set_send_buffer_size(size)
+    {
+        warn!(size, error = %e, "proxy.listener.send_buffer_size: not applied");
+    }
+    if options.fastopen_queue > 0
+        && let Err(e) = set_fastopen(socket, options.fastopen_queue)
+    {
+        warn!(queue = options.fastopen_queue, error = %e, "proxy.listener.fastopen_queue: not applied");
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_fastopen(socket: &SockRef<'_>, queue: u32) -> io::Result<()> {
+    let queue = libc::c_int::try_from(queue).unwrap_or(libc::c_int::MAX);
+    // SAFETY: `queue` outlives the call and its size is passed along.
+    let rc = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN,
+            (&queue as *const libc::c_int).cast(),
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if rc == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_fastopen(_socket: &SockRef<'_>, _queue: u32) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "TCP Fast Open is only supported on Linux",
+    ))
+}
+
+/// A non-blocking listening socket on `addr`.
+fn bind(addr: SocketAddr, reuse_port: bool, options: &ListenerConfig) -> io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    socket.set_reuse_address(true)?;
+    #[cfg(unix)]
+    if reuse_port {
+        socket.set_reuse_port(true)?;
+    }
+    #[cfg(not(unix))]
+    let _ = reuse_port;
+    socket.set_nonblocking(true)?;
+    // Buffer sizes must be set before listen() to shape the TCP window of
+    // the connections accepted from this socket.
+    tune(&SockRef::from(&socket), options);
+    socket.bind(&addr.into())?;
+    socket.listen(i32::try_from(options.backlog).unwrap_or(i32::MAX))?;
+    Ok(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Listening sockets on `port`, from `/proc/net/tcp{,6}`.
+    #[cfg(target_os = "linux")]
+    fn listening_sockets(port: u16) -> usize {
+        const LISTEN: &str = "0A";
+        let port = format!(":{port:04X}");
+        ["/proc/net/tcp", "/proc/net/tcp6"]
+            .iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .flat_map(|table| {
+                table
+                    .lines()
+                    .skip(1)
+                    .map(|line| {
+                        let fields: Vec<&str> = line.split_whitespace().collect();
+                        fields.get(1).is_some_and(|local| local.ends_with(&port))
+                            && fields.get(3) == Some(&LISTEN)
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .filter(|listening| *listening)
+            .count()
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn per_worker_mode_binds_one_socket_per_worker() {
+        let listeners = bind_listeners(
+            "127.0.0.1:0",
+            4,
+            ListenerMode::PerWorker,
+            &ListenerConfig::default(),
+        )
+        .unwrap();
+        let port = listeners[0].local_addr().unwrap().port();
+        assert_eq!(listeners.len(), 4);
+        assert!(
+            listeners
+                .iter()
+                .all(|l| l.local_addr().unwrap().port() == port)
+        );
+        assert_eq!(listening_sockets(port), 4);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn shared_mode_binds_a_single_socket() {
+        let listeners = bind_listeners(
+            "127.0.0.1:0",
+            4,
+            ListenerMode::Shared,
+            &ListenerConfig::default(),
+        )
+        .unwrap();
+        let port = listeners[0].local_addr().unwrap().port();
+        assert_eq!(listeners.len(), 4);
+        assert_eq!(listening_sockets(port), 1);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn listener_options_are_applied() {
+        let options = ListenerConfig {
+            recv_buffer_size: Some(64 * 1024),
+            fastopen_queue: 16,
+            ..ListenerConfig::default()
+        };
+        let listeners = bind_listeners("127.0.0.1:0", 1, ListenerMode::Shared, &options).unwrap();
+        let socket = SockRef::from(&listeners[0]);
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+        let mut queue: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let rc = unsafe {
+            libc::getsockopt(
+                socket.as_raw_fd(),
+                libc::IPPROTO_TCP,
+                libc::TCP_FASTOPEN,
+                (&mut queue as *mut libc::c_int).cast(),
+                &mut len,
+            )
+        };
+        assert_eq!(rc, 0);
+        assert_eq!(queue, 16);
+    }
+
+    #[test]
+    fn listener_reuseport_overrides_the_proxy_setting() {
+        let mut proxy = ProxyConfig {
+            reuse_port: true,
+            ..ProxyConfig::default()
+        };
+        proxy.listener.reuseport = Some(false);
+        let (mode, options) = effective_options(&proxy);
+        assert_eq!(mode, ListenerMode::Shared);
+        assert_eq!(options.reuseport, Some(false));
+    }
+
+    #[test]
+    fn disabled_reuse_port_shares_one_listener() {
+        assert_eq!(ListenerMode::from_config(false), ListenerMode::Shared);
+    }
+
+    #[test]
+    fn every_listener_accepts() {
+        for mode in [ListenerMode::PerWorker, ListenerMode::Shared] {
+            let listeners =
+                bind_listeners("127.0.0.1:0", 2, mode, &ListenerConfig::default()).unwrap();
+            let addr = listeners[0].local_addr().unwrap();
+            let _client = std::net::TcpStream::connect(addr).unwrap();
+            // Reuseport hashes the connection to one of the sockets.
+            let accepted = (0..200).any(|_| {
+                let any = listeners.iter().any(|l| l.accept().is_ok());
+                if !any {
+                    std::thread::sleep(std::time::Duration::from_millis(5));
+                }
+                any
+            });
+            assert!(accepted, "{mode:?}");
+        }
+    }
+
+    fn fds(listeners: &[(&str, Vec<RawFd>)]) -> ListenerFds {
+        ListenerFds {
+            listeners: listeners
+                .iter()
+                .map(|(name, fds)| (name.to_string(), fds.clone()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn listener_fds_round_trip() {
+        let handed = fds(&[("http", vec![3, 4]), ("https", vec![7])]);
+        assert_eq!(handed.to_string(), "http=3,4;https=7");
+        assert_eq!("http=3,4;https=7".parse::<ListenerFds>().unwrap(), handed);
+        assert_eq!(
+            "public=5;internal=".parse::<ListenerFds>().unwrap(),
+            fds(&[("public", vec![5]), ("internal", vec![])])
+        );
+        assert!("http=x".parse::<ListenerFds>().is_err());
+        assert!("3,4".parse::<ListenerFds>().is_err());
+        assert!("http=3;http=4".parse::<ListenerFds>().is_err());
+    }
+
+    fn named(name: &str, addr: &str) -> ProxyListener {
+        ProxyListener {
+            name: name.to_string(),
+            addr: addr.to_string(),
+            protocol: ListenerProtocol::Http,
+            tls: Default::default(),
+            proxy_protocol: None,
+        }
+    }
+
+    #[test]
+    fn named_listeners_are_bound_in_order() {
+        let proxy = ProxyConfig {
+            listeners: vec![
+                named("public", "127.0.0.1:0"),
+                named("internal", "127.0.0.1:0"),
+            ],
+            ..ProxyConfig::default()
+        };
+        let bound = WorkerListeners::bind(&proxy, 2).unwrap();
+        let names: Vec<_> = bound
+            .listeners
+            .iter()
+            .map(|l| l.spec.name.as_str())
+            .collect();
+        assert_eq!(names, ["public", "internal"]);
+        assert_eq!(bound.workers(), 2);
+        assert_eq!(bound.fds().all().count(), 4);
+    }
+
+    #[test]
+    fn bad_listener_names_are_rejected() {
+        for names in [["a", "a"], ["a", ""], ["a", "b=c"]] {
+            let proxy = ProxyConfig {
+                listeners: names.iter().map(|n| named(n, "127.0.0.1:0")).collect(),
+                ..ProxyConfig::default()
+            };
+            assert!(WorkerListeners::bind(&proxy, 1).is_err(), "{names:?}");
+        }
+    }
+
+    #[test]
+    fn only_the_legacy_https_listener_may_fail_to_bind() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken = taken.local_addr().unwrap().to_string();
+        let legacy = ProxyConfig {
+            http_addr: "127.0.0.1:0".to_string(),
+            https_addr: taken.clone(),
+            reuse_port: false,
+            ..ProxyConfig::default()
+        };
+        let bound = WorkerListeners::bind(&legacy, 1).unwrap();
+        assert_eq!(bound.listeners.len(), 1);
+        assert_eq!(bound.listeners[0].spec.name, "http");
+
+        let mut tls = named("public", &taken);
+        tls.protocol = ListenerProtocol::Https;
+        let explicit = ProxyConfig {
+            listeners: vec![named("internal", "127.0.0.1:0"), tls],
+            reuse_port: false,
+            ..ProxyConfig::default()
+        };
+        let err = WorkerListeners::bind(&explicit, 1).err().unwrap();
+        assert!(err.to_string().contains("`public`"), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn inherited_sockets_are_reused_and_topped_up() {
+        use std::os::fd::IntoRawFd;
+
+        let listeners = bind_listeners(
+            "127.0.0.1:0",
+            1,
+            ListenerMode::Shared,
+            &ListenerConfig::default(),
+        )
+        .unwrap();
+        let addr = listeners[0].local_addr().unwrap();
+        let handed = listeners.into_iter().map(IntoRawFd::into_raw_fd).collect();
+        let fds = fds(&[("http", handed)]);
+        let proxy = ProxyConfig {
+            http_addr: addr.to_string(),
+            https_addr: String::new(),
+            reuse_port: false,
+            ..ProxyConfig::default()
+        };
+        let inherited = WorkerListeners::inherit(&fds, &proxy, 3).unwrap();
+        assert_eq!(inherited.listeners.len(), 1);
+        let http = &inherited.listeners[0].sockets;
+        assert_eq!(http.len(), 3);
+        assert_eq!(http[0].as_raw_fd(), fds.listeners[0].1[0]);
+        let _client = std::net::TcpStream::connect(addr).unwrap();
+        let accepted = (0..200).any(|_| {
+            let ok = http[2].accept().is_ok();
+            if !ok {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            ok
+        });
+        assert!(accepted);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn a_moved_address_is_bound_anew() {
+        use std::os::fd::IntoRawFd;
+
+        let old = bind_listeners(
+            "127.0.0.1:0",
+            1,
+            ListenerMode::Shared,
+            &ListenerConfig::default(),
+        )
+        .unwrap();
+        let fds = fds(&[(
+            "http",
+            old.into_iter().map(IntoRawFd::into_raw_fd).collect(),
+        )]);
+        let proxy = ProxyConfig {
+            http_addr: "127.0.0.2:0".to_string(),
+            https_addr: String::new(),
+            ..ProxyConfig::default()
+        };
+        let inherited = WorkerListeners::inherit(&fds, &proxy, 1).unwrap();
+        assert_ne!(
+            inherited.listeners[0].sockets[0].as_raw_fd(),
+            fds.listeners[0].1[0]
+        );
+    }
+
+    #[test]
+    fn an_address_in_use_is_an_error() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap().to_string();
+        assert!(
+            bind_listeners(
+                &addr,
+                2,
+                ListenerMode::PerWorker,
+                &ListenerConfig::default()
+            )
+            .is_err()
+        );
+    }
+}
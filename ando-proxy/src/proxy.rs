@@ -1,26 +1,66 @@
-use ando_core::route::Route;
-use ando_core::router::Router;
+use crate::connection::ClientAddrs;
+use crate::decompress::{Coding, DecodeError, decompress};
+use crate::dns::is_hostname;
+use crate::forwarded::{TrustedProxies, add_forwarded_headers};
+use crate::hop_by_hop::HopByHop;
+use crate::tls::{ClientCert, UpstreamTls};
+use ando_core::balancer::{Balancer, HashOn};
+use ando_core::config::{HTTP_LISTENER, ProxyConfig};
+use ando_core::error_page::{ErrorPages, ErrorVars};
+use ando_core::global_rule::GlobalRule;
+use ando_core::route::{RequestDecompression, Route};
+use ando_core::router::{MatchContext, Router};
 use ando_core::service::Service;
-use ando_core::upstream::Upstream;
-use ando_plugin::pipeline::PluginPipeline;
-use ando_plugin::plugin::{Phase, PluginContext, PluginResult};
+use ando_core::shutdown::Shutdown;
+use ando_core::upstream::{
+    DiscoveryType, PassiveHealthCheck, ProxyProtocolVersion, RetryOn, Timeout, Upstream,
+};
+use ando_core::vars::cookie_value;
+use ando_observability::access_log::{AccessLogEntry, AccessLogger};
+use ando_observability::metrics::{LocalMetrics, MetricsCollector};
+use ando_observability::otel::{RequestSpan, RequestTracer};
+use ando_observability::worker_stats::WorkerCounters;
+use ando_plugin::filter::PluginFilter;
+use ando_plugin::pipeline::{PipelineEntry, PluginPipeline, PluginTimer};
+use ando_plugin::plugin::{
+    AccessFuture, ConsumerIndex, Phase, PluginContext, PluginResult, is_disabled, priority_override,
+};
 use ando_plugin::registry::PluginRegistry;
 use ando_store::cache::ConfigCache;
+use ando_store::changes::ChangeSet;
+use ando_store::discovery::{DiscoveredNode, nodes_on_port};
+use ando_store::health::{BreakerTransition, CircuitBreakers, UpstreamFailure};
+use bytes::Bytes;
+use monoio::buf::{IoBuf, IoBufMut, IoVecBuf, IoVecBufMut};
+use monoio::io::{AsyncReadRent, AsyncWriteRent, Split};
 use monoio::net::TcpStream;
-use std::collections::{HashMap, VecDeque};
+use monoio_http::h2::client::SendRequest;
+use monoio_rustls::ClientTlsStream;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 // ── Pre-built static error responses (zero heap alloc) ────────
 
 pub const RESP_404: &[u8] =
     b"HTTP/1.1 404 Not Found\r\ncontent-type: application/json\r\ncontent-length: 41\r\nconnection: keep-alive\r\n\r\n{\"error\":\"no route matched\",\"status\":404}";
 
-pub const RESP_401_INVALID: &[u8] =
-    b"HTTP/1.1 401 Unauthorized\r\ncontent-type: application/json\r\ncontent-length: 40\r\nconnection: keep-alive\r\n\r\n{\"error\":\"Invalid API key\",\"status\":401}";
+pub const RESP_400_ENCODING: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\ncontent-type: application/json\r\ncontent-length: 56\r\nconnection: keep-alive\r\n\r\n{\"error\":\"malformed request body encoding\",\"status\":400}";
+
+pub const RESP_400_CERT: &[u8] =
+    b"HTTP/1.1 400 Bad Request\r\ncontent-type: application/json\r\ncontent-length: 52\r\nconnection: keep-alive\r\n\r\n{\"error\":\"client certificate required\",\"status\":400}";
+
+pub const RESP_413: &[u8] =
+    b"HTTP/1.1 413 Payload Too Large\r\ncontent-type: application/json\r\ncontent-length: 47\r\nconnection: keep-alive\r\n\r\n{\"error\":\"request body too large\",\"status\":413}";
 
 pub const RESP_502: &[u8] =
     b"HTTP/1.1 502 Bad Gateway\r\ncontent-type: application/json\r\ncontent-length: 39\r\nconnection: keep-alive\r\n\r\n{\"error\":\"upstream error\",\"status\":502}";
 
+pub const RESP_504: &[u8] =
+    b"HTTP/1.1 504 Gateway Timeout\r\ncontent-type: application/json\r\ncontent-length: 41\r\nconnection: keep-alive\r\n\r\n{\"error\":\"upstream timeout\",\"status\":504}";
+
 // ── ProxyWorker ───────────────────────────────────────────────
 
 /// Per-worker proxy state. Created ONCE per thread, reused across
@@ -33,20 +73,228 @@ pub struct ProxyWorker {
     router: Arc<Router>,
     /// Router version for cache invalidation.
     router_version: u64,
+    /// Health table version the ejected-node snapshot was taken at.
+    health_version: u64,
+    /// DNS table version the resolved-node snapshot was taken at.
+    dns_version: u64,
+    /// Discovery registry version the discovered-node snapshot was taken at.
+    discovery_version: u64,
+    /// Change log version the thread-local caches are in sync with.
+    changes_version: u64,
 
     // ── Thread-local caches (rebuilt on version change) ──
     pipeline_cache: HashMap<String, Arc<PluginPipeline>>,
+    balancers: Balancers,
 
     // ── Snapshots from DashMap (cold path only) ──
     upstreams: HashMap<String, Upstream>,
     services: HashMap<String, Service>,
-    consumer_keys: HashMap<String, String>,
+    /// Plugins every route gets under its own: `plugins.global` of the
+    /// gateway config, overlaid by the stored global rules.
+    global_plugins: HashMap<String, serde_json::Value>,
+    /// Consumer credentials handed to auth plugins that verify secrets.
+    consumer_index: Arc<ConsumerIndex>,
 
     // ── Shared immutable ──
+    /// `plugins.global` of the gateway config.
+    config_global_plugins: HashMap<String, serde_json::Value>,
     plugin_registry: Arc<PluginRegistry>,
     config_cache: ConfigCache,
+    metrics: Option<Arc<MetricsCollector>>,
+    /// Request series buffered on this thread until `flush_metrics`.
+    local_metrics: Option<LocalMetrics>,
+    /// Route matched by the last `handle_request*` call, kept only while
+    /// metrics are enabled. Empty when no route matched.
+    matched_route: String,
+    tracer: Option<Arc<RequestTracer>>,
+    /// Span of the request being handled, handed to its `Exchange`.
+    span: Option<RequestSpan>,
+    access_log: Option<Arc<AccessLogger>>,
+    /// Access log entry of the request being handled, handed to its
+    /// `Exchange`.
+    access: Option<AccessLogEntry>,
+    /// Largest upstream body buffered for body-filter plugins.
+    max_filtered_body: usize,
+    /// Plugin calls slower than this are logged. `None` disables the check.
+    slow_plugin_threshold: Option<Duration>,
+    /// Upstream timeouts of routes and upstreams that set none.
+    timeouts: UpstreamTimeouts,
+    /// Peers whose `X-Forwarded-For` names the client.
+    trusted_proxies: TrustedProxies,
+    /// Size limits the connection loop applies before routing.
+    limits: RequestLimits,
+    /// Deadlines and reuse limit of client connections.
+    client_timeouts: ClientTimeouts,
+    /// Longest gap between reads of a streaming response.
+    streaming_idle_timeout: Duration,
+    /// Label of this worker in per-worker metrics.
+    worker_label: String,
+    /// Client connections currently open on this worker.
+    open_connections: usize,
+    /// Requests read and not yet answered on this worker.
+    in_flight: usize,
+    /// Set once the gateway shuts down; `None` never drains.
+    shutdown: Option<Arc<Shutdown>>,
+    /// The gateway's `errors` pages; `None` when none are configured.
+    error_pages: Option<Arc<ErrorPages>>,
+    /// Error pages of the request being handled, handed to its
+    /// `Exchange`. Only set when some page applies to it.
+    error_page: Option<Box<ErrorPageContext>>,
+    /// This worker's share of the shared accept/connection/request stats.
+    counters: Arc<WorkerCounters>,
+}
+
+/// Client connection deadlines and reuse limit; see `ProxyConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientTimeouts {
+    /// From connecting, or from the first byte of a later request, to the
+    /// end of the request head.
+    pub header: Duration,
+    /// Each read of a request head or body.
+    pub read: Duration,
+    /// Wait for the next request on a keepalive connection.
+    pub idle: Duration,
+    /// Requests served per connection. `None` is unlimited.
+    pub max_requests: Option<usize>,
+}
+
+impl ClientTimeouts {
+    pub fn from_config(proxy: &ProxyConfig) -> Self {
+        Self {
+            header: Duration::from_millis(proxy.client_header_timeout_ms),
+            read: Duration::from_millis(proxy.client_read_timeout_ms),
+            idle: Duration::from_millis(proxy.client_idle_timeout_ms),
+            max_requests: (proxy.max_keepalive_requests > 0)
+                .then_some(proxy.max_keepalive_requests),
+        }
+    }
+}
+
+impl Default for ClientTimeouts {
+    fn default() -> Self {
+        Self::from_config(&ProxyConfig::default())
+    }
+}
+
+/// Request size limits; see `ProxyConfig`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestLimits {
+    /// Largest request line plus headers, in bytes.
+    pub max_header_size: usize,
+    /// Most header fields in a request.
+    pub max_headers: usize,
+    /// Largest request body, in bytes. `None` is unlimited.
+    pub max_body_size: Option<u64>,
+}
+
+/// Most header fields `RequestLimits::max_headers` can allow.
+pub const MAX_HEADERS: usize = 256;
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            max_header_size: 8192,
+            max_headers: 64,
+            max_body_size: None,
+        }
+    }
+}
+
+impl RequestLimits {
+    pub fn from_config(proxy: &ProxyConfig) -> Self {
+        Self {
+            // Room for at least a request line.
+            max_header_size: proxy.max_header_size.max(256),
+            max_headers: proxy.max_headers.clamp(1, MAX_HEADERS),
+            max_body_size: (proxy.max_body_size > 0).then_some(proxy.max_body_size),
+        }
+    }
+}
+
+/// Decode a request body for a route with `request_decompression`.
+/// `Ok(None)` when the body is empty or carries no coding to decode; an
+/// error is the response refusing the request.
+fn decode_request_body(
+    headers: &[(&str, &str)],
+    body: &[u8],
+    settings: &RequestDecompression,
+) -> Result<Option<Vec<u8>>, &'static [u8]> {
+    let Some(coding) = Coding::of_request(headers) else {
+        return Ok(None);
+    };
+    // The rest of a body streamed past the buffer is still in the socket
+    // and cannot be checked against the limit.
+    if request_body_size(headers, body) > body.len() as u64 {
+        return Err(RESP_413);
+    }
+    if body.is_empty() {
+        return Ok(None);
+    }
+    match decompress(coding, body, settings.max_decompressed_size) {
+        Ok(decoded) => Ok(Some(decoded)),
+        Err(DecodeError::Corrupt) => Err(RESP_400_ENCODING),
+        Err(DecodeError::TooLarge) => Err(RESP_413),
+    }
+}
+
+/// Attach a decoded or rewritten request body to a proxied request.
+fn with_decoded_body(mut result: RequestResult, decoded: Option<Vec<u8>>) -> RequestResult {
+    if let RequestResult::Proxy {
+        ref mut request_body,
+        ..
+    } = result
+    {
+        *request_body = decoded;
+    }
+    result
+}
+
+/// Carry the plugins' edits of the client's request headers to the
+/// upstream request: an edited header is sent with every value it has
+/// now, or dropped when a plugin removed it. A header a plugin set in
+/// `ctx.upstream_headers` wins over both.
+fn forward_header_edits(
+    ctx: &PluginContext,
+    overrides: &mut Vec<(String, String)>,
+    removed: &mut Vec<String>,
+) {
+    for name in ctx.request_headers.edited() {
+        if ctx
+            .upstream_headers
+            .keys()
+            .any(|k| k.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+        let before = overrides.len();
+        overrides.extend(
+            ctx.request_headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(k, v)| (k.to_string(), v.to_string())),
+        );
+        if overrides.len() == before {
+            removed.push(name.clone());
+        }
+    }
+}
+
+/// Body size of a request: its `content-length`, else the bytes given
+/// (a decoded chunked body).
+fn request_body_size(headers: &[(&str, &str)], body: &[u8]) -> u64 {
+    headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .unwrap_or(body.len() as u64)
 }
 
+/// Default for `ProxyWorker::with_max_filtered_body`.
+pub const DEFAULT_MAX_FILTERED_BODY: usize = 1024 * 1024;
+
+/// Default for `ProxyWorker::with_streaming_idle_timeout`.
+pub const DEFAULT_STREAMING_IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
 impl ProxyWorker {
     pub fn new(
         router: Arc<Router>,
@@ -56,26 +304,300 @@ impl ProxyWorker {
         let mut worker = Self {
             router_version: router.version(),
             router,
+            health_version: 0,
+            dns_version: 0,
+            discovery_version: 0,
+            changes_version: config_cache.changes.version(),
             pipeline_cache: HashMap::with_capacity(64),
+            balancers: Balancers::new(config_cache.breakers.clone()),
             upstreams: HashMap::new(),
             services: HashMap::new(),
-            consumer_keys: HashMap::new(),
+            global_plugins: HashMap::new(),
+            consumer_index: Arc::default(),
+            config_global_plugins: HashMap::new(),
             plugin_registry,
             config_cache,
+            metrics: None,
+            local_metrics: None,
+            matched_route: String::new(),
+            tracer: None,
+            span: None,
+            access_log: None,
+            access: None,
+            max_filtered_body: DEFAULT_MAX_FILTERED_BODY,
+            slow_plugin_threshold: None,
+            timeouts: UpstreamTimeouts::default(),
+            trusted_proxies: TrustedProxies::default(),
+            limits: RequestLimits::default(),
+            client_timeouts: ClientTimeouts::default(),
+            streaming_idle_timeout: DEFAULT_STREAMING_IDLE_TIMEOUT,
+            worker_label: "0".to_string(),
+            open_connections: 0,
+            in_flight: 0,
+            shutdown: None,
+            error_pages: None,
+            error_page: None,
+            counters: Arc::default(),
         };
         worker.snapshot_from_cache();
         worker
     }
 
-    /// Check for config updates. Called once per accept loop iteration.
+    /// Attach the metrics collector. Request and plugin response series
+    /// are buffered per thread; see `flush_metrics`.
+    pub fn with_metrics(mut self, metrics: Arc<MetricsCollector>) -> Self {
+        self.local_metrics = metrics.local();
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Attach the OpenTelemetry tracer. Requests to routes with `tracing`
+    /// get a span, and the upstream request carries it as `traceparent`.
+    pub fn with_tracer(mut self, tracer: Arc<RequestTracer>) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    /// Attach the access logger. Every request, matched or not, gets an
+    /// entry once its response is sent.
+    pub fn with_access_log(mut self, access_log: Arc<AccessLogger>) -> Self {
+        self.access_log = Some(access_log);
+        self
+    }
+
+    /// Set the request size limits of `proxy` config.
+    pub fn with_request_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Request size limits for the connection loop.
+    pub fn request_limits(&self) -> RequestLimits {
+        self.limits
+    }
+
+    /// Set the client connection deadlines of `proxy` config.
+    pub fn with_client_timeouts(mut self, timeouts: ClientTimeouts) -> Self {
+        self.client_timeouts = timeouts;
+        self
+    }
+
+    /// Client connection deadlines for the connection loop.
+    pub fn client_timeouts(&self) -> ClientTimeouts {
+        self.client_timeouts
+    }
+
+    /// Set how long a streaming response may go without new bytes.
+    pub fn with_streaming_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.streaming_idle_timeout = timeout;
+        self
+    }
+
+    /// Read deadline of streaming responses, in place of the upstream's.
+    pub fn streaming_idle_timeout(&self) -> Duration {
+        self.streaming_idle_timeout
+    }
+
+    /// Name this worker in per-worker metrics.
+    pub fn with_worker_id(mut self, id: usize) -> Self {
+        self.worker_label = id.to_string();
+        self
+    }
+
+    /// Report connections and requests into `counters`; see
+    /// `WorkerStats`.
+    pub fn with_worker_counters(mut self, counters: Arc<WorkerCounters>) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    /// Count a client connection as open until `connection_closed`.
+    pub fn connection_opened(&mut self) {
+        self.open_connections += 1;
+        self.counters.connection_opened();
+    }
+
+    pub fn connection_closed(&mut self) {
+        self.open_connections = self.open_connections.saturating_sub(1);
+        self.counters.connection_closed();
+    }
+
+    /// Client connections currently open on this worker.
+    pub fn open_connections(&self) -> usize {
+        self.open_connections
+    }
+
+    /// Count a request as in flight until `request_finished`.
+    pub fn request_started(&mut self) {
+        self.in_flight += 1;
+    }
+
+    pub fn request_finished(&mut self) {
+        self.in_flight = self.in_flight.saturating_sub(1);
+    }
+
+    /// Requests read and not yet answered on this worker.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    /// Drain when `shutdown` begins; see `draining`.
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Whether the gateway is shutting down: keepalive connections close
+    /// after their current response.
+    pub fn draining(&self) -> bool {
+        self.shutdown.as_ref().is_some_and(|s| s.is_draining())
+    }
+
+    /// Render gateway-generated errors with `pages` where they cover the
+    /// status; see `ErrorPages`.
+    pub fn with_error_pages(mut self, pages: Arc<ErrorPages>) -> Self {
+        self.error_pages = (!pages.is_empty()).then_some(pages);
+        self
+    }
+
+    /// Cap the upstream body size buffered for body-filter plugins.
+    pub fn with_max_filtered_body(mut self, bytes: usize) -> Self {
+        self.max_filtered_body = bytes;
+        self
+    }
+
+    /// Upstream timeouts used where neither the route nor its upstream
+    /// sets one.
+    pub fn with_upstream_timeouts(mut self, timeouts: UpstreamTimeouts) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Honor `X-Forwarded-*` headers from these peers.
+    pub fn with_trusted_proxies(mut self, trusted: TrustedProxies) -> Self {
+        self.trusted_proxies = trusted;
+        self
+    }
+
+    /// Apply `plugins` to every route, under its own plugins and the
+    /// stored global rules.
+    pub fn with_global_plugins(mut self, plugins: HashMap<String, serde_json::Value>) -> Self {
+        self.config_global_plugins = plugins;
+        self.snapshot_global_rules();
+        self.pipeline_cache.clear();
+        self
+    }
+
+    /// Log plugin calls slower than `ms` at warn level. 0 disables it.
+    pub fn with_slow_plugin_threshold(mut self, ms: u64) -> Self {
+        self.slow_plugin_threshold = (ms > 0).then(|| Duration::from_millis(ms));
+        self
+    }
+
+    /// Check for config updates. Called when the worker's config
+    /// notification fires; see `ando_store::notify`.
+    ///
+    /// Only cache entries fed by the routes, services, upstreams and
+    /// consumers recorded in the change log are evicted. A router swap
+    /// with no recorded changes, or a change log that has moved on past
+    /// this worker, falls back to dropping every cache.
     #[inline]
     pub fn maybe_update_router(&mut self, new_router: Arc<Router>) {
         let v = new_router.version();
-        if v != self.router_version {
-            self.router = new_router;
-            self.router_version = v;
+        let changes_v = self.config_cache.changes.version();
+        if v == self.router_version && changes_v == self.changes_version {
+            return;
+        }
+        let swapped = v != self.router_version;
+        self.router = new_router;
+        self.router_version = v;
+
+        match self.config_cache.changes.since(self.changes_version) {
+            Some((changes, latest)) if !(swapped && changes.is_empty()) => {
+                self.changes_version = latest;
+                self.apply_changes(&changes);
+            }
+            _ => {
+                self.changes_version = changes_v;
+                self.pipeline_cache.clear();
+                self.balancers.clear();
+                self.snapshot_from_cache();
+            }
+        }
+    }
+
+    /// Evict the thread-local cache entries that depend on `changes` and
+    /// re-snapshot the changed entities.
+    fn apply_changes(&mut self, changes: &ChangeSet) {
+        for id in &changes.routes {
+            self.pipeline_cache.remove(id);
+            self.balancers.route.remove(id);
+        }
+        if !changes.services.is_empty() {
+            // Routes inherit their service's plugins.
+            for route in self.router.routes().values() {
+                if let Some(ref svc_id) = route.service_id
+                    && changes.services.contains(svc_id)
+                {
+                    self.pipeline_cache.remove(&route.id);
+                }
+            }
+        }
+        for id in &changes.services {
+            self.balancers.service.remove(id);
+            match self.config_cache.services.get(id) {
+                Some(svc) => self.services.insert(id.clone(), svc.value().clone()),
+                None => self.services.remove(id),
+            };
+        }
+        for id in &changes.upstreams {
+            self.balancers.named.remove(id);
+            match self.config_cache.upstreams.get(id) {
+                Some(ups) => self.upstreams.insert(id.clone(), ups.value().clone()),
+                None => self.upstreams.remove(id),
+            };
+        }
+        if changes.consumers {
+            self.snapshot_consumers();
+        }
+        if changes.global_rules {
+            // Every route's pipeline includes the global plugins.
+            self.snapshot_global_rules();
             self.pipeline_cache.clear();
-            self.snapshot_from_cache();
+        }
+    }
+
+    /// Check for upstream health changes. Called once per accept loop
+    /// iteration.
+    #[inline]
+    pub fn maybe_update_health(&mut self) {
+        let v = self.config_cache.health.version();
+        if v != self.health_version {
+            self.health_version = v;
+            self.balancers.down = self.config_cache.health.unhealthy_snapshot();
+        }
+    }
+
+    /// Check for re-resolved upstream hostnames. Called once per accept
+    /// loop iteration.
+    #[inline]
+    pub fn maybe_update_dns(&mut self) {
+        let v = self.config_cache.dns.version();
+        if v != self.dns_version {
+            self.dns_version = v;
+            self.balancers.set_dns(self.config_cache.dns.snapshot());
+        }
+    }
+
+    /// Check for changed endpoints of discovered services. Called once per
+    /// accept loop iteration.
+    #[inline]
+    pub fn maybe_update_discovery(&mut self) {
+        let v = self.config_cache.discovery.version();
+        if v != self.discovery_version {
+            self.discovery_version = v;
+            self.balancers
+                .set_discovered(self.config_cache.discovery.snapshot());
         }
     }
 
@@ -91,17 +613,45 @@ impl ProxyWorker {
             self.services
                 .insert(entry.key().clone(), entry.value().clone());
         }
-        self.consumer_keys.clear();
-        for entry in self.config_cache.consumer_key_index.iter() {
-            self.consumer_keys
-                .insert(entry.key().clone(), entry.value().clone());
+        self.snapshot_consumers();
+        self.snapshot_global_rules();
+    }
+
+    /// Merge the global rules, in id order, over `plugins.global`.
+    fn snapshot_global_rules(&mut self) {
+        let mut rules: Vec<GlobalRule> = self
+            .config_cache
+            .global_rules
+            .iter()
+            .map(|e| e.value().clone())
+            .collect();
+        rules.sort_by(|a, b| a.id.cmp(&b.id));
+        self.global_plugins = self.config_global_plugins.clone();
+        for rule in rules {
+            self.global_plugins.extend(rule.plugins);
         }
     }
 
-    /// Collect all unique upstream addresses from config (for pool pre-warming).
+    fn snapshot_consumers(&mut self) {
+        let consumers: Vec<_> = self
+            .config_cache
+            .consumers
+            .iter()
+            .map(|e| e.value().clone())
+            .collect();
+        self.consumer_index = Arc::new(ConsumerIndex::build(&consumers));
+    }
+
+    /// Collect all unique plain-HTTP upstream addresses from config (for
+    /// pool pre-warming; TLS, gRPC and PROXY protocol upstreams connect on
+    /// demand).
     pub fn upstream_addresses(&self) -> Vec<String> {
         let mut addrs = Vec::new();
-        for ups in self.upstreams.values() {
+        for ups in self
+            .upstreams
+            .values()
+            .filter(|u| !u.is_tls() && !u.is_grpc() && u.send_proxy_protocol.is_none())
+        {
             for addr in ups.nodes.keys() {
                 if !addrs.contains(addr) {
                     addrs.push(addr.clone());
@@ -110,7 +660,11 @@ impl ProxyWorker {
         }
         // Also check routes with inline upstreams
         for route in self.router.routes().values() {
-            if let Some(ref ups) = route.upstream {
+            if let Some(ref ups) = route.upstream
+                && !ups.is_tls()
+                && !ups.is_grpc()
+                && ups.send_proxy_protocol.is_none()
+            {
                 for addr in ups.nodes.keys() {
                     if !addrs.contains(addr) {
                         addrs.push(addr.clone());
@@ -124,7 +678,8 @@ impl ProxyWorker {
     /// Hot path: process request. Returns what to do next.
     ///
     /// Takes &str header references (zero-copy from read buffer).
-    /// No DashMap access. No unnecessary allocations.
+    /// No DashMap access (except circuit breaker lookups for upstreams with
+    /// passive checks while a node is failing). No unnecessary allocations.
     #[inline]
     pub fn handle_request(
         &mut self,
@@ -134,144 +689,713 @@ impl ProxyWorker {
         headers: &[(&str, &str)],
         client_ip: &str,
     ) -> RequestResult {
+        self.handle_request_with_body(method, path, host, headers, client_ip, &[])
+    }
+
+    /// `handle_request` with the request body bytes at hand. The body is
+    /// only copied for routes whose plugins read it.
+    pub fn handle_request_with_body(
+        &mut self,
+        method: &str,
+        path: &str,
+        host: Option<&str>,
+        headers: &[(&str, &str)],
+        client_ip: &str,
+        body: &[u8],
+    ) -> RequestResult {
+        self.handle_request_with_scheme(
+            "http",
+            HTTP_LISTENER,
+            None,
+            method,
+            path,
+            host,
+            headers,
+            client_ip,
+            body,
+        )
+    }
+
+    /// `handle_request_with_body` for a request that arrived over `scheme`
+    /// (`"http"` or `"https"`), exposed to plugins as `ctx.scheme`, on the
+    /// listener named `listener`, which routes with `listeners` must name.
+    /// `client_cert` is set on TLS connections to SSL objects with
+    /// `client_tls`: requests are refused with 400 when it is required and
+    /// did not verify, and plugins see it as `ctx.vars["ssl_client_*"]`.
+    ///
+    /// `peer_ip` is the immediate peer; the client address seen by routing,
+    /// plugins and logs is resolved from it via `trusted_proxies`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn handle_request_with_scheme(
+        &mut self,
+        scheme: &'static str,
+        listener: &str,
+        client_cert: Option<&ClientCert>,
+        method: &str,
+        path: &str,
+        host: Option<&str>,
+        headers: &[(&str, &str)],
+        peer_ip: &str,
+        body: &[u8],
+    ) -> RequestResult {
+        self.counters.request();
+        let client_ip = self.trusted_proxies.client_ip(peer_ip, headers);
+        let mut result = self.route_request(
+            scheme,
+            listener,
+            client_cert,
+            method,
+            path,
+            host,
+            headers,
+            client_ip,
+            body,
+        );
+        if let RequestResult::Proxy {
+            ref mut upstream_headers,
+            ..
+        } = result
+        {
+            add_forwarded_headers(
+                upstream_headers,
+                &self.trusted_proxies,
+                peer_ip,
+                client_ip,
+                scheme,
+                headers,
+            );
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn route_request(
+        &mut self,
+        scheme: &'static str,
+        listener: &str,
+        client_cert: Option<&ClientCert>,
+        method: &str,
+        path: &str,
+        host: Option<&str>,
+        headers: &[(&str, &str)],
+        client_ip: &str,
+        body: &[u8],
+    ) -> RequestResult {
+        self.access = self
+            .access_log
+            .as_ref()
+            .map(|log| log.entry(method, path, host, headers, client_ip));
+        self.error_page = None;
+        if client_cert.is_some_and(ClientCert::refused) {
+            return RequestResult::Static(RESP_400_CERT);
+        }
+
         // ── Route match — extract data immediately, release borrow ──
-        let (route_id, has_plugins, upstream_addr, upstream_path) = {
-            let route = match self.router.match_route(method, path, host) {
-                Some(r) => r,
-                None => return RequestResult::Static(RESP_404),
+        let (
+            route_id,
+            has_plugins,
+            mut resolved,
+            upstream_path,
+            route_params,
+            service_id,
+            upgrade,
+            streaming,
+            route_timeout,
+            decoded,
+        ) = {
+            // Match on the path alone; the query string feeds `arg_*` vars.
+            let (route_path, query) = match path.split_once('?') {
+                Some((p, q)) => (p, Some(q)),
+                None => (path, None),
+            };
+            let ctx = MatchContext {
+                listener,
+                client_ip,
+                headers,
+                query,
+            };
+            let matched = match self
+                .router
+                .match_route_params(method, route_path, host, &ctx)
+            {
+                Some(m) => m,
+                None => {
+                    self.matched_route.clear();
+                    self.span = None;
+                    self.error_page =
+                        ErrorPageContext::new(self.error_pages.as_ref(), None, "", headers);
+                    return self.with_error_page(RequestResult::Static(RESP_404));
+                }
+            };
+            let route = matched.route;
+            if self.local_metrics.is_some() {
+                self.matched_route.clone_from(&route.id);
+            }
+            if let Some(ref mut access) = self.access {
+                access.route_id.clone_from(&route.id);
+            }
+            // Small bodies are read in full by now; bigger ones are
+            // refused here, before they are streamed to the upstream.
+            if let Some(max) = route.max_body_size
+                && request_body_size(headers, body) > max
+            {
+                self.span = None;
+                return RequestResult::Static(RESP_413);
+            }
+            let decoded = match route.request_decompression {
+                Some(ref settings) => match decode_request_body(headers, body, settings) {
+                    Ok(decoded) => decoded,
+                    Err(resp) => {
+                        self.span = None;
+                        return RequestResult::Static(resp);
+                    }
+                },
+                None => None,
+            };
+            self.span = match self.tracer {
+                Some(ref tracer) if route.tracing => {
+                    tracer.start(method, path, &route.id, matched.uri, headers)
+                }
+                _ => None,
             };
 
             let id = route.id.clone();
-            let has_plugins = !route.plugins.is_empty()
-                || route.plugin_config_id.is_some()
-                || route.service_id.is_some();
-            let addr = self.resolve_upstream(route);
-            let up_path = compute_upstream_path(&route.uri, path, route.strip_prefix);
-            (id, has_plugins, addr, up_path)
+            let has_plugins = route.has_plugins() || !self.global_plugins.is_empty();
+            let req = RequestAttrs {
+                client_ip,
+                path,
+                host,
+                headers,
+            };
+            let resolved = Self::resolve_upstream(
+                route,
+                &self.upstreams,
+                &self.services,
+                &mut self.balancers,
+                &req,
+            )
+            .for_route(&route.id);
+            let up_path = compute_upstream_path(matched.uri, path, route.strip_prefix);
+            let upgrade = route.enable_websocket && is_websocket_upgrade(headers);
+            // Only the plugin pipeline reads captures; skip the copies otherwise.
+            let params: Vec<(String, String)> = if has_plugins {
+                matched
+                    .params
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            let service_id = if has_plugins {
+                route.service_id.clone()
+            } else {
+                None
+            };
+            (
+                id,
+                has_plugins,
+                resolved,
+                up_path,
+                params,
+                service_id,
+                upgrade,
+                route.streaming,
+                route.timeout.unwrap_or_default(),
+                decoded,
+            )
         };
         // immutable borrow of self.router is now released
 
         // ── FAST PATH: no plugins → proxy directly ──
         if !has_plugins {
-            return RequestResult::Proxy {
-                upstream_addr,
+            self.note_config_error(&resolved);
+            self.error_page =
+                ErrorPageContext::new(self.error_pages.as_ref(), None, &route_id, headers);
+            let result = self.note_upstream(resolved.into_result(
                 upstream_path,
-            };
+                upgrade,
+                streaming,
+                route_timeout,
+                self.timeouts,
+            ));
+            return with_decoded_body(self.with_error_page(result), decoded);
         }
 
         // ── SLOW PATH: plugin pipeline ──
         let pipeline = self.get_or_build_pipeline(&route_id);
+        self.error_page = ErrorPageContext::new(
+            self.error_pages.as_ref(),
+            pipeline.error_pages(),
+            &route_id,
+            headers,
+        );
 
         // Build PluginContext (only for routes WITH plugins)
-        let header_map: HashMap<String, String> = headers
+        let mut request_headers: Vec<(String, String)> = headers
             .iter()
-            .map(|(k, v)| (k.to_lowercase(), v.to_string()))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
+        // Plugins see a decoded body as the upstream will receive it.
+        let body = match decoded {
+            Some(ref decoded) => {
+                request_headers.retain(|(k, _)| {
+                    !k.eq_ignore_ascii_case("content-encoding")
+                        && !k.eq_ignore_ascii_case("content-length")
+                });
+                request_headers.push(("content-length".to_string(), decoded.len().to_string()));
+                decoded.as_slice()
+            }
+            None => body,
+        };
 
         let mut ctx = PluginContext::new(
             route_id.clone(),
             client_ip.to_string(),
             method.to_string(),
             path.to_string(),
-            header_map,
+            HashMap::new(),
         );
+        ctx.request_headers = request_headers.into();
+        ctx.scheme = scheme;
+        if let Some(cert) = client_cert {
+            cert.add_vars(&mut ctx.vars);
+        }
+        ctx.route_params = route_params;
+        ctx.service_id = service_id;
+        ctx.request_body_size = match decoded {
+            Some(ref decoded) => decoded.len() as u64,
+            None => request_body_size(headers, body),
+        };
+        if pipeline.reads_request_body() {
+            ctx.request_body = Some(body.to_vec());
+        }
+        if pipeline.has_auth_plugins() {
+            ctx.consumers = Some(Arc::clone(&self.consumer_index));
+        }
 
-        // Execute Rewrite + Access phases
-        for phase in &[Phase::Rewrite, Phase::Access] {
-            match pipeline.execute_phase(*phase, &mut ctx) {
+        // Execute Rewrite + Access + BeforeProxy phases
+        for phase in &[Phase::Rewrite, Phase::Access, Phase::BeforeProxy] {
+            let outcome = pipeline.execute_phase(*phase, &mut ctx);
+            // real-ip may have derived another client address.
+            if ctx.client_ip != client_ip
+                && let Some(ref mut access) = self.access
+            {
+                access.client_ip.clone_from(&ctx.client_ip);
+            }
+            match outcome {
                 PluginResult::Continue => {}
                 PluginResult::Response {
                     status,
                     headers,
                     body,
                 } => {
-                    return RequestResult::PluginResponse {
+                    self.record_plugin_response(ctx.responder.as_deref(), status);
+                    self.note_consumer(ctx.consumer.as_deref());
+                    self.note_plugin_outcomes(&ctx);
+                    let response = RequestResult::PluginResponse {
                         status,
                         headers,
                         body: body.unwrap_or_default(),
                     };
+                    return if ctx.error_page {
+                        self.with_error_page(response)
+                    } else {
+                        response
+                    };
                 }
             }
         }
+        self.note_consumer(ctx.consumer.as_deref());
+        self.note_plugin_outcomes(&ctx);
 
-        // Consumer validation (key-auth)
-        if pipeline.has_auth_plugins()
-            && let Some(key) = ctx.vars.get("_key_auth_key").and_then(|v| v.as_str())
-        {
-            match self.consumer_keys.get(key) {
-                Some(username) => ctx.consumer = Some(username.clone()),
-                None => return RequestResult::Static(RESP_401_INVALID),
+        // A plugin (request-transformer) may have rewritten a body it read
+        // in full; the upstream is sent that instead.
+        let rewritten = match ctx.request_body {
+            Some(ref edited)
+                if body.len() as u64 == ctx.request_body_size && edited.as_slice() != body =>
+            {
+                Some(edited.clone())
             }
-        }
+            _ => None,
+        };
 
-        // Before proxy phase
-        match pipeline.execute_phase(Phase::BeforeProxy, &mut ctx) {
-            PluginResult::Continue => {}
-            PluginResult::Response {
-                status,
+        // A plugin (traffic-split) may have chosen another upstream.
+        if let Some(addr) = ctx.upstream_addr.take() {
+            resolved = Resolved::Node {
+                addr,
+                passive: None,
+                tls: None,
+                grpc: false,
+                host: None,
+                proxy_protocol: None,
+                timeout: Timeout::default(),
+                retry: None,
+            };
+        } else if let Some(id) = ctx.upstream_id.take() {
+            let req = RequestAttrs {
+                client_ip,
+                path,
+                host,
                 headers,
-                body,
-            } => {
-                return RequestResult::PluginResponse {
-                    status,
-                    headers,
-                    body: body.unwrap_or_default(),
-                };
+            };
+            match self.upstreams.get(&id) {
+                Some(ups) => {
+                    if let Some(picked) = self.balancers.pick(UpstreamScope::Named, &id, ups, &req)
+                    {
+                        resolved = picked.for_route(&route_id);
+                    }
+                }
+                None => {
+                    tracing::debug!(route = %route_id, upstream = %id, "Plugin chose an unknown upstream")
+                }
             }
         }
+        self.note_config_error(&resolved);
 
-        RequestResult::Proxy {
-            upstream_addr,
+        let result = self.note_upstream(resolved.into_result(
             upstream_path,
+            upgrade,
+            streaming,
+            route_timeout,
+            self.timeouts,
+        ));
+        let mut result = self.with_error_page(result);
+        if let RequestResult::Proxy {
+            ref mut upstream_headers,
+            ref mut removed_headers,
+            ref mut response_headers,
+            ref mut pending_access,
+            ref mut response_plugins,
+            ..
+        } = result
+        {
+            if pipeline.has_async_access() {
+                *pending_access = Some(Box::new(PendingAccess {
+                    futures: pipeline.access_futures(&mut ctx),
+                }));
+            }
+            forward_header_edits(&ctx, upstream_headers, removed_headers);
+            upstream_headers.extend(ctx.upstream_headers.drain());
+            response_headers.extend(ctx.response_headers.drain());
+            if pipeline.has_phase(Phase::HeaderFilter)
+                || pipeline.has_phase(Phase::BodyFilter)
+                || pipeline.has_phase(Phase::Log)
+            {
+                *response_plugins = Some(Box::new(ResponsePlugins {
+                    pipeline,
+                    ctx,
+                    max_body: self.max_filtered_body,
+                }));
+            }
         }
+        with_decoded_body(result, rewritten.or(decoded))
     }
 
     /// Resolve upstream address from local snapshot (never DashMap).
-    fn resolve_upstream(&self, route: &Route) -> String {
+    ///
+    /// Takes the snapshot maps explicitly rather than `&self` so the matched
+    /// route can stay borrowed from `self.router` while balancer state is
+    /// advanced.
+    fn resolve_upstream(
+        route: &Route,
+        upstreams: &HashMap<String, Upstream>,
+        services: &HashMap<String, Service>,
+        balancers: &mut Balancers,
+        req: &RequestAttrs<'_>,
+    ) -> Resolved {
         if let Some(ref ups) = route.upstream
-            && let Some(addr) = ups.first_node()
+            && let Some(resolved) = balancers.pick(UpstreamScope::Route, &route.id, ups, req)
         {
-            return addr.to_string();
+            return resolved;
         }
-        if let Some(ref id) = route.upstream_id
-            && let Some(ups) = self.upstreams.get(id)
-            && let Some(addr) = ups.first_node()
-        {
-            return addr.to_string();
+        if let Some(ref id) = route.upstream_id {
+            let Some(ups) = upstreams.get(id) else {
+                return Resolved::Missing("missing_upstream");
+            };
+            if let Some(resolved) = balancers.pick(UpstreamScope::Named, id, ups, req) {
+                return resolved;
+            }
         }
-        if let Some(ref svc_id) = route.service_id
-            && let Some(svc) = self.services.get(svc_id)
-        {
+        if let Some(ref svc_id) = route.service_id {
+            let Some(svc) = services.get(svc_id) else {
+                return Resolved::Missing("missing_service");
+            };
             if let Some(ref ups) = svc.upstream
-                && let Some(addr) = ups.first_node()
+                && let Some(resolved) = balancers.pick(UpstreamScope::Service, svc_id, ups, req)
             {
-                return addr.to_string();
+                return resolved;
             }
-            if let Some(ref ups_id) = svc.upstream_id
-                && let Some(ups) = self.upstreams.get(ups_id)
-                && let Some(addr) = ups.first_node()
-            {
-                return addr.to_string();
+            if let Some(ref ups_id) = svc.upstream_id {
+                let Some(ups) = upstreams.get(ups_id) else {
+                    return Resolved::Missing("missing_upstream");
+                };
+                if let Some(resolved) = balancers.pick(UpstreamScope::Named, ups_id, ups, req) {
+                    return resolved;
+                }
             }
         }
-        "127.0.0.1:80".to_string()
+        Resolved::Missing("no_upstream")
     }
 
-    fn get_or_build_pipeline(&mut self, route_id: &str) -> Arc<PluginPipeline> {
-        if let Some(cached) = self.pipeline_cache.get(route_id) {
-            return Arc::clone(cached);
+    /// Name the chosen upstream on the request span and access log entry,
+    /// and pass the span on to the upstream as its parent.
+    fn note_upstream(&mut self, mut result: RequestResult) -> RequestResult {
+        if let Some(ref mut access) = self.access
+            && let RequestResult::Proxy {
+                ref upstream_addr, ..
+            } = result
+        {
+            access.upstream_addr = Some(upstream_addr.clone());
+        }
+        if let Some(ref mut span) = self.span
+            && let RequestResult::Proxy {
+                ref upstream_addr,
+                ref mut upstream_headers,
+                ..
+            } = result
+        {
+            span.set_upstream(upstream_addr);
+            upstream_headers.push(("traceparent".to_string(), span.traceparent()));
         }
+        result
+    }
 
-        let route = self.router.get_route(route_id);
-        let mut merged: HashMap<String, serde_json::Value> = HashMap::new();
-        let mut has_auth = false;
+    /// Count a request refused because its route references missing config.
+    fn note_config_error(&self, resolved: &Resolved) {
+        if let Resolved::Missing(kind) = resolved
+            && let Some(ref metrics) = self.metrics
+        {
+            metrics.record_config_error(kind);
+        }
+    }
 
-        if let Some(route) = route {
-            if let Some(ref svc_id) = route.service_id
-                && let Some(svc) = self.services.get(svc_id)
-            {
-                for (name, config) in &svc.plugins {
-                    merged.insert(name.clone(), config.clone());
+    /// Count the outcomes plugins left in `ctx.vars`: proxy-cache's
+    /// `cache_status`, proxy-mirror's `mirror` and fault-injection's
+    /// `fault_injection` list.
+    fn note_plugin_outcomes(&mut self, ctx: &PluginContext) {
+        let Some(ref mut metrics) = self.local_metrics else {
+            return;
+        };
+        let var = |name: &str| ctx.vars.get(name).and_then(|v| v.as_str());
+        if let Some(status) = var("cache_status") {
+            metrics.record_cache_status(&ctx.route_id, status);
+        }
+        if let Some(outcome) = var("mirror") {
+            metrics.record_mirror(&ctx.route_id, outcome);
+        }
+        if let Some(faults) = ctx.vars.get("fault_injection").and_then(|v| v.as_array()) {
+            for fault in faults.iter().filter_map(|f| f.as_str()) {
+                metrics.record_fault(&ctx.route_id, fault);
+            }
+        }
+    }
+
+    /// Name the authenticated consumer on the request span and access log
+    /// entry.
+    fn note_consumer(&mut self, consumer: Option<&str>) {
+        if let Some(ref mut span) = self.span {
+            span.set_consumer(consumer);
+        }
+        if let Some(ref mut access) = self.access {
+            access.consumer = consumer.map(str::to_string);
+        }
+    }
+
+    /// Node to retry a request on after `failed` failed with `cause`.
+    /// `None` when `cause` is not in the upstream's `retry_on`, or when
+    /// the attempts or time budget are spent or every node has been tried;
+    /// the last two count as exhausted.
+    pub fn retry_node(
+        &mut self,
+        retry: &mut Retry,
+        failed: &str,
+        cause: RetryOn,
+    ) -> Option<UpstreamTarget> {
+        if !retry.retry_on.contains(&cause) {
+            return None;
+        }
+        retry.tried.push(failed.to_string());
+        let in_budget = retry.remaining > 0 && retry.deadline.is_none_or(|d| Instant::now() < d);
+        let ups = match retry.scope {
+            UpstreamScope::Route => self
+                .router
+                .get_route(&retry.upstream)
+                .and_then(|r| r.upstream.as_ref()),
+            UpstreamScope::Service => self
+                .services
+                .get(&retry.upstream)
+                .and_then(|s| s.upstream.as_ref()),
+            UpstreamScope::Named => self.upstreams.get(&retry.upstream),
+        };
+        let next = match ups {
+            Some(ups) if in_budget => self.balancers.pick_retry(retry, ups),
+            _ => None,
+        };
+        let outcome = if next.is_some() {
+            retry.remaining -= 1;
+            "retried"
+        } else {
+            "exhausted"
+        };
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_upstream_retry(&retry.route, outcome);
+        }
+        match next? {
+            Resolved::Node {
+                addr,
+                tls,
+                host,
+                proxy_protocol,
+                ..
+            } => Some(UpstreamTarget {
+                addr,
+                tls,
+                host,
+                proxy_protocol,
+            }),
+            Resolved::Tripped(_) | Resolved::Missing(_) | Resolved::NoEndpoints => None,
+        }
+    }
+
+    /// Start timing the exchange of the request just handled. The route is
+    /// only copied when metrics are enabled.
+    #[inline]
+    pub fn exchange(&mut self, started: Instant) -> Exchange {
+        Exchange {
+            route: if self.local_metrics.is_some() {
+                self.matched_route.clone()
+            } else {
+                String::new()
+            },
+            started,
+            span: self.span.take(),
+            access: self.access.take(),
+            error_page: self.error_page.take(),
+            upstream_error: None,
+        }
+    }
+
+    /// `result` with the body of the request's error page, if one covers
+    /// its status. Proxied results pass through.
+    fn with_error_page(&self, result: RequestResult) -> RequestResult {
+        let status = match result {
+            RequestResult::Static(resp) => crate::connection::static_status(resp),
+            RequestResult::PluginResponse { status, .. } => status,
+            RequestResult::Proxy { .. } => return result,
+        };
+        let Some((content_type, body)) = self
+            .error_page
+            .as_ref()
+            .and_then(|page| page.render(status))
+        else {
+            return result;
+        };
+        let mut headers = match result {
+            RequestResult::PluginResponse { headers, .. } => headers,
+            _ => Vec::new(),
+        };
+        headers.retain(|(name, _)| !name.eq_ignore_ascii_case("content-type"));
+        headers.push(("content-type".to_string(), content_type));
+        RequestResult::PluginResponse {
+            status,
+            headers,
+            body,
+        }
+    }
+
+    /// Record a finished exchange under its route, end its span and log it.
+    /// `status` is the one the client was sent; an upstream failure noted
+    /// on the exchange is counted and logged alongside.
+    #[inline]
+    pub fn record_exchange(&mut self, exchange: Exchange, method: &str, status: u16) {
+        self.counters.response(status);
+        let elapsed = exchange.started.elapsed();
+        if let Some(ref mut metrics) = self.local_metrics {
+            metrics.record_request(&exchange.route, method, status, elapsed.as_secs_f64());
+            if let Some(kind) = exchange.upstream_error {
+                metrics.record_upstream_error(&exchange.route, kind);
+            }
+        }
+        if let Some(span) = exchange.span
+            && let Some(ref tracer) = self.tracer
+        {
+            tracer.finish(span, status, elapsed);
+        }
+        if let Some(mut access) = exchange.access
+            && let Some(ref log) = self.access_log
+        {
+            access.response_status = status;
+            access.latency_ms = elapsed.as_secs_f64() * 1000.0;
+            access.upstream_error = exchange.upstream_error.map(str::to_string);
+            log.log(access);
+        }
+    }
+
+    /// Count a request answered by a plugin instead of the upstream.
+    pub fn record_plugin_response(&mut self, plugin: Option<&str>, status: u16) {
+        if let Some(ref mut metrics) = self.local_metrics {
+            metrics.record_plugin_response(plugin.unwrap_or(""), status);
+        }
+    }
+
+    /// Push this thread's buffered series and pool size to the shared
+    /// collector. Called periodically by the worker loop.
+    pub fn flush_metrics(&mut self, pool: &ConnPool) {
+        if let Some(ref mut metrics) = self.local_metrics {
+            metrics.report_pool_connections(pool.idle_counts());
+            metrics.report_open_connections(&self.worker_label, self.open_connections);
+            metrics.flush();
+        }
+    }
+
+    /// Feed the outcome of a proxied exchange into the node's circuit
+    /// breaker. `failure` is `None` for a successful exchange.
+    pub fn record_upstream_outcome(
+        &self,
+        addr: &str,
+        passive: &PassiveHealthCheck,
+        failure: Option<UpstreamFailure>,
+    ) {
+        let breakers = &self.config_cache.breakers;
+        let transition = match failure {
+            None => breakers.record_success(addr),
+            Some(f) => breakers.record_failure(addr, f, passive, Instant::now()),
+        };
+        match transition {
+            Some(BreakerTransition::Opened) => {
+                tracing::warn!(node = %addr, "Upstream circuit breaker opened");
+                if let Some(ref m) = self.metrics {
+                    m.record_breaker_transition(addr, "open");
+                }
+            }
+            Some(BreakerTransition::Closed) => {
+                tracing::info!(node = %addr, "Upstream circuit breaker closed");
+                if let Some(ref m) = self.metrics {
+                    m.record_breaker_transition(addr, "closed");
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn get_or_build_pipeline(&mut self, route_id: &str) -> Arc<PluginPipeline> {
+        if let Some(cached) = self.pipeline_cache.get(route_id) {
+            return Arc::clone(cached);
+        }
+
+        let route = self.router.get_route(route_id);
+        // Global plugins, then the service's, then the route's own: a
+        // later level replaces a config of the same plugin.
+        let mut merged = self.global_plugins.clone();
+        let mut has_auth = false;
+
+        if let Some(route) = route {
+            if let Some(ref svc_id) = route.service_id
+                && let Some(svc) = self.services.get(svc_id)
+            {
+                for (name, config) in &svc.plugins {
+                    merged.insert(name.clone(), config.clone());
                 }
             }
             for (name, config) in &route.plugins {
@@ -279,33 +1403,679 @@ impl ProxyWorker {
             }
         }
 
-        let mut instances: Vec<Arc<dyn ando_plugin::plugin::PluginInstance>> = Vec::new();
+        let mut entries = Vec::new();
         for (name, config) in &merged {
-            if matches!(name.as_str(), "key-auth" | "jwt-auth" | "basic-auth") {
+            if is_disabled(config) {
+                continue;
+            }
+            if matches!(
+                name.as_str(),
+                "key-auth" | "jwt-auth" | "basic-auth" | "hmac-auth" | "mtls-auth"
+            ) {
                 has_auth = true;
             }
-            if let Some(factory) = self.plugin_registry.get(name)
-                && let Ok(inst) = factory.configure(config)
-            {
-                instances.push(Arc::from(inst));
+            let Some(factory) = self.plugin_registry.get(name) else {
+                continue;
+            };
+            // A route may reorder the plugin for itself with
+            // `_meta.priority` and limit it to some requests with
+            // `_meta.filter`.
+            let configured = priority_override(config)
+                .and_then(|priority| Ok((priority, PluginFilter::from_config(config)?)))
+                .map_err(anyhow::Error::msg)
+                .and_then(|meta| Ok((factory.configure(config)?, meta)));
+            match configured {
+                Ok((inst, (priority, filter))) => {
+                    let entry = PipelineEntry::new(Arc::from(inst));
+                    entries.push(PipelineEntry {
+                        priority: priority.unwrap_or(entry.priority),
+                        filter,
+                        ..entry
+                    });
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        route = %route_id,
+                        plugin = %name,
+                        error = %e,
+                        "plugin config failed to load, skipping plugin"
+                    );
+                    if let Some(ref metrics) = self.metrics {
+                        metrics.record_plugin_config_error(route_id, name);
+                    }
+                }
             }
         }
 
-        let pipeline = Arc::new(PluginPipeline::build(instances, has_auth));
+        let mut pipeline = PluginPipeline::build_entries(entries, has_auth);
+        // With a single plugin, the route's latency already tells which
+        // one is slow; only pay for the clock if metrics want the series.
+        let metrics = self.metrics.clone().filter(|m| m.is_enabled());
+        if metrics.is_some() || (self.slow_plugin_threshold.is_some() && pipeline.len() > 1) {
+            pipeline = pipeline.with_timer(Arc::new(PluginTimings {
+                metrics,
+                slow: self.slow_plugin_threshold,
+            }));
+        }
+        let pipeline = Arc::new(pipeline);
         self.pipeline_cache
             .insert(route_id.to_string(), Arc::clone(&pipeline));
         pipeline
     }
 }
 
+/// Feeds plugin call durations into `ando_plugin_duration_seconds` and
+/// warns about slow calls.
+struct PluginTimings {
+    metrics: Option<Arc<MetricsCollector>>,
+    slow: Option<Duration>,
+}
+
+impl PluginTimer for PluginTimings {
+    fn observe(&self, route_id: &str, plugin: &str, phase: Phase, elapsed: Duration) {
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_plugin_duration(plugin, phase.as_str(), elapsed.as_secs_f64());
+        }
+        if let Some(slow) = self.slow
+            && elapsed > slow
+        {
+            tracing::warn!(
+                route = %route_id,
+                plugin = %plugin,
+                phase = phase.as_str(),
+                duration_ms = elapsed.as_secs_f64() * 1000.0,
+                "slow plugin"
+            );
+        }
+    }
+}
+
+// ── Load balancing ────────────────────────────────────────────
+
+/// Where an upstream definition lives. Inline upstreams have no ID of their
+/// own, so their balancer state is keyed by the owning route or service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum UpstreamScope {
+    Route,
+    Service,
+    Named,
+}
+
+/// Request attributes available to hashing balancers.
+pub struct RequestAttrs<'a> {
+    pub client_ip: &'a str,
+    pub path: &'a str,
+    pub host: Option<&'a str>,
+    pub headers: &'a [(&'a str, &'a str)],
+}
+
+impl<'a> RequestAttrs<'a> {
+    /// Resolve the hash key for a `chash` upstream. Missing headers and
+    /// cookies fall back to the client IP, as in APISIX.
+    fn hash_key(&self, hash_on: &HashOn) -> &'a str {
+        let found = match hash_on {
+            HashOn::Var(name) => match name.as_str() {
+                "uri" => Some(self.path),
+                "host" => self.host,
+                _ => None,
+            },
+            HashOn::Header(name) => self
+                .headers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(name))
+                .map(|(_, v)| *v),
+            HashOn::Cookie(name) => self
+                .headers
+                .iter()
+                .filter(|(k, _)| k.eq_ignore_ascii_case("cookie"))
+                .find_map(|(_, v)| cookie_value(v, name)),
+        };
+        found.unwrap_or(self.client_ip)
+    }
+}
+
+/// Outcome of node selection for a request.
+enum Resolved {
+    Node {
+        addr: String,
+        passive: Option<Arc<PassiveHealthCheck>>,
+        tls: Option<UpstreamTls>,
+        grpc: bool,
+        host: Option<String>,
+        proxy_protocol: Option<ProxyProtocolVersion>,
+        /// Timeouts set on the upstream.
+        timeout: Timeout,
+        retry: Option<Box<Retry>>,
+    },
+    /// Every node's circuit breaker is open; retry after this many seconds.
+    Tripped(u64),
+    /// The route references an upstream or service that does not exist,
+    /// or has none at all. Holds the `ando_config_errors_total` kind.
+    Missing(&'static str),
+    /// The upstream's discovered service has no ready endpoint.
+    NoEndpoints,
+}
+
+impl Resolved {
+    /// `addr` is what the balancer picked: `node` itself, or one of the
+    /// addresses it resolved to. TLS and the Host header go by the node.
+    fn node(addr: &str, node: &str, ups: &Upstream) -> Self {
+        Resolved::Node {
+            addr: addr.to_string(),
+            passive: ups.passive_check().cloned(),
+            tls: UpstreamTls::for_node(ups, node),
+            grpc: ups.is_grpc(),
+            host: ups.host_header(node),
+            proxy_protocol: ups.send_proxy_protocol,
+            timeout: ups.timeouts(),
+            retry: None,
+        }
+    }
+
+    /// Attribute retries to `route`.
+    fn for_route(mut self, route: &str) -> Self {
+        if let Resolved::Node {
+            retry: Some(ref mut retry),
+            ..
+        } = self
+        {
+            retry.route = route.to_string();
+        }
+        self
+    }
+
+    /// `route_timeout` overrides the upstream's timeouts, which override
+    /// `defaults`.
+    fn into_result(
+        self,
+        upstream_path: String,
+        upgrade: bool,
+        streaming: bool,
+        route_timeout: Timeout,
+        defaults: UpstreamTimeouts,
+    ) -> RequestResult {
+        match self {
+            Resolved::Node {
+                addr,
+                passive,
+                tls,
+                grpc,
+                host,
+                proxy_protocol,
+                timeout,
+                retry,
+            } => RequestResult::Proxy {
+                upstream_addr: addr,
+                upstream_path,
+                upgrade,
+                streaming,
+                passive,
+                tls,
+                grpc,
+                upstream_host: host,
+                proxy_protocol,
+                timeouts: defaults.with(route_timeout.or(timeout)),
+                retry,
+                upstream_headers: Vec::new(),
+                removed_headers: Vec::new(),
+                response_headers: Vec::new(),
+                pending_access: None,
+                response_plugins: None,
+                request_body: None,
+            },
+            Resolved::Tripped(retry_after) => RequestResult::PluginResponse {
+                status: 503,
+                headers: vec![
+                    ("content-type".to_string(), "application/json".to_string()),
+                    ("retry-after".to_string(), retry_after.to_string()),
+                ],
+                body: br#"{"error":"no healthy upstream","status":503}"#.to_vec(),
+            },
+            Resolved::NoEndpoints => RequestResult::PluginResponse {
+                status: 503,
+                headers: vec![("content-type".to_string(), "application/json".to_string())],
+                body: br#"{"error":"no endpoints for discovered service","status":503}"#.to_vec(),
+            },
+            Resolved::Missing(kind) => {
+                let error = match kind {
+                    "no_upstream" => "route has no upstream",
+                    _ => "route config references a missing object",
+                };
+                RequestResult::PluginResponse {
+                    status: 503,
+                    headers: vec![("content-type".to_string(), "application/json".to_string())],
+                    body: format!(r#"{{"error":"{error}","kind":"{kind}","status":503}}"#)
+                        .into_bytes(),
+                }
+            }
+        }
+    }
+}
+
+/// Thread-local balancer state, one entry per multi-node upstream.
+///
+/// Built lazily on first use and dropped on router version bumps, so
+/// weight edits are picked up together with the rest of the snapshot.
+/// A node given by hostname counts once per address it resolved to.
+#[derive(Default)]
+struct Balancers {
+    route: HashMap<String, Balancer>,
+    service: HashMap<String, Balancer>,
+    named: HashMap<String, Balancer>,
+    /// Named upstream id → nodes ejected by the active health checker.
+    down: HashMap<String, HashSet<String>>,
+    /// Shared passive health state, consulted only for upstreams with
+    /// `checks.passive` configured.
+    breakers: CircuitBreakers,
+    /// Addresses of hostname nodes and discovered services.
+    addrs: NodeAddresses,
+    /// Balancers built over each hostname or discovered service, dropped
+    /// when its addresses change.
+    by_source: HashMap<AddressSource, HashSet<(UpstreamScope, String)>>,
+}
+
+/// What the addresses of a balancer were taken from, besides the
+/// upstream's own `nodes`.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum AddressSource {
+    Host(String),
+    Service(DiscoveryType, String),
+}
+
+impl Balancers {
+    fn new(breakers: CircuitBreakers) -> Self {
+        Self {
+            breakers,
+            ..Default::default()
+        }
+    }
+
+    /// Select a node from `ups`. Upstreams with a single address bypass
+    /// the balancer; a discovered service without endpoints yields
+    /// `Resolved::NoEndpoints`.
+    ///
+    /// Nodes ejected by active checks are skipped unless every node is down.
+    /// Nodes with an open circuit breaker are always skipped; if none remain
+    /// the request is rejected with `Resolved::Tripped`.
+    ///
+    /// A node of a multi-node upstream with `retries` comes with the
+    /// `Retry` state that picks the next one.
+    #[inline]
+    fn pick(
+        &mut self,
+        scope: UpstreamScope,
+        id: &str,
+        ups: &Upstream,
+        req: &RequestAttrs<'_>,
+    ) -> Option<Resolved> {
+        let mut resolved = self.pick_node(scope, id, ups, req)?;
+        if ups.retries > 0
+            && self.addrs.count(ups) > 1
+            && let Resolved::Node { ref mut retry, .. } = resolved
+        {
+            let hash_key = (ups.lb_type == "chash").then(|| req.hash_key(&ups.hash_source()));
+            *retry = Some(Box::new(Retry {
+                route: String::new(),
+                scope,
+                upstream: id.to_string(),
+                hash_key: hash_key.map(str::to_string),
+                retry_on: ups.retry_on.clone(),
+                remaining: ups.retries,
+                deadline: (ups.retry_timeout > 0.0)
+                    .then(|| Duration::try_from_secs_f64(ups.retry_timeout).ok())
+                    .flatten()
+                    .map(|budget| Instant::now() + budget),
+                tried: Vec::new(),
+            }));
+        }
+        Some(resolved)
+    }
+
+    /// Pick the next node for `retry`: one not tried yet, skipping nodes
+    /// that are down or have an open breaker. `None` if the upstream is
+    /// gone or no such node is left.
+    fn pick_retry(&mut self, retry: &Retry, ups: &Upstream) -> Option<Resolved> {
+        let passive = ups.passive_check().is_some();
+        let now = Instant::now();
+        let breakers = &self.breakers;
+        let addrs = &self.addrs;
+        let down = match retry.scope {
+            UpstreamScope::Named => self.down.get(&retry.upstream),
+            _ => None,
+        };
+        let usable = |a: &str| {
+            let tried = retry.tried.iter().any(|t| t == a);
+            let tripped = passive && breakers.is_open(a, now);
+            let is_down = down.is_some_and(|d| d.contains(addrs.origin(ups, a)));
+            !(tried || tripped || is_down)
+        };
+        let node = |a: &str| Resolved::node(a, addrs.origin(ups, a), ups);
+        let admitted = |a: &&str| !passive || breakers.try_trial(a, now);
+        let map = match retry.scope {
+            UpstreamScope::Route => &mut self.route,
+            UpstreamScope::Service => &mut self.service,
+            UpstreamScope::Named => &mut self.named,
+        };
+        match map.get_mut(&retry.upstream) {
+            Some(balancer) => balancer
+                .pick_where(retry.hash_key.as_deref(), usable)
+                .filter(admitted)
+                .map(node),
+            None => {
+                let nodes = addrs.endpoints(ups);
+                let mut sorted: Vec<&str> = nodes.keys().map(String::as_str).collect();
+                sorted.sort_unstable();
+                sorted
+                    .into_iter()
+                    .find(|a| usable(a))
+                    .filter(admitted)
+                    .map(node)
+            }
+        }
+    }
+
+    #[inline]
+    fn pick_node(
+        &mut self,
+        scope: UpstreamScope,
+        id: &str,
+        ups: &Upstream,
+        req: &RequestAttrs<'_>,
+    ) -> Option<Resolved> {
+        let passive = ups.passive_check().is_some();
+        let now = Instant::now();
+        let breakers = &self.breakers;
+        let addrs = &self.addrs;
+        let tripped = |a: &str| passive && breakers.is_open(a, now);
+        let retry_after = || {
+            let nodes = addrs.endpoints(ups);
+            Resolved::Tripped(breakers.retry_after(nodes.keys().map(String::as_str), now))
+        };
+        let node = |a: &str| Resolved::node(a, addrs.origin(ups, a), ups);
+
+        match addrs.count(ups) {
+            0 if ups.discovery_type.is_some() => return Some(Resolved::NoEndpoints),
+            0 | 1 => {
+                let (addr, first) = addrs.single(ups)?;
+                if tripped(addr) || (passive && !breakers.try_trial(addr, now)) {
+                    return Some(retry_after());
+                }
+                return Some(Resolved::node(addr, first, ups));
+            }
+            _ => {}
+        }
+        let map = match scope {
+            UpstreamScope::Route => &mut self.route,
+            UpstreamScope::Service => &mut self.service,
+            UpstreamScope::Named => &mut self.named,
+        };
+        if !map.contains_key(id) {
+            map.insert(
+                id.to_string(),
+                Balancer::for_nodes(&ups.lb_type, &addrs.endpoints(ups)),
+            );
+            for source in NodeAddresses::sources(ups) {
+                self.by_source
+                    .entry(source)
+                    .or_default()
+                    .insert((scope, id.to_string()));
+            }
+        }
+        let balancer = map.get_mut(id)?;
+        let key = if balancer.needs_hash_key() {
+            Some(req.hash_key(&ups.hash_source()))
+        } else {
+            None
+        };
+        let down = match scope {
+            UpstreamScope::Named => self.down.get(id),
+            _ => None,
+        };
+
+        if !passive && down.is_none() {
+            return balancer.pick(key).or_else(|| ups.first_node()).map(node);
+        }
+
+        let is_down = |a: &str| down.is_some_and(|d| d.contains(addrs.origin(ups, a)));
+        loop {
+            let addr = match balancer.pick_where(key, |a| !is_down(a) && !tripped(a)) {
+                Some(addr) => addr,
+                // Every usable node is ejected by active checks: send traffic
+                // anyway, but still honour open breakers.
+                None => match balancer.pick_where(key, |a| !tripped(a)) {
+                    Some(addr) => addr,
+                    None if passive => return Some(retry_after()),
+                    None => return ups.first_node().map(node),
+                },
+            };
+            // A half-open node takes one trial request. When another request
+            // got it first, the node now counts as tripped: pick again.
+            if !passive || breakers.try_trial(addr, now) {
+                return Some(node(addr));
+            }
+        }
+    }
+
+    /// Take in a new DNS snapshot, dropping the balancers built over the
+    /// nodes whose addresses changed.
+    fn set_dns(&mut self, resolved: HashMap<String, Vec<SocketAddr>>) {
+        let resolved: HashMap<String, Vec<String>> = resolved
+            .into_iter()
+            .map(|(node, addrs)| (node, addrs.iter().map(ToString::to_string).collect()))
+            .collect();
+        let old = &self.addrs.dns;
+        let changed: HashSet<&String> = old
+            .keys()
+            .chain(resolved.keys())
+            .filter(|node| old.get(*node) != resolved.get(*node))
+            .collect();
+        let changed: Vec<AddressSource> = changed
+            .into_iter()
+            .map(|node| AddressSource::Host(node.clone()))
+            .collect();
+        self.drop_built_from(changed);
+        self.addrs.dns = resolved;
+    }
+
+    /// Take in a new discovery snapshot, dropping the balancers built over
+    /// the services whose nodes changed.
+    fn set_discovered(
+        &mut self,
+        discovered: HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>>,
+    ) {
+        let old = &self.addrs.discovered;
+        let services = |all: &HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>>| {
+            all.iter()
+                .flat_map(|(kind, services)| services.keys().map(move |s| (*kind, s.clone())))
+                .collect::<HashSet<_>>()
+        };
+        let get =
+            |all: &HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>>,
+             kind: &DiscoveryType,
+             service: &str| { all.get(kind).and_then(|s| s.get(service)).cloned() };
+        let changed: Vec<AddressSource> = services(old)
+            .union(&services(&discovered))
+            .filter(|(kind, service)| get(old, kind, service) != get(&discovered, kind, service))
+            .map(|(kind, service)| AddressSource::Service(*kind, service.clone()))
+            .collect();
+        self.drop_built_from(changed);
+        self.addrs.discovered = discovered;
+    }
+
+    fn drop_built_from(&mut self, sources: Vec<AddressSource>) {
+        for source in sources {
+            for (scope, id) in self.by_source.remove(&source).unwrap_or_default() {
+                match scope {
+                    UpstreamScope::Route => self.route.remove(&id),
+                    UpstreamScope::Service => self.service.remove(&id),
+                    UpstreamScope::Named => self.named.remove(&id),
+                };
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.route.clear();
+        self.service.clear();
+        self.named.clear();
+        self.by_source.clear();
+    }
+}
+
+/// Addresses of upstream nodes that are not in the upstream itself: those
+/// of resolved hostnames and of discovered services.
+#[derive(Default)]
+struct NodeAddresses {
+    /// Hostname node → addresses it last resolved to.
+    dns: HashMap<String, Vec<String>>,
+    /// Registry → service → ready endpoints.
+    discovered: HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>>,
+}
+
+impl NodeAddresses {
+    /// Discovered addresses of `ups`; `None` if it has no `discovery_type`.
+    fn discovered<'a>(&'a self, ups: &'a Upstream) -> Option<impl Iterator<Item = &'a str>> {
+        let (kind, service) = ups.discovery()?;
+        let nodes = self
+            .discovered
+            .get(&kind)
+            .and_then(|services| services.get(service))
+            .map_or(&[][..], Vec::as_slice);
+        Some(nodes_on_port(nodes, ups.port_name.as_deref()))
+    }
+
+    /// Addresses the balancer spreads `ups` over. A discovered upstream
+    /// uses its service's endpoints, at equal weights. Otherwise these are
+    /// its nodes, with each resolved hostname replaced by all of its
+    /// addresses at the node's weight; nodes not resolved yet are kept and
+    /// resolved when connecting.
+    fn endpoints(&self, ups: &Upstream) -> HashMap<String, u32> {
+        if let Some(found) = self.discovered(ups) {
+            return found.map(|addr| (addr.to_string(), 1)).collect();
+        }
+        let mut nodes = HashMap::with_capacity(ups.nodes.len());
+        for (node, weight) in &ups.nodes {
+            match self.dns.get(node) {
+                Some(addrs) => {
+                    for addr in addrs {
+                        *nodes.entry(addr.clone()).or_default() += weight;
+                    }
+                }
+                None => {
+                    *nodes.entry(node.clone()).or_default() += weight;
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Number of addresses `ups` is spread over; see [`Self::endpoints`].
+    fn count(&self, ups: &Upstream) -> usize {
+        if let Some(found) = self.discovered(ups) {
+            return found.count();
+        }
+        ups.nodes
+            .keys()
+            .map(|node| self.dns.get(node).map_or(1, Vec::len))
+            .sum()
+    }
+
+    /// The address of a single-address upstream, with its node.
+    fn single<'a>(&'a self, ups: &'a Upstream) -> Option<(&'a str, &'a str)> {
+        if let Some(mut found) = self.discovered(ups) {
+            return found.next().map(|addr| (addr, addr));
+        }
+        let first = ups.first_node()?;
+        let addr = self
+            .dns
+            .get(first)
+            .and_then(|addrs| addrs.first())
+            .map_or(first, String::as_str);
+        Some((addr, first))
+    }
+
+    /// The configured node of `ups` that the picked `addr` belongs to.
+    /// Discovered addresses are nodes of their own.
+    fn origin<'a>(&self, ups: &'a Upstream, addr: &'a str) -> &'a str {
+        if ups.nodes.contains_key(addr) {
+            return addr;
+        }
+        ups.nodes
+            .keys()
+            .find(|node| {
+                self.dns
+                    .get(*node)
+                    .is_some_and(|addrs| addrs.iter().any(|a| a == addr))
+            })
+            .map_or(addr, String::as_str)
+    }
+
+    /// What a balancer for `ups` is built from, so it can be dropped when
+    /// that changes.
+    fn sources(ups: &Upstream) -> Vec<AddressSource> {
+        if let Some((kind, service)) = ups.discovery() {
+            return vec![AddressSource::Service(kind, service.to_string())];
+        }
+        ups.nodes
+            .keys()
+            .filter(|node| is_hostname(node))
+            .map(|node| AddressSource::Host(node.clone()))
+            .collect()
+    }
+}
+
 // ── Request result ────────────────────────────────────────────
 
+// Proxy is the common case; boxing it would allocate on every request.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum RequestResult {
     /// Proxy to upstream at this address, forwarding the given path.
     Proxy {
         upstream_addr: String,
         upstream_path: String,
+        /// WebSocket handshake on a route with `enable_websocket`: the
+        /// upgrade headers are forwarded and a 101 response switches the
+        /// connection to a raw tunnel.
+        upgrade: bool,
+        /// The route has `streaming`: relay the response as a stream
+        /// whatever its content type.
+        streaming: bool,
+        /// Passive health config of the chosen upstream; the connection
+        /// loop reports the exchange outcome when set.
+        passive: Option<Arc<PassiveHealthCheck>>,
+        /// Set for `https` and `grpcs` upstreams: connect with TLS using
+        /// these settings.
+        tls: Option<UpstreamTls>,
+        /// `grpc`/`grpcs` upstream: forward the request over HTTP/2.
+        grpc: bool,
+        /// Host header required by the upstream's `pass_host`; `None`
+        /// forwards the client's.
+        upstream_host: Option<String>,
+        /// Start new upstream connections with a PROXY protocol header.
+        proxy_protocol: Option<ProxyProtocolVersion>,
+        /// Connect, send and read deadlines for the upstream I/O.
+        timeouts: UpstreamTimeouts,
+        /// Set when the upstream allows retries on other nodes; see
+        /// `ProxyWorker::retry_node`.
+        retry: Option<Box<Retry>>,
+        /// Headers set by plugins, replacing client headers of the same name.
+        upstream_headers: Vec<(String, String)>,
+        /// Lowercase names of client headers a plugin removed (e.g.
+        /// key-auth's `hide_credentials`); they are not forwarded.
+        removed_headers: Vec<String>,
+        /// Headers set by plugins before proxying, added to the response.
+        response_headers: Vec<(String, String)>,
+        /// Async access hooks the connection loop must await before
+        /// sending the upstream request.
+        pending_access: Option<Box<PendingAccess>>,
+        /// Set when the route has body-filter or log plugins: the
+        /// connection loop runs them on the upstream response.
+        response_plugins: Option<Box<ResponsePlugins>>,
+        /// Decoded request body, set when the route has
+        /// `request_decompression`, or a body a plugin rewrote: sent
+        /// instead of the client's body, without its `content-encoding`.
+        request_body: Option<Vec<u8>>,
     },
     /// Send a pre-built static response (zero alloc).
     Static(&'static [u8]),
@@ -317,59 +2087,518 @@ pub enum RequestResult {
     },
 }
 
-// ── Connection pool ───────────────────────────────────────────
+/// Retry state of one request: where its upstream is defined, the nodes
+/// tried so far and what is left of its attempts and time budget.
+#[derive(Debug)]
+pub struct Retry {
+    /// Route the request matched, for `ando_upstream_retries_total`.
+    route: String,
+    scope: UpstreamScope,
+    /// Id of the route, service or upstream defining the upstream.
+    upstream: String,
+    /// `chash` key of the request, so retries walk the hash ring.
+    hash_key: Option<String>,
+    retry_on: Vec<RetryOn>,
+    remaining: u32,
+    deadline: Option<Instant>,
+    tried: Vec<String>,
+}
 
-/// Thread-local upstream connection pool.
-/// Avoids TCP handshake on every request (saves ~0.5-2ms RTT).
-///
-/// Pre-warmed at startup: each worker opens N connections to every
-/// known upstream before accepting any traffic.
-pub struct ConnPool {
-    pools: HashMap<String, VecDeque<TcpStream>>,
-    max_idle: usize,
+/// A node to send the request to, with its TLS settings and Host header.
+#[derive(Debug)]
+pub struct UpstreamTarget {
+    pub addr: String,
+    pub tls: Option<UpstreamTls>,
+    pub host: Option<String>,
+    /// PROXY protocol header to start new connections with.
+    pub proxy_protocol: Option<ProxyProtocolVersion>,
 }
 
-impl ConnPool {
-    pub fn new(max_idle_per_host: usize) -> Self {
-        Self {
-            pools: HashMap::with_capacity(16),
-            max_idle: max_idle_per_host,
+impl UpstreamTarget {
+    /// Key of the pooled HTTP/1.1 connections to the target usable by a
+    /// request from `client`. TLS connections are pooled apart from plain
+    /// ones to the same node.
+    pub fn pool_key(&self, client: &ClientAddrs) -> String {
+        let key = match self.tls {
+            Some(ref tls) => tls.pool_key(&self.addr),
+            None => self.addr.clone(),
+        };
+        self.client_key(key, client)
+    }
+
+    /// `key`, narrowed to `client` when connections start with a PROXY
+    /// protocol header: one names a single client and serves no other.
+    pub fn client_key(&self, key: String, client: &ClientAddrs) -> String {
+        match self.proxy_protocol {
+            Some(_) => format!("{key}|proxy={}>{}", client.peer, client.local),
+            None => key,
         }
     }
+}
 
-    #[inline]
-    pub fn take(&mut self, addr: &str) -> Option<TcpStream> {
-        self.pools.get_mut(addr).and_then(|q| q.pop_front())
+/// Deadlines of one upstream exchange. Send and read apply to each write
+/// and read on the upstream connection, not to the exchange as a whole.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpstreamTimeouts {
+    pub connect: Duration,
+    pub send: Duration,
+    pub read: Duration,
+}
+
+impl UpstreamTimeouts {
+    pub fn from_millis(connect: u64, send: u64, read: u64) -> Self {
+        Self {
+            connect: Duration::from_millis(connect),
+            send: Duration::from_millis(send),
+            read: Duration::from_millis(read),
+        }
     }
 
-    #[inline]
-    pub fn put(&mut self, addr: String, stream: TcpStream) {
-        let queue = self
-            .pools
-            .entry(addr)
-            .or_insert_with(|| VecDeque::with_capacity(self.max_idle));
-        if queue.len() < self.max_idle {
-            queue.push_back(stream);
+    /// Replace the deadlines `timeout` sets. Negative or non-finite values
+    /// are ignored.
+    pub fn with(self, timeout: Timeout) -> Self {
+        let secs = |v: Option<f64>, default: Duration| {
+            v.and_then(|v| Duration::try_from_secs_f64(v).ok())
+                .unwrap_or(default)
+        };
+        Self {
+            connect: secs(timeout.connect, self.connect),
+            send: secs(timeout.send, self.send),
+            read: secs(timeout.read, self.read),
         }
-        // else: drop stream (closes fd)
     }
+}
 
-    /// Pre-warm connection pool: open `count` connections to each addr.
-    /// Called once at worker startup, before accepting any traffic.
-    pub async fn warm(&mut self, addrs: &[String], count: usize) {
-        for addr in addrs {
-            // Resolve hostname → IPs once per upstream (std blocking DNS — cold path).
-            // Sort IPv4-first: on macOS `localhost` resolves to `::1` AND `127.0.0.1`,
-            // and most upstream servers are IPv4-only.
-            let socket_addr: std::net::SocketAddr = {
-                let parsed = addr.parse::<std::net::SocketAddr>().ok().or_else(|| {
-                    use std::net::ToSocketAddrs;
-                    let all: Vec<_> = addr.as_str().to_socket_addrs().ok()?.collect();
-                    // prefer IPv4
-                    all.iter()
-                        .copied()
-                        .find(|a| a.is_ipv4())
-                        .or_else(|| all.into_iter().next())
+impl Default for UpstreamTimeouts {
+    /// APISIX's defaults: 60s for each phase.
+    fn default() -> Self {
+        Self::from_millis(60_000, 60_000, 60_000)
+    }
+}
+
+/// Route, start time, span and access log entry of one exchange, for
+/// request metrics, tracing, access logging and the worker's status counts.
+pub struct Exchange {
+    route: String,
+    started: Instant,
+    span: Option<RequestSpan>,
+    access: Option<AccessLogEntry>,
+    error_page: Option<Box<ErrorPageContext>>,
+    /// Why the upstream exchange failed; see [`Exchange::upstream_failed`].
+    upstream_error: Option<&'static str>,
+}
+
+impl Exchange {
+    /// Note that the upstream exchange failed. `kind` is `"connect"`,
+    /// `"connect_timeout"`, `"timeout"`, `"exchange"`,
+    /// `"invalid_response"` or `"truncated"`; it becomes the `kind` of
+    /// `ando_upstream_errors_total` and the access log's `upstream_error`.
+    pub fn upstream_failed(&mut self, kind: &'static str) {
+        self.upstream_error.get_or_insert(kind);
+    }
+
+    /// The error page response for `status`, when one covers it; the
+    /// caller sends its static response otherwise.
+    pub fn error_response(&self, status: u16) -> Option<Vec<u8>> {
+        let (content_type, body) = self.error_page.as_ref()?.render(status)?;
+        let mut resp = Vec::with_capacity(body.len() + 128);
+        build_response(
+            &mut resp,
+            status,
+            &[("content-type".to_string(), content_type)],
+            &body,
+        );
+        Some(resp)
+    }
+}
+
+/// What an error of one request renders with: the route's error pages
+/// over the gateway's, and the request's variables.
+pub struct ErrorPageContext {
+    global: Option<Arc<ErrorPages>>,
+    route: Option<Arc<ErrorPages>>,
+    route_id: String,
+    request_id: String,
+    accept: Option<String>,
+}
+
+impl ErrorPageContext {
+    /// `None`, at no cost, when neither level has pages.
+    fn new(
+        global: Option<&Arc<ErrorPages>>,
+        route: Option<&Arc<ErrorPages>>,
+        route_id: &str,
+        headers: &[(&str, &str)],
+    ) -> Option<Box<Self>> {
+        if global.is_none() && route.is_none() {
+            return None;
+        }
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| *v)
+        };
+        Some(Box::new(Self {
+            global: global.cloned(),
+            route: route.cloned(),
+            route_id: route_id.to_string(),
+            request_id: header("x-request-id").unwrap_or_default().to_string(),
+            accept: header("accept").map(str::to_string),
+        }))
+    }
+
+    /// Content type and body of the page for `status`, if any covers it.
+    fn render(&self, status: u16) -> Option<(String, Vec<u8>)> {
+        let accept = self.accept.as_deref();
+        let page = self
+            .route
+            .as_ref()
+            .and_then(|pages| pages.select(status, accept))
+            .or_else(|| self.global.as_ref()?.select(status, accept))?;
+        let vars = ErrorVars {
+            status,
+            route_id: &self.route_id,
+            request_id: &self.request_id,
+        };
+        Some((page.content_type().to_string(), page.render(&vars)))
+    }
+}
+
+/// Async access hooks carried from `handle_request` to the connection loop.
+pub struct PendingAccess {
+    futures: Vec<(String, AccessFuture)>,
+}
+
+/// Response of an async access hook that rejected the request.
+pub struct Rejection {
+    pub plugin: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Headers from the async access hooks of a request they let through.
+#[derive(Debug, Default)]
+pub struct Admitted {
+    /// Added to the proxied response.
+    pub response_headers: Vec<(String, String)>,
+    /// Set on the upstream request.
+    pub upstream_headers: Vec<(String, String)>,
+}
+
+impl Admitted {
+    /// Merge into the headers `handle_request` prepared: the hooks'
+    /// upstream headers replace plugin headers of the same name.
+    pub fn apply(
+        self,
+        upstream_headers: &mut Vec<(String, String)>,
+        response_headers: &mut Vec<(String, String)>,
+    ) {
+        for (name, value) in self.upstream_headers {
+            upstream_headers.retain(|(n, _)| *n != name);
+            upstream_headers.push((name, value));
+        }
+        response_headers.extend(self.response_headers);
+    }
+}
+
+impl PendingAccess {
+    /// Await the hooks in priority order. `Ok` carries the headers they
+    /// set; `Err` is the response of the first plugin that rejected the
+    /// request.
+    pub async fn run(self) -> Result<Admitted, Rejection> {
+        let mut admitted = Admitted::default();
+        for (plugin, fut) in self.futures {
+            let outcome = fut.await;
+            match outcome.result {
+                PluginResult::Continue => {
+                    admitted.response_headers.extend(outcome.response_headers);
+                    admitted.upstream_headers.extend(outcome.upstream_headers);
+                }
+                PluginResult::Response {
+                    status,
+                    headers,
+                    body,
+                } => {
+                    return Err(Rejection {
+                        plugin,
+                        status,
+                        headers,
+                        body: body.unwrap_or_default(),
+                    });
+                }
+            }
+        }
+        Ok(admitted)
+    }
+}
+
+impl std::fmt::Debug for PendingAccess {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PendingAccess")
+            .field("hooks", &self.futures.len())
+            .finish()
+    }
+}
+
+/// Plugin state carried from `handle_request` to the connection loop for
+/// the phases that run on the upstream response.
+pub struct ResponsePlugins {
+    pipeline: Arc<PluginPipeline>,
+    ctx: PluginContext,
+    /// Upstream bodies larger than this are streamed unfiltered.
+    pub max_body: usize,
+}
+
+impl ResponsePlugins {
+    /// Whether the upstream body must be buffered for body-filter plugins.
+    pub fn filters_body(&self) -> bool {
+        self.pipeline.has_phase(Phase::BodyFilter)
+    }
+
+    /// Whether header-filter plugins rewrite the upstream response head.
+    pub fn filters_headers(&self) -> bool {
+        self.pipeline.has_phase(Phase::HeaderFilter)
+    }
+
+    /// Run the header-filter phase over the end-to-end headers of an
+    /// upstream response and return the headers to send. Plugins edit
+    /// them as `ctx.upstream_response_headers`; what they set in
+    /// `ctx.response_headers` replaces headers of the same name and what
+    /// they add is sent next to them. The upstream's response is sent
+    /// either way: a plugin can't answer in its place this late.
+    pub fn filter_headers(
+        &mut self,
+        status: u16,
+        headers: Vec<(String, String)>,
+    ) -> Vec<(String, String)> {
+        self.ctx.response_status = Some(status);
+        self.ctx.upstream_response_headers = headers;
+        let _ = self
+            .pipeline
+            .execute_phase(Phase::HeaderFilter, &mut self.ctx);
+        let mut headers = std::mem::take(&mut self.ctx.upstream_response_headers);
+        let plugins = &mut self.ctx.response_headers;
+        headers.retain(|(name, _)| !plugins.replaces(name));
+        headers.extend(plugins.drain());
+        headers
+    }
+
+    /// Run the body-filter phase over a complete upstream body and return
+    /// the response to send: the (possibly rewritten) upstream response
+    /// with `extra` headers appended, or the short-circuit response of a
+    /// plugin that rejected it. Plugins see `headers` as
+    /// `ctx.upstream_response_headers`.
+    pub fn apply(
+        &mut self,
+        status: u16,
+        headers: Vec<(String, String)>,
+        extra: &[(String, String)],
+        body: Vec<u8>,
+    ) -> (u16, Vec<(String, String)>, Vec<u8>) {
+        self.ctx.response_status = Some(status);
+        self.ctx.response_body = Some(body);
+        self.ctx.upstream_response_headers = headers;
+        let result = self
+            .pipeline
+            .execute_phase(Phase::BodyFilter, &mut self.ctx);
+        let mut headers = std::mem::take(&mut self.ctx.upstream_response_headers);
+        match result {
+            PluginResult::Continue => {
+                headers.extend(extra.iter().cloned());
+                (
+                    status,
+                    headers,
+                    self.ctx.response_body.take().unwrap_or_default(),
+                )
+            }
+            PluginResult::Response {
+                status,
+                headers,
+                body,
+            } => (status, headers, body.unwrap_or_default()),
+        }
+    }
+
+    /// Run the log phase for a finished exchange; `status` is what the
+    /// client received.
+    pub fn log(&mut self, status: u16) {
+        self.ctx.response_status = Some(status);
+        self.pipeline.execute_log(&self.ctx);
+    }
+}
+
+impl std::fmt::Debug for ResponsePlugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ResponsePlugins")
+            .field("route_id", &self.ctx.route_id)
+            .field("max_body", &self.max_body)
+            .finish_non_exhaustive()
+    }
+}
+
+// ── Connection pool ───────────────────────────────────────────
+
+/// Default for [`ConnPool::with_idle_timeout`].
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Thread-local upstream connection pool.
+/// Avoids TCP handshake on every request (saves ~0.5-2ms RTT).
+///
+/// Pre-warmed at startup: each worker opens N connections to every
+/// known upstream before accepting any traffic.
+///
+/// v2 design: A pooled socket may have been closed by the upstream while
+/// it sat idle, and a write to a half-closed socket can still succeed and
+/// then hang or read EOF. `take()` therefore drops connections older than
+/// the idle timeout and probes the rest with a non-blocking peek before
+/// handing them out; `sweep()` prunes the same way on a timer.
+///
+/// HTTP/2 connections to gRPC upstreams are multiplexed rather than
+/// checked out, so the pool keeps one shared sender per node beside the
+/// idle HTTP/1.1 sockets.
+pub struct ConnPool {
+    pools: HashMap<String, VecDeque<IdleConn>>,
+    h2: HashMap<String, SendRequest<Bytes>>,
+    max_idle: usize,
+    idle_timeout: Duration,
+    stats: PoolStats,
+}
+
+struct IdleConn {
+    stream: UpstreamStream,
+    since: Instant,
+}
+
+/// Pool counters accumulated since the last [`ConnPool::take_stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Checkouts served by a live pooled connection.
+    pub hits: u64,
+    /// Checkouts that found no usable connection.
+    pub misses: u64,
+    /// Connections dropped as expired or dead.
+    pub evictions: u64,
+}
+
+impl ConnPool {
+    pub fn new(max_idle_per_host: usize) -> Self {
+        Self {
+            pools: HashMap::with_capacity(16),
+            h2: HashMap::new(),
+            max_idle: max_idle_per_host,
+            idle_timeout: DEFAULT_POOL_IDLE_TIMEOUT,
+            stats: PoolStats::default(),
+        }
+    }
+
+    /// Set how long a connection may sit idle before it is discarded.
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Check out a live connection for pool key `addr` (the node address,
+    /// or [`UpstreamTls::pool_key`] for TLS), evicting any expired or
+    /// closed ones found ahead of it.
+    pub fn take(&mut self, addr: &str) -> Option<UpstreamStream> {
+        let now = Instant::now();
+        if let Some(queue) = self.pools.get_mut(addr) {
+            while let Some(conn) = queue.pop_front() {
+                if conn.is_reusable(now, self.idle_timeout) {
+                    self.stats.hits += 1;
+                    return Some(conn.stream);
+                }
+                self.stats.evictions += 1;
+            }
+        }
+        self.stats.misses += 1;
+        None
+    }
+
+    #[inline]
+    pub fn put(&mut self, addr: String, stream: UpstreamStream) {
+        let queue = self
+            .pools
+            .entry(addr)
+            .or_insert_with(|| VecDeque::with_capacity(self.max_idle));
+        if queue.len() < self.max_idle {
+            queue.push_back(IdleConn {
+                stream,
+                since: Instant::now(),
+            });
+        }
+        // else: drop stream (closes fd)
+    }
+
+    /// The HTTP/2 connection open to pool key `key`, if any. It may have
+    /// closed since it was stored; callers check with `ready()`.
+    pub fn h2_sender(&self, key: &str) -> Option<SendRequest<Bytes>> {
+        self.h2.get(key).cloned()
+    }
+
+    pub fn put_h2_sender(&mut self, key: String, sender: SendRequest<Bytes>) {
+        self.h2.insert(key, sender);
+    }
+
+    pub fn remove_h2_sender(&mut self, key: &str) {
+        self.h2.remove(key);
+    }
+
+    /// Drop every expired or closed idle connection. Returns how many were
+    /// evicted.
+    pub fn sweep(&mut self) -> usize {
+        let now = Instant::now();
+        let idle_timeout = self.idle_timeout;
+        let mut evicted = 0;
+        for queue in self.pools.values_mut() {
+            let before = queue.len();
+            queue.retain(|conn| conn.is_reusable(now, idle_timeout));
+            evicted += before - queue.len();
+        }
+        self.stats.evictions += evicted as u64;
+        evicted
+    }
+
+    /// Idle connections currently held for `addr`.
+    pub fn idle_count(&self, addr: &str) -> usize {
+        self.pools.get(addr).map_or(0, VecDeque::len)
+    }
+
+    /// Idle connections per pool key.
+    pub fn idle_counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.pools
+            .iter()
+            .map(|(addr, conns)| (addr.as_str(), conns.len()))
+    }
+
+    /// Return the counters accumulated since the previous call and reset
+    /// them.
+    pub fn take_stats(&mut self) -> PoolStats {
+        std::mem::take(&mut self.stats)
+    }
+
+    /// Pre-warm connection pool: open `count` connections to each addr.
+    /// Called once at worker startup, before accepting any traffic.
+    pub async fn warm(&mut self, addrs: &[String], count: usize) {
+        for addr in addrs {
+            // Resolve hostname → IPs once per upstream (std blocking DNS — cold path).
+            // Sort IPv4-first: on macOS `localhost` resolves to `::1` AND `127.0.0.1`,
+            // and most upstream servers are IPv4-only.
+            let socket_addr: std::net::SocketAddr = {
+                let parsed = addr.parse::<std::net::SocketAddr>().ok().or_else(|| {
+                    use std::net::ToSocketAddrs;
+                    let all: Vec<_> = addr.as_str().to_socket_addrs().ok()?.collect();
+                    // prefer IPv4
+                    all.iter()
+                        .copied()
+                        .find(|a| a.is_ipv4())
+                        .or_else(|| all.into_iter().next())
                 });
                 match parsed {
                     Some(sa) => sa,
@@ -389,7 +2618,10 @@ impl ConnPool {
                     Ok(stream) => {
                         // Set TCP_NODELAY on pooled connections
                         let _ = stream.set_nodelay(true);
-                        queue.push_back(stream);
+                        queue.push_back(IdleConn {
+                            stream: UpstreamStream::Plain(stream),
+                            since: Instant::now(),
+                        });
                     }
                     Err(e) => {
                         tracing::warn!(addr = %addr, error = %e, "Pool pre-warm connect failed");
@@ -404,6 +2636,136 @@ impl ConnPool {
     }
 }
 
+impl IdleConn {
+    fn is_reusable(&self, now: Instant, idle_timeout: Duration) -> bool {
+        now.duration_since(self.since) < idle_timeout && self.stream.is_open()
+    }
+}
+
+/// A connection to an upstream node, plain TCP or TLS.
+pub enum UpstreamStream {
+    Plain(TcpStream),
+    Tls {
+        stream: Box<ClientTlsStream<TcpStream>>,
+        /// Socket under the TLS session, for idle liveness probes.
+        #[cfg(unix)]
+        fd: std::os::fd::RawFd,
+    },
+}
+
+impl From<TcpStream> for UpstreamStream {
+    fn from(stream: TcpStream) -> Self {
+        UpstreamStream::Plain(stream)
+    }
+}
+
+impl UpstreamStream {
+    /// Run the TLS handshake for `tls` over a connected socket.
+    pub async fn handshake(tcp: TcpStream, tls: &UpstreamTls) -> std::io::Result<Self> {
+        let name = tls.server_name().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "invalid upstream sni")
+        })?;
+        #[cfg(unix)]
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&tcp);
+        let stream = tls
+            .connector()
+            .connect(name, tcp)
+            .await
+            .map_err(std::io::Error::other)?;
+        Ok(UpstreamStream::Tls {
+            stream: Box::new(stream),
+            #[cfg(unix)]
+            fd,
+        })
+    }
+
+    #[cfg(unix)]
+    fn is_open(&self) -> bool {
+        let fd = match self {
+            UpstreamStream::Plain(stream) => std::os::fd::AsRawFd::as_raw_fd(stream),
+            UpstreamStream::Tls { fd, .. } => *fd,
+        };
+        is_open(fd)
+    }
+
+    #[cfg(not(unix))]
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+impl AsyncReadRent for UpstreamStream {
+    async fn read<T: IoBufMut>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        match self {
+            UpstreamStream::Plain(s) => s.read(buf).await,
+            UpstreamStream::Tls { stream, .. } => stream.read(buf).await,
+        }
+    }
+
+    async fn readv<T: IoVecBufMut>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        match self {
+            UpstreamStream::Plain(s) => s.readv(buf).await,
+            UpstreamStream::Tls { stream, .. } => stream.readv(buf).await,
+        }
+    }
+}
+
+impl AsyncWriteRent for UpstreamStream {
+    async fn write<T: IoBuf>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        match self {
+            UpstreamStream::Plain(s) => s.write(buf).await,
+            UpstreamStream::Tls { stream, .. } => stream.write(buf).await,
+        }
+    }
+
+    async fn writev<T: IoVecBuf>(&mut self, buf: T) -> monoio::BufResult<usize, T> {
+        match self {
+            UpstreamStream::Plain(s) => s.writev(buf).await,
+            UpstreamStream::Tls { stream, .. } => stream.writev(buf).await,
+        }
+    }
+
+    async fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            UpstreamStream::Plain(s) => s.flush().await,
+            UpstreamStream::Tls { stream, .. } => stream.flush().await,
+        }
+    }
+
+    async fn shutdown(&mut self) -> std::io::Result<()> {
+        match self {
+            UpstreamStream::Plain(s) => s.shutdown().await,
+            UpstreamStream::Tls { stream, .. } => stream.shutdown().await,
+        }
+    }
+}
+
+// SAFETY: both variants are `Split`, and a TLS session only reads and
+// writes through its own halves.
+unsafe impl Split for UpstreamStream {}
+
+/// Whether an idle socket can still carry a request.
+///
+/// Peeks one byte without blocking: EOF means the upstream closed it, and
+/// unsolicited bytes (e.g. a 408 sent before closing) would be mistaken for
+/// the next response, so both count as unusable. Only "would block" means
+/// the connection is quiet and open.
+#[cfg(unix)]
+fn is_open(fd: std::os::fd::RawFd) -> bool {
+    let mut byte = 0u8;
+    // SAFETY: the fd is owned by the pooled stream for the duration of the
+    // call and the buffer is a valid 1-byte region.
+    let n = unsafe {
+        libc::recv(
+            fd,
+            (&raw mut byte).cast(),
+            1,
+            libc::MSG_PEEK | libc::MSG_DONTWAIT,
+        )
+    };
+    n < 0 && std::io::Error::last_os_error().kind() == std::io::ErrorKind::WouldBlock
+}
+
 // ── Response building helpers ─────────────────────────────────
 
 /// Build HTTP response into a buffer (no format! overhead).
@@ -427,6 +2789,24 @@ pub fn build_response(buf: &mut Vec<u8>, status: u16, headers: &[(String, String
     buf.extend_from_slice(body);
 }
 
+/// Split an absolute-form request target (`http://host/path`) into its
+/// authority and path. Other targets come back unchanged.
+pub fn split_absolute_form(target: &str) -> (Option<&str>, &str) {
+    let Some(rest) = target
+        .strip_prefix("http://")
+        .or_else(|| target.strip_prefix("https://"))
+    else {
+        return (None, target);
+    };
+    match rest.find('/') {
+        Some(slash) if !rest[..slash].contains('?') && slash > 0 => {
+            (Some(&rest[..slash]), &rest[slash..])
+        }
+        None if !rest.is_empty() && !rest.contains('?') => (Some(rest), "/"),
+        _ => (None, target),
+    }
+}
+
 /// Compute the path to send to the upstream.
 ///
 /// If `strip_prefix` is false, returns `request_path` unchanged.
@@ -461,23 +2841,110 @@ pub fn compute_upstream_path(route_uri: &str, request_path: &str, strip_prefix:
 }
 
 /// Build upstream HTTP request into a buffer. Zero-copy from &str refs.
+///
+/// `overrides` are plugin-set headers; they replace client headers of the
+/// same name.
 pub fn build_upstream_request(
     buf: &mut Vec<u8>,
     method: &str,
     path: &str,
     headers: &[(&str, &str)],
+    overrides: &[(String, String)],
+    host: Option<&str>,
     body: &[u8],
 ) {
+    build_upstream_request_head(buf, method, path, headers, overrides, host, body.len());
+    buf.extend_from_slice(body);
+}
+
+/// Build the upstream request head for a body of `content_length` bytes
+/// that the caller sends separately. The client's own framing headers are
+/// replaced.
+pub fn build_upstream_request_head(
+    buf: &mut Vec<u8>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    overrides: &[(String, String)],
+    host: Option<&str>,
+    content_length: usize,
+) {
+    let framing = Framing::Length(content_length);
+    write_request_head(buf, method, path, headers, overrides, host, framing);
+}
+
+/// Build a WebSocket handshake request for the upstream. Unlike
+/// [`build_upstream_request`], the `upgrade` header is kept and the
+/// connection is marked for upgrade.
+pub fn build_upgrade_request(
+    buf: &mut Vec<u8>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    overrides: &[(String, String)],
+    host: Option<&str>,
+) {
+    write_request_head(
+        buf,
+        method,
+        path,
+        headers,
+        overrides,
+        host,
+        Framing::Upgrade,
+    );
+}
+
+/// Whether the request is a WebSocket handshake: `Connection` lists
+/// `upgrade` and `Upgrade` is `websocket`.
+pub fn is_websocket_upgrade(headers: &[(&str, &str)]) -> bool {
+    let mut connection_upgrade = false;
+    let mut websocket = false;
+    for (name, value) in headers {
+        if name.eq_ignore_ascii_case("connection") {
+            connection_upgrade |= value
+                .split(',')
+                .any(|token| token.trim().eq_ignore_ascii_case("upgrade"));
+        } else if name.eq_ignore_ascii_case("upgrade") {
+            websocket = value.trim().eq_ignore_ascii_case("websocket");
+        }
+    }
+    connection_upgrade && websocket
+}
+
+/// How the upstream request head ends: a body of known length, or a
+/// WebSocket upgrade.
+#[derive(Clone, Copy)]
+enum Framing {
+    Length(usize),
+    Upgrade,
+}
+
+/// `host` (from `pass_host`) replaces the client's Host header; a plugin
+/// override of `host` wins over both.
+fn write_request_head(
+    buf: &mut Vec<u8>,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    overrides: &[(String, String)],
+    host: Option<&str>,
+    framing: Framing,
+) {
+    let upgrade = matches!(framing, Framing::Upgrade);
     buf.clear();
     buf.extend_from_slice(method.as_bytes());
     buf.push(b' ');
     buf.extend_from_slice(path.as_bytes());
     buf.extend_from_slice(b" HTTP/1.1\r\n");
+    let hop = HopByHop::new(headers.iter().copied());
     for (name, value) in headers {
-        if name.eq_ignore_ascii_case("connection")
-            || name.eq_ignore_ascii_case("keep-alive")
-            || name.eq_ignore_ascii_case("transfer-encoding")
-            || name.eq_ignore_ascii_case("upgrade")
+        let handshake = upgrade && name.eq_ignore_ascii_case("upgrade");
+        if (hop.drops(name) && !handshake)
+            || name.eq_ignore_ascii_case("content-length")
+            || name.eq_ignore_ascii_case("expect")
+            || (host.is_some() && name.eq_ignore_ascii_case("host"))
+            || overrides.iter().any(|(o, _)| name.eq_ignore_ascii_case(o))
         {
             continue;
         }
@@ -486,17 +2953,35 @@ pub fn build_upstream_request(
         buf.extend_from_slice(value.as_bytes());
         buf.extend_from_slice(b"\r\n");
     }
-    buf.extend_from_slice(b"connection: keep-alive\r\n");
-    if !body.is_empty() {
+    if let Some(host) = host
+        && !overrides
+            .iter()
+            .any(|(o, _)| o.eq_ignore_ascii_case("host"))
+    {
+        buf.extend_from_slice(b"host: ");
+        buf.extend_from_slice(host.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    for (name, value) in overrides {
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b": ");
+        buf.extend_from_slice(value.as_bytes());
+        buf.extend_from_slice(b"\r\n");
+    }
+    if upgrade {
+        buf.extend_from_slice(b"connection: upgrade\r\n");
+    } else {
+        buf.extend_from_slice(b"connection: keep-alive\r\n");
+    }
+    if let Framing::Length(content_length) = framing
+        && content_length > 0
+    {
         buf.extend_from_slice(b"content-length: ");
         let mut itoa_buf = itoa::Buffer::new();
-        buf.extend_from_slice(itoa_buf.format(body.len()).as_bytes());
+        buf.extend_from_slice(itoa_buf.format(content_length).as_bytes());
         buf.extend_from_slice(b"\r\n");
     }
     buf.extend_from_slice(b"\r\n");
-    if !body.is_empty() {
-        buf.extend_from_slice(body);
-    }
 }
 
 pub fn status_text(status: u16) -> &'static str {
@@ -529,6 +3014,7 @@ mod tests {
     use ando_core::router::Router;
     use ando_plugin::registry::PluginRegistry;
     use ando_store::cache::ConfigCache;
+    use ando_store::changes::Entity;
     use std::collections::HashMap;
     use std::sync::Arc;
 
@@ -641,6 +3127,23 @@ mod tests {
         );
     }
 
+    // ── split_absolute_form ──────────────────────────────────────
+
+    #[test]
+    fn split_absolute_form_separates_authority_and_path() {
+        assert_eq!(
+            split_absolute_form("http://api.example.com:8080/v1?x=1"),
+            (Some("api.example.com:8080"), "/v1?x=1")
+        );
+        assert_eq!(
+            split_absolute_form("https://example.com"),
+            (Some("example.com"), "/")
+        );
+        assert_eq!(split_absolute_form("/plain"), (None, "/plain"));
+        assert_eq!(split_absolute_form("*"), (None, "*"));
+        assert_eq!(split_absolute_form("http:///x"), (None, "http:///x"));
+    }
+
     // ── build_response ───────────────────────────────────────────
 
     #[test]
@@ -699,7 +3202,7 @@ mod tests {
     #[test]
     fn build_upstream_request_basic_format() {
         let mut buf = Vec::new();
-        build_upstream_request(&mut buf, "GET", "/api", &[], b"");
+        build_upstream_request(&mut buf, "GET", "/api", &[], &[], None, b"");
         let text = String::from_utf8(buf).unwrap();
         assert!(text.starts_with("GET /api HTTP/1.1\r\n"));
         assert!(text.contains("connection: keep-alive\r\n"));
@@ -715,7 +3218,7 @@ mod tests {
             ("upgrade", "websocket"),
             ("x-forwarded-for", "1.2.3.4"),
         ];
-        build_upstream_request(&mut buf, "POST", "/", &headers, b"");
+        build_upstream_request(&mut buf, "POST", "/", &headers, &[], None, b"");
         let text = String::from_utf8(buf).unwrap();
         // hop-by-hop must be removed
         assert!(!text.contains("transfer-encoding: chunked"));
@@ -725,10 +3228,190 @@ mod tests {
         assert!(text.contains("x-forwarded-for: 1.2.3.4\r\n"));
     }
 
+    #[test]
+    fn build_upstream_request_drops_connection_listed_headers() {
+        let mut buf = Vec::new();
+        let headers = [
+            ("Connection", "x-hop, keep-alive"),
+            ("x-hop", "secret"),
+            ("proxy-connection", "keep-alive"),
+            ("te", "trailers"),
+            ("trailer", "x-checksum"),
+            ("x-end", "kept"),
+        ];
+        build_upstream_request(&mut buf, "GET", "/", &headers, &[], None, b"");
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("x-hop"));
+        assert!(!text.contains("proxy-connection"));
+        assert!(!text.contains("te: trailers"));
+        assert!(!text.contains("trailer: "));
+        assert!(text.contains("x-end: kept\r\n"));
+        assert!(text.contains("connection: keep-alive\r\n"));
+    }
+
+    #[test]
+    fn build_upstream_request_head_replaces_client_framing() {
+        let mut buf = Vec::new();
+        let headers = [
+            ("content-length", "999"),
+            ("expect", "100-continue"),
+            ("content-type", "application/json"),
+        ];
+        build_upstream_request_head(&mut buf, "POST", "/up", &headers, &[], None, 2_000_000);
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("999"));
+        assert!(!text.contains("expect"));
+        assert!(text.contains("content-type: application/json\r\n"));
+        assert!(text.ends_with("content-length: 2000000\r\n\r\n"));
+    }
+
+    #[test]
+    fn build_upgrade_request_keeps_handshake_headers() {
+        let mut buf = Vec::new();
+        let headers = [
+            ("connection", "Upgrade"),
+            ("upgrade", "websocket"),
+            ("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ=="),
+            ("sec-websocket-version", "13"),
+            ("sec-websocket-protocol", "chat"),
+        ];
+        build_upgrade_request(&mut buf, "GET", "/ws", &headers, &[], None);
+        let text = String::from_utf8(buf).unwrap();
+        assert!(text.contains("upgrade: websocket\r\n"));
+        assert!(text.contains("sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n"));
+        assert!(text.contains("sec-websocket-version: 13\r\n"));
+        assert!(text.contains("sec-websocket-protocol: chat\r\n"));
+        assert!(text.ends_with("connection: upgrade\r\n\r\n"));
+        assert!(!text.contains("Upgrade\r\n"));
+    }
+
+    #[test]
+    fn is_websocket_upgrade_needs_both_headers() {
+        assert!(is_websocket_upgrade(&[
+            ("Connection", "keep-alive, Upgrade"),
+            ("Upgrade", "WebSocket"),
+        ]));
+        assert!(!is_websocket_upgrade(&[("upgrade", "websocket")]));
+        assert!(!is_websocket_upgrade(&[
+            ("connection", "upgrade"),
+            ("upgrade", "h2c"),
+        ]));
+    }
+
+    #[test]
+    fn handle_request_upgrade_only_on_websocket_routes() {
+        let ws: Route = serde_json::from_value(serde_json::json!({
+            "id": "ws", "uri": "/ws", "enable_websocket": true,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let plain = simple_route("plain", "/plain", "127.0.0.1:8080");
+        let mut w = make_worker(vec![ws, plain]);
+        let handshake = [("connection", "upgrade"), ("upgrade", "websocket")];
+
+        let upgrade_of = |result| match result {
+            RequestResult::Proxy { upgrade, .. } => upgrade,
+            other => panic!("Expected Proxy, got {other:?}"),
+        };
+        assert!(upgrade_of(
+            w.handle_request("GET", "/ws", None, &handshake, "1.2.3.4")
+        ));
+        assert!(!upgrade_of(w.handle_request(
+            "GET",
+            "/ws",
+            None,
+            &[],
+            "1.2.3.4"
+        )));
+        assert!(!upgrade_of(
+            w.handle_request("GET", "/plain", None, &handshake, "1.2.3.4")
+        ));
+    }
+
+    #[test]
+    fn handle_request_resolves_route_over_upstream_over_default_timeouts() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "t", "uri": "/t", "timeout": { "read": 120 },
+            "upstream": {
+                "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin",
+                "timeout": { "read": 1, "connect": 0.5 }, "write_timeout_ms": 700
+            }
+        }))
+        .unwrap();
+        let plain = simple_route("plain", "/plain", "127.0.0.1:8080");
+        let defaults = UpstreamTimeouts::from_millis(2000, 5000, 5000);
+        let mut w = make_worker(vec![route, plain]).with_upstream_timeouts(defaults);
+
+        let timeouts_of = |result| match result {
+            RequestResult::Proxy { timeouts, .. } => timeouts,
+            other => panic!("Expected Proxy, got {other:?}"),
+        };
+        assert_eq!(
+            timeouts_of(w.handle_request("GET", "/t", None, &[], "1.2.3.4")),
+            UpstreamTimeouts::from_millis(500, 700, 120_000)
+        );
+        assert_eq!(
+            timeouts_of(w.handle_request("GET", "/plain", None, &[], "1.2.3.4")),
+            defaults
+        );
+        // Nonsense values keep the fallback.
+        let bad = Timeout {
+            read: Some(-1.0),
+            ..Timeout::default()
+        };
+        assert_eq!(defaults.with(bad), defaults);
+    }
+
+    #[test]
+    fn build_upstream_request_overrides_replace_client_headers() {
+        let mut buf = Vec::new();
+        let headers = [("X-User-Id", "spoofed"), ("accept", "*/*")];
+        let overrides = [("x-user-id".to_string(), "alice".to_string())];
+        build_upstream_request(&mut buf, "GET", "/", &headers, &overrides, None, b"");
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("spoofed"));
+        assert!(text.contains("x-user-id: alice\r\n"));
+        assert!(text.contains("accept: */*\r\n"));
+    }
+
+    #[test]
+    fn build_upstream_request_host_override_replaces_client_host() {
+        let mut buf = Vec::new();
+        let headers = [("Host", "gateway.example.com"), ("accept", "*/*")];
+        build_upstream_request(
+            &mut buf,
+            "GET",
+            "/",
+            &headers,
+            &[],
+            Some("backend:8080"),
+            b"",
+        );
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("gateway.example.com"));
+        assert_eq!(text.matches("host: backend:8080\r\n").count(), 1);
+
+        // A plugin-set host wins over pass_host.
+        let mut buf = Vec::new();
+        let overrides = [("host".to_string(), "plugin.example.com".to_string())];
+        build_upstream_request(
+            &mut buf,
+            "GET",
+            "/",
+            &headers,
+            &overrides,
+            Some("backend:8080"),
+            b"",
+        );
+        let text = String::from_utf8(buf).unwrap();
+        assert!(!text.contains("backend:8080"));
+        assert!(text.contains("host: plugin.example.com\r\n"));
+    }
+
     #[test]
     fn build_upstream_request_adds_content_length_for_body() {
         let mut buf = Vec::new();
-        build_upstream_request(&mut buf, "POST", "/", &[], b"body-data");
+        build_upstream_request(&mut buf, "POST", "/", &[], &[], None, b"body-data");
         let text = String::from_utf8(buf).unwrap();
         assert!(text.contains("content-length: 9\r\n"));
         assert!(text.ends_with("body-data"));
@@ -749,291 +3432,1454 @@ mod tests {
         let result = w.handle_request("GET", "/api", None, &[], "1.2.3.4");
         match result {
             RequestResult::Proxy { upstream_addr, .. } => {
-                assert_eq!(upstream_addr, "127.0.0.1:8080");
+                assert_eq!(upstream_addr, "127.0.0.1:8080");
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_disabled_route_returns_404() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/disabled", "status": 0,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+        let result = w.handle_request("GET", "/disabled", None, &[], "1.2.3.4");
+        assert!(matches!(result, RequestResult::Static(RESP_404)));
+    }
+
+    #[test]
+    fn handle_request_wildcard_path_matches_subpaths() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/api/*", "status": 1,
+            "methods": ["GET"],
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+        let result = w.handle_request("GET", "/api/users/list", None, &[], "1.2.3.4");
+        assert!(matches!(result, RequestResult::Proxy { .. }));
+    }
+
+    #[test]
+    fn handle_request_strip_prefix_strips_uri_prefix() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/api/v1/*", "status": 1,
+            "strip_prefix": true,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+
+        // /api/v1/users → /users
+        match w.handle_request("GET", "/api/v1/users", None, &[], "x") {
+            RequestResult::Proxy { upstream_path, .. } => assert_eq!(upstream_path, "/users"),
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+
+        // /api/v1/ → /
+        match w.handle_request("GET", "/api/v1/", None, &[], "x") {
+            RequestResult::Proxy { upstream_path, .. } => assert_eq!(upstream_path, "/"),
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_strip_prefix_uses_matched_uris_entry() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/api/v1/*", "uris": ["/legacy/*"], "status": 1,
+            "strip_prefix": true,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+
+        match w.handle_request("GET", "/legacy/a/b", None, &[], "x") {
+            RequestResult::Proxy { upstream_path, .. } => assert_eq!(upstream_path, "/a/b"),
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_no_strip_prefix_passes_full_path() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/api/v1/*", "status": 1,
+            "strip_prefix": false,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+
+        match w.handle_request("GET", "/api/v1/users", None, &[], "x") {
+            RequestResult::Proxy { upstream_path, .. } => {
+                assert_eq!(upstream_path, "/api/v1/users")
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_method_specific_route() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/only-get",
+            "methods": ["GET"], "status": 1,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+        assert!(matches!(
+            w.handle_request("GET", "/only-get", None, &[], "x"),
+            RequestResult::Proxy { .. }
+        ));
+        assert!(matches!(
+            w.handle_request("POST", "/only-get", None, &[], "x"),
+            RequestResult::Static(RESP_404)
+        ));
+    }
+
+    // ── handle_request — key-auth plugin ────────────────────────
+
+    #[test]
+    fn handle_request_key_auth_missing_key_returns_plugin_401() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+
+        // No apikey header → key-auth plugin returns 401
+        let result = w.handle_request("GET", "/secure", None, &[], "1.2.3.4");
+        match result {
+            RequestResult::PluginResponse { status, .. } => assert_eq!(status, 401),
+            other => panic!("Expected PluginResponse 401, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_key_auth_invalid_key_returns_plugin_401() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
+        // Empty consumer store — key present in header but no consumer has it
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+
+        let result = w.handle_request("GET", "/secure", None, &[("apikey", "bad-key")], "1.2.3.4");
+        match result {
+            RequestResult::PluginResponse { status, body, .. } => {
+                assert_eq!(status, 401);
+                assert_eq!(body, br#"{"error":"Invalid API key","status":401}"#);
+            }
+            other => panic!("Expected PluginResponse 401, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_key_auth_valid_key_proxies_request() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
+
+        // Add consumer with a known key
+        let cache = ConfigCache::new();
+        let mut plugins: HashMap<String, serde_json::Value> = HashMap::new();
+        plugins.insert(
+            "key-auth".to_string(),
+            serde_json::json!({ "key": "valid-key-123" }),
+        );
+        cache.consumers.insert(
+            "alice".to_string(),
+            Consumer {
+                username: "alice".to_string(),
+                plugins,
+                desc: None,
+                labels: HashMap::new(),
+            },
+        );
+        cache.reindex_consumers();
+
+        let mut w = make_worker_with_registry(vec![route], registry, cache);
+        let result = w.handle_request(
+            "GET",
+            "/secure",
+            None,
+            &[("apikey", "valid-key-123")],
+            "1.2.3.4",
+        );
+        assert!(
+            matches!(result, RequestResult::Proxy { .. }),
+            "valid consumer key must proxy the request"
+        );
+    }
+
+    #[test]
+    fn handle_request_consumer_restriction_sees_key_auth_consumer() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/tenant",
+            "status": 1,
+            "plugins": {
+                "key-auth": {},
+                "consumer-restriction": { "whitelist": ["alice"] }
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+
+        let cache = ConfigCache::new();
+        for (name, key) in [("alice", "alice-key"), ("bob", "bob-key")] {
+            let mut plugins: HashMap<String, serde_json::Value> = HashMap::new();
+            plugins.insert("key-auth".to_string(), serde_json::json!({ "key": key }));
+            cache.consumers.insert(
+                name.to_string(),
+                Consumer {
+                    username: name.to_string(),
+                    plugins,
+                    desc: None,
+                    labels: HashMap::new(),
+                },
+            );
+        }
+        cache.reindex_consumers();
+
+        let mut w = make_worker_with_registry(vec![route], registry, cache);
+        let alice = w.handle_request(
+            "GET",
+            "/tenant",
+            None,
+            &[("apikey", "alice-key")],
+            "1.2.3.4",
+        );
+        assert!(matches!(alice, RequestResult::Proxy { .. }));
+        match w.handle_request("GET", "/tenant", None, &[("apikey", "bob-key")], "1.2.3.4") {
+            RequestResult::PluginResponse { status, .. } => assert_eq!(status, 403),
+            other => panic!("Expected 403, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn handle_request_debug_echo_reflects_consumer_and_plugins() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/echo/{id}",
+            "status": 1,
+            "plugins": {
+                "key-auth": {},
+                "debug-echo": { "enabled_in_production": true }
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+
+        let cache = ConfigCache::new();
+        let mut plugins: HashMap<String, serde_json::Value> = HashMap::new();
+        plugins.insert("key-auth".to_string(), serde_json::json!({ "key": "k" }));
+        cache.consumers.insert(
+            "alice".to_string(),
+            Consumer {
+                username: "alice".to_string(),
+                plugins,
+                desc: None,
+                labels: HashMap::new(),
+            },
+        );
+        cache.reindex_consumers();
+
+        let mut w = make_worker_with_registry(vec![route], registry, cache);
+        match w.handle_request("GET", "/echo/7", None, &[("apikey", "k")], "127.0.0.1") {
+            RequestResult::PluginResponse { status, body, .. } => {
+                assert_eq!(status, 200);
+                let echo: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                assert_eq!(echo["consumer"], "alice");
+                assert_eq!(echo["path_params"]["id"], "7");
+                assert_eq!(
+                    echo["executed_plugins"],
+                    serde_json::json!(["key-auth", "debug-echo"])
+                );
+            }
+            other => panic!("Expected echo, got {:?}", other),
+        }
+        // Other clients are proxied normally.
+        let result = w.handle_request("GET", "/echo/7", None, &[("apikey", "k")], "203.0.113.9");
+        assert!(matches!(result, RequestResult::Proxy { .. }));
+    }
+
+    #[test]
+    fn handle_request_with_body_feeds_request_validation() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/users",
+            "status": 1,
+            "plugins": {
+                "request-validation": {
+                    "body_schema": { "type": "object", "required": ["name"] }
+                }
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+
+        let ok = w.handle_request_with_body("POST", "/users", None, &[], "x", br#"{"name":"a"}"#);
+        assert!(matches!(ok, RequestResult::Proxy { .. }));
+        match w.handle_request_with_body("POST", "/users", None, &[], "x", b"{}") {
+            RequestResult::PluginResponse { status, .. } => assert_eq!(status, 400),
+            other => panic!("Expected 400, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn request_transformer_body_replaces_the_client_body() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/users",
+            "status": 1,
+            "plugins": {
+                "request-transformer": {
+                    "remove": { "json": ["debug"] },
+                    "add": { "headers": { "x-route": "$route_id" } }
+                }
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+        let json = [("content-type", "application/json")];
+
+        match w.handle_request_with_body(
+            "POST",
+            "/users",
+            None,
+            &json,
+            "x",
+            br#"{"a":1,"debug":1}"#,
+        ) {
+            RequestResult::Proxy {
+                request_body,
+                upstream_headers,
+                ..
+            } => {
+                assert_eq!(request_body.as_deref(), Some(&br#"{"a":1}"#[..]));
+                assert!(upstream_headers.contains(&("x-route".into(), "r1".into())));
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+        // An untouched body is sent as the client sent it.
+        match w.handle_request_with_body("POST", "/users", None, &json, "x", br#"{"a":1}"#) {
+            RequestResult::Proxy { request_body, .. } => assert!(request_body.is_none()),
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    fn error_pages(config: serde_json::Value) -> Arc<ErrorPages> {
+        Arc::new(ErrorPages::compile(&serde_json::from_value(config).unwrap()).unwrap())
+    }
+
+    #[test]
+    fn no_route_renders_the_error_page_or_keeps_the_static_response() {
+        let mut plain = make_worker(vec![]);
+        let result = plain.handle_request("GET", "/none", None, &[], "1.2.3.4");
+        assert!(matches!(result, RequestResult::Static(RESP_404)));
+
+        let mut w = make_worker(vec![]).with_error_pages(error_pages(serde_json::json!({
+            "404": [
+                {"body_template": r#"{"missing":"{{request_id}}","status":{{status}}}"#},
+                {"content_type": "text/html", "body_template": "<p>{{status}}</p>"}
+            ]
+        })));
+        let headers = [("x-request-id", "r-\"1\"")];
+        match w.handle_request("GET", "/none", None, &headers, "1.2.3.4") {
+            RequestResult::PluginResponse {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 404);
+                assert_eq!(
+                    headers,
+                    vec![("content-type".to_string(), "application/json".to_string())]
+                );
+                assert_eq!(body, br#"{"missing":"r-\"1\"","status":404}"#);
+            }
+            other => panic!("Expected the error page, got {other:?}"),
+        }
+        let html = [("accept", "text/html")];
+        match w.handle_request("GET", "/none", None, &html, "1.2.3.4") {
+            RequestResult::PluginResponse { body, .. } => assert_eq!(body, b"<p>404</p>"),
+            other => panic!("Expected the error page, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn error_page_plugin_overrides_global_pages() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1",
+            "uri": "/limited",
+            "plugins": {
+                "limit-req": {"rate": 1, "burst": 0, "nodelay": true, "rejected_code": 429},
+                "error-page": {"pages": {
+                    "502": {"content_type": "text/plain", "body_template": "{{route_id}} is down"}
+                }}
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let plain = simple_route("plain", "/plain", "127.0.0.1:8080");
+        let mut w = make_worker_with_registry(vec![route, plain], registry, ConfigCache::new())
+            .with_error_pages(error_pages(serde_json::json!({
+                "429": {"body_template": r#"{"slow_down":"{{route_id}}"}"#},
+                "502": {"body_template": r#"{"bad_gateway":"{{route_id}}"}"#}
+            })));
+
+        // Upstream errors after routing use the route's page, then the
+        // gateway's.
+        assert!(matches!(
+            w.handle_request("GET", "/limited", None, &[], "1.2.3.4"),
+            RequestResult::Proxy { .. }
+        ));
+        let resp = w.exchange(Instant::now()).error_response(502).unwrap();
+        let text = String::from_utf8(resp).unwrap();
+        assert!(text.starts_with("HTTP/1.1 502 Bad Gateway\r\n"), "{text}");
+        assert!(text.contains("content-type: text/plain\r\n"), "{text}");
+        assert!(text.ends_with("\r\n\r\nr1 is down"), "{text}");
+        assert!(w.exchange(Instant::now()).error_response(504).is_none());
+
+        w.handle_request("GET", "/plain", None, &[], "1.2.3.4");
+        let resp = w.exchange(Instant::now()).error_response(502).unwrap();
+        assert!(resp.ends_with(br#"{"bad_gateway":"plain"}"#));
+
+        // The limiter opts in: its rejection gets the gateway's 429 page,
+        // with its own headers kept.
+        match w.handle_request("GET", "/limited", None, &[], "1.2.3.4") {
+            RequestResult::PluginResponse { status, body, .. } => {
+                assert_eq!(status, 429);
+                assert_eq!(body, br#"{"slow_down":"r1"}"#);
+            }
+            other => panic!("Expected 429, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn handle_request_hmac_auth_sees_consumer_credentials() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/signed",
+            "plugins": { "hmac-auth": { "clock_skew": 0 } },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let cache = ConfigCache::new();
+        cache.consumers.insert(
+            "alice".to_string(),
+            serde_json::from_value(serde_json::json!({
+                "username": "alice",
+                "plugins": { "hmac-auth": { "access_key": "ak", "secret_key": "s" } }
+            }))
+            .unwrap(),
+        );
+        let mut w = make_worker_with_registry(vec![route], registry, cache);
+
+        let body_of = |result| match result {
+            RequestResult::PluginResponse { status, body, .. } => {
+                assert_eq!(status, 401);
+                String::from_utf8(body).unwrap()
+            }
+            other => panic!("Expected PluginResponse 401, got {:?}", other),
+        };
+        let headers = |ak| {
+            [
+                ("x-hmac-access-key", ak),
+                ("x-hmac-timestamp", "1"),
+                ("x-hmac-signature", "AAAA"),
+            ]
+        };
+        // A known access key gets as far as signature verification.
+        let known = w.handle_request("GET", "/signed", None, &headers("ak"), "1.2.3.4");
+        assert!(body_of(known).contains("Invalid signature"));
+        let unknown = w.handle_request("GET", "/signed", None, &headers("zz"), "1.2.3.4");
+        assert!(body_of(unknown).contains("Invalid access key"));
+    }
+
+    #[test]
+    fn handle_request_rate_limit_headers_reach_proxy_result() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/limited",
+            "plugins": { "rate-limiting": { "count": 2, "time_window": 60, "key": "header.x-tenant" } },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+
+        let remaining = |w: &mut ProxyWorker, tenant| match w.handle_request(
+            "GET",
+            "/limited",
+            None,
+            &[("x-tenant", tenant)],
+            "1.2.3.4",
+        ) {
+            RequestResult::Proxy {
+                response_headers, ..
+            } => response_headers
+                .into_iter()
+                .find(|(k, _)| k == "x-ratelimit-remaining")
+                .map(|(_, v)| v)
+                .unwrap(),
+            other => panic!("Expected Proxy, got {:?}", other),
+        };
+        assert_eq!(remaining(&mut w, "a"), "1");
+        assert_eq!(remaining(&mut w, "a"), "0");
+        assert_eq!(remaining(&mut w, "b"), "1");
+        assert!(matches!(
+            w.handle_request("GET", "/limited", None, &[("x-tenant", "a")], "1.2.3.4"),
+            RequestResult::PluginResponse { status: 429, .. }
+        ));
+    }
+
+    // ── maybe_update_router ──────────────────────────────────────
+
+    #[test]
+    fn maybe_update_router_no_op_on_same_version() {
+        let routes = vec![simple_route("r1", "/a", "127.0.0.1:8080")];
+        let mut w = make_worker(routes);
+        let old_version = w.router_version;
+        let same_router = Arc::new(Router::build(vec![], old_version).unwrap());
+        w.maybe_update_router(same_router);
+        assert_eq!(w.router_version, old_version);
+    }
+
+    #[test]
+    fn maybe_update_router_updates_on_new_version() {
+        let routes = vec![simple_route("r1", "/a", "127.0.0.1:8080")];
+        let mut w = make_worker(routes);
+        let old_version = w.router_version;
+        let new_router = Arc::new(
+            Router::build(
+                vec![simple_route("r2", "/b", "127.0.0.1:9090")],
+                old_version + 1,
+            )
+            .unwrap(),
+        );
+        w.maybe_update_router(Arc::clone(&new_router));
+        assert_eq!(w.router_version, old_version + 1);
+        // New route should now match
+        let result = w.handle_request("GET", "/b", None, &[], "x");
+        assert!(matches!(result, RequestResult::Proxy { .. }));
+    }
+
+    // ── upstream_addresses ───────────────────────────────────────
+
+    #[test]
+    fn upstream_addresses_returns_inline_route_nodes() {
+        let route = simple_route("r1", "/api", "10.0.0.1:8080");
+        let w = make_worker(vec![route]);
+        let addrs = w.upstream_addresses();
+        assert!(addrs.contains(&"10.0.0.1:8080".to_string()));
+    }
+
+    #[test]
+    fn proxy_protocol_upstreams_are_pooled_per_client() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/pp", "status": 1,
+            "upstream": {
+                "nodes": { "10.0.0.1:8080": 1 },
+                "send_proxy_protocol": "v2"
+            }
+        }))
+        .unwrap();
+        let mut w = make_worker(vec![route]);
+        assert!(w.upstream_addresses().is_empty());
+        let RequestResult::Proxy {
+            upstream_addr,
+            proxy_protocol,
+            ..
+        } = w.handle_request("GET", "/pp", None, &[], "x")
+        else {
+            panic!("expected Proxy");
+        };
+        assert_eq!(proxy_protocol, Some(ProxyProtocolVersion::V2));
+
+        let client = |peer: &str| ClientAddrs {
+            peer: peer.parse().unwrap(),
+            local: "10.0.0.9:80".parse().unwrap(),
+        };
+        let mut target = UpstreamTarget {
+            addr: upstream_addr,
+            tls: None,
+            host: None,
+            proxy_protocol,
+        };
+        let (a, b) = (client("192.0.2.1:1000"), client("192.0.2.2:1000"));
+        assert_ne!(target.pool_key(&a), target.pool_key(&b));
+        target.proxy_protocol = None;
+        assert_eq!(target.pool_key(&a), target.pool_key(&b));
+    }
+
+    // ── resolve_upstream: missing config answers 503 ─────────────
+
+    #[test]
+    fn handle_request_missing_reference_answers_503() {
+        let routes = vec![
+            serde_json::from_value(serde_json::json!({
+                "id": "r1", "uri": "/gone-ups", "status": 1, "upstream_id": "ups-9"
+            }))
+            .unwrap(),
+            serde_json::from_value(serde_json::json!({
+                "id": "r2", "uri": "/gone-svc", "status": 1, "service_id": "svc-9"
+            }))
+            .unwrap(),
+            serde_json::from_value(serde_json::json!({
+                "id": "r3", "uri": "/no-ups", "status": 1
+            }))
+            .unwrap(),
+        ];
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker(routes).with_metrics(Arc::clone(&metrics));
+
+        for (path, kind) in [
+            ("/gone-ups", "missing_upstream"),
+            ("/gone-svc", "missing_service"),
+            ("/no-ups", "no_upstream"),
+        ] {
+            match w.handle_request("GET", path, None, &[], "x") {
+                RequestResult::PluginResponse { status, body, .. } => {
+                    assert_eq!(status, 503);
+                    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+                    assert_eq!(body["kind"], kind);
+                }
+                other => panic!("Expected 503, got {:?}", other),
+            }
+        }
+        let counter = metrics.config_errors.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["missing_upstream"]).get(), 1);
+        assert_eq!(counter.with_label_values(&["missing_service"]).get(), 1);
+        assert_eq!(counter.with_label_values(&["no_upstream"]).get(), 1);
+    }
+
+    #[test]
+    fn plugin_less_route_is_counted_timed_and_logged() {
+        use ando_core::config::AccessLogConfig;
+
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "bare", "uri": "/bare", "status": 1,
+            "upstream": { "nodes": { "10.0.0.1:8080": 1 } }
+        }))
+        .unwrap();
+        let logger = AccessLogger::from_config(&AccessLogConfig {
+            enabled: true,
+            path: std::env::temp_dir()
+                .join(format!("ando-fast-path-{}.log", std::process::id()))
+                .to_string_lossy()
+                .into_owned(),
+            ..AccessLogConfig::default()
+        })
+        .unwrap()
+        .unwrap();
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker(vec![route])
+            .with_metrics(Arc::clone(&metrics))
+            .with_access_log(Arc::new(logger));
+
+        let started = Instant::now();
+        let result = w.handle_request("GET", "/bare", None, &[], "203.0.113.9");
+        assert!(matches!(result, RequestResult::Proxy { .. }));
+        let exchange = w.exchange(started);
+        assert_eq!(exchange.route, "bare");
+        assert_eq!(exchange.started, started);
+        assert!(exchange.access.is_some());
+        w.record_exchange(exchange, "GET", 200);
+        w.flush_metrics(&ConnPool::new(1));
+
+        let requests = metrics.http_requests_total.as_ref().unwrap();
+        assert_eq!(requests.with_label_values(&["bare", "GET", "200"]).get(), 1);
+        let duration = metrics.http_request_duration.as_ref().unwrap();
+        assert_eq!(duration.with_label_values(&["bare"]).get_sample_count(), 1);
+    }
+
+    #[test]
+    fn handle_request_mock_route_answers_without_upstream() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/mock", "status": 1,
+            "plugins": { "mock": { "status": 503, "body": { "error": "maintenance" } } }
+        }))
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+        match w.handle_request("GET", "/mock", None, &[], "x") {
+            RequestResult::PluginResponse {
+                status,
+                headers,
+                body,
+            } => {
+                assert_eq!(status, 503);
+                assert!(
+                    headers.contains(&("content-type".to_string(), "application/json".to_string()))
+                );
+                assert_eq!(body, br#"{"error":"maintenance"}"#);
+            }
+            other => panic!("Expected PluginResponse, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn fault_injection_abort_precedes_auth_and_is_counted() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "chaos", "uri": "/chaos", "status": 1,
+            "upstream": { "nodes": { "127.0.0.1:1": 1 } },
+            "plugins": {
+                "key-auth": {},
+                "fault-injection": { "abort": {
+                    "http_status": 503, "body": "injected",
+                    "vars": [[["http_x_chaos", "==", "on"]]]
+                } }
+            }
+        }))
+        .unwrap();
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new())
+            .with_metrics(Arc::clone(&metrics));
+
+        match w.handle_request("GET", "/chaos", None, &[("x-chaos", "on")], "x") {
+            RequestResult::PluginResponse { status, body, .. } => {
+                assert_eq!(status, 503);
+                assert_eq!(body, b"injected");
+            }
+            other => panic!("Expected PluginResponse, got {:?}", other),
+        }
+        // Untagged traffic reaches key-auth.
+        match w.handle_request("GET", "/chaos", None, &[], "x") {
+            RequestResult::PluginResponse { status, .. } => assert_eq!(status, 401),
+            other => panic!("Expected PluginResponse, got {:?}", other),
+        }
+        w.flush_metrics(&ConnPool::new(1));
+        let faults = metrics.fault_injections.as_ref().unwrap();
+        assert_eq!(faults.with_label_values(&["chaos", "abort"]).get(), 1);
+    }
+
+    // ── resolve_upstream: via upstream_id reference ───────────────
+
+    #[test]
+    fn handle_request_resolves_upstream_by_id() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/ref-ups", "status": 1,
+            "upstream_id": "ups1"
+        }))
+        .unwrap();
+        let cache = ConfigCache::new();
+        let ups: Upstream = serde_json::from_value(serde_json::json!({
+            "id": "ups1",
+            "nodes": { "10.0.0.2:9090": 1 },
+            "type": "roundrobin"
+        }))
+        .unwrap();
+        cache.upstreams.insert("ups1".to_string(), ups);
+
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache);
+        let result = w.handle_request("GET", "/ref-ups", None, &[], "x");
+        match result {
+            RequestResult::Proxy { upstream_addr, .. } => {
+                assert_eq!(upstream_addr, "10.0.0.2:9090");
             }
             other => panic!("Expected Proxy, got {:?}", other),
         }
     }
 
+    // ── resolve_upstream: via service_id → upstream ──────────────
+
     #[test]
-    fn handle_request_disabled_route_returns_404() {
+    fn handle_request_resolves_upstream_via_service() {
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/disabled", "status": 0,
-            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            "id": "r1", "uri": "/svc", "status": 1,
+            "service_id": "svc1"
         }))
         .unwrap();
-        let mut w = make_worker(vec![route]);
-        let result = w.handle_request("GET", "/disabled", None, &[], "1.2.3.4");
-        assert!(matches!(result, RequestResult::Static(RESP_404)));
+        let cache = ConfigCache::new();
+        let svc = ando_core::service::Service {
+            id: "svc1".into(),
+            name: None,
+            desc: None,
+            upstream_id: None,
+            upstream: Some(
+                serde_json::from_value(serde_json::json!({
+                    "nodes": { "10.0.0.3:7070": 1 },
+                    "type": "roundrobin"
+                }))
+                .unwrap(),
+            ),
+            plugins: HashMap::new(),
+            hosts: Vec::new(),
+            timeout: None,
+            labels: HashMap::new(),
+        };
+        cache.services.insert("svc1".to_string(), svc);
+
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache);
+        let result = w.handle_request("GET", "/svc", None, &[], "x");
+        match result {
+            RequestResult::Proxy { upstream_addr, .. } => {
+                assert_eq!(upstream_addr, "10.0.0.3:7070");
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    // ── resolve_upstream: weighted round-robin ───────────────────
+
+    fn proxied_addr(w: &mut ProxyWorker, path: &str) -> String {
+        match w.handle_request("GET", path, None, &[], "x") {
+            RequestResult::Proxy { upstream_addr, .. } => upstream_addr,
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
     }
 
     #[test]
-    fn handle_request_wildcard_path_matches_subpaths() {
+    fn handle_request_distributes_by_weight_for_inline_upstream() {
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/api/*", "status": 1,
-            "methods": ["GET"],
-            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            "id": "r1", "uri": "/wrr", "status": 1,
+            "upstream": {
+                "nodes": { "10.0.0.1:8080": 3, "10.0.0.2:8080": 1 },
+                "type": "roundrobin"
+            }
         }))
         .unwrap();
         let mut w = make_worker(vec![route]);
-        let result = w.handle_request("GET", "/api/users/list", None, &[], "1.2.3.4");
-        assert!(matches!(result, RequestResult::Proxy { .. }));
+
+        let picks: Vec<String> = (0..400).map(|_| proxied_addr(&mut w, "/wrr")).collect();
+        let first = picks.iter().filter(|a| *a == "10.0.0.1:8080").count();
+        let second = picks.iter().filter(|a| *a == "10.0.0.2:8080").count();
+        assert_eq!(first, 300);
+        assert_eq!(second, 100);
     }
 
     #[test]
-    fn handle_request_strip_prefix_strips_uri_prefix() {
+    fn handle_request_distributes_by_weight_for_named_upstream() {
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/api/v1/*", "status": 1,
-            "strip_prefix": true,
-            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            "id": "r1", "uri": "/named", "status": 1,
+            "upstream_id": "ups1"
         }))
         .unwrap();
-        let mut w = make_worker(vec![route]);
+        let cache = ConfigCache::new();
+        let ups: Upstream = serde_json::from_value(serde_json::json!({
+            "id": "ups1",
+            "nodes": { "10.0.0.1:8080": 1, "10.0.0.2:8080": 1 }
+        }))
+        .unwrap();
+        cache.upstreams.insert("ups1".to_string(), ups);
 
-        // /api/v1/users → /users
-        match w.handle_request("GET", "/api/v1/users", None, &[], "x") {
-            RequestResult::Proxy { upstream_path, .. } => assert_eq!(upstream_path, "/users"),
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache);
+        let a = proxied_addr(&mut w, "/named");
+        let b = proxied_addr(&mut w, "/named");
+        assert_ne!(a, b, "equal weights must alternate between nodes");
+    }
+
+    fn chash_route(hash_on: serde_json::Value) -> Route {
+        let mut ups = serde_json::json!({
+            "nodes": { "10.0.0.1:8080": 1, "10.0.0.2:8080": 1, "10.0.0.3:8080": 1 },
+            "type": "chash"
+        });
+        ups.as_object_mut()
+            .unwrap()
+            .extend(hash_on.as_object().unwrap().clone());
+        serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/ch", "status": 1, "upstream": ups
+        }))
+        .unwrap()
+    }
+
+    fn chash_addr(w: &mut ProxyWorker, headers: &[(&str, &str)], client_ip: &str) -> String {
+        match w.handle_request("GET", "/ch", None, headers, client_ip) {
+            RequestResult::Proxy { upstream_addr, .. } => upstream_addr,
             other => panic!("Expected Proxy, got {:?}", other),
         }
+    }
 
-        // /api/v1/ → /
-        match w.handle_request("GET", "/api/v1/", None, &[], "x") {
-            RequestResult::Proxy { upstream_path, .. } => assert_eq!(upstream_path, "/"),
-            other => panic!("Expected Proxy, got {:?}", other),
+    #[test]
+    fn handle_request_chash_is_sticky_per_header() {
+        let mut w = make_worker(vec![chash_route(
+            serde_json::json!({ "hash_on": "header", "key": "X-User" }),
+        )]);
+        let mut seen = std::collections::HashSet::new();
+        for i in 0..50 {
+            let user = format!("user-{i}");
+            let first = chash_addr(&mut w, &[("x-user", &user)], "1.1.1.1");
+            for _ in 0..3 {
+                assert_eq!(chash_addr(&mut w, &[("X-User", &user)], "2.2.2.2"), first);
+            }
+            seen.insert(first);
         }
+        assert!(seen.len() > 1, "keys should spread across nodes");
     }
 
     #[test]
-    fn handle_request_no_strip_prefix_passes_full_path() {
+    fn handle_request_chash_by_cookie() {
+        let mut w = make_worker(vec![chash_route(
+            serde_json::json!({ "hash_on": "cookie", "key": "sid" }),
+        )]);
+        let a = chash_addr(&mut w, &[("cookie", "theme=dark; sid=abc")], "1.1.1.1");
+        let b = chash_addr(&mut w, &[("cookie", "sid=abc")], "9.9.9.9");
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn handle_request_chash_falls_back_to_client_ip() {
+        let mut w = make_worker(vec![chash_route(
+            serde_json::json!({ "hash_on": "header", "key": "x-user" }),
+        )]);
+        let a = chash_addr(&mut w, &[], "10.1.2.3");
+        for _ in 0..5 {
+            assert_eq!(chash_addr(&mut w, &[], "10.1.2.3"), a);
+        }
+        let defaults = chash_route(serde_json::json!({}));
+        let mut w2 = make_worker(vec![defaults]);
+        assert_eq!(chash_addr(&mut w2, &[], "10.1.2.3"), a);
+    }
+
+    #[test]
+    fn maybe_update_health_skips_ejected_nodes() {
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/api/v1/*", "status": 1,
-            "strip_prefix": false,
-            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            "id": "r1", "uri": "/hc", "status": 1,
+            "upstream_id": "ups1"
         }))
         .unwrap();
-        let mut w = make_worker(vec![route]);
+        let cache = ConfigCache::new();
+        let ups: Upstream = serde_json::from_value(serde_json::json!({
+            "id": "ups1",
+            "nodes": { "10.0.0.1:8080": 1, "10.0.0.2:8080": 1 }
+        }))
+        .unwrap();
+        cache.upstreams.insert("ups1".to_string(), ups);
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache.clone());
 
-        match w.handle_request("GET", "/api/v1/users", None, &[], "x") {
-            RequestResult::Proxy { upstream_path, .. } => {
-                assert_eq!(upstream_path, "/api/v1/users")
-            }
-            other => panic!("Expected Proxy, got {:?}", other),
+        cache
+            .health
+            .record("ups1", "10.0.0.1:8080", Err("refused".into()), 1, 1, 0);
+        w.maybe_update_health();
+        for _ in 0..5 {
+            assert_eq!(proxied_addr(&mut w, "/hc"), "10.0.0.2:8080");
         }
+
+        // All nodes down: fall back to sending traffic anyway.
+        cache
+            .health
+            .record("ups1", "10.0.0.2:8080", Err("refused".into()), 1, 1, 0);
+        w.maybe_update_health();
+        let a = proxied_addr(&mut w, "/hc");
+        let b = proxied_addr(&mut w, "/hc");
+        assert_ne!(a, b);
+
+        cache
+            .health
+            .record("ups1", "10.0.0.1:8080", Ok(()), 1, 1, 0);
+        w.maybe_update_health();
+        for _ in 0..5 {
+            assert_eq!(proxied_addr(&mut w, "/hc"), "10.0.0.1:8080");
+        }
+    }
+
+    fn dns_answer(cache: &ConfigCache, node: &str, addrs: &[&str]) {
+        cache
+            .dns
+            .update(node, addrs.iter().map(|a| a.parse().unwrap()).collect());
     }
 
     #[test]
-    fn handle_request_method_specific_route() {
+    fn resolved_hostname_spreads_over_every_address() {
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/only-get",
-            "methods": ["GET"], "status": 1,
-            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            "id": "r1", "uri": "/dns", "status": 1,
+            "upstream_id": "ups1"
         }))
         .unwrap();
-        let mut w = make_worker(vec![route]);
-        assert!(matches!(
-            w.handle_request("GET", "/only-get", None, &[], "x"),
-            RequestResult::Proxy { .. }
-        ));
-        assert!(matches!(
-            w.handle_request("POST", "/only-get", None, &[], "x"),
-            RequestResult::Static(RESP_404)
-        ));
-    }
+        let cache = ConfigCache::new();
+        let ups: Upstream = serde_json::from_value(serde_json::json!({
+            "id": "ups1",
+            "nodes": { "api.internal:8080": 1 },
+            "pass_host": "node"
+        }))
+        .unwrap();
+        cache.upstreams.insert("ups1".to_string(), ups);
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache.clone());
+        assert_eq!(proxied_addr(&mut w, "/dns"), "api.internal:8080");
 
-    // ── handle_request — key-auth plugin ────────────────────────
+        dns_answer(
+            &cache,
+            "api.internal:8080",
+            &["10.0.1.1:8080", "10.0.1.2:8080"],
+        );
+        w.maybe_update_dns();
+        let picks: HashSet<String> = (0..4).map(|_| proxied_addr(&mut w, "/dns")).collect();
+        assert_eq!(
+            picks,
+            HashSet::from(["10.0.1.1:8080".to_string(), "10.0.1.2:8080".to_string()])
+        );
+        match w.handle_request("GET", "/dns", None, &[], "x") {
+            RequestResult::Proxy { upstream_host, .. } => {
+                assert_eq!(upstream_host.as_deref(), Some("api.internal:8080"))
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+
+        // Ejecting the node by name covers all of its addresses.
+        cache
+            .health
+            .record("ups1", "api.internal:8080", Err("refused".into()), 1, 1, 0);
+        w.maybe_update_health();
+        assert!(proxied_addr(&mut w, "/dns").starts_with("10.0.1."));
+    }
 
     #[test]
-    fn handle_request_key_auth_missing_key_returns_plugin_401() {
-        let mut registry = PluginRegistry::new();
-        ando_plugins::register_all(&mut registry);
-        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
-        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+    fn dns_change_rebuilds_only_the_affected_balancers() {
+        let routes: Vec<Route> = [
+            serde_json::json!({
+                "id": "r1", "uri": "/named", "status": 1,
+                "upstream": { "nodes": { "api.internal:80": 1 } }
+            }),
+            serde_json::json!({
+                "id": "r2", "uri": "/ips", "status": 1,
+                "upstream": { "nodes": { "10.0.0.1:80": 1, "10.0.0.2:80": 1 } }
+            }),
+        ]
+        .into_iter()
+        .map(|r| serde_json::from_value(r).unwrap())
+        .collect();
+        let cache = ConfigCache::new();
+        dns_answer(&cache, "api.internal:80", &["10.0.1.1:80", "10.0.1.2:80"]);
+        let mut w = make_worker_with_registry(routes, PluginRegistry::new(), cache.clone());
+        w.maybe_update_dns();
+        proxied_addr(&mut w, "/named");
+        let first = proxied_addr(&mut w, "/ips");
 
-        // No apikey header → key-auth plugin returns 401
-        let result = w.handle_request("GET", "/secure", None, &[], "1.2.3.4");
-        match result {
-            RequestResult::PluginResponse { status, .. } => assert_eq!(status, 401),
-            other => panic!("Expected PluginResponse 401, got {:?}", other),
+        dns_answer(&cache, "api.internal:80", &["10.0.2.1:80"]);
+        w.maybe_update_dns();
+        for _ in 0..3 {
+            assert_eq!(proxied_addr(&mut w, "/named"), "10.0.2.1:80");
         }
+        // The other route's balancer kept its round-robin position.
+        assert_ne!(proxied_addr(&mut w, "/ips"), first);
     }
 
-    #[test]
-    fn handle_request_key_auth_invalid_key_returns_static_401() {
-        let mut registry = PluginRegistry::new();
-        ando_plugins::register_all(&mut registry);
-        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
-        // Empty consumer store — key present in header but no consumer has it
-        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+    fn publish(cache: &ConfigCache, service: &str, nodes: &[(&str, &str)]) {
+        let nodes = nodes
+            .iter()
+            .map(|(port_name, addr)| DiscoveredNode {
+                port_name: port_name.to_string(),
+                addr: addr.to_string(),
+            })
+            .collect();
+        cache
+            .discovery
+            .publish(DiscoveryType::Kubernetes, service, nodes);
+    }
 
-        let result = w.handle_request("GET", "/secure", None, &[("apikey", "bad-key")], "1.2.3.4");
-        assert!(
-            matches!(result, RequestResult::Static(RESP_401_INVALID)),
-            "wrong consumer key must return RESP_401_INVALID"
-        );
+    fn discovered_route(port_name: Option<&str>) -> Route {
+        serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/k8s", "status": 1,
+            "upstream": {
+                "discovery_type": "kubernetes",
+                "service_name": "shop/checkout",
+                "port_name": port_name,
+                "nodes": { "10.9.9.9:80": 1 }
+            }
+        }))
+        .unwrap()
     }
 
     #[test]
-    fn handle_request_key_auth_valid_key_proxies_request() {
-        let mut registry = PluginRegistry::new();
-        ando_plugins::register_all(&mut registry);
-        let route = route_with_key_auth("r1", "/secure", "127.0.0.1:8080");
-
-        // Add consumer with a known key
+    fn discovered_upstream_balances_over_ready_endpoints() {
         let cache = ConfigCache::new();
-        let mut plugins: HashMap<String, serde_json::Value> = HashMap::new();
-        plugins.insert(
-            "key-auth".to_string(),
-            serde_json::json!({ "key": "valid-key-123" }),
+        publish(
+            &cache,
+            "shop/checkout",
+            &[
+                ("http", "10.1.0.1:8080"),
+                ("http", "10.1.0.2:8080"),
+                ("metrics", "10.1.0.1:9090"),
+            ],
         );
-        cache.consumers.insert(
-            "alice".to_string(),
-            Consumer {
-                username: "alice".to_string(),
-                plugins,
-                desc: None,
-                labels: HashMap::new(),
-            },
+        let mut w = make_worker_with_registry(
+            vec![discovered_route(Some("http"))],
+            PluginRegistry::new(),
+            cache.clone(),
+        );
+        w.maybe_update_discovery();
+        // Static `nodes` are ignored once discovery is set.
+        let picks: HashSet<String> = (0..4).map(|_| proxied_addr(&mut w, "/k8s")).collect();
+        assert_eq!(
+            picks,
+            HashSet::from(["10.1.0.1:8080".to_string(), "10.1.0.2:8080".to_string()])
         );
-        cache.rebuild_consumer_key_index();
 
-        let mut w = make_worker_with_registry(vec![route], registry, cache);
-        let result = w.handle_request(
-            "GET",
-            "/secure",
-            None,
-            &[("apikey", "valid-key-123")],
-            "1.2.3.4",
+        // A pod going unready stops its traffic on the next update.
+        publish(&cache, "shop/checkout", &[("http", "10.1.0.2:8080")]);
+        w.maybe_update_discovery();
+        for _ in 0..3 {
+            assert_eq!(proxied_addr(&mut w, "/k8s"), "10.1.0.2:8080");
+        }
+    }
+
+    #[test]
+    fn discovered_upstream_without_endpoints_is_503() {
+        let cache = ConfigCache::new();
+        let mut w = make_worker_with_registry(
+            vec![discovered_route(None)],
+            PluginRegistry::new(),
+            cache.clone(),
         );
+        let no_endpoints =
+            |w: &mut ProxyWorker| match w.handle_request("GET", "/k8s", None, &[], "x") {
+                RequestResult::PluginResponse { status, body, .. } => {
+                    assert_eq!(status, 503);
+                    assert_eq!(
+                        body,
+                        br#"{"error":"no endpoints for discovered service","status":503}"#
+                    );
+                }
+                other => panic!("Expected 503, got {:?}", other),
+            };
+        // Not discovered yet.
+        no_endpoints(&mut w);
+
+        publish(&cache, "shop/checkout", &[("", "10.1.0.1:80")]);
+        w.maybe_update_discovery();
+        assert_eq!(proxied_addr(&mut w, "/k8s"), "10.1.0.1:80");
+
+        // Known, but with every endpoint gone.
+        publish(&cache, "shop/checkout", &[]);
+        w.maybe_update_discovery();
+        no_endpoints(&mut w);
+    }
+
+    fn passive_route(nodes: serde_json::Value) -> Route {
+        serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/cb", "status": 1,
+            "upstream": {
+                "nodes": nodes,
+                "checks": { "passive": {
+                    "unhealthy": { "http_failures": 2, "tcp_failures": 1 },
+                    "cooldown": 30
+                }}
+            }
+        }))
+        .unwrap()
+    }
+
+    fn proxy_target(w: &mut ProxyWorker, path: &str) -> (String, Arc<PassiveHealthCheck>) {
+        match w.handle_request("GET", path, None, &[], "x") {
+            RequestResult::Proxy {
+                upstream_addr,
+                passive,
+                ..
+            } => (upstream_addr, passive.expect("passive config forwarded")),
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn open_breaker_routes_around_node() {
+        let mut w = make_worker(vec![passive_route(
+            serde_json::json!({ "10.0.0.1:8080": 1, "10.0.0.2:8080": 1 }),
+        )]);
+        let (_, cfg) = proxy_target(&mut w, "/cb");
+        w.record_upstream_outcome("10.0.0.1:8080", &cfg, Some(UpstreamFailure::Http));
         assert!(
-            matches!(result, RequestResult::Proxy { .. }),
-            "valid consumer key must proxy the request"
+            !w.config_cache
+                .breakers
+                .is_open("10.0.0.1:8080", Instant::now())
         );
-    }
+        w.record_upstream_outcome("10.0.0.1:8080", &cfg, Some(UpstreamFailure::Http));
+        for _ in 0..5 {
+            assert_eq!(proxy_target(&mut w, "/cb").0, "10.0.0.2:8080");
+        }
 
-    // ── maybe_update_router ──────────────────────────────────────
+        w.record_upstream_outcome("10.0.0.1:8080", &cfg, None);
+        let picks: HashSet<String> = (0..4).map(|_| proxy_target(&mut w, "/cb").0).collect();
+        assert_eq!(picks.len(), 2, "closed breaker restores the node");
+    }
 
     #[test]
-    fn maybe_update_router_no_op_on_same_version() {
-        let routes = vec![simple_route("r1", "/a", "127.0.0.1:8080")];
-        let mut w = make_worker(routes);
-        let old_version = w.router_version;
-        let same_router = Arc::new(Router::build(vec![], old_version).unwrap());
-        w.maybe_update_router(same_router);
-        assert_eq!(w.router_version, old_version);
+    fn all_breakers_open_returns_503_with_retry_after() {
+        let mut w = make_worker(vec![passive_route(
+            serde_json::json!({ "10.0.0.1:8080": 1 }),
+        )]);
+        let (addr, cfg) = proxy_target(&mut w, "/cb");
+        w.record_upstream_outcome(&addr, &cfg, Some(UpstreamFailure::Tcp));
+
+        match w.handle_request("GET", "/cb", None, &[], "x") {
+            RequestResult::PluginResponse {
+                status, headers, ..
+            } => {
+                assert_eq!(status, 503);
+                let retry = headers
+                    .iter()
+                    .find(|(k, _)| k == "retry-after")
+                    .map(|(_, v)| v.parse::<u64>().unwrap())
+                    .unwrap();
+                assert!((1..=30).contains(&retry));
+            }
+            other => panic!("Expected 503, got {:?}", other),
+        }
     }
 
     #[test]
-    fn maybe_update_router_updates_on_new_version() {
-        let routes = vec![simple_route("r1", "/a", "127.0.0.1:8080")];
-        let mut w = make_worker(routes);
-        let old_version = w.router_version;
-        let new_router = Arc::new(
-            Router::build(
-                vec![simple_route("r2", "/b", "127.0.0.1:9090")],
-                old_version + 1,
-            )
-            .unwrap(),
-        );
-        w.maybe_update_router(Arc::clone(&new_router));
-        assert_eq!(w.router_version, old_version + 1);
-        // New route should now match
-        let result = w.handle_request("GET", "/b", None, &[], "x");
-        assert!(matches!(result, RequestResult::Proxy { .. }));
+    fn breakers_ignored_without_passive_config() {
+        let mut w = make_worker(vec![simple_route("r1", "/plain", "10.0.0.9:8080")]);
+        let cfg: PassiveHealthCheck =
+            serde_json::from_value(serde_json::json!({ "unhealthy": { "tcp_failures": 1 } }))
+                .unwrap();
+        w.record_upstream_outcome("10.0.0.9:8080", &cfg, Some(UpstreamFailure::Tcp));
+        match w.handle_request("GET", "/plain", None, &[], "x") {
+            RequestResult::Proxy {
+                upstream_addr,
+                passive,
+                ..
+            } => {
+                assert_eq!(upstream_addr, "10.0.0.9:8080");
+                assert!(passive.is_none());
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
     }
 
-    // ── upstream_addresses ───────────────────────────────────────
-
     #[test]
-    fn upstream_addresses_returns_inline_route_nodes() {
-        let route = simple_route("r1", "/api", "10.0.0.1:8080");
-        let w = make_worker(vec![route]);
-        let addrs = w.upstream_addresses();
-        assert!(addrs.contains(&"10.0.0.1:8080".to_string()));
+    fn handle_request_applies_route_conditions() {
+        let internal: Route = serde_json::from_value(serde_json::json!({
+            "id": "internal", "uri": "/cond", "status": 1, "priority": 1,
+            "remote_addrs": ["10.0.0.0/8"],
+            "vars": [["arg_debug", "==", "1"]],
+            "upstream": { "nodes": { "10.0.0.1:8080": 1 } }
+        }))
+        .unwrap();
+        let public = simple_route("public", "/cond", "10.0.0.2:8080");
+        let mut w = make_worker(vec![internal, public]);
+
+        let addr = |w: &mut ProxyWorker, path: &str, ip: &str| match w.handle_request(
+            "GET",
+            path,
+            None,
+            &[],
+            ip,
+        ) {
+            RequestResult::Proxy { upstream_addr, .. } => upstream_addr,
+            other => panic!("Expected Proxy, got {:?}", other),
+        };
+        assert_eq!(addr(&mut w, "/cond?debug=1", "10.9.9.9"), "10.0.0.1:8080");
+        assert_eq!(addr(&mut w, "/cond?debug=0", "10.9.9.9"), "10.0.0.2:8080");
+        assert_eq!(addr(&mut w, "/cond?debug=1", "8.8.8.8"), "10.0.0.2:8080");
     }
 
-    // ── resolve_upstream: fallback to 127.0.0.1:80 ───────────────
+    #[test]
+    fn forwarded_for_is_honored_only_from_trusted_proxies() {
+        let internal: Route = serde_json::from_value(serde_json::json!({
+            "id": "internal", "uri": "/xff", "status": 1, "priority": 1,
+            "remote_addrs": ["10.0.0.0/8"],
+            "upstream": { "nodes": { "10.0.0.1:8080": 1 } }
+        }))
+        .unwrap();
+        let public = simple_route("public", "/xff", "10.0.0.2:8080");
+        let mut w = make_worker(vec![internal, public])
+            .with_trusted_proxies(TrustedProxies::new(&["192.168.0.0/16".to_string()]));
+
+        let spoofed = [("host", "api.test"), ("x-forwarded-for", "10.9.9.9")];
+        let send = |w: &mut ProxyWorker, peer: &str| match w.handle_request(
+            "GET",
+            "/xff",
+            Some("api.test"),
+            &spoofed,
+            peer,
+        ) {
+            RequestResult::Proxy {
+                upstream_addr,
+                upstream_headers,
+                ..
+            } => (upstream_addr, upstream_headers),
+            other => panic!("Expected Proxy, got {:?}", other),
+        };
+        let value = |headers: &[(String, String)], name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        };
+
+        // An untrusted client cannot claim an internal address.
+        let (addr, headers) = send(&mut w, "8.8.8.8");
+        assert_eq!(addr, "10.0.0.2:8080");
+        assert_eq!(value(&headers, "x-forwarded-for").unwrap(), "8.8.8.8");
+        assert_eq!(value(&headers, "x-real-ip").unwrap(), "8.8.8.8");
+        assert_eq!(value(&headers, "x-forwarded-proto").unwrap(), "http");
+        assert_eq!(value(&headers, "x-forwarded-host").unwrap(), "api.test");
+
+        // Relayed by a trusted proxy, the same header names the client.
+        let (addr, headers) = send(&mut w, "192.168.1.1");
+        assert_eq!(addr, "10.0.0.1:8080");
+        assert_eq!(
+            value(&headers, "x-forwarded-for").unwrap(),
+            "10.9.9.9, 192.168.1.1"
+        );
+        assert_eq!(value(&headers, "x-real-ip").unwrap(), "10.9.9.9");
+    }
 
     #[test]
-    fn handle_request_no_upstream_falls_back_to_localhost() {
+    fn real_ip_plugin_feeds_access_log_and_restriction() {
+        use ando_core::config::AccessLogConfig;
+
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(ando_plugins::traffic::real_ip::RealIpPlugin));
+        registry.register(Arc::new(
+            ando_plugins::traffic::ip_restriction::IpRestrictionPlugin,
+        ));
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/no-ups", "status": 1
+            "id": "cdn", "uri": "/cdn", "status": 1,
+            "plugins": {
+                "real-ip": {
+                    "source": "CF-Connecting-IP",
+                    "trusted_addresses": ["173.245.48.0/20"]
+                },
+                "ip-restriction": { "denylist": ["198.51.100.7"] }
+            },
+            "upstream": { "nodes": { "10.0.0.1:8080": 1 } }
         }))
         .unwrap();
-        let mut w = make_worker(vec![route]);
-        let result = w.handle_request("GET", "/no-ups", None, &[], "x");
+        let logger = AccessLogger::from_config(&AccessLogConfig {
+            enabled: true,
+            path: std::env::temp_dir()
+                .join(format!("ando-real-ip-{}.log", std::process::id()))
+                .to_string_lossy()
+                .into_owned(),
+            ..AccessLogConfig::default()
+        })
+        .unwrap()
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new())
+            .with_access_log(Arc::new(logger));
+
+        let logged_ip = |w: &mut ProxyWorker, cf: &str| {
+            let result = w.handle_request(
+                "GET",
+                "/cdn",
+                None,
+                &[("cf-connecting-ip", cf)],
+                "173.245.48.1",
+            );
+            let ip = w
+                .exchange(Instant::now())
+                .access
+                .map(|a| a.client_ip)
+                .unwrap();
+            (result, ip)
+        };
+        let (result, ip) = logged_ip(&mut w, "203.0.113.9");
         match result {
-            RequestResult::Proxy { upstream_addr, .. } => {
-                assert_eq!(
-                    upstream_addr, "127.0.0.1:80",
-                    "route with no upstream should fallback to 127.0.0.1:80"
-                );
-            }
+            RequestResult::Proxy {
+                upstream_headers, ..
+            } => assert!(
+                upstream_headers.contains(&("x-real-ip".to_string(), "203.0.113.9".to_string()))
+            ),
             other => panic!("Expected Proxy, got {:?}", other),
         }
-    }
+        assert_eq!(ip, "203.0.113.9");
 
-    // ── resolve_upstream: via upstream_id reference ───────────────
+        let (result, ip) = logged_ip(&mut w, "198.51.100.7");
+        assert!(matches!(
+            result,
+            RequestResult::PluginResponse { status: 403, .. }
+        ));
+        assert_eq!(ip, "198.51.100.7");
+    }
 
     #[test]
-    fn handle_request_resolves_upstream_by_id() {
+    fn traffic_split_overrides_the_route_upstream() {
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(
+            ando_plugins::traffic::traffic_split::TrafficSplitPlugin::new(),
+        ));
         let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/ref-ups", "status": 1,
-            "upstream_id": "ups1"
+            "id": "split", "uri": "/split", "status": 1,
+            "plugins": {
+                "traffic-split": { "rules": [
+                    {
+                        "match": [{ "vars": [["http_x_canary", "==", "always"]] }],
+                        "weighted_upstreams": [{ "upstream_id": "canary" }]
+                    },
+                    {
+                        "match": [{ "vars": [["http_x_canary", "==", "inline"]] }],
+                        "weighted_upstreams": [{ "upstream": { "nodes": { "10.0.0.3:80": 1 } } }]
+                    },
+                    {
+                        "match": [{ "vars": [["http_x_canary", "==", "missing"]] }],
+                        "weighted_upstreams": [{ "upstream_id": "nope" }]
+                    }
+                ]}
+            },
+            "upstream": { "nodes": { "10.0.0.1:80": 1 } }
         }))
         .unwrap();
         let cache = ConfigCache::new();
         let ups: Upstream = serde_json::from_value(serde_json::json!({
-            "id": "ups1",
-            "nodes": { "10.0.0.2:9090": 1 },
-            "type": "roundrobin"
+            "id": "canary", "nodes": { "10.0.0.2:80": 1 }
         }))
         .unwrap();
-        cache.upstreams.insert("ups1".to_string(), ups);
+        cache.upstreams.insert("canary".to_string(), ups);
+        let mut w = make_worker_with_registry(vec![route], registry, cache);
 
-        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache);
-        let result = w.handle_request("GET", "/ref-ups", None, &[], "x");
-        match result {
-            RequestResult::Proxy { upstream_addr, .. } => {
-                assert_eq!(upstream_addr, "10.0.0.2:9090");
+        let mut upstream = |canary: &str| {
+            let headers: &[(&str, &str)] = if canary.is_empty() {
+                &[]
+            } else {
+                &[("x-canary", canary)]
+            };
+            match w.handle_request("GET", "/split", None, headers, "x") {
+                RequestResult::Proxy { upstream_addr, .. } => upstream_addr,
+                other => panic!("Expected Proxy, got {:?}", other),
             }
-            other => panic!("Expected Proxy, got {:?}", other),
-        }
+        };
+        assert_eq!(upstream("always"), "10.0.0.2:80");
+        assert_eq!(upstream("inline"), "10.0.0.3:80");
+        // Unknown upstreams and unmatched requests keep the route's own.
+        assert_eq!(upstream("missing"), "10.0.0.1:80");
+        assert_eq!(upstream(""), "10.0.0.1:80");
     }
 
-    // ── resolve_upstream: via service_id → upstream ──────────────
-
     #[test]
-    fn handle_request_resolves_upstream_via_service() {
-        let route: Route = serde_json::from_value(serde_json::json!({
-            "id": "r1", "uri": "/svc", "status": 1,
-            "service_id": "svc1"
-        }))
-        .unwrap();
-        let cache = ConfigCache::new();
-        let svc = ando_core::service::Service {
-            id: "svc1".into(),
-            name: None,
-            desc: None,
-            upstream_id: None,
-            upstream: Some(
-                serde_json::from_value(serde_json::json!({
-                    "nodes": { "10.0.0.3:7070": 1 },
-                    "type": "roundrobin"
-                }))
-                .unwrap(),
-            ),
-            plugins: HashMap::new(),
-            labels: HashMap::new(),
+    fn maybe_update_router_picks_up_new_weights() {
+        let route = |weights: serde_json::Value| -> Route {
+            serde_json::from_value(serde_json::json!({
+                "id": "r1", "uri": "/w", "status": 1,
+                "upstream": { "nodes": weights, "type": "roundrobin" }
+            }))
+            .unwrap()
         };
-        cache.services.insert("svc1".to_string(), svc);
+        let mut w = make_worker(vec![route(
+            serde_json::json!({ "10.0.0.1:8080": 1, "10.0.0.2:8080": 1 }),
+        )]);
+        let _ = proxied_addr(&mut w, "/w");
 
-        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache);
-        let result = w.handle_request("GET", "/svc", None, &[], "x");
-        match result {
-            RequestResult::Proxy { upstream_addr, .. } => {
-                assert_eq!(upstream_addr, "10.0.0.3:7070");
-            }
-            other => panic!("Expected Proxy, got {:?}", other),
+        let new_router = Arc::new(
+            Router::build(
+                vec![route(
+                    serde_json::json!({ "10.0.0.1:8080": 0, "10.0.0.2:8080": 1 }),
+                )],
+                w.router_version + 1,
+            )
+            .unwrap(),
+        );
+        w.maybe_update_router(new_router);
+        for _ in 0..5 {
+            assert_eq!(proxied_addr(&mut w, "/w"), "10.0.0.2:8080");
         }
     }
 
@@ -1085,12 +4931,377 @@ mod tests {
         );
     }
 
+    // ── get_or_build_pipeline counts skipped plugins ─────────────
+
+    #[test]
+    fn pipeline_skips_misconfigured_plugin_and_counts_it() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/cors", "status": 1,
+            "plugins": { "cors": { "allow_origins": "not-an-array" } },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new())
+            .with_metrics(Arc::clone(&metrics));
+
+        assert!(w.get_or_build_pipeline("r1").is_empty());
+        let counter = metrics.plugin_config_errors.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["r1", "cors"]).get(), 1);
+    }
+
+    // ── Plugin timing ────────────────────────────────────────────
+
+    /// Sleeps for `sleep_ms` (from its config) in the access phase.
+    struct SleepPlugin(&'static str);
+    impl ando_plugin::plugin::Plugin for SleepPlugin {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn phases(&self) -> &[Phase] {
+            &[Phase::Access]
+        }
+        fn configure(
+            &self,
+            config: &serde_json::Value,
+        ) -> anyhow::Result<Box<dyn ando_plugin::plugin::PluginInstance>> {
+            Ok(Box::new(Sleep(
+                self.0,
+                Duration::from_millis(config["sleep_ms"].as_u64().unwrap_or(0)),
+            )))
+        }
+    }
+    struct Sleep(&'static str, Duration);
+    impl ando_plugin::plugin::PluginInstance for Sleep {
+        fn name(&self) -> &str {
+            self.0
+        }
+        fn access(&self, _ctx: &mut PluginContext) -> PluginResult {
+            std::thread::sleep(self.1);
+            PluginResult::Continue
+        }
+    }
+
+    fn sleepy_worker(plugins: serde_json::Value) -> ProxyWorker {
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(SleepPlugin("slow")));
+        registry.register(Arc::new(SleepPlugin("fast")));
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/sleepy", "status": 1, "plugins": plugins,
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        make_worker_with_registry(vec![route], registry, ConfigCache::new())
+    }
+
+    /// Run `f` with warn-level logs captured, and return them.
+    fn capture_logs(f: impl FnOnce()) -> String {
+        #[derive(Clone, Default)]
+        struct Buf(Arc<std::sync::Mutex<Vec<u8>>>);
+        impl std::io::Write for Buf {
+            fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(b);
+                Ok(b.len())
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let buf = Buf::default();
+        let writer = buf.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::WARN)
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        String::from_utf8(buf.0.lock().unwrap().clone()).unwrap()
+    }
+
+    #[test]
+    fn slow_plugin_is_logged_and_timed() {
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = sleepy_worker(serde_json::json!({
+            "slow": { "sleep_ms": 30 }, "fast": {}
+        }))
+        .with_metrics(Arc::clone(&metrics))
+        .with_slow_plugin_threshold(10);
+
+        let logs = capture_logs(|| {
+            let _ = w.handle_request("GET", "/sleepy", None, &[], "10.0.0.1");
+        });
+        assert!(logs.contains("slow plugin"), "{logs}");
+        assert!(
+            logs.contains("route=r1") && logs.contains("plugin=slow"),
+            "{logs}"
+        );
+        assert!(!logs.contains("plugin=fast"), "{logs}");
+
+        let hist = metrics.plugin_duration.as_ref().unwrap();
+        let slow = hist.with_label_values(&["slow", "access"]);
+        assert_eq!(slow.get_sample_count(), 1);
+        assert!(slow.get_sample_sum() >= 0.03);
+        assert_eq!(
+            hist.with_label_values(&["fast", "rewrite"])
+                .get_sample_count(),
+            1
+        );
+        let stats = metrics.plugin_latencies();
+        assert!(stats.iter().any(|s| s.plugin == "slow" && s.calls == 3));
+    }
+
+    #[test]
+    fn lone_plugin_is_not_timed_without_metrics() {
+        let mut w = sleepy_worker(serde_json::json!({ "slow": { "sleep_ms": 30 } }))
+            .with_slow_plugin_threshold(10);
+        let logs = capture_logs(|| {
+            let _ = w.handle_request("GET", "/sleepy", None, &[], "10.0.0.1");
+        });
+        assert!(!logs.contains("slow plugin"), "{logs}");
+    }
+
+    // ── maybe_update_router evicts only changed entries ──────────
+
+    #[test]
+    fn maybe_update_router_keeps_pipelines_of_unchanged_routes() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let r1 = route_with_key_auth("r1", "/one", "127.0.0.1:8080");
+        let r2 = route_with_key_auth("r2", "/two", "127.0.0.1:8080");
+        let cache = ConfigCache::new();
+        let mut w =
+            make_worker_with_registry(vec![r1.clone(), r2.clone()], registry, cache.clone());
+        let one = w.get_or_build_pipeline("r1");
+        let two = w.get_or_build_pipeline("r2");
+
+        // Update r1 the way the admin API does: cache, change log, router.
+        let r1 = route_with_key_auth("r1", "/one", "127.0.0.1:8081");
+        cache.routes.insert("r1".to_string(), r1.clone());
+        cache.changes.record(Entity::Route, "r1");
+        let new_router = Arc::new(Router::build(vec![r1, r2], w.router_version + 1).unwrap());
+        w.maybe_update_router(new_router);
+
+        assert!(!w.pipeline_cache.contains_key("r1"));
+        assert!(Arc::ptr_eq(&w.get_or_build_pipeline("r2"), &two));
+        assert!(!Arc::ptr_eq(&w.get_or_build_pipeline("r1"), &one));
+    }
+
+    #[test]
+    fn global_rule_change_rebuilds_every_pipeline() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let r1 = route_with_key_auth("r1", "/one", "127.0.0.1:8080");
+        let cache = ConfigCache::new();
+        let mut w = make_worker_with_registry(vec![r1], registry, cache.clone());
+        assert_eq!(w.get_or_build_pipeline("r1").len(), 1);
+
+        let rule: GlobalRule = serde_json::from_value(serde_json::json!({
+            "id": "1",
+            "plugins": { "response-headers-policy": {} }
+        }))
+        .unwrap();
+        cache.global_rules.insert("1".to_string(), rule);
+        cache.changes.record(Entity::GlobalRule, "1");
+        w.maybe_update_router(Arc::clone(&w.router));
+        assert!(w.pipeline_cache.is_empty());
+        assert_eq!(w.get_or_build_pipeline("r1").len(), 2);
+
+        cache.global_rules.remove("1");
+        cache.changes.record(Entity::GlobalRule, "1");
+        w.maybe_update_router(Arc::clone(&w.router));
+        assert_eq!(w.get_or_build_pipeline("r1").len(), 1);
+    }
+
+    #[test]
+    fn route_config_overrides_and_disables_global_plugins() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route = |id: &str, plugins: serde_json::Value| -> Route {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "uri": format!("/{id}"), "plugins": plugins,
+                "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            }))
+            .unwrap()
+        };
+        let routes = vec![
+            route("plain", serde_json::json!({})),
+            route(
+                "off",
+                serde_json::json!({ "response-headers-policy": { "_meta": { "disable": true } } }),
+            ),
+        ];
+        let mut w =
+            make_worker_with_registry(routes, registry, ConfigCache::new()).with_global_plugins(
+                HashMap::from([("response-headers-policy".to_string(), serde_json::json!({}))]),
+            );
+        assert!(
+            w.get_or_build_pipeline("plain")
+                .has_phase(Phase::HeaderFilter)
+        );
+        assert!(w.get_or_build_pipeline("off").is_empty());
+    }
+
+    #[test]
+    fn meta_priority_reorders_plugins_on_its_route_only() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route = |id: &str, transformer: serde_json::Value| -> Route {
+            serde_json::from_value(serde_json::json!({
+                "id": id, "uri": format!("/{id}"), "status": 1,
+                "plugins": {
+                    "cors": {},
+                    "request-transformer": transformer,
+                    "debug-echo": { "enabled_in_production": true }
+                },
+                "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+            }))
+            .unwrap()
+        };
+        let routes = vec![
+            route("default", serde_json::json!({})),
+            route(
+                "flipped",
+                serde_json::json!({ "_meta": { "priority": 3000 } }),
+            ),
+        ];
+        let mut w = make_worker_with_registry(routes, registry, ConfigCache::new());
+        let mut executed = |path: &str| match w.handle_request("GET", path, None, &[], "127.0.0.1")
+        {
+            RequestResult::PluginResponse { body, .. } => {
+                serde_json::from_slice::<serde_json::Value>(&body).unwrap()["executed_plugins"]
+                    .clone()
+            }
+            other => panic!("Expected echo, got {:?}", other),
+        };
+        assert_eq!(
+            executed("/default"),
+            serde_json::json!(["cors", "request-transformer", "debug-echo"])
+        );
+        assert_eq!(
+            executed("/flipped"),
+            serde_json::json!(["request-transformer", "cors", "debug-echo"])
+        );
+    }
+
+    #[test]
+    fn invalid_meta_priority_skips_the_plugin_and_counts_it() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/cors", "status": 1,
+            "plugins": { "cors": { "_meta": { "priority": 1u64 << 40 } } },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new())
+            .with_metrics(Arc::clone(&metrics));
+
+        assert!(w.get_or_build_pipeline("r1").is_empty());
+        let counter = metrics.plugin_config_errors.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["r1", "cors"]).get(), 1);
+    }
+
+    #[test]
+    fn meta_filter_applies_a_plugin_to_matching_requests_only() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/orders/{id}", "status": 1,
+            "plugins": {
+                "mock": {
+                    "status": 503,
+                    "_meta": { "filter": [["http_x_env", "==", "staging"], ["path_id", ">", 100]] }
+                }
+            },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new());
+        let staging = [("x-env", "staging")];
+        assert!(matches!(
+            w.handle_request("GET", "/orders/200", None, &staging, "127.0.0.1"),
+            RequestResult::PluginResponse { status: 503, .. }
+        ));
+        assert!(matches!(
+            w.handle_request("GET", "/orders/7", None, &staging, "127.0.0.1"),
+            RequestResult::Proxy { .. }
+        ));
+        assert!(matches!(
+            w.handle_request("GET", "/orders/200", None, &[], "127.0.0.1"),
+            RequestResult::Proxy { .. }
+        ));
+    }
+
+    #[test]
+    fn invalid_meta_filter_skips_the_plugin_and_counts_it() {
+        let mut registry = PluginRegistry::new();
+        ando_plugins::register_all(&mut registry);
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/cors", "status": 1,
+            "plugins": { "cors": { "_meta": { "filter": [["http_x_env", "~~", "("]] } } },
+            "upstream": { "nodes": { "127.0.0.1:8080": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+        let mut w = make_worker_with_registry(vec![route], registry, ConfigCache::new())
+            .with_metrics(Arc::clone(&metrics));
+
+        assert!(w.get_or_build_pipeline("r1").is_empty());
+        let counter = metrics.plugin_config_errors.as_ref().unwrap();
+        assert_eq!(counter.with_label_values(&["r1", "cors"]).get(), 1);
+    }
+
+    #[test]
+    fn maybe_update_router_picks_up_upstream_change_without_router_swap() {
+        let route: Route = serde_json::from_value(serde_json::json!({
+            "id": "r1", "uri": "/ref-ups", "status": 1,
+            "upstream_id": "ups1"
+        }))
+        .unwrap();
+        let ups = |addr: &str| -> Upstream {
+            serde_json::from_value(serde_json::json!({
+                "id": "ups1", "nodes": { addr: 1 }, "type": "roundrobin"
+            }))
+            .unwrap()
+        };
+        let cache = ConfigCache::new();
+        cache
+            .upstreams
+            .insert("ups1".to_string(), ups("10.0.0.2:9090"));
+        let mut w = make_worker_with_registry(vec![route], PluginRegistry::new(), cache.clone());
+
+        cache
+            .upstreams
+            .insert("ups1".to_string(), ups("10.0.0.3:9090"));
+        cache.changes.record(Entity::Upstream, "ups1");
+        let router = Arc::clone(&w.router);
+        w.maybe_update_router(router);
+
+        match w.handle_request("GET", "/ref-ups", None, &[], "x") {
+            RequestResult::Proxy { upstream_addr, .. } => {
+                assert_eq!(upstream_addr, "10.0.0.3:9090");
+            }
+            other => panic!("Expected Proxy, got {:?}", other),
+        }
+    }
+
     // ── ConnPool: take from empty returns None ───────────────────
 
     #[test]
     fn conn_pool_take_empty_returns_none() {
         let mut pool = ConnPool::new(10);
         assert!(pool.take("127.0.0.1:8080").is_none());
+        assert_eq!(
+            pool.take_stats(),
+            PoolStats {
+                misses: 1,
+                ..PoolStats::default()
+            }
+        );
+        assert_eq!(pool.take_stats(), PoolStats::default());
     }
 
     // ── ConnPool: max_idle enforced ──────────────────────────────
@@ -1104,7 +5315,7 @@ mod tests {
     #[test]
     fn build_upstream_request_no_body_no_content_length() {
         let mut buf = Vec::new();
-        build_upstream_request(&mut buf, "GET", "/test", &[], b"");
+        build_upstream_request(&mut buf, "GET", "/test", &[], &[], None, b"");
         let text = String::from_utf8(buf).unwrap();
         assert!(
             !text.contains("content-length:"),
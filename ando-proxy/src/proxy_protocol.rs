@@ -0,0 +1,408 @@
+//! PROXY protocol (v1 text and v2 binary) headers, as sent by HAProxy and
+//! L4 load balancers (AWS NLB, GCP) ahead of the client's first byte.
+//!
+//! Listeners with `proxy_protocol` read the header before anything else
+//! (before the TLS handshake on HTTPS listeners) and take the client
+//! address from it. The reads are sized from what the header still needs,
+//! so none overshoots into the client's data and the stream is handed on
+//! as it is. Upstreams with `send_proxy_protocol` get a header naming the
+//! client at the start of every new connection.
+
+use ando_core::upstream::ProxyProtocolVersion;
+use monoio::io::AsyncReadRentExt;
+use monoio::net::TcpStream;
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Signature starting every v2 header.
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// Longest v1 header, CRLF included.
+const V1_MAX_LEN: usize = 107;
+/// Shortest v1 header: `PROXY UNKNOWN\r\n`.
+const V1_MIN_LEN: usize = 15;
+/// v2 signature, version/command, family and address length.
+const V2_FIXED_LEN: usize = 16;
+/// Bytes read first: enough to tell the versions apart, and no more than
+/// the shortest header of either.
+const FIRST_READ: usize = 8;
+
+/// Addresses a PROXY protocol header names. Both are `None` for `LOCAL`
+/// (v2 health checks) and `UNKNOWN` headers, and for address families
+/// other than TCP/UDP over IPv4 and IPv6: the connection's own apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxyHeader {
+    pub source: Option<SocketAddr>,
+    pub destination: Option<SocketAddr>,
+}
+
+/// Outcome of `parse`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Parsed {
+    /// The header and its length in bytes.
+    Complete(ProxyHeader, usize),
+    /// The header needs at least this many more bytes, and reading that
+    /// many cannot go past its end.
+    Incomplete(usize),
+}
+
+/// Parse the PROXY protocol header at the start of `buf`.
+pub fn parse(buf: &[u8]) -> Result<Parsed, String> {
+    if buf.is_empty() {
+        return Ok(Parsed::Incomplete(FIRST_READ));
+    }
+    let probe = buf.len().min(V2_SIGNATURE.len());
+    if buf[..probe] == V2_SIGNATURE[..probe] {
+        return parse_v2(buf);
+    }
+    let probe = buf.len().min(V1_PREFIX.len());
+    if buf[..probe] == V1_PREFIX[..probe] {
+        return parse_v1(buf);
+    }
+    Err("not a PROXY protocol header".to_string())
+}
+
+fn parse_v1(buf: &[u8]) -> Result<Parsed, String> {
+    let Some(end) = buf.windows(2).position(|w| w == b"\r\n") else {
+        if buf.len() >= V1_MAX_LEN {
+            return Err("v1 header longer than 107 bytes".to_string());
+        }
+        // The CRLF is still to come: one byte if the last read ended on
+        // its CR, else at least two.
+        let needed = if buf.len() < V1_MIN_LEN {
+            V1_MIN_LEN - buf.len()
+        } else if buf.last() == Some(&b'\r') {
+            1
+        } else {
+            2
+        };
+        return Ok(Parsed::Incomplete(needed.min(V1_MAX_LEN - buf.len())));
+    };
+    let line = std::str::from_utf8(&buf[V1_PREFIX.len()..end])
+        .map_err(|_| "v1 header is not ASCII".to_string())?;
+    let fields: Vec<&str> = line.split(' ').collect();
+    let header = match fields[..] {
+        ["UNKNOWN", ..] => ProxyHeader {
+            source: None,
+            destination: None,
+        },
+        [family @ ("TCP4" | "TCP6"), src, dst, sport, dport] => {
+            let ip = |s: &str| -> Result<IpAddr, String> {
+                let ip = if family == "TCP4" {
+                    s.parse::<Ipv4Addr>().map(IpAddr::V4)
+                } else {
+                    s.parse::<Ipv6Addr>().map(IpAddr::V6)
+                };
+                ip.map_err(|_| format!("v1 header: invalid {family} address `{s}`"))
+            };
+            let port = |s: &str| -> Result<u16, String> {
+                s.parse()
+                    .map_err(|_| format!("v1 header: invalid port `{s}`"))
+            };
+            ProxyHeader {
+                source: Some(SocketAddr::new(ip(src)?, port(sport)?)),
+                destination: Some(SocketAddr::new(ip(dst)?, port(dport)?)),
+            }
+        }
+        _ => return Err(format!("v1 header: malformed `PROXY {line}`")),
+    };
+    Ok(Parsed::Complete(header, end + 2))
+}
+
+fn parse_v2(buf: &[u8]) -> Result<Parsed, String> {
+    if buf.len() < V2_FIXED_LEN {
+        return Ok(Parsed::Incomplete(V2_FIXED_LEN - buf.len()));
+    }
+    let version = buf[12] >> 4;
+    if version != 2 {
+        return Err(format!("v2 header: unsupported version {version}"));
+    }
+    let len = V2_FIXED_LEN + u16::from_be_bytes([buf[14], buf[15]]) as usize;
+    if buf.len() < len {
+        return Ok(Parsed::Incomplete(len - buf.len()));
+    }
+    let local = ProxyHeader {
+        source: None,
+        destination: None,
+    };
+    let header = match buf[12] & 0x0f {
+        0x0 => local,
+        0x1 => {
+            let addrs = &buf[V2_FIXED_LEN..len];
+            let port = |at: usize| u16::from_be_bytes([addrs[at], addrs[at + 1]]);
+            match buf[13] >> 4 {
+                // AF_INET: two addresses, then two ports; TLVs follow.
+                0x1 if addrs.len() >= 12 => {
+                    let ip = |at: usize| -> Ipv4Addr {
+                        <[u8; 4]>::try_from(&addrs[at..at + 4]).unwrap().into()
+                    };
+                    ProxyHeader {
+                        source: Some(SocketAddr::new(ip(0).into(), port(8))),
+                        destination: Some(SocketAddr::new(ip(4).into(), port(10))),
+                    }
+                }
+                // AF_INET6
+                0x2 if addrs.len() >= 36 => {
+                    let ip = |at: usize| -> Ipv6Addr {
+                        <[u8; 16]>::try_from(&addrs[at..at + 16]).unwrap().into()
+                    };
+                    ProxyHeader {
+                        source: Some(SocketAddr::new(ip(0).into(), port(32))),
+                        destination: Some(SocketAddr::new(ip(16).into(), port(34))),
+                    }
+                }
+                0x1 | 0x2 => return Err("v2 header: address block too short".to_string()),
+                // AF_UNSPEC and AF_UNIX name no IP address.
+                _ => local,
+            }
+        }
+        command => return Err(format!("v2 header: unsupported command {command}")),
+    };
+    Ok(Parsed::Complete(header, len))
+}
+
+/// Read the PROXY protocol header a connection starts with, within
+/// `limit`. Fails with `InvalidData` when it is malformed or missing.
+pub async fn read_header(stream: &mut TcpStream, limit: Duration) -> io::Result<ProxyHeader> {
+    let read = async {
+        let mut buf = Vec::with_capacity(V1_MAX_LEN);
+        let mut want = FIRST_READ;
+        loop {
+            let (res, chunk) = stream.read_exact(vec![0u8; want]).await;
+            res?;
+            buf.extend_from_slice(&chunk);
+            match parse(&buf).map_err(|e| io::Error::new(ErrorKind::InvalidData, e))? {
+                Parsed::Complete(header, _) => return Ok(header),
+                Parsed::Incomplete(more) => want = more,
+            }
+        }
+    };
+    monoio::time::timeout(limit, read)
+        .await
+        .unwrap_or_else(|_| Err(ErrorKind::TimedOut.into()))
+}
+
+/// The header announcing a connection from `source` to `destination`.
+/// When one address is IPv4 and the other IPv6, the IPv4 one is sent
+/// IPv4-mapped.
+pub fn encode(
+    version: ProxyProtocolVersion,
+    source: SocketAddr,
+    destination: SocketAddr,
+) -> Vec<u8> {
+    let (src, dst) = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => (IpAddr::V4(src), IpAddr::V4(dst)),
+        (src, dst) => (IpAddr::V6(to_v6(src)), IpAddr::V6(to_v6(dst))),
+    };
+    match version {
+        ProxyProtocolVersion::V1 => {
+            let family = if src.is_ipv4() { "TCP4" } else { "TCP6" };
+            format!(
+                "PROXY {family} {src} {dst} {} {}\r\n",
+                source.port(),
+                destination.port()
+            )
+            .into_bytes()
+        }
+        ProxyProtocolVersion::V2 => {
+            let mut out = Vec::with_capacity(V2_FIXED_LEN + 36);
+            out.extend_from_slice(V2_SIGNATURE);
+            // Version 2, PROXY.
+            out.push(0x21);
+            match (src, dst) {
+                (IpAddr::V4(src), IpAddr::V4(dst)) => {
+                    // AF_INET, STREAM.
+                    out.push(0x11);
+                    out.extend_from_slice(&12u16.to_be_bytes());
+                    out.extend_from_slice(&src.octets());
+                    out.extend_from_slice(&dst.octets());
+                }
+                (src, dst) => {
+                    // AF_INET6, STREAM.
+                    out.push(0x21);
+                    out.extend_from_slice(&36u16.to_be_bytes());
+                    out.extend_from_slice(&to_v6(src).octets());
+                    out.extend_from_slice(&to_v6(dst).octets());
+                }
+            }
+            out.extend_from_slice(&source.port().to_be_bytes());
+            out.extend_from_slice(&destination.port().to_be_bytes());
+            out
+        }
+    }
+}
+
+fn to_v6(ip: IpAddr) -> Ipv6Addr {
+    match ip {
+        IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+        IpAddr::V6(ip) => ip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn complete(buf: &[u8]) -> (ProxyHeader, usize) {
+        match parse(buf).unwrap() {
+            Parsed::Complete(header, len) => (header, len),
+            Parsed::Incomplete(n) => panic!("incomplete, {n} more"),
+        }
+    }
+
+    /// Feed `buf` the way `read_header` does, checking no read overshoots.
+    fn read_in_steps(buf: &[u8]) -> Result<(ProxyHeader, usize), String> {
+        let mut have = FIRST_READ.min(buf.len());
+        loop {
+            match parse(&buf[..have])? {
+                Parsed::Complete(header, len) => {
+                    assert_eq!(len, have, "read past the header");
+                    return Ok((header, len));
+                }
+                Parsed::Incomplete(more) => {
+                    assert!(have + more <= buf.len(), "read past the input");
+                    have += more;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn parses_v1_headers() {
+        let (header, len) = complete(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\nGET /");
+        assert_eq!(len, 43);
+        assert_eq!(header.source, Some(addr("203.0.113.7:51234")));
+        assert_eq!(header.destination, Some(addr("10.0.0.1:443")));
+
+        let (header, _) = complete(b"PROXY TCP6 2001:db8::7 2001:db8::1 4000 80\r\n");
+        assert_eq!(header.source, Some(addr("[2001:db8::7]:4000")));
+        assert_eq!(header.destination, Some(addr("[2001:db8::1]:80")));
+
+        let (header, len) = complete(b"PROXY UNKNOWN ffff:f::1 x 1 2\r\n");
+        assert_eq!(len, 31);
+        assert_eq!(header.source, None);
+        assert_eq!(header.destination, None);
+    }
+
+    #[test]
+    fn parses_v2_headers() {
+        let mut v4 = V2_SIGNATURE.to_vec();
+        v4.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]);
+        v4.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1, 0xc8, 0x22, 0x01, 0xbb]);
+        v4.extend_from_slice(b"GET /");
+        let (header, len) = complete(&v4);
+        assert_eq!(len, 28);
+        assert_eq!(header.source, Some(addr("203.0.113.7:51234")));
+        assert_eq!(header.destination, Some(addr("10.0.0.1:443")));
+
+        // TLVs after the addresses are skipped.
+        let mut v6 = V2_SIGNATURE.to_vec();
+        v6.extend_from_slice(&[0x21, 0x21, 0x00, 0x2a]);
+        v6.extend_from_slice(&"2001:db8::7".parse::<Ipv6Addr>().unwrap().octets());
+        v6.extend_from_slice(&"2001:db8::1".parse::<Ipv6Addr>().unwrap().octets());
+        v6.extend_from_slice(&[0x0f, 0xa0, 0x00, 0x50]);
+        v6.extend_from_slice(&[0x04, 0x00, 0x03, b'a', b'b', b'c']);
+        let (header, len) = complete(&v6);
+        assert_eq!(len, 58);
+        assert_eq!(header.source, Some(addr("[2001:db8::7]:4000")));
+        assert_eq!(header.destination, Some(addr("[2001:db8::1]:80")));
+
+        // LOCAL: the connection's own addresses apply.
+        let mut local = V2_SIGNATURE.to_vec();
+        local.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+        let (header, len) = complete(&local);
+        assert_eq!(len, 16);
+        assert_eq!(header.source, None);
+    }
+
+    #[test]
+    fn reads_never_pass_the_header() {
+        let v1 = b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\n";
+        assert_eq!(read_in_steps(v1).unwrap().1, v1.len());
+        let unknown = b"PROXY UNKNOWN\r\n";
+        assert_eq!(read_in_steps(unknown).unwrap().1, unknown.len());
+        let v2 = encode(
+            ProxyProtocolVersion::V2,
+            addr("[2001:db8::7]:4000"),
+            addr("[2001:db8::1]:80"),
+        );
+        assert_eq!(read_in_steps(&v2).unwrap().1, v2.len());
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for bad in [
+            &b"GET / HTTP/1.1\r\n\r\n"[..],
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 51234\r\n",
+            b"PROXY TCP4 2001:db8::7 10.0.0.1 1 2\r\n",
+            b"PROXY TCP6 1.2.3.4 1.2.3.4 1 2\r\n",
+            b"PROXY TCP4 1.2.3.4 1.2.3.4 70000 2\r\n",
+            b"PROXY UDP4 1.2.3.4 1.2.3.4 1 2\r\n",
+        ] {
+            assert!(parse(bad).is_err(), "{}", String::from_utf8_lossy(bad));
+        }
+        let long = [&b"PROXY TCP6 "[..], &[b'1'; 100]].concat();
+        assert!(read_in_steps(&long).is_err());
+
+        let mut v1_version = V2_SIGNATURE.to_vec();
+        v1_version.extend_from_slice(&[0x11, 0x11, 0x00, 0x00]);
+        assert!(parse(&v1_version).is_err());
+        let mut short = V2_SIGNATURE.to_vec();
+        short.extend_from_slice(&[0x21, 0x11, 0x00, 0x04, 1, 2, 3, 4]);
+        assert!(parse(&short).is_err());
+        let mut command = V2_SIGNATURE.to_vec();
+        command.extend_from_slice(&[0x22, 0x11, 0x00, 0x00]);
+        assert!(parse(&command).is_err());
+    }
+
+    #[test]
+    fn encodes_v1_byte_exact() {
+        assert_eq!(
+            encode(
+                ProxyProtocolVersion::V1,
+                addr("203.0.113.7:51234"),
+                addr("10.0.0.1:443")
+            ),
+            b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\n"
+        );
+        assert_eq!(
+            encode(
+                ProxyProtocolVersion::V1,
+                addr("[2001:db8::7]:4000"),
+                addr("10.0.0.1:80")
+            ),
+            b"PROXY TCP6 2001:db8::7 ::ffff:10.0.0.1 4000 80\r\n"
+        );
+    }
+
+    #[test]
+    fn encodes_v2_byte_exact() {
+        let mut expected = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+        expected.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]);
+        expected.extend_from_slice(&[203, 0, 113, 7, 10, 0, 0, 1, 0xc8, 0x22, 0x01, 0xbb]);
+        let header = encode(
+            ProxyProtocolVersion::V2,
+            addr("203.0.113.7:51234"),
+            addr("10.0.0.1:443"),
+        );
+        assert_eq!(header, expected);
+        assert_eq!(complete(&header).0.source, Some(addr("203.0.113.7:51234")));
+
+        let header = encode(
+            ProxyProtocolVersion::V2,
+            addr("[2001:db8::7]:4000"),
+            addr("10.0.0.1:80"),
+        );
+        assert_eq!(&header[12..16], &[0x21, 0x21, 0x00, 0x24]);
+        assert_eq!(header.len(), 52);
+        assert_eq!(
+            complete(&header).0.destination,
+            Some(addr("[::ffff:10.0.0.1]:80"))
+        );
+    }
+}
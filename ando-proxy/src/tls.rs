@@ -0,0 +1,710 @@
+//! TLS termination for the HTTPS listener.
+//!
+//! v2 design: Certificates live in `ConfigCache::ssl_certs`, fed by etcd
+//! like every other entity. The resolver reads that map on each handshake
+//! and picks a certificate by SNI, so a watcher update takes effect on the
+//! next connection without restarting workers. Parsed keys are cached by
+//! certificate id and content fingerprint, so PEM decoding only happens
+//! the first time a certificate (or a new version of it) is served.
+//! Keys the Admin API stored encrypted are decrypted with the SSL master
+//! key at that point.
+//!
+//! SSL objects with `client_tls` ask their clients for a certificate
+//! (mTLS). rustls settles on a verifier before the SNI is known, so while
+//! any SSL object has one, each handshake gets its own config that asks
+//! for a certificate and keeps it; the connection then checks it against
+//! the CAs of the SSL object it was served (see `TlsServer`).
+//!
+//! Connections to `https` upstreams use one of two process-wide client
+//! configs: verifying against the system roots, or not verifying at all
+//! for upstreams with `tls_verify: false`.
+
+use ando_core::ssl::{ClientTls, ClientTlsMode, MasterKey, SslCertificate};
+use ando_core::upstream::{Upstream, node_host};
+use dashmap::DashMap;
+use monoio_rustls::{TlsAcceptor, TlsConnector};
+use rustls::client::{ServerCertVerified, ServerCertVerifier};
+use rustls::server::{
+    AllowAnyAuthenticatedClient, ClientCertVerified, ClientCertVerifier, ClientHello,
+    NoServerSessionStorage, ResolvesServerCert,
+};
+use rustls::sign::{CertifiedKey, any_supported_type};
+use rustls::{
+    Certificate, ClientConfig, DistinguishedName, PrivateKey, RootCertStore, ServerConfig,
+    ServerName,
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tracing::warn;
+
+/// Build the server config shared by every worker's TLS acceptor.
+pub fn server_config(resolver: Arc<CertResolver>, http2: bool) -> Arc<ServerConfig> {
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(resolver);
+    config.alpn_protocols = alpn(http2);
+    Arc::new(config)
+}
+
+fn alpn(http2: bool) -> Vec<Vec<u8>> {
+    if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    }
+}
+
+/// The HTTPS listener's TLS side, shared by every worker.
+pub struct TlsServer {
+    resolver: Arc<CertResolver>,
+    /// Config for handshakes that ask for no client certificate.
+    config: Arc<ServerConfig>,
+    http2: bool,
+}
+
+impl TlsServer {
+    pub fn new(resolver: Arc<CertResolver>, http2: bool) -> Self {
+        Self {
+            config: server_config(Arc::clone(&resolver), http2),
+            resolver,
+            http2,
+        }
+    }
+
+    /// Acceptor for one connection. While some SSL object has
+    /// `client_tls`, also the handshake state that ends up holding the
+    /// client's certificate.
+    ///
+    /// Such handshakes offer the subjects of every client CA, never insist
+    /// on a certificate, and do not resume sessions: a resumed session
+    /// would skip the verifier that records the certificate.
+    pub fn acceptor(&self) -> (TlsAcceptor, Option<Arc<Handshake>>) {
+        let subjects = self.resolver.client_ca_subjects();
+        if subjects.is_empty() {
+            return (TlsAcceptor::from(Arc::clone(&self.config)), None);
+        }
+        let handshake = Arc::new(Handshake {
+            resolver: Arc::clone(&self.resolver),
+            subjects,
+            seen: Mutex::default(),
+        });
+        let mut config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_client_cert_verifier(Arc::clone(&handshake) as Arc<dyn ClientCertVerifier>)
+            .with_cert_resolver(Arc::clone(&handshake) as Arc<dyn ResolvesServerCert>);
+        config.alpn_protocols = alpn(self.http2);
+        config.session_storage = Arc::new(NoServerSessionStorage {});
+        (TlsAcceptor::from(config), Some(handshake))
+    }
+}
+
+/// How well a certificate matches the requested server name; lower wins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    Exact,
+    Wildcard,
+    /// Certificate without `snis`, served when nothing else matches.
+    Default,
+}
+
+fn match_sni(snis: &[String], sni: Option<&str>) -> Option<Match> {
+    if snis.is_empty() {
+        return Some(Match::Default);
+    }
+    let sni = sni?;
+    let mut best = None;
+    for pattern in snis {
+        if pattern.eq_ignore_ascii_case(sni) {
+            return Some(Match::Exact);
+        }
+        // `*.example.com` covers exactly one extra label.
+        if let Some(suffix) = pattern.strip_prefix("*.")
+            && let Some((label, rest)) = sni.split_once('.')
+            && !label.is_empty()
+            && rest.eq_ignore_ascii_case(suffix)
+        {
+            best = Some(Match::Wildcard);
+        }
+    }
+    best
+}
+
+fn fingerprint(ssl: &SslCertificate) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    ssl.cert.hash(&mut hasher);
+    ssl.key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Decode a PEM certificate chain and private key into a signing key.
+pub fn parse_certified_key(cert_pem: &str, key_pem: &str) -> Result<CertifiedKey, String> {
+    let chain: Vec<Certificate> = pem::parse_many(cert_pem)
+        .map_err(|e| format!("invalid certificate PEM: {e}"))?
+        .into_iter()
+        .filter(|p| p.tag() == "CERTIFICATE")
+        .map(|p| Certificate(p.into_contents()))
+        .collect();
+    if chain.is_empty() {
+        return Err("no certificate found in PEM".into());
+    }
+    let key = pem::parse_many(key_pem)
+        .map_err(|e| format!("invalid key PEM: {e}"))?
+        .into_iter()
+        .find(|p| p.tag().ends_with("PRIVATE KEY"))
+        .ok_or("no private key found in PEM")?;
+    let signing_key = any_supported_type(&PrivateKey(key.into_contents()))
+        .map_err(|_| "unsupported private key type".to_string())?;
+    Ok(CertifiedKey::new(chain, signing_key))
+}
+
+/// Picks the certificate for each handshake from the SSL cache.
+///
+/// An exact SNI match beats a `*.domain` wildcard, which beats an enabled
+/// certificate with no `snis` (the default). Disabled certificates and
+/// ones that fail to parse are skipped.
+pub struct CertResolver {
+    certs: Arc<DashMap<String, SslCertificate>>,
+    /// id → (fingerprint, parsed key)
+    parsed: DashMap<String, (u64, Arc<CertifiedKey>)>,
+    /// id → (fingerprint of `client_tls`, parsed CAs)
+    client_cas: DashMap<String, (u64, Arc<ClientCa>)>,
+    /// Decrypts keys stored encrypted.
+    master_key: Option<MasterKey>,
+}
+
+impl std::fmt::Debug for CertResolver {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CertResolver")
+            .field("certs", &self.certs.len())
+            .finish()
+    }
+}
+
+impl CertResolver {
+    pub fn new(certs: Arc<DashMap<String, SslCertificate>>) -> Self {
+        Self {
+            certs,
+            parsed: DashMap::new(),
+            client_cas: DashMap::new(),
+            master_key: None,
+        }
+    }
+
+    /// Decrypt keys stored encrypted with `master_key` (`ssl.master_key`).
+    pub fn with_master_key(mut self, master_key: Option<MasterKey>) -> Self {
+        self.master_key = master_key;
+        self
+    }
+
+    /// Certificate to serve for `sni`, if any.
+    pub fn certified_key(&self, sni: Option<&str>) -> Option<Arc<CertifiedKey>> {
+        self.pick(sni).map(|(_, key)| key)
+    }
+
+    /// The SSL object to serve for `sni` and its key.
+    fn pick(&self, sni: Option<&str>) -> Option<(String, Arc<CertifiedKey>)> {
+        let mut candidates: Vec<(Match, String)> = self
+            .certs
+            .iter()
+            .filter(|e| e.status == 1)
+            .filter_map(|e| match_sni(&e.snis, sni).map(|m| (m, e.id.clone())))
+            .collect();
+        candidates.sort();
+        candidates
+            .into_iter()
+            .find_map(|(_, id)| self.load(&id).map(|key| (id, key)))
+    }
+
+    /// Subjects of the client CAs of every enabled SSL object.
+    fn client_ca_subjects(&self) -> Vec<DistinguishedName> {
+        let ids: Vec<String> = self
+            .certs
+            .iter()
+            .filter(|e| e.status == 1 && e.client_tls.is_some())
+            .map(|e| e.id.clone())
+            .collect();
+        let mut subjects = Vec::new();
+        for id in ids {
+            if let Some(ca) = self.client_ca(&id) {
+                subjects.extend(ca.subjects.iter().cloned());
+            }
+        }
+        subjects
+    }
+
+    /// Client certificate settings of SSL object `id`, if it has any.
+    fn client_ca(&self, id: &str) -> Option<Arc<ClientCa>> {
+        let ssl = self.certs.get(id)?;
+        let client = ssl.client_tls.as_ref()?;
+        let mut hasher = DefaultHasher::new();
+        client.hash(&mut hasher);
+        let print = hasher.finish();
+        if let Some(entry) = self.client_cas.get(id)
+            && entry.0 == print
+        {
+            return Some(Arc::clone(&entry.1));
+        }
+        let ca = Arc::new(ClientCa::new(id, client));
+        drop(ssl);
+        self.client_cas.retain(|id, _| self.certs.contains_key(id));
+        self.client_cas
+            .insert(id.to_string(), (print, Arc::clone(&ca)));
+        Some(ca)
+    }
+
+    fn load(&self, id: &str) -> Option<Arc<CertifiedKey>> {
+        let ssl = self.certs.get(id)?;
+        let print = fingerprint(&ssl);
+        if let Some(entry) = self.parsed.get(id)
+            && entry.0 == print
+        {
+            return Some(Arc::clone(&entry.1));
+        }
+        let parsed = ssl
+            .key_pem(self.master_key.as_ref())
+            .and_then(|key| parse_certified_key(&ssl.cert, &key));
+        match parsed {
+            Ok(key) => {
+                let key = Arc::new(key);
+                drop(ssl);
+                self.parsed.retain(|id, _| self.certs.contains_key(id));
+                self.parsed
+                    .insert(id.to_string(), (print, Arc::clone(&key)));
+                Some(key)
+            }
+            Err(e) => {
+                warn!(ssl_id = %id, error = %e, "Skipping unusable SSL certificate");
+                None
+            }
+        }
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        self.certified_key(client_hello.server_name())
+    }
+}
+
+// ── Client certificates ───────────────────────────────────────
+
+/// An SSL object's `client_tls`, parsed.
+struct ClientCa {
+    verifier: AllowAnyAuthenticatedClient,
+    subjects: Vec<DistinguishedName>,
+    verify_depth: u8,
+    mode: ClientTlsMode,
+}
+
+impl ClientCa {
+    /// CAs that fail to parse are skipped; with none left, no client
+    /// certificate verifies.
+    fn new(id: &str, client: &ClientTls) -> Self {
+        let mut roots = RootCertStore::empty();
+        match pem::parse_many(&client.ca) {
+            Ok(blocks) => {
+                let ders: Vec<Vec<u8>> = blocks
+                    .into_iter()
+                    .filter(|p| p.tag() == "CERTIFICATE")
+                    .map(|p| p.into_contents())
+                    .collect();
+                roots.add_parsable_certificates(&ders);
+            }
+            Err(e) => warn!(ssl_id = %id, error = %e, "Invalid client CA PEM"),
+        }
+        if roots.is_empty() {
+            warn!(ssl_id = %id, "No usable client CA: client certificates will not verify");
+        }
+        Self {
+            subjects: roots.roots.iter().map(|r| r.subject().clone()).collect(),
+            verifier: AllowAnyAuthenticatedClient::new(roots),
+            verify_depth: client.verify_depth,
+            mode: client.mode,
+        }
+    }
+
+    /// `$ssl_client_verify` for `chain`: `SUCCESS`, `NONE` (no
+    /// certificate) or `FAILED:<reason>`.
+    fn verify(&self, chain: Option<&(Certificate, Vec<Certificate>)>) -> String {
+        let Some((end_entity, intermediates)) = chain else {
+            return "NONE".to_string();
+        };
+        // The CA itself counts towards the depth.
+        if intermediates.len() >= usize::from(self.verify_depth.max(1)) {
+            return "FAILED:certificate chain too long".to_string();
+        }
+        match self
+            .verifier
+            .verify_client_cert(end_entity, intermediates, SystemTime::now())
+        {
+            Ok(_) => "SUCCESS".to_string(),
+            Err(e) => format!("FAILED:{e}"),
+        }
+    }
+}
+
+/// One handshake of a connection whose SSL objects may ask for a client
+/// certificate: serves the certificate for its SNI and keeps the client's
+/// certificate for `client_cert` to verify.
+pub struct Handshake {
+    resolver: Arc<CertResolver>,
+    subjects: Vec<DistinguishedName>,
+    seen: Mutex<Seen>,
+}
+
+#[derive(Default)]
+struct Seen {
+    /// The SSL object served.
+    ssl_id: Option<String>,
+    /// End-entity certificate and intermediates the client sent.
+    chain: Option<(Certificate, Vec<Certificate>)>,
+}
+
+impl std::fmt::Debug for Handshake {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handshake")
+            .field("subjects", &self.subjects.len())
+            .finish()
+    }
+}
+
+impl Handshake {
+    /// The client's certificate, checked against the CAs of the SSL object
+    /// served; `None` when that object has no `client_tls`.
+    pub fn client_cert(&self) -> Option<ClientCert> {
+        let seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        let ca = self.resolver.client_ca(seen.ssl_id.as_deref()?)?;
+        let verify = ca.verify(seen.chain.as_ref());
+        let mut cert = seen
+            .chain
+            .as_ref()
+            .map(|(end_entity, _)| ClientCert::parse(&end_entity.0))
+            .unwrap_or_default();
+        cert.verify = verify;
+        cert.required = ca.mode == ClientTlsMode::Required;
+        Some(cert)
+    }
+}
+
+impl ResolvesServerCert for Handshake {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let (id, key) = self.resolver.pick(client_hello.server_name())?;
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).ssl_id = Some(id);
+        Some(key)
+    }
+}
+
+impl ClientCertVerifier for Handshake {
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn client_auth_root_subjects(&self) -> &[DistinguishedName] {
+        &self.subjects
+    }
+
+    /// Accepts any chain: it is verified once the SNI is known. rustls
+    /// still checks that the client holds the certificate's key.
+    fn verify_client_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        _now: SystemTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        self.seen.lock().unwrap_or_else(|e| e.into_inner()).chain =
+            Some((end_entity.clone(), intermediates.to_vec()));
+        Ok(ClientCertVerified::assertion())
+    }
+}
+
+/// The client certificate of a connection to an SSL object with
+/// `client_tls`, as plugins see it in `ctx.vars["ssl_client_*"]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientCert {
+    /// `SUCCESS`, `NONE` or `FAILED:<reason>`, like nginx's
+    /// `$ssl_client_verify`.
+    pub verify: String,
+    /// Requests without a verified certificate are refused.
+    pub required: bool,
+    /// Subject and issuer DNs (RFC 4514).
+    pub subject: String,
+    pub issuer: String,
+    /// Serial number, upper-case hex.
+    pub serial: String,
+    /// SHA-256 of the DER certificate, lower-case hex.
+    pub fingerprint: String,
+    /// DNS names, URIs, e-mail addresses and IP addresses of the
+    /// subjectAltName extension.
+    pub san: Vec<String>,
+}
+
+impl ClientCert {
+    /// Details of the DER certificate `der`; empty where it does not parse.
+    fn parse(der: &[u8]) -> Self {
+        use x509_parser::prelude::{FromDer, GeneralName, X509Certificate};
+
+        let mut cert = Self {
+            fingerprint: Sha256::digest(der)
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+            ..Self::default()
+        };
+        let Ok((_, x509)) = X509Certificate::from_der(der) else {
+            return cert;
+        };
+        cert.subject = x509.subject().to_string();
+        cert.issuer = x509.issuer().to_string();
+        cert.serial = x509
+            .raw_serial()
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect();
+        if let Ok(Some(san)) = x509.subject_alternative_name() {
+            for name in &san.value.general_names {
+                match name {
+                    GeneralName::DNSName(v) | GeneralName::URI(v) | GeneralName::RFC822Name(v) => {
+                        cert.san.push(v.to_string());
+                    }
+                    GeneralName::IPAddress(ip) => {
+                        let ip = match *ip {
+                            [a, b, c, d] => Some(std::net::IpAddr::from([*a, *b, *c, *d])),
+                            _ => <[u8; 16]>::try_from(*ip).ok().map(std::net::IpAddr::from),
+                        };
+                        cert.san.extend(ip.map(|ip| ip.to_string()));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        cert
+    }
+
+    pub fn verified(&self) -> bool {
+        self.verify == "SUCCESS"
+    }
+
+    /// Whether requests on this connection are refused: `required` mode
+    /// without a verified certificate.
+    pub fn refused(&self) -> bool {
+        self.required && !self.verified()
+    }
+
+    /// Expose the certificate as `ssl_client_*` variables; the details
+    /// only when a certificate was sent.
+    pub fn add_vars(&self, vars: &mut HashMap<String, serde_json::Value>) {
+        vars.insert("ssl_client_verify".into(), self.verify.clone().into());
+        if self.fingerprint.is_empty() {
+            return;
+        }
+        vars.insert("ssl_client_s_dn".into(), self.subject.clone().into());
+        vars.insert("ssl_client_i_dn".into(), self.issuer.clone().into());
+        vars.insert("ssl_client_serial".into(), self.serial.clone().into());
+        vars.insert(
+            "ssl_client_fingerprint".into(),
+            self.fingerprint.clone().into(),
+        );
+        vars.insert("ssl_client_san".into(), self.san.clone().into());
+    }
+}
+
+// ── Upstream TLS ──────────────────────────────────────────────
+
+/// TLS settings for connections to one node of an `https` or `grpcs`
+/// upstream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpstreamTls {
+    /// Name sent as SNI and checked against the node's certificate.
+    pub sni: String,
+    pub verify: bool,
+    /// Negotiate h2 by ALPN (`grpcs` upstreams).
+    pub h2: bool,
+}
+
+impl UpstreamTls {
+    /// Settings for node `addr` of `ups`; `None` for plaintext upstreams.
+    pub fn for_node(ups: &Upstream, addr: &str) -> Option<Self> {
+        if !ups.is_tls() {
+            return None;
+        }
+        let sni = match ups.sni {
+            Some(ref sni) => sni.clone(),
+            None => node_host(addr).to_string(),
+        };
+        Some(Self {
+            sni,
+            verify: ups.tls_verify,
+            h2: ups.is_grpc(),
+        })
+    }
+
+    /// Connection pool key for `addr`. TLS connections are only reused
+    /// for the same server name and verification mode, and never for a
+    /// plain-HTTP upstream on the same address.
+    pub fn pool_key(&self, addr: &str) -> String {
+        let scheme = if self.h2 { "h2" } else { "https" };
+        let mode = if self.verify { "" } else { "?insecure" };
+        format!("{scheme}://{}@{addr}{mode}", self.sni)
+    }
+
+    pub fn server_name(&self) -> Option<ServerName> {
+        ServerName::try_from(self.sni.as_str()).ok()
+    }
+
+    /// Connector for this upstream's verification mode and ALPN.
+    pub fn connector(&self) -> TlsConnector {
+        static CONNECTORS: [OnceLock<TlsConnector>; 4] = [const { OnceLock::new() }; 4];
+        let slot = usize::from(self.verify) * 2 + usize::from(self.h2);
+        CONNECTORS[slot]
+            .get_or_init(|| TlsConnector::from(client_config(self.verify, self.h2)))
+            .clone()
+    }
+}
+
+fn client_config(verify: bool, h2: bool) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    if verify {
+        let native = rustls_native_certs::load_native_certs();
+        for e in &native.errors {
+            warn!(error = %e, "Failed to load system root certificates");
+        }
+        let ders: Vec<&[u8]> = native.certs.iter().map(|c| c.as_ref()).collect();
+        roots.add_parsable_certificates(&ders);
+    }
+    let mut config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    if !verify {
+        config
+            .dangerous()
+            .set_certificate_verifier(Arc::new(NoVerification));
+    }
+    if h2 {
+        config.alpn_protocols = vec![b"h2".to_vec()];
+    }
+    config
+}
+
+/// Accepts any upstream certificate (`tls_verify: false`). Handshake
+/// signatures are still checked against the presented key.
+struct NoVerification;
+
+impl ServerCertVerifier for NoVerification {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snis(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn exact_match_is_case_insensitive() {
+        let s = snis(&["api.example.com"]);
+        assert_eq!(match_sni(&s, Some("API.example.com")), Some(Match::Exact));
+        assert_eq!(match_sni(&s, Some("www.example.com")), None);
+        assert_eq!(match_sni(&s, None), None);
+    }
+
+    #[test]
+    fn wildcard_covers_one_label() {
+        let s = snis(&["*.example.com"]);
+        assert_eq!(match_sni(&s, Some("a.example.com")), Some(Match::Wildcard));
+        assert_eq!(match_sni(&s, Some("a.b.example.com")), None);
+        assert_eq!(match_sni(&s, Some("example.com")), None);
+        assert_eq!(match_sni(&s, Some(".example.com")), None);
+    }
+
+    #[test]
+    fn exact_beats_wildcard_beats_default() {
+        assert!(Match::Exact < Match::Wildcard);
+        assert!(Match::Wildcard < Match::Default);
+        let s = snis(&["*.example.com", "a.example.com"]);
+        assert_eq!(match_sni(&s, Some("a.example.com")), Some(Match::Exact));
+        assert_eq!(match_sni(&[], Some("anything")), Some(Match::Default));
+        assert_eq!(match_sni(&[], None), Some(Match::Default));
+    }
+
+    #[test]
+    fn unusable_certificate_is_skipped() {
+        let certs = Arc::new(DashMap::new());
+        certs.insert(
+            "bad".to_string(),
+            SslCertificate {
+                id: "bad".into(),
+                cert: "not pem".into(),
+                key: "not pem".into(),
+                snis: vec![],
+                status: 1,
+                client_tls: None,
+            },
+        );
+        let resolver = CertResolver::new(certs);
+        assert!(resolver.certified_key(Some("a.example.com")).is_none());
+        assert!(parse_certified_key("", "").is_err());
+    }
+
+    #[test]
+    fn upstream_tls_defaults_sni_to_node_host() {
+        let mut ups: Upstream =
+            serde_json::from_str(r#"{"nodes":{"api.example.com:443":1},"scheme":"https"}"#)
+                .unwrap();
+        let tls = UpstreamTls::for_node(&ups, "api.example.com:443").unwrap();
+        assert_eq!(tls.sni, "api.example.com");
+        assert!(tls.verify);
+        assert_eq!(
+            UpstreamTls::for_node(&ups, "[::1]:8443").unwrap().sni,
+            "::1"
+        );
+
+        ups.sni = Some("internal.example.com".into());
+        ups.tls_verify = false;
+        let tls = UpstreamTls::for_node(&ups, "10.0.0.1:443").unwrap();
+        assert_eq!(tls.sni, "internal.example.com");
+        assert!(!tls.verify);
+
+        assert!(!tls.h2);
+        ups.scheme = "grpcs".into();
+        assert!(UpstreamTls::for_node(&ups, "10.0.0.1:443").unwrap().h2);
+
+        ups.scheme = "http".into();
+        assert!(UpstreamTls::for_node(&ups, "10.0.0.1:443").is_none());
+    }
+
+    #[test]
+    fn upstream_tls_pool_keys_differ_by_sni_and_mode() {
+        let tls = |sni: &str, verify| UpstreamTls {
+            sni: sni.into(),
+            verify,
+            h2: false,
+        };
+        let addr = "10.0.0.1:443";
+        let key = tls("a.example.com", true).pool_key(addr);
+        assert_ne!(key, addr);
+        assert_ne!(key, tls("b.example.com", true).pool_key(addr));
+        assert_ne!(key, tls("a.example.com", false).pool_key(addr));
+        let h2 = UpstreamTls {
+            h2: true,
+            ..tls("a.example.com", true)
+        };
+        assert_ne!(key, h2.pool_key(addr));
+    }
+}
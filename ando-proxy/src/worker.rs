@@ -1,14 +1,41 @@
-use ando_core::config::GatewayConfig;
+use ando_core::config::{GatewayConfig, ListenerProtocol};
+use ando_core::error_page::ErrorPages;
 use ando_core::router::Router;
+use ando_core::shutdown::Shutdown;
+use ando_observability::access_log::AccessLogger;
+use ando_observability::metrics::MetricsCollector;
+use ando_observability::otel::RequestTracer;
+use ando_observability::statsd::StatsdExporter;
+use ando_observability::worker_stats::{WorkerCounters, WorkerStats};
 use ando_plugin::registry::PluginRegistry;
 use ando_store::cache::ConfigCache;
+use ando_store::notify::pending;
 use arc_swap::ArcSwap;
+use crossbeam_channel::Receiver;
+use monoio::io::{CancelHandle, Canceller};
+use monoio::net::TcpListener;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
-use crate::proxy::{ConnPool, ProxyWorker};
+use crate::connection::ClientAddrs;
+use crate::forwarded::TrustedProxies;
+use crate::listener::{ListenerMode, WorkerListeners};
+use crate::proxy::{ClientTimeouts, ConnPool, ProxyWorker, RequestLimits, UpstreamTimeouts};
+use crate::proxy_protocol;
+use crate::tls::{CertResolver, TlsServer};
+
+/// How often workers add their thread-local metrics to the shared series.
+const METRICS_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How often an idle worker looks for a pending config notification.
+const CONFIG_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How often workers look for a shutdown, and a draining worker for the
+/// end of its requests in flight.
+const SHUTDOWN_CHECK_INTERVAL: Duration = Duration::from_millis(50);
 
 /// Shared state across all worker threads.
 ///
@@ -19,6 +46,19 @@ pub struct SharedState {
     pub plugin_registry: Arc<PluginRegistry>,
     pub config_cache: ConfigCache,
     pub config: Arc<GatewayConfig>,
+    /// Prometheus collector — a no-op when metrics are disabled — and the
+    /// StatsD exporter, when enabled.
+    pub metrics: Arc<MetricsCollector>,
+    /// OpenTelemetry span export; `None` when disabled.
+    pub tracer: Option<Arc<RequestTracer>>,
+    /// Access log file writer; `None` when disabled.
+    pub access_log: Option<Arc<AccessLogger>>,
+    /// Accept, connection and request counters, one set per worker.
+    pub workers: Arc<WorkerStats>,
+    /// Begun on SIGTERM/SIGINT: workers stop accepting and drain.
+    pub shutdown: Arc<Shutdown>,
+    /// The `errors` pages, compiled once for every worker.
+    pub error_pages: Arc<ErrorPages>,
 }
 
 impl SharedState {
@@ -28,107 +68,478 @@ impl SharedState {
         config_cache: ConfigCache,
         config: GatewayConfig,
     ) -> Arc<Self> {
+        let mut metrics = MetricsCollector::new(config.observability.prometheus.enabled)
+            .unwrap_or_else(|e| {
+                error!(error = %e, "Metrics registration failed, metrics disabled");
+                MetricsCollector::new(false).expect("disabled collector is infallible")
+            });
+        match StatsdExporter::from_config(&config.observability.statsd, &metrics) {
+            Ok(Some(statsd)) => metrics = metrics.with_sink(Arc::new(statsd)),
+            Ok(None) => {}
+            Err(e) => error!(error = %e, "StatsD exporter setup failed, StatsD export disabled"),
+        }
+        let tracer = RequestTracer::from_config(&config.observability.opentelemetry)
+            .unwrap_or_else(|e| {
+                error!(error = %e, "OpenTelemetry exporter setup failed, tracing disabled");
+                None
+            });
+        let access_log = AccessLogger::from_config(&config.observability.access_log)
+            .unwrap_or_else(|e| {
+                error!(error = %e, "Access log setup failed, access logging disabled");
+                None
+            });
+        let error_pages = ErrorPages::compile(&config.errors).unwrap_or_else(|e| {
+            error!(error = %e, "Error pages setup failed, built-in error responses used");
+            ErrorPages::default()
+        });
+        let workers = WorkerStats::new(config.effective_workers());
+        let shutdown = Shutdown::new(Duration::from_millis(
+            config.proxy.graceful_shutdown_timeout_ms,
+        ));
         Arc::new(Self {
             router: Arc::new(ArcSwap::new(Arc::new(router))),
             plugin_registry: Arc::new(plugin_registry),
             config_cache,
             config: Arc::new(config),
+            metrics: Arc::new(metrics),
+            tracer: tracer.map(Arc::new),
+            access_log: access_log.map(Arc::new),
+            workers: Arc::new(workers),
+            shutdown: Arc::new(shutdown),
+            error_pages: Arc::new(error_pages),
         })
     }
 }
 
 /// Spawn monoio worker threads — one per core.
 ///
-/// Each thread runs an independent monoio runtime with its own
-/// TCP listener (via SO_REUSEPORT), event loop, and proxy state.
+/// Each thread runs an independent monoio runtime with its own event loop
+/// and proxy state, and accepts on every listener of
+/// `proxy.effective_listeners()` — on its own socket (SO_REUSEPORT)
+/// unless sockets are shared; see `listener`. TLS listeners pick
+/// certificates from the SSL cache per connection by SNI. With
+/// `proxy.pin_workers` each thread is pinned to one CPU core.
+///
+/// Once `shared.shutdown` begins, each worker closes its listeners, waits
+/// for its requests in flight (at most until the drain deadline) and its
+/// thread exits; join the handles to wait for that.
 pub fn spawn_workers(
     shared: Arc<SharedState>,
     num_workers: usize,
 ) -> Vec<std::thread::JoinHandle<()>> {
-    let listen_addr = shared.config.proxy.http_addr.clone();
+    let listeners = WorkerListeners::bind(&shared.config.proxy, num_workers)
+        .unwrap_or_else(|e| panic!("Failed to bind the proxy listeners: {e}"));
+    spawn_workers_on(shared, listeners)
+}
+
+/// A listener as the workers serve it.
+#[derive(Clone)]
+struct Served {
+    name: Arc<str>,
+    addr: Arc<str>,
+    /// Set on HTTPS listeners.
+    tls: Option<Arc<TlsServer>>,
+    /// Connections start with a PROXY protocol header.
+    proxy_protocol: bool,
+}
+
+/// `spawn_workers` on listeners bound (or inherited in a binary upgrade)
+/// beforehand: one worker per socket of each listener.
+pub fn spawn_workers_on(
+    shared: Arc<SharedState>,
+    listeners: WorkerListeners,
+) -> Vec<std::thread::JoinHandle<()>> {
+    let num_workers = listeners.workers();
+    let mode = if listeners.options.reuseport == Some(true) {
+        ListenerMode::PerWorker
+    } else {
+        ListenerMode::Shared
+    };
+    let pin = shared.config.proxy.pin_workers;
+    let resolver = Arc::new(
+        CertResolver::new(Arc::clone(&shared.config_cache.ssl_certs))
+            .with_master_key(shared.config.ssl.master_key()),
+    );
+    let mut served = Vec::with_capacity(listeners.listeners.len());
+    let mut sockets = Vec::with_capacity(listeners.listeners.len());
+    for listener in listeners.listeners {
+        let tls = (listener.spec.protocol == ListenerProtocol::Https).then(|| {
+            let http2 = shared.config.proxy.http2(&listener.spec);
+            Arc::new(TlsServer::new(Arc::clone(&resolver), http2))
+        });
+        served.push(Served {
+            name: listener.spec.name.as_str().into(),
+            addr: listener.spec.addr.as_str().into(),
+            tls,
+            proxy_protocol: shared.config.proxy.proxy_protocol(&listener.spec),
+        });
+        sockets.push(listener.sockets.into_iter());
+    }
     let mut handles = Vec::with_capacity(num_workers);
 
     for worker_id in 0..num_workers {
         let shared = Arc::clone(&shared);
-        let addr = listen_addr.clone();
+        let own: Vec<(Served, std::net::TcpListener)> = served
+            .iter()
+            .cloned()
+            .zip(sockets.iter_mut())
+            .filter_map(|(listener, sockets)| Some((listener, sockets.next()?)))
+            .collect();
 
         let handle = std::thread::Builder::new()
             .name(format!("ando-worker-{}", worker_id))
             .spawn(move || {
+                if pin {
+                    pin_to_core(worker_id);
+                }
                 let mut rt = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
                     .enable_all()
                     .build()
                     .expect("Failed to build monoio runtime");
 
-                rt.block_on(worker_loop(worker_id, shared, addr));
+                rt.block_on(worker_loop(worker_id, shared, own));
             })
             .expect("Failed to spawn worker thread");
 
         handles.push(handle);
     }
 
-    info!(workers = num_workers, addr = %listen_addr, "Workers spawned");
+    let names: Vec<&str> = served.iter().map(|l| &*l.name).collect();
+    info!(workers = num_workers, listeners = ?names, sockets = ?mode, "Workers spawned");
     handles
 }
 
+/// Pin the calling worker thread to CPU core `worker_id` (modulo the
+/// core count). Failure leaves the thread unpinned.
+fn pin_to_core(worker_id: usize) {
+    if !cfg!(target_os = "linux") {
+        warn!(
+            worker = worker_id,
+            "proxy.pin_workers is only supported on Linux"
+        );
+        return;
+    }
+    let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let core = worker_id % cores;
+    match monoio::utils::bind_to_cpu_set([core]) {
+        Ok(()) => debug!(worker = worker_id, core, "Worker pinned"),
+        Err(e) => warn!(worker = worker_id, core, error = %e, "Worker pinning failed"),
+    }
+}
+
 /// Main loop for a single worker thread.
 ///
 /// Creates ONE ProxyWorker and ONE ConnPool for this thread.
 /// All connections on this thread share them via Rc<RefCell>.
 ///
-/// Pool is pre-warmed before accepting any traffic.
-async fn worker_loop(worker_id: usize, shared: Arc<SharedState>, addr: String) {
-    use monoio::net::TcpListener;
-
-    let listener = TcpListener::bind(&addr).unwrap_or_else(|e| {
-        panic!("Worker {} failed to bind to {}: {}", worker_id, addr, e);
-    });
+/// Pool is pre-warmed before accepting any traffic. Returns once the
+/// worker has drained after a shutdown.
+async fn worker_loop(
+    worker_id: usize,
+    shared: Arc<SharedState>,
+    listeners: Vec<(Served, std::net::TcpListener)>,
+) {
+    let listeners: Vec<(Served, TcpListener)> = listeners
+        .into_iter()
+        .map(|(served, socket)| {
+            let socket = TcpListener::from_std(socket).unwrap_or_else(|e| {
+                panic!(
+                    "Worker {} failed to register listener `{}`: {}",
+                    worker_id, served.name, e
+                );
+            });
+            (served, socket)
+        })
+        .collect();
 
-    info!(worker = worker_id, addr = %addr, "Worker listening");
+    let counters = shared.workers.worker(worker_id);
 
     // ── Create ONCE per thread ──
     let pool_size = shared.config.proxy.keepalive_pool_size;
-    let proxy_inner = ProxyWorker::new(
+    let mut proxy_inner = ProxyWorker::new(
         shared.router.load_full(),
         Arc::clone(&shared.plugin_registry),
         shared.config_cache.clone(),
-    );
+    )
+    .with_worker_id(worker_id)
+    .with_worker_counters(Arc::clone(&counters))
+    .with_shutdown(Arc::clone(&shared.shutdown))
+    .with_error_pages(Arc::clone(&shared.error_pages))
+    .with_metrics(Arc::clone(&shared.metrics))
+    .with_max_filtered_body(shared.config.proxy.max_filtered_body_bytes)
+    .with_request_limits(RequestLimits::from_config(&shared.config.proxy))
+    .with_client_timeouts(ClientTimeouts::from_config(&shared.config.proxy))
+    .with_streaming_idle_timeout(Duration::from_millis(
+        shared.config.proxy.streaming_idle_timeout_ms,
+    ))
+    .with_slow_plugin_threshold(shared.config.proxy.slow_plugin_threshold_ms)
+    .with_global_plugins(shared.config.plugins.global.clone())
+    .with_trusted_proxies(TrustedProxies::new(&shared.config.proxy.trusted_proxies))
+    .with_upstream_timeouts(UpstreamTimeouts::from_millis(
+        shared.config.proxy.connect_timeout_ms,
+        shared.config.proxy.write_timeout_ms,
+        shared.config.proxy.read_timeout_ms,
+    ));
+    if let Some(ref tracer) = shared.tracer {
+        proxy_inner = proxy_inner.with_tracer(Arc::clone(tracer));
+    }
+    if let Some(ref access_log) = shared.access_log {
+        proxy_inner = proxy_inner.with_access_log(Arc::clone(access_log));
+    }
 
     // ── Pre-warm connection pool ──
     let upstream_addrs = proxy_inner.upstream_addresses();
-    let mut pool_inner = ConnPool::new(pool_size);
+    let idle_timeout = Duration::from_millis(shared.config.proxy.pool_idle_timeout_ms);
+    let mut pool_inner = ConnPool::new(pool_size).with_idle_timeout(idle_timeout);
     let warm_count = (pool_size / 2).max(8).min(pool_size); // warm half the pool
     pool_inner.warm(&upstream_addrs, warm_count).await;
 
     let proxy = Rc::new(RefCell::new(proxy_inner));
     let conn_pool = Rc::new(RefCell::new(pool_inner));
+    let config_changes = shared.config_cache.notifier.subscribe();
+    // Changes made since the router was loaded above.
+    refresh_worker(&shared, &proxy);
+
+    monoio::spawn(sweep_pool(
+        worker_id,
+        Rc::clone(&conn_pool),
+        Arc::clone(&shared),
+    ));
+    if shared.metrics.is_enabled() || shared.metrics.has_sinks() {
+        monoio::spawn(flush_metrics(Rc::clone(&proxy), Rc::clone(&conn_pool)));
+    }
+    monoio::spawn(watch_config(
+        config_changes.clone(),
+        Arc::clone(&shared),
+        Rc::clone(&proxy),
+    ));
 
+    let mut accepting = Vec::with_capacity(listeners.len());
+    for (served, socket) in listeners {
+        info!(
+            worker = worker_id,
+            listener = %served.name,
+            addr = %served.addr,
+            tls = served.tls.is_some(),
+            proxy_protocol = served.proxy_protocol,
+            "Worker listening"
+        );
+        accepting.push(monoio::spawn(accept_loop(
+            worker_id,
+            socket,
+            served,
+            Rc::clone(&proxy),
+            Rc::clone(&conn_pool),
+            Arc::clone(&shared),
+            config_changes.clone(),
+            Arc::clone(&counters),
+        )));
+    }
+    // Each loop closes its listener when the shutdown begins; new
+    // connections are refused from then on (or, after a binary upgrade,
+    // accepted by the new process).
+    for accepted in accepting {
+        accepted.await;
+    }
+    drain(worker_id, &shared.shutdown, &proxy).await;
+}
+
+/// Accept loop for one listener. The worker's listeners share its proxy
+/// state and connection pool.
+#[allow(clippy::too_many_arguments)]
+async fn accept_loop(
+    worker_id: usize,
+    listener: TcpListener,
+    served: Served,
+    proxy: Rc<RefCell<ProxyWorker>>,
+    conn_pool: Rc<RefCell<ConnPool>>,
+    shared: Arc<SharedState>,
+    config_changes: Receiver<()>,
+    counters: Arc<WorkerCounters>,
+) {
+    let name: Rc<str> = Rc::from(&*served.name);
+    let stop = cancel_on_shutdown(&shared);
     loop {
-        match listener.accept().await {
-            Ok((stream, peer_addr)) => {
+        match listener.cancelable_accept(stop.clone()).await {
+            Ok((mut stream, peer_addr)) => {
+                counters.accepted();
                 // TCP_NODELAY — disable Nagle's for lowest latency
-                let _ = stream.set_nodelay(true);
-
-                // Check for router updates (cheap atomic load)
-                {
-                    let current = shared.router.load_full();
-                    proxy.borrow_mut().maybe_update_router(current);
+                if shared.config.proxy.listener.nodelay {
+                    let _ = stream.set_nodelay(true);
                 }
 
+                // Pick up pending config and upstream health updates
+                check_updates(&shared, &proxy, &config_changes);
+
+                let name = Rc::clone(&name);
+                let tls = served.tls.clone();
+                let proxy_protocol = served.proxy_protocol;
                 let proxy = Rc::clone(&proxy);
                 let pool = Rc::clone(&conn_pool);
 
                 monoio::spawn(async move {
-                    if let Err(e) =
-                        crate::connection::handle_connection(stream, peer_addr, proxy, pool).await
-                    {
+                    let mut addrs = ClientAddrs::of(&stream, peer_addr);
+                    if proxy_protocol {
+                        // Before the TLS handshake and the request head;
+                        // the header timeout covers it.
+                        let limit = proxy.borrow().client_timeouts().header;
+                        match proxy_protocol::read_header(&mut stream, limit).await {
+                            Ok(header) => {
+                                addrs.peer = header.source.unwrap_or(addrs.peer);
+                                addrs.local = header.destination.unwrap_or(addrs.local);
+                            }
+                            Err(e) => {
+                                warn!(
+                                    peer = %peer_addr,
+                                    error = %e,
+                                    "Invalid PROXY protocol header, closing connection"
+                                );
+                                return;
+                            }
+                        }
+                    }
+                    let served = match tls {
+                        Some(tls) => {
+                            crate::connection::handle_tls_connection_on(
+                                name, stream, addrs, tls, proxy, pool,
+                            )
+                            .await
+                        }
+                        None => {
+                            crate::connection::handle_connection_on(
+                                name, stream, addrs, proxy, pool,
+                            )
+                            .await
+                        }
+                    };
+                    if let Err(e) = served {
                         tracing::debug!(error = %e, "Connection closed");
                     }
                 });
             }
+            Err(_) if shared.shutdown.is_draining() => return,
             Err(e) => {
-                error!(worker = worker_id, error = %e, "Accept error");
+                error!(worker = worker_id, listener = %served.name, error = %e, "Accept error");
             }
         }
     }
 }
+
+/// A handle that cancels pending accepts once the gateway starts shutting
+/// down. An accept the kernel completed first still yields its
+/// connection, which is served rather than dropped.
+fn cancel_on_shutdown(shared: &Arc<SharedState>) -> CancelHandle {
+    let canceller = Canceller::new();
+    let handle = canceller.handle();
+    let shared = Arc::clone(shared);
+    monoio::spawn(async move {
+        shutdown_begun(&shared.shutdown).await;
+        canceller.cancel();
+    });
+    handle
+}
+
+/// Resolves once the gateway starts shutting down.
+async fn shutdown_begun(shutdown: &Shutdown) {
+    while !shutdown.is_draining() {
+        monoio::time::sleep(SHUTDOWN_CHECK_INTERVAL).await;
+    }
+}
+
+/// Wait until no request is in flight on this worker, or the drain
+/// deadline passes. Keepalive connections close after their current
+/// response meanwhile; idle ones are dropped with the worker.
+async fn drain(worker_id: usize, shutdown: &Shutdown, proxy: &RefCell<ProxyWorker>) {
+    let deadline = shutdown.deadline().unwrap_or_else(Instant::now);
+    loop {
+        let in_flight = proxy.borrow().in_flight();
+        if in_flight == 0 {
+            info!(worker = worker_id, "Worker drained");
+            return;
+        }
+        if Instant::now() >= deadline {
+            warn!(
+                worker = worker_id,
+                in_flight, "Graceful shutdown timeout, abandoning requests in flight"
+            );
+            return;
+        }
+        monoio::time::sleep(SHUTDOWN_CHECK_INTERVAL).await;
+    }
+}
+
+/// Refresh the router and snapshots if a config notification is pending,
+/// and pick up upstream node updates (cheap atomic loads).
+fn check_updates(shared: &SharedState, proxy: &RefCell<ProxyWorker>, changes: &Receiver<()>) {
+    if pending(changes) {
+        refresh_worker(shared, proxy);
+    }
+    update_nodes(proxy);
+}
+
+/// Pick up health, DNS and discovery updates to upstream nodes.
+fn update_nodes(proxy: &RefCell<ProxyWorker>) {
+    let mut proxy = proxy.borrow_mut();
+    proxy.maybe_update_health();
+    proxy.maybe_update_dns();
+    proxy.maybe_update_discovery();
+}
+
+/// Swap in the current router and re-snapshot what changed.
+fn refresh_worker(shared: &SharedState, proxy: &RefCell<ProxyWorker>) {
+    let current = shared.router.load_full();
+    proxy.borrow_mut().maybe_update_router(current);
+}
+
+/// Apply config notifications on a worker that sees no new connections.
+/// A burst of notifications is drained as one refresh. Node updates are
+/// picked up on the same tick, so a removed endpoint stops receiving
+/// traffic on keepalive connections within `CONFIG_CHECK_INTERVAL`.
+pub async fn watch_config(
+    changes: Receiver<()>,
+    shared: Arc<SharedState>,
+    proxy: Rc<RefCell<ProxyWorker>>,
+) {
+    loop {
+        monoio::time::sleep(CONFIG_CHECK_INTERVAL).await;
+        if pending(&changes) {
+            refresh_worker(&shared, &proxy);
+        }
+        update_nodes(&proxy);
+    }
+}
+
+/// Periodically prune idle upstream connections on this worker and report
+/// the pool counters to the metrics collector.
+async fn sweep_pool(worker_id: usize, pool: Rc<RefCell<ConnPool>>, shared: Arc<SharedState>) {
+    let interval =
+        (pool.borrow().idle_timeout() / 2).clamp(Duration::from_secs(1), Duration::from_secs(30));
+    loop {
+        monoio::time::sleep(interval).await;
+        let (evicted, stats, idle) = {
+            let mut pool = pool.borrow_mut();
+            let evicted = pool.sweep();
+            let idle: usize = pool.idle_counts().map(|(_, n)| n).sum();
+            (evicted, pool.take_stats(), idle)
+        };
+        shared.workers.worker(worker_id).set_pool_connections(idle);
+        if evicted > 0 {
+            debug!(
+                worker = worker_id,
+                evicted, "Pruned idle upstream connections"
+            );
+        }
+        shared
+            .metrics
+            .record_pool_events(stats.hits, stats.misses, stats.evictions);
+    }
+}
+
+/// Push the worker's thread-local request series and pool size to the
+/// shared collector every `METRICS_FLUSH_INTERVAL`.
+async fn flush_metrics(proxy: Rc<RefCell<ProxyWorker>>, pool: Rc<RefCell<ConnPool>>) {
+    loop {
+        monoio::time::sleep(METRICS_FLUSH_INTERVAL).await;
+        proxy.borrow_mut().flush_metrics(&pool.borrow());
+    }
+}
@@ -7,15 +7,16 @@
 use ando_core::router::Router;
 use ando_plugin::registry::PluginRegistry;
 use ando_proxy::connection::handle_connection;
-use ando_proxy::proxy::{ConnPool, ProxyWorker};
+use ando_proxy::proxy::{ClientTimeouts, ConnPool, ProxyWorker, RequestLimits};
 use ando_store::cache::ConfigCache;
 use monoio::io::{AsyncReadRent, AsyncWriteRentExt};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::Arc;
 
-fn make_rt() -> monoio::Runtime<monoio::LegacyDriver> {
+fn make_rt() -> monoio::Runtime<monoio::time::TimeDriver<monoio::LegacyDriver>> {
     monoio::RuntimeBuilder::<monoio::LegacyDriver>::new()
+        .enable_timer()
         .build()
         .expect("monoio runtime build failed")
 }
@@ -473,3 +474,4167 @@ fn handle_connection_static_405_for_method_not_allowed() {
         );
     });
 }
+
+// ── Test 10: body-filter plugin rewrites the upstream body ───────────────
+
+struct UppercaseBody;
+
+impl ando_plugin::plugin::Plugin for UppercaseBody {
+    fn name(&self) -> &str {
+        "uppercase-body"
+    }
+    fn phases(&self) -> &[ando_plugin::plugin::Phase] {
+        &[ando_plugin::plugin::Phase::BodyFilter]
+    }
+    fn configure(
+        &self,
+        _config: &serde_json::Value,
+    ) -> anyhow::Result<Box<dyn ando_plugin::plugin::PluginInstance>> {
+        Ok(Box::new(UppercaseBody))
+    }
+}
+
+impl ando_plugin::plugin::PluginInstance for UppercaseBody {
+    fn name(&self) -> &str {
+        "uppercase-body"
+    }
+    fn filters_body(&self) -> bool {
+        true
+    }
+    fn body_filter(
+        &self,
+        ctx: &mut ando_plugin::plugin::PluginContext,
+    ) -> ando_plugin::plugin::PluginResult {
+        if let Some(body) = ctx.response_body.as_mut() {
+            body.make_ascii_uppercase();
+            body.extend_from_slice(b"!");
+        }
+        ando_plugin::plugin::PluginResult::Continue
+    }
+}
+
+/// Run one request through a proxy whose route uses `uppercase-body`,
+/// against an upstream that writes its body in two pieces. Returns the raw
+/// client-side response.
+fn roundtrip_with_body_filter(max_filtered_body: usize) -> String {
+    let echo_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let echo_addr = echo_listener.local_addr().unwrap();
+    drop(echo_listener);
+
+    make_rt().block_on(async move {
+        let echo =
+            monoio::net::TcpListener::bind(format!("127.0.0.1:{}", echo_addr.port()).as_str())
+                .unwrap();
+        monoio::spawn(async move {
+            if let Ok((mut stream, _)) = echo.accept().await {
+                let (_n, _buf) = stream.read(vec![0u8; 4096]).await;
+                let head = b"HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: 11\r\nconnection: close\r\n\r\nhello";
+                let (_, _) = stream.write_all(head.to_vec()).await;
+                let (_, _) = stream.write_all(b"-ando!".to_vec()).await;
+            }
+        });
+
+        let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+            "id": "r-filter",
+            "uri": "/filter",
+            "plugins": { "uppercase-body": {} },
+            "upstream": {
+                "nodes": { format!("127.0.0.1:{}", echo_addr.port()): 1 },
+                "type": "roundrobin"
+            }
+        }))
+        .unwrap();
+        let router = Arc::new(Router::build(vec![route], 1).unwrap());
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(UppercaseBody));
+        let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new())
+            .with_max_filtered_body(max_filtered_body);
+
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(0)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (_, _) = client
+            .write_all(
+                b"GET /filter HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n".to_vec(),
+            )
+            .await;
+
+        let mut out = Vec::new();
+        loop {
+            let (n, buf) = client.read(vec![0u8; 1024]).await;
+            match n {
+                Ok(0) | Err(_) => break,
+                Ok(n) => out.extend_from_slice(&buf[..n]),
+            }
+        }
+        String::from_utf8(out).unwrap()
+    })
+}
+
+#[test]
+fn body_filter_plugin_rewrites_response_and_content_length() {
+    let resp = roundtrip_with_body_filter(1024);
+    assert!(resp.starts_with("HTTP/1.1 200"), "got: {resp:?}");
+    assert!(resp.contains("content-length: 12\r\n"), "got: {resp:?}");
+    assert!(
+        resp.contains("content-type: text/plain\r\n"),
+        "got: {resp:?}"
+    );
+    assert!(resp.ends_with("\r\n\r\nHELLO-ANDO!!"), "got: {resp:?}");
+}
+
+#[test]
+fn body_filter_skips_bodies_over_the_cap() {
+    let resp = roundtrip_with_body_filter(4);
+    assert!(resp.contains("content-length: 11\r\n"), "got: {resp:?}");
+    assert!(resp.ends_with("\r\n\r\nhello-ando!"), "got: {resp:?}");
+}
+
+// ── Test 12: plugin response headers are spliced into streamed replies ────
+
+#[test]
+fn rate_limit_headers_added_to_proxied_response() {
+    let echo_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let echo_addr = echo_listener.local_addr().unwrap();
+    drop(echo_listener);
+
+    make_rt().block_on(async move {
+        let echo =
+            monoio::net::TcpListener::bind(format!("127.0.0.1:{}", echo_addr.port()).as_str())
+                .unwrap();
+        monoio::spawn(async move {
+            if let Ok((mut stream, _)) = echo.accept().await {
+                let (_n, _buf) = stream.read(vec![0u8; 4096]).await;
+                let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+                let (_, _) = stream.write_all(resp.to_vec()).await;
+            }
+        });
+
+        let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+            "id": "r-limited",
+            "uri": "/limited",
+            "plugins": { "rate-limiting": { "count": 5, "time_window": 60 } },
+            "upstream": {
+                "nodes": { format!("127.0.0.1:{}", echo_addr.port()): 1 },
+                "type": "roundrobin"
+            }
+        }))
+        .unwrap();
+        let router = Arc::new(Router::build(vec![route], 1).unwrap());
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(
+            ando_plugins::traffic::rate_limiting::RateLimitingPlugin,
+        ));
+        let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new());
+
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(0)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (_, _) = client
+            .write_all(
+                b"GET /limited HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n".to_vec(),
+            )
+            .await;
+
+        let mut out = Vec::new();
+        loop {
+            let (n, buf) = client.read(vec![0u8; 1024]).await;
+            match n {
+                Ok(0) | Err(_) => break,
+                Ok(n) => out.extend_from_slice(&buf[..n]),
+            }
+        }
+        let resp = String::from_utf8(out).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "got: {resp:?}");
+        assert!(resp.contains("x-ratelimit-limit: 5\r\n"), "got: {resp:?}");
+        assert!(
+            resp.contains("x-ratelimit-remaining: 4\r\n"),
+            "got: {resp:?}"
+        );
+        assert!(resp.ends_with("\r\n\r\nok"), "got: {resp:?}");
+    });
+}
+
+// ── Test 13: async access hooks are awaited before proxying ───────────────
+
+struct AsyncGate {
+    allow: bool,
+    /// Awaited before deciding, to hold the hook open like real I/O.
+    sleep_ms: u64,
+}
+
+impl ando_plugin::plugin::Plugin for AsyncGate {
+    fn name(&self) -> &str {
+        "async-gate"
+    }
+    fn phases(&self) -> &[ando_plugin::plugin::Phase] {
+        &[ando_plugin::plugin::Phase::Access]
+    }
+    fn configure(
+        &self,
+        config: &serde_json::Value,
+    ) -> anyhow::Result<Box<dyn ando_plugin::plugin::PluginInstance>> {
+        Ok(Box::new(AsyncGate {
+            allow: config["allow"].as_bool().unwrap_or(false),
+            sleep_ms: config["sleep_ms"].as_u64().unwrap_or(0),
+        }))
+    }
+}
+
+impl ando_plugin::plugin::PluginInstance for AsyncGate {
+    fn name(&self) -> &str {
+        "async-gate"
+    }
+    fn has_async_access(&self) -> bool {
+        true
+    }
+    fn access_async(
+        &self,
+        _ctx: &mut ando_plugin::plugin::PluginContext,
+    ) -> Option<ando_plugin::plugin::AccessFuture> {
+        use ando_plugin::plugin::{AsyncAccess, PluginResult};
+        let outcome = if self.allow {
+            AsyncAccess {
+                result: PluginResult::Continue,
+                response_headers: vec![("x-gate".into(), "open".into())],
+                upstream_headers: vec![("x-gate-user".into(), "alice".into())],
+            }
+        } else {
+            AsyncAccess {
+                result: PluginResult::Response {
+                    status: 429,
+                    headers: vec![],
+                    body: Some(b"denied".to_vec()),
+                },
+                response_headers: vec![],
+                upstream_headers: Vec::new(),
+            }
+        };
+        if self.sleep_ms == 0 {
+            return Some(Box::pin(std::future::ready(outcome)));
+        }
+        let sleep =
+            ando_plugins::background::sleep(std::time::Duration::from_millis(self.sleep_ms));
+        Some(Box::pin(async move {
+            sleep.await;
+            outcome
+        }))
+    }
+}
+
+fn roundtrip_with_async_gate(allow: bool) -> String {
+    let echo_listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let echo_addr = echo_listener.local_addr().unwrap();
+    drop(echo_listener);
+
+    make_rt().block_on(async move {
+        let echo =
+            monoio::net::TcpListener::bind(format!("127.0.0.1:{}", echo_addr.port()).as_str())
+                .unwrap();
+        monoio::spawn(async move {
+            if let Ok((mut stream, _)) = echo.accept().await {
+                let (_n, _buf) = stream.read(vec![0u8; 4096]).await;
+                let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok";
+                let (_, _) = stream.write_all(resp.to_vec()).await;
+            }
+        });
+
+        let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+            "id": "r-gate",
+            "uri": "/gate",
+            "plugins": { "async-gate": { "allow": allow } },
+            "upstream": {
+                "nodes": { format!("127.0.0.1:{}", echo_addr.port()): 1 },
+                "type": "roundrobin"
+            }
+        }))
+        .unwrap();
+        let router = Arc::new(Router::build(vec![route], 1).unwrap());
+        let mut registry = PluginRegistry::new();
+        registry.register(Arc::new(AsyncGate { allow, sleep_ms: 0 }));
+        let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new());
+
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(0)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (_, _) = client
+            .write_all(
+                b"GET /gate HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n".to_vec(),
+            )
+            .await;
+
+        let mut out = Vec::new();
+        loop {
+            let (n, buf) = client.read(vec![0u8; 1024]).await;
+            match n {
+                Ok(0) | Err(_) => break,
+                Ok(n) => out.extend_from_slice(&buf[..n]),
+            }
+        }
+        String::from_utf8(out).unwrap()
+    })
+}
+
+#[test]
+fn async_access_hook_adds_headers_when_allowed() {
+    let resp = roundtrip_with_async_gate(true);
+    assert!(resp.starts_with("HTTP/1.1 200"), "got: {resp:?}");
+    assert!(resp.contains("x-gate: open\r\n"), "got: {resp:?}");
+    assert!(resp.ends_with("\r\n\r\nok"), "got: {resp:?}");
+}
+
+#[test]
+fn async_access_hook_sets_upstream_headers() {
+    let upstream = spawn_head_echo();
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-gate",
+        "uri": "/gate",
+        "plugins": { "async-gate": { "allow": true } },
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let router = Arc::new(Router::build(vec![route], 1).unwrap());
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(AsyncGate {
+        allow: true,
+        sleep_ms: 0,
+    }));
+    let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new());
+
+    let resp = raw_through_worker(
+        worker,
+        "GET /gate HTTP/1.1\r\nhost: localhost\r\nX-Gate-User: mallory\r\nconnection: close\r\n\r\n",
+    );
+    assert!(resp.contains("x-gate-user: alice\r\n"), "got: {resp:?}");
+    assert!(!resp.contains("mallory"), "got: {resp:?}");
+}
+
+#[test]
+fn async_access_hook_rejection_skips_upstream() {
+    let resp = roundtrip_with_async_gate(false);
+    assert!(resp.starts_with("HTTP/1.1 429"), "got: {resp:?}");
+    assert!(resp.ends_with("denied"), "got: {resp:?}");
+}
+
+#[test]
+fn sleeping_async_access_hook_does_not_block_the_worker() {
+    let upstream = spawn_head_echo();
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(AsyncGate {
+        allow: true,
+        sleep_ms: 0,
+    }));
+    let routes: Vec<ando_core::route::Route> = [
+        serde_json::json!({
+            "id": "r-slow",
+            "uri": "/slow",
+            "plugins": { "async-gate": { "allow": true, "sleep_ms": 10 } },
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+        serde_json::json!({
+            "id": "r-fast",
+            "uri": "/fast",
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+    ]
+    .into_iter()
+    .map(|v| serde_json::from_value(v).unwrap())
+    .collect();
+    let router = Arc::new(Router::build(routes, 1).unwrap());
+    let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new());
+
+    // A worker runtime: the hook is woken from the plugins' background
+    // tokio runtime, on another thread.
+    let mut rt = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+        .enable_all()
+        .build()
+        .unwrap();
+    let (order, slow_elapsed) = rt.block_on(async {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                monoio::spawn(handle_connection(
+                    stream,
+                    peer,
+                    Rc::clone(&proxy),
+                    Rc::clone(&pool),
+                ));
+            }
+        });
+
+        let order = Rc::new(RefCell::new(Vec::new()));
+        let request = |path: &'static str| {
+            let order = Rc::clone(&order);
+            async move {
+                let started = std::time::Instant::now();
+                let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+                let req =
+                    format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n");
+                let (res, _) = client.write_all(req.into_bytes()).await;
+                res.unwrap();
+                let resp = read_until(&mut client, |_| false).await;
+                assert!(resp.starts_with("HTTP/1.1 200"), "{path}: {resp:?}");
+                order.borrow_mut().push(path);
+                started.elapsed()
+            }
+        };
+        let slow = monoio::spawn(request("/slow"));
+        // Let the slow request reach its hook before the fast one starts.
+        monoio::time::sleep(std::time::Duration::from_millis(2)).await;
+        request("/fast").await;
+        let slow_elapsed = slow.await;
+        (order.take(), slow_elapsed)
+    });
+
+    // The fast request was served while the hook of the slow one slept.
+    assert_eq!(order, vec!["/fast", "/slow"]);
+    assert!(slow_elapsed >= std::time::Duration::from_millis(10));
+}
+
+// ── Connection pool liveness ───────────────────────────────────────────────
+
+/// Upstream on a plain thread that answers one keepalive request per
+/// connection and then closes it, so the proxy pools a socket the server
+/// has already shut.
+fn spawn_closing_upstream() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok");
+        }
+    });
+    addr
+}
+
+#[test]
+fn pooled_connection_closed_by_upstream_is_replaced() {
+    let upstream = spawn_closing_upstream();
+
+    make_rt().block_on(async {
+        let route = serde_json::json!({
+            "id": "r-pool",
+            "uri": "/pool",
+            "status": 1,
+            "upstream": {
+                "nodes": { upstream.to_string(): 1 },
+                "type": "roundrobin"
+            }
+        });
+
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+
+        let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        let pool_handle = Rc::clone(&pool);
+
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        for request in [
+            &b"GET /pool HTTP/1.1\r\nhost: localhost\r\n\r\n"[..],
+            b"GET /pool HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+        ] {
+            let (res, _) = client.write_all(request.to_vec()).await;
+            res.unwrap();
+            let (n, buf) = client.read(vec![0u8; 1024]).await;
+            let n = n.unwrap_or(0);
+            assert_eq!(status_line(&buf[..n]), "HTTP/1.1 200 OK");
+            // Let the upstream's FIN reach the pooled socket.
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+
+        let stats = pool_handle.borrow_mut().take_stats();
+        assert_eq!(stats.hits, 0);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.evictions, 1);
+    });
+}
+
+#[test]
+fn sweep_evicts_expired_and_closed_connections() {
+    let upstream = spawn_closing_upstream();
+    let addr = upstream.to_string();
+
+    make_rt().block_on(async {
+        let mut pool = ConnPool::new(4).with_idle_timeout(std::time::Duration::from_secs(60));
+        let mut open = monoio::net::TcpStream::connect(addr.as_str())
+            .await
+            .unwrap();
+        let (res, _) = open.write_all(b"GET / HTTP/1.1\r\n\r\n".to_vec()).await;
+        res.unwrap();
+        let (n, _) = open.read(vec![0u8; 256]).await;
+        assert!(n.unwrap() > 0);
+        pool.put(addr.clone(), open.into());
+        pool.put(
+            addr.clone(),
+            monoio::net::TcpStream::connect(addr.as_str())
+                .await
+                .unwrap()
+                .into(),
+        );
+        std::thread::sleep(std::time::Duration::from_millis(50));
+
+        // The first connection was closed by the upstream; the second is
+        // still waiting for its request.
+        assert_eq!(pool.sweep(), 1);
+        assert_eq!(pool.idle_count(&addr), 1);
+
+        let mut pool = pool.with_idle_timeout(std::time::Duration::ZERO);
+        assert_eq!(pool.sweep(), 1);
+        assert_eq!(pool.idle_count(&addr), 0);
+        assert_eq!(pool.take_stats().evictions, 2);
+    });
+}
+
+// ── Upstream timeouts ──────────────────────────────────────────────────────
+
+/// Upstream that waits `delay` before answering each request.
+fn spawn_slow_upstream(delay: std::time::Duration) -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                std::thread::sleep(delay);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                );
+            });
+        }
+    });
+    addr
+}
+
+#[test]
+fn route_and_upstream_read_timeouts_answer_504() {
+    let upstream = spawn_slow_upstream(std::time::Duration::from_millis(600)).to_string();
+    let worker = make_worker(vec![
+        serde_json::json!({
+            "id": "r-route-timeout", "uri": "/route-timeout",
+            "timeout": { "read": 0.2 },
+            "upstream": { "nodes": { &upstream: 1 }, "type": "roundrobin" }
+        }),
+        serde_json::json!({
+            "id": "r-upstream-timeout", "uri": "/upstream-timeout",
+            "upstream": {
+                "nodes": { &upstream: 1 }, "type": "roundrobin",
+                "timeout": { "connect": 1, "read": 0.2 }
+            }
+        }),
+        serde_json::json!({
+            "id": "r-default", "uri": "/default",
+            "upstream": { "nodes": { &upstream: 1 }, "type": "roundrobin" }
+        }),
+    ]);
+
+    make_rt().block_on(async {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                monoio::spawn(handle_connection(
+                    stream,
+                    peer,
+                    Rc::clone(&proxy),
+                    Rc::clone(&pool),
+                ));
+            }
+        });
+
+        for (path, expected) in [
+            ("/route-timeout", "HTTP/1.1 504 Gateway Timeout"),
+            ("/upstream-timeout", "HTTP/1.1 504 Gateway Timeout"),
+            ("/default", "HTTP/1.1 200 OK"),
+        ] {
+            let started = std::time::Instant::now();
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req =
+                format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n");
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+            let resp = read_until(&mut client, |_| false).await;
+            assert_eq!(resp.lines().next(), Some(expected), "{path}: {resp}");
+            if expected.contains("504") {
+                assert!(started.elapsed() < std::time::Duration::from_millis(500));
+            }
+        }
+    });
+}
+
+// ── Upstream retries ───────────────────────────────────────────────────────
+
+/// Upstream answering every request with `status` after `delay`.
+fn spawn_status_upstream(status: &'static str, delay: std::time::Duration) -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                std::thread::sleep(delay);
+                let resp = format!(
+                    "HTTP/1.1 {status}\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{status}",
+                    status.len()
+                );
+                let _ = stream.write_all(resp.as_bytes());
+            });
+        }
+    });
+    addr
+}
+
+fn dead_addr() -> std::net::SocketAddr {
+    let tmp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    tmp.local_addr().unwrap()
+}
+
+/// Send one request per path through a fresh proxy for `routes` and
+/// return the responses, plus the worker's metrics.
+fn through_proxy(
+    routes: Vec<serde_json::Value>,
+    paths: &[&str],
+) -> (
+    Vec<String>,
+    Arc<ando_observability::metrics::MetricsCollector>,
+) {
+    let metrics = Arc::new(ando_observability::metrics::MetricsCollector::new(true).unwrap());
+    let worker = make_worker(routes).with_metrics(Arc::clone(&metrics));
+    (serve_requests(worker, paths), metrics)
+}
+
+/// Send one GET per path, each on its own connection, through `worker`
+/// and return the raw responses. Buffered metrics are flushed at the end.
+fn serve_requests(worker: ProxyWorker, paths: &[&str]) -> Vec<String> {
+    let paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        let (accept_proxy, accept_pool) = (Rc::clone(&proxy), Rc::clone(&pool));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                monoio::spawn(handle_connection(
+                    stream,
+                    peer,
+                    Rc::clone(&accept_proxy),
+                    Rc::clone(&accept_pool),
+                ));
+            }
+        });
+        let mut responses = Vec::new();
+        for path in paths {
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req =
+                format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n");
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+            responses.push(read_until(&mut client, |_| false).await);
+        }
+        proxy.borrow_mut().flush_metrics(&pool.borrow());
+        responses
+    })
+}
+
+/// Answers every lookup with whatever the test last stored.
+struct StubResolver(Arc<std::sync::Mutex<Vec<std::net::SocketAddr>>>);
+
+impl ando_proxy::dns::Resolve for StubResolver {
+    fn resolve(&self, _node: &str) -> std::io::Result<ando_proxy::dns::Resolution> {
+        Ok(ando_proxy::dns::Resolution {
+            addrs: self.0.lock().unwrap().clone(),
+            ttl: Some(std::time::Duration::from_secs(1)),
+        })
+    }
+}
+
+#[test]
+fn requests_follow_a_hostname_to_its_new_address() {
+    let before = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let after = spawn_status_upstream("201 Created", std::time::Duration::ZERO);
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-dns", "uri": "/dns",
+        "upstream": { "nodes": { "backend.internal:80": 1 } }
+    }))
+    .unwrap();
+    let cache = ConfigCache::new();
+    cache.routes.insert(route.id.clone(), route.clone());
+    let answer = Arc::new(std::sync::Mutex::new(vec![before]));
+    let mut refresher = ando_proxy::dns::DnsRefresher::new(
+        cache.clone(),
+        StubResolver(Arc::clone(&answer)),
+        std::time::Duration::from_secs(30),
+    );
+    let router = Arc::new(Router::build(vec![route], 1).unwrap());
+    let worker = ProxyWorker::new(router, Arc::new(PluginRegistry::new()), cache);
+
+    let start = std::time::Instant::now();
+    refresher.run_once(start);
+    let responses = make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                // As the worker accept loop does.
+                proxy.borrow_mut().maybe_update_dns();
+                monoio::spawn(handle_connection(
+                    stream,
+                    peer,
+                    Rc::clone(&proxy),
+                    Rc::clone(&pool),
+                ));
+            }
+        });
+        let mut responses = Vec::new();
+        for round in 0..2 {
+            if round == 1 {
+                *answer.lock().unwrap() = vec![after];
+                refresher.run_once(start + std::time::Duration::from_secs(1));
+            }
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req = b"GET /dns HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n";
+            let (res, _) = client.write_all(req.to_vec()).await;
+            res.unwrap();
+            responses.push(read_until(&mut client, |_| false).await);
+        }
+        responses
+    });
+    assert!(
+        responses[0].starts_with("HTTP/1.1 200 OK"),
+        "{}",
+        responses[0]
+    );
+    assert!(
+        responses[1].starts_with("HTTP/1.1 201 Created"),
+        "{}",
+        responses[1]
+    );
+}
+
+fn retries(metrics: &ando_observability::metrics::MetricsCollector, outcome: &str) -> u64 {
+    metrics
+        .upstream_retries
+        .as_ref()
+        .unwrap()
+        .with_label_values(&["r-retry", outcome])
+        .get()
+}
+
+#[test]
+fn connect_failure_is_retried_on_another_node() {
+    let alive = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    // The heavier dead node is picked first.
+    let (responses, metrics) = through_proxy(
+        vec![serde_json::json!({
+            "id": "r-retry", "uri": "/retry",
+            "upstream": {
+                "nodes": { dead_addr().to_string(): 10, alive.to_string(): 1 },
+                "type": "roundrobin"
+            }
+        })],
+        &["/retry", "/retry"],
+    );
+    for resp in &responses {
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    }
+    assert_eq!(retries(&metrics, "retried"), 2);
+    assert_eq!(retries(&metrics, "exhausted"), 0);
+}
+
+#[test]
+fn exhausted_retries_answer_502() {
+    let (responses, metrics) = through_proxy(
+        vec![serde_json::json!({
+            "id": "r-retry", "uri": "/retry",
+            "upstream": {
+                "nodes": { dead_addr().to_string(): 1, dead_addr().to_string(): 1 },
+                "type": "roundrobin", "retries": 3
+            }
+        })],
+        &["/retry"],
+    );
+    assert!(responses[0].starts_with("HTTP/1.1 502"), "{}", responses[0]);
+    assert_eq!(retries(&metrics, "retried"), 1);
+    assert_eq!(retries(&metrics, "exhausted"), 1);
+}
+
+/// Answers every request with a head promising more body than it sends,
+/// then closes.
+fn spawn_truncating_upstream() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 100\r\n\r\nshort");
+        }
+    });
+    addr
+}
+
+#[test]
+fn metrics_and_access_log_record_the_status_sent_and_upstream_errors() {
+    use ando_core::config::AccessLogConfig;
+    use ando_observability::access_log::AccessLogger;
+
+    let dir = std::env::temp_dir().join(format!("ando-upstream-errors-it-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("access.log");
+    let logger = Arc::new(
+        AccessLogger::from_config(&AccessLogConfig {
+            enabled: true,
+            path: path.to_string_lossy().into_owned(),
+            format: "$route_id $status $upstream_error".into(),
+            flush_interval_ms: 60_000,
+            ..AccessLogConfig::default()
+        })
+        .unwrap()
+        .unwrap(),
+    );
+    let route = |id: &str, node: std::net::SocketAddr| {
+        serde_json::json!({
+            "id": id, "uri": format!("/{id}"),
+            "upstream": { "nodes": { node.to_string(): 1 }, "type": "roundrobin" }
+        })
+    };
+    let failing = spawn_status_upstream("500 Internal Server Error", std::time::Duration::ZERO);
+    let metrics = Arc::new(ando_observability::metrics::MetricsCollector::new(true).unwrap());
+    let worker = make_worker(vec![
+        route("failing", failing),
+        route("refused", dead_addr()),
+        route("cut", spawn_truncating_upstream()),
+    ])
+    .with_metrics(Arc::clone(&metrics))
+    .with_access_log(Arc::clone(&logger));
+
+    let responses = serve_requests(worker, &["/failing", "/refused", "/cut"]);
+    assert!(responses[0].starts_with("HTTP/1.1 500"), "{}", responses[0]);
+    assert!(responses[1].starts_with("HTTP/1.1 502"), "{}", responses[1]);
+    assert!(responses[2].starts_with("HTTP/1.1 200"), "{}", responses[2]);
+
+    let requests = metrics.http_requests_total.as_ref().unwrap();
+    let errors = metrics.upstream_errors.as_ref().unwrap();
+    for (route, status) in [("failing", "500"), ("refused", "502"), ("cut", "200")] {
+        let sent = requests.with_label_values(&[route, "GET", status]).get();
+        assert_eq!(sent, 1, "{route} {status}");
+    }
+    assert_eq!(
+        requests.with_label_values(&["failing", "GET", "200"]).get(),
+        0
+    );
+    assert_eq!(errors.with_label_values(&["refused", "connect"]).get(), 1);
+    assert_eq!(errors.with_label_values(&["cut", "truncated"]).get(), 1);
+    // An upstream answering 500 did not fail.
+    for kind in [
+        "connect",
+        "timeout",
+        "exchange",
+        "invalid_response",
+        "truncated",
+    ] {
+        assert_eq!(errors.with_label_values(&["failing", kind]).get(), 0);
+    }
+
+    logger.flush();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        written,
+        "failing 500 -\nrefused 502 connect\ncut 200 truncated\n"
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn upstream_failure_answers_with_the_configured_error_page() {
+    let pages: std::collections::HashMap<String, ando_core::config::ErrorPageSet> =
+        serde_json::from_value(serde_json::json!({
+            "502": {"body_template": r#"{"error":"bad gateway","route":"{{route_id}}"}"#}
+        }))
+        .unwrap();
+    let pages = ando_core::error_page::ErrorPages::compile(&pages).unwrap();
+    let worker = make_worker(vec![serde_json::json!({
+        "id": "r-down", "uri": "/down",
+        "upstream": { "nodes": { dead_addr().to_string(): 1 }, "type": "roundrobin" }
+    })])
+    .with_error_pages(Arc::new(pages));
+    let responses = serve_requests(worker, &["/down"]);
+    let resp = &responses[0];
+    assert!(resp.starts_with("HTTP/1.1 502 Bad Gateway\r\n"), "{resp}");
+    assert!(
+        resp.contains("content-type: application/json\r\n"),
+        "{resp}"
+    );
+    assert!(
+        resp.ends_with("\r\n\r\n{\"error\":\"bad gateway\",\"route\":\"r-down\"}"),
+        "{resp}"
+    );
+}
+
+#[test]
+fn status_retry_stops_when_the_time_budget_is_spent() {
+    let failing = spawn_status_upstream("502 Bad Gateway", std::time::Duration::from_millis(200));
+    let alive = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let route = |id: &str, uri: &str, retry_timeout: f64| {
+        serde_json::json!({
+            "id": id, "uri": uri,
+            "upstream": {
+                "nodes": { failing.to_string(): 10, alive.to_string(): 1 },
+                "type": "roundrobin",
+                "retry_on": ["http_502"], "retry_timeout": retry_timeout
+            }
+        })
+    };
+    let (responses, metrics) = through_proxy(
+        vec![
+            route("r-retry", "/budget", 0.1),
+            route("r-roomy", "/roomy", 5.0),
+        ],
+        &["/budget", "/roomy"],
+    );
+    assert!(
+        responses[0].starts_with("HTTP/1.1 502 Bad Gateway"),
+        "{}",
+        responses[0]
+    );
+    assert_eq!(retries(&metrics, "exhausted"), 1);
+    assert!(
+        responses[1].starts_with("HTTP/1.1 200 OK"),
+        "{}",
+        responses[1]
+    );
+}
+
+// ── Response cache ─────────────────────────────────────────────────────────
+
+#[test]
+fn proxy_cache_serves_repeat_requests_without_the_upstream() {
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let served = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::clone(&served);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let n = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            let body = format!("catalog v{n}");
+            let resp = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            let _ = stream.write_all(resp.as_bytes());
+        }
+    });
+
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-cache", "uri": "/catalog/*",
+        "plugins": { "proxy-cache": { "ttl_secs": 60 } },
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(
+        ando_plugins::traffic::proxy_cache::ProxyCachePlugin::new(),
+    ));
+    let metrics = Arc::new(ando_observability::metrics::MetricsCollector::new(true).unwrap());
+    let worker = ProxyWorker::new(
+        Arc::new(Router::build(vec![route], 1).unwrap()),
+        Arc::new(registry),
+        ConfigCache::new(),
+    )
+    .with_metrics(Arc::clone(&metrics));
+
+    let responses = serve_requests(worker, &["/catalog/1", "/catalog/1", "/catalog/2"]);
+    let lower: Vec<String> = responses.iter().map(|r| r.to_ascii_lowercase()).collect();
+    assert!(
+        lower[0].contains("x-cache-status: miss"),
+        "{}",
+        responses[0]
+    );
+    assert!(responses[0].ends_with("catalog v1"), "{}", responses[0]);
+    assert!(lower[1].contains("x-cache-status: hit"), "{}", responses[1]);
+    assert!(
+        lower[1].contains("content-type: text/plain"),
+        "{}",
+        responses[1]
+    );
+    assert!(responses[1].ends_with("catalog v1"), "{}", responses[1]);
+    assert!(
+        lower[2].contains("x-cache-status: miss"),
+        "{}",
+        responses[2]
+    );
+    assert_eq!(served.load(Ordering::SeqCst), 2);
+
+    let lookups = metrics.cache_requests.as_ref().unwrap();
+    assert_eq!(lookups.with_label_values(&["r-cache", "HIT"]).get(), 1);
+    assert_eq!(lookups.with_label_values(&["r-cache", "MISS"]).get(), 2);
+}
+
+// ── Traffic mirroring ──────────────────────────────────────────────────────
+
+fn mirror_worker(upstream: std::net::SocketAddr, mirror: std::net::SocketAddr) -> ProxyWorker {
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-mirror", "uri": "/mirror/*",
+        "plugins": { "proxy-mirror": { "host": format!("http://{mirror}") } },
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(
+        ando_plugins::traffic::proxy_mirror::ProxyMirrorPlugin::new(),
+    ));
+    ProxyWorker::new(
+        Arc::new(Router::build(vec![route], 1).unwrap()),
+        Arc::new(registry),
+        ConfigCache::new(),
+    )
+}
+
+#[test]
+fn proxy_mirror_copies_requests_to_the_mirror() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let mirror = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let n = stream.read(&mut buf).unwrap_or(0);
+            let _ = tx.send(String::from_utf8_lossy(&buf[..n]).into_owned());
+            let _ = stream.write_all(b"HTTP/1.1 204 No Content\r\n\r\n");
+        }
+    });
+    let upstream = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let metrics = Arc::new(ando_observability::metrics::MetricsCollector::new(true).unwrap());
+    let worker = mirror_worker(upstream, mirror).with_metrics(Arc::clone(&metrics));
+
+    let responses = serve_requests(worker, &["/mirror/a?x=1"]);
+    assert!(
+        responses[0].starts_with("HTTP/1.1 200 OK"),
+        "{}",
+        responses[0]
+    );
+    let copy = rx.recv_timeout(std::time::Duration::from_secs(5)).unwrap();
+    assert!(copy.starts_with("GET /mirror/a?x=1 HTTP/1.1\r\n"), "{copy}");
+    let queued = metrics.mirror_requests.as_ref().unwrap();
+    assert_eq!(queued.with_label_values(&["r-mirror", "queued"]).get(), 1);
+}
+
+#[test]
+fn proxy_mirror_down_leaves_primary_untouched() {
+    let upstream = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let worker = mirror_worker(upstream, dead_addr());
+    let responses = serve_requests(worker, &["/mirror/a", "/mirror/b", "/mirror/c"]);
+    for resp in &responses {
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    }
+}
+
+// ── Chunked transfer-encoding ──────────────────────────────────────────────
+
+/// Start a proxy for a single client connection with one route to
+/// `upstream`. Returns the proxy address.
+fn spawn_proxy(route_id: &str, uri: &str, upstream: std::net::SocketAddr) -> std::net::SocketAddr {
+    let route = serde_json::json!({
+        "id": route_id,
+        "uri": uri,
+        "upstream": {
+            "nodes": { upstream.to_string(): 1 },
+            "type": "roundrobin"
+        }
+    });
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+    let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+    monoio::spawn(async move {
+        if let Ok((stream, peer)) = listener.accept().await {
+            let _ = handle_connection(stream, peer, proxy, pool).await;
+        }
+    });
+    proxy_addr
+}
+
+/// Read from `client` until `done` holds for the bytes received so far,
+/// or the connection closes.
+async fn read_until(client: &mut monoio::net::TcpStream, done: impl Fn(&[u8]) -> bool) -> String {
+    let mut out = Vec::new();
+    while !done(&out) {
+        let (n, buf) = client.read(vec![0u8; 1024]).await;
+        match n {
+            Ok(0) | Err(_) => break,
+            Ok(n) => out.extend_from_slice(&buf[..n]),
+        }
+    }
+    String::from_utf8(out).unwrap()
+}
+
+/// Serve one client connection on a proxy running on its own thread, so a
+/// blocking client can pace its writes. Returns the proxy address.
+fn spawn_proxy_thread(
+    route_id: &str,
+    uri: &str,
+    upstream: std::net::SocketAddr,
+) -> std::net::SocketAddr {
+    let (route_id, uri) = (route_id.to_string(), uri.to_string());
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        make_rt().block_on(async move {
+            let route = serde_json::json!({
+                "id": route_id,
+                "uri": uri,
+                "upstream": {
+                    "nodes": { upstream.to_string(): 1 },
+                    "type": "roundrobin"
+                }
+            });
+            let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            tx.send(listener.local_addr().unwrap()).unwrap();
+            let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+    });
+    rx.recv().unwrap()
+}
+
+/// Write `parts` to `addr` with a pause between each, so the proxy sees
+/// them as separate reads, then read the response until EOF.
+fn send_in_parts(addr: std::net::SocketAddr, parts: &[&[u8]]) -> String {
+    use std::io::{Read, Write};
+
+    let mut client = std::net::TcpStream::connect(addr).unwrap();
+    client.set_nodelay(true).unwrap();
+    for part in parts {
+        client.write_all(part).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(30));
+    }
+    let mut resp = String::new();
+    let _ = client.read_to_string(&mut resp);
+    resp
+}
+
+#[test]
+fn chunked_response_relayed_across_segments_and_connection_reused() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let accepted = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = Arc::clone(&accepted);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            // Serve keepalive requests until the proxy hangs up.
+            let mut buf = [0u8; 4096];
+            while matches!(stream.read(&mut buf), Ok(n) if n > 0) {
+                for part in [
+                    &b"HTTP/1.1 200 OK\r\ntransfer-encoding: chunked\r\n\r\n5\r\nhel"[..],
+                    b"lo\r\n6\r\n-",
+                    b"ando!\r\n0\r\nx-trailer: 1\r\n",
+                    b"\r\n",
+                ] {
+                    stream.write_all(part).unwrap();
+                    stream.flush().unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+            }
+        }
+    });
+
+    make_rt().block_on(async {
+        let proxy_addr = spawn_proxy("r-chunked", "/chunked", upstream);
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let (res, _) = client
+                .write_all(b"GET /chunked HTTP/1.1\r\nhost: localhost\r\n\r\n".to_vec())
+                .await;
+            res.unwrap();
+            let resp = read_until(&mut client, |b| b.ends_with(b"x-trailer: 1\r\n\r\n")).await;
+            assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "got: {resp:?}");
+            assert!(
+                resp.ends_with("\r\n\r\n5\r\nhello\r\n6\r\n-ando!\r\n0\r\nx-trailer: 1\r\n\r\n"),
+                "got: {resp:?}"
+            );
+        }
+    });
+    // The second request reused the pooled connection, so the proxy must
+    // have found the end of the first chunked body.
+    assert_eq!(accepted.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+fn chunked_request_body_decoded_and_forwarded_with_content_length() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !req.ends_with(b"hello, world") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "upstream request truncated: {req:?}");
+            req.extend_from_slice(&buf[..n]);
+        }
+        tx.send(String::from_utf8(req).unwrap()).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+            .unwrap();
+    });
+
+    let proxy_addr = spawn_proxy_thread("r-upload", "/upload", upstream);
+    let resp = send_in_parts(
+        proxy_addr,
+        &[
+            b"POST /upload HTTP/1.1\r\nhost: localhost\r\ntransfer-encoding: chunked\r\nconnection: close\r\n\r\n5\r\nhel",
+            b"lo\r\n7\r\n, world\r\n",
+            b"0\r\n\r\n",
+        ],
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "got: {resp:?}");
+
+    let forwarded = rx.recv().unwrap().to_ascii_lowercase();
+    assert!(
+        forwarded.contains("content-length: 12\r\n"),
+        "got: {forwarded:?}"
+    );
+    assert!(
+        !forwarded.contains("transfer-encoding"),
+        "got: {forwarded:?}"
+    );
+    assert!(
+        forwarded.ends_with("\r\n\r\nhello, world"),
+        "got: {forwarded:?}"
+    );
+}
+
+#[test]
+fn malformed_chunked_request_rejected_with_400() {
+    make_rt().block_on(async {
+        // Never contacted: the body is rejected before routing.
+        let proxy_addr = spawn_proxy("r-bad", "/upload", "127.0.0.1:9".parse().unwrap());
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (res, _) = client
+            .write_all(
+                b"POST /upload HTTP/1.1\r\nhost: localhost\r\ntransfer-encoding: chunked\r\n\r\nzz\r\n"
+                    .to_vec(),
+            )
+            .await;
+        res.unwrap();
+        let resp = read_until(&mut client, |_| false).await;
+        assert_eq!(status_line(resp.as_bytes()), "HTTP/1.1 400 Bad Request");
+    });
+}
+
+// ── Request bodies and heads spanning several reads ────────────────────────
+
+/// Upstream that reads one request with a content-length body and replies
+/// with the number of body bytes it received.
+fn spawn_counting_upstream() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = Vec::new();
+        let mut buf = [0u8; 65536];
+        let (head_end, len) = loop {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "upstream request head truncated");
+            req.extend_from_slice(&buf[..n]);
+            if let Some(pos) = req.windows(4).position(|w| w == b"\r\n\r\n") {
+                let head = String::from_utf8_lossy(&req[..pos]).to_ascii_lowercase();
+                let len: usize = head
+                    .lines()
+                    .find_map(|l| l.strip_prefix("content-length: "))
+                    .map_or(0, |v| v.parse().unwrap());
+                break (pos + 4, len);
+            }
+        };
+        while req.len() < head_end + len {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0, "upstream request body truncated");
+            req.extend_from_slice(&buf[..n]);
+        }
+        let body = &req[head_end..];
+        let intact = body.iter().enumerate().all(|(i, b)| *b == (i % 251) as u8);
+        let reply = format!("{}:{intact}", body.len());
+        let resp = format!(
+            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{reply}",
+            reply.len()
+        );
+        stream.write_all(resp.as_bytes()).unwrap();
+    });
+    addr
+}
+
+/// POST a `len`-byte body through the proxy in 16 KiB writes and return
+/// the response.
+fn post_large_body(len: usize) -> String {
+    let upstream = spawn_counting_upstream();
+    make_rt().block_on(async move {
+        let proxy_addr = spawn_proxy("r-big", "/big", upstream);
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let head = format!(
+            "POST /big HTTP/1.1\r\nhost: localhost\r\ncontent-length: {len}\r\nconnection: close\r\n\r\n"
+        );
+        let (res, _) = client.write_all(head.into_bytes()).await;
+        res.unwrap();
+        let body: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+        for part in body.chunks(16 * 1024) {
+            let (res, _) = client.write_all(part.to_vec()).await;
+            res.unwrap();
+        }
+        read_until(&mut client, |_| false).await
+    })
+}
+
+#[test]
+fn large_request_body_forwarded_in_full() {
+    let resp = post_large_body(64 * 1024);
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "got: {resp:?}");
+    assert!(resp.ends_with("\r\n\r\n65536:true"), "got: {resp:?}");
+}
+
+#[test]
+fn request_body_over_buffer_limit_streamed_to_upstream() {
+    let resp = post_large_body(3 * 1024 * 1024);
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "got: {resp:?}");
+    assert!(resp.ends_with("\r\n\r\n3145728:true"), "got: {resp:?}");
+}
+
+#[test]
+fn request_head_split_across_writes_is_reassembled() {
+    let upstream = spawn_counting_upstream();
+    let proxy_addr = spawn_proxy_thread("r-split", "/split", upstream);
+    let resp = send_in_parts(
+        proxy_addr,
+        &[
+            b"POST /split HTTP/1.1\r\nhost: loc",
+            b"alhost\r\ncontent-length: 3\r\nconnection: close\r\n\r\n\x00",
+            b"\x01\x02",
+        ],
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "got: {resp:?}");
+    assert!(resp.ends_with("\r\n\r\n3:true"), "got: {resp:?}");
+}
+
+#[test]
+fn oversized_request_head_rejected_with_431() {
+    make_rt().block_on(async {
+        let proxy_addr = spawn_proxy("r-431", "/", "127.0.0.1:9".parse().unwrap());
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let mut head = b"GET / HTTP/1.1\r\nx-big: ".to_vec();
+        head.resize(9000, b'a');
+        let (res, _) = client.write_all(head).await;
+        res.unwrap();
+        let resp = read_until(&mut client, |_| false).await;
+        assert_eq!(
+            status_line(resp.as_bytes()),
+            "HTTP/1.1 431 Request Header Fields Too Large"
+        );
+    });
+}
+
+// ── Request size limits ────────────────────────────────────────────────────
+
+/// Send `request` to a proxy serving `worker` and read the response until
+/// the proxy closes. The upstream is never reached in these tests.
+fn send_with_limits(worker: ProxyWorker, request: Vec<u8>) -> String {
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (res, _) = client.write_all(request).await;
+        res.unwrap();
+        read_until(&mut client, |_| false).await
+    })
+}
+
+fn limited_worker(limits: RequestLimits, route: serde_json::Value) -> ProxyWorker {
+    make_worker(vec![route]).with_request_limits(limits)
+}
+
+fn unreachable_route(extra: serde_json::Value) -> serde_json::Value {
+    let mut route = serde_json::json!({
+        "id": "r-limits",
+        "uri": "/up",
+        "upstream": { "nodes": { "127.0.0.1:9": 1 }, "type": "roundrobin" }
+    });
+    route
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    route
+}
+
+#[test]
+fn too_many_headers_rejected_with_431() {
+    let mut head = b"GET /up HTTP/1.1\r\n".to_vec();
+    for i in 0..65 {
+        head.extend_from_slice(format!("x-h{i}: v\r\n").as_bytes());
+    }
+    head.extend_from_slice(b"\r\n");
+    let resp = send_with_limits(
+        make_worker(vec![unreachable_route(serde_json::json!({}))]),
+        head,
+    );
+    assert_eq!(
+        status_line(resp.as_bytes()),
+        "HTTP/1.1 431 Request Header Fields Too Large"
+    );
+}
+
+#[test]
+fn configured_header_size_limit_answers_431() {
+    let limits = RequestLimits {
+        max_header_size: 512,
+        ..RequestLimits::default()
+    };
+    let mut head = b"GET /up HTTP/1.1\r\nx-big: ".to_vec();
+    head.resize(1000, b'a');
+    head.extend_from_slice(b"\r\n\r\n");
+    let resp = send_with_limits(
+        limited_worker(limits, unreachable_route(serde_json::json!({}))),
+        head,
+    );
+    assert_eq!(
+        status_line(resp.as_bytes()),
+        "HTTP/1.1 431 Request Header Fields Too Large"
+    );
+}
+
+#[test]
+fn declared_body_over_global_limit_answers_413_before_it_is_read() {
+    let limits = RequestLimits {
+        max_body_size: Some(1024),
+        ..RequestLimits::default()
+    };
+    // With Expect: 100-continue the client gets 413 instead of 100.
+    for expect in ["", "expect: 100-continue\r\n"] {
+        let head = format!("POST /up HTTP/1.1\r\nhost: a\r\n{expect}content-length: 4096\r\n\r\n");
+        let resp = send_with_limits(
+            limited_worker(limits, unreachable_route(serde_json::json!({}))),
+            head.into_bytes(),
+        );
+        assert!(
+            resp.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+            "got: {resp:?}"
+        );
+    }
+}
+
+#[test]
+fn chunked_body_over_global_limit_answers_413() {
+    let limits = RequestLimits {
+        max_body_size: Some(16),
+        ..RequestLimits::default()
+    };
+    let request = b"POST /up HTTP/1.1\r\nhost: a\r\ntransfer-encoding: chunked\r\n\r\n10\r\n0123456789abcdef\r\n1\r\nx\r\n0\r\n\r\n";
+    let resp = send_with_limits(
+        limited_worker(limits, unreachable_route(serde_json::json!({}))),
+        request.to_vec(),
+    );
+    assert!(
+        resp.starts_with("HTTP/1.1 413 Payload Too Large\r\n"),
+        "got: {resp:?}"
+    );
+}
+
+#[test]
+fn route_body_limit_answers_413() {
+    let route = unreachable_route(serde_json::json!({ "max_body_size": 4 }));
+    let request =
+        b"POST /up HTTP/1.1\r\nhost: a\r\ncontent-length: 5\r\nconnection: close\r\n\r\nhello";
+    let resp = send_with_limits(make_worker(vec![route]), request.to_vec());
+    assert!(resp.starts_with("HTTP/1.1 413 "), "got: {resp:?}");
+    assert!(resp.contains("request body too large"), "got: {resp:?}");
+}
+
+// ── Client timeouts ────────────────────────────────────────────────────────
+
+fn short_timeouts() -> ClientTimeouts {
+    ClientTimeouts {
+        header: std::time::Duration::from_millis(400),
+        read: std::time::Duration::from_millis(200),
+        idle: std::time::Duration::from_millis(200),
+        max_requests: None,
+    }
+}
+
+/// Serve one client connection with `worker` on the current runtime.
+/// Returns the proxy address and the worker, to inspect its state.
+fn spawn_worker(worker: ProxyWorker) -> (std::net::SocketAddr, Rc<RefCell<ProxyWorker>>) {
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy = Rc::new(RefCell::new(worker));
+    let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+    monoio::spawn({
+        let proxy = Rc::clone(&proxy);
+        async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        }
+    });
+    (proxy_addr, proxy)
+}
+
+/// Send `parts` with `pause` between them, then read until the proxy
+/// closes. Writes after the proxy gave up are ignored.
+async fn send_paced(
+    addr: std::net::SocketAddr,
+    parts: &[&[u8]],
+    pause: std::time::Duration,
+) -> String {
+    let mut client = monoio::net::TcpStream::connect(addr.to_string().as_str())
+        .await
+        .unwrap();
+    for part in parts {
+        let _ = client.write_all(part.to_vec()).await;
+        monoio::time::sleep(pause).await;
+    }
+    read_until(&mut client, |_| false).await
+}
+
+#[test]
+fn request_head_trickled_past_the_header_timeout_gets_408() {
+    make_rt().block_on(async {
+        let worker = make_worker(vec![]).with_client_timeouts(short_timeouts());
+        let (addr, _) = spawn_worker(worker);
+        // Every line arrives well within the read timeout, but the head as
+        // a whole takes longer than the header timeout.
+        let mut parts: Vec<&[u8]> = vec![b"GET /slow HTTP/1.1\r\n"];
+        parts.extend(std::iter::repeat_n(b"x-pad: y\r\n".as_slice(), 10));
+        let resp = send_paced(addr, &parts, std::time::Duration::from_millis(100)).await;
+        assert_eq!(status_line(resp.as_bytes()), "HTTP/1.1 408 Request Timeout");
+    });
+}
+
+#[test]
+fn stalled_head_or_body_gets_408() {
+    let requests: [&[u8]; 2] = [
+        b"GET /up HTTP/1.1\r\nhost: a",
+        b"POST /up HTTP/1.1\r\nhost: a\r\ncontent-length: 10\r\n\r\nabc",
+    ];
+    for request in requests {
+        make_rt().block_on(async {
+            let worker = make_worker(vec![unreachable_route(serde_json::json!({}))])
+                .with_client_timeouts(short_timeouts());
+            let (addr, _) = spawn_worker(worker);
+            let started = std::time::Instant::now();
+            let resp = send_paced(addr, &[request], std::time::Duration::ZERO).await;
+            assert_eq!(status_line(resp.as_bytes()), "HTTP/1.1 408 Request Timeout");
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        });
+    }
+}
+
+#[test]
+fn idle_keepalive_connection_closed_silently() {
+    make_rt().block_on(async {
+        let worker = make_worker(vec![]).with_client_timeouts(short_timeouts());
+        let (addr, proxy) = spawn_worker(worker);
+        let mut client = monoio::net::TcpStream::connect(addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (res, _) = client
+            .write_all(b"GET /x HTTP/1.1\r\nhost: a\r\n\r\n".to_vec())
+            .await;
+        res.unwrap();
+        let first = read_until(&mut client, |b| b.ends_with(b"}")).await;
+        assert!(first.starts_with("HTTP/1.1 404 "), "got: {first:?}");
+        assert_eq!(proxy.borrow().open_connections(), 1);
+
+        let idle_from = std::time::Instant::now();
+        let rest = read_until(&mut client, |_| false).await;
+        assert_eq!(rest, "", "idle close sends nothing");
+        assert!(idle_from.elapsed() >= std::time::Duration::from_millis(150));
+        assert_eq!(proxy.borrow().open_connections(), 0);
+    });
+}
+
+#[test]
+fn connection_closed_after_max_keepalive_requests() {
+    make_rt().block_on(async {
+        let timeouts = ClientTimeouts {
+            max_requests: Some(2),
+            ..short_timeouts()
+        };
+        let (addr, _) = spawn_worker(make_worker(vec![]).with_client_timeouts(timeouts));
+        let mut client = monoio::net::TcpStream::connect(addr.to_string().as_str())
+            .await
+            .unwrap();
+        for _ in 0..2 {
+            let (res, _) = client
+                .write_all(b"GET /x HTTP/1.1\r\nhost: a\r\n\r\n".to_vec())
+                .await;
+            res.unwrap();
+            let resp = read_until(&mut client, |b| b.ends_with(b"}")).await;
+            assert!(resp.starts_with("HTTP/1.1 404 "), "got: {resp:?}");
+        }
+        // Closed right away, without waiting out the idle timeout.
+        let started = std::time::Instant::now();
+        assert_eq!(read_until(&mut client, |_| false).await, "");
+        assert!(started.elapsed() < std::time::Duration::from_millis(150));
+    });
+}
+
+// ── WebSocket upgrade passthrough ──────────────────────────────────────────
+
+/// Minimal WebSocket echo server: completes the handshake (RFC 6455 sample
+/// key) and echoes one masked text frame back unmasked. Sends the
+/// handshake request it received on `tx`.
+fn spawn_ws_echo(tx: std::sync::mpsc::Sender<String>) -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !req.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0);
+            req.extend_from_slice(&buf[..n]);
+        }
+        tx.send(String::from_utf8(req).unwrap()).unwrap();
+        stream
+            .write_all(
+                b"HTTP/1.1 101 Switching Protocols\r\nupgrade: websocket\r\nconnection: Upgrade\r\nsec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n\r\n",
+            )
+            .unwrap();
+
+        // One masked client frame: FIN+text, MASK+len, 4-byte key, payload.
+        let mut head = [0u8; 6];
+        stream.read_exact(&mut head).unwrap();
+        assert_eq!(head[0], 0x81);
+        let len = (head[1] & 0x7f) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).unwrap();
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= head[2 + i % 4];
+        }
+        let mut frame = vec![0x81, len as u8];
+        frame.extend_from_slice(&payload);
+        stream.write_all(&frame).unwrap();
+    });
+    addr
+}
+
+fn websocket_route(upstream: std::net::SocketAddr, enabled: bool) -> serde_json::Value {
+    serde_json::json!({
+        "id": "r-ws",
+        "uri": "/ws",
+        "enable_websocket": enabled,
+        "upstream": {
+            "nodes": { upstream.to_string(): 1 },
+            "type": "roundrobin"
+        }
+    })
+}
+
+const WS_HANDSHAKE: &[u8] = b"GET /ws HTTP/1.1\r\nhost: localhost\r\nconnection: Upgrade\r\nupgrade: websocket\r\nsec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\nsec-websocket-version: 13\r\nsec-websocket-protocol: chat\r\n\r\n";
+
+#[test]
+fn websocket_handshake_and_frame_round_trip() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    let upstream = spawn_ws_echo(tx);
+
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(make_worker(vec![websocket_route(
+            upstream, true,
+        )])));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (res, _) = client.write_all(WS_HANDSHAKE.to_vec()).await;
+        res.unwrap();
+        let resp = read_until(&mut client, |b| b.ends_with(b"\r\n\r\n")).await;
+        assert_eq!(
+            status_line(resp.as_bytes()),
+            "HTTP/1.1 101 Switching Protocols"
+        );
+        assert!(resp.contains("sec-websocket-accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+
+        let key = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![0x81, 0x80 | 5];
+        frame.extend_from_slice(&key);
+        frame.extend(b"hello".iter().enumerate().map(|(i, b)| b ^ key[i % 4]));
+        let (res, _) = client.write_all(frame).await;
+        res.unwrap();
+        let (n, echoed) = client.read(vec![0u8; 64]).await;
+        assert_eq!(&echoed[..n.unwrap()], b"\x81\x05hello");
+    });
+
+    let handshake = rx.recv().unwrap().to_ascii_lowercase();
+    assert!(
+        handshake.contains("upgrade: websocket\r\n"),
+        "got: {handshake:?}"
+    );
+    assert!(
+        handshake.contains("connection: upgrade\r\n"),
+        "got: {handshake:?}"
+    );
+    assert!(
+        handshake.contains("sec-websocket-key: dghlihnhbxbszsbub25jzq==\r\n"),
+        "got: {handshake:?}"
+    );
+    assert!(
+        handshake.contains("sec-websocket-protocol: chat\r\n"),
+        "got: {handshake:?}"
+    );
+}
+
+#[test]
+fn websocket_upgrade_headers_stripped_when_route_disallows() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let upstream = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut req = Vec::new();
+        let mut buf = [0u8; 4096];
+        while !req.ends_with(b"\r\n\r\n") {
+            let n = stream.read(&mut buf).unwrap();
+            assert!(n > 0);
+            req.extend_from_slice(&buf[..n]);
+        }
+        tx.send(String::from_utf8(req).unwrap()).unwrap();
+        let _ = stream.write_all(b"HTTP/1.1 426 Upgrade Required\r\ncontent-length: 0\r\n\r\n");
+    });
+
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(make_worker(vec![websocket_route(
+            upstream, false,
+        )])));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+
+        let mut client = monoio::net::TcpStream::connect(proxy_addr.to_string().as_str())
+            .await
+            .unwrap();
+        let (res, _) = client.write_all(WS_HANDSHAKE.to_vec()).await;
+        res.unwrap();
+        let resp = read_until(&mut client, |b| b.ends_with(b"\r\n\r\n")).await;
+        assert_eq!(
+            status_line(resp.as_bytes()),
+            "HTTP/1.1 426 Upgrade Required"
+        );
+    });
+
+    let forwarded = rx.recv().unwrap().to_ascii_lowercase();
+    assert!(
+        !forwarded.contains("upgrade: websocket"),
+        "got: {forwarded:?}"
+    );
+    assert!(
+        forwarded.contains("connection: keep-alive\r\n"),
+        "got: {forwarded:?}"
+    );
+}
+
+// ── TLS termination ────────────────────────────────────────────────────────
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!(
+        "{}/tests/fixtures/tls/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+fn fixture_der(name: &str) -> Vec<u8> {
+    pem::parse(fixture(name)).unwrap().into_contents()
+}
+
+fn ssl_cert(id: &str, name: &str, snis: &[&str]) -> ando_core::ssl::SslCertificate {
+    ando_core::ssl::SslCertificate {
+        id: id.into(),
+        cert: fixture(&format!("{name}.crt")),
+        key: fixture(&format!("{name}.key")),
+        snis: snis.iter().map(|s| s.to_string()).collect(),
+        status: 1,
+        client_tls: None,
+    }
+}
+
+fn tls_certs() -> Arc<dashmap::DashMap<String, ando_core::ssl::SslCertificate>> {
+    let certs = Arc::new(dashmap::DashMap::new());
+    certs.insert("a".into(), ssl_cert("a", "a", &["a.example.com"]));
+    certs.insert("b".into(), ssl_cert("b", "b", &["b.example.com"]));
+    certs.insert("fallback".into(), ssl_cert("fallback", "default", &[]));
+    certs
+}
+
+fn served_cert(resolver: &ando_proxy::tls::CertResolver, sni: Option<&str>) -> Vec<u8> {
+    resolver.certified_key(sni).unwrap().cert[0].0.clone()
+}
+
+#[test]
+fn cert_resolver_prefers_exact_sni_then_default() {
+    let certs = tls_certs();
+    certs.insert("off".into(), {
+        let mut ssl = ssl_cert("off", "b", &["c.example.com"]);
+        ssl.status = 0;
+        ssl
+    });
+    let resolver = ando_proxy::tls::CertResolver::new(certs);
+    assert_eq!(
+        served_cert(&resolver, Some("a.example.com")),
+        fixture_der("a.crt")
+    );
+    assert_eq!(
+        served_cert(&resolver, Some("B.example.com")),
+        fixture_der("b.crt")
+    );
+    // Unknown, disabled and missing names all get the default certificate.
+    assert_eq!(
+        served_cert(&resolver, Some("c.example.com")),
+        fixture_der("default.crt")
+    );
+    assert_eq!(served_cert(&resolver, None), fixture_der("default.crt"));
+}
+
+#[test]
+fn cert_resolver_picks_up_cache_updates() {
+    let certs = tls_certs();
+    let resolver = ando_proxy::tls::CertResolver::new(Arc::clone(&certs));
+    assert_eq!(
+        served_cert(&resolver, Some("a.example.com")),
+        fixture_der("a.crt")
+    );
+
+    // Same id, new certificate — as when the watcher applies an etcd PUT.
+    certs.insert("a".into(), ssl_cert("a", "b", &["a.example.com"]));
+    assert_eq!(
+        served_cert(&resolver, Some("a.example.com")),
+        fixture_der("b.crt")
+    );
+
+    certs.remove("fallback");
+    certs.remove("a");
+    assert!(resolver.certified_key(Some("a.example.com")).is_none());
+}
+
+#[test]
+fn cert_resolver_decrypts_keys_stored_encrypted() {
+    use ando_core::ssl::MasterKey;
+
+    let certs = Arc::new(dashmap::DashMap::new());
+    let mut ssl = ssl_cert("a", "a", &["a.example.com"]);
+    ssl.key = MasterKey::new("s3cret").encrypt(&ssl.key);
+    certs.insert("a".to_string(), ssl);
+
+    let resolver = ando_proxy::tls::CertResolver::new(Arc::clone(&certs))
+        .with_master_key(Some(MasterKey::new("s3cret")));
+    assert_eq!(
+        served_cert(&resolver, Some("a.example.com")),
+        fixture_der("a.crt")
+    );
+    // Without the master key, or with another, the certificate is unusable.
+    let resolver = ando_proxy::tls::CertResolver::new(Arc::clone(&certs));
+    assert!(resolver.certified_key(Some("a.example.com")).is_none());
+    let resolver =
+        ando_proxy::tls::CertResolver::new(certs).with_master_key(Some(MasterKey::new("other")));
+    assert!(resolver.certified_key(Some("a.example.com")).is_none());
+}
+
+#[test]
+fn tls_listener_serves_certificate_for_each_sni() {
+    use ando_proxy::connection::handle_tls_connection;
+    use ando_proxy::tls::{CertResolver, TlsServer};
+
+    make_rt().block_on(async {
+        let upstream = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let upstream_addr = upstream.local_addr().unwrap();
+        monoio::spawn(async move {
+            while let Ok((mut stream, _)) = upstream.accept().await {
+                monoio::spawn(async move {
+                    let (_, _) = stream.read(vec![0u8; 4096]).await;
+                    let resp =
+                        b"HTTP/1.1 200 OK\r\ncontent-length: 6\r\nconnection: close\r\n\r\nsecure";
+                    let (_, _) = stream.write_all(resp.to_vec()).await;
+                });
+            }
+        });
+
+        let route = serde_json::json!({
+            "id": "r-tls",
+            "uri": "/",
+            "upstream": {
+                "nodes": { upstream_addr.to_string(): 1 },
+                "type": "roundrobin"
+            }
+        });
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        let tls = Arc::new(TlsServer::new(
+            Arc::new(CertResolver::new(tls_certs())),
+            false,
+        ));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let (tls, proxy, pool) = (tls.clone(), proxy.clone(), pool.clone());
+                monoio::spawn(async move {
+                    let _ = handle_tls_connection(stream, peer, tls, proxy, pool).await;
+                });
+            }
+        });
+
+        let mut roots = rustls::RootCertStore::empty();
+        for name in ["a.crt", "b.crt"] {
+            roots.add(&rustls::Certificate(fixture_der(name))).unwrap();
+        }
+        let connector = monoio_rustls::TlsConnector::from(
+            rustls::ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        );
+
+        for (sni, expected) in [("a.example.com", "a.crt"), ("b.example.com", "b.crt")] {
+            let tcp = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let name = rustls::ServerName::try_from(sni).unwrap();
+            let mut client = connector.connect(name, tcp).await.unwrap();
+            let req = format!("GET / HTTP/1.1\r\nhost: {sni}\r\nconnection: close\r\n\r\n");
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+
+            let mut resp = Vec::new();
+            loop {
+                let (n, buf) = client.read(vec![0u8; 1024]).await;
+                match n {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => resp.extend_from_slice(&buf[..n]),
+                }
+            }
+            let resp = String::from_utf8(resp).unwrap();
+            assert!(resp.starts_with("HTTP/1.1 200"), "{sni}: {resp:?}");
+            assert!(resp.ends_with("secure"), "{sni}: {resp:?}");
+
+            let (_, session) = client.into_parts();
+            let served = &session.peer_certificates().unwrap()[0];
+            assert_eq!(served.0, fixture_der(expected), "{sni}");
+        }
+    });
+}
+
+// ── Upstream TLS ───────────────────────────────────────────────────────────
+
+/// Start a keep-alive HTTPS upstream presenting the self-signed
+/// `a.example.com` certificate. Returns its address and a counter of
+/// completed TLS handshakes.
+fn spawn_tls_upstream() -> (std::net::SocketAddr, Rc<std::cell::Cell<usize>>) {
+    use ando_proxy::tls::{CertResolver, server_config};
+
+    let certs = Arc::new(dashmap::DashMap::new());
+    certs.insert("a".to_string(), ssl_cert("a", "a", &[]));
+    let acceptor =
+        monoio_rustls::TlsAcceptor::from(server_config(Arc::new(CertResolver::new(certs)), false));
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handshakes = Rc::new(std::cell::Cell::new(0));
+    let counter = Rc::clone(&handshakes);
+    monoio::spawn(async move {
+        while let Ok((tcp, _)) = listener.accept().await {
+            let acceptor = acceptor.clone();
+            let counter = Rc::clone(&counter);
+            monoio::spawn(async move {
+                let Ok(mut stream) = acceptor.accept(tcp).await else {
+                    return;
+                };
+                counter.set(counter.get() + 1);
+                let mut buf = vec![0u8; 4096];
+                loop {
+                    let (res, returned) = stream.read(buf).await;
+                    buf = returned;
+                    if !matches!(res, Ok(n) if n > 0) {
+                        break;
+                    }
+                    let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 9\r\n\r\nover-tls!";
+                    let (res, _) = stream.write_all(resp.to_vec()).await;
+                    if res.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    (addr, handshakes)
+}
+
+fn tls_upstream_route(upstream: std::net::SocketAddr, verify: bool) -> serde_json::Value {
+    serde_json::json!({
+        "id": "r-upstream-tls",
+        "uri": "/",
+        "upstream": {
+            "nodes": { upstream.to_string(): 1 },
+            "type": "roundrobin",
+            "scheme": "https",
+            "sni": "a.example.com",
+            "tls_verify": verify
+        }
+    })
+}
+
+/// Send `count` keep-alive requests through a proxy for `route` and
+/// return each response's status line and body.
+async fn proxy_requests(route: serde_json::Value, count: usize) -> Vec<String> {
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+    let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+    monoio::spawn(async move {
+        if let Ok((stream, peer)) = listener.accept().await {
+            let _ = handle_connection(stream, peer, proxy, pool).await;
+        }
+    });
+
+    let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+    let mut responses = Vec::new();
+    for _ in 0..count {
+        let req = b"GET / HTTP/1.1\r\nhost: a.example.com\r\n\r\n".to_vec();
+        let (res, _) = client.write_all(req).await;
+        res.unwrap();
+        let resp = read_until(&mut client, |b| {
+            let text = String::from_utf8_lossy(b);
+            text.ends_with("over-tls!") || text.ends_with("\"status\":502}")
+        })
+        .await;
+        let status = status_line(resp.as_bytes()).to_string();
+        let body = resp.split("\r\n\r\n").nth(1).unwrap_or("").to_string();
+        responses.push(format!("{status} {body}"));
+    }
+    responses
+}
+
+#[test]
+fn https_upstream_with_verification_rejects_self_signed_cert() {
+    make_rt().block_on(async {
+        let (upstream, _) = spawn_tls_upstream();
+        let responses = proxy_requests(tls_upstream_route(upstream, true), 1).await;
+        assert!(responses[0].starts_with("HTTP/1.1 502"), "{responses:?}");
+    });
+}
+
+#[test]
+fn https_upstream_without_verification_proxies_and_reuses_connection() {
+    make_rt().block_on(async {
+        let (upstream, handshakes) = spawn_tls_upstream();
+        let responses = proxy_requests(tls_upstream_route(upstream, false), 2).await;
+        for resp in &responses {
+            assert_eq!(resp, "HTTP/1.1 200 OK over-tls!");
+        }
+        assert_eq!(
+            handshakes.get(),
+            1,
+            "second request should reuse the TLS connection"
+        );
+    });
+}
+
+// ── pass_host ──────────────────────────────────────────────────────────────
+
+/// Upstream that answers every request with the Host header it received.
+fn spawn_host_echo() -> std::net::SocketAddr {
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    monoio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            monoio::spawn(async move {
+                let (res, buf) = stream.read(vec![0u8; 4096]).await;
+                let n = res.unwrap_or(0);
+                let head = String::from_utf8_lossy(&buf[..n]).into_owned();
+                let hosts: Vec<&str> = head
+                    .lines()
+                    .filter_map(|l| l.split_once(':'))
+                    .filter(|(name, _)| name.eq_ignore_ascii_case("host"))
+                    .map(|(_, value)| value.trim())
+                    .collect();
+                let body = hosts.join(",");
+                let resp = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                let (_, _) = stream.write_all(resp.into_bytes()).await;
+            });
+        }
+    });
+    addr
+}
+
+/// The Host header(s) the upstream sees for a client request with
+/// `host: gateway.example.com` under the given upstream settings.
+async fn upstream_host_for(pass_host: &str, upstream_host: Option<&str>) -> String {
+    let upstream = spawn_host_echo();
+    let route = serde_json::json!({
+        "id": "r-host",
+        "uri": "/",
+        "upstream": {
+            "nodes": { upstream.to_string(): 1 },
+            "type": "roundrobin",
+            "pass_host": pass_host,
+            "upstream_host": upstream_host
+        }
+    });
+    let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let proxy = Rc::new(RefCell::new(make_worker(vec![route])));
+    let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+    monoio::spawn(async move {
+        if let Ok((stream, peer)) = listener.accept().await {
+            let _ = handle_connection(stream, peer, proxy, pool).await;
+        }
+    });
+
+    let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+    let req = b"GET / HTTP/1.1\r\nhost: gateway.example.com\r\nconnection: close\r\n\r\n";
+    let (res, _) = client.write_all(req.to_vec()).await;
+    res.unwrap();
+    let resp = read_until(&mut client, |_| false).await;
+    resp.split("\r\n\r\n").nth(1).unwrap_or("").to_string()
+}
+
+#[test]
+fn pass_host_controls_upstream_host_header() {
+    make_rt().block_on(async {
+        assert_eq!(upstream_host_for("pass", None).await, "gateway.example.com");
+        assert_eq!(
+            upstream_host_for("rewrite", Some("api.internal")).await,
+            "api.internal"
+        );
+        // The node listens on an ephemeral (non-default) port, which must
+        // be kept in the Host header.
+        let node = upstream_host_for("node", None).await;
+        let (host, port) = node.split_once(':').unwrap();
+        assert_eq!(host, "127.0.0.1");
+        assert!(port.parse::<u16>().unwrap() > 0, "{node}");
+    });
+}
+
+// ── Prometheus metrics ─────────────────────────────────────────────────────
+
+/// Upstream that answers one keep-alive request and then holds the
+/// connection open until the proxy drops it.
+fn spawn_keepalive_upstream() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 4096];
+        let _ = stream.read(&mut buf).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+            .unwrap();
+        while matches!(stream.read(&mut buf), Ok(n) if n > 0) {}
+    });
+    addr
+}
+
+#[test]
+fn metrics_record_requests_plugin_responses_and_pool() {
+    use ando_observability::metrics::MetricsCollector;
+
+    let upstream = spawn_keepalive_upstream();
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    let routes: Vec<ando_core::route::Route> = [
+        serde_json::json!({
+            "id": "r-open",
+            "uri": "/open",
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+        serde_json::json!({
+            "id": "r-secure",
+            "uri": "/secure",
+            "plugins": { "key-auth": {} },
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+    ]
+    .into_iter()
+    .map(|v| serde_json::from_value(v).unwrap())
+    .collect();
+    let router = Arc::new(Router::build(routes, 1).unwrap());
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(ando_plugins::auth::key_auth::KeyAuthPlugin));
+    let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new())
+        .with_metrics(Arc::clone(&metrics));
+
+    make_rt().block_on(async {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        let (p, c) = (Rc::clone(&proxy), Rc::clone(&pool));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, Rc::clone(&p), Rc::clone(&c)).await;
+            }
+        });
+
+        for (path, status) in [("/open", "200"), ("/missing", "404"), ("/secure", "401")] {
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req =
+                format!("GET {path} HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n");
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+            let resp = read_until(&mut client, |_| false).await;
+            assert!(status_line(resp.as_bytes()).contains(status), "{resp}");
+        }
+
+        proxy.borrow_mut().flush_metrics(&pool.borrow());
+    });
+
+    let text = metrics.render();
+    for series in [
+        r#"ando_http_requests_total{method="GET",route="r-open",status="200"} 1"#,
+        r#"ando_http_requests_total{method="GET",route="",status="404"} 1"#,
+        r#"ando_http_requests_total{method="GET",route="r-secure",status="401"} 1"#,
+        r#"ando_http_request_duration_seconds_count{route="r-open"} 1"#,
+        r#"ando_plugin_responses_total{plugin="key-auth",status="401"} 1"#,
+    ] {
+        assert!(text.contains(series), "missing {series} in:\n{text}");
+    }
+    let pool_series = format!(r#"ando_upstream_pool_connections{{addr="{upstream}"}} 1"#);
+    assert!(
+        text.contains(&pool_series),
+        "missing {pool_series} in:\n{text}"
+    );
+}
+
+// ── OpenTelemetry ──────────────────────────────────────────────────────────
+
+/// Upstream that answers every request with the request head it received.
+fn spawn_head_echo() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                while let Ok(n) = stream.read(&mut buf)
+                    && n > 0
+                {
+                    let head = &buf[..n];
+                    let resp = format!("HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n", head.len());
+                    let mut out = resp.into_bytes();
+                    out.extend_from_slice(head);
+                    if stream.write_all(&out).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+#[test]
+fn traced_route_injects_traceparent_and_exports_span() {
+    use ando_observability::otel::RequestTracer;
+    use opentelemetry::Value;
+    use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider};
+
+    const PARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let upstream = spawn_head_echo();
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let tracer = RequestTracer::with_provider(provider, &Default::default());
+    let worker = make_worker(vec![
+        serde_json::json!({
+            "id": "r-traced",
+            "uri": "/traced/*",
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+        serde_json::json!({
+            "id": "r-quiet",
+            "uri": "/quiet",
+            "tracing": false,
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+    ])
+    .with_tracer(Arc::new(tracer));
+
+    let seen: Vec<String> = make_rt().block_on(async {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, Rc::clone(&proxy), Rc::clone(&pool)).await;
+            }
+        });
+
+        let mut seen = Vec::new();
+        for path in ["/traced/a?x=1", "/quiet"] {
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req = format!(
+                "GET {path} HTTP/1.1\r\nhost: localhost\r\ntraceparent: {PARENT}\r\n\
+                 tracestate: vendor=abc\r\nconnection: close\r\n\r\n"
+            );
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+            let resp = read_until(&mut client, |_| false).await;
+            seen.push(resp.split_once("\r\n\r\n").unwrap().1.to_lowercase());
+        }
+        seen
+    });
+
+    // The opted-out route forwards the caller's context untouched.
+    assert!(
+        seen[1].contains(&format!("traceparent: {PARENT}")),
+        "{}",
+        seen[1]
+    );
+
+    let spans = exporter.get_finished_spans().unwrap();
+    assert_eq!(spans.len(), 1, "{spans:?}");
+    let span = &spans[0];
+    let ctx = &span.span_context;
+    assert_eq!(
+        ctx.trace_id().to_string(),
+        "4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+    let injected = format!("traceparent: 00-{}-{}-01", ctx.trace_id(), ctx.span_id());
+    assert!(seen[0].contains(&injected), "{}", seen[0]);
+    assert_eq!(seen[0].matches("traceparent:").count(), 1, "{}", seen[0]);
+    assert!(seen[0].contains("tracestate: vendor=abc"));
+
+    let attr = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(span.name, "GET /traced/*");
+    assert_eq!(attr("ando.route.id"), Some(Value::from("r-traced")));
+    assert_eq!(attr("url.path"), Some(Value::from("/traced/a")));
+    assert_eq!(
+        attr("ando.upstream.address"),
+        Some(Value::from(upstream.to_string()))
+    );
+    assert_eq!(attr("http.response.status_code"), Some(Value::from(200i64)));
+}
+
+// ── Access log ───────────────────────────────────────────────
+
+#[test]
+fn access_log_records_proxied_and_unmatched_requests() {
+    use ando_core::config::AccessLogConfig;
+    use ando_observability::access_log::AccessLogger;
+
+    let dir = std::env::temp_dir().join(format!("ando-access-log-it-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("access.log");
+    let logger = Arc::new(
+        AccessLogger::from_config(&AccessLogConfig {
+            enabled: true,
+            path: path.to_string_lossy().into_owned(),
+            format: "$route_id $status $upstream_addr $request_id \"$request\"".into(),
+            flush_interval_ms: 60_000,
+            ..AccessLogConfig::default()
+        })
+        .unwrap()
+        .unwrap(),
+    );
+    let upstream = spawn_head_echo();
+    let worker = make_worker(vec![serde_json::json!({
+        "id": "r-logged",
+        "uri": "/logged",
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    })])
+    .with_access_log(Arc::clone(&logger));
+
+    make_rt().block_on(async {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, Rc::clone(&proxy), Rc::clone(&pool)).await;
+            }
+        });
+
+        for path in ["/logged", "/missing"] {
+            let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+            let req = format!(
+                "GET {path} HTTP/1.1\r\nhost: localhost\r\nx-request-id: id-{}\r\n\
+                 connection: close\r\n\r\n",
+                &path[1..]
+            );
+            let (res, _) = client.write_all(req.into_bytes()).await;
+            res.unwrap();
+            read_until(&mut client, |_| false).await;
+        }
+    });
+
+    logger.flush();
+    let written = std::fs::read_to_string(&path).unwrap();
+    assert_eq!(
+        written,
+        format!(
+            "r-logged 200 {upstream} id-logged \"GET /logged HTTP/1.1\"\n\
+             - 404 - id-missing \"GET /missing HTTP/1.1\"\n"
+        )
+    );
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+// ── HTTP/2 and absolute-form targets ───────────────────────────────────────
+
+#[test]
+fn absolute_form_target_routes_by_its_authority() {
+    let route = || unreachable_route(serde_json::json!({ "hosts": ["a.example.com"] }));
+    let resp = send_with_limits(
+        make_worker(vec![route()]),
+        b"GET http://a.example.com/up HTTP/1.1\r\nhost: other.example.com\r\nconnection: close\r\n\r\n"
+            .to_vec(),
+    );
+    assert!(resp.starts_with("HTTP/1.1 502"), "{resp:?}");
+
+    let resp = send_with_limits(
+        make_worker(vec![route()]),
+        b"GET /up HTTP/1.1\r\nhost: other.example.com\r\nconnection: close\r\n\r\n".to_vec(),
+    );
+    assert!(resp.starts_with("HTTP/1.1 404"), "{resp:?}");
+}
+
+/// Start a TLS proxy offering h2 for `routes`, with key-auth available,
+/// and an upstream that answers `upstream-ok` and reports each request
+/// it sees.
+fn spawn_h2_proxy(
+    routes: impl FnOnce(std::net::SocketAddr) -> Vec<serde_json::Value>,
+    heads: std::sync::mpsc::Sender<String>,
+) -> std::net::SocketAddr {
+    use ando_proxy::connection::handle_tls_connection;
+    use ando_proxy::tls::{CertResolver, TlsServer};
+
+    let upstream = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    let routes = routes(upstream.local_addr().unwrap());
+    upstream.set_nonblocking(true).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    std::thread::spawn(move || {
+        make_rt().block_on(async move {
+            let upstream = monoio::net::TcpListener::from_std(upstream).unwrap();
+            let listener = monoio::net::TcpListener::from_std(listener).unwrap();
+            monoio::spawn(async move {
+                while let Ok((mut stream, _)) = upstream.accept().await {
+                    let heads = heads.clone();
+                    monoio::spawn(async move {
+                        let head = read_until(&mut stream, |b| {
+                            let Some(end) = b.windows(4).position(|w| w == b"\r\n\r\n") else {
+                                return false;
+                            };
+                            let head = String::from_utf8_lossy(&b[..end]).to_lowercase();
+                            let len = head
+                                .lines()
+                                .find_map(|l| l.strip_prefix("content-length:"))
+                                .map_or(0, |v| v.trim().parse().unwrap());
+                            b.len() >= end + 4 + len
+                        })
+                        .await;
+                        let _ = heads.send(head.to_lowercase());
+                        let resp = b"HTTP/1.1 200 OK\r\ncontent-length: 11\r\n\r\nupstream-ok";
+                        let (_, _) = stream.write_all(resp.to_vec()).await;
+                    });
+                }
+            });
+
+            let mut registry = PluginRegistry::new();
+            registry.register(Arc::new(ando_plugins::auth::key_auth::KeyAuthPlugin));
+            let routes = routes
+                .into_iter()
+                .map(|v| serde_json::from_value(v).unwrap())
+                .collect();
+            let router = Arc::new(Router::build(routes, 1).unwrap());
+            let worker = ProxyWorker::new(router, Arc::new(registry), ConfigCache::new());
+            let proxy = Rc::new(RefCell::new(worker));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+            let tls = Arc::new(TlsServer::new(
+                Arc::new(CertResolver::new(tls_certs())),
+                true,
+            ));
+            while let Ok((stream, peer)) = listener.accept().await {
+                let (tls, proxy, pool) = (tls.clone(), proxy.clone(), pool.clone());
+                monoio::spawn(async move {
+                    let _ = handle_tls_connection(stream, peer, tls, proxy, pool).await;
+                });
+            }
+        });
+    });
+    proxy_addr
+}
+
+/// Send one HTTP/2 request to `addr` over TLS with SNI `a.example.com`
+/// and return the status and body.
+async fn h2_request(
+    addr: std::net::SocketAddr,
+    method: &str,
+    uri: &str,
+    body: &[u8],
+) -> (u16, String) {
+    let mut roots = rustls::RootCertStore::empty();
+    roots
+        .add(&rustls::Certificate(fixture_der("a.crt")))
+        .unwrap();
+    let mut config = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    let connector = monoio_rustls::TlsConnector::from(config);
+    let tcp = monoio::net::TcpStream::connect(addr).await.unwrap();
+    let name = rustls::ServerName::try_from("a.example.com").unwrap();
+    let tls = connector.connect(name, tcp).await.unwrap();
+
+    let (mut sender, conn) = monoio_http::h2::client::handshake(tls).await.unwrap();
+    monoio::spawn(async move {
+        let _ = conn.await;
+    });
+    let request = http::Request::builder()
+        .method(method)
+        .uri(uri)
+        .body(())
+        .unwrap();
+    let (response, mut stream) = sender.send_request(request, body.is_empty()).unwrap();
+    if !body.is_empty() {
+        stream
+            .send_data(bytes::Bytes::copy_from_slice(body), true)
+            .unwrap();
+    }
+    let response = response.await.unwrap();
+    let status = response.status().as_u16();
+    let mut recv = response.into_body();
+    let mut out = Vec::new();
+    while let Some(chunk) = recv.data().await {
+        let chunk = chunk.unwrap();
+        let _ = recv.flow_control().release_capacity(chunk.len());
+        out.extend_from_slice(&chunk);
+    }
+    (status, String::from_utf8(out).unwrap())
+}
+
+fn h2_routes(upstream: std::net::SocketAddr) -> Vec<serde_json::Value> {
+    vec![
+        serde_json::json!({
+            "id": "r-h2",
+            "uri": "/h2/*",
+            "hosts": ["a.example.com"],
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+        serde_json::json!({
+            "id": "r-h2-auth",
+            "uri": "/locked",
+            "plugins": { "key-auth": {} },
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        }),
+    ]
+}
+
+#[test]
+fn h2_requests_are_routed_by_authority_and_proxied_over_http1() {
+    let (tx, heads) = std::sync::mpsc::channel();
+    let addr = spawn_h2_proxy(h2_routes, tx);
+    make_rt().block_on(async move {
+        let (status, body) = h2_request(addr, "GET", "https://a.example.com/h2/x?q=1", b"").await;
+        assert_eq!((status, body.as_str()), (200, "upstream-ok"));
+        let head = heads.recv().unwrap();
+        assert!(head.starts_with("get /h2/x?q=1 http/1.1"), "{head:?}");
+        assert!(head.contains("host: a.example.com"), "{head:?}");
+
+        let (status, body) =
+            h2_request(addr, "POST", "https://a.example.com/h2/post", b"hello").await;
+        assert_eq!((status, body.as_str()), (200, "upstream-ok"));
+        let head = heads.recv().unwrap();
+        assert!(head.contains("content-length: 5"), "{head:?}");
+        assert!(head.ends_with("hello"), "{head:?}");
+
+        let (status, _) = h2_request(addr, "GET", "https://b.example.com/h2/x", b"").await;
+        assert_eq!(status, 404);
+    });
+}
+
+#[test]
+fn h2_plugin_response_short_circuits_the_upstream() {
+    let (tx, heads) = std::sync::mpsc::channel();
+    let addr = spawn_h2_proxy(h2_routes, tx);
+    make_rt().block_on(async move {
+        let (status, _) = h2_request(addr, "GET", "https://a.example.com/locked", b"").await;
+        assert_eq!(status, 401);
+    });
+    assert!(heads.try_recv().is_err());
+}
+
+// ── Streaming responses ────────────────────────────────────────────────────
+
+/// Upstream answering with `head` and then each of `events` after `gap`,
+/// closing the connection to end the body.
+fn spawn_streaming_upstream(
+    head: &'static str,
+    events: &'static [&'static str],
+    gap: std::time::Duration,
+) -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let _ = stream.write_all(head.as_bytes());
+                for event in events {
+                    std::thread::sleep(gap);
+                    if stream.write_all(event.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Send a keep-alive GET for `path` through a proxy for `routes` and read
+/// until the proxy closes the connection.
+fn stream_through_proxy(routes: Vec<serde_json::Value>, path: &str) -> String {
+    raw_through_proxy(
+        routes,
+        &format!("GET {path} HTTP/1.1\r\nhost: localhost\r\n\r\n"),
+    )
+}
+
+/// Write `request` as is to a proxy for `routes` and read until the proxy
+/// closes the connection.
+fn raw_through_proxy(routes: Vec<serde_json::Value>, request: &str) -> String {
+    let worker = make_worker(routes);
+    let request = request.to_string();
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+        let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+        let (res, _) = client.write_all(request.into_bytes()).await;
+        res.unwrap();
+        read_until(&mut client, |_| false).await
+    })
+}
+
+const EVENTS: &[&str] = &["data: one\n\n", "data: two\n\n", "data: three\n\n"];
+
+#[test]
+fn event_stream_outlives_the_upstream_read_timeout() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\ncontent-type: text/event-stream\r\ncache-control: no-cache\r\n\r\n",
+        EVENTS,
+        std::time::Duration::from_millis(300),
+    );
+    let resp = stream_through_proxy(
+        vec![serde_json::json!({
+            "id": "r-sse", "uri": "/events",
+            "upstream": {
+                "nodes": { upstream.to_string(): 1 }, "type": "roundrobin",
+                "timeout": { "connect": 1, "read": 0.1 }
+            }
+        })],
+        "/events",
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(
+        resp.ends_with("data: one\n\ndata: two\n\ndata: three\n\n"),
+        "{resp}"
+    );
+}
+
+#[test]
+fn streaming_route_relays_a_close_delimited_body_until_eof() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\n\r\n",
+        EVENTS,
+        std::time::Duration::from_millis(300),
+    );
+    let route = |id: &str, uri: &str, streaming: bool| {
+        serde_json::json!({
+            "id": id, "uri": uri, "streaming": streaming,
+            "upstream": {
+                "nodes": { upstream.to_string(): 1 }, "type": "roundrobin",
+                "timeout": { "connect": 1, "read": 0.1 }
+            }
+        })
+    };
+
+    let resp = stream_through_proxy(vec![route("r-stream", "/stream", true)], "/stream");
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(
+        resp.ends_with("data: one\n\ndata: two\n\ndata: three\n\n"),
+        "{resp}"
+    );
+
+    // Without the hint the upstream read deadline still applies.
+    let resp = stream_through_proxy(vec![route("r-plain", "/plain", false)], "/plain");
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(!resp.contains("data: one"), "{resp}");
+}
+
+#[test]
+fn close_delimited_body_is_relayed_in_full() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\n\r\n",
+        EVENTS,
+        std::time::Duration::from_millis(20),
+    );
+    let resp = stream_through_proxy(
+        vec![serde_json::json!({
+            "id": "r-eof", "uri": "/eof",
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        })],
+        "/eof",
+    );
+    assert!(
+        resp.ends_with("data: one\n\ndata: two\n\ndata: three\n\n"),
+        "{resp}"
+    );
+}
+
+// ── Hop-by-hop headers and framing ─────────────────────────────────────────
+
+fn echo_route(upstream: std::net::SocketAddr) -> Vec<serde_json::Value> {
+    vec![serde_json::json!({
+        "id": "r-hop", "uri": "/hop",
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    })]
+}
+
+#[test]
+fn hop_by_hop_request_headers_stay_at_the_proxy() {
+    let resp = raw_through_proxy(
+        echo_route(spawn_head_echo()),
+        "GET /hop HTTP/1.1\r\nhost: localhost\r\nconnection: close, x-secret\r\n\
+         x-secret: 1\r\nte: trailers\r\nproxy-connection: keep-alive\r\nx-end: 1\r\n\r\n",
+    );
+    let seen = resp.to_lowercase();
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(seen.contains("x-end: 1\r\n"), "{resp}");
+    for name in ["x-secret", "te:", "proxy-connection"] {
+        assert!(!seen.contains(name), "{name} forwarded: {resp}");
+    }
+}
+
+#[test]
+fn hop_by_hop_response_headers_stay_at_the_proxy() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\nconnection: keep-alive, x-upstream\r\nkeep-alive: timeout=5\r\n\
+         x-upstream: secret\r\ncontent-length: 2\r\ncontent-length: 2\r\n\r\nok",
+        &[],
+        std::time::Duration::ZERO,
+    );
+    let resp = raw_through_proxy(
+        echo_route(upstream),
+        "GET /hop HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK\r\n"), "{resp}");
+    assert_eq!(resp.matches("content-length: 2\r\n").count(), 1, "{resp}");
+    assert!(resp.contains("connection: close\r\n"), "{resp}");
+    assert!(!resp.contains("keep-alive"), "{resp}");
+    assert!(!resp.contains("x-upstream"), "{resp}");
+    assert!(resp.ends_with("\r\n\r\nok"), "{resp}");
+}
+
+// ── Repeated headers ───────────────────────────────────────────────────────
+
+/// A route behind key-auth with `hide_credentials`, from a trusted peer.
+fn hide_credentials_worker(upstream: std::net::SocketAddr) -> ProxyWorker {
+    use ando_proxy::forwarded::TrustedProxies;
+
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-multi", "uri": "/multi",
+        "plugins": {
+            "key-auth": { "hide_credentials": true },
+            "security-headers": {}
+        },
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let cache = ConfigCache::new();
+    cache.consumers.insert(
+        "alice".to_string(),
+        serde_json::from_value(serde_json::json!({
+            "username": "alice",
+            "plugins": { "key-auth": { "key": "alice-key" } }
+        }))
+        .unwrap(),
+    );
+    cache.reindex_consumers();
+    ProxyWorker::new(
+        Arc::new(Router::build(vec![route], 1).unwrap()),
+        Arc::new(registry),
+        cache,
+    )
+    .with_trusted_proxies(TrustedProxies::new(&["127.0.0.0/8".to_string()]))
+}
+
+fn raw_through_worker(worker: ProxyWorker, request: &'static str) -> String {
+    make_rt().block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+        let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+        let (res, _) = client.write_all(request.as_bytes().to_vec()).await;
+        res.unwrap();
+        read_until(&mut client, |_| false).await
+    })
+}
+
+#[test]
+fn repeated_request_headers_reach_the_upstream_on_plugin_routes() {
+    let resp = raw_through_worker(
+        hide_credentials_worker(spawn_head_echo()),
+        "GET /multi HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\
+         X-Forwarded-For: 198.51.100.1\r\nX-Forwarded-For: 198.51.100.2\r\n\
+         Accept: text/html\r\nAccept: application/json\r\napikey: alice-key\r\n\r\n",
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(
+        resp.contains("x-forwarded-for: 198.51.100.1, 198.51.100.2, 127.0.0.1\r\n"),
+        "{resp}"
+    );
+    assert!(resp.contains("Accept: text/html\r\n"), "{resp}");
+    assert!(resp.contains("Accept: application/json\r\n"), "{resp}");
+    // hide_credentials keeps the key from the upstream.
+    assert!(!resp.to_lowercase().contains("apikey"), "{resp}");
+}
+
+#[test]
+fn repeated_set_cookie_headers_reach_the_client_on_plugin_routes() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\nset-cookie: a=1; Path=/\r\nset-cookie: b=2; HttpOnly\r\n\
+         content-length: 2\r\n\r\nok",
+        &[],
+        std::time::Duration::ZERO,
+    );
+    let resp = raw_through_worker(
+        hide_credentials_worker(upstream),
+        "GET /multi HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\
+         apikey: alice-key\r\n\r\n",
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert!(resp.contains("set-cookie: a=1; Path=/\r\n"), "{resp}");
+    assert!(resp.contains("set-cookie: b=2; HttpOnly\r\n"), "{resp}");
+}
+
+/// A route whose header filter adds two `Set-Cookie` headers to the
+/// upstream's own; `filter_body` also buffers the body through
+/// `uppercase-body`.
+fn set_cookie_worker(upstream: std::net::SocketAddr, filter_body: bool) -> ProxyWorker {
+    let mut plugins = serde_json::json!({
+        "response-headers-policy": {
+            "add": ["set-cookie: csrf=2; Path=/", "Set-Cookie: region=eu"]
+        }
+    });
+    if filter_body {
+        plugins["uppercase-body"] = serde_json::json!({});
+    }
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-cookies", "uri": "/cookies", "plugins": plugins,
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    registry.register(Arc::new(UppercaseBody));
+    ProxyWorker::new(
+        Arc::new(Router::build(vec![route], 1).unwrap()),
+        Arc::new(registry),
+        ConfigCache::new(),
+    )
+}
+
+#[test]
+fn header_filter_adds_set_cookie_next_to_the_upstreams() {
+    for filter_body in [false, true] {
+        let upstream = spawn_streaming_upstream(
+            "HTTP/1.1 200 OK\r\nset-cookie: session=1; HttpOnly\r\ncontent-length: 2\r\n\r\nok",
+            &[],
+            std::time::Duration::ZERO,
+        );
+        let resp = raw_through_worker(
+            set_cookie_worker(upstream, filter_body),
+            "GET /cookies HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n",
+        );
+        assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+        for cookie in [
+            "set-cookie: session=1; HttpOnly\r\n",
+            "set-cookie: csrf=2; Path=/\r\n",
+            "set-cookie: region=eu\r\n",
+        ] {
+            assert!(resp.contains(cookie), "filter_body={filter_body}: {resp}");
+        }
+        let body = if filter_body { "OK!" } else { "ok" };
+        assert!(resp.ends_with(&format!("\r\n\r\n{body}")), "{resp}");
+    }
+}
+
+#[test]
+fn ambiguous_request_framing_is_refused() {
+    let upstream = spawn_head_echo();
+    for framing in [
+        "content-length: 5\r\ntransfer-encoding: chunked",
+        "transfer-encoding: chunked\r\ncontent-length: 5",
+        "content-length: 5\r\ncontent-length: 6",
+        "content-length: 5, 6",
+        "transfer-encoding: chunked, identity",
+        "content-length: +5",
+    ] {
+        let resp = raw_through_proxy(
+            echo_route(upstream),
+            &format!("POST /hop HTTP/1.1\r\nhost: localhost\r\n{framing}\r\n\r\nhello"),
+        );
+        assert!(resp.starts_with("HTTP/1.1 400"), "{framing}: {resp}");
+    }
+
+    // Repeated lengths that agree are one length.
+    let resp = raw_through_proxy(
+        echo_route(upstream),
+        "POST /hop HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\
+         content-length: 5\r\ncontent-length: 5\r\n\r\nhello",
+    );
+    assert!(resp.starts_with("HTTP/1.1 200 OK"), "{resp}");
+    assert_eq!(resp.matches("content-length: 5\r\n").count(), 1, "{resp}");
+}
+
+#[test]
+fn conflicting_upstream_lengths_answer_502() {
+    let upstream = spawn_streaming_upstream(
+        "HTTP/1.1 200 OK\r\ncontent-length: 2\r\ncontent-length: 3\r\n\r\nok!",
+        &[],
+        std::time::Duration::ZERO,
+    );
+    let resp = raw_through_proxy(
+        echo_route(upstream),
+        "GET /hop HTTP/1.1\r\nhost: localhost\r\n\r\n",
+    );
+    assert!(resp.starts_with("HTTP/1.1 502"), "{resp}");
+}
+
+// ── Config notifications ───────────────────────────────────────────────────
+
+#[test]
+fn notified_route_is_routable_without_a_new_connection() {
+    use ando_proxy::proxy::RequestResult;
+    use ando_proxy::worker::{SharedState, watch_config};
+
+    let cache = ConfigCache::new();
+    let shared = SharedState::new(
+        Router::build(vec![], 1).unwrap(),
+        PluginRegistry::new(),
+        cache.clone(),
+        Default::default(),
+    );
+    let proxy = Rc::new(RefCell::new(ProxyWorker::new(
+        shared.router.load_full(),
+        Arc::clone(&shared.plugin_registry),
+        cache.clone(),
+    )));
+    let routed = |proxy: &RefCell<ProxyWorker>| {
+        matches!(
+            proxy
+                .borrow_mut()
+                .handle_request("GET", "/late", None, &[], "127.0.0.1"),
+            RequestResult::Proxy { .. }
+        )
+    };
+
+    make_rt().block_on(async {
+        monoio::spawn(watch_config(
+            cache.notifier.subscribe(),
+            Arc::clone(&shared),
+            Rc::clone(&proxy),
+        ));
+        assert!(!routed(&proxy));
+
+        // What a writer does: cache, then router, then the signal.
+        let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+            "id": "r-late", "uri": "/late",
+            "upstream": { "nodes": { "127.0.0.1:1": 1 }, "type": "roundrobin" }
+        }))
+        .unwrap();
+        cache.routes.insert(route.id.clone(), route);
+        let version = shared.router.load().version() + 1;
+        shared.router.store(Arc::new(
+            Router::build(cache.all_routes(), version).unwrap(),
+        ));
+        assert!(!routed(&proxy), "picked up before the notification");
+        cache.notifier.notify();
+
+        monoio::time::sleep(std::time::Duration::from_millis(300)).await;
+        assert!(routed(&proxy));
+    });
+}
+
+// ── Worker listeners ───────────────────────────────────────────────────────
+
+#[test]
+fn spawned_workers_count_accepts_and_requests() {
+    use ando_proxy::worker::{SharedState, spawn_workers};
+    use std::io::{Read, Write};
+
+    let ok = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let failing = spawn_status_upstream("503 Service Unavailable", std::time::Duration::ZERO);
+    let routes = [("ok", "/ok", ok), ("failing", "/failing", failing)]
+        .into_iter()
+        .map(|(id, uri, addr)| {
+            serde_json::from_value(serde_json::json!({
+                "id": id,
+                "uri": uri,
+                "upstream": {"nodes": {addr.to_string(): 1}},
+            }))
+            .unwrap()
+        })
+        .collect();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = ando_core::config::GatewayConfig::default();
+    config.proxy.http_addr = format!("127.0.0.1:{port}");
+    config.proxy.https_addr = String::new();
+    config.proxy.workers = 2;
+    config.proxy.pin_workers = true;
+    // Worker counters do not depend on Prometheus metrics.
+    assert!(!config.observability.prometheus.enabled);
+    let shared = SharedState::new(
+        Router::build(routes, 1).unwrap(),
+        PluginRegistry::new(),
+        ConfigCache::new(),
+        config,
+    );
+    spawn_workers(Arc::clone(&shared), 2);
+
+    for (path, status) in [
+        ("/none", "404"),
+        ("/ok", "200"),
+        ("/ok", "200"),
+        ("/failing", "503"),
+    ] {
+        let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client
+            .write_all(
+                format!("GET {path} HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n").as_bytes(),
+            )
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with(&format!("HTTP/1.1 {status}")), "{resp}");
+    }
+
+    let report = shared.workers.report();
+    assert_eq!(report.workers.len(), 2);
+    assert_eq!(report.total.accepts, 4);
+    assert_eq!(report.total.requests, 4);
+    assert_eq!(report.total.responses_4xx, 1);
+    assert_eq!(report.total.responses_5xx, 1);
+}
+
+#[test]
+fn named_listeners_scope_routes() {
+    use ando_core::config::{ListenerProtocol, ProxyListener};
+    use ando_proxy::listener::WorkerListeners;
+    use ando_proxy::worker::{SharedState, spawn_workers_on};
+    use std::io::{Read, Write};
+
+    let public_api = spawn_status_upstream("201 Created", std::time::Duration::ZERO);
+    let internal_api = spawn_status_upstream("202 Accepted", std::time::Duration::ZERO);
+    let open = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let routes = [
+        (
+            "public-api",
+            "/api",
+            serde_json::json!(["public"]),
+            public_api,
+        ),
+        (
+            "internal-api",
+            "/api",
+            serde_json::json!(["internal"]),
+            internal_api,
+        ),
+        ("metrics", "/metrics", serde_json::json!(["internal"]), open),
+        ("health", "/health", serde_json::json!([]), open),
+    ]
+    .into_iter()
+    .map(|(id, uri, listeners, addr)| {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "uri": uri,
+            "listeners": listeners,
+            "upstream": {"nodes": {addr.to_string(): 1}},
+        }))
+        .unwrap()
+    })
+    .collect();
+    let listener = |name: &str, protocol| ProxyListener {
+        name: name.to_string(),
+        addr: "127.0.0.1:0".to_string(),
+        protocol,
+        tls: Default::default(),
+        proxy_protocol: None,
+    };
+    let mut config = ando_core::config::GatewayConfig::default();
+    config.proxy.listeners = vec![
+        listener("public", ListenerProtocol::Https),
+        listener("internal", ListenerProtocol::Http),
+    ];
+    config.proxy.reuse_port = false;
+    let cache = ConfigCache::new();
+    for entry in tls_certs().iter() {
+        cache
+            .ssl_certs
+            .insert(entry.key().clone(), entry.value().clone());
+    }
+    let bound = WorkerListeners::bind(&config.proxy, 1).unwrap();
+    let addr = |i: usize| bound.listeners[i].sockets[0].local_addr().unwrap();
+    let (public, internal) = (addr(0), addr(1));
+    let shared = SharedState::new(
+        Router::build(routes, 1).unwrap(),
+        PluginRegistry::new(),
+        cache,
+        config,
+    );
+    spawn_workers_on(Arc::clone(&shared), bound);
+
+    let plain_get = |path: &str| {
+        let mut client = std::net::TcpStream::connect(internal).unwrap();
+        client
+            .write_all(
+                format!("GET {path} HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n").as_bytes(),
+            )
+            .unwrap();
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).unwrap();
+        resp
+    };
+    let tls_get = |path: &str| mtls_get(public, "a.example.com", None, path);
+
+    // The same path reaches a different route on each listener.
+    let resp = tls_get("/api");
+    assert!(resp.starts_with("HTTP/1.1 201"), "{resp}");
+    let resp = plain_get("/api");
+    assert!(resp.starts_with("HTTP/1.1 202"), "{resp}");
+    // A route scoped to one listener is invisible on the other.
+    let resp = plain_get("/metrics");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let resp = tls_get("/metrics");
+    assert!(resp.starts_with("HTTP/1.1 404"), "{resp}");
+    // Unscoped routes are served everywhere.
+    for resp in [tls_get("/health"), plain_get("/health")] {
+        assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    }
+}
+
+/// TCP_NODELAY of the gateway's end of `client`, found among the
+/// descriptors of this process (the workers run in it).
+#[cfg(target_os = "linux")]
+fn gateway_side_nodelay(client: &std::net::TcpStream) -> Option<bool> {
+    use std::os::fd::BorrowedFd;
+
+    let (local, peer) = (client.local_addr().unwrap(), client.peer_addr().unwrap());
+    std::fs::read_dir("/proc/self/fd")
+        .unwrap()
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .find_map(|fd| {
+            // SAFETY: only queried; a descriptor closed meanwhile fails the
+            // queries below.
+            let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+            let socket = socket2::SockRef::from(&fd);
+            let ours = socket.local_addr().ok()?.as_socket()? == peer
+                && socket.peer_addr().ok()?.as_socket()? == local;
+            ours.then(|| socket.nodelay().unwrap())
+        })
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn accepted_connections_follow_listener_nodelay() {
+    use ando_proxy::worker::{SharedState, spawn_workers};
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    let slow = spawn_status_upstream("200 OK", Duration::from_millis(500));
+    for nodelay in [true, false] {
+        let route = serde_json::from_value(serde_json::json!({
+            "id": "slow",
+            "uri": "/slow",
+            "upstream": {"nodes": {slow.to_string(): 1}},
+        }))
+        .unwrap();
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut config = ando_core::config::GatewayConfig::default();
+        config.proxy.http_addr = format!("127.0.0.1:{port}");
+        config.proxy.https_addr = String::new();
+        config.proxy.listener.nodelay = nodelay;
+        let shared = SharedState::new(
+            Router::build(vec![route], 1).unwrap(),
+            PluginRegistry::new(),
+            ConfigCache::new(),
+            config,
+        );
+        let workers = spawn_workers(Arc::clone(&shared), 1);
+
+        let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        client
+            .write_all(b"GET /slow HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        // The request waits on the upstream, so the connection is open.
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(gateway_side_nodelay(&client), Some(nodelay));
+        let mut resp = String::new();
+        client.read_to_string(&mut resp).unwrap();
+        assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+
+        shared.shutdown.begin();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+}
+
+#[test]
+fn shutdown_drains_requests_in_flight_and_refuses_new_connections() {
+    use ando_proxy::worker::{SharedState, spawn_workers};
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    let slow = spawn_status_upstream("200 OK", Duration::from_millis(600));
+    let route = serde_json::from_value(serde_json::json!({
+        "id": "slow",
+        "uri": "/slow",
+        "upstream": {"nodes": {slow.to_string(): 1}},
+    }))
+    .unwrap();
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let mut config = ando_core::config::GatewayConfig::default();
+    config.proxy.http_addr = format!("127.0.0.1:{port}");
+    config.proxy.https_addr = String::new();
+    config.proxy.workers = 2;
+    config.proxy.graceful_shutdown_timeout_ms = 5_000;
+    let shared = SharedState::new(
+        Router::build(vec![route], 1).unwrap(),
+        PluginRegistry::new(),
+        ConfigCache::new(),
+        config,
+    );
+    let handles = spawn_workers(Arc::clone(&shared), 2);
+
+    // A keepalive request still waiting on the upstream when the
+    // shutdown begins.
+    let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+    client
+        .write_all(b"GET /slow HTTP/1.1\r\nhost: a\r\n\r\n")
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    shared.shutdown.begin();
+    std::thread::sleep(Duration::from_millis(200));
+
+    assert!(std::net::TcpStream::connect(("127.0.0.1", port)).is_err());
+
+    let mut resp = String::new();
+    client.read_to_string(&mut resp).unwrap();
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    assert!(resp.contains("connection: close\r\n"), "{resp}");
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+}
+
+// ── Request decompression ─────────────────────────────────────────────────
+
+/// An upstream answering 200 to every request and passing each raw
+/// request (head and content-length body) to the returned receiver.
+fn spawn_capturing_upstream() -> (std::net::SocketAddr, std::sync::mpsc::Receiver<Vec<u8>>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut req = Vec::new();
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stream.read(&mut buf) {
+                if n == 0 {
+                    break;
+                }
+                req.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&req);
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let len = text[..end]
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length: "))
+                        .map_or(0, |v| v.trim().parse().unwrap());
+                    if req.len() >= end + 4 + len {
+                        break;
+                    }
+                }
+            }
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok");
+            let _ = tx.send(req);
+        }
+    });
+    (addr, rx)
+}
+
+/// Send `body` with `Content-Encoding: gzip` through a proxy for a route
+/// with `request_decompression` and a request-validation schema.
+fn post_gzip(upstream: std::net::SocketAddr, body: &[u8]) -> String {
+    use std::io::{Read, Write};
+
+    let route = serde_json::json!({
+        "id": "r-gzip",
+        "uri": "/upload",
+        "request_decompression": { "max_decompressed_size": 4096 },
+        "upstream": { "nodes": { upstream.to_string(): 1 } },
+        "plugins": { "request-validation": { "body_schema": {
+            "type": "object", "required": ["user"]
+        } } }
+    });
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        make_rt().block_on(async move {
+            let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            tx.send(listener.local_addr().unwrap()).unwrap();
+            let mut registry = PluginRegistry::new();
+            ando_plugins::register_all(&mut registry);
+            let parsed = serde_json::from_value(route).unwrap();
+            let proxy = ProxyWorker::new(
+                Arc::new(Router::build(vec![parsed], 1).unwrap()),
+                Arc::new(registry),
+                ConfigCache::new(),
+            );
+            let proxy = Rc::new(RefCell::new(proxy));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+            if let Ok((stream, peer)) = listener.accept().await {
+                let _ = handle_connection(stream, peer, proxy, pool).await;
+            }
+        });
+    });
+    let mut client = std::net::TcpStream::connect(rx.recv().unwrap()).unwrap();
+    let head = format!(
+        "POST /upload HTTP/1.1\r\nhost: a\r\ncontent-type: application/json\r\n\
+         content-encoding: gzip\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+        body.len()
+    );
+    client.write_all(head.as_bytes()).unwrap();
+    client.write_all(body).unwrap();
+    let mut resp = String::new();
+    let _ = client.read_to_string(&mut resp);
+    resp
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut enc = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    enc.write_all(data).unwrap();
+    enc.finish().unwrap()
+}
+
+#[test]
+fn gzip_request_body_is_decoded_for_plugins_and_upstream() {
+    let (upstream, requests) = spawn_capturing_upstream();
+    let json = br#"{"user":"alice","note":"compressed on the phone"}"#;
+    let resp = post_gzip(upstream, &gzip(json));
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+
+    let req = String::from_utf8(requests.recv().unwrap()).unwrap();
+    let (head, body) = req.split_once("\r\n\r\n").unwrap();
+    assert!(
+        !head.to_ascii_lowercase().contains("content-encoding"),
+        "{head}"
+    );
+    let length = format!("content-length: {}", json.len());
+    assert!(head.lines().any(|line| line == length), "{head}");
+    assert_eq!(body.as_bytes(), json);
+}
+
+#[test]
+fn decoded_body_is_what_request_validation_checks() {
+    let (upstream, _requests) = spawn_capturing_upstream();
+    let resp = post_gzip(upstream, &gzip(br#"{"note":"no user"}"#));
+    assert!(resp.starts_with("HTTP/1.1 400"), "{resp}");
+}
+
+#[test]
+fn corrupt_gzip_request_body_answers_400() {
+    let (upstream, requests) = spawn_capturing_upstream();
+    let resp = post_gzip(upstream, b"\x1f\x8b definitely not gzip");
+    assert!(resp.starts_with("HTTP/1.1 400"), "{resp}");
+    assert!(resp.contains("malformed request body encoding"), "{resp}");
+    assert!(requests.try_recv().is_err());
+}
+
+#[test]
+fn gzip_bomb_past_the_limit_answers_413() {
+    let (upstream, requests) = spawn_capturing_upstream();
+    let bomb = gzip(&vec![b'{'; 1 << 20]);
+    let resp = post_gzip(upstream, &bomb);
+    assert!(resp.starts_with("HTTP/1.1 413"), "{resp}");
+    assert!(requests.try_recv().is_err());
+}
+
+// ── Global plugins ─────────────────────────────────────────────────────────
+
+/// Upstream answering every request with fingerprinting headers.
+fn spawn_chatty_upstream() -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let mut buf = [0u8; 4096];
+            let _ = stream.read(&mut buf);
+            let _ = stream.write_all(
+                b"HTTP/1.1 200 OK\r\nServer: nginx/1.25\r\nX-Powered-By: PHP/8.2\r\n\
+                  content-length: 2\r\nconnection: close\r\n\r\nok",
+            );
+        }
+    });
+    addr
+}
+
+#[test]
+fn global_response_headers_policy_applies_unless_the_route_overrides_it() {
+    let upstream = spawn_chatty_upstream();
+    let route = |id: &str, plugins: serde_json::Value| {
+        serde_json::json!({
+            "id": id, "uri": format!("/{id}"), "plugins": plugins,
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        })
+    };
+    let routes: Vec<ando_core::route::Route> = [
+        route("plain", serde_json::json!({})),
+        route(
+            "raw",
+            serde_json::json!({ "response-headers-policy": { "_meta": { "disable": true } } }),
+        ),
+        route(
+            "custom",
+            serde_json::json!({ "response-headers-policy": { "remove": ["x-powered-by"] } }),
+        ),
+    ]
+    .into_iter()
+    .map(|v| serde_json::from_value(v).unwrap())
+    .collect();
+    let mut registry = PluginRegistry::new();
+    ando_plugins::register_all(&mut registry);
+    let worker = ProxyWorker::new(
+        Arc::new(Router::build(routes, 1).unwrap()),
+        Arc::new(registry),
+        ConfigCache::new(),
+    )
+    .with_global_plugins(std::collections::HashMap::from([
+        (
+            "response-headers-policy".to_string(),
+            serde_json::json!({ "set": { "x-served-by": "ando" } }),
+        ),
+        ("security-headers".to_string(), serde_json::json!({})),
+    ]));
+
+    let responses = serve_requests(worker, &["/plain", "/raw", "/custom"]);
+    let lower: Vec<String> = responses.iter().map(|r| r.to_ascii_lowercase()).collect();
+    for resp in &lower {
+        assert!(resp.starts_with("http/1.1 200"), "{resp}");
+        assert!(resp.ends_with("\r\n\r\nok"), "{resp}");
+        assert!(
+            resp.contains("x-content-type-options: nosniff\r\n"),
+            "{resp}"
+        );
+    }
+
+    let plain = &lower[0];
+    assert!(!plain.contains("server:"), "{plain}");
+    assert!(!plain.contains("x-powered-by:"), "{plain}");
+    assert!(plain.contains("x-served-by: ando\r\n"), "{plain}");
+    assert!(plain.contains("strict-transport-security:"), "{plain}");
+
+    let raw = &lower[1];
+    assert!(raw.contains("server: nginx/1.25\r\n"), "{raw}");
+    assert!(raw.contains("x-powered-by: php/8.2\r\n"), "{raw}");
+    assert!(!raw.contains("x-served-by:"), "{raw}");
+
+    // The route's config replaces the global one wholesale.
+    let custom = &lower[2];
+    assert!(custom.contains("server: nginx/1.25\r\n"), "{custom}");
+    assert!(!custom.contains("x-powered-by:"), "{custom}");
+    assert!(!custom.contains("x-served-by:"), "{custom}");
+}
+
+// ── limit-conn ─────────────────────────────────────────────────────────────
+
+/// Upstream that answers after `delay` and records the most requests it
+/// had in progress at once.
+fn spawn_gauged_upstream(
+    delay: std::time::Duration,
+) -> (std::net::SocketAddr, Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let current = Arc::new(AtomicUsize::new(0));
+    let peak = Arc::new(AtomicUsize::new(0));
+    let seen = Arc::clone(&peak);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            let (current, peak) = (Arc::clone(&current), Arc::clone(&peak));
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                let _ = stream.read(&mut buf);
+                let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(delay);
+                current.fetch_sub(1, Ordering::SeqCst);
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                );
+            });
+        }
+    });
+    (addr, seen)
+}
+
+fn limit_conn_worker(upstream: std::net::SocketAddr, limit: serde_json::Value) -> ProxyWorker {
+    let route: ando_core::route::Route = serde_json::from_value(serde_json::json!({
+        "id": "r-fragile",
+        "uri": "/fragile",
+        "plugins": { "limit-conn": limit },
+        "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+    }))
+    .unwrap();
+    let mut registry = PluginRegistry::new();
+    registry.register(Arc::new(
+        ando_plugins::traffic::limit_conn::LimitConnPlugin::new(),
+    ));
+    ProxyWorker::new(
+        Arc::new(Router::build(vec![route], 1).unwrap()),
+        Arc::new(registry),
+        ConfigCache::new(),
+    )
+}
+
+/// Send `n` concurrent GETs for `/fragile` and return the status codes in
+/// the order the responses completed.
+fn concurrent_gets(worker: ProxyWorker, n: usize) -> Vec<u16> {
+    // Queued requests are woken from the plugins' background runtime.
+    let mut rt = monoio::RuntimeBuilder::<monoio::FusionDriver>::new()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async move {
+        let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let proxy = Rc::new(RefCell::new(worker));
+        let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+        monoio::spawn(async move {
+            while let Ok((stream, peer)) = listener.accept().await {
+                monoio::spawn(handle_connection(
+                    stream,
+                    peer,
+                    Rc::clone(&proxy),
+                    Rc::clone(&pool),
+                ));
+            }
+        });
+        let statuses = Rc::new(RefCell::new(Vec::new()));
+        let clients: Vec<_> = (0..n)
+            .map(|_| {
+                let statuses = Rc::clone(&statuses);
+                monoio::spawn(async move {
+                    let mut client = monoio::net::TcpStream::connect(proxy_addr).await.unwrap();
+                    let req =
+                        b"GET /fragile HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n";
+                    let (res, _) = client.write_all(req.to_vec()).await;
+                    res.unwrap();
+                    let resp = read_until(&mut client, |_| false).await;
+                    statuses
+                        .borrow_mut()
+                        .push(resp[9..12].parse::<u16>().unwrap());
+                })
+            })
+            .collect();
+        for client in clients {
+            client.await;
+        }
+        statuses.take()
+    })
+}
+
+fn count(statuses: &[u16], status: u16) -> usize {
+    statuses.iter().filter(|s| **s == status).count()
+}
+
+#[test]
+fn limit_conn_caps_concurrent_requests_to_the_upstream() {
+    let (upstream, peak) = spawn_gauged_upstream(std::time::Duration::from_millis(200));
+    let worker = limit_conn_worker(upstream, serde_json::json!({ "conn": 2, "key": "route" }));
+    let statuses = concurrent_gets(worker, 5);
+    assert_eq!(count(&statuses, 200), 2, "{statuses:?}");
+    assert_eq!(count(&statuses, 503), 3, "{statuses:?}");
+    assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 2);
+}
+
+#[test]
+fn limit_conn_queues_a_burst_one_at_a_time() {
+    let (upstream, peak) = spawn_gauged_upstream(std::time::Duration::from_millis(30));
+    let worker = limit_conn_worker(
+        upstream,
+        serde_json::json!({ "conn": 1, "burst": 3, "default_conn_delay_ms": 2000, "key": "route" }),
+    );
+    let statuses = concurrent_gets(worker, 4);
+    assert_eq!(statuses, vec![200; 4]);
+    assert_eq!(peak.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[test]
+fn limit_conn_rejects_a_queued_request_after_its_delay() {
+    let (upstream, _) = spawn_gauged_upstream(std::time::Duration::from_millis(400));
+    let worker = limit_conn_worker(
+        upstream,
+        serde_json::json!({
+            "conn": 1, "burst": 1, "default_conn_delay_ms": 50,
+            "rejected_code": 429, "key": "route"
+        }),
+    );
+    let started = std::time::Instant::now();
+    let statuses = concurrent_gets(worker, 3);
+    // One request proxied, one timed out in the queue, one found it full;
+    // both rejections come back before the proxied response.
+    assert_eq!(statuses, vec![429, 429, 200]);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(400));
+}
+
+#[test]
+fn limit_conn_slot_is_freed_when_the_upstream_fails() {
+    let worker = limit_conn_worker(
+        dead_addr(),
+        serde_json::json!({ "conn": 1, "key": "route" }),
+    );
+    let responses = serve_requests(worker, &["/fragile", "/fragile", "/fragile"]);
+    for resp in &responses {
+        assert!(resp.starts_with("HTTP/1.1 502"), "{resp}");
+    }
+}
+
+// ── Client certificates (mTLS) ─────────────────────────────────────────────
+
+fn client_fingerprint() -> String {
+    use sha2::Digest;
+    sha2::Sha256::digest(fixture_der("client.crt"))
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// A TLS proxy serving `a.example.com`, whose SSL object verifies client
+/// certificates against `client-ca.crt` in `mode`, and `b.example.com`,
+/// which asks for none. `partner-b` owns the `client.crt` certificate by
+/// fingerprint and SAN.
+fn spawn_mtls_proxy(mode: &str) -> std::net::SocketAddr {
+    use ando_proxy::connection::handle_tls_connection;
+    use ando_proxy::tls::{CertResolver, TlsServer};
+
+    let upstream = spawn_status_upstream("200 OK", std::time::Duration::ZERO);
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let proxy_addr = listener.local_addr().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let certs = tls_certs();
+    certs.get_mut("a").unwrap().client_tls = Some(
+        serde_json::from_value(serde_json::json!({ "ca": fixture("client-ca.crt"), "mode": mode }))
+            .unwrap(),
+    );
+    let cache = ConfigCache::new();
+    cache.consumers.insert(
+        "partner-b".into(),
+        serde_json::from_value(serde_json::json!({
+            "username": "partner-b",
+            "plugins": { "mtls-auth": {
+                "fingerprint": client_fingerprint(), "san": "b.partners.example"
+            } }
+        }))
+        .unwrap(),
+    );
+    let route = |id: &str, plugins: serde_json::Value| {
+        serde_json::json!({
+            "id": id, "uri": format!("/{id}"), "plugins": plugins,
+            "upstream": { "nodes": { upstream.to_string(): 1 }, "type": "roundrobin" }
+        })
+    };
+    let echo = serde_json::json!({ "enabled_in_production": true });
+    let routes: Vec<ando_core::route::Route> = [
+        route("open", serde_json::json!({})),
+        route(
+            "by-fingerprint",
+            serde_json::json!({ "mtls-auth": { "consumer_by": "fingerprint" }, "debug-echo": echo }),
+        ),
+        route(
+            "by-san",
+            serde_json::json!({ "mtls-auth": { "consumer_by": "san" }, "debug-echo": echo }),
+        ),
+        route(
+            "acl",
+            serde_json::json!({
+                "mtls-auth": { "consumer_by": "fingerprint" },
+                "consumer-restriction": { "whitelist": ["partner-c"] }
+            }),
+        ),
+    ]
+    .into_iter()
+    .map(|v| serde_json::from_value(v).unwrap())
+    .collect();
+
+    std::thread::spawn(move || {
+        make_rt().block_on(async move {
+            let listener = monoio::net::TcpListener::from_std(listener).unwrap();
+            let mut registry = PluginRegistry::new();
+            ando_plugins::register_all(&mut registry);
+            let router = Arc::new(Router::build(routes, 1).unwrap());
+            let worker = ProxyWorker::new(router, Arc::new(registry), cache);
+            let proxy = Rc::new(RefCell::new(worker));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+            let tls = Arc::new(TlsServer::new(Arc::new(CertResolver::new(certs)), false));
+            while let Ok((stream, peer)) = listener.accept().await {
+                let (tls, proxy, pool) = (tls.clone(), proxy.clone(), pool.clone());
+                monoio::spawn(async move {
+                    let _ = handle_tls_connection(stream, peer, tls, proxy, pool).await;
+                });
+            }
+        });
+    });
+    proxy_addr
+}
+
+/// GET `path` over TLS with `sni`, presenting the certificate `client`
+/// (a fixture name) if given; returns the response, or an empty string if
+/// the handshake failed.
+fn mtls_get(addr: std::net::SocketAddr, sni: &str, client: Option<&str>, path: &str) -> String {
+    let mut roots = rustls::RootCertStore::empty();
+    for name in ["a.crt", "b.crt"] {
+        roots.add(&rustls::Certificate(fixture_der(name))).unwrap();
+    }
+    let builder = rustls::ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots);
+    let config = match client {
+        Some(name) => builder
+            .with_client_auth_cert(
+                vec![rustls::Certificate(fixture_der(&format!("{name}.crt")))],
+                rustls::PrivateKey(fixture_der(&format!("{name}.key"))),
+            )
+            .unwrap(),
+        None => builder.with_no_client_auth(),
+    };
+    let sni = sni.to_string();
+    let path = path.to_string();
+    make_rt().block_on(async move {
+        let connector = monoio_rustls::TlsConnector::from(config);
+        let tcp = monoio::net::TcpStream::connect(addr).await.unwrap();
+        let name = rustls::ServerName::try_from(sni.as_str()).unwrap();
+        let Ok(mut stream) = connector.connect(name, tcp).await else {
+            return String::new();
+        };
+        let req = format!("GET {path} HTTP/1.1\r\nhost: {sni}\r\nconnection: close\r\n\r\n");
+        let (res, _) = stream.write_all(req.into_bytes()).await;
+        res.unwrap();
+        let mut resp = Vec::new();
+        loop {
+            let (n, buf) = stream.read(vec![0u8; 4096]).await;
+            match n {
+                Ok(0) | Err(_) => break,
+                Ok(n) => resp.extend_from_slice(&buf[..n]),
+            }
+        }
+        String::from_utf8(resp).unwrap()
+    })
+}
+
+fn echoed_consumer(resp: &str) -> serde_json::Value {
+    let body = resp.split("\r\n\r\n").nth(1).unwrap();
+    serde_json::from_str::<serde_json::Value>(body).unwrap()["consumer"].clone()
+}
+
+#[test]
+fn required_client_certificate_is_enforced_per_sni() {
+    let addr = spawn_mtls_proxy("required");
+    let a = "a.example.com";
+
+    let resp = mtls_get(addr, a, Some("client"), "/open");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let resp = mtls_get(addr, a, Some("client"), "/by-fingerprint");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    assert_eq!(echoed_consumer(&resp), "partner-b");
+
+    for client in [None, Some("rogue")] {
+        let resp = mtls_get(addr, a, client, "/open");
+        assert!(resp.starts_with("HTTP/1.1 400"), "{client:?}: {resp}");
+        assert!(resp.contains("client certificate required"), "{resp}");
+    }
+
+    // The other SSL object asks for no certificate.
+    let resp = mtls_get(addr, "b.example.com", None, "/open");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+}
+
+#[test]
+fn optional_client_certificate_leaves_routes_to_mtls_auth() {
+    let addr = spawn_mtls_proxy("optional");
+    let a = "a.example.com";
+
+    let resp = mtls_get(addr, a, None, "/open");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let resp = mtls_get(addr, a, None, "/by-fingerprint");
+    assert!(resp.starts_with("HTTP/1.1 401"), "{resp}");
+    assert!(resp.contains("Missing client certificate"), "{resp}");
+    let resp = mtls_get(addr, a, Some("rogue"), "/by-san");
+    assert!(resp.starts_with("HTTP/1.1 401"), "{resp}");
+    assert!(resp.contains("Invalid client certificate"), "{resp}");
+
+    let resp = mtls_get(addr, a, Some("client"), "/by-san");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    assert_eq!(echoed_consumer(&resp), "partner-b");
+    // The mapped consumer is what consumer ACLs see.
+    let resp = mtls_get(addr, a, Some("client"), "/acl");
+    assert!(resp.starts_with("HTTP/1.1 403"), "{resp}");
+}
+
+// ── PROXY protocol ─────────────────────────────────────────────────────────
+
+#[test]
+fn proxy_protocol_listener_takes_the_client_from_the_header() {
+    use ando_core::config::{ListenerProtocol, ProxyListener};
+    use ando_proxy::listener::WorkerListeners;
+    use ando_proxy::worker::{SharedState, spawn_workers_on};
+    use std::io::{Read, Write};
+
+    let (upstream, seen) = spawn_capturing_upstream();
+    let route = serde_json::from_value(serde_json::json!({
+        "id": "behind-lb",
+        "uri": "/who",
+        "upstream": {"nodes": {upstream.to_string(): 1}},
+    }))
+    .unwrap();
+    let mut config = ando_core::config::GatewayConfig::default();
+    config.proxy.listeners = vec![ProxyListener {
+        name: "behind-lb".to_string(),
+        addr: "127.0.0.1:0".to_string(),
+        protocol: ListenerProtocol::Http,
+        tls: Default::default(),
+        proxy_protocol: Some(true),
+    }];
+    config.proxy.reuse_port = false;
+    let bound = WorkerListeners::bind(&config.proxy, 1).unwrap();
+    let addr = bound.listeners[0].sockets[0].local_addr().unwrap();
+    let shared = SharedState::new(
+        Router::build(vec![route], 1).unwrap(),
+        PluginRegistry::new(),
+        ConfigCache::new(),
+        config,
+    );
+    spawn_workers_on(Arc::clone(&shared), bound);
+
+    let send = |preamble: &[u8]| {
+        let mut client = std::net::TcpStream::connect(addr).unwrap();
+        client.write_all(preamble).unwrap();
+        client
+            .write_all(b"GET /who HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        let mut resp = String::new();
+        let _ = client.read_to_string(&mut resp);
+        resp
+    };
+
+    let resp = send(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 80\r\n");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let req = String::from_utf8(seen.recv().unwrap())
+        .unwrap()
+        .to_lowercase();
+    assert!(req.contains("x-real-ip: 203.0.113.7\r\n"), "{req}");
+
+    let mut v2 = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    v2.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c]);
+    v2.extend_from_slice(&[198, 51, 100, 9, 10, 0, 0, 1, 0x30, 0x39, 0x00, 0x50]);
+    let resp = send(&v2);
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let req = String::from_utf8(seen.recv().unwrap())
+        .unwrap()
+        .to_lowercase();
+    assert!(req.contains("x-real-ip: 198.51.100.9\r\n"), "{req}");
+
+    // A LOCAL header (a load balancer's health check) keeps the peer.
+    let mut local = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+    local.extend_from_slice(&[0x20, 0x00, 0x00, 0x00]);
+    let resp = send(&local);
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+    let req = String::from_utf8(seen.recv().unwrap())
+        .unwrap()
+        .to_lowercase();
+    assert!(req.contains("x-real-ip: 127.0.0.1\r\n"), "{req}");
+
+    // Without a valid header the connection is closed unanswered and
+    // nothing reaches the upstream.
+    for bad in [&b""[..], b"PROXY TCP4 nope\r\n", b"PROXY TCP9 1 2 3 4\r\n"] {
+        assert_eq!(send(bad), "", "{}", String::from_utf8_lossy(bad));
+    }
+    assert!(
+        seen.recv_timeout(std::time::Duration::from_millis(200))
+            .is_err()
+    );
+}
+
+/// An upstream that reads the PROXY protocol header off each connection,
+/// passes it to the returned receiver and answers the request that
+/// follows with 200.
+fn spawn_proxy_protocol_upstream() -> (std::net::SocketAddr, std::sync::mpsc::Receiver<Vec<u8>>) {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            // v1 ends at its LF; v2 gives its length in bytes 14-15.
+            let mut header = vec![0u8; 16];
+            if stream.read_exact(&mut header).is_err() {
+                continue;
+            }
+            let mut rest = if header.starts_with(b"PROXY ") {
+                let mut byte = [0u8; 1];
+                while header.last() != Some(&b'\n') && stream.read_exact(&mut byte).is_ok() {
+                    header.push(byte[0]);
+                }
+                vec![]
+            } else {
+                vec![0u8; u16::from_be_bytes([header[14], header[15]]) as usize]
+            };
+            let _ = stream.read_exact(&mut rest);
+            header.extend_from_slice(&rest);
+            let _ = tx.send(header);
+            let mut head = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => head.extend_from_slice(&buf[..n]),
+                }
+            }
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok");
+        }
+    });
+    (addr, rx)
+}
+
+#[test]
+fn send_proxy_protocol_starts_upstream_connections_with_the_header() {
+    for version in ["v1", "v2"] {
+        // Both ends are 127.0.0.1; the ports are only known once bound.
+        let expected = |client: u16, gateway: u16| match version {
+            "v1" => format!("PROXY TCP4 127.0.0.1 127.0.0.1 {client} {gateway}\r\n").into_bytes(),
+            _ => {
+                let mut v2 = b"\r\n\r\n\0\r\nQUIT\n".to_vec();
+                v2.extend_from_slice(&[0x21, 0x11, 0x00, 0x0c, 127, 0, 0, 1, 127, 0, 0, 1]);
+                v2.extend_from_slice(&client.to_be_bytes());
+                v2.extend_from_slice(&gateway.to_be_bytes());
+                v2
+            }
+        };
+        let (upstream, headers) = spawn_proxy_protocol_upstream();
+        let worker = make_worker(vec![serde_json::json!({
+            "id": "pp", "uri": "/pp",
+            "upstream": {
+                "nodes": { upstream.to_string(): 1 },
+                "send_proxy_protocol": version
+            }
+        })]);
+
+        let (resp, client, gateway) = make_rt().block_on(async move {
+            let listener = monoio::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let gateway = listener.local_addr().unwrap();
+            let proxy = Rc::new(RefCell::new(worker));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+            monoio::spawn(async move {
+                if let Ok((stream, peer)) = listener.accept().await {
+                    let _ = handle_connection(stream, peer, proxy, pool).await;
+                }
+            });
+            let mut client = monoio::net::TcpStream::connect(gateway).await.unwrap();
+            let local = client.local_addr().unwrap();
+            let req = b"GET /pp HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n".to_vec();
+            let (res, _) = client.write_all(req).await;
+            res.unwrap();
+            (read_until(&mut client, |_| false).await, local, gateway)
+        });
+        assert!(resp.starts_with("HTTP/1.1 200"), "{version}: {resp}");
+        assert_eq!(
+            headers.recv().unwrap(),
+            expected(client.port(), gateway.port()),
+            "{version}"
+        );
+    }
+}
@@ -0,0 +1,351 @@
+//! gRPC through the gateway: a tonic client speaks HTTP/2 over TLS to the
+//! proxy, which forwards to a tonic server on a `grpc` upstream.
+//!
+//! The proxy runs on its own monoio thread, the tonic server and client
+//! on tokio.
+
+// `tonic::Status` is the error type of every call here.
+#![allow(clippy::result_large_err)]
+
+use ando_core::router::Router;
+use ando_observability::metrics::MetricsCollector;
+use ando_plugin::registry::PluginRegistry;
+use ando_proxy::connection::handle_tls_connection;
+use ando_proxy::proxy::{ConnPool, ProxyWorker};
+use ando_proxy::tls::{CertResolver, TlsServer};
+use ando_store::cache::ConfigCache;
+use hyper_util::rt::TokioIo;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::codec::ProstCodec;
+use tonic::codegen::{BoxFuture, Context, Poll, Service, StdError, http};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tonic::{Code, Status};
+
+// ── Test service ─────────────────────────────────────────────────────────
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct Msg {
+    #[prost(string, tag = "1")]
+    text: String,
+}
+
+/// `test.Echo`: unary `Say` echoes its text (or fails for "fail"),
+/// server-streaming `Count` sends 1..=n.
+#[derive(Clone)]
+struct Echo;
+
+impl tonic::server::NamedService for Echo {
+    const NAME: &'static str = "test.Echo";
+}
+
+struct Say;
+
+impl tonic::server::UnaryService<Msg> for Say {
+    type Response = Msg;
+    type Future = BoxFuture<tonic::Response<Msg>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Msg>) -> Self::Future {
+        let text = request.into_inner().text;
+        Box::pin(async move {
+            if text == "fail" {
+                return Err(Status::not_found("no such greeting"));
+            }
+            Ok(tonic::Response::new(Msg {
+                text: format!("echo: {text}"),
+            }))
+        })
+    }
+}
+
+struct Count;
+
+impl tonic::server::ServerStreamingService<Msg> for Count {
+    type Response = Msg;
+    type ResponseStream = tokio_stream::Iter<std::vec::IntoIter<Result<Msg, Status>>>;
+    type Future = BoxFuture<tonic::Response<Self::ResponseStream>, Status>;
+
+    fn call(&mut self, request: tonic::Request<Msg>) -> Self::Future {
+        let n: usize = request.into_inner().text.parse().unwrap_or(0);
+        let items: Vec<_> = (1..=n)
+            .map(|i| {
+                Ok(Msg {
+                    text: i.to_string(),
+                })
+            })
+            .collect();
+        Box::pin(async move { Ok(tonic::Response::new(tokio_stream::iter(items))) })
+    }
+}
+
+impl<B> Service<http::Request<B>> for Echo
+where
+    B: tonic::codegen::Body + Send + 'static,
+    B::Error: Into<StdError> + Send + 'static,
+{
+    type Response = http::Response<tonic::body::BoxBody>;
+    type Error = std::convert::Infallible;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        let mut grpc = tonic::server::Grpc::new(ProstCodec::<Msg, Msg>::default());
+        match req.uri().path() {
+            "/test.Echo/Say" => Box::pin(async move { Ok(grpc.unary(Say, req).await) }),
+            "/test.Echo/Count" => {
+                Box::pin(async move { Ok(grpc.server_streaming(Count, req).await) })
+            }
+            _ => Box::pin(async { Ok(Status::unimplemented("").into_http()) }),
+        }
+    }
+}
+
+/// Start the tonic server on its own thread.
+fn spawn_grpc_upstream() -> std::net::SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            tonic::transport::Server::builder()
+                .add_service(Echo)
+                .serve_with_incoming(tokio_stream::wrappers::TcpListenerStream::new(listener))
+                .await
+                .unwrap();
+        });
+    });
+    addr
+}
+
+// ── Gateway ──────────────────────────────────────────────────────────────
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!(
+        "{}/tests/fixtures/tls/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+fn grpc_route(upstream: std::net::SocketAddr) -> serde_json::Value {
+    serde_json::json!({
+        "id": "r-grpc",
+        "uri": "/test.Echo/*",
+        "hosts": ["a.example.com"],
+        "upstream": {
+            "nodes": { upstream.to_string(): 1 },
+            "type": "roundrobin",
+            "scheme": "grpc"
+        }
+    })
+}
+
+/// Start a TLS proxy offering h2 for `route`, flushing its metrics to
+/// `metrics` every few milliseconds.
+fn spawn_gateway(route: serde_json::Value, metrics: Arc<MetricsCollector>) -> std::net::SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let mut rt = monoio::RuntimeBuilder::<monoio::LegacyDriver>::new()
+            .enable_timer()
+            .build()
+            .unwrap();
+        rt.block_on(async move {
+            let listener = monoio::net::TcpListener::from_std(listener).unwrap();
+            let route = serde_json::from_value(route).unwrap();
+            let router = Arc::new(Router::build(vec![route], 1).unwrap());
+            let worker =
+                ProxyWorker::new(router, Arc::new(PluginRegistry::new()), ConfigCache::new())
+                    .with_metrics(metrics);
+            let proxy = Rc::new(RefCell::new(worker));
+            let pool = Rc::new(RefCell::new(ConnPool::new(4)));
+
+            let certs = Arc::new(dashmap::DashMap::new());
+            certs.insert(
+                "a".to_string(),
+                ando_core::ssl::SslCertificate {
+                    id: "a".into(),
+                    cert: fixture("a.crt"),
+                    key: fixture("a.key"),
+                    snis: vec!["a.example.com".into()],
+                    status: 1,
+                    client_tls: None,
+                },
+            );
+            let tls = Arc::new(TlsServer::new(Arc::new(CertResolver::new(certs)), true));
+
+            monoio::spawn({
+                let (proxy, pool) = (proxy.clone(), pool.clone());
+                async move {
+                    loop {
+                        monoio::time::sleep(Duration::from_millis(10)).await;
+                        proxy.borrow_mut().flush_metrics(&pool.borrow());
+                    }
+                }
+            });
+            while let Ok((stream, peer)) = listener.accept().await {
+                let (tls, proxy, pool) = (tls.clone(), proxy.clone(), pool.clone());
+                monoio::spawn(async move {
+                    let _ = handle_tls_connection(stream, peer, tls, proxy, pool).await;
+                });
+            }
+        });
+    });
+    addr
+}
+
+// ── Client ───────────────────────────────────────────────────────────────
+
+/// Connects to the gateway over TLS with SNI `a.example.com`, offering h2.
+#[derive(Clone)]
+struct GatewayConnector(std::net::SocketAddr);
+
+impl Service<Uri> for GatewayConnector {
+    type Response = TokioIo<tokio_rustls::client::TlsStream<tokio::net::TcpStream>>;
+    type Error = std::io::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let addr = self.0;
+        Box::pin(async move {
+            use tokio_rustls::rustls;
+            let mut roots = rustls::RootCertStore::empty();
+            let der = pem::parse(fixture("a.crt")).unwrap().into_contents();
+            roots.add(der.into()).unwrap();
+            let mut config = rustls::ClientConfig::builder()
+                .with_root_certificates(roots)
+                .with_no_client_auth();
+            config.alpn_protocols = vec![b"h2".to_vec()];
+            let tcp = tokio::net::TcpStream::connect(addr).await?;
+            let name = rustls::pki_types::ServerName::try_from("a.example.com").unwrap();
+            let tls = tokio_rustls::TlsConnector::from(Arc::new(config))
+                .connect(name, tcp)
+                .await?;
+            Ok(TokioIo::new(tls))
+        })
+    }
+}
+
+fn channel(gateway: std::net::SocketAddr) -> Channel {
+    Endpoint::from_static("http://a.example.com")
+        .connect_with_connector_lazy(GatewayConnector(gateway))
+}
+
+async fn say(channel: &Channel, text: &str) -> Result<String, Status> {
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.unwrap();
+    let path = http::uri::PathAndQuery::from_static("/test.Echo/Say");
+    let request = tonic::Request::new(Msg { text: text.into() });
+    let response = grpc
+        .unary(request, path, ProstCodec::<Msg, Msg>::default())
+        .await?;
+    Ok(response.into_inner().text)
+}
+
+async fn count(channel: &Channel, n: usize) -> Result<Vec<String>, Status> {
+    let mut grpc = tonic::client::Grpc::new(channel.clone());
+    grpc.ready().await.unwrap();
+    let path = http::uri::PathAndQuery::from_static("/test.Echo/Count");
+    let request = tonic::Request::new(Msg {
+        text: n.to_string(),
+    });
+    let mut stream = grpc
+        .server_streaming(request, path, ProstCodec::<Msg, Msg>::default())
+        .await?
+        .into_inner();
+    let mut items = Vec::new();
+    while let Some(msg) = stream.message().await? {
+        items.push(msg.text);
+    }
+    Ok(items)
+}
+
+fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// Wait for the gateway's next metrics flush to contain `series`.
+fn await_series(metrics: &MetricsCollector, series: &str) {
+    for _ in 0..200 {
+        if metrics.render().contains(series) {
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    panic!("missing {series} in:\n{}", metrics.render());
+}
+
+// ── Tests ────────────────────────────────────────────────────────────────
+
+#[test]
+fn unary_call_and_error_status_pass_through() {
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    let gateway = spawn_gateway(grpc_route(spawn_grpc_upstream()), Arc::clone(&metrics));
+    block_on(async {
+        let channel = channel(gateway);
+        assert_eq!(say(&channel, "hi").await.unwrap(), "echo: hi");
+
+        let err = say(&channel, "fail").await.unwrap_err();
+        assert_eq!(err.code(), Code::NotFound);
+        assert_eq!(err.message(), "no such greeting");
+    });
+    // NOT_FOUND is recorded as its HTTP equivalent, not the 200 on the wire.
+    await_series(
+        &metrics,
+        r#"ando_http_requests_total{method="POST",route="r-grpc",status="200"} 1"#,
+    );
+    await_series(
+        &metrics,
+        r#"ando_http_requests_total{method="POST",route="r-grpc",status="404"} 1"#,
+    );
+}
+
+#[test]
+fn server_streaming_call_relays_every_message_and_trailers() {
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    let gateway = spawn_gateway(grpc_route(spawn_grpc_upstream()), metrics);
+    block_on(async {
+        let channel = channel(gateway);
+        assert_eq!(count(&channel, 3).await.unwrap(), ["1", "2", "3"]);
+        assert_eq!(count(&channel, 0).await.unwrap(), Vec::<String>::new());
+        // The upstream connection is shared by later calls.
+        assert_eq!(count(&channel, 2).await.unwrap(), ["1", "2"]);
+    });
+}
+
+#[test]
+fn unreachable_upstream_answers_unavailable() {
+    let dead = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let metrics = Arc::new(MetricsCollector::new(true).unwrap());
+    let gateway = spawn_gateway(grpc_route(dead), Arc::clone(&metrics));
+    block_on(async {
+        let err = say(&channel(gateway), "hi").await.unwrap_err();
+        assert_eq!(err.code(), Code::Unavailable);
+    });
+    await_series(
+        &metrics,
+        r#"ando_http_requests_total{method="POST",route="r-grpc",status="503"} 1"#,
+    );
+}
@@ -15,7 +15,7 @@
 use ando_core::config::GatewayConfig;
 use ando_core::consumer::Consumer;
 use ando_core::route::Route;
-use ando_core::router::Router;
+use ando_core::router::{MatchContext, Router};
 use ando_core::upstream::Upstream;
 use ando_plugin::registry::PluginRegistry;
 use ando_plugins::register_all;
@@ -87,7 +87,7 @@ fn route_in_cache_is_matched_by_router() {
     let routes = cache.all_routes();
     let router = Router::build(routes, 1).unwrap();
 
-    let matched = router.match_route("GET", "/hello", None);
+    let matched = router.match_route("GET", "/hello", None, &MatchContext::default());
     assert!(matched.is_some(), "Route /hello should match");
     assert_eq!(matched.unwrap().id, "r1");
 }
@@ -115,7 +115,7 @@ fn consumer_key_lookup_after_index_rebuild() {
         "alice".into(),
         make_consumer_entry("alice", "secret-key-123"),
     );
-    cache.rebuild_consumer_key_index();
+    cache.reindex_consumers();
 
     let username = cache.find_consumer_by_key("secret-key-123");
     assert_eq!(username.as_deref(), Some("alice"));
@@ -126,7 +126,7 @@ fn consumer_key_lookup_after_index_rebuild() {
 #[test]
 fn consumer_key_unknown_returns_none() {
     let cache = ConfigCache::new();
-    cache.rebuild_consumer_key_index();
+    cache.reindex_consumers();
     assert!(cache.find_consumer_by_key("nonexistent").is_none());
 }
 
@@ -143,7 +143,9 @@ fn disabled_route_is_not_matched_by_router() {
     let router = Router::build(routes, 1).unwrap();
 
     assert!(
-        router.match_route("GET", "/off", None).is_none(),
+        router
+            .match_route("GET", "/off", None, &MatchContext::default())
+            .is_none(),
         "Disabled route must not match"
     );
 }
@@ -171,10 +173,34 @@ fn plugin_registry_has_all_plugins_after_register_all() {
         "key-auth",
         "basic-auth",
         "jwt-auth",
+        "hmac-auth",
+        "mtls-auth",
+        "consumer-restriction",
+        "openid-connect",
+        "real-ip",
+        "fault-injection",
+        "client-control",
         "ip-restriction",
+        "ua-restriction",
+        "referer-restriction",
+        "time-restriction",
+        "request-validation",
         "rate-limiting",
+        "limit-req",
+        "limit-conn",
+        "api-breaker",
+        "mock",
+        "proxy-cache",
+        "proxy-mirror",
+        "traffic-split",
+        "body-routing",
+        "request-transformer",
+        "debug-echo",
         "cors",
         "security-headers",
+        "response-headers-policy",
+        "response-transformer",
+        "error-page",
     ];
     for name in &expected {
         assert!(
@@ -205,7 +231,11 @@ fn shared_state_provides_consistent_view() {
 
     // Router should match /api
     let current_router = shared.router.load();
-    assert!(current_router.match_route("GET", "/api", None).is_some());
+    assert!(
+        current_router
+            .match_route("GET", "/api", None, &MatchContext::default())
+            .is_some()
+    );
 
     // Cache upstream accessible
     assert!(shared.config_cache.upstreams.get("up1").is_some());
@@ -220,13 +250,29 @@ fn hot_arcswap_router_swap_is_immediately_visible() {
 
     let swap = Arc::new(ArcSwap::new(Arc::new(router_v1)));
 
-    assert!(swap.load().match_route("GET", "/v1", None).is_some());
-    assert!(swap.load().match_route("GET", "/v2", None).is_none());
+    assert!(
+        swap.load()
+            .match_route("GET", "/v1", None, &MatchContext::default())
+            .is_some()
+    );
+    assert!(
+        swap.load()
+            .match_route("GET", "/v2", None, &MatchContext::default())
+            .is_none()
+    );
 
     swap.store(Arc::new(router_v2));
 
-    assert!(swap.load().match_route("GET", "/v1", None).is_none());
-    assert!(swap.load().match_route("GET", "/v2", None).is_some());
+    assert!(
+        swap.load()
+            .match_route("GET", "/v1", None, &MatchContext::default())
+            .is_none()
+    );
+    assert!(
+        swap.load()
+            .match_route("GET", "/v2", None, &MatchContext::default())
+            .is_some()
+    );
 }
 
 // ── Test 10: method-specific route only matches correct method ────────────────
@@ -242,7 +288,19 @@ fn method_specific_route_only_matches_correct_method() {
     let routes = cache.all_routes();
     let router = Router::build(routes, 1).unwrap();
 
-    assert!(router.match_route("GET", "/resource", None).is_some());
-    assert!(router.match_route("POST", "/resource", None).is_none());
-    assert!(router.match_route("DELETE", "/resource", None).is_none());
+    assert!(
+        router
+            .match_route("GET", "/resource", None, &MatchContext::default())
+            .is_some()
+    );
+    assert!(
+        router
+            .match_route("POST", "/resource", None, &MatchContext::default())
+            .is_none()
+    );
+    assert!(
+        router
+            .match_route("DELETE", "/resource", None, &MatchContext::default())
+            .is_none()
+    );
 }
@@ -3,27 +3,41 @@
 //
 //  Architecture: monoio thread-per-core + shared-nothing data plane
 //  Admin API:    axum on dedicated tokio thread
-//  Config:       standalone YAML / etcd with watch
+//  Config:       standalone YAML / etcd or SQL with watch
 // ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━
 
+mod upgrade;
+
 #[global_allocator]
 static GLOBAL: tikv_jemallocator::Jemalloc = tikv_jemallocator::Jemalloc;
 
-use ando_core::config::GatewayConfig;
+use ando_admin::audit::AuditSink;
+use ando_admin::auth::{AdminAuth, KeyReloader};
+use ando_core::config::{DeploymentConfig, DeploymentMode, GatewayConfig};
 use ando_core::router::Router;
 use ando_plugin::registry::PluginRegistry;
+use ando_proxy::listener::{LISTEN_FDS_ENV, ListenerFds, WorkerListeners};
 use ando_proxy::worker::{self, SharedState};
 use ando_store::cache::ConfigCache;
+use ando_store::etcd::EtcdStore;
+use ando_store::sql::SqlStore;
+use ando_store::standalone::FileProvider;
+use ando_store::store::ConfigStore;
+use anyhow::Context;
 use clap::Parser;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 use tokio::sync::Notify;
-use tracing::info;
+use tracing::{error, info, warn};
 
 /// Global shutdown flag — checked by signal handler.
 static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 
+/// Set by SIGUSR2: hand the listeners to a new binary, then drain.
+static UPGRADE: AtomicBool = AtomicBool::new(false);
+
 #[derive(Parser, Debug)]
 #[command(name = "ando", version, about = "Ando CE — Zero-Overhead API Gateway")]
 struct Cli {
@@ -39,11 +53,28 @@ struct Cli {
     /// Data written via the Admin API is saved here and reloaded on restart.
     #[arg(long, default_value = "data/ando-state.json")]
     state_file: PathBuf,
+
+    /// Take over the listening sockets of the gateway that started this
+    /// process for a binary upgrade (SIGUSR2), instead of binding them.
+    #[arg(long)]
+    upgrade: bool,
+
+    /// Load and validate the config file strictly, print the effective
+    /// config with secrets masked, and exit without starting the gateway.
+    #[arg(long)]
+    check_config: bool,
 }
 
 fn main() -> anyhow::Result<()> {
+    let started = Instant::now();
     let cli = Cli::parse();
 
+    if cli.check_config {
+        let config = GatewayConfig::load_strict(&cli.config)?;
+        print!("{}", config.masked_yaml()?);
+        return Ok(());
+    }
+
     // ── Tracing ──
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -79,13 +110,39 @@ fn main() -> anyhow::Result<()> {
     // ── Plugin registry ──
     let mut registry = PluginRegistry::new();
     ando_plugins::register_all(&mut registry);
+    ando_plugin::external::load_dirs(&mut registry, &config.plugins.external_dirs);
     info!(plugins = registry.len(), "Plugins registered");
+    for (name, plugin_config) in &config.plugins.global {
+        match registry.get(name) {
+            Some(plugin) => {
+                plugin.configure(plugin_config).map_err(|e| {
+                    anyhow::anyhow!("plugins.global: invalid config for plugin `{name}`: {e}")
+                })?;
+            }
+            None => warn!(plugin = %name, "plugins.global: unknown plugin is ignored"),
+        }
+    }
 
     // ── Config cache ──
     let cache = ConfigCache::new();
 
+    // ── Standalone config file (replaces the Admin API state file) ──
+    let config_file = match config.deployment.mode {
+        DeploymentMode::Standalone => config.deployment.standalone.config_path.clone(),
+        DeploymentMode::Etcd | DeploymentMode::Sql => None,
+    };
+
+    // ── Config store (etcd / SQL; replaces the Admin API state file) ──
+    let store = connect_store(&config.deployment)?;
+
     // ── Restore persisted state (routes / upstreams / consumers) ──
-    ando_admin::persist::load_state(&cli.state_file, &cache);
+    match store {
+        Some((ref rt, ref store)) => {
+            rt.block_on(ando_store::store::load(store.as_ref(), &cache))?
+        }
+        None if config_file.is_none() => ando_admin::persist::load_state(&cli.state_file, &cache),
+        None => {}
+    }
 
     // ── Initial router (built from persisted routes, or empty) ──
     let initial_routes = cache.all_routes();
@@ -94,6 +151,79 @@ fn main() -> anyhow::Result<()> {
     // ── Shared state ──
     let shared = SharedState::new(router, registry, cache.clone(), config.clone());
 
+    let _config_file_handle = match config_file {
+        Some(ref path) => {
+            let registry = Arc::clone(&shared.plugin_registry);
+            let listeners = config.proxy.listener_names();
+            let mut provider = FileProvider::new(path, cache.clone(), Arc::clone(&shared.router))
+                .with_route_check(Box::new(move |route| {
+                    if let Some(name) = route.listeners.iter().find(|n| !listeners.contains(n)) {
+                        return Err(format!("unknown listener `{name}`"));
+                    }
+                    for (name, plugin_config) in &route.plugins {
+                        if let Some(plugin) = registry.get(name)
+                            && !ando_plugin::plugin::is_disabled(plugin_config)
+                        {
+                            plugin
+                                .configure(plugin_config)
+                                .map_err(|e| format!("invalid config for plugin `{name}`: {e}"))?;
+                        }
+                    }
+                    Ok(())
+                }));
+            provider.load()?;
+            let interval = Duration::from_millis(config.deployment.standalone.poll_interval_ms);
+            Some(provider.spawn(interval))
+        }
+        None => None,
+    };
+
+    // ── Config store watcher (keeps cache and router in sync) ──
+    let (store_changes, _store_changes_rx) = crossbeam_channel::bounded(1);
+    if let Some((ref rt, ref store)) = store {
+        let store = Arc::clone(store);
+        let (cache, router) = (cache.clone(), Arc::clone(&shared.router));
+        rt.spawn(async move {
+            if let Err(e) = store.watch(cache, router, store_changes).await {
+                tracing::error!(error = %e, "Config store watcher stopped");
+            }
+        });
+    }
+
+    // ── Admin API keys (re-read from the config file while running) ──
+    let admin_auth = AdminAuth::from_config(&config.admin);
+    if !admin_auth.is_enabled() {
+        warn!("No admin.api_keys configured — Admin API is unauthenticated");
+    }
+    let plain_keys = cache
+        .ssl_certs
+        .iter()
+        .filter(|s| !s.key_is_encrypted())
+        .count();
+    if plain_keys > 0 {
+        warn!(
+            certificates = plain_keys,
+            "SSL private keys are stored unencrypted — set ssl.master_key or ANDO_SSL_MASTER_KEY and re-save them"
+        );
+    }
+    let _key_reloader_handle = cli
+        .config
+        .exists()
+        .then(|| KeyReloader::new(&cli.config, admin_auth.clone()).spawn(Duration::from_secs(2)));
+
+    // ── Listening sockets (inherited in a binary upgrade) ──
+    let listeners = if cli.upgrade {
+        let fds: ListenerFds = std::env::var(LISTEN_FDS_ENV)
+            .with_context(|| format!("--upgrade: {LISTEN_FDS_ENV} is not set"))?
+            .parse()?;
+        info!(%fds, "Upgrade: taking over listening sockets");
+        WorkerListeners::inherit(&fds, &config.proxy, num_workers)?
+    } else {
+        WorkerListeners::bind(&config.proxy, num_workers)
+            .context("Failed to bind the proxy listeners")?
+    };
+    let listener_fds = listeners.fds();
+
     // ── Admin API state ──
     let config_changed = Arc::new(Notify::new());
     let admin_state = Arc::new(ando_admin::server::AdminState {
@@ -101,14 +231,32 @@ fn main() -> anyhow::Result<()> {
         router_swap: Arc::clone(&shared.router),
         plugin_registry: Arc::clone(&shared.plugin_registry),
         config_changed: config_changed.clone(),
-        state_file: Some(cli.state_file.clone()),
+        state_file: (config_file.is_none() && store.is_none()).then(|| cli.state_file.clone()),
         edition: "community",
+        read_only: config_file.is_some(),
+        auth: admin_auth,
+        audit: AuditSink::from_config(&config.compliance.audit_log)?,
+        metrics: Some(Arc::clone(&shared.metrics)),
+        workers: Some(Arc::clone(&shared.workers)),
+        started,
+        shutdown: Some(Arc::clone(&shared.shutdown)),
+        listener: Some(listeners.options.clone()),
+        listeners: Some(config.proxy.listener_names()),
+        ssl: config.ssl.clone(),
+        store: store.as_ref().map(|(_, store)| Arc::clone(store)),
     });
 
     // ── Start admin API on a dedicated tokio thread ──
     let admin_config = config.admin.clone();
     if admin_config.enabled {
         let admin_state = Arc::clone(&admin_state);
+        // The previous process serves the Admin API until it has drained.
+        let bind_wait = if cli.upgrade {
+            Duration::from_millis(config.proxy.graceful_shutdown_timeout_ms)
+                + Duration::from_secs(5)
+        } else {
+            Duration::ZERO
+        };
         std::thread::Builder::new()
             .name("ando-admin".to_string())
             .spawn(move || {
@@ -118,7 +266,12 @@ fn main() -> anyhow::Result<()> {
                     .expect("Failed to build tokio runtime for admin");
 
                 rt.block_on(async {
-                    if let Err(e) = ando_admin::server::start_admin(admin_config, admin_state).await
+                    if let Err(e) = ando_admin::server::start_admin_when_free(
+                        admin_config,
+                        admin_state,
+                        bind_wait,
+                    )
+                    .await
                     {
                         tracing::error!(error = %e, "Admin API failed");
                     }
@@ -129,12 +282,28 @@ fn main() -> anyhow::Result<()> {
         info!(addr = %config.admin.addr, "Admin API started");
     }
 
+    // ── Active upstream health checks ──
+    let _health_handle = ando_proxy::health_check::spawn_health_checker(cache.clone());
+
+    // ── Service discovery ──
+    let _k8s_handle = config.discovery.kubernetes.as_ref().and_then(|k8s| {
+        ando_proxy::kubernetes::spawn_kubernetes_discovery(cache.discovery.clone(), k8s)
+            .inspect_err(|e| tracing::error!(error = %e, "Kubernetes discovery not started"))
+            .ok()
+    });
+
+    // ── Upstream hostname re-resolution ──
+    let _dns_handle = ando_proxy::dns::spawn_dns_refresher(
+        cache.clone(),
+        Duration::from_millis(config.proxy.dns_refresh_interval_ms),
+    );
+
     // ── Spawn monoio worker threads ──
-    let worker_handles = worker::spawn_workers(Arc::clone(&shared), num_workers);
+    let worker_handles = worker::spawn_workers_on(Arc::clone(&shared), listeners);
 
     info!(
         workers = num_workers,
-        proxy_addr = %config.proxy.http_addr,
+        listeners = ?config.proxy.listener_names(),
         admin_addr = %config.admin.addr,
         "Ando CE is ready — serving traffic"
     );
@@ -142,22 +311,93 @@ fn main() -> anyhow::Result<()> {
     // ── Graceful shutdown: wait for SIGTERM/SIGINT ──
     setup_signal_handler();
 
-    // Wait for shutdown signal
+    if cli.upgrade {
+        upgrade::notify_ready()?;
+        info!("Upgrade: serving, the previous process drains");
+    }
+
+    // Wait for shutdown signal, or for a binary upgrade to take over
     while !SHUTDOWN.load(Ordering::Relaxed) {
+        if UPGRADE.swap(false, Ordering::Relaxed) {
+            let binary = match config.proxy.upgrade_binary {
+                Some(ref path) => PathBuf::from(path),
+                None => std::env::current_exe()?,
+            };
+            match upgrade::spawn_successor(&binary, &listener_fds) {
+                Ok(pid) => {
+                    info!(pid, "Upgrade: new process ready, handing over");
+                    break;
+                }
+                Err(e) => error!(error = %e, "Upgrade failed, still serving"),
+            }
+        }
         std::thread::sleep(std::time::Duration::from_millis(100));
     }
 
-    info!("Shutdown signal received, stopping...");
+    // Workers stop accepting, finish their requests in flight (closing
+    // keepalive connections after them) and exit by the drain deadline.
+    info!(
+        timeout_ms = config.proxy.graceful_shutdown_timeout_ms,
+        "Shutdown signal received, draining..."
+    );
+    shared.shutdown.begin();
+    for handle in worker_handles {
+        if handle.join().is_err() {
+            warn!("Worker thread panicked during shutdown");
+        }
+    }
 
-    // In the current architecture, workers run in an infinite accept loop.
-    // On process exit, all threads are cleaned up by the OS.
-    // Future improvement: send shutdown notification to each worker.
-    drop(worker_handles);
+    // Then flush what the exporters still buffer.
+    if let Some(ref audit) = admin_state.audit {
+        audit.flush();
+    }
+    if let Some(ref tracer) = shared.tracer {
+        tracer.shutdown();
+    }
+    if let Some(ref access_log) = shared.access_log {
+        access_log.flush();
+    }
 
     info!("Ando CE stopped");
     Ok(())
 }
 
+/// Connect to the etcd or SQL config store `deployment.mode` selects.
+/// Returns it with the runtime its connections and watcher run on, or
+/// `None` in standalone mode.
+fn connect_store(
+    deployment: &DeploymentConfig,
+) -> anyhow::Result<Option<(tokio::runtime::Runtime, Arc<dyn ConfigStore>)>> {
+    if deployment.mode == DeploymentMode::Standalone {
+        return Ok(None);
+    }
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("ando-config-store")
+        .enable_all()
+        .build()?;
+    let store: Arc<dyn ConfigStore> = rt.block_on(async {
+        anyhow::Ok(if deployment.mode == DeploymentMode::Etcd {
+            let etcd = deployment
+                .etcd
+                .as_ref()
+                .context("deployment.mode is etcd but deployment.etcd is not set")?;
+            Arc::new(EtcdStore::connect(etcd).await?) as Arc<dyn ConfigStore>
+        } else {
+            let sql = deployment
+                .sql
+                .as_ref()
+                .context("deployment.mode is sql but deployment.sql is not set")?;
+            Arc::new(
+                SqlStore::connect(&sql.url, sql.max_connections)
+                    .await?
+                    .with_poll_interval(Duration::from_millis(sql.poll_interval_ms)),
+            )
+        })
+    })?;
+    Ok(Some((rt, store)))
+}
+
 /// Raise RLIMIT_NOFILE to min(hard_limit, 65536) so workers can open enough
 /// upstream connections without hitting EMFILE (os error 24).
 /// macOS ships with a default soft limit of 256 which is far too low for
@@ -198,14 +438,18 @@ fn raise_fd_limit() {
 }
 
 fn setup_signal_handler() {
-    // SIGTERM (docker stop) + SIGINT (Ctrl+C)
-    for sig in [libc::SIGTERM, libc::SIGINT] {
+    // SIGTERM (docker stop) + SIGINT (Ctrl+C), SIGUSR2 (binary upgrade)
+    for sig in [libc::SIGTERM, libc::SIGINT, libc::SIGUSR2] {
         unsafe {
-            libc::signal(sig, signal_handler as libc::sighandler_t);
+            libc::signal(sig, signal_handler as *const () as libc::sighandler_t);
         }
     }
 }
 
-extern "C" fn signal_handler(_sig: libc::c_int) {
-    SHUTDOWN.store(true, Ordering::Relaxed);
+extern "C" fn signal_handler(sig: libc::c_int) {
+    if sig == libc::SIGUSR2 {
+        UPGRADE.store(true, Ordering::Relaxed);
+    } else {
+        SHUTDOWN.store(true, Ordering::Relaxed);
+    }
 }
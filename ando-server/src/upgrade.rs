@@ -0,0 +1,107 @@
+//! Binary upgrade: hand the listening sockets to a new process.
+//!
+//! On SIGUSR2 the running gateway starts the new binary with its own
+//! arguments plus `--upgrade`. The listener descriptors stay open across
+//! `exec` and are listed in `LISTEN_FDS_ENV`; a socket pair whose other
+//! end is named by `READY_FD_ENV` carries the new process's readiness.
+//! Once it reports ready the old process drains as on SIGTERM, so no
+//! connection is refused and no request in flight is cut off. A new
+//! process that exits or stays silent for `READY_TIMEOUT` is stopped and
+//! the old one keeps serving.
+
+use ando_proxy::listener::{LISTEN_FDS_ENV, ListenerFds};
+use anyhow::{Context, bail};
+use std::ffi::OsString;
+use std::io::{ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::Command;
+use std::time::Duration;
+use tracing::info;
+
+/// Environment variable naming the descriptor the new process reports
+/// readiness on.
+pub const READY_FD_ENV: &str = "ANDO_UPGRADE_READY_FD";
+
+/// How long the new process gets to start serving.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Start `binary` as the successor of this process, handing it the
+/// sockets in `fds`, and wait until it is serving. Returns its pid.
+pub fn spawn_successor(binary: &Path, fds: &ListenerFds) -> anyhow::Result<u32> {
+    let (mut ready, theirs) = UnixStream::pair().context("upgrade: socketpair failed")?;
+    let inherit: Vec<RawFd> = fds.all().chain([theirs.as_raw_fd()]).collect();
+    let mut args: Vec<OsString> = std::env::args_os()
+        .skip(1)
+        .filter(|arg| arg != "--upgrade")
+        .collect();
+    args.push("--upgrade".into());
+
+    let mut command = Command::new(binary);
+    command
+        .args(args)
+        .env(LISTEN_FDS_ENV, fds.to_string())
+        .env(READY_FD_ENV, theirs.as_raw_fd().to_string());
+    // SAFETY: only sysconf and fcntl, which are async-signal-safe, run
+    // between fork and exec.
+    unsafe {
+        command.pre_exec(move || {
+            // Client connections accepted through io_uring lack CLOEXEC;
+            // inherited, they would outlive this process's close of them.
+            let max_fd = libc::sysconf(libc::_SC_OPEN_MAX).clamp(256, 1 << 20) as RawFd;
+            for fd in 3..max_fd {
+                if !inherit.contains(&fd) {
+                    libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                }
+            }
+            // Keep the handed-over descriptors open across exec.
+            for &fd in &inherit {
+                if libc::fcntl(fd, libc::F_SETFD, 0) == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+    info!(binary = %binary.display(), %fds, "Upgrade: starting new binary");
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("upgrade: cannot start {}", binary.display()))?;
+    drop(theirs);
+    info!(
+        pid = child.id(),
+        "Upgrade: waiting for the new process to be ready"
+    );
+
+    ready.set_read_timeout(Some(READY_TIMEOUT))?;
+    let mut byte = [0u8; 1];
+    let outcome = match ready.read(&mut byte) {
+        Ok(1) => return Ok(child.id()),
+        Ok(_) => "exited before it was ready".to_string(),
+        Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+            format!("not ready after {}s", READY_TIMEOUT.as_secs())
+        }
+        Err(e) => format!("readiness unknown: {e}"),
+    };
+    let _ = child.kill();
+    let _ = child.wait();
+    bail!("upgrade: new process (pid {}) {outcome}", child.id())
+}
+
+/// Tell the process that started this one for an upgrade that it is
+/// serving; the previous process then drains.
+pub fn notify_ready() -> anyhow::Result<()> {
+    let fd: RawFd = std::env::var(READY_FD_ENV)
+        .with_context(|| format!("--upgrade: {READY_FD_ENV} is not set"))?
+        .parse()
+        .with_context(|| format!("--upgrade: invalid {READY_FD_ENV}"))?;
+    // SAFETY: the descriptor was left open for this process by the one
+    // that started it, and is not used elsewhere.
+    let mut ready = unsafe { UnixStream::from_raw_fd(fd) };
+    ready
+        .write_all(b"1")
+        .context("upgrade: cannot report readiness")?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Binary upgrade (SIGUSR2) between two processes of the gateway binary.
+#![cfg(target_os = "linux")]
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// An upstream answering 200 to every request, after `delay` for `/slow`.
+fn spawn_upstream(delay: Duration) -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { break };
+            std::thread::spawn(move || {
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).unwrap_or(0);
+                if buf[..n].starts_with(b"GET /slow ") {
+                    std::thread::sleep(delay);
+                }
+                let _ = stream.write_all(
+                    b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\nok",
+                );
+            });
+        }
+    });
+    addr
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+/// Send `GET path` on `client` and read the response until the gateway
+/// closes the connection.
+fn request(mut client: TcpStream, path: &str) -> String {
+    client
+        .write_all(
+            format!("GET {path} HTTP/1.1\r\nhost: a\r\nconnection: close\r\n\r\n").as_bytes(),
+        )
+        .unwrap();
+    let mut resp = String::new();
+    client.read_to_string(&mut resp).unwrap();
+    resp
+}
+
+fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    while !done() {
+        assert!(Instant::now() < deadline, "timed out waiting for {what}");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Pids of the children of `parent`, from `/proc/*/stat`.
+fn children_of(parent: u32) -> Vec<u32> {
+    std::fs::read_dir("/proc")
+        .unwrap()
+        .filter_map(|entry| {
+            let pid: u32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            // The field after the parenthesized command name and state.
+            let ppid: u32 = stat
+                .rsplit_once(')')?
+                .1
+                .split_whitespace()
+                .nth(1)?
+                .parse()
+                .ok()?;
+            (ppid == parent).then_some(pid)
+        })
+        .collect()
+}
+
+fn signal(pid: u32, sig: libc::c_int) {
+    assert_eq!(unsafe { libc::kill(pid as libc::pid_t, sig) }, 0);
+}
+
+fn wait_exit(child: &mut Child) -> std::process::ExitStatus {
+    let deadline = Instant::now() + Duration::from_secs(20);
+    loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            return status;
+        }
+        assert!(Instant::now() < deadline, "process did not exit");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn upgrade_hands_over_listeners_without_dropping_requests_in_flight() {
+    let upstream = spawn_upstream(Duration::from_millis(1500));
+    let port = free_port();
+    let dir = tempfile::tempdir().unwrap();
+    let routes = dir.path().join("apisix.yaml");
+    std::fs::write(
+        &routes,
+        format!("routes:\n  - id: r1\n    uri: /*\n    upstream:\n      nodes:\n        \"{upstream}\": 1\n"),
+    )
+    .unwrap();
+    let config = dir.path().join("ando.yaml");
+    std::fs::write(
+        &config,
+        format!(
+            "proxy:\n  http_addr: \"127.0.0.1:{port}\"\n  https_addr: \"\"\n  workers: 2\n\
+             admin:\n  enabled: false\n\
+             deployment:\n  mode: standalone\n  standalone:\n    config_path: \"{}\"\n",
+            routes.display()
+        ),
+    )
+    .unwrap();
+
+    let mut old = Command::new(env!("CARGO_BIN_EXE_ando-server"))
+        .arg("--config")
+        .arg(&config)
+        .arg("--state-file")
+        .arg(dir.path().join("state.json"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let addr: SocketAddr = format!("127.0.0.1:{port}").parse().unwrap();
+    wait_until("the gateway to serve", || {
+        TcpStream::connect(addr).is_ok_and(|c| request(c, "/ok").starts_with("HTTP/1.1 200"))
+    });
+
+    // A request still waiting on the upstream when the upgrade starts.
+    let in_flight = TcpStream::connect(addr).unwrap();
+    let slow = std::thread::spawn(move || request(in_flight, "/slow"));
+    std::thread::sleep(Duration::from_millis(300));
+
+    signal(old.id(), libc::SIGUSR2);
+    let mut new_pid = None;
+    wait_until("the new process", || {
+        new_pid = children_of(old.id()).first().copied();
+        new_pid.is_some()
+    });
+    let new_pid = new_pid.unwrap();
+
+    // Every connection is served while the two processes hand over.
+    for _ in 0..10 {
+        let resp = request(TcpStream::connect(addr).unwrap(), "/ok");
+        assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    let resp = slow.join().unwrap();
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+
+    // The old process drains and exits; the new one keeps serving.
+    assert!(wait_exit(&mut old).success());
+    let resp = request(TcpStream::connect(addr).unwrap(), "/ok");
+    assert!(resp.starts_with("HTTP/1.1 200"), "{resp}");
+
+    signal(new_pid, libc::SIGTERM);
+    wait_until("the new process to exit", || {
+        std::fs::read_to_string(format!("/proc/{new_pid}/stat"))
+            .map_or(true, |stat| stat.contains(") Z "))
+    });
+}
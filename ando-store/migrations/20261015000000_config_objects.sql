@@ -0,0 +1,21 @@
+-- Config objects, one row per (kind, id). A delete keeps the row with a
+-- NULL value so pollers on other nodes see it.
+CREATE TABLE IF NOT EXISTS config_objects (
+    kind TEXT NOT NULL,
+    id TEXT NOT NULL,
+    value_json TEXT,
+    updated_at BIGINT NOT NULL,
+    version BIGINT NOT NULL,
+    PRIMARY KEY (kind, id)
+);
+
+CREATE INDEX IF NOT EXISTS config_objects_version ON config_objects (version);
+
+-- Single-row change counter. Every write bumps it inside its transaction,
+-- which also serializes writers so versions become visible in order.
+CREATE TABLE IF NOT EXISTS config_version (
+    id INTEGER PRIMARY KEY,
+    version BIGINT NOT NULL
+);
+
+INSERT INTO config_version (id, version) VALUES (1, 0);
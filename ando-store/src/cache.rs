@@ -1,4 +1,12 @@
+use crate::changes::ChangeLog;
+use crate::credentials::CredentialIndex;
+use crate::discovery::DiscoveryRegistry;
+use crate::dns::DnsTable;
+use crate::health::{CircuitBreakers, HealthTable};
+use crate::notify::ConfigNotifier;
+use crate::sync_status::SyncStatus;
 use ando_core::consumer::Consumer;
+use ando_core::global_rule::GlobalRule;
 use ando_core::plugin_config::PluginConfig;
 use ando_core::route::Route;
 use ando_core::service::Service;
@@ -21,8 +29,27 @@ pub struct ConfigCache {
     pub consumers: Arc<DashMap<String, Consumer>>,
     pub ssl_certs: Arc<DashMap<String, SslCertificate>>,
     pub plugin_configs: Arc<DashMap<String, PluginConfig>>,
-    /// Consumer key → username index (for key-auth O(1) lookup).
-    pub consumer_key_index: Arc<DashMap<String, String>>,
+    pub global_rules: Arc<DashMap<String, GlobalRule>>,
+    /// Credential id → username, per auth plugin. Kept in step with
+    /// `consumers` by whoever writes them.
+    pub credentials: CredentialIndex,
+    /// Upstream node health, maintained by the active health checker.
+    pub health: HealthTable,
+    /// Per-node circuit breakers, fed by passive health checks.
+    pub breakers: CircuitBreakers,
+    /// Addresses of upstream nodes given by hostname, kept fresh by the
+    /// DNS refresher.
+    pub dns: DnsTable,
+    /// Nodes of upstreams with a `discovery_type`, kept fresh by the
+    /// discovery watchers.
+    pub discovery: DiscoveryRegistry,
+    /// Ids of routes, services, upstreams and consumers written since
+    /// startup, so workers can evict only the caches they feed.
+    pub changes: ChangeLog,
+    /// Wakes the workers once writes to the cache and router are done.
+    pub notifier: ConfigNotifier,
+    /// Last failure to load config from its source, if not recovered.
+    pub sync: SyncStatus,
 }
 
 impl ConfigCache {
@@ -34,32 +61,70 @@ impl ConfigCache {
             consumers: Arc::new(DashMap::new()),
             ssl_certs: Arc::new(DashMap::new()),
             plugin_configs: Arc::new(DashMap::new()),
-            consumer_key_index: Arc::new(DashMap::new()),
+            global_rules: Arc::new(DashMap::new()),
+            credentials: CredentialIndex::new(),
+            health: HealthTable::new(),
+            breakers: CircuitBreakers::new(),
+            dns: DnsTable::new(),
+            discovery: DiscoveryRegistry::new(),
+            changes: ChangeLog::new(),
+            notifier: ConfigNotifier::new(),
+            sync: SyncStatus::new(),
         }
     }
 
-    /// Rebuild the consumer key index from all consumers.
-    pub fn rebuild_consumer_key_index(&self) {
-        self.consumer_key_index.clear();
+    /// Bring the credential index in line with all consumers after a bulk
+    /// load. Updated in place: credentials that stay never go missing.
+    pub fn reindex_consumers(&self) {
         for entry in self.consumers.iter() {
-            let consumer = entry.value();
-            if let Some(key_auth_config) = consumer.plugins.get("key-auth")
-                && let Some(key) = key_auth_config.get("key").and_then(|v| v.as_str())
-            {
-                self.consumer_key_index
-                    .insert(key.to_string(), consumer.username.clone());
-            }
+            self.credentials.insert(entry.value());
         }
+        self.credentials
+            .retain(|username| self.consumers.contains_key(username));
+    }
+
+    /// Store `consumer` and index its credentials.
+    pub fn put_consumer(&self, consumer: Consumer) {
+        self.credentials.insert(&consumer);
+        self.consumers.insert(consumer.username.clone(), consumer);
+    }
+
+    /// Remove the consumer `username` and its credentials.
+    pub fn remove_consumer(&self, username: &str) -> Option<Consumer> {
+        let removed = self.consumers.remove(username).map(|(_, c)| c);
+        self.credentials.remove(username);
+        removed
     }
 
     /// Look up a consumer by API key (O(1)).
     pub fn find_consumer_by_key(&self, key: &str) -> Option<String> {
-        self.consumer_key_index.get(key).map(|v| v.value().clone())
+        self.find_consumer("key-auth", key)
+    }
+
+    /// Look up the consumer holding credential `id` of auth plugin `plugin`.
+    pub fn find_consumer(&self, plugin: &str, id: &str) -> Option<String> {
+        self.credentials.get(plugin, id)
     }
 
-    /// Get all routes as a Vec (for router building).
+    /// Get all routes as a Vec (for router building), with their
+    /// service's defaults applied; see [`Service::apply_defaults`].
+    ///
+    /// [`Service::apply_defaults`]: ando_core::service::Service::apply_defaults
     pub fn all_routes(&self) -> Vec<Route> {
-        self.routes.iter().map(|r| r.value().clone()).collect()
+        self.routes
+            .iter()
+            .map(|r| {
+                let mut route = r.value().clone();
+                let service = route
+                    .service_id
+                    .as_deref()
+                    .and_then(|id| self.services.get(id));
+                if let Some(service) = service {
+                    service.apply_defaults(&mut route);
+                }
+                route
+            })
+            .collect()
     }
 }
 
@@ -99,7 +164,7 @@ mod tests {
         cache
             .consumers
             .insert("alice".to_string(), make_consumer("alice", "secret-abc"));
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
         assert_eq!(
             cache.find_consumer_by_key("secret-abc"),
             Some("alice".to_string())
@@ -109,7 +174,7 @@ mod tests {
     #[test]
     fn find_consumer_by_key_unknown_returns_none() {
         let cache = ConfigCache::new();
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
         assert!(cache.find_consumer_by_key("not-a-key").is_none());
     }
 
@@ -124,18 +189,18 @@ mod tests {
     }
 
     #[test]
-    fn rebuild_consumer_key_index_replaces_stale_entries() {
+    fn reindex_consumers_replaces_stale_entries() {
         let cache = ConfigCache::new();
         cache
             .consumers
             .insert("alice".to_string(), make_consumer("alice", "old-key"));
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
 
         cache.consumers.remove("alice");
         cache
             .consumers
             .insert("alice".to_string(), make_consumer("alice", "new-key"));
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
 
         assert!(
             cache.find_consumer_by_key("old-key").is_none(),
@@ -148,7 +213,7 @@ mod tests {
     }
 
     #[test]
-    fn rebuild_consumer_key_index_multiple_consumers() {
+    fn reindex_consumers_multiple_consumers() {
         let cache = ConfigCache::new();
         cache
             .consumers
@@ -159,7 +224,7 @@ mod tests {
         cache
             .consumers
             .insert("carol".to_string(), make_consumer("carol", "key-c"));
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
 
         assert_eq!(
             cache.find_consumer_by_key("key-a"),
@@ -182,8 +247,24 @@ mod tests {
             labels: HashMap::new(),
         };
         cache.consumers.insert("noauth".to_string(), consumer);
-        cache.rebuild_consumer_key_index();
-        assert_eq!(cache.consumer_key_index.len(), 0);
+        cache.reindex_consumers();
+        assert_eq!(cache.credentials.len(), 0);
+    }
+
+    #[test]
+    fn put_and_remove_consumer_keep_the_index_in_step() {
+        let cache = ConfigCache::new();
+        cache.put_consumer(make_consumer("alice", "key-a"));
+        cache.put_consumer(make_consumer("bob", "key-b"));
+        assert_eq!(
+            cache.find_consumer_by_key("key-a"),
+            Some("alice".to_string())
+        );
+        cache.put_consumer(make_consumer("alice", "key-a2"));
+        assert!(cache.find_consumer_by_key("key-a").is_none());
+        assert!(cache.remove_consumer("alice").is_some());
+        assert!(cache.find_consumer_by_key("key-a2").is_none());
+        assert_eq!(cache.find_consumer_by_key("key-b"), Some("bob".to_string()));
     }
 
     // ── all_routes ───────────────────────────────────────────────
@@ -224,6 +305,31 @@ mod tests {
         assert_eq!(routes[0].id, "r2");
     }
 
+    #[test]
+    fn all_routes_take_their_service_defaults() {
+        let cache = ConfigCache::new();
+        let service: ando_core::service::Service = serde_json::from_value(serde_json::json!({
+            "id": "s1", "hosts": ["api.example.com"], "timeout": {"read": 30.0}
+        }))
+        .unwrap();
+        cache.services.insert("s1".to_string(), service);
+        let mut own = make_route("r1", "/a");
+        own.service_id = Some("s1".into());
+        own.hosts = vec!["own.example.com".into()];
+        let mut bare = make_route("r2", "/b");
+        bare.service_id = Some("s1".into());
+        cache.routes.insert("r1".to_string(), own);
+        cache.routes.insert("r2".to_string(), bare);
+
+        let mut routes = cache.all_routes();
+        routes.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(routes[0].hosts, ["own.example.com"]);
+        assert_eq!(routes[1].hosts, ["api.example.com"]);
+        assert_eq!(routes[1].timeout.unwrap().read, Some(30.0));
+        // The stored route keeps only its own values.
+        assert!(cache.routes.get("r2").unwrap().hosts.is_empty());
+    }
+
     // ── clone shares underlying DashMaps ────────────────────────
 
     #[test]
@@ -245,6 +351,6 @@ mod tests {
         assert!(cache.routes.is_empty());
         assert!(cache.consumers.is_empty());
         assert!(cache.upstreams.is_empty());
-        assert!(cache.consumer_key_index.is_empty());
+        assert!(cache.credentials.is_empty());
     }
 }
@@ -0,0 +1,141 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Entries kept before the oldest are dropped. A worker that falls further
+/// behind than this gets `None` from `since` and rebuilds everything.
+const MAX_ENTRIES: usize = 1024;
+
+/// Kind of config entity a change refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entity {
+    Route,
+    Service,
+    Upstream,
+    Consumer,
+    GlobalRule,
+}
+
+/// Ids changed between two versions of the change log.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeSet {
+    pub routes: HashSet<String>,
+    pub services: HashSet<String>,
+    pub upstreams: HashSet<String>,
+    /// Consumers are indexed as a whole, so only the fact matters.
+    pub consumers: bool,
+    /// Global rules feed every route's pipeline; only the fact matters.
+    pub global_rules: bool,
+}
+
+impl ChangeSet {
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+            && self.services.is_empty()
+            && self.upstreams.is_empty()
+            && !self.consumers
+            && !self.global_rules
+    }
+}
+
+/// Bounded log of config writes, shared by every writer of the cache.
+///
+/// v2 design: The admin API and the etcd watcher record the id of every
+/// entity they insert or remove. Worker cores compare `version()` when
+/// notified of a change and, when it moved, fetch the ids changed `since`
+/// their last sync so they only evict the thread-local caches those ids
+/// feed.
+#[derive(Clone, Default)]
+pub struct ChangeLog {
+    entries: Arc<Mutex<VecDeque<(u64, Entity, String)>>>,
+    version: Arc<AtomicU64>,
+}
+
+impl ChangeLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a write to `id`. Call after the cache itself was updated.
+    pub fn record(&self, entity: Entity, id: &str) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let v = self.version.load(Ordering::Relaxed) + 1;
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back((v, entity, id.to_string()));
+        self.version.store(v, Ordering::Release);
+    }
+
+    /// Bumped on every `record`.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Everything recorded after version `v`, with the version it runs up
+    /// to. `None` if entries after `v` were already dropped.
+    pub fn since(&self, v: u64) -> Option<(ChangeSet, u64)> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let latest = self.version.load(Ordering::Acquire);
+        let oldest = entries.front().map_or(latest + 1, |(v, _, _)| *v);
+        if v + 1 < oldest {
+            return None;
+        }
+        let mut set = ChangeSet::default();
+        for (_, entity, id) in entries.iter().filter(|(ev, _, _)| *ev > v) {
+            match entity {
+                Entity::Route => set.routes.insert(id.clone()),
+                Entity::Service => set.services.insert(id.clone()),
+                Entity::Upstream => set.upstreams.insert(id.clone()),
+                Entity::Consumer => {
+                    set.consumers = true;
+                    continue;
+                }
+                Entity::GlobalRule => {
+                    set.global_rules = true;
+                    continue;
+                }
+            };
+        }
+        Some((set, latest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn since_collects_changes_after_version() {
+        let log = ChangeLog::new();
+        log.record(Entity::Route, "r1");
+        let v = log.version();
+        log.record(Entity::Route, "r2");
+        log.record(Entity::Service, "s1");
+        log.record(Entity::Consumer, "alice");
+        log.record(Entity::GlobalRule, "g1");
+
+        let (set, latest) = log.since(v).unwrap();
+        assert_eq!(latest, 5);
+        assert_eq!(set.routes, HashSet::from(["r2".to_string()]));
+        assert_eq!(set.services, HashSet::from(["s1".to_string()]));
+        assert!(set.upstreams.is_empty());
+        assert!(set.consumers);
+        assert!(set.global_rules);
+
+        let (set, _) = log.since(latest).unwrap();
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn since_reports_truncated_log() {
+        let log = ChangeLog::new();
+        for i in 0..MAX_ENTRIES + 1 {
+            log.record(Entity::Upstream, &format!("u{i}"));
+        }
+        assert!(log.since(0).is_none());
+        let (set, _) = log.since(1).unwrap();
+        assert_eq!(set.upstreams.len(), MAX_ENTRIES);
+    }
+}
@@ -0,0 +1,247 @@
+use ando_core::consumer::{CREDENTIAL_ID_FIELDS, Consumer};
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Credential id → username index over all consumers, per auth plugin.
+///
+/// v2 design: Maintained incrementally, one consumer at a time. A reverse
+/// map remembers which ids each consumer contributed, so a put or delete
+/// touches only that consumer's entries: new ids are inserted before stale
+/// ones are removed, and lookups of every other consumer never miss while
+/// the index changes. Writers of the same consumer serialize on its
+/// reverse-map entry.
+#[derive(Clone)]
+pub struct CredentialIndex {
+    /// Auth plugin → credential id → username.
+    by_plugin: Arc<HashMap<&'static str, DashMap<String, String>>>,
+    /// Username → the `(plugin, id)` pairs it is indexed under.
+    owned: Arc<DashMap<String, Vec<(&'static str, String)>>>,
+}
+
+impl CredentialIndex {
+    pub fn new() -> Self {
+        Self {
+            by_plugin: Arc::new(
+                CREDENTIAL_ID_FIELDS
+                    .iter()
+                    .map(|(plugin, _)| (*plugin, DashMap::new()))
+                    .collect(),
+            ),
+            owned: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Index `consumer`'s credentials, replacing its previous ones.
+    pub fn insert(&self, consumer: &Consumer) {
+        let ids: Vec<(&'static str, String)> = consumer
+            .credential_ids()
+            .map(|(plugin, id)| (plugin, id.to_string()))
+            .collect();
+        let mut owned = self.owned.entry(consumer.username.clone()).or_default();
+        for (plugin, id) in &ids {
+            self.by_plugin[plugin].insert(id.clone(), consumer.username.clone());
+        }
+        for (plugin, id) in owned.iter().filter(|old| !ids.contains(old)) {
+            self.release(plugin, id, &consumer.username);
+        }
+        *owned = ids;
+    }
+
+    /// Drop every credential of the consumer `username`.
+    pub fn remove(&self, username: &str) {
+        if let Some((_, ids)) = self.owned.remove(username) {
+            for (plugin, id) in &ids {
+                self.release(plugin, id, username);
+            }
+        }
+    }
+
+    /// Drop consumers for which `keep` returns false.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        let gone: Vec<String> = self
+            .owned
+            .iter()
+            .filter(|e| !keep(e.key()))
+            .map(|e| e.key().clone())
+            .collect();
+        for username in gone {
+            self.remove(&username);
+        }
+    }
+
+    /// The username holding credential `id` for `plugin`.
+    pub fn get(&self, plugin: &str, id: &str) -> Option<String> {
+        self.by_plugin
+            .get(plugin)?
+            .get(id)
+            .map(|u| u.value().clone())
+    }
+
+    /// Number of indexed credentials.
+    pub fn len(&self) -> usize {
+        self.by_plugin.values().map(DashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove `id` unless another consumer has taken it over since.
+    fn release(&self, plugin: &str, id: &str, username: &str) {
+        self.by_plugin[plugin].remove_if(id, |_, owner| owner == username);
+    }
+}
+
+impl Default for CredentialIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::thread;
+
+    fn consumer(username: &str, plugins: serde_json::Value) -> Consumer {
+        serde_json::from_value(json!({ "username": username, "plugins": plugins })).unwrap()
+    }
+
+    #[test]
+    fn indexes_every_auth_plugin() {
+        let index = CredentialIndex::new();
+        index.insert(&consumer(
+            "alice",
+            json!({
+                "key-auth": {"key": "k1"},
+                "basic-auth": {"username": "al", "password": "pw"},
+                "hmac-auth": {"access_key": "ak", "secret_key": "sk"},
+            }),
+        ));
+        assert_eq!(index.get("key-auth", "k1").as_deref(), Some("alice"));
+        assert_eq!(index.get("basic-auth", "al").as_deref(), Some("alice"));
+        assert_eq!(index.get("hmac-auth", "ak").as_deref(), Some("alice"));
+        assert!(index.get("key-auth", "al").is_none());
+        assert_eq!(index.len(), 3);
+    }
+
+    #[test]
+    fn update_drops_only_the_stale_credentials() {
+        let index = CredentialIndex::new();
+        index.insert(&consumer("alice", json!({"key-auth": {"key": "old"}})));
+        index.insert(&consumer("alice", json!({"key-auth": {"key": "new"}})));
+        assert!(index.get("key-auth", "old").is_none());
+        assert_eq!(index.get("key-auth", "new").as_deref(), Some("alice"));
+        assert_eq!(index.len(), 1);
+    }
+
+    #[test]
+    fn remove_keeps_an_id_another_consumer_took_over() {
+        let index = CredentialIndex::new();
+        index.insert(&consumer("alice", json!({"key-auth": {"key": "shared"}})));
+        index.insert(&consumer("bob", json!({"key-auth": {"key": "shared"}})));
+        index.remove("alice");
+        assert_eq!(index.get("key-auth", "shared").as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn retain_drops_absent_consumers() {
+        let index = CredentialIndex::new();
+        index.insert(&consumer("alice", json!({"key-auth": {"key": "a"}})));
+        index.insert(&consumer("bob", json!({"key-auth": {"key": "b"}})));
+        index.retain(|username| username == "bob");
+        assert!(index.get("key-auth", "a").is_none());
+        assert_eq!(index.get("key-auth", "b").as_deref(), Some("bob"));
+    }
+
+    #[test]
+    fn untouched_consumers_never_miss_during_updates() {
+        let index = CredentialIndex::new();
+        for i in 0..100 {
+            index.insert(&consumer(
+                &format!("stable-{i}"),
+                json!({"key-auth": {"key": format!("stable-key-{i}")}}),
+            ));
+        }
+        let done = Arc::new(AtomicBool::new(false));
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let index = index.clone();
+                thread::spawn(move || {
+                    for round in 0..2_000 {
+                        let name = format!("churn-{w}");
+                        if round % 3 == 0 {
+                            index.remove(&name);
+                        } else {
+                            index.insert(&consumer(
+                                &name,
+                                json!({"key-auth": {"key": format!("churn-{w}-{round}")}}),
+                            ));
+                        }
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let (index, done) = (index.clone(), done.clone());
+                thread::spawn(move || {
+                    let mut lookups = 0;
+                    while !done.load(Ordering::Relaxed) || lookups == 0 {
+                        for i in 0..100 {
+                            assert_eq!(
+                                index.get("key-auth", &format!("stable-key-{i}")),
+                                Some(format!("stable-{i}")),
+                            );
+                        }
+                        lookups += 1;
+                    }
+                })
+            })
+            .collect();
+        for w in writers {
+            w.join().unwrap();
+        }
+        done.store(true, Ordering::Relaxed);
+        for r in readers {
+            r.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn updated_consumer_is_always_found_under_its_kept_id() {
+        let index = CredentialIndex::new();
+        index.insert(&consumer("alice", json!({"key-auth": {"key": "k"}})));
+        let done = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let index = index.clone();
+            thread::spawn(move || {
+                for round in 0..2_000 {
+                    // Same key-auth key, rotating hmac-auth credentials.
+                    index.insert(&consumer(
+                        "alice",
+                        json!({
+                            "key-auth": {"key": "k"},
+                            "hmac-auth": {"access_key": format!("ak-{round}"), "secret_key": "s"},
+                        }),
+                    ));
+                }
+            })
+        };
+        let reader = {
+            let (index, done) = (index.clone(), done.clone());
+            thread::spawn(move || {
+                while !done.load(Ordering::Relaxed) {
+                    assert_eq!(index.get("key-auth", "k").as_deref(), Some("alice"));
+                }
+            })
+        };
+        writer.join().unwrap();
+        done.store(true, Ordering::Relaxed);
+        reader.join().unwrap();
+        assert_eq!(index.len(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+use ando_core::upstream::DiscoveryType;
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// One ready endpoint of a discovered service, on one of its ports.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DiscoveredNode {
+    /// Name of the service port; empty for an unnamed port.
+    pub port_name: String,
+    /// `ip:port` to connect to.
+    pub addr: String,
+}
+
+/// Nodes of every discovered service, keyed by registry and service name.
+///
+/// v2 design: Written only by the discovery watchers. Worker cores compare
+/// `version()` on accept and pull a fresh `snapshot()` only when some
+/// service's node set has changed; the router is never rebuilt for it.
+#[derive(Clone, Default)]
+pub struct DiscoveryRegistry {
+    services: Arc<DashMap<(DiscoveryType, String), Vec<DiscoveredNode>>>,
+    version: Arc<AtomicU64>,
+}
+
+impl DiscoveryRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the nodes of `service`. An empty list is kept: the service
+    /// exists but has no ready endpoint. Returns true if the set changed.
+    pub fn publish(
+        &self,
+        kind: DiscoveryType,
+        service: &str,
+        mut nodes: Vec<DiscoveredNode>,
+    ) -> bool {
+        nodes.sort();
+        nodes.dedup();
+        let key = (kind, service.to_string());
+        let changed = self.services.get(&key).is_none_or(|n| *n != nodes);
+        if changed {
+            self.services.insert(key, nodes);
+            self.version.fetch_add(1, Ordering::Release);
+        }
+        changed
+    }
+
+    /// Forget `service`, e.g. once its last EndpointSlice is deleted.
+    pub fn remove(&self, kind: DiscoveryType, service: &str) -> bool {
+        let removed = self.services.remove(&(kind, service.to_string())).is_some();
+        if removed {
+            self.version.fetch_add(1, Ordering::Release);
+        }
+        removed
+    }
+
+    /// Current nodes of `service`, if it has been discovered.
+    pub fn get(&self, kind: DiscoveryType, service: &str) -> Option<Vec<DiscoveredNode>> {
+        self.services
+            .get(&(kind, service.to_string()))
+            .map(|n| n.value().clone())
+    }
+
+    /// Bumped every time a service's node set changes.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Registry → service → nodes, for every discovered service.
+    pub fn snapshot(&self) -> HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>> {
+        let mut all: HashMap<DiscoveryType, HashMap<String, Vec<DiscoveredNode>>> = HashMap::new();
+        for entry in self.services.iter() {
+            let (kind, service) = entry.key();
+            all.entry(*kind)
+                .or_default()
+                .insert(service.clone(), entry.value().clone());
+        }
+        all
+    }
+}
+
+/// Addresses of the nodes on `port_name`. Without a port name, the
+/// service's only port is used, or else its unnamed one.
+pub fn nodes_on_port<'a>(
+    nodes: &'a [DiscoveredNode],
+    port_name: Option<&'a str>,
+) -> impl Iterator<Item = &'a str> + 'a {
+    let port = port_name.unwrap_or_else(|| match nodes.first() {
+        Some(first) if nodes.iter().all(|n| n.port_name == first.port_name) => &first.port_name,
+        _ => "",
+    });
+    nodes
+        .iter()
+        .filter(move |n| n.port_name == port)
+        .map(|n| n.addr.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(port_name: &str, addr: &str) -> DiscoveredNode {
+        DiscoveredNode {
+            port_name: port_name.into(),
+            addr: addr.into(),
+        }
+    }
+
+    #[test]
+    fn version_moves_only_when_the_set_changes() {
+        let r = DiscoveryRegistry::new();
+        let k = DiscoveryType::Kubernetes;
+        assert!(r.publish(
+            k,
+            "ns/a",
+            vec![node("", "10.0.0.2:80"), node("", "10.0.0.1:80")]
+        ));
+        assert!(!r.publish(
+            k,
+            "ns/a",
+            vec![node("", "10.0.0.1:80"), node("", "10.0.0.2:80")]
+        ));
+        assert_eq!(r.version(), 1);
+        assert!(r.publish(k, "ns/a", vec![]));
+        assert_eq!(r.get(k, "ns/a"), Some(vec![]));
+        assert!(r.remove(k, "ns/a"));
+        assert!(!r.remove(k, "ns/a"));
+        assert_eq!(r.version(), 3);
+        assert!(r.snapshot().is_empty());
+    }
+
+    #[test]
+    fn port_selection() {
+        let single = [node("http", "10.0.0.1:8080"), node("http", "10.0.0.2:8080")];
+        assert_eq!(nodes_on_port(&single, None).count(), 2);
+        assert_eq!(nodes_on_port(&single, Some("http")).count(), 2);
+        assert_eq!(nodes_on_port(&single, Some("grpc")).count(), 0);
+
+        let multi = [node("", "10.0.0.1:80"), node("metrics", "10.0.0.1:9090")];
+        assert_eq!(
+            nodes_on_port(&multi, None).collect::<Vec<_>>(),
+            ["10.0.0.1:80"]
+        );
+        assert_eq!(
+            nodes_on_port(&multi, Some("metrics")).collect::<Vec<_>>(),
+            ["10.0.0.1:9090"]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use dashmap::DashMap;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Resolved addresses of upstream nodes given by hostname, keyed by the
+/// node as configured (`host:port`).
+///
+/// v2 design: Written only by the DNS refresher thread. Worker cores never
+/// resolve on the hot path — they compare `version()` on accept and pull a
+/// fresh `snapshot()` only when some node's address set has changed.
+#[derive(Clone, Default)]
+pub struct DnsTable {
+    nodes: Arc<DashMap<String, Vec<SocketAddr>>>,
+    version: Arc<AtomicU64>,
+}
+
+impl DnsTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the addresses `node` resolved to. The order of `addrs` does
+    /// not matter; returns true if the set differs from the stored one.
+    pub fn update(&self, node: &str, mut addrs: Vec<SocketAddr>) -> bool {
+        addrs.sort();
+        addrs.dedup();
+        let changed = match self.nodes.get_mut(node) {
+            Some(mut current) if *current != addrs => {
+                *current = addrs;
+                true
+            }
+            Some(_) => false,
+            None => {
+                self.nodes.insert(node.to_string(), addrs);
+                true
+            }
+        };
+        if changed {
+            self.version.fetch_add(1, Ordering::Release);
+        }
+        changed
+    }
+
+    /// Addresses `node` last resolved to, if it has been resolved.
+    pub fn get(&self, node: &str) -> Option<Vec<SocketAddr>> {
+        self.nodes.get(node).map(|a| a.value().clone())
+    }
+
+    /// Bumped every time a node's address set changes.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Node → resolved addresses, for every resolved node.
+    pub fn snapshot(&self) -> HashMap<String, Vec<SocketAddr>> {
+        self.nodes
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+
+    /// Drop nodes that are no longer configured.
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        let before = self.nodes.len();
+        self.nodes.retain(|node, _| keep(node));
+        if self.nodes.len() != before {
+            self.version.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(list: &[&str]) -> Vec<SocketAddr> {
+        list.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn version_moves_only_when_the_set_changes() {
+        let t = DnsTable::new();
+        assert!(t.update("api:80", addrs(&["10.0.0.2:80", "10.0.0.1:80"])));
+        assert_eq!(t.version(), 1);
+        assert!(!t.update("api:80", addrs(&["10.0.0.1:80", "10.0.0.2:80"])));
+        assert_eq!(t.version(), 1);
+        assert!(t.update("api:80", addrs(&["10.0.0.3:80"])));
+        assert_eq!(t.version(), 2);
+        assert_eq!(t.get("api:80").unwrap(), addrs(&["10.0.0.3:80"]));
+    }
+
+    #[test]
+    fn retain_drops_unconfigured_nodes() {
+        let t = DnsTable::new();
+        t.update("a:80", addrs(&["10.0.0.1:80"]));
+        t.update("b:80", addrs(&["10.0.0.2:80"]));
+        t.retain(|n| n == "a:80");
+        assert_eq!(t.version(), 3);
+        assert_eq!(t.snapshot().len(), 1);
+        t.retain(|n| n == "a:80");
+        assert_eq!(t.version(), 3);
+    }
+}
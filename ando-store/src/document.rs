@@ -0,0 +1,438 @@
+//! Whole-gateway config document, shared by the standalone config file and
+//! the Admin API bulk import/export.
+//!
+//! v2 design: A document is validated as a unit — ids, cross-references and
+//! route conditions — against the config it would produce, before any of it
+//! is written. Applying it diffs each entity map against the cache so only
+//! ids that really changed are written and recorded in the change log.
+
+use crate::cache::ConfigCache;
+use crate::changes::Entity;
+use ando_core::consumer::Consumer;
+use ando_core::global_rule::GlobalRule;
+use ando_core::plugin_config::PluginConfig;
+use ando_core::route::Route;
+use ando_core::router::validate_conditions;
+use ando_core::service::Service;
+use ando_core::ssl::SslCertificate;
+use ando_core::upstream::Upstream;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// Every config entity of the gateway.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ConfigDocument {
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(default)]
+    pub upstreams: Vec<Upstream>,
+    #[serde(default)]
+    pub consumers: Vec<Consumer>,
+    #[serde(default)]
+    pub plugin_configs: Vec<PluginConfig>,
+    #[serde(default)]
+    pub ssls: Vec<SslCertificate>,
+    #[serde(default)]
+    pub global_rules: Vec<GlobalRule>,
+}
+
+/// How a document is applied to the existing config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApplyMode {
+    /// Upsert the document's objects and keep everything else.
+    #[default]
+    Merge,
+    /// Make the config exactly the document: objects not in it are deleted.
+    Replace,
+}
+
+/// Objects written for one entity type.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub created: usize,
+    pub updated: usize,
+    pub deleted: usize,
+}
+
+/// Per-entity result of `ConfigDocument::apply`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ApplySummary {
+    pub routes: Counts,
+    pub services: Counts,
+    pub upstreams: Counts,
+    pub consumers: Counts,
+    pub plugin_configs: Counts,
+    pub ssls: Counts,
+    pub global_rules: Counts,
+}
+
+impl ConfigDocument {
+    /// Snapshot of everything in `cache`, sorted by id.
+    pub fn export(cache: &ConfigCache) -> Self {
+        fn sorted<T: Clone>(map: &DashMap<String, T>) -> Vec<T> {
+            let mut items: Vec<(String, T)> = map
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect();
+            items.sort_by(|a, b| a.0.cmp(&b.0));
+            items.into_iter().map(|(_, v)| v).collect()
+        }
+        Self {
+            routes: sorted(&cache.routes),
+            services: sorted(&cache.services),
+            upstreams: sorted(&cache.upstreams),
+            consumers: sorted(&cache.consumers),
+            plugin_configs: sorted(&cache.plugin_configs),
+            ssls: sorted(&cache.ssl_certs),
+            global_rules: sorted(&cache.global_rules),
+        }
+    }
+
+    /// The config `cache` would hold after applying this document.
+    pub fn resulting(&self, cache: &ConfigCache, mode: ApplyMode) -> Self {
+        if mode == ApplyMode::Replace {
+            return self.clone();
+        }
+        fn merge<T: Clone>(
+            base: Vec<T>,
+            overlay: &[T],
+            id: impl Fn(&T) -> Option<String>,
+        ) -> Vec<T> {
+            let replaced: HashSet<String> = overlay.iter().filter_map(&id).collect();
+            base.into_iter()
+                .filter(|item| id(item).is_none_or(|i| !replaced.contains(&i)))
+                .chain(overlay.iter().cloned())
+                .collect()
+        }
+        let base = Self::export(cache);
+        Self {
+            routes: merge(base.routes, &self.routes, |r| Some(r.id.clone())),
+            services: merge(base.services, &self.services, |s| Some(s.id.clone())),
+            upstreams: merge(base.upstreams, &self.upstreams, |u| u.id.clone()),
+            consumers: merge(base.consumers, &self.consumers, |c| {
+                Some(c.username.clone())
+            }),
+            plugin_configs: merge(base.plugin_configs, &self.plugin_configs, |p| {
+                Some(p.id.clone())
+            }),
+            ssls: merge(base.ssls, &self.ssls, |s| Some(s.id.clone())),
+            global_rules: merge(base.global_rules, &self.global_rules, |g| {
+                Some(g.id.clone())
+            }),
+        }
+    }
+
+    /// The routes as the router is built from them, with their service's
+    /// defaults applied; see [`ConfigCache::all_routes`].
+    pub fn router_routes(&self) -> Vec<Route> {
+        let services: HashMap<&str, &Service> =
+            self.services.iter().map(|s| (s.id.as_str(), s)).collect();
+        self.routes
+            .iter()
+            .map(|route| {
+                let mut route = route.clone();
+                if let Some(service) = route.service_id.as_deref().and_then(|id| services.get(id)) {
+                    service.apply_defaults(&mut route);
+                }
+                route
+            })
+            .collect()
+    }
+
+    /// Every problem with this document taken as the complete config:
+    /// missing or duplicate ids, dangling references, invalid route
+    /// conditions, and whatever `check` reports per route.
+    pub fn errors(&self, check: &dyn Fn(&Route) -> Result<(), String>) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut upstreams = HashSet::new();
+        for ups in &self.upstreams {
+            check_id("upstream", ups.id.as_deref(), &mut errors, &mut upstreams);
+        }
+        let mut plugin_configs = HashSet::new();
+        for pc in &self.plugin_configs {
+            check_id(
+                "plugin_config",
+                Some(&pc.id),
+                &mut errors,
+                &mut plugin_configs,
+            );
+        }
+        let mut consumers = HashSet::new();
+        for consumer in &self.consumers {
+            check_id(
+                "consumer",
+                Some(&consumer.username),
+                &mut errors,
+                &mut consumers,
+            );
+        }
+        let mut ssls = HashSet::new();
+        for ssl in &self.ssls {
+            check_id("ssl", Some(&ssl.id), &mut errors, &mut ssls);
+            if ssl.cert.is_empty() || ssl.key.is_empty() {
+                errors.push(format!("ssl `{}`: requires `cert` and `key`", ssl.id));
+            }
+        }
+        let mut global_rules = HashSet::new();
+        for rule in &self.global_rules {
+            check_id(
+                "global_rule",
+                Some(&rule.id),
+                &mut errors,
+                &mut global_rules,
+            );
+        }
+        let mut services = HashSet::new();
+        for svc in &self.services {
+            check_id("service", Some(&svc.id), &mut errors, &mut services);
+            if let Some(ref ups) = svc.upstream_id
+                && !upstreams.contains(ups)
+            {
+                errors.push(format!("service `{}`: unknown upstream `{ups}`", svc.id));
+            }
+        }
+        let mut routes = HashSet::new();
+        for route in &self.routes {
+            let id = &route.id;
+            check_id("route", Some(id), &mut errors, &mut routes);
+            if route.paths().next().is_none() {
+                errors.push(format!("route `{id}`: requires `uri` or `uris`"));
+            }
+            if let Some(ref ups) = route.upstream_id
+                && !upstreams.contains(ups)
+            {
+                errors.push(format!("route `{id}`: unknown upstream `{ups}`"));
+            }
+            if let Some(ref svc) = route.service_id
+                && !services.contains(svc)
+            {
+                errors.push(format!("route `{id}`: unknown service `{svc}`"));
+            }
+            if let Some(ref pc) = route.plugin_config_id
+                && !plugin_configs.contains(pc)
+            {
+                errors.push(format!("route `{id}`: unknown plugin_config `{pc}`"));
+            }
+            if let Err(e) = validate_conditions(route).and_then(|()| check(route)) {
+                errors.push(format!("route `{id}`: {e}"));
+            }
+        }
+        errors
+    }
+
+    /// Write the document into `cache`. Validate first: nothing here
+    /// rejects bad input.
+    pub fn apply(self, cache: &ConfigCache, mode: ApplyMode) -> ApplySummary {
+        let record = |entity| move |id: &str| cache.changes.record(entity, id);
+        let summary = ApplySummary {
+            upstreams: sync(
+                &cache.upstreams,
+                self.upstreams
+                    .into_iter()
+                    .filter_map(|u| Some((u.id.clone()?, u))),
+                mode,
+                record(Entity::Upstream),
+            ),
+            services: sync(
+                &cache.services,
+                self.services.into_iter().map(|s| (s.id.clone(), s)),
+                mode,
+                record(Entity::Service),
+            ),
+            consumers: sync(
+                &cache.consumers,
+                self.consumers.into_iter().map(|c| (c.username.clone(), c)),
+                mode,
+                record(Entity::Consumer),
+            ),
+            plugin_configs: sync(
+                &cache.plugin_configs,
+                self.plugin_configs.into_iter().map(|p| (p.id.clone(), p)),
+                mode,
+                |_| {},
+            ),
+            ssls: sync(
+                &cache.ssl_certs,
+                self.ssls.into_iter().map(|s| (s.id.clone(), s)),
+                mode,
+                |_| {},
+            ),
+            global_rules: sync(
+                &cache.global_rules,
+                self.global_rules.into_iter().map(|g| (g.id.clone(), g)),
+                mode,
+                record(Entity::GlobalRule),
+            ),
+            routes: sync(
+                &cache.routes,
+                self.routes.into_iter().map(|r| (r.id.clone(), r)),
+                mode,
+                record(Entity::Route),
+            ),
+        };
+        cache.reindex_consumers();
+        summary
+    }
+}
+
+/// Report `id` if it is missing or already in `seen`.
+fn check_id(kind: &str, id: Option<&str>, errors: &mut Vec<String>, seen: &mut HashSet<String>) {
+    match id {
+        None | Some("") => errors.push(format!("{kind} without id")),
+        Some(id) if !seen.insert(id.to_string()) => errors.push(format!("duplicate {kind} `{id}`")),
+        Some(_) => {}
+    }
+}
+
+/// Write `items` into `map`, calling `changed` for every id that was
+/// created, updated or (in replace mode) deleted.
+fn sync<T: Serialize>(
+    map: &DashMap<String, T>,
+    items: impl Iterator<Item = (String, T)>,
+    mode: ApplyMode,
+    changed: impl Fn(&str),
+) -> Counts {
+    let mut counts = Counts::default();
+    let mut seen = HashSet::new();
+    for (id, item) in items {
+        let new = serde_json::to_value(&item).ok();
+        let old = map
+            .get(&id)
+            .map(|old| serde_json::to_value(old.value()).ok());
+        match &old {
+            Some(old) if *old == new => {}
+            Some(_) => counts.updated += 1,
+            None => counts.created += 1,
+        }
+        if old.as_ref().is_none_or(|old| *old != new) {
+            map.insert(id.clone(), item);
+            changed(&id);
+        }
+        seen.insert(id);
+    }
+    if mode == ApplyMode::Replace {
+        let stale: Vec<String> = map
+            .iter()
+            .filter(|e| !seen.contains(e.key()))
+            .map(|e| e.key().clone())
+            .collect();
+        for id in stale {
+            map.remove(&id);
+            changed(&id);
+            counts.deleted += 1;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(value: serde_json::Value) -> ConfigDocument {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn seeded() -> ConfigCache {
+        let cache = ConfigCache::new();
+        doc(serde_json::json!({
+            "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+            "routes": [
+                { "id": "r1", "uri": "/one", "upstream_id": "u1" },
+                { "id": "r2", "uri": "/two", "upstream_id": "u1" }
+            ]
+        }))
+        .apply(&cache, ApplyMode::Replace);
+        cache
+    }
+
+    fn update() -> ConfigDocument {
+        doc(serde_json::json!({
+            "upstreams": [{ "id": "u1", "nodes": { "127.0.0.1:8080": 1 } }],
+            "routes": [
+                { "id": "r1", "uri": "/one-v2", "upstream_id": "u1" },
+                { "id": "r3", "uri": "/three", "upstream_id": "u1" }
+            ]
+        }))
+    }
+
+    #[test]
+    fn errors_report_every_dangling_reference() {
+        let errors = doc(serde_json::json!({
+            "services": [{ "id": "s1", "upstream_id": "gone" }],
+            "routes": [
+                { "id": "r1", "uri": "/a", "upstream_id": "missing" },
+                { "id": "r2", "uri": "/b", "service_id": "nope" },
+                { "id": "r2", "uri": "/c", "plugin_config_id": "pc" }
+            ]
+        }))
+        .errors(&|_| Ok(()));
+        assert_eq!(
+            errors,
+            [
+                "service `s1`: unknown upstream `gone`",
+                "route `r1`: unknown upstream `missing`",
+                "route `r2`: unknown service `nope`",
+                "duplicate route `r2`",
+                "route `r2`: unknown plugin_config `pc`",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_keeps_objects_missing_from_document() {
+        let cache = seeded();
+        let summary = update().apply(&cache, ApplyMode::Merge);
+        assert_eq!(
+            summary.routes,
+            Counts {
+                created: 1,
+                updated: 1,
+                deleted: 0
+            }
+        );
+        assert_eq!(summary.upstreams, Counts::default());
+        assert!(cache.routes.contains_key("r2"));
+        assert_eq!(cache.routes.get("r1").unwrap().uri, "/one-v2");
+    }
+
+    #[test]
+    fn replace_deletes_objects_missing_from_document() {
+        let cache = seeded();
+        let v = cache.changes.version();
+        let summary = update().apply(&cache, ApplyMode::Replace);
+        assert_eq!(
+            summary.routes,
+            Counts {
+                created: 1,
+                updated: 1,
+                deleted: 1
+            }
+        );
+        assert!(!cache.routes.contains_key("r2"));
+        // The untouched upstream is not reported to workers.
+        let (changes, _) = cache.changes.since(v).unwrap();
+        assert!(changes.upstreams.is_empty());
+        assert_eq!(changes.routes.len(), 3);
+    }
+
+    #[test]
+    fn resulting_merge_resolves_references_to_existing_objects() {
+        let cache = seeded();
+        let only_route = doc(serde_json::json!({
+            "routes": [{ "id": "r9", "uri": "/nine", "upstream_id": "u1" }]
+        }));
+        let merged = only_route.resulting(&cache, ApplyMode::Merge);
+        assert!(merged.errors(&|_| Ok(())).is_empty());
+        assert_eq!(merged.routes.len(), 3);
+        let replaced = only_route.resulting(&cache, ApplyMode::Replace);
+        assert_eq!(
+            replaced.errors(&|_| Ok(())),
+            ["route `r9`: unknown upstream `u1`"]
+        );
+    }
+}
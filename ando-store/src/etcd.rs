@@ -1,6 +1,15 @@
 use crate::cache::ConfigCache;
 use crate::schema::Schema;
-use anyhow::Result;
+use crate::store::{ConfigStore, Kind};
+use crate::watcher::ConfigWatcher;
+use ando_core::config::{EtcdConfig, EtcdTlsConfig};
+use ando_core::router::Router;
+use anyhow::{Context, Result};
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use serde_json::Value;
+use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
 /// etcd client wrapper for CRUD operations.
@@ -10,13 +19,13 @@ pub struct EtcdStore {
 }
 
 impl EtcdStore {
-    /// Connect to etcd.
-    pub async fn connect(endpoints: &[String], prefix: &str) -> Result<Self> {
-        let client = etcd_client::Client::connect(endpoints, None).await?;
-        info!("Connected to etcd at {:?}", endpoints);
+    /// Connect to etcd with the credentials and TLS settings of `config`.
+    pub async fn connect(config: &EtcdConfig) -> Result<Self> {
+        let client = connect_client(config).await?;
+        info!("Connected to etcd at {:?}", config.endpoints);
         Ok(Self {
             client,
-            schema: Schema::new(prefix),
+            schema: Schema::new(&config.prefix),
         })
     }
 
@@ -26,7 +35,8 @@ impl EtcdStore {
         self.load_services(cache).await?;
         self.load_upstreams(cache).await?;
         self.load_consumers(cache).await?;
-        cache.rebuild_consumer_key_index();
+        self.load_ssl(cache).await?;
+        cache.reindex_consumers();
         info!("Loaded all config from etcd");
         Ok(())
     }
@@ -103,6 +113,23 @@ impl EtcdStore {
         Ok(())
     }
 
+    async fn load_ssl(&mut self, cache: &ConfigCache) -> Result<()> {
+        let prefix = self.schema.ssl_prefix();
+        let resp = self
+            .client
+            .get(
+                prefix.as_bytes(),
+                Some(etcd_client::GetOptions::new().with_prefix()),
+            )
+            .await?;
+        for kv in resp.kvs() {
+            if let Ok(ssl) = serde_json::from_slice::<ando_core::ssl::SslCertificate>(kv.value()) {
+                cache.ssl_certs.insert(ssl.id.clone(), ssl);
+            }
+        }
+        Ok(())
+    }
+
     /// Put a route into etcd.
     pub async fn put_route(&mut self, route: &ando_core::route::Route) -> Result<()> {
         let key = self.schema.route_key(&route.id);
@@ -149,6 +176,121 @@ impl EtcdStore {
     }
 }
 
+#[async_trait]
+impl ConfigStore for EtcdStore {
+    async fn list(&self, kind: Kind) -> Result<Vec<Value>> {
+        let resp = self
+            .client
+            .clone()
+            .get(
+                self.schema.kind_prefix(kind),
+                Some(etcd_client::GetOptions::new().with_prefix()),
+            )
+            .await?;
+        Ok(resp
+            .kvs()
+            .iter()
+            .filter_map(|kv| serde_json::from_slice(kv.value()).ok())
+            .collect())
+    }
+
+    async fn get(&self, kind: Kind, id: &str) -> Result<Option<Value>> {
+        let resp = self
+            .client
+            .clone()
+            .get(self.schema.key(kind, id), None)
+            .await?;
+        match resp.kvs().first() {
+            Some(kv) => Ok(Some(serde_json::from_slice(kv.value())?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, kind: Kind, id: &str, value: &Value) -> Result<()> {
+        let value = serde_json::to_vec(value)?;
+        self.client
+            .clone()
+            .put(self.schema.key(kind, id), value, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, kind: Kind, id: &str) -> Result<()> {
+        self.client
+            .clone()
+            .delete(self.schema.key(kind, id), None)
+            .await?;
+        Ok(())
+    }
+
+    async fn watch(
+        &self,
+        cache: ConfigCache,
+        router: Arc<ArcSwap<Router>>,
+        notify: crossbeam_channel::Sender<()>,
+    ) -> Result<()> {
+        ConfigWatcher::new(&self.schema.root_prefix())
+            .with_router(router)
+            .watch_client(self.client.clone(), cache, notify)
+            .await;
+        Ok(())
+    }
+}
+
+/// Open an etcd client for `config`. Credentials are checked here, so a
+/// wrong password or a rejected certificate fails the connection with
+/// etcd's own reason.
+pub(crate) async fn connect_client(config: &EtcdConfig) -> Result<etcd_client::Client> {
+    let options = connect_options(config)?;
+    etcd_client::Client::connect(&config.endpoints, Some(options))
+        .await
+        .with_context(|| format!("cannot connect to etcd at {}", config.endpoints.join(", ")))
+}
+
+fn connect_options(config: &EtcdConfig) -> Result<etcd_client::ConnectOptions> {
+    let mut options = etcd_client::ConnectOptions::new()
+        .with_connect_timeout(Duration::from_secs(config.timeout_secs));
+    if let Some((username, password)) = config.credentials() {
+        options = options.with_user(username, password);
+    }
+    if let Some(ref tls) = config.tls {
+        options = options.with_tls(tls_options(tls)?);
+    }
+    Ok(options)
+}
+
+fn tls_options(tls: &EtcdTlsConfig) -> Result<etcd_client::TlsOptions> {
+    if tls.insecure_skip_verify {
+        anyhow::bail!(
+            "deployment.etcd.tls.insecure_skip_verify is not supported: \
+             set tls.ca_cert to the CA that signed the etcd server certificates"
+        );
+    }
+    let read = |field: &str, path: &str| {
+        std::fs::read(path)
+            .with_context(|| format!("deployment.etcd.tls.{field}: cannot read {path}"))
+    };
+    let mut options = etcd_client::TlsOptions::new();
+    options = match tls.ca_cert {
+        Some(ref path) => {
+            options.ca_certificate(etcd_client::Certificate::from_pem(read("ca_cert", path)?))
+        }
+        None => options.with_native_roots(),
+    };
+    match (&tls.client_cert, &tls.client_key) {
+        (Some(cert), Some(key)) => {
+            let identity = etcd_client::Identity::from_pem(
+                read("client_cert", cert)?,
+                read("client_key", key)?,
+            );
+            options = options.identity(identity);
+        }
+        (None, None) => {}
+        _ => anyhow::bail!("deployment.etcd.tls: client_cert and client_key must be set together"),
+    }
+    Ok(options)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -186,6 +328,8 @@ mod tests {
             upstream_id: Some("ups1".into()),
             upstream: None,
             plugins: HashMap::new(),
+            hosts: Vec::new(),
+            timeout: None,
             labels: HashMap::new(),
         };
         let bytes = serde_json::to_vec(&svc).unwrap();
@@ -331,7 +475,7 @@ mod tests {
             labels: HashMap::new(),
         };
         cache.consumers.insert(consumer.username.clone(), consumer);
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
 
         assert_eq!(cache.routes.len(), 1);
         assert_eq!(cache.services.len(), 1);
@@ -339,4 +483,58 @@ mod tests {
         assert_eq!(cache.consumers.len(), 1);
         assert_eq!(cache.find_consumer_by_key("k1"), Some("alice".to_string()));
     }
+
+    // ── Connection settings ──
+
+    fn etcd_config(tls: serde_json::Value) -> EtcdConfig {
+        serde_json::from_value(serde_json::json!({
+            "endpoints": ["https://127.0.0.1:2379"],
+            "tls": tls
+        }))
+        .unwrap()
+    }
+
+    fn options_error(tls: serde_json::Value) -> String {
+        format!("{:#}", connect_options(&etcd_config(tls)).unwrap_err())
+    }
+
+    #[test]
+    fn tls_settings_are_checked_before_connecting() {
+        let err = options_error(serde_json::json!({"insecure_skip_verify": true}));
+        assert!(
+            err.contains("insecure_skip_verify is not supported"),
+            "{err}"
+        );
+
+        let err = options_error(serde_json::json!({"client_cert": "/tmp/cert.pem"}));
+        assert!(err.contains("must be set together"), "{err}");
+
+        let err = options_error(serde_json::json!({"ca_cert": "/nonexistent/ca.pem"}));
+        assert!(
+            err.contains("tls.ca_cert: cannot read /nonexistent/ca.pem"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn tls_settings_load_pem_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let pem = dir.path().join("ca.pem");
+        std::fs::write(
+            &pem,
+            "-----BEGIN CERTIFICATE-----\n-----END CERTIFICATE-----\n",
+        )
+        .unwrap();
+        let path = pem.to_str().unwrap();
+        assert!(
+            connect_options(&etcd_config(serde_json::json!({
+                "ca_cert": path,
+                "client_cert": path,
+                "client_key": path
+            })))
+            .is_ok()
+        );
+        // System roots without a CA file.
+        assert!(connect_options(&etcd_config(serde_json::json!({}))).is_ok());
+    }
 }
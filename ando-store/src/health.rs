@@ -0,0 +1,496 @@
+use ando_core::upstream::PassiveHealthCheck;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Health state of a single upstream node, as seen by the active checker.
+#[derive(Debug, Clone, Serialize)]
+pub struct NodeHealth {
+    pub healthy: bool,
+    pub consecutive_successes: u32,
+    pub consecutive_failures: u32,
+    /// Unix timestamp (seconds) of the last completed probe.
+    pub last_checked_unix: u64,
+    /// Error or unexpected status from the last failed probe.
+    pub last_error: Option<String>,
+}
+
+impl Default for NodeHealth {
+    fn default() -> Self {
+        Self {
+            healthy: true,
+            consecutive_successes: 0,
+            consecutive_failures: 0,
+            last_checked_unix: 0,
+            last_error: None,
+        }
+    }
+}
+
+/// Shared upstream health table: upstream id → node address → state.
+///
+/// v2 design: Written only by the health checker thread and read by the
+/// admin API. Worker cores never touch the DashMap on the hot path — they
+/// compare `version()` on accept and pull a fresh `unhealthy_snapshot()`
+/// only when a node has changed state.
+#[derive(Clone, Default)]
+pub struct HealthTable {
+    nodes: Arc<DashMap<String, HashMap<String, NodeHealth>>>,
+    version: Arc<AtomicU64>,
+}
+
+impl HealthTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a probe result. The node flips to unhealthy after
+    /// `unhealthy_after` consecutive failures and back to healthy after
+    /// `healthy_after` consecutive successes. Returns true if it flipped.
+    pub fn record(
+        &self,
+        upstream_id: &str,
+        addr: &str,
+        result: Result<(), String>,
+        healthy_after: u32,
+        unhealthy_after: u32,
+        now_unix: u64,
+    ) -> bool {
+        let mut entry = self.nodes.entry(upstream_id.to_string()).or_default();
+        let node = entry.entry(addr.to_string()).or_default();
+        node.last_checked_unix = now_unix;
+
+        let was_healthy = node.healthy;
+        match result {
+            Ok(()) => {
+                node.consecutive_successes = node.consecutive_successes.saturating_add(1);
+                node.consecutive_failures = 0;
+                node.last_error = None;
+                if !node.healthy && node.consecutive_successes >= healthy_after.max(1) {
+                    node.healthy = true;
+                }
+            }
+            Err(e) => {
+                node.consecutive_failures = node.consecutive_failures.saturating_add(1);
+                node.consecutive_successes = 0;
+                node.last_error = Some(e);
+                if node.healthy && node.consecutive_failures >= unhealthy_after.max(1) {
+                    node.healthy = false;
+                }
+            }
+        }
+
+        let flipped = was_healthy != node.healthy;
+        if flipped {
+            self.version.fetch_add(1, Ordering::Release);
+        }
+        flipped
+    }
+
+    /// Whether a node is currently considered healthy. Unknown nodes are.
+    pub fn is_healthy(&self, upstream_id: &str, addr: &str) -> bool {
+        self.nodes
+            .get(upstream_id)
+            .and_then(|n| n.get(addr).map(|h| h.healthy))
+            .unwrap_or(true)
+    }
+
+    /// Bumped every time a node changes state.
+    #[inline]
+    pub fn version(&self) -> u64 {
+        self.version.load(Ordering::Acquire)
+    }
+
+    /// Upstream id → addresses of nodes currently marked unhealthy.
+    pub fn unhealthy_snapshot(&self) -> HashMap<String, HashSet<String>> {
+        self.nodes
+            .iter()
+            .filter_map(|entry| {
+                let down: HashSet<String> = entry
+                    .value()
+                    .iter()
+                    .filter(|(_, h)| !h.healthy)
+                    .map(|(addr, _)| addr.clone())
+                    .collect();
+                (!down.is_empty()).then(|| (entry.key().clone(), down))
+            })
+            .collect()
+    }
+
+    /// Health state of every node of one upstream.
+    pub fn upstream(&self, upstream_id: &str) -> Option<HashMap<String, NodeHealth>> {
+        self.nodes.get(upstream_id).map(|n| n.value().clone())
+    }
+
+    /// Health state of every checked upstream.
+    pub fn all(&self) -> HashMap<String, HashMap<String, NodeHealth>> {
+        self.nodes
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect()
+    }
+
+    /// Drop state for nodes that are no longer configured. `keep` returns
+    /// true for (upstream id, address) pairs that are still checked.
+    pub fn retain(&self, keep: impl Fn(&str, &str) -> bool) {
+        let mut changed = false;
+        self.nodes.retain(|ups, nodes| {
+            nodes.retain(|addr, h| {
+                let kept = keep(ups, addr);
+                changed |= !kept && !h.healthy;
+                kept
+            });
+            !nodes.is_empty()
+        });
+        if changed {
+            self.version.fetch_add(1, Ordering::Release);
+        }
+    }
+}
+
+// ── Passive health (circuit breakers) ────────────────────────
+
+/// Failure reported by the proxy for a single upstream exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpstreamFailure {
+    /// Connect, write or read error.
+    Tcp,
+    /// Response status listed in `unhealthy.http_statuses`.
+    Http,
+}
+
+/// Circuit breaker state change, reported for metrics and logging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerTransition {
+    Opened,
+    Closed,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    tcp_failures: u32,
+    http_failures: u32,
+    /// First failure counted; the counts start over once it is older than
+    /// the check's `window`.
+    window_start: Option<Instant>,
+    /// Set while the breaker is open. Once it has passed, the node is
+    /// half-open: one trial request is let through and its result decides.
+    open_until: Option<Instant>,
+    /// While a half-open trial is in flight, other requests are refused
+    /// until then. A trial whose outcome never comes frees up after it.
+    trial_until: Option<Instant>,
+    cooldown: Duration,
+}
+
+/// Per-node circuit breakers shared by all workers, keyed by address.
+///
+/// v2 design: Only nodes with recent failures have an entry, and workers
+/// skip the DashMap entirely while `tracked` is zero — the common case.
+#[derive(Clone, Default)]
+pub struct CircuitBreakers {
+    nodes: Arc<DashMap<String, Breaker>>,
+    tracked: Arc<AtomicUsize>,
+}
+
+impl CircuitBreakers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `addr` is currently tripped and must not receive traffic:
+    /// open, or half-open with its trial request in flight.
+    #[inline]
+    pub fn is_open(&self, addr: &str, now: Instant) -> bool {
+        if self.tracked.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        self.nodes.get(addr).is_some_and(|b| {
+            b.open_until.is_some_and(|until| now < until)
+                || b.trial_until.is_some_and(|until| now < until)
+        })
+    }
+
+    /// Claim the trial request of `addr` if it is half-open. `false` when
+    /// another request holds it; a node that is not half-open is always
+    /// admitted.
+    pub fn try_trial(&self, addr: &str, now: Instant) -> bool {
+        if self.tracked.load(Ordering::Relaxed) == 0 {
+            return true;
+        }
+        // The read guard is gone before `get_mut` below.
+        let half_open = self
+            .nodes
+            .get(addr)
+            .and_then(|b| b.open_until)
+            .is_some_and(|until| now >= until);
+        if !half_open {
+            return true;
+        }
+        let Some(mut breaker) = self.nodes.get_mut(addr) else {
+            return true;
+        };
+        if breaker.trial_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        breaker.trial_until = Some(now + breaker.cooldown);
+        true
+    }
+
+    /// Seconds until the first of `addrs` leaves the open state.
+    pub fn retry_after<'a>(&self, addrs: impl IntoIterator<Item = &'a str>, now: Instant) -> u64 {
+        addrs
+            .into_iter()
+            .filter_map(|a| self.nodes.get(a).and_then(|b| b.open_until))
+            .map(|until| until.saturating_duration_since(now).as_secs().max(1))
+            .min()
+            .unwrap_or(1)
+    }
+
+    /// Record a failed exchange with `addr`. Trips the breaker after
+    /// `cfg.unhealthy` consecutive failures within `cfg.window` seconds,
+    /// or immediately when a half-open trial fails.
+    pub fn record_failure(
+        &self,
+        addr: &str,
+        failure: UpstreamFailure,
+        cfg: &PassiveHealthCheck,
+        now: Instant,
+    ) -> Option<BreakerTransition> {
+        let mut entry = self.nodes.entry(addr.to_string()).or_insert_with(|| {
+            self.tracked.fetch_add(1, Ordering::Relaxed);
+            Breaker::default()
+        });
+        let breaker = entry.value_mut();
+        breaker.cooldown = Duration::from_secs(cfg.cooldown);
+
+        let window = Duration::from_secs(cfg.window);
+        if breaker
+            .window_start
+            .is_none_or(|start| now.saturating_duration_since(start) >= window)
+        {
+            breaker.tcp_failures = 0;
+            breaker.http_failures = 0;
+            breaker.window_start = Some(now);
+        }
+        let (count, threshold) = match failure {
+            UpstreamFailure::Tcp => (&mut breaker.tcp_failures, cfg.unhealthy.tcp_failures),
+            UpstreamFailure::Http => (&mut breaker.http_failures, cfg.unhealthy.http_failures),
+        };
+        *count = count.saturating_add(1);
+        let tripped = *count >= threshold.max(1);
+
+        match breaker.open_until {
+            Some(until) if now < until => None,
+            Some(_) => {
+                // Half-open trial failed — reopen for another cooldown.
+                breaker.open_until = Some(now + breaker.cooldown);
+                breaker.trial_until = None;
+                None
+            }
+            None if tripped => {
+                breaker.open_until = Some(now + breaker.cooldown);
+                Some(BreakerTransition::Opened)
+            }
+            None => None,
+        }
+    }
+
+    /// Record a successful exchange with `addr`, closing its breaker.
+    #[inline]
+    pub fn record_success(&self, addr: &str) -> Option<BreakerTransition> {
+        if self.tracked.load(Ordering::Relaxed) == 0 {
+            return None;
+        }
+        let (_, breaker) = self.nodes.remove(addr)?;
+        self.tracked.fetch_sub(1, Ordering::Relaxed);
+        breaker.open_until.map(|_| BreakerTransition::Closed)
+    }
+
+    /// Addresses whose breaker is open or half-open.
+    pub fn open_nodes(&self) -> HashSet<String> {
+        self.nodes
+            .iter()
+            .filter(|b| b.open_until.is_some())
+            .map(|b| b.key().clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fail(t: &HealthTable, n: u32) {
+        for _ in 0..n {
+            t.record("u1", "a:1", Err("refused".into()), 2, 3, 1);
+        }
+    }
+
+    #[test]
+    fn unknown_node_is_healthy() {
+        let t = HealthTable::new();
+        assert!(t.is_healthy("u1", "a:1"));
+        assert!(t.unhealthy_snapshot().is_empty());
+    }
+
+    #[test]
+    fn node_ejected_after_threshold_failures() {
+        let t = HealthTable::new();
+        fail(&t, 2);
+        assert!(t.is_healthy("u1", "a:1"));
+        assert_eq!(t.version(), 0);
+        fail(&t, 1);
+        assert!(!t.is_healthy("u1", "a:1"));
+        assert_eq!(t.version(), 1);
+        assert!(t.unhealthy_snapshot()["u1"].contains("a:1"));
+    }
+
+    #[test]
+    fn node_recovers_after_threshold_successes() {
+        let t = HealthTable::new();
+        fail(&t, 3);
+        assert!(!t.record("u1", "a:1", Ok(()), 2, 3, 2));
+        assert!(t.record("u1", "a:1", Ok(()), 2, 3, 3));
+        assert!(t.is_healthy("u1", "a:1"));
+        assert_eq!(t.version(), 2);
+        let node = &t.upstream("u1").unwrap()["a:1"];
+        assert_eq!(node.last_checked_unix, 3);
+        assert!(node.last_error.is_none());
+    }
+
+    #[test]
+    fn success_resets_failure_streak() {
+        let t = HealthTable::new();
+        fail(&t, 2);
+        t.record("u1", "a:1", Ok(()), 2, 3, 1);
+        fail(&t, 2);
+        assert!(t.is_healthy("u1", "a:1"));
+    }
+
+    #[test]
+    fn retain_drops_removed_nodes_and_bumps_version() {
+        let t = HealthTable::new();
+        fail(&t, 3);
+        t.record("u1", "b:1", Ok(()), 2, 3, 1);
+        let v = t.version();
+        t.retain(|_, addr| addr == "b:1");
+        assert!(t.version() > v);
+        assert!(t.unhealthy_snapshot().is_empty());
+        assert_eq!(t.all()["u1"].len(), 1);
+    }
+
+    fn passive(http_failures: u32, tcp_failures: u32) -> PassiveHealthCheck {
+        serde_json::from_value(serde_json::json!({
+            "unhealthy": { "http_failures": http_failures, "tcp_failures": tcp_failures },
+            "cooldown": 10,
+            "window": 30
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn breaker_opens_after_consecutive_failures() {
+        let b = CircuitBreakers::new();
+        let cfg = passive(3, 2);
+        let now = Instant::now();
+        assert!(!b.is_open("a:1", now));
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, now),
+            None
+        );
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, now),
+            None
+        );
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, now),
+            Some(BreakerTransition::Opened)
+        );
+        assert!(b.is_open("a:1", now));
+        assert!(!b.is_open("b:1", now));
+        assert_eq!(b.retry_after(["a:1"], now), 10);
+
+        assert_eq!(
+            b.record_failure("b:1", UpstreamFailure::Tcp, &cfg, now),
+            None
+        );
+        assert_eq!(
+            b.record_failure("b:1", UpstreamFailure::Tcp, &cfg, now),
+            Some(BreakerTransition::Opened)
+        );
+    }
+
+    #[test]
+    fn success_resets_breaker_counts() {
+        let b = CircuitBreakers::new();
+        let cfg = passive(2, 2);
+        let now = Instant::now();
+        b.record_failure("a:1", UpstreamFailure::Http, &cfg, now);
+        assert_eq!(b.record_success("a:1"), None);
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, now),
+            None
+        );
+        assert!(!b.is_open("a:1", now));
+    }
+
+    #[test]
+    fn breaker_half_opens_after_cooldown() {
+        let b = CircuitBreakers::new();
+        let cfg = passive(1, 1);
+        let now = Instant::now();
+        b.record_failure("a:1", UpstreamFailure::Tcp, &cfg, now);
+        let later = now + Duration::from_secs(11);
+        assert!(!b.is_open("a:1", later), "cooldown elapsed: trial allowed");
+        assert!(b.try_trial("a:1", later));
+        assert!(b.is_open("a:1", later), "one trial at a time");
+        assert!(!b.try_trial("a:1", later));
+
+        // Failed trial reopens without a second transition.
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Tcp, &cfg, later),
+            None
+        );
+        assert!(b.is_open("a:1", later));
+
+        // Successful trial closes the breaker.
+        let trial = later + Duration::from_secs(11);
+        assert_eq!(b.record_success("a:1"), Some(BreakerTransition::Closed));
+        assert!(!b.is_open("a:1", trial));
+        assert!(b.open_nodes().is_empty());
+    }
+
+    #[test]
+    fn lost_trial_frees_up_after_a_cooldown() {
+        let b = CircuitBreakers::new();
+        let now = Instant::now();
+        b.record_failure("a:1", UpstreamFailure::Tcp, &passive(1, 1), now);
+        let later = now + Duration::from_secs(11);
+        assert!(b.try_trial("a:1", later));
+        assert!(b.is_open("a:1", later + Duration::from_secs(5)));
+        assert!(!b.is_open("a:1", later + Duration::from_secs(10)));
+        assert!(b.try_trial("a:1", later + Duration::from_secs(10)));
+        assert!(b.try_trial("b:1", later), "closed nodes are admitted");
+    }
+
+    #[test]
+    fn failures_outside_the_window_start_over() {
+        let b = CircuitBreakers::new();
+        let cfg = passive(2, 2);
+        let now = Instant::now();
+        b.record_failure("a:1", UpstreamFailure::Http, &cfg, now);
+        let later = now + Duration::from_secs(31);
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, later),
+            None
+        );
+        assert!(!b.is_open("a:1", later));
+        assert_eq!(
+            b.record_failure("a:1", UpstreamFailure::Http, &cfg, later),
+            Some(BreakerTransition::Opened)
+        );
+    }
+}
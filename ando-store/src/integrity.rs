@@ -0,0 +1,267 @@
+//! Cross-reference checks over the config cache.
+//!
+//! v2 design: References are checked against the cache as it is, so the
+//! same functions serve the Admin API (before a write lands), the etcd
+//! watcher (after a change was applied) and the orphan report. Plugin
+//! names are checked against a caller-supplied predicate because the
+//! plugin registry lives above the store.
+
+use crate::cache::ConfigCache;
+use ando_core::route::Route;
+use ando_core::service::Service;
+use serde::Serialize;
+use std::fmt;
+
+/// What a reference points at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefTarget {
+    Upstream,
+    Service,
+    PluginConfig,
+    Plugin,
+}
+
+impl RefTarget {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Upstream => "upstream",
+            Self::Service => "service",
+            Self::PluginConfig => "plugin_config",
+            Self::Plugin => "plugin",
+        }
+    }
+
+    /// Field holding the reference.
+    pub fn field(self) -> &'static str {
+        match self {
+            Self::Upstream => "upstream_id",
+            Self::Service => "service_id",
+            Self::PluginConfig => "plugin_config_id",
+            Self::Plugin => "plugins",
+        }
+    }
+}
+
+/// A reference from one config object to another that does not exist.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct BrokenRef {
+    /// Kind of the referencing object: `route`, `service` or
+    /// `plugin_config`.
+    pub kind: &'static str,
+    pub id: String,
+    pub target: RefTarget,
+    /// The missing id, or plugin name.
+    pub missing: String,
+}
+
+impl fmt::Display for BrokenRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} `{}`: unknown {} `{}`",
+            self.kind,
+            self.id,
+            self.target.as_str(),
+            self.missing
+        )
+    }
+}
+
+fn broken(kind: &'static str, id: &str, target: RefTarget, missing: &str) -> BrokenRef {
+    BrokenRef {
+        kind,
+        id: id.to_string(),
+        target,
+        missing: missing.to_string(),
+    }
+}
+
+/// References of `route` to upstreams, services and plugin configs
+/// missing from `cache`.
+pub fn route_refs(cache: &ConfigCache, route: &Route) -> Vec<BrokenRef> {
+    let mut refs = Vec::new();
+    if let Some(ref id) = route.upstream_id
+        && !cache.upstreams.contains_key(id)
+    {
+        refs.push(broken("route", &route.id, RefTarget::Upstream, id));
+    }
+    if let Some(ref id) = route.service_id
+        && !cache.services.contains_key(id)
+    {
+        refs.push(broken("route", &route.id, RefTarget::Service, id));
+    }
+    if let Some(ref id) = route.plugin_config_id
+        && !cache.plugin_configs.contains_key(id)
+    {
+        refs.push(broken("route", &route.id, RefTarget::PluginConfig, id));
+    }
+    refs
+}
+
+/// References of `service` to upstreams missing from `cache`.
+pub fn service_refs(cache: &ConfigCache, service: &Service) -> Vec<BrokenRef> {
+    match service.upstream_id {
+        Some(ref id) if !cache.upstreams.contains_key(id) => {
+            vec![broken("service", &service.id, RefTarget::Upstream, id)]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Every dangling id reference in `cache`, sorted.
+pub fn broken_refs(cache: &ConfigCache) -> Vec<BrokenRef> {
+    let mut refs: Vec<BrokenRef> = cache
+        .routes
+        .iter()
+        .flat_map(|r| route_refs(cache, r.value()))
+        .chain(
+            cache
+                .services
+                .iter()
+                .flat_map(|s| service_refs(cache, s.value())),
+        )
+        .collect();
+    refs.sort();
+    refs
+}
+
+/// Plugins of routes, services, plugin configs and global rules for
+/// which `known` is false, sorted.
+pub fn unknown_plugins(cache: &ConfigCache, known: &dyn Fn(&str) -> bool) -> Vec<BrokenRef> {
+    let mut refs = Vec::new();
+    let mut check = |kind, id: &str, names: &mut dyn Iterator<Item = &String>| {
+        for name in names.filter(|n| !known(n)) {
+            refs.push(broken(kind, id, RefTarget::Plugin, name));
+        }
+    };
+    for r in cache.routes.iter() {
+        check("route", &r.id, &mut r.plugins.keys());
+    }
+    for s in cache.services.iter() {
+        check("service", &s.id, &mut s.plugins.keys());
+    }
+    for pc in cache.plugin_configs.iter() {
+        check("plugin_config", &pc.id, &mut pc.plugins.keys());
+    }
+    for g in cache.global_rules.iter() {
+        check("global_rule", &g.id, &mut g.plugins.keys());
+    }
+    refs.sort();
+    refs
+}
+
+/// References that removing `target` `id` would break, sorted.
+pub fn refs_to(cache: &ConfigCache, target: RefTarget, id: &str) -> Vec<BrokenRef> {
+    let points_here = |r: &Option<String>| r.as_deref() == Some(id);
+    let mut refs: Vec<BrokenRef> = Vec::new();
+    for r in cache.routes.iter() {
+        let field = match target {
+            RefTarget::Upstream => &r.upstream_id,
+            RefTarget::Service => &r.service_id,
+            RefTarget::PluginConfig => &r.plugin_config_id,
+            RefTarget::Plugin => continue,
+        };
+        if points_here(field) {
+            refs.push(broken("route", &r.id, target, id));
+        }
+    }
+    if target == RefTarget::Upstream {
+        for s in cache.services.iter() {
+            if points_here(&s.upstream_id) {
+                refs.push(broken("service", &s.id, target, id));
+            }
+        }
+    }
+    refs.sort();
+    refs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ando_core::plugin_config::PluginConfig;
+    use ando_core::upstream::Upstream;
+
+    fn seeded() -> ConfigCache {
+        let cache = ConfigCache::new();
+        let ups: Upstream =
+            serde_json::from_value(serde_json::json!({"id": "u1", "nodes": {"127.0.0.1:80": 1}}))
+                .unwrap();
+        cache.upstreams.insert("u1".into(), ups);
+        let svc: Service = serde_json::from_value(serde_json::json!({
+            "id": "s1", "upstream_id": "u1", "plugins": {"cors": {}}
+        }))
+        .unwrap();
+        cache.services.insert("s1".into(), svc);
+        let pc: PluginConfig =
+            serde_json::from_value(serde_json::json!({"id": "pc1", "plugins": {"ee-only": {}}}))
+                .unwrap();
+        cache.plugin_configs.insert("pc1".into(), pc);
+        for route in [
+            serde_json::json!({"id": "r1", "uri": "/a", "upstream_id": "u1"}),
+            serde_json::json!({"id": "r2", "uri": "/b", "service_id": "s1", "plugin_config_id": "pc1"}),
+        ] {
+            let route: Route = serde_json::from_value(route).unwrap();
+            cache.routes.insert(route.id.clone(), route);
+        }
+        cache
+    }
+
+    fn messages(refs: &[BrokenRef]) -> Vec<String> {
+        refs.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn consistent_config_has_no_broken_refs() {
+        assert!(broken_refs(&seeded()).is_empty());
+    }
+
+    #[test]
+    fn broken_refs_lists_every_dangling_id() {
+        let cache = seeded();
+        cache.upstreams.remove("u1");
+        cache.plugin_configs.remove("pc1");
+        let route: Route = serde_json::from_value(
+            serde_json::json!({"id": "r3", "uri": "/c", "service_id": "gone"}),
+        )
+        .unwrap();
+        cache.routes.insert("r3".into(), route);
+        assert_eq!(
+            messages(&broken_refs(&cache)),
+            [
+                "route `r1`: unknown upstream `u1`",
+                "route `r2`: unknown plugin_config `pc1`",
+                "route `r3`: unknown service `gone`",
+                "service `s1`: unknown upstream `u1`",
+            ]
+        );
+    }
+
+    #[test]
+    fn refs_to_names_the_objects_a_delete_would_break() {
+        let cache = seeded();
+        assert_eq!(
+            messages(&refs_to(&cache, RefTarget::Upstream, "u1")),
+            [
+                "route `r1`: unknown upstream `u1`",
+                "service `s1`: unknown upstream `u1`",
+            ]
+        );
+        assert_eq!(
+            messages(&refs_to(&cache, RefTarget::Service, "s1")),
+            ["route `r2`: unknown service `s1`"]
+        );
+        assert!(refs_to(&cache, RefTarget::Upstream, "u2").is_empty());
+    }
+
+    #[test]
+    fn unknown_plugins_checks_every_plugin_block() {
+        let cache = seeded();
+        let refs = unknown_plugins(&cache, &|name| name == "cors");
+        assert_eq!(
+            messages(&refs),
+            ["plugin_config `pc1`: unknown plugin `ee-only`"]
+        );
+    }
+}
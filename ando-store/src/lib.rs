@@ -1,4 +1,16 @@
 pub mod cache;
+pub mod changes;
+pub mod credentials;
+pub mod discovery;
+pub mod dns;
+pub mod document;
 pub mod etcd;
+pub mod health;
+pub mod integrity;
+pub mod notify;
 pub mod schema;
+pub mod sql;
+pub mod standalone;
+pub mod store;
+pub mod sync_status;
 pub mod watcher;
@@ -0,0 +1,97 @@
+use crossbeam_channel::{Receiver, Sender, TrySendError};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// "Config changed" signal fanned out to the worker cores.
+///
+/// v2 design: Every worker subscribes once and owns a `bounded(1)`
+/// crossbeam receiver. Writers of the cache call `notify` once the cache
+/// and router are updated; a worker that has not drained its receiver yet
+/// already has a signal pending, so a burst of writes coalesces into one
+/// refresh per worker. Workers drain non-blockingly, so the signal never
+/// stalls a monoio event loop.
+#[derive(Clone, Default)]
+pub struct ConfigNotifier {
+    subscribers: Arc<Mutex<Vec<Sender<()>>>>,
+    /// Unix time of the last `notify`, in milliseconds; 0 before any.
+    last_notified_ms: Arc<AtomicU64>,
+}
+
+impl ConfigNotifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A receiver that gets a signal after each burst of changes.
+    pub fn subscribe(&self) -> Receiver<()> {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        self.lock().push(tx);
+        rx
+    }
+
+    /// Signal every subscriber. Subscribers that went away are dropped.
+    pub fn notify(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        self.last_notified_ms
+            .store(now.as_millis() as u64, Ordering::Relaxed);
+        self.lock()
+            .retain(|tx| !matches!(tx.try_send(()), Err(TrySendError::Disconnected(()))));
+    }
+
+    /// When workers were last told to reload; `None` before the first
+    /// change since startup.
+    pub fn last_notified(&self) -> Option<SystemTime> {
+        match self.last_notified_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(UNIX_EPOCH + Duration::from_millis(ms)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Sender<()>>> {
+        self.subscribers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Drain `changes`; whether any signal was pending.
+pub fn pending(changes: &Receiver<()>) -> bool {
+    changes.try_iter().count() > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn burst_coalesces_into_one_signal_per_subscriber() {
+        let notifier = ConfigNotifier::new();
+        let (a, b) = (notifier.subscribe(), notifier.subscribe());
+        for _ in 0..5 {
+            notifier.notify();
+        }
+        assert!(pending(&a));
+        assert!(!pending(&a));
+        assert!(pending(&b));
+    }
+
+    #[test]
+    fn notify_records_when_it_happened() {
+        let notifier = ConfigNotifier::new();
+        assert!(notifier.last_notified().is_none());
+        let before = SystemTime::now() - Duration::from_millis(1);
+        notifier.notify();
+        assert!(notifier.last_notified().unwrap() >= before);
+    }
+
+    #[test]
+    fn dropped_subscribers_are_forgotten() {
+        let notifier = ConfigNotifier::new();
+        drop(notifier.subscribe());
+        let live = notifier.subscribe();
+        notifier.notify();
+        assert_eq!(notifier.lock().len(), 1);
+        assert!(pending(&live));
+    }
+}
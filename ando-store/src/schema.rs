@@ -1,3 +1,5 @@
+use crate::store::Kind;
+
 /// etcd key schema (APISIX-compatible).
 pub struct Schema {
     prefix: String,
@@ -10,6 +12,20 @@ impl Schema {
         }
     }
 
+    /// Prefix of every key, e.g. `/ando/`.
+    pub fn root_prefix(&self) -> String {
+        format!("{}/", self.prefix)
+    }
+
+    /// Prefix of every key of `kind`, e.g. `/ando/routes/`.
+    pub fn kind_prefix(&self, kind: Kind) -> String {
+        format!("{}/{}/", self.prefix, kind.segment())
+    }
+
+    pub fn key(&self, kind: Kind, id: &str) -> String {
+        format!("{}/{}/{}", self.prefix, kind.segment(), id)
+    }
+
     pub fn routes_prefix(&self) -> String {
         format!("{}/routes/", self.prefix)
     }
@@ -57,6 +73,14 @@ impl Schema {
     pub fn plugin_config_key(&self, id: &str) -> String {
         format!("{}/plugin_configs/{}", self.prefix, id)
     }
+
+    pub fn global_rules_prefix(&self) -> String {
+        format!("{}/global_rules/", self.prefix)
+    }
+
+    pub fn global_rule_key(&self, id: &str) -> String {
+        format!("{}/global_rules/{}", self.prefix, id)
+    }
 }
 
 impl Default for Schema {
@@ -0,0 +1,229 @@
+//! SQL config store: Postgres or SQLite.
+//!
+//! v2 design: Every object is a row of `config_objects(kind, id,
+//! value_json, updated_at, version)`. Each write takes the next value of
+//! a single-row counter in the same transaction and stamps it on the row,
+//! so `version` orders all changes the way etcd revisions do. A delete
+//! keeps the row with a NULL value (a tombstone) so it can be seen.
+//!
+//! Change detection polls for rows with a newer version and hands them to
+//! `ConfigWatcher` as watch events, with the counter as the revision, so
+//! the cache and router are updated exactly as with etcd. The schema is
+//! created by migrations embedded in the binary, run on connect.
+
+use crate::cache::ConfigCache;
+use crate::store::{ConfigStore, Kind};
+use crate::watcher::{ConfigWatcher, Listing, WatchBatch, WatchError, WatchEvent, WatchSource};
+use ando_core::router::Router;
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use serde_json::Value;
+use sqlx::AnyPool;
+use sqlx::any::AnyPoolOptions;
+use sqlx::migrate::Migrator;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::info;
+
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Config store backed by a SQL database.
+pub struct SqlStore {
+    pool: AnyPool,
+    poll_interval: Duration,
+}
+
+impl SqlStore {
+    /// Connect to `url` (`postgres://…` or `sqlite:…`) and bring the
+    /// schema up to date.
+    pub async fn connect(url: &str, max_connections: u32) -> Result<Self> {
+        sqlx::any::install_default_drivers();
+        // Every connection to an in-memory SQLite database gets its own
+        // empty one, so a single connection must serve everything and
+        // never be closed.
+        let in_memory = url.contains(":memory:") || url.contains("mode=memory");
+        let options = if in_memory {
+            AnyPoolOptions::new()
+                .max_connections(1)
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+        } else {
+            AnyPoolOptions::new().max_connections(max_connections)
+        };
+        let pool = options.connect(url).await?;
+        MIGRATOR.run(&pool).await?;
+        info!(backend = %url.split(':').next().unwrap_or(""), "Connected to SQL config store");
+        Ok(Self {
+            pool,
+            poll_interval: Duration::from_secs(1),
+        })
+    }
+
+    /// How often `watch` polls for changes. Defaults to one second.
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Store `value` for `kind` `id`, or a tombstone when `None`.
+    async fn write(&self, kind: Kind, id: &str, value: Option<String>) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        let (version,): (i64,) = sqlx::query_as(
+            "UPDATE config_version SET version = version + 1 WHERE id = 1 RETURNING version",
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        sqlx::query(
+            "INSERT INTO config_objects (kind, id, value_json, updated_at, version) \
+             VALUES ($1, $2, $3, $4, $5) \
+             ON CONFLICT (kind, id) DO UPDATE SET value_json = excluded.value_json, \
+             updated_at = excluded.updated_at, version = excluded.version",
+        )
+        .bind(kind.as_str())
+        .bind(id)
+        .bind(value)
+        .bind(now_ms())
+        .bind(version)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConfigStore for SqlStore {
+    async fn list(&self, kind: Kind) -> Result<Vec<Value>> {
+        let rows: Vec<(String,)> = sqlx::query_as(
+            "SELECT value_json FROM config_objects \
+             WHERE kind = $1 AND value_json IS NOT NULL ORDER BY id",
+        )
+        .bind(kind.as_str())
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows
+            .iter()
+            .filter_map(|(json,)| serde_json::from_str(json).ok())
+            .collect())
+    }
+
+    async fn get(&self, kind: Kind, id: &str) -> Result<Option<Value>> {
+        let row: Option<(Option<String>,)> =
+            sqlx::query_as("SELECT value_json FROM config_objects WHERE kind = $1 AND id = $2")
+                .bind(kind.as_str())
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+        match row.and_then(|(json,)| json) {
+            Some(json) => Ok(Some(serde_json::from_str(&json)?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn put(&self, kind: Kind, id: &str, value: &Value) -> Result<()> {
+        self.write(kind, id, Some(value.to_string())).await
+    }
+
+    async fn delete(&self, kind: Kind, id: &str) -> Result<()> {
+        if self.get(kind, id).await?.is_none() {
+            return Ok(());
+        }
+        self.write(kind, id, None).await
+    }
+
+    async fn watch(
+        &self,
+        cache: ConfigCache,
+        router: Arc<ArcSwap<Router>>,
+        notify: crossbeam_channel::Sender<()>,
+    ) -> Result<()> {
+        info!(interval = ?self.poll_interval, "Starting SQL config poller");
+        let mut source = SqlSource {
+            store: self,
+            from: 0,
+        };
+        ConfigWatcher::new("")
+            .with_router(router)
+            .run(&mut source, &cache, &notify)
+            .await;
+        Ok(())
+    }
+}
+
+/// `WatchSource` polling `config_objects`. Keys are `/<segment>/<id>`,
+/// as the watcher expects.
+struct SqlSource<'a> {
+    store: &'a SqlStore,
+    /// Lowest version not delivered yet.
+    from: i64,
+}
+
+fn key(kind: &str, id: &str) -> Option<String> {
+    Kind::parse(kind).map(|k| format!("/{}/{id}", k.segment()))
+}
+
+impl WatchSource for SqlSource<'_> {
+    async fn list(&mut self, _prefix: &str) -> Result<Listing> {
+        // Read the counter first: rows changed after it are listed too,
+        // and delivered again by the next poll, which is harmless.
+        let (revision,): (i64,) = sqlx::query_as("SELECT version FROM config_version WHERE id = 1")
+            .fetch_one(&self.store.pool)
+            .await?;
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT kind, id, value_json FROM config_objects WHERE value_json IS NOT NULL",
+        )
+        .fetch_all(&self.store.pool)
+        .await?;
+        Ok(Listing {
+            revision,
+            kvs: rows
+                .into_iter()
+                .filter_map(|(kind, id, json)| Some((key(&kind, &id)?, json.into_bytes())))
+                .collect(),
+        })
+    }
+
+    async fn watch(&mut self, _prefix: &str, revision: i64) -> Result<()> {
+        self.from = revision;
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Result<WatchBatch, WatchError> {
+        loop {
+            let rows: Vec<(String, String, Option<String>, i64)> = sqlx::query_as(
+                "SELECT kind, id, value_json, version FROM config_objects \
+                 WHERE version >= $1 ORDER BY version",
+            )
+            .bind(self.from)
+            .fetch_all(&self.store.pool)
+            .await
+            .map_err(|e| WatchError::Lost(e.to_string()))?;
+            if rows.is_empty() {
+                tokio::time::sleep(self.store.poll_interval).await;
+                continue;
+            }
+            let mut batch = WatchBatch::default();
+            for (kind, id, json, version) in rows {
+                batch.revision = batch.revision.max(version);
+                let Some(key) = key(&kind, &id) else { continue };
+                batch.events.push(match json {
+                    Some(json) => WatchEvent::Put {
+                        key,
+                        value: json.into_bytes(),
+                    },
+                    None => WatchEvent::Delete { key },
+                });
+            }
+            self.from = batch.revision + 1;
+            return Ok(batch);
+        }
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as i64)
+}
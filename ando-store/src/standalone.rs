@@ -0,0 +1,248 @@
+//! File-based config provider for standalone mode.
+//!
+//! v2 design: The YAML file (APISIX standalone style: top-level `routes`,
+//! `services`, `upstreams`, `consumers`, `plugin_configs`, `ssls` and
+//! `global_rules` lists) is the source of truth.
+//! A dedicated thread polls its mtime; a changed file is parsed and fully
+//! validated as a `ConfigDocument`, plus a trial router build, before
+//! anything is touched. Only then is the `ConfigCache` replaced with its
+//! contents and the new router swapped in. A file that fails any check
+//! leaves the running config as is.
+
+use crate::cache::ConfigCache;
+use crate::document::{ApplyMode, ConfigDocument};
+use ando_core::route::Route;
+use ando_core::router::Router;
+use anyhow::{Context, bail};
+use arc_swap::ArcSwap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+
+/// Extra per-route check run during validation (e.g. plugin configs).
+pub type RouteCheck = Box<dyn Fn(&Route) -> Result<(), String> + Send>;
+
+/// Parse a standalone config file.
+pub fn parse(yaml: &str) -> anyhow::Result<ConfigDocument> {
+    // An empty file is far more likely a save in progress than an
+    // intentional wipe of every route.
+    if yaml.trim().is_empty() {
+        bail!("file is empty");
+    }
+    Ok(serde_yaml::from_str(yaml)?)
+}
+
+/// Loads a standalone config file into the cache and router, and reloads
+/// it when it changes.
+pub struct FileProvider {
+    path: PathBuf,
+    cache: ConfigCache,
+    router: Arc<ArcSwap<Router>>,
+    check: Option<RouteCheck>,
+    /// mtime and length of the file as last read, valid or not.
+    seen: Option<(SystemTime, u64)>,
+}
+
+impl FileProvider {
+    pub fn new(path: impl Into<PathBuf>, cache: ConfigCache, router: Arc<ArcSwap<Router>>) -> Self {
+        Self {
+            path: path.into(),
+            cache,
+            router,
+            check: None,
+            seen: None,
+        }
+    }
+
+    /// Run `check` on every route before a file is accepted.
+    pub fn with_route_check(mut self, check: RouteCheck) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read, validate and apply the file. On error nothing is changed.
+    pub fn load(&mut self) -> anyhow::Result<()> {
+        self.seen = file_stamp(&self.path);
+        let yaml = std::fs::read_to_string(&self.path)
+            .with_context(|| format!("reading {}", self.path.display()))?;
+        let doc = parse(&yaml)?;
+        let errors = doc.errors(&|route| self.check.as_ref().map_or(Ok(()), |check| check(route)));
+        if !errors.is_empty() {
+            bail!("{}", errors.join("; "));
+        }
+        let version = self.router.load().version() + 1;
+        let router = Router::build(doc.router_routes(), version)?;
+        let (routes, upstreams) = (doc.routes.len(), doc.upstreams.len());
+        doc.apply(&self.cache, ApplyMode::Replace);
+        self.router.store(Arc::new(router));
+        self.cache.notifier.notify();
+        info!(
+            path = %self.path.display(),
+            routes,
+            upstreams,
+            version,
+            "standalone: config loaded"
+        );
+        Ok(())
+    }
+
+    /// Reload if the file changed since it was last read. Returns whether
+    /// a new config was applied.
+    pub fn poll(&mut self) -> anyhow::Result<bool> {
+        if file_stamp(&self.path) == self.seen {
+            return Ok(false);
+        }
+        match self.load() {
+            Ok(()) => {
+                self.cache.sync.recovered();
+                Ok(true)
+            }
+            Err(e) => {
+                self.cache.sync.failed(&e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Poll the file every `interval` on a dedicated thread.
+    pub fn spawn(mut self, interval: Duration) -> std::thread::JoinHandle<()> {
+        std::thread::Builder::new()
+            .name("ando-config-file".to_string())
+            .spawn(move || {
+                loop {
+                    std::thread::sleep(interval);
+                    if let Err(e) = self.poll() {
+                        error!(
+                            path = %self.path.display(),
+                            error = %format!("{e:#}"),
+                            "standalone: invalid config file, keeping previous config"
+                        );
+                    }
+                }
+            })
+            .expect("Failed to spawn config file watcher thread")
+    }
+}
+
+fn file_stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let meta = std::fs::metadata(path).ok()?;
+    Some((meta.modified().ok()?, meta.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use tempfile::tempdir;
+
+    const INITIAL: &str = r#"
+upstreams:
+  - id: u1
+    nodes:
+      "127.0.0.1:8080": 1
+routes:
+  - id: r1
+    uri: /one
+    upstream_id: u1
+consumers:
+  - username: alice
+    plugins:
+      key-auth:
+        key: alice-key
+#END
+"#;
+
+    fn provider(dir: &Path) -> (FileProvider, ConfigCache, Arc<ArcSwap<Router>>) {
+        let cache = ConfigCache::new();
+        let router = Arc::new(ArcSwap::from_pointee(Router::build(vec![], 0).unwrap()));
+        let provider = FileProvider::new(dir.join("apisix.yaml"), cache.clone(), router.clone());
+        (provider, cache, router)
+    }
+
+    /// Write `yaml` and move the mtime forward so `poll` sees a change
+    /// even within the filesystem's timestamp granularity.
+    fn rewrite(path: &Path, yaml: &str) {
+        std::fs::write(path, yaml).unwrap();
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+    }
+
+    #[test]
+    fn load_populates_cache_and_router() {
+        let dir = tempdir().unwrap();
+        let (mut provider, cache, router) = provider(dir.path());
+        std::fs::write(provider.path(), INITIAL).unwrap();
+
+        provider.load().unwrap();
+        assert!(cache.upstreams.contains_key("u1"));
+        assert_eq!(
+            cache.find_consumer_by_key("alice-key").as_deref(),
+            Some("alice")
+        );
+        let router = router.load();
+        assert_eq!(router.version(), 1);
+        assert!(router.get_route("r1").is_some());
+    }
+
+    #[test]
+    fn poll_reloads_changed_file() {
+        let dir = tempdir().unwrap();
+        let (mut provider, cache, router) = provider(dir.path());
+        std::fs::write(provider.path(), INITIAL).unwrap();
+        provider.load().unwrap();
+        assert!(!provider.poll().unwrap(), "unchanged file is not reloaded");
+
+        let v = cache.changes.version();
+        let updated = INITIAL.replace(
+            "consumers:",
+            "  - id: r2\n    uri: /two\n    upstream_id: u1\nconsumers:",
+        );
+        rewrite(provider.path(), &updated);
+        assert!(provider.poll().unwrap());
+
+        let router = router.load();
+        assert_eq!(router.version(), 2);
+        assert!(router.get_route("r1").is_some());
+        assert!(router.get_route("r2").is_some());
+        // Only the new route is reported as changed.
+        let (changes, _) = cache.changes.since(v).unwrap();
+        assert_eq!(changes.routes, HashSet::from(["r2".to_string()]));
+        assert!(changes.upstreams.is_empty());
+        assert!(!changes.consumers);
+    }
+
+    #[test]
+    fn invalid_file_keeps_previous_config() {
+        let dir = tempdir().unwrap();
+        let (mut provider, cache, router) = provider(dir.path());
+        std::fs::write(provider.path(), INITIAL).unwrap();
+        provider.load().unwrap();
+
+        for bad in [
+            "routes: [ {id: r1, uri: /one",
+            "",
+            "routes:\n  - id: r1\n    uri: /one\n    upstream_id: missing\n",
+            "routes:\n  - id: r1\n    upstream_id: u1\n",
+        ] {
+            rewrite(provider.path(), bad);
+            assert!(provider.poll().is_err(), "{bad:?}");
+            assert_eq!(router.load().version(), 1);
+            assert!(router.load().get_route("r1").is_some());
+            assert!(cache.routes.contains_key("r1"));
+            assert!(cache.upstreams.contains_key("u1"));
+            // Logged once, not on every poll.
+            assert!(!provider.poll().unwrap());
+            assert!(cache.sync.last_error().is_some(), "{bad:?}");
+        }
+
+        rewrite(provider.path(), INITIAL);
+        assert!(provider.poll().unwrap());
+        assert!(cache.sync.last_error().is_none());
+    }
+}
@@ -0,0 +1,213 @@
+//! Backend-agnostic config storage.
+//!
+//! v2 design: `ConfigStore` is the one interface the Admin API and the
+//! server use to reach the config source of truth, so etcd and SQL
+//! (Postgres / SQLite) are interchangeable through `deployment.mode`.
+//! Objects travel as JSON values keyed by kind and id. Each backend also
+//! keeps a `ConfigCache` and router in sync with what is stored, which
+//! both do by feeding `ConfigWatcher`, so change handling is the same
+//! whatever the backend.
+
+use crate::cache::ConfigCache;
+use crate::document::{ApplyMode, ConfigDocument};
+use ando_core::router::Router;
+use anyhow::Result;
+use arc_swap::ArcSwap;
+use async_trait::async_trait;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Kind of config object a store holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Route,
+    Service,
+    Upstream,
+    Consumer,
+    PluginConfig,
+    Ssl,
+    GlobalRule,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 7] = [
+        Kind::Route,
+        Kind::Service,
+        Kind::Upstream,
+        Kind::Consumer,
+        Kind::PluginConfig,
+        Kind::Ssl,
+        Kind::GlobalRule,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Route => "route",
+            Self::Service => "service",
+            Self::Upstream => "upstream",
+            Self::Consumer => "consumer",
+            Self::PluginConfig => "plugin_config",
+            Self::Ssl => "ssl",
+            Self::GlobalRule => "global_rule",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|k| k.as_str() == s)
+    }
+
+    /// Key path segment, as in `/ando/routes/r1`.
+    pub fn segment(self) -> &'static str {
+        match self {
+            Self::Route => "routes",
+            Self::Service => "services",
+            Self::Upstream => "upstreams",
+            Self::Consumer => "consumers",
+            Self::PluginConfig => "plugin_configs",
+            Self::Ssl => "ssl",
+            Self::GlobalRule => "global_rules",
+        }
+    }
+
+    /// Id of a stored object; consumers are keyed by username.
+    pub fn id_of(self, value: &Value) -> Option<&str> {
+        let field = if self == Self::Consumer {
+            "username"
+        } else {
+            "id"
+        };
+        value.get(field)?.as_str()
+    }
+}
+
+/// Where config lives: etcd or a SQL database.
+#[async_trait]
+pub trait ConfigStore: Send + Sync {
+    /// Every stored object of `kind`.
+    async fn list(&self, kind: Kind) -> Result<Vec<Value>>;
+
+    async fn get(&self, kind: Kind, id: &str) -> Result<Option<Value>>;
+
+    /// Create or replace an object.
+    async fn put(&self, kind: Kind, id: &str, value: &Value) -> Result<()>;
+
+    /// Remove an object. Removing a missing one is not an error.
+    async fn delete(&self, kind: Kind, id: &str) -> Result<()>;
+
+    /// Keep `cache` and `router` equal to what is stored, signalling
+    /// `notify` after every change. Runs until nobody receives on
+    /// `notify` any more.
+    async fn watch(
+        &self,
+        cache: ConfigCache,
+        router: Arc<ArcSwap<Router>>,
+        notify: crossbeam_channel::Sender<()>,
+    ) -> Result<()>;
+}
+
+/// Serialize and store one object.
+pub async fn put_object<T: Serialize>(
+    store: &dyn ConfigStore,
+    kind: Kind,
+    id: &str,
+    object: &T,
+) -> Result<()> {
+    store.put(kind, id, &serde_json::to_value(object)?).await
+}
+
+/// Everything in `store`. Objects that fail to parse are left out, as
+/// the watcher ignores them.
+pub async fn export(store: &dyn ConfigStore) -> Result<ConfigDocument> {
+    async fn read<T: DeserializeOwned>(store: &dyn ConfigStore, kind: Kind) -> Result<Vec<T>> {
+        Ok(store
+            .list(kind)
+            .await?
+            .into_iter()
+            .filter_map(|v| serde_json::from_value(v).ok())
+            .collect())
+    }
+    Ok(ConfigDocument {
+        routes: read(store, Kind::Route).await?,
+        services: read(store, Kind::Service).await?,
+        upstreams: read(store, Kind::Upstream).await?,
+        consumers: read(store, Kind::Consumer).await?,
+        plugin_configs: read(store, Kind::PluginConfig).await?,
+        ssls: read(store, Kind::Ssl).await?,
+        global_rules: read(store, Kind::GlobalRule).await?,
+    })
+}
+
+/// Replace the cache with everything in `store`.
+pub async fn load(store: &dyn ConfigStore, cache: &ConfigCache) -> Result<()> {
+    export(store).await?.apply(cache, ApplyMode::Replace);
+    Ok(())
+}
+
+/// Make `store` hold exactly `doc`: put every object in it and delete
+/// everything else.
+pub async fn replace(store: &dyn ConfigStore, doc: &ConfigDocument) -> Result<()> {
+    fn values<T: Serialize>(objects: &[T]) -> Result<Vec<Value>> {
+        Ok(objects
+            .iter()
+            .map(serde_json::to_value)
+            .collect::<Result<_, _>>()?)
+    }
+    // Referenced kinds first on put and last on delete, so readers never
+    // see a dangling id.
+    let kinds = [
+        (Kind::Upstream, values(&doc.upstreams)?),
+        (Kind::PluginConfig, values(&doc.plugin_configs)?),
+        (Kind::Service, values(&doc.services)?),
+        (Kind::Consumer, values(&doc.consumers)?),
+        (Kind::Ssl, values(&doc.ssls)?),
+        (Kind::GlobalRule, values(&doc.global_rules)?),
+        (Kind::Route, values(&doc.routes)?),
+    ];
+    for (kind, objects) in &kinds {
+        for value in objects {
+            if let Some(id) = kind.id_of(value) {
+                store.put(*kind, id, value).await?;
+            }
+        }
+    }
+    for (kind, objects) in kinds.iter().rev() {
+        let keep: HashSet<&str> = objects.iter().filter_map(|v| kind.id_of(v)).collect();
+        for value in store.list(*kind).await? {
+            if let Some(id) = kind.id_of(&value)
+                && !keep.contains(id)
+            {
+                store.delete(*kind, id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kind_names_roundtrip() {
+        for kind in Kind::ALL {
+            assert_eq!(Kind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(Kind::parse("routes"), None);
+        assert_eq!(Kind::PluginConfig.segment(), "plugin_configs");
+        assert_eq!(Kind::GlobalRule.segment(), "global_rules");
+    }
+
+    #[test]
+    fn consumers_are_keyed_by_username() {
+        let consumer = serde_json::json!({"username": "alice"});
+        assert_eq!(Kind::Consumer.id_of(&consumer), Some("alice"));
+        assert_eq!(Kind::Route.id_of(&consumer), None);
+        assert_eq!(
+            Kind::Route.id_of(&serde_json::json!({"id": "r1"})),
+            Some("r1")
+        );
+    }
+}
@@ -0,0 +1,70 @@
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The most recent failure to load config from its source (etcd, SQL or
+/// the standalone file), cleared once loading works again.
+///
+/// v2 design: Written by the single watcher of the config source, read by
+/// the Admin API status endpoint, so a watcher that keeps failing while
+/// workers serve the previous config is visible.
+#[derive(Clone, Default)]
+pub struct SyncStatus {
+    last_error: Arc<Mutex<Option<SyncError>>>,
+}
+
+/// One config sync failure.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncError {
+    /// Unix time of the failure, in milliseconds.
+    pub at_unix_ms: u64,
+    pub message: String,
+}
+
+impl SyncStatus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record that syncing failed with `error`.
+    pub fn failed(&self, error: impl std::fmt::Display) {
+        let at_unix_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        *self.lock() = Some(SyncError {
+            at_unix_ms,
+            message: format!("{error:#}"),
+        });
+    }
+
+    /// Record that syncing works again.
+    pub fn recovered(&self) {
+        self.lock().take();
+    }
+
+    /// The failure since the last successful sync, if any.
+    pub fn last_error(&self) -> Option<SyncError> {
+        self.lock().clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<SyncError>> {
+        self.last_error.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failure_is_kept_until_recovery() {
+        let status = SyncStatus::new();
+        assert!(status.last_error().is_none());
+        status.failed("connection refused");
+        status.failed("timed out");
+        assert_eq!(status.last_error().unwrap().message, "timed out");
+        status.recovered();
+        assert!(status.last_error().is_none());
+    }
+}
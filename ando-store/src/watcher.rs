@@ -1,98 +1,309 @@
 use crate::cache::ConfigCache;
+use crate::changes::Entity;
+use crate::document::{ApplyMode, ConfigDocument};
+use crate::integrity;
 use crate::schema::Schema;
-use tracing::info;
+use ando_core::config::EtcdConfig;
+use ando_core::router::Router;
+use arc_swap::ArcSwap;
+use crossbeam_channel::TrySendError;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, error, info, warn};
 
 /// etcd watcher — watches for config changes and updates the cache.
 ///
 /// v2 design: The watcher runs on a dedicated tokio thread (not monoio).
 /// When a change is detected, it updates the DashMap cache and sends a
 /// "config changed" signal to all worker cores via crossbeam channels.
+///
+/// It starts with a full sync and tracks the revision it has applied, so
+/// a new watch resumes right after it. When events may have been lost
+/// (the revision was compacted, or the watch broke) it syncs in full
+/// again: deletes only arrive as events, so the listing is diffed against
+/// the cache and anything missing from it is removed.
+///
+/// The SQL store feeds the same loop from a poller, with its change
+/// counter as the revision.
 pub struct ConfigWatcher {
     schema: Schema,
+    /// Rebuilt from the cache after every burst of changes, when set.
+    router: Option<Arc<ArcSwap<Router>>>,
+    /// How long a burst of watch events may run before the router is
+    /// rebuilt.
+    debounce: Duration,
+}
+
+/// Default for `ConfigWatcher::with_debounce`.
+pub const DEFAULT_REBUILD_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// One change seen on the watch.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum WatchEvent {
+    Put { key: String, value: Vec<u8> },
+    Delete { key: String },
+}
+
+/// Events of one watch response, and the revision of the last of them
+/// (0 when there are none).
+#[derive(Debug, Default)]
+pub(crate) struct WatchBatch {
+    pub(crate) revision: i64,
+    pub(crate) events: Vec<WatchEvent>,
+}
+
+/// Every key under the prefix as of `revision`.
+#[derive(Debug, Default)]
+pub(crate) struct Listing {
+    pub(crate) revision: i64,
+    pub(crate) kvs: Vec<(String, Vec<u8>)>,
+}
+
+/// Why a watch stopped delivering events.
+#[derive(Debug)]
+pub(crate) enum WatchError {
+    /// The start revision was compacted away; holds the compaction
+    /// revision.
+    Compacted(i64),
+    /// The stream failed, closed or was canceled.
+    Lost(String),
+}
+
+/// The store operations the watcher needs, so tests can script them.
+pub(crate) trait WatchSource {
+    async fn list(&mut self, prefix: &str) -> anyhow::Result<Listing>;
+    /// Replace the current watch with one on `prefix` from `revision`.
+    async fn watch(&mut self, prefix: &str, revision: i64) -> anyhow::Result<()>;
+    async fn next(&mut self) -> Result<WatchBatch, WatchError>;
 }
 
 impl ConfigWatcher {
     pub fn new(prefix: &str) -> Self {
         Self {
             schema: Schema::new(prefix),
+            router: None,
+            debounce: DEFAULT_REBUILD_DEBOUNCE,
         }
     }
 
-    /// Start watching etcd for changes. Blocks forever.
+    /// Keep applying watch events for up to `debounce` after the first of
+    /// a burst, then rebuild the router and notify workers once. Zero
+    /// rebuilds after every watch response.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Rebuild and swap in `router` whenever the cache changes.
+    pub fn with_router(mut self, router: Arc<ArcSwap<Router>>) -> Self {
+        self.router = Some(router);
+        self
+    }
+
+    /// Connect to etcd as `etcd` configures and watch it for changes.
+    /// Runs until nobody receives on `notify` any more.
     pub async fn watch(
         &self,
-        endpoints: &[String],
+        etcd: &EtcdConfig,
         cache: ConfigCache,
         notify: crossbeam_channel::Sender<()>,
     ) -> anyhow::Result<()> {
-        let mut client = etcd_client::Client::connect(endpoints, None).await?;
-        let prefix = format!(
-            "{}/",
-            self.schema
-                .routes_prefix()
-                .trim_end_matches('/')
-                .rsplit('/')
-                .skip(1)
-                .collect::<Vec<_>>()
-                .into_iter()
-                .rev()
-                .collect::<Vec<_>>()
-                .join("/")
-        );
-
-        info!(prefix = %prefix, "Starting etcd watcher");
+        let client = crate::etcd::connect_client(etcd).await?;
+        self.watch_client(client, cache, notify).await;
+        Ok(())
+    }
 
-        let (_watcher, mut stream) = client
-            .watch(
-                prefix.as_bytes(),
-                Some(etcd_client::WatchOptions::new().with_prefix()),
-            )
-            .await?;
+    /// Like `watch`, over an existing connection.
+    pub(crate) async fn watch_client(
+        &self,
+        client: etcd_client::Client,
+        cache: ConfigCache,
+        notify: crossbeam_channel::Sender<()>,
+    ) {
+        info!(prefix = %self.schema.root_prefix(), "Starting etcd watcher");
+        let mut source = EtcdSource {
+            client,
+            watch: None,
+        };
+        self.run(&mut source, &cache, &notify).await;
+    }
 
-        while let Ok(Ok(Some(resp))) =
-            tokio::time::timeout(std::time::Duration::from_secs(30), stream.message()).await
-        {
-            for event in resp.events() {
-                if let Some(kv) = event.kv() {
-                    let key = String::from_utf8_lossy(kv.key());
-                    match event.event_type() {
-                        etcd_client::EventType::Put => {
-                            self.handle_put(&key, kv.value(), &cache);
+    /// Sync, then apply watch events until `notify` is disconnected.
+    pub(crate) async fn run(
+        &self,
+        source: &mut impl WatchSource,
+        cache: &ConfigCache,
+        notify: &crossbeam_channel::Sender<()>,
+    ) {
+        let prefix = self.schema.root_prefix();
+        // Last revision applied to the cache; `None` until a full sync.
+        let mut revision: Option<i64> = None;
+        let mut failures = 0;
+        let mut last_failure = None;
+        loop {
+            let rev = match revision {
+                Some(rev) => rev,
+                None => match self.resync(source, &prefix, cache).await {
+                    Ok(rev) => {
+                        self.rebuild_router(cache);
+                        if !changed(cache, notify) {
+                            return;
                         }
-                        etcd_client::EventType::Delete => {
-                            self.handle_delete(&key, &cache);
+                        revision = Some(rev);
+                        rev
+                    }
+                    Err(e) => {
+                        let e = format!("{e:#}");
+                        log_failure(&mut last_failure, "config store sync failed", &e);
+                        cache.sync.failed(format_args!("sync failed: {e}"));
+                        backoff(&mut failures).await;
+                        continue;
+                    }
+                },
+            };
+            if let Err(e) = source.watch(&prefix, rev + 1).await {
+                let e = format!("{e:#}");
+                log_failure(&mut last_failure, "config store watch failed to start", &e);
+                cache
+                    .sync
+                    .failed(format_args!("watch failed to start: {e}"));
+                backoff(&mut failures).await;
+                continue;
+            }
+            failures = 0;
+            last_failure = None;
+            cache.sync.recovered();
+            loop {
+                // Apply batches until the burst goes quiet, then rebuild
+                // once; see `with_debounce`.
+                let mut next = source.next().await;
+                let deadline = Instant::now() + self.debounce;
+                let mut applied = false;
+                let error = loop {
+                    let batch = match next {
+                        Ok(batch) => batch,
+                        Err(e) => break Some(e),
+                    };
+                    for event in &batch.events {
+                        match event {
+                            WatchEvent::Put { key, value } => self.handle_put(key, value, cache),
+                            WatchEvent::Delete { key } => self.handle_delete(key, cache),
                         }
                     }
+                    revision = revision.max(Some(batch.revision));
+                    applied = true;
+                    let left = deadline.saturating_duration_since(Instant::now());
+                    if left.is_zero() {
+                        break None;
+                    }
+                    match tokio::time::timeout(left, source.next()).await {
+                        Ok(more) => next = more,
+                        Err(_) => break None,
+                    }
+                };
+                if applied {
+                    warn_broken_refs(cache);
+                    self.rebuild_router(cache);
+                    if !changed(cache, notify) {
+                        return;
+                    }
+                }
+                match error {
+                    None => {}
+                    Some(WatchError::Compacted(compacted)) => {
+                        warn!(
+                            revision = ?revision,
+                            compacted,
+                            "watch revision was compacted, resyncing"
+                        );
+                        revision = None;
+                        break;
+                    }
+                    Some(WatchError::Lost(e)) => {
+                        warn!(error = %e, "config store watch lost, resyncing");
+                        cache.sync.failed(format_args!("watch lost: {e}"));
+                        revision = None;
+                        break;
+                    }
                 }
             }
-            // Notify worker cores that config has changed
-            let _ = notify.try_send(());
         }
+    }
 
-        Ok(())
+    /// Make the cache exactly what is stored under `prefix`, removing
+    /// objects that are gone. Returns the revision of the listing.
+    async fn resync(
+        &self,
+        source: &mut impl WatchSource,
+        prefix: &str,
+        cache: &ConfigCache,
+    ) -> anyhow::Result<i64> {
+        let listing = source.list(prefix).await?;
+        let summary = document(&listing.kvs).apply(cache, ApplyMode::Replace);
+        info!(
+            revision = listing.revision,
+            ?summary,
+            "Synced config from store"
+        );
+        warn_broken_refs(cache);
+        Ok(listing.revision)
+    }
+
+    fn rebuild_router(&self, cache: &ConfigCache) {
+        let Some(ref router) = self.router else {
+            return;
+        };
+        let version = router.load().version() + 1;
+        match Router::build(cache.all_routes(), version) {
+            Ok(rebuilt) => router.store(Arc::new(rebuilt)),
+            Err(e) => error!(error = %e, "Failed to rebuild router, keeping the previous one"),
+        }
     }
 
     fn handle_put(&self, key: &str, value: &[u8], cache: &ConfigCache) {
         if key.contains("/routes/") {
             if let Ok(route) = serde_json::from_slice::<ando_core::route::Route>(value) {
                 info!(route_id = %route.id, "Route updated");
-                cache.routes.insert(route.id.clone(), route);
+                let id = route.id.clone();
+                cache.routes.insert(id.clone(), route);
+                cache.changes.record(Entity::Route, &id);
             }
         } else if key.contains("/services/") {
             if let Ok(svc) = serde_json::from_slice::<ando_core::service::Service>(value) {
-                cache.services.insert(svc.id.clone(), svc);
+                let id = svc.id.clone();
+                cache.services.insert(id.clone(), svc);
+                cache.changes.record(Entity::Service, &id);
             }
         } else if key.contains("/upstreams/") {
             if let Ok(ups) = serde_json::from_slice::<ando_core::upstream::Upstream>(value)
-                && let Some(ref id) = ups.id
+                && let Some(id) = ups.id.clone()
             {
                 cache.upstreams.insert(id.clone(), ups);
+                cache.changes.record(Entity::Upstream, &id);
+            }
+        } else if key.contains("/consumers/") {
+            if let Ok(consumer) = serde_json::from_slice::<ando_core::consumer::Consumer>(value) {
+                let id = consumer.username.clone();
+                cache.put_consumer(consumer);
+                cache.changes.record(Entity::Consumer, &id);
+            }
+        } else if key.contains("/plugin_configs/") {
+            if let Ok(pc) = serde_json::from_slice::<ando_core::plugin_config::PluginConfig>(value)
+            {
+                cache.plugin_configs.insert(pc.id.clone(), pc);
             }
-        } else if key.contains("/consumers/")
-            && let Ok(consumer) = serde_json::from_slice::<ando_core::consumer::Consumer>(value)
+        } else if key.contains("/global_rules/") {
+            if let Ok(rule) = serde_json::from_slice::<ando_core::global_rule::GlobalRule>(value) {
+                let id = rule.id.clone();
+                cache.global_rules.insert(id.clone(), rule);
+                cache.changes.record(Entity::GlobalRule, &id);
+            }
+        } else if key.contains("/ssl/")
+            && let Ok(ssl) = serde_json::from_slice::<ando_core::ssl::SslCertificate>(value)
         {
-            cache.consumers.insert(consumer.username.clone(), consumer);
-            cache.rebuild_consumer_key_index();
+            info!(ssl_id = %ssl.id, "SSL certificate updated");
+            cache.ssl_certs.insert(ssl.id.clone(), ssl);
         }
     }
 
@@ -101,17 +312,173 @@ impl ConfigWatcher {
         let id = key.rsplit('/').next().unwrap_or("");
         if key.contains("/routes/") {
             cache.routes.remove(id);
+            cache.changes.record(Entity::Route, id);
         } else if key.contains("/services/") {
             cache.services.remove(id);
+            cache.changes.record(Entity::Service, id);
         } else if key.contains("/upstreams/") {
             cache.upstreams.remove(id);
+            cache.changes.record(Entity::Upstream, id);
+        } else if key.contains("/consumers/") {
+            cache.remove_consumer(id);
+            cache.changes.record(Entity::Consumer, id);
+        } else if key.contains("/plugin_configs/") {
+            cache.plugin_configs.remove(id);
+        } else if key.contains("/global_rules/") {
+            cache.global_rules.remove(id);
+            cache.changes.record(Entity::GlobalRule, id);
+        } else if key.contains("/ssl/") {
+            cache.ssl_certs.remove(id);
+        }
+    }
+}
+
+/// Signal workers that the config changed. `false` once nobody listens
+/// on `notify`.
+fn changed(cache: &ConfigCache, notify: &crossbeam_channel::Sender<()>) -> bool {
+    cache.notifier.notify();
+    !matches!(notify.try_send(()), Err(TrySendError::Disconnected(())))
+}
+
+/// Log a failure to reach the store with its full cause, e.g. the TLS or
+/// authentication error. Retries failing the same way are only logged at
+/// debug level until something else happens.
+fn log_failure(last: &mut Option<String>, what: &str, error: &str) {
+    if last.as_deref() == Some(error) {
+        debug!(error, "{what} again");
+    } else {
+        warn!(error, "{what}");
+        *last = Some(error.to_string());
+    }
+}
+
+/// Wait before retrying the store: 100 ms, doubling per failure up to ~6 s.
+async fn backoff(failures: &mut u32) {
+    tokio::time::sleep(Duration::from_millis(100 << (*failures).min(6))).await;
+    *failures += 1;
+}
+
+/// The config stored in `kvs`. Values that fail to parse are left out,
+/// as `handle_put` ignores them.
+fn document(kvs: &[(String, Vec<u8>)]) -> ConfigDocument {
+    fn parse<T: serde::de::DeserializeOwned>(value: &[u8], into: &mut Vec<T>) {
+        if let Ok(item) = serde_json::from_slice(value) {
+            into.push(item);
+        }
+    }
+    let mut doc = ConfigDocument::default();
+    for (key, value) in kvs {
+        if key.contains("/routes/") {
+            parse(value, &mut doc.routes);
+        } else if key.contains("/services/") {
+            parse(value, &mut doc.services);
+        } else if key.contains("/upstreams/") {
+            parse(value, &mut doc.upstreams);
         } else if key.contains("/consumers/") {
-            cache.consumers.remove(id);
-            cache.rebuild_consumer_key_index();
+            parse(value, &mut doc.consumers);
+        } else if key.contains("/plugin_configs/") {
+            parse(value, &mut doc.plugin_configs);
+        } else if key.contains("/ssl/") {
+            parse(value, &mut doc.ssls);
+        } else if key.contains("/global_rules/") {
+            parse(value, &mut doc.global_rules);
+        }
+    }
+    doc
+}
+
+/// `WatchSource` over a live etcd connection.
+struct EtcdSource {
+    client: etcd_client::Client,
+    /// The current watch; dropping the watcher cancels it.
+    watch: Option<(etcd_client::Watcher, etcd_client::WatchStream)>,
+}
+
+impl WatchSource for EtcdSource {
+    async fn list(&mut self, prefix: &str) -> anyhow::Result<Listing> {
+        let resp = self
+            .client
+            .get(
+                prefix.as_bytes(),
+                Some(etcd_client::GetOptions::new().with_prefix()),
+            )
+            .await?;
+        Ok(Listing {
+            revision: resp.header().map_or(0, |h| h.revision()),
+            kvs: resp
+                .kvs()
+                .iter()
+                .map(|kv| {
+                    (
+                        String::from_utf8_lossy(kv.key()).into_owned(),
+                        kv.value().to_vec(),
+                    )
+                })
+                .collect(),
+        })
+    }
+
+    async fn watch(&mut self, prefix: &str, revision: i64) -> anyhow::Result<()> {
+        self.watch = None;
+        let options = etcd_client::WatchOptions::new()
+            .with_prefix()
+            .with_start_revision(revision);
+        self.watch = Some(self.client.watch(prefix.as_bytes(), Some(options)).await?);
+        Ok(())
+    }
+
+    async fn next(&mut self) -> Result<WatchBatch, WatchError> {
+        let Some((_, ref mut stream)) = self.watch else {
+            return Err(WatchError::Lost("no watch".to_string()));
+        };
+        loop {
+            let resp = match stream.message().await {
+                Ok(Some(resp)) => resp,
+                Ok(None) => return Err(WatchError::Lost("watch stream closed".to_string())),
+                Err(e) => return Err(WatchError::Lost(e.to_string())),
+            };
+            if resp.compact_revision() > 0 {
+                return Err(WatchError::Compacted(resp.compact_revision()));
+            }
+            if resp.canceled() {
+                return Err(WatchError::Lost(format!(
+                    "watch canceled: {}",
+                    resp.cancel_reason()
+                )));
+            }
+            if resp.events().is_empty() {
+                // Creation and progress notices carry no events.
+                continue;
+            }
+            let mut batch = WatchBatch::default();
+            for event in resp.events() {
+                let Some(kv) = event.kv() else { continue };
+                let key = String::from_utf8_lossy(kv.key()).into_owned();
+                batch.revision = batch.revision.max(kv.mod_revision());
+                batch.events.push(match event.event_type() {
+                    etcd_client::EventType::Put => WatchEvent::Put {
+                        key,
+                        value: kv.value().to_vec(),
+                    },
+                    etcd_client::EventType::Delete => WatchEvent::Delete { key },
+                });
+            }
+            return Ok(batch);
         }
     }
 }
 
+/// Writes made directly to etcd or the database are not checked before
+/// they land, so dangling references are only reported once applied.
+/// Requests on affected routes get a 503.
+fn warn_broken_refs(cache: &ConfigCache) {
+    let broken = integrity::broken_refs(cache);
+    if !broken.is_empty() {
+        let refs: Vec<String> = broken.iter().map(ToString::to_string).collect();
+        warn!(count = broken.len(), refs = %refs.join("; "), "Config has dangling references");
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -119,7 +486,7 @@ mod tests {
     use ando_core::route::Route;
     use ando_core::service::Service;
     use ando_core::upstream::Upstream;
-    use std::collections::HashMap;
+    use std::collections::{HashMap, VecDeque};
 
     fn watcher() -> ConfigWatcher {
         ConfigWatcher::new("/ando")
@@ -270,7 +637,7 @@ mod tests {
             &cache,
         );
         assert_eq!(cache.consumers.len(), 1);
-        assert!(cache.consumer_key_index.is_empty());
+        assert!(cache.credentials.is_empty());
     }
 
     // ── handle_put: invalid JSON ────────────────────────────────
@@ -298,7 +665,7 @@ mod tests {
     fn handle_put_unknown_prefix_is_noop() {
         let w = watcher();
         let cache = ConfigCache::new();
-        w.handle_put("/ando/ssl/cert1", br#"{"id":"cert1"}"#, &cache);
+        w.handle_put("/ando/plugin_configs/pc1", br#"{"id":"pc1"}"#, &cache);
         assert_eq!(cache.routes.len(), 0);
         assert_eq!(cache.services.len(), 0);
         assert_eq!(cache.upstreams.len(), 0);
@@ -353,7 +720,7 @@ mod tests {
         let cache = ConfigCache::new();
         let consumer = make_consumer("alice", Some("secret-abc"));
         cache.consumers.insert("alice".to_string(), consumer);
-        cache.rebuild_consumer_key_index();
+        cache.reindex_consumers();
         assert!(cache.find_consumer_by_key("secret-abc").is_some());
 
         w.handle_delete("/ando/consumers/alice", &cache);
@@ -380,12 +747,65 @@ mod tests {
         let route = make_route("r1");
         cache.routes.insert("r1".to_string(), route);
 
-        w.handle_delete("/ando/ssl/cert1", &cache);
+        w.handle_delete("/ando/plugin_configs/pc1", &cache);
         assert_eq!(cache.routes.len(), 1); // unchanged
     }
 
+    // ── change log ──────────────────────────────────────────────
+
+    #[test]
+    fn handle_put_and_delete_record_changes() {
+        let w = watcher();
+        let cache = ConfigCache::new();
+        let route_json = serde_json::to_vec(&make_route("r1")).unwrap();
+        w.handle_put("/ando/routes/r1", &route_json, &cache);
+        w.handle_delete("/ando/upstreams/ups1", &cache);
+        w.handle_put("/ando/plugin_configs/pc1", b"{}", &cache);
+
+        let (changes, latest) = cache.changes.since(0).unwrap();
+        assert_eq!(latest, 2);
+        assert!(changes.routes.contains("r1"));
+        assert!(changes.upstreams.contains("ups1"));
+        assert!(changes.services.is_empty());
+        assert!(!changes.consumers);
+    }
+
+    // ── ssl ─────────────────────────────────────────────────────
+
+    #[test]
+    fn handle_put_and_delete_ssl_certificate() {
+        let w = watcher();
+        let cache = ConfigCache::new();
+        w.handle_put(
+            "/ando/ssl/cert1",
+            br#"{"id":"cert1","cert":"C","key":"K","snis":["a.example.com"]}"#,
+            &cache,
+        );
+        assert_eq!(
+            cache.ssl_certs.get("cert1").unwrap().snis,
+            ["a.example.com"]
+        );
+
+        w.handle_delete("/ando/ssl/cert1", &cache);
+        assert!(cache.ssl_certs.is_empty());
+    }
+
     // ── multiple entities ───────────────────────────────────────
 
+    #[test]
+    fn handle_put_and_delete_plugin_config() {
+        let w = watcher();
+        let cache = ConfigCache::new();
+        w.handle_put(
+            "/ando/plugin_configs/pc1",
+            br#"{"id": "pc1", "plugins": {"cors": {}}}"#,
+            &cache,
+        );
+        assert!(cache.plugin_configs.contains_key("pc1"));
+        w.handle_delete("/ando/plugin_configs/pc1", &cache);
+        assert!(cache.plugin_configs.is_empty());
+    }
+
     #[test]
     fn handle_put_multiple_entity_types() {
         let w = watcher();
@@ -426,4 +846,260 @@ mod tests {
             Some("bob".to_string())
         );
     }
+
+    // ── run: revisions, compaction and resync ───────────────────
+
+    /// Scripted etcd. `list` serves `listings` in turn and `next` serves
+    /// `stream`; once the stream is drained the notify receiver is
+    /// dropped, which ends `run`.
+    struct MockSource {
+        listings: VecDeque<Listing>,
+        stream: VecDeque<Result<WatchBatch, WatchError>>,
+        failing_watches: u32,
+        watched_from: Vec<i64>,
+        rx: Option<crossbeam_channel::Receiver<()>>,
+    }
+
+    impl WatchSource for MockSource {
+        async fn list(&mut self, _prefix: &str) -> anyhow::Result<Listing> {
+            Ok(self.listings.pop_front().expect("unexpected list"))
+        }
+
+        async fn watch(&mut self, _prefix: &str, revision: i64) -> anyhow::Result<()> {
+            self.watched_from.push(revision);
+            if self.failing_watches > 0 {
+                self.failing_watches -= 1;
+                anyhow::bail!("connection refused");
+            }
+            Ok(())
+        }
+
+        async fn next(&mut self) -> Result<WatchBatch, WatchError> {
+            self.stream.pop_front().unwrap_or_else(|| {
+                self.rx = None;
+                Ok(WatchBatch::default())
+            })
+        }
+    }
+
+    fn route_kv(id: &str, uri: &str) -> (String, Vec<u8>) {
+        let route: Route =
+            serde_json::from_value(serde_json::json!({ "id": id, "uri": uri })).unwrap();
+        (
+            format!("/ando/routes/{id}"),
+            serde_json::to_vec(&route).unwrap(),
+        )
+    }
+
+    fn upstream_kv(id: &str) -> (String, Vec<u8>) {
+        (
+            format!("/ando/upstreams/{id}"),
+            serde_json::to_vec(&make_upstream(id)).unwrap(),
+        )
+    }
+
+    fn put(kv: (String, Vec<u8>)) -> WatchEvent {
+        WatchEvent::Put {
+            key: kv.0,
+            value: kv.1,
+        }
+    }
+
+    fn batch(revision: i64, events: Vec<WatchEvent>) -> Result<WatchBatch, WatchError> {
+        Ok(WatchBatch { revision, events })
+    }
+
+    /// Run the watcher over `source` against `cache` until the script
+    /// is drained.
+    async fn run(source: &mut MockSource, cache: &ConfigCache) {
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        source.rx = Some(rx);
+        let watcher = watcher();
+        tokio::time::timeout(Duration::from_secs(5), watcher.run(source, cache, &tx))
+            .await
+            .expect("watcher did not finish the script");
+    }
+
+    fn source(listings: Vec<Listing>, stream: Vec<Result<WatchBatch, WatchError>>) -> MockSource {
+        MockSource {
+            listings: listings.into(),
+            stream: stream.into(),
+            failing_watches: 0,
+            watched_from: Vec::new(),
+            rx: None,
+        }
+    }
+
+    fn route_ids(cache: &ConfigCache) -> Vec<String> {
+        let mut ids: Vec<String> = cache.routes.iter().map(|r| r.key().clone()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[tokio::test]
+    async fn compaction_resyncs_and_drops_objects_deleted_meanwhile() {
+        let cache = ConfigCache::new();
+        // A route cached before the watcher started, since deleted.
+        cache.routes.insert("stale".into(), make_route("stale"));
+        let mut source = source(
+            vec![
+                Listing {
+                    revision: 5,
+                    kvs: vec![
+                        route_kv("r1", "/one"),
+                        route_kv("r2", "/two"),
+                        upstream_kv("u1"),
+                    ],
+                },
+                // After compaction: r1 changed, r2 and r3 were deleted.
+                Listing {
+                    revision: 12,
+                    kvs: vec![route_kv("r1", "/one-v2"), upstream_kv("u1")],
+                },
+            ],
+            vec![
+                batch(7, vec![put(route_kv("r3", "/three"))]),
+                Err(WatchError::Compacted(10)),
+                batch(13, vec![put(route_kv("r4", "/four"))]),
+            ],
+        );
+        run(&mut source, &cache).await;
+
+        assert_eq!(route_ids(&cache), ["r1", "r4"]);
+        assert_eq!(cache.routes.get("r1").unwrap().uri, "/one-v2");
+        assert!(cache.upstreams.contains_key("u1"));
+        // Each watch starts right after the revision it is caught up to.
+        assert_eq!(source.watched_from, [6, 13]);
+    }
+
+    #[tokio::test]
+    async fn lost_watch_resyncs_before_resuming() {
+        let cache = ConfigCache::new();
+        let mut source = source(
+            vec![
+                Listing {
+                    revision: 3,
+                    kvs: vec![route_kv("r1", "/one"), upstream_kv("u1")],
+                },
+                Listing {
+                    revision: 8,
+                    kvs: vec![route_kv("r1", "/one")],
+                },
+            ],
+            vec![
+                Err(WatchError::Lost("watch stream closed".into())),
+                batch(
+                    9,
+                    vec![WatchEvent::Delete {
+                        key: "/ando/routes/r1".into(),
+                    }],
+                ),
+            ],
+        );
+        run(&mut source, &cache).await;
+
+        assert!(cache.routes.is_empty());
+        assert!(cache.upstreams.is_empty());
+        assert_eq!(source.watched_from, [4, 9]);
+    }
+
+    #[tokio::test]
+    async fn failed_watch_start_retries_from_the_same_revision() {
+        let cache = ConfigCache::new();
+        let mut source = source(
+            vec![Listing {
+                revision: 5,
+                kvs: vec![route_kv("r1", "/one")],
+            }],
+            vec![batch(6, vec![put(route_kv("r2", "/two"))])],
+        );
+        source.failing_watches = 1;
+        run(&mut source, &cache).await;
+
+        assert_eq!(route_ids(&cache), ["r1", "r2"]);
+        // One listing only: nothing was missed, so no resync.
+        assert!(source.listings.is_empty());
+        assert_eq!(source.watched_from, [6, 6]);
+    }
+
+    #[tokio::test]
+    async fn burst_of_single_key_changes_rebuilds_the_router_once() {
+        let cache = ConfigCache::new();
+        let router = Arc::new(ArcSwap::new(Arc::new(Router::build(vec![], 0).unwrap())));
+        let mut source = source(
+            vec![Listing {
+                revision: 1,
+                kvs: vec![route_kv("r0", "/zero")],
+            }],
+            (2..5)
+                .map(|rev| batch(rev, vec![put(route_kv(&format!("r{rev}"), "/burst"))]))
+                .collect(),
+        );
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        source.rx = Some(rx);
+        let watcher = watcher()
+            .with_router(Arc::clone(&router))
+            .with_debounce(Duration::from_millis(50));
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            watcher.run(&mut source, &cache, &tx),
+        )
+        .await
+        .expect("watcher did not finish the script");
+
+        // One rebuild for the initial sync, one for the whole burst.
+        assert_eq!(router.load().version(), 2);
+        assert_eq!(router.load().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn service_change_alone_rebuilds_its_routes() {
+        let service_kv = |host: &str| {
+            let svc = serde_json::json!({ "id": "s1", "hosts": [host] });
+            (
+                "/ando/services/s1".to_string(),
+                serde_json::to_vec(&svc).unwrap(),
+            )
+        };
+        let route = serde_json::json!({ "id": "r1", "uri": "/one", "service_id": "s1" });
+        let route_kv = (
+            "/ando/routes/r1".to_string(),
+            serde_json::to_vec(&route).unwrap(),
+        );
+        let cache = ConfigCache::new();
+        let router = Arc::new(ArcSwap::new(Arc::new(Router::build(vec![], 0).unwrap())));
+        let mut source = source(
+            vec![Listing {
+                revision: 1,
+                kvs: vec![route_kv, service_kv("a.example.com")],
+            }],
+            vec![batch(2, vec![put(service_kv("b.example.com"))])],
+        );
+        let (tx, rx) = crossbeam_channel::bounded(1);
+        source.rx = Some(rx);
+        let watcher = watcher().with_router(Arc::clone(&router));
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            watcher.run(&mut source, &cache, &tx),
+        )
+        .await
+        .expect("watcher did not finish the script");
+
+        assert_eq!(router.load().version(), 2);
+        let hosts = router.load().get_route("r1").unwrap().hosts.clone();
+        assert_eq!(hosts, ["b.example.com"]);
+    }
+
+    #[test]
+    fn document_sorts_keys_by_entity() {
+        let doc = document(&[
+            route_kv("r1", "/one"),
+            upstream_kv("u1"),
+            ("/ando/routes/bad".into(), b"not-json".to_vec()),
+            ("/ando/unknown/x".into(), b"{}".to_vec()),
+        ]);
+        assert_eq!(doc.routes.len(), 1);
+        assert_eq!(doc.upstreams.len(), 1);
+        assert!(doc.services.is_empty());
+    }
 }
@@ -0,0 +1,69 @@
+//! etcd over TLS with client certificates and an RBAC user.
+//!
+//! Needs a TLS-enabled etcd with auth on, so it only runs when
+//! `ANDO_TEST_ETCD_TLS_ENDPOINT` is set, e.g. against
+//!
+//! ```sh
+//! etcd --listen-client-urls https://0.0.0.0:2379 \
+//!      --advertise-client-urls https://127.0.0.1:2379 \
+//!      --cert-file server.pem --key-file server-key.pem \
+//!      --trusted-ca-file ca.pem --client-cert-auth
+//! etcdctl ... user add root:root && etcdctl ... auth enable
+//! ```
+//!
+//! with `ANDO_TEST_ETCD_TLS_CA`, `ANDO_TEST_ETCD_TLS_CERT` and
+//! `ANDO_TEST_ETCD_TLS_KEY` naming the CA and a client certificate and key,
+//! and `ANDO_TEST_ETCD_USER` / `ANDO_TEST_ETCD_PASSWORD` the user.
+
+use ando_core::config::{EtcdConfig, EtcdTlsConfig};
+use ando_store::etcd::EtcdStore;
+use ando_store::store::{ConfigStore, Kind};
+use serde_json::json;
+
+/// The TLS etcd's settings, or `None` to skip.
+fn tls_etcd() -> Option<EtcdConfig> {
+    let endpoint = std::env::var("ANDO_TEST_ETCD_TLS_ENDPOINT").ok()?;
+    let var = |name: &str| std::env::var(name).unwrap_or_else(|_| panic!("{name} is not set"));
+    Some(EtcdConfig {
+        endpoints: vec![endpoint],
+        prefix: format!("/ando-tls-test-{}", std::process::id()),
+        timeout_secs: 5,
+        // Read from the environment as a config file would reference it.
+        username: Some("${ANDO_TEST_ETCD_USER}".into()),
+        password: Some("${ANDO_TEST_ETCD_PASSWORD}".into()),
+        tls: Some(EtcdTlsConfig {
+            ca_cert: Some(var("ANDO_TEST_ETCD_TLS_CA")),
+            client_cert: Some(var("ANDO_TEST_ETCD_TLS_CERT")),
+            client_key: Some(var("ANDO_TEST_ETCD_TLS_KEY")),
+            insecure_skip_verify: false,
+        }),
+    })
+}
+
+#[tokio::test]
+async fn connects_with_client_certificate_and_user() {
+    let Some(config) = tls_etcd() else {
+        eprintln!("ANDO_TEST_ETCD_TLS_ENDPOINT not set, skipping");
+        return;
+    };
+    let store = EtcdStore::connect(&config).await.unwrap();
+    let route = json!({"id": "r1", "uri": "/tls"});
+    store.put(Kind::Route, "r1", &route).await.unwrap();
+    assert_eq!(store.get(Kind::Route, "r1").await.unwrap(), Some(route));
+    store.delete(Kind::Route, "r1").await.unwrap();
+}
+
+#[tokio::test]
+async fn wrong_password_names_the_auth_failure() {
+    let Some(mut config) = tls_etcd() else {
+        eprintln!("ANDO_TEST_ETCD_TLS_ENDPOINT not set, skipping");
+        return;
+    };
+    config.password = Some("definitely-wrong".into());
+    let err = match EtcdStore::connect(&config).await {
+        Ok(_) => panic!("connected with a wrong password"),
+        Err(e) => format!("{e:#}"),
+    };
+    assert!(err.contains("cannot connect to etcd"), "{err}");
+    assert!(err.contains("authentication failed"), "{err}");
+}
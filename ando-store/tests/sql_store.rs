@@ -0,0 +1,154 @@
+//! SQL config store against SQLite: CRUD through `ConfigStore` and
+//! change propagation into the cache and router.
+
+use ando_core::router::Router;
+use ando_store::cache::ConfigCache;
+use ando_store::document::ConfigDocument;
+use ando_store::sql::SqlStore;
+use ando_store::store::{self, ConfigStore, Kind};
+use arc_swap::ArcSwap;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+async fn memory_store() -> SqlStore {
+    SqlStore::connect("sqlite::memory:", 1).await.unwrap()
+}
+
+/// Poll `cond` for up to two seconds.
+async fn eventually(what: &str, cond: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if cond() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("timed out waiting for {what}");
+}
+
+// ── CRUD ────────────────────────────────────────────────────────
+
+#[tokio::test]
+async fn put_get_list_delete() {
+    let store = memory_store().await;
+    let r1 = json!({"id": "r1", "uri": "/a"});
+    store.put(Kind::Route, "r1", &r1).await.unwrap();
+    store
+        .put(Kind::Route, "r2", &json!({"id": "r2", "uri": "/b"}))
+        .await
+        .unwrap();
+    store
+        .put(Kind::Upstream, "u1", &json!({"id": "u1", "nodes": {}}))
+        .await
+        .unwrap();
+
+    assert_eq!(store.get(Kind::Route, "r1").await.unwrap(), Some(r1));
+    assert_eq!(store.get(Kind::Service, "r1").await.unwrap(), None);
+    assert_eq!(store.list(Kind::Route).await.unwrap().len(), 2);
+
+    let updated = json!({"id": "r1", "uri": "/changed"});
+    store.put(Kind::Route, "r1", &updated).await.unwrap();
+    assert_eq!(store.get(Kind::Route, "r1").await.unwrap(), Some(updated));
+
+    store.delete(Kind::Route, "r1").await.unwrap();
+    store.delete(Kind::Route, "missing").await.unwrap();
+    assert_eq!(store.get(Kind::Route, "r1").await.unwrap(), None);
+    let ids: Vec<_> = store
+        .list(Kind::Route)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|v| v["id"].clone())
+        .collect();
+    assert_eq!(ids, [json!("r2")]);
+}
+
+#[tokio::test]
+async fn replace_makes_the_store_hold_exactly_the_document() {
+    let store = memory_store().await;
+    store
+        .put(Kind::Route, "old", &json!({"id": "old", "uri": "/old"}))
+        .await
+        .unwrap();
+    let doc: ConfigDocument = serde_json::from_value(json!({
+        "upstreams": [{"id": "u1", "nodes": {"127.0.0.1:80": 1}}],
+        "routes": [{"id": "new", "uri": "/new", "upstream_id": "u1"}],
+        "consumers": [{"username": "alice"}]
+    }))
+    .unwrap();
+    store::replace(&store, &doc).await.unwrap();
+
+    let cache = ConfigCache::new();
+    store::load(&store, &cache).await.unwrap();
+    assert!(cache.routes.contains_key("new"));
+    assert!(!cache.routes.contains_key("old"));
+    assert!(cache.upstreams.contains_key("u1"));
+    assert!(cache.consumers.contains_key("alice"));
+}
+
+// ── Change propagation ──────────────────────────────────────────
+
+#[tokio::test]
+async fn changes_reach_the_cache_and_router() {
+    let store = Arc::new(
+        memory_store()
+            .await
+            .with_poll_interval(Duration::from_millis(10)),
+    );
+    let cache = ConfigCache::new();
+    let router = Arc::new(ArcSwap::from_pointee(Router::build(vec![], 0).unwrap()));
+    let (tx, _rx) = crossbeam_channel::bounded(1);
+    let watch = tokio::spawn({
+        let (store, cache, router) = (store.clone(), cache.clone(), router.clone());
+        async move { store.watch(cache, router, tx).await }
+    });
+
+    store
+        .put(Kind::Route, "r1", &json!({"id": "r1", "uri": "/a"}))
+        .await
+        .unwrap();
+    eventually("route in router", || {
+        router.load().get_route("r1").is_some()
+    })
+    .await;
+    assert!(cache.routes.contains_key("r1"));
+
+    store.delete(Kind::Route, "r1").await.unwrap();
+    eventually("route removed", || router.load().get_route("r1").is_none()).await;
+    assert!(!cache.routes.contains_key("r1"));
+    watch.abort();
+}
+
+#[tokio::test]
+async fn writes_from_another_node_are_picked_up() {
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("sqlite://{}?mode=rwc", dir.path().join("ando.db").display());
+    let local = SqlStore::connect(&url, 2)
+        .await
+        .unwrap()
+        .with_poll_interval(Duration::from_millis(10));
+    let remote = SqlStore::connect(&url, 2).await.unwrap();
+    remote
+        .put(Kind::Route, "before", &json!({"id": "before", "uri": "/b"}))
+        .await
+        .unwrap();
+
+    let cache = ConfigCache::new();
+    let router = Arc::new(ArcSwap::from_pointee(Router::build(vec![], 0).unwrap()));
+    let (tx, _rx) = crossbeam_channel::bounded(1);
+    let watch = tokio::spawn({
+        let (cache, router) = (cache.clone(), router.clone());
+        async move { local.watch(cache, router, tx).await }
+    });
+
+    eventually("initial sync", || cache.routes.contains_key("before")).await;
+    remote
+        .put(Kind::Route, "after", &json!({"id": "after", "uri": "/c"}))
+        .await
+        .unwrap();
+    eventually("remote write", || {
+        router.load().get_route("after").is_some()
+    })
+    .await;
+    watch.abort();
+}
@@ -3,7 +3,7 @@
 # bench.sh — Ando CE vs APISIX vs KrakenD vs Kong vs Tyk
 # ============================================================
 # Usage:
-#   ./benchmark/bench.sh [baseline|plain|auth|stress|ramp|all]
+#   ./benchmark/bench.sh [baseline|plain|auth|stress|ramp|listeners|all]
 #
 # Env overrides:
 #   BENCH_DURATION=60s BENCH_CONNECTIONS=400 ./benchmark/bench.sh
@@ -366,6 +366,31 @@ bench_stress() {
   ok "Tyk stress:     ${TYK_STRESS_RPS:-?} req/s  p99 ${TYK_STRESS_P99:-?}"
 }
 
+# Ando CE only: per-worker SO_REUSEPORT listeners vs one shared listener.
+bench_listeners() {
+  header "Scenario 5 — Ando CE listeners: reuseport vs shared (${STRESS_CONNECTIONS} conns, ${DURATION})"
+
+  info "Setting up the shared-listener Ando CE..."
+  dcurl -X PUT "http://ando-ce-shared:9180/apisix/admin/upstreams/bench-echo" \
+    -H "Content-Type: application/json" \
+    -d '{"id":"bench-echo","type":"roundrobin","nodes":{"echo:3000":1}}' >/dev/null
+  dcurl -X PUT "http://ando-ce-shared:9180/apisix/admin/routes/bench-plain" \
+    -H "Content-Type: application/json" \
+    -d '{"id":"bench-plain","uri":"/bench/plain","methods":["GET"],"upstream_id":"bench-echo","plugins":{}}' >/dev/null
+
+  local host label rps p99
+  for host in ando-ce ando-ce-shared; do
+    label="listeners_${host//-/_}"
+    warmup "http://${host}:9080/bench/plain" "${host}"
+    run_wrk "http://${host}:9080/bench/plain" "${label}" "" "${STRESS_CONNECTIONS}"
+    rps=$(extract_rps "${RESULTS_DIR}/wrk_${label}.txt")
+    p99=$(extract_p99 "${RESULTS_DIR}/wrk_${label}.txt")
+    ok "${host}: ${rps:-?} req/s  p99 ${p99:-?}"
+    dcurl "http://${host}:9180/apisix/admin/workers/stats" > "${RESULTS_DIR}/workers_${label}.json" || true
+  done
+  info "Per-worker accept counts: ${RESULTS_DIR}/workers_listeners_*.json"
+}
+
 bench_ramp() {
   header "Scenario 4 — Concurrency Ramp (10 → 1000)"
   local RAMP_CONNS=(10 50 100 250 500 1000)
@@ -768,9 +793,11 @@ case "${SCENARIO}" in
     bench_stress; write_report ;;
   ramp)
     bench_ramp; write_report ;;
+  listeners)
+    bench_listeners ;;
   *)
     err "Unknown scenario: ${SCENARIO}"
-    echo "Usage: $0 [all|baseline|plain|auth|stress|ramp]"
+    echo "Usage: $0 [all|baseline|plain|auth|stress|ramp|listeners]"
     exit 1 ;;
 esac
 
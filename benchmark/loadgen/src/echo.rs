@@ -0,0 +1,135 @@
+//! Built-in upstream for the benchmark routes: answers every request with
+//! a 200 carrying the request body, or `ok` for a request without one.
+
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Start serving on `addr` and return the bound address.
+pub async fn spawn(addr: &str) -> io::Result<SocketAddr> {
+    let listener = TcpListener::bind(addr).await?;
+    let local = listener.local_addr()?;
+    tokio::spawn(async move {
+        loop {
+            if let Ok((stream, _)) = listener.accept().await {
+                let _ = stream.set_nodelay(true);
+                tokio::spawn(serve(stream));
+            }
+        }
+    });
+    Ok(local)
+}
+
+async fn serve(mut stream: TcpStream) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(8192);
+    let mut response = Vec::with_capacity(256);
+    loop {
+        let request = loop {
+            if let Some(request) = parse_request(&buf)? {
+                break request;
+            }
+            buf.reserve(8192);
+            if stream.read_buf(&mut buf).await? == 0 {
+                return Ok(());
+            }
+        };
+        let body = match &buf[request.head..request.len] {
+            [] => b"ok",
+            body => body,
+        };
+        response.clear();
+        response.extend_from_slice(
+            format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: text/plain\r\ncontent-length: {}\r\n\r\n",
+                body.len()
+            )
+            .as_bytes(),
+        );
+        response.extend_from_slice(body);
+        stream.write_all(&response).await?;
+        buf.drain(..request.len);
+        if request.close {
+            return Ok(());
+        }
+    }
+}
+
+struct Request {
+    /// Length of the head.
+    head: usize,
+    /// Length of head and body.
+    len: usize,
+    close: bool,
+}
+
+/// The request at the start of `buf`, `None` until it is complete. Bodies
+/// need a `Content-Length`.
+fn parse_request(buf: &[u8]) -> io::Result<Option<Request>> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Request::new(&mut headers);
+    let head = match parsed.parse(buf) {
+        Ok(httparse::Status::Complete(head)) => head,
+        Ok(httparse::Status::Partial) => return Ok(None),
+        Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+    };
+    let mut body = 0;
+    let mut close = parsed.version == Some(0);
+    for header in parsed.headers.iter() {
+        if header.name.eq_ignore_ascii_case("content-length") {
+            body = std::str::from_utf8(header.value)
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "bad content-length"))?;
+        } else if header.name.eq_ignore_ascii_case("connection") {
+            close = header.value.eq_ignore_ascii_case(b"close");
+        }
+    }
+    if buf.len() < head + body {
+        return Ok(None);
+    }
+    Ok(Some(Request {
+        head,
+        len: head + body,
+        close,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load::Conn;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn answers_requests_on_one_connection() {
+        let addr = spawn("127.0.0.1:0").await.unwrap();
+        let mut conn = Conn::connect(addr, Duration::from_secs(5)).await.unwrap();
+        for _ in 0..3 {
+            let response = conn
+                .exchange(b"GET /bench/plain/0 HTTP/1.1\r\nHost: echo\r\n\r\n")
+                .await
+                .unwrap();
+            assert_eq!((response.status, response.keep_alive), (200, true));
+        }
+        let response = conn
+            .exchange(b"POST / HTTP/1.1\r\nContent-Length: 4\r\nConnection: close\r\n\r\nping")
+            .await
+            .unwrap();
+        assert_eq!(response.status, 200);
+    }
+
+    #[test]
+    fn requests_are_framed_by_content_length() {
+        let request = b"POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nping";
+        assert!(
+            parse_request(&request[..request.len() - 1])
+                .unwrap()
+                .is_none()
+        );
+        let parsed = parse_request(request).unwrap().unwrap();
+        assert_eq!((parsed.len, parsed.close), (request.len(), false));
+        let close = b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n";
+        assert!(parse_request(close).unwrap().unwrap().close);
+    }
+}
@@ -0,0 +1,202 @@
+//! Latency histogram with HDR bucketing.
+//!
+//! Values are microseconds. Up to 2048 each value has its own bucket; above,
+//! every power of two is split into 1024 buckets, so a value is known to
+//! within 0.1% (three significant digits) at any magnitude, in a few
+//! hundred kilobytes at most. Buckets are allocated as values reach them.
+
+/// Values below this are exact.
+const SUB_BUCKETS: u64 = 2048;
+const HALF: u64 = SUB_BUCKETS / 2;
+/// Larger values (over an hour) are recorded as this.
+const HIGHEST: u64 = 3_600_000_000;
+
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, value: u64) {
+        let value = value.min(HIGHEST);
+        let index = bucket(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        if self.total == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.total += 1;
+        self.sum += u128::from(value);
+    }
+
+    /// Add the values recorded in `other`.
+    pub fn merge(&mut self, other: &Histogram) {
+        if other.total == 0 {
+            return;
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        if self.total == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.sum += other.sum;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.total as f64
+    }
+
+    /// The value below or at which `quantile` (0.0–1.0) of the values fall:
+    /// the highest value of the bucket holding that rank, capped at the
+    /// largest value recorded. 0 when empty.
+    pub fn value_at_quantile(&self, quantile: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return highest_equivalent(index).min(self.max);
+            }
+        }
+        self.max
+    }
+}
+
+fn bucket(value: u64) -> usize {
+    if value < SUB_BUCKETS {
+        return value as usize;
+    }
+    // `value >> shift` falls in [HALF, SUB_BUCKETS).
+    let shift = u64::from(63 - value.leading_zeros()) - 10;
+    (SUB_BUCKETS + (shift - 1) * HALF + ((value >> shift) - HALF)) as usize
+}
+
+/// The largest value that lands in bucket `index`.
+fn highest_equivalent(index: usize) -> u64 {
+    let index = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let offset = index - SUB_BUCKETS;
+    let shift = offset / HALF + 1;
+    let lowest = (offset % HALF + HALF) << shift;
+    lowest + (1 << shift) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_contiguous_and_within_a_thousandth() {
+        for value in [
+            0, 1, 2047, 2048, 2049, 4095, 4096, 123_456, 9_999_999, HIGHEST,
+        ] {
+            let highest = highest_equivalent(bucket(value));
+            assert!(highest >= value, "{value}");
+            assert!((highest - value) as f64 <= value as f64 / 1000.0, "{value}");
+        }
+        for index in 2040..5000 {
+            assert_eq!(bucket(highest_equivalent(index)), index);
+            assert_eq!(bucket(highest_equivalent(index) + 1), index + 1);
+        }
+    }
+
+    #[test]
+    fn quantiles_of_a_uniform_range() {
+        let mut histogram = Histogram::new();
+        for value in 1..=10_000 {
+            histogram.record(value);
+        }
+        assert_eq!(histogram.count(), 10_000);
+        assert_eq!(histogram.min(), 1);
+        assert_eq!(histogram.max(), 10_000);
+        assert_eq!(histogram.mean(), 5000.5);
+        for (quantile, exact) in [(0.5, 5000), (0.9, 9000), (0.99, 9900), (0.999, 9990)] {
+            let value = histogram.value_at_quantile(quantile);
+            assert!(
+                value >= exact && value - exact <= exact / 1000,
+                "p{quantile}: {value}"
+            );
+        }
+        assert_eq!(histogram.value_at_quantile(0.0), 1);
+        assert_eq!(histogram.value_at_quantile(1.0), 10_000);
+    }
+
+    #[test]
+    fn tail_quantiles_find_outliers() {
+        let mut histogram = Histogram::new();
+        for _ in 0..999 {
+            histogram.record(100);
+        }
+        histogram.record(50_000);
+        assert_eq!(histogram.value_at_quantile(0.99), 100);
+        assert_eq!(histogram.value_at_quantile(0.999), 100);
+        assert_eq!(histogram.value_at_quantile(0.9999), 50_000);
+    }
+
+    #[test]
+    fn merge_is_the_same_as_recording_together() {
+        let (mut a, mut b, mut both) = (Histogram::new(), Histogram::new(), Histogram::new());
+        for value in [5, 70, 3000] {
+            a.record(value);
+            both.record(value);
+        }
+        for value in [1, 250_000] {
+            b.record(value);
+            both.record(value);
+        }
+        a.merge(&b);
+        a.merge(&Histogram::new());
+        assert_eq!((a.count(), a.min(), a.max()), (5, 1, 250_000));
+        assert_eq!(a.mean(), both.mean());
+        for quantile in [0.2, 0.5, 0.8, 1.0] {
+            assert_eq!(
+                a.value_at_quantile(quantile),
+                both.value_at_quantile(quantile)
+            );
+        }
+    }
+
+    #[test]
+    fn empty_histogram_reports_zeros() {
+        let histogram = Histogram::new();
+        assert_eq!(histogram.value_at_quantile(0.99), 0);
+        assert_eq!(histogram.mean(), 0.0);
+    }
+}
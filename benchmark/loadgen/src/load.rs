@@ -0,0 +1,361 @@
+//! The load: keep-alive HTTP/1.1 connections on raw sockets, each sending
+//! its next request as soon as the previous response is read.
+
+use crate::histogram::Histogram;
+use crate::report::Errors;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, timeout};
+
+/// Pause before reconnecting after a failed connect.
+const CONNECT_BACKOFF: Duration = Duration::from_millis(10);
+
+pub struct LoadSpec {
+    pub target: SocketAddr,
+    /// `Host` header.
+    pub host: String,
+    /// Requested in turn by every connection, each starting at its own.
+    pub paths: Vec<String>,
+    pub headers: Vec<(String, String)>,
+    pub connections: usize,
+    pub warmup: Duration,
+    pub duration: Duration,
+    /// Per connect and per request.
+    pub timeout: Duration,
+}
+
+impl LoadSpec {
+    fn requests(&self) -> Vec<Vec<u8>> {
+        self.paths
+            .iter()
+            .map(|path| request(path, &self.host, &self.headers))
+            .collect()
+    }
+}
+
+/// What the requests sent after the warmup got.
+pub struct Outcome {
+    pub latencies: Histogram,
+    pub errors: Errors,
+}
+
+pub async fn run(spec: &LoadSpec) -> Outcome {
+    let requests: Arc<[Vec<u8>]> = spec.requests().into();
+    let measure_from = Instant::now() + spec.warmup;
+    let deadline = measure_from + spec.duration;
+    let tasks: Vec<_> = (0..spec.connections)
+        .map(|first| {
+            let requests = Arc::clone(&requests);
+            let (target, limit) = (spec.target, spec.timeout);
+            tokio::spawn(async move {
+                connection(target, limit, &requests, first, measure_from, deadline).await
+            })
+        })
+        .collect();
+    let mut outcome = Outcome {
+        latencies: Histogram::new(),
+        errors: Errors::default(),
+    };
+    for task in tasks {
+        if let Ok((latencies, errors)) = task.await {
+            outcome.latencies.merge(&latencies);
+            outcome.errors.merge(&errors);
+        }
+    }
+    outcome
+}
+
+/// One connection's loop until `deadline`. Requests sent from
+/// `measure_from` on are counted.
+async fn connection(
+    target: SocketAddr,
+    limit: Duration,
+    requests: &[Vec<u8>],
+    first: usize,
+    measure_from: Instant,
+    deadline: Instant,
+) -> (Histogram, Errors) {
+    let mut latencies = Histogram::new();
+    let mut errors = Errors::default();
+    let mut conn = None;
+    let mut next = first;
+    loop {
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        let measured = now >= measure_from;
+        let stream = match conn {
+            Some(ref mut stream) => stream,
+            None => match Conn::connect(target, limit).await {
+                Ok(stream) => conn.insert(stream),
+                Err(_) => {
+                    if measured {
+                        errors.connect += 1;
+                    }
+                    tokio::time::sleep(CONNECT_BACKOFF).await;
+                    continue;
+                }
+            },
+        };
+        let request = &requests[next % requests.len()];
+        next += 1;
+        let sent = Instant::now();
+        let result = timeout(limit, stream.exchange(request)).await;
+        match result {
+            Ok(Ok(response)) => {
+                if measured {
+                    latencies.record(sent.elapsed().as_micros() as u64);
+                    if response.status >= 400 {
+                        *errors.status.entry(response.status).or_default() += 1;
+                    }
+                }
+                if !response.keep_alive {
+                    conn = None;
+                }
+            }
+            Ok(Err(_)) => {
+                if measured {
+                    errors.io += 1;
+                }
+                conn = None;
+            }
+            Err(_) => {
+                if measured {
+                    errors.timeout += 1;
+                }
+                conn = None;
+            }
+        }
+    }
+    (latencies, errors)
+}
+
+/// Send one request for `path` on a fresh connection.
+pub async fn probe(spec: &LoadSpec, path: &str) -> io::Result<Response> {
+    let mut conn = Conn::connect(spec.target, spec.timeout).await?;
+    let request = request(path, &spec.host, &spec.headers);
+    timeout(spec.timeout, conn.exchange(&request))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "no response"))?
+}
+
+fn request(path: &str, host: &str, headers: &[(String, String)]) -> Vec<u8> {
+    let mut request = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n");
+    for (name, value) in headers {
+        request.push_str(&format!("{name}: {value}\r\n"));
+    }
+    request.push_str("\r\n");
+    request.into_bytes()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Response {
+    pub status: u16,
+    /// Whether the connection may carry another request.
+    pub keep_alive: bool,
+}
+
+/// A client connection, with the bytes read past the last response.
+pub struct Conn {
+    stream: TcpStream,
+    buf: Vec<u8>,
+}
+
+impl Conn {
+    pub async fn connect(target: SocketAddr, limit: Duration) -> io::Result<Self> {
+        let stream = timeout(limit, TcpStream::connect(target))
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "connect timed out"))??;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            buf: Vec::with_capacity(8192),
+        })
+    }
+
+    /// Write `request` and read its response through to the end of the body.
+    pub async fn exchange(&mut self, request: &[u8]) -> io::Result<Response> {
+        self.stream.write_all(request).await?;
+        let mut eof = false;
+        loop {
+            if let Some((response, len)) = parse_response(&self.buf, eof)? {
+                self.buf.drain(..len);
+                return Ok(response);
+            }
+            if eof {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.buf.reserve(8192);
+            eof = self.stream.read_buf(&mut self.buf).await? == 0;
+        }
+    }
+}
+
+/// The response at the start of `buf` and its length in bytes, `None`
+/// until it is complete. `eof`: the server closed the connection, which
+/// ends a body that has neither a length nor chunks.
+fn parse_response(buf: &[u8], eof: bool) -> io::Result<Option<(Response, usize)>> {
+    let mut headers = [httparse::EMPTY_HEADER; 64];
+    let mut parsed = httparse::Response::new(&mut headers);
+    let head = match parsed.parse(buf).map_err(invalid)? {
+        httparse::Status::Complete(head) => head,
+        httparse::Status::Partial => return Ok(None),
+    };
+    let status = parsed.code.unwrap_or_default();
+    let mut keep_alive = parsed.version == Some(1);
+    let mut length = None;
+    let mut chunked = false;
+    for header in parsed.headers.iter() {
+        let value = String::from_utf8_lossy(header.value);
+        let value = value.trim();
+        if header.name.eq_ignore_ascii_case("content-length") {
+            length = Some(value.parse::<usize>().map_err(invalid)?);
+        } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+            chunked = value.to_ascii_lowercase().ends_with("chunked");
+        } else if header.name.eq_ignore_ascii_case("connection") {
+            if value.eq_ignore_ascii_case("close") {
+                keep_alive = false;
+            } else if value.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
+            }
+        }
+    }
+    let body = if (100..200).contains(&status) || status == 204 || status == 304 {
+        0
+    } else if chunked {
+        match chunked_length(&buf[head..])? {
+            Some(body) => body,
+            None => return Ok(None),
+        }
+    } else if let Some(length) = length {
+        length
+    } else {
+        let response = Response {
+            status,
+            keep_alive: false,
+        };
+        return Ok(eof.then_some((response, buf.len())));
+    };
+    if buf.len() - head < body {
+        return Ok(None);
+    }
+    Ok(Some((Response { status, keep_alive }, head + body)))
+}
+
+/// Length of the chunked body at the start of `buf`, trailers included,
+/// `None` until it is complete.
+fn chunked_length(buf: &[u8]) -> io::Result<Option<usize>> {
+    let mut pos = 0;
+    loop {
+        let Some(end) = find_crlf(&buf[pos..]) else {
+            return Ok(None);
+        };
+        let line = std::str::from_utf8(&buf[pos..pos + end]).map_err(invalid)?;
+        let size = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size, 16).map_err(invalid)?;
+        pos += end + 2;
+        if size == 0 {
+            // Trailers, up to an empty line.
+            loop {
+                let Some(end) = find_crlf(&buf[pos..]) else {
+                    return Ok(None);
+                };
+                pos += end + 2;
+                if end == 0 {
+                    return Ok(Some(pos));
+                }
+            }
+        }
+        pos += size + 2;
+        if pos > buf.len() {
+            return Ok(None);
+        }
+    }
+}
+
+fn find_crlf(buf: &[u8]) -> Option<usize> {
+    buf.windows(2).position(|w| w == b"\r\n")
+}
+
+fn invalid(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(response: &str, eof: bool) -> Option<(Response, usize)> {
+        parse_response(response.as_bytes(), eof).unwrap()
+    }
+
+    fn ok(keep_alive: bool) -> Response {
+        Response {
+            status: 200,
+            keep_alive,
+        }
+    }
+
+    #[test]
+    fn content_length_bodies() {
+        let response = "HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        assert_eq!(parse(response, false), Some((ok(true), response.len())));
+        assert_eq!(parse(&response[..response.len() - 1], false), None);
+        assert_eq!(parse(&response[..10], false), None);
+        // A pipelined response behind it is left in the buffer.
+        let two = format!("{response}HTTP/1.1 404 Not Found\r\n");
+        assert_eq!(parse(&two, false), Some((ok(true), response.len())));
+    }
+
+    #[test]
+    fn chunked_bodies() {
+        let response = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                        5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nx-trailer: 1\r\n\r\n";
+        assert_eq!(parse(response, false), Some((ok(true), response.len())));
+        for cut in [50, 60, response.len() - 2] {
+            assert_eq!(parse(&response[..cut], false), None, "{cut}");
+        }
+        let bad = "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n";
+        assert!(parse_response(bad.as_bytes(), false).is_err());
+    }
+
+    #[test]
+    fn connection_reuse() {
+        let close = "HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(parse(close, false), Some((ok(false), close.len())));
+        let http10 = "HTTP/1.0 200 OK\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(parse(http10, false), Some((ok(false), http10.len())));
+        let http10_keep = "HTTP/1.0 200 OK\r\nConnection: keep-alive\r\nContent-Length: 0\r\n\r\n";
+        assert_eq!(
+            parse(http10_keep, false),
+            Some((ok(true), http10_keep.len()))
+        );
+        // No length: the body runs to the end of the connection.
+        let unframed = "HTTP/1.1 200 OK\r\n\r\nsome body";
+        assert_eq!(parse(unframed, false), None);
+        assert_eq!(parse(unframed, true), Some((ok(false), unframed.len())));
+    }
+
+    #[test]
+    fn bodyless_statuses() {
+        let response = "HTTP/1.1 204 No Content\r\nContent-Length: 10\r\n\r\n";
+        let (parsed, len) = parse(response, false).unwrap();
+        assert_eq!((parsed.status, len), (204, response.len()));
+        let response = "HTTP/1.1 401 Unauthorized\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(parse(response, false).unwrap().0.status, 401);
+    }
+
+    #[test]
+    fn requests_carry_the_host_and_headers() {
+        let headers = [("apikey".to_string(), "secret".to_string())];
+        assert_eq!(
+            request("/bench/1", "gw:9080", &headers),
+            b"GET /bench/1 HTTP/1.1\r\nHost: gw:9080\r\napikey: secret\r\n\r\n"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+//! `ando-loadgen` — drives HTTP/1.1 keep-alive load through a running
+//! gateway and reports throughput and latency percentiles.
+//!
+//! It starts its own echo upstream, programs the benchmark routes through
+//! the Admin API, runs the load and removes the routes again:
+//!
+//! ```text
+//! ando-loadgen --target 127.0.0.1:9080 --scenario key-auth --routes 100 -c 200 -d 30s \
+//!     --output current.json --compare baseline.json
+//! ```
+//!
+//! The gateway is whatever listens on `--target`, so the same run can be
+//! pointed at either build. With `--compare`, the exit code is 1 when p99
+//! grew by more than `--max-p99-regression` percent over the baseline
+//! report.
+
+mod echo;
+mod histogram;
+mod load;
+mod report;
+mod routes;
+
+use anyhow::{Context, bail};
+use clap::Parser;
+use load::LoadSpec;
+use report::{Comparison, Report};
+use routes::{Admin, Scenario};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+
+/// How long the routes may take to reach the proxy after setup.
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Parser)]
+#[command(
+    name = "ando-loadgen",
+    about = "Load generator and latency report for Ando CE"
+)]
+struct Cli {
+    /// Proxy address of the gateway under test, host:port.
+    #[arg(long, default_value = "127.0.0.1:9080")]
+    target: String,
+
+    /// Admin API URL.
+    #[arg(long, default_value = "http://127.0.0.1:9180")]
+    admin: String,
+
+    /// Admin API key (X-API-KEY).
+    #[arg(long)]
+    admin_key: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = Scenario::Plain)]
+    scenario: Scenario,
+
+    /// Routes to create; requests go to each in turn.
+    #[arg(long, default_value_t = 1)]
+    routes: usize,
+
+    /// Concurrent keep-alive connections.
+    #[arg(short, long, default_value_t = 100)]
+    connections: usize,
+
+    /// Measured run time, e.g. 30s, 500ms, 2m.
+    #[arg(short, long, default_value = "10s", value_parser = parse_duration)]
+    duration: Duration,
+
+    /// Load before the measurement starts.
+    #[arg(long, default_value = "1s", value_parser = parse_duration)]
+    warmup: Duration,
+
+    /// Limit per connect and per request.
+    #[arg(long, default_value = "5s", value_parser = parse_duration)]
+    timeout: Duration,
+
+    /// Listen address of the built-in echo upstream.
+    #[arg(long, default_value = "127.0.0.1:0")]
+    echo_addr: String,
+
+    /// Don't start the echo upstream; routes point at --upstream.
+    #[arg(long)]
+    no_echo: bool,
+
+    /// Upstream node for the routes, host:port, as the gateway reaches it.
+    /// Defaults to the echo upstream's address.
+    #[arg(long)]
+    upstream: Option<String>,
+
+    /// Key the key-auth scenario's consumer authenticates with.
+    #[arg(long, default_value = "bench-secret-key")]
+    api_key: String,
+
+    /// Use routes the gateway already has instead of creating them.
+    #[arg(long)]
+    skip_setup: bool,
+
+    /// Leave the created routes in place after the run.
+    #[arg(long)]
+    keep_routes: bool,
+
+    /// Print the report as JSON.
+    #[arg(long)]
+    json: bool,
+
+    /// Write the JSON report to this file.
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Baseline JSON report to compare p99 against.
+    #[arg(long)]
+    compare: Option<PathBuf>,
+
+    /// p99 growth over the baseline, in percent, that fails the run.
+    #[arg(long, default_value_t = 10.0)]
+    max_p99_regression: f64,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+    if cli.routes == 0 || cli.connections == 0 {
+        bail!("--routes and --connections must be at least 1");
+    }
+    let baseline = match cli.compare {
+        Some(ref path) => Some(read_report(path)?),
+        None => None,
+    };
+
+    let target = tokio::net::lookup_host(&cli.target)
+        .await
+        .ok()
+        .and_then(|mut addrs| addrs.next())
+        .with_context(|| format!("cannot resolve --target {}", cli.target))?;
+    let admin = Admin::new(&cli.admin, cli.admin_key.clone());
+    if !cli.skip_setup {
+        let echo = if cli.no_echo {
+            None
+        } else {
+            let addr = echo::spawn(&cli.echo_addr)
+                .await
+                .with_context(|| format!("echo upstream on {}", cli.echo_addr))?;
+            eprintln!("echo upstream listening on {addr}");
+            Some(addr)
+        };
+        let node = match (&cli.upstream, echo) {
+            (Some(node), _) => node.clone(),
+            (None, Some(addr)) => addr.to_string(),
+            (None, None) => bail!("--no-echo needs --upstream"),
+        };
+        admin
+            .setup(cli.scenario, cli.routes, &node, &cli.api_key)
+            .await
+            .context("creating the benchmark routes")?;
+    }
+
+    let spec = LoadSpec {
+        target,
+        host: cli.target.clone(),
+        paths: cli.scenario.paths(cli.routes),
+        headers: cli.scenario.headers(&cli.api_key),
+        connections: cli.connections,
+        warmup: cli.warmup,
+        duration: cli.duration,
+        timeout: cli.timeout,
+    };
+    let result = measure(&cli, &spec).await;
+    if !cli.skip_setup
+        && !cli.keep_routes
+        && let Err(e) = admin.teardown(cli.scenario, cli.routes).await
+    {
+        eprintln!("warning: removing the benchmark routes: {e:#}");
+    }
+    let report = result?;
+
+    if cli.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{report}");
+    }
+    if let Some(ref path) = cli.output {
+        std::fs::write(path, serde_json::to_vec_pretty(&report)?)
+            .with_context(|| format!("writing {}", path.display()))?;
+    }
+    if report.requests == 0 {
+        eprintln!("no responses received");
+        return Ok(ExitCode::FAILURE);
+    }
+    if let Some(baseline) = baseline {
+        let comparison = Comparison::p99(&baseline, &report, cli.max_p99_regression);
+        if cli.json {
+            eprintln!("{comparison}");
+        } else {
+            println!("{comparison}");
+        }
+        if comparison.regressed {
+            return Ok(ExitCode::FAILURE);
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Wait for the routes to be served, then run the load.
+async fn measure(cli: &Cli, spec: &LoadSpec) -> anyhow::Result<Report> {
+    let started = tokio::time::Instant::now();
+    loop {
+        match load::probe(spec, &spec.paths[spec.paths.len() - 1]).await {
+            Ok(response) if response.status != 404 => break,
+            result if started.elapsed() > READY_TIMEOUT => {
+                let seen = match result {
+                    Ok(response) => format!("status {}", response.status),
+                    Err(e) => e.to_string(),
+                };
+                bail!("{} does not serve the benchmark routes: {seen}", cli.target);
+            }
+            _ => tokio::time::sleep(Duration::from_millis(100)).await,
+        }
+    }
+    eprintln!(
+        "{} connections for {:?} (+{:?} warmup) → {}",
+        spec.connections, spec.duration, spec.warmup, cli.target
+    );
+    let outcome = load::run(spec).await;
+    Ok(Report::new(
+        cli.scenario.as_str(),
+        &cli.target,
+        cli.routes,
+        cli.connections,
+        spec.duration,
+        &outcome.latencies,
+        outcome.errors,
+    ))
+}
+
+fn read_report(path: &PathBuf) -> anyhow::Result<Report> {
+    let json = std::fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    serde_json::from_slice(&json).with_context(|| format!("parsing {}", path.display()))
+}
+
+/// `30s`, `500ms`, `2m`; a bare number is seconds.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .map_or((value, ""), |at| value.split_at(at));
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{value}`"))?;
+    let secs = match unit {
+        "ms" => number / 1000.0,
+        "" | "s" => number,
+        "m" => number * 60.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in `{value}`, use ms, s or m"
+            ));
+        }
+    };
+    Ok(Duration::from_secs_f64(secs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("500ms"), Ok(Duration::from_millis(500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration("1.5"), Ok(Duration::from_millis(1500)));
+        assert!(parse_duration("10h").is_err());
+        assert!(parse_duration("s").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! The result of a run: what CI stores as JSON and compares against.
+
+use crate::histogram::Histogram;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Failed requests, by cause.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Errors {
+    pub connect: u64,
+    pub timeout: u64,
+    /// Reset connections and malformed responses.
+    pub io: u64,
+    /// Responses with a 4xx or 5xx status, by status.
+    pub status: BTreeMap<u16, u64>,
+}
+
+impl Errors {
+    pub fn merge(&mut self, other: &Errors) {
+        self.connect += other.connect;
+        self.timeout += other.timeout;
+        self.io += other.io;
+        for (status, count) in &other.status {
+            *self.status.entry(*status).or_default() += count;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.connect + self.timeout + self.io + self.status.values().sum::<u64>()
+    }
+}
+
+/// Latencies in milliseconds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    pub min: f64,
+    pub mean: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+    pub p999: f64,
+    pub max: f64,
+}
+
+impl Latency {
+    /// From a histogram of microseconds.
+    pub fn from_histogram(histogram: &Histogram) -> Self {
+        let ms = |us: u64| us as f64 / 1000.0;
+        Self {
+            min: ms(histogram.min()),
+            mean: histogram.mean() / 1000.0,
+            p50: ms(histogram.value_at_quantile(0.5)),
+            p90: ms(histogram.value_at_quantile(0.9)),
+            p99: ms(histogram.value_at_quantile(0.99)),
+            p999: ms(histogram.value_at_quantile(0.999)),
+            max: ms(histogram.max()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub scenario: String,
+    pub target: String,
+    pub routes: usize,
+    pub connections: usize,
+    /// Measured seconds, warmup excluded.
+    pub duration_secs: f64,
+    /// Responses received, whatever their status.
+    pub requests: u64,
+    pub requests_per_sec: f64,
+    pub latency_ms: Latency,
+    pub errors: Errors,
+}
+
+impl Report {
+    pub fn new(
+        scenario: &str,
+        target: &str,
+        routes: usize,
+        connections: usize,
+        elapsed: Duration,
+        latencies: &Histogram,
+        errors: Errors,
+    ) -> Self {
+        let secs = elapsed.as_secs_f64();
+        Self {
+            scenario: scenario.to_string(),
+            target: target.to_string(),
+            routes,
+            connections,
+            duration_secs: secs,
+            requests: latencies.count(),
+            requests_per_sec: if secs > 0.0 {
+                latencies.count() as f64 / secs
+            } else {
+                0.0
+            },
+            latency_ms: Latency::from_histogram(latencies),
+            errors,
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} scenario, {} routes, {} connections → {} ({:.1}s)",
+            self.scenario, self.routes, self.connections, self.target, self.duration_secs
+        )?;
+        writeln!(
+            f,
+            "  requests    {} ({:.0} req/s)",
+            self.requests, self.requests_per_sec
+        )?;
+        let l = &self.latency_ms;
+        writeln!(
+            f,
+            "  latency ms  p50 {:.3}  p90 {:.3}  p99 {:.3}  p999 {:.3}",
+            l.p50, l.p90, l.p99, l.p999
+        )?;
+        writeln!(
+            f,
+            "              min {:.3}  mean {:.3}  max {:.3}",
+            l.min, l.mean, l.max
+        )?;
+        let e = &self.errors;
+        write!(
+            f,
+            "  errors      {} (connect {}, timeout {}, io {}",
+            e.total(),
+            e.connect,
+            e.timeout,
+            e.io
+        )?;
+        for (status, count) in &e.status {
+            write!(f, ", {status}: {count}")?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// p99 of a run against a baseline's.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    pub baseline_p99: f64,
+    pub p99: f64,
+    /// Relative change in percent; positive is slower.
+    pub change_pct: f64,
+    pub regressed: bool,
+}
+
+impl Comparison {
+    /// `regressed` when p99 grew by more than `threshold_pct` percent.
+    pub fn p99(baseline: &Report, report: &Report, threshold_pct: f64) -> Self {
+        let (before, after) = (baseline.latency_ms.p99, report.latency_ms.p99);
+        let change_pct = if before > 0.0 {
+            (after - before) / before * 100.0
+        } else if after > 0.0 {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        Self {
+            baseline_p99: before,
+            p99: after,
+            change_pct,
+            regressed: change_pct > threshold_pct,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "p99 {:.3}ms vs baseline {:.3}ms ({:+.1}%){}",
+            self.p99,
+            self.baseline_p99,
+            self.change_pct,
+            if self.regressed {
+                " — REGRESSION"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(latencies: &[u64], secs: u64) -> Report {
+        let mut histogram = Histogram::new();
+        for &us in latencies {
+            histogram.record(us);
+        }
+        Report::new(
+            "plain",
+            "127.0.0.1:9080",
+            4,
+            2,
+            Duration::from_secs(secs),
+            &histogram,
+            Errors::default(),
+        )
+    }
+
+    #[test]
+    fn throughput_and_latency_in_milliseconds() {
+        let latencies: Vec<u64> = (1..=1000).map(|i| i * 10).collect();
+        let run = report(&latencies, 4);
+        assert_eq!(run.requests, 1000);
+        assert_eq!(run.requests_per_sec, 250.0);
+        assert_eq!(run.latency_ms.min, 0.01);
+        assert_eq!(run.latency_ms.max, 10.0);
+        assert_eq!(run.latency_ms.mean, 5.005);
+        assert!((run.latency_ms.p50 - 5.0).abs() <= 0.005);
+        assert!((run.latency_ms.p99 - 9.9).abs() <= 0.01);
+        assert!((run.latency_ms.p999 - 9.99).abs() <= 0.01);
+        assert_eq!(report(&[], 0).requests_per_sec, 0.0);
+    }
+
+    #[test]
+    fn errors_merge_and_total() {
+        let mut errors = Errors {
+            connect: 1,
+            status: [(502, 2)].into(),
+            ..Errors::default()
+        };
+        errors.merge(&Errors {
+            timeout: 3,
+            io: 1,
+            status: [(502, 1), (401, 5)].into(),
+            ..Errors::default()
+        });
+        assert_eq!(errors.status, [(401, 5), (502, 3)].into());
+        assert_eq!(errors.total(), 13);
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let mut report = report(&[100, 200, 300], 1);
+        report.errors.status.insert(503, 2);
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["latency_ms"]["p99"], 0.3);
+        assert_eq!(value["errors"]["status"]["503"], 2);
+    }
+
+    #[test]
+    fn p99_regression_beyond_the_threshold() {
+        let baseline = report(&[1000; 100], 1);
+        let same = Comparison::p99(&baseline, &baseline, 10.0);
+        assert_eq!(same.change_pct, 0.0);
+        assert!(!same.regressed);
+
+        let slower = report(&[1080; 100], 1);
+        let within = Comparison::p99(&baseline, &slower, 10.0);
+        assert!((within.change_pct - 8.0).abs() < 1e-9);
+        assert!(!within.regressed);
+        assert!(Comparison::p99(&baseline, &slower, 5.0).regressed);
+
+        let faster = Comparison::p99(&slower, &baseline, 0.0);
+        assert!(faster.change_pct < 0.0);
+        assert!(!faster.regressed);
+
+        let empty = report(&[], 1);
+        assert!(Comparison::p99(&empty, &baseline, 10.0).regressed);
+        assert!(!Comparison::p99(&empty, &empty, 10.0).regressed);
+    }
+}
@@ -0,0 +1,161 @@
+//! The benchmark routes, programmed through the Admin API.
+
+use anyhow::{Context, bail};
+use serde_json::{Value, json};
+
+const UPSTREAM_ID: &str = "bench-echo";
+const CONSUMER: &str = "bench-user";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Scenario {
+    /// Routes without plugins.
+    Plain,
+    /// Routes behind `key-auth`, requests carrying a consumer's key.
+    KeyAuth,
+    /// Routes with three plugins: an access check and a request and a
+    /// response transformation.
+    Plugins,
+}
+
+impl Scenario {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Plain => "plain",
+            Self::KeyAuth => "key-auth",
+            Self::Plugins => "plugins",
+        }
+    }
+
+    /// One path per route: `/bench/<scenario>/<n>`.
+    pub fn paths(self, routes: usize) -> Vec<String> {
+        (0..routes)
+            .map(|n| format!("/bench/{}/{n}", self.as_str()))
+            .collect()
+    }
+
+    /// Headers every request carries.
+    pub fn headers(self, api_key: &str) -> Vec<(String, String)> {
+        match self {
+            Self::KeyAuth => vec![("apikey".into(), api_key.into())],
+            Self::Plain | Self::Plugins => Vec::new(),
+        }
+    }
+
+    fn plugins(self) -> Value {
+        match self {
+            Self::Plain => json!({}),
+            Self::KeyAuth => json!({ "key-auth": {} }),
+            Self::Plugins => json!({
+                "ip-restriction": { "allowlist": ["0.0.0.0/0", "::/0"] },
+                "request-transformer": { "add": { "headers": { "x-bench": "loadgen" } } },
+                "response-transformer": { "add": { "headers": { "x-route": "$route_id" } } }
+            }),
+        }
+    }
+
+    fn route_id(self, n: usize) -> String {
+        format!("bench-{}-{n}", self.as_str())
+    }
+}
+
+pub struct Admin {
+    client: reqwest::Client,
+    base: String,
+    key: Option<String>,
+}
+
+impl Admin {
+    /// `base`: the Admin API's URL, e.g. `http://127.0.0.1:9180`.
+    pub fn new(base: &str, key: Option<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base: base.trim_end_matches('/').to_string(),
+            key,
+        }
+    }
+
+    /// Create the upstream pointing at `node` (`host:port`), the consumer
+    /// for key-auth, and `routes` routes for `scenario`.
+    pub async fn setup(
+        &self,
+        scenario: Scenario,
+        routes: usize,
+        node: &str,
+        api_key: &str,
+    ) -> anyhow::Result<()> {
+        self.put(
+            &format!("upstreams/{UPSTREAM_ID}"),
+            json!({ "id": UPSTREAM_ID, "type": "roundrobin", "nodes": { node: 1 } }),
+        )
+        .await?;
+        if scenario == Scenario::KeyAuth {
+            self.put(
+                &format!("consumers/{CONSUMER}"),
+                json!({ "username": CONSUMER, "plugins": { "key-auth": { "key": api_key } } }),
+            )
+            .await?;
+        }
+        for (n, uri) in scenario.paths(routes).into_iter().enumerate() {
+            let id = scenario.route_id(n);
+            self.put(
+                &format!("routes/{id}"),
+                json!({
+                    "id": id,
+                    "uri": uri,
+                    "methods": ["GET"],
+                    "upstream_id": UPSTREAM_ID,
+                    "plugins": scenario.plugins()
+                }),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Delete what `setup` created.
+    pub async fn teardown(&self, scenario: Scenario, routes: usize) -> anyhow::Result<()> {
+        for n in 0..routes {
+            self.delete(&format!("routes/{}", scenario.route_id(n)))
+                .await?;
+        }
+        if scenario == Scenario::KeyAuth {
+            self.delete(&format!("consumers/{CONSUMER}")).await?;
+        }
+        self.delete(&format!("upstreams/{UPSTREAM_ID}")).await
+    }
+
+    async fn put(&self, path: &str, body: Value) -> anyhow::Result<()> {
+        let request = self.client.put(self.url(path)).json(&body);
+        self.send(request, "PUT", path).await
+    }
+
+    async fn delete(&self, path: &str) -> anyhow::Result<()> {
+        let request = self.client.delete(self.url(path));
+        self.send(request, "DELETE", path).await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/apisix/admin/{path}", self.base)
+    }
+
+    async fn send(
+        &self,
+        mut request: reqwest::RequestBuilder,
+        method: &str,
+        path: &str,
+    ) -> anyhow::Result<()> {
+        if let Some(ref key) = self.key {
+            request = request.header("X-API-KEY", key);
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("{method} {}", self.url(path)))?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            bail!("{method} {}: {status} {body}", self.url(path));
+        }
+        Ok(())
+    }
+}
@@ -14,6 +14,7 @@ export interface Route {
   plugins?: Record<string, unknown>;
   status?: number;
   strip_prefix?: boolean;
+  enable_websocket?: boolean;
 }
 
 export interface Service {
@@ -30,6 +31,11 @@ export interface Upstream {
   name?: string;
   nodes: Record<string, number>;
   type?: string;
+  scheme?: "http" | "https";
+  tls_verify?: boolean;
+  sni?: string;
+  pass_host?: "pass" | "node" | "rewrite";
+  upstream_host?: string;
 }
 
 export interface Consumer {
@@ -10,13 +10,25 @@ export const CE_PLUGINS: PluginInfo[] = [
   { name: "key-auth", phase: "access", icon: "key", desc: "API key authentication via header or query string" },
   { name: "jwt-auth", phase: "access", icon: "shield", desc: "JWT token validation with configurable claims" },
   { name: "basic-auth", phase: "access", icon: "user", desc: "HTTP Basic authentication against consumer credentials" },
+  { name: "hmac-auth", phase: "access", icon: "key", desc: "HMAC-signed request authentication with clock-skew protection" },
+  { name: "openid-connect", phase: "access", icon: "user", desc: "OpenID Connect login with session cookies, or bearer-token validation" },
+  { name: "consumer-restriction", phase: "access", icon: "user", desc: "Allow or deny specific consumers per route" },
   { name: "ip-restriction", phase: "access", icon: "globe", desc: "Allow/deny lists based on client IP or CIDR range" },
-  { name: "rate-limiting", phase: "access", icon: "activity", desc: "Request rate limits per route or consumer (in-memory counter)" },
+  { name: "ua-restriction", phase: "access", icon: "globe", desc: "Allow/deny lists of User-Agent patterns" },
+  { name: "referer-restriction", phase: "access", icon: "globe", desc: "Allow/deny lists of Referer hosts with wildcard domains" },
+  { name: "request-validation", phase: "access", icon: "shield", desc: "JSON Schema validation of headers, query parameters and body" },
+  { name: "rate-limiting", phase: "access", icon: "activity", desc: "Request rate limits per route or consumer (in-memory or Redis counter)" },
+  { name: "limit-req", phase: "access", icon: "activity", desc: "Leaky-bucket request smoothing with burst queueing" },
+  { name: "limit-conn", phase: "access", icon: "activity", desc: "Caps concurrent requests per key, queueing a short burst" },
+  { name: "api-breaker", phase: "access", icon: "activity", desc: "Route-level circuit breaker tripped by upstream status codes" },
+  { name: "fault-injection", phase: "access", icon: "activity", desc: "Aborts or delays a share of matching requests for chaos testing" },
+  { name: "mock", phase: "access", icon: "layers", desc: "Canned responses for maintenance pages and API mocking, no upstream needed" },
+  { name: "debug-echo", phase: "before_proxy", icon: "activity", desc: "Reflects the request as it would be sent upstream, for allowlisted clients" },
   { name: "cors", phase: "header_filter", icon: "layers", desc: "Cross-Origin Resource Sharing headers for browser clients" },
+  { name: "response-headers-policy", phase: "header_filter", icon: "layers", desc: "Strips server fingerprint headers and sets fixed response headers" },
 ];
 
 export const EE_PLUGINS: PluginInfo[] = [
-  { name: "hmac-auth", phase: "access", icon: "key", desc: "HMAC-signed request authentication with clock-skew protection", features: ["SHA-256 / SHA-512 signatures", "Configurable clock skew tolerance", "Request body signing"] },
   { name: "oauth2", phase: "access", icon: "shield", desc: "Full OAuth 2.0 authorization code and client credentials flow", features: ["Authorization code flow", "Client credentials grant", "Token introspection endpoint", "PKCE support"] },
   { name: "rate-limiting-advanced", phase: "access", icon: "activity", desc: "Distributed rate limiting with sliding window and Redis backend", features: ["Redis-backed counters", "Sliding window algorithm", "Per-consumer quotas", "Burst allowance"] },
   { name: "traffic-mirror", phase: "access", icon: "layers", desc: "Mirror production traffic to staging for shadow testing", features: ["Percentage-based mirroring", "Header-based routing", "Async fire-and-forget", "Response comparison"] },
@@ -27,15 +39,18 @@ export const EE_PLUGINS: PluginInfo[] = [
 export const COMPARISON_ROWS = [
   { feature: "Open-source core (monoio, io_uring)", ce: true, ee: true },
   { feature: "Routes / Upstreams / Consumers CRUD", ce: true, ee: true },
-  { feature: "key-auth, jwt-auth, basic-auth", ce: true, ee: true },
-  { feature: "ip-restriction", ce: true, ee: true },
-  { feature: "rate-limiting (in-memory)", ce: true, ee: true },
+  { feature: "key-auth, jwt-auth, basic-auth, hmac-auth", ce: true, ee: true },
+  { feature: "ip-restriction, ua-restriction, referer-restriction", ce: true, ee: true },
+  { feature: "rate-limiting (in-memory or Redis)", ce: true, ee: true },
+  { feature: "limit-req (leaky bucket)", ce: true, ee: true },
+  { feature: "limit-conn (concurrency cap)", ce: true, ee: true },
+  { feature: "mock responses", ce: true, ee: true },
   { feature: "CORS plugin", ce: true, ee: true },
   { feature: "Security headers (HSTS, CSP, …)", ce: true, ee: true },
   { feature: "Prometheus metrics", ce: true, ee: true },
   { feature: "Admin REST API", ce: true, ee: true },
   { feature: "Built-in Dashboard", ce: true, ee: true },
-  { feature: "hmac-auth / oauth2", ce: false, ee: true },
+  { feature: "oauth2", ce: false, ee: true },
   { feature: "rate-limiting-advanced (Redis)", ce: false, ee: true },
   { feature: "traffic-mirror / canary-release", ce: false, ee: true },
   { feature: "circuit-breaker", ce: false, ee: true },